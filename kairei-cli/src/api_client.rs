@@ -20,6 +20,9 @@ pub enum ApiError {
     #[error("API error: {status} - {message}")]
     Api { status: StatusCode, message: String },
 
+    #[error("Precondition failed: the resource changed since it was read")]
+    PreconditionFailed,
+
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
 
@@ -29,10 +32,19 @@ pub enum ApiError {
 
 pub type ApiResult<T> = Result<T, ApiError>;
 
+/// A response together with the `ETag` the server returned for it
+#[derive(Debug, Clone)]
+pub struct Versioned<T> {
+    pub value: T,
+    pub etag: Option<String>,
+}
+
+#[derive(Clone)]
 pub struct ApiClient {
     client: Client,
     base_url: String,
     api_key: SecretString,
+    if_match: Option<String>,
 }
 
 impl ApiClient {
@@ -41,6 +53,17 @@ impl ApiClient {
             client: Client::new(),
             base_url: base_url.to_string(),
             api_key: api_key.clone(),
+            if_match: None,
+        }
+    }
+
+    /// A client that sends `If-Match: <etag>` with its requests, so that a change
+    /// fails with [`ApiError::PreconditionFailed`] when someone else changed the
+    /// resource first
+    pub fn with_if_match(&self, etag: &str) -> Self {
+        Self {
+            if_match: Some(etag.to_string()),
+            ..self.clone()
         }
     }
 
@@ -51,6 +74,20 @@ impl ApiClient {
         path: &str,
         body: Option<&T>,
     ) -> ApiResult<R>
+    where
+        T: Serialize + ?Sized,
+        R: DeserializeOwned,
+    {
+        Ok(self.request_versioned(method, path, body).await?.value)
+    }
+
+    /// Make a generic API request and return the response with its `ETag`
+    async fn request_versioned<T, R>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<&T>,
+    ) -> ApiResult<Versioned<R>>
     where
         T: Serialize + ?Sized,
         R: DeserializeOwned,
@@ -66,6 +103,9 @@ impl ApiClient {
             )
             .header("Content-Type", "application/json");
 
+        if let Some(etag) = &self.if_match {
+            request = request.header("If-Match", etag);
+        }
         if let Some(body) = body {
             request = request.json(body);
         }
@@ -74,7 +114,17 @@ impl ApiClient {
 
         let status = response.status();
         if status.is_success() {
-            Ok(response.json::<R>().await?)
+            let etag = response
+                .headers()
+                .get(reqwest::header::ETAG)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string());
+            Ok(Versioned {
+                value: response.json::<R>().await?,
+                etag,
+            })
+        } else if status == StatusCode::PRECONDITION_FAILED {
+            Err(ApiError::PreconditionFailed)
         } else {
             let error_text = response
                 .text()
@@ -141,6 +191,7 @@ impl ApiClient {
             name: name.to_string(),
             description: description.map(|s| s.to_string()),
             config,
+            ..Default::default()
         };
 
         self.request(reqwest::Method::POST, "/api/v1/systems", Some(&request))
@@ -156,6 +207,19 @@ impl ApiClient {
         .await
     }
 
+    /// Get the system with its `ETag`, to pass to [`Self::with_if_match`]
+    pub async fn get_system_versioned(
+        &self,
+        system_id: &str,
+    ) -> ApiResult<Versioned<SystemStatus>> {
+        self.request_versioned(
+            reqwest::Method::GET,
+            &format!("/api/v1/systems/{}", system_id),
+            None::<&()>,
+        )
+        .await
+    }
+
     pub async fn start_system(&self, system_id: &str, dsl: Option<&str>) -> ApiResult<Value> {
         let request = StartSystemRequest {
             dsl: dsl.map(|s| s.to_string()),
@@ -207,6 +271,20 @@ impl ApiClient {
         .await
     }
 
+    /// Get the agent with its `ETag`, to pass to [`Self::with_if_match`]
+    pub async fn get_agent_versioned(
+        &self,
+        system_id: &str,
+        agent_id: &str,
+    ) -> ApiResult<Versioned<AgentStatus>> {
+        self.request_versioned(
+            reqwest::Method::GET,
+            &format!("/api/v1/systems/{}/agents/{}", system_id, agent_id),
+            None::<&()>,
+        )
+        .await
+    }
+
     pub async fn create_agent(
         &self,
         _system_id: &str,
@@ -492,6 +570,57 @@ mod tests {
         assert_eq!(response["status"], "ok");
     }
 
+    #[tokio::test]
+    async fn test_stop_system_if_match() {
+        let mut server = mockito::Server::new_async().await;
+
+        let _get = server
+            .mock("GET", "/api/v1/systems/test-system")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_header("etag", "\"3\"")
+            .with_body(
+                r#"{"started_at": "2023-01-01T00:00:00Z", "running": true, "uptime": 3600,
+                "agent_count": 5, "running_agent_count": 3, "event_queue_size": 10,
+                "event_subscribers": 2, "event_capacity": 100}"#,
+            )
+            .create_async()
+            .await;
+        let _stop_current = server
+            .mock("POST", "/api/v1/systems/test-system/stop")
+            .match_header("if-match", "\"3\"")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"status": "ok"}"#)
+            .create_async()
+            .await;
+        let _stop_stale = server
+            .mock("POST", "/api/v1/systems/test-system/stop")
+            .match_header("if-match", "\"2\"")
+            .with_status(412)
+            .create_async()
+            .await;
+
+        let client = create_test_client(&server);
+        let system = client.get_system_versioned("test-system").await.unwrap();
+        assert_eq!(system.value.agent_count, 5);
+        let etag = system.etag.unwrap();
+        assert_eq!(etag, "\"3\"");
+
+        let response = client
+            .with_if_match(&etag)
+            .stop_system("test-system")
+            .await
+            .unwrap();
+        assert_eq!(response["status"], "ok");
+
+        let result = client
+            .with_if_match("\"2\"")
+            .stop_system("test-system")
+            .await;
+        assert!(matches!(result, Err(ApiError::PreconditionFailed)));
+    }
+
     #[tokio::test]
     async fn test_delete_system() {
         // Create a mock server
@@ -6,13 +6,14 @@ use kairei_cli::{
 use kairei_core::{
     Error,
     analyzer::Parser as _,
-    config::{self, SecretConfig},
+    config::{self, SecretConfig, SystemConfig},
+    diagnostics,
     preprocessor::Preprocessor,
     system::System,
     tokenizer::token::Token,
     type_checker::run_type_checker,
 };
-use kairei_http::{models::SystemConfig, services::compiler::models::ValidationError};
+use kairei_http::services::compiler::models::ValidationError;
 use secrecy::ExposeSecret;
 use std::path::PathBuf;
 use std::{
@@ -36,6 +37,10 @@ struct Cli {
     #[arg(short, long, default_value = "secret.json", global = true)]
     secret: PathBuf,
 
+    /// Config profile whose overrides are applied to the config file (e.g. dev, prod)
+    #[arg(long, env = "KAIREI_PROFILE", global = true)]
+    profile: Option<String>,
+
     /// Enable debug mode
     #[arg(short, long, global = true)]
     verbose: bool,
@@ -103,6 +108,12 @@ enum Commands {
 
     /// Manage API credentials
     Login(LoginArgs),
+
+    /// Explain a diagnostic code, e.g. `kairei explain type.mismatch`
+    Explain {
+        /// Diagnostic code
+        code: String,
+    },
 }
 
 #[derive(Parser)]
@@ -369,9 +380,11 @@ async fn format_file(args: &FmtArgs) -> Result<(), Error> {
         .parse(tokens.as_slice(), 0)
         .map_err(|e| Error::Internal(format!("Failed to parse: {}", e)))?;
 
-    // Type check
+    // Type check, as the declared DSL version reads it. The file itself keeps its syntax.
     if args.strict {
         let mut root = root.clone();
+        kairei_core::dsl_version::check_compatibility(&mut root)
+            .map_err(|e| Error::Internal(format!("Unsupported DSL version: {}", e)))?;
         run_type_checker(&mut root)
             .map_err(|e| Error::Internal(format!("Failed to type check: {}", e)))?;
     }
@@ -399,10 +412,11 @@ async fn run_local(
     args: &RunArgs,
     config_path: &PathBuf,
     secret_path: &PathBuf,
+    profile: Option<&str>,
 ) -> Result<(), Error> {
     // Load config
-    let config: SystemConfig = if config_path.exists() {
-        config::from_file(config_path)?
+    let config = if config_path.exists() {
+        SystemConfig::from_file_with_profile(config_path, profile)?
     } else {
         // Default config
         SystemConfig::default()
@@ -420,11 +434,7 @@ async fn run_local(
     debug!("secret_config: {:?}", secret_config);
 
     // Initialize system
-    let mut system = System::new(
-        &kairei_core::config::SystemConfig::from(config),
-        &secret_config,
-    )
-    .await;
+    let mut system = System::new(&config, &secret_config).await;
 
     // Load and parse DSL
     let dsl = std::fs::read_to_string(&args.dsl)
@@ -549,7 +559,7 @@ async fn handle_system_commands(cmd: &SystemCommands, cli: &Cli) -> Result<(), E
             let config = config_file
                 .clone()
                 .map(|path| {
-                    config::from_file::<kairei_core::config::SystemConfig, &PathBuf>(&path)
+                    SystemConfig::from_file_with_profile(&path, cli.profile.as_deref())
                         .map_err(|e| Error::Internal(format!("Failed to read config file: {}", e)))
                         .unwrap()
                 })
@@ -847,16 +857,29 @@ async fn handle_login_command(args: &LoginArgs) -> Result<(), Error> {
     Ok(())
 }
 
+fn explain_code(code: &str) -> Result<(), Error> {
+    let explanation = diagnostics::explain(code)
+        .ok_or_else(|| Error::Internal(format!("Unknown diagnostic code: {}", code)))?;
+    println!("{}: {}\n", explanation.code, explanation.summary);
+    println!("{}\n", explanation.explanation);
+    println!("Example:\n{}\n", explanation.example);
+    println!("Fix:\n{}", explanation.fix);
+    Ok(())
+}
+
 async fn run(cli: &Cli) -> Result<(), Error> {
     match &cli.command {
         Commands::Fmt(args) => format_file(args).await,
-        Commands::Run(args) => run_local(args, &cli.config, &cli.secret).await,
+        Commands::Run(args) => {
+            run_local(args, &cli.config, &cli.secret, cli.profile.as_deref()).await
+        }
         Commands::Compiler { command } => handle_compiler_commands(command, cli).await,
         Commands::System { command } => handle_system_commands(command, cli).await,
         Commands::Agent { command } => handle_agent_commands(command, cli).await,
         Commands::Event { command } => handle_event_commands(command, cli).await,
         Commands::Doc { command } => handle_doc_commands(command, cli).await,
         Commands::Login(args) => handle_login_command(args).await,
+        Commands::Explain { code } => explain_code(code),
     }
 }
 
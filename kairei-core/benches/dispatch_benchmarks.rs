@@ -0,0 +1,108 @@
+//! Benchmarks for the handler dispatch index
+//!
+//! Dispatches one second's worth of events at 10k events/sec to 200 agents, first
+//! the way agents did without an index, each agent looking up its own handlers
+//! for every event, then through a single lookup in the `DispatchIndex`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use criterion::{Criterion, Throughput, black_box, criterion_group, criterion_main};
+use dashmap::DashMap;
+
+use kairei_core::event::dispatch::{DispatchIndex, DispatchKey, HandlerKind, HandlerRoute};
+use kairei_core::event_bus::{Event, EventCategory, Value};
+use kairei_core::event_registry::EventType;
+
+const AGENTS: usize = 200;
+const EVENTS_PER_SECOND: usize = 10_000;
+/// Message types handled by the agents; each is handled by AGENTS / TOPICS agents
+const TOPICS: usize = 50;
+
+fn topic(i: usize) -> EventType {
+    EventType::Message {
+        content_type: format!("topic_{}", i % TOPICS),
+    }
+}
+
+fn events() -> Vec<Event> {
+    (0..EVENTS_PER_SECOND)
+        .map(|i| Event {
+            event_type: topic(i),
+            parameters: HashMap::from([("value".to_string(), Value::Integer(i as i64))]),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Observe and react handler keys of each agent, as agents keep them
+fn agent_handlers() -> Vec<(DashMap<String, ()>, DashMap<String, ()>)> {
+    (0..AGENTS)
+        .map(|i| {
+            let observe = DashMap::new();
+            observe.insert(topic(i).to_string(), ());
+            let react = DashMap::new();
+            react.insert(topic(i + 1).to_string(), ());
+            (observe, react)
+        })
+        .collect()
+}
+
+fn bench_dispatch(c: &mut Criterion) {
+    let events = events();
+    let mut group = c.benchmark_group("dispatch_200_agents");
+    group.throughput(Throughput::Elements(EVENTS_PER_SECOND as u64));
+
+    let handlers = agent_handlers();
+    group.bench_function("per_agent_lookup", |b| {
+        b.iter(|| {
+            let mut matched = 0;
+            for event in &events {
+                for (observe, react) in &handlers {
+                    if !matches!(event.category(), EventCategory::Agent) {
+                        continue;
+                    }
+                    matched += usize::from(observe.contains_key(&event.event_type.to_string()));
+                    matched += usize::from(react.contains_key(&event.event_type.to_string()));
+                }
+            }
+            black_box(matched)
+        })
+    });
+
+    let index = Arc::new(DispatchIndex::new(EVENTS_PER_SECOND));
+    let subscriptions: Vec<_> = (0..AGENTS)
+        .map(|i| {
+            let route = |kind, event_type: EventType| HandlerRoute {
+                key: DispatchKey {
+                    event: event_type.to_string(),
+                    responder: None,
+                },
+                kind,
+                required_parameters: vec!["value".to_string()],
+            };
+            index.subscribe(
+                &format!("agent_{}", i),
+                vec![
+                    route(HandlerKind::Observe, topic(i)),
+                    route(HandlerKind::React, topic(i + 1)),
+                ],
+            )
+        })
+        .collect();
+    group.bench_function("dispatch_index", |b| {
+        b.iter(|| {
+            let mut matched = 0;
+            for event in &events {
+                matched += index.route(event);
+            }
+            black_box(matched)
+        })
+    });
+    drop(subscriptions);
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_dispatch);
+criterion_main!(benches);
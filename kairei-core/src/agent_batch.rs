@@ -0,0 +1,363 @@
+//! # Agent Batches
+//!
+//! An [`AgentBatch`] applies one operation to every agent an [`AgentSelector`]
+//! picks, so that a world of many agents is started, stopped, scaled or reloaded
+//! with one call instead of one call per agent. The agents are operated on
+//! concurrently, at most `parallelism` at a time, and each gets its own
+//! [`BatchOutcome`]: by default a failure does not stop the others.
+//!
+//! An atomic batch starts no new operation after the first failure, and then
+//! undoes the operations already applied: started agents are stopped and stopped
+//! agents started again. Scaling and reloading cannot be undone, so a batch of them
+//! is rejected in atomic mode.
+//!
+//! Selectors pick among the user agents of the system, the world and the built-in
+//! agents aside. Names and globs match the agent names, capabilities the requests
+//! listed in the [`AgentCatalog`](crate::catalog::AgentCatalog) and labels the
+//! labels the agents were declared with.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use futures::{StreamExt, stream};
+use glob::Pattern;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use utoipa::ToSchema;
+
+use crate::eval::context::AgentType;
+use crate::system::{System, SystemError, SystemResult};
+
+/// The operation of a batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchOperation {
+    Start,
+    Stop,
+    /// Adds `instances` instances of each agent
+    ScaleUp,
+    /// Removes `instances` instances of each agent
+    ScaleDown,
+    /// Rebuilds each agent from its registered definition, keeping its state
+    Reload,
+}
+
+impl BatchOperation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Start => "start",
+            Self::Stop => "stop",
+            Self::ScaleUp => "scale_up",
+            Self::ScaleDown => "scale_down",
+            Self::Reload => "reload",
+        }
+    }
+
+    /// The operation undoing this one, for the operations an atomic batch can roll back
+    pub fn inverse(&self) -> Option<Self> {
+        match self {
+            Self::Start => Some(Self::Stop),
+            Self::Stop => Some(Self::Start),
+            Self::ScaleUp | Self::ScaleDown | Self::Reload => None,
+        }
+    }
+}
+
+/// Whether an agent is running, for [`AgentSelector::Status`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Running,
+    Stopped,
+}
+
+/// The agents a batch applies to, e.g. `{"glob": "worker-*"}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentSelector {
+    /// These agents, in this order. A name that is not an agent fails.
+    Names(Vec<String>),
+    /// Agents whose name matches the glob, with `*`, `?` and `[...]`
+    Glob(String),
+    /// Agents answering this request, as listed in the catalog
+    Capability(String),
+    /// Agents having all these labels
+    Labels(HashMap<String, String>),
+    /// Agents running, or stopped
+    Status(RunStatus),
+}
+
+/// One operation applied to the agents a selector picks
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct AgentBatch {
+    pub operation: BatchOperation,
+    pub selector: AgentSelector,
+    /// Instances added or removed for each agent by `scale_up` and `scale_down`
+    #[serde(default = "default_instances")]
+    pub instances: usize,
+    /// Maximum number of agents operated on at the same time
+    #[serde(default = "default_parallelism")]
+    pub parallelism: usize,
+    /// Roll back the applied operations when one fails
+    #[serde(default)]
+    pub atomic: bool,
+}
+
+fn default_instances() -> usize {
+    1
+}
+
+fn default_parallelism() -> usize {
+    8
+}
+
+impl AgentBatch {
+    pub fn new(operation: BatchOperation, selector: AgentSelector) -> Self {
+        Self {
+            operation,
+            selector,
+            instances: default_instances(),
+            parallelism: default_parallelism(),
+            atomic: false,
+        }
+    }
+
+    pub fn with_instances(mut self, instances: usize) -> Self {
+        self.instances = instances;
+        self
+    }
+
+    pub fn with_parallelism(mut self, parallelism: usize) -> Self {
+        self.parallelism = parallelism;
+        self
+    }
+
+    pub fn with_atomic(mut self, atomic: bool) -> Self {
+        self.atomic = atomic;
+        self
+    }
+}
+
+/// What a batch did to one agent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum BatchOutcome {
+    Succeeded,
+    /// The operation had nothing to do, e.g. starting an agent already running,
+    /// or was not started since an atomic batch failed
+    Skipped {
+        reason: String,
+    },
+    Failed {
+        error: String,
+    },
+    /// The operation was applied, then undone since the atomic batch failed
+    RolledBack,
+}
+
+/// The outcome of a batch for one agent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct BatchResult {
+    pub agent: String,
+    pub outcome: BatchOutcome,
+}
+
+/// Number of agents per outcome
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct BatchSummary {
+    pub selected: usize,
+    pub succeeded: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub rolled_back: usize,
+}
+
+/// The outcomes of a batch, in the order the selector picked the agents
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct BatchReport {
+    pub results: Vec<BatchResult>,
+    pub summary: BatchSummary,
+}
+
+impl BatchReport {
+    fn new(results: Vec<BatchResult>) -> Self {
+        let mut summary = BatchSummary {
+            selected: results.len(),
+            ..Default::default()
+        };
+        for result in &results {
+            match result.outcome {
+                BatchOutcome::Succeeded => summary.succeeded += 1,
+                BatchOutcome::Skipped { .. } => summary.skipped += 1,
+                BatchOutcome::Failed { .. } => summary.failed += 1,
+                BatchOutcome::RolledBack => summary.rolled_back += 1,
+            }
+        }
+        Self { results, summary }
+    }
+}
+
+impl System {
+    /// Applies `batch` to the agents its selector picks, see the [module docs](self).
+    /// Fails only when the batch itself is invalid; the failures of single agents
+    /// are reported in their outcomes.
+    pub async fn run_agent_batch(&self, batch: &AgentBatch) -> SystemResult<BatchReport> {
+        if batch.parallelism == 0 {
+            return Err(SystemError::InvalidBatch(
+                "parallelism must be at least 1".to_string(),
+            ));
+        }
+        if batch.atomic && batch.operation.inverse().is_none() {
+            return Err(SystemError::InvalidBatch(format!(
+                "{} cannot be rolled back, so it cannot run atomically",
+                batch.operation.as_str()
+            )));
+        }
+        let agents = self.select_agents(&batch.selector).await?;
+
+        let failed = AtomicBool::new(false);
+        let mut results: Vec<BatchResult> = stream::iter(agents)
+            .map(|agent| {
+                let failed = &failed;
+                async move {
+                    // 原子的なバッチは失敗の後に新しい操作を始めない
+                    let outcome = if batch.atomic && failed.load(Ordering::SeqCst) {
+                        BatchOutcome::Skipped {
+                            reason: "the atomic batch failed on another agent".to_string(),
+                        }
+                    } else {
+                        self.apply_batch_operation(&agent, batch.operation, batch)
+                            .await
+                    };
+                    if matches!(outcome, BatchOutcome::Failed { .. }) {
+                        failed.store(true, Ordering::SeqCst);
+                    }
+                    BatchResult { agent, outcome }
+                }
+            })
+            .buffered(batch.parallelism)
+            .collect()
+            .await;
+
+        if let Some(inverse) = batch
+            .operation
+            .inverse()
+            .filter(|_| batch.atomic && failed.load(Ordering::SeqCst))
+        {
+            for result in results
+                .iter_mut()
+                .filter(|result| result.outcome == BatchOutcome::Succeeded)
+            {
+                result.outcome = match self
+                    .apply_batch_operation(&result.agent, inverse, batch)
+                    .await
+                {
+                    BatchOutcome::Succeeded => BatchOutcome::RolledBack,
+                    BatchOutcome::Failed { error } | BatchOutcome::Skipped { reason: error } => {
+                        warn!("Failed to roll back {}: {}", result.agent, error);
+                        BatchOutcome::Failed {
+                            error: format!("applied, but the rollback failed: {}", error),
+                        }
+                    }
+                    BatchOutcome::RolledBack => unreachable!(),
+                };
+            }
+        }
+        Ok(BatchReport::new(results))
+    }
+
+    async fn select_agents(&self, selector: &AgentSelector) -> SystemResult<Vec<String>> {
+        if let AgentSelector::Names(names) = selector {
+            return Ok(names.clone());
+        }
+        let registry = self.agent_registry().read().await;
+        let world = AgentType::World.to_string();
+        let mut agents: Vec<String> = registry
+            .agent_names_by_types(vec![AgentType::User])
+            .into_iter()
+            .filter(|agent| *agent != world)
+            .collect();
+        agents.sort();
+
+        let selected = match selector {
+            AgentSelector::Names(_) => unreachable!(),
+            AgentSelector::Glob(glob) => {
+                let pattern = Pattern::new(glob).map_err(|e| {
+                    SystemError::InvalidBatch(format!("invalid glob {}: {}", glob, e))
+                })?;
+                agents
+                    .into_iter()
+                    .filter(|agent| pattern.matches(agent))
+                    .collect()
+            }
+            AgentSelector::Capability(request) => {
+                let capable: HashSet<String> = self
+                    .catalog()
+                    .into_iter()
+                    .filter(|entry| entry.requests.iter().any(|r| r.name == *request))
+                    .map(|entry| entry.name)
+                    .collect();
+                agents
+                    .into_iter()
+                    .filter(|agent| capable.contains(agent))
+                    .collect()
+            }
+            AgentSelector::Labels(selector) => agents
+                .into_iter()
+                .filter(|agent| {
+                    registry.agent_labels(agent).is_some_and(|labels| {
+                        selector
+                            .iter()
+                            .all(|(key, value)| labels.get(key) == Some(value))
+                    })
+                })
+                .collect(),
+            AgentSelector::Status(status) => agents
+                .into_iter()
+                .filter(|agent| registry.is_agent_running(agent) == (*status == RunStatus::Running))
+                .collect(),
+        };
+        Ok(selected)
+    }
+
+    async fn apply_batch_operation(
+        &self,
+        agent: &str,
+        operation: BatchOperation,
+        batch: &AgentBatch,
+    ) -> BatchOutcome {
+        if let Err(e) = self.get_agent_status(agent).await {
+            return BatchOutcome::Failed {
+                error: e.to_string(),
+            };
+        }
+        let running = self.agent_registry().read().await.is_agent_running(agent);
+        let skipped = |reason: &str| BatchOutcome::Skipped {
+            reason: reason.to_string(),
+        };
+        let applied = match operation {
+            BatchOperation::Start if running => return skipped("already running"),
+            BatchOperation::Stop if !running => return skipped("not running"),
+            BatchOperation::Start => self.start_agent(agent).await,
+            BatchOperation::Stop => self.stop_agent(agent).await,
+            BatchOperation::ScaleUp => self
+                .scale_up(agent, batch.instances, HashMap::new())
+                .await
+                .map(|_| ()),
+            BatchOperation::ScaleDown => {
+                self.scale_down(agent, batch.instances, HashMap::new())
+                    .await
+            }
+            BatchOperation::Reload => match self.get_agent_ast(agent).await {
+                Ok(agent_def) => self.reload_agent(&agent_def).await,
+                Err(e) => Err(e),
+            },
+        };
+        match applied {
+            Ok(()) => BatchOutcome::Succeeded,
+            Err(e) => BatchOutcome::Failed {
+                error: e.to_string(),
+            },
+        }
+    }
+}
@@ -0,0 +1,486 @@
+//! Logs written by agents with `log.<level>("message", key: value, ...)`.
+//!
+//! A log at or above the agent's [`AgentLogConfig::level`] becomes a tracing event
+//! with the target [`AGENT_LOG_TARGET`] and the fields `agent`, `handler` and
+//! `fields`, the key/value pairs as JSON. When [`AgentLogConfig::publish_events`]
+//! is set, it is also published as an [`EventType::AgentLog`] system event so
+//! operators can follow agent logs with the other events of the system. Published
+//! logs are capped at [`AgentLogConfig::max_events_per_second`], further logs only
+//! go to tracing, and their message and fields at
+//! [`AgentLogConfig::max_event_bytes`].
+//!
+//! The last [`AgentLogConfig::recent_logs`] logs at or above the level are also kept
+//! in memory as [`CapturedLog`]s, read with `System::agent_logs`, so the logs of one
+//! agent can be inspected without filtering the output of the whole system.
+//!
+//! The scaled instances of an agent share its logger. Its config, e.g. `debug` for
+//! one noisy agent while the others stay at `info`, is changed while the agent runs
+//! with `System::set_agent_log_config`.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::config::AgentLogConfig;
+use crate::event::event_bus::{self, Event};
+use crate::event_registry::EventType;
+use crate::expression::Value;
+
+/// Target of the tracing events of agent logs
+pub const AGENT_LOG_TARGET: &str = "kairei::agent_log";
+
+/// Severity of a `log` statement
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 4] = [Self::Debug, Self::Info, Self::Warn, Self::Error];
+
+    /// Name used after `log.` in the DSL
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Debug => "debug",
+            Self::Info => "info",
+            Self::Warn => "warn",
+            Self::Error => "error",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|level| level.as_str() == name)
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// An evaluated `log` statement
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogRecord {
+    pub level: LogLevel,
+    pub message: String,
+    pub fields: Vec<(String, Value)>,
+}
+
+/// A log kept in memory by its agent's logger
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct CapturedLog {
+    pub timestamp: DateTime<Utc>,
+    /// The agent, or the scaled instance, that logged
+    pub agent: String,
+    pub handler: String,
+    pub level: LogLevel,
+    pub message: String,
+    /// The key/value pairs of the log
+    #[schema(value_type = Object)]
+    pub fields: serde_json::Value,
+}
+
+/// 公開したイベントを数える 1 秒単位の窓
+struct RateWindow {
+    started: Instant,
+    published: u32,
+}
+
+/// Writes the logs of one agent, see the [module docs](self)
+pub struct AgentLogger {
+    config: RwLock<AgentLogConfig>,
+    window: Mutex<RateWindow>,
+    dropped: AtomicU64,
+    recent: Mutex<VecDeque<CapturedLog>>,
+}
+
+// 窓の開始時刻は含めない。設定を比較する Debug 出力が実行ごとに変わらないように
+impl fmt::Debug for AgentLogger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AgentLogger")
+            .field("config", &self.config)
+            .field("dropped", &self.dropped)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for AgentLogger {
+    fn default() -> Self {
+        Self::new(AgentLogConfig::default())
+    }
+}
+
+impl AgentLogger {
+    pub fn new(config: AgentLogConfig) -> Self {
+        Self {
+            config: RwLock::new(config),
+            window: Mutex::new(RateWindow {
+                started: Instant::now(),
+                published: 0,
+            }),
+            dropped: AtomicU64::new(0),
+            recent: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn config(&self) -> AgentLogConfig {
+        self.config
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub fn set_config(&self, config: AgentLogConfig) {
+        *self.config.write().unwrap_or_else(|e| e.into_inner()) = config;
+    }
+
+    /// Logs not published because of the rate cap
+    pub fn dropped_events(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// The logs kept in memory, oldest first
+    pub fn recent_logs(&self) -> Vec<CapturedLog> {
+        let recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+        recent.iter().cloned().collect()
+    }
+
+    fn capture(&self, log: CapturedLog, capacity: usize) {
+        let mut recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+        recent.push_back(log);
+        // 設定で容量が減った場合もここで切り詰める
+        while recent.len() > capacity {
+            recent.pop_front();
+        }
+    }
+
+    /// Write `record` to tracing. Returns the event to publish, if the agent
+    /// publishes its logs and the rate cap allows it.
+    pub fn log(&self, agent_name: &str, handler: &str, record: &LogRecord) -> Option<Event> {
+        let config = self.config();
+        if record.level < config.level {
+            return None;
+        }
+
+        let fields: serde_json::Map<String, serde_json::Value> = record
+            .fields
+            .iter()
+            .map(|(key, value)| {
+                let value = event_bus::Value::from(value.clone());
+                (key.clone(), serde_json::Value::from(&value))
+            })
+            .collect();
+        let fields = serde_json::Value::Object(fields);
+        self.capture(
+            CapturedLog {
+                timestamp: Utc::now(),
+                agent: agent_name.to_string(),
+                handler: handler.to_string(),
+                level: record.level,
+                message: record.message.clone(),
+                fields: fields.clone(),
+            },
+            config.recent_logs,
+        );
+        let fields = fields.to_string();
+        let message = &record.message;
+        // tracing のレベルは定数である必要があるので分岐する
+        match record.level {
+            LogLevel::Debug => {
+                tracing::debug!(target: AGENT_LOG_TARGET, agent = agent_name, handler, fields = %fields, "{}", message)
+            }
+            LogLevel::Info => {
+                tracing::info!(target: AGENT_LOG_TARGET, agent = agent_name, handler, fields = %fields, "{}", message)
+            }
+            LogLevel::Warn => {
+                tracing::warn!(target: AGENT_LOG_TARGET, agent = agent_name, handler, fields = %fields, "{}", message)
+            }
+            LogLevel::Error => {
+                tracing::error!(target: AGENT_LOG_TARGET, agent = agent_name, handler, fields = %fields, "{}", message)
+            }
+        }
+
+        if !config.publish_events || !self.admit(config.max_events_per_second) {
+            return None;
+        }
+        Some(Self::event(
+            agent_name,
+            handler,
+            record,
+            config.max_event_bytes,
+        ))
+    }
+
+    fn admit(&self, max_per_second: u32) -> bool {
+        let mut window = self.window.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        if now.duration_since(window.started) >= Duration::from_secs(1) {
+            *window = RateWindow {
+                started: now,
+                published: 0,
+            };
+        }
+        if window.published < max_per_second {
+            window.published += 1;
+            true
+        } else {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            false
+        }
+    }
+
+    /// メッセージとフィールドを合わせて `max_bytes` までに切り詰めたイベント
+    fn event(agent_name: &str, handler: &str, record: &LogRecord, max_bytes: usize) -> Event {
+        let message = truncate(&record.message, max_bytes);
+        let mut remaining = max_bytes - message.len();
+        let mut truncated = message.len() < record.message.len();
+        let mut fields = HashMap::new();
+        for (key, value) in &record.fields {
+            let value = event_bus::Value::from(value.clone());
+            // 値は JSON で書いたときの長さで数える
+            let size = key.len() + serde_json::Value::from(&value).to_string().len();
+            if size > remaining {
+                truncated = true;
+                continue;
+            }
+            remaining -= size;
+            fields.insert(key.clone(), value);
+        }
+
+        Event {
+            event_type: EventType::AgentLog {
+                agent_name: agent_name.to_string(),
+            },
+            parameters: HashMap::from([
+                (
+                    "level".to_string(),
+                    event_bus::Value::from(record.level.as_str()),
+                ),
+                ("handler".to_string(), event_bus::Value::from(handler)),
+                ("message".to_string(), event_bus::Value::from(message)),
+                ("fields".to_string(), event_bus::Value::Map(fields)),
+                (
+                    "truncated".to_string(),
+                    event_bus::Value::Boolean(truncated),
+                ),
+            ]),
+            ..Default::default()
+        }
+    }
+}
+
+fn truncate(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// The loggers of a system's agents, keyed by agent name
+#[derive(Debug, Default)]
+pub struct AgentLoggers {
+    loggers: DashMap<String, Arc<AgentLogger>>,
+}
+
+impl AgentLoggers {
+    /// The logger of `agent_name`, created with `config` if it has none
+    pub fn get_or_create(&self, agent_name: &str, config: AgentLogConfig) -> Arc<AgentLogger> {
+        self.loggers
+            .entry(agent_name.to_string())
+            .or_insert_with(|| Arc::new(AgentLogger::new(config)))
+            .clone()
+    }
+
+    pub fn set_config(&self, agent_name: &str, config: AgentLogConfig) {
+        match self.loggers.get(agent_name) {
+            Some(logger) => logger.set_config(config),
+            None => {
+                self.get_or_create(agent_name, config);
+            }
+        }
+    }
+
+    /// The logs `agent_name` and its scaled instances keep in memory, oldest first
+    pub fn recent_logs(&self, agent_name: &str) -> Vec<CapturedLog> {
+        self.loggers
+            .get(agent_name)
+            .map(|logger| logger.recent_logs())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+
+    /// tracing の出力を貯めるライター
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn record(level: LogLevel, message: &str) -> LogRecord {
+        LogRecord {
+            level,
+            message: message.to_string(),
+            fields: vec![
+                ("order_id".to_string(), Value::Integer(42)),
+                ("status".to_string(), Value::String("paid".to_string())),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_log_writes_tracing_fields() {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .with_max_level(tracing::Level::DEBUG)
+            .finish();
+        let logger = AgentLogger::new(AgentLogConfig {
+            level: LogLevel::Info,
+            ..Default::default()
+        });
+
+        tracing::subscriber::with_default(subscriber, || {
+            logger.log(
+                "Orders",
+                "Checkout",
+                &record(LogLevel::Info, "order placed"),
+            );
+            logger.log(
+                "Orders",
+                "Checkout",
+                &record(LogLevel::Debug, "filtered out"),
+            );
+        });
+
+        let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].contains("INFO"));
+        assert!(lines[0].contains(AGENT_LOG_TARGET));
+        assert!(lines[0].contains("order placed"));
+        assert!(lines[0].contains("agent=\"Orders\""));
+        assert!(lines[0].contains("handler=\"Checkout\""));
+        assert!(lines[0].contains(r#"fields={"order_id":42,"status":"paid"}"#));
+    }
+
+    #[test]
+    fn test_recent_logs_are_kept_per_level() {
+        let loggers = AgentLoggers::default();
+        let noisy = loggers.get_or_create(
+            "Noisy",
+            AgentLogConfig {
+                level: LogLevel::Debug,
+                recent_logs: 2,
+                ..Default::default()
+            },
+        );
+        let quiet = loggers.get_or_create("Quiet", AgentLogConfig::default());
+
+        for message in ["first", "second", "third"] {
+            noisy.log("Noisy-1", "Tick", &record(LogLevel::Debug, message));
+            quiet.log("Quiet", "Tick", &record(LogLevel::Debug, message));
+        }
+
+        // 古いものから捨てる
+        let logs = loggers.recent_logs("Noisy");
+        let messages: Vec<&str> = logs.iter().map(|log| log.message.as_str()).collect();
+        assert_eq!(messages, vec!["second", "third"]);
+        assert_eq!(logs[0].agent, "Noisy-1");
+        assert_eq!(logs[0].level, LogLevel::Debug);
+        assert_eq!(logs[0].fields["order_id"], 42);
+        assert!(loggers.recent_logs("Quiet").is_empty());
+        assert!(loggers.recent_logs("Unknown").is_empty());
+    }
+
+    #[test]
+    fn test_log_events_are_capped() {
+        let logger = AgentLogger::new(AgentLogConfig {
+            publish_events: true,
+            max_events_per_second: 3,
+            max_event_bytes: 20,
+            ..Default::default()
+        });
+
+        let events: Vec<Event> = (0..5)
+            .filter_map(|_| logger.log("Orders", "Checkout", &record(LogLevel::Warn, "paid")))
+            .collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!(logger.dropped_events(), 2);
+
+        let event = &events[0];
+        assert_eq!(
+            event.event_type,
+            EventType::AgentLog {
+                agent_name: "Orders".to_string()
+            }
+        );
+        assert_eq!(
+            event.parameters["level"],
+            event_bus::Value::String("warn".to_string())
+        );
+        // "paid" (4) と order_id (8 + 2) は収まり、status (6 + 6) は収まらない
+        let event_bus::Value::Map(fields) = &event.parameters["fields"] else {
+            panic!("fields is not a map: {:?}", event.parameters["fields"]);
+        };
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields["order_id"], event_bus::Value::Integer(42));
+        assert_eq!(
+            event.parameters["truncated"],
+            event_bus::Value::Boolean(true)
+        );
+
+        // 公開しない設定ではイベントを作らない
+        logger.set_config(AgentLogConfig::default());
+        assert!(
+            logger
+                .log("Orders", "Checkout", &record(LogLevel::Error, "paid"))
+                .is_none()
+        );
+    }
+}
@@ -1,9 +1,14 @@
+use crate::ast::HandlerBlock;
+use crate::background_tasks::BackgroundTasks;
 use crate::config::AgentConfig;
+use crate::eval::budget::LlmBudgetStats;
 use crate::eval::context::AgentType;
 use crate::eval::expression;
 use crate::event_bus::{ErrorEvent, ErrorSeverity, Event, EventBus, LastStatus, Value};
 use crate::event_registry::EventType;
-use crate::runtime::RuntimeAgent;
+use crate::request_queue::RequestQueueStats;
+use crate::response_cache::ResponseCacheStats;
+use crate::runtime::{RuntimeAgent, RuntimeResult};
 use dashmap::DashMap;
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -18,6 +23,7 @@ pub struct AgentRegistry {
     running_agents: Arc<DashMap<String, tokio::task::JoinHandle<()>>>,
     shutdown_tx: broadcast::Sender<AgentType>, // Systemから渡される
     config: AgentConfig,
+    background_tasks: BackgroundTasks,
 }
 
 impl Clone for AgentRegistry {
@@ -27,6 +33,7 @@ impl Clone for AgentRegistry {
             running_agents: self.running_agents.clone(),
             shutdown_tx: self.shutdown_tx.clone(),
             config: self.config.clone(),
+            background_tasks: self.background_tasks.clone(),
         }
     }
 }
@@ -38,8 +45,16 @@ impl AgentRegistry {
             running_agents: Arc::new(DashMap::new()),
             shutdown_tx: shutdown_tx.clone(), // Systemから渡される
             config: config.clone(),
+            background_tasks: BackgroundTasks::new(),
         }
     }
+
+    /// エージェントのイベントループを System のタスクとして起動する
+    pub fn with_background_tasks(mut self, background_tasks: BackgroundTasks) -> Self {
+        self.background_tasks = background_tasks;
+        self
+    }
+
     pub async fn run(&self) -> AgentResult<()> {
         let mut shutdown_rx = self.shutdown_tx.subscribe();
         loop {
@@ -109,6 +124,7 @@ impl AgentRegistry {
                     );
                     params
                 },
+                ..Default::default()
             })
             .await?;
 
@@ -136,6 +152,7 @@ impl AgentRegistry {
                     params.insert("agent_id".to_string(), Value::String(id.to_string()));
                     params
                 },
+                ..Default::default()
             })
             .await?;
 
@@ -165,12 +182,13 @@ impl AgentRegistry {
                     params.insert("agent_id".to_string(), Value::String(id.to_string()));
                     params
                 },
+                ..Default::default()
             })
             .await?;
 
         let cloned_id = id.to_string();
         let shutdown_rx = self.shutdown_tx.subscribe();
-        let handle = tokio::spawn(async move {
+        let handle = self.background_tasks.spawn(async move {
             if let Err(e) = agent.run(shutdown_rx).await {
                 // エラー発生時もイベントを発行
                 let _ = event_bus
@@ -295,6 +313,28 @@ impl AgentRegistry {
             .collect()
     }
 
+    pub fn agent_labels(&self, id: &str) -> Option<HashMap<String, String>> {
+        self.agents.get(id).map(|agent| agent.value().labels())
+    }
+
+    pub fn agent_request_queue_stats(&self, id: &str) -> Option<RequestQueueStats> {
+        self.agents
+            .get(id)
+            .and_then(|agent| agent.value().request_queue_stats())
+    }
+
+    pub fn agent_llm_budget_stats(&self, id: &str) -> Option<LlmBudgetStats> {
+        self.agents
+            .get(id)
+            .and_then(|agent| agent.value().llm_budget_stats())
+    }
+
+    pub fn agent_response_cache_stats(&self, id: &str) -> Option<ResponseCacheStats> {
+        self.agents
+            .get(id)
+            .and_then(|agent| agent.value().response_cache_stats())
+    }
+
     pub async fn agent_status(&self, id: &str) -> Option<LastStatus> {
         if let Some(agent) = self.agents.get(id) {
             Some(agent.value().status().await)
@@ -322,6 +362,42 @@ impl AgentRegistry {
         }
     }
 
+    pub async fn agent_state_snapshot(
+        &self,
+        id: &str,
+    ) -> Option<HashMap<String, expression::Value>> {
+        let agent = self.agents.get(id)?.value().clone();
+        Some(agent.state_snapshot().await)
+    }
+
+    /// Replace the config values of a running agent
+    pub async fn update_agent_config(
+        &self,
+        id: &str,
+        values: HashMap<String, expression::Value>,
+    ) -> RuntimeResult<()> {
+        let agent = self
+            .agents
+            .get(id)
+            .ok_or_else(|| AgentError::AgentNotFound {
+                agent_id: id.to_string(),
+            })?
+            .clone();
+        agent.update_config(values).await
+    }
+
+    /// Evaluate `block` once in the context of an agent, e.g. the world's `onInit`
+    pub async fn run_block(&self, id: &str, block: &HandlerBlock) -> RuntimeResult<()> {
+        let agent = self
+            .agents
+            .get(id)
+            .ok_or_else(|| AgentError::AgentNotFound {
+                agent_id: id.to_string(),
+            })?
+            .clone();
+        agent.run_block(block).await
+    }
+
     pub fn get_builtin_agent_names(&self) -> Vec<String> {
         self.agent_names_by_types(AgentRegistry::builtin_agent_types())
     }
@@ -556,6 +632,7 @@ mod tests {
                         params.insert("agent_id".to_string(), Value::String(self.name.clone()));
                         params
                     },
+                    ..Default::default()
                 })
                 .await?;
             Ok(())
@@ -0,0 +1,261 @@
+//! Settings of agents tuned while they run.
+//!
+//! An agent's [`AgentSettings`] are the defaults of its think calls: the provider,
+//! the temperature and the maximum tokens apply unless the `with` block of the think
+//! sets them, and the LLM budget replaces the agent's `llm_budgets` entry. Only
+//! these settings are tunable; the rest of the agent is fixed by its DSL and the
+//! system config.
+//!
+//! The settings are changed with `System::update_agent_settings` by an
+//! [`AgentSettingsPatch`], checked as a whole before it is applied, and take effect
+//! from the next think call of the agent and its scaled instances. They are kept
+//! in memory and survive restarts of the agent, not of the system.
+
+use std::sync::{Arc, RwLock};
+
+use dashmap::DashMap;
+use serde::{Deserialize, Deserializer, Serialize};
+use utoipa::ToSchema;
+
+use crate::config::LlmBudgetConfig;
+use crate::eval::budget::LlmBudget;
+
+/// Highest temperature an agent may be tuned to, as in the `with` block of a think
+pub const MAX_TEMPERATURE: f32 = 2.0;
+
+/// Runtime-tunable settings of an agent. An unset setting follows the provider
+/// config and the system config.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct AgentSettings {
+    /// Provider of the think calls without a `provider` attribute, instead of the
+    /// primary provider
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+
+    /// Default temperature of the think calls, between 0 and 2
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+
+    /// Default maximum tokens of the think calls
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<usize>,
+
+    /// Cap on the agent's think calls, replacing its `llm_budgets` entry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub llm_budget: Option<LlmBudgetConfig>,
+}
+
+impl AgentSettings {
+    /// The first invalid setting and why, given whether a provider is registered
+    pub fn check(&self, has_provider: impl Fn(&str) -> bool) -> Result<(), (&'static str, String)> {
+        if let Some(provider) = self.provider.as_deref().filter(|name| !has_provider(name)) {
+            return Err((
+                "provider",
+                format!("no provider `{}` is registered", provider),
+            ));
+        }
+        if let Some(temperature) = self
+            .temperature
+            .filter(|t| !(0.0..=MAX_TEMPERATURE).contains(t))
+        {
+            return Err((
+                "temperature",
+                format!(
+                    "must be between 0 and {}, got {}",
+                    MAX_TEMPERATURE, temperature
+                ),
+            ));
+        }
+        if self.max_tokens == Some(0) {
+            return Err(("max_tokens", "must be at least 1".to_string()));
+        }
+        if let Some(budget) = &self.llm_budget {
+            // 0 件や 0 秒の窓ではどの呼び出しも通らない
+            if budget.max_calls == 0 || budget.window.is_zero() {
+                return Err((
+                    "llm_budget",
+                    "max_calls and window must be positive".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Changes to [`AgentSettings`]. An absent field keeps the setting, `null` unsets
+/// it; fields other than the settings are rejected.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AgentSettingsPatch {
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<String>)]
+    pub provider: Option<Option<String>>,
+
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<f32>)]
+    pub temperature: Option<Option<f32>>,
+
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<usize>)]
+    pub max_tokens: Option<Option<usize>>,
+
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<LlmBudgetConfig>)]
+    pub llm_budget: Option<Option<LlmBudgetConfig>>,
+}
+
+impl AgentSettingsPatch {
+    /// `settings` with the changes of the patch
+    pub fn apply(&self, settings: &AgentSettings) -> AgentSettings {
+        fn merge<T: Clone>(current: &Option<T>, change: &Option<Option<T>>) -> Option<T> {
+            match change {
+                Some(value) => value.clone(),
+                None => current.clone(),
+            }
+        }
+        AgentSettings {
+            provider: merge(&settings.provider, &self.provider),
+            temperature: merge(&settings.temperature, &self.temperature),
+            max_tokens: merge(&settings.max_tokens, &self.max_tokens),
+            llm_budget: merge(&settings.llm_budget, &self.llm_budget),
+        }
+    }
+}
+
+/// `null` を「未設定に戻す」として、フィールドがないことと区別する
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// The settings of one agent, shared by its scaled instances
+#[derive(Debug, Default)]
+pub struct RuntimeSettings {
+    settings: RwLock<AgentSettings>,
+    llm_budget: RwLock<Option<Arc<LlmBudget>>>,
+}
+
+impl RuntimeSettings {
+    pub fn get(&self) -> AgentSettings {
+        self.settings.read().unwrap().clone()
+    }
+
+    /// Apply `patch` if the resulting settings pass `check`, returning them
+    pub fn update<E>(
+        &self,
+        patch: &AgentSettingsPatch,
+        check: impl FnOnce(&AgentSettings) -> Result<(), E>,
+    ) -> Result<AgentSettings, E> {
+        let mut settings = self.settings.write().unwrap();
+        let updated = patch.apply(&settings);
+        check(&updated)?;
+        // 上限が変わったときだけ窓を作り直す
+        if updated.llm_budget != settings.llm_budget {
+            *self.llm_budget.write().unwrap() = updated
+                .llm_budget
+                .clone()
+                .map(|config| Arc::new(LlmBudget::new(config)));
+        }
+        *settings = updated.clone();
+        Ok(updated)
+    }
+
+    /// The budget of the `llm_budget` setting, if set
+    pub fn llm_budget(&self) -> Option<Arc<LlmBudget>> {
+        self.llm_budget.read().unwrap().clone()
+    }
+}
+
+/// The runtime settings of a system's agents, keyed by agent name
+#[derive(Debug, Default)]
+pub struct AgentSettingsStore {
+    settings: DashMap<String, Arc<RuntimeSettings>>,
+}
+
+impl AgentSettingsStore {
+    pub fn get_or_create(&self, agent_name: &str) -> Arc<RuntimeSettings> {
+        self.settings
+            .entry(agent_name.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// The settings of `agent_name`, all unset unless they were tuned
+    pub fn get(&self, agent_name: &str) -> AgentSettings {
+        self.settings
+            .get(agent_name)
+            .map(|settings| settings.get())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_patch_keeps_absent_and_unsets_null() {
+        let settings = AgentSettings {
+            provider: Some("fast".to_string()),
+            temperature: Some(0.7),
+            ..Default::default()
+        };
+        let patch: AgentSettingsPatch =
+            serde_json::from_str(r#"{"temperature": null, "max_tokens": 128}"#).unwrap();
+
+        assert_eq!(
+            patch.apply(&settings),
+            AgentSettings {
+                provider: Some("fast".to_string()),
+                temperature: None,
+                max_tokens: Some(128),
+                llm_budget: None,
+            }
+        );
+        assert!(serde_json::from_str::<AgentSettingsPatch>(r#"{"model": "gpt-4o"}"#).is_err());
+    }
+
+    #[test]
+    fn test_invalid_update_is_not_applied() {
+        let settings = RuntimeSettings::default();
+        let check = |settings: &AgentSettings| settings.check(|name| name == "fast");
+        let patch = |json: &str| serde_json::from_str::<AgentSettingsPatch>(json).unwrap();
+
+        let updated = settings
+            .update(&patch(r#"{"temperature": 0.2, "provider": "fast"}"#), check)
+            .unwrap();
+        assert_eq!(updated.temperature, Some(0.2));
+
+        let (setting, _) = settings
+            .update(&patch(r#"{"temperature": 2.5}"#), check)
+            .unwrap_err();
+        assert_eq!(setting, "temperature");
+        let (setting, _) = settings
+            .update(&patch(r#"{"provider": "slow"}"#), check)
+            .unwrap_err();
+        assert_eq!(setting, "provider");
+        assert_eq!(settings.get(), updated);
+
+        assert!(settings.llm_budget().is_none());
+        settings
+            .update(
+                &AgentSettingsPatch {
+                    llm_budget: Some(Some(LlmBudgetConfig {
+                        max_calls: 1,
+                        window: Duration::from_secs(60),
+                    })),
+                    ..Default::default()
+                },
+                check,
+            )
+            .unwrap();
+        let budget = settings.llm_budget().unwrap();
+        budget.try_acquire().unwrap();
+        assert!(budget.try_acquire().is_err());
+    }
+}
@@ -12,6 +12,8 @@
 //! * **Repetition Combinators**: Parsers that handle repetition like `Many`, `Many1`, `SeparatedList`
 //! * **Transformation Combinators**: Parsers that transform outputs like `Map`, `AsUnit`
 //! * **Error Handling Combinators**: Parsers that provide context like `WithContext`
+//! * **Operator Combinators**: `Pratt`, which parses prefix and infix operator chains
+//!   described by an `OperatorTable`
 
 use super::core::ParseError;
 use super::core::ParseResult;
@@ -907,6 +909,184 @@ where
     }
 }
 
+/// Associativity of an infix operator in an [`OperatorTable`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Associativity {
+    /// `a - b - c` is `(a - b) - c`
+    Left,
+    /// `a ^ b ^ c` is `a ^ (b ^ c)`
+    Right,
+}
+
+struct InfixOperator<I, O> {
+    token: I,
+    precedence: u8,
+    associativity: Associativity,
+    build: Box<dyn Fn(O, O) -> O>,
+}
+
+struct PrefixOperator<I, O> {
+    token: I,
+    build: Box<dyn Fn(O) -> O>,
+}
+
+/// OperatorTable: Operators understood by a [`Pratt`] parser
+///
+/// Infix operators bind tighter the higher their precedence. A prefix operator applies
+/// to a single operand and does not nest, so `- -a` does not parse. Operators are
+/// looked up in the order they were added.
+pub struct OperatorTable<I, O> {
+    infix: Vec<InfixOperator<I, O>>,
+    prefix: Vec<PrefixOperator<I, O>>,
+}
+
+impl<I, O> OperatorTable<I, O> {
+    /// Creates an empty table
+    pub fn new() -> Self {
+        Self {
+            infix: Vec::new(),
+            prefix: Vec::new(),
+        }
+    }
+
+    /// Adds an infix operator
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The token of the operator
+    /// * `precedence` - Binding power; higher binds tighter
+    /// * `associativity` - How a chain of operators of equal precedence groups
+    /// * `build` - Builds the output from the left and right operands
+    pub fn with_infix(
+        mut self,
+        token: I,
+        precedence: u8,
+        associativity: Associativity,
+        build: impl Fn(O, O) -> O + 'static,
+    ) -> Self {
+        self.infix.push(InfixOperator {
+            token,
+            precedence,
+            associativity,
+            build: Box::new(build),
+        });
+        self
+    }
+
+    /// Adds a prefix operator
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The token of the operator
+    /// * `build` - Builds the output from the operand
+    pub fn with_prefix(mut self, token: I, build: impl Fn(O) -> O + 'static) -> Self {
+        self.prefix.push(PrefixOperator {
+            token,
+            build: Box::new(build),
+        });
+        self
+    }
+}
+
+impl<I, O> Default for OperatorTable<I, O> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Pratt: Parses operands joined by the operators of an [`OperatorTable`]
+///
+/// Operands are parsed with the inner parser, optionally preceded by a prefix operator.
+/// An infix operator whose right operand fails to parse is left unconsumed, the same
+/// way `Many` stops. If no operand can be parsed, it returns a NoAlternative error.
+pub struct Pratt<P, I, O> {
+    /// The parser for operands
+    operand: P,
+    /// The operators to parse between operands
+    table: OperatorTable<I, O>,
+    /// Infix operators below this precedence end the expression
+    min_precedence: u8,
+}
+
+impl<P, I, O> Pratt<P, I, O> {
+    /// Creates a new Pratt parser
+    ///
+    /// # Arguments
+    ///
+    /// * `operand` - The parser for operands
+    /// * `table` - The operators to parse between operands
+    pub fn new(operand: P, table: OperatorTable<I, O>) -> Self {
+        Self {
+            operand,
+            table,
+            min_precedence: 0,
+        }
+    }
+
+    /// Only parses infix operators of at least `min_precedence`
+    pub fn with_min_precedence(mut self, min_precedence: u8) -> Self {
+        self.min_precedence = min_precedence;
+        self
+    }
+}
+
+impl<P, I, O> Pratt<P, I, O>
+where
+    P: Parser<I, O>,
+    I: PartialEq,
+{
+    fn parse_operand(&self, input: &[I], pos: usize) -> ParseResult<O> {
+        if let Some(token) = input.get(pos) {
+            for prefix in self.table.prefix.iter().filter(|p| &p.token == token) {
+                if let Ok((next_pos, operand)) = self.operand.parse(input, pos + 1) {
+                    return Ok((next_pos, (prefix.build)(operand)));
+                }
+            }
+        }
+        self.operand
+            .parse(input, pos)
+            .map_err(|_| ParseError::NoAlternative {
+                position: pos,
+                context: None,
+            })
+    }
+
+    // min_precedence は左結合の右辺で u8::MAX + 1 になり得るので u16 で扱う
+    fn parse_min(&self, input: &[I], pos: usize, min_precedence: u16) -> ParseResult<O> {
+        let (mut current_pos, mut left) = self.parse_operand(input, pos)?;
+        while let Some(infix) = input.get(current_pos).and_then(|token| {
+            self.table
+                .infix
+                .iter()
+                .find(|op| &op.token == token)
+                .filter(|op| u16::from(op.precedence) >= min_precedence)
+        }) {
+            let right_precedence = match infix.associativity {
+                Associativity::Left => u16::from(infix.precedence) + 1,
+                Associativity::Right => u16::from(infix.precedence),
+            };
+            match self.parse_min(input, current_pos + 1, right_precedence) {
+                Ok((next_pos, right)) => {
+                    left = (infix.build)(left, right);
+                    current_pos = next_pos;
+                }
+                Err(_) => break,
+            }
+        }
+        Ok((current_pos, left))
+    }
+}
+
+impl<P, I, O> Parser<I, O> for Pratt<P, I, O>
+where
+    P: Parser<I, O>,
+    I: PartialEq,
+{
+    fn parse(&self, input: &[I], pos: usize) -> ParseResult<O> {
+        self.parse_min(input, pos, u16::from(self.min_precedence))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1407,4 +1587,43 @@ mod tests {
             })
         );
     }
+
+    #[test]
+    fn test_pratt() {
+        let digit = Satisfy::new(|x: &char| x.is_ascii_digit().then(|| x.to_string()));
+        let table = || {
+            OperatorTable::<char, String>::new()
+                .with_infix('+', 1, Associativity::Left, |l, r| format!("({}+{})", l, r))
+                .with_infix('^', 2, Associativity::Right, |l, r| {
+                    format!("({}^{})", l, r)
+                })
+                .with_prefix('-', |x| format!("(-{})", x))
+        };
+        let parse = |s: &str| {
+            let input: Vec<char> = s.chars().collect();
+            Pratt::new(digit.clone(), table()).parse(&input, 0)
+        };
+
+        // 結合性と優先順位
+        assert_eq!(parse("1+2+3"), Ok((5, "((1+2)+3)".to_string())));
+        assert_eq!(parse("1^2^3"), Ok((5, "(1^(2^3))".to_string())));
+        assert_eq!(parse("1+2^3+4"), Ok((7, "((1+(2^3))+4)".to_string())));
+        assert_eq!(parse("-1^-2"), Ok((5, "((-1)^(-2))".to_string())));
+
+        // 右辺のない演算子は消費しない
+        assert_eq!(parse("1+2+"), Ok((3, "(1+2)".to_string())));
+        // 前置演算子は入れ子にならない
+        assert_eq!(
+            parse("--1"),
+            Err(ParseError::NoAlternative {
+                position: 0,
+                context: None
+            })
+        );
+
+        // 最低優先順位より弱い演算子で止まる
+        let input: Vec<char> = "1^2+3".chars().collect();
+        let parser = Pratt::new(digit.clone(), table()).with_min_precedence(2);
+        assert_eq!(parser.parse(&input, 0), Ok((3, "(1^2)".to_string())));
+    }
 }
@@ -5,6 +5,8 @@
 
 use thiserror::Error;
 
+use crate::message_catalog::{Diagnostic, codes};
+
 /// Parser trait defines the core parsing interface.
 ///
 /// All parsers in the system implement this trait, which takes an input slice
@@ -115,6 +117,24 @@ impl ParseError {
         }
     }
 
+    /// The diagnostic of this error, rendered by a [`MessageCatalog`](crate::message_catalog::MessageCatalog)
+    pub fn diagnostic(&self) -> Diagnostic {
+        match self {
+            ParseError::UnexpectedEOF { message, .. } => {
+                Diagnostic::new(codes::PARSE_UNEXPECTED_EOF).with_arg("message", message)
+            }
+            ParseError::Unexpected {
+                expected, parsed, ..
+            } => Diagnostic::new(codes::PARSE_UNEXPECTED)
+                .with_arg("expected", expected)
+                .with_arg("found", parsed),
+            ParseError::NoAlternative { .. } => Diagnostic::new(codes::PARSE_NO_ALTERNATIVE),
+            ParseError::Failure { message, .. } => {
+                Diagnostic::new(codes::PARSE_FAILURE).with_arg("message", message)
+            }
+        }
+    }
+
     pub fn get_position(&self) -> usize {
         match self {
             ParseError::UnexpectedEOF { position, .. } => *position,
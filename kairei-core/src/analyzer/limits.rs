@@ -0,0 +1,503 @@
+//! Size limits on DSL input.
+//!
+//! A large enough DSL exhausts memory or the stack of the parser, type checker or
+//! evaluator, all of which recurse over the AST. [`check_token_nesting`] runs before
+//! parsing and bounds bracket nesting, so the parser never recurses deeper than the
+//! limit; [`check_root`] runs on the parsed AST before type checking. Both report the
+//! limit that was exceeded as an [`AnalysisLimitError`].
+
+use thiserror::Error;
+
+use crate::{
+    ast::{
+        AnswerDef, Argument, EventHandler, Expression, HandlerBlock, LifecycleDef, Literal, Root,
+        Statement,
+    },
+    config::AnalysisLimits,
+    message_catalog::{Diagnostic, codes},
+    tokenizer::{
+        symbol::Delimiter,
+        token::{Token, TokenSpan},
+    },
+};
+
+#[derive(Debug, Clone, Error, PartialEq)]
+pub enum AnalysisLimitError {
+    #[error("{handler} of {agent} has {count} statements, more than the limit of {max}")]
+    TooManyStatements {
+        agent: String,
+        handler: String,
+        count: usize,
+        max: usize,
+    },
+
+    #[error("{agent} has {count} handlers, more than the limit of {max}")]
+    TooManyHandlers {
+        agent: String,
+        count: usize,
+        max: usize,
+    },
+
+    #[error("{location} is nested deeper than the limit of {max}")]
+    NestingTooDeep { location: String, max: usize },
+}
+
+impl AnalysisLimitError {
+    /// The diagnostic of this error, rendered by a [`MessageCatalog`](crate::message_catalog::MessageCatalog)
+    pub fn diagnostic(&self) -> Diagnostic {
+        match self {
+            Self::TooManyStatements {
+                agent,
+                handler,
+                count,
+                max,
+            } => Diagnostic::new(codes::LIMIT_TOO_MANY_STATEMENTS)
+                .with_arg("agent", agent)
+                .with_arg("handler", handler)
+                .with_arg("count", count)
+                .with_arg("max", max),
+            Self::TooManyHandlers { agent, count, max } => {
+                Diagnostic::new(codes::LIMIT_TOO_MANY_HANDLERS)
+                    .with_arg("agent", agent)
+                    .with_arg("count", count)
+                    .with_arg("max", max)
+            }
+            Self::NestingTooDeep { location, max } => {
+                Diagnostic::new(codes::LIMIT_NESTING_TOO_DEEP)
+                    .with_arg("location", location)
+                    .with_arg("max", max)
+            }
+        }
+    }
+}
+
+/// Reject bracket nesting deeper than [`AnalysisLimits::max_nesting_depth`]
+pub fn check_token_nesting(
+    tokens: &[TokenSpan],
+    limits: &AnalysisLimits,
+) -> Result<(), AnalysisLimitError> {
+    let mut depth = 0usize;
+    for token_span in tokens {
+        match token_span.token {
+            Token::Delimiter(Delimiter::OpenBrace)
+            | Token::Delimiter(Delimiter::OpenSetBrace)
+            | Token::Delimiter(Delimiter::OpenParen)
+            | Token::Delimiter(Delimiter::OpenBracket) => {
+                depth += 1;
+                if depth > limits.max_nesting_depth {
+                    return Err(AnalysisLimitError::NestingTooDeep {
+                        location: format!("Input at {}", token_span.span),
+                        max: limits.max_nesting_depth,
+                    });
+                }
+            }
+            Token::Delimiter(Delimiter::CloseBrace)
+            | Token::Delimiter(Delimiter::CloseParen)
+            | Token::Delimiter(Delimiter::CloseBracket) => {
+                // 括弧の対応はパーサーが検査する
+                depth = depth.saturating_sub(1);
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Check the handlers of every agent and of the world against `limits`
+pub fn check_root(root: &Root, limits: &AnalysisLimits) -> Result<(), AnalysisLimitError> {
+    if let Some(world) = &root.world_def {
+        let handlers: Vec<_> = world
+            .handlers
+            .handlers
+            .iter()
+            .map(|handler| (format!("on {}", handler.event_name), &handler.block))
+            .collect();
+        check_agent(&format!("world {}", world.name), &handlers, limits)?;
+    }
+    for agent in &root.micro_agent_defs {
+        let handlers = agent_handlers(
+            agent.lifecycle.as_ref(),
+            [
+                agent.observe.as_ref().map(|o| &o.handlers),
+                agent.react.as_ref().map(|r| &r.handlers),
+            ],
+            agent.answer.as_ref(),
+        );
+        check_agent(&format!("agent {}", agent.name), &handlers, limits)?;
+    }
+    for agent in &root.sistence_agent_defs {
+        let handlers = agent_handlers(
+            agent.lifecycle.as_ref(),
+            [
+                agent.observe.as_ref().map(|o| &o.handlers),
+                agent.react.as_ref().map(|r| &r.handlers),
+            ],
+            agent.answer.as_ref(),
+        );
+        check_agent(&format!("agent {}", agent.name), &handlers, limits)?;
+    }
+    Ok(())
+}
+
+/// Every handler of an agent with its name, e.g. `on request GetForecast`
+fn agent_handlers<'a>(
+    lifecycle: Option<&'a LifecycleDef>,
+    event_handlers: [Option<&'a Vec<EventHandler>>; 2],
+    answer: Option<&'a AnswerDef>,
+) -> Vec<(String, &'a HandlerBlock)> {
+    let lifecycle = lifecycle
+        .into_iter()
+        .flat_map(|lifecycle| {
+            [
+                ("on init", &lifecycle.on_init),
+                ("on destroy", &lifecycle.on_destroy),
+            ]
+        })
+        .filter_map(|(name, block)| block.as_ref().map(|block| (name.to_string(), block)));
+    let events = event_handlers
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|handler| (format!("on {}", handler.event_type), &handler.block));
+    let requests = answer
+        .into_iter()
+        .flat_map(|answer| &answer.handlers)
+        .map(|handler| {
+            (
+                format!("on request {}", handler.request_type),
+                &handler.block,
+            )
+        });
+    lifecycle.chain(events).chain(requests).collect()
+}
+
+fn check_agent(
+    agent: &str,
+    handlers: &[(String, &HandlerBlock)],
+    limits: &AnalysisLimits,
+) -> Result<(), AnalysisLimitError> {
+    if handlers.len() > limits.max_handlers_per_agent {
+        return Err(AnalysisLimitError::TooManyHandlers {
+            agent: agent.to_string(),
+            count: handlers.len(),
+            max: limits.max_handlers_per_agent,
+        });
+    }
+    for (handler, block) in handlers {
+        let mut checker = HandlerChecker {
+            limits,
+            statements: 0,
+        };
+        let result = checker.statements(&block.statements, 1);
+        if checker.statements > limits.max_statements_per_handler {
+            return Err(AnalysisLimitError::TooManyStatements {
+                agent: agent.to_string(),
+                handler: handler.clone(),
+                count: checker.statements,
+                max: limits.max_statements_per_handler,
+            });
+        }
+        result.map_err(|TooDeep| AnalysisLimitError::NestingTooDeep {
+            location: format!("{} of {}", handler, agent),
+            max: limits.max_nesting_depth,
+        })?;
+    }
+    Ok(())
+}
+
+struct TooDeep;
+
+/// Counts the statements of one handler and bounds its nesting.
+/// Stops descending at the depth limit, so its own recursion stays bounded.
+struct HandlerChecker<'a> {
+    limits: &'a AnalysisLimits,
+    statements: usize,
+}
+
+impl HandlerChecker<'_> {
+    fn enter(&self, depth: usize) -> Result<(), TooDeep> {
+        if depth > self.limits.max_nesting_depth {
+            Err(TooDeep)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn statements(&mut self, statements: &[Statement], depth: usize) -> Result<(), TooDeep> {
+        for statement in statements {
+            self.statement(statement, depth)?;
+            if self.statements > self.limits.max_statements_per_handler {
+                // 件数の超過は呼び出し側が報告する
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, statement: &Statement, depth: usize) -> Result<(), TooDeep> {
+        self.enter(depth)?;
+        self.statements += 1;
+        match statement {
+            Statement::Expression(expression)
+            | Statement::Return(expression)
+            | Statement::Yield(expression) => self.expression(expression, depth + 1),
+            Statement::Assignment { target, value } => {
+                for target in target {
+                    self.expression(target, depth + 1)?;
+                }
+                self.expression(value, depth + 1)
+            }
+            Statement::Let { value, .. } => self.expression(value, depth + 1),
+            Statement::Emit { parameters, .. } => self.arguments(parameters, depth + 1),
+            Statement::Log {
+                message, fields, ..
+            } => {
+                self.expression(message, depth + 1)?;
+                for (_, value) in fields {
+                    self.expression(value, depth + 1)?;
+                }
+                Ok(())
+            }
+            Statement::Block(statements) => self.statements(statements, depth + 1),
+            Statement::WithError {
+                statement,
+                error_handler_block,
+            } => {
+                self.statement(statement, depth + 1)?;
+                self.statements(&error_handler_block.error_handler_statements, depth + 1)
+            }
+            Statement::If {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                self.expression(condition, depth + 1)?;
+                self.statements(then_block, depth + 1)?;
+                match else_block {
+                    Some(else_block) => self.statements(else_block, depth + 1),
+                    None => Ok(()),
+                }
+            }
+            Statement::Retry {
+                times,
+                backoff,
+                body,
+            } => {
+                self.expression(times, depth + 1)?;
+                if let Some(backoff) = backoff {
+                    self.expression(backoff, depth + 1)?;
+                }
+                self.statements(body, depth + 1)
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression, depth: usize) -> Result<(), TooDeep> {
+        self.enter(depth)?;
+        match expression {
+            Expression::Literal(literal) => self.literal(literal, depth + 1),
+            Expression::Variable(_) | Expression::StateAccess(_) => Ok(()),
+            Expression::FunctionCall { arguments, .. } | Expression::Await(arguments) => {
+                for argument in arguments {
+                    self.expression(argument, depth + 1)?;
+                }
+                Ok(())
+            }
+            Expression::WillAction { parameters, .. } => {
+                for parameter in parameters {
+                    self.expression(parameter, depth + 1)?;
+                }
+                Ok(())
+            }
+            Expression::Think { args, .. } => self.arguments(args, depth + 1),
+            Expression::Request { parameters, .. } => self.arguments(parameters, depth + 1),
+            Expression::BinaryOp { left, right, .. } => {
+                self.expression(left, depth + 1)?;
+                self.expression(right, depth + 1)
+            }
+            Expression::Ok(inner) | Expression::Err(inner) => self.expression(inner, depth + 1),
+        }
+    }
+
+    fn arguments(&mut self, arguments: &[Argument], depth: usize) -> Result<(), TooDeep> {
+        for argument in arguments {
+            match argument {
+                Argument::Named { value, .. } | Argument::Positional(value) => {
+                    self.expression(value, depth)?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn literal(&mut self, literal: &Literal, depth: usize) -> Result<(), TooDeep> {
+        self.enter(depth)?;
+        match literal {
+            Literal::List(items) | Literal::Set(items) => {
+                for item in items {
+                    self.literal(item, depth + 1)?;
+                }
+                Ok(())
+            }
+            Literal::Map(entries) => {
+                for value in entries.values() {
+                    self.literal(value, depth + 1)?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ast::{ASTError, BinaryOperator, MicroAgentDef, RequestHandler, TypeInfo},
+        ast_registry::AstRegistry,
+        tokenizer::token::Tokenizer,
+    };
+
+    fn limits() -> AnalysisLimits {
+        AnalysisLimits {
+            max_statements_per_handler: 3,
+            max_handlers_per_agent: 2,
+            max_nesting_depth: 6,
+        }
+    }
+
+    fn root(handlers: Vec<Vec<Statement>>) -> Root {
+        Root {
+            dsl_version: None,
+            type_defs: vec![],
+            world_def: None,
+            micro_agent_defs: vec![MicroAgentDef {
+                name: "Weather".to_string(),
+                answer: Some(AnswerDef {
+                    handlers: handlers
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, statements)| RequestHandler {
+                            request_type: format!("Request{}", i).as_str().into(),
+                            parameters: vec![],
+                            return_type: TypeInfo::Simple("String".to_string()),
+                            constraints: None,
+                            requires: None,
+                            block: HandlerBlock { statements },
+                            doc: None,
+                            cache: None,
+                        })
+                        .collect(),
+                }),
+                ..Default::default()
+            }],
+            sistence_agent_defs: vec![],
+        }
+    }
+
+    fn sum(terms: usize) -> Expression {
+        (1..terms).fold(Expression::Variable("x".to_string()), |left, _| {
+            Expression::BinaryOp {
+                op: BinaryOperator::Add,
+                left: Box::new(left),
+                right: Box::new(Expression::Variable("x".to_string())),
+            }
+        })
+    }
+
+    fn ret() -> Statement {
+        Statement::Return(Expression::Variable("x".to_string()))
+    }
+
+    #[test]
+    fn test_normal_ast_passes() {
+        let root = root(vec![vec![ret(), ret()], vec![Statement::Return(sum(3))]]);
+        assert_eq!(check_root(&root, &limits()), Ok(()));
+    }
+
+    #[test]
+    fn test_too_many_statements() {
+        let root = root(vec![vec![
+            ret(),
+            Statement::If {
+                condition: Expression::Variable("x".to_string()),
+                then_block: vec![ret(), ret()],
+                else_block: None,
+            },
+        ]]);
+        assert_eq!(
+            check_root(&root, &limits()),
+            Err(AnalysisLimitError::TooManyStatements {
+                agent: "agent Weather".to_string(),
+                handler: "on request Request0".to_string(),
+                count: 4,
+                max: 3,
+            })
+        );
+    }
+
+    #[test]
+    fn test_too_many_handlers() {
+        let root = root(vec![vec![ret()], vec![ret()], vec![ret()]]);
+        assert_eq!(
+            check_root(&root, &limits()),
+            Err(AnalysisLimitError::TooManyHandlers {
+                agent: "agent Weather".to_string(),
+                count: 3,
+                max: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn test_nesting_too_deep() {
+        let root = root(vec![vec![Statement::Return(sum(10))]]);
+        assert_eq!(
+            check_root(&root, &limits()),
+            Err(AnalysisLimitError::NestingTooDeep {
+                location: "on request Request0 of agent Weather".to_string(),
+                max: 6,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_registry_rejects_oversized_dsl() {
+        let registry = AstRegistry::default().with_limits(limits());
+        let dsl = r#"
+            micro Weather {
+                answer {
+                    on request Forecast(city: String) -> Result<String, Error> {
+                        a = 1
+                        b = 2
+                        c = 3
+                        return Ok(city)
+                    }
+                }
+            }
+        "#;
+        assert!(matches!(
+            registry.create_ast_from_dsl(dsl).await,
+            Err(ASTError::LimitExceeded(
+                AnalysisLimitError::TooManyStatements { count: 4, .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_token_nesting() {
+        let tokens = Tokenizer::new()
+            .tokenize(&format!("x = {}1{};", "(".repeat(7), ")".repeat(7)))
+            .unwrap();
+        assert!(matches!(
+            check_token_nesting(&tokens, &limits()),
+            Err(AnalysisLimitError::NestingTooDeep { max: 6, .. })
+        ));
+
+        let tokens = Tokenizer::new()
+            .tokenize(&format!("x = {}1{};", "(".repeat(6), ")".repeat(6)))
+            .unwrap();
+        assert_eq!(check_token_nesting(&tokens, &limits()), Ok(()));
+    }
+}
@@ -45,6 +45,7 @@ pub mod combinators;
 pub mod core;
 pub mod doc_parser;
 pub mod documentation_collector;
+pub mod limits;
 pub mod parsers;
 pub mod prelude;
 
@@ -4,7 +4,7 @@ use super::{
     handlers::{answer::*, observe::*, react::*},
     statement::*,
     types::parse_type_info,
-    world::parse_policy,
+    world::{parse_config_keyword, parse_localized_persona, parse_persona, parse_policy},
     *,
 };
 use crate::ast;
@@ -13,31 +13,45 @@ use crate::tokenizer::{keyword::Keyword, token::Token};
 pub fn parse_agent_def() -> impl Parser<Token, ast::MicroAgentDef> {
     with_context(
         map(
-            tuple5(
+            tuple6(
+                parse_doc_comment(),
                 as_unit(parse_micro_agent_keyword()),
                 parse_identifier(),
                 parse_open_brace(),
                 many(choice(vec![
                     Box::new(map(parse_policy(), AgentDefItem::Policy)),
+                    Box::new(map(parse_persona(), AgentDefItem::Persona)),
+                    Box::new(map(parse_localized_persona(), |(locale, text)| {
+                        AgentDefItem::LocalizedPersona(locale, text)
+                    })),
+                    Box::new(map(parse_contract(), AgentDefItem::Contract)),
                     Box::new(map(parse_lifecycle(), AgentDefItem::Lifecycle)),
                     Box::new(map(parse_state(), AgentDefItem::State)),
+                    Box::new(map(parse_agent_config(), AgentDefItem::Config)),
                     Box::new(map(parse_observe(), AgentDefItem::Observe)),
                     Box::new(map(parse_answer(), AgentDefItem::Answer)),
                     Box::new(map(parse_react(), AgentDefItem::React)),
                 ])),
                 parse_close_brace(),
             ),
-            |(_, name, _, items, _)| {
+            |(doc, _, name, _, items, _)| {
                 let mut agent = ast::MicroAgentDef {
                     name,
+                    doc,
                     ..Default::default()
                 };
 
                 for item in items {
                     match item {
                         AgentDefItem::Policy(policy) => agent.policies.push(policy),
+                        AgentDefItem::Persona(text) => agent.persona = Some(text),
+                        AgentDefItem::LocalizedPersona(locale, text) => {
+                            agent.localized_personas.insert(locale, text);
+                        }
+                        AgentDefItem::Contract(contracts) => agent.contracts.extend(contracts),
                         AgentDefItem::Lifecycle(lifecycle) => agent.lifecycle = Some(lifecycle),
                         AgentDefItem::State(state) => agent.state = Some(state),
+                        AgentDefItem::Config(config) => agent.config = Some(config),
                         AgentDefItem::Observe(observe) => agent.observe = Some(observe),
                         AgentDefItem::Answer(answer) => agent.answer = Some(answer),
                         AgentDefItem::React(react) => agent.react = Some(react),
@@ -58,8 +72,12 @@ pub use sistence::parse_sistence_agent_def;
 #[derive(Debug, Clone, PartialEq)]
 enum AgentDefItem {
     Policy(ast::Policy),
+    Persona(String),
+    LocalizedPersona(String, String),
+    Contract(Vec<ast::RequestContract>),
     Lifecycle(ast::LifecycleDef),
     State(ast::StateDef),
+    Config(ast::AgentConfigDef),
     Observe(ast::ObserveDef),
     Answer(ast::AnswerDef),
     React(ast::ReactDef),
@@ -155,20 +173,22 @@ pub fn parse_state() -> impl Parser<Token, ast::StateDef> {
 fn parse_state_var() -> impl Parser<Token, (String, ast::StateVarDef)> {
     with_context(
         map(
-            tuple5(
+            tuple6(
+                parse_doc_comment(),
                 parse_identifier(),
                 as_unit(parse_colon()),
                 parse_type_info(),
                 optional(preceded(as_unit(parse_equal()), parse_expression())),
                 as_unit(parse_semicolon()),
             ),
-            |(name, _, type_info, initial_value, _)| {
+            |(doc, name, _, type_info, initial_value, _)| {
                 (
                     name.clone(),
                     ast::StateVarDef {
                         name,
                         type_info,
                         initial_value,
+                        doc,
                     },
                 )
             },
@@ -177,6 +197,53 @@ fn parse_state_var() -> impl Parser<Token, (String, ast::StateVarDef)> {
     )
 }
 
+/// Parses the configuration parameters of a MicroAgent.
+///
+/// ```text
+/// config {
+///     region: String,
+///     max_results: Int = 10
+/// }
+/// ```
+///
+/// Defaults are literals, since they are checked against the supplied values
+/// before any handler runs.
+pub fn parse_agent_config() -> impl Parser<Token, ast::AgentConfigDef> {
+    with_context(
+        map(
+            tuple4(
+                as_unit(parse_config_keyword()),
+                as_unit(parse_open_brace()),
+                separated_list(parse_config_param(), as_unit(parse_comma())),
+                as_unit(parse_close_brace()),
+            ),
+            |(_, _, parameters, _)| ast::AgentConfigDef { parameters },
+        ),
+        "agent config",
+    )
+}
+
+fn parse_config_param() -> impl Parser<Token, ast::ConfigParamDef> {
+    with_context(
+        map(
+            tuple5(
+                parse_doc_comment(),
+                parse_identifier(),
+                as_unit(parse_colon()),
+                parse_type_info(),
+                optional(preceded(as_unit(parse_equal()), parse_literal())),
+            ),
+            |(doc, name, _, type_info, default)| ast::ConfigParamDef {
+                name,
+                type_info,
+                default,
+                doc,
+            },
+        ),
+        "config parameter",
+    )
+}
+
 fn parse_state_keyword() -> impl Parser<Token, Token> {
     with_context(equal(Token::Keyword(Keyword::State)), "state keyword")
 }
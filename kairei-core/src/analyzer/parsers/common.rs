@@ -6,7 +6,7 @@ use crate::tokenizer::{
     keyword::Keyword,
     literal::{Literal, StringPart},
     symbol::Delimiter,
-    token::Token,
+    token::{CommentType, Token},
 };
 use std::{collections::HashMap, time::Duration};
 
@@ -31,6 +31,7 @@ pub fn parse_literal() -> impl Parser<Token, ast::Literal> {
             Box::new(parse_duration()),
             Box::new(parse_list()),
             Box::new(parse_map()),
+            Box::new(parse_set()),
             Box::new(parse_retry()),
             Box::new(parse_null()),
         ]),
@@ -71,6 +72,10 @@ pub fn parse_close_bracket() -> impl Parser<Token, Token> {
     equal(Token::Delimiter(Delimiter::CloseBracket))
 }
 
+pub fn parse_at() -> impl Parser<Token, Token> {
+    equal(Token::Delimiter(Delimiter::At))
+}
+
 pub fn parse_open_brace() -> impl Parser<Token, Token> {
     with_context(equal(Token::Delimiter(Delimiter::OpenBrace)), "open brace")
 }
@@ -87,6 +92,10 @@ pub fn parse_angle_close() -> impl Parser<Token, Token> {
     equal(Token::Operator(Operator::Greater))
 }
 
+pub fn parse_question() -> impl Parser<Token, Token> {
+    equal(Token::Operator(Operator::Question))
+}
+
 // リテラルパーサー
 fn parse_float() -> impl Parser<Token, ast::Literal> {
     with_context(map(parse_f64(), ast::Literal::Float), "float")
@@ -97,7 +106,13 @@ fn parse_integer() -> impl Parser<Token, ast::Literal> {
 }
 
 fn parse_string() -> impl Parser<Token, ast::Literal> {
-    with_context(parse_regular_string(), "string")
+    with_context(
+        choice(vec![
+            Box::new(parse_regular_string()),
+            Box::new(parse_raw_string()),
+        ]),
+        "string",
+    )
 }
 
 fn parse_regular_string() -> impl Parser<Token, ast::Literal> {
@@ -107,26 +122,34 @@ fn parse_regular_string() -> impl Parser<Token, ast::Literal> {
             Token::Literal(Literal::String(StringLiteral::Triple(parts))) => Some(parts),
             _ => None,
         };
-        parts.map(|parts| {
-            match parts.len() {
-                // 0 means empty string
-                0 => ast::Literal::String("".to_string()),
-                // 1 or more means regular string, concat them
-                _ => ast::Literal::String(
-                    parts
-                        .iter()
-                        .map(|part| match part {
-                            StringPart::Literal(s) => s.clone(),
-                            StringPart::Interpolation(w) => format!("${{{}}}", w),
-                            StringPart::NewLine => "\n".to_string(),
-                        })
-                        .collect::<String>(),
-                ),
-            }
-        })
+        parts.map(|parts| ast::Literal::String(concat_string_parts(parts)))
+    })
+}
+
+// raw string は補間しないため、専用の Literal として保持する
+fn parse_raw_string() -> impl Parser<Token, ast::Literal> {
+    satisfy(|token| match token {
+        Token::Literal(Literal::String(StringLiteral::Raw { hashes, parts })) => {
+            Some(ast::Literal::RawString {
+                value: concat_string_parts(parts),
+                hashes: *hashes,
+            })
+        }
+        _ => None,
     })
 }
 
+fn concat_string_parts(parts: &[StringPart]) -> String {
+    parts
+        .iter()
+        .map(|part| match part {
+            StringPart::Literal(s) => s.clone(),
+            StringPart::Interpolation(w) => format!("${{{}}}", w),
+            StringPart::NewLine => "\n".to_string(),
+        })
+        .collect::<String>()
+}
+
 fn parse_boolean() -> impl Parser<Token, ast::Literal> {
     choice(vec![Box::new(parse_true()), Box::new(parse_false())])
 }
@@ -149,6 +172,47 @@ pub fn parse_on_keyword() -> impl Parser<Token, Token> {
     with_context(equal(Token::Keyword(Keyword::On)), "on keyword")
 }
 
+/// Doc comments preceding an item, joined line by line.
+///
+/// Only documentation comments survive preprocessing, and only in front of
+/// `micro`, `on`, state variables and request contracts.
+pub fn parse_doc_comment() -> impl Parser<Token, Option<String>> {
+    with_context(
+        optional(map(
+            many1(satisfy(|token| match token {
+                Token::Comment {
+                    content,
+                    comment_type: CommentType::DocumentationLine,
+                } => Some(content.clone()),
+                Token::Comment {
+                    content,
+                    comment_type: CommentType::DocumentationBlock,
+                } => Some(normalize_doc_block(content)),
+                _ => None,
+            })),
+            |lines| lines.join("\n"),
+        )),
+        "doc comment",
+    )
+}
+
+/// `/** ... */` の各行から先頭の `*` と空白を取り除く
+fn normalize_doc_block(content: &str) -> String {
+    let lines: Vec<&str> = content
+        .lines()
+        .map(|line| {
+            let line = line.trim();
+            line.strip_prefix('*').map_or(line, str::trim_start)
+        })
+        .collect();
+    let start = lines.iter().position(|line| !line.is_empty());
+    let end = lines.iter().rposition(|line| !line.is_empty());
+    match (start, end) {
+        (Some(start), Some(end)) => lines[start..=end].join("\n"),
+        _ => String::new(),
+    }
+}
+
 pub fn parse_to_keyword() -> impl Parser<Token, Token> {
     with_context(equal(Token::Keyword(Keyword::To)), "to keyword")
 }
@@ -195,6 +259,20 @@ fn parse_map() -> impl Parser<Token, ast::Literal> {
     )
 }
 
+fn parse_set() -> impl Parser<Token, ast::Literal> {
+    with_context(
+        map(
+            delimited(
+                as_unit(equal(Token::Delimiter(Delimiter::OpenSetBrace))),
+                separated_list(lazy(parse_literal), as_unit(parse_comma())),
+                as_unit(parse_close_brace()),
+            ),
+            ast::Literal::Set,
+        ),
+        "set",
+    )
+}
+
 fn parse_map_entry() -> impl Parser<Token, (String, ast::Literal)> {
     with_context(
         map(
@@ -311,7 +389,7 @@ pub fn parse_usize() -> impl Parser<Token, usize> {
     })
 }
 
-fn parse_duration() -> impl Parser<Token, ast::Literal> {
+pub fn parse_duration() -> impl Parser<Token, ast::Literal> {
     choice(vec![
         Box::new(parse_duration_millis()),
         Box::new(parse_duration_sec()),
@@ -363,8 +441,12 @@ fn parse_sec() -> impl Parser<Token, String> {
     expected(parse_identifier(), "s".to_string())
 }
 
+// `2m` も分として受け付ける
 fn parse_min() -> impl Parser<Token, String> {
-    expected(parse_identifier(), "min".to_string())
+    choice(vec![
+        Box::new(expected(parse_identifier(), "min".to_string())),
+        Box::new(expected(parse_identifier(), "m".to_string())),
+    ])
 }
 
 fn parse_hour() -> impl Parser<Token, String> {
@@ -429,6 +511,24 @@ mod tests {
         assert_eq!(result, ast::Literal::Map(expected_map));
     }
 
+    #[test]
+    fn test_parse_set() {
+        let input = vec![
+            Token::Delimiter(Delimiter::OpenSetBrace),
+            Token::Literal(Literal::Integer(2)),
+            Token::Delimiter(Delimiter::Comma),
+            Token::Literal(Literal::Integer(1)),
+            Token::Delimiter(Delimiter::CloseBrace),
+        ];
+        let (rest, result) = parse_literal().parse(&input, 0).unwrap();
+        assert_eq!(rest, 5);
+        // 重複の除去と並べ替えは評価時に行う
+        assert_eq!(
+            result,
+            ast::Literal::Set(vec![ast::Literal::Integer(2), ast::Literal::Integer(1)])
+        );
+    }
+
     #[test]
     fn test_parse_retry() {
         let input = vec![
@@ -671,6 +771,16 @@ mod tests {
             result,
             super::ast::Literal::Duration(std::time::Duration::from_secs(60))
         );
+        let input = &[
+            Token::Literal(Literal::Integer(10)),
+            Token::Identifier("m".to_string()),
+        ];
+        let (rest, result) = super::parse_duration().parse(input, 0).unwrap();
+        assert_eq!(rest, 2);
+        assert_eq!(
+            result,
+            super::ast::Literal::Duration(std::time::Duration::from_secs(600))
+        );
         let input = &[
             Token::Literal(Literal::Integer(1)),
             Token::Identifier("h".to_string()),
@@ -33,10 +33,11 @@ pub fn documented_parse_request_handler() -> impl DocParserExt<Token, ast::Reque
     let parser = parse_request_handler();
 
     let doc = DocBuilder::new("parse_request_handler", ParserCategory::Handler)
-        .description("Request handlers define how an agent responds to specific request types. Each handler specifies the request type, parameters, return type (must be a Result type), and optional quality constraints. Request handlers enforce type safety and provide a clear contract for agent interactions.")
+        .description("Request handlers define how an agent responds to specific request types. Each handler specifies the request type, parameters, return type (must be a Result type), and optional quality constraints. Parameters may declare `where` constraints, a range or a Boolean expression over the parameter, which are checked before the handler runs; violations are returned to the requester without executing the body. Request handlers enforce type safety and provide a clear contract for agent interactions.")
         .example("on request GetData(id: String) -> Result<Data, Error> {\n  return dataStore.fetch(id)\n}")
         .example("on query.UserInfo(userId: String) -> Result<UserProfile, Error> {\n  return userDatabase.getProfile(userId)\n}")
         .example("on action.UpdateProfile(profile: Profile) -> Result<Boolean, Error> {\n  return Ok(true)\n}")
+        .example("on request plan(city: String where len(city) > 0, days: Int where days in 1..30) -> Result<String, Error> {\n  return Ok(think(\"Plan ${days} days in ${city}\"))\n}")
         .related_parser("parse_answer")
         .related_parser("parse_request_type")
         .build();
@@ -49,10 +50,11 @@ pub fn documented_parse_request_type() -> impl DocParserExt<Token, ast::RequestT
     let parser = parse_request_type();
 
     let doc = DocBuilder::new("parse_request_type", ParserCategory::Handler)
-        .description("Request types define the category and purpose of a request. KAIREI supports three types of requests: query (for data retrieval), action (for operations that may change state), and custom request types. The request type helps determine how the request is processed and what permissions are granted.")
+        .description("Request types define the category and purpose of a request. KAIREI supports three types of requests: query (for data retrieval), action (for operations that may change state), and custom request types. `request _` declares the catch-all handler, which answers every request type no other handler matches and receives that type as its only parameter. The request type helps determine how the request is processed and what permissions are granted.")
         .example("query.GetUserData")
         .example("action.UpdateProfile")
         .example("request CustomOperation")
+        .example("request _")
         .related_parser("parse_request_handler")
         .build();
 
@@ -120,6 +120,24 @@ pub fn documented_parse_assignment_statement() -> impl DocParserExt<Token, ast::
     document(parser, doc)
 }
 
+/// Returns a documented version of the let statement parser
+pub fn documented_parse_let_statement() -> impl DocParserExt<Token, ast::Statement> {
+    // We'll use the public parse_statement function and filter for let statements
+    let parser = filter_parser(parse_statement(), |stmt| {
+        matches!(stmt, ast::Statement::Let { .. })
+    });
+
+    let doc = DocBuilder::new("parse_let_statement", ParserCategory::Statement)
+        .description("Let statements bind the value of an expression to a name. The expression is evaluated once, when the statement runs, and every reference to the name reuses the value, so an expensive call like `think` is not repeated. The type is inferred from the value, or checked against an annotation.")
+        .example("let forecast = think(\"Forecast for Tokyo\")")
+        .example("let count: Int = items.len()")
+        .related_parser("parse_statement")
+        .related_parser("parse_assignment_statement")
+        .build();
+
+    document(parser, doc)
+}
+
 /// Returns a documented version of the block statement parser
 pub fn documented_parse_block_statement() -> impl DocParserExt<Token, ast::Statement> {
     // We'll use the public parse_statement function and filter for block statements
@@ -155,6 +173,24 @@ pub fn documented_parse_if_statement() -> impl DocParserExt<Token, ast::Statemen
     document(parser, doc)
 }
 
+/// Returns a documented version of the retry statement parser
+pub fn documented_parse_retry_statement() -> impl DocParserExt<Token, ast::Statement> {
+    // We'll use the public parse_statement function and filter for retry statements
+    let parser = filter_parser(parse_statement(), |stmt| {
+        matches!(stmt, ast::Statement::Retry { .. })
+    });
+
+    let doc = DocBuilder::new("parse_retry_statement", ParserCategory::Statement)
+        .description("Retry statements run their block again when it fails, up to `times` attempts. With a `backoff`, they wait that long before the second attempt and twice as long before each next one. The current attempt, counting from 1, is available as `retry.attempt`. When every attempt fails, the last error goes to the `onFail` handler of the statement.")
+        .example("retry(times: 3) { think(\"Suggest a hotel\") }")
+        .example("retry(times: 3, backoff: 2s) { fetchForecast(city) } onFail(err) { return Err(err) }")
+        .related_parser("parse_statement")
+        .related_parser("parse_error_handler")
+        .build();
+
+    document(parser, doc)
+}
+
 /// Returns a documented version of the expression statement parser
 pub fn documented_parse_expression_statement() -> impl DocParserExt<Token, ast::Statement> {
     // We'll use the public parse_statement function and filter for expression statements
@@ -257,8 +293,10 @@ impl DocumentationProvider for StatementDocProvider {
         vec![
             as_any_doc_parser(documented_parse_statement()),
             as_any_doc_parser(documented_parse_assignment_statement()),
+            as_any_doc_parser(documented_parse_let_statement()),
             as_any_doc_parser(documented_parse_block_statement()),
             as_any_doc_parser(documented_parse_if_statement()),
+            as_any_doc_parser(documented_parse_retry_statement()),
             as_any_doc_parser(documented_parse_expression_statement()),
             as_any_doc_parser(documented_parse_return_statement()),
             as_any_doc_parser(documented_parse_error_handler()),
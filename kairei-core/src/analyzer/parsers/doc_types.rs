@@ -120,6 +120,23 @@ pub fn documented_parse_array_type() -> impl DocParserExt<Token, ast::TypeInfo>
     document(parser, doc)
 }
 
+/// Returns a documented version of the set type parser
+pub fn documented_parse_set_type() -> impl DocParserExt<Token, ast::TypeInfo> {
+    // Since parse_set_type is private, we'll use parse_type_info and filter for set types
+    let parser = filter_parser(parse_type_info(), |type_info| {
+        matches!(type_info, ast::TypeInfo::Set(_))
+    });
+
+    let doc = DocBuilder::new("parse_set_type", ParserCategory::Type)
+        .description("Set types represent collections of distinct elements, always kept in ascending order. Elements must be Boolean, Int or String.")
+        .example("Set{String}")
+        .example("Set{Int}")
+        .related_parser("parse_type_info")
+        .build();
+
+    document(parser, doc)
+}
+
 /// Returns a documented version of the result type parser
 pub fn documented_parse_result_type() -> impl DocParserExt<Token, ast::TypeInfo> {
     let parser = parse_result_type();
@@ -135,6 +152,37 @@ pub fn documented_parse_result_type() -> impl DocParserExt<Token, ast::TypeInfo>
     document(parser, doc)
 }
 
+/// Returns a documented version of the generic type parser
+pub fn documented_parse_generic_type() -> impl DocParserExt<Token, ast::TypeInfo> {
+    // Since parse_generic_type is private, we'll use parse_type_info and filter for generic types
+    let parser = filter_parser(parse_type_info(), |type_info| {
+        matches!(type_info, ast::TypeInfo::Generic { .. })
+    });
+
+    let doc = DocBuilder::new("parse_generic_type", ParserCategory::Type)
+        .description("Generic types apply a type declared with type parameters to type arguments. The number of arguments must match the declaration.")
+        .example("Pair<Int, String>")
+        .example("Page<Array{User}>")
+        .related_parser("parse_type_def")
+        .build();
+
+    document(parser, doc)
+}
+
+/// Returns a documented version of the type definition parser
+pub fn documented_parse_type_def() -> impl DocParserExt<Token, ast::TypeDef> {
+    let parser = parse_type_def();
+
+    let doc = DocBuilder::new("parse_type_def", ParserCategory::Type)
+        .description("Type definitions declare a named record type at the top of a file, optionally generic over type parameters used in its fields.")
+        .example("type Point { x: Float, y: Float }")
+        .example("type Pair<A, B> { first: A, second: B }")
+        .related_parser("parse_generic_type")
+        .build();
+
+    document(parser, doc)
+}
+
 /// Returns a documented version of the simple type parser
 pub fn documented_parse_simple_type() -> impl DocParserExt<Token, ast::TypeInfo> {
     // Since parse_simple_type is private, we'll use parse_type_info and filter for simple types
@@ -232,7 +280,10 @@ impl DocumentationProvider for TypeDocProvider {
             as_any_doc_parser(documented_parse_custom_type()),
             as_any_doc_parser(documented_parse_option_type()),
             as_any_doc_parser(documented_parse_array_type()),
+            as_any_doc_parser(documented_parse_set_type()),
             as_any_doc_parser(documented_parse_result_type()),
+            as_any_doc_parser(documented_parse_generic_type()),
+            as_any_doc_parser(documented_parse_type_def()),
             as_any_doc_parser(documented_parse_simple_type()),
             as_any_doc_parser(documented_parse_field()),
             as_any_doc_parser(documented_parse_field_typed_with_default()),
@@ -1,10 +1,15 @@
 use tracing::warn;
 
 use super::{
-    super::{core::*, prelude::*},
+    super::{
+        combinators::{Associativity, OperatorTable},
+        core::*,
+        prelude::*,
+    },
     *,
 };
 use crate::ast;
+use crate::eval::context::{AGENT_CONFIG_VARIABLE, REQUEST_METADATA_VARIABLE};
 use crate::tokenizer::{keyword::Keyword, symbol::Operator, token::Token};
 
 // Import will action parser
@@ -16,229 +21,147 @@ pub fn parse_expression() -> impl Parser<Token, ast::Expression> {
     with_context(lazy(parse_binary_expression), "expression")
 }
 
-pub fn parse_binary_expression() -> impl Parser<Token, ast::Expression> {
-    with_context(parse_logical_or(), "binary expression")
-}
-
-fn parse_logical_or() -> impl Parser<Token, ast::Expression> {
-    with_context(
-        map(
-            tuple2(
-                parse_logical_and(),
-                many(tuple2(parse_operator_or(), parse_logical_and())),
-            ),
-            |(first, rest)| {
-                rest.into_iter()
-                    .fold(first, |left, (op, right)| ast::Expression::BinaryOp {
-                        op,
-                        left: Box::new(left),
-                        right: Box::new(right),
-                    })
-            },
-        ),
-        "logical or",
-    )
-}
-
-fn parse_logical_and() -> impl Parser<Token, ast::Expression> {
-    with_context(
-        map(
-            tuple2(
-                parse_comparison(),
-                many(tuple2(parse_operator_and(), parse_comparison())),
-            ),
-            |(first, rest)| {
-                rest.into_iter()
-                    .fold(first, |left, (op, right)| ast::Expression::BinaryOp {
-                        op,
-                        left: Box::new(left),
-                        right: Box::new(right),
-                    })
-            },
-        ),
-        "logical and",
-    )
-}
-
-fn parse_comparison() -> impl Parser<Token, ast::Expression> {
-    with_context(
-        map(
-            tuple2(
-                parse_additive(),
-                many(tuple2(parse_operator_comparison(), parse_additive())),
-            ),
-            |(first, rest)| {
-                rest.into_iter()
-                    .fold(first, |left, (op, right)| ast::Expression::BinaryOp {
-                        op,
-                        left: Box::new(left),
-                        right: Box::new(right),
-                    })
-            },
-        ),
-        "comparison",
-    )
-}
-
-fn parse_operator_comparison() -> impl Parser<Token, ast::BinaryOperator> {
-    with_context(
-        choice(vec![
-            Box::new(parse_comparison_equal()),
-            Box::new(parse_comparison_not_equal()),
-            Box::new(parse_comparison_greater()),
-            Box::new(parse_comparison_greater_equal()),
-            Box::new(parse_comparison_less()),
-            Box::new(parse_comparison_less_equal()),
-        ]),
-        "comparison operator",
-    )
-}
-
-fn parse_operator_or() -> impl Parser<Token, ast::BinaryOperator> {
-    map(equal(Token::Operator(Operator::Or)), |_| {
-        ast::BinaryOperator::Or
-    })
-}
-
-fn parse_operator_and() -> impl Parser<Token, ast::BinaryOperator> {
-    map(equal(Token::Operator(Operator::And)), |_| {
-        ast::BinaryOperator::And
-    })
-}
-
-fn parse_comparison_equal() -> impl Parser<Token, ast::BinaryOperator> {
-    map(equal(Token::Operator(Operator::EqualEqual)), |_| {
-        ast::BinaryOperator::Equal
-    })
-}
-
-fn parse_comparison_not_equal() -> impl Parser<Token, ast::BinaryOperator> {
-    map(equal(Token::Operator(Operator::NotEqual)), |_| {
-        ast::BinaryOperator::NotEqual
-    })
-}
-
-fn parse_comparison_greater() -> impl Parser<Token, ast::BinaryOperator> {
-    map(equal(Token::Operator(Operator::Greater)), |_| {
-        ast::BinaryOperator::GreaterThan
-    })
-}
-
-fn parse_comparison_greater_equal() -> impl Parser<Token, ast::BinaryOperator> {
-    map(equal(Token::Operator(Operator::GreaterEqual)), |_| {
-        ast::BinaryOperator::GreaterThanEqual
-    })
-}
-
-fn parse_comparison_less() -> impl Parser<Token, ast::BinaryOperator> {
-    map(equal(Token::Operator(Operator::Less)), |_| {
-        ast::BinaryOperator::LessThan
-    })
-}
+// 二項演算子の優先順位。値が大きいほど強く結合する
+const PRECEDENCE_OR: u8 = 1;
+const PRECEDENCE_AND: u8 = 2;
+const PRECEDENCE_COMPARISON: u8 = 3;
+const PRECEDENCE_ADDITIVE: u8 = 4;
+const PRECEDENCE_MULTIPLICATIVE: u8 = 5;
 
-fn parse_comparison_less_equal() -> impl Parser<Token, ast::BinaryOperator> {
-    map(equal(Token::Operator(Operator::LessEqual)), |_| {
-        ast::BinaryOperator::LessThanEqual
-    })
+/// Binary and unary expressions of the DSL, over [`experimental_operator_table`] with the
+/// `experimental-operators` feature and [`default_operator_table`] otherwise.
+pub fn parse_binary_expression() -> impl Parser<Token, ast::Expression> {
+    if cfg!(feature = "experimental-operators") {
+        parse_expression_with(experimental_operator_table())
+    } else {
+        parse_expression_with(default_operator_table())
+    }
 }
 
-fn parse_additive() -> impl Parser<Token, ast::Expression> {
-    with_context(
-        map(
-            tuple2(
-                parse_multiplicative(),
-                many(tuple2(
-                    choice(vec![
-                        Box::new(parse_operator_add()),
-                        Box::new(parse_operator_subtract()),
-                    ]),
-                    parse_multiplicative(),
-                )),
-            ),
-            |(first, rest)| {
-                rest.into_iter()
-                    .fold(first, |left, (op, right)| ast::Expression::BinaryOp {
-                        op,
-                        left: Box::new(left),
-                        right: Box::new(right),
-                    })
-            },
-        ),
-        "additive",
-    )
+/// Binary and unary expressions over the operators of `table`.
+/// Expressions nested in a primary (arguments, think parameters, ...) use the default table.
+pub fn parse_expression_with(
+    table: OperatorTable<Token, ast::Expression>,
+) -> impl Parser<Token, ast::Expression> {
+    with_context(pratt(parse_primary(), table), "binary expression")
 }
 
-// 乗除算 (*, /)
-fn parse_multiplicative() -> impl Parser<Token, ast::Expression> {
-    with_context(
-        map(
-            tuple2(
-                parse_unary(),
-                many(tuple2(
-                    choice(vec![
-                        Box::new(parse_operator_multiply()),
-                        Box::new(parse_operator_divide()),
-                    ]),
-                    parse_unary(),
-                )),
-            ),
-            |(first, rest)| {
-                rest.into_iter()
-                    .fold(first, |left, (op, right)| ast::Expression::BinaryOp {
-                        op,
-                        left: Box::new(left),
-                        right: Box::new(right),
-                    })
-            },
-        ),
-        "multiplicative",
-    )
-}
-
-fn parse_unary() -> impl Parser<Token, ast::Expression> {
-    with_context(
-        choice(vec![
-            Box::new(map(
-                tuple2(parse_operator_not(), parse_primary()),
-                |(op, expr)| ast::Expression::BinaryOp {
-                    op,
-                    left: Box::new(expr),
-                    right: Box::new(ast::Expression::Literal(ast::Literal::String(
-                        "OPERATOR_NOT".to_string(),
-                    ))),
-                },
-            )),
-            Box::new(map(
-                tuple2(parse_operator_minus(), parse_primary()),
-                |(op, expr)| ast::Expression::BinaryOp {
-                    op,
-                    left: Box::new(expr),
-                    right: Box::new(ast::Expression::Literal(ast::Literal::String(
-                        "OPERATOR_MINUS".to_string(),
-                    ))),
-                },
-            )),
-            Box::new(parse_primary()),
-        ]),
-        "unary",
-    )
+fn binary(
+    op: ast::BinaryOperator,
+) -> impl Fn(ast::Expression, ast::Expression) -> ast::Expression + 'static {
+    move |left, right| ast::Expression::BinaryOp {
+        op: op.clone(),
+        left: Box::new(left),
+        right: Box::new(right),
+    }
 }
 
-fn parse_operator_not() -> impl Parser<Token, ast::BinaryOperator> {
-    with_context(
-        map(equal(Token::Operator(Operator::Not)), |_| {
-            ast::BinaryOperator::NotEqual
-        }),
-        "not operator",
-    )
+/// The operators of the DSL: `||`, `&&`, comparisons, `+ -`, `* /`, all left associative,
+/// and the prefix `!` and `-`.
+pub fn default_operator_table() -> OperatorTable<Token, ast::Expression> {
+    // 単項演算子は専用の AST がないため、目印の文字列を右辺にした二項演算で表す
+    let unary = |op: ast::BinaryOperator, marker: &'static str| {
+        move |operand: ast::Expression| ast::Expression::BinaryOp {
+            op: op.clone(),
+            left: Box::new(operand),
+            right: Box::new(ast::Expression::Literal(ast::Literal::String(
+                marker.to_string(),
+            ))),
+        }
+    };
+    let left = Associativity::Left;
+    OperatorTable::new()
+        .with_infix(
+            Token::Operator(Operator::Or),
+            PRECEDENCE_OR,
+            left,
+            binary(ast::BinaryOperator::Or),
+        )
+        .with_infix(
+            Token::Operator(Operator::And),
+            PRECEDENCE_AND,
+            left,
+            binary(ast::BinaryOperator::And),
+        )
+        .with_infix(
+            Token::Operator(Operator::EqualEqual),
+            PRECEDENCE_COMPARISON,
+            left,
+            binary(ast::BinaryOperator::Equal),
+        )
+        .with_infix(
+            Token::Operator(Operator::NotEqual),
+            PRECEDENCE_COMPARISON,
+            left,
+            binary(ast::BinaryOperator::NotEqual),
+        )
+        .with_infix(
+            Token::Operator(Operator::Greater),
+            PRECEDENCE_COMPARISON,
+            left,
+            binary(ast::BinaryOperator::GreaterThan),
+        )
+        .with_infix(
+            Token::Operator(Operator::GreaterEqual),
+            PRECEDENCE_COMPARISON,
+            left,
+            binary(ast::BinaryOperator::GreaterThanEqual),
+        )
+        .with_infix(
+            Token::Operator(Operator::Less),
+            PRECEDENCE_COMPARISON,
+            left,
+            binary(ast::BinaryOperator::LessThan),
+        )
+        .with_infix(
+            Token::Operator(Operator::LessEqual),
+            PRECEDENCE_COMPARISON,
+            left,
+            binary(ast::BinaryOperator::LessThanEqual),
+        )
+        .with_infix(
+            Token::Operator(Operator::Plus),
+            PRECEDENCE_ADDITIVE,
+            left,
+            binary(ast::BinaryOperator::Add),
+        )
+        .with_infix(
+            Token::Operator(Operator::Minus),
+            PRECEDENCE_ADDITIVE,
+            left,
+            binary(ast::BinaryOperator::Subtract),
+        )
+        .with_infix(
+            Token::Operator(Operator::Multiply),
+            PRECEDENCE_MULTIPLICATIVE,
+            left,
+            binary(ast::BinaryOperator::Multiply),
+        )
+        .with_infix(
+            Token::Operator(Operator::Divide),
+            PRECEDENCE_MULTIPLICATIVE,
+            left,
+            binary(ast::BinaryOperator::Divide),
+        )
+        .with_prefix(
+            Token::Operator(Operator::Not),
+            unary(ast::BinaryOperator::NotEqual, "OPERATOR_NOT"),
+        )
+        .with_prefix(
+            Token::Operator(Operator::Minus),
+            unary(ast::BinaryOperator::Subtract, "OPERATOR_MINUS"),
+        )
 }
 
-fn parse_operator_minus() -> impl Parser<Token, ast::BinaryOperator> {
-    with_context(
-        map(equal(Token::Operator(Operator::Minus)), |_| {
-            ast::BinaryOperator::Subtract
-        }),
-        "minus operator",
+/// [`default_operator_table`] and the operators whose syntax may still change: the
+/// remainder `%`, as tight as `*` and `/`. Used for the DSL with the
+/// `experimental-operators` feature.
+pub fn experimental_operator_table() -> OperatorTable<Token, ast::Expression> {
+    default_operator_table().with_infix(
+        Token::Operator(Operator::Percent),
+        PRECEDENCE_MULTIPLICATIVE,
+        Associativity::Left,
+        binary(ast::BinaryOperator::Modulo),
     )
 }
 
@@ -250,8 +173,10 @@ fn parse_primary() -> impl Parser<Token, ast::Expression> {
             Box::new(parse_think()),
             Box::new(parse_function_call()),
             Box::new(map(parse_literal(), ast::Expression::Literal)),
-            Box::new(map(parse_identifier(), ast::Expression::Variable)),
-            Box::new(map(parse_state_access(), ast::Expression::StateAccess)),
+            Box::new(map(parse_state_access(), |path| match path.0.as_slice() {
+                [name] => ast::Expression::Variable(name.clone()),
+                _ => ast::Expression::StateAccess(path),
+            })),
             Box::new(parse_request()),
             Box::new(parse_await()),
             Box::new(will::parse_will_action()),
@@ -260,13 +185,31 @@ fn parse_primary() -> impl Parser<Token, ast::Expression> {
     )
 }
 
+/// `user.profile.name` のようなドット区切りのパス。
+/// answer ハンドラの暗黙の `request` とエージェントの `config` もパスの先頭に使える
+/// (`request.requester`, `config.region`)。
 fn parse_state_access() -> impl Parser<Token, ast::StateAccessPath> {
     with_context(
         map(
-            tuple2(
-                parse_identifier(),
-                many(preceded(as_unit(parse_dot()), parse_identifier())),
-            ),
+            choice(vec![
+                Box::new(tuple2(
+                    parse_identifier(),
+                    many(preceded(as_unit(parse_dot()), parse_identifier())),
+                )),
+                // `request Foo to Bar()` と区別するため、request はフィールド参照がある場合のみ
+                Box::new(tuple2(
+                    map(parse_request_keyword(), |_| {
+                        REQUEST_METADATA_VARIABLE.to_string()
+                    }),
+                    many1(preceded(as_unit(parse_dot()), parse_identifier())),
+                )),
+                Box::new(tuple2(
+                    map(world::parse_config_keyword(), |_| {
+                        AGENT_CONFIG_VARIABLE.to_string()
+                    }),
+                    many1(preceded(as_unit(parse_dot()), parse_identifier())),
+                )),
+            ]),
             |(first, rest)| {
                 ast::StateAccessPath(
                     std::iter::once(first)
@@ -453,7 +396,9 @@ fn collect_with_settings(settings: Vec<ThinkAttributeKV>) -> ast::ThinkAttribute
         provider: None,
         model: None,
         temperature: None,
+        top_p: None,
         max_tokens: None,
+        stream: false,
         retry: None,
         policies: vec![],
         prompt_generator_type: None,
@@ -462,11 +407,22 @@ fn collect_with_settings(settings: Vec<ThinkAttributeKV>) -> ast::ThinkAttribute
 
     for setting in settings {
         match (setting.key.as_str(), setting.value) {
-            ("provider", ast::Literal::String(s)) => block.provider = Some(s),
-            ("model", ast::Literal::String(s)) => block.model = Some(s),
+            ("provider", ast::Literal::String(s) | ast::Literal::RawString { value: s, .. }) => {
+                block.provider = Some(s)
+            }
+            ("model", ast::Literal::String(s) | ast::Literal::RawString { value: s, .. }) => {
+                block.model = Some(s)
+            }
             ("temperature", ast::Literal::Float(f)) => block.temperature = Some(f),
+            ("temperature", ast::Literal::Integer(n)) => block.temperature = Some(n as f64),
+            ("top_p", ast::Literal::Float(f)) => block.top_p = Some(f),
+            ("top_p", ast::Literal::Integer(n)) => block.top_p = Some(n as f64),
             ("retry", ast::Literal::Retry(r)) => block.retry = Some(r),
-            ("max_tokens", ast::Literal::Integer(n)) => block.max_tokens = Some(n as u32),
+            ("stream", ast::Literal::Boolean(stream)) => block.stream = stream,
+            // 負の値は 0 にして型チェックで弾く
+            ("max_tokens", ast::Literal::Integer(n)) => {
+                block.max_tokens = Some(u32::try_from(n).unwrap_or(0))
+            }
             ("policies", ast::Literal::List(policies)) => {
                 for policy in policies {
                     if let ast::Literal::String(text) = policy {
@@ -618,42 +574,6 @@ fn parse_await_keyword() -> impl Parser<Token, Token> {
     with_context(equal(Token::Keyword(Keyword::Await)), "await")
 }
 
-fn parse_operator_add() -> impl Parser<Token, ast::BinaryOperator> {
-    with_context(
-        map(equal(Token::Operator(Operator::Plus)), |_| {
-            ast::BinaryOperator::Add
-        }),
-        "add operator",
-    )
-}
-
-fn parse_operator_subtract() -> impl Parser<Token, ast::BinaryOperator> {
-    with_context(
-        map(equal(Token::Operator(Operator::Minus)), |_| {
-            ast::BinaryOperator::Subtract
-        }),
-        "subtract operator",
-    )
-}
-
-fn parse_operator_multiply() -> impl Parser<Token, ast::BinaryOperator> {
-    with_context(
-        map(equal(Token::Operator(Operator::Multiply)), |_| {
-            ast::BinaryOperator::Multiply
-        }),
-        "multiply operator",
-    )
-}
-
-fn parse_operator_divide() -> impl Parser<Token, ast::BinaryOperator> {
-    with_context(
-        map(equal(Token::Operator(Operator::Divide)), |_| {
-            ast::BinaryOperator::Divide
-        }),
-        "divide operator",
-    )
-}
-
 pub fn parse_literal_expression() -> impl Parser<Token, ast::Expression> {
     with_context(
         map(parse_literal(), ast::Expression::Literal),
@@ -668,6 +588,24 @@ mod tests {
 
     use super::*;
 
+    // 旧来の優先順位ごとのパーサーに相当する、最低優先順位を指定した Pratt パーサー
+    fn parse_comparison() -> impl Parser<Token, ast::Expression> {
+        pratt(parse_primary(), default_operator_table()).with_min_precedence(PRECEDENCE_COMPARISON)
+    }
+
+    fn parse_additive() -> impl Parser<Token, ast::Expression> {
+        pratt(parse_primary(), default_operator_table()).with_min_precedence(PRECEDENCE_ADDITIVE)
+    }
+
+    fn parse_multiplicative() -> impl Parser<Token, ast::Expression> {
+        pratt(parse_primary(), default_operator_table())
+            .with_min_precedence(PRECEDENCE_MULTIPLICATIVE)
+    }
+
+    fn parse_unary() -> impl Parser<Token, ast::Expression> {
+        pratt(parse_primary(), default_operator_table()).with_min_precedence(u8::MAX)
+    }
+
     #[test]
     fn test_parse_binary_expression_logical() {
         // a && b || c のテスト
@@ -823,6 +761,30 @@ mod tests {
         assert_eq!(path.0, vec!["state", "user", "name"]);
     }
 
+    #[test]
+    fn test_parse_primary_dotted_access() {
+        // 単一識別子は変数、ドット区切りはパス
+        let input = &[Token::Identifier("name".to_string())];
+        let (_, expr) = parse_primary().parse(input, 0).unwrap();
+        assert_eq!(expr, ast::Expression::Variable("name".to_string()));
+
+        // 暗黙の request のフィールド参照
+        let input = &[
+            Token::Keyword(Keyword::Request),
+            Token::Operator(Operator::Dot),
+            Token::Identifier("requester".to_string()),
+        ];
+        let (pos, expr) = parse_primary().parse(input, 0).unwrap();
+        assert_eq!(pos, 3);
+        assert_eq!(
+            expr,
+            ast::Expression::StateAccess(ast::StateAccessPath(vec![
+                "request".to_string(),
+                "requester".to_string()
+            ]))
+        );
+    }
+
     #[test]
     fn test_parse_ok_err() {
         // OKのテスト
@@ -1073,23 +1035,86 @@ mod tests {
     }
 
     #[test]
-    fn test_parse_operators() {
-        // 論理演算子
-        let input = &[Token::Operator(Operator::Or)];
-        let (pos, op) = parse_operator_or().parse(input, 0).unwrap();
-        assert_eq!(pos, 1);
-        assert_eq!(op, ast::BinaryOperator::Or);
+    fn test_default_operator_table() {
+        let operand = |name: &str| ast::Expression::Variable(name.to_string());
+        let infix_tests = vec![
+            (Operator::Or, ast::BinaryOperator::Or),
+            (Operator::And, ast::BinaryOperator::And),
+            (Operator::EqualEqual, ast::BinaryOperator::Equal),
+            (Operator::NotEqual, ast::BinaryOperator::NotEqual),
+            (Operator::Greater, ast::BinaryOperator::GreaterThan),
+            (
+                Operator::GreaterEqual,
+                ast::BinaryOperator::GreaterThanEqual,
+            ),
+            (Operator::Less, ast::BinaryOperator::LessThan),
+            (Operator::LessEqual, ast::BinaryOperator::LessThanEqual),
+            (Operator::Plus, ast::BinaryOperator::Add),
+            (Operator::Minus, ast::BinaryOperator::Subtract),
+            (Operator::Multiply, ast::BinaryOperator::Multiply),
+            (Operator::Divide, ast::BinaryOperator::Divide),
+        ];
+        for (operator, expected_op) in infix_tests {
+            let input = &[
+                Token::Identifier("a".to_string()),
+                Token::Operator(operator.clone()),
+                Token::Identifier("b".to_string()),
+            ];
+            assert_eq!(
+                parse_binary_expression().parse(input, 0),
+                Ok((
+                    3,
+                    ast::Expression::BinaryOp {
+                        op: expected_op,
+                        left: Box::new(operand("a")),
+                        right: Box::new(operand("b")),
+                    }
+                )),
+                "Failed to parse {:?}",
+                operator
+            );
+        }
 
-        let input = &[Token::Operator(Operator::And)];
-        let (pos, op) = parse_operator_and().parse(input, 0).unwrap();
-        assert_eq!(pos, 1);
-        assert_eq!(op, ast::BinaryOperator::And);
+        // 中置演算子でないトークンの手前で止まる
+        let input = &[
+            Token::Identifier("a".to_string()),
+            Token::Operator(Operator::Not),
+            Token::Identifier("b".to_string()),
+        ];
+        assert_eq!(
+            parse_binary_expression().parse(input, 0),
+            Ok((1, operand("a")))
+        );
 
-        // 比較演算子
-        let input = &[Token::Operator(Operator::EqualEqual)];
-        let (pos, op) = parse_comparison_equal().parse(input, 0).unwrap();
-        assert_eq!(pos, 1);
-        assert_eq!(op, ast::BinaryOperator::Equal);
+        let prefix_tests = vec![
+            (Operator::Not, ast::BinaryOperator::NotEqual, "OPERATOR_NOT"),
+            (
+                Operator::Minus,
+                ast::BinaryOperator::Subtract,
+                "OPERATOR_MINUS",
+            ),
+        ];
+        for (operator, expected_op, marker) in prefix_tests {
+            let input = &[
+                Token::Operator(operator.clone()),
+                Token::Identifier("a".to_string()),
+            ];
+            assert_eq!(
+                parse_binary_expression().parse(input, 0),
+                Ok((
+                    2,
+                    ast::Expression::BinaryOp {
+                        op: expected_op,
+                        left: Box::new(operand("a")),
+                        right: Box::new(ast::Expression::Literal(ast::Literal::String(
+                            marker.to_string()
+                        ))),
+                    }
+                )),
+                "Failed to parse {:?}",
+                operator
+            );
+        }
     }
 
     #[test]
@@ -1117,63 +1142,6 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_parse_operator_comparison() {
-        // すべての比較演算子をテスト
-        let comparison_tests = vec![
-            (
-                Token::Operator(Operator::EqualEqual),
-                ast::BinaryOperator::Equal,
-            ),
-            (
-                Token::Operator(Operator::NotEqual),
-                ast::BinaryOperator::NotEqual,
-            ),
-            (
-                Token::Operator(Operator::Greater),
-                ast::BinaryOperator::GreaterThan,
-            ),
-            (
-                Token::Operator(Operator::GreaterEqual),
-                ast::BinaryOperator::GreaterThanEqual,
-            ),
-            (
-                Token::Operator(Operator::Less),
-                ast::BinaryOperator::LessThan,
-            ),
-            (
-                Token::Operator(Operator::LessEqual),
-                ast::BinaryOperator::LessThanEqual,
-            ),
-        ];
-
-        for (token, expected_op) in comparison_tests {
-            let input = &[token.clone()];
-            let result = parse_operator_comparison().parse(input, 0);
-            assert!(result.is_ok(), "Failed to parse {:?}", token);
-            let (pos, op) = result.unwrap();
-            assert_eq!(pos, 1);
-            assert_eq!(op, expected_op);
-        }
-
-        // 非比較演算子はパースに失敗することを確認
-        let non_comparison_tests = vec![
-            Token::Operator(Operator::Plus),
-            Token::Operator(Operator::Minus),
-            Token::Operator(Operator::Multiply),
-            Token::Operator(Operator::Divide),
-            Token::Operator(Operator::And),
-            Token::Operator(Operator::Or),
-            Token::Operator(Operator::Not),
-        ];
-
-        for token in non_comparison_tests {
-            let input = &[token.clone()];
-            let result = parse_operator_comparison().parse(input, 0);
-            assert!(result.is_err(), "Should not parse {:?}", token);
-        }
-    }
-
     #[test]
     fn test_parse_additive() {
         let input = &[
@@ -1250,44 +1218,6 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_parse_operator_add() {
-        let input = &[Token::Operator(Operator::Plus)];
-        let (pos, op) = parse_operator_add().parse(input, 0).unwrap();
-        assert_eq!(pos, 1);
-        assert_eq!(op, ast::BinaryOperator::Add);
-    }
-
-    #[test]
-    fn test_parse_operator_add_fails() {
-        let input = &[Token::Operator(Operator::Minus)];
-        assert!(parse_operator_add().parse(input, 0).is_err());
-    }
-
-    #[test]
-    fn test_parse_operator_subtract() {
-        let input = &[Token::Operator(Operator::Minus)];
-        let (pos, op) = parse_operator_subtract().parse(input, 0).unwrap();
-        assert_eq!(pos, 1);
-        assert_eq!(op, ast::BinaryOperator::Subtract);
-    }
-
-    #[test]
-    fn test_parse_operator_multiply() {
-        let input = &[Token::Operator(Operator::Multiply)];
-        let (pos, op) = parse_operator_multiply().parse(input, 0).unwrap();
-        assert_eq!(pos, 1);
-        assert_eq!(op, ast::BinaryOperator::Multiply);
-    }
-
-    #[test]
-    fn test_parse_operator_divide() {
-        let input = &[Token::Operator(Operator::Divide)];
-        let (pos, op) = parse_operator_divide().parse(input, 0).unwrap();
-        assert_eq!(pos, 1);
-        assert_eq!(op, ast::BinaryOperator::Divide);
-    }
-
     #[test]
     fn test_parse_unary() {
         let input = &[
@@ -1341,22 +1271,6 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_parse_operator_not() {
-        let input = &[Token::Operator(Operator::Not)];
-        let (pos, op) = parse_operator_not().parse(input, 0).unwrap();
-        assert_eq!(pos, 1);
-        assert_eq!(op, ast::BinaryOperator::NotEqual);
-    }
-
-    #[test]
-    fn test_parse_operator_minus() {
-        let input = &[Token::Operator(Operator::Minus)];
-        let (pos, op) = parse_operator_minus().parse(input, 0).unwrap();
-        assert_eq!(pos, 1);
-        assert_eq!(op, ast::BinaryOperator::Subtract);
-    }
-
     #[test]
     fn test_parse_think() {
         // 基本的なthink式（with blockなし）
@@ -1631,6 +1545,32 @@ mod tests {
         assert_eq!(block.plugins.len(), 1);
     }
 
+    #[test]
+    fn test_collect_sampling_settings() {
+        let settings = vec![
+            ThinkAttributeKV {
+                key: "temperature".to_string(),
+                value: ast::Literal::Integer(1),
+            },
+            ThinkAttributeKV {
+                key: "top_p".to_string(),
+                value: ast::Literal::Float(0.9),
+            },
+            ThinkAttributeKV {
+                key: "max_tokens".to_string(),
+                value: ast::Literal::Integer(-1),
+            },
+        ];
+
+        let block = collect_with_settings(settings);
+
+        assert_eq!(block.temperature, Some(1.0));
+        assert_eq!(block.top_p, Some(0.9));
+        // 負の値は型チェックで弾けるよう 0 になる
+        assert_eq!(block.max_tokens, Some(0));
+        assert!(block.plugins.is_empty());
+    }
+
     #[test]
     fn test_parse_with_keyword() {
         let input = &[
@@ -1734,4 +1674,305 @@ mod tests {
             _ => panic!("Expected named argument"),
         }
     }
+
+    fn tokens(dsl: &str) -> Vec<Token> {
+        use crate::preprocessor::{Preprocessor, TokenPreprocessor};
+        let token_spans = crate::tokenizer::token::Tokenizer::new()
+            .tokenize(dsl)
+            .unwrap();
+        TokenPreprocessor::default()
+            .process(token_spans)
+            .into_iter()
+            .map(|span| span.token)
+            .collect()
+    }
+
+    // 演算子の結合を括弧で表す。単項演算子は前置で表す
+    fn grouping(expr: &ast::Expression) -> String {
+        match expr {
+            ast::Expression::BinaryOp { op, left, right } => match right.as_ref() {
+                ast::Expression::Literal(ast::Literal::String(marker))
+                    if marker == "OPERATOR_NOT" =>
+                {
+                    format!("!{}", grouping(left))
+                }
+                ast::Expression::Literal(ast::Literal::String(marker))
+                    if marker == "OPERATOR_MINUS" =>
+                {
+                    format!("-{}", grouping(left))
+                }
+                _ => format!("({} {:?} {})", grouping(left), op, grouping(right)),
+            },
+            ast::Expression::Variable(name) => name.clone(),
+            other => format!("{:?}", other),
+        }
+    }
+
+    fn parse_grouping(dsl: &str) -> (usize, String) {
+        let (pos, expr) = parse_binary_expression().parse(&tokens(dsl), 0).unwrap();
+        (pos, grouping(&expr))
+    }
+
+    #[test]
+    fn test_binary_expression_associativity() {
+        assert_eq!(
+            parse_grouping("a - b - c"),
+            (5, "((a Subtract b) Subtract c)".to_string())
+        );
+        assert_eq!(
+            parse_grouping("a / b * c"),
+            (5, "((a Divide b) Multiply c)".to_string())
+        );
+        assert_eq!(
+            parse_grouping("a == b != c"),
+            (5, "((a Equal b) NotEqual c)".to_string())
+        );
+        assert_eq!(
+            parse_grouping("a || b || c"),
+            (5, "((a Or b) Or c)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_binary_expression_precedence() {
+        assert_eq!(
+            parse_grouping("a || b && c == d + e * f"),
+            (
+                11,
+                "(a Or (b And (c Equal (d Add (e Multiply f)))))".to_string()
+            )
+        );
+        assert_eq!(
+            parse_grouping("a * b - c > d || e"),
+            (
+                9,
+                "((((a Multiply b) Subtract c) GreaterThan d) Or e)".to_string()
+            )
+        );
+        // 単項演算子と二項演算子の混在
+        assert_eq!(
+            parse_grouping("-a * b + !c == d"),
+            (9, "(((-a Multiply b) Add !c) Equal d)".to_string())
+        );
+        assert_eq!(parse_grouping("a - -b"), (4, "(a Subtract -b)".to_string()));
+        assert_eq!(parse_grouping("!a && !b"), (5, "(!a And !b)".to_string()));
+    }
+
+    #[test]
+    fn test_binary_expression_incomplete() {
+        // 右辺のない演算子は消費しない
+        assert_eq!(parse_grouping("a +"), (1, "a".to_string()));
+        assert_eq!(
+            parse_grouping("a * b + )"),
+            (3, "(a Multiply b)".to_string())
+        );
+        // 単項演算子は入れ子にならない
+        assert_eq!(
+            parse_binary_expression().parse(&tokens("- -a"), 0),
+            Err(ParseError::NoAlternative {
+                position: 0,
+                context: None
+            })
+        );
+        assert_eq!(parse_grouping("a - - - b"), (1, "a".to_string()));
+    }
+
+    #[test]
+    fn test_experimental_operators() {
+        let parse = |table| {
+            let (pos, expr) = parse_expression_with(table)
+                .parse(&tokens("a + b % c * d"), 0)
+                .unwrap();
+            (pos, grouping(&expr))
+        };
+        assert_eq!(
+            parse(default_operator_table()),
+            (3, "(a Add b)".to_string())
+        );
+        assert_eq!(
+            parse(experimental_operator_table()),
+            (7, "(a Add ((b Modulo c) Multiply d))".to_string())
+        );
+        // DSL で使えるのは experimental-operators フィーチャーを有効にしたときだけ
+        let expected = if cfg!(feature = "experimental-operators") {
+            3
+        } else {
+            1
+        };
+        assert_eq!(parse_grouping("a % b").0, expected);
+    }
+
+    /// 優先順位ごとの関数で組んでいた以前のパーサー。Pratt パーサーとの比較用
+    mod legacy {
+        use super::super::{
+            super::super::{core::*, prelude::*},
+            parse_primary,
+        };
+        use crate::ast;
+        use crate::tokenizer::{symbol::Operator, token::Token};
+
+        type Operation = (ast::BinaryOperator, ast::Expression);
+
+        fn fold(
+            operand: impl Parser<Token, ast::Expression>,
+            operation: impl Parser<Token, Operation>,
+        ) -> impl Parser<Token, ast::Expression> {
+            map(tuple2(operand, many(operation)), |(first, rest)| {
+                rest.into_iter()
+                    .fold(first, |left, (op, right)| ast::Expression::BinaryOp {
+                        op,
+                        left: Box::new(left),
+                        right: Box::new(right),
+                    })
+            })
+        }
+
+        fn operator(
+            token: Operator,
+            op: ast::BinaryOperator,
+        ) -> impl Parser<Token, ast::BinaryOperator> {
+            map(equal(Token::Operator(token)), move |_| op.clone())
+        }
+
+        pub fn parse_logical_or() -> impl Parser<Token, ast::Expression> {
+            fold(
+                parse_logical_and(),
+                tuple2(
+                    operator(Operator::Or, ast::BinaryOperator::Or),
+                    parse_logical_and(),
+                ),
+            )
+        }
+
+        fn parse_logical_and() -> impl Parser<Token, ast::Expression> {
+            fold(
+                parse_comparison(),
+                tuple2(
+                    operator(Operator::And, ast::BinaryOperator::And),
+                    parse_comparison(),
+                ),
+            )
+        }
+
+        fn parse_comparison() -> impl Parser<Token, ast::Expression> {
+            fold(
+                parse_additive(),
+                tuple2(
+                    choice(vec![
+                        Box::new(operator(Operator::EqualEqual, ast::BinaryOperator::Equal)),
+                        Box::new(operator(Operator::NotEqual, ast::BinaryOperator::NotEqual)),
+                        Box::new(operator(
+                            Operator::Greater,
+                            ast::BinaryOperator::GreaterThan,
+                        )),
+                        Box::new(operator(
+                            Operator::GreaterEqual,
+                            ast::BinaryOperator::GreaterThanEqual,
+                        )),
+                        Box::new(operator(Operator::Less, ast::BinaryOperator::LessThan)),
+                        Box::new(operator(
+                            Operator::LessEqual,
+                            ast::BinaryOperator::LessThanEqual,
+                        )),
+                    ]),
+                    parse_additive(),
+                ),
+            )
+        }
+
+        fn parse_additive() -> impl Parser<Token, ast::Expression> {
+            fold(
+                parse_multiplicative(),
+                tuple2(
+                    choice(vec![
+                        Box::new(operator(Operator::Plus, ast::BinaryOperator::Add)),
+                        Box::new(operator(Operator::Minus, ast::BinaryOperator::Subtract)),
+                    ]),
+                    parse_multiplicative(),
+                ),
+            )
+        }
+
+        fn parse_multiplicative() -> impl Parser<Token, ast::Expression> {
+            fold(
+                parse_unary(),
+                tuple2(
+                    choice(vec![
+                        Box::new(operator(Operator::Multiply, ast::BinaryOperator::Multiply)),
+                        Box::new(operator(Operator::Divide, ast::BinaryOperator::Divide)),
+                    ]),
+                    parse_unary(),
+                ),
+            )
+        }
+
+        fn parse_unary() -> impl Parser<Token, ast::Expression> {
+            let unary = |token: Operator, op: ast::BinaryOperator, marker: &'static str| {
+                Box::new(map(
+                    tuple2(operator(token, op), parse_primary()),
+                    move |(op, expr)| ast::Expression::BinaryOp {
+                        op,
+                        left: Box::new(expr),
+                        right: Box::new(ast::Expression::Literal(ast::Literal::String(
+                            marker.to_string(),
+                        ))),
+                    },
+                ))
+            };
+            choice(vec![
+                unary(Operator::Not, ast::BinaryOperator::NotEqual, "OPERATOR_NOT"),
+                unary(
+                    Operator::Minus,
+                    ast::BinaryOperator::Subtract,
+                    "OPERATOR_MINUS",
+                ),
+                Box::new(parse_primary()),
+            ])
+        }
+    }
+
+    #[test]
+    fn test_binary_expression_matches_legacy_parser() {
+        let pattern = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../kairei-http/dsl/**/*.kairei"
+        );
+        let mut sources: Vec<String> = glob::glob(pattern)
+            .unwrap()
+            .map(|path| std::fs::read_to_string(path.unwrap()).unwrap())
+            .collect();
+        assert!(!sources.is_empty(), "no DSL fixtures found for {}", pattern);
+        sources.extend(
+            [
+                "a - b - c * d / e + f",
+                "-a * b + !c == d && e != -f || g <= h",
+                "x > 1 && y >= 2 || z < 3 && w <= 4",
+                "count + 1 == limit - -1",
+                "!ready || - total / 2",
+                "a + (b - c) * foo(d, e + f) - state.value",
+                "a + - - b",
+                "a * * b",
+                "a || && b",
+                "1 + 2 +",
+                "think(\"x\") + await(request Foo to Bar())",
+            ]
+            .map(String::from),
+        );
+
+        let legacy = legacy::parse_logical_or();
+        let pratt = parse_binary_expression();
+        for source in &sources {
+            let tokens = tokens(source);
+            // 部分式を含め、すべての開始位置で同じ結果になること
+            for pos in 0..=tokens.len() {
+                assert_eq!(
+                    pratt.parse(&tokens, pos),
+                    legacy.parse(&tokens, pos),
+                    "diverged at token {} of {:?}",
+                    pos,
+                    source
+                );
+            }
+        }
+    }
 }
@@ -2,7 +2,7 @@ use super::super::{
     super::{core::*, prelude::*},
     *,
 };
-use crate::analyzer::parsers::handlers::parse_parameters;
+use crate::analyzer::parsers::handlers::{parse_parameters, parse_requires};
 use crate::ast;
 use crate::{
     analyzer::parsers::{
@@ -67,12 +67,14 @@ pub fn parse_answer() -> impl Parser<Token, ast::AnswerDef> {
 /// - Request type (query, action, or custom)
 /// - Parameters with types
 /// - Return type (must be Result)
+/// - Optional `@cache(...)` directive before the `on` keyword
+/// - Optional `requires` precondition
 /// - Optional quality constraints
 /// - Handler implementation block
 ///
 /// # Example
 /// ```text
-/// on request GetData(id: String) -> Result<Data> {
+/// on request GetData(id: String) -> Result<Data> requires self.ready {
 ///     with {
 ///         strictness: 0.9
 ///     }
@@ -82,38 +84,159 @@ pub fn parse_answer() -> impl Parser<Token, ast::AnswerDef> {
 pub fn parse_request_handler() -> impl Parser<Token, ast::RequestHandler> {
     with_context(
         map(
-            tuple6(
-                as_unit(parse_on_keyword()),
+            tuple4(
+                parse_doc_comment(),
+                optional(parse_cache_directive()),
+                tuple5(
+                    as_unit(parse_on_keyword()),
+                    parse_request_type(),
+                    parse_parameters(),
+                    preceded(as_unit(parse_arrow()), parse_type_info()),
+                    optional(parse_requires()),
+                ),
+                tuple2(optional(parse_constraints()), parse_statements()),
+            ),
+            |(
+                doc,
+                cache,
+                (_, request_type, parameters, return_type, requires),
+                (constraints, block),
+            )| {
+                ast::RequestHandler {
+                    request_type,
+                    parameters,
+                    return_type,
+                    requires,
+                    constraints,
+                    block: ast::HandlerBlock { statements: block },
+                    doc,
+                    cache,
+                }
+            },
+        ),
+        "request handler",
+    )
+}
+
+/// Cache Directive Parser
+///
+/// Parses the `@cache(...)` directive of an answer handler: how long a response
+/// is kept, then optionally the parameters the cache key is made of.
+///
+/// # Example
+/// ```text
+/// @cache(ttl: 10m, key: [city, days])
+/// ```
+pub fn parse_cache_directive() -> impl Parser<Token, ast::CacheDirective> {
+    with_context(
+        map(
+            preceded(
+                as_unit(tuple2(
+                    as_unit(parse_at()),
+                    expected(parse_identifier(), "cache".to_string()),
+                )),
+                delimited(
+                    as_unit(parse_open_paren()),
+                    tuple2(
+                        preceded(
+                            as_unit(tuple2(
+                                expected(parse_identifier(), "ttl".to_string()),
+                                as_unit(parse_colon()),
+                            )),
+                            parse_duration(),
+                        ),
+                        optional(preceded(
+                            as_unit(tuple3(
+                                as_unit(parse_comma()),
+                                expected(parse_identifier(), "key".to_string()),
+                                as_unit(parse_colon()),
+                            )),
+                            delimited(
+                                as_unit(parse_open_bracket()),
+                                separated_list(parse_identifier(), as_unit(parse_comma())),
+                                as_unit(parse_close_bracket()),
+                            ),
+                        )),
+                    ),
+                    as_unit(parse_close_paren()),
+                ),
+            ),
+            |(ttl, key)| ast::CacheDirective {
+                // parse_duration は Duration のリテラルしか返さない
+                ttl: match ttl {
+                    ast::Literal::Duration(ttl) => ttl,
+                    _ => Default::default(),
+                },
+                key,
+            },
+        ),
+        "cache directive",
+    )
+}
+
+/// Contract Block Parser
+///
+/// Parses the contracts of the requests an agent answers. A contract has the
+/// signature of a request handler without its body.
+///
+/// # Example
+/// ```text
+/// contract {
+///     /// Forecast for the next days
+///     request GetForecast(city: String, days: Int) -> String
+///     query.GetStatus() -> Boolean
+/// }
+/// ```
+pub fn parse_contract() -> impl Parser<Token, Vec<ast::RequestContract>> {
+    with_context(
+        map(
+            tuple4(
+                as_unit(parse_contract_keyword()),
+                as_unit(parse_open_brace()),
+                many(parse_request_contract()),
+                as_unit(parse_close_brace()),
+            ),
+            |(_, _, contracts, _)| contracts,
+        ),
+        "contract",
+    )
+}
+
+/// Parses a single request contract: `request Name(params) -> Type`
+pub fn parse_request_contract() -> impl Parser<Token, ast::RequestContract> {
+    with_context(
+        map(
+            tuple4(
+                parse_doc_comment(),
                 parse_request_type(),
                 parse_parameters(),
                 preceded(as_unit(parse_arrow()), parse_type_info()),
-                optional(parse_constraints()),
-                parse_statements(),
             ),
-            |(_, request_type, parameters, return_type, constraints, block)| ast::RequestHandler {
+            |(doc, request_type, parameters, return_type)| ast::RequestContract {
                 request_type,
                 parameters,
                 return_type,
-                constraints,
-                block: ast::HandlerBlock { statements: block },
+                doc,
             },
         ),
-        "request handler",
+        "request contract",
     )
 }
 
 /// Request Type Parser
 ///
-/// Parses the type of request being handled. Supports three types:
+/// Parses the type of request being handled. Supports four types:
 /// - Query: For data retrieval operations
 /// - Action: For state-changing operations
 /// - Custom: For user-defined request types
+/// - Fallback: The catch-all `_`, for the request types no other handler matches
 ///
 /// # Examples
 /// ```text
 /// query.GetUserData
 /// action.UpdateProfile
 /// request CustomOperation
+/// request _
 /// ```
 pub fn parse_request_type() -> impl Parser<Token, ast::RequestType> {
     with_context(
@@ -132,6 +255,10 @@ pub fn parse_request_type() -> impl Parser<Token, ast::RequestType> {
                 ),
                 |action_type| ast::RequestType::Action { action_type },
             )),
+            Box::new(map(
+                preceded(as_unit(parse_request()), as_unit(parse_wildcard())),
+                |_| ast::RequestType::Fallback,
+            )),
             Box::new(map(
                 preceded(as_unit(parse_request()), parse_identifier()),
                 ast::RequestType::Custom,
@@ -145,6 +272,13 @@ fn parse_request() -> impl Parser<Token, Token> {
     with_context(equal(Token::Keyword(Keyword::Request)), "request keyword")
 }
 
+fn parse_wildcard() -> impl Parser<Token, Token> {
+    with_context(
+        equal(Token::Identifier("_".to_string())),
+        "catch-all request type",
+    )
+}
+
 /// Quality Constraints Parser
 ///
 /// Parses quality constraints for request handlers.
@@ -152,13 +286,15 @@ fn parse_request() -> impl Parser<Token, Token> {
 /// - Response accuracy (strictness)
 /// - Response consistency (stability)
 /// - Response time (latency)
+/// - Transforms applied to the answer (postprocess)
 ///
 /// # Example
 /// ```text
 /// with {
 ///     strictness: 0.9,  // 90% accuracy requirement
 ///     stability: 0.8,   // 80% consistency requirement
-///     latency: 1000     // 1 second response time limit
+///     latency: 1000,    // 1 second response time limit
+///     postprocess: ["strip_fences", { json_field: "answer" }]
 /// }
 /// ```
 pub fn parse_constraints() -> impl Parser<Token, ast::Constraints> {
@@ -177,6 +313,7 @@ pub fn parse_constraints() -> impl Parser<Token, ast::Constraints> {
                             strictness: None,
                             stability: None,
                             latency: None,
+                            postprocess: vec![],
                         };
                         for (key, value) in items {
                             match (key.as_str(), value) {
@@ -189,6 +326,9 @@ pub fn parse_constraints() -> impl Parser<Token, ast::Constraints> {
                                 ("latency", ast::Literal::Integer(v)) => {
                                     constraints.latency = Some(v as u32)
                                 }
+                                ("postprocess", ast::Literal::List(transforms)) => {
+                                    constraints.postprocess = transforms
+                                }
                                 _ => {}
                             }
                         }
@@ -216,6 +356,10 @@ fn parse_answer_keyword() -> impl Parser<Token, Token> {
     with_context(equal(Token::Keyword(Keyword::Answer)), "answer keyword")
 }
 
+fn parse_contract_keyword() -> impl Parser<Token, Token> {
+    with_context(equal(Token::Keyword(Keyword::Contract)), "contract keyword")
+}
+
 fn parse_query_keyword() -> impl Parser<Token, Token> {
     with_context(equal(Token::Keyword(Keyword::Query)), "query keyword")
 }
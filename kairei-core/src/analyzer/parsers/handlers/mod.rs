@@ -4,29 +4,60 @@ pub mod react;
 
 use super::super::{core::*, prelude::*};
 use super::{statement::*, *};
-use crate::analyzer::parsers::types::parse_type_info;
-use crate::{ast, tokenizer::token::Token};
+use crate::analyzer::parsers::{
+    expression::{parse_dot, parse_expression},
+    types::parse_type_info,
+};
+use crate::{
+    ast,
+    tokenizer::{keyword::Keyword, token::Token},
+};
 
 /// Core handler parsing functionality shared between react and world contexts
 pub fn parse_handler_def() -> impl Parser<Token, ast::HandlerDef> {
     with_context(
         map(
-            tuple4(
+            tuple5(
+                parse_doc_comment(),
                 as_unit(parse_on_keyword()),
                 parse_identifier(),
                 parse_parameters(),
                 parse_statements(),
             ),
-            |(_, event_name, parameters, block)| ast::HandlerDef {
+            |(doc, _, event_name, parameters, block)| ast::HandlerDef {
                 event_name,
                 parameters,
                 block: ast::HandlerBlock { statements: block },
+                doc,
             },
         ),
         "handler",
     )
 }
 
+/// Precondition Parser
+///
+/// Parses the `requires` clause of an event or request handler. The condition
+/// is a Boolean expression over the agent's state and the handler's parameters,
+/// evaluated before the handler block runs.
+///
+/// # Example
+/// ```text
+/// on request Withdraw(amount: Int) -> Result<Int, Error> requires self.balance >= amount {
+///     // Handler implementation
+/// }
+/// ```
+pub fn parse_requires() -> impl Parser<Token, ast::Expression> {
+    with_context(
+        preceded(as_unit(parse_requires_keyword()), parse_expression()),
+        "requires clause",
+    )
+}
+
+fn parse_requires_keyword() -> impl Parser<Token, Token> {
+    with_context(equal(Token::Keyword(Keyword::Requires)), "requires keyword")
+}
+
 pub fn parse_parameters() -> impl Parser<Token, Vec<ast::Parameter>> {
     with_context(
         map(
@@ -45,18 +76,115 @@ pub fn parse_parameter() -> impl Parser<Token, ast::Parameter> {
     with_context(
         choice(vec![
             Box::new(map(
-                tuple3(
+                tuple5(
                     parse_identifier(),
                     as_unit(parse_colon()),
                     parse_type_info(),
+                    optional(parse_question()),
+                    optional(parse_parameter_constraint()),
                 ),
-                |(name, _, type_info)| ast::Parameter { name, type_info },
+                // `T?` は Option<T> の省略形
+                |(name, _, type_info, question, constraint)| {
+                    let constraint = constraint.map(|constraint| constraint.for_parameter(&name));
+                    ast::Parameter {
+                        name,
+                        type_info: match question {
+                            Some(_) => ast::TypeInfo::Option(Box::new(type_info)),
+                            None => type_info,
+                        },
+                        constraint,
+                    }
+                },
             )),
             Box::new(map(parse_identifier(), |name| ast::Parameter {
                 name,
                 type_info: ast::TypeInfo::any(),
+                constraint: None,
             })),
         ]),
         "parameter",
     )
 }
+
+/// `where` clause of a parameter before it is attached to the parameter
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstraintClause {
+    Range {
+        subject: String,
+        start: ast::Literal,
+        end: ast::Literal,
+    },
+    Condition(ast::Expression),
+}
+
+impl ConstraintClause {
+    /// A range over another name than the parameter stays a condition, so that
+    /// the type checker reports the foreign reference
+    fn for_parameter(self, name: &str) -> ast::ParameterConstraint {
+        match self {
+            ConstraintClause::Range {
+                subject,
+                start,
+                end,
+            } => {
+                let range = ast::ParameterConstraint::Range { start, end };
+                if subject == name {
+                    range
+                } else {
+                    ast::ParameterConstraint::Condition(range.condition(&subject))
+                }
+            }
+            ConstraintClause::Condition(condition) => {
+                ast::ParameterConstraint::Condition(condition)
+            }
+        }
+    }
+}
+
+/// Parameter Constraint Parser
+///
+/// Parses the `where` clause of a handler parameter: either an inclusive range
+/// or a Boolean expression over the parameter. Constraints are checked after the
+/// parameters are bound and before the handler block runs.
+///
+/// # Example
+/// ```text
+/// on request plan(city: String where len(city) > 0, days: Int where days in 1..30) {
+///     // Handler implementation
+/// }
+/// ```
+pub fn parse_parameter_constraint() -> impl Parser<Token, ConstraintClause> {
+    with_context(
+        preceded(
+            as_unit(parse_where()),
+            choice(vec![
+                Box::new(map(
+                    tuple6(
+                        parse_identifier(),
+                        as_unit(parse_in()),
+                        parse_literal(),
+                        as_unit(parse_dot()),
+                        as_unit(parse_dot()),
+                        parse_literal(),
+                    ),
+                    |(subject, _, start, _, _, end)| ConstraintClause::Range {
+                        subject,
+                        start,
+                        end,
+                    },
+                )),
+                Box::new(map(parse_expression(), ConstraintClause::Condition)),
+            ]),
+        ),
+        "parameter constraint",
+    )
+}
+
+// `where` と `in` は予約語ではなく、この位置でのみ意味を持つ
+fn parse_where() -> impl Parser<Token, Token> {
+    with_context(equal(Token::Identifier("where".to_string())), "where")
+}
+
+fn parse_in() -> impl Parser<Token, Token> {
+    with_context(equal(Token::Identifier("in".to_string())), "in")
+}
@@ -1,5 +1,5 @@
 use super::super::super::{core::*, prelude::*};
-use crate::analyzer::parsers::handlers::parse_parameters;
+use crate::analyzer::parsers::handlers::{parse_parameters, parse_requires};
 use crate::ast;
 use crate::{
     analyzer::parsers::{expression::*, statement::*, *},
@@ -55,8 +55,10 @@ pub fn parse_observe() -> impl Parser<Token, ast::ObserveDef> {
 /// Each handler defines how the agent responds to specific events.
 ///
 /// # Handler Structure
+/// - Optional `@replay(...)` directive before the `on` keyword
 /// - Event type (built-in or custom)
 /// - Optional parameters with types
+/// - Optional `requires` precondition
 /// - Handler implementation block
 ///
 /// # Example
@@ -65,29 +67,67 @@ pub fn parse_observe() -> impl Parser<Token, ast::ObserveDef> {
 ///     // Handle tick event
 /// }
 ///
-/// on CustomEvent(data: EventData) {
+/// @replay(skip)
+/// on CustomEvent(data: EventData) requires self.active {
 ///     // Handle custom event with data
 /// }
 /// ```
 pub fn parse_event_handler() -> impl Parser<Token, ast::EventHandler> {
     with_context(
         map(
-            tuple4(
-                as_unit(parse_on_keyword()),
-                parse_event_type(),
-                optional(parse_parameters()),
-                parse_statements(),
+            tuple3(
+                parse_doc_comment(),
+                optional(parse_replay_directive()),
+                tuple5(
+                    as_unit(parse_on_keyword()),
+                    parse_event_type(),
+                    optional(parse_parameters()),
+                    optional(parse_requires()),
+                    parse_statements(),
+                ),
             ),
-            |(_, event_type, parameters, block)| ast::EventHandler {
+            |(doc, replay, (_, event_type, parameters, requires, block))| ast::EventHandler {
                 event_type,
                 parameters: parameters.unwrap_or_default(),
+                requires,
                 block: ast::HandlerBlock { statements: block },
+                doc,
+                replay,
             },
         ),
         "event handler",
     )
 }
 
+/// Replay Directive Parser
+///
+/// Parses the `@replay(...)` directive of an observe or react handler. The
+/// arguments are checked by the type checker.
+///
+/// # Example
+/// ```text
+/// @replay(skip)
+/// ```
+pub fn parse_replay_directive() -> impl Parser<Token, ast::ReplayDirective> {
+    with_context(
+        map(
+            preceded(
+                as_unit(tuple2(
+                    as_unit(parse_at()),
+                    expected(parse_identifier(), "replay".to_string()),
+                )),
+                delimited(
+                    as_unit(parse_open_paren()),
+                    separated_list(parse_identifier(), as_unit(parse_comma())),
+                    as_unit(parse_close_paren()),
+                ),
+            ),
+            |arguments| ast::ReplayDirective { arguments },
+        ),
+        "replay directive",
+    )
+}
+
 /// Event Type Parser
 ///
 /// Parses the type of event being handled. Supports:
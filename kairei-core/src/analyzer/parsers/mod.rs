@@ -36,7 +36,7 @@ pub mod doc_types;
 /// Parsers for expressions in the KAIREI DSL
 pub mod expression;
 /// Parsers for event handlers
-mod handlers;
+pub(crate) mod handlers;
 /// Parsers for statements in the KAIREI DSL
 pub mod statement;
 /// Parsers for type definitions
@@ -3,12 +3,14 @@ use tracing::instrument;
 use super::{
     super::{core::*, prelude::*},
     expression::*,
+    types::parse_type_info,
     *,
 };
 use crate::ast;
 use crate::{
     Statement,
-    tokenizer::{keyword::Keyword, token::Token},
+    agent_log::LogLevel,
+    tokenizer::{keyword::Keyword, symbol::Operator, token::Token},
 };
 
 #[instrument(level = "debug")]
@@ -17,6 +19,21 @@ pub fn parse_statement() -> impl Parser<Token, ast::Statement> {
         map(
             lazy(|| {
                 choice(vec![
+                    // `log.info(...)` は式としても読めるので、式文より先に試す
+                    Box::new(tuple2(
+                        parse_log_statement(),
+                        optional(parse_error_handler()),
+                    )),
+                    // `retry(...)` も関数呼び出しとして読めてしまうので、式文より先に試す
+                    Box::new(tuple2(
+                        parse_retry_statement(),
+                        optional(parse_error_handler()),
+                    )),
+                    // `let` 単体も変数として読めてしまうので、式文より先に試す
+                    Box::new(tuple2(
+                        parse_let_statement(),
+                        optional(parse_error_handler()),
+                    )),
                     Box::new(tuple2(
                         parse_assignment_statement(),
                         optional(parse_error_handler()),
@@ -29,6 +46,10 @@ pub fn parse_statement() -> impl Parser<Token, ast::Statement> {
                         parse_return_statement(),
                         optional(parse_error_handler()),
                     )),
+                    Box::new(tuple2(
+                        parse_yield_statement(),
+                        optional(parse_error_handler()),
+                    )),
                     Box::new(tuple2(
                         parse_emit_statement(),
                         optional(parse_error_handler()),
@@ -44,11 +65,12 @@ pub fn parse_statement() -> impl Parser<Token, ast::Statement> {
                 ])
             }),
             |(statement, error_handler)| match error_handler {
+                // onFail が受け取るのは仮の文なので、読んだ文に差し替える
                 Some(Statement::WithError {
-                    statement,
                     error_handler_block,
+                    ..
                 }) => Statement::WithError {
-                    statement,
+                    statement: Box::new(statement),
                     error_handler_block,
                 },
                 _ => statement,
@@ -280,6 +302,32 @@ fn parse_assignment_target() -> impl Parser<Token, Vec<ast::Expression>> {
     )
 }
 
+/// `let name = value` or `let name: Type = value`. `let` is not a keyword, so a
+/// variable named `let` can still be used.
+fn parse_let_statement() -> impl Parser<Token, ast::Statement> {
+    with_context(
+        map(
+            tuple5(
+                as_unit(parse_let_keyword()),
+                parse_identifier(),
+                optional(preceded(as_unit(parse_colon()), parse_type_info())),
+                as_unit(parse_equal()),
+                parse_expression(),
+            ),
+            |(_, name, type_info, _, value)| ast::Statement::Let {
+                name,
+                type_info,
+                value,
+            },
+        ),
+        "let statement",
+    )
+}
+
+fn parse_let_keyword() -> impl Parser<Token, Token> {
+    with_context(equal(Token::Identifier("let".to_string())), "let keyword")
+}
+
 #[instrument(level = "debug")]
 fn parse_return_statement() -> impl Parser<Token, ast::Statement> {
     with_context(
@@ -295,20 +343,36 @@ fn parse_return_keyword() -> impl Parser<Token, Token> {
     with_context(equal(Token::Keyword(Keyword::Return)), "return keyword")
 }
 
+fn parse_yield_statement() -> impl Parser<Token, ast::Statement> {
+    with_context(
+        map(
+            preceded(as_unit(parse_yield_keyword()), parse_expression()),
+            ast::Statement::Yield,
+        ),
+        "yield statement",
+    )
+}
+
+fn parse_yield_keyword() -> impl Parser<Token, Token> {
+    with_context(equal(Token::Keyword(Keyword::Yield)), "yield keyword")
+}
+
 #[instrument(level = "debug")]
 fn parse_emit_statement() -> impl Parser<Token, ast::Statement> {
     with_context(
         map(
-            tuple4(
+            tuple5(
                 as_unit(parse_emit_keyword()),
+                optional(as_unit(equal(Token::Operator(Operator::Not)))),
                 parse_identifier(),
                 parse_emit_arguments(),
                 optional(parse_emit_target()),
             ),
-            |(_, event_type, parameters, target)| ast::Statement::Emit {
+            |(_, immediate, event_type, parameters, target)| ast::Statement::Emit {
                 event_type: ast::EventType::Custom(event_type),
                 parameters,
                 target,
+                immediate: immediate.is_some(),
             },
         ),
         "emit statement",
@@ -333,6 +397,115 @@ fn parse_emit_arguments() -> impl Parser<Token, Vec<ast::Argument>> {
     with_context(parse_arguments(), "emit arguments")
 }
 
+/// `log.<level>("message", key: value, ...)`. `log` is not a keyword, so a function
+/// named `log` can still be called.
+fn parse_log_statement() -> impl Parser<Token, ast::Statement> {
+    with_context(
+        map(
+            tuple4(
+                as_unit(parse_log_target()),
+                as_unit(parse_dot()),
+                parse_log_level(),
+                delimited(
+                    as_unit(parse_open_paren()),
+                    tuple2(
+                        parse_expression(),
+                        many(preceded(as_unit(parse_comma()), parse_log_field())),
+                    ),
+                    as_unit(parse_close_paren()),
+                ),
+            ),
+            |(_, _, level, (message, fields))| ast::Statement::Log {
+                level,
+                message,
+                fields,
+            },
+        ),
+        "log statement",
+    )
+}
+
+fn parse_log_target() -> impl Parser<Token, Token> {
+    with_context(equal(Token::Identifier("log".to_string())), "log target")
+}
+
+fn parse_log_level() -> impl Parser<Token, LogLevel> {
+    with_context(
+        satisfy(|token| match token {
+            Token::Identifier(name) => LogLevel::from_name(name),
+            _ => None,
+        }),
+        "log level",
+    )
+}
+
+fn parse_log_field() -> impl Parser<Token, (String, ast::Expression)> {
+    with_context(
+        map(
+            tuple3(
+                parse_identifier(),
+                as_unit(parse_colon()),
+                parse_expression(),
+            ),
+            |(key, _, value)| (key, value),
+        ),
+        "log field",
+    )
+}
+
+fn parse_retry_statement() -> impl Parser<Token, ast::Statement> {
+    with_context(
+        map(
+            tuple3(
+                as_unit(parse_retry_keyword()),
+                delimited(
+                    as_unit(parse_open_paren()),
+                    tuple2(
+                        parse_retry_argument("times"),
+                        optional(preceded(
+                            as_unit(parse_comma()),
+                            parse_retry_argument("backoff"),
+                        )),
+                    ),
+                    as_unit(parse_close_paren()),
+                ),
+                parse_statements(),
+            ),
+            |(_, (times, backoff), body)| ast::Statement::Retry {
+                times,
+                backoff,
+                body,
+            },
+        ),
+        "retry statement",
+    )
+}
+
+fn parse_retry_keyword() -> impl Parser<Token, Token> {
+    with_context(
+        equal(Token::Identifier("retry".to_string())),
+        "retry keyword",
+    )
+}
+
+fn parse_retry_argument(name: &'static str) -> impl Parser<Token, ast::Expression> {
+    with_context(
+        map(
+            tuple3(
+                as_unit(equal(Token::Identifier(name.to_string()))),
+                as_unit(parse_colon()),
+                // 式の整数リテラルは単位を読まないので、`2s` は先に期間として読む
+                choice(vec![
+                    Box::new(map(parse_duration(), ast::Expression::Literal)),
+                    Box::new(parse_expression()),
+                ]),
+            ),
+            |(_, _, value)| value,
+        ),
+        "retry argument",
+    )
+}
+
 fn parse_if_statement() -> impl Parser<Token, ast::Statement> {
     with_context(
         map(
@@ -582,6 +755,146 @@ mod tests {
         assert_eq!(parse_return_statement().parse(&input, 0), Ok((2, expected)));
     }
 
+    #[test]
+    fn test_parse_yield_statement() {
+        let input = vec![
+            Token::Keyword(Keyword::Yield),
+            Token::Identifier("partial".to_string()),
+        ];
+        let expected = ast::Statement::Yield(ast::Expression::Variable("partial".to_string()));
+        assert_eq!(parse_statement().parse(&input, 0), Ok((2, expected)));
+    }
+
+    #[test]
+    fn test_parse_log_statement() {
+        let input = vec![
+            Token::Identifier("log".to_string()),
+            Token::Operator(Operator::Dot),
+            Token::Identifier("warn".to_string()),
+            Token::Delimiter(Delimiter::OpenParen),
+            Token::Identifier("message".to_string()),
+            Token::Delimiter(Delimiter::Comma),
+            Token::Identifier("retries".to_string()),
+            Token::Delimiter(Delimiter::Colon),
+            Token::Literal(Literal::Integer(3)),
+            Token::Delimiter(Delimiter::CloseParen),
+        ];
+        let expected = ast::Statement::Log {
+            level: LogLevel::Warn,
+            message: ast::Expression::Variable("message".to_string()),
+            fields: vec![(
+                "retries".to_string(),
+                ast::Expression::Literal(ast::Literal::Integer(3)),
+            )],
+        };
+        assert_eq!(parse_statement().parse(&input, 0), Ok((10, expected)));
+
+        // log は予約語ではないので、関数としても呼べる
+        let input = vec![
+            Token::Identifier("log".to_string()),
+            Token::Delimiter(Delimiter::OpenParen),
+            Token::Identifier("err".to_string()),
+            Token::Delimiter(Delimiter::CloseParen),
+        ];
+        assert!(matches!(
+            parse_statement().parse(&input, 0),
+            Ok((
+                4,
+                ast::Statement::Expression(ast::Expression::FunctionCall { .. })
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_parse_retry_statement() {
+        let input = vec![
+            Token::Identifier("retry".to_string()),
+            Token::Delimiter(Delimiter::OpenParen),
+            Token::Identifier("times".to_string()),
+            Token::Delimiter(Delimiter::Colon),
+            Token::Literal(Literal::Integer(3)),
+            Token::Delimiter(Delimiter::Comma),
+            Token::Identifier("backoff".to_string()),
+            Token::Delimiter(Delimiter::Colon),
+            Token::Literal(Literal::Integer(2)),
+            Token::Identifier("s".to_string()),
+            Token::Delimiter(Delimiter::CloseParen),
+            Token::Delimiter(Delimiter::OpenBrace),
+            Token::Identifier("fetch".to_string()),
+            Token::Delimiter(Delimiter::OpenParen),
+            Token::Delimiter(Delimiter::CloseParen),
+            Token::Delimiter(Delimiter::CloseBrace),
+            Token::Keyword(Keyword::OnFail),
+            Token::Delimiter(Delimiter::OpenBrace),
+            Token::Delimiter(Delimiter::CloseBrace),
+        ];
+        let (rest, statement) = parse_statement().parse(&input, 0).unwrap();
+        assert_eq!(rest, 19);
+        let ast::Statement::WithError { statement, .. } = statement else {
+            panic!("expected onFail, got {:?}", statement);
+        };
+        assert_eq!(
+            *statement,
+            ast::Statement::Retry {
+                times: ast::Expression::Literal(ast::Literal::Integer(3)),
+                backoff: Some(ast::Expression::Literal(ast::Literal::Duration(
+                    std::time::Duration::from_secs(2)
+                ))),
+                body: vec![ast::Statement::Expression(ast::Expression::FunctionCall {
+                    function: "fetch".to_string(),
+                    arguments: vec![],
+                })],
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_let_statement() {
+        let input = vec![
+            Token::Identifier("let".to_string()),
+            Token::Identifier("forecast".to_string()),
+            Token::Delimiter(Delimiter::Colon),
+            Token::Identifier("String".to_string()),
+            Token::Delimiter(Delimiter::Equal),
+            Token::Identifier("fetch".to_string()),
+            Token::Delimiter(Delimiter::OpenParen),
+            Token::Delimiter(Delimiter::CloseParen),
+        ];
+        assert_eq!(
+            parse_statement().parse(&input, 0),
+            Ok((
+                8,
+                ast::Statement::Let {
+                    name: "forecast".to_string(),
+                    type_info: Some(ast::TypeInfo::Simple("String".to_string())),
+                    value: ast::Expression::FunctionCall {
+                        function: "fetch".to_string(),
+                        arguments: vec![],
+                    },
+                }
+            ))
+        );
+
+        // 型注釈は省略できる
+        let input = vec![
+            Token::Identifier("let".to_string()),
+            Token::Identifier("count".to_string()),
+            Token::Delimiter(Delimiter::Equal),
+            Token::Literal(Literal::Integer(1)),
+        ];
+        assert_eq!(
+            parse_statement().parse(&input, 0),
+            Ok((
+                4,
+                ast::Statement::Let {
+                    name: "count".to_string(),
+                    type_info: None,
+                    value: ast::Expression::Literal(ast::Literal::Integer(1)),
+                }
+            ))
+        );
+    }
+
     #[test]
     fn test_parse_emit_statement() {
         let input = vec![
@@ -597,6 +910,7 @@ mod tests {
                 ast::Literal::Integer(42),
             ))],
             target: None,
+            immediate: false,
         };
         assert_eq!(parse_emit_statement().parse(&input, 0), Ok((5, expected)));
     }
@@ -618,10 +932,29 @@ mod tests {
                 ast::Literal::Integer(42),
             ))],
             target: Some("target".to_string()),
+            immediate: false,
         };
         assert_eq!(parse_emit_statement().parse(&input, 0), Ok((7, expected)));
     }
 
+    #[test]
+    fn test_parse_immediate_emit_statement() {
+        let input = vec![
+            Token::Keyword(Keyword::Emit),
+            Token::Operator(Operator::Not),
+            Token::Identifier("Alert".to_string()),
+            Token::Delimiter(Delimiter::OpenParen),
+            Token::Delimiter(Delimiter::CloseParen),
+        ];
+        let expected = ast::Statement::Emit {
+            event_type: ast::EventType::Custom("Alert".to_string()),
+            parameters: vec![],
+            target: None,
+            immediate: true,
+        };
+        assert_eq!(parse_emit_statement().parse(&input, 0), Ok((5, expected)));
+    }
+
     #[test]
     fn test_parse_if_statement() {
         let input = vec![
@@ -55,6 +55,7 @@ fn test_parse_agent_def() {
                         name: "counter".to_string(),
                         type_info: ast::TypeInfo::Simple("Integer".to_string()),
                         initial_value: Some(ast::Expression::Literal(ast::Literal::Integer(0))),
+                        doc: None,
                     },
                 );
                 vars
@@ -64,15 +65,24 @@ fn test_parse_agent_def() {
             handlers: vec![ast::EventHandler {
                 event_type: ast::EventType::Tick,
                 parameters: vec![],
+                requires: None,
                 block: ast::HandlerBlock {
                     statements: vec![ast::Statement::Return(ast::Expression::Literal(
                         ast::Literal::Null,
                     ))],
                 },
+                doc: None,
+                replay: None,
             }],
         }),
         answer: None,
         react: None,
+        doc: None,
+        labels: Default::default(),
+        persona: None,
+        localized_personas: Default::default(),
+        contracts: vec![],
+        config: None,
     };
 
     assert_eq!(
@@ -81,6 +91,33 @@ fn test_parse_agent_def() {
     );
 }
 
+#[test]
+fn test_parse_localized_personas() {
+    let text = |text: &str| {
+        Token::Literal(Literal::String(StringLiteral::Single(vec![
+            StringPart::Literal(text.to_string()),
+        ])))
+    };
+    let input = vec![
+        Token::Keyword(Keyword::Micro),
+        Token::Identifier("Planner".to_string()),
+        Token::Delimiter(Delimiter::OpenBrace),
+        Token::Keyword(Keyword::Persona),
+        text("You are a travel planner."),
+        Token::Keyword(Keyword::Persona),
+        Token::Identifier("ja".to_string()),
+        text("あなたは旅行プランナーです。"),
+        Token::Delimiter(Delimiter::CloseBrace),
+    ];
+    let (rest, agent) = parse_agent_def().parse(&input, 0).unwrap();
+    assert_eq!(rest, input.len());
+    assert_eq!(agent.persona, Some("You are a travel planner.".to_string()));
+    assert_eq!(
+        agent.localized_personas,
+        HashMap::from([("ja".to_string(), "あなたは旅行プランナーです。".to_string())])
+    );
+}
+
 #[test]
 fn test_parse_lifecycle() {
     let input = vec![
@@ -149,6 +186,7 @@ fn test_parse_state() {
                     name: "counter".to_string(),
                     type_info: ast::TypeInfo::Simple("Integer".to_string()),
                     initial_value: Some(ast::Expression::Literal(ast::Literal::Integer(0))),
+                    doc: None,
                 },
             );
             vars.insert(
@@ -157,6 +195,7 @@ fn test_parse_state() {
                     name: "name".to_string(),
                     type_info: ast::TypeInfo::Simple("String".to_string()),
                     initial_value: None,
+                    doc: None,
                 },
             );
             vars
@@ -206,6 +245,7 @@ fn test_parse_full_state_block() {
                     name: "counter".to_string(),
                     type_info: ast::TypeInfo::Simple("Int".to_string()),
                     initial_value: Some(ast::Expression::Literal(ast::Literal::Integer(0))),
+                    doc: None,
                 },
             );
             vars.insert(
@@ -216,6 +256,7 @@ fn test_parse_full_state_block() {
                     initial_value: Some(ast::Expression::Literal(ast::Literal::String(
                         "test".to_string(),
                     ))),
+                    doc: None,
                 },
             );
             vars.insert(
@@ -224,6 +265,7 @@ fn test_parse_full_state_block() {
                     name: "active".to_string(),
                     type_info: ast::TypeInfo::Simple("Bool".to_string()),
                     initial_value: Some(ast::Expression::Literal(ast::Literal::Boolean(true))),
+                    doc: None,
                 },
             );
             vars
@@ -268,6 +310,7 @@ fn test_parse_lifecycle_block() {
                 event_type: ast::EventType::Custom("Shutdown".to_string()),
                 parameters: vec![],
                 target: Some("manager".to_string()),
+                immediate: false,
             }],
         }),
     };
@@ -313,6 +356,7 @@ fn test_parse_observe_block() {
             ast::EventHandler {
                 event_type: ast::EventType::Tick,
                 parameters: vec![],
+                requires: None,
                 block: ast::HandlerBlock {
                     statements: vec![ast::Statement::Assignment {
                         target: vec![ast::Expression::Variable("counter".to_string())],
@@ -323,10 +367,13 @@ fn test_parse_observe_block() {
                         },
                     }],
                 },
+                doc: None,
+                replay: None,
             },
             ast::EventHandler {
                 event_type: ast::EventType::Custom("StateUpdated".to_string()),
                 parameters: vec![],
+                requires: None,
                 block: ast::HandlerBlock {
                     statements: vec![ast::Statement::Assignment {
                         target: vec![ast::Expression::Variable("name".to_string())],
@@ -335,6 +382,8 @@ fn test_parse_observe_block() {
                         )),
                     }],
                 },
+                doc: None,
+                replay: None,
             },
         ],
     };
@@ -418,17 +467,21 @@ fn test_parse_answer_block() {
                     err_type: Box::new(ast::TypeInfo::Simple("Error".to_string())),
                 },
                 constraints: None,
+                requires: None,
                 block: ast::HandlerBlock {
                     statements: vec![ast::Statement::Return(ast::Expression::Ok(Box::new(
                         ast::Expression::Variable("counter".to_string()),
                     )))],
                 },
+                doc: None,
+                cache: None,
             },
             RequestHandler {
                 request_type: ast::RequestType::Custom("SetName".to_string()),
                 parameters: vec![ast::Parameter {
                     name: "newName".to_string(),
                     type_info: ast::TypeInfo::Simple("String".to_string()),
+                    constraint: None,
                 }],
                 return_type: ast::TypeInfo::Result {
                     ok_type: Box::new(ast::TypeInfo::Simple("Bool".to_string())),
@@ -438,7 +491,9 @@ fn test_parse_answer_block() {
                     strictness: Some(0.9),
                     stability: Some(0.95),
                     latency: None,
+                    postprocess: vec![],
                 }),
+                requires: None,
                 block: ast::HandlerBlock {
                     statements: vec![
                         ast::Statement::Assignment {
@@ -450,6 +505,8 @@ fn test_parse_answer_block() {
                         ))),
                     ],
                 },
+                doc: None,
+                cache: None,
             },
         ],
     };
@@ -500,7 +557,9 @@ fn test_parse_react_block() {
             parameters: vec![ast::Parameter {
                 name: "content".to_string(),
                 type_info: ast::TypeInfo::Simple("String".to_string()),
+                constraint: None,
             }],
+            requires: None,
             block: ast::HandlerBlock {
                 statements: vec![
                     ast::Statement::Assignment {
@@ -524,9 +583,12 @@ fn test_parse_react_block() {
                             },
                         ],
                         target: Some("manager".to_string()),
+                        immediate: false,
                     },
                 ],
             },
+            doc: None,
+            replay: None,
         }],
     };
 
@@ -41,23 +41,30 @@ fn test_parse_observe() {
             ast::EventHandler {
                 event_type: ast::EventType::Tick,
                 parameters: vec![],
+                requires: None,
                 block: ast::HandlerBlock {
                     statements: vec![ast::Statement::Return(ast::Expression::Literal(
                         ast::Literal::Null,
                     ))],
                 },
+                doc: None,
+                replay: None,
             },
             ast::EventHandler {
                 event_type: ast::EventType::Custom("CustomEvent".to_string()),
                 parameters: vec![ast::Parameter {
                     name: "param".to_string(),
                     type_info: ast::TypeInfo::Simple("String".to_string()),
+                    constraint: None,
                 }],
+                requires: None,
                 block: ast::HandlerBlock {
                     statements: vec![ast::Statement::Return(ast::Expression::Variable(
                         "param".to_string(),
                     ))],
                 },
+                doc: None,
+                replay: None,
             },
         ],
     };
@@ -130,11 +137,14 @@ fn test_parse_answer() {
                 parameters: vec![],
                 return_type: ast::TypeInfo::Simple("String".to_string()),
                 constraints: None,
+                requires: None,
                 block: ast::HandlerBlock {
                     statements: vec![ast::Statement::Return(ast::Expression::Literal(
                         ast::Literal::String("data".to_string()),
                     ))],
                 },
+                doc: None,
+                cache: None,
             },
             ast::RequestHandler {
                 request_type: ast::RequestType::Action {
@@ -143,6 +153,7 @@ fn test_parse_answer() {
                 parameters: vec![ast::Parameter {
                     name: "input".to_string(),
                     type_info: ast::TypeInfo::Simple("String".to_string()),
+                    constraint: None,
                 }],
                 return_type: ast::TypeInfo::Result {
                     ok_type: Box::new(ast::TypeInfo::Simple("String".to_string())),
@@ -152,12 +163,16 @@ fn test_parse_answer() {
                     strictness: Some(0.8),
                     stability: None,
                     latency: None,
+                    postprocess: vec![],
                 }),
+                requires: None,
                 block: ast::HandlerBlock {
                     statements: vec![ast::Statement::Return(ast::Expression::Ok(Box::new(
                         ast::Expression::Variable("input".to_string()),
                     )))],
                 },
+                doc: None,
+                cache: None,
             },
         ],
     };
@@ -197,12 +212,16 @@ fn test_parse_react() {
             parameters: vec![ast::Parameter {
                 name: "new_status".to_string(),
                 type_info: ast::TypeInfo::Simple("String".to_string()),
+                constraint: None,
             }],
+            requires: None,
             block: ast::HandlerBlock {
                 statements: vec![ast::Statement::Return(ast::Expression::Variable(
                     "new_status".to_string(),
                 ))],
             },
+            doc: None,
+            replay: None,
         }],
     };
 
@@ -233,12 +252,14 @@ fn test_parse_handler() {
             parameters: vec![ast::Parameter {
                 name: "param1".to_string(),
                 type_info: ast::TypeInfo::Simple("String".to_string()),
+                constraint: None,
             }],
             block: ast::HandlerBlock {
                 statements: vec![ast::Statement::Return(ast::Expression::Variable(
                     "param1".to_string(),
                 ))],
             },
+            doc: None,
         }],
     };
     assert_eq!(
@@ -257,10 +278,117 @@ fn test_parse_parameter() {
     let expected = ast::Parameter {
         name: "name".to_string(),
         type_info: ast::TypeInfo::Simple("String".to_string()),
+        constraint: None,
     };
     assert_eq!(parse_parameter().parse(&input, 0), Ok((3, expected)));
 }
 
+#[test]
+fn test_parse_optional_parameter() {
+    let input = vec![
+        Token::Identifier("note".to_string()),
+        Token::Delimiter(Delimiter::Colon),
+        Token::Identifier("String".to_string()),
+        Token::Operator(Operator::Question),
+    ];
+    let expected = ast::Parameter {
+        name: "note".to_string(),
+        type_info: ast::TypeInfo::Option(Box::new(ast::TypeInfo::Simple("String".to_string()))),
+        constraint: None,
+    };
+    assert_eq!(parse_parameter().parse(&input, 0), Ok((4, expected)));
+
+    // 必須パラメータと混在できる
+    let input = vec![
+        Token::Delimiter(Delimiter::OpenParen),
+        Token::Identifier("title".to_string()),
+        Token::Delimiter(Delimiter::Colon),
+        Token::Identifier("String".to_string()),
+        Token::Delimiter(Delimiter::Comma),
+        Token::Identifier("note".to_string()),
+        Token::Delimiter(Delimiter::Colon),
+        Token::Identifier("String".to_string()),
+        Token::Operator(Operator::Question),
+        Token::Delimiter(Delimiter::CloseParen),
+    ];
+    let parameters = parse_parameters().parse(&input, 0).unwrap().1;
+    assert_eq!(
+        parameters[0].type_info,
+        ast::TypeInfo::Simple("String".to_string())
+    );
+    assert_eq!(
+        parameters[1].type_info,
+        ast::TypeInfo::Option(Box::new(ast::TypeInfo::Simple("String".to_string())))
+    );
+}
+
+#[test]
+fn test_parse_parameter_constraint() {
+    let days = |subject: &str| {
+        vec![
+            Token::Identifier("days".to_string()),
+            Token::Delimiter(Delimiter::Colon),
+            Token::Identifier("Int".to_string()),
+            Token::Identifier("where".to_string()),
+            Token::Identifier(subject.to_string()),
+            Token::Identifier("in".to_string()),
+            Token::Literal(Literal::Integer(1)),
+            Token::Operator(Operator::Dot),
+            Token::Operator(Operator::Dot),
+            Token::Literal(Literal::Integer(30)),
+        ]
+    };
+    let (_, parameter) = parse_parameter().parse(&days("days"), 0).unwrap();
+    assert_eq!(
+        parameter.constraint,
+        Some(ast::ParameterConstraint::Range {
+            start: ast::Literal::Integer(1),
+            end: ast::Literal::Integer(30),
+        })
+    );
+
+    // 別名の範囲は条件式として残し、型検査で弾く
+    let (_, parameter) = parse_parameter().parse(&days("weeks"), 0).unwrap();
+    assert_eq!(
+        parameter.constraint,
+        Some(ast::ParameterConstraint::Condition(
+            ast::ParameterConstraint::Range {
+                start: ast::Literal::Integer(1),
+                end: ast::Literal::Integer(30),
+            }
+            .condition("weeks")
+        ))
+    );
+
+    let input = vec![
+        Token::Identifier("city".to_string()),
+        Token::Delimiter(Delimiter::Colon),
+        Token::Identifier("String".to_string()),
+        Token::Identifier("where".to_string()),
+        Token::Identifier("len".to_string()),
+        Token::Delimiter(Delimiter::OpenParen),
+        Token::Identifier("city".to_string()),
+        Token::Delimiter(Delimiter::CloseParen),
+        Token::Operator(Operator::Greater),
+        Token::Literal(Literal::Integer(0)),
+    ];
+    let expected = ast::Parameter {
+        name: "city".to_string(),
+        type_info: ast::TypeInfo::Simple("String".to_string()),
+        constraint: Some(ast::ParameterConstraint::Condition(
+            ast::Expression::BinaryOp {
+                op: ast::BinaryOperator::GreaterThan,
+                left: Box::new(ast::Expression::FunctionCall {
+                    function: "len".to_string(),
+                    arguments: vec![ast::Expression::Variable("city".to_string())],
+                }),
+                right: Box::new(ast::Expression::Literal(ast::Literal::Integer(0))),
+            },
+        )),
+    };
+    assert_eq!(parse_parameter().parse(&input, 0), Ok((10, expected)));
+}
+
 #[test]
 fn test_parse_parameters() {
     let input = vec![
@@ -273,6 +401,7 @@ fn test_parse_parameters() {
     let expected = vec![ast::Parameter {
         name: "param1".to_string(),
         type_info: ast::TypeInfo::Simple("String".to_string()),
+        constraint: None,
     }];
     assert_eq!(parse_parameters().parse(&input, 0), Ok((5, expected)));
 }
@@ -386,3 +515,179 @@ fn test_parse_request() {
     ];
     assert!(parse_request().parse(input, 0).is_err());
 }
+
+#[test]
+fn test_parse_replay_directive() {
+    // @replay(skip) on Deposited(amount: Int) { return amount }
+    let input = vec![
+        Token::Delimiter(Delimiter::At),
+        Token::Identifier("replay".to_string()),
+        Token::Delimiter(Delimiter::OpenParen),
+        Token::Identifier("skip".to_string()),
+        Token::Delimiter(Delimiter::CloseParen),
+        Token::Keyword(Keyword::On),
+        Token::Identifier("Deposited".to_string()),
+        Token::Delimiter(Delimiter::OpenParen),
+        Token::Identifier("amount".to_string()),
+        Token::Delimiter(Delimiter::Colon),
+        Token::Identifier("Int".to_string()),
+        Token::Delimiter(Delimiter::CloseParen),
+        Token::Delimiter(Delimiter::OpenBrace),
+        Token::Keyword(Keyword::Return),
+        Token::Identifier("amount".to_string()),
+        Token::Delimiter(Delimiter::CloseBrace),
+    ];
+    let (pos, handler) = parse_event_handler().parse(&input, 0).unwrap();
+    assert_eq!(pos, input.len());
+    let replay = handler.replay.unwrap();
+    assert_eq!(replay.arguments, vec!["skip".to_string()]);
+    assert_eq!(replay.policy(), Some(ast::ReplayPolicy::Skip));
+
+    // 不正な引数も構文としては読み、型検査で弾く
+    let input = vec![
+        Token::Delimiter(Delimiter::At),
+        Token::Identifier("replay".to_string()),
+        Token::Delimiter(Delimiter::OpenParen),
+        Token::Identifier("safe".to_string()),
+        Token::Delimiter(Delimiter::Comma),
+        Token::Identifier("skip".to_string()),
+        Token::Delimiter(Delimiter::CloseParen),
+    ];
+    let (_, replay) = parse_replay_directive().parse(&input, 0).unwrap();
+    assert_eq!(replay.policy(), None);
+}
+
+#[test]
+fn test_parse_requires() {
+    // on Deposit(amount: Int) requires amount > 0 { return amount }
+    let input = vec![
+        Token::Keyword(Keyword::On),
+        Token::Identifier("Deposit".to_string()),
+        Token::Delimiter(Delimiter::OpenParen),
+        Token::Identifier("amount".to_string()),
+        Token::Delimiter(Delimiter::Colon),
+        Token::Identifier("Int".to_string()),
+        Token::Delimiter(Delimiter::CloseParen),
+        Token::Keyword(Keyword::Requires),
+        Token::Identifier("amount".to_string()),
+        Token::Operator(Operator::Greater),
+        Token::Literal(Literal::Integer(0)),
+        Token::Delimiter(Delimiter::OpenBrace),
+        Token::Keyword(Keyword::Return),
+        Token::Identifier("amount".to_string()),
+        Token::Delimiter(Delimiter::CloseBrace),
+    ];
+    let (_, handler) = parse_event_handler().parse(&input, 0).unwrap();
+    assert_eq!(
+        handler.requires,
+        Some(ast::Expression::BinaryOp {
+            op: ast::BinaryOperator::GreaterThan,
+            left: Box::new(ast::Expression::Variable("amount".to_string())),
+            right: Box::new(ast::Expression::Literal(ast::Literal::Integer(0))),
+        })
+    );
+    assert_eq!(handler.block.statements.len(), 1);
+
+    // on request Withdraw(amount: Int) -> Int requires balance >= amount with { latency: 100 } { return amount }
+    let input = vec![
+        Token::Keyword(Keyword::On),
+        Token::Keyword(Keyword::Request),
+        Token::Identifier("Withdraw".to_string()),
+        Token::Delimiter(Delimiter::OpenParen),
+        Token::Identifier("amount".to_string()),
+        Token::Delimiter(Delimiter::Colon),
+        Token::Identifier("Int".to_string()),
+        Token::Delimiter(Delimiter::CloseParen),
+        Token::Operator(Operator::Arrow),
+        Token::Identifier("Int".to_string()),
+        Token::Keyword(Keyword::Requires),
+        Token::Identifier("balance".to_string()),
+        Token::Operator(Operator::GreaterEqual),
+        Token::Identifier("amount".to_string()),
+        Token::Keyword(Keyword::With),
+        Token::Delimiter(Delimiter::OpenBrace),
+        Token::Identifier("latency".to_string()),
+        Token::Delimiter(Delimiter::Colon),
+        Token::Literal(Literal::Integer(100)),
+        Token::Delimiter(Delimiter::CloseBrace),
+        Token::Delimiter(Delimiter::OpenBrace),
+        Token::Keyword(Keyword::Return),
+        Token::Identifier("amount".to_string()),
+        Token::Delimiter(Delimiter::CloseBrace),
+    ];
+    let (pos, handler) = parse_request_handler().parse(&input, 0).unwrap();
+    assert_eq!(pos, input.len());
+    assert_eq!(
+        handler.requires,
+        Some(ast::Expression::BinaryOp {
+            op: ast::BinaryOperator::GreaterThanEqual,
+            left: Box::new(ast::Expression::Variable("balance".to_string())),
+            right: Box::new(ast::Expression::Variable("amount".to_string())),
+        })
+    );
+    assert_eq!(handler.constraints.unwrap().latency, Some(100));
+}
+
+#[test]
+fn test_parse_cache_directive() {
+    // @cache(ttl: 10m, key: [city]) on request Forecast(city: String, days: Int) -> String { return city }
+    let input = vec![
+        Token::Delimiter(Delimiter::At),
+        Token::Identifier("cache".to_string()),
+        Token::Delimiter(Delimiter::OpenParen),
+        Token::Identifier("ttl".to_string()),
+        Token::Delimiter(Delimiter::Colon),
+        Token::Literal(Literal::Integer(10)),
+        Token::Identifier("m".to_string()),
+        Token::Delimiter(Delimiter::Comma),
+        Token::Identifier("key".to_string()),
+        Token::Delimiter(Delimiter::Colon),
+        Token::Delimiter(Delimiter::OpenBracket),
+        Token::Identifier("city".to_string()),
+        Token::Delimiter(Delimiter::CloseBracket),
+        Token::Delimiter(Delimiter::CloseParen),
+        Token::Keyword(Keyword::On),
+        Token::Keyword(Keyword::Request),
+        Token::Identifier("Forecast".to_string()),
+        Token::Delimiter(Delimiter::OpenParen),
+        Token::Identifier("city".to_string()),
+        Token::Delimiter(Delimiter::Colon),
+        Token::Identifier("String".to_string()),
+        Token::Delimiter(Delimiter::Comma),
+        Token::Identifier("days".to_string()),
+        Token::Delimiter(Delimiter::Colon),
+        Token::Identifier("Int".to_string()),
+        Token::Delimiter(Delimiter::CloseParen),
+        Token::Operator(Operator::Arrow),
+        Token::Identifier("String".to_string()),
+        Token::Delimiter(Delimiter::OpenBrace),
+        Token::Keyword(Keyword::Return),
+        Token::Identifier("city".to_string()),
+        Token::Delimiter(Delimiter::CloseBrace),
+    ];
+    let (pos, handler) = parse_request_handler().parse(&input, 0).unwrap();
+    assert_eq!(pos, input.len());
+    assert_eq!(
+        handler.cache,
+        Some(ast::CacheDirective {
+            ttl: std::time::Duration::from_secs(600),
+            key: Some(vec!["city".to_string()]),
+        })
+    );
+
+    // key を省略するとすべてのパラメータがキーになる
+    let input = vec![
+        Token::Delimiter(Delimiter::At),
+        Token::Identifier("cache".to_string()),
+        Token::Delimiter(Delimiter::OpenParen),
+        Token::Identifier("ttl".to_string()),
+        Token::Delimiter(Delimiter::Colon),
+        Token::Literal(Literal::Integer(30)),
+        Token::Identifier("s".to_string()),
+        Token::Delimiter(Delimiter::CloseParen),
+    ];
+    let (_, directive) = parse_cache_directive().parse(&input, 0).unwrap();
+    assert_eq!(directive.ttl, std::time::Duration::from_secs(30));
+    assert_eq!(directive.key, None);
+    assert_eq!(directive.key_parameters(&handler), vec!["city", "days"]);
+}
@@ -272,3 +272,87 @@ fn test_parse_result_type() {
         }
     );
 }
+
+#[test]
+fn test_parse_type_def_with_type_params() {
+    // type Pair<A, B> { first: A, second: Array{B} }
+    let input = &[
+        Token::Identifier("type".to_string()),
+        Token::Identifier("Pair".to_string()),
+        Token::Operator(Operator::Less),
+        Token::Identifier("A".to_string()),
+        Token::Delimiter(Delimiter::Comma),
+        Token::Identifier("B".to_string()),
+        Token::Operator(Operator::Greater),
+        Token::Delimiter(Delimiter::OpenBrace),
+        Token::Identifier("first".to_string()),
+        Token::Delimiter(Delimiter::Colon),
+        Token::Identifier("A".to_string()),
+        Token::Delimiter(Delimiter::Comma),
+        Token::Identifier("second".to_string()),
+        Token::Delimiter(Delimiter::Colon),
+        Token::Identifier("Array".to_string()),
+        Token::Delimiter(Delimiter::OpenBrace),
+        Token::Identifier("B".to_string()),
+        Token::Delimiter(Delimiter::CloseBrace),
+        Token::Delimiter(Delimiter::CloseBrace),
+    ];
+    let (pos, result) = parse_type_def().parse(input, 0).unwrap();
+    assert_eq!(pos, input.len());
+    assert_eq!(result.name, "Pair");
+    assert_eq!(result.type_params, vec!["A".to_string(), "B".to_string()]);
+    assert_eq!(
+        result.fields["first"].type_info,
+        Some(ast::TypeInfo::Simple("A".to_string()))
+    );
+    assert_eq!(
+        result.fields["second"].type_info,
+        Some(ast::TypeInfo::Array(Box::new(ast::TypeInfo::Simple(
+            "B".to_string()
+        ))))
+    );
+
+    // 型パラメータのない宣言
+    let input = &[
+        Token::Identifier("type".to_string()),
+        Token::Identifier("Point".to_string()),
+        Token::Delimiter(Delimiter::OpenBrace),
+        Token::Identifier("x".to_string()),
+        Token::Delimiter(Delimiter::Colon),
+        Token::Identifier("Float".to_string()),
+        Token::Delimiter(Delimiter::CloseBrace),
+    ];
+    let (pos, result) = parse_type_def().parse(input, 0).unwrap();
+    assert_eq!(pos, input.len());
+    assert!(result.type_params.is_empty());
+    assert_eq!(result.fields.len(), 1);
+}
+
+#[test]
+fn test_parse_generic_type() {
+    // Pair<Int, Option{String}>
+    let input = &[
+        Token::Identifier("Pair".to_string()),
+        Token::Operator(Operator::Less),
+        Token::Identifier("Int".to_string()),
+        Token::Delimiter(Delimiter::Comma),
+        Token::Identifier("Option".to_string()),
+        Token::Delimiter(Delimiter::OpenBrace),
+        Token::Identifier("String".to_string()),
+        Token::Delimiter(Delimiter::CloseBrace),
+        Token::Operator(Operator::Greater),
+    ];
+    let (pos, result) = parse_type_info().parse(input, 0).unwrap();
+    assert_eq!(pos, input.len());
+    assert_eq!(
+        result,
+        ast::TypeInfo::Generic {
+            name: "Pair".to_string(),
+            args: vec![
+                ast::TypeInfo::Simple("Int".to_string()),
+                ast::TypeInfo::Option(Box::new(ast::TypeInfo::Simple("String".to_string()))),
+            ],
+        }
+    );
+    assert_eq!(result.to_string(), "Pair<Int, Option<String>>");
+}
@@ -32,10 +32,117 @@ fn test_parse_world() {
             config: None,
             events: ast::EventsDef { events: vec![] },
             handlers: ast::HandlersDef { handlers: vec![] },
+            persona: None,
+            schedules: vec![],
+            on_init: None,
         }
     );
 }
 
+#[test]
+fn test_parse_world_persona() {
+    let input = vec![
+        Token::Keyword(Keyword::World),
+        Token::Identifier("test".to_string()),
+        Token::Delimiter(Delimiter::OpenBrace),
+        Token::Keyword(Keyword::Persona),
+        Token::Literal(Literal::String(StringLiteral::Triple(vec![
+            StringPart::Literal("You are a planner for ".to_string()),
+            StringPart::Interpolation("company".to_string()),
+        ]))),
+        Token::Delimiter(Delimiter::CloseBrace),
+    ];
+    let (rest, world) = parse_world().parse(&input, 0).unwrap();
+    assert_eq!(rest, 6);
+    assert_eq!(
+        world.persona,
+        Some("You are a planner for ${company}".to_string())
+    );
+}
+
+#[test]
+fn test_parse_world_schedules() {
+    let cron = |text: &str| {
+        Token::Literal(Literal::String(StringLiteral::Single(vec![
+            StringPart::Literal(text.to_string()),
+        ])))
+    };
+    let input = vec![
+        Token::Keyword(Keyword::World),
+        Token::Identifier("test".to_string()),
+        Token::Delimiter(Delimiter::OpenBrace),
+        Token::Identifier("schedule".to_string()),
+        cron("0 2 * * *"),
+        Token::Keyword(Keyword::Emit),
+        Token::Identifier("cleanupRequested".to_string()),
+        Token::Delimiter(Delimiter::OpenParen),
+        Token::Identifier("scope".to_string()),
+        Token::Delimiter(Delimiter::Colon),
+        cron("all"),
+        Token::Delimiter(Delimiter::CloseParen),
+        Token::Identifier("schedule".to_string()),
+        cron("*/5 * * * *"),
+        Token::Keyword(Keyword::Emit),
+        Token::Identifier("heartbeat".to_string()),
+        Token::Delimiter(Delimiter::CloseBrace),
+    ];
+    let (rest, world) = parse_world().parse(&input, 0).unwrap();
+    assert_eq!(rest, input.len());
+    assert_eq!(
+        world.schedules,
+        vec![
+            ast::ScheduleDef {
+                cron: "0 2 * * *".to_string(),
+                event: "cleanupRequested".to_string(),
+                parameters: vec![ast::Argument::Named {
+                    name: "scope".to_string(),
+                    value: ast::Expression::Literal(ast::Literal::String("all".to_string())),
+                }],
+            },
+            ast::ScheduleDef {
+                cron: "*/5 * * * *".to_string(),
+                event: "heartbeat".to_string(),
+                parameters: vec![],
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_parse_world_on_init() {
+    let input = vec![
+        Token::Keyword(Keyword::World),
+        Token::Identifier("test".to_string()),
+        Token::Delimiter(Delimiter::OpenBrace),
+        Token::Keyword(Keyword::OnInit),
+        Token::Delimiter(Delimiter::OpenBrace),
+        Token::Identifier("increment".to_string()),
+        Token::Delimiter(Delimiter::OpenParen),
+        Token::Literal(Literal::String(StringLiteral::Single(vec![
+            StringPart::Literal("seats".to_string()),
+        ]))),
+        Token::Delimiter(Delimiter::Comma),
+        Token::Literal(Literal::Integer(10)),
+        Token::Delimiter(Delimiter::CloseParen),
+        Token::Delimiter(Delimiter::CloseBrace),
+        Token::Delimiter(Delimiter::CloseBrace),
+    ];
+    let (rest, world) = parse_world().parse(&input, 0).unwrap();
+    assert_eq!(rest, input.len());
+    assert_eq!(
+        world.on_init,
+        Some(ast::HandlerBlock {
+            statements: vec![ast::Statement::Expression(ast::Expression::FunctionCall {
+                function: "increment".to_string(),
+                arguments: vec![
+                    ast::Expression::Literal(ast::Literal::String("seats".to_string())),
+                    ast::Expression::Literal(ast::Literal::Integer(10)),
+                ],
+            })],
+        })
+    );
+}
+
 #[test]
 fn test_parse_config() {
     let input = vec![
@@ -102,6 +209,7 @@ fn test_parse_parameters() {
     let expected = vec![ast::Parameter {
         name: "param1".to_string(),
         type_info: ast::TypeInfo::Simple("String".to_string()),
+        constraint: None,
     }];
     assert_eq!(parse_parameters().parse(&input, 0), Ok((5, expected)));
 }
@@ -116,6 +224,7 @@ fn test_parse_parameter() {
     let expected = ast::Parameter {
         name: "name".to_string(),
         type_info: ast::TypeInfo::Simple("String".to_string()),
+        constraint: None,
     };
     assert_eq!(parse_parameter().parse(&input, 0), Ok((3, expected)));
 }
@@ -13,6 +13,8 @@ pub fn parse_type_info() -> impl Parser<Token, ast::TypeInfo> {
                 Box::new(parse_result_type()),
                 Box::new(parse_option_type()),
                 Box::new(parse_array_type()),
+                Box::new(parse_set_type()),
+                Box::new(parse_generic_type()),
                 Box::new(parse_simple_type()),
                 Box::new(parse_custom_type()),
             ])
@@ -21,6 +23,40 @@ pub fn parse_type_info() -> impl Parser<Token, ast::TypeInfo> {
     )
 }
 
+/// Parses a type declared at the top of a file, generic over the parameters in angle
+/// brackets if it has any. `type` is not a keyword.
+///
+/// # Example
+/// ```text
+/// type Pair<A, B> { first: A, second: B }
+/// ```
+pub fn parse_type_def() -> impl Parser<Token, ast::TypeDef> {
+    with_context(
+        map(
+            tuple4(
+                as_unit(equal(Token::Identifier("type".to_string()))),
+                parse_identifier(),
+                optional(delimited(
+                    as_unit(parse_angle_open()),
+                    separated_list(parse_identifier(), as_unit(parse_comma())),
+                    as_unit(parse_angle_close()),
+                )),
+                delimited(
+                    as_unit(parse_open_brace()),
+                    separated_list(lazy(parse_field), as_unit(parse_comma())),
+                    as_unit(parse_close_brace()),
+                ),
+            ),
+            |(_, name, type_params, fields)| ast::TypeDef {
+                name,
+                type_params: type_params.unwrap_or_default(),
+                fields: fields.into_iter().collect(),
+            },
+        ),
+        "type definition",
+    )
+}
+
 pub fn parse_custom_type() -> impl Parser<Token, ast::TypeInfo> {
     with_context(
         map(
@@ -62,6 +98,13 @@ fn parse_array_type() -> impl Parser<Token, ast::TypeInfo> {
     )
 }
 
+fn parse_set_type() -> impl Parser<Token, ast::TypeInfo> {
+    with_context(
+        map(parse_generic_single_arg("Set"), ast::TypeInfo::Set),
+        "Set type",
+    )
+}
+
 fn parse_generic_single_arg(type_name: &'static str) -> impl Parser<Token, Box<ast::TypeInfo>> {
     map(
         tuple2(
@@ -96,6 +139,24 @@ pub fn parse_result_type() -> impl Parser<Token, ast::TypeInfo> {
     )
 }
 
+/// `Pair<Int, String>`: a declared type applied to type arguments
+fn parse_generic_type() -> impl Parser<Token, ast::TypeInfo> {
+    with_context(
+        map(
+            tuple2(
+                parse_identifier(),
+                delimited(
+                    as_unit(parse_angle_open()),
+                    separated_list(parse_type_info(), as_unit(parse_comma())),
+                    as_unit(parse_angle_close()),
+                ),
+            ),
+            |(name, args)| ast::TypeInfo::Generic { name, args },
+        ),
+        "generic type",
+    )
+}
+
 fn parse_simple_type() -> impl Parser<Token, ast::TypeInfo> {
     with_context(
         map(parse_identifier(), ast::TypeInfo::Simple),
@@ -165,11 +226,13 @@ fn parse_field_inferred() -> impl Parser<Token, ast::FieldInfo> {
 fn parse_type_reference() -> impl Parser<Token, ast::TypeInfo> {
     lazy(|| {
         choice(vec![
-            Box::new(parse_simple_type()), // String, Intなど
-            Box::new(parse_result_type()), // Result<T, E>
-            Box::new(parse_option_type()), // Option<T>
-            Box::new(parse_array_type()),  // Array<T>
-                                           // カスタム型の参照はOK、定義は不可
+            Box::new(parse_result_type()),  // Result<T, E>
+            Box::new(parse_option_type()),  // Option<T>
+            Box::new(parse_array_type()),   // Array<T>
+            Box::new(parse_set_type()),     // Set<T>
+            Box::new(parse_generic_type()), // Pair<A, B>
+            Box::new(parse_simple_type()),  // String, Intなど
+                                            // カスタム型の参照はOK、定義は不可
         ])
     })
 }
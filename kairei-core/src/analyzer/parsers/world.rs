@@ -139,8 +139,10 @@ use uuid::Uuid;
 
 use super::{
     super::{core::*, prelude::*},
-    agent::parse_agent_def,
+    agent::{parse_agent_def, parse_init_handler},
+    expression::parse_arguments,
     handlers::{parse_handler_def, parse_parameters},
+    types::parse_type_def,
     *,
 };
 use crate::ast;
@@ -159,17 +161,50 @@ use std::collections::HashMap;
 /// not on raw text, ensuring proper lexical analysis has already been performed.
 ///
 /// # Returns
-/// A parser that produces an `ast::Root` containing the World and MicroAgent definitions
+/// A parser that produces an `ast::Root` containing the DSL version declaration, the
+/// type definitions, and the World and MicroAgent definitions
 pub fn parse_root() -> impl Parser<Token, ast::Root> {
     with_context(
         map(
-            tuple2(optional(parse_world()), many(parse_agent_def())),
-            |(world_def, micro_agent_defs)| ast::Root::new(world_def, micro_agent_defs, vec![]),
+            tuple4(
+                optional(parse_dsl_version()),
+                many(parse_type_def()),
+                optional(parse_world()),
+                many(parse_agent_def()),
+            ),
+            |(dsl_version, type_defs, world_def, micro_agent_defs)| {
+                let mut root = ast::Root::new(world_def, micro_agent_defs, vec![]);
+                root.dsl_version = dsl_version;
+                root.type_defs = type_defs;
+                root
+            },
         ),
         "root",
     )
 }
 
+/// Parses the DSL version declaration at the top of a file.
+///
+/// The version is checked against the versions this build reads in
+/// [`crate::dsl_version::check_compatibility`]. `kairei` is not a keyword.
+///
+/// # Example
+/// ```text
+/// kairei "0.4"
+/// ```
+pub fn parse_dsl_version() -> impl Parser<Token, String> {
+    with_context(
+        map(
+            preceded(
+                as_unit(equal(Token::Identifier("kairei".to_string()))),
+                parse_literal(),
+            ),
+            |version| version.to_string(),
+        ),
+        "dsl version",
+    )
+}
+
 /// Parses a World definition block, including its configuration, events, and handlers.
 ///
 /// The World block is the top-level container that defines the environment where
@@ -178,6 +213,7 @@ pub fn parse_root() -> impl Parser<Token, ast::Root> {
 /// - Custom event definitions
 /// - Event handlers
 /// - Global policies
+/// - One-time setup in `onInit`, run before any agent starts
 ///
 /// # Example
 /// ```text
@@ -188,6 +224,9 @@ pub fn parse_root() -> impl Parser<Token, ast::Root> {
 ///     events {
 ///         UserRequestedItinerary
 ///     }
+///     onInit {
+///         increment("open_seats", 40)
+///     }
 /// }
 /// ```
 pub fn parse_world() -> impl Parser<Token, ast::WorldDef> {
@@ -199,33 +238,45 @@ pub fn parse_world() -> impl Parser<Token, ast::WorldDef> {
                 parse_open_brace(),
                 many(choice(vec![
                     Box::new(map(parse_policy(), WorldDefItem::Policy)),
+                    Box::new(map(parse_persona(), WorldDefItem::Persona)),
                     Box::new(map(parse_config(), WorldDefItem::Config)),
                     Box::new(map(parse_events(), WorldDefItem::Events)),
                     Box::new(map(parse_handlers(), WorldDefItem::Handlers)),
+                    Box::new(map(parse_schedule(), WorldDefItem::Schedule)),
+                    Box::new(map(parse_init_handler(), WorldDefItem::OnInit)),
                 ])),
                 parse_close_brace(),
             ),
             |(_, name, _, items, _)| {
                 let mut policies = vec![];
+                let mut persona = None;
                 let mut config = None;
                 let mut events = None;
                 let mut handlers = None;
+                let mut schedules = vec![];
+                let mut on_init = None;
 
                 for item in items {
                     match item {
                         WorldDefItem::Policy(policy) => policies.push(policy),
+                        WorldDefItem::Persona(text) => persona = Some(text),
                         WorldDefItem::Config(config_def) => config = Some(config_def),
                         WorldDefItem::Events(events_def) => events = Some(events_def),
                         WorldDefItem::Handlers(handlers_def) => handlers = Some(handlers_def),
+                        WorldDefItem::Schedule(schedule) => schedules.push(schedule),
+                        WorldDefItem::OnInit(block) => on_init = Some(block),
                     }
                 }
 
                 ast::WorldDef {
                     name,
                     policies,
+                    persona,
                     config,
                     events: events.unwrap_or_default(),
                     handlers: handlers.unwrap_or_default(),
+                    schedules,
+                    on_init,
                 }
             },
         ),
@@ -237,9 +288,12 @@ pub fn parse_world() -> impl Parser<Token, ast::WorldDef> {
 #[derive(Debug, Clone, PartialEq)]
 enum WorldDefItem {
     Policy(ast::Policy),
+    Persona(String),
     Config(ast::ConfigDef),
     Events(ast::EventsDef),
     Handlers(ast::HandlersDef),
+    Schedule(ast::ScheduleDef),
+    OnInit(ast::HandlerBlock),
 }
 
 fn parse_world_keyword() -> impl Parser<Token, Token> {
@@ -273,6 +327,93 @@ fn parse_policy_keyword() -> impl Parser<Token, Token> {
     with_context(equal(Token::Keyword(Keyword::Policy)), "policy keyword")
 }
 
+/// Parses a persona declaration in a World or MicroAgent definition.
+///
+/// The persona is the prompt preamble placed ahead of every think prompt.
+/// A world persona is the default for all agents; an agent persona overrides it.
+/// Like think prompts, it may reference state with `${name}`.
+///
+/// # Example
+/// ```text
+/// persona """
+/// You are a careful travel planner working for ${company}.
+/// """
+/// ```
+pub fn parse_persona() -> impl Parser<Token, String> {
+    with_context(
+        map(
+            preceded(as_unit(parse_persona_keyword()), parse_literal()),
+            |text| text.to_string(),
+        ),
+        "persona",
+    )
+}
+
+/// Parses a persona declared for one locale in a MicroAgent definition.
+///
+/// The language tag follows the keyword. Think calls made in that locale use this
+/// persona instead of the agent's default one.
+///
+/// # Example
+/// ```text
+/// persona ja "あなたは慎重な旅行プランナーです。"
+/// ```
+pub fn parse_localized_persona() -> impl Parser<Token, (String, String)> {
+    with_context(
+        map(
+            preceded(
+                as_unit(parse_persona_keyword()),
+                tuple2(parse_identifier(), parse_literal()),
+            ),
+            |(locale, text)| (locale, text.to_string()),
+        ),
+        "localized persona",
+    )
+}
+
+fn parse_persona_keyword() -> impl Parser<Token, Token> {
+    with_context(equal(Token::Keyword(Keyword::Persona)), "persona keyword")
+}
+
+/// Parses a schedule declaration in a World definition.
+///
+/// A schedule emits an event each time its cron expression fires; see
+/// [`crate::cron`] for the supported syntax. Arguments are optional and must be
+/// named literals, which the type checker enforces along with the cron expression.
+/// `schedule` is not a keyword, so a will action named `schedule` still parses.
+///
+/// # Example
+/// ```text
+/// schedule "0 2 * * *" emit cleanupRequested(scope: "all")
+/// schedule "*/5 * * * *" emit heartbeat
+/// ```
+pub fn parse_schedule() -> impl Parser<Token, ast::ScheduleDef> {
+    with_context(
+        map(
+            tuple5(
+                as_unit(parse_schedule_keyword()),
+                parse_literal(),
+                as_unit(equal(Token::Keyword(Keyword::Emit))),
+                parse_identifier(),
+                optional(parse_arguments()),
+            ),
+            |(_, cron, _, event, parameters)| ast::ScheduleDef {
+                cron: cron.to_string(),
+                event,
+                parameters: parameters.unwrap_or_default(),
+            },
+        ),
+        "schedule",
+    )
+}
+
+fn parse_schedule_keyword() -> impl Parser<Token, Token> {
+    with_context(
+        equal(Token::Identifier("schedule".to_string())),
+        "schedule keyword",
+    )
+}
+
 /// Parses the configuration block of a World definition.
 ///
 /// The config block allows setting various World parameters:
@@ -306,7 +447,7 @@ pub fn parse_config() -> impl Parser<Token, ast::ConfigDef> {
     )
 }
 
-fn parse_config_keyword() -> impl Parser<Token, Token> {
+pub fn parse_config_keyword() -> impl Parser<Token, Token> {
     with_context(equal(Token::Keyword(Keyword::Config)), "config keyword")
 }
 
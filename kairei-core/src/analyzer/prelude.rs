@@ -410,6 +410,24 @@ where
     WithContext::new(parser, c)
 }
 
+/// Creates a parser for operand chains joined by prefix and infix operators
+///
+/// # Arguments
+///
+/// * `operand` - The parser for operands
+/// * `table` - The operators with their precedence, associativity and constructors
+///
+/// # Returns
+///
+/// A Pratt parser that accepts every operator of the table, see
+/// [`Pratt::with_min_precedence`] to stop at looser operators
+pub fn pratt<P, I, O>(operand: P, table: OperatorTable<I, O>) -> Pratt<P, I, O>
+where
+    P: Parser<I, O>,
+{
+    Pratt::new(operand, table)
+}
+
 /// Creates a parser that lazily constructs another parser
 ///
 /// # Arguments
@@ -4,6 +4,10 @@ use std::collections::HashMap;
 // Root AST Definition
 #[derive(Debug, Clone)]
 pub struct Root {
+    /// Version the file declares with `kairei "0.4"`, see [`crate::dsl_version`]
+    pub dsl_version: Option<String>,
+    /// Types declared with `type` before the world and the agents
+    pub type_defs: Vec<TypeDef>,
     pub world_def: Option<WorldDef>,
     pub micro_agent_defs: Vec<MicroAgentDef>,
     pub sistence_agent_defs: Vec<SistenceAgentDef>,
@@ -16,6 +20,8 @@ impl Root {
         sistence_agent_defs: Vec<SistenceAgentDef>,
     ) -> Self {
         Self {
+            dsl_version: None,
+            type_defs: Vec::new(),
             world_def,
             micro_agent_defs,
             sistence_agent_defs,
@@ -84,6 +90,67 @@ pub struct MicroAgentDef {
     pub observe: Option<ObserveDef>,
     pub answer: Option<AnswerDef>,
     pub react: Option<ReactDef>,
+    /// Doc comment (`///` or `/** */`) preceding the `micro` keyword
+    pub doc: Option<String>,
+    /// Metadata labels (e.g. `team`, `environment`) used to group and find agents
+    pub labels: HashMap<String, String>,
+    /// Prompt preamble (`persona "..."`) placed ahead of every think prompt.
+    /// Overrides the world-level default.
+    pub persona: Option<String>,
+    /// Prompt preambles for one locale (`persona ja "..."`), keyed by language tag.
+    /// Chosen over `persona` for the think calls made in that locale.
+    pub localized_personas: HashMap<String, String>,
+    /// Contracts (`contract { ... }`) of the requests the agent answers
+    pub contracts: Vec<RequestContract>,
+    /// Deployment-specific parameters (`config { ... }`), read as `config.<name>`
+    pub config: Option<AgentConfigDef>,
+}
+
+impl MicroAgentDef {
+    /// The contract declared for `request_type`, named as in [`RequestType`]'s `Display`
+    pub fn contract(&self, request_type: &str) -> Option<&RequestContract> {
+        self.contracts
+            .iter()
+            .find(|contract| contract.request_type.to_string() == request_type)
+    }
+}
+
+/// Configuration Block
+///
+/// Declares typed parameters of a MicroAgent whose values are supplied per
+/// deployment in `SystemConfig::agent_configs` instead of the DSL.
+///
+/// # Example
+/// ```text
+/// config {
+///     region: String,
+///     max_results: Int = 10
+/// }
+/// ```
+///
+/// Parameters are:
+/// - Validated against their types when the system initializes
+/// - Required unless they have a default
+/// - Read-only in every handler, as `config.region`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AgentConfigDef {
+    pub parameters: Vec<ConfigParamDef>,
+}
+
+impl AgentConfigDef {
+    pub fn parameter(&self, name: &str) -> Option<&ConfigParamDef> {
+        self.parameters.iter().find(|param| param.name == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigParamDef {
+    pub name: String,
+    pub type_info: TypeInfo,
+    /// Value used when the system config does not supply one
+    pub default: Option<Literal>,
+    /// Doc comment preceding the parameter declaration
+    pub doc: Option<String>,
 }
 
 // ライフサイクル定義
@@ -122,6 +189,8 @@ pub struct StateVarDef {
     pub name: String,
     pub type_info: TypeInfo,
     pub initial_value: Option<Expression>,
+    /// Doc comment preceding the variable declaration
+    pub doc: Option<String>,
 }
 
 /// Observe Block Definition
@@ -211,9 +280,31 @@ pub struct ReactDef {
 pub struct WorldDef {
     pub name: String,
     pub policies: Vec<Policy>,
+    /// Default prompt preamble for every agent in the world (`persona "..."`)
+    pub persona: Option<String>,
     pub config: Option<ConfigDef>,
     pub events: EventsDef,
     pub handlers: HandlersDef,
+    /// Events emitted on a cron schedule (`schedule "0 2 * * *" emit ...`)
+    pub schedules: Vec<ScheduleDef>,
+    /// One-time world setup (`onInit { ... }`), run by `System::initialize`
+    /// before any agent starts
+    pub on_init: Option<HandlerBlock>,
+}
+
+/// A world-level scheduled emission:
+///
+/// ```text
+/// schedule "0 2 * * *" emit cleanupRequested(scope: "all")
+/// ```
+///
+/// The cron expression is checked by the type checker; see [`crate::cron`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduleDef {
+    pub cron: String,
+    pub event: String,
+    /// Named literal arguments of the emitted event
+    pub parameters: Vec<Argument>,
 }
 
 // 設定定義
@@ -299,6 +390,8 @@ pub struct HandlerDef {
     pub event_name: String,
     pub parameters: Vec<Parameter>,
     pub block: HandlerBlock,
+    /// Doc comment preceding the `on` keyword
+    pub doc: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Hash, Eq)]
@@ -333,7 +426,13 @@ impl fmt::Display for EventType {
 pub struct EventHandler {
     pub event_type: EventType,
     pub parameters: Vec<Parameter>, // イベントの型に応じたパラメータ定義
+    /// Precondition of the `requires` clause, checked before the block runs
+    pub requires: Option<Expression>,
     pub block: HandlerBlock,
+    /// Doc comment preceding the `on` keyword
+    pub doc: Option<String>,
+    /// `@replay(...)` directive preceding the `on` keyword
+    pub replay: Option<ReplayDirective>,
 }
 
 impl From<HandlerDef> for EventHandler {
@@ -341,11 +440,49 @@ impl From<HandlerDef> for EventHandler {
         Self {
             event_type: EventType::Custom(handler.event_name),
             parameters: handler.parameters,
+            requires: None,
             block: handler.block,
+            doc: handler.doc,
+            replay: None,
+        }
+    }
+}
+
+/// Replay of an observe or react handler from the event journal
+///
+/// The arguments are checked by the type checker: exactly one of `skip` or `safe`.
+///
+/// # Example
+/// ```text
+/// @replay(skip)
+/// on OrderPlaced(order_id: String) { ... }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayDirective {
+    pub arguments: Vec<String>,
+}
+
+impl ReplayDirective {
+    /// The policy named by the arguments, `None` when they are invalid
+    pub fn policy(&self) -> Option<ReplayPolicy> {
+        match self.arguments.as_slice() {
+            [argument] if argument == "safe" => Some(ReplayPolicy::Safe),
+            [argument] if argument == "skip" => Some(ReplayPolicy::Skip),
+            _ => None,
         }
     }
 }
 
+/// Whether a handler runs for events replayed from the event journal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayPolicy {
+    /// Runs again: the handler only updates state
+    Safe,
+    /// Does not run: the handler has side effects, such as sending emails or
+    /// calling external tools
+    Skip,
+}
+
 impl EventHandler {
     /// イベントタイプに応じた適切なパラメータを持っているか検証
     pub fn validate_parameters(&self) -> Result<(), String> {
@@ -374,6 +511,7 @@ pub enum RequestType {
     Query { query_type: String },
     Action { action_type: String },
     Custom(String), // 拡張性のために残す
+    Fallback,       // `request _`: 他のどのハンドラにも一致しないリクエストを受ける
 }
 
 impl From<&str> for RequestType {
@@ -388,6 +526,7 @@ impl fmt::Display for RequestType {
             RequestType::Query { query_type } => write!(f, "Query.{}", query_type),
             RequestType::Action { action_type } => write!(f, "Action.{}", action_type),
             RequestType::Custom(name) => write!(f, "{}", name),
+            RequestType::Fallback => write!(f, "_"),
         }
     }
 }
@@ -398,8 +537,41 @@ pub struct RequestHandler {
     pub request_type: RequestType,
     pub parameters: Vec<Parameter>, // リクエストの型に応じたパラメータ定義
     pub return_type: TypeInfo,
+    /// Precondition of the `requires` clause, checked before the block runs
+    pub requires: Option<Expression>,
     pub constraints: Option<Constraints>,
     pub block: HandlerBlock,
+    /// Doc comment preceding the `on` keyword
+    pub doc: Option<String>,
+    /// `@cache(...)` directive preceding the `on` keyword
+    pub cache: Option<CacheDirective>,
+}
+
+/// Response caching of an answer handler
+///
+/// A successful response is kept for `ttl` and returned to later requests with
+/// the same values of the key parameters, without running the handler again.
+///
+/// # Example
+/// ```text
+/// @cache(ttl: 10m, key: [city, days])
+/// on request GetForecast(city: String, days: Int, locale: String) -> String { ... }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheDirective {
+    pub ttl: Duration,
+    /// Parameters the cache key is computed from; all of them when `None`
+    pub key: Option<Vec<String>>,
+}
+
+impl CacheDirective {
+    /// Names of the parameters of `handler` that make up the cache key
+    pub fn key_parameters<'a>(&'a self, handler: &'a RequestHandler) -> Vec<&'a str> {
+        match &self.key {
+            Some(key) => key.iter().map(String::as_str).collect(),
+            None => handler.parameters.iter().map(|p| p.name.as_str()).collect(),
+        }
+    }
 }
 
 impl RequestHandler {
@@ -415,16 +587,85 @@ impl RequestHandler {
             RequestType::Custom(_) => {
                 // カスタムリクエストは任意のパラメータを許容
             }
+            RequestType::Fallback => {
+                // 元のリクエストタイプだけを受け取る
+                let string = TypeInfo::Simple("String".to_string());
+                if !matches!(self.parameters.as_slice(), [param] if param.type_info == string) {
+                    return Err(
+                        "the catch-all handler takes exactly one String parameter, the request type"
+                            .to_string(),
+                    );
+                }
+            }
         }
         Ok(())
     }
 }
 
+/// Request Contract Declaration
+///
+/// Declares the parameters and the answer type of a request the agent answers,
+/// so that callers can rely on them. The type checker requires an answer handler
+/// with exactly these parameters for each contract, returning the declared type
+/// or a `Result` of it. Requests to the HTTP API are validated against the
+/// contract before they reach the agent.
+///
+/// # Example
+/// ```text
+/// contract {
+///     request GetForecast(city: String, days: Int) -> String
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestContract {
+    pub request_type: RequestType,
+    pub parameters: Vec<Parameter>,
+    pub return_type: TypeInfo,
+    /// Doc comment preceding the declaration
+    pub doc: Option<String>,
+}
+
 // パラメータ定義
 #[derive(Debug, Clone, PartialEq)]
 pub struct Parameter {
     pub name: String,
     pub type_info: TypeInfo,
+    /// Constraint of the `where` clause, checked before the handler block runs
+    pub constraint: Option<ParameterConstraint>,
+}
+
+/// Declarative constraint on a handler parameter
+///
+/// ```text
+/// on request plan(city: String where len(city) > 0, days: Int where days in 1..30)
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParameterConstraint {
+    /// `where <condition>`, a Boolean expression over the parameter
+    Condition(Expression),
+    /// `where <name> in <start>..<end>`, both bounds inclusive
+    Range { start: Literal, end: Literal },
+}
+
+impl ParameterConstraint {
+    /// The constraint as a Boolean expression over the parameter `name`
+    pub fn condition(&self, name: &str) -> Expression {
+        match self {
+            ParameterConstraint::Condition(condition) => condition.clone(),
+            ParameterConstraint::Range { start, end } => {
+                let bound = |op, literal: &Literal| Expression::BinaryOp {
+                    op,
+                    left: Box::new(Expression::Variable(name.to_string())),
+                    right: Box::new(Expression::Literal(literal.clone())),
+                };
+                Expression::BinaryOp {
+                    op: BinaryOperator::And,
+                    left: Box::new(bound(BinaryOperator::GreaterThanEqual, start)),
+                    right: Box::new(bound(BinaryOperator::LessThanEqual, end)),
+                }
+            }
+        }
+    }
 }
 
 // 制約定義
@@ -433,6 +674,8 @@ pub struct Constraints {
     pub strictness: Option<f64>,
     pub stability: Option<f64>,
     pub latency: Option<u32>,
+    /// Transforms applied to the answer, see [`crate::eval::postprocess`]
+    pub postprocess: Vec<Literal>,
 }
 
 /// Type Information for the MicroAgent DSL
@@ -470,10 +713,18 @@ pub enum TypeInfo {
     Option(Box<TypeInfo>),
     Array(Box<TypeInfo>),
     Map(Box<TypeInfo>, Box<TypeInfo>),
+    /// `Set<T>`; `T` must be a [hashable](TypeInfo::is_hashable) type
+    Set(Box<TypeInfo>),
     Custom {
         name: String,
         fields: HashMap<String, FieldInfo>,
     },
+    /// `Name<T1, T2>`: a [`TypeDef`] applied to type arguments. The type checker
+    /// replaces it with the `Custom` type it instantiates.
+    Generic {
+        name: String,
+        args: Vec<TypeInfo>,
+    },
 }
 
 impl TypeInfo {
@@ -487,6 +738,38 @@ impl TypeInfo {
             _ => false,
         }
     }
+
+    /// JSON のオブジェクトや配列で表す型かどうか。Result と Option は成功時の型で判断する
+    pub fn is_structured(&self) -> bool {
+        const SCALARS: [&str; 7] = [
+            "Int",
+            "Float",
+            "String",
+            "Boolean",
+            "Duration",
+            "Timestamp",
+            "Any",
+        ];
+        match self {
+            Self::Simple(name) => !SCALARS.contains(&name.as_str()),
+            Self::Result { ok_type, .. } => ok_type.is_structured(),
+            Self::Option(inner) => inner.is_structured(),
+            Self::Array(_)
+            | Self::Map(..)
+            | Self::Set(_)
+            | Self::Custom { .. }
+            | Self::Generic { .. } => true,
+        }
+    }
+
+    /// Whether values of this type can be set elements: `Boolean`, `Int` or `String`.
+    /// `Any` is accepted here and checked when the set is built.
+    pub fn is_hashable(&self) -> bool {
+        matches!(
+            self,
+            Self::Simple(name) if ["Boolean", "Int", "String", "Any"].contains(&name.as_str())
+        )
+    }
 }
 
 impl fmt::Display for TypeInfo {
@@ -499,6 +782,7 @@ impl fmt::Display for TypeInfo {
             TypeInfo::Option(inner) => write!(f, "Option<{}>", inner),
             TypeInfo::Array(inner) => write!(f, "Array<{}>", inner),
             TypeInfo::Map(key, value) => write!(f, "Map<{}, {}>", key, value),
+            TypeInfo::Set(element) => write!(f, "Set<{}>", element),
             TypeInfo::Custom { name, fields } => {
                 write!(f, "{}", name)?;
 
@@ -512,6 +796,16 @@ impl fmt::Display for TypeInfo {
                 }
                 Ok(())
             }
+            TypeInfo::Generic { name, args } => {
+                write!(f, "{}<", name)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ">")
+            }
         }
     }
 }
@@ -522,6 +816,19 @@ impl From<&str> for TypeInfo {
     }
 }
 
+/// A type declared at the top of a file, generic over `type_params` if it has any
+///
+/// ```text
+/// type Pair<A, B> { first: A, second: B }
+/// type Point { x: Float, y: Float }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeDef {
+    pub name: String,
+    pub type_params: Vec<String>,
+    pub fields: HashMap<String, FieldInfo>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct FieldInfo {
     pub type_info: Option<TypeInfo>, // None の場合は型推論
@@ -571,12 +878,29 @@ pub enum Statement {
         target: Vec<Expression>,
         value: Expression,
     },
+    /// `let name = value` or `let name: Type = value`. The value is evaluated once,
+    /// when the statement runs, and every reference to `name` reuses it
+    Let {
+        name: String,
+        type_info: Option<TypeInfo>,
+        value: Expression,
+    },
     Return(Expression),
+    /// Partial response of an answer handler, published before the final `return`
+    Yield(Expression),
     // events
     Emit {
         event_type: EventType,
         parameters: Vec<Argument>,
         target: Option<String>, // Noneの場合はブロードキャスト
+        /// `emit!`: published at once instead of when the handler commits
+        immediate: bool,
+    },
+    /// `log.<level>("message", key: value, ...)`, see [`crate::agent_log`]
+    Log {
+        level: LogLevel,
+        message: Expression,
+        fields: Vec<(String, Expression)>,
     },
     // grouping
     Block(Statements),
@@ -590,6 +914,13 @@ pub enum Statement {
         then_block: Statements,
         else_block: Option<Statements>,
     },
+    /// `retry(times: 3, backoff: 2s) { ... }` runs the body again on a failure,
+    /// waiting `backoff` before the 2nd attempt and twice as long before each next one
+    Retry {
+        times: Expression,
+        backoff: Option<Expression>,
+        body: Statements,
+    },
 }
 
 // Extension trait for Statement building
@@ -613,6 +944,10 @@ use proc_macro2::TokenStream;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::agent_log::LogLevel;
+use crate::analyzer::limits::AnalysisLimitError;
+use crate::dsl_version::DslVersionError;
+use crate::message_catalog::{Diagnostic, codes};
 use crate::tokenizer::token::{TokenSpan, TokenizerError};
 use crate::type_checker::TypeCheckError;
 
@@ -718,7 +1053,10 @@ pub struct ThinkAttributes {
     // オプション項目
     pub model: Option<String>,
     pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
     pub max_tokens: Option<u32>,
+    /// In an answer handler, forward the output to the caller as it is generated
+    pub stream: bool,
 
     // リトライ設定
     pub retry: Option<RetryConfig>,
@@ -788,10 +1126,18 @@ pub enum Literal {
     Integer(i64),
     Float(f64),
     String(String),
+    /// Raw string (`r"..."`, `r#"..."#`). Evaluates to an ordinary string, but
+    /// `${...}` inside it is never interpolated.
+    RawString {
+        value: String,
+        hashes: usize,
+    },
     Boolean(bool),
     Duration(Duration),
     List(Vec<Literal>),
     Map(HashMap<String, Literal>),
+    /// `#{a, b}`. Duplicate elements are kept here and merged on evaluation.
+    Set(Vec<Literal>),
     Retry(RetryConfig),
     Null,
 }
@@ -802,6 +1148,7 @@ impl fmt::Display for Literal {
             Literal::Integer(i) => write!(f, "{}", i),
             Literal::Float(f_data) => write!(f, "{}", f_data),
             Literal::String(s) => write!(f, "{}", s),
+            Literal::RawString { value, .. } => write!(f, "{}", value),
             Literal::Boolean(b) => write!(f, "{}", b),
             Literal::Duration(d) => write!(f, "{:?}", d),
             Literal::List(literals) => {
@@ -824,6 +1171,16 @@ impl fmt::Display for Literal {
                 }
                 write!(f, "}}")
             }
+            Literal::Set(literals) => {
+                write!(f, "#{{")?;
+                for (i, literal) in literals.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", literal)?;
+                }
+                write!(f, "}}")
+            }
             Literal::Retry(retry_config) => write!(f, "{:?}", retry_config),
             Literal::Null => write!(f, "null"),
         }
@@ -844,6 +1201,8 @@ pub enum BinaryOperator {
     Subtract,
     Multiply,
     Divide,
+    /// Remainder, see the `experimental-operators` feature
+    Modulo,
     Equal,
     NotEqual,
     LessThan,
@@ -861,6 +1220,7 @@ impl fmt::Display for BinaryOperator {
             BinaryOperator::Subtract => write!(f, "-"),
             BinaryOperator::Multiply => write!(f, "*"),
             BinaryOperator::Divide => write!(f, "/"),
+            BinaryOperator::Modulo => write!(f, "%"),
             BinaryOperator::Equal => write!(f, "=="),
             BinaryOperator::NotEqual => write!(f, "!="),
             BinaryOperator::LessThan => write!(f, "<"),
@@ -916,6 +1276,7 @@ impl From<WorldDef> for (MicroAgentDef, EventsDef) {
                 name: "tick_interval".to_string(),
                 type_info: TypeInfo::Simple("Duration".to_string()),
                 initial_value: Some(Expression::Literal(Literal::Duration(config.tick_interval))),
+                doc: None,
             },
         );
         // world.config.max_agents
@@ -927,6 +1288,7 @@ impl From<WorldDef> for (MicroAgentDef, EventsDef) {
                 initial_value: Some(Expression::Literal(Literal::Integer(
                     config.max_agents as i64,
                 ))),
+                doc: None,
             },
         );
         // world.config.event_buffer_size
@@ -938,6 +1300,7 @@ impl From<WorldDef> for (MicroAgentDef, EventsDef) {
                 initial_value: Some(Expression::Literal(Literal::Integer(
                     config.event_buffer_size as i64,
                 ))),
+                doc: None,
             },
         );
         let agent = MicroAgentDef {
@@ -964,6 +1327,13 @@ impl From<WorldDef> for (MicroAgentDef, EventsDef) {
                     .collect(),
             }),
             lifecycle: None,
+            doc: None,
+            labels: Default::default(),
+            // world の persona は System 側で全エージェント共通の既定値として扱う
+            persona: None,
+            localized_personas: Default::default(),
+            contracts: vec![],
+            config: None,
         };
 
         (agent, world.events)
@@ -980,8 +1350,10 @@ pub enum ASTError {
     #[error("Parse error: {message}, {error} for {token_span:?}")]
     ParseError {
         message: String,
-        token_span: Option<TokenSpan>,
+        token_span: Option<Box<TokenSpan>>,
         error: String,
+        /// The parse error for localized rendering; `error` is its English text
+        diagnostic: Box<Diagnostic>,
     },
     #[error("AST not found: {0}")]
     ASTNotFound(String),
@@ -989,6 +1361,24 @@ pub enum ASTError {
     TokenizeError(#[from] TokenizerError),
     #[error("Type check error: {0}")]
     TypeCheckError(#[from] TypeCheckError),
+    #[error("Analysis limit exceeded: {0}")]
+    LimitExceeded(#[from] AnalysisLimitError),
+    #[error("DSL version error: {0}")]
+    DslVersion(#[from] DslVersionError),
+}
+
+impl ASTError {
+    /// The diagnostic of this error, rendered by a [`MessageCatalog`](crate::message_catalog::MessageCatalog)
+    pub fn diagnostic(&self) -> Diagnostic {
+        match self {
+            Self::ParseError { diagnostic, .. } => diagnostic.as_ref().clone(),
+            Self::ASTNotFound(name) => Diagnostic::new(codes::AST_NOT_FOUND).with_arg("name", name),
+            Self::TokenizeError(error) => error.diagnostic(),
+            Self::TypeCheckError(error) => error.diagnostic(),
+            Self::LimitExceeded(error) => error.diagnostic(),
+            Self::DslVersion(error) => error.diagnostic(),
+        }
+    }
 }
 
 pub type ASTResult<T> = Result<T, ASTError>;
@@ -1018,8 +1408,12 @@ mod tests {
                     event_name: "Tick".to_string(),
                     parameters: vec![],
                     block: HandlerBlock { statements: vec![] },
+                    doc: None,
                 }],
             },
+            persona: None,
+            schedules: vec![],
+            on_init: None,
         };
 
         let (agent, events) = world.into();
@@ -1042,6 +1436,7 @@ mod tests {
             event_type: EventType::Custom("TestEvent".into()),
             parameters: vec![],
             target: None,
+            immediate: false,
         };
 
         let handler = ErrorHandlerBlock {
@@ -1050,6 +1445,7 @@ mod tests {
                 event_type: EventType::Custom("TestEvent".into()),
                 parameters: vec![],
                 target: None,
+                immediate: false,
             }],
             control: None,
         };
@@ -52,9 +52,11 @@ use crate::{
     ASTError, ASTResult, AnswerDef, EventsDef, Expression, HandlerBlock, HandlersDef, Literal,
     MicroAgentDef, RequestHandler, RequestType, StateAccessPath, StateDef, StateVarDef, Statement,
     TypeInfo, WorldDef,
-    analyzer::{self, Parser},
+    analyzer::{self, Parser, limits},
     ast,
-    config::AgentConfig,
+    config::{AgentConfig, AnalysisLimits},
+    dsl_version,
+    message_catalog::{Diagnostic, codes},
     preprocessor::{self, Preprocessor},
     tokenizer::{
         self,
@@ -70,16 +72,25 @@ use crate::{
 #[derive(Debug, Clone, Default)]
 pub struct AstRegistry {
     asts: Arc<DashMap<String, Arc<MicroAgentDef>>>,
+    limits: AnalysisLimits,
 }
 
 impl AstRegistry {
+    /// Limits checked by [`create_ast_from_dsl`](Self::create_ast_from_dsl)
+    pub fn with_limits(mut self, limits: AnalysisLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
     /// Transforms a DSL string into an Abstract Syntax Tree (AST) representation.
     ///
     /// This method implements the complete parsing pipeline for KAIREI DSL:
     /// 1. **Tokenization**: Converts raw text into a sequence of tokens
     /// 2. **Preprocessing**: Normalizes and transforms tokens for consistent parsing
     /// 3. **Parsing**: Builds a hierarchical AST structure from the token stream
-    /// 4. **Type Checking**: Validates type correctness across the entire AST
+    /// 4. **Version Check**: Checks the declared DSL version and applies the
+    ///    compatibility shims of older ones (see [`crate::dsl_version`])
+    /// 5. **Type Checking**: Validates type correctness across the entire AST
     ///
     /// The parsing flow is:
     /// ```text
@@ -95,6 +106,8 @@ impl AstRegistry {
     ///
     /// # Errors
     /// * `ASTError::ParseError` - If the DSL cannot be parsed correctly
+    /// * `ASTError::LimitExceeded` - If the DSL exceeds the registry's [`AnalysisLimits`]
+    /// * `ASTError::DslVersion` - If the DSL declares a version this build cannot read
     /// * `ASTError::TypeError` - If type checking fails
     ///
     /// # Example
@@ -106,7 +119,7 @@ impl AstRegistry {
     /// let dsl = r#"
     ///     micro ExampleAgent {
     ///         state {
-    ///             counter: i64 = 0;
+    ///             counter: Int = 0;
     ///         }
     ///     }
     /// "#;
@@ -116,27 +129,17 @@ impl AstRegistry {
     /// # }
     /// ```
     pub async fn create_ast_from_dsl(&self, dsl: &str) -> ASTResult<ast::Root> {
-        // 1. Tokenization: Convert DSL string into tokens
-        let mut tokenizer = tokenizer::token::Tokenizer::new();
-        let tokens = tokenizer.tokenize(dsl).map_err(ASTError::from)?;
-
-        // 2. Preprocessing: Apply token transformations
-        let preprocessor = preprocessor::TokenPreprocessor::default();
-        let token_spans: Vec<TokenSpan> = preprocessor.process(tokens);
-        debug!("create_ast_from_dsl: token_spans: {:?}", token_spans);
-        let tokens: Vec<Token> = token_spans
-            .iter()
-            .map(|ts| ts.token.clone())
-            .collect::<Vec<Token>>();
-        debug!("create_ast_from_dsl: tokens: {:?}", tokens);
+        // 1-2. Tokenization and preprocessing
+        let (token_spans, tokens) = self.tokenize(dsl)?;
 
         // 3. Parsing: Convert tokens into AST structure
         let (pos, mut root) = analyzer::parsers::world::parse_root()
             .parse(tokens.as_slice(), 0)
             .map_err(|e: analyzer::ParseError| ASTError::ParseError {
                 message: "failed to parse DSL".to_string(),
-                token_span: token_spans.get(e.get_position()).cloned(),
+                token_span: token_spans.get(e.get_position()).cloned().map(Box::new),
                 error: e.to_string(),
+                diagnostic: Box::new(e.diagnostic()),
             })?;
         debug!("{:?}", root);
 
@@ -150,16 +153,77 @@ impl AstRegistry {
             );
             return Err(ASTError::ParseError {
                 message: "failed to parse DSL".to_string(),
-                token_span: token_spans.get(pos).cloned(),
+                token_span: token_spans.get(pos).cloned().map(Box::new),
                 error: "not all tokens were consumed".to_string(),
+                diagnostic: Box::new(Diagnostic::new(codes::PARSE_TRAILING_TOKENS)),
             });
         }
 
-        // 4. Type Checking: Validate type correctness in the AST
+        // 4. Limits: Reject ASTs too large to type check and evaluate safely
+        limits::check_root(&root, &self.limits)?;
+
+        // 5. Version: Reject newer DSL versions and rewrite older ones with shims
+        dsl_version::check_compatibility(&mut root)?;
+
+        // 6. Type Checking: Validate type correctness in the AST
         run_type_checker(&mut root).map_err(ASTError::from)?;
 
         Ok(root)
     }
+
+    /// Parses a single statement, such as an expression typed into a debug console.
+    ///
+    /// The statement goes through the same tokenizer, preprocessor and nesting limit
+    /// as [`create_ast_from_dsl`](Self::create_ast_from_dsl), but is not type checked:
+    /// its types depend on the scope it is evaluated in.
+    ///
+    /// # Errors
+    /// * `ASTError::ParseError` - If the source is not exactly one statement
+    /// * `ASTError::LimitExceeded` - If the source is nested too deeply
+    pub fn create_statement_from_dsl(&self, dsl: &str) -> ASTResult<Statement> {
+        let (token_spans, tokens) = self.tokenize(dsl)?;
+
+        let (pos, statement) = analyzer::parsers::statement::parse_statement()
+            .parse(tokens.as_slice(), 0)
+            .map_err(|e: analyzer::ParseError| ASTError::ParseError {
+                message: "failed to parse statement".to_string(),
+                token_span: token_spans.get(e.get_position()).cloned().map(Box::new),
+                error: e.to_string(),
+                diagnostic: Box::new(e.diagnostic()),
+            })?;
+
+        if pos != tokens.len() {
+            return Err(ASTError::ParseError {
+                message: "failed to parse statement".to_string(),
+                token_span: token_spans.get(pos).cloned().map(Box::new),
+                error: "not all tokens were consumed".to_string(),
+                diagnostic: Box::new(Diagnostic::new(codes::PARSE_TRAILING_TOKENS)),
+            });
+        }
+
+        Ok(statement)
+    }
+
+    /// Tokenizes and preprocesses `dsl`, rejecting nesting beyond the limits
+    fn tokenize(&self, dsl: &str) -> ASTResult<(Vec<TokenSpan>, Vec<Token>)> {
+        // 1. Tokenization: Convert DSL string into tokens
+        let mut tokenizer = tokenizer::token::Tokenizer::new();
+        let tokens = tokenizer.tokenize(dsl).map_err(ASTError::from)?;
+
+        // 2. Preprocessing: Apply token transformations
+        let preprocessor = preprocessor::TokenPreprocessor::default();
+        let token_spans: Vec<TokenSpan> = preprocessor.process(tokens);
+        // 深い入れ子でパーサーのスタックが溢れる前に弾く
+        limits::check_token_nesting(&token_spans, &self.limits)?;
+        debug!("tokenize: token_spans: {:?}", token_spans);
+        let tokens: Vec<Token> = token_spans
+            .iter()
+            .map(|ts| ts.token.clone())
+            .collect::<Vec<Token>>();
+        debug!("tokenize: tokens: {:?}", tokens);
+
+        Ok((token_spans, tokens))
+    }
     pub async fn register_agent_ast(
         &mut self,
         _agent_name: &str,
@@ -187,9 +251,12 @@ impl AstRegistry {
         WorldDef {
             name: "world".to_string(),
             policies: vec![],
+            persona: None,
             config: None,
             events: EventsDef { events: vec![] },
             handlers: HandlersDef { handlers: vec![] },
+            schedules: vec![],
+            on_init: None,
         }
     }
 
@@ -211,6 +278,7 @@ impl AstRegistry {
                             initial_value: Some(Expression::Literal(Literal::Boolean(
                                 config.enabled,
                             ))),
+                            doc: None,
                         },
                     );
                     vars.insert(
@@ -221,6 +289,7 @@ impl AstRegistry {
                             initial_value: Some(Expression::Literal(Literal::Integer(
                                 config.max_instances_per_agent as i64,
                             ))),
+                            doc: None,
                         },
                     );
                     vars
@@ -233,11 +302,14 @@ impl AstRegistry {
                     parameters: vec![],
                     return_type: TypeInfo::Simple("i64".to_string()),
                     constraints: None,
+                    requires: None,
                     block: HandlerBlock {
                         statements: vec![Statement::Return(Expression::StateAccess(
                             StateAccessPath(vec!["self".into(), "max_instances_per_agent".into()]),
                         ))],
                     },
+                    doc: None,
+                    cache: None,
                 }],
             }),
             ..Default::default()
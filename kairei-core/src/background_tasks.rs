@@ -0,0 +1,105 @@
+//! # Background Tasks
+//!
+//! Long-running loops started on behalf of a [`System`](crate::system::System) (event
+//! forwarding, native features, agent event loops) are spawned through
+//! [`BackgroundTasks`] instead of a bare `tokio::spawn`. Shutting the system down then
+//! cancels and awaits all of them, and dropping it without shutdown at least cancels
+//! them, so no task keeps the event bus or provider clients alive afterwards.
+//!
+//! Tasks that end on their own once their input channel closes (e.g. the prompt
+//! archive worker) may stay detached, but must say so where they are spawned.
+
+use std::future::Future;
+
+use tokio::task::JoinHandle;
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+
+/// A cancellable group of tracked tasks. Clones share the same group.
+#[derive(Debug, Clone, Default)]
+pub struct BackgroundTasks {
+    tracker: TaskTracker,
+    token: CancellationToken,
+}
+
+impl BackgroundTasks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Spawns `task` into the group. Once the group is cancelled the task is dropped at
+    /// its next await point; tasks spawned after cancellation finish immediately.
+    pub fn spawn<F>(&self, task: F) -> JoinHandle<()>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let token = self.token.clone();
+        self.tracker.spawn(async move {
+            tokio::select! {
+                _ = token.cancelled() => {}
+                _ = task => {}
+            }
+        })
+    }
+
+    /// Number of tasks that have not finished yet
+    pub fn len(&self) -> usize {
+        self.tracker.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tracker.is_empty()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Cancels all tasks without waiting for them, usable from `Drop`.
+    pub fn cancel(&self) {
+        self.tracker.close();
+        self.token.cancel();
+    }
+
+    /// Cancels all tasks and waits until every one of them has finished.
+    pub async fn shutdown(&self) {
+        self.cancel();
+        self.tracker.wait().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_shutdown_cancels_pending_tasks() {
+        let tasks = BackgroundTasks::new();
+        for _ in 0..3 {
+            tasks.spawn(std::future::pending());
+        }
+        tasks.spawn(async {});
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(tasks.len(), 3);
+
+        tokio::time::timeout(Duration::from_secs(1), tasks.shutdown())
+            .await
+            .unwrap();
+        assert!(tasks.is_empty());
+        assert!(tasks.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_spawn_after_cancel_finishes_immediately() {
+        let tasks = BackgroundTasks::new();
+        tasks.cancel();
+
+        let handle = tasks.spawn(std::future::pending());
+        tokio::time::timeout(Duration::from_secs(1), handle)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(tasks.is_empty());
+    }
+}
@@ -0,0 +1,290 @@
+//! # Agent Catalog
+//!
+//! The [`AgentCatalog`] lists the agents of a [`System`](crate::system::System) and the
+//! requests their `answer` handlers accept, so that an orchestrating agent can choose a
+//! delegate at runtime instead of naming it in the DSL.
+//!
+//! The system keeps the catalog current: an agent is listed when it is registered or
+//! created by scaling up, and removed when scaled down. Registering the AST of a listed
+//! agent again refreshes the signatures of the agent and of its scaled instances.
+//! Suspended agents stay listed, since a request wakes them. Agents without request
+//! handlers are not listed.
+//!
+//! Handlers read the catalog with the `list_agents()` and `agent_requests(name)`
+//! built-ins, the [`CatalogPlugin`](crate::provider::plugins::catalog::CatalogPlugin)
+//! adds it to think prompts, and the HTTP API serves it.
+
+use std::{collections::BTreeMap, fmt, sync::RwLock};
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    ast::{MicroAgentDef, Parameter, RequestContract, RequestHandler, RequestType},
+    eval::expression::Value,
+};
+
+/// A parameter of a request, with its DSL type
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ParameterSignature {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_name: String,
+}
+
+impl ParameterSignature {
+    fn from_parameters(parameters: &[Parameter]) -> Vec<Self> {
+        parameters
+            .iter()
+            .map(|p| Self {
+                name: p.name.clone(),
+                type_name: p.type_info.to_string(),
+            })
+            .collect()
+    }
+}
+
+/// A request an agent answers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RequestSignature {
+    pub name: String,
+    pub parameters: Vec<ParameterSignature>,
+    pub return_type: String,
+    /// Doc comment of the handler
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc: Option<String>,
+}
+
+impl RequestSignature {
+    pub fn from_handler(handler: &RequestHandler) -> Self {
+        Self {
+            name: handler.request_type.to_string(),
+            parameters: ParameterSignature::from_parameters(&handler.parameters),
+            return_type: handler.return_type.to_string(),
+            doc: handler.doc.clone(),
+        }
+    }
+
+    pub fn from_contract(contract: &RequestContract) -> Self {
+        Self {
+            name: contract.request_type.to_string(),
+            parameters: ParameterSignature::from_parameters(&contract.parameters),
+            return_type: contract.return_type.to_string(),
+            doc: contract.doc.clone(),
+        }
+    }
+
+    /// The descriptor returned by `agent_requests(name)`:
+    /// `{ name, parameters: [{ name, type }], return_type }`
+    pub fn to_value(&self) -> Value {
+        let parameters = self
+            .parameters
+            .iter()
+            .map(|p| {
+                Value::Map(
+                    [
+                        ("name".to_string(), Value::String(p.name.clone())),
+                        ("type".to_string(), Value::String(p.type_name.clone())),
+                    ]
+                    .into(),
+                )
+            })
+            .collect();
+        Value::Map(
+            [
+                ("name".to_string(), Value::String(self.name.clone())),
+                ("parameters".to_string(), Value::List(parameters)),
+                (
+                    "return_type".to_string(),
+                    Value::String(self.return_type.clone()),
+                ),
+            ]
+            .into(),
+        )
+    }
+}
+
+/// `GetForecast(city: String) -> Result<String, Error>`
+impl fmt::Display for RequestSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}(", self.name)?;
+        for (i, parameter) in self.parameters.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}: {}", parameter.name, parameter.type_name)?;
+        }
+        write!(f, ") -> {}", self.return_type)
+    }
+}
+
+/// An agent and the requests it answers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct AgentEntry {
+    pub name: String,
+    pub requests: Vec<RequestSignature>,
+}
+
+#[derive(Debug, Clone)]
+struct Listed {
+    // スケールで作られたインスタンスは元の AST 名を持つ
+    ast_name: String,
+    requests: Vec<RequestSignature>,
+}
+
+/// The request signatures of the agents of a system, ordered by agent name
+#[derive(Debug, Default)]
+pub struct AgentCatalog {
+    agents: RwLock<BTreeMap<String, Listed>>,
+}
+
+impl AgentCatalog {
+    /// List `agent_name`, created from the AST registered as `ast_name`
+    pub fn insert(&self, agent_name: &str, ast_name: &str, agent_def: &MicroAgentDef) {
+        self.agents.write().unwrap().insert(
+            agent_name.to_string(),
+            Listed {
+                ast_name: ast_name.to_string(),
+                requests: Self::signatures(agent_def),
+            },
+        );
+    }
+
+    pub fn remove(&self, agent_name: &str) {
+        self.agents.write().unwrap().remove(agent_name);
+    }
+
+    /// Refresh the agents created from `ast_name` after its AST was registered again
+    pub fn update_ast(&self, ast_name: &str, agent_def: &MicroAgentDef) {
+        let requests = Self::signatures(agent_def);
+        for listed in self
+            .agents
+            .write()
+            .unwrap()
+            .values_mut()
+            .filter(|listed| listed.ast_name == ast_name)
+        {
+            listed.requests = requests.clone();
+        }
+    }
+
+    pub fn agent_names(&self) -> Vec<String> {
+        self.agents
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, listed)| !listed.requests.is_empty())
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Requests answered by `agent_name`; empty for agents not listed
+    pub fn requests(&self, agent_name: &str) -> Vec<RequestSignature> {
+        self.agents
+            .read()
+            .unwrap()
+            .get(agent_name)
+            .map(|listed| listed.requests.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn entries(&self) -> Vec<AgentEntry> {
+        self.agents
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, listed)| !listed.requests.is_empty())
+            .map(|(name, listed)| AgentEntry {
+                name: name.clone(),
+                requests: listed.requests.clone(),
+            })
+            .collect()
+    }
+
+    fn signatures(agent_def: &MicroAgentDef) -> Vec<RequestSignature> {
+        agent_def
+            .answer
+            .iter()
+            .flat_map(|answer| &answer.handlers)
+            // catch-all は個別のリクエストとして公開しない
+            .filter(|handler| handler.request_type != RequestType::Fallback)
+            .map(RequestSignature::from_handler)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{AnswerDef, HandlerBlock, Parameter, TypeInfo};
+
+    fn agent_def(requests: &[&str]) -> MicroAgentDef {
+        MicroAgentDef {
+            answer: Some(AnswerDef {
+                handlers: requests
+                    .iter()
+                    .map(|request| RequestHandler {
+                        request_type: (*request).into(),
+                        parameters: vec![Parameter {
+                            name: "city".to_string(),
+                            type_info: TypeInfo::Simple("String".to_string()),
+                            constraint: None,
+                        }],
+                        return_type: TypeInfo::Result {
+                            ok_type: Box::new(TypeInfo::Simple("String".to_string())),
+                            err_type: Box::new(TypeInfo::Simple("Error".to_string())),
+                        },
+                        constraints: None,
+                        requires: None,
+                        block: HandlerBlock { statements: vec![] },
+                        doc: None,
+                        cache: None,
+                    })
+                    .collect(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_catalog_follows_scaling_and_reload() {
+        let catalog = AgentCatalog::default();
+        catalog.insert("Weather", "Weather", &agent_def(&["GetForecast"]));
+        catalog.insert("Weather-1", "Weather", &agent_def(&["GetForecast"]));
+        // リクエストを受けないエージェントは載らない
+        catalog.insert("Ticker", "Ticker", &agent_def(&[]));
+        assert_eq!(catalog.agent_names(), vec!["Weather", "Weather-1"]);
+        assert_eq!(
+            catalog.requests("Weather")[0].to_string(),
+            "GetForecast(city: String) -> Result<String, Error>"
+        );
+
+        catalog.update_ast("Weather", &agent_def(&["GetForecast", "GetAlerts"]));
+        assert_eq!(catalog.requests("Weather-1").len(), 2);
+
+        catalog.remove("Weather-1");
+        assert_eq!(catalog.agent_names(), vec!["Weather"]);
+        assert!(catalog.requests("Unknown").is_empty());
+    }
+
+    #[test]
+    fn test_request_descriptor() {
+        let signature = RequestSignature::from_handler(
+            &agent_def(&["GetForecast"]).answer.unwrap().handlers[0],
+        );
+        let Value::Map(descriptor) = signature.to_value() else {
+            panic!("descriptor should be a map");
+        };
+        assert_eq!(descriptor["name"], Value::String("GetForecast".to_string()));
+        assert_eq!(
+            descriptor["parameters"],
+            Value::List(vec![Value::Map(
+                [
+                    ("name".to_string(), Value::String("city".to_string())),
+                    ("type".to_string(), Value::String("String".to_string())),
+                ]
+                .into()
+            )])
+        );
+    }
+}
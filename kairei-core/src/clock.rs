@@ -0,0 +1,88 @@
+//! Time source for runtime timeouts that tests can move by hand.
+
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+pub trait Clock: Send + Sync + fmt::Debug {
+    fn now(&self) -> Instant;
+
+    /// Wall-clock time, for schedules that fire at a time of day
+    fn utc_now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    /// Waits until `duration` has passed on this clock
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// The real monotonic clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that stands still until advanced. Clones share the same time.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    start: Instant,
+    start_utc: DateTime<Utc>,
+    elapsed: Arc<Mutex<Duration>>,
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        Self::starting_at(Utc::now())
+    }
+
+    /// A clock whose wall-clock time starts at `start_utc`
+    pub fn starting_at(start_utc: DateTime<Utc>) -> Self {
+        Self {
+            start: Instant::now(),
+            start_utc,
+            elapsed: Arc::new(Mutex::new(Duration::ZERO)),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.elapsed.lock().unwrap() += by;
+    }
+
+    /// Time advanced since the clock was created
+    pub fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().unwrap()
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.start + *self.elapsed.lock().unwrap()
+    }
+
+    fn utc_now(&self) -> DateTime<Utc> {
+        self.start_utc
+            + chrono::Duration::from_std(self.elapsed()).expect("mock clock advanced out of range")
+    }
+
+    // 待たずに時刻だけ進める
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        self.advance(duration);
+        Box::pin(std::future::ready(()))
+    }
+}
@@ -3,8 +3,13 @@ use std::{collections::HashMap, fs::File, io::BufReader, path::Path, time::Durat
 use utoipa::ToSchema;
 
 use crate::{
-    Error, InternalResult, expression::Value, provider::config::plugins::SharedMemoryConfig,
-    provider::provider::ProviderType, type_checker::TypeCheckError,
+    Error, InternalResult,
+    agent_log::LogLevel,
+    expression::Value,
+    message_catalog::Locale,
+    provider::config::plugins::{PersistentSharedMemoryConfig, SharedMemoryConfig},
+    provider::provider::ProviderType,
+    type_checker::TypeCheckError,
 };
 use std::convert::TryFrom;
 
@@ -28,6 +33,16 @@ pub struct SystemConfig {
     #[schema(value_type = u64, pattern = "uint64 as milliseconds")]
     pub request_timeout: Duration,
 
+    /// Recording of the events sent with `System::send_event`, replayed by
+    /// `System::initialize`. Off unless `enabled` is set.
+    #[serde(default)]
+    pub event_journal: EventJournalConfig,
+
+    /// Recording of the observe and react handler runs of the agents, stepped
+    /// through by debug sessions. Off unless `enabled` is set.
+    #[serde(default)]
+    pub handler_recording: HandlerRecordingConfig,
+
     #[serde(default)]
     pub agent_config: AgentConfig,
 
@@ -36,6 +51,104 @@ pub struct SystemConfig {
 
     #[serde(default)]
     pub provider_configs: ProviderConfigs,
+
+    /// World-level default prompt preamble placed ahead of every think prompt.
+    /// A `persona` in the world DSL replaces it; an agent `persona` overrides both.
+    #[serde(default)]
+    pub prompt_preamble: Option<String>,
+
+    /// How agent requests share the runtime. Defaults to handling every request
+    /// inline on its agent's own task.
+    #[serde(default)]
+    pub scheduler: SchedulerConfig,
+
+    /// Persistence of pending agent requests across restarts. Off unless
+    /// `base_dir` is set and the agent is listed.
+    #[serde(default)]
+    pub request_queue: RequestQueueConfig,
+
+    /// LLM call budgets keyed by agent name. Agents not listed are unlimited.
+    #[serde(default)]
+    pub llm_budgets: HashMap<String, LlmBudgetConfig>,
+
+    /// Evaluator limits keyed by agent name, replacing `agent_config.eval_limits`
+    /// for the agents listed
+    #[serde(default)]
+    pub eval_limits: HashMap<String, EvalLimits>,
+
+    /// Execution guardrails keyed by agent name, replacing
+    /// `agent_config.guardrails` for the agents listed
+    #[serde(default)]
+    pub guardrails: HashMap<String, ExecutionGuardrails>,
+
+    /// Suspension of user agents that receive no request for a while. Off unless
+    /// `idle_timeout` is set.
+    #[serde(default)]
+    pub idle_eviction: IdleEvictionConfig,
+
+    /// Names of the handler secrets each agent may read with `secret("name")`, keyed
+    /// by agent name. Agents not listed may read none.
+    #[serde(default)]
+    pub secret_grants: HashMap<String, Vec<String>>,
+
+    /// Size limits on parsed DSL, checked before an AST is accepted
+    #[serde(default)]
+    pub analysis_limits: AnalysisLimits,
+
+    /// Time limit of an expression evaluated with `System::eval_expression`,
+    /// and of a handler run with `System::test_handler`
+    #[serde(default = "default_debug_eval_timeout", with = "duration_ms")]
+    #[schema(value_type = u64, pattern = "uint64 as milliseconds")]
+    pub debug_eval_timeout: Duration,
+
+    /// Checks of provider credentials against their APIs during
+    /// `System::initialize`. Off unless `enabled` is set.
+    #[serde(default)]
+    pub validate_credentials: CredentialValidationConfig,
+
+    /// Scan of this config and the DSL source for leaked secrets before startup
+    #[serde(default)]
+    pub secret_scan: SecretScanConfig,
+
+    /// Language of parser and type checker diagnostics rendered by
+    /// `System::render_error`
+    #[serde(default)]
+    pub locale: Locale,
+
+    /// Values of the `config { ... }` parameters agents declare, keyed by agent
+    /// name. Checked against the declared types when the agent is built.
+    #[serde(default)]
+    pub agent_configs: HashMap<String, AgentConfigValues>,
+
+    /// Handling of the `log.<level>(...)` statements of each agent, keyed by agent
+    /// name. Agents not listed log at `info` and above without publishing events.
+    #[serde(default)]
+    pub agent_logs: HashMap<String, AgentLogConfig>,
+
+    /// Agents in conversational mode, keyed by agent name. Their think calls see the
+    /// prior exchanges of the same session. Agents not listed answer each request
+    /// on its own.
+    #[serde(default)]
+    pub conversations: HashMap<String, ConversationConfig>,
+
+    /// Storage of the counters and rate limiters agents share through
+    /// `increment(...)` and `try_acquire(...)`. Kept in memory when unset.
+    #[serde(default)]
+    pub shared_counters: Option<PersistentSharedMemoryConfig>,
+
+    /// Handling of observe and react handlers for event types the event registry
+    /// does not know, checked by `System::initialize`
+    #[serde(default)]
+    pub event_validation: EventValidationMode,
+
+    /// Responses kept for the answer handlers declared with `@cache(...)`
+    #[serde(default)]
+    pub response_cache: ResponseCacheConfig,
+
+    /// Acknowledgement and retries of events published with
+    /// `EventBus::publish_critical`
+    #[serde(default)]
+    pub critical_delivery: CriticalDeliveryConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
@@ -48,6 +161,447 @@ pub struct AgentConfig {
 
     #[serde(default)]
     pub monitor: Option<MonitorConfig>,
+
+    /// Cap on the agent's think calls; unlimited when unset
+    #[serde(default)]
+    pub llm_budget: Option<LlmBudgetConfig>,
+
+    /// Limits on a single handler execution
+    #[serde(default)]
+    pub guardrails: ExecutionGuardrails,
+
+    /// Limits on the depth and the values of a single handler execution
+    #[serde(default)]
+    pub eval_limits: EvalLimits,
+
+    /// What happens to a handler whose `requires` clause does not hold
+    #[serde(default)]
+    pub preconditions: PreconditionMode,
+
+    /// Default locale of the agent's prompts and responses, a language tag such as
+    /// `ja`. A `locale` parameter of the agent's `config` block overrides it, and a
+    /// request's locale overrides both; unsupported tags fall back to English.
+    #[serde(default)]
+    pub locale: Option<String>,
+}
+
+/// Handling of a handler whose `requires` precondition evaluates to false
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PreconditionMode {
+    /// The handler fails with a precondition error; a request is answered with it
+    #[default]
+    Fail,
+    /// The handler block does not run; a request is answered with no value
+    Skip,
+}
+
+/// Handling of an observe or react handler whose event type is not registered.
+/// Custom events are always accepted, as are the `StateUpdated` events of state
+/// variables the loaded agents declare.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EventValidationMode {
+    /// Initialization fails, listing every unregistered event type
+    Strict,
+    /// A warning is logged for each unregistered event type
+    #[default]
+    Lenient,
+}
+
+/// Limits on a single handler execution. Exceeding one fails the handler with an
+/// error that `onFail` can catch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ExecutionGuardrails {
+    #[serde(default = "default_max_think_calls")]
+    pub max_think_calls: u32,
+
+    /// Prompt plus completion tokens, as reported by the provider
+    #[serde(default = "default_max_execution_tokens")]
+    pub max_tokens: u64,
+
+    /// Requests sent to other agents
+    #[serde(default = "default_max_sub_requests")]
+    pub max_sub_requests: u32,
+}
+
+impl Default for ExecutionGuardrails {
+    fn default() -> Self {
+        Self {
+            max_think_calls: default_max_think_calls(),
+            max_tokens: default_max_execution_tokens(),
+            max_sub_requests: default_max_sub_requests(),
+        }
+    }
+}
+
+/// Resource limits of the evaluator for a single handler execution. Exceeding one
+/// fails the handler with an error that `onFail` can catch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct EvalLimits {
+    /// Expressions and statements nested within each other
+    #[serde(default = "default_max_eval_depth")]
+    pub max_depth: u32,
+
+    /// Bytes of a string, or elements of a list, map, set or tuple, that one
+    /// expression creates
+    #[serde(default = "default_max_value_size")]
+    pub max_value_size: u64,
+
+    /// Estimated bytes of all the values the execution creates
+    #[serde(default = "default_max_allocation_bytes")]
+    pub max_allocation_bytes: u64,
+}
+
+impl Default for EvalLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: default_max_eval_depth(),
+            max_value_size: default_max_value_size(),
+            max_allocation_bytes: default_max_allocation_bytes(),
+        }
+    }
+}
+
+/// At most `max_calls` think calls per `window`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct LlmBudgetConfig {
+    pub max_calls: u32,
+
+    #[serde(with = "duration_ms")]
+    #[schema(value_type = u64, pattern = "uint64 as milliseconds")]
+    pub window: Duration,
+}
+
+/// Scheduling policy for answer handlers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SchedulingPolicy {
+    /// Each agent handles its requests one by one on its own task
+    #[default]
+    Inline,
+    /// Requests run on a shared worker pool in arrival order
+    Fifo,
+    /// Requests run on a shared worker pool, taking one request per agent in turn
+    RoundRobin,
+    /// Like `RoundRobin`, but an agent takes up to its weight in requests per turn
+    WeightedFair,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SchedulerConfig {
+    #[serde(default)]
+    pub policy: SchedulingPolicy,
+
+    /// Worker pool size; ignored by `Inline`
+    #[serde(default = "default_scheduler_workers")]
+    pub workers: usize,
+
+    /// Requests per turn for `WeightedFair`, keyed by agent name (default 1)
+    #[serde(default)]
+    pub weights: HashMap<String, u32>,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            policy: SchedulingPolicy::default(),
+            workers: default_scheduler_workers(),
+            weights: HashMap::new(),
+        }
+    }
+}
+
+/// When an enqueued request is written to storage
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueDurability {
+    /// Write before acknowledging each request; concurrent enqueues share a write
+    #[default]
+    Immediate,
+    /// Wait `batch_window` before writing, so a burst of requests shares one write
+    Batched,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RequestQueueConfig {
+    /// Directory holding one queue file per agent
+    #[serde(default)]
+    pub base_dir: Option<String>,
+
+    /// Names of the agents whose pending requests are persisted
+    #[serde(default)]
+    pub agents: Vec<String>,
+
+    #[serde(default)]
+    pub durability: QueueDurability,
+
+    /// Write delay for `Batched`
+    #[serde(default = "default_queue_batch_window", with = "duration_ms")]
+    #[schema(value_type = u64, pattern = "uint64 as milliseconds")]
+    pub batch_window: Duration,
+}
+
+impl RequestQueueConfig {
+    /// Whether requests for `agent_name` are persisted
+    pub fn persists(&self, agent_name: &str) -> bool {
+        self.base_dir.is_some() && self.agents.iter().any(|a| a == agent_name)
+    }
+}
+
+/// 一定時間リクエストのないユーザーエージェントの退避
+///
+/// An agent without a request for `idle_timeout` is suspended: its state is saved and
+/// it is removed from memory. The next request to it restores it first.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct IdleEvictionConfig {
+    /// Idle time before an agent is suspended; eviction is off when unset
+    #[serde(default, with = "option_duration_ms")]
+    #[schema(value_type = Option<u64>, pattern = "uint64 as milliseconds")]
+    pub idle_timeout: Option<Duration>,
+
+    /// How often agents are checked for idleness
+    #[serde(default = "default_idle_check_interval", with = "duration_ms")]
+    #[schema(value_type = u64, pattern = "uint64 as milliseconds")]
+    pub check_interval: Duration,
+
+    /// Directory holding the state of suspended agents; kept in memory when unset
+    #[serde(default)]
+    pub base_dir: Option<String>,
+}
+
+/// `@cache(...)` を宣言した answer ハンドラの応答キャッシュ
+///
+/// Each agent keeps at most `max_entries_per_agent` responses; storing one more
+/// evicts the one used least recently. The cache of an agent is emptied when the
+/// agent is reloaded.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct ResponseCacheConfig {
+    #[serde(default = "default_response_cache_max_entries")]
+    pub max_entries_per_agent: usize,
+
+    /// Storage of the responses. Kept in memory when unset; a persistent one keeps
+    /// them across restarts, so that a request recovered by the [`RequestQueueConfig`]
+    /// queue is answered from the cache instead of running its handler again.
+    #[serde(default)]
+    pub memory: Option<PersistentSharedMemoryConfig>,
+}
+
+impl Default for ResponseCacheConfig {
+    fn default() -> Self {
+        Self {
+            max_entries_per_agent: default_response_cache_max_entries(),
+            memory: None,
+        }
+    }
+}
+
+/// 重要イベントの配信確認と再送
+///
+/// A critical event is delivered again every `retry_interval` to the consumers that
+/// have not acknowledged it. After `max_attempts` deliveries the failure is escalated
+/// and the retries stop. See [`crate::event::critical`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct CriticalDeliveryConfig {
+    #[serde(default = "default_critical_retry_interval", with = "duration_ms")]
+    #[schema(value_type = u64, pattern = "uint64 as milliseconds")]
+    pub retry_interval: Duration,
+
+    #[serde(default = "default_critical_max_attempts")]
+    pub max_attempts: u32,
+
+    /// Time after which a delivery receipt resolves without every acknowledgement
+    #[serde(default = "default_critical_deadline", with = "duration_ms")]
+    #[schema(value_type = u64, pattern = "uint64 as milliseconds")]
+    pub deadline: Duration,
+
+    /// Unacknowledged critical events held at once; publishing one more fails
+    #[serde(default = "default_critical_max_pending")]
+    pub max_pending: usize,
+
+    /// Directory mirroring the unacknowledged critical events; kept in memory when unset
+    #[serde(default)]
+    pub base_dir: Option<String>,
+}
+
+impl Default for CriticalDeliveryConfig {
+    fn default() -> Self {
+        Self {
+            retry_interval: default_critical_retry_interval(),
+            max_attempts: default_critical_max_attempts(),
+            deadline: default_critical_deadline(),
+            max_pending: default_critical_max_pending(),
+            base_dir: None,
+        }
+    }
+}
+
+/// DSL の規模の上限
+///
+/// Guards the parser, type checker and evaluator against pathologically large ASTs.
+/// Statements are counted with the statements nested in blocks; handlers include
+/// lifecycle handlers. The nesting depth counts nested expressions and, before parsing,
+/// nested brackets.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct AnalysisLimits {
+    #[serde(default = "default_max_statements_per_handler")]
+    pub max_statements_per_handler: usize,
+
+    #[serde(default = "default_max_handlers_per_agent")]
+    pub max_handlers_per_agent: usize,
+
+    #[serde(default = "default_max_nesting_depth")]
+    pub max_nesting_depth: usize,
+}
+
+/// 起動時のプロバイダー認証情報の検証
+///
+/// Each provider is asked whether its API accepts its credentials. The result is
+/// published as a `ProviderStatusUpdated` event and shown in the system status.
+/// Invalid credentials only log a warning unless the provider is listed in `required`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct CredentialValidationConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Providers whose credentials must be valid for the system to start
+    #[serde(default)]
+    pub required: Vec<String>,
+}
+
+/// エージェントの設定値
+///
+/// Supplies the parameters an agent declares in its `config { ... }` block, see
+/// [`crate::config_values`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct AgentConfigValues {
+    /// JSON values keyed by parameter name
+    #[serde(default)]
+    pub values: HashMap<String, serde_json::Value>,
+
+    /// Allow `System::update_agent_config` to replace the values while the agent runs
+    #[serde(default)]
+    pub hot_reload: bool,
+}
+
+/// Handling of an agent's `log.<level>(...)` statements, see [`crate::agent_log`]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct AgentLogConfig {
+    /// Logs below this level are discarded
+    #[serde(default)]
+    pub level: LogLevel,
+
+    /// Also publish the logs as `agent_log` system events
+    #[serde(default)]
+    pub publish_events: bool,
+
+    /// Events published per second; further logs only go to tracing
+    #[serde(default = "default_max_log_events_per_second")]
+    pub max_events_per_second: u32,
+
+    /// Bytes of the message and fields kept in an event
+    #[serde(default = "default_max_log_event_bytes")]
+    pub max_event_bytes: usize,
+
+    /// Recent logs kept in memory for the agent's logs endpoint, the oldest dropped
+    /// first; 0 keeps none
+    #[serde(default = "default_recent_logs")]
+    pub recent_logs: usize,
+}
+
+impl Default for AgentLogConfig {
+    fn default() -> Self {
+        Self {
+            level: LogLevel::default(),
+            publish_events: false,
+            max_events_per_second: default_max_log_events_per_second(),
+            max_event_bytes: default_max_log_event_bytes(),
+            recent_logs: default_recent_logs(),
+        }
+    }
+}
+
+/// History kept for an agent in conversational mode. When a new exchange exceeds
+/// either limit, the oldest exchanges are dropped.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct ConversationConfig {
+    /// Exchanges kept, each a request and its answer
+    #[serde(default = "default_conversation_max_turns")]
+    pub max_turns: usize,
+
+    /// Estimated tokens of the kept exchanges, at about 4 characters per token;
+    /// unlimited when unset
+    #[serde(default)]
+    pub max_tokens: Option<usize>,
+}
+
+impl Default for ConversationConfig {
+    fn default() -> Self {
+        Self {
+            max_turns: default_conversation_max_turns(),
+            max_tokens: None,
+        }
+    }
+}
+
+/// 漏洩したシークレットの検出
+///
+/// Values of the system config and the DSL source matching one of `patterns` stop
+/// the system from starting. Secrets belong in the secret config, which is not scanned.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct SecretScanConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// Regular expressions of secret formats, see
+    /// [`DEFAULT_SECRET_PATTERNS`](crate::secret_scan::DEFAULT_SECRET_PATTERNS)
+    #[serde(default = "default_secret_patterns")]
+    pub patterns: Vec<String>,
+
+    /// Start anyway, logging a warning for each finding
+    #[serde(default)]
+    pub warn_only: bool,
+}
+
+impl Default for SecretScanConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            patterns: default_secret_patterns(),
+            warn_only: false,
+        }
+    }
+}
+
+impl Default for AnalysisLimits {
+    fn default() -> Self {
+        Self {
+            max_statements_per_handler: default_max_statements_per_handler(),
+            max_handlers_per_agent: default_max_handlers_per_agent(),
+            max_nesting_depth: default_max_nesting_depth(),
+        }
+    }
+}
+
+impl Default for IdleEvictionConfig {
+    fn default() -> Self {
+        Self {
+            idle_timeout: None,
+            check_interval: default_idle_check_interval(),
+            base_dir: None,
+        }
+    }
+}
+
+impl Default for RequestQueueConfig {
+    fn default() -> Self {
+        Self {
+            base_dir: None,
+            agents: vec![],
+            durability: QueueDurability::default(),
+            batch_window: default_queue_batch_window(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -58,6 +612,13 @@ pub struct ContextConfig {
     #[serde(default = "default_request_timeout", with = "duration_ms")]
     #[schema(value_type = u64, pattern = "uint64 as milliseconds")]
     pub request_timeout: Duration,
+    /// Retries `retry(...)` may spend across a chain of agent requests, counted
+    /// from the handler that starts the chain. Unlimited when unset.
+    #[serde(default)]
+    pub retry_budget: Option<u32>,
+    /// Requests delivered again are answered with the original response
+    #[serde(default)]
+    pub request_dedup: RequestDedupConfig,
 }
 
 impl Default for ContextConfig {
@@ -65,6 +626,31 @@ impl Default for ContextConfig {
         Self {
             access_timeout: default_access_timeout(),
             request_timeout: default_request_timeout(),
+            retry_budget: None,
+            request_dedup: RequestDedupConfig::default(),
+        }
+    }
+}
+
+/// 応答側で同じリクエストの再送をまとめる設定
+///
+/// A request with the requester and request ID of one still running, or answered in
+/// the last `window`, is not run again, see [`crate::event::request_manager::DedupWindow`]. At most
+/// `max_entries` requests are remembered. A zero `window` turns it off.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct RequestDedupConfig {
+    #[serde(default = "default_request_dedup_window", with = "duration_ms")]
+    #[schema(value_type = u64, pattern = "uint64 as milliseconds")]
+    pub window: Duration,
+    #[serde(default = "default_request_dedup_max_entries")]
+    pub max_entries: usize,
+}
+
+impl Default for RequestDedupConfig {
+    fn default() -> Self {
+        Self {
+            window: default_request_dedup_window(),
+            max_entries: default_request_dedup_max_entries(),
         }
     }
 }
@@ -116,6 +702,60 @@ impl Default for MonitorConfig {
     }
 }
 
+/// 送られたイベントの記録と起動時の再生
+///
+/// The custom events sent with `System::send_event` are written to the journal.
+/// The next run replays them to the observe and react handlers of its initial user
+/// agents before they handle new events. See [`crate::event::journal`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct EventJournalConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Whether the journaled events are replayed on startup; when unset the journal
+    /// is only written
+    #[serde(default = "default_true")]
+    pub replay_on_startup: bool,
+
+    /// Directory of the journal; kept in memory when unset
+    #[serde(default)]
+    pub base_dir: Option<String>,
+}
+
+impl Default for EventJournalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            replay_on_startup: true,
+            base_dir: None,
+        }
+    }
+}
+
+/// ハンドラ実行の記録
+///
+/// Each observe and react handler run is recorded with the state it started from,
+/// the outputs of its provider calls and the changes of each of its statements. The
+/// last `max_recordings` runs of the system are kept in memory. See
+/// [`crate::eval::recording`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct HandlerRecordingConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    #[serde(default = "default_max_recordings")]
+    pub max_recordings: usize,
+}
+
+impl Default for HandlerRecordingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_recordings: default_max_recordings(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct NativeFeatureConfig {
     #[serde(default = "default_shutdown_timeout", with = "duration_ms")]
@@ -127,6 +767,17 @@ pub struct NativeFeatureConfig {
 
     #[serde(default = "default_metrics_config")]
     pub metrics: Option<MetricsConfig>,
+
+    /// Forwarding of events to and from another System. Off unless set and enabled.
+    #[serde(default)]
+    pub remote_bridge: Option<RemoteBridgeConfig>,
+
+    #[serde(default)]
+    pub broker_bridge: Option<BrokerBridgeConfig>,
+
+    /// Firing of the `schedule` declarations of the world
+    #[serde(default)]
+    pub schedules: Option<ScheduleConfig>,
 }
 
 impl Default for NativeFeatureConfig {
@@ -135,6 +786,9 @@ impl Default for NativeFeatureConfig {
             shutdown_timeout: default_shutdown_timeout(),
             ticker: default_ticker_config(),
             metrics: default_metrics_config(),
+            remote_bridge: None,
+            broker_bridge: None,
+            schedules: None,
         }
     }
 }
@@ -144,37 +798,240 @@ pub struct TickerConfig {
     #[serde(default = "default_true")]
     pub enabled: bool,
 
-    #[serde(default = "default_tick_interval", with = "duration_ms")]
+    #[serde(default = "default_tick_interval", with = "duration_ms")]
+    #[schema(value_type = u64, pattern = "uint64 as milliseconds")]
+    pub tick_interval: Duration,
+}
+
+impl Default for TickerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_true(),
+            tick_interval: default_tick_interval(),
+        }
+    }
+}
+
+// metricsの設定
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MetricsConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    #[serde(default = "default_metrics_tick_interval")]
+    pub metrics_interval: usize,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_true(),
+            metrics_interval: default_metrics_tick_interval(),
+        }
+    }
+}
+
+/// Settings of the remote event bridge. A bridge with `listen` publishes the events
+/// a peer forwards to it; a bridge with `peer` forwards local events to that peer.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RemoteBridgeConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Address to accept a peer on, e.g. `0.0.0.0:7400`
+    #[serde(default)]
+    pub listen: Option<String>,
+
+    /// Address of the peer to forward local events to
+    #[serde(default)]
+    pub peer: Option<String>,
+
+    /// Event types forwarded to the peer, by their display name (the name of a
+    /// custom event, or e.g. `AgentStarted`). Nothing is forwarded when empty.
+    #[serde(default)]
+    pub event_types: Vec<String>,
+
+    /// Pre-shared token the dialing side presents and the listening side requires
+    #[serde(default)]
+    pub token: Option<String>,
+
+    /// How often the dialing side sends a heartbeat. Keep it below `heartbeat_timeout`.
+    #[serde(default = "default_bridge_heartbeat_interval", with = "duration_ms")]
+    #[schema(value_type = u64, pattern = "uint64 as milliseconds")]
+    pub heartbeat_interval: Duration,
+
+    /// A connection silent for this long is dropped and redialled
+    #[serde(default = "default_bridge_heartbeat_timeout", with = "duration_ms")]
+    #[schema(value_type = u64, pattern = "uint64 as milliseconds")]
+    pub heartbeat_timeout: Duration,
+
+    #[serde(default = "default_bridge_reconnect_delay", with = "duration_ms")]
+    #[schema(value_type = u64, pattern = "uint64 as milliseconds")]
+    pub reconnect_delay: Duration,
+
+    /// Unacknowledged events kept for redelivery; the oldest are dropped beyond it
+    #[serde(default = "default_bridge_max_pending")]
+    pub max_pending: usize,
+
+    /// IDs of received events remembered to drop redeliveries
+    #[serde(default = "default_bridge_dedup_capacity")]
+    pub dedup_capacity: usize,
+}
+
+impl Default for RemoteBridgeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen: None,
+            peer: None,
+            event_types: Vec::new(),
+            token: None,
+            heartbeat_interval: default_bridge_heartbeat_interval(),
+            heartbeat_timeout: default_bridge_heartbeat_timeout(),
+            reconnect_delay: default_bridge_reconnect_delay(),
+            max_pending: default_bridge_max_pending(),
+            dedup_capacity: default_bridge_dedup_capacity(),
+        }
+    }
+}
+
+/// Settings of the scheduler firing the `schedule` declarations of the world
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ScheduleConfig {
+    /// What to do with fire times that passed while the scheduler was not running
+    #[serde(default)]
+    pub missed_fires: MissedFirePolicy,
+
+    /// How late a fire may be and still count as on time rather than missed
+    #[serde(default = "default_schedule_misfire_threshold", with = "duration_ms")]
+    #[schema(value_type = u64, pattern = "uint64 as milliseconds")]
+    pub misfire_threshold: Duration,
+
+    /// Longest wait between two checks of the clock, so that a clock moved by hand
+    /// or a host resumed from sleep is noticed
+    #[serde(default = "default_schedule_poll_interval", with = "duration_ms")]
+    #[schema(value_type = u64, pattern = "uint64 as milliseconds")]
+    pub poll_interval: Duration,
+}
+
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        Self {
+            missed_fires: MissedFirePolicy::default(),
+            misfire_threshold: default_schedule_misfire_threshold(),
+            poll_interval: default_schedule_poll_interval(),
+        }
+    }
+}
+
+/// Handling of schedule fire times missed during downtime
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MissedFirePolicy {
+    /// Missed fires are dropped; the schedule resumes at its next fire time
+    #[default]
+    Skip,
+    /// Missed fires are caught up with a single emission
+    FireOnce,
+}
+
+/// Settings of the bridge between the event bus and an external message broker
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BrokerBridgeConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    #[serde(default)]
+    pub broker: BrokerConfig,
+
+    /// Local events published to broker topics
+    #[serde(default)]
+    pub exports: Vec<BrokerExport>,
+
+    /// Broker topics published on the local bus as custom events
+    #[serde(default)]
+    pub imports: Vec<BrokerImport>,
+
+    /// Wait before retrying a failed publish or subscription
+    #[serde(default = "default_bridge_reconnect_delay", with = "duration_ms")]
+    #[schema(value_type = u64, pattern = "uint64 as milliseconds")]
+    pub reconnect_delay: Duration,
+
+    /// Exported messages waiting for the broker; the oldest are dropped beyond it
+    #[serde(default = "default_bridge_max_pending")]
+    pub max_pending: usize,
+
+    /// IDs of imported messages remembered to drop redeliveries
+    #[serde(default = "default_bridge_dedup_capacity")]
+    pub dedup_capacity: usize,
+
+    /// How often the bridge's counters are published as a `MetricsSummary` event
+    #[serde(default = "default_broker_metrics_interval", with = "duration_ms")]
     #[schema(value_type = u64, pattern = "uint64 as milliseconds")]
-    pub tick_interval: Duration,
+    pub metrics_interval: Duration,
 }
 
-impl Default for TickerConfig {
+impl Default for BrokerBridgeConfig {
     fn default() -> Self {
         Self {
-            enabled: default_true(),
-            tick_interval: default_tick_interval(),
+            enabled: false,
+            broker: BrokerConfig::default(),
+            exports: Vec::new(),
+            imports: Vec::new(),
+            reconnect_delay: default_bridge_reconnect_delay(),
+            max_pending: default_bridge_max_pending(),
+            dedup_capacity: default_bridge_dedup_capacity(),
+            metrics_interval: default_broker_metrics_interval(),
         }
     }
 }
 
-// metricsの設定
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
-pub struct MetricsConfig {
-    #[serde(default = "default_true")]
-    pub enabled: bool,
+/// The broker a [`BrokerBridgeConfig`] connects to
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BrokerConfig {
+    /// A broker inside the process, for tests and local development
+    #[default]
+    InProcess,
+    /// A NATS server, e.g. `nats://localhost:4222`. Requires the `nats` feature.
+    Nats { url: String },
+}
 
-    #[serde(default = "default_metrics_tick_interval")]
-    pub metrics_interval: usize,
+/// How an exported event is written to its topic
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BrokerEncoding {
+    /// The binary wire format of the remote event bridge
+    Binary,
+    /// A JSON object with `event_type`, `event_id`, `parent_event_id`,
+    /// `root_event_id` and `parameters`
+    #[default]
+    Json,
 }
 
-impl Default for MetricsConfig {
-    fn default() -> Self {
-        Self {
-            enabled: default_true(),
-            metrics_interval: default_metrics_tick_interval(),
-        }
-    }
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BrokerExport {
+    /// Event type by its display name (the name of a custom event, or e.g. `AgentStarted`)
+    pub event_type: String,
+    pub topic: String,
+    #[serde(default)]
+    pub encoding: BrokerEncoding,
+}
+
+/// A topic whose messages, JSON objects of parameters, become custom events
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BrokerImport {
+    pub topic: String,
+    /// Name of the custom event published for each message
+    pub event_name: String,
+    /// Required parameters by their DSL type (`String`, `Int`, `Float`, `Boolean`,
+    /// `Duration` or `DateTime`). Messages missing one, or with a value not
+    /// coercible to its type, are rejected. Other parameters pass through.
+    #[serde(default)]
+    pub parameters: HashMap<String, String>,
+    /// Field holding the message ID, used as the event ID and to drop redeliveries
+    #[serde(default = "default_broker_id_field")]
+    pub id_field: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -220,6 +1077,10 @@ pub struct ProviderConfig {
     pub provider_specific: HashMap<String, serde_json::Value>,
 
     pub plugin_configs: HashMap<String, PluginConfig>,
+
+    // 同時実行数の制限
+    #[serde(default)]
+    pub concurrency: ConcurrencyConfig,
 }
 
 impl Default for ProviderConfig {
@@ -231,6 +1092,7 @@ impl Default for ProviderConfig {
             endpoint: EndpointConfig::default(),
             provider_specific: HashMap::new(),
             plugin_configs: HashMap::new(),
+            concurrency: ConcurrencyConfig::default(),
         }
     }
 }
@@ -262,6 +1124,7 @@ impl TryFrom<HashMap<String, Value>> for ProviderConfig {
             endpoint: EndpointConfig::default(),
             provider_specific: HashMap::new(),
             plugin_configs: HashMap::new(),
+            concurrency: ConcurrencyConfig::default(),
         })
     }
 }
@@ -273,6 +1136,12 @@ pub enum PluginConfig {
     Rag(RagConfig),
     Search(SearchConfig),
     SharedMemory(SharedMemoryConfig),
+    PromptArchive(PromptArchiveConfig),
+    Catalog(CatalogConfig),
+    #[strum(serialize = "openapi_tools")]
+    OpenApiTools(OpenApiToolsConfig),
+    #[strum(serialize = "sistence_memory")]
+    SistenceMemory(SistenceContextConfig),
     Unknown(HashMap<String, serde_json::Value>),
 }
 
@@ -300,6 +1169,164 @@ impl Default for MemoryConfig {
     }
 }
 
+/// プロンプト/レスポンスのアーカイブ設定
+///
+/// Every prompt and response of the provider is written as JSONL into `directory`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct PromptArchiveConfig {
+    pub directory: String,
+    #[serde(default = "default_archive_batch_size")]
+    pub batch_size: usize,
+    /// Records beyond this many unflushed entries are dropped instead of blocking the provider
+    #[serde(default = "default_archive_buffer_capacity")]
+    pub buffer_capacity: usize,
+    #[serde(default = "default_archive_flush_interval", with = "duration_ms")]
+    #[schema(value_type = u64, pattern = "uint64 as milliseconds")]
+    pub flush_interval: Duration,
+    /// Regex patterns whose matches are masked before records are written
+    #[serde(default)]
+    pub redact_patterns: Vec<String>,
+}
+
+impl Default for PromptArchiveConfig {
+    fn default() -> Self {
+        Self {
+            directory: default_archive_directory(),
+            batch_size: default_archive_batch_size(),
+            buffer_capacity: default_archive_buffer_capacity(),
+            flush_interval: default_archive_flush_interval(),
+            redact_patterns: vec![],
+        }
+    }
+}
+
+/// 埋め込みのバッチ処理の設定
+///
+/// Items waiting for an embedding are sent to the embedding provider `batch_size`
+/// at a time, or after `flush_interval` when fewer are waiting. A failed call is
+/// tried `max_attempts` times in all, waiting `retry_backoff` and then twice as
+/// long before each retry.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct EmbeddingSchedulerConfig {
+    #[serde(default = "default_embedding_batch_size")]
+    pub batch_size: usize,
+    #[serde(default = "default_embedding_flush_interval", with = "duration_ms")]
+    #[schema(value_type = u64, pattern = "uint64 as milliseconds")]
+    pub flush_interval: Duration,
+    #[serde(default = "default_embedding_max_attempts")]
+    pub max_attempts: u32,
+    #[serde(default = "default_embedding_retry_backoff", with = "duration_ms")]
+    #[schema(value_type = u64, pattern = "uint64 as milliseconds")]
+    pub retry_backoff: Duration,
+}
+
+impl Default for EmbeddingSchedulerConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: default_embedding_batch_size(),
+            flush_interval: default_embedding_flush_interval(),
+            max_attempts: default_embedding_max_attempts(),
+            retry_backoff: default_embedding_retry_backoff(),
+        }
+    }
+}
+
+/// 他のエージェントとそのリクエストをプロンプトに載せる設定
+///
+/// The section lists at most `max_agents` agents and is cut at `max_chars` characters.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct CatalogConfig {
+    #[serde(default = "default_catalog_max_agents")]
+    pub max_agents: usize,
+    #[serde(default = "default_catalog_max_chars")]
+    pub max_chars: usize,
+}
+
+impl Default for CatalogConfig {
+    fn default() -> Self {
+        Self {
+            max_agents: default_catalog_max_agents(),
+            max_chars: default_catalog_max_chars(),
+        }
+    }
+}
+
+/// エージェントの SistenceMemory から、リクエストに関連する項目をプロンプトに載せる設定
+///
+/// Items are read from the SistenceMemory namespace named after the agent, at most
+/// `limit` of them. With `record_context`, or when the request is profiled, the ids
+/// and relevance of the injected items are kept per request.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct SistenceContextConfig {
+    #[serde(default = "default_sistence_context_limit")]
+    pub limit: usize,
+    #[serde(default)]
+    pub record_context: bool,
+}
+
+impl Default for SistenceContextConfig {
+    fn default() -> Self {
+        Self {
+            limit: default_sistence_context_limit(),
+            record_context: false,
+        }
+    }
+}
+
+/// OpenAPI の各オペレーションを LLM と `call_tool(name, args)` から呼べるツールにする設定
+///
+/// Operations of every spec in `specs` become tools named by their `operationId`.
+/// When `allowed_tools` is set, only the listed tools are offered and callable.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct OpenApiToolsConfig {
+    pub specs: Vec<OpenApiSpecConfig>,
+    #[serde(default)]
+    pub allowed_tools: Option<Vec<String>>,
+    #[serde(default = "default_tool_timeout", with = "duration_ms")]
+    #[schema(value_type = u64, pattern = "uint64 as milliseconds")]
+    pub timeout: Duration,
+    /// Retries after a connection error, a timeout, a 429 or a 5xx response
+    #[serde(default = "default_tool_max_retries")]
+    pub max_retries: u32,
+    #[serde(default = "default_tool_retry_delay", with = "duration_ms")]
+    #[schema(value_type = u64, pattern = "uint64 as milliseconds")]
+    pub retry_delay: Duration,
+}
+
+impl Default for OpenApiToolsConfig {
+    fn default() -> Self {
+        Self {
+            specs: vec![],
+            allowed_tools: None,
+            timeout: default_tool_timeout(),
+            max_retries: default_tool_max_retries(),
+            retry_delay: default_tool_retry_delay(),
+        }
+    }
+}
+
+/// An OpenAPI 3 document in JSON, as a file path or an http(s) URL
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct OpenApiSpecConfig {
+    pub location: String,
+    /// Overrides the first entry of the spec's `servers`
+    #[serde(default)]
+    pub base_url: Option<String>,
+    #[serde(default)]
+    pub auth: Option<ToolAuthConfig>,
+}
+
+/// Credentials sent with every call to the operations of a spec.
+/// `secret_key` names an entry of the provider's `additional_auth` secrets.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ToolAuthConfig {
+    /// `Authorization: Bearer <secret>`
+    Bearer { secret_key: String },
+    /// `<header>: <secret>`
+    ApiKey { header: String, secret_key: String },
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
 pub struct RagConfig {
     #[serde(default = "default_collection_name")]
@@ -355,8 +1382,32 @@ pub struct CommonConfig {
     pub temperature: f32,
     #[serde(default = "default_max_tokens")]
     pub max_tokens: usize,
+    /// Nucleus sampling; the LLM's own default when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
     #[serde(default = "default_model")]
     pub model: String,
+    /// Form of the response the caller expects
+    #[serde(default)]
+    pub output_format: OutputFormat,
+    /// JSON Schema the response must satisfy, see [`crate::provider::response_schema`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_schema: Option<serde_json::Value>,
+    /// Repair prompts sent for a response violating `response_schema` before the
+    /// request fails
+    #[serde(default = "default_max_repair_attempts")]
+    pub max_repair_attempts: usize,
+}
+
+/// Expected form of an LLM response
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    /// A single JSON object. LLMs with a native JSON mode use it; others are
+    /// instructed through the prompt.
+    Json,
 }
 
 impl Default for CommonConfig {
@@ -364,11 +1415,29 @@ impl Default for CommonConfig {
         Self {
             temperature: default_temperature(),
             max_tokens: default_max_tokens(),
+            top_p: None,
             model: default_model(),
+            output_format: OutputFormat::default(),
+            response_schema: None,
+            max_repair_attempts: default_max_repair_attempts(),
         }
     }
 }
 
+/// プロバイダーごとのLLM呼び出しの同時実行制限
+///
+/// Calls beyond `max_concurrent_calls` wait for a free slot. With `max_queue_wait` set,
+/// a call that waits longer fails with `ProviderError::Overloaded`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct ConcurrencyConfig {
+    /// Maximum number of in-flight calls, unlimited when unset
+    #[serde(default)]
+    pub max_concurrent_calls: Option<usize>,
+    #[serde(default, with = "option_duration_ms")]
+    #[schema(value_type = Option<u64>, pattern = "uint64 as milliseconds")]
+    pub max_queue_wait: Option<Duration>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct EndpointConfig {
     #[serde(default = "default_endpoint")]
@@ -392,6 +1461,9 @@ impl Default for EndpointConfig {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SecretConfig {
     pub providers: HashMap<String, ProviderSecretConfig>,
+    /// 名前付きのシークレット。SystemConfig の secret_grants で許可されたエージェントのみ読める
+    #[serde(default)]
+    pub handler_secrets: HashMap<String, String>,
 }
 
 impl Default for SecretConfig {
@@ -402,6 +1474,7 @@ impl Default for SecretConfig {
                 map.insert(default_provider_name(), ProviderSecretConfig::default());
                 map
             },
+            handler_secrets: HashMap::new(),
         }
     }
 }
@@ -429,6 +1502,22 @@ pub struct ProviderSecretConfig {
 }
 
 // デフォルト値の定義
+fn default_max_log_events_per_second() -> u32 {
+    20
+}
+
+fn default_max_log_event_bytes() -> usize {
+    4096
+}
+
+fn default_recent_logs() -> usize {
+    100
+}
+
+fn default_conversation_max_turns() -> usize {
+    10
+}
+
 fn default_event_buffer_size() -> usize {
     1000
 }
@@ -447,6 +1536,19 @@ fn default_shutdown_timeout() -> Duration {
 fn default_request_timeout() -> Duration {
     Duration::from_secs(60)
 }
+fn default_request_dedup_window() -> Duration {
+    Duration::from_secs(30)
+}
+fn default_request_dedup_max_entries() -> usize {
+    1000
+}
+fn default_debug_eval_timeout() -> Duration {
+    Duration::from_secs(1)
+}
+fn default_max_recordings() -> usize {
+    100
+}
+
 fn default_true() -> bool {
     true
 }
@@ -463,10 +1565,86 @@ fn default_retention_period() -> Duration {
     Duration::from_secs(3600)
 }
 
+fn default_scheduler_workers() -> usize {
+    4
+}
+
+fn default_queue_batch_window() -> Duration {
+    Duration::from_millis(5)
+}
+
+fn default_idle_check_interval() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_response_cache_max_entries() -> usize {
+    256
+}
+
+fn default_critical_retry_interval() -> Duration {
+    Duration::from_secs(1)
+}
+
+fn default_critical_max_attempts() -> u32 {
+    5
+}
+
+fn default_critical_deadline() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_critical_max_pending() -> usize {
+    1024
+}
+
+fn default_max_statements_per_handler() -> usize {
+    10_000
+}
+
+fn default_max_handlers_per_agent() -> usize {
+    1_000
+}
+
+fn default_max_nesting_depth() -> usize {
+    128
+}
+
+fn default_max_think_calls() -> u32 {
+    20
+}
+
+fn default_max_execution_tokens() -> u64 {
+    200_000
+}
+
+fn default_max_sub_requests() -> u32 {
+    20
+}
+
+// 評価は再帰するので、ワーカースレッドのスタック (2 MiB) に収まる深さにする
+fn default_max_eval_depth() -> u32 {
+    32
+}
+
+fn default_max_value_size() -> u64 {
+    16 * 1024 * 1024
+}
+
+fn default_max_allocation_bytes() -> u64 {
+    256 * 1024 * 1024
+}
+
 fn default_access_timeout() -> Duration {
     Duration::from_secs(5)
 }
 
+fn default_secret_patterns() -> Vec<String> {
+    crate::secret_scan::DEFAULT_SECRET_PATTERNS
+        .iter()
+        .map(|pattern| pattern.to_string())
+        .collect()
+}
+
 fn default_ticker_config() -> Option<TickerConfig> {
     Some(TickerConfig::default())
 }
@@ -475,6 +1653,42 @@ fn default_metrics_config() -> Option<MetricsConfig> {
     Some(MetricsConfig::default())
 }
 
+fn default_schedule_misfire_threshold() -> Duration {
+    Duration::from_secs(60)
+}
+
+fn default_schedule_poll_interval() -> Duration {
+    Duration::from_secs(1)
+}
+
+fn default_bridge_heartbeat_interval() -> Duration {
+    Duration::from_secs(1)
+}
+
+fn default_bridge_heartbeat_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_bridge_reconnect_delay() -> Duration {
+    Duration::from_millis(500)
+}
+
+fn default_bridge_max_pending() -> usize {
+    10_000
+}
+
+fn default_bridge_dedup_capacity() -> usize {
+    10_000
+}
+
+fn default_broker_metrics_interval() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_broker_id_field() -> String {
+    "event_id".to_string()
+}
+
 fn default_provider_name() -> String {
     "default_provider".to_string()
 }
@@ -490,6 +1704,10 @@ fn default_max_tokens() -> usize {
     1000
 }
 
+fn default_max_repair_attempts() -> usize {
+    2
+}
+
 fn default_model() -> String {
     "gpt-4o-mini".to_string()
 }
@@ -554,6 +1772,62 @@ fn default_metrics_tick_interval() -> usize {
     60
 }
 
+fn default_archive_directory() -> String {
+    "prompt_archive".to_string()
+}
+
+fn default_archive_batch_size() -> usize {
+    16
+}
+
+fn default_archive_buffer_capacity() -> usize {
+    1024
+}
+
+fn default_archive_flush_interval() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_embedding_batch_size() -> usize {
+    32
+}
+
+fn default_embedding_flush_interval() -> Duration {
+    Duration::from_millis(500)
+}
+
+fn default_embedding_max_attempts() -> u32 {
+    3
+}
+
+fn default_embedding_retry_backoff() -> Duration {
+    Duration::from_secs(1)
+}
+
+fn default_catalog_max_agents() -> usize {
+    20
+}
+
+fn default_catalog_max_chars() -> usize {
+    4000
+}
+
+fn default_sistence_context_limit() -> usize {
+    5
+}
+
+fn default_tool_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_tool_max_retries() -> u32 {
+    2
+}
+
+fn default_tool_retry_delay() -> Duration {
+    Duration::from_millis(200)
+}
+
 // Duration型のシリアライズ/デシリアライズヘルパー
 pub mod duration_ms {
     use serde::{Deserialize, Deserializer, Serializer};
@@ -575,6 +1849,29 @@ pub mod duration_ms {
     }
 }
 
+pub mod option_duration_ms {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match duration {
+            Some(duration) => serializer.serialize_some(&(duration.as_millis() as u64)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let millis = Option::<u64>::deserialize(deserializer)?;
+        Ok(millis.map(Duration::from_millis))
+    }
+}
+
 pub mod duration_secs {
     use serde::{Deserialize, Deserializer, Serializer};
     use std::time::Duration;
@@ -603,17 +1900,129 @@ impl Default for SystemConfig {
             init_timeout: default_init_timeout(),
             shutdown_timeout: default_shutdown_timeout(),
             request_timeout: default_request_timeout(),
+            event_journal: EventJournalConfig::default(),
+            handler_recording: HandlerRecordingConfig::default(),
             agent_config: AgentConfig::default(),
             native_feature_config: NativeFeatureConfig::default(),
             provider_configs: ProviderConfigs::default(),
+            prompt_preamble: None,
+            scheduler: SchedulerConfig::default(),
+            request_queue: RequestQueueConfig::default(),
+            llm_budgets: HashMap::new(),
+            eval_limits: HashMap::new(),
+            guardrails: HashMap::new(),
+            idle_eviction: IdleEvictionConfig::default(),
+            secret_grants: HashMap::new(),
+            analysis_limits: AnalysisLimits::default(),
+            debug_eval_timeout: default_debug_eval_timeout(),
+            validate_credentials: CredentialValidationConfig::default(),
+            secret_scan: SecretScanConfig::default(),
+            locale: Locale::default(),
+            agent_configs: HashMap::new(),
+            agent_logs: HashMap::new(),
+            conversations: HashMap::new(),
+            shared_counters: None,
+            event_validation: EventValidationMode::default(),
+            response_cache: ResponseCacheConfig::default(),
+            critical_delivery: CriticalDeliveryConfig::default(),
         }
     }
 }
 
+/// Environment variable selecting the profile applied by [`SystemConfig::from_file`]
+pub const PROFILE_ENV_VAR: &str = "KAIREI_PROFILE";
+
+/// Key of the profile overrides in a config file
+pub const PROFILES_KEY: &str = "profiles";
+
 impl SystemConfig {
-    // JSONファイルから設定を読み込む
+    /// Evaluator limits of `agent_name`: its entry in `eval_limits`, or else
+    /// `agent_config.eval_limits`
+    pub fn eval_limits_for(&self, agent_name: &str) -> EvalLimits {
+        self.eval_limits
+            .get(agent_name)
+            .unwrap_or(&self.agent_config.eval_limits)
+            .clone()
+    }
+
+    /// Execution guardrails of `agent_name`: its entry in `guardrails`, or else
+    /// `agent_config.guardrails`
+    pub fn guardrails_for(&self, agent_name: &str) -> ExecutionGuardrails {
+        self.guardrails
+            .get(agent_name)
+            .unwrap_or(&self.agent_config.guardrails)
+            .clone()
+    }
+
+    // JSONファイルから設定を読み込む。KAIREI_PROFILE があればそのプロファイルを適用する
     pub fn from_file(path: &str) -> InternalResult<Self> {
-        from_file(path)
+        let profile = std::env::var(PROFILE_ENV_VAR)
+            .ok()
+            .filter(|profile| !profile.is_empty());
+        Self::from_file_with_profile(path, profile.as_deref())
+    }
+
+    /// Reads a config file and applies the overrides of `profile`, see
+    /// [`Self::from_value_with_profile`]
+    pub fn from_file_with_profile<P: AsRef<Path>>(
+        path: P,
+        profile: Option<&str>,
+    ) -> InternalResult<Self> {
+        let value: serde_json::Value = from_file(path)?;
+        Self::from_value_with_profile(value, profile)
+    }
+
+    /// Builds the config from a JSON object whose optional `profiles` member maps
+    /// profile names (e.g. `dev`, `prod`) to overrides of the other members.
+    ///
+    /// The overrides of `profile` are merged into the base: objects member by member,
+    /// any other value replacing the base value. Without `profile` the base is used
+    /// as it is. A profile the config does not define is an error.
+    pub fn from_value_with_profile(
+        mut value: serde_json::Value,
+        profile: Option<&str>,
+    ) -> InternalResult<Self> {
+        let mut profiles = match value.as_object_mut() {
+            Some(object) => object.remove(PROFILES_KEY),
+            None => None,
+        };
+        if let Some(profile) = profile {
+            let overrides = profiles
+                .as_mut()
+                .and_then(|profiles| profiles.get_mut(profile))
+                .map(serde_json::Value::take)
+                .ok_or_else(|| {
+                    let known = profiles
+                        .as_ref()
+                        .and_then(serde_json::Value::as_object)
+                        .map(|profiles| profiles.keys().cloned().collect::<Vec<_>>().join(", "))
+                        .unwrap_or_default();
+                    Error::Internal(format!(
+                        "Unknown config profile '{}' (defined: [{}])",
+                        profile, known
+                    ))
+                })?;
+            merge_json(&mut value, overrides);
+        }
+        serde_json::from_value(value)
+            .map_err(|e| Error::Internal(format!("Failed to parse config: {}", e)))
+    }
+}
+
+// オブジェクトはメンバーごとに再帰的に、それ以外は丸ごと上書きする
+fn merge_json(base: &mut serde_json::Value, overrides: serde_json::Value) {
+    match (base, overrides) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
     }
 }
 
@@ -630,4 +2039,81 @@ mod tests {
         let deserialized: SystemConfig = serde_json::from_str(&json).unwrap();
         assert_eq!(format!("{:?}", config), format!("{:?}", deserialized));
     }
+
+    #[test]
+    fn test_guardrails_replaced_per_agent() {
+        let config: SystemConfig = serde_json::from_value(serde_json::json!({
+            "agent_config": { "guardrails": { "max_think_calls": 5 } },
+            "guardrails": { "Planner": { "max_think_calls": 50 } }
+        }))
+        .unwrap();
+        assert_eq!(config.guardrails_for("Planner").max_think_calls, 50);
+        assert_eq!(config.guardrails_for("Other").max_think_calls, 5);
+        assert_eq!(
+            config.guardrails_for("Other").max_sub_requests,
+            ExecutionGuardrails::default().max_sub_requests
+        );
+    }
+
+    fn profiled_config() -> serde_json::Value {
+        serde_json::json!({
+            "max_agents": 10,
+            "event_buffer_size": 500,
+            "response_cache": { "max_entries_per_agent": 32 },
+            "profiles": {
+                "dev": {
+                    "max_agents": 2
+                },
+                "prod": {
+                    "max_agents": 100,
+                    "response_cache": { "max_entries_per_agent": 1024 }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_profile_overrides_merged_into_base() {
+        let base = SystemConfig::from_value_with_profile(profiled_config(), None).unwrap();
+        assert_eq!(base.max_agents, 10);
+        assert_eq!(base.response_cache.max_entries_per_agent, 32);
+
+        let dev = SystemConfig::from_value_with_profile(profiled_config(), Some("dev")).unwrap();
+        assert_eq!(dev.max_agents, 2);
+        assert_eq!(dev.response_cache.max_entries_per_agent, 32);
+        assert_eq!(dev.event_buffer_size, 500);
+
+        let prod = SystemConfig::from_value_with_profile(profiled_config(), Some("prod")).unwrap();
+        assert_eq!(prod.max_agents, 100);
+        assert_eq!(prod.response_cache.max_entries_per_agent, 1024);
+        assert_eq!(prod.event_buffer_size, 500);
+        // 設定にない値は既定値のまま
+        assert_eq!(prod.init_timeout, SystemConfig::default().init_timeout);
+    }
+
+    #[test]
+    fn test_unknown_profile_is_an_error() {
+        let error =
+            SystemConfig::from_value_with_profile(profiled_config(), Some("staging")).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("Unknown config profile 'staging' (defined: [dev, prod])"),
+            "{}",
+            error
+        );
+
+        let without_profiles = serde_json::json!({ "max_agents": 10 });
+        assert!(SystemConfig::from_value_with_profile(without_profiles, Some("dev")).is_err());
+    }
+
+    #[test]
+    fn test_from_file_with_profile() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        std::fs::write(&path, profiled_config().to_string()).unwrap();
+
+        let config = SystemConfig::from_file_with_profile(&path, Some("prod")).unwrap();
+        assert_eq!(config.max_agents, 100);
+    }
 }
@@ -0,0 +1,191 @@
+//! Values of the configuration parameters agents declare.
+//!
+//! A MicroAgent declares typed parameters in a `config { ... }` block and the
+//! deployment supplies them in [`SystemConfig::agent_configs`], keyed by agent
+//! name. When the agent is built, [`resolve`] checks the supplied JSON against the
+//! declared types with the same rules as request parameters (see
+//! [`coercion`](crate::event::coercion)), falls back to the declared defaults and
+//! rejects keys the agent does not declare. A parameter typed `Option<T>` without
+//! a value resolves to `null`; any other parameter without one is an error.
+//!
+//! Handlers read the resolved values as `config.<name>` and cannot assign them.
+//! Agents whose [`AgentConfigValues::hot_reload`] is set accept new values while
+//! they run through `System::update_agent_config`.
+//!
+//! [`SystemConfig::agent_configs`]: crate::config::SystemConfig::agent_configs
+//! [`AgentConfigValues::hot_reload`]: crate::config::AgentConfigValues::hot_reload
+
+use std::collections::HashMap;
+
+use crate::{
+    AgentConfigDef, TypeInfo,
+    event::coercion::{self, CoercionError},
+    expression::{ExpressionEvaluator, Value},
+};
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum AgentConfigError {
+    #[error("Agent {agent_name}: config `{key}` is required but not supplied")]
+    Missing { agent_name: String, key: String },
+    #[error("Agent {agent_name}: config `{key}` expects {expected}: {reason}")]
+    TypeMismatch {
+        agent_name: String,
+        key: String,
+        expected: String,
+        reason: String,
+    },
+    #[error("Agent {agent_name}: config `{key}` is not declared")]
+    Undeclared { agent_name: String, key: String },
+    #[error("Agent {agent_name}: config `{key}` has an invalid default: {message}")]
+    InvalidDefault {
+        agent_name: String,
+        key: String,
+        message: String,
+    },
+    #[error("Agent {agent_name} does not allow its config to be reloaded")]
+    ReloadNotAllowed { agent_name: String },
+    #[error("Agent {agent_name}: setting `{setting}` is invalid: {reason}")]
+    InvalidSetting {
+        agent_name: String,
+        setting: String,
+        reason: String,
+    },
+}
+
+/// The values `agent_name` reads as `config.<name>`, from the `supplied` JSON and
+/// the defaults of `declared`
+pub fn resolve(
+    agent_name: &str,
+    declared: Option<&AgentConfigDef>,
+    supplied: &HashMap<String, serde_json::Value>,
+) -> Result<HashMap<String, Value>, AgentConfigError> {
+    let empty = AgentConfigDef::default();
+    let declared = declared.unwrap_or(&empty);
+
+    // エラーを決定的にするため、未宣言のキーは名前順で最初のものを報告する
+    if let Some(key) = supplied
+        .keys()
+        .filter(|key| declared.parameter(key).is_none())
+        .min()
+    {
+        return Err(AgentConfigError::Undeclared {
+            agent_name: agent_name.to_string(),
+            key: key.clone(),
+        });
+    }
+
+    let mut values = HashMap::new();
+    for param in &declared.parameters {
+        let value = match supplied.get(&param.name).filter(|value| !value.is_null()) {
+            Some(json) => coerce(&param.type_info, &param.name, json).map_err(|e| {
+                AgentConfigError::TypeMismatch {
+                    agent_name: agent_name.to_string(),
+                    key: param.name.clone(),
+                    expected: e.expected,
+                    reason: e.reason,
+                }
+            })?,
+            None => match (&param.default, &param.type_info) {
+                (Some(default), _) => ExpressionEvaluator::eval_literal(default).map_err(|e| {
+                    AgentConfigError::InvalidDefault {
+                        agent_name: agent_name.to_string(),
+                        key: param.name.clone(),
+                        message: e.to_string(),
+                    }
+                })?,
+                (None, TypeInfo::Option(_)) => Value::Null,
+                (None, _) => {
+                    return Err(AgentConfigError::Missing {
+                        agent_name: agent_name.to_string(),
+                        key: param.name.clone(),
+                    });
+                }
+            },
+        };
+        values.insert(param.name.clone(), value);
+    }
+    Ok(values)
+}
+
+fn coerce(
+    type_info: &TypeInfo,
+    name: &str,
+    json: &serde_json::Value,
+) -> Result<Value, CoercionError> {
+    let value = match coercion::parameter_type(type_info) {
+        Some(target) => coercion::coerce(name, json, &target)?,
+        None => coercion::from_json(json),
+    };
+    Ok(Value::from(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{ConfigParamDef, Literal};
+
+    fn declared() -> AgentConfigDef {
+        let param = |name: &str, type_name: &str, default| ConfigParamDef {
+            name: name.to_string(),
+            type_info: TypeInfo::Simple(type_name.to_string()),
+            default,
+            doc: None,
+        };
+        AgentConfigDef {
+            parameters: vec![
+                param("region", "String", None),
+                param("max_results", "Int", Some(Literal::Integer(10))),
+                ConfigParamDef {
+                    name: "label".to_string(),
+                    type_info: TypeInfo::Option(Box::new(TypeInfo::Simple("String".to_string()))),
+                    default: None,
+                    doc: None,
+                },
+            ],
+        }
+    }
+
+    fn supplied(value: serde_json::Value) -> HashMap<String, serde_json::Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_resolve_applies_defaults() {
+        let values = resolve(
+            "Search",
+            Some(&declared()),
+            &supplied(json!({"region": "eu-west-1"})),
+        )
+        .unwrap();
+        assert_eq!(values["region"], Value::String("eu-west-1".to_string()));
+        assert_eq!(values["max_results"], Value::Integer(10));
+        assert_eq!(values["label"], Value::Null);
+    }
+
+    #[test]
+    fn test_resolve_rejects_invalid_values() {
+        assert_eq!(
+            resolve("Search", Some(&declared()), &HashMap::new()),
+            Err(AgentConfigError::Missing {
+                agent_name: "Search".to_string(),
+                key: "region".to_string(),
+            })
+        );
+        let error = resolve(
+            "Search",
+            Some(&declared()),
+            &supplied(json!({"region": "eu-west-1", "max_results": "ten"})),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            AgentConfigError::TypeMismatch { ref key, .. } if key == "max_results"
+        ));
+        assert!(matches!(
+            resolve("Search", None, &supplied(json!({"region": "eu-west-1"}))),
+            Err(AgentConfigError::Undeclared { .. })
+        ));
+    }
+}
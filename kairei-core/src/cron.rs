@@ -0,0 +1,343 @@
+//! # Cron Expressions
+//!
+//! The 5-field cron subset used by `schedule` declarations of the World DSL:
+//! `minute hour day-of-month month day-of-week`, evaluated in UTC.
+//!
+//! Each field is `*`, a value, a range `a-b`, a step `*/n` or `a-b/n`, or a
+//! comma-separated list of these. Day of week runs from 0 (Sunday) to 6, with 7 as
+//! another name for Sunday. Month and weekday names and the `L`, `W`, `#` and `?`
+//! extensions are not supported.
+//!
+//! As in cron, when both day of month and day of week are restricted (do not start
+//! with `*`), a day matching either one fires.
+
+use std::{fmt, str::FromStr};
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Timelike, Utc};
+use thiserror::Error;
+
+/// Years searched for the next fire time before giving up, e.g. for `0 0 30 2 *`
+const SEARCH_YEARS: i32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CronField {
+    Minute,
+    Hour,
+    DayOfMonth,
+    Month,
+    DayOfWeek,
+}
+
+impl CronField {
+    const ALL: [CronField; 5] = [
+        CronField::Minute,
+        CronField::Hour,
+        CronField::DayOfMonth,
+        CronField::Month,
+        CronField::DayOfWeek,
+    ];
+
+    fn range(self) -> (u32, u32) {
+        match self {
+            CronField::Minute => (0, 59),
+            CronField::Hour => (0, 23),
+            CronField::DayOfMonth => (1, 31),
+            CronField::Month => (1, 12),
+            CronField::DayOfWeek => (0, 7),
+        }
+    }
+}
+
+impl fmt::Display for CronField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CronField::Minute => "minute",
+            CronField::Hour => "hour",
+            CronField::DayOfMonth => "day-of-month",
+            CronField::Month => "month",
+            CronField::DayOfWeek => "day-of-week",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum CronError {
+    #[error(
+        "expected 5 fields (minute hour day-of-month month day-of-week), found {found} in '{expression}'"
+    )]
+    FieldCount { expression: String, found: usize },
+
+    #[error("invalid {field} field '{value}': {reason}")]
+    InvalidField {
+        field: CronField,
+        value: String,
+        reason: String,
+    },
+}
+
+/// A parsed cron expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    expression: String,
+    /// Bit `n` is set when value `n` of the field matches
+    masks: [u64; 5],
+    day_of_month_restricted: bool,
+    day_of_week_restricted: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self, CronError> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != CronField::ALL.len() {
+            return Err(CronError::FieldCount {
+                expression: expression.to_string(),
+                found: fields.len(),
+            });
+        }
+        let mut masks = [0; 5];
+        for (field, text) in CronField::ALL.into_iter().zip(&fields) {
+            masks[field as usize] = parse_field(field, text)?;
+        }
+        // 曜日の 7 は日曜日 (0) の別名
+        if masks[4] & (1 << 7) != 0 {
+            masks[4] = (masks[4] | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            expression: fields.join(" "),
+            masks,
+            day_of_month_restricted: !fields[2].starts_with('*'),
+            day_of_week_restricted: !fields[4].starts_with('*'),
+        })
+    }
+
+    /// The expression, with whitespace normalized
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    /// Whether the schedule fires in the minute of `time`
+    pub fn matches(&self, time: DateTime<Utc>) -> bool {
+        self.matches_month(time)
+            && self.matches_day(time)
+            && self.matches_field(CronField::Hour, time.hour())
+            && self.matches_field(CronField::Minute, time.minute())
+    }
+
+    /// The first fire time strictly after `after`, or `None` when the schedule
+    /// cannot fire in the next years
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut time = after
+            .with_second(0)
+            .and_then(|t| t.with_nanosecond(0))
+            .unwrap_or(after)
+            + Duration::minutes(1);
+        let last_year = after.year() + SEARCH_YEARS;
+        // 合わないフィールドは単位ごと読み飛ばす
+        while time.year() <= last_year {
+            if !self.matches_month(time) {
+                time = start_of_next_month(time)?;
+            } else if !self.matches_day(time) {
+                time = start_of_day(time) + Duration::days(1);
+            } else if !self.matches_field(CronField::Hour, time.hour()) {
+                time = time.with_minute(0)? + Duration::hours(1);
+            } else if !self.matches_field(CronField::Minute, time.minute()) {
+                time += Duration::minutes(1);
+            } else {
+                return Some(time);
+            }
+        }
+        None
+    }
+
+    fn matches_field(&self, field: CronField, value: u32) -> bool {
+        self.masks[field as usize] & (1 << value) != 0
+    }
+
+    fn matches_month(&self, time: DateTime<Utc>) -> bool {
+        self.matches_field(CronField::Month, time.month())
+    }
+
+    fn matches_day(&self, time: DateTime<Utc>) -> bool {
+        let day_of_month = self.matches_field(CronField::DayOfMonth, time.day());
+        let day_of_week =
+            self.matches_field(CronField::DayOfWeek, time.weekday().num_days_from_sunday());
+        if self.day_of_month_restricted && self.day_of_week_restricted {
+            day_of_month || day_of_week
+        } else {
+            day_of_month && day_of_week
+        }
+    }
+}
+
+impl FromStr for CronSchedule {
+    type Err = CronError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+fn parse_field(field: CronField, text: &str) -> Result<u64, CronError> {
+    let invalid = |reason: String| CronError::InvalidField {
+        field,
+        value: text.to_string(),
+        reason,
+    };
+    let (min, max) = field.range();
+    let number = |s: &str| -> Result<u32, CronError> {
+        let value: u32 = s
+            .parse()
+            .map_err(|_| invalid(format!("'{}' is not a number", s)))?;
+        if value < min || value > max {
+            return Err(invalid(format!(
+                "{} is out of range {}-{}",
+                value, min, max
+            )));
+        }
+        Ok(value)
+    };
+
+    let mut mask = 0;
+    for item in text.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .map_err(|_| invalid(format!("step '{}' is not a number", step)))?;
+                if step == 0 {
+                    return Err(invalid("step must be at least 1".to_string()));
+                }
+                (range, step)
+            }
+            None => (item, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            "" => return Err(invalid("empty list item".to_string())),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (number(start)?, number(end)?),
+                // `5/15` は 5 から上限まで
+                None if item.contains('/') => (number(range)?, max),
+                None => {
+                    let value = number(range)?;
+                    (value, value)
+                }
+            },
+        };
+        if start > end {
+            return Err(invalid(format!("range {}-{} is reversed", start, end)));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+fn start_of_day(time: DateTime<Utc>) -> DateTime<Utc> {
+    Utc.from_utc_datetime(&time.date_naive().and_hms_opt(0, 0, 0).unwrap())
+}
+
+fn start_of_next_month(time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let (year, month) = if time.month() == 12 {
+        (time.year() + 1, 1)
+    } else {
+        (time.year(), time.month() + 1)
+    };
+    let date = NaiveDate::from_ymd_opt(year, month, 1)?;
+    Some(Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn next(expression: &str, after: &str) -> Option<DateTime<Utc>> {
+        CronSchedule::parse(expression)
+            .unwrap()
+            .next_after(utc(after))
+    }
+
+    #[test]
+    fn test_next_fire_times() {
+        assert_eq!(
+            next("0 2 * * *", "2025-03-10T01:59:30Z"),
+            Some(utc("2025-03-10T02:00:00Z"))
+        );
+        // ちょうど発火時刻なら次の発火時刻
+        assert_eq!(
+            next("0 2 * * *", "2025-03-10T02:00:00Z"),
+            Some(utc("2025-03-11T02:00:00Z"))
+        );
+        assert_eq!(
+            next("*/15 9-17 * * 1-5", "2025-03-14T17:50:00Z"),
+            Some(utc("2025-03-17T09:00:00Z"))
+        );
+        assert_eq!(
+            next("30 0 1 1,7 *", "2025-03-01T00:00:00Z"),
+            Some(utc("2025-07-01T00:30:00Z"))
+        );
+        assert_eq!(
+            next("0 0 29 2 *", "2025-03-01T00:00:00Z"),
+            Some(utc("2028-02-29T00:00:00Z"))
+        );
+        assert_eq!(next("0 0 30 2 *", "2025-03-01T00:00:00Z"), None);
+    }
+
+    #[test]
+    fn test_day_of_month_or_day_of_week() {
+        // 13 日または金曜日
+        let schedule = CronSchedule::parse("0 0 13 * 5").unwrap();
+        assert!(schedule.matches(utc("2025-03-13T00:00:00Z")));
+        assert!(schedule.matches(utc("2025-03-14T00:00:00Z")));
+        assert!(!schedule.matches(utc("2025-03-15T00:00:00Z")));
+
+        // 7 は日曜日
+        let schedule = CronSchedule::parse("0 0 * * 7").unwrap();
+        assert!(schedule.matches(utc("2025-03-16T00:00:00Z")));
+        assert!(!schedule.matches(utc("2025-03-17T00:00:00Z")));
+    }
+
+    #[test]
+    fn test_invalid_expressions() {
+        assert_eq!(
+            CronSchedule::parse("0 2 * *").unwrap_err(),
+            CronError::FieldCount {
+                expression: "0 2 * *".to_string(),
+                found: 4
+            }
+        );
+        let error = |expression: &str| CronSchedule::parse(expression).unwrap_err().to_string();
+        assert_eq!(
+            error("0 24 * * *"),
+            "invalid hour field '24': 24 is out of range 0-23"
+        );
+        assert_eq!(
+            error("*/0 * * * *"),
+            "invalid minute field '*/0': step must be at least 1"
+        );
+        assert_eq!(
+            error("0 0 * JAN *"),
+            "invalid month field 'JAN': 'JAN' is not a number"
+        );
+        assert_eq!(
+            error("0 0 * * 5-1"),
+            "invalid day-of-week field '5-1': range 5-1 is reversed"
+        );
+        assert_eq!(
+            error("0 0 1,,2 * *"),
+            "invalid day-of-month field '1,,2': empty list item"
+        );
+    }
+}
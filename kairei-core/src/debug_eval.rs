@@ -0,0 +1,324 @@
+//! # Debug Evaluation
+//!
+//! [`System::eval_expression`](crate::system::System::eval_expression) evaluates a DSL
+//! expression against the state of a running agent, so that a developer can inspect
+//! the agent from a debug console:
+//!
+//! ```text
+//! profile.city == "Tokyo"   →   Boolean(true) : Boolean
+//! ```
+//!
+//! The source goes through the same tokenizer and parser as agent definitions, and
+//! must be a single expression. Before anything runs:
+//!
+//! * assignments and `emit` are rejected, since evaluation never writes;
+//! * `think`, `request`, `will`, `await` and `call_tool` are rejected anywhere in the
+//!   expression, since they reach outside the agent;
+//! * the expression is type checked against the agent's state variables, each typed
+//!   from its current value.
+//!
+//! The expression is then evaluated against a snapshot of the state, in a read-only
+//! context of its own with no providers and a private event bus, within
+//! [`SystemConfig::debug_eval_timeout`](crate::config::SystemConfig::debug_eval_timeout).
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use dashmap::DashMap;
+use thiserror::Error;
+
+use crate::{
+    ASTError, Expression, Statement, TypeInfo,
+    ast_registry::AstRegistry,
+    catalog::AgentCatalog,
+    config::ContextConfig,
+    eval::{
+        context::{AgentInfo, ExecutionContext, StateAccessMode},
+        evaluator::{EvalError, Evaluator},
+        expression::Value,
+    },
+    event_bus::EventBus,
+    provider::provider_registry::ProviderInstance,
+    type_checker::{TypeCheckError, TypeContext, visitor::DefaultVisitor},
+};
+
+#[derive(Debug, Error)]
+pub enum DebugEvalError {
+    #[error("Failed to parse expression: {0}")]
+    Parse(#[from] ASTError),
+
+    #[error("{0} is not allowed, debug evaluation is read-only")]
+    ReadOnly(&'static str),
+
+    #[error("Only a single expression can be evaluated, found {0}")]
+    NotAnExpression(&'static str),
+
+    #[error("`{keyword}` expressions are not allowed in debug evaluation, since they {reason}")]
+    Forbidden {
+        keyword: &'static str,
+        reason: &'static str,
+    },
+
+    #[error("Type error: {0}")]
+    Type(#[from] TypeCheckError),
+
+    #[error("Evaluation failed: {0}")]
+    Eval(#[from] EvalError),
+
+    #[error("Evaluation did not finish within {0:?}")]
+    Timeout(Duration),
+}
+
+pub type DebugEvalResult<T> = Result<T, DebugEvalError>;
+
+/// The value of an expression and its type
+#[derive(Debug, Clone, PartialEq)]
+pub struct DebugEvaluation {
+    pub value: Value,
+    pub type_info: TypeInfo,
+}
+
+/// Parse `source` and check that it is a single expression safe to evaluate
+pub fn parse_expression(registry: &AstRegistry, source: &str) -> DebugEvalResult<Expression> {
+    match registry.create_statement_from_dsl(source)? {
+        Statement::Expression(expression) => {
+            check_expression(&expression)?;
+            Ok(expression)
+        }
+        Statement::Assignment { .. } => Err(DebugEvalError::ReadOnly("Assignment")),
+        Statement::Let { .. } => Err(DebugEvalError::NotAnExpression("`let`")),
+        Statement::Emit { .. } => Err(DebugEvalError::ReadOnly("`emit`")),
+        Statement::Log { .. } => Err(DebugEvalError::NotAnExpression("`log`")),
+        Statement::Return(_) => Err(DebugEvalError::NotAnExpression("`return`")),
+        Statement::Yield(_) => Err(DebugEvalError::NotAnExpression("`yield`")),
+        Statement::If { .. } => Err(DebugEvalError::NotAnExpression("`if`")),
+        Statement::Retry { .. } => Err(DebugEvalError::NotAnExpression("`retry`")),
+        Statement::Block(_) => Err(DebugEvalError::NotAnExpression("a block")),
+        Statement::WithError { .. } => Err(DebugEvalError::NotAnExpression("`onFail`")),
+    }
+}
+
+/// Reject expressions that reach outside the agent
+fn check_expression(expression: &Expression) -> DebugEvalResult<()> {
+    match expression {
+        Expression::Literal(_) | Expression::Variable(_) | Expression::StateAccess(_) => Ok(()),
+        Expression::FunctionCall { function, .. } if function == "call_tool" => {
+            Err(DebugEvalError::Forbidden {
+                keyword: "call_tool",
+                reason: "call external APIs",
+            })
+        }
+        Expression::FunctionCall { arguments, .. } => {
+            arguments.iter().try_for_each(check_expression)
+        }
+        Expression::BinaryOp { left, right, .. } => {
+            check_expression(left)?;
+            check_expression(right)
+        }
+        Expression::Ok(inner) | Expression::Err(inner) => check_expression(inner),
+        Expression::Think { .. } => Err(DebugEvalError::Forbidden {
+            keyword: "think",
+            reason: "call an LLM",
+        }),
+        Expression::Request { .. } => Err(DebugEvalError::Forbidden {
+            keyword: "request",
+            reason: "send a request to another agent",
+        }),
+        Expression::WillAction { .. } => Err(DebugEvalError::Forbidden {
+            keyword: "will",
+            reason: "act outside the agent",
+        }),
+        Expression::Await(_) => Err(DebugEvalError::Forbidden {
+            keyword: "await",
+            reason: "wait for requests and thinks",
+        }),
+    }
+}
+
+/// Type `expression` against the state variables in `state`
+pub fn infer_type(
+    expression: &Expression,
+    state: &HashMap<String, Value>,
+) -> DebugEvalResult<TypeInfo> {
+    let mut ctx = TypeContext::new();
+    for (name, value) in state {
+        ctx.scope.insert_type(name.clone(), type_of(value));
+    }
+    Ok(DefaultVisitor::new().infer_type(expression, &ctx)?)
+}
+
+/// Evaluate `expression` against a snapshot of the state of `agent_name`
+pub async fn evaluate(
+    agent_name: &str,
+    expression: &Expression,
+    state: HashMap<String, Value>,
+    catalog: Arc<AgentCatalog>,
+    timeout: Duration,
+) -> DebugEvalResult<DebugEvaluation> {
+    let type_info = infer_type(expression, &state)?;
+
+    // スナップショットを専用のコンテキストに写し、読み取り専用で評価する
+    let snapshot = ExecutionContext::new(
+        Arc::new(EventBus::new(16)),
+        AgentInfo {
+            agent_name: agent_name.to_string(),
+            ..Default::default()
+        },
+        StateAccessMode::ReadWrite,
+        ContextConfig::default(),
+        Arc::new(ProviderInstance::default()),
+        Arc::new(DashMap::new()),
+        vec![],
+    )
+    .with_catalog(catalog);
+    for (name, value) in state {
+        snapshot.set_state(&name, value).map_err(EvalError::from)?;
+    }
+    let context = Arc::new(snapshot.fork(Some(StateAccessMode::ReadOnly)).await);
+
+    let value = tokio::time::timeout(
+        timeout,
+        Evaluator::new().eval_expression(expression, context),
+    )
+    .await
+    .map_err(|_| DebugEvalError::Timeout(timeout))??;
+    Ok(DebugEvaluation { value, type_info })
+}
+
+/// Type of a state value, as the type checker infers it for the equivalent literal
+pub fn type_of(value: &Value) -> TypeInfo {
+    match value {
+        Value::Integer(_) | Value::UInteger(_) => TypeInfo::Simple("Int".to_string()),
+        Value::Float(_) => TypeInfo::Simple("Float".to_string()),
+        Value::String(_) | Value::Secret(_) => TypeInfo::Simple("String".to_string()),
+        Value::Boolean(_) => TypeInfo::Simple("Boolean".to_string()),
+        Value::Duration(_) => TypeInfo::Simple("Duration".to_string()),
+        Value::Error(_) => TypeInfo::Simple("Error".to_string()),
+        Value::List(items) => TypeInfo::Array(Box::new(common_type(items))),
+        // キーは実行時にしか分からないので、値の型だけを揃える
+        Value::Map(fields) => TypeInfo::Map(
+            Box::new(TypeInfo::Simple("String".to_string())),
+            Box::new(common_type(fields.values())),
+        ),
+        Value::Set(set) => {
+            let elements: Vec<Value> = set.iter().cloned().map(Value::from).collect();
+            TypeInfo::Set(Box::new(common_type(&elements)))
+        }
+        Value::Ok(inner) => TypeInfo::Result {
+            ok_type: Box::new(type_of(inner)),
+            err_type: Box::new(TypeInfo::Simple("Error".to_string())),
+        },
+        Value::Err(inner) => TypeInfo::Result {
+            ok_type: Box::new(TypeInfo::any()),
+            err_type: Box::new(type_of(inner)),
+        },
+        Value::Delay(_) | Value::Tuple(_) | Value::Unit | Value::Null => TypeInfo::any(),
+    }
+}
+
+/// The type shared by all `values`, or `Any` if they differ
+fn common_type<'a>(values: impl IntoIterator<Item = &'a Value>) -> TypeInfo {
+    let mut types = values.into_iter().map(type_of);
+    let Some(first) = types.next() else {
+        return TypeInfo::any();
+    };
+    if types.all(|type_info| type_info == first) {
+        first
+    } else {
+        TypeInfo::any()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> DebugEvalResult<Expression> {
+        parse_expression(&AstRegistry::default(), source)
+    }
+
+    fn state() -> HashMap<String, Value> {
+        HashMap::from([
+            ("count".to_string(), Value::Integer(2)),
+            (
+                "profile".to_string(),
+                Value::Map(HashMap::from([
+                    ("name".to_string(), Value::String("Taro".to_string())),
+                    ("city".to_string(), Value::String("Tokyo".to_string())),
+                ])),
+            ),
+        ])
+    }
+
+    async fn eval(source: &str) -> DebugEvalResult<DebugEvaluation> {
+        evaluate(
+            "Counter",
+            &parse(source)?,
+            state(),
+            Arc::new(AgentCatalog::default()),
+            Duration::from_secs(1),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_reads_nested_state_field() -> DebugEvalResult<()> {
+        let evaluation = eval("profile.city").await?;
+        assert_eq!(evaluation.value, Value::String("Tokyo".to_string()));
+        assert_eq!(evaluation.type_info, TypeInfo::Simple("String".to_string()));
+
+        let evaluation = eval("count + 1").await?;
+        assert_eq!(evaluation.value, Value::Integer(3));
+        assert_eq!(evaluation.type_info, TypeInfo::Simple("Int".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_writes_are_rejected() {
+        assert!(matches!(
+            parse("count = 0"),
+            Err(DebugEvalError::ReadOnly("Assignment"))
+        ));
+        assert!(matches!(
+            parse("return count"),
+            Err(DebugEvalError::NotAnExpression(_))
+        ));
+    }
+
+    #[test]
+    fn test_requests_are_rejected() {
+        let error = parse(r#"len(request GetCount to Counter())"#).unwrap_err();
+        assert!(matches!(
+            error,
+            DebugEvalError::Forbidden {
+                keyword: "request",
+                ..
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "`request` expressions are not allowed in debug evaluation, since they send a request to another agent"
+        );
+        assert!(matches!(
+            parse(r#"think("What is the weather?")"#),
+            Err(DebugEvalError::Forbidden {
+                keyword: "think",
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse(r#"len(call_tool("listPets", {}))"#),
+            Err(DebugEvalError::Forbidden {
+                keyword: "call_tool",
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_undefined_state_is_a_type_error() {
+        assert!(matches!(
+            eval("missing + 1").await,
+            Err(DebugEvalError::Type(_))
+        ));
+    }
+}
@@ -0,0 +1,447 @@
+//! # Debug Sessions
+//!
+//! A debug session runs a [`HandlerRecording`] again one statement at a time, so that
+//! a developer can watch what each statement of the handler's block did:
+//!
+//! * [`System::debug_start`] loads the recording, binds the recorded event to the
+//!   handler's parameters and pauses before the first statement of its block;
+//! * [`System::debug_step`] runs the next statement and reports its source text, the
+//!   state variables and local variables it changed, and the events emitted so far;
+//! * [`System::debug_inspect`] evaluates a read-only expression against the paused
+//!   state and local variables, as [`debug_eval`](crate::debug_eval) does for a
+//!   running agent.
+//!
+//! The session runs in a context of its own with a private event bus, like a
+//! [handler test](crate::handler_test), starting from the recorded initial state.
+//! `think` is answered with the recorded provider outputs, in the order they were
+//! made, so the session never reaches an LLM and steps through the recorded run
+//! deterministically. Nothing is committed. Each step is limited to
+//! [`SystemConfig::debug_eval_timeout`](crate::config::SystemConfig::debug_eval_timeout).
+//!
+//! The session ends after the last statement, a `return`, or a failing statement.
+//!
+//! [`System::debug_start`]: crate::system::System::debug_start
+//! [`System::debug_step`]: crate::system::System::debug_step
+//! [`System::debug_inspect`]: crate::system::System::debug_inspect
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use thiserror::Error;
+
+use crate::{
+    EventHandler, Expression, HandlerBlock,
+    config::ProviderConfig,
+    debug_eval::{self, DebugEvalError, DebugEvaluation},
+    eval::{
+        context::ExecutionContext,
+        evaluator::{EvalError, Evaluator},
+        recording::{self, HandlerKind, HandlerRecording, Recorder, StateChange},
+        statement::{ControlFlow, StatementResult},
+    },
+    event_bus::{Event, EventBus},
+    handler_test::{self, TestedAgent},
+    provider::{
+        capabilities::common::{Capabilities, CapabilityType},
+        provider::{Provider, ProviderSecret},
+        provider_registry::ProviderInstance,
+        request::{ProviderContext, ProviderRequest, ProviderResponse},
+        types::{ProviderError, ProviderResult},
+    },
+    runtime::RuntimeError,
+};
+
+/// Name of the provider answering `think` when the recording made no provider call
+pub const RECORDED_PROVIDER_NAME: &str = "debug_session_recorded";
+
+/// 1 回の実行で発行されるイベントを取りこぼさない程度の容量
+const EVENT_CAPACITY: usize = 256;
+
+#[derive(Debug, Error)]
+pub enum DebugSessionError {
+    #[error("Recording {0} not found")]
+    RecordingNotFound(String),
+
+    #[error("Debug session {0} not found")]
+    SessionNotFound(String),
+
+    #[error("Agent {agent_name} has no {kind:?} handler for event {event}")]
+    HandlerNotFound {
+        agent_name: String,
+        kind: HandlerKind,
+        event: String,
+    },
+
+    #[error("The requires clause of the handler does not hold for the recorded event")]
+    NotRun,
+
+    #[error("Debug session has finished")]
+    Finished,
+
+    #[error("Failed to run the handler: {0}")]
+    Runtime(#[from] RuntimeError),
+
+    #[error("Evaluation failed: {0}")]
+    Eval(#[from] EvalError),
+
+    #[error("Inspection failed: {0}")]
+    Inspect(#[from] DebugEvalError),
+
+    #[error("Statement did not finish within {0:?}")]
+    Timeout(Duration),
+}
+
+pub type DebugSessionResult<T> = Result<T, DebugSessionError>;
+
+/// What a statement did in a debug session
+#[derive(Debug, Clone, PartialEq)]
+pub struct DebugStep {
+    /// Position of the statement in the handler's block, from 0
+    pub index: usize,
+    /// Source text of the statement
+    pub statement: String,
+    /// State variables the statement changed, ordered by name
+    pub state_changes: Vec<StateChange>,
+    /// Local variables the statement bound or changed, ordered by name
+    pub variable_changes: Vec<StateChange>,
+    /// Events emitted by the handler so far, in order
+    pub pending_events: Vec<Event>,
+    /// Whether the handler has no statement left to run
+    pub finished: bool,
+}
+
+/// A recorded handler run being stepped through, see the [module docs](self)
+pub struct DebugSession {
+    recording: Arc<HandlerRecording>,
+    handler: EventHandler,
+    context: Arc<ExecutionContext>,
+    // ハンドラが発行したイベントを集める
+    recorder: Recorder,
+    evaluator: Evaluator,
+    agent: TestedAgent,
+    next: usize,
+    finished: bool,
+    timeout: Duration,
+}
+
+impl DebugSession {
+    /// Load `recording` of a handler of `agent` and pause before its first statement
+    pub async fn start(
+        agent: TestedAgent,
+        recording: Arc<HandlerRecording>,
+        timeout: Duration,
+    ) -> DebugSessionResult<Self> {
+        let event_type = recording.event.event_type.to_string();
+        let handler =
+            handler_test::find_handler(&agent.definition, &event_type, Some(recording.kind))
+                .map(|(_, handler)| handler.clone())
+                .ok_or_else(|| DebugSessionError::HandlerNotFound {
+                    agent_name: agent.definition.name.clone(),
+                    kind: recording.kind,
+                    event: event_type,
+                })?;
+
+        let (primary, providers) = recorded_providers(&recording);
+        let recorder = Recorder::new(recording.initial_state.clone());
+        let base = handler_test::sandbox_context(
+            &agent,
+            Arc::new(EventBus::new(EVENT_CAPACITY)),
+            primary,
+            providers,
+        )
+        .with_recorder(Some(recorder.clone()));
+        for (name, value) in &recording.initial_state {
+            base.set_state(name, value.clone())
+                .map_err(EvalError::from)?;
+        }
+        let evaluator = Evaluator::new();
+        let context = tokio::time::timeout(
+            timeout,
+            handler_test::enter_handler::<DebugSessionError>(
+                &evaluator,
+                &handler,
+                &base,
+                &recording.event,
+            ),
+        )
+        .await
+        .map_err(|_| DebugSessionError::Timeout(timeout))??
+        .ok_or(DebugSessionError::NotRun)?;
+
+        Ok(Self {
+            finished: handler.block.statements.is_empty(),
+            recording,
+            handler,
+            context,
+            recorder,
+            evaluator,
+            agent,
+            next: 0,
+            timeout,
+        })
+    }
+
+    pub fn recording(&self) -> &Arc<HandlerRecording> {
+        &self.recording
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Run the next statement of the handler's block
+    pub async fn step(&mut self) -> DebugSessionResult<DebugStep> {
+        if self.finished {
+            return Err(DebugSessionError::Finished);
+        }
+        let index = self.next;
+        let statement = self.handler.block.statements[index].clone();
+        let state_before = recording::current_state(&self.context).await;
+        let variables_before = self
+            .context
+            .local_variables()
+            .await
+            .map_err(EvalError::from)?;
+
+        // 1 文だけのブロックとして評価し、文の間で止まる
+        let block = HandlerBlock {
+            statements: vec![statement.clone()],
+        };
+        let result = tokio::time::timeout(
+            self.timeout,
+            self.evaluator.eval_block(&block, self.context.clone()),
+        )
+        .await;
+        self.next += 1;
+        let result = match result {
+            Ok(Ok(result)) => result,
+            Ok(Err(e)) => {
+                self.finished = true;
+                return Err(e.into());
+            }
+            Err(_) => {
+                self.finished = true;
+                return Err(DebugSessionError::Timeout(self.timeout));
+            }
+        };
+        self.finished = matches!(result, StatementResult::Control(ControlFlow::Return(_)))
+            || self.next == self.handler.block.statements.len();
+
+        let state_after = recording::current_state(&self.context).await;
+        let variables_after = self
+            .context
+            .local_variables()
+            .await
+            .map_err(EvalError::from)?;
+        Ok(DebugStep {
+            index,
+            statement: recording::statement_text(&statement),
+            state_changes: recording::state_changes(&state_before, &state_after),
+            variable_changes: recording::state_changes(&variables_before, &variables_after),
+            pending_events: self.recorder.emitted_events(),
+            finished: self.finished,
+        })
+    }
+
+    /// Evaluate `expression` against the paused state and local variables. Local
+    /// variables hide state variables of the same name, as in the handler
+    pub async fn inspect(&self, expression: &Expression) -> DebugSessionResult<DebugEvaluation> {
+        let mut values = recording::current_state(&self.context).await;
+        values.extend(
+            self.context
+                .local_variables()
+                .await
+                .map_err(EvalError::from)?,
+        );
+        Ok(debug_eval::evaluate(
+            &self.agent.definition.name,
+            expression,
+            values,
+            self.agent.catalog.clone(),
+            self.timeout,
+        )
+        .await?)
+    }
+}
+
+/// 記録された出力を呼び出し順に返すプロバイダー
+#[derive(Debug)]
+struct RecordedProvider {
+    outputs: Mutex<VecDeque<String>>,
+}
+
+#[async_trait]
+impl Provider for RecordedProvider {
+    async fn execute(
+        &self,
+        _context: &ProviderContext,
+        _request: &ProviderRequest,
+    ) -> ProviderResult<ProviderResponse> {
+        // 記録より多く呼ばれたら、記録と違う実行になっている
+        let output = self.outputs.lock().unwrap().pop_front().ok_or_else(|| {
+            ProviderError::InternalError("No recorded provider response left".to_string())
+        })?;
+        Ok(ProviderResponse {
+            output,
+            ..Default::default()
+        })
+    }
+
+    async fn capabilities(&self) -> Capabilities {
+        Capabilities::from(CapabilityType::Generate)
+    }
+
+    fn name(&self) -> &str {
+        RECORDED_PROVIDER_NAME
+    }
+
+    async fn initialize(
+        &mut self,
+        _config: &ProviderConfig,
+        _secret: &ProviderSecret,
+    ) -> ProviderResult<()> {
+        Ok(())
+    }
+}
+
+/// 記録されたすべてのプロバイダー名で、記録された出力を返すプロバイダー。
+/// プライマリは最初に呼ばれたプロバイダー
+fn recorded_providers(
+    recording: &HandlerRecording,
+) -> (
+    Arc<ProviderInstance>,
+    DashMap<String, Arc<ProviderInstance>>,
+) {
+    let provider: Arc<dyn Provider> = Arc::new(RecordedProvider {
+        outputs: Mutex::new(
+            recording
+                .responses
+                .iter()
+                .map(|response| response.output.clone())
+                .collect(),
+        ),
+    });
+    let instance = |name: &str| {
+        Arc::new(ProviderInstance {
+            config: ProviderConfig {
+                name: name.to_string(),
+                ..Default::default()
+            },
+            provider: provider.clone(),
+            secret: ProviderSecret::default(),
+        })
+    };
+    let providers = DashMap::new();
+    for response in &recording.responses {
+        providers
+            .entry(response.provider.clone())
+            .or_insert_with(|| instance(&response.provider));
+    }
+    let primary = recording.responses.first().map_or_else(
+        || instance(RECORDED_PROVIDER_NAME),
+        |response| providers.get(&response.provider).unwrap().clone(),
+    );
+    (primary, providers)
+}
+
+/// Debug sessions of a system, keyed by session ID
+pub type DebugSessions = DashMap<String, Arc<tokio::sync::Mutex<DebugSession>>>;
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::Utc;
+
+    use super::*;
+    use crate::{
+        ast_registry::AstRegistry,
+        catalog::AgentCatalog,
+        eval::{expression::Value, recording::RecordedResponse},
+        event_bus,
+        event_registry::EventType,
+    };
+
+    async fn forecaster() -> TestedAgent {
+        let root = AstRegistry::default()
+            .create_ast_from_dsl(
+                r#"
+                micro Forecaster {
+                    react {
+                        on Forecast(city: String) {
+                            today = think("Weather in ${city} today")
+                            tomorrow = think("Weather in ${city} tomorrow")
+                        }
+                    }
+                }
+                "#,
+            )
+            .await
+            .unwrap();
+        TestedAgent {
+            definition: Arc::new(root.micro_agent_defs[0].clone()),
+            state: None,
+            config_values: HashMap::new(),
+            world_policies: vec![],
+            catalog: Arc::new(AgentCatalog::default()),
+        }
+    }
+
+    fn recording(outputs: &[&str]) -> Arc<HandlerRecording> {
+        Arc::new(HandlerRecording {
+            id: "recording".to_string(),
+            agent_name: "Forecaster".to_string(),
+            kind: HandlerKind::React,
+            event: Event {
+                event_type: EventType::Custom("Forecast".to_string()),
+                parameters: HashMap::from([(
+                    "city".to_string(),
+                    event_bus::Value::String("Tokyo".to_string()),
+                )]),
+                ..Default::default()
+            },
+            initial_state: HashMap::new(),
+            responses: outputs
+                .iter()
+                .map(|output| RecordedResponse {
+                    provider: "openai".to_string(),
+                    output: output.to_string(),
+                })
+                .collect(),
+            steps: vec![],
+            recorded_at: Utc::now(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_think_is_answered_from_the_recording() -> DebugSessionResult<()> {
+        let mut session = DebugSession::start(
+            forecaster().await,
+            recording(&["Sunny"]),
+            Duration::from_secs(1),
+        )
+        .await?;
+
+        let step = session.step().await?;
+        assert_eq!(step.variable_changes.len(), 1);
+        assert_eq!(
+            step.variable_changes[0].after,
+            Value::Map(HashMap::from([(
+                "output".to_string(),
+                Value::String("Sunny".to_string())
+            )]))
+        );
+        // 記録より多い呼び出しは LLM に届かずに失敗し、セッションは終わる
+        assert!(matches!(
+            session.step().await,
+            Err(DebugSessionError::Eval(_))
+        ));
+        assert!(session.is_finished());
+        Ok(())
+    }
+}
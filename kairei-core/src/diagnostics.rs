@@ -0,0 +1,429 @@
+//! # Diagnostic Code Registry
+//!
+//! Every code in [`codes`] is registered here with the component that emits it, a
+//! one-line summary and an extended explanation: what the diagnostic means, an
+//! example of the failing construct and how to fix it. [`explain`] looks a code up,
+//! e.g. for `kairei explain type.mismatch` or `GET /api/v1/docs/diagnostics/{code}`.
+//!
+//! A test checks that every code in [`DIAGNOSTIC_CODES`] is registered, and that
+//! diagnostics are only created from the constants of [`codes`].
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::message_catalog::{DIAGNOSTIC_CODES, codes};
+
+/// The part of the toolchain that emits a diagnostic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticComponent {
+    Tokenizer,
+    Parser,
+    AstRegistry,
+    AnalysisLimits,
+    TypeChecker,
+    /// Errors of requests, shown to the end users in failure responses
+    Runtime,
+}
+
+/// The registered documentation of a diagnostic code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+pub struct Explanation {
+    pub code: &'static str,
+    pub component: DiagnosticComponent,
+    /// One-line description
+    pub summary: &'static str,
+    /// What triggers the diagnostic
+    pub explanation: &'static str,
+    /// DSL (or configuration) that produces the diagnostic
+    pub example: &'static str,
+    /// How to fix the example
+    pub fix: &'static str,
+}
+
+/// The explanation of `code`, or `None` for an unknown code
+pub fn explain(code: &str) -> Option<Explanation> {
+    EXPLANATIONS
+        .iter()
+        .find(|explanation| explanation.code == code)
+        .copied()
+}
+
+/// The explanations of every diagnostic code, in the order of [`DIAGNOSTIC_CODES`]
+pub fn explanations() -> Vec<Explanation> {
+    DIAGNOSTIC_CODES
+        .iter()
+        .filter_map(|code| explain(code))
+        .collect()
+}
+
+use DiagnosticComponent::*;
+
+const EXPLANATIONS: &[Explanation] = &[
+    Explanation {
+        code: codes::PARSE_UNEXPECTED_EOF,
+        component: Parser,
+        summary: "The DSL ends in the middle of a construct",
+        explanation: "The parser reached the end of the input while a block, expression or \
+                      statement was still open. This is usually a missing closing brace or \
+                      parenthesis.",
+        example: "micro Counter {\n    state { count: Int = 0; }\n",
+        fix: "micro Counter {\n    state { count: Int = 0; }\n}",
+    },
+    Explanation {
+        code: codes::PARSE_UNEXPECTED,
+        component: Parser,
+        summary: "A token other than the expected one was found",
+        explanation: "The construct being parsed requires a specific token, such as a \
+                      delimiter or keyword, at this position.",
+        example: "micro Counter {\n    state { count: Int = 0 }\n}",
+        fix: "micro Counter {\n    state { count: Int = 0; }\n}",
+    },
+    Explanation {
+        code: codes::PARSE_NO_ALTERNATIVE,
+        component: Parser,
+        summary: "No construct of the DSL starts with the tokens here",
+        explanation: "The parser tried every construct allowed at this position, e.g. every \
+                      kind of statement in a handler, and none of them matched. Check for a \
+                      misspelled keyword.",
+        example: "micro Counter {\n    observe {\n        on Tick { count += 1 }\n    }\n}",
+        fix: "micro Counter {\n    observe {\n        on Tick { self.count = self.count + 1 }\n    }\n}",
+    },
+    Explanation {
+        code: codes::PARSE_FAILURE,
+        component: Parser,
+        summary: "A construct started but could not be completed",
+        explanation: "The parser committed to a construct, for example after its keyword, \
+                      and the rest of it is malformed. The message names the construct.",
+        example: "micro Greeter {\n    answer {\n        on request Greet() -> Result<String, Error> {\n            return Ok(\"hi\"\n        }\n    }\n}",
+        fix: "micro Greeter {\n    answer {\n        on request Greet() -> Result<String, Error> {\n            return Ok(\"hi\")\n        }\n    }\n}",
+    },
+    Explanation {
+        code: codes::PARSE_TRAILING_TOKENS,
+        component: Parser,
+        summary: "Tokens remain after the last complete definition",
+        explanation: "Everything up to a point parsed as valid definitions, but the input \
+                      continues with tokens that start no definition. This is usually an \
+                      extra closing brace.",
+        example: "micro Counter { }\n}",
+        fix: "micro Counter { }",
+    },
+    Explanation {
+        code: codes::TOKENIZE_INVALID,
+        component: Tokenizer,
+        summary: "The input contains a character sequence that is not a token",
+        explanation: "The tokenizer could not read a keyword, identifier, symbol, literal or \
+                      comment at this position, e.g. a stray character or an unterminated \
+                      block comment.",
+        example: "micro Counter { @count }",
+        fix: "micro Counter { state { count: Int = 0; } }",
+    },
+    Explanation {
+        code: codes::AST_NOT_FOUND,
+        component: AstRegistry,
+        summary: "A definition was looked up by a name that is not registered",
+        explanation: "The system was asked for the AST of an agent or world that was never \
+                      registered, e.g. when starting or updating an agent by a misspelled \
+                      name.",
+        example: "system.get_agent_ast(\"Countr\")",
+        fix: "system.get_agent_ast(\"Counter\")",
+    },
+    Explanation {
+        code: codes::LIMIT_TOO_MANY_STATEMENTS,
+        component: AnalysisLimits,
+        summary: "A handler has more statements than allowed",
+        explanation: "The number of statements of a handler, including the statements nested \
+                      in its blocks, exceeds `analysis_limits.max_statements_per_handler`.",
+        example: "analysis_limits:\n  max_statements_per_handler: 2\n\non Tick { a = 1\n b = 2\n c = 3 }",
+        fix: "Split the handler into smaller handlers or events, or raise \
+              `analysis_limits.max_statements_per_handler`.",
+    },
+    Explanation {
+        code: codes::LIMIT_TOO_MANY_HANDLERS,
+        component: AnalysisLimits,
+        summary: "An agent has more handlers than allowed",
+        explanation: "The number of handlers of an agent, including lifecycle handlers, \
+                      exceeds `analysis_limits.max_handlers_per_agent`.",
+        example: "analysis_limits:\n  max_handlers_per_agent: 1\n\nmicro A {\n    observe { on Tick { } on Tock { } }\n}",
+        fix: "Split the agent into several agents, or raise \
+              `analysis_limits.max_handlers_per_agent`.",
+    },
+    Explanation {
+        code: codes::LIMIT_NESTING_TOO_DEEP,
+        component: AnalysisLimits,
+        summary: "Expressions or brackets are nested too deeply",
+        explanation: "Nested expressions or, before parsing, nested brackets go deeper than \
+                      `analysis_limits.max_nesting_depth`.",
+        example: "analysis_limits:\n  max_nesting_depth: 2\n\nx = ((1 + (2 + 3)))",
+        fix: "Assign intermediate results to variables, or raise \
+              `analysis_limits.max_nesting_depth`.",
+    },
+    Explanation {
+        code: codes::VERSION_INVALID,
+        component: AstRegistry,
+        summary: "The DSL version declaration is malformed",
+        explanation: "A file declares its DSL version as `kairei \"MAJOR.MINOR\"` on its first \
+                      line, and the declared value is not in that form.",
+        example: "kairei \"v0.4\"",
+        fix: "kairei \"0.4\"",
+    },
+    Explanation {
+        code: codes::VERSION_TOO_NEW,
+        component: AstRegistry,
+        summary: "The file is written for a newer DSL than this build implements",
+        explanation: "The file declares a DSL version newer than the one this build \
+                      implements, so it may use syntax this build does not know.",
+        example: "kairei \"0.6\"",
+        fix: "Upgrade KAIREI, or rewrite the file for the supported version and declare it.",
+    },
+    Explanation {
+        code: codes::VERSION_TOO_OLD,
+        component: AstRegistry,
+        summary: "The file is written for a DSL version this build no longer reads",
+        explanation: "Files for older DSL versions are rewritten by compatibility shims, \
+                      down to the oldest version this build still has shims for.",
+        example: "kairei \"0.3\"",
+        fix: "Migrate the file to the current DSL and update its declaration.",
+    },
+    Explanation {
+        code: codes::TYPE_MISMATCH,
+        component: TypeChecker,
+        summary: "A value has a different type than required",
+        explanation: "A value is used where another type is expected, e.g. in an assignment \
+                      to a typed state variable or a condition.",
+        example: "state { count: Int = \"zero\"; }",
+        fix: "state { count: Int = 0; }",
+    },
+    Explanation {
+        code: codes::TYPE_UNDEFINED_TYPE,
+        component: TypeChecker,
+        summary: "A type name is not defined",
+        explanation: "A type annotation names a type that is neither built in nor declared \
+                      in the world.",
+        example: "state { count: Integer = 0; }",
+        fix: "state { count: Int = 0; }",
+    },
+    Explanation {
+        code: codes::TYPE_INVALID_TYPE_ARGUMENTS,
+        component: TypeChecker,
+        summary: "The type arguments or typed arguments of a construct are invalid",
+        explanation: "A generic type or a built-in function received arguments of the wrong \
+                      number or kind, e.g. a weighted choice with fewer weights than options.",
+        example: "choice = choose_weighted([\"a\", \"b\"], [1])",
+        fix: "choice = choose_weighted([\"a\", \"b\"], [1, 1])",
+    },
+    Explanation {
+        code: codes::TYPE_RECURSIVE_TYPE,
+        component: TypeChecker,
+        summary: "Declared types contain each other without indirection",
+        explanation: "A declared type reaches itself through fields that always hold a value, \
+                      so its values would never end. A reference through Option, Array, Set \
+                      or Map breaks the cycle, since it can be empty.",
+        example: "type Node { value: Int, next: Node }",
+        fix: "type Node { value: Int, next: Option{Node} }",
+    },
+    Explanation {
+        code: codes::TYPE_INVALID_STATE_VARIABLE,
+        component: TypeChecker,
+        summary: "A state variable is declared or used incorrectly",
+        explanation: "A state variable is declared with an invalid initial value, or assigned \
+                      where it is read-only, such as the agent config.",
+        example: "on Tick { config.limit = 10 }",
+        fix: "on Tick { self.limit = 10 }",
+    },
+    Explanation {
+        code: codes::TYPE_INVALID_HANDLER_SIGNATURE,
+        component: TypeChecker,
+        summary: "A handler's parameters or return type do not fit its event or request",
+        explanation: "A handler declares parameters that collide with its implicit variables, \
+                      an event is emitted without a required parameter, a contract does not \
+                      match its handler, or `yield` is used outside an answer handler.",
+        example: "events { Tick(count: Int) }\n\non Start { emit Tick() }",
+        fix: "events { Tick(count: Int) }\n\non Start { emit Tick(count: 1) }",
+    },
+    Explanation {
+        code: codes::TYPE_INVALID_THINK_BLOCK,
+        component: TypeChecker,
+        summary: "A think block has invalid attributes",
+        explanation: "The attributes of a think block are outside the ranges LLM providers \
+                      accept, e.g. a temperature outside 0.0 to 2.0.",
+        example: "think(\"Summarize\") with { temperature: 3.0 }",
+        fix: "think(\"Summarize\") with { temperature: 0.7 }",
+    },
+    Explanation {
+        code: codes::TYPE_INFERENCE,
+        component: TypeChecker,
+        summary: "The type of an expression cannot be inferred",
+        explanation: "The type checker has no way to determine the type of an expression, \
+                      e.g. an empty list without an annotation.",
+        example: "items = []",
+        fix: "state { items: List<String> = []; }",
+    },
+    Explanation {
+        code: codes::TYPE_UNDEFINED_VARIABLE,
+        component: TypeChecker,
+        summary: "A variable is used but not defined",
+        explanation: "The name is neither a local variable, a handler parameter nor a state \
+                      variable of the agent.",
+        example: "on Tick { self.count = cnt + 1 }",
+        fix: "on Tick { self.count = self.count + 1 }",
+    },
+    Explanation {
+        code: codes::TYPE_UNDEFINED_FUNCTION,
+        component: TypeChecker,
+        summary: "A function is called but not defined",
+        explanation: "The called name is not a built-in function or a function available to \
+                      the agent.",
+        example: "x = lenght(items)",
+        fix: "x = len(items)",
+    },
+    Explanation {
+        code: codes::TYPE_INVALID_RETURN_TYPE,
+        component: TypeChecker,
+        summary: "A handler returns a value of the wrong type",
+        explanation: "The returned value does not match the return type declared by the \
+                      request handler.",
+        example: "on request Count() -> Result<Int, Error> { return Ok(\"one\") }",
+        fix: "on request Count() -> Result<Int, Error> { return Ok(1) }",
+    },
+    Explanation {
+        code: codes::TYPE_INVALID_ARGUMENT_TYPE,
+        component: TypeChecker,
+        summary: "A function argument has the wrong type",
+        explanation: "An argument passed to a function does not have the type of the \
+                      corresponding parameter.",
+        example: "x = len(42)",
+        fix: "x = len(\"42\")",
+    },
+    Explanation {
+        code: codes::TYPE_INVALID_OPERATOR_TYPE,
+        component: TypeChecker,
+        summary: "An operator is applied to operands it does not support",
+        explanation: "The operand types of a binary operator do not fit the operator, e.g. \
+                      subtracting a string from a number.",
+        example: "x = 1 - \"one\"",
+        fix: "x = 1 - 1",
+    },
+    Explanation {
+        code: codes::TYPE_INVALID_WILL_ACTION,
+        component: TypeChecker,
+        summary: "A will action is malformed",
+        explanation: "A `will` action names an unknown action or is used where actions are \
+                      not allowed.",
+        example: "will frobnicate(\"x\")",
+        fix: "will notify(\"x\")",
+    },
+    Explanation {
+        code: codes::TYPE_WILL_ACTION_PARAMETER,
+        component: TypeChecker,
+        summary: "A will action has invalid parameters",
+        explanation: "The parameters of a `will` action are missing, extra or of the wrong \
+                      type for that action.",
+        example: "will notify()",
+        fix: "will notify(\"done\")",
+    },
+    Explanation {
+        code: codes::TYPE_INVALID_SISTENCE_CONTEXT,
+        component: TypeChecker,
+        summary: "A sistence construct is used outside a sistence agent",
+        explanation: "Constructs that need the proactive context of a sistence agent are used \
+                      in an agent that is not one.",
+        example: "micro Helper {\n    answer { on request Ask() -> Result<String, Error> { will notify(\"x\") } }\n}",
+        fix: "sistence Helper {\n    answer { on request Ask() -> Result<String, Error> { will notify(\"x\") } }\n}",
+    },
+    Explanation {
+        code: codes::TYPE_INVALID_SCHEDULE,
+        component: TypeChecker,
+        summary: "A world schedule is invalid",
+        explanation: "A `schedule` of the world has an invalid cron expression, or passes \
+                      arguments that are not named literals.",
+        example: "world W {\n    schedule \"0 24 * * *\" emit Nightly()\n}",
+        fix: "world W {\n    schedule \"0 2 * * *\" emit Nightly()\n}",
+    },
+    Explanation {
+        code: codes::REQUEST_PRECONDITION_FAILED,
+        component: Runtime,
+        summary: "The requires clause of an answer handler did not hold",
+        explanation: "The handler declares a `requires` precondition that evaluated to \
+                      false for this request, so its block did not run. With the `skip` \
+                      precondition mode the request is answered with no value instead.",
+        example: "on request Withdraw(amount: Int) -> Result<Int, Error> requires self.balance >= amount { ... }",
+        fix: "Send the request once the precondition holds, or check it in the handler \
+              block and answer with an explanatory error.",
+    },
+    Explanation {
+        code: codes::REQUEST_INVALID_PARAMETERS,
+        component: Runtime,
+        summary: "Request parameters violate the constraints of the handler",
+        explanation: "A parameter of the request does not satisfy the `where` constraint \
+                      declared for it. The response lists every violated constraint.",
+        example: "on request Forecast(days: Int where days in 1..14) ...\n// requested with days: 30",
+        fix: "// request with days: 7",
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, fs, path::Path};
+
+    use super::*;
+
+    #[test]
+    fn test_explain() {
+        let explanation = explain(codes::TYPE_MISMATCH).unwrap();
+        assert_eq!(explanation.code, "type.mismatch");
+        assert_eq!(explanation.component, DiagnosticComponent::TypeChecker);
+        assert!(explain("type.no_such_code").is_none());
+        assert_eq!(explanations().len(), DIAGNOSTIC_CODES.len());
+    }
+
+    #[test]
+    fn test_every_code_is_registered_once() {
+        let mut registered = HashSet::new();
+        for explanation in EXPLANATIONS {
+            assert!(
+                DIAGNOSTIC_CODES.contains(&explanation.code),
+                "unknown code {} in the registry",
+                explanation.code
+            );
+            assert!(
+                registered.insert(explanation.code),
+                "{} is registered twice",
+                explanation.code
+            );
+            assert!(!explanation.summary.is_empty() && !explanation.fix.is_empty());
+        }
+        for code in DIAGNOSTIC_CODES {
+            assert!(registered.contains(code), "{} is not registered", code);
+        }
+    }
+
+    // 登録済みのコード定数以外からは Diagnostic を作らない
+    #[test]
+    fn test_diagnostics_are_created_from_registered_codes() {
+        // このファイル自身に一致しないよう分けて書く
+        const CALL: &str = concat!("Diagnostic", "::new(");
+        fn walk(dir: &Path, offenders: &mut Vec<String>) {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    walk(&path, offenders);
+                } else if path.extension().is_some_and(|ext| ext == "rs") {
+                    let source = fs::read_to_string(&path).unwrap();
+                    for (index, _) in source.match_indices(CALL) {
+                        let argument = source[index + CALL.len()..].trim_start();
+                        if !argument.starts_with("codes::") {
+                            offenders.push(format!("{}: {}", path.display(), index));
+                        }
+                    }
+                }
+            }
+        }
+        let mut offenders = Vec::new();
+        walk(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("src"),
+            &mut offenders,
+        );
+        assert!(offenders.is_empty(), "{:?}", offenders);
+    }
+}
@@ -0,0 +1,318 @@
+//! Versions of the DSL and compatibility with files written for older ones.
+//!
+//! A DSL file may declare the version it is written for on its first line:
+//!
+//! ```text
+//! kairei "0.4"
+//!
+//! micro Counter { ... }
+//! ```
+//!
+//! [`check_compatibility`] compares the declaration against the versions this build
+//! reads. A file for a newer version than [`CURRENT_DSL_VERSION`] is rejected before
+//! type checking, instead of failing on whatever syntax it uses. A file for an older
+//! version, down to [`OLDEST_DSL_VERSION`], is rewritten by the [`SHIMS`] of every
+//! syntax change since, so that it means what it meant then. Files without a
+//! declaration are read as the current version.
+
+use std::{fmt, str::FromStr};
+
+use thiserror::Error;
+
+use crate::{
+    ast::{AnswerDef, ObserveDef, Parameter, ReactDef, Root, StateDef, TypeInfo},
+    message_catalog::{Diagnostic, codes},
+};
+
+/// `MAJOR.MINOR` version of the DSL
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DslVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl DslVersion {
+    pub const fn new(major: u32, minor: u32) -> Self {
+        Self { major, minor }
+    }
+}
+
+impl fmt::Display for DslVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+impl FromStr for DslVersion {
+    type Err = DslVersionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || DslVersionError::Invalid {
+            declared: s.to_string(),
+        };
+        let (major, minor) = s.trim().split_once('.').ok_or_else(invalid)?;
+        Ok(Self {
+            major: major.parse().map_err(|_| invalid())?,
+            minor: minor.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// The version of the DSL this build implements
+pub const CURRENT_DSL_VERSION: DslVersion = DslVersion::new(0, 5);
+
+/// The oldest version this build reads, through the [`SHIMS`]
+pub const OLDEST_DSL_VERSION: DslVersion = DslVersion::new(0, 4);
+
+#[derive(Debug, Clone, Error, PartialEq)]
+pub enum DslVersionError {
+    #[error("\"{declared}\" is not a DSL version, declare one as kairei \"MAJOR.MINOR\"")]
+    Invalid { declared: String },
+
+    #[error("this file requires DSL {required}, this build supports up to {supported}")]
+    TooNew {
+        required: DslVersion,
+        supported: DslVersion,
+    },
+
+    #[error(
+        "this file is written for DSL {declared}, this build reads DSL {oldest} to {current}; migrate it to DSL {current}"
+    )]
+    TooOld {
+        declared: DslVersion,
+        oldest: DslVersion,
+        current: DslVersion,
+    },
+}
+
+impl DslVersionError {
+    /// The diagnostic of this error, rendered by a [`MessageCatalog`](crate::message_catalog::MessageCatalog)
+    pub fn diagnostic(&self) -> Diagnostic {
+        match self {
+            Self::Invalid { declared } => {
+                Diagnostic::new(codes::VERSION_INVALID).with_arg("declared", declared)
+            }
+            Self::TooNew {
+                required,
+                supported,
+            } => Diagnostic::new(codes::VERSION_TOO_NEW)
+                .with_arg("required", required)
+                .with_arg("supported", supported),
+            Self::TooOld {
+                declared,
+                oldest,
+                current,
+            } => Diagnostic::new(codes::VERSION_TOO_OLD)
+                .with_arg("declared", declared)
+                .with_arg("oldest", oldest)
+                .with_arg("current", current),
+        }
+    }
+}
+
+/// Rewrites the AST of a file written before a syntax change into its current form
+#[derive(Debug, Clone, Copy)]
+pub struct CompatibilityShim {
+    /// The version that made the change; files declaring an older one are rewritten
+    pub introduced_in: DslVersion,
+    pub description: &'static str,
+    pub apply: fn(&mut Root),
+}
+
+/// Every shim, in the order of the versions that introduced their changes
+pub const SHIMS: &[CompatibilityShim] = &[CompatibilityShim {
+    introduced_in: DslVersion::new(0, 5),
+    description: "Primitive types are named Int, Float, String and Boolean instead of \
+                  i64, f64, string and bool",
+    apply: rename_primitive_types,
+}];
+
+/// Checks the version `root` declares against the versions this build reads, and
+/// applies the shims it needs. Returns the declared version.
+///
+/// # Errors
+/// * `DslVersionError::Invalid` - If the declaration is not `MAJOR.MINOR`
+/// * `DslVersionError::TooNew` - If the file is for a newer version than this build
+/// * `DslVersionError::TooOld` - If the file is older than [`OLDEST_DSL_VERSION`]
+pub fn check_compatibility(root: &mut Root) -> Result<Option<DslVersion>, DslVersionError> {
+    let declared: DslVersion = match &root.dsl_version {
+        Some(declared) => declared.parse()?,
+        None => return Ok(None),
+    };
+    if declared > CURRENT_DSL_VERSION {
+        return Err(DslVersionError::TooNew {
+            required: declared,
+            supported: CURRENT_DSL_VERSION,
+        });
+    }
+    if declared < OLDEST_DSL_VERSION {
+        return Err(DslVersionError::TooOld {
+            declared,
+            oldest: OLDEST_DSL_VERSION,
+            current: CURRENT_DSL_VERSION,
+        });
+    }
+    for shim in SHIMS.iter().filter(|shim| declared < shim.introduced_in) {
+        (shim.apply)(root);
+    }
+    Ok(Some(declared))
+}
+
+// 0.4 まではプリミティブ型を Rust 風の名前で書いていた
+fn rename_primitive_types(root: &mut Root) {
+    if let Some(world) = &mut root.world_def {
+        for event in &mut world.events.events {
+            rename_parameter_types(&mut event.parameters);
+        }
+        for handler in &mut world.handlers.handlers {
+            rename_parameter_types(&mut handler.parameters);
+        }
+    }
+    for agent in &mut root.micro_agent_defs {
+        rename_handler_types(
+            agent.state.as_mut(),
+            agent.observe.as_mut(),
+            agent.answer.as_mut(),
+            agent.react.as_mut(),
+        );
+        for contract in &mut agent.contracts {
+            rename_parameter_types(&mut contract.parameters);
+            rename_type(&mut contract.return_type);
+        }
+        if let Some(config) = &mut agent.config {
+            for parameter in &mut config.parameters {
+                rename_type(&mut parameter.type_info);
+            }
+        }
+    }
+    for agent in &mut root.sistence_agent_defs {
+        rename_handler_types(
+            agent.state.as_mut(),
+            agent.observe.as_mut(),
+            agent.answer.as_mut(),
+            agent.react.as_mut(),
+        );
+    }
+}
+
+fn rename_handler_types(
+    state: Option<&mut StateDef>,
+    observe: Option<&mut ObserveDef>,
+    answer: Option<&mut AnswerDef>,
+    react: Option<&mut ReactDef>,
+) {
+    if let Some(state) = state {
+        for variable in state.variables.values_mut() {
+            rename_type(&mut variable.type_info);
+        }
+    }
+    for handler in observe
+        .into_iter()
+        .flat_map(|observe| observe.handlers.iter_mut())
+        .chain(
+            react
+                .into_iter()
+                .flat_map(|react| react.handlers.iter_mut()),
+        )
+    {
+        rename_parameter_types(&mut handler.parameters);
+    }
+    if let Some(answer) = answer {
+        for handler in &mut answer.handlers {
+            rename_parameter_types(&mut handler.parameters);
+            rename_type(&mut handler.return_type);
+        }
+    }
+}
+
+fn rename_parameter_types(parameters: &mut [Parameter]) {
+    for parameter in parameters {
+        rename_type(&mut parameter.type_info);
+    }
+}
+
+fn rename_type(type_info: &mut TypeInfo) {
+    match type_info {
+        TypeInfo::Simple(name) => {
+            let renamed = match name.as_str() {
+                "i32" | "i64" => "Int",
+                "f32" | "f64" => "Float",
+                "str" | "string" => "String",
+                "bool" => "Boolean",
+                _ => return,
+            };
+            *name = renamed.to_string();
+        }
+        TypeInfo::Result { ok_type, err_type } => {
+            rename_type(ok_type);
+            rename_type(err_type);
+        }
+        TypeInfo::Option(inner) | TypeInfo::Array(inner) | TypeInfo::Set(inner) => {
+            rename_type(inner)
+        }
+        TypeInfo::Map(key, value) => {
+            rename_type(key);
+            rename_type(value);
+        }
+        TypeInfo::Custom { fields, .. } => {
+            for field in fields.values_mut() {
+                if let Some(type_info) = &mut field.type_info {
+                    rename_type(type_info);
+                }
+            }
+        }
+        TypeInfo::Generic { args, .. } => args.iter_mut().for_each(rename_type),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn root(dsl_version: Option<&str>) -> Root {
+        let mut root = Root::new(None, vec![], vec![]);
+        root.dsl_version = dsl_version.map(str::to_string);
+        root
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!("0.4".parse::<DslVersion>().unwrap(), DslVersion::new(0, 4));
+        assert!(DslVersion::new(0, 10) > DslVersion::new(0, 9));
+        assert!(matches!(
+            "0.4.1".parse::<DslVersion>(),
+            Err(DslVersionError::Invalid { .. })
+        ));
+        assert!("four".parse::<DslVersion>().is_err());
+    }
+
+    #[test]
+    fn test_check_compatibility() {
+        assert_eq!(check_compatibility(&mut root(None)), Ok(None));
+        assert_eq!(
+            check_compatibility(&mut root(Some("0.5"))),
+            Ok(Some(CURRENT_DSL_VERSION))
+        );
+
+        let error = check_compatibility(&mut root(Some("0.6"))).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "this file requires DSL 0.6, this build supports up to 0.5"
+        );
+        assert!(matches!(
+            check_compatibility(&mut root(Some("0.3"))),
+            Err(DslVersionError::TooOld { .. })
+        ));
+    }
+
+    #[test]
+    fn test_shims_are_ordered_and_supported() {
+        for pair in SHIMS.windows(2) {
+            assert!(pair[0].introduced_in <= pair[1].introduced_in);
+        }
+        for shim in SHIMS {
+            assert!(OLDEST_DSL_VERSION < shim.introduced_in);
+            assert!(shim.introduced_in <= CURRENT_DSL_VERSION);
+        }
+    }
+}
@@ -0,0 +1,331 @@
+//! # DSL Watcher
+//!
+//! Development mode as a library: a [`DslWatcher`] watches a set of DSL files and
+//! applies them to a running [`System`] when they change. Changes are debounced,
+//! then the files are parsed and type checked together as one DSL:
+//!
+//! - Files that do not compile are reported with their rendered diagnostics, and
+//!   the system keeps running the last files that did.
+//! - When the world is unchanged, only the agents whose definitions changed are
+//!   reloaded with [`System::reload_agent`], and agents dropped from the files are
+//!   removed.
+//! - Otherwise the whole world is reloaded with [`System::reload_world`].
+//!
+//! Reloads never overlap: changes made while one runs are collapsed into a single
+//! reload of the latest files. Each outcome is logged and passed to the callback
+//! given with [`DslWatcher::with_callback`], so CLIs can report it.
+//!
+//! ```no_run
+//! # use std::sync::Arc;
+//! # use kairei_core::{dsl_watcher::DslWatcher, system::System};
+//! # async fn example(system: Arc<System>) {
+//! let watcher = Arc::new(
+//!     DslWatcher::new(system, ["agents/world.kairei", "agents/planner.kairei"])
+//!         .with_initial_load(true)
+//!         .with_callback(|event| eprintln!("{}", event)),
+//! );
+//! let handle = watcher.spawn();
+//! # handle.abort();
+//! # }
+//! ```
+
+use std::{
+    fmt, fs,
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, SystemTime},
+};
+
+use tokio::{sync::Mutex, task::JoinHandle};
+use tracing::{info, warn};
+
+use crate::{Root, system::System};
+
+/// Called with the outcome of every load
+pub type WatchCallback = Arc<dyn Fn(&WatchEvent) + Send + Sync>;
+
+/// Outcome of loading the watched files
+#[derive(Debug, Clone, PartialEq)]
+pub enum WatchEvent {
+    /// The files were applied to the system
+    Reloaded(ReloadScope),
+    /// The files could not be read, did not compile or were rejected by the
+    /// system, which keeps running the last good ones
+    Failed { message: String },
+}
+
+/// What a reload replaced
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReloadScope {
+    /// The world and all agents of the files
+    World,
+    /// Only these agents; the rest of the system was untouched
+    Agents {
+        reloaded: Vec<String>,
+        removed: Vec<String>,
+    },
+}
+
+impl fmt::Display for WatchEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WatchEvent::Reloaded(ReloadScope::World) => write!(f, "Reloaded the world"),
+            WatchEvent::Reloaded(ReloadScope::Agents { reloaded, removed }) => {
+                write!(f, "Reloaded agents [{}]", reloaded.join(", "))?;
+                if !removed.is_empty() {
+                    write!(f, ", removed [{}]", removed.join(", "))?;
+                }
+                Ok(())
+            }
+            WatchEvent::Failed { message } => write!(f, "Reload failed: {}", message),
+        }
+    }
+}
+
+/// The files last applied to the system
+struct Applied {
+    sources: String,
+    root: Root,
+}
+
+/// Watches DSL files and reloads a [`System`] from them (see the [module docs](self))
+pub struct DslWatcher {
+    system: Arc<System>,
+    paths: Vec<PathBuf>,
+    debounce: Duration,
+    poll_interval: Duration,
+    initial_load: bool,
+    callback: Option<WatchCallback>,
+    // 変更の通し番号。デバウンス後に最新の変更だけが読み込む
+    changes: AtomicU64,
+    // 再読み込みを直列化するロックを兼ねる
+    applied: Mutex<Option<Applied>>,
+}
+
+impl DslWatcher {
+    pub fn new(system: Arc<System>, paths: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+        Self {
+            system,
+            paths: paths.into_iter().map(Into::into).collect(),
+            debounce: Duration::from_millis(300),
+            poll_interval: Duration::from_millis(500),
+            initial_load: false,
+            callback: None,
+            changes: AtomicU64::new(0),
+            applied: Mutex::new(None),
+        }
+    }
+
+    /// How long the files must stay unchanged before they are loaded
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// How often [`Self::spawn`] checks the modification times of the files
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Apply the files when the watcher starts. Without it, the files as they are
+    /// at start are taken as already loaded, and only later changes are applied.
+    pub fn with_initial_load(mut self, initial_load: bool) -> Self {
+        self.initial_load = initial_load;
+        self
+    }
+
+    pub fn with_callback(mut self, callback: impl Fn(&WatchEvent) + Send + Sync + 'static) -> Self {
+        self.callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Loads the files now and applies them as a whole world
+    pub async fn load(&self) -> Option<WatchEvent> {
+        let mut applied = self.applied.lock().await;
+        self.apply(&mut applied, true).await
+    }
+
+    /// Takes the files as they are as already loaded, so the next change is
+    /// applied as a diff against them
+    pub async fn baseline(&self) -> Option<WatchEvent> {
+        let mut applied = self.applied.lock().await;
+        let sources = match self.read_sources() {
+            Ok(sources) => sources,
+            Err(message) => return Some(self.report(WatchEvent::Failed { message })),
+        };
+        match self.system.parse_dsl(&sources).await {
+            Ok(root) => {
+                *applied = Some(Applied { sources, root });
+                None
+            }
+            Err(error) => Some(self.report(WatchEvent::Failed {
+                message: self.system.render_error(&error).await,
+            })),
+        }
+    }
+
+    /// Handles a change of the watched files.
+    ///
+    /// Returns `None` when a later change superseded this one during the debounce
+    /// or while an earlier reload ran, and when the files are unchanged since the
+    /// last load.
+    pub async fn handle_change(&self) -> Option<WatchEvent> {
+        let change = self.changes.fetch_add(1, Ordering::SeqCst) + 1;
+        tokio::time::sleep(self.debounce).await;
+        // 待つ間に次の変更があれば、そちらが読み込む
+        if self.changes.load(Ordering::SeqCst) != change {
+            return None;
+        }
+        let mut applied = self.applied.lock().await;
+        // 前の再読み込みを待つ間に来た変更も同じ
+        if self.changes.load(Ordering::SeqCst) != change {
+            return None;
+        }
+        self.apply(&mut applied, false).await
+    }
+
+    /// Loads or baselines the files, then polls their modification times and
+    /// handles each change
+    pub fn spawn(self: &Arc<Self>) -> JoinHandle<()> {
+        let watcher = self.clone();
+        tokio::spawn(async move {
+            if watcher.initial_load {
+                watcher.load().await;
+            } else {
+                watcher.baseline().await;
+            }
+            let mut modified = watcher.modified_times();
+            loop {
+                tokio::time::sleep(watcher.poll_interval).await;
+                let current = watcher.modified_times();
+                if current == modified {
+                    continue;
+                }
+                modified = current;
+                let watcher = watcher.clone();
+                tokio::spawn(async move { watcher.handle_change().await });
+            }
+        })
+    }
+
+    fn modified_times(&self) -> Vec<Option<SystemTime>> {
+        self.paths
+            .iter()
+            .map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+            .collect()
+    }
+
+    fn read_sources(&self) -> Result<String, String> {
+        let mut sources = String::new();
+        for path in &self.paths {
+            let content = fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            sources.push_str(&format!("// From: {}\n", path.display()));
+            sources.push_str(&content);
+            sources.push_str("\n\n");
+        }
+        Ok(sources)
+    }
+
+    async fn apply(&self, applied: &mut Option<Applied>, whole_world: bool) -> Option<WatchEvent> {
+        let sources = match self.read_sources() {
+            Ok(sources) => sources,
+            Err(message) => return Some(self.report(WatchEvent::Failed { message })),
+        };
+        // 保存し直しただけなら何もしない
+        if !whole_world && applied.as_ref().is_some_and(|a| a.sources == sources) {
+            return None;
+        }
+        let root = match self.system.parse_dsl(&sources).await {
+            Ok(root) => root,
+            Err(error) => {
+                return Some(self.report(WatchEvent::Failed {
+                    message: self.system.render_error(&error).await,
+                }));
+            }
+        };
+
+        let previous = applied.as_ref().map(|a| &a.root);
+        let scope = match previous {
+            Some(previous) if !whole_world && previous.world_def == root.world_def => {
+                agents_scope(previous, &root)
+            }
+            _ => ReloadScope::World,
+        };
+        let removed = previous.map(|previous| removed_agents(previous, &root));
+        if let Err(error) = self
+            .reload(&scope, &root, removed.unwrap_or_default())
+            .await
+        {
+            return Some(self.report(WatchEvent::Failed {
+                message: self.system.render_error(&error).await,
+            }));
+        }
+        *applied = Some(Applied { sources, root });
+        Some(self.report(WatchEvent::Reloaded(scope)))
+    }
+
+    async fn reload(
+        &self,
+        scope: &ReloadScope,
+        root: &Root,
+        removed: Vec<String>,
+    ) -> crate::system::SystemResult<()> {
+        if let ReloadScope::Agents { reloaded, .. } = scope {
+            for agent_def in &root.micro_agent_defs {
+                if reloaded.contains(&agent_def.name) {
+                    self.system.reload_agent(agent_def).await?;
+                }
+            }
+        } else {
+            self.system.reload_world(root.clone()).await?;
+        }
+        for agent_name in removed {
+            self.system.remove_agent(&agent_name).await?;
+        }
+        Ok(())
+    }
+
+    fn report(&self, event: WatchEvent) -> WatchEvent {
+        match &event {
+            WatchEvent::Failed { .. } => warn!("{}", event),
+            WatchEvent::Reloaded(_) => info!("{}", event),
+        }
+        if let Some(callback) = &self.callback {
+            callback(&event);
+        }
+        event
+    }
+}
+
+/// Agents of `next` that are new or changed since `previous`
+fn agents_scope(previous: &Root, next: &Root) -> ReloadScope {
+    let reloaded = next
+        .micro_agent_defs
+        .iter()
+        .filter(|agent_def| !previous.micro_agent_defs.contains(agent_def))
+        .map(|agent_def| agent_def.name.clone())
+        .collect();
+    ReloadScope::Agents {
+        reloaded,
+        removed: removed_agents(previous, next),
+    }
+}
+
+fn removed_agents(previous: &Root, next: &Root) -> Vec<String> {
+    previous
+        .micro_agent_defs
+        .iter()
+        .filter(|agent_def| {
+            !next
+                .micro_agent_defs
+                .iter()
+                .any(|next_def| next_def.name == agent_def.name)
+        })
+        .map(|agent_def| agent_def.name.clone())
+        .collect()
+}
@@ -0,0 +1,263 @@
+//! Per-agent budgets for LLM calls.
+//!
+//! An [`LlmBudget`] allows an agent at most `max_calls` think calls per fixed window.
+//! The window starts with the first call and restarts with the first call after it
+//! has elapsed. A think call beyond the budget fails before reaching the provider.
+//!
+//! [`ExecutionUsage`] counts think calls, tokens and sub-requests of a single handler
+//! execution against the agent's [`ExecutionGuardrails`], so that a handler stuck in
+//! a loop fails instead of calling the LLM without end.
+
+use std::{
+    fmt,
+    sync::{
+        Mutex,
+        atomic::{AtomicU32, AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::config::{ExecutionGuardrails, LlmBudgetConfig};
+
+/// Remaining budget, reported with the agent status
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct LlmBudgetStats {
+    /// Calls allowed per window
+    pub max_calls: u32,
+    /// Calls left in the current window
+    pub remaining: u32,
+    /// Milliseconds until the current window ends; 0 when no window is open
+    pub resets_in_ms: u64,
+}
+
+/// Rejection of a call over budget
+#[derive(Debug, Clone, PartialEq)]
+pub struct BudgetExhausted {
+    pub max_calls: u32,
+    pub window: Duration,
+    /// Time until the window ends and calls are allowed again
+    pub retry_after: Duration,
+}
+
+#[derive(Debug)]
+pub struct LlmBudget {
+    config: LlmBudgetConfig,
+    window: Mutex<Option<Window>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Window {
+    started: Instant,
+    used: u32,
+}
+
+impl LlmBudget {
+    pub fn new(config: LlmBudgetConfig) -> Self {
+        Self {
+            config,
+            window: Mutex::new(None),
+        }
+    }
+
+    /// Take one call from the budget
+    pub fn try_acquire(&self) -> Result<(), BudgetExhausted> {
+        let now = Instant::now();
+        let mut window = self.window.lock().unwrap();
+        let current = match *window {
+            Some(current) if now.duration_since(current.started) < self.config.window => current,
+            _ => Window {
+                started: now,
+                used: 0,
+            },
+        };
+        if current.used >= self.config.max_calls {
+            return Err(BudgetExhausted {
+                max_calls: self.config.max_calls,
+                window: self.config.window,
+                retry_after: self.config.window - now.duration_since(current.started),
+            });
+        }
+        *window = Some(Window {
+            used: current.used + 1,
+            ..current
+        });
+        Ok(())
+    }
+
+    pub fn stats(&self) -> LlmBudgetStats {
+        let now = Instant::now();
+        let window = *self.window.lock().unwrap();
+        let open = window.filter(|w| now.duration_since(w.started) < self.config.window);
+        LlmBudgetStats {
+            max_calls: self.config.max_calls,
+            remaining: self
+                .config
+                .max_calls
+                .saturating_sub(open.map_or(0, |w| w.used)),
+            resets_in_ms: open.map_or(0, |w| {
+                (self.config.window - now.duration_since(w.started)).as_millis() as u64
+            }),
+        }
+    }
+}
+
+/// A per-execution limit of [`ExecutionGuardrails`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Guardrail {
+    ThinkCalls,
+    Tokens,
+    SubRequests,
+}
+
+impl fmt::Display for Guardrail {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Guardrail::ThinkCalls => write!(f, "max_think_calls"),
+            Guardrail::Tokens => write!(f, "max_tokens"),
+            Guardrail::SubRequests => write!(f, "max_sub_requests"),
+        }
+    }
+}
+
+/// Usage of one handler execution so far
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionCounters {
+    pub think_calls: u32,
+    pub tokens: u64,
+    pub sub_requests: u32,
+}
+
+/// Rejection of a call over one of the guardrails
+#[derive(Debug, Clone, PartialEq)]
+pub struct GuardrailExceeded {
+    pub guardrail: Guardrail,
+    pub limit: u64,
+    pub counters: ExecutionCounters,
+}
+
+/// Counters of one handler execution, shared by the contexts forked from it
+#[derive(Debug, Default)]
+pub struct ExecutionUsage {
+    think_calls: AtomicU32,
+    tokens: AtomicU64,
+    sub_requests: AtomicU32,
+    // 最初に超えたガードレール。実行を後から分析するための印
+    tripped: Mutex<Option<Guardrail>>,
+}
+
+impl ExecutionUsage {
+    /// Count a think call. Fails once the calls or the tokens so far reach their limit.
+    pub fn begin_think(&self, limits: &ExecutionGuardrails) -> Result<(), GuardrailExceeded> {
+        if self.tokens.load(Ordering::SeqCst) >= limits.max_tokens {
+            return Err(self.trip(Guardrail::Tokens, limits.max_tokens));
+        }
+        Self::take(&self.think_calls, limits.max_think_calls)
+            .map(|_| ())
+            .map_err(|_| self.trip(Guardrail::ThinkCalls, limits.max_think_calls.into()))
+    }
+
+    /// Add the tokens reported for a think call
+    pub fn record_tokens(&self, tokens: u64) {
+        self.tokens.fetch_add(tokens, Ordering::SeqCst);
+    }
+
+    /// Count a request to another agent
+    pub fn begin_sub_request(&self, limits: &ExecutionGuardrails) -> Result<(), GuardrailExceeded> {
+        Self::take(&self.sub_requests, limits.max_sub_requests)
+            .map(|_| ())
+            .map_err(|_| self.trip(Guardrail::SubRequests, limits.max_sub_requests.into()))
+    }
+
+    pub fn counters(&self) -> ExecutionCounters {
+        ExecutionCounters {
+            think_calls: self.think_calls.load(Ordering::SeqCst),
+            tokens: self.tokens.load(Ordering::SeqCst),
+            sub_requests: self.sub_requests.load(Ordering::SeqCst),
+        }
+    }
+
+    /// The guardrail this execution hit first, if any
+    pub fn tripped(&self) -> Option<Guardrail> {
+        *self.tripped.lock().unwrap()
+    }
+
+    fn take(counter: &AtomicU32, max: u32) -> Result<u32, u32> {
+        counter.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+            (n < max).then(|| n + 1)
+        })
+    }
+
+    fn trip(&self, guardrail: Guardrail, limit: u64) -> GuardrailExceeded {
+        self.tripped.lock().unwrap().get_or_insert(guardrail);
+        GuardrailExceeded {
+            guardrail,
+            limit,
+            counters: self.counters(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget_resets_after_window() {
+        let budget = LlmBudget::new(LlmBudgetConfig {
+            max_calls: 2,
+            window: Duration::from_millis(100),
+        });
+        assert_eq!(budget.stats().remaining, 2);
+
+        budget.try_acquire().unwrap();
+        budget.try_acquire().unwrap();
+        let exhausted = budget.try_acquire().unwrap_err();
+        assert_eq!(exhausted.max_calls, 2);
+        assert!(exhausted.retry_after <= Duration::from_millis(100));
+        assert_eq!(budget.stats().remaining, 0);
+        assert!(budget.stats().resets_in_ms > 0);
+
+        std::thread::sleep(Duration::from_millis(120));
+        assert_eq!(budget.stats().remaining, 2);
+        budget.try_acquire().unwrap();
+        assert_eq!(budget.stats().remaining, 1);
+    }
+
+    #[test]
+    fn test_execution_usage_stops_at_limits() {
+        let limits = ExecutionGuardrails {
+            max_think_calls: 2,
+            max_tokens: 100,
+            max_sub_requests: 1,
+        };
+        let usage = ExecutionUsage::default();
+
+        usage.begin_think(&limits).unwrap();
+        usage.begin_think(&limits).unwrap();
+        let exceeded = usage.begin_think(&limits).unwrap_err();
+        assert_eq!(exceeded.guardrail, Guardrail::ThinkCalls);
+        assert_eq!(exceeded.limit, 2);
+        assert_eq!(exceeded.counters.think_calls, 2);
+
+        usage.begin_sub_request(&limits).unwrap();
+        assert_eq!(
+            usage.begin_sub_request(&limits).unwrap_err().guardrail,
+            Guardrail::SubRequests
+        );
+
+        let fresh = ExecutionUsage::default();
+        fresh.record_tokens(100);
+        assert_eq!(
+            fresh.begin_think(&limits).unwrap_err().guardrail,
+            Guardrail::Tokens
+        );
+
+        // 最初に超えたガードレールが記録される
+        assert_eq!(usage.tripped(), Some(Guardrail::ThinkCalls));
+        assert_eq!(fresh.tripped(), Some(Guardrail::Tokens));
+    }
+}
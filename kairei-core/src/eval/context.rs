@@ -1,24 +1,39 @@
 use std::collections::HashMap;
-use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use chrono::{DateTime, Utc};
 use dashmap::DashMap;
+use rand::{Rng, SeedableRng, rngs::StdRng};
 use serde::{Deserialize, Serialize};
 use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 use tracing::debug;
 use uuid::Uuid;
 
+use super::budget::{ExecutionUsage, GuardrailExceeded, LlmBudget, LlmBudgetStats};
+use super::evaluator::{ConstraintViolation, EvalError};
 use super::expression::Value;
 use super::generator::{PromptGenerator, StandardPromptGenerator};
+use super::limits::EvalUsage;
+use super::profile::Profiler;
+use super::recording::Recorder;
+use super::secret::{SecretValue, SecretVault};
 use crate::Policy;
-use crate::config::ContextConfig;
+use crate::agent_log::{AgentLogger, LogRecord};
+use crate::agent_settings::{AgentSettings, RuntimeSettings};
+use crate::catalog::AgentCatalog;
+use crate::clock::{Clock, SystemClock};
+use crate::config::{ContextConfig, EvalLimits, ExecutionGuardrails, OutputFormat};
 use crate::event::event_bus::{self, Event, EventBus, EventError, ToEventType};
+use crate::event::stream_event::{STREAM_EVENT_KEY, StreamEvent, StreamUsage, USAGE_KEY};
 use crate::event_registry::EventType;
+use crate::message_catalog::{Locale, MessageCatalog};
+use crate::provider::plugins::memory::shared_counters::SharedCounters;
+use crate::provider::plugins::openapi_tools::ToolRegistry;
 use crate::provider::provider_registry::ProviderInstance;
 use crate::provider::types::ProviderError;
-use crate::request_manager::{RequestError, RequestManager};
+use crate::request_manager::{Admission, RequestError, RequestManager, RetryBudget};
 use crate::runtime::RuntimeError;
 
 pub struct SafeRwLock<T> {
@@ -130,6 +145,126 @@ pub struct AgentInfo {
     pub created_at: DateTime<Utc>,
 }
 
+/// リクエスト単位で伝播されるエンドユーザーの情報。
+/// HTTP ヘッダーや認証情報から作られ、Answer ハンドラと think に渡される。
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct RequestContext {
+    pub user_id: Option<String>,
+    pub locale: Option<String>,
+    /// 会話モードのエージェントとの、これまでのやり取り（古い順）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conversation: Vec<ConversationTurn>,
+}
+
+/// A prior exchange of a conversation: what the user asked and what the agent answered
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct ConversationTurn {
+    pub user: String,
+    pub agent: String,
+}
+
+/// Answer ハンドラ内で RequestContext を参照するための変数名
+pub const REQUEST_USER_ID_VARIABLE: &str = "request_user_id";
+pub const REQUEST_LOCALE_VARIABLE: &str = "request_locale";
+
+/// Answer ハンドラ内で処理中のリクエストのメタデータ (`request.requester` 等) を参照するための変数名
+pub const REQUEST_METADATA_VARIABLE: &str = "request";
+/// Observe / React ハンドラ内で受信したイベントのメタデータ (`event.event_type` 等) を参照するための変数名
+pub const EVENT_METADATA_VARIABLE: &str = "event";
+/// ハンドラ内で実行中のロケール (`context.locale`) を参照するための変数名
+pub const EXECUTION_CONTEXT_VARIABLE: &str = "context";
+/// エージェントの `config` ブロックでロケールの既定値を宣言するパラメータ名
+pub const AGENT_LOCALE_PARAMETER: &str = "locale";
+/// 最初の think で作られるセッション ID を保持する状態変数名
+pub const SESSION_ID_STATE: &str = "session_id";
+/// ハンドラ内でエージェントの設定値 (`config.region` 等) を参照するための変数名
+pub const AGENT_CONFIG_VARIABLE: &str = "config";
+
+impl RequestContext {
+    pub fn new(user_id: Option<String>, locale: Option<String>) -> Self {
+        Self {
+            user_id,
+            locale,
+            conversation: Vec::new(),
+        }
+    }
+
+    /// think のプロンプトに含める、これまでのやり取り
+    pub fn with_conversation(mut self, conversation: Vec<ConversationTurn>) -> Self {
+        self.conversation = conversation;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.user_id.is_none() && self.locale.is_none() && self.conversation.is_empty()
+    }
+
+    /// イベントパラメータとして送るための変換
+    pub fn to_event_value(&self) -> event_bus::Value {
+        let mut map = HashMap::new();
+        if let Some(user_id) = &self.user_id {
+            map.insert(
+                "user_id".to_string(),
+                event_bus::Value::String(user_id.clone()),
+            );
+        }
+        if let Some(locale) = &self.locale {
+            map.insert(
+                "locale".to_string(),
+                event_bus::Value::String(locale.clone()),
+            );
+        }
+        if !self.conversation.is_empty() {
+            let turns = self
+                .conversation
+                .iter()
+                .map(|turn| {
+                    event_bus::Value::Map(HashMap::from([
+                        (
+                            "user".to_string(),
+                            event_bus::Value::String(turn.user.clone()),
+                        ),
+                        (
+                            "agent".to_string(),
+                            event_bus::Value::String(turn.agent.clone()),
+                        ),
+                    ]))
+                })
+                .collect();
+            map.insert("conversation".to_string(), event_bus::Value::List(turns));
+        }
+        event_bus::Value::Map(map)
+    }
+
+    pub fn from_event_value(value: &event_bus::Value) -> Option<Self> {
+        let event_bus::Value::Map(map) = value else {
+            return None;
+        };
+        let get = |map: &HashMap<String, event_bus::Value>, key: &str| match map.get(key) {
+            Some(event_bus::Value::String(s)) => Some(s.clone()),
+            _ => None,
+        };
+        let conversation = match map.get("conversation") {
+            Some(event_bus::Value::List(turns)) => turns
+                .iter()
+                .filter_map(|turn| match turn {
+                    event_bus::Value::Map(turn) => Some(ConversationTurn {
+                        user: get(turn, "user")?,
+                        agent: get(turn, "agent")?,
+                    }),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        Some(Self {
+            user_id: get(map, "user_id"),
+            locale: get(map, "locale"),
+            conversation,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
 pub enum AgentType {
     World,
@@ -176,6 +311,25 @@ impl std::fmt::Display for AgentType {
     }
 }
 
+/// ワールド単位の既定のプロンプト前置き (persona)。
+/// System と全エージェントで共有され、更新すると以降の think に反映される。
+#[derive(Clone, Debug, Default)]
+pub struct WorldPreamble(Arc<std::sync::RwLock<Option<String>>>);
+
+impl WorldPreamble {
+    pub fn new(preamble: Option<String>) -> Self {
+        Self(Arc::new(std::sync::RwLock::new(preamble)))
+    }
+
+    pub fn get(&self) -> Option<String> {
+        self.0.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn set(&self, preamble: Option<String>) {
+        *self.0.write().unwrap_or_else(|e| e.into_inner()) = preamble;
+    }
+}
+
 type ParentScopes = Vec<Arc<DashMap<String, Arc<SafeRwLock<Value>>>>>;
 
 /// 共有可能なコンテキストの状態
@@ -192,6 +346,98 @@ pub struct SharedContext {
     pub providers: Arc<DashMap<String, Arc<ProviderInstance>>>,
     pub prompt_generator: Arc<dyn PromptGenerator>,
     pub policies: Vec<Policy>,
+    // プロンプト前置き: エージェントの persona がワールドの既定値より優先される
+    agent_preamble: Option<String>,
+    world_preamble: WorldPreamble,
+    // エージェントのロケール別の前置き。実行中のロケールのものがあれば優先される
+    localized_preambles: Arc<HashMap<Locale, String>>,
+    // エージェントの既定値。リクエストのロケールが対応していれば置き換わる
+    locale: Locale,
+    // リクエスト処理中のみ設定される
+    request_context: Option<RequestContext>,
+    // answer ハンドラの実行中のみ設定される
+    partial_responses: Option<PartialResponseTarget>,
+    // ハンドラを起動したイベント。発行するイベントの親として記録する
+    trigger_event: Option<Arc<Event>>,
+    // think の呼び出し回数の上限。fork したコンテキスト間で共有する
+    llm_budget: Option<Arc<LlmBudget>>,
+    // 実行中に調整できる think の既定値。スケールしたインスタンス間で共有する
+    runtime_settings: Arc<RuntimeSettings>,
+    // ハンドラ 1 回の実行あたりの上限と、その実行での使用量
+    guardrails: ExecutionGuardrails,
+    execution_usage: Arc<ExecutionUsage>,
+    // 評価の深さと作った値の大きさ。上限はハンドラ 1 回の実行ごとに数える
+    eval_usage: Arc<EvalUsage>,
+    // retry(...) の残り回数。リクエストの連鎖で共有する。None なら無制限
+    retry_limit: Option<u32>,
+    retry_budget: Option<Arc<RetryBudget>>,
+    // ハンドラが構造化された値を返す場合、think に JSON での応答を求める
+    output_format: OutputFormat,
+    // secret("name") で読めるシークレット
+    secrets: Arc<SecretVault>,
+    // list_agents() / agent_requests(name) が読むシステムのカタログ
+    catalog: Arc<AgentCatalog>,
+    // call_tool(name, args) で呼べるツール
+    tools: Arc<ToolRegistry>,
+    // log.<level>(...) の出力先。スケールしたインスタンス間で共有する
+    agent_logger: Arc<AgentLogger>,
+    // ハンドラ実行の記録が有効なときの observe / react ハンドラの実行中のみ設定される
+    recorder: Option<Recorder>,
+    // choose_weighted(...) が使う乱数。fork したコンテキスト間で共有する
+    rng: Arc<Mutex<StdRng>>,
+    // retry(...) のバックオフで待つ時計
+    clock: Arc<dyn Clock>,
+    // increment(...) / try_acquire(...) が使う、エージェント間で共有するカウンター
+    shared_counters: Arc<SharedCounters>,
+    // プロファイルを求めたリクエストの処理中のみ設定される
+    profiler: Option<Profiler>,
+    // ハンドラの実行中のみ設定される。状態の書き込みとイベントは commit() まで保留する
+    transaction: Option<Arc<Mutex<StateTransaction>>>,
+    // commit() は書き込み、state_snapshot() は読み取りで取る。エージェントのコンテキスト間で共有する
+    commit_lock: Arc<std::sync::RwLock<()>>,
+}
+
+/// The state writes and events of one handler run, applied by
+/// [`ExecutionContext::commit`] when the handler succeeds and dropped with the
+/// context when it fails or is cancelled.
+///
+/// Observe, react and lifecycle handlers of an agent run one at a time in its run
+/// loop, so two transactions of the same agent never interleave and each commit
+/// applies on top of the previous one. Answer handlers run concurrently but read
+/// only; they read the committed state and never the writes of a running handler.
+/// The writes of a commit are applied under a single write lock, and
+/// [`ExecutionContext::state_snapshot`] (what idle eviction and restarts save)
+/// reads under the same lock, so a snapshot holds all or none of a commit. A
+/// single read outside a snapshot is not ordered against commits: reading two
+/// variables one after the other may see one before and one after a commit.
+/// Scaled instances do not share state, so there is nothing to order between them.
+#[derive(Clone, Default)]
+struct StateTransaction {
+    // None は削除
+    writes: HashMap<String, Option<Value>>,
+    // 最初に書いた順。状態変更の通知はこの順に送る
+    order: Vec<String>,
+    // 系譜を付けた状態で、発行した順に保留する
+    events: Vec<Event>,
+}
+
+impl StateTransaction {
+    fn stage(&mut self, name: &str, value: Option<Value>) {
+        if self.writes.insert(name.to_string(), value).is_none() {
+            self.order.push(name.to_string());
+        }
+    }
+}
+
+/// The writes and events of a transaction at some point, to go back to with
+/// [`ExecutionContext::rollback_to`]
+pub struct Savepoint(Option<StateTransaction>);
+
+/// `yield` による部分応答の宛先となるリクエストと、送信済みの部分応答の数
+#[derive(Clone)]
+struct PartialResponseTarget {
+    request: EventType,
+    sequence: Arc<AtomicU64>,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -266,9 +512,13 @@ impl ExecutionContext {
         policies: Vec<Policy>,
     ) -> Self {
         let (mut event_rx, _) = event_bus.subscribe();
-        let request_manager = Arc::new(RequestManager::new(
-            event_bus.clone(),
-            config.request_timeout,
+        let request_manager = Arc::new(
+            RequestManager::new(event_bus.clone(), config.request_timeout)
+                .with_dedup_window(&config.request_dedup),
+        );
+        let eval_usage = Arc::new(EvalUsage::new(
+            &agent_info.agent_name,
+            EvalLimits::default(),
         ));
         let new_self = Self {
             shared: SharedContext {
@@ -282,15 +532,48 @@ impl ExecutionContext {
                 // only 1 variant, when appended type, inject here.
                 prompt_generator: Arc::new(StandardPromptGenerator),
                 policies,
+                agent_preamble: None,
+                world_preamble: WorldPreamble::default(),
+                localized_preambles: Arc::new(HashMap::new()),
+                locale: Locale::default(),
+                request_context: None,
+                partial_responses: None,
+                trigger_event: None,
+                llm_budget: None,
+                runtime_settings: Arc::new(RuntimeSettings::default()),
+                guardrails: ExecutionGuardrails::default(),
+                execution_usage: Arc::new(ExecutionUsage::default()),
+                eval_usage,
+                retry_limit: config.retry_budget,
+                retry_budget: config
+                    .retry_budget
+                    .map(|retries| Arc::new(RetryBudget::new(retries))),
+                output_format: OutputFormat::default(),
+                secrets: Arc::new(SecretVault::default()),
+                catalog: Arc::new(AgentCatalog::default()),
+                tools: Arc::new(ToolRegistry::default()),
+                agent_logger: Arc::new(AgentLogger::default()),
+                recorder: None,
+                rng: Arc::new(Mutex::new(StdRng::from_entropy())),
+                clock: Arc::new(SystemClock),
+                shared_counters: Arc::new(SharedCounters::default()),
+                profiler: None,
+                transaction: None,
+                commit_lock: Arc::new(std::sync::RwLock::new(())),
             },
             current_scope: DashMap::new(),
             access_mode,
             timeout: config.access_timeout,
         };
-        let self_ref = new_self.clone();
+        // 意図的に System のタスク管理の外で動かす。RequestManager を弱参照で持つので、
+        // コンテキストが破棄されてイベントバスが閉じるとループも終了する。
+        let request_manager = Arc::downgrade(&new_self.shared.request_manager);
         tokio::spawn(async move {
             while let Ok(event) = event_rx.recv().await {
-                let _ = self_ref.shared.request_manager.handle_event(&event);
+                let Some(request_manager) = request_manager.upgrade() else {
+                    break;
+                };
+                let _ = request_manager.handle_event(&event);
             }
         });
         new_self
@@ -350,7 +633,10 @@ impl ExecutionContext {
             }
         }
 
-        // 最後にグローバル状態を確認
+        // 最後にグローバル状態を確認。ハンドラが書いた値を優先する
+        if let Some(staged) = self.staged_state(name) {
+            return staged.ok_or_else(|| ContextError::VariableNotFound(name.to_string()));
+        }
         if let Some(value) = self.shared.state.get(name) {
             return match value.read_with_timeout(self.timeout).await {
                 Ok(guard) => Ok(guard.clone()),
@@ -380,6 +666,9 @@ impl ExecutionContext {
     /// 状態変数の読み取り
     #[tracing::instrument(skip(self), level = "debug")]
     pub async fn get_state(&self, name: &str) -> Result<Value, ContextError> {
+        if let Some(staged) = self.staged_state(name) {
+            return staged.ok_or_else(|| ContextError::VariableNotFound(name.to_string()));
+        }
         if let Some(value) = self.shared.state.get(name) {
             match value.read_with_timeout(self.timeout).await {
                 Ok(guard) => Ok(guard.clone()),
@@ -396,6 +685,10 @@ impl ExecutionContext {
         match self.access_mode {
             StateAccessMode::ReadOnly => Err(ContextError::ReadOnlyViolation),
             StateAccessMode::ReadWrite => {
+                if let Some(mut transaction) = self.transaction() {
+                    transaction.stage(name, Some(value));
+                    return Ok(());
+                }
                 let value_ref = value.clone();
                 let safe_value = Arc::new(SafeRwLock::new(value));
                 self.shared.state.insert(name.to_string(), safe_value);
@@ -412,6 +705,17 @@ impl ExecutionContext {
     {
         match self.access_mode {
             StateAccessMode::ReadOnly => Err(ContextError::ReadOnlyViolation),
+            StateAccessMode::ReadWrite if self.shared.transaction.is_some() => {
+                // 反映済みの値は変えず、更新した複製を書き込みとして保留する
+                let mut value = self.get_state(name).await?;
+                match f(&mut value) {
+                    Ok(()) => self.set_state(name, value),
+                    Err(_) => Err(ContextError::InvalidValue {
+                        key: name.to_string(),
+                        message: "Invalid value".to_string(),
+                    }),
+                }
+            }
             StateAccessMode::ReadWrite => {
                 if let Some(value) = self.shared.state.get(name) {
                     match value.write_with_timeout(self.timeout).await {
@@ -436,9 +740,50 @@ impl ExecutionContext {
         }
     }
 
+    /// 現在または親のスコープにあるローカル変数か
+    pub fn has_local(&self, name: &str) -> bool {
+        self.current_scope.contains_key(name)
+            || self
+                .shared
+                .parent_scopes
+                .iter()
+                .any(|scope| scope.contains_key(name))
+    }
+
+    /// The local variables visible in this scope, with the values
+    /// [`get_variable`](Self::get_variable) would read
+    pub async fn local_variables(&self) -> Result<HashMap<String, Value>, ContextError> {
+        let mut variables = HashMap::new();
+        // 内側のスコープの値で外側の同名の変数を上書きする
+        let scopes = self
+            .shared
+            .parent_scopes
+            .iter()
+            .map(|scope| scope.as_ref())
+            .chain(std::iter::once(&self.current_scope));
+        for scope in scopes {
+            for entry in scope.iter() {
+                let value = match entry.value().read_with_timeout(self.timeout).await {
+                    Ok(guard) => guard.clone(),
+                    Err(LockError::Timeout) => {
+                        return Err(ContextError::LockTimeout(entry.key().clone()));
+                    }
+                    Err(LockError::Deadlock) => {
+                        return Err(ContextError::Deadlock(entry.key().clone()));
+                    }
+                };
+                variables.insert(entry.key().clone(), value);
+            }
+        }
+        Ok(variables)
+    }
+
     /// 状態変数の確認
     pub fn is_state(&self, name: &str) -> bool {
-        self.shared.state.contains_key(name)
+        match self.staged_state(name) {
+            Some(staged) => staged.is_some(),
+            None => self.shared.state.contains_key(name),
+        }
     }
 
     /// 状態変数の削除
@@ -446,6 +791,15 @@ impl ExecutionContext {
     pub async fn remove_state(&self, name: &str) -> Result<(), ContextError> {
         match self.access_mode {
             StateAccessMode::ReadOnly => Err(ContextError::ReadOnlyViolation),
+            StateAccessMode::ReadWrite if self.shared.transaction.is_some() => {
+                if !self.is_state(name) {
+                    return Err(ContextError::VariableNotFound(name.to_string()));
+                }
+                if let Some(mut transaction) = self.transaction() {
+                    transaction.stage(name, None);
+                }
+                Ok(())
+            }
             StateAccessMode::ReadWrite => {
                 if self.shared.state.remove(name).is_some() {
                     Ok(())
@@ -458,11 +812,129 @@ impl ExecutionContext {
 
     /// 状態変数の一覧を取得
     pub fn list_state_variables(&self) -> Vec<String> {
-        self.shared
+        let transaction = self.transaction();
+        let staged = |name: &str| {
+            transaction
+                .as_ref()
+                .and_then(|t| t.writes.get(name).cloned())
+        };
+        let mut names: Vec<String> = self
+            .shared
             .state
             .iter()
             .map(|entry| entry.key().clone())
-            .collect()
+            .filter(|name| !matches!(staged(name.as_str()), Some(None)))
+            .collect();
+        if let Some(transaction) = &transaction {
+            // ハンドラが新しく作った状態変数
+            names.extend(
+                transaction
+                    .order
+                    .iter()
+                    .filter(|name| {
+                        matches!(transaction.writes.get(*name), Some(Some(_)))
+                            && !self.shared.state.contains_key(*name)
+                    })
+                    .cloned(),
+            );
+        }
+        names
+    }
+
+    /// The committed state, with all or none of the writes of each commit
+    pub async fn state_snapshot(&self) -> HashMap<String, Value> {
+        let entries: Vec<_> = {
+            let _commit = self.shared.commit_lock.read().unwrap();
+            self.shared
+                .state
+                .iter()
+                .map(|entry| (entry.key().clone(), entry.value().clone()))
+                .collect()
+        };
+        // commit() は値を置き換えるので、ロックを離した後に読んでも同じ版の値になる
+        let mut snapshot = HashMap::new();
+        for (name, value) in entries {
+            if let Ok(guard) = value.read_with_timeout(self.timeout).await {
+                snapshot.insert(name, guard.clone());
+            }
+        }
+        snapshot
+    }
+
+    /// Run a handler in a transaction, see [`StateTransaction`]: its state writes
+    /// and events wait for [`commit`](Self::commit). Forks of the context share the
+    /// transaction.
+    pub fn with_transaction(mut self) -> Self {
+        self.shared.transaction = Some(Arc::new(Mutex::new(StateTransaction::default())));
+        self
+    }
+
+    fn transaction(&self) -> Option<std::sync::MutexGuard<'_, StateTransaction>> {
+        self.shared
+            .transaction
+            .as_ref()
+            .map(|transaction| transaction.lock().unwrap())
+    }
+
+    /// ハンドラが書いた値。`Some(None)` は削除したことを表す
+    fn staged_state(&self, name: &str) -> Option<Option<Value>> {
+        self.transaction()
+            .and_then(|transaction| transaction.writes.get(name).cloned())
+    }
+
+    /// Apply the state writes of the transaction under a single lock, then publish
+    /// a `StateUpdated` event per written variable in the order they were first
+    /// written, then the held events in the order they were emitted. Without a
+    /// transaction, does nothing.
+    pub async fn commit(&self) -> Result<(), ContextError> {
+        let Some(transaction) = &self.shared.transaction else {
+            return Ok(());
+        };
+        let StateTransaction {
+            writes,
+            order,
+            events,
+        } = std::mem::take(&mut *transaction.lock().unwrap());
+        {
+            let _commit = self.shared.commit_lock.write().unwrap();
+            for name in &order {
+                match &writes[name] {
+                    Some(value) => {
+                        self.shared
+                            .state
+                            .insert(name.clone(), Arc::new(SafeRwLock::new(value.clone())));
+                    }
+                    None => {
+                        self.shared.state.remove(name);
+                    }
+                }
+            }
+        }
+        for name in &order {
+            if let Some(value) = &writes[name] {
+                self.notify_state_update(name, value)?;
+            }
+        }
+        for event in events {
+            self.shared
+                .event_bus
+                .publish(event)
+                .await
+                .map_err(|e| ContextError::EventSendFailed(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// The writes and events of the transaction so far
+    pub fn savepoint(&self) -> Savepoint {
+        Savepoint(self.transaction().map(|transaction| transaction.clone()))
+    }
+
+    /// Drop the writes and events since `savepoint`
+    pub fn rollback_to(&self, savepoint: Savepoint) {
+        if let (Some(mut transaction), Some(saved)) = (self.transaction(), savepoint.0) {
+            *transaction = saved;
+        }
     }
 
     pub fn agent_info(&self) -> AgentInfo {
@@ -473,9 +945,372 @@ impl ExecutionContext {
         self.shared.agent_info.agent_name.clone()
     }
 
+    pub fn request_context(&self) -> Option<&RequestContext> {
+        self.shared.request_context.as_ref()
+    }
+
+    /// RequestContext を設定し、ハンドラから参照できるように
+    /// `request_user_id` / `request_locale` 変数（未設定時は null）を現在のスコープに追加する。
+    /// 対応しているロケールが指定されていれば、エージェントの既定値の代わりに使う。
+    pub fn with_request_context(mut self, request_context: RequestContext) -> Self {
+        if let Some(locale) = request_context.locale.as_deref().and_then(Locale::from_tag) {
+            self = self.with_locale(locale);
+        }
+        for (name, value) in [
+            (REQUEST_USER_ID_VARIABLE, &request_context.user_id),
+            (REQUEST_LOCALE_VARIABLE, &request_context.locale),
+        ] {
+            let value = value.clone().map(Value::String).unwrap_or(Value::Null);
+            self.current_scope
+                .insert(name.to_string(), Arc::new(SafeRwLock::new(value)));
+        }
+        self.shared.request_context = Some(request_context);
+        self
+    }
+
+    /// ハンドラを起動したイベントを記録する。このコンテキストから発行するイベントと
+    /// リクエストには、このイベントが親 (parent_event_id / root_event_id) として付く。
+    /// リクエストが再試行の残りを持っていれば、連鎖の一部としてそれを引き継ぐ。
+    pub fn with_trigger_event(mut self, event: &Event) -> Self {
+        if let Some(budget) = RetryBudget::for_event(event, None) {
+            self.shared.retry_budget = Some(Arc::new(budget));
+        }
+        self.shared.trigger_event = Some(Arc::new(event.clone()));
+        self
+    }
+
+    /// 起動イベントを親として設定する（親が明示されていれば何もしない）
+    fn with_lineage(&self, event: Event) -> Event {
+        match &self.shared.trigger_event {
+            Some(trigger) if event.parent_event_id.is_none() => event.caused_by(trigger),
+            _ => event,
+        }
+    }
+
+    /// 処理中のリクエストのメタデータを `request` 変数として現在のスコープに追加する。
+    /// フィールドは requester / request_id / received_at (RFC 3339) / timeout (リクエスト元の待ち時間)。
+    pub fn with_request_metadata(self, request: &Event) -> Self {
+        let EventType::Request {
+            requester,
+            request_id,
+            ..
+        } = &request.event_type
+        else {
+            return self;
+        };
+        let timeout = self.shared.request_manager.timeout(request);
+        let metadata = [
+            ("requester", Value::String(requester.clone())),
+            ("request_id", Value::String(request_id.clone())),
+            ("received_at", Value::String(Utc::now().to_rfc3339())),
+            ("timeout", Value::Duration(timeout)),
+        ];
+        self.with_metadata_variable(REQUEST_METADATA_VARIABLE, metadata)
+    }
+
+    /// 受信したイベントのメタデータを `event` 変数として現在のスコープに追加する。
+    /// イベント自体は発行時刻を持たないため、emitted_at はエージェントがイベントバスから受け取った時刻。
+    pub fn with_event_metadata(self, event: &Event) -> Self {
+        let parameters = event
+            .parameters
+            .iter()
+            .filter(|(key, _)| key.as_str() != event_bus::REQUEST_CONTEXT_KEY)
+            .map(|(key, value)| (key.clone(), Value::from(value.clone())))
+            .collect();
+        let metadata = [
+            ("event_type", Value::String(event.event_type.to_string())),
+            ("emitted_at", Value::String(Utc::now().to_rfc3339())),
+            ("parameters", Value::Map(parameters)),
+        ];
+        self.with_metadata_variable(EVENT_METADATA_VARIABLE, metadata)
+    }
+
+    /// エージェントの設定値を `config` 変数として現在のスコープに追加する。
+    /// fork したコンテキストも同じ値を共有するため、[`Self::update_agent_config`] による
+    /// 置き換えはそれ以降の参照に反映される。
+    pub fn with_agent_config(self, values: HashMap<String, Value>) -> Self {
+        self.current_scope.insert(
+            AGENT_CONFIG_VARIABLE.to_string(),
+            Arc::new(SafeRwLock::new(Value::Map(values))),
+        );
+        self
+    }
+
+    /// `config` 変数の値を置き換える（設定のホットリロード）
+    pub async fn update_agent_config(
+        &self,
+        values: HashMap<String, Value>,
+    ) -> Result<(), ContextError> {
+        let config = self
+            .current_scope
+            .get(AGENT_CONFIG_VARIABLE)
+            .map(|entry| entry.value().clone())
+            .ok_or_else(|| ContextError::VariableNotFound(AGENT_CONFIG_VARIABLE.to_string()))?;
+        match config.write_with_timeout(self.timeout).await {
+            Ok(mut guard) => {
+                *guard = Value::Map(values);
+                Ok(())
+            }
+            Err(LockError::Timeout) => {
+                Err(ContextError::LockTimeout(AGENT_CONFIG_VARIABLE.to_string()))
+            }
+            Err(LockError::Deadlock) => {
+                Err(ContextError::Deadlock(AGENT_CONFIG_VARIABLE.to_string()))
+            }
+        }
+    }
+
+    fn with_metadata_variable<const N: usize>(
+        self,
+        name: &str,
+        fields: [(&str, Value); N],
+    ) -> Self {
+        let value = Value::Map(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        );
+        self.current_scope
+            .insert(name.to_string(), Arc::new(SafeRwLock::new(value)));
+        self
+    }
+
+    /// think のプロンプト前置きを設定する。agent が None の場合は world の既定値を使う
+    pub fn with_prompt_preamble(mut self, agent: Option<String>, world: WorldPreamble) -> Self {
+        self.shared.agent_preamble = agent;
+        self.shared.world_preamble = world;
+        self
+    }
+
+    /// エージェントのロケール別のプロンプト前置きを設定する
+    pub fn with_localized_preambles(mut self, preambles: HashMap<Locale, String>) -> Self {
+        self.shared.localized_preambles = Arc::new(preambles);
+        self
+    }
+
+    /// 現時点で有効なプロンプト前置き（補間前のテンプレート）。
+    /// 実行中のロケールの前置き、エージェントの前置き、ワールドの既定値の順に探す
+    pub fn prompt_preamble(&self) -> Option<String> {
+        self.shared
+            .localized_preambles
+            .get(&self.shared.locale)
+            .cloned()
+            .or_else(|| self.shared.agent_preamble.clone())
+            .or_else(|| self.shared.world_preamble.get())
+    }
+
+    /// 実行中のロケールを設定し、`context.locale` として参照できるようにする
+    pub fn with_locale(mut self, locale: Locale) -> Self {
+        self.shared.locale = locale;
+        self.with_metadata_variable(
+            EXECUTION_CONTEXT_VARIABLE,
+            [("locale", Value::String(locale.to_string()))],
+        )
+    }
+
+    /// テンプレートの日付・数値の書式と、応答に含めるエラーの言語に使うロケール
+    pub fn locale(&self) -> Locale {
+        self.shared.locale
+    }
+
+    /// think の呼び出し回数に上限を設ける
+    pub fn with_llm_budget(mut self, budget: Option<LlmBudget>) -> Self {
+        self.shared.llm_budget = budget.map(Arc::new);
+        self
+    }
+
+    /// 実行中に設定された上限があれば、それが設定ファイルの上限より優先される
+    pub fn llm_budget(&self) -> Option<Arc<LlmBudget>> {
+        self.shared
+            .runtime_settings
+            .llm_budget()
+            .or_else(|| self.shared.llm_budget.clone())
+    }
+
+    pub fn llm_budget_stats(&self) -> Option<LlmBudgetStats> {
+        self.llm_budget().map(|budget| budget.stats())
+    }
+
+    /// 実行中に調整できる think の既定値を設定する。スケールしたインスタンス間で共有する
+    pub fn with_runtime_settings(mut self, runtime_settings: Arc<RuntimeSettings>) -> Self {
+        self.shared.runtime_settings = runtime_settings;
+        self
+    }
+
+    pub fn runtime_settings(&self) -> AgentSettings {
+        self.shared.runtime_settings.get()
+    }
+
+    /// `secret("name")` で読めるシークレットを設定する
+    pub fn with_secrets(mut self, secrets: SecretVault) -> Self {
+        self.shared.secrets = Arc::new(secrets);
+        self
+    }
+
+    pub fn secret(&self, name: &str) -> Option<SecretValue> {
+        self.shared.secrets.get(name)
+    }
+
+    /// システムのカタログを設定する。System 内のエージェントで共有する
+    pub fn with_catalog(mut self, catalog: Arc<AgentCatalog>) -> Self {
+        self.shared.catalog = catalog;
+        self
+    }
+
+    pub fn catalog(&self) -> &AgentCatalog {
+        &self.shared.catalog
+    }
+
+    /// `call_tool(name, args)` で呼べるツールを設定する。System 内のエージェントで共有する
+    pub fn with_tools(mut self, tools: Arc<ToolRegistry>) -> Self {
+        self.shared.tools = tools;
+        self
+    }
+
+    pub fn tools(&self) -> &ToolRegistry {
+        &self.shared.tools
+    }
+
+    pub fn with_agent_logger(mut self, agent_logger: Arc<AgentLogger>) -> Self {
+        self.shared.agent_logger = agent_logger;
+        self
+    }
+
+    /// ハンドラの実行を文ごとに記録する（[`crate::eval::recording`] 参照）
+    pub fn with_recorder(mut self, recorder: Option<Recorder>) -> Self {
+        self.shared.recorder = recorder;
+        self
+    }
+
+    pub fn recorder(&self) -> Option<&Recorder> {
+        self.shared.recorder.as_ref()
+    }
+
+    /// 乱数を固定のシードで初期化する。テストで選択を再現するために使う
+    pub fn with_rng_seed(mut self, seed: u64) -> Self {
+        self.shared.rng = Arc::new(Mutex::new(StdRng::seed_from_u64(seed)));
+        self
+    }
+
+    /// A uniform value in `[0, 1)` from the context's random number generator
+    pub fn random(&self) -> f64 {
+        self.shared
+            .rng
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .gen_range(0.0..1.0)
+    }
+
+    /// `retry(...)` のバックオフで待つ時計を差し替える。テストでは MockClock を使う
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.shared.clock = clock;
+        self
+    }
+
+    pub fn clock(&self) -> &dyn Clock {
+        self.shared.clock.as_ref()
+    }
+
+    /// `increment(...)` などで使うカウンターを設定する。System 内のエージェントで共有する
+    pub fn with_shared_counters(mut self, shared_counters: Arc<SharedCounters>) -> Self {
+        self.shared.shared_counters = shared_counters;
+        self
+    }
+
+    pub fn shared_counters(&self) -> &SharedCounters {
+        &self.shared.shared_counters
+    }
+
+    pub fn with_guardrails(mut self, guardrails: ExecutionGuardrails) -> Self {
+        self.shared.guardrails = guardrails;
+        self
+    }
+
+    pub fn with_eval_limits(mut self, limits: EvalLimits) -> Self {
+        self.shared.eval_usage =
+            Arc::new(EvalUsage::new(&self.shared.agent_info.agent_name, limits));
+        self
+    }
+
+    /// ハンドラの実行を開始する。使用量と再試行の残りはここから数え直す
+    pub fn with_new_execution(mut self) -> Self {
+        self.shared.execution_usage = Arc::new(ExecutionUsage::default());
+        self.shared.eval_usage = Arc::new(EvalUsage::new(
+            &self.shared.agent_info.agent_name,
+            self.shared.eval_usage.limits().clone(),
+        ));
+        self.shared.retry_budget = self
+            .shared
+            .retry_limit
+            .map(|retries| Arc::new(RetryBudget::new(retries)));
+        self
+    }
+
+    /// retry(...) で再試行を 1 回使う。残りがなければ false
+    pub fn try_spend_retry(&self) -> bool {
+        self.shared
+            .retry_budget
+            .as_ref()
+            .is_none_or(|budget| budget.try_spend())
+    }
+
+    /// 再試行の残り回数。None なら無制限
+    pub fn retries_left(&self) -> Option<u32> {
+        self.shared
+            .retry_budget
+            .as_ref()
+            .map(|budget| budget.remaining())
+    }
+
+    pub fn guardrails(&self) -> &ExecutionGuardrails {
+        &self.shared.guardrails
+    }
+
+    pub fn execution_usage(&self) -> &ExecutionUsage {
+        &self.shared.execution_usage
+    }
+
+    pub fn eval_usage(&self) -> &Arc<EvalUsage> {
+        &self.shared.eval_usage
+    }
+
+    pub fn with_output_format(mut self, output_format: OutputFormat) -> Self {
+        self.shared.output_format = output_format;
+        self
+    }
+
+    pub fn output_format(&self) -> OutputFormat {
+        self.shared.output_format
+    }
+
+    /// リクエストの処理時間の内訳を記録する。応答にはその結果が付く
+    pub fn with_profiler(mut self, profiler: Option<Profiler>) -> Self {
+        self.shared.profiler = profiler;
+        self
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.shared.profiler.as_ref()
+    }
+
+    /// `yield` で部分応答を送れるように、処理中のリクエストを設定する
+    pub fn with_partial_responses(mut self, request: EventType) -> Self {
+        self.shared.partial_responses = Some(PartialResponseTarget {
+            request,
+            sequence: Arc::new(AtomicU64::new(0)),
+        });
+        self
+    }
+
+    /// 部分応答を送れるか。answer ハンドラの実行中のみ true
+    pub fn accepts_partial_responses(&self) -> bool {
+        self.shared.partial_responses.is_some()
+    }
+
     #[tracing::instrument(skip(self), level = "debug")]
     pub async fn session_id(&self) -> Result<String, ContextError> {
-        let session_id = if let Some(session_id) = self.shared.state.get("session_id") {
+        let session_id = if let Some(session_id) = self.shared.state.get(SESSION_ID_STATE) {
             session_id
                 .value()
                 .read_with_timeout(Duration::from_secs(5))
@@ -487,7 +1322,7 @@ impl ExecutionContext {
                 .map_err(ContextError::from)?
         } else {
             let session_id = Uuid::new_v4().to_string();
-            let _ = self.set_state("session_id", Value::String(session_id.clone()));
+            let _ = self.set_state(SESSION_ID_STATE, Value::String(session_id.clone()));
             session_id
         };
         Ok(session_id)
@@ -535,9 +1370,26 @@ impl ExecutionContext {
         }
     }
 
+    /// ハンドラの `emit` を発行する。トランザクション中なら commit() まで保留する
+    pub async fn emit_on_commit(&self, event: Event) -> Result<(), ContextError> {
+        if let Some(mut transaction) = self.transaction() {
+            let event = self.with_lineage(event);
+            if let Some(recorder) = self.recorder() {
+                recorder.record_event(&event);
+            }
+            transaction.events.push(event);
+            return Ok(());
+        }
+        self.emit_event(event).await
+    }
+
     // イベント関連のメソッド
     #[tracing::instrument(skip(self), level = "debug")]
     pub async fn emit_event(&self, event: Event) -> Result<(), ContextError> {
+        let event = self.with_lineage(event);
+        if let Some(recorder) = self.recorder() {
+            recorder.record_event(&event);
+        }
         self.shared
             .event_bus
             .publish(event)
@@ -545,6 +1397,73 @@ impl ExecutionContext {
             .map_err(|e| ContextError::EventSendFailed(e.to_string()))
     }
 
+    /// ガードレールを超えたことを、その時点の使用量とともにシステムイベントで通知する
+    pub async fn emit_guardrail_tripped(
+        &self,
+        exceeded: &GuardrailExceeded,
+    ) -> Result<(), ContextError> {
+        let counters = exceeded.counters;
+        let parameters = HashMap::from([
+            (
+                "guardrail".to_string(),
+                event_bus::Value::from(exceeded.guardrail.to_string()),
+            ),
+            (
+                "limit".to_string(),
+                event_bus::Value::Integer(exceeded.limit as i64),
+            ),
+            (
+                "think_calls".to_string(),
+                event_bus::Value::Integer(counters.think_calls.into()),
+            ),
+            (
+                "tokens".to_string(),
+                event_bus::Value::Integer(counters.tokens as i64),
+            ),
+            (
+                "sub_requests".to_string(),
+                event_bus::Value::Integer(counters.sub_requests.into()),
+            ),
+        ]);
+        self.emit_event(Event {
+            event_type: EventType::GuardrailTripped {
+                agent_name: self.agent_name(),
+            },
+            parameters,
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// 処理中のリクエストの ID。リクエストハンドラ以外では None
+    pub fn request_id(&self) -> Option<&str> {
+        match &self.shared.trigger_event.as_deref()?.event_type {
+            EventType::Request { request_id, .. } => Some(request_id),
+            _ => None,
+        }
+    }
+
+    /// 実行中のハンドラ名。起動イベントの種類で表し、無ければ空文字列
+    pub fn handler_name(&self) -> String {
+        self.shared
+            .trigger_event
+            .as_ref()
+            .map(|event| event.event_type.to_string())
+            .unwrap_or_default()
+    }
+
+    /// `log` 文の出力。エージェントが公開する設定ならシステムイベントも発行する
+    pub async fn write_log(&self, record: LogRecord) -> Result<(), ContextError> {
+        match self
+            .shared
+            .agent_logger
+            .log(&self.agent_name(), &self.handler_name(), &record)
+        {
+            Some(event) => self.emit_event(event).await,
+            None => Ok(()),
+        }
+    }
+
     // onFail などのエラーイベントの発行
     #[tracing::instrument(skip(self), level = "debug")]
     pub async fn emit_failure(&self, error: ContextError) -> Result<(), ContextError> {
@@ -564,10 +1483,11 @@ impl ExecutionContext {
                 );
                 parameters
             },
+            ..Default::default()
         };
         self.shared
             .event_bus
-            .publish(error_event)
+            .publish(self.with_lineage(error_event))
             .await
             .map_err(|e| {
                 ContextError::EventError(EventError::SendFailed {
@@ -588,7 +1508,10 @@ impl ExecutionContext {
             request_id,
         } = request
         {
-            let event = match result {
+            let serialization = self
+                .profiler()
+                .map(|profiler| profiler.enter("serialization"));
+            let mut event = match result {
                 Ok(value) => Event {
                     event_type: EventType::ResponseSuccess {
                         request_id,
@@ -599,22 +1522,68 @@ impl ExecutionContext {
                     parameters: vec![("response".to_string(), event_bus::Value::from(value))]
                         .into_iter()
                         .collect::<HashMap<String, event_bus::Value>>(),
+                    ..Default::default()
                 },
-                Err(error) => Event {
-                    event_type: EventType::ResponseFailure {
-                        request_id,
-                        request_type,
-                        requester,
-                        responder,
-                    },
-                    parameters: vec![(
+                Err(error) => {
+                    let mut parameters = vec![(
                         "error".to_string(),
                         event_bus::Value::from(error.to_string()),
                     )]
                     .into_iter()
-                    .collect::<HashMap<String, event_bus::Value>>(),
-                },
+                    .collect::<HashMap<String, event_bus::Value>>();
+                    // リクエストに起因するエラーは、エンドユーザー向けにロケールの言語でも添える
+                    if let Some(diagnostic) = match &error {
+                        RuntimeError::Eval(error) => error.diagnostic(),
+                        _ => None,
+                    } {
+                        parameters.insert(
+                            event_bus::ERROR_MESSAGE_KEY.to_string(),
+                            event_bus::Value::String(
+                                MessageCatalog::new(self.locale()).render(&diagnostic),
+                            ),
+                        );
+                    }
+                    // 制約違反は要求元が一覧できるよう構造のまま渡す
+                    if let RuntimeError::Eval(EvalError::ValidationFailed { violations, .. }) =
+                        &error
+                    {
+                        parameters.insert(
+                            event_bus::VIOLATIONS_KEY.to_string(),
+                            event_bus::Value::List(
+                                violations
+                                    .iter()
+                                    .map(ConstraintViolation::to_event_value)
+                                    .collect(),
+                            ),
+                        );
+                    }
+                    Event {
+                        event_type: EventType::ResponseFailure {
+                            request_id,
+                            request_type,
+                            requester,
+                            responder,
+                        },
+                        parameters,
+                        ..Default::default()
+                    }
+                }
             };
+            drop(serialization);
+            // ストリーミングの要求元が done の前に使用量を送れるように添える
+            event.parameters.insert(
+                USAGE_KEY.to_string(),
+                StreamUsage::from(self.execution_usage().counters()).to_event_value(),
+            );
+            if let Some(budget) = &self.shared.retry_budget {
+                budget.stamp(&mut event);
+            }
+            if let Some(profiler) = self.profiler() {
+                event.parameters.insert(
+                    event_bus::PROFILE_KEY.to_string(),
+                    profiler.finish().to_event_value(),
+                );
+            }
             self.emit_event(event).await?
         } else {
             return Err(ContextError::EventError(EventError::UnsupportedType {
@@ -629,25 +1598,111 @@ impl ExecutionContext {
         parameters.insert("value".to_string(), event_bus::Value::from(value.clone()));
         self.shared
             .event_bus
-            .sync_publish(Event {
+            .sync_publish(self.with_lineage(Event {
                 event_type: EventType::StateUpdated {
                     agent_name: self.shared.agent_info.agent_name.clone(),
                     state_name: key.to_string(),
                 },
                 parameters,
-            })
+                ..Default::default()
+            }))
             .map_err(|e| ContextError::EventSendFailed(e.to_string()))?;
         Ok(())
     }
 
+    /// 処理中のリクエストに部分応答を送る。`sequence` は 0 から順に振られる
+    #[tracing::instrument(skip(self), level = "debug")]
+    pub async fn send_partial_response(&self, value: Value) -> Result<(), ContextError> {
+        let Some(target) = &self.shared.partial_responses else {
+            return Err(ContextError::Failure(
+                "yield is only allowed in answer handlers".to_string(),
+            ));
+        };
+        self.send_partial(
+            target,
+            "response".to_string(),
+            event_bus::Value::from(value),
+        )
+        .await
+    }
+
+    /// 処理中のリクエストにストリームイベントを送る。answer ハンドラの外では何もしない
+    #[tracing::instrument(skip(self), level = "debug")]
+    pub async fn send_stream_event(&self, stream_event: StreamEvent) -> Result<(), ContextError> {
+        let Some(target) = &self.shared.partial_responses else {
+            return Ok(());
+        };
+        let json = serde_json::to_string(&stream_event)
+            .map_err(|e| ContextError::Failure(e.to_string()))?;
+        self.send_partial(
+            target,
+            STREAM_EVENT_KEY.to_string(),
+            event_bus::Value::String(json),
+        )
+        .await
+    }
+
+    async fn send_partial(
+        &self,
+        target: &PartialResponseTarget,
+        key: String,
+        value: event_bus::Value,
+    ) -> Result<(), ContextError> {
+        let EventType::Request {
+            request_type,
+            requester,
+            responder,
+            request_id,
+        } = target.request.clone()
+        else {
+            return Err(ContextError::EventError(EventError::UnsupportedType {
+                event_type: target.request.to_string(),
+            }));
+        };
+        let sequence = target.sequence.fetch_add(1, Ordering::SeqCst);
+        let event = Event {
+            event_type: EventType::ResponsePartial {
+                request_id,
+                request_type,
+                requester,
+                responder,
+            },
+            parameters: vec![
+                (key, value),
+                (
+                    "sequence".to_string(),
+                    event_bus::Value::Integer(sequence as i64),
+                ),
+            ]
+            .into_iter()
+            .collect::<HashMap<String, event_bus::Value>>(),
+            ..Default::default()
+        };
+        self.emit_event(event).await
+    }
+
+    /// リクエストを送って応答を待つ。処理中のリクエストがあれば、その期限と再試行の残りを引き継ぐ
     #[tracing::instrument(skip(self), level = "debug")]
     pub async fn send_request(&self, request: Event) -> Result<Event, ContextError> {
         debug!("Send Request, I'm {}", self.agent_name());
-        self.shared
-            .request_manager
-            .request(&request)
-            .await
-            .map_err(ContextError::from)
+        let mut request = self.with_lineage(request);
+        if let Some(budget) = &self.shared.retry_budget {
+            budget.stamp(&mut request);
+        }
+        let response = match &self.shared.trigger_event {
+            Some(trigger) => {
+                self.shared
+                    .request_manager
+                    .request_within(trigger, &request)
+                    .await
+            }
+            None => self.shared.request_manager.request(&request).await,
+        }
+        .map_err(ContextError::from)?;
+        if let Some(budget) = &self.shared.retry_budget {
+            budget.settle(&response);
+        }
+        Ok(response)
     }
 
     #[tracing::instrument(skip(self), level = "debug")]
@@ -659,6 +1714,12 @@ impl ExecutionContext {
             .map_err(ContextError::from)?;
         Ok(())
     }
+
+    /// Whether the agent should run a request it received, or answer it as a
+    /// duplicate of one received before
+    pub fn admit_request(&self, request: &Event) -> Admission {
+        self.shared.request_manager.admit(request)
+    }
 }
 
 #[cfg(test)]
@@ -1,13 +1,26 @@
 use super::{
+    budget::{ExecutionCounters, Guardrail},
     context::{ContextError, ExecutionContext},
     expression::Value,
+    limits::EvalLimit,
+    postprocess::{AnswerPipeline, PostprocessError},
     statement::{ControlFlow, StatementEvaluator, StatementResult},
 };
 use crate::{
-    Expression, HandlerBlock, event_registry::EventType, provider::types::ProviderError,
+    Expression, HandlerBlock, Parameter, TypeInfo, event_bus,
+    event_registry::EventType,
+    formatter::{Formatter, config::FormatterConfig},
+    message_catalog::{Diagnostic, codes},
+    provider::{
+        plugins::{memory::shared_counters::SharedCounterError, openapi_tools::ToolError},
+        types::ProviderError,
+    },
+    request_manager::RequestError,
     runtime::RuntimeError,
 };
-use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, sync::Arc, time::Duration};
+use utoipa::ToSchema;
 
 /// Top-level evaluator for the KAIREI DSL execution pipeline
 ///
@@ -119,6 +132,24 @@ impl Evaluator {
         }
     }
 
+    /// Evaluates a handler block like [`Self::eval_handler_block`], but returns its
+    /// failure instead of emitting it, for callers that stop on it
+    #[tracing::instrument(skip(self, context), level = "debug")]
+    pub async fn eval_block(
+        &self,
+        block: &HandlerBlock,
+        context: Arc<ExecutionContext>,
+    ) -> EvalResult<StatementResult> {
+        match self
+            .statement_evaluator
+            .eval_block(&block.statements, context)
+            .await?
+        {
+            StatementResult::Value(Value::Error(e)) => Err(EvalError::Eval(e)),
+            result => Ok(result),
+        }
+    }
+
     /// Evaluates an answer handler block and sends the response
     ///
     /// This method is specifically designed for request-response patterns in the KAIREI
@@ -133,15 +164,18 @@ impl Evaluator {
     ///
     /// # Returns
     ///
-    /// - `Ok(StatementResult)`: The result of the evaluation (typically Unit)
+    /// - `Ok(Some(value))`: The value of the success response that was sent
+    /// - `Ok(None)`: A failure response was sent
     /// - `Err(EvalError)`: If an unrecoverable error occurs during evaluation
     ///
     /// # Response Handling
     ///
     /// The method handles different evaluation results:
-    /// - `Return(Value::Ok(v))`: Sends a success response with the inner value
+    /// - `Return(Value::Ok(v))`: Sends a success response with the inner value,
+    ///   after running it through `pipeline`
     /// - `Return(Value::Err(e))`: Sends a failure response with the error
     /// - `Return(Value::Error(e))`: Returns an unhandled exception error
+    /// - A failing `pipeline` step: Sends a failure response naming the step
     /// - `Value::Unit`: Sends a success response with Unit value
     /// - Other results: Returns an unexpected result error
     ///
@@ -150,54 +184,59 @@ impl Evaluator {
     /// ```ignore
     /// use kairei_core::HandlerBlock;
     /// use kairei_core::event_registry::EventType;
-    /// use kairei_core::eval::{Evaluator, ExecutionContext};
+    /// use kairei_core::eval::{Evaluator, ExecutionContext, postprocess::AnswerPipeline};
     /// use std::sync::Arc;
     ///
     /// let answer_block = HandlerBlock { statements: vec![/* ... */] };
     /// let evaluator = Evaluator::new();
     /// let context = Arc::new(ExecutionContext::new(/* ... */));
     /// let event = EventType::Request { /* ... */ };
-    /// let result = evaluator.eval_answer_handler_block(&answer_block, context, event).await?;
+    /// let result = evaluator
+    ///     .eval_answer_handler_block(&answer_block, context, event, &AnswerPipeline::default())
+    ///     .await?;
     /// ```
-    #[tracing::instrument(skip(self, context), level = "debug")]
+    #[tracing::instrument(skip(self, context, pipeline), level = "debug")]
     pub async fn eval_answer_handler_block(
         &self,
         block: &HandlerBlock,
         context: Arc<ExecutionContext>,
         event: EventType,
-    ) -> EvalResult<StatementResult> {
+        pipeline: &AnswerPipeline,
+    ) -> EvalResult<Option<Value>> {
+        // yield で部分応答を送れるようにリクエストを紐付ける
+        let context = Arc::new(
+            context
+                .fork(None)
+                .await
+                .with_partial_responses(event.clone()),
+        );
         let result = self
             .statement_evaluator
             .eval_block(&block.statements, context.clone())
             .await;
-        match result {
+        let response = match result {
             Ok(StatementResult::Control(ControlFlow::Return(value))) => {
+                let postprocess = context
+                    .profiler()
+                    .map(|profiler| profiler.enter("postprocess"));
                 let response = match value {
-                    Value::Ok(inner) => Ok(*inner),
+                    Value::Ok(inner) => pipeline
+                        .apply(*inner)
+                        .map_err(|e| RuntimeError::from(EvalError::from(e))),
                     Value::Err(inner) => Err(RuntimeError::EvalFailure(*inner)),
                     // Exception case returns an error
                     Value::Error(e) => {
                         return Err(EvalError::Eval(format!("Unhandled exception: {:?}", e)));
                     }
-                    other => Ok(other),
+                    other => pipeline
+                        .apply(other)
+                        .map_err(|e| RuntimeError::from(EvalError::from(e))),
                 };
-                context
-                    .send_response(event, response)
-                    .await
-                    .map_err(|e| EvalError::SendResponseFailed(format!("error: {}", e)))?;
-            }
-            Ok(StatementResult::Value(Value::Unit)) => {
-                context
-                    .send_response(event, Ok(Value::Unit))
-                    .await
-                    .map_err(|e| EvalError::SendResponseFailed(format!("error: {}", e)))?;
-            }
-            Err(e) => {
-                context
-                    .send_response(event, Err(RuntimeError::from(e)))
-                    .await
-                    .map_err(|e| EvalError::SendResponseFailed(format!("error: {}", e)))?;
+                drop(postprocess);
+                response
             }
+            Ok(StatementResult::Value(Value::Unit)) => Ok(Value::Unit),
+            Err(e) => Err(RuntimeError::from(e)),
             // Other cases return an error
             Ok(s) => {
                 return Err(EvalError::Eval(format!(
@@ -205,8 +244,88 @@ impl Evaluator {
                     s
                 )))?;
             }
+        };
+        let answered = response.as_ref().ok().cloned();
+        context
+            .send_response(event, response)
+            .await
+            .map_err(|e| EvalError::SendResponseFailed(format!("error: {}", e)))?;
+        Ok(answered)
+    }
+
+    /// Evaluates the `requires` precondition of a handler
+    ///
+    /// A handler without a precondition may always run. The condition is
+    /// evaluated in the handler's context, after its parameters are bound, and
+    /// must produce a Boolean.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(true)`: The handler block may run
+    /// - `Ok(false)`: The precondition does not hold
+    /// - `Err(EvalError)`: If the condition fails to evaluate or is not a Boolean
+    #[tracing::instrument(skip(self, context), level = "debug")]
+    pub async fn eval_precondition(
+        &self,
+        requires: Option<&Expression>,
+        context: Arc<ExecutionContext>,
+    ) -> EvalResult<bool> {
+        let Some(condition) = requires else {
+            return Ok(true);
+        };
+        match self.eval_expression(condition, context).await? {
+            Value::Boolean(holds) => Ok(holds),
+            other => Err(EvalError::InvalidOperation(format!(
+                "requires clause must evaluate to a Boolean, got {:?}",
+                other
+            ))),
         }
-        Ok(StatementResult::Value(Value::Unit))
+    }
+
+    /// Evaluates the `where` constraints of a handler's parameters
+    ///
+    /// Every constraint is evaluated, after the parameters are bound, so that all
+    /// violations can be reported at once. A missing optional parameter (bound to
+    /// null) is not checked.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(violations)`: The constraints that do not hold, in parameter order
+    /// - `Err(EvalError)`: If a constraint fails to evaluate or is not a Boolean
+    #[tracing::instrument(skip(self, context), level = "debug")]
+    pub async fn eval_parameter_constraints(
+        &self,
+        parameters: &[Parameter],
+        context: Arc<ExecutionContext>,
+    ) -> EvalResult<Vec<ConstraintViolation>> {
+        let mut violations = Vec::new();
+        for param in parameters {
+            let Some(constraint) = &param.constraint else {
+                continue;
+            };
+            if matches!(param.type_info, TypeInfo::Option(_))
+                && matches!(context.get_variable(&param.name).await, Ok(Value::Null))
+            {
+                continue;
+            }
+            let condition = constraint.condition(&param.name);
+            match self.eval_expression(&condition, context.clone()).await? {
+                Value::Boolean(true) => {}
+                Value::Boolean(false) => violations.push(ConstraintViolation {
+                    parameter: param.name.clone(),
+                    constraint: Formatter::new(FormatterConfig::default())
+                        .format_parameter_constraint(&param.name, constraint)
+                        .unwrap_or_else(|_| format!("{:?}", constraint)),
+                }),
+                other => {
+                    return Err(EvalError::InvalidOperation(format!(
+                        "constraint of parameter '{}' must evaluate to a Boolean, got {:?}",
+                        param.name, other
+                    )));
+                }
+            }
+        }
+        Ok(violations)
     }
 
     /// Evaluates an expression and returns its value
@@ -263,6 +382,12 @@ pub enum EvalError {
     Provider(#[from] ProviderError),
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
+    #[error("Answer post-processing failed: {0}")]
+    Postprocess(#[from] PostprocessError),
+    #[error("Tool call failed: {0}")]
+    Tool(#[from] ToolError),
+    #[error("Shared counter failed: {0}")]
+    SharedCounter(#[from] SharedCounterError),
     #[error("Send response failed: {0}")]
     SendResponseFailed(String),
     #[error("Variable not found: {name}, {messages}")]
@@ -273,6 +398,141 @@ pub enum EvalError {
     InvalidParameter { name: String, value: String },
     #[error("Invalid operation: {0}")]
     InvalidOperation(String),
+    #[error(
+        "Budget exceeded: agent {agent_name} may call the LLM {max_calls} times per {window:?}, retry after {retry_after:?}"
+    )]
+    BudgetExceeded {
+        agent_name: String,
+        max_calls: u32,
+        window: Duration,
+        retry_after: Duration,
+    },
+    #[error(
+        "Budget exceeded: agent {agent_name} hit {guardrail} = {limit} in one execution (think calls {}, tokens {}, sub-requests {})",
+        .counters.think_calls,
+        .counters.tokens,
+        .counters.sub_requests
+    )]
+    ExecutionBudgetExceeded {
+        agent_name: String,
+        guardrail: Guardrail,
+        limit: u64,
+        counters: ExecutionCounters,
+    },
+    #[error(
+        "Evaluation limit exceeded: agent {agent_name} reached {actual} against {limit} = {max}"
+    )]
+    LimitExceeded {
+        agent_name: String,
+        limit: EvalLimit,
+        max: u64,
+        actual: u64,
+    },
+    #[error("Secret not granted: agent {agent_name} may not read secret {name}")]
+    SecretNotGranted { agent_name: String, name: String },
+    #[error(
+        "Precondition failed: agent {agent_name} did not meet the requires clause of {handler}"
+    )]
+    PreconditionFailed { agent_name: String, handler: String },
+    #[error(
+        "Validation failed: {handler} of agent {agent_name} rejected its parameters: {}",
+        ConstraintViolation::join(.violations)
+    )]
+    ValidationFailed {
+        agent_name: String,
+        handler: String,
+        violations: Vec<ConstraintViolation>,
+    },
+}
+
+/// A parameter whose `where` constraint does not hold
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ConstraintViolation {
+    /// Name of the parameter
+    pub parameter: String,
+    /// Source text of the constraint, e.g. `days in 1..30`
+    pub constraint: String,
+}
+
+impl ConstraintViolation {
+    pub fn to_event_value(&self) -> event_bus::Value {
+        event_bus::Value::Map(HashMap::from([
+            (
+                "parameter".to_string(),
+                event_bus::Value::from(self.parameter.as_str()),
+            ),
+            (
+                "constraint".to_string(),
+                event_bus::Value::from(self.constraint.as_str()),
+            ),
+        ]))
+    }
+
+    pub fn from_event_value(value: &event_bus::Value) -> Option<Self> {
+        let event_bus::Value::Map(map) = value else {
+            return None;
+        };
+        let get = |key: &str| match map.get(key) {
+            Some(event_bus::Value::String(s)) => Some(s.clone()),
+            _ => None,
+        };
+        Some(Self {
+            parameter: get("parameter")?,
+            constraint: get("constraint")?,
+        })
+    }
+
+    fn join(violations: &[ConstraintViolation]) -> String {
+        violations
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl fmt::Display for ConstraintViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} violates `{}`", self.parameter, self.constraint)
+    }
+}
+
+impl EvalError {
+    /// Whether `retry(...)` runs its body again after this error. Exceeded budgets,
+    /// denied secrets, unmet preconditions, rejected parameters and passed deadlines
+    /// fail the same way on every attempt.
+    pub fn is_retryable(&self) -> bool {
+        !matches!(
+            self,
+            EvalError::BudgetExceeded { .. }
+                | EvalError::ExecutionBudgetExceeded { .. }
+                | EvalError::LimitExceeded { .. }
+                | EvalError::SecretNotGranted { .. }
+                | EvalError::PreconditionFailed { .. }
+                | EvalError::ValidationFailed { .. }
+                | EvalError::SendResponseFailed(_)
+                | EvalError::Context(ContextError::Request(RequestError::DeadlineExceeded(_)))
+        )
+    }
+
+    /// The diagnostic shown to end users for errors caused by the request itself
+    pub fn diagnostic(&self) -> Option<Diagnostic> {
+        match self {
+            EvalError::PreconditionFailed { handler, .. } => Some(
+                Diagnostic::new(codes::REQUEST_PRECONDITION_FAILED).with_arg("handler", handler),
+            ),
+            EvalError::ValidationFailed {
+                handler,
+                violations,
+                ..
+            } => Some(
+                Diagnostic::new(codes::REQUEST_INVALID_PARAMETERS)
+                    .with_arg("handler", handler)
+                    .with_arg("violations", ConstraintViolation::join(violations)),
+            ),
+            _ => None,
+        }
+    }
 }
 
 pub type EvalResult<T> = Result<T, EvalError>;
@@ -4,15 +4,28 @@ use std::time::Duration;
 use std::{collections::HashMap, sync::Arc};
 
 use async_recursion::async_recursion;
+use rand::{Rng, SeedableRng, rngs::StdRng};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use tracing::debug;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
 use uuid::Uuid;
 
-use super::context::{ExecutionContext, VariableAccess};
-use crate::config::{MemoryConfig, PluginConfig, RagConfig, SearchConfig};
+use super::budget::GuardrailExceeded;
+use super::context::{ContextError, ExecutionContext, VariableAccess};
+use super::postprocess::from_json;
+use super::secret::SecretValue;
+use super::set::{SetElement, ValueSet};
+use super::template::render_value;
+use crate::catalog::RequestSignature;
+use crate::config::{
+    CatalogConfig, MemoryConfig, OpenApiToolsConfig, OutputFormat, PluginConfig, RagConfig,
+    SearchConfig, SistenceContextConfig,
+};
 use crate::eval::evaluator::{EvalError, EvalResult};
+use crate::event::stream_event::StreamEvent;
 use crate::event_bus::Event;
+use crate::provider::plugins::memory::shared_counters::RateLimit;
 use crate::provider::provider_registry::ProviderInstance;
 use crate::provider::request::{
     ExecutionState, ProviderContext, ProviderRequest, ProviderResponse, RequestInput,
@@ -34,6 +47,8 @@ pub enum Value {
     Boolean(bool),
     List(Vec<Value>),
     Map(HashMap<String, Value>),
+    /// `#{a, b}`; see [`ValueSet`] for equality and ordering
+    Set(ValueSet),
     Duration(std::time::Duration),
     Delay(RetryDelay),
     Tuple(Vec<Value>),
@@ -41,6 +56,9 @@ pub enum Value {
     Error(String), // Error name for handling.
     Ok(Box<Value>),
     Err(Box<Value>),
+    /// From `secret("name")`; always shown and serialized masked
+    #[serde(skip_deserializing)]
+    Secret(SecretValue),
     #[default]
     Null,
 }
@@ -49,6 +67,7 @@ impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Value::String(s) => write!(f, "{}", s),
+            Value::Secret(secret) => write!(f, "{}", secret),
             _ => write!(f, "{:?}", self),
         }
     }
@@ -62,6 +81,36 @@ impl Default for ExpressionEvaluator {
     }
 }
 
+impl From<SetElement> for Value {
+    fn from(element: SetElement) -> Self {
+        match element {
+            SetElement::Boolean(b) => Value::Boolean(b),
+            SetElement::Integer(i) => Value::Integer(i),
+            SetElement::String(s) => Value::String(s),
+        }
+    }
+}
+
+impl TryFrom<&Value> for SetElement {
+    type Error = EvalError;
+
+    /// Only booleans, integers and strings can be set elements
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Boolean(b) => Ok(SetElement::Boolean(*b)),
+            Value::Integer(i) => Ok(SetElement::Integer(*i)),
+            Value::UInteger(u) => i64::try_from(*u)
+                .map(SetElement::Integer)
+                .map_err(|_| EvalError::Eval(format!("{} is too large for a set element", u))),
+            Value::String(s) => Ok(SetElement::String(s.clone())),
+            other => Err(EvalError::Eval(format!(
+                "Set elements must be Boolean, Int or String, but got {:?}",
+                other
+            ))),
+        }
+    }
+}
+
 impl From<ProviderResponse> for Value {
     fn from(response: ProviderResponse) -> Self {
         let mut hash_map = HashMap::new();
@@ -78,13 +127,12 @@ impl ExpressionEvaluator {
         expr: &Expression,
         context: Arc<ExecutionContext>,
     ) -> EvalResult<Value> {
-        match expr {
+        let usage = context.eval_usage().clone();
+        let _frame = usage.enter()?;
+        let value = match expr {
             Expression::Literal(lit) => Self::eval_literal(lit),
             Expression::Variable(name) => self.eval_variable(name, context).await,
-            Expression::StateAccess(path) => {
-                self.eval_state_access(path.0.join(".").as_str(), context)
-                    .await
-            }
+            Expression::StateAccess(path) => self.eval_state_access(&path.0, context).await,
             Expression::FunctionCall {
                 function,
                 arguments,
@@ -128,7 +176,18 @@ impl ExpressionEvaluator {
             Expression::Err(expression) => Ok(Value::Err(Box::new(
                 self.eval_expression(expression, context).await?,
             ))),
+        }?;
+        // 変数の読み出しや Ok / Err での包み直しは新しい値を作らない
+        if !matches!(
+            expr,
+            Expression::Variable(_)
+                | Expression::StateAccess(_)
+                | Expression::Ok(_)
+                | Expression::Err(_)
+        ) {
+            usage.track(&value)?;
         }
+        Ok(value)
     }
 
     pub fn new() -> Self {
@@ -186,11 +245,11 @@ impl ExpressionEvaluator {
         Ok(evaluated_params)
     }
 
-    fn eval_literal(lit: &Literal) -> EvalResult<Value> {
+    pub(crate) fn eval_literal(lit: &Literal) -> EvalResult<Value> {
         Ok(match lit {
             Literal::Integer(i) => Value::Integer(*i),
             Literal::Float(f) => Value::Float(*f),
-            Literal::String(s) => Value::String(s.clone()),
+            Literal::String(s) | Literal::RawString { value: s, .. } => Value::String(s.clone()),
             Literal::Boolean(b) => Value::Boolean(*b),
             Literal::Duration(d) => Value::Duration(*d),
             Literal::List(items) => {
@@ -207,6 +266,13 @@ impl ExpressionEvaluator {
                 }
                 Value::Map(map)
             }
+            Literal::Set(items) => {
+                let mut set = ValueSet::new();
+                for item in items {
+                    set.insert(SetElement::try_from(&Self::eval_literal(item)?)?);
+                }
+                Value::Set(set)
+            }
             Literal::Null => Value::Null,
             Literal::Retry(retry) => {
                 // make hashmap
@@ -243,10 +309,25 @@ impl ExpressionEvaluator {
     #[tracing::instrument(skip(self, context))]
     async fn eval_state_access(
         &self,
-        path: &str,
+        path: &[String],
         context: Arc<ExecutionContext>,
     ) -> EvalResult<Value> {
-        let access = VariableAccess::State(path.to_string());
+        // ローカル変数 (request / event 等の Map) のフィールド参照を優先し、なければ状態変数
+        if let [root, fields @ ..] = path {
+            if !fields.is_empty() {
+                if let Ok(mut value) = context.get_variable(root).await {
+                    for field in fields {
+                        value = match value {
+                            Value::Map(mut map) => map.remove(field),
+                            _ => None,
+                        }
+                        .ok_or_else(|| ContextError::VariableNotFound(path.join(".")))?;
+                    }
+                    return Ok(value);
+                }
+            }
+        }
+        let access = VariableAccess::State(path.join("."));
         context.get(access).await.map_err(EvalError::from)
     }
 
@@ -257,34 +338,124 @@ impl ExpressionEvaluator {
         with_block: &Option<ThinkAttributes>,
         context: Arc<ExecutionContext>,
     ) -> EvalResult<Value> {
-        let provider_name = if let Some(with_block) = with_block {
-            with_block.provider.clone()
-        } else {
-            None
-        };
+        // with ブロックの指定、実行中に設定された既定のプロバイダー、プライマリの順
+        let provider_name = with_block
+            .as_ref()
+            .and_then(|with_block| with_block.provider.clone())
+            .or_else(|| context.runtime_settings().provider);
 
         let provider = self.select_provider(provider_name, context.clone()).await?;
 
         let policies = self.collect_policies(context.clone(), with_block.as_ref())?;
 
         let request = self
-            .to_provider_request(provider.as_ref(), args, with_block, context, policies)
+            .to_provider_request(
+                provider.as_ref(),
+                args,
+                with_block,
+                context.clone(),
+                policies,
+            )
             .await?;
 
-        let context = ProviderContext {
+        // 上限を超えた呼び出しはプロバイダーに届く前に失敗させる
+        if let Some(budget) = context.llm_budget() {
+            budget
+                .try_acquire()
+                .map_err(|exhausted| EvalError::BudgetExceeded {
+                    agent_name: context.agent_name(),
+                    max_calls: exhausted.max_calls,
+                    window: exhausted.window,
+                    retry_after: exhausted.retry_after,
+                })?;
+        }
+        if let Err(exceeded) = context.execution_usage().begin_think(context.guardrails()) {
+            return Err(self.guardrail_tripped(exceeded, context).await);
+        }
+
+        // stream: true なら生成中の出力を部分応答として呼び出し元に送る
+        let streaming = with_block
+            .as_ref()
+            .is_some_and(|attributes| attributes.stream)
+            && context.accepts_partial_responses();
+        let (chunks, mut chunk_receiver) = if streaming {
+            let (sender, receiver) = mpsc::unbounded_channel();
+            (Some(sender), Some(receiver))
+        } else {
+            (None, None)
+        };
+        let provider_context = ProviderContext {
             config: provider.config.clone(),
             secret: provider.secret.clone(),
+            profiler: context.profiler().cloned(),
+            chunks,
         };
 
-        let response = provider
-            .provider
-            .execute(&context, &request)
-            .await
-            .map_err(EvalError::from)?;
+        let _span = context
+            .profiler()
+            .map(|profiler| profiler.enter(format!("provider {}", provider.config.name)));
+        let execute = async {
+            let response = provider.provider.execute(&provider_context, &request).await;
+            // 送信側を閉じて転送を終わらせる
+            drop(provider_context);
+            response
+        };
+        let forward = async {
+            let mut forwarded = 0;
+            if let Some(receiver) = chunk_receiver.as_mut() {
+                while let Some(chunk) = receiver.recv().await {
+                    if !chunk.is_empty() {
+                        context.send_partial_response(Value::String(chunk)).await?;
+                        forwarded += 1;
+                    }
+                }
+            }
+            Ok::<_, ContextError>(forwarded)
+        };
+        let (response, forwarded) = tokio::join!(execute, forward);
+        let response = response.map_err(EvalError::from)?;
+        // 断片を流せなかったプロバイダーでも出力全体を 1 つの部分応答として送る
+        if forwarded? == 0 && streaming {
+            context
+                .send_partial_response(Value::String(response.output.clone()))
+                .await?;
+        }
+        if let Some((prompt, completion)) = response.metadata.token_usage {
+            context
+                .execution_usage()
+                .record_tokens((prompt + completion) as u64);
+        }
+        if let Some(recorder) = context.recorder() {
+            recorder.record_response(&provider.config.name, &response.output);
+        }
 
         Ok(Value::from(response))
     }
 
+    /// ガードレール超過をシステムイベントで通知し、onFail で捕捉できるエラーにする
+    async fn guardrail_tripped(
+        &self,
+        exceeded: GuardrailExceeded,
+        context: Arc<ExecutionContext>,
+    ) -> EvalError {
+        warn!(
+            "Agent {} hit {} = {}: {:?}",
+            context.agent_name(),
+            exceeded.guardrail,
+            exceeded.limit,
+            exceeded.counters
+        );
+        if let Err(e) = context.emit_guardrail_tripped(&exceeded).await {
+            warn!("Failed to emit guardrail event: {}", e);
+        }
+        EvalError::ExecutionBudgetExceeded {
+            agent_name: context.agent_name(),
+            guardrail: exceeded.guardrail,
+            limit: exceeded.limit,
+            counters: exceeded.counters,
+        }
+    }
+
     #[tracing::instrument(skip(self, context))]
     async fn select_provider(
         &self,
@@ -326,12 +497,12 @@ impl ExpressionEvaluator {
         policies: Vec<Policy>,
     ) -> EvalResult<ProviderRequest> {
         let (query_template, tail_args) = self.query_from_args(args, context.clone()).await?;
-        let mut query_str = query_template.to_string();
-        let vars = self.extract_variables_from_template(&query_str);
-        for var in vars {
-            let value = context.get_variable(&var).await?;
-            query_str = query_str.replace(&format!("${{{}}}", var), &value.to_string());
-        }
+        let query_str = query_template.to_string();
+        // raw string の中の ${...} は補間しない
+        let query_str = match Self::split_query_args(args).0 {
+            Some(Expression::Literal(Literal::RawString { .. })) => query_str,
+            _ => self.interpolate_template(&query_str, &context).await?,
+        };
         let query = Value::String(query_str);
 
         let parameters = self
@@ -348,9 +519,26 @@ impl ExpressionEvaluator {
             agent_name: context.agent_name().to_string(),
             agent_info: context.agent_info().clone(),
             trace_id: context.generate_trace_id(),
+            request_id: context.request_id().map(str::to_string),
+            request_context: context.request_context().cloned(),
+            prompt_preamble: match context.prompt_preamble() {
+                Some(preamble) => Some(self.interpolate_template(&preamble, &context).await?),
+                None => None,
+            },
         };
 
         let mut config = provider.config.clone();
+        if context.output_format() == OutputFormat::Json {
+            config.common_config.output_format = OutputFormat::Json;
+        }
+        // 実行中に設定された既定値。think ブロックの値がさらに優先される
+        let settings = context.runtime_settings();
+        if let Some(temperature) = settings.temperature {
+            config.common_config.temperature = temperature;
+        }
+        if let Some(max_tokens) = settings.max_tokens {
+            config.common_config.max_tokens = max_tokens;
+        }
         if let Some(attrs) = think_attrs {
             if let Some(model) = attrs.model.clone() {
                 config.common_config.model = model;
@@ -358,6 +546,9 @@ impl ExpressionEvaluator {
             if let Some(temperature) = attrs.temperature {
                 config.common_config.temperature = temperature as f32;
             }
+            if let Some(top_p) = attrs.top_p {
+                config.common_config.top_p = Some(top_p as f32);
+            }
             if let Some(max_tokens) = attrs.max_tokens {
                 config.common_config.max_tokens = max_tokens as usize;
             }
@@ -381,6 +572,7 @@ impl ExpressionEvaluator {
             input,
             state,
             config,
+            ..Default::default()
         })
     }
 
@@ -390,65 +582,59 @@ impl ExpressionEvaluator {
         args: &[Argument],
         context: Arc<ExecutionContext>,
     ) -> EvalResult<(Value, Vec<Argument>)> {
-        if args.len() == 1 {
-            let content = match args.first() {
-                Some(Argument::Positional(expr)) => self.eval_expression(expr, context).await?,
-                Some(Argument::Named { value, .. }) => self.eval_expression(value, context).await?,
-                None => Value::Null,
-            };
-            return Ok((content, vec![]));
+        match Self::split_query_args(args) {
+            (Some(expr), tail) => Ok((self.eval_expression(expr, context).await?, tail)),
+            (None, tail) => Ok((Value::Null, tail)),
         }
+    }
 
-        if let Some(Argument::Named { value, .. }) = args
-            .iter()
-            .find(|arg| matches!(arg, Argument::Named { name, .. } if name == "query"))
-        {
-            let filter_not_query = args
-                .iter()
-                .filter(|arg| !matches!(arg, Argument::Named { name, .. } if name == "query"))
-                .cloned()
-                .collect::<Vec<Argument>>();
-            return Ok((
-                self.eval_expression(value, context).await?,
-                filter_not_query,
-            ));
+    /// Pick the query expression out of think arguments and return it with the
+    /// remaining arguments.
+    fn split_query_args(args: &[Argument]) -> (Option<&Expression>, Vec<Argument>) {
+        if args.len() == 1 {
+            let expr = match args.first() {
+                Some(Argument::Positional(expr)) => Some(expr),
+                Some(Argument::Named { value, .. }) => Some(value),
+                None => None,
+            };
+            return (expr, vec![]);
         }
 
-        if let Some(Argument::Named { value, .. }) = args
-            .iter()
-            .find(|arg| matches!(arg, Argument::Named { name, .. } if name == "query"))
-        {
-            let filter_not_query = args
+        for key in ["query", "message"] {
+            if let Some(Argument::Named { value, .. }) = args
                 .iter()
-                .filter(|arg| !matches!(arg, Argument::Named { name, .. } if name == "query"))
-                .cloned()
-                .collect::<Vec<Argument>>();
-            return Ok((
-                self.eval_expression(value, context).await?,
-                filter_not_query,
-            ));
+                .find(|arg| matches!(arg, Argument::Named { name, .. } if name == key))
+            {
+                let rest = args
+                    .iter()
+                    .filter(|arg| !matches!(arg, Argument::Named { name, .. } if name == key))
+                    .cloned()
+                    .collect::<Vec<Argument>>();
+                return (Some(value), rest);
+            }
         }
-        if let Some(Argument::Named { value, .. }) = args
-            .iter()
-            .find(|arg| matches!(arg, Argument::Named { name, .. } if name == "message"))
-        {
-            let filter_not_query = args
-                .iter()
-                .filter(|arg| !matches!(arg, Argument::Named { name, .. } if name == "message"))
-                .cloned()
-                .collect::<Vec<Argument>>();
-            return Ok((
-                self.eval_expression(value, context).await?,
-                filter_not_query,
-            ));
+        if let Some(Argument::Positional(expr)) = args.first() {
+            return (Some(expr), args[1..].to_vec());
         }
-        if let Some(arg) = args.first() {
-            let tail = args[1..].to_vec();
-            if let Argument::Positional(expr) = arg {
-                return Ok((self.eval_expression(expr, context).await?, tail));
-            }
+        (None, args.to_vec())
+    }
+
+    /// テンプレート中の `${name}` を変数（なければ state）の値で置き換える。
+    /// 日付と数値は実行中のロケールの書式で書く
+    async fn interpolate_template(
+        &self,
+        template: &str,
+        context: &ExecutionContext,
+    ) -> EvalResult<String> {
+        let mut result = template.to_string();
+        for var in self.extract_variables_from_template(template) {
+            let value = context.get_variable(&var).await?;
+            result = result.replace(
+                &format!("${{{}}}", var),
+                &render_value(&value, context.locale()),
+            );
         }
-        Ok((Value::Null, args.to_vec()))
+        Ok(result)
     }
 
     fn extract_variables_from_template(&self, template: &str) -> HashSet<String> {
@@ -479,23 +665,40 @@ impl ExpressionEvaluator {
         _options: &Option<RequestAttributes>,
         context: Arc<ExecutionContext>,
     ) -> EvalResult<Value> {
+        if let Err(exceeded) = context
+            .execution_usage()
+            .begin_sub_request(context.guardrails())
+        {
+            return Err(self.guardrail_tripped(exceeded, context).await);
+        }
+
+        let responder = self.resolve_responder(agent, &context).await;
+
         // パラメータの評価
         let evaluated_params = self.eval_arguments(parameters, context.clone()).await?;
 
-        let event_params = evaluated_params
+        let mut event_params: HashMap<String, event_bus::Value> = evaluated_params
             .iter()
             .map(|(k, v)| (k.clone(), event_bus::Value::from(v.clone())))
             .collect();
+        // 呼び出し元のリクエストコンテキストを引き継ぐ
+        if let Some(request_context) = context.request_context() {
+            event_params.insert(
+                event_bus::REQUEST_CONTEXT_KEY.to_string(),
+                request_context.to_event_value(),
+            );
+        }
 
         // リクエストの構築と送信
         let request = Event {
             event_type: event_registry::EventType::Request {
                 request_id: Uuid::new_v4().to_string(),
                 requester: context.agent_name().clone(),
-                responder: agent.to_string(),
+                responder,
                 request_type: request_type.to_string(),
             },
             parameters: event_params,
+            ..Default::default()
         };
         debug!("Create Request: {:?}", request);
         let response_event = context.send_request(request).await?;
@@ -512,6 +715,20 @@ impl ExpressionEvaluator {
         }
     }
 
+    /// `request X to delegate(...)` で `delegate` が文字列か think の結果を持つローカル変数なら、
+    /// その値を宛先にする。カタログを見て選んだエージェントに振り分けるため
+    async fn resolve_responder(&self, agent: &str, context: &ExecutionContext) -> String {
+        let name = match context.get_variable(agent).await {
+            Ok(Value::String(name)) => Some(name),
+            Ok(Value::Map(mut response)) => match response.remove("output") {
+                Some(Value::String(output)) => Some(output),
+                _ => None,
+            },
+            _ => None,
+        };
+        name.map_or_else(|| agent.to_string(), |name| name.trim().to_string())
+    }
+
     #[tracing::instrument(skip(self, context))]
     async fn eval_await(
         &self,
@@ -570,8 +787,25 @@ impl ExpressionEvaluator {
         // 組み込み関数の評価
         match function {
             "len" => self.eval_len_function(&evaluated_args),
+            "insert" | "remove" | "contains" | "union" | "intersect" | "to_list" => {
+                self.eval_set_function(function, &evaluated_args)
+            }
             "sum" => self.eval_sum_function(&evaluated_args),
             "avg" => self.eval_avg_function(&evaluated_args),
+            "secret" => self.eval_secret_function(&evaluated_args, &context),
+            "list_agents" => self.eval_list_agents_function(&evaluated_args, &context),
+            "agent_requests" => self.eval_agent_requests_function(&evaluated_args, &context),
+            "call_tool" => self.eval_call_tool_function(evaluated_args, &context).await,
+            "choose_weighted" => {
+                self.eval_choose_function(function, &evaluated_args, 2, || context.random())
+            }
+            "choose_seeded" => self.eval_choose_function(function, &evaluated_args, 3, || {
+                seeded_random(&evaluated_args[2])
+            }),
+            "increment" | "decrement_if_positive" | "try_acquire" => {
+                self.eval_shared_counter_function(function, &evaluated_args, &context)
+                    .await
+            }
             //"max" => self.eval_max_function(&evaluated_args),
             //"min" => self.eval_min_function(&evaluated_args),
             //"now" => self.eval_now_function(),
@@ -597,6 +831,7 @@ impl ExpressionEvaluator {
             BinaryOperator::Subtract => self.eval_subtract(&left_val, &right_val),
             BinaryOperator::Multiply => self.eval_multiply(&left_val, &right_val),
             BinaryOperator::Divide => self.eval_divide(&left_val, &right_val),
+            BinaryOperator::Modulo => self.eval_modulo(&left_val, &right_val),
             BinaryOperator::Equal => self.eval_equal(&left_val, &right_val),
             BinaryOperator::NotEqual => self.eval_not_equal(&left_val, &right_val),
             BinaryOperator::LessThan => self.eval_less_than(&left_val, &right_val),
@@ -610,6 +845,211 @@ impl ExpressionEvaluator {
 
     // 以下、組み込み関数の実装
 
+    /// ポリシーで許可されたシークレットのみ返す
+    fn eval_secret_function(
+        &self,
+        args: &[Value],
+        context: &ExecutionContext,
+    ) -> EvalResult<Value> {
+        let name = match args {
+            [Value::String(name)] => name,
+            _ => {
+                return Err(EvalError::Eval(
+                    "secret function requires exactly one string argument".to_string(),
+                ));
+            }
+        };
+        context
+            .secret(name)
+            .map(Value::Secret)
+            .ok_or_else(|| EvalError::SecretNotGranted {
+                agent_name: context.agent_name(),
+                name: name.clone(),
+            })
+    }
+
+    fn eval_list_agents_function(
+        &self,
+        args: &[Value],
+        context: &ExecutionContext,
+    ) -> EvalResult<Value> {
+        if !args.is_empty() {
+            return Err(EvalError::Eval(
+                "list_agents function takes no arguments".to_string(),
+            ));
+        }
+        Ok(Value::List(
+            context
+                .catalog()
+                .agent_names()
+                .into_iter()
+                .map(Value::String)
+                .collect(),
+        ))
+    }
+
+    fn eval_agent_requests_function(
+        &self,
+        args: &[Value],
+        context: &ExecutionContext,
+    ) -> EvalResult<Value> {
+        let agent_name = match args {
+            [Value::String(name)] => name,
+            _ => {
+                return Err(EvalError::Eval(
+                    "agent_requests function requires exactly one string argument".to_string(),
+                ));
+            }
+        };
+        Ok(Value::List(
+            context
+                .catalog()
+                .requests(agent_name)
+                .iter()
+                .map(RequestSignature::to_value)
+                .collect(),
+        ))
+    }
+
+    /// OpenAPI から読み込んだツールを呼び出し、JSON の応答を値にする
+    async fn eval_call_tool_function(
+        &self,
+        args: Vec<Value>,
+        context: &ExecutionContext,
+    ) -> EvalResult<Value> {
+        let (name, arguments) = match <[Value; 2]>::try_from(args) {
+            Ok(
+                [
+                    Value::String(name),
+                    arguments @ (Value::Map(_) | Value::Null),
+                ],
+            ) => (name, arguments),
+            _ => {
+                return Err(EvalError::Eval(
+                    "call_tool function requires a tool name and a map of arguments".to_string(),
+                ));
+            }
+        };
+        let arguments = serde_json::Value::from(&event_bus::Value::from(arguments));
+        // ストリーミング中の要求元に呼び出しの開始と引数を知らせる
+        let id = format!("call_{}", Uuid::new_v4().simple());
+        context
+            .send_stream_event(StreamEvent::ToolCallStart {
+                id: id.clone(),
+                name: name.clone(),
+            })
+            .await?;
+        context
+            .send_stream_event(StreamEvent::ToolCallArgsDelta {
+                id,
+                delta: arguments.to_string(),
+            })
+            .await?;
+        let response = context.tools().call(&name, &arguments).await?;
+        Ok(from_json(response))
+    }
+
+    /// エージェント間で共有するカウンターとレート制限。どれも他のエージェントと競合しても更新を失わない
+    async fn eval_shared_counter_function(
+        &self,
+        function: &str,
+        args: &[Value],
+        context: &ExecutionContext,
+    ) -> EvalResult<Value> {
+        let counters = context.shared_counters();
+        match (function, args) {
+            ("increment", [Value::String(key), Value::Integer(by)]) => {
+                Ok(Value::Integer(counters.increment(key, *by).await?))
+            }
+            ("decrement_if_positive", [Value::String(key)]) => {
+                Ok(Value::Boolean(counters.decrement_if_positive(key).await?))
+            }
+            (
+                "try_acquire",
+                [
+                    Value::String(key),
+                    Value::Integer(rate),
+                    Value::Duration(per),
+                ],
+            ) => {
+                let rate = u32::try_from(*rate).map_err(|_| EvalError::InvalidParameter {
+                    name: "rate".to_string(),
+                    value: rate.to_string(),
+                })?;
+                let limit = RateLimit { rate, per: *per };
+                Ok(Value::Boolean(counters.try_acquire(key, limit).await?))
+            }
+            _ => Err(EvalError::Eval(format!(
+                "Invalid arguments for {} function: {:?}",
+                function, args
+            ))),
+        }
+    }
+
+    /// 重みに比例して選択肢を 1 つ選ぶ。`roll` は `[0, 1)` の一様乱数を返す
+    fn eval_choose_function(
+        &self,
+        function: &str,
+        args: &[Value],
+        arity: usize,
+        roll: impl FnOnce() -> f64,
+    ) -> EvalResult<Value> {
+        let (options, weights) = match args {
+            [Value::List(options), Value::List(weights), ..] if args.len() == arity => {
+                (options, weights)
+            }
+            _ => {
+                return Err(EvalError::Eval(format!(
+                    "{} function requires a list of options and a list of weights",
+                    function
+                )));
+            }
+        };
+        if options.len() != weights.len() {
+            return Err(EvalError::Eval(format!(
+                "{} function requires as many weights as options, got {} options and {} weights",
+                function,
+                options.len(),
+                weights.len()
+            )));
+        }
+        let weights = weights
+            .iter()
+            .map(|weight| match weight {
+                Value::Integer(i) if *i >= 0 => Ok(*i as f64),
+                Value::UInteger(u) => Ok(*u as f64),
+                Value::Float(f) if *f >= 0.0 && f.is_finite() => Ok(*f),
+                _ => Err(EvalError::Eval(format!(
+                    "{} function requires non-negative weights, but got {:?}",
+                    function, weight
+                ))),
+            })
+            .collect::<EvalResult<Vec<f64>>>()?;
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 {
+            return Err(EvalError::Eval(format!(
+                "{} function requires a positive total weight",
+                function
+            )));
+        }
+
+        let mut target = roll() * total;
+        for (option, weight) in options.iter().zip(&weights) {
+            if target < *weight {
+                return Ok(option.clone());
+            }
+            target -= weight;
+        }
+        // 丸め誤差で末尾を越えたら、重みのある最後の選択肢
+        Ok(options
+            .iter()
+            .zip(&weights)
+            .rev()
+            .find(|(_, weight)| **weight > 0.0)
+            .map(|(option, _)| option.clone())
+            .unwrap_or(Value::Null))
+    }
+
     fn eval_len_function(&self, args: &[Value]) -> EvalResult<Value> {
         if args.len() != 1 {
             return Err(EvalError::Eval(
@@ -621,13 +1061,49 @@ impl ExpressionEvaluator {
             Value::String(s) => Ok(Value::Integer(s.len() as i64)),
             Value::List(l) => Ok(Value::Integer(l.len() as i64)),
             Value::Map(m) => Ok(Value::Integer(m.len() as i64)),
+            Value::Set(s) => Ok(Value::Integer(s.len() as i64)),
             _ => Err(EvalError::Eval(format!(
-                "len function requires string, list, or map, but got {:?}",
+                "len function requires string, list, map, or set, but got {:?}",
                 args[0]
             ))),
         }
     }
 
+    /// 集合は値として扱い、insert や remove は新しい集合を返す
+    fn eval_set_function(&self, function: &str, args: &[Value]) -> EvalResult<Value> {
+        let set = match args.first() {
+            Some(Value::Set(set)) => set,
+            _ => {
+                return Err(EvalError::Eval(format!(
+                    "{} function requires a set as its first argument",
+                    function
+                )));
+            }
+        };
+        match (function, &args[1..]) {
+            ("to_list", []) => Ok(Value::List(set.iter().cloned().map(Value::from).collect())),
+            ("insert", [element]) => {
+                let mut set = set.clone();
+                set.insert(SetElement::try_from(element)?);
+                Ok(Value::Set(set))
+            }
+            ("remove", [element]) => {
+                let mut set = set.clone();
+                set.remove(&SetElement::try_from(element)?);
+                Ok(Value::Set(set))
+            }
+            ("contains", [element]) => Ok(Value::Boolean(
+                SetElement::try_from(element).is_ok_and(|element| set.contains(&element)),
+            )),
+            ("union", [Value::Set(other)]) => Ok(Value::Set(set.union(other))),
+            ("intersect", [Value::Set(other)]) => Ok(Value::Set(set.intersect(other))),
+            _ => Err(EvalError::Eval(format!(
+                "Invalid arguments for {} function: {:?}",
+                function, args
+            ))),
+        }
+    }
+
     fn eval_sum_function(&self, args: &[Value]) -> EvalResult<Value> {
         if args.len() != 1 {
             return Err(EvalError::Eval(
@@ -717,6 +1193,10 @@ impl ExpressionEvaluator {
             (Value::Integer(l), Value::Float(r)) => Ok(Value::Float(*l as f64 + r)),
             (Value::Float(l), Value::Integer(r)) => Ok(Value::Float(l + *r as f64)),
             (Value::String(l), Value::String(r)) => Ok(Value::String(l.clone() + r)),
+            // シークレットを含む連結はシークレットのまま
+            (Value::Secret(l), Value::String(r)) => Ok(Value::Secret(l.concat(r))),
+            (Value::String(l), Value::Secret(r)) => Ok(Value::Secret(r.prepend(l))),
+            (Value::Secret(l), Value::Secret(r)) => Ok(Value::Secret(l.concat(r.expose()))),
             _ => Err(EvalError::Eval(format!("{:?} + {:?}", left, right))),
         }
     }
@@ -756,6 +1236,21 @@ impl ExpressionEvaluator {
         }
     }
 
+    fn eval_modulo(&self, left: &Value, right: &Value) -> EvalResult<Value> {
+        match (left, right) {
+            (Value::Integer(l), Value::Integer(r)) => {
+                if *r == 0 {
+                    return Err(EvalError::Eval("division by zero".to_string()));
+                }
+                Ok(Value::Integer(l.wrapping_rem(*r)))
+            }
+            (Value::Float(l), Value::Float(r)) => Ok(Value::Float(l % r)),
+            (Value::Integer(l), Value::Float(r)) => Ok(Value::Float(*l as f64 % r)),
+            (Value::Float(l), Value::Integer(r)) => Ok(Value::Float(l % *r as f64)),
+            _ => Err(EvalError::Eval(format!("{:?} % {:?}", left, right))),
+        }
+    }
+
     fn eval_equal(&self, left: &Value, right: &Value) -> EvalResult<Value> {
         Ok(Value::Boolean(left == right))
     }
@@ -817,12 +1312,31 @@ impl ExpressionEvaluator {
     }
 }
 
+/// A uniform value in `[0, 1)` determined by `seed`, for `choose_seeded`
+fn seeded_random(seed: &Value) -> f64 {
+    // 実行をまたいで同じ値になるよう、std のハッシュではなく FNV-1a を使う
+    let hash = seed
+        .to_string()
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    StdRng::seed_from_u64(hash).gen_range(0.0..1.0)
+}
+
 impl PluginConfig {
     fn new(name: &str, map: HashMap<String, ast::Literal>) -> Self {
         match name.parse::<PluginConfig>() {
             Ok(PluginConfig::Memory(_)) => PluginConfig::Memory(MemoryConfig::from(map)),
             Ok(PluginConfig::Search(_)) => PluginConfig::Search(SearchConfig::from(map)),
             Ok(PluginConfig::Rag(_)) => PluginConfig::Rag(RagConfig::from(map)),
+            Ok(PluginConfig::Catalog(_)) => PluginConfig::Catalog(CatalogConfig::from(map)),
+            Ok(PluginConfig::OpenApiTools(_)) => {
+                PluginConfig::OpenApiTools(OpenApiToolsConfig::from(map))
+            }
+            Ok(PluginConfig::SistenceMemory(_)) => {
+                PluginConfig::SistenceMemory(SistenceContextConfig::from(map))
+            }
             _ => {
                 let mut hash_map = HashMap::new();
                 for (key, value) in map {
@@ -879,6 +1393,50 @@ impl From<HashMap<String, ast::Literal>> for MemoryConfig {
     }
 }
 
+impl From<HashMap<String, ast::Literal>> for CatalogConfig {
+    fn from(map: HashMap<String, ast::Literal>) -> Self {
+        let mut config = CatalogConfig::default();
+        if let Some(ast::Literal::Integer(i)) = map.get("max_agents") {
+            config.max_agents = *i as usize;
+        }
+        if let Some(ast::Literal::Integer(i)) = map.get("max_chars") {
+            config.max_chars = *i as usize;
+        }
+        config
+    }
+}
+
+impl From<HashMap<String, ast::Literal>> for SistenceContextConfig {
+    fn from(map: HashMap<String, ast::Literal>) -> Self {
+        let mut config = SistenceContextConfig::default();
+        if let Some(ast::Literal::Integer(i)) = map.get("limit") {
+            config.limit = *i as usize;
+        }
+        if let Some(ast::Literal::Boolean(b)) = map.get("record_context") {
+            config.record_context = *b;
+        }
+        config
+    }
+}
+
+// think からはツールの絞り込みだけを指定できる。仕様の読み込みはプロバイダーの設定で行う
+impl From<HashMap<String, ast::Literal>> for OpenApiToolsConfig {
+    fn from(map: HashMap<String, ast::Literal>) -> Self {
+        let mut config = OpenApiToolsConfig::default();
+        if let Some(ast::Literal::List(l)) = map.get("allowed_tools") {
+            config.allowed_tools = Some(
+                l.iter()
+                    .filter_map(|v| match v {
+                        ast::Literal::String(s) => Some(s.clone()),
+                        _ => None,
+                    })
+                    .collect(),
+            );
+        }
+        config
+    }
+}
+
 #[allow(clippy::assigning_clones)]
 impl From<HashMap<String, ast::Literal>> for RagConfig {
     fn from(map: HashMap<String, ast::Literal>) -> Self {
@@ -900,7 +1458,9 @@ impl From<HashMap<String, ast::Literal>> for RagConfig {
 impl From<ast::Literal> for serde_json::Value {
     fn from(value: ast::Literal) -> serde_json::Value {
         match value {
-            ast::Literal::String(s) => serde_json::Value::String(s),
+            ast::Literal::String(s) | ast::Literal::RawString { value: s, .. } => {
+                serde_json::Value::String(s)
+            }
             ast::Literal::Integer(i) => serde_json::Value::Number(i.into()),
             ast::Literal::Float(f) => serde_json::Value::Number(
                 serde_json::Number::from_f64(f).unwrap_or(serde_json::Number::from(0)),
@@ -954,6 +1514,670 @@ mod tests {
         ))
     }
 
+    #[tokio::test]
+    async fn test_think_rejected_over_budget_until_window_resets() {
+        use crate::{
+            config::{LlmBudgetConfig, ProviderConfig},
+            eval::budget::LlmBudget,
+            provider::{
+                capabilities::common::Capabilities,
+                llm::{LLMResponse, MockProviderLLM},
+                providers::standard::StandardProvider,
+            },
+        };
+
+        let mut llm = MockProviderLLM::new();
+        llm.expect_name().return_const("mock".to_string());
+        llm.expect_capabilities().returning(Capabilities::default);
+        llm.expect_send_message().times(3).returning(|_, _| {
+            Box::pin(async {
+                Ok(LLMResponse {
+                    content: "ok".to_string(),
+                    ..Default::default()
+                })
+            })
+        });
+        let context = Arc::new(
+            ExecutionContext::new(
+                Arc::new(EventBus::new(16)),
+                AgentInfo {
+                    agent_name: "frugal".to_string(),
+                    ..Default::default()
+                },
+                StateAccessMode::ReadWrite,
+                ContextConfig::default(),
+                Arc::new(ProviderInstance {
+                    config: ProviderConfig::default(),
+                    provider: Arc::new(StandardProvider::new(llm, vec![])),
+                    secret: Default::default(),
+                }),
+                Arc::new(DashMap::new()),
+                vec![],
+            )
+            .with_llm_budget(Some(LlmBudget::new(LlmBudgetConfig {
+                max_calls: 2,
+                window: Duration::from_millis(200),
+            }))),
+        );
+        let evaluator = ExpressionEvaluator::new();
+        let think = Expression::Think {
+            args: vec![Argument::Positional(Expression::Literal(Literal::String(
+                "hello".to_string(),
+            )))],
+            with_block: None,
+        };
+
+        for _ in 0..2 {
+            evaluator
+                .eval_expression(&think, context.clone())
+                .await
+                .unwrap();
+        }
+        let result = evaluator.eval_expression(&think, context.clone()).await;
+        assert!(
+            matches!(&result, Err(EvalError::BudgetExceeded { agent_name, max_calls: 2, .. }) if agent_name == "frugal"),
+            "{:?}",
+            result
+        );
+        assert_eq!(context.llm_budget_stats().unwrap().remaining, 0);
+
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        evaluator
+            .eval_expression(&think, context.clone())
+            .await
+            .unwrap();
+        assert_eq!(context.llm_budget_stats().unwrap().remaining, 1);
+    }
+
+    fn secret_call(name: &str) -> Expression {
+        Expression::FunctionCall {
+            function: "secret".to_string(),
+            arguments: vec![Expression::Literal(Literal::String(name.to_string()))],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_secret_requires_grant_and_stays_masked() {
+        use crate::eval::secret::{SECRET_MASK, SecretVault};
+
+        let secrets = HashMap::from([
+            ("api_key".to_string(), "s3cr3t".to_string()),
+            ("db_password".to_string(), "hunter2".to_string()),
+        ]);
+        let context = Arc::new(
+            ExecutionContext::new(
+                Arc::new(EventBus::new(16)),
+                AgentInfo {
+                    agent_name: "notifier".to_string(),
+                    ..Default::default()
+                },
+                StateAccessMode::ReadWrite,
+                ContextConfig::default(),
+                Arc::new(ProviderInstance::default()),
+                Arc::new(DashMap::new()),
+                vec![],
+            )
+            .with_secrets(SecretVault::granted(&secrets, &["api_key".to_string()])),
+        );
+        let evaluator = ExpressionEvaluator::new();
+
+        let result = evaluator
+            .eval_expression(&secret_call("db_password"), context.clone())
+            .await;
+        assert!(
+            matches!(&result, Err(EvalError::SecretNotGranted { agent_name, name }) if agent_name == "notifier" && name == "db_password"),
+            "{:?}",
+            result
+        );
+
+        // 連結してもシークレットのまま。ヘッダーなどの用途では値を取り出せる
+        let header = Expression::BinaryOp {
+            op: BinaryOperator::Add,
+            left: Box::new(Expression::Literal(Literal::String("Bearer ".to_string()))),
+            right: Box::new(secret_call("api_key")),
+        };
+        let value = evaluator
+            .eval_expression(&header, context.clone())
+            .await
+            .unwrap();
+        match &value {
+            Value::Secret(secret) => assert_eq!(secret.expose(), "Bearer s3cr3t"),
+            other => panic!("Expected Secret, got {:?}", other),
+        }
+
+        // 表示、ログ、シリアライズ、イベントではマスクされる
+        assert_eq!(value.to_string(), SECRET_MASK);
+        assert!(!format!("{:?}", value).contains("s3cr3t"));
+        assert!(!serde_json::to_string(&value).unwrap().contains("s3cr3t"));
+        assert_eq!(
+            event_bus::Value::from(value.clone()),
+            event_bus::Value::String(SECRET_MASK.to_string())
+        );
+
+        context.set_variable("key", value).await.unwrap();
+        let prompt = evaluator
+            .interpolate_template("Authorize with ${key}", &context)
+            .await
+            .unwrap();
+        assert_eq!(prompt, "Authorize with ****");
+    }
+
+    #[tokio::test]
+    async fn test_secret_masked_in_think_prompt() {
+        use crate::{
+            eval::secret::SecretVault,
+            provider::{
+                capabilities::common::Capabilities,
+                llm::{LLMResponse, MockProviderLLM},
+                providers::standard::StandardProvider,
+            },
+        };
+
+        let mut llm = MockProviderLLM::new();
+        llm.expect_name().return_const("mock".to_string());
+        llm.expect_capabilities().returning(Capabilities::default);
+        llm.expect_send_message()
+            .withf(|prompt, _| prompt.contains("****") && !prompt.contains("s3cr3t"))
+            .times(1)
+            .returning(|_, _| {
+                Box::pin(async {
+                    Ok(LLMResponse {
+                        content: "ok".to_string(),
+                        ..Default::default()
+                    })
+                })
+            });
+        let secrets = HashMap::from([("api_key".to_string(), "s3cr3t".to_string())]);
+        let context = Arc::new(
+            ExecutionContext::new(
+                Arc::new(EventBus::new(16)),
+                AgentInfo::default(),
+                StateAccessMode::ReadWrite,
+                ContextConfig::default(),
+                Arc::new(ProviderInstance {
+                    config: Default::default(),
+                    provider: Arc::new(StandardProvider::new(llm, vec![])),
+                    secret: Default::default(),
+                }),
+                Arc::new(DashMap::new()),
+                vec![],
+            )
+            .with_secrets(SecretVault::granted(&secrets, &["api_key".to_string()])),
+        );
+        let evaluator = ExpressionEvaluator::new();
+        let key = evaluator
+            .eval_expression(&secret_call("api_key"), context.clone())
+            .await
+            .unwrap();
+        context.set_variable("key", key).await.unwrap();
+
+        let think = Expression::Think {
+            args: vec![
+                Argument::Positional(Expression::Literal(Literal::String(
+                    "Call the API with ${key}".to_string(),
+                ))),
+                Argument::Named {
+                    name: "token".to_string(),
+                    value: Expression::Variable("key".to_string()),
+                },
+            ],
+            with_block: None,
+        };
+        evaluator.eval_expression(&think, context).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_think_sampling_overrides_reach_provider() {
+        use crate::provider::{
+            capabilities::common::Capabilities,
+            llm::{LLMResponse, MockProviderLLM},
+            providers::standard::StandardProvider,
+        };
+
+        let mut llm = MockProviderLLM::new();
+        llm.expect_name().return_const("mock".to_string());
+        llm.expect_capabilities().returning(Capabilities::default);
+        // think ブロックの値がプロバイダー既定値より優先される
+        llm.expect_send_message()
+            .withf(|_, config| {
+                config.common_config.temperature == 1.5
+                    && config.common_config.top_p == Some(0.9)
+                    && config.common_config.max_tokens == 64
+            })
+            .times(1)
+            .returning(|_, _| {
+                Box::pin(async {
+                    Ok(LLMResponse {
+                        content: "ok".to_string(),
+                        ..Default::default()
+                    })
+                })
+            });
+        let context = Arc::new(ExecutionContext::new(
+            Arc::new(EventBus::new(16)),
+            AgentInfo::default(),
+            StateAccessMode::ReadWrite,
+            ContextConfig::default(),
+            Arc::new(ProviderInstance {
+                config: Default::default(),
+                provider: Arc::new(StandardProvider::new(llm, vec![])),
+                secret: Default::default(),
+            }),
+            Arc::new(DashMap::new()),
+            vec![],
+        ));
+
+        let think = Expression::Think {
+            args: vec![Argument::Positional(Expression::Literal(Literal::String(
+                "Write a poem".to_string(),
+            )))],
+            with_block: Some(ThinkAttributes {
+                temperature: Some(1.5),
+                top_p: Some(0.9),
+                max_tokens: Some(64),
+                ..Default::default()
+            }),
+        };
+        ExpressionEvaluator::new()
+            .eval_expression(&think, context)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_runtime_settings_reach_next_think() {
+        use crate::agent_settings::{AgentSettingsPatch, RuntimeSettings};
+        use crate::provider::{
+            capabilities::common::Capabilities,
+            llm::{LLMResponse, MockProviderLLM},
+            providers::standard::StandardProvider,
+        };
+
+        let mut llm = MockProviderLLM::new();
+        llm.expect_name().return_const("mock".to_string());
+        llm.expect_capabilities().returning(Capabilities::default);
+        llm.expect_send_message()
+            .withf(|_, config| {
+                config.common_config.temperature == 0.3 && config.common_config.max_tokens == 64
+            })
+            .times(1)
+            .returning(|_, _| {
+                Box::pin(async {
+                    Ok(LLMResponse {
+                        content: "ok".to_string(),
+                        ..Default::default()
+                    })
+                })
+            });
+        let settings = Arc::new(RuntimeSettings::default());
+        let context = Arc::new(
+            ExecutionContext::new(
+                Arc::new(EventBus::new(16)),
+                AgentInfo::default(),
+                StateAccessMode::ReadWrite,
+                ContextConfig::default(),
+                Arc::new(ProviderInstance {
+                    config: Default::default(),
+                    provider: Arc::new(StandardProvider::new(llm, vec![])),
+                    secret: Default::default(),
+                }),
+                Arc::new(DashMap::new()),
+                vec![],
+            )
+            .with_runtime_settings(settings.clone()),
+        );
+
+        // エージェントの作成後に変えた値も次の think から使われる
+        let patch: AgentSettingsPatch =
+            serde_json::from_str(r#"{"temperature": 0.3, "max_tokens": 64}"#).unwrap();
+        settings
+            .update(&patch, |settings| settings.check(|_| false))
+            .unwrap();
+
+        let think = Expression::Think {
+            args: vec![Argument::Positional(Expression::Literal(Literal::String(
+                "Write a poem".to_string(),
+            )))],
+            with_block: None,
+        };
+        ExpressionEvaluator::new()
+            .eval_expression(&think, context)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_catalog_builtins() {
+        use crate::{
+            ast::{AnswerDef, HandlerBlock, MicroAgentDef, Parameter, RequestHandler, TypeInfo},
+            catalog::AgentCatalog,
+        };
+
+        let catalog = AgentCatalog::default();
+        catalog.insert(
+            "Weather",
+            "Weather",
+            &MicroAgentDef {
+                answer: Some(AnswerDef {
+                    handlers: vec![RequestHandler {
+                        request_type: "GetForecast".into(),
+                        parameters: vec![Parameter {
+                            name: "city".to_string(),
+                            type_info: TypeInfo::Simple("String".to_string()),
+                            constraint: None,
+                        }],
+                        return_type: "String".into(),
+                        constraints: None,
+                        requires: None,
+                        block: HandlerBlock { statements: vec![] },
+                        doc: None,
+                        cache: None,
+                    }],
+                }),
+                ..Default::default()
+            },
+        );
+        let context = Arc::new(
+            ExecutionContext::new(
+                Arc::new(EventBus::new(16)),
+                AgentInfo::default(),
+                StateAccessMode::ReadWrite,
+                ContextConfig::default(),
+                Arc::new(ProviderInstance::default()),
+                Arc::new(DashMap::new()),
+                vec![],
+            )
+            .with_catalog(Arc::new(catalog)),
+        );
+        let evaluator = ExpressionEvaluator::new();
+        let call = |function: &str, arguments: Vec<Expression>| Expression::FunctionCall {
+            function: function.to_string(),
+            arguments,
+        };
+
+        let agents = evaluator
+            .eval_expression(&call("list_agents", vec![]), context.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            agents,
+            Value::List(vec![Value::String("Weather".to_string())])
+        );
+
+        let weather = Expression::Literal(Literal::String("Weather".to_string()));
+        let requests = evaluator
+            .eval_expression(&call("agent_requests", vec![weather]), context.clone())
+            .await
+            .unwrap();
+        let Value::List(requests) = requests else {
+            panic!("Expected List, got {:?}", requests);
+        };
+        let Value::Map(descriptor) = &requests[0] else {
+            panic!("Expected Map, got {:?}", requests[0]);
+        };
+        assert_eq!(descriptor["name"], Value::String("GetForecast".to_string()));
+
+        let unknown = Expression::Literal(Literal::String("Unknown".to_string()));
+        assert_eq!(
+            evaluator
+                .eval_expression(&call("agent_requests", vec![unknown]), context)
+                .await
+                .unwrap(),
+            Value::List(vec![])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_choose_builtins_are_reproducible() {
+        let seeded_context = |seed: u64| {
+            let context = ExecutionContext::new(
+                Arc::new(EventBus::new(16)),
+                AgentInfo::default(),
+                StateAccessMode::ReadWrite,
+                ContextConfig::default(),
+                Arc::new(ProviderInstance::default()),
+                Arc::new(DashMap::new()),
+                vec![],
+            );
+            Arc::new(context.with_rng_seed(seed))
+        };
+        let list = |items: Vec<Literal>| Expression::Literal(Literal::List(items));
+        let options = list(
+            ["red", "green", "blue"]
+                .map(|s| Literal::String(s.to_string()))
+                .to_vec(),
+        );
+        // green は重みが 0 なので選ばれない
+        let weights = list(vec![
+            Literal::Integer(1),
+            Literal::Integer(0),
+            Literal::Float(2.5),
+        ]);
+        let choose_weighted = Expression::FunctionCall {
+            function: "choose_weighted".to_string(),
+            arguments: vec![options.clone(), weights.clone()],
+        };
+        let evaluator = ExpressionEvaluator::new();
+
+        let mut runs = Vec::new();
+        for _ in 0..2 {
+            let context = seeded_context(42);
+            let mut picks = Vec::new();
+            for _ in 0..20 {
+                picks.push(
+                    evaluator
+                        .eval_expression(&choose_weighted, context.clone())
+                        .await
+                        .unwrap(),
+                );
+            }
+            runs.push(picks);
+        }
+        assert_eq!(runs[0], runs[1]);
+        assert!(!runs[0].contains(&Value::String("green".to_string())));
+        assert!(runs[0].contains(&Value::String("red".to_string())));
+        assert!(runs[0].contains(&Value::String("blue".to_string())));
+
+        // シードが同じなら、コンテキストの乱数によらず同じ選択肢
+        let choose_seeded = |seed: &str| Expression::FunctionCall {
+            function: "choose_seeded".to_string(),
+            arguments: vec![
+                options.clone(),
+                weights.clone(),
+                Expression::Literal(Literal::String(seed.to_string())),
+            ],
+        };
+        let first = evaluator
+            .eval_expression(&choose_seeded("user-7"), seeded_context(1))
+            .await
+            .unwrap();
+        let second = evaluator
+            .eval_expression(&choose_seeded("user-7"), seeded_context(2))
+            .await
+            .unwrap();
+        assert_eq!(first, second);
+        assert_ne!(first, Value::String("green".to_string()));
+
+        let mismatched = Expression::FunctionCall {
+            function: "choose_weighted".to_string(),
+            arguments: vec![options.clone(), list(vec![Literal::Integer(1)])],
+        };
+        assert!(matches!(
+            evaluator.eval_expression(&mismatched, seeded_context(42)).await,
+            Err(EvalError::Eval(message)) if message.contains("as many weights as options")
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_shared_counter_builtins() {
+        use crate::provider::plugins::memory::shared_counters::SharedCounters;
+
+        // 2 つのエージェントが同じカウンターを使う
+        let counters = Arc::new(SharedCounters::default());
+        let agent_context = || {
+            Arc::new(
+                ExecutionContext::new(
+                    Arc::new(EventBus::new(16)),
+                    AgentInfo::default(),
+                    StateAccessMode::ReadWrite,
+                    ContextConfig::default(),
+                    Arc::new(ProviderInstance::default()),
+                    Arc::new(DashMap::new()),
+                    vec![],
+                )
+                .with_shared_counters(counters.clone()),
+            )
+        };
+        let call = |function: &str, arguments: Vec<Literal>| Expression::FunctionCall {
+            function: function.to_string(),
+            arguments: arguments.into_iter().map(Expression::Literal).collect(),
+        };
+        let key = || Literal::String("seats".to_string());
+        let evaluator = ExpressionEvaluator::new();
+
+        let increment = call("increment", vec![key(), Literal::Integer(2)]);
+        assert_eq!(
+            evaluator
+                .eval_expression(&increment, agent_context())
+                .await
+                .unwrap(),
+            Value::Integer(2)
+        );
+        let decrement = call("decrement_if_positive", vec![key()]);
+        let other = agent_context();
+        for expected in [true, true, false] {
+            assert_eq!(
+                evaluator
+                    .eval_expression(&decrement, other.clone())
+                    .await
+                    .unwrap(),
+                Value::Boolean(expected)
+            );
+        }
+
+        let acquire = call(
+            "try_acquire",
+            vec![
+                Literal::String("api".to_string()),
+                Literal::Integer(1),
+                Literal::Duration(Duration::from_secs(60)),
+            ],
+        );
+        assert_eq!(
+            evaluator
+                .eval_expression(&acquire, agent_context())
+                .await
+                .unwrap(),
+            Value::Boolean(true)
+        );
+        assert_eq!(
+            evaluator
+                .eval_expression(&acquire, agent_context())
+                .await
+                .unwrap(),
+            Value::Boolean(false)
+        );
+
+        let negative_rate = call(
+            "try_acquire",
+            vec![
+                key(),
+                Literal::Integer(-1),
+                Literal::Duration(Duration::ZERO),
+            ],
+        );
+        assert!(matches!(
+            evaluator.eval_expression(&negative_rate, agent_context()).await,
+            Err(EvalError::InvalidParameter { name, .. }) if name == "rate"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_call_tool_builtin() {
+        use crate::{
+            config::{OpenApiSpecConfig, OpenApiToolsConfig},
+            provider::{
+                plugins::openapi_tools::{OpenApiToolProvider, ToolError, ToolRegistry},
+                provider::ProviderSecret,
+            },
+        };
+
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/pets")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "limit".to_string(),
+                "1".to_string(),
+            ))
+            .with_body(r#"[{"id": 1, "name": "Tom"}]"#)
+            .create_async()
+            .await;
+        let config = OpenApiToolsConfig {
+            specs: vec![OpenApiSpecConfig {
+                location: concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/tests/fixtures/openapi/petstore.json"
+                )
+                .to_string(),
+                base_url: Some(server.url()),
+                auth: None,
+            }],
+            ..Default::default()
+        };
+        let tools = ToolRegistry::default();
+        tools.register(Arc::new(
+            OpenApiToolProvider::load(&config, &ProviderSecret::default())
+                .await
+                .unwrap(),
+        ));
+        let context = Arc::new(
+            ExecutionContext::new(
+                Arc::new(EventBus::new(16)),
+                AgentInfo::default(),
+                StateAccessMode::ReadWrite,
+                ContextConfig::default(),
+                Arc::new(ProviderInstance::default()),
+                Arc::new(DashMap::new()),
+                vec![],
+            )
+            .with_tools(Arc::new(tools)),
+        );
+        let evaluator = ExpressionEvaluator::new();
+        let call_tool = |name: &str, arguments: Literal| Expression::FunctionCall {
+            function: "call_tool".to_string(),
+            arguments: vec![
+                Expression::Literal(Literal::String(name.to_string())),
+                Expression::Literal(arguments),
+            ],
+        };
+
+        let pets = evaluator
+            .eval_expression(
+                &call_tool(
+                    "listPets",
+                    Literal::Map(HashMap::from([("limit".to_string(), Literal::Integer(1))])),
+                ),
+                context.clone(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            pets,
+            Value::List(vec![Value::Map(HashMap::from([
+                ("id".to_string(), Value::Integer(1)),
+                ("name".to_string(), Value::String("Tom".to_string())),
+            ]))])
+        );
+
+        let result = evaluator
+            .eval_expression(&call_tool("adoptPet", Literal::Null), context)
+            .await;
+        assert!(matches!(
+            result,
+            Err(EvalError::Tool(ToolError::UnknownTool(_)))
+        ));
+    }
+
     #[tokio::test]
     async fn test_literal_evaluation() {
         let evaluator = ExpressionEvaluator::new();
@@ -1129,6 +2353,22 @@ mod tests {
         };
         let result = evaluator.eval_expression(&expr, context.clone()).await;
         assert!(result.is_err());
+
+        // Remainder
+        let remainder = |right: i64| Expression::BinaryOp {
+            op: BinaryOperator::Modulo,
+            left: Box::new(Expression::Literal(Literal::Integer(-7))),
+            right: Box::new(Expression::Literal(Literal::Integer(right))),
+        };
+        let result = evaluator
+            .eval_expression(&remainder(3), context.clone())
+            .await
+            .unwrap();
+        assert!(matches!(result, Value::Integer(-1)));
+        let result = evaluator
+            .eval_expression(&remainder(0), context.clone())
+            .await;
+        assert!(result.is_err());
     }
 
     #[tokio::test]
@@ -1187,6 +2427,108 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_set_functions() {
+        let evaluator = ExpressionEvaluator::new();
+        let context = setup_context().await;
+        let set = |items: Vec<Literal>| Expression::Literal(Literal::Set(items));
+        let call = |function: &str, arguments: Vec<Expression>| Expression::FunctionCall {
+            function: function.to_string(),
+            arguments,
+        };
+
+        // 重複は取り除かれ、挿入順に関係なく昇順に並ぶ
+        let tags = set(vec![
+            Literal::String("b".to_string()),
+            Literal::String("a".to_string()),
+            Literal::String("b".to_string()),
+        ]);
+        let inserted = call(
+            "insert",
+            vec![
+                tags.clone(),
+                Expression::Literal(Literal::String("c".to_string())),
+            ],
+        );
+        let result = evaluator
+            .eval_expression(&call("to_list", vec![inserted.clone()]), context.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            result,
+            Value::List(vec![
+                Value::String("a".to_string()),
+                Value::String("b".to_string()),
+                Value::String("c".to_string()),
+            ])
+        );
+
+        let result = evaluator
+            .eval_expression(&call("len", vec![inserted.clone()]), context.clone())
+            .await
+            .unwrap();
+        assert_eq!(result, Value::Integer(3));
+
+        let removed = call(
+            "remove",
+            vec![
+                inserted,
+                Expression::Literal(Literal::String("a".to_string())),
+            ],
+        );
+        let result = evaluator
+            .eval_expression(
+                &call(
+                    "contains",
+                    vec![
+                        removed,
+                        Expression::Literal(Literal::String("a".to_string())),
+                    ],
+                ),
+                context.clone(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result, Value::Boolean(false));
+
+        let left = set(vec![Literal::Integer(3), Literal::Integer(1)]);
+        let right = set(vec![Literal::Integer(2), Literal::Integer(3)]);
+        let union = evaluator
+            .eval_expression(
+                &call("union", vec![left.clone(), right.clone()]),
+                context.clone(),
+            )
+            .await
+            .unwrap();
+        let expected = evaluator
+            .eval_expression(
+                &set(vec![
+                    Literal::Integer(1),
+                    Literal::Integer(2),
+                    Literal::Integer(3),
+                ]),
+                context.clone(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(union, expected);
+
+        let result = evaluator
+            .eval_expression(
+                &call("to_list", vec![call("intersect", vec![left, right])]),
+                context.clone(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result, Value::List(vec![Value::Integer(3)]));
+
+        // 浮動小数点数は要素にできない
+        let result = evaluator
+            .eval_expression(&set(vec![Literal::Float(1.5)]), context.clone())
+            .await;
+        assert!(result.is_err());
+    }
+
     #[tokio::test]
     async fn test_comparison_operations() {
         let evaluator = ExpressionEvaluator::new();
@@ -1636,6 +2978,17 @@ mod tests {
             _ => panic!("Expected Rag config"),
         }
 
+        let tools_map = HashMap::from([(
+            "allowed_tools".to_string(),
+            ast::Literal::List(vec![ast::Literal::String("listPets".to_string())]),
+        )]);
+        match PluginConfig::new("openapi_tools", tools_map) {
+            PluginConfig::OpenApiTools(config) => {
+                assert_eq!(config.allowed_tools, Some(vec!["listPets".to_string()]))
+            }
+            _ => panic!("Expected OpenApiTools config"),
+        }
+
         // 異常系: 無効なキー
         let map = HashMap::new();
         match PluginConfig::new("invalid", map) {
@@ -0,0 +1,207 @@
+//! Resource limits of the evaluator.
+//!
+//! [`EvalUsage`] checks one handler execution against the agent's [`EvalLimits`]:
+//! how deeply the expressions and statements being evaluated are nested, the size
+//! of each value an expression creates, and an estimate of the bytes of all the
+//! values created so far. A DSL that doubles a string without end, or nests
+//! expressions deeper than the stack allows, fails with
+//! [`EvalError::LimitExceeded`] instead of taking the process down.
+//!
+//! The checks are comparisons of atomic counters, so they run on every expression.
+//! Sizes are shallow: a string counts its bytes, a list, map, set or tuple its
+//! elements, and the elements are counted when the expressions creating them are.
+
+use std::{
+    fmt,
+    mem::size_of,
+    sync::{
+        Arc,
+        atomic::{AtomicU32, AtomicU64, Ordering},
+    },
+};
+
+use super::{
+    evaluator::{EvalError, EvalResult},
+    expression::Value,
+};
+use crate::config::EvalLimits;
+
+/// A limit of [`EvalLimits`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvalLimit {
+    Depth,
+    ValueSize,
+    Allocation,
+}
+
+impl fmt::Display for EvalLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalLimit::Depth => write!(f, "max_depth"),
+            EvalLimit::ValueSize => write!(f, "max_value_size"),
+            EvalLimit::Allocation => write!(f, "max_allocation_bytes"),
+        }
+    }
+}
+
+/// Depth and allocation of one handler execution, shared by the contexts forked
+/// from it
+#[derive(Debug)]
+pub struct EvalUsage {
+    agent_name: String,
+    limits: EvalLimits,
+    depth: AtomicU32,
+    allocated: AtomicU64,
+}
+
+/// An expression or statement being evaluated; leaving it when dropped
+#[derive(Debug)]
+pub struct EvalFrame {
+    usage: Arc<EvalUsage>,
+}
+
+impl EvalUsage {
+    pub fn new(agent_name: &str, limits: EvalLimits) -> Self {
+        Self {
+            agent_name: agent_name.to_string(),
+            limits,
+            depth: AtomicU32::new(0),
+            allocated: AtomicU64::new(0),
+        }
+    }
+
+    pub fn limits(&self) -> &EvalLimits {
+        &self.limits
+    }
+
+    /// Enters an expression or statement nested in the current ones
+    pub fn enter(self: &Arc<Self>) -> EvalResult<EvalFrame> {
+        let depth = self.depth.fetch_add(1, Ordering::Relaxed) + 1;
+        // 失敗してもフレームを返して、抜けるときに数え戻す
+        let frame = EvalFrame {
+            usage: self.clone(),
+        };
+        if depth > self.limits.max_depth {
+            return Err(self.exceeded(
+                EvalLimit::Depth,
+                self.limits.max_depth.into(),
+                depth.into(),
+            ));
+        }
+        Ok(frame)
+    }
+
+    /// Checks a value an expression created and adds it to the allocation
+    pub fn track(&self, value: &Value) -> EvalResult<()> {
+        let (size, bytes) = shallow_size(value);
+        if size > self.limits.max_value_size {
+            return Err(self.exceeded(EvalLimit::ValueSize, self.limits.max_value_size, size));
+        }
+        if bytes == 0 {
+            return Ok(());
+        }
+        let allocated = self.allocated.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        if allocated > self.limits.max_allocation_bytes {
+            // onFail が応答を組み立てられるように、超過したら数え直す
+            self.allocated.store(0, Ordering::Relaxed);
+            return Err(self.exceeded(
+                EvalLimit::Allocation,
+                self.limits.max_allocation_bytes,
+                allocated,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Estimated bytes of the values created so far
+    pub fn allocated(&self) -> u64 {
+        self.allocated.load(Ordering::Relaxed)
+    }
+
+    fn exceeded(&self, limit: EvalLimit, max: u64, actual: u64) -> EvalError {
+        tracing::warn!(
+            "Agent {} hit {} = {} ({})",
+            self.agent_name,
+            limit,
+            max,
+            actual
+        );
+        EvalError::LimitExceeded {
+            agent_name: self.agent_name.clone(),
+            limit,
+            max,
+            actual,
+        }
+    }
+}
+
+impl Drop for EvalFrame {
+    fn drop(&mut self) {
+        self.usage.depth.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Size counted against `max_value_size` and estimated bytes of `value` itself
+fn shallow_size(value: &Value) -> (u64, u64) {
+    let (size, element_bytes) = match value {
+        Value::String(s) => (s.len(), 1),
+        Value::List(values) | Value::Tuple(values) => (values.len(), size_of::<Value>()),
+        Value::Map(map) => (map.len(), size_of::<String>() + size_of::<Value>()),
+        Value::Set(set) => (set.len(), size_of::<Value>()),
+        _ => (0, 0),
+    };
+    (size as u64, (size * element_bytes) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(limits: EvalLimits) -> Arc<EvalUsage> {
+        Arc::new(EvalUsage::new("Sandbox", limits))
+    }
+
+    fn limit_of(result: EvalResult<impl fmt::Debug>) -> EvalLimit {
+        match result {
+            Err(EvalError::LimitExceeded { limit, .. }) => limit,
+            other => panic!("expected LimitExceeded, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_depth_counts_open_frames() {
+        let usage = usage(EvalLimits {
+            max_depth: 2,
+            ..Default::default()
+        });
+        let outer = usage.enter().unwrap();
+        let inner = usage.enter().unwrap();
+        assert_eq!(limit_of(usage.enter()), EvalLimit::Depth);
+        drop(inner);
+        // 抜けたフレームの分だけまた入れる
+        let _inner = usage.enter().unwrap();
+        drop(outer);
+    }
+
+    #[test]
+    fn test_value_size_and_allocation() {
+        let usage = usage(EvalLimits {
+            max_value_size: 8,
+            max_allocation_bytes: 12,
+            ..Default::default()
+        });
+        let string = |len: usize| Value::String("x".repeat(len));
+
+        usage.track(&Value::Integer(1)).unwrap();
+        usage.track(&string(8)).unwrap();
+        assert_eq!(limit_of(usage.track(&string(9))), EvalLimit::ValueSize);
+        assert_eq!(
+            limit_of(usage.track(&Value::List(vec![Value::Null; 9]))),
+            EvalLimit::ValueSize
+        );
+        assert_eq!(usage.allocated(), 8);
+
+        assert_eq!(limit_of(usage.track(&string(5))), EvalLimit::Allocation);
+        assert_eq!(usage.allocated(), 0);
+    }
+}
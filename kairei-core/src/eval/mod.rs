@@ -24,6 +24,28 @@
 //! ## Generator
 //! Handles prompt generation for LLM integration.
 //!
+//! ## Limits
+//! Depth, value size and allocation caps on a single handler execution.
+//!
+//! ## Post-processing
+//! Ordered transforms applied to the value of an answer before it is sent.
+//!
+//! ## Profiling
+//! Timing breakdown of a single request, collected when the request asks for it.
+//!
+//! ## Recording
+//! Statement-by-statement recordings of observe and react handler runs, stepped
+//! through again by debug sessions.
+//!
+//! ## Secrets
+//! Secret values granted to agents, masked wherever they are printed.
+//!
+//! ## Sets
+//! Set values, with a deterministic element order.
+//!
+//! ## Templates
+//! Locale-dependent rendering of the values interpolated into prompts.
+//!
 //! # Evaluation Pipeline
 //!
 //! 1. AST nodes from the parser are passed to the Evaluator
@@ -38,8 +60,16 @@
 //! - Runtime: Manages agent lifecycle and event processing
 //! - Provider System: Integrates with external services and LLMs
 
+pub mod budget;
 pub mod context;
 pub mod evaluator;
 pub mod expression;
 pub mod generator;
+pub mod limits;
+pub mod postprocess;
+pub mod profile;
+pub mod recording;
+pub mod secret;
+pub mod set;
 pub mod statement;
+pub mod template;
@@ -0,0 +1,390 @@
+//! # Answer Post-processing
+//!
+//! An answer handler can list transforms under `postprocess` in its `with` block.
+//! They are applied in order to the value the handler returns, before the response
+//! is sent:
+//!
+//! ```text
+//! on request Summarize(text: String) -> Result<String, Error> with {
+//!     postprocess: [
+//!         "strip_fences",
+//!         { json_field: "summary.text" },
+//!         { regex_replace: { pattern: r"\s+", replacement: " " } },
+//!         "trim"
+//!     ]
+//! } { ... }
+//! ```
+//!
+//! | transform                                 | effect                                        |
+//! |-------------------------------------------|-----------------------------------------------|
+//! | `"trim"`                                  | removes surrounding whitespace                |
+//! | `"strip_fences"`                          | removes a surrounding markdown code fence     |
+//! | `{ json_field: "a.b" }`                   | parses JSON and takes the field at the path   |
+//! | `{ regex_replace: { pattern, replacement } }` | replaces every match of the pattern       |
+//!
+//! The pipeline is built when the handler is registered, so an unknown transform or
+//! an invalid pattern fails the agent start. A transform that fails on a value, such
+//! as a missing JSON field, fails the request with the step that failed.
+
+use std::{collections::HashMap, fmt};
+
+use regex::Regex;
+use thiserror::Error;
+
+use super::expression::Value;
+use super::set::ValueSet;
+use crate::ast::{Constraints, Literal};
+
+#[derive(Debug, Error)]
+pub enum PostprocessError {
+    #[error("Invalid post-processing transform {0}: {1}")]
+    InvalidTransform(String, String),
+
+    #[error("Post-processing step {step} ({transform}) failed: {source}")]
+    StepFailed {
+        step: usize,
+        transform: String,
+        source: TransformError,
+    },
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum TransformError {
+    #[error("expected a string, found {0}")]
+    NotAString(String),
+
+    #[error("not valid JSON: {0}")]
+    InvalidJson(String),
+
+    #[error("field `{0}` not found")]
+    MissingField(String),
+}
+
+pub type PostprocessResult<T> = Result<T, PostprocessError>;
+
+/// A single step of a pipeline
+#[derive(Debug, Clone)]
+pub enum AnswerTransform {
+    Trim,
+    StripFences,
+    /// Dot separated path; numeric segments index into lists
+    JsonField(String),
+    RegexReplace {
+        pattern: Regex,
+        replacement: String,
+    },
+}
+
+impl AnswerTransform {
+    pub fn json_field(path: impl Into<String>) -> Self {
+        Self::JsonField(path.into())
+    }
+
+    pub fn regex_replace(pattern: &str, replacement: impl Into<String>) -> PostprocessResult<Self> {
+        let regex = Regex::new(pattern).map_err(|e| {
+            PostprocessError::InvalidTransform(
+                format!("regex_replace `{}`", pattern),
+                e.to_string(),
+            )
+        })?;
+        Ok(Self::RegexReplace {
+            pattern: regex,
+            replacement: replacement.into(),
+        })
+    }
+
+    /// Build a transform from its DSL form: a name, or a map with a single entry
+    pub fn from_literal(literal: &Literal) -> PostprocessResult<Self> {
+        let invalid = |message: &str| {
+            PostprocessError::InvalidTransform(literal.to_string(), message.to_string())
+        };
+        match literal {
+            Literal::String(name) => match name.as_str() {
+                "trim" => Ok(Self::Trim),
+                "strip_fences" => Ok(Self::StripFences),
+                _ => Err(invalid("unknown transform")),
+            },
+            Literal::Map(entries) if entries.len() == 1 => {
+                match entries.iter().next().expect("map has one entry") {
+                    (name, path) if name == "json_field" => string_literal(path)
+                        .map(Self::json_field)
+                        .ok_or_else(|| invalid("expected a string path")),
+                    (name, Literal::Map(options)) if name == "regex_replace" => {
+                        let option = |key: &str| options.get(key).and_then(string_literal);
+                        match (option("pattern"), option("replacement")) {
+                            (Some(pattern), Some(replacement)) => {
+                                Self::regex_replace(pattern, replacement)
+                            }
+                            _ => Err(invalid("expected string `pattern` and `replacement`")),
+                        }
+                    }
+                    _ => Err(invalid("unknown transform")),
+                }
+            }
+            _ => Err(invalid("expected a transform name or a map with one entry")),
+        }
+    }
+
+    pub fn apply(&self, value: Value) -> Result<Value, TransformError> {
+        match self {
+            Self::Trim => Ok(Value::String(text(value)?.trim().to_string())),
+            Self::StripFences => Ok(Value::String(strip_fences(&text(value)?).to_string())),
+            Self::JsonField(path) => {
+                // 構造化出力は既に Map なので、文字列のときだけ JSON として読む
+                let mut current = match value {
+                    Value::String(s) => from_json(
+                        serde_json::from_str(&s)
+                            .map_err(|e| TransformError::InvalidJson(e.to_string()))?,
+                    ),
+                    other => other,
+                };
+                for (i, segment) in path.split('.').enumerate() {
+                    let next = match current {
+                        Value::Map(mut fields) => fields.remove(segment),
+                        Value::List(mut items) => segment
+                            .parse::<usize>()
+                            .ok()
+                            .filter(|index| *index < items.len())
+                            .map(|index| items.swap_remove(index)),
+                        _ => None,
+                    };
+                    current = next.ok_or_else(|| {
+                        let seen: Vec<&str> = path.split('.').take(i + 1).collect();
+                        TransformError::MissingField(seen.join("."))
+                    })?;
+                }
+                Ok(current)
+            }
+            Self::RegexReplace {
+                pattern,
+                replacement,
+            } => Ok(Value::String(
+                pattern
+                    .replace_all(&text(value)?, replacement.as_str())
+                    .into_owned(),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for AnswerTransform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Trim => write!(f, "trim"),
+            Self::StripFences => write!(f, "strip_fences"),
+            Self::JsonField(path) => write!(f, "json_field `{}`", path),
+            Self::RegexReplace { pattern, .. } => write!(f, "regex_replace `{}`", pattern),
+        }
+    }
+}
+
+/// Ordered transforms applied to the value of an answer
+#[derive(Debug, Clone, Default)]
+pub struct AnswerPipeline {
+    transforms: Vec<AnswerTransform>,
+}
+
+impl AnswerPipeline {
+    pub fn new(transforms: Vec<AnswerTransform>) -> Self {
+        Self { transforms }
+    }
+
+    pub fn with_transform(mut self, transform: AnswerTransform) -> Self {
+        self.transforms.push(transform);
+        self
+    }
+
+    /// The pipeline declared under `postprocess` in the handler's constraints
+    pub fn from_constraints(constraints: Option<&Constraints>) -> PostprocessResult<Self> {
+        let transforms = constraints
+            .map(|constraints| constraints.postprocess.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .map(AnswerTransform::from_literal)
+            .collect::<PostprocessResult<_>>()?;
+        Ok(Self::new(transforms))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+
+    pub fn apply(&self, value: Value) -> PostprocessResult<Value> {
+        self.transforms
+            .iter()
+            .enumerate()
+            .try_fold(value, |value, (i, transform)| {
+                transform
+                    .apply(value)
+                    .map_err(|source| PostprocessError::StepFailed {
+                        step: i + 1,
+                        transform: transform.to_string(),
+                        source,
+                    })
+            })
+    }
+}
+
+fn string_literal(literal: &Literal) -> Option<&str> {
+    match literal {
+        Literal::String(s) | Literal::RawString { value: s, .. } => Some(s),
+        _ => None,
+    }
+}
+
+fn text(value: Value) -> Result<String, TransformError> {
+    match value {
+        Value::String(s) => Ok(s),
+        other => Err(TransformError::NotAString(format!("{:?}", other))),
+    }
+}
+
+/// The content of a fenced block, or the trimmed text when it is not fenced
+pub(crate) fn strip_fences(text: &str) -> &str {
+    let trimmed = text.trim();
+    let Some(rest) = trimmed.strip_prefix("```") else {
+        return trimmed;
+    };
+    // 開きフェンスの行には言語名が付くことがある
+    let body = rest.split_once('\n').map_or(rest, |(_, body)| body);
+    body.trim_end().strip_suffix("```").unwrap_or(body).trim()
+}
+
+pub(crate) fn from_json(json: serde_json::Value) -> Value {
+    if let Some(set) = ValueSet::from_json(&json) {
+        return Value::Set(set);
+    }
+    match json {
+        serde_json::Value::Null => Value::Null,
+        serde_json::Value::Bool(b) => Value::Boolean(b),
+        serde_json::Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => Value::Integer(i),
+            (None, Some(u)) => Value::UInteger(u),
+            _ => Value::Float(n.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(s) => Value::String(s),
+        serde_json::Value::Array(items) => Value::List(items.into_iter().map(from_json).collect()),
+        serde_json::Value::Object(fields) => Value::Map(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key, from_json(value)))
+                .collect::<HashMap<_, _>>(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    #[test]
+    fn test_strip_fences_then_extract_field() -> PostprocessResult<()> {
+        let pipeline = AnswerPipeline::default()
+            .with_transform(AnswerTransform::StripFences)
+            .with_transform(AnswerTransform::json_field("result.items.1"))
+            .with_transform(AnswerTransform::Trim);
+        let answer = "Here you go:\n```json\n{\"result\": {\"items\": [\"a\", \"  b  \"]}}\n```\n";
+        // 前置きのある文字列はフェンスで始まらないので JSON として読めない
+        assert!(pipeline.apply(string(answer)).is_err());
+
+        let answer = "```json\n{\"result\": {\"items\": [\"a\", \"  b  \"]}}\n```\n";
+        assert_eq!(pipeline.apply(string(answer))?, string("b"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_each_transform() -> Result<(), TransformError> {
+        assert_eq!(
+            AnswerTransform::Trim.apply(string("  hi \n"))?,
+            string("hi")
+        );
+        assert_eq!(
+            AnswerTransform::StripFences.apply(string("```\nplain\n```"))?,
+            string("plain")
+        );
+        assert_eq!(
+            AnswerTransform::StripFences.apply(string(" not fenced "))?,
+            string("not fenced")
+        );
+        assert_eq!(
+            AnswerTransform::json_field("count").apply(string(r#"{"count": 3}"#))?,
+            Value::Integer(3)
+        );
+        let structured = Value::Map(HashMap::from([("name".to_string(), string("Taro"))]));
+        assert_eq!(
+            AnswerTransform::json_field("name").apply(structured)?,
+            string("Taro")
+        );
+        let replace = AnswerTransform::regex_replace(r"\s+", " ").unwrap();
+        assert_eq!(replace.apply(string("a \n\t b"))?, string("a b"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_step_is_named() {
+        let pipeline = AnswerPipeline::new(vec![
+            AnswerTransform::StripFences,
+            AnswerTransform::json_field("answer.text"),
+        ]);
+        let error = pipeline
+            .apply(string("```json\n{\"answer\": {}}\n```"))
+            .unwrap_err();
+        assert!(matches!(
+            &error,
+            PostprocessError::StepFailed { step: 2, source: TransformError::MissingField(path), .. }
+                if path == "answer.text"
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Post-processing step 2 (json_field `answer.text`) failed: field `answer.text` not found"
+        );
+
+        assert!(matches!(
+            AnswerTransform::Trim.apply(Value::Integer(1)),
+            Err(TransformError::NotAString(_))
+        ));
+        assert!(matches!(
+            AnswerTransform::json_field("a").apply(string("not json")),
+            Err(TransformError::InvalidJson(_))
+        ));
+    }
+
+    #[test]
+    fn test_from_literals() {
+        let literals = vec![
+            Literal::String("strip_fences".to_string()),
+            Literal::Map(HashMap::from([(
+                "regex_replace".to_string(),
+                Literal::Map(HashMap::from([
+                    ("pattern".to_string(), Literal::String("x+".to_string())),
+                    ("replacement".to_string(), Literal::String("y".to_string())),
+                ])),
+            )])),
+        ];
+        let constraints = Constraints {
+            strictness: None,
+            stability: None,
+            latency: None,
+            postprocess: literals,
+        };
+        let pipeline = AnswerPipeline::from_constraints(Some(&constraints)).unwrap();
+        assert_eq!(
+            pipeline.apply(string("```\naxxb\n```")).unwrap(),
+            string("ayb")
+        );
+        assert!(AnswerPipeline::from_constraints(None).unwrap().is_empty());
+
+        assert!(matches!(
+            AnswerTransform::from_literal(&Literal::String("uppercase".to_string())),
+            Err(PostprocessError::InvalidTransform(..))
+        ));
+        assert!(matches!(
+            AnswerTransform::regex_replace("(", ""),
+            Err(PostprocessError::InvalidTransform(..))
+        ));
+    }
+}
@@ -0,0 +1,310 @@
+//! Timing breakdown of a single request.
+//!
+//! A request asks for a profile with the [`PROFILE_KEY`] parameter. Its answer
+//! handler then runs with a [`Profiler`] in the execution context, which records the
+//! time the request waited for dispatch, each statement of the handler, each provider
+//! call split into prompt assembly (per plugin), network and response processing, and
+//! the serialization of the response. The resulting [`RequestProfile`] travels back
+//! in the [`PROFILE_KEY`] parameter of the response event.
+//!
+//! A section opened while another one is open becomes its child, so the top-level
+//! sections add up to about the total. Requests without the parameter get no
+//! profiler, and the instrumented code only checks for its absence.
+//!
+//! [`PROFILE_KEY`]: crate::event_bus::PROFILE_KEY
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{ast::Statement, event_bus};
+
+/// Name of the section for the time between the dispatch of a request and the start
+/// of its handler
+pub const DISPATCH_WAIT_SECTION: &str = "dispatch_wait";
+
+/// Timing breakdown of a request, from its dispatch until its response was built
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RequestProfile {
+    /// Milliseconds from the dispatch of the request until its response was built
+    pub total_ms: f64,
+    /// Top-level sections, in the order they started
+    pub sections: Vec<ProfileSection>,
+}
+
+/// A timed part of a request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ProfileSection {
+    /// e.g. `dispatch_wait`, `statement 2 (assignment)`, `provider default`,
+    /// `assembly`, `plugin Memory`, `network`, `processing` or `serialization`
+    pub name: String,
+    pub duration_ms: f64,
+    /// Sections that ran within this one, in the order they started
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schema(no_recursion)]
+    pub children: Vec<ProfileSection>,
+}
+
+impl RequestProfile {
+    /// Every section, depth first
+    pub fn all_sections(&self) -> Vec<&ProfileSection> {
+        let mut sections = Vec::new();
+        let mut pending: Vec<&ProfileSection> = self.sections.iter().rev().collect();
+        while let Some(section) = pending.pop() {
+            sections.push(section);
+            pending.extend(section.children.iter().rev());
+        }
+        sections
+    }
+
+    pub fn to_event_value(&self) -> event_bus::Value {
+        event_bus::Value::Map(HashMap::from([
+            (
+                "total_ms".to_string(),
+                event_bus::Value::Float(self.total_ms),
+            ),
+            (
+                "sections".to_string(),
+                event_bus::Value::List(
+                    self.sections
+                        .iter()
+                        .map(ProfileSection::to_event_value)
+                        .collect(),
+                ),
+            ),
+        ]))
+    }
+
+    pub fn from_event_value(value: &event_bus::Value) -> Option<Self> {
+        serde_json::from_value(serde_json::Value::from(value)).ok()
+    }
+}
+
+impl ProfileSection {
+    fn to_event_value(&self) -> event_bus::Value {
+        event_bus::Value::Map(HashMap::from([
+            (
+                "name".to_string(),
+                event_bus::Value::from(self.name.as_str()),
+            ),
+            (
+                "duration_ms".to_string(),
+                event_bus::Value::Float(self.duration_ms),
+            ),
+            (
+                "children".to_string(),
+                event_bus::Value::List(
+                    self.children
+                        .iter()
+                        .map(ProfileSection::to_event_value)
+                        .collect(),
+                ),
+            ),
+        ]))
+    }
+}
+
+/// Records the sections of one request. Clones share the record.
+#[derive(Debug, Clone)]
+pub struct Profiler {
+    record: Arc<Mutex<Record>>,
+}
+
+#[derive(Debug)]
+struct Record {
+    started: Instant,
+    dispatch_wait: Duration,
+    sections: Vec<RecordedSection>,
+    // 開いている区間の添字（開いた順）
+    open: Vec<usize>,
+}
+
+#[derive(Debug)]
+struct RecordedSection {
+    name: String,
+    parent: Option<usize>,
+    started: Instant,
+    duration: Option<Duration>,
+}
+
+impl Profiler {
+    /// Starts profiling a request dispatched at `requested_at`. The time since then
+    /// is recorded as the [`DISPATCH_WAIT_SECTION`].
+    pub fn new(requested_at: DateTime<Utc>) -> Self {
+        let dispatch_wait = (Utc::now() - requested_at).to_std().unwrap_or_default();
+        Self {
+            record: Arc::new(Mutex::new(Record {
+                started: Instant::now(),
+                dispatch_wait,
+                sections: Vec::new(),
+                open: Vec::new(),
+            })),
+        }
+    }
+
+    /// Opens a section, closed when the returned span is dropped
+    pub fn enter(&self, name: impl Into<String>) -> ProfileSpan {
+        let mut record = self.record.lock().unwrap();
+        let id = record.sections.len();
+        let parent = record.open.last().copied();
+        record.sections.push(RecordedSection {
+            name: name.into(),
+            parent,
+            started: Instant::now(),
+            duration: None,
+        });
+        record.open.push(id);
+        ProfileSpan {
+            profiler: self.clone(),
+            id,
+        }
+    }
+
+    fn exit(&self, id: usize) {
+        let mut record = self.record.lock().unwrap();
+        let section = &mut record.sections[id];
+        section.duration = Some(section.started.elapsed());
+        record.open.retain(|open| *open != id);
+    }
+
+    /// The profile up to now. Sections still open count until now.
+    pub fn finish(&self) -> RequestProfile {
+        let record = self.record.lock().unwrap();
+        let now = Instant::now();
+        let mut children: Vec<Vec<usize>> = vec![Vec::new(); record.sections.len()];
+        let mut roots = Vec::new();
+        for (id, section) in record.sections.iter().enumerate() {
+            match section.parent {
+                Some(parent) => children[parent].push(id),
+                None => roots.push(id),
+            }
+        }
+
+        fn build(
+            id: usize,
+            record: &Record,
+            children: &[Vec<usize>],
+            now: Instant,
+        ) -> ProfileSection {
+            let section = &record.sections[id];
+            ProfileSection {
+                name: section.name.clone(),
+                duration_ms: millis(
+                    section
+                        .duration
+                        .unwrap_or_else(|| now.duration_since(section.started)),
+                ),
+                children: children[id]
+                    .iter()
+                    .map(|child| build(*child, record, children, now))
+                    .collect(),
+            }
+        }
+
+        let mut sections = vec![ProfileSection {
+            name: DISPATCH_WAIT_SECTION.to_string(),
+            duration_ms: millis(record.dispatch_wait),
+            children: Vec::new(),
+        }];
+        sections.extend(
+            roots
+                .into_iter()
+                .map(|id| build(id, &record, &children, now)),
+        );
+        RequestProfile {
+            total_ms: millis(record.dispatch_wait + now.duration_since(record.started)),
+            sections,
+        }
+    }
+}
+
+/// An open section of a [`Profiler`], closed when dropped
+#[derive(Debug)]
+pub struct ProfileSpan {
+    profiler: Profiler,
+    id: usize,
+}
+
+impl Drop for ProfileSpan {
+    fn drop(&mut self) {
+        self.profiler.exit(self.id);
+    }
+}
+
+/// Name of the section of the `index`-th (from 0) statement of a block
+pub(crate) fn statement_section(index: usize, statement: &Statement) -> String {
+    let kind = match statement {
+        Statement::Expression(_) => "expression",
+        Statement::Assignment { .. } => "assignment",
+        Statement::Let { .. } => "let",
+        Statement::Return(_) => "return",
+        Statement::Yield(_) => "yield",
+        Statement::Emit { .. } => "emit",
+        Statement::Log { .. } => "log",
+        Statement::Block(_) => "block",
+        Statement::WithError { .. } => "onFail",
+        Statement::If { .. } => "if",
+        Statement::Retry { .. } => "retry",
+    };
+    format!("statement {} ({})", index + 1, kind)
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nested_sections() {
+        let profiler = Profiler::new(Utc::now() - chrono::Duration::milliseconds(20));
+        {
+            let _statement = profiler.enter("statement 1 (assignment)");
+            let _provider = profiler.enter("provider default");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        let _serialization = profiler.enter("serialization");
+
+        let profile = profiler.finish();
+        let names: Vec<&str> = profile
+            .all_sections()
+            .iter()
+            .map(|section| section.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "dispatch_wait",
+                "statement 1 (assignment)",
+                "provider default",
+                "serialization"
+            ]
+        );
+        assert!(profile.sections[0].duration_ms >= 20.0);
+        assert!(profile.sections[1].duration_ms >= profile.sections[1].children[0].duration_ms);
+
+        // 最上位の区間の合計は全体の時間とほぼ一致する
+        let sum: f64 = profile.sections.iter().map(|s| s.duration_ms).sum();
+        assert!(sum <= profile.total_ms);
+        assert!(profile.total_ms - sum < 5.0);
+    }
+
+    #[test]
+    fn test_event_value_round_trip() {
+        let profiler = Profiler::new(Utc::now());
+        drop(profiler.enter("statement 1 (return)"));
+        let profile = profiler.finish();
+        assert_eq!(
+            RequestProfile::from_event_value(&profile.to_event_value()),
+            Some(profile)
+        );
+    }
+}
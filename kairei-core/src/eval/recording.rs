@@ -0,0 +1,347 @@
+//! Recordings of handler runs.
+//!
+//! With `handler_recording.enabled` in the system configuration, every run of an
+//! observe or react handler whose block runs is recorded as a [`HandlerRecording`]:
+//!
+//! * the event that triggered it and the state it started from;
+//! * the output of each provider call, in the order they were made;
+//! * after each statement of the handler's block, the state variables the statement
+//!   changed and the events it emitted. Statements nested in `if` or `onFail` blocks
+//!   count as part of the statement containing them.
+//!
+//! A [`Recorder`] in the execution context collects them while the handler runs, and
+//! the instrumented code only checks for its absence. The run is recorded whether the
+//! handler succeeds or fails; a failing statement has no step.
+//!
+//! The last `handler_recording.max_recordings` runs of the system are kept in memory
+//! by [`HandlerRecordings`], and can be stepped through in a
+//! [debug session](crate::debug_session).
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::{
+    context::{ExecutionContext, SESSION_ID_STATE},
+    expression::Value,
+};
+use crate::{
+    Statement,
+    config::HandlerRecordingConfig,
+    event_bus::Event,
+    formatter::{Formatter, config::FormatterConfig},
+};
+
+/// Kind of a recorded handler
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HandlerKind {
+    Observe,
+    React,
+}
+
+/// A variable a statement changed. `before` is `None` for a new variable
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateChange {
+    pub name: String,
+    pub before: Option<Value>,
+    pub after: Value,
+}
+
+/// A recorded run of an observe or react handler
+#[derive(Debug, Clone, PartialEq)]
+pub struct HandlerRecording {
+    pub id: String,
+    pub agent_name: String,
+    pub kind: HandlerKind,
+    /// The event the handler ran for
+    pub event: Event,
+    /// State of the agent when the handler started
+    pub initial_state: HashMap<String, Value>,
+    /// Outputs of the provider calls, in the order they were made
+    pub responses: Vec<RecordedResponse>,
+    /// The statements of the handler's block that ran, in order
+    pub steps: Vec<RecordedStep>,
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// Output of a provider call during a recorded run
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedResponse {
+    pub provider: String,
+    pub output: String,
+}
+
+/// A statement of a recorded run and what it did
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedStep {
+    /// Source text of the statement
+    pub statement: String,
+    /// Variables the statement changed, ordered by name
+    pub state_changes: Vec<StateChange>,
+    /// Events the statement emitted, in order
+    pub emitted_events: Vec<Event>,
+}
+
+/// Records the run of one handler. Clones share the record.
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    record: Arc<Mutex<Record>>,
+}
+
+#[derive(Debug)]
+struct Record {
+    initial_state: HashMap<String, Value>,
+    // 直前に記録した文の後の状態
+    state: HashMap<String, Value>,
+    // ハンドラが発行したイベント
+    events: Vec<Event>,
+    // 直前に記録した文までに発行されたイベントの数
+    emitted: usize,
+    // 評価中のブロックの深さ。ハンドラのブロック（深さ 1）の文だけを記録する
+    depth: usize,
+    responses: Vec<RecordedResponse>,
+    steps: Vec<RecordedStep>,
+}
+
+impl Recorder {
+    /// Starts recording a handler that starts from `initial_state`
+    pub fn new(initial_state: HashMap<String, Value>) -> Self {
+        Self {
+            record: Arc::new(Mutex::new(Record {
+                state: initial_state.clone(),
+                initial_state,
+                events: Vec::new(),
+                emitted: 0,
+                depth: 0,
+                responses: Vec::new(),
+                steps: Vec::new(),
+            })),
+        }
+    }
+
+    /// Marks a block as being evaluated until the returned guard is dropped
+    pub fn enter_block(&self) -> RecordedBlock {
+        self.record.lock().unwrap().depth += 1;
+        RecordedBlock {
+            recorder: self.clone(),
+        }
+    }
+
+    /// Records the changes of `statement`, which just ran in `context`, if it is a
+    /// statement of the handler's block
+    pub async fn statement_finished(&self, statement: &Statement, context: &ExecutionContext) {
+        if self.record.lock().unwrap().depth != 1 {
+            return;
+        }
+        let state = current_state(context).await;
+        let mut record = self.record.lock().unwrap();
+        let emitted_events = record.events[record.emitted..].to_vec();
+        let state_changes = state_changes(&record.state, &state);
+        record.steps.push(RecordedStep {
+            statement: statement_text(statement),
+            state_changes,
+            emitted_events,
+        });
+        record.state = state;
+        record.emitted = record.events.len();
+    }
+
+    /// Records `event`, emitted by the handler
+    pub fn record_event(&self, event: &Event) {
+        self.record.lock().unwrap().events.push(event.clone());
+    }
+
+    /// The events emitted by the handler so far, in the order they were emitted
+    pub fn emitted_events(&self) -> Vec<Event> {
+        self.record.lock().unwrap().events.clone()
+    }
+
+    /// Records the output of a call to `provider`
+    pub fn record_response(&self, provider: &str, output: &str) {
+        self.record
+            .lock()
+            .unwrap()
+            .responses
+            .push(RecordedResponse {
+                provider: provider.to_string(),
+                output: output.to_string(),
+            });
+    }
+
+    /// The recording of the run of `agent_name`'s handler of `kind` for `event`
+    pub fn finish(&self, agent_name: &str, kind: HandlerKind, event: &Event) -> HandlerRecording {
+        let record = self.record.lock().unwrap();
+        HandlerRecording {
+            id: Uuid::new_v4().to_string(),
+            agent_name: agent_name.to_string(),
+            kind,
+            event: event.clone(),
+            initial_state: record.initial_state.clone(),
+            responses: record.responses.clone(),
+            steps: record.steps.clone(),
+            recorded_at: Utc::now(),
+        }
+    }
+}
+
+/// A block being evaluated by a recorded handler, see [`Recorder::enter_block`]
+#[derive(Debug)]
+pub struct RecordedBlock {
+    recorder: Recorder,
+}
+
+impl Drop for RecordedBlock {
+    fn drop(&mut self) {
+        self.recorder.record.lock().unwrap().depth -= 1;
+    }
+}
+
+/// The last recorded handler runs of a system
+#[derive(Debug)]
+pub struct HandlerRecordings {
+    max_recordings: usize,
+    recordings: Mutex<VecDeque<Arc<HandlerRecording>>>,
+}
+
+impl HandlerRecordings {
+    pub fn new(config: &HandlerRecordingConfig) -> Self {
+        Self {
+            max_recordings: config.max_recordings,
+            recordings: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Keeps `recording`, dropping the oldest one when full
+    pub fn push(&self, recording: HandlerRecording) {
+        let mut recordings = self.recordings.lock().unwrap();
+        recordings.push_back(Arc::new(recording));
+        while recordings.len() > self.max_recordings {
+            recordings.pop_front();
+        }
+    }
+
+    /// The kept recordings, oldest first
+    pub fn list(&self) -> Vec<Arc<HandlerRecording>> {
+        self.recordings.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<HandlerRecording>> {
+        self.recordings
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|recording| recording.id == id)
+            .cloned()
+    }
+}
+
+/// The state variables of `context`
+pub(crate) async fn current_state(context: &ExecutionContext) -> HashMap<String, Value> {
+    let mut state = HashMap::new();
+    for name in context.list_state_variables() {
+        // think が作るセッション ID はハンドラの変更として扱わない
+        if name == SESSION_ID_STATE {
+            continue;
+        }
+        if let Ok(value) = context.get_state(&name).await {
+            state.insert(name, value);
+        }
+    }
+    state
+}
+
+/// The variables of `after` that differ from `before`, ordered by name
+pub(crate) fn state_changes(
+    before: &HashMap<String, Value>,
+    after: &HashMap<String, Value>,
+) -> Vec<StateChange> {
+    let mut changes: Vec<StateChange> = after
+        .iter()
+        .filter(|(name, value)| before.get(*name) != Some(*value))
+        .map(|(name, value)| StateChange {
+            name: name.clone(),
+            before: before.get(name).cloned(),
+            after: value.clone(),
+        })
+        .collect();
+    changes.sort_by(|a, b| a.name.cmp(&b.name));
+    changes
+}
+
+/// Source text of `statement`, or its AST when it cannot be formatted
+pub(crate) fn statement_text(statement: &Statement) -> String {
+    Formatter::new(FormatterConfig::default())
+        .format_statement(statement)
+        .unwrap_or_else(|_| format!("{:?}", statement))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recording(id: &str) -> HandlerRecording {
+        HandlerRecording {
+            id: id.to_string(),
+            agent_name: "Counter".to_string(),
+            kind: HandlerKind::Observe,
+            event: Event::default(),
+            initial_state: HashMap::new(),
+            responses: vec![],
+            steps: vec![],
+            recorded_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_oldest_recordings_are_dropped() {
+        let recordings = HandlerRecordings::new(&HandlerRecordingConfig {
+            enabled: true,
+            max_recordings: 2,
+        });
+        for id in ["a", "b", "c"] {
+            recordings.push(recording(id));
+        }
+        let ids: Vec<String> = recordings
+            .list()
+            .iter()
+            .map(|recording| recording.id.clone())
+            .collect();
+        assert_eq!(ids, vec!["b", "c"]);
+        assert!(recordings.get("a").is_none());
+    }
+
+    #[test]
+    fn test_state_changes_are_ordered_by_name() {
+        let before = HashMap::from([
+            ("count".to_string(), Value::Integer(1)),
+            ("name".to_string(), Value::String("a".to_string())),
+        ]);
+        let after = HashMap::from([
+            ("count".to_string(), Value::Integer(2)),
+            ("name".to_string(), Value::String("a".to_string())),
+            ("added".to_string(), Value::Boolean(true)),
+        ]);
+        assert_eq!(
+            state_changes(&before, &after),
+            vec![
+                StateChange {
+                    name: "added".to_string(),
+                    before: None,
+                    after: Value::Boolean(true),
+                },
+                StateChange {
+                    name: "count".to_string(),
+                    before: Some(Value::Integer(1)),
+                    after: Value::Integer(2),
+                },
+            ]
+        );
+    }
+}
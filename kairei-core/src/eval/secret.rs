@@ -0,0 +1,125 @@
+//! Secrets for handlers.
+//!
+//! A handler reads a secret with the `secret("name")` built-in. The secret must be
+//! defined in [`SecretConfig::handler_secrets`] and granted to the agent in
+//! [`SystemConfig::secret_grants`]. The result is a [`SecretValue`]: formatting,
+//! serialization and prompt interpolation only ever show [`SECRET_MASK`]. Code that
+//! needs the value itself, like a will action setting a request header, calls
+//! [`SecretValue::expose`].
+//!
+//! [`SecretConfig::handler_secrets`]: crate::config::SecretConfig::handler_secrets
+//! [`SystemConfig::secret_grants`]: crate::config::SystemConfig::secret_grants
+
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use serde::{Serialize, Serializer};
+use tracing::warn;
+
+/// Shown in place of a secret
+pub const SECRET_MASK: &str = "****";
+
+/// A secret value that never shows itself
+#[derive(Clone, PartialEq, Eq)]
+pub struct SecretValue(Arc<str>);
+
+impl SecretValue {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(Arc::from(value.into()))
+    }
+
+    /// The raw value. Only for the place the secret is meant for.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// The secret followed by `suffix`, e.g. `"Bearer " + secret("token")`
+    pub fn concat(&self, suffix: &str) -> Self {
+        Self::new(format!("{}{}", self.0, suffix))
+    }
+
+    pub fn prepend(&self, prefix: &str) -> Self {
+        Self::new(format!("{}{}", prefix, self.0))
+    }
+}
+
+impl fmt::Debug for SecretValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(SECRET_MASK)
+    }
+}
+
+impl fmt::Display for SecretValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(SECRET_MASK)
+    }
+}
+
+impl Serialize for SecretValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(SECRET_MASK)
+    }
+}
+
+/// The secrets one agent was granted
+#[derive(Clone, Default)]
+pub struct SecretVault {
+    secrets: HashMap<String, SecretValue>,
+}
+
+impl SecretVault {
+    /// Pick the `granted` names out of `secrets`
+    pub fn granted(secrets: &HashMap<String, String>, granted: &[String]) -> Self {
+        let secrets = granted
+            .iter()
+            .filter_map(|name| match secrets.get(name) {
+                Some(value) => Some((name.clone(), SecretValue::new(value.clone()))),
+                None => {
+                    warn!("Secret {} is granted but not defined", name);
+                    None
+                }
+            })
+            .collect();
+        Self { secrets }
+    }
+
+    pub fn get(&self, name: &str) -> Option<SecretValue> {
+        self.secrets.get(name).cloned()
+    }
+}
+
+impl fmt::Debug for SecretVault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.secrets.keys()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_is_masked() {
+        let secret = SecretValue::new("s3cr3t");
+        assert_eq!(secret.expose(), "s3cr3t");
+        assert_eq!(secret.to_string(), SECRET_MASK);
+        assert_eq!(format!("{:?}", secret), SECRET_MASK);
+        assert_eq!(serde_json::to_string(&secret).unwrap(), "\"****\"");
+
+        let header = secret.prepend("Bearer ");
+        assert_eq!(header.expose(), "Bearer s3cr3t");
+        assert_eq!(header.to_string(), SECRET_MASK);
+    }
+
+    #[test]
+    fn test_vault_holds_granted_secrets_only() {
+        let secrets = HashMap::from([
+            ("api_key".to_string(), "s3cr3t".to_string()),
+            ("db_password".to_string(), "hunter2".to_string()),
+        ]);
+        let vault = SecretVault::granted(&secrets, &["api_key".to_string(), "missing".to_string()]);
+        assert_eq!(vault.get("api_key").unwrap().expose(), "s3cr3t");
+        assert!(vault.get("db_password").is_none());
+        assert!(vault.get("missing").is_none());
+        assert!(!format!("{:?}", vault).contains("s3cr3t"));
+    }
+}
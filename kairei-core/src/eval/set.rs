@@ -0,0 +1,178 @@
+//! Sets of values.
+//!
+//! A [`ValueSet`] is written `#{a, b, c}` in the DSL and typed `Set<T>`. Its
+//! elements are [`SetElement`]s: `Boolean`, `Int` and `String`, the builtin types
+//! with exact equality. `Float` cannot be an element since `NaN` is not equal to
+//! itself.
+//!
+//! Equality: two sets are equal when they hold the same elements. Elements of
+//! different types are never equal, so `#{1}` and `#{"1"}` are different sets.
+//!
+//! Ordering: every view of a set — iteration, `to_list`, `Debug`, JSON and the
+//! wire encoding — lists its elements in ascending order. Booleans come first
+//! (`false` before `true`), then integers in numeric order, then strings in
+//! byte order. The order never depends on insertion order, so the same set
+//! always serializes to the same bytes.
+//!
+//! Outside the runtime a set is a JSON object with the single key
+//! [`SET_JSON_MARKER`] holding the sorted array, e.g. `{"$set": [1, 2]}`. Any
+//! other object is a map.
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+/// Key of the JSON object holding the elements of a set
+pub const SET_JSON_MARKER: &str = "$set";
+
+/// An element of a set. The variant order is the order of elements in a set.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum SetElement {
+    Boolean(bool),
+    Integer(i64),
+    String(String),
+}
+
+impl SetElement {
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            Self::Boolean(b) => serde_json::Value::Bool(*b),
+            Self::Integer(i) => serde_json::Value::from(*i),
+            Self::String(s) => serde_json::Value::String(s.clone()),
+        }
+    }
+
+    /// `None` for JSON that cannot be an element
+    pub fn from_json(json: &serde_json::Value) -> Option<Self> {
+        match json {
+            serde_json::Value::Bool(b) => Some(Self::Boolean(*b)),
+            serde_json::Value::Number(n) => n.as_i64().map(Self::Integer),
+            serde_json::Value::String(s) => Some(Self::String(s.clone())),
+            _ => None,
+        }
+    }
+}
+
+/// A set of values, always in the order described in the [module docs](self)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ValueSet(BTreeSet<SetElement>);
+
+impl ValueSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns whether the element was new
+    pub fn insert(&mut self, element: SetElement) -> bool {
+        self.0.insert(element)
+    }
+
+    /// Returns whether the element was present
+    pub fn remove(&mut self, element: &SetElement) -> bool {
+        self.0.remove(element)
+    }
+
+    pub fn contains(&self, element: &SetElement) -> bool {
+        self.0.contains(element)
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        self.0.union(&other.0).cloned().collect()
+    }
+
+    pub fn intersect(&self, other: &Self) -> Self {
+        self.0.intersection(&other.0).cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Elements in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = &SetElement> {
+        self.0.iter()
+    }
+
+    /// `{"$set": [...]}` with the elements in ascending order
+    pub fn to_json(&self) -> serde_json::Value {
+        let items = self.iter().map(SetElement::to_json).collect();
+        let mut object = serde_json::Map::new();
+        object.insert(SET_JSON_MARKER.to_string(), serde_json::Value::Array(items));
+        serde_json::Value::Object(object)
+    }
+
+    /// The set encoded by [`Self::to_json`]. `None` for any other JSON, including
+    /// a marked object whose items cannot be elements.
+    pub fn from_json(json: &serde_json::Value) -> Option<Self> {
+        let object = json.as_object().filter(|object| object.len() == 1)?;
+        object
+            .get(SET_JSON_MARKER)?
+            .as_array()?
+            .iter()
+            .map(SetElement::from_json)
+            .collect()
+    }
+}
+
+impl FromIterator<SetElement> for ValueSet {
+    fn from_iter<I: IntoIterator<Item = SetElement>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl IntoIterator for ValueSet {
+    type Item = SetElement;
+    type IntoIter = std::collections::btree_set::IntoIter<SetElement>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_order_does_not_depend_on_insertion() {
+        let elements = [
+            SetElement::String("b".to_string()),
+            SetElement::Integer(10),
+            SetElement::String("a".to_string()),
+            SetElement::Boolean(true),
+            SetElement::Integer(-2),
+            SetElement::Boolean(false),
+        ];
+        let forward: ValueSet = elements.iter().cloned().collect();
+        let backward: ValueSet = elements.iter().rev().cloned().collect();
+
+        assert_eq!(forward, backward);
+        assert_eq!(
+            forward.to_json(),
+            json!({"$set": [false, true, -2, 10, "a", "b"]})
+        );
+        assert_eq!(
+            forward.to_json().to_string(),
+            backward.to_json().to_string()
+        );
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let set: ValueSet = [SetElement::Integer(1), SetElement::String("1".to_string())]
+            .into_iter()
+            .collect();
+        // 型の違う要素は等しくない
+        assert_eq!(set.len(), 2);
+        assert_eq!(ValueSet::from_json(&set.to_json()), Some(set));
+
+        assert_eq!(ValueSet::from_json(&json!([1, 2])), None);
+        assert_eq!(ValueSet::from_json(&json!({"$set": [1.5]})), None);
+        assert_eq!(ValueSet::from_json(&json!({"$set": [1], "other": 2})), None);
+    }
+}
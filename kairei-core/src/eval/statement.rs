@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use async_recursion::async_recursion;
@@ -6,10 +7,12 @@ use tracing::debug;
 use super::{
     context::{ExecutionContext, StateAccessMode, VariableAccess},
     expression::{ExpressionEvaluator, Value},
+    profile::statement_section,
 };
 use crate::eval::evaluator::{EvalError, EvalResult};
 use crate::{
-    Argument, ErrorHandlerBlock, EventType, Expression, Statement,
+    Argument, ErrorHandlerBlock, EventType, Expression, OnFailControl, OnFailReturn, Statement,
+    agent_log::{LogLevel, LogRecord},
     event_bus::{self, Event},
     event_registry,
 };
@@ -44,6 +47,7 @@ impl StatementEvaluator {
         statement: &Statement,
         context: Arc<ExecutionContext>,
     ) -> EvalResult<StatementResult> {
+        let _frame = context.eval_usage().enter()?;
         // Dispatch to the appropriate evaluation method based on the statement type
         match statement {
             Statement::Expression(expr) => Ok(StatementResult::Value(
@@ -52,17 +56,31 @@ impl StatementEvaluator {
             Statement::Return(expr) => Ok(StatementResult::Control(ControlFlow::Return(
                 self.eval_return(expr, context).await?,
             ))),
+            Statement::Yield(expr) => Ok(StatementResult::Value(
+                self.eval_yield(expr, context).await?,
+            )),
             Statement::Assignment { target, value } => Ok(StatementResult::Value(
                 self.eval_assignment(target, value, context).await?,
             )),
+            Statement::Let { name, value, .. } => Ok(StatementResult::Value(
+                self.eval_let(name, value, context).await?,
+            )),
             Statement::Emit {
                 event_type,
                 parameters,
                 target,
+                immediate,
             } => Ok(StatementResult::Value(
-                self.eval_emit(event_type, parameters, target, context)
+                self.eval_emit(event_type, parameters, target, *immediate, context)
                     .await?,
             )),
+            Statement::Log {
+                level,
+                message,
+                fields,
+            } => Ok(StatementResult::Value(
+                self.eval_log(*level, message, fields, context).await?,
+            )),
             Statement::Block(block) => self.eval_block(block, context).await,
             Statement::If {
                 condition,
@@ -79,6 +97,11 @@ impl StatementEvaluator {
                 self.eval_with_error(statement, error_handler_block, context)
                     .await
             }
+            Statement::Retry {
+                times,
+                backoff,
+                body,
+            } => self.eval_retry(times, backoff, body, context).await,
         }
     }
 }
@@ -109,6 +132,33 @@ impl StatementEvaluator {
             .await
     }
 
+    /// 部分応答を送る。`return` と同じく `Ok` は中身を送り、`Err` は最終応答で返す必要がある
+    #[tracing::instrument(skip(self, context), level = "debug")]
+    async fn eval_yield(
+        &self,
+        expr: &Expression,
+        context: Arc<ExecutionContext>,
+    ) -> EvalResult<Value> {
+        let value = match self
+            .expression_evaluator
+            .eval_expression(expr, context.clone())
+            .await?
+        {
+            Value::Ok(inner) => *inner,
+            Value::Err(_) => {
+                return Err(EvalError::InvalidOperation(
+                    "yield cannot publish an error, return it instead".to_string(),
+                ));
+            }
+            Value::Error(e) => {
+                return Err(EvalError::Eval(format!("Unhandled exception: {:?}", e)));
+            }
+            other => other,
+        };
+        context.send_partial_response(value).await?;
+        Ok(Value::Unit)
+    }
+
     #[tracing::instrument(skip(self, context), level = "debug")]
     pub async fn eval_expression(
         &self,
@@ -137,13 +187,13 @@ impl StatementEvaluator {
         match (targets.len(), &value) {
             // 単一のターゲットへの代入
             (1, _) => {
-                let access = self.get_variable_access(&targets[0])?;
+                let access = self.get_variable_access(&targets[0], &context)?;
                 context.set(access, value.clone()).await?;
             }
             // タプル値を複数のターゲットに分配
             (n, Value::Tuple(values)) if n == values.len() => {
                 for (target, value) in targets.iter().zip(values) {
-                    let access = self.get_variable_access(target)?;
+                    let access = self.get_variable_access(target, &context)?;
                     context.set(access, value.clone()).await?;
                 }
             }
@@ -166,8 +216,34 @@ impl StatementEvaluator {
         Ok(Value::Unit)
     }
 
-    fn get_variable_access(&self, target: &Expression) -> EvalResult<VariableAccess> {
+    /// 値は一度だけ評価し、以降の参照は束縛した値を読む
+    #[tracing::instrument(skip(self, context), level = "debug")]
+    async fn eval_let(
+        &self,
+        name: &str,
+        value: &Expression,
+        context: Arc<ExecutionContext>,
+    ) -> EvalResult<Value> {
+        let value = self
+            .expression_evaluator
+            .eval_expression(value, context.clone())
+            .await?;
+        context
+            .set(VariableAccess::Local(name.to_string()), value)
+            .await?;
+        Ok(Value::Unit)
+    }
+
+    fn get_variable_access(
+        &self,
+        target: &Expression,
+        context: &ExecutionContext,
+    ) -> EvalResult<VariableAccess> {
         match target {
+            // 読み取りと同じく、同名のローカル変数がなければ状態変数に書く
+            Expression::Variable(name) if !context.has_local(name) && context.is_state(name) => {
+                Ok(VariableAccess::State(name.clone()))
+            }
             Expression::Variable(name) => Ok(VariableAccess::Local(name.clone())),
             Expression::StateAccess(path) => Ok(VariableAccess::State(path.to_string())),
             _ => Err(EvalError::InvalidOperation(
@@ -182,6 +258,7 @@ impl StatementEvaluator {
         event_type: &EventType,
         parameters: &[Argument],
         target: &Option<String>,
+        immediate: bool,
         context: Arc<ExecutionContext>,
     ) -> EvalResult<Value> {
         // パラメータの評価
@@ -198,10 +275,49 @@ impl StatementEvaluator {
             .map(|(k, v)| (k.clone(), event_bus::Value::from(v.clone())))
             .collect();
 
-        // イベントの構築と発行
+        // イベントの構築と発行。emit! 以外はハンドラが成功するまで保留する
         let event = Event::new(&event_type, &event_params);
-        context.emit_event(event).await?;
+        if immediate {
+            context.emit_event(event).await?;
+        } else {
+            context.emit_on_commit(event).await?;
+        }
+
+        Ok(Value::Unit)
+    }
 
+    /// ログを出力する。メッセージは文字列以外なら表示形式に変換する
+    #[tracing::instrument(skip(self, context), level = "debug")]
+    async fn eval_log(
+        &self,
+        level: LogLevel,
+        message: &Expression,
+        fields: &[(String, Expression)],
+        context: Arc<ExecutionContext>,
+    ) -> EvalResult<Value> {
+        let message = match self
+            .expression_evaluator
+            .eval_expression(message, context.clone())
+            .await?
+        {
+            Value::String(message) => message,
+            other => other.to_string(),
+        };
+        let mut evaluated = Vec::with_capacity(fields.len());
+        for (key, value) in fields {
+            let value = self
+                .expression_evaluator
+                .eval_expression(value, context.clone())
+                .await?;
+            evaluated.push((key.clone(), value));
+        }
+        context
+            .write_log(LogRecord {
+                level,
+                message,
+                fields: evaluated,
+            })
+            .await?;
         Ok(Value::Unit)
     }
 
@@ -241,8 +357,17 @@ impl StatementEvaluator {
         context: Arc<ExecutionContext>,
     ) -> EvalResult<StatementResult> {
         let mut last = Value::Unit;
-        for stmt in statements.iter() {
-            let result = self.eval_statement(stmt, context.clone()).await?;
+        let _block = context.recorder().map(|recorder| recorder.enter_block());
+        for (index, stmt) in statements.iter().enumerate() {
+            let span = context
+                .profiler()
+                .map(|profiler| profiler.enter(statement_section(index, stmt)));
+            let result = self.eval_statement(stmt, context.clone()).await;
+            drop(span);
+            let result = result?;
+            if let Some(recorder) = context.recorder() {
+                recorder.statement_finished(stmt, &context).await;
+            }
             match result {
                 StatementResult::Value(value) => {
                     last = value;
@@ -259,6 +384,72 @@ impl StatementEvaluator {
         Ok(StatementResult::Value(last))
     }
 
+    /// Runs `body` up to `times` times until an attempt succeeds. Each attempt gets its
+    /// own scope with `retry.attempt` bound, counting from 1. After the last attempt
+    /// its error is returned, so an `onFail` on the statement handles it. Every retry
+    /// is taken from the retry budget of the request chain; once it is spent, the
+    /// error of the current attempt is returned.
+    #[tracing::instrument(skip(self, context), level = "debug")]
+    async fn eval_retry(
+        &self,
+        times: &Expression,
+        backoff: &Option<Expression>,
+        body: &[Statement],
+        context: Arc<ExecutionContext>,
+    ) -> EvalResult<StatementResult> {
+        let times = match self.eval_expression(times, context.clone()).await? {
+            Value::Integer(times) if times >= 1 => times,
+            other => {
+                return Err(EvalError::InvalidParameter {
+                    name: "times".to_string(),
+                    value: format!("{:?}", other),
+                });
+            }
+        };
+        let mut delay = match backoff {
+            Some(backoff) => match self.eval_expression(backoff, context.clone()).await? {
+                Value::Duration(delay) => delay,
+                other => {
+                    return Err(EvalError::InvalidParameter {
+                        name: "backoff".to_string(),
+                        value: format!("{:?}", other),
+                    });
+                }
+            },
+            None => std::time::Duration::ZERO,
+        };
+
+        let mut attempt = 1;
+        loop {
+            let attempt_context = Arc::new(context.fork(None).await);
+            attempt_context
+                .set_variable(
+                    "retry",
+                    Value::Map(HashMap::from([(
+                        "attempt".to_string(),
+                        Value::Integer(attempt),
+                    )])),
+                )
+                .await?;
+            match self.eval_block(body, attempt_context).await {
+                Ok(result) => return Ok(result),
+                // 再試行はリクエストの連鎖で共有する予算から使う
+                Err(error)
+                    if attempt < times && error.is_retryable() && context.try_spend_retry() =>
+                {
+                    debug!("Retrying after attempt {} failed: {}", attempt, error);
+                }
+                Err(error) => return Err(error),
+            }
+            // think の呼び出し回数などの使用量は fork 間で共有されるので、試行をまたいで数えられる
+            if !delay.is_zero() {
+                context.clock().sleep(delay).await;
+                delay = delay.saturating_mul(2);
+            }
+            attempt += 1;
+        }
+    }
+
     #[tracing::instrument(skip(self, context), level = "debug")]
     async fn eval_with_error(
         &self,
@@ -266,9 +457,13 @@ impl StatementEvaluator {
         error_handler_block: &ErrorHandlerBlock,
         context: Arc<ExecutionContext>,
     ) -> EvalResult<StatementResult> {
+        let savepoint = context.savepoint();
         match self.eval_statement(statement, context.clone()).await {
             Ok(value) => Ok(value),
             Err(error) => {
+                // 失敗した文の状態の書き込みとイベントは、onFail の前に取り消す
+                context.rollback_to(savepoint);
+
                 // Create new scope for error handler
                 let error_context = Arc::new(context.fork(Some(StateAccessMode::ReadOnly)).await);
 
@@ -280,14 +475,33 @@ impl StatementEvaluator {
                         .map_err(|e| EvalError::Eval(format!("Error Binding Failed: {}", e)))?;
                 }
 
-                // Execute error handler block
+                // Execute error handler block, a nested `return` ends the handler
                 let result = self
-                    .eval_block(&error_handler_block.error_handler_statements, error_context)
-                    .await;
+                    .eval_block(
+                        &error_handler_block.error_handler_statements,
+                        error_context.clone(),
+                    )
+                    .await?;
+                if let StatementResult::Control(ControlFlow::Return(_)) = result {
+                    return Ok(result);
+                }
 
-                // Return Unit or propagate error
-                // TODO: check return type compile(parse) time.
-                result.map(|_| StatementResult::Value(Value::Unit))
+                // `return Ok(..)` / `return Err(..)` / `rethrow` で終わる場合
+                match &error_handler_block.control {
+                    Some(OnFailControl::Return(on_fail_return)) => {
+                        let value = match on_fail_return {
+                            OnFailReturn::Ok(expr) => Value::Ok(Box::new(
+                                self.eval_expression(expr, error_context).await?,
+                            )),
+                            OnFailReturn::Err(expr) => Value::Err(Box::new(
+                                self.eval_expression(expr, error_context).await?,
+                            )),
+                        };
+                        Ok(StatementResult::Control(ControlFlow::Return(value)))
+                    }
+                    Some(OnFailControl::Rethrow) => Err(error),
+                    None => Ok(StatementResult::Value(Value::Unit)),
+                }
             }
         }
     }
@@ -299,7 +513,7 @@ mod tests {
     use event_bus::EventBus;
 
     use crate::{
-        BinaryOperator, Literal,
+        BinaryOperator, Literal, StateAccessPath,
         config::ContextConfig,
         eval::context::{AgentInfo, StateAccessMode},
         provider::provider_registry::ProviderInstance,
@@ -629,6 +843,131 @@ mod tests {
         ));
     }
 
+    fn assign(name: &str, value: i64) -> Statement {
+        Statement::Assignment {
+            target: vec![Expression::Variable(name.to_string())],
+            value: Expression::Literal(Literal::Integer(value)),
+        }
+    }
+
+    fn failing_statement() -> Statement {
+        Statement::Expression(Expression::Variable("undefined".to_string()))
+    }
+
+    #[tokio::test]
+    async fn test_nested_return_skips_remaining_statements() {
+        let evaluator = StatementEvaluator::new(Arc::new(ExpressionEvaluator::new()));
+        let context = setup_context().await;
+
+        let stmt = Statement::Block(vec![
+            Statement::If {
+                condition: Expression::Literal(Literal::Boolean(true)),
+                then_block: vec![
+                    Statement::Block(vec![Statement::If {
+                        condition: Expression::Literal(Literal::Boolean(false)),
+                        then_block: vec![assign("skipped", 1)],
+                        else_block: Some(vec![
+                            Statement::Return(Expression::Literal(Literal::Integer(7))),
+                            assign("after_inner", 1),
+                        ]),
+                    }]),
+                    assign("after_block", 1),
+                ],
+                else_block: None,
+            },
+            assign("after_if", 1),
+        ]);
+
+        let result = evaluator
+            .eval_statement(&stmt, context.clone())
+            .await
+            .unwrap();
+        assert!(matches!(
+            result,
+            StatementResult::Control(ControlFlow::Return(Value::Integer(7)))
+        ));
+        for name in ["skipped", "after_inner", "after_block", "after_if"] {
+            assert!(context.get_variable(name).await.is_err(), "{} ran", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_return_from_error_handler() {
+        let evaluator = StatementEvaluator::new(Arc::new(ExpressionEvaluator::new()));
+        let context = setup_context().await;
+
+        // onFail { return Ok(1) } は後続の文を実行せずに値を返す
+        let stmt = Statement::Block(vec![
+            Statement::WithError {
+                statement: Box::new(failing_statement()),
+                error_handler_block: ErrorHandlerBlock {
+                    error_binding: None,
+                    error_handler_statements: vec![],
+                    control: Some(OnFailControl::Return(OnFailReturn::Ok(
+                        Expression::Literal(Literal::Integer(1)),
+                    ))),
+                },
+            },
+            assign("after", 1),
+        ]);
+        let result = evaluator
+            .eval_statement(&stmt, context.clone())
+            .await
+            .unwrap();
+        assert!(matches!(
+            result,
+            StatementResult::Control(ControlFlow::Return(Value::Ok(ref v))) if **v == Value::Integer(1)
+        ));
+        assert!(context.get_variable("after").await.is_err());
+
+        // ハンドラ内の return も同様に伝播する
+        let stmt = Statement::Block(vec![
+            Statement::WithError {
+                statement: Box::new(failing_statement()),
+                error_handler_block: ErrorHandlerBlock {
+                    error_binding: None,
+                    error_handler_statements: vec![
+                        Statement::If {
+                            condition: Expression::Literal(Literal::Boolean(true)),
+                            then_block: vec![Statement::Return(Expression::Literal(
+                                Literal::Integer(2),
+                            ))],
+                            else_block: None,
+                        },
+                        assign("after_return", 1),
+                    ],
+                    control: None,
+                },
+            },
+            assign("after", 1),
+        ]);
+        let result = evaluator
+            .eval_statement(&stmt, context.clone())
+            .await
+            .unwrap();
+        assert!(matches!(
+            result,
+            StatementResult::Control(ControlFlow::Return(Value::Integer(2)))
+        ));
+        assert!(context.get_variable("after").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rethrow_from_error_handler() {
+        let evaluator = StatementEvaluator::new(Arc::new(ExpressionEvaluator::new()));
+        let context = setup_context().await;
+
+        let stmt = Statement::WithError {
+            statement: Box::new(failing_statement()),
+            error_handler_block: ErrorHandlerBlock {
+                error_binding: None,
+                error_handler_statements: vec![],
+                control: Some(OnFailControl::Rethrow),
+            },
+        };
+        assert!(evaluator.eval_statement(&stmt, context).await.is_err());
+    }
+
     // エラーケースのテスト
     #[tokio::test]
     async fn test_error_cases() {
@@ -649,4 +988,625 @@ mod tests {
         let result = evaluator.eval_statement(&stmt, context).await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_think_loop_trips_guardrail_caught_by_on_fail() {
+        use crate::{
+            config::{ExecutionGuardrails, ProviderConfig},
+            eval::budget::Guardrail,
+            event_registry::EventType,
+            provider::{
+                capabilities::common::Capabilities,
+                llm::{LLMResponse, MockProviderLLM},
+                providers::standard::StandardProvider,
+            },
+        };
+
+        // 上限ちょうどの 3 回だけプロバイダーに届く
+        let mut llm = MockProviderLLM::new();
+        llm.expect_name().return_const("mock".to_string());
+        llm.expect_capabilities().returning(Capabilities::default);
+        llm.expect_send_message().times(3).returning(|_, _| {
+            Box::pin(async {
+                Ok(LLMResponse {
+                    content: "again".to_string(),
+                    ..Default::default()
+                })
+            })
+        });
+        let event_bus = Arc::new(EventBus::new(16));
+        let (mut event_rx, _) = event_bus.subscribe();
+        let context = Arc::new(
+            ExecutionContext::new(
+                event_bus,
+                AgentInfo {
+                    agent_name: "looper".to_string(),
+                    ..Default::default()
+                },
+                StateAccessMode::ReadWrite,
+                ContextConfig::default(),
+                Arc::new(ProviderInstance {
+                    config: ProviderConfig::default(),
+                    provider: Arc::new(StandardProvider::new(llm, vec![])),
+                    secret: Default::default(),
+                }),
+                Arc::new(DashMap::new()),
+                vec![],
+            )
+            .with_guardrails(ExecutionGuardrails {
+                max_think_calls: 3,
+                ..Default::default()
+            })
+            .with_new_execution(),
+        );
+        let evaluator = StatementEvaluator::new(Arc::new(ExpressionEvaluator::new()));
+        let think = Statement::Expression(Expression::Think {
+            args: vec![Argument::Positional(Expression::Literal(Literal::String(
+                "once more".to_string(),
+            )))],
+            with_block: None,
+        });
+
+        let stmt = Statement::WithError {
+            statement: Box::new(Statement::Block(vec![think; 10])),
+            error_handler_block: ErrorHandlerBlock {
+                error_binding: Some("err".to_string()),
+                error_handler_statements: vec![],
+                control: Some(OnFailControl::Return(OnFailReturn::Err(
+                    Expression::Variable("err".to_string()),
+                ))),
+            },
+        };
+        let result = evaluator
+            .eval_statement(&stmt, context.clone())
+            .await
+            .unwrap();
+        let StatementResult::Control(ControlFlow::Return(Value::Err(error))) = result else {
+            panic!("unexpected result: {:?}", result);
+        };
+        let Value::Error(message) = *error else {
+            panic!("unexpected error value: {:?}", error);
+        };
+        assert!(message.contains("max_think_calls = 3"), "{}", message);
+        assert!(message.contains("think calls 3"), "{}", message);
+        assert_eq!(
+            context.execution_usage().tripped(),
+            Some(Guardrail::ThinkCalls)
+        );
+
+        // 状態の更新など、ほかのイベントは読み飛ばす
+        let event = loop {
+            let event = event_rx.recv().await.unwrap();
+            if matches!(event.event_type, EventType::GuardrailTripped { .. }) {
+                break event;
+            }
+        };
+        assert_eq!(
+            event.event_type,
+            EventType::GuardrailTripped {
+                agent_name: "looper".to_string()
+            }
+        );
+        assert_eq!(
+            event.parameters.get("think_calls"),
+            Some(&event_bus::Value::Integer(3))
+        );
+    }
+
+    /// 呼び出しごとに `results` の結果を返すプロバイダーで think するコンテキスト
+    fn scripted_think_context(
+        results: Vec<Result<&'static str, &'static str>>,
+        max_think_calls: u32,
+        clock: Arc<crate::clock::MockClock>,
+    ) -> Arc<ExecutionContext> {
+        use crate::{
+            config::{ExecutionGuardrails, ProviderConfig},
+            provider::{
+                capabilities::common::Capabilities,
+                llm::{LLMResponse, MockProviderLLM},
+                providers::standard::StandardProvider,
+                types::ProviderError,
+            },
+        };
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let calls = AtomicUsize::new(0);
+        let mut llm = MockProviderLLM::new();
+        llm.expect_name().return_const("mock".to_string());
+        llm.expect_capabilities().returning(Capabilities::default);
+        llm.expect_send_message().returning(move |_, _| {
+            let result = results[calls.fetch_add(1, Ordering::SeqCst)]
+                .map(|content| LLMResponse {
+                    content: content.to_string(),
+                    ..Default::default()
+                })
+                .map_err(|message| ProviderError::ApiError(message.to_string()));
+            Box::pin(async move { result })
+        });
+        Arc::new(
+            ExecutionContext::new(
+                Arc::new(EventBus::new(16)),
+                AgentInfo::default(),
+                StateAccessMode::ReadWrite,
+                ContextConfig::default(),
+                Arc::new(ProviderInstance {
+                    config: ProviderConfig::default(),
+                    provider: Arc::new(StandardProvider::new(llm, vec![])),
+                    secret: Default::default(),
+                }),
+                Arc::new(DashMap::new()),
+                vec![],
+            )
+            .with_guardrails(ExecutionGuardrails {
+                max_think_calls,
+                ..Default::default()
+            })
+            .with_clock(clock)
+            .with_new_execution(),
+        )
+    }
+
+    fn retry_think(times: i64, backoff: Option<std::time::Duration>) -> Statement {
+        Statement::Retry {
+            times: Expression::Literal(Literal::Integer(times)),
+            backoff: backoff.map(|backoff| Expression::Literal(Literal::Duration(backoff))),
+            body: vec![Statement::Expression(Expression::Think {
+                args: vec![Argument::Positional(Expression::Literal(Literal::String(
+                    "plan the trip".to_string(),
+                )))],
+                with_block: None,
+            })],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_succeeds_after_failures() {
+        let clock = Arc::new(crate::clock::MockClock::new());
+        let context = scripted_think_context(
+            vec![Err("overloaded"), Err("overloaded"), Ok("done")],
+            3,
+            clock.clone(),
+        );
+        let evaluator = StatementEvaluator::new(Arc::new(ExpressionEvaluator::new()));
+
+        let stmt = retry_think(3, Some(std::time::Duration::from_secs(2)));
+        let result = evaluator
+            .eval_statement(&stmt, context.clone())
+            .await
+            .unwrap();
+
+        let StatementResult::Value(Value::Map(response)) = result else {
+            panic!("unexpected result: {:?}", result);
+        };
+        assert_eq!(
+            response.get("output"),
+            Some(&Value::String("done".to_string()))
+        );
+        // 2 秒待ってから 2 回目、さらに倍の 4 秒待ってから 3 回目
+        assert_eq!(clock.elapsed(), std::time::Duration::from_secs(6));
+        assert_eq!(context.execution_usage().counters().think_calls, 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_exhausted_falls_through_to_on_fail() {
+        let context = scripted_think_context(
+            vec![Err("attempt 1"), Err("attempt 2"), Err("attempt 3")],
+            10,
+            Arc::new(crate::clock::MockClock::new()),
+        );
+        let evaluator = StatementEvaluator::new(Arc::new(ExpressionEvaluator::new()));
+
+        let stmt = Statement::WithError {
+            statement: Box::new(retry_think(3, None)),
+            error_handler_block: ErrorHandlerBlock {
+                error_binding: Some("err".to_string()),
+                error_handler_statements: vec![],
+                control: Some(OnFailControl::Return(OnFailReturn::Err(
+                    Expression::Variable("err".to_string()),
+                ))),
+            },
+        };
+        let result = evaluator
+            .eval_statement(&stmt, context.clone())
+            .await
+            .unwrap();
+
+        let StatementResult::Control(ControlFlow::Return(Value::Err(error))) = result else {
+            panic!("unexpected result: {:?}", result);
+        };
+        // 最後の試行のエラーが onFail に渡る
+        let Value::Error(message) = *error else {
+            panic!("unexpected error value: {:?}", error);
+        };
+        assert!(message.contains("attempt 3"), "{}", message);
+        assert_eq!(context.execution_usage().counters().think_calls, 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_counts_think_calls_across_attempts() {
+        let context = scripted_think_context(
+            vec![Err("overloaded"), Err("overloaded"), Ok("done")],
+            2,
+            Arc::new(crate::clock::MockClock::new()),
+        );
+        let evaluator = StatementEvaluator::new(Arc::new(ExpressionEvaluator::new()));
+
+        let result = evaluator
+            .eval_statement(&retry_think(5, None), context.clone())
+            .await;
+
+        // 上限に達した後は試行を続けない
+        assert!(matches!(
+            result,
+            Err(EvalError::ExecutionBudgetExceeded { .. })
+        ));
+        assert_eq!(context.execution_usage().counters().think_calls, 2);
+    }
+
+    #[tokio::test]
+    async fn test_retry_spends_budget_of_request_chain() {
+        let context = scripted_think_context(
+            vec![Err("overloaded"), Err("overloaded"), Ok("done")],
+            10,
+            Arc::new(crate::clock::MockClock::new()),
+        );
+        // 呼び出し元のリクエストに残っている再試行は 1 回だけ
+        let mut trigger = crate::event_bus::Event::request_builder()
+            .request_type("plan")
+            .requester("caller")
+            .responder("planner")
+            .request_id("chain")
+            .build()
+            .unwrap();
+        crate::request_manager::RetryBudget::new(1).stamp(&mut trigger);
+        let context = Arc::new(context.fork(None).await.with_trigger_event(&trigger));
+        let evaluator = StatementEvaluator::new(Arc::new(ExpressionEvaluator::new()));
+
+        let result = evaluator
+            .eval_statement(&retry_think(3, None), context.clone())
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(context.execution_usage().counters().think_calls, 2);
+        assert_eq!(context.retries_left(), Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_let_evaluates_think_once() {
+        // スクリプトの結果は 1 つだけなので、2 回目の think は呼ばれてはいけない
+        let context = scripted_think_context(
+            vec![Ok("sunny")],
+            10,
+            Arc::new(crate::clock::MockClock::new()),
+        );
+        let evaluator = StatementEvaluator::new(Arc::new(ExpressionEvaluator::new()));
+        let forecast = || Expression::Variable("forecast".to_string());
+
+        let block = vec![
+            Statement::Let {
+                name: "forecast".to_string(),
+                type_info: None,
+                value: Expression::Think {
+                    args: vec![Argument::Positional(Expression::Literal(Literal::String(
+                        "forecast for Tokyo".to_string(),
+                    )))],
+                    with_block: None,
+                },
+            },
+            Statement::Assignment {
+                target: vec![Expression::Variable("first".to_string())],
+                value: forecast(),
+            },
+            Statement::Assignment {
+                target: vec![Expression::Variable("second".to_string())],
+                value: forecast(),
+            },
+        ];
+        evaluator.eval_block(&block, context.clone()).await.unwrap();
+
+        let first = context.get_variable("first").await.unwrap();
+        let Value::Map(response) = &first else {
+            panic!("unexpected value: {:?}", first);
+        };
+        assert_eq!(
+            response.get("output"),
+            Some(&Value::String("sunny".to_string()))
+        );
+        assert_eq!(context.get_variable("second").await.unwrap(), first);
+        assert_eq!(context.execution_usage().counters().think_calls, 1);
+    }
+
+    fn limited_context(limits: crate::config::EvalLimits) -> Arc<ExecutionContext> {
+        Arc::new(
+            ExecutionContext::new(
+                Arc::new(EventBus::new(16)),
+                AgentInfo {
+                    agent_name: "sandboxed".to_string(),
+                    ..Default::default()
+                },
+                StateAccessMode::ReadWrite,
+                ContextConfig::default(),
+                Arc::new(ProviderInstance::default()),
+                Arc::new(DashMap::new()),
+                vec![],
+            )
+            .with_eval_limits(limits)
+            .with_new_execution(),
+        )
+    }
+
+    fn limit_hit(result: EvalResult<StatementResult>) -> crate::eval::limits::EvalLimit {
+        match result {
+            Err(EvalError::LimitExceeded { limit, .. }) => limit,
+            other => panic!("expected LimitExceeded, got {:?}", other),
+        }
+    }
+
+    fn add(left: Expression, right: Expression) -> Expression {
+        Expression::BinaryOp {
+            op: BinaryOperator::Add,
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_doubling_string_trips_allocation_caught_by_on_fail() {
+        use crate::{config::EvalLimits, eval::limits::EvalLimit};
+
+        let context = limited_context(EvalLimits {
+            max_allocation_bytes: 1024 * 1024,
+            ..Default::default()
+        });
+        let evaluator = StatementEvaluator::new(Arc::new(ExpressionEvaluator::new()));
+        let s = || Expression::Variable("s".to_string());
+        let mut doubling = vec![Statement::Assignment {
+            target: vec![s()],
+            value: Expression::Literal(Literal::String("x".to_string())),
+        }];
+        // 64 回倍にすれば、上限がなければ確保しきれない
+        doubling.extend((0..64).map(|_| Statement::Assignment {
+            target: vec![s()],
+            value: add(s(), s()),
+        }));
+
+        assert_eq!(
+            limit_hit(
+                evaluator
+                    .eval_statement(&Statement::Block(doubling.clone()), context.clone())
+                    .await
+            ),
+            EvalLimit::Allocation
+        );
+
+        let stmt = Statement::WithError {
+            statement: Box::new(Statement::Block(doubling)),
+            error_handler_block: ErrorHandlerBlock {
+                error_binding: Some("err".to_string()),
+                error_handler_statements: vec![],
+                control: Some(OnFailControl::Return(OnFailReturn::Err(
+                    Expression::Variable("err".to_string()),
+                ))),
+            },
+        };
+        let result = evaluator.eval_statement(&stmt, context).await.unwrap();
+        let StatementResult::Control(ControlFlow::Return(Value::Err(error))) = result else {
+            panic!("unexpected result: {:?}", result);
+        };
+        assert!(
+            error
+                .to_string()
+                .contains("agent sandboxed reached 2097151 against max_allocation_bytes = 1048576"),
+            "{}",
+            error
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deep_nested_expression_trips_depth() {
+        use crate::{config::EvalLimits, eval::limits::EvalLimit};
+
+        let one = || Expression::Literal(Literal::Integer(1));
+        let nest = |depth: usize| {
+            Statement::Expression((0..depth).fold(one(), |inner, _| add(one(), inner)))
+        };
+        let evaluator = StatementEvaluator::new(Arc::new(ExpressionEvaluator::new()));
+
+        let shallow = limited_context(EvalLimits {
+            max_depth: 16,
+            ..Default::default()
+        });
+        assert_eq!(
+            limit_hit(evaluator.eval_statement(&nest(20), shallow.clone()).await),
+            EvalLimit::Depth
+        );
+        // 失敗したあともフレームは数え戻される
+        let result = evaluator
+            .eval_statement(&Statement::Expression(add(one(), one())), shallow)
+            .await
+            .unwrap();
+        assert!(matches!(result, StatementResult::Value(Value::Integer(2))));
+
+        // 既定の上限はスタックが尽きる前に止める
+        assert_eq!(
+            limit_hit(
+                evaluator
+                    .eval_statement(&nest(1000), limited_context(EvalLimits::default()))
+                    .await
+            ),
+            EvalLimit::Depth
+        );
+        // 既定の上限では普通に評価できる
+        let result = evaluator
+            .eval_statement(&nest(20), limited_context(EvalLimits::default()))
+            .await
+            .unwrap();
+        assert!(matches!(result, StatementResult::Value(Value::Integer(21))));
+    }
+
+    #[tokio::test]
+    async fn test_enormous_list_literal_trips_value_size() {
+        use crate::{config::EvalLimits, eval::limits::EvalLimit};
+
+        let context = limited_context(EvalLimits {
+            max_value_size: 100_000,
+            ..Default::default()
+        });
+        let evaluator = StatementEvaluator::new(Arc::new(ExpressionEvaluator::new()));
+        let list = |len: usize| {
+            Statement::Expression(Expression::Literal(Literal::List(vec![
+                Literal::Integer(0);
+                len
+            ])))
+        };
+
+        assert_eq!(
+            limit_hit(
+                evaluator
+                    .eval_statement(&list(100_001), context.clone())
+                    .await
+            ),
+            EvalLimit::ValueSize
+        );
+        assert!(
+            evaluator
+                .eval_statement(&list(100_000), context)
+                .await
+                .is_ok()
+        );
+    }
+
+    /// first と second を書き、それぞれの後にイベントを発行する本体。
+    /// second に未定義の変数を代入すると、2 つの書き込みの間で失敗する
+    fn two_writes(second: Expression, immediate: bool) -> Statement {
+        let state = |name: &str| Expression::StateAccess(StateAccessPath(vec![name.to_string()]));
+        let emit = |name: &str| Statement::Emit {
+            event_type: EventType::Custom(name.to_string()),
+            parameters: vec![],
+            target: None,
+            immediate,
+        };
+        Statement::Block(vec![
+            Statement::Assignment {
+                target: vec![state("first")],
+                value: Expression::Literal(Literal::Integer(1)),
+            },
+            emit("FirstWritten"),
+            Statement::Assignment {
+                target: vec![state("second")],
+                value: second,
+            },
+            emit("SecondWritten"),
+        ])
+    }
+
+    async fn transaction_test_context() -> (ExecutionContext, event_bus::EventReceiver) {
+        let event_bus = Arc::new(EventBus::new(16));
+        let base = ExecutionContext::new(
+            event_bus.clone(),
+            AgentInfo {
+                agent_name: "Counter".to_string(),
+                ..Default::default()
+            },
+            StateAccessMode::ReadWrite,
+            ContextConfig::default(),
+            Arc::new(ProviderInstance::default()),
+            Arc::new(DashMap::new()),
+            vec![],
+        );
+        base.set_state("first", Value::Integer(0)).unwrap();
+        base.set_state("second", Value::Integer(0)).unwrap();
+        // 初期値の通知の後に購読する
+        (base, event_bus.subscribe().0)
+    }
+
+    /// ランタイムと同じく、ハンドラごとにトランザクションを持つコンテキスト
+    async fn handler_context(base: &ExecutionContext) -> Arc<ExecutionContext> {
+        Arc::new(
+            base.fork(Some(StateAccessMode::ReadWrite))
+                .await
+                .with_transaction(),
+        )
+    }
+
+    fn published(events: &mut event_bus::EventReceiver) -> Vec<String> {
+        std::iter::from_fn(|| events.receiver.try_recv().ok())
+            .map(|event| event.event_type.to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_handler_commits_all_writes_or_none() {
+        let evaluator = StatementEvaluator::new(Arc::new(ExpressionEvaluator::new()));
+        let (base, mut events) = transaction_test_context().await;
+
+        // 2 つの書き込みの間で失敗すると、どちらも反映されずイベントも発行されない
+        let context = handler_context(&base).await;
+        let failing = two_writes(Expression::Variable("undefined".to_string()), false);
+        assert!(
+            evaluator
+                .eval_statement(&failing, context.clone())
+                .await
+                .is_err()
+        );
+        // 実行中は自分の書き込みが読める
+        assert_eq!(context.get_state("first").await.unwrap(), Value::Integer(1));
+        drop(context);
+        assert_eq!(base.get_state("first").await.unwrap(), Value::Integer(0));
+        assert_eq!(base.get_state("second").await.unwrap(), Value::Integer(0));
+        assert!(published(&mut events).is_empty());
+
+        // 成功すると両方を反映し、状態の通知、保留したイベントの順に発行する
+        let context = handler_context(&base).await;
+        let succeeding = two_writes(Expression::Literal(Literal::Integer(2)), false);
+        evaluator
+            .eval_statement(&succeeding, context.clone())
+            .await
+            .unwrap();
+        assert_eq!(base.get_state("first").await.unwrap(), Value::Integer(0));
+        assert!(published(&mut events).is_empty());
+
+        context.commit().await.unwrap();
+        assert_eq!(base.get_state("first").await.unwrap(), Value::Integer(1));
+        assert_eq!(base.get_state("second").await.unwrap(), Value::Integer(2));
+        assert_eq!(
+            published(&mut events),
+            [
+                "StateUpdated(Counter.first)",
+                "StateUpdated(Counter.second)",
+                "FirstWritten",
+                "SecondWritten",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_on_fail_drops_writes_of_failed_statement() {
+        let evaluator = StatementEvaluator::new(Arc::new(ExpressionEvaluator::new()));
+        let (base, mut events) = transaction_test_context().await;
+        let context = handler_context(&base).await;
+
+        // onFail で処理を続けても、失敗した文の書き込みは残らない。emit! は発行済み
+        let stmt = Statement::WithError {
+            statement: Box::new(two_writes(
+                Expression::Variable("undefined".to_string()),
+                true,
+            )),
+            error_handler_block: ErrorHandlerBlock {
+                error_binding: None,
+                error_handler_statements: vec![],
+                control: None,
+            },
+        };
+        evaluator
+            .eval_statement(&stmt, context.clone())
+            .await
+            .unwrap();
+        assert_eq!(context.get_state("first").await.unwrap(), Value::Integer(0));
+        assert_eq!(published(&mut events), ["FirstWritten"]);
+
+        context.commit().await.unwrap();
+        assert_eq!(base.get_state("first").await.unwrap(), Value::Integer(0));
+        assert!(published(&mut events).is_empty());
+    }
 }
@@ -0,0 +1,98 @@
+//! # Template Rendering
+//!
+//! Think prompts and personas interpolate values with `${name}`. Values are rendered
+//! in the locale of the execution, see [`ExecutionContext::locale`]:
+//!
+//! | value                           | `en`                     | `ja`                      |
+//! |---------------------------------|--------------------------|---------------------------|
+//! | `Timestamp` (RFC 3339 string)   | `May 1, 2024 09:30 UTC`  | `2024年5月1日 09:30 UTC`  |
+//! | `Float`                         | `1,234.5`                | `1,234.5`                 |
+//!
+//! Timestamps are RFC 3339 strings at runtime, so any string that parses as one is
+//! rendered as a date, in UTC. Other values render as before.
+//!
+//! [`ExecutionContext::locale`]: super::context::ExecutionContext::locale
+
+use chrono::{DateTime, Utc};
+
+use super::expression::Value;
+use crate::message_catalog::Locale;
+
+/// `value` as interpolated into a template rendered in `locale`
+pub fn render_value(value: &Value, locale: Locale) -> String {
+    match value {
+        Value::String(s) => match DateTime::parse_from_rfc3339(s) {
+            Ok(timestamp) => render_timestamp(&timestamp.with_timezone(&Utc), locale),
+            Err(_) => s.clone(),
+        },
+        Value::Float(f) => render_float(*f, locale),
+        other => other.to_string(),
+    }
+}
+
+fn render_timestamp(timestamp: &DateTime<Utc>, locale: Locale) -> String {
+    let format = match locale {
+        Locale::En => "%B %-d, %Y %H:%M UTC",
+        Locale::Ja => "%Y年%-m月%-d日 %H:%M UTC",
+    };
+    timestamp.format(format).to_string()
+}
+
+fn render_float(value: f64, locale: Locale) -> String {
+    if !value.is_finite() {
+        return value.to_string();
+    }
+    // 英語と日本語はどちらも 3 桁ごとにカンマ、小数点はピリオド
+    let (group_separator, decimal_separator) = match locale {
+        Locale::En | Locale::Ja => (',', '.'),
+    };
+    let text = value.abs().to_string();
+    let (integer, fraction) = text.split_once('.').unwrap_or((&text, ""));
+    let mut rendered = String::new();
+    if value.is_sign_negative() && value != 0.0 {
+        rendered.push('-');
+    }
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            rendered.push(group_separator);
+        }
+        rendered.push(digit);
+    }
+    if !fraction.is_empty() {
+        rendered.push(decimal_separator);
+        rendered.push_str(fraction);
+    }
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_timestamp() {
+        let timestamp = Value::String("2024-05-01T18:30:00+09:00".to_string());
+        assert_eq!(
+            render_value(&timestamp, Locale::En),
+            "May 1, 2024 09:30 UTC"
+        );
+        assert_eq!(
+            render_value(&timestamp, Locale::Ja),
+            "2024年5月1日 09:30 UTC"
+        );
+        // 日付でない文字列はそのまま
+        let text = Value::String("2024-05-01".to_string());
+        assert_eq!(render_value(&text, Locale::Ja), "2024-05-01");
+    }
+
+    #[test]
+    fn test_render_float() {
+        assert_eq!(render_value(&Value::Float(1234.5), Locale::En), "1,234.5");
+        assert_eq!(
+            render_value(&Value::Float(-1234567.0), Locale::Ja),
+            "-1,234,567"
+        );
+        assert_eq!(render_value(&Value::Float(0.25), Locale::En), "0.25");
+        assert_eq!(render_value(&Value::Float(f64::NAN), Locale::En), "NaN");
+    }
+}
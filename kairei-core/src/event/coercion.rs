@@ -0,0 +1,527 @@
+//! Coercion of JSON parameters into event [`Value`]s.
+//!
+//! Clients outside the runtime send parameters as JSON, which cannot tell a
+//! `Duration` from an `Int` or a `Timestamp` from a `String`. When the target
+//! types are known, from a registered event or an answer handler's parameters,
+//! [`coerce_parameters`] converts each value into the variant the handler expects:
+//!
+//! | Target      | Accepted JSON                                                    |
+//! |-------------|------------------------------------------------------------------|
+//! | `String`    | string                                                           |
+//! | `Int`       | integer, float without fraction, numeric string (`"42"`)         |
+//! | `Float`     | number, numeric string (`"4.2"`)                                 |
+//! | `Boolean`   | boolean, `"true"` / `"false"`                                    |
+//! | `Duration`  | non-negative integer milliseconds, DSL duration (`"5s"`, `"150ms"`, `"2min"`, `"1h"`) |
+//! | `DateTime`  | RFC 3339 string, normalized to UTC                               |
+//! | `List<T>`   | array, each element coerced to `T`                               |
+//! | `Map<K, V>` | object, each value coerced to `V`                                |
+//! | `Set<T>`    | array or `{"$set": [...]}`, each element coerced to `T`; duplicates merge |
+//!
+//! Any other value for these targets is rejected with a [`CoercionError`] naming
+//! the parameter, rather than guessed at. `null`, `Json`, custom types and
+//! parameters without a known type are passed through as they are. Without a
+//! target type, only `{"$set": [...]}` becomes a set; a plain array stays a list.
+//!
+//! When the agent declares a contract for the request, [`coerce_contract_parameters`]
+//! additionally requires every parameter not typed `Option<T>` and rejects
+//! parameters the contract does not declare.
+
+use std::{collections::HashMap, time::Duration};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use thiserror::Error;
+
+use super::{event_bus::Value, event_registry::ParameterType};
+use crate::{
+    Parameter, RequestContract, TypeInfo,
+    eval::set::{SET_JSON_MARKER, SetElement, ValueSet},
+};
+
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("Parameter '{parameter}' expects {expected}: {reason}")]
+pub struct CoercionError {
+    /// Path of the rejected value, e.g. `delays[1]` or `limits.max`
+    pub parameter: String,
+    pub expected: String,
+    pub reason: String,
+}
+
+/// Parameter types of a handler, keyed by parameter name. Parameters whose type
+/// has no coercion rule are left out.
+pub fn parameter_schema(parameters: &[Parameter]) -> HashMap<String, ParameterType> {
+    parameters
+        .iter()
+        .filter_map(|p| parameter_type(&p.type_info).map(|t| (p.name.clone(), t)))
+        .collect()
+}
+
+/// The coercion target of a DSL type. `Option<T>` coerces like `T`.
+pub fn parameter_type(type_info: &TypeInfo) -> Option<ParameterType> {
+    match type_info {
+        TypeInfo::Simple(name) => match name.as_str() {
+            "String" => Some(ParameterType::String),
+            "Int" => Some(ParameterType::Int),
+            "Float" => Some(ParameterType::Float),
+            "Boolean" => Some(ParameterType::Boolean),
+            "Duration" => Some(ParameterType::Duration),
+            "Timestamp" | "DateTime" => Some(ParameterType::DateTime),
+            _ => None,
+        },
+        TypeInfo::Option(inner) => parameter_type(inner),
+        TypeInfo::Array(inner) => Some(ParameterType::List(Box::new(
+            parameter_type(inner).unwrap_or(ParameterType::Json),
+        ))),
+        TypeInfo::Map(key, value) => Some(ParameterType::Map(
+            Box::new(parameter_type(key).unwrap_or(ParameterType::String)),
+            Box::new(parameter_type(value).unwrap_or(ParameterType::Json)),
+        )),
+        TypeInfo::Set(element) => Some(ParameterType::Set(Box::new(
+            parameter_type(element).unwrap_or(ParameterType::Json),
+        ))),
+        TypeInfo::Result { .. } | TypeInfo::Custom { .. } | TypeInfo::Generic { .. } => None,
+    }
+}
+
+/// Coerce a JSON object of parameters against `schema`.
+/// `null` stands for no parameters; any other non-object is rejected.
+pub fn coerce_parameters(
+    parameters: &serde_json::Value,
+    schema: &HashMap<String, ParameterType>,
+) -> Result<HashMap<String, Value>, CoercionError> {
+    let object = match parameters {
+        serde_json::Value::Null => return Ok(HashMap::new()),
+        serde_json::Value::Object(object) => object,
+        other => {
+            return Err(CoercionError {
+                parameter: "payload".to_string(),
+                expected: "an object of parameters".to_string(),
+                reason: format!("got {}", kind(other)),
+            });
+        }
+    };
+    object
+        .iter()
+        .map(|(name, value)| {
+            let value = match schema.get(name) {
+                Some(target) => coerce(name, value, target)?,
+                None => from_json(value),
+            };
+            Ok((name.clone(), value))
+        })
+        .collect()
+}
+
+/// Coerce the parameters of a request against the contract declared for it
+pub fn coerce_contract_parameters(
+    parameters: &serde_json::Value,
+    contract: &RequestContract,
+) -> Result<HashMap<String, Value>, CoercionError> {
+    let coerced = coerce_parameters(parameters, &parameter_schema(&contract.parameters))?;
+
+    // エラーを決定的にするため、未宣言のパラメータは名前順で最初のものを報告する
+    if let Some(unknown) = coerced
+        .keys()
+        .filter(|name| !contract.parameters.iter().any(|p| &p.name == *name))
+        .min()
+    {
+        return Err(CoercionError {
+            parameter: unknown.clone(),
+            expected: "no such parameter".to_string(),
+            reason: format!("not declared by the contract of {}", contract.request_type),
+        });
+    }
+    for parameter in &contract.parameters {
+        let given = !matches!(coerced.get(&parameter.name), None | Some(Value::Null));
+        if !given && !matches!(parameter.type_info, TypeInfo::Option(_)) {
+            return Err(CoercionError {
+                parameter: parameter.name.clone(),
+                expected: parameter.type_info.to_string(),
+                reason: "required by the contract".to_string(),
+            });
+        }
+    }
+    Ok(coerced)
+}
+
+/// Coerce one JSON value to `target`. `name` is used in the error.
+pub fn coerce(
+    name: &str,
+    value: &serde_json::Value,
+    target: &ParameterType,
+) -> Result<Value, CoercionError> {
+    use serde_json::Value as Json;
+
+    let reject = |reason: String| CoercionError {
+        parameter: name.to_string(),
+        expected: target.to_string(),
+        reason,
+    };
+    match (target, value) {
+        (_, Json::Null) => Ok(Value::Null),
+        (ParameterType::Json | ParameterType::Custom(_), value) => Ok(from_json(value)),
+
+        (ParameterType::String, Json::String(s)) => Ok(Value::String(s.clone())),
+
+        (ParameterType::Int, Json::Number(n)) => match (n.as_i64(), n.as_f64()) {
+            (Some(i), _) => Ok(Value::Integer(i)),
+            (None, Some(f)) if f.fract() == 0.0 && f.abs() < i64::MAX as f64 => {
+                Ok(Value::Integer(f as i64))
+            }
+            _ => Err(reject(format!("{} is not a whole number in range", n))),
+        },
+        (ParameterType::Int, Json::String(s)) => s
+            .trim()
+            .parse::<i64>()
+            .map(Value::Integer)
+            .map_err(|_| reject(format!("\"{}\" is not an integer", s))),
+
+        (ParameterType::Float, Json::Number(n)) => n
+            .as_f64()
+            .map(Value::Float)
+            .ok_or_else(|| reject(format!("{} is out of range", n))),
+        (ParameterType::Float, Json::String(s)) => match s.trim().parse::<f64>() {
+            Ok(f) if f.is_finite() => Ok(Value::Float(f)),
+            _ => Err(reject(format!("\"{}\" is not a number", s))),
+        },
+
+        (ParameterType::Boolean, Json::Bool(b)) => Ok(Value::Boolean(*b)),
+        (ParameterType::Boolean, Json::String(s)) => match s.as_str() {
+            "true" => Ok(Value::Boolean(true)),
+            "false" => Ok(Value::Boolean(false)),
+            _ => Err(reject(format!("\"{}\" is not true or false", s))),
+        },
+
+        (ParameterType::Duration, Json::Number(n)) => n
+            .as_u64()
+            .map(|ms| Value::Duration(Duration::from_millis(ms)))
+            .ok_or_else(|| reject(format!("{} is not a whole number of milliseconds", n))),
+        (ParameterType::Duration, Json::String(s)) => parse_duration(s)
+            .map(Value::Duration)
+            .ok_or_else(|| reject(format!("\"{}\" is not a duration like \"5s\"", s))),
+
+        (ParameterType::DateTime, Json::String(s)) => DateTime::parse_from_rfc3339(s)
+            .map(|t| {
+                Value::String(
+                    t.with_timezone(&Utc)
+                        .to_rfc3339_opts(SecondsFormat::AutoSi, true),
+                )
+            })
+            .map_err(|_| reject(format!("\"{}\" is not an RFC 3339 timestamp", s))),
+
+        (ParameterType::List(item), Json::Array(items)) => items
+            .iter()
+            .enumerate()
+            .map(|(i, v)| coerce(&format!("{}[{}]", name, i), v, item))
+            .collect::<Result<_, _>>()
+            .map(Value::List),
+        (ParameterType::Set(item), Json::Object(entries)) if entries.len() == 1 => {
+            match entries.get(SET_JSON_MARKER) {
+                Some(Json::Array(items)) => coerce_set(name, items, item),
+                _ => Err(reject(format!(
+                    "got an object without \"{}\"",
+                    SET_JSON_MARKER
+                ))),
+            }
+        }
+        (ParameterType::Set(item), Json::Array(items)) => coerce_set(name, items, item),
+
+        (ParameterType::Map(_, item), Json::Object(entries)) => entries
+            .iter()
+            .map(|(k, v)| Ok((k.clone(), coerce(&format!("{}.{}", name, k), v, item)?)))
+            .collect::<Result<_, _>>()
+            .map(Value::Map),
+
+        (_, value) => Err(reject(format!("got {}", kind(value)))),
+    }
+}
+
+fn coerce_set(
+    name: &str,
+    items: &[serde_json::Value],
+    item: &ParameterType,
+) -> Result<Value, CoercionError> {
+    items
+        .iter()
+        .enumerate()
+        .map(|(index, v)| {
+            let name = format!("{}[{}]", name, index);
+            let element = match coerce(&name, v, item)? {
+                Value::Boolean(b) => SetElement::Boolean(b),
+                Value::Integer(i) => SetElement::Integer(i),
+                Value::String(s) => SetElement::String(s),
+                _ => {
+                    return Err(CoercionError {
+                        parameter: name,
+                        expected: "Boolean, Int or String".to_string(),
+                        reason: format!("{} cannot be a set element", kind(v)),
+                    });
+                }
+            };
+            Ok(element)
+        })
+        .collect::<Result<ValueSet, _>>()
+        .map(Value::Set)
+}
+
+/// Convert JSON without a target type
+pub fn from_json(value: &serde_json::Value) -> Value {
+    if let Some(set) = ValueSet::from_json(value) {
+        return Value::Set(set);
+    }
+    match value {
+        serde_json::Value::Null => Value::Null,
+        serde_json::Value::Bool(b) => Value::Boolean(*b),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => Value::Integer(i),
+            None => Value::Float(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        serde_json::Value::String(s) => Value::String(s.clone()),
+        serde_json::Value::Array(items) => Value::List(items.iter().map(from_json).collect()),
+        serde_json::Value::Object(entries) => Value::Map(
+            entries
+                .iter()
+                .map(|(k, v)| (k.clone(), from_json(v)))
+                .collect(),
+        ),
+    }
+}
+
+/// DSL の Duration リテラルと同じ単位 (ms, s, min, h) を受け付ける
+fn parse_duration(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = s.split_at(split);
+    let amount: u64 = amount.parse().ok()?;
+    match unit.trim() {
+        "ms" => Some(Duration::from_millis(amount)),
+        "s" => Some(Duration::from_secs(amount)),
+        "min" => amount.checked_mul(60).map(Duration::from_secs),
+        "h" => amount.checked_mul(60 * 60).map(Duration::from_secs),
+        _ => None,
+    }
+}
+
+fn kind(value: &serde_json::Value) -> &'static str {
+    match value {
+        serde_json::Value::Null => "null",
+        serde_json::Value::Bool(_) => "a boolean",
+        serde_json::Value::Number(_) => "a number",
+        serde_json::Value::String(_) => "a string",
+        serde_json::Value::Array(_) => "an array",
+        serde_json::Value::Object(_) => "an object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn ok(value: serde_json::Value, target: ParameterType) -> Value {
+        coerce("p", &value, &target).unwrap()
+    }
+
+    fn rejected(value: serde_json::Value, target: ParameterType) -> CoercionError {
+        coerce("p", &value, &target).unwrap_err()
+    }
+
+    #[test]
+    fn test_duration_from_millis_and_units() {
+        let ms = |ms| Value::Duration(Duration::from_millis(ms));
+        assert_eq!(ok(json!(1500), ParameterType::Duration), ms(1500));
+        assert_eq!(ok(json!("150ms"), ParameterType::Duration), ms(150));
+        assert_eq!(ok(json!("5s"), ParameterType::Duration), ms(5_000));
+        assert_eq!(ok(json!("2min"), ParameterType::Duration), ms(120_000));
+        assert_eq!(ok(json!("1h"), ParameterType::Duration), ms(3_600_000));
+
+        // 単位のない文字列、負数、小数は曖昧なので拒否する
+        rejected(json!("5000"), ParameterType::Duration);
+        rejected(json!(-1), ParameterType::Duration);
+        rejected(json!(1.5), ParameterType::Duration);
+        rejected(json!("5 days"), ParameterType::Duration);
+    }
+
+    #[test]
+    fn test_int_from_numbers_and_numeric_strings() {
+        assert_eq!(ok(json!(42), ParameterType::Int), Value::Integer(42));
+        assert_eq!(ok(json!(42.0), ParameterType::Int), Value::Integer(42));
+        assert_eq!(ok(json!(" -7 "), ParameterType::Int), Value::Integer(-7));
+
+        let error = rejected(json!(4.2), ParameterType::Int);
+        assert_eq!(error.expected, "Int");
+        rejected(json!("4.2"), ParameterType::Int);
+        rejected(json!("many"), ParameterType::Int);
+        rejected(json!(true), ParameterType::Int);
+    }
+
+    #[test]
+    fn test_float_from_numbers_and_numeric_strings() {
+        assert_eq!(ok(json!(1.25), ParameterType::Float), Value::Float(1.25));
+        assert_eq!(ok(json!(3), ParameterType::Float), Value::Float(3.0));
+        assert_eq!(ok(json!("0.5"), ParameterType::Float), Value::Float(0.5));
+
+        rejected(json!("NaN"), ParameterType::Float);
+        rejected(json!("half"), ParameterType::Float);
+    }
+
+    #[test]
+    fn test_boolean_and_string() {
+        assert_eq!(
+            ok(json!(true), ParameterType::Boolean),
+            Value::Boolean(true)
+        );
+        assert_eq!(
+            ok(json!("false"), ParameterType::Boolean),
+            Value::Boolean(false)
+        );
+        rejected(json!("yes"), ParameterType::Boolean);
+        rejected(json!(1), ParameterType::Boolean);
+
+        assert_eq!(
+            ok(json!("hello"), ParameterType::String),
+            Value::String("hello".to_string())
+        );
+        rejected(json!(5), ParameterType::String);
+    }
+
+    #[test]
+    fn test_timestamp_normalized_to_utc() {
+        assert_eq!(
+            ok(json!("2024-05-01T09:00:00+09:00"), ParameterType::DateTime),
+            Value::String("2024-05-01T00:00:00Z".to_string())
+        );
+        rejected(json!("2024-05-01"), ParameterType::DateTime);
+        rejected(json!(1714521600), ParameterType::DateTime);
+    }
+
+    #[test]
+    fn test_nested_values_name_the_element() {
+        let target = ParameterType::List(Box::new(ParameterType::Duration));
+        assert_eq!(
+            ok(json!(["1s", 10]), target.clone()),
+            Value::List(vec![
+                Value::Duration(Duration::from_secs(1)),
+                Value::Duration(Duration::from_millis(10)),
+            ])
+        );
+        assert_eq!(rejected(json!(["1s", "soon"]), target).parameter, "p[1]");
+
+        let target = ParameterType::Map(
+            Box::new(ParameterType::String),
+            Box::new(ParameterType::Int),
+        );
+        assert_eq!(rejected(json!({"max": "lots"}), target).parameter, "p.max");
+    }
+
+    #[test]
+    fn test_set_from_array_or_marked_object() {
+        let target = ParameterType::Set(Box::new(ParameterType::Int));
+        let expected = Value::Set(
+            [SetElement::Integer(1), SetElement::Integer(2)]
+                .into_iter()
+                .collect(),
+        );
+        // 重複は取り除かれ、順序は問わない
+        assert_eq!(ok(json!([2, 1, 2]), target.clone()), expected);
+        assert_eq!(ok(json!({"$set": [1, 2]}), target.clone()), expected);
+        assert_eq!(
+            rejected(json!([1, "two"]), target.clone()).parameter,
+            "p[1]"
+        );
+        rejected(json!({"items": [1]}), target);
+
+        let error = rejected(
+            json!([1.5]),
+            ParameterType::Set(Box::new(ParameterType::Float)),
+        );
+        assert_eq!(error.parameter, "p[0]");
+
+        // 共有メモリなど JSON を経由しても集合のまま戻る
+        assert_eq!(from_json(&serde_json::Value::from(&expected)), expected);
+    }
+
+    #[test]
+    fn test_coerce_parameters_against_handler_schema() {
+        let schema = parameter_schema(&[
+            Parameter {
+                name: "timeout".to_string(),
+                type_info: TypeInfo::Simple("Duration".to_string()),
+                constraint: None,
+            },
+            Parameter {
+                name: "retries".to_string(),
+                type_info: TypeInfo::Option(Box::new(TypeInfo::Simple("Int".to_string()))),
+                constraint: None,
+            },
+        ]);
+
+        let params = coerce_parameters(
+            &json!({"timeout": "5s", "retries": "3", "note": 1.5}),
+            &schema,
+        )
+        .unwrap();
+        assert_eq!(
+            params.get("timeout"),
+            Some(&Value::Duration(Duration::from_secs(5)))
+        );
+        assert_eq!(params.get("retries"), Some(&Value::Integer(3)));
+        // スキーマにないパラメータはそのまま渡す
+        assert_eq!(params.get("note"), Some(&Value::Float(1.5)));
+
+        let error = coerce_parameters(&json!({"timeout": true}), &schema).unwrap_err();
+        assert_eq!(error.parameter, "timeout");
+        assert_eq!(error.expected, "Duration");
+
+        assert!(coerce_parameters(&json!(null), &schema).unwrap().is_empty());
+        assert_eq!(
+            coerce_parameters(&json!([1]), &schema)
+                .unwrap_err()
+                .parameter,
+            "payload"
+        );
+    }
+
+    #[test]
+    fn test_coerce_parameters_against_contract() {
+        let contract = RequestContract {
+            request_type: "GetForecast".into(),
+            parameters: vec![
+                Parameter {
+                    name: "city".to_string(),
+                    type_info: TypeInfo::Simple("String".to_string()),
+                    constraint: None,
+                },
+                Parameter {
+                    name: "days".to_string(),
+                    type_info: TypeInfo::Option(Box::new(TypeInfo::Simple("Int".to_string()))),
+                    constraint: None,
+                },
+            ],
+            return_type: TypeInfo::Simple("String".to_string()),
+            doc: None,
+        };
+
+        let params =
+            coerce_contract_parameters(&json!({"city": "Tokyo", "days": "3"}), &contract).unwrap();
+        assert_eq!(params.get("days"), Some(&Value::Integer(3)));
+        // Option のパラメータは省略できる
+        coerce_contract_parameters(&json!({"city": "Tokyo"}), &contract).unwrap();
+
+        let error = coerce_contract_parameters(&json!({"days": 3}), &contract).unwrap_err();
+        assert_eq!(error.parameter, "city");
+        assert_eq!(error.expected, "String");
+        let error = coerce_contract_parameters(&json!({"city": null}), &contract).unwrap_err();
+        assert_eq!(error.parameter, "city");
+
+        let error = coerce_contract_parameters(
+            &json!({"city": "Tokyo", "unit": "C", "lang": "ja"}),
+            &contract,
+        )
+        .unwrap_err();
+        assert_eq!(error.parameter, "lang");
+
+        let error = coerce_contract_parameters(&json!({"city": 42}), &contract).unwrap_err();
+        assert_eq!(error.parameter, "city");
+    }
+}
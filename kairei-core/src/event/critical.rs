@@ -0,0 +1,708 @@
+//! # Critical Event Delivery
+//!
+//! [`EventBus::publish`] broadcasts an event and forgets it: a subscriber lagging
+//! behind the channel capacity skips it. Events that must reach their consumers, such
+//! as billing or audit records, are published with [`EventBus::publish_critical`].
+//!
+//! A consumer registers under a name with the event types it must acknowledge
+//! ([`EventBus::register_critical_consumer`]) and receives each critical event of
+//! those types as a [`CriticalDelivery`] on its own [`CriticalReceiver`]. Once the
+//! event is processed it calls [`AckHandle::ack`] on the handle of the delivery.
+//!
+//! A critical event stays in a bounded buffer until every consumer registered for
+//! its type at publish time has acknowledged it. Every `retry_interval` it is
+//! delivered again to the consumers that have not. After `max_attempts` deliveries
+//! the bus publishes an [`EventType::CriticalDeliveryFailed`] system event and stops
+//! retrying; the event stays buffered until acknowledged. The [`DeliveryReceipt`]
+//! returned on publish resolves once all consumers acknowledged, or when `deadline`
+//! passes (see [`CriticalDeliveryConfig`]).
+//!
+//! With a store the buffer is mirrored in the [`CRITICAL_NAMESPACE`] namespace,
+//! written before each delivery. [`EventBus::redeliver_critical`] resumes the
+//! events a previous run left unacknowledged. A consumer may thus see an event more
+//! than once, and must handle it idempotently.
+//!
+//! [`EventBus::publish`]: super::event_bus::EventBus::publish
+//! [`EventBus::publish_critical`]: super::event_bus::EventBus::publish_critical
+//! [`EventBus::register_critical_consumer`]: super::event_bus::EventBus::register_critical_consumer
+//! [`EventBus::redeliver_critical`]: super::event_bus::EventBus::redeliver_critical
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, Weak},
+    task::{Context, Poll},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tracing::warn;
+use uuid::Uuid;
+
+use super::{
+    event_bus::{Event, EventError, EventResult, Value},
+    event_registry::EventType,
+    lineage::EventLineageLog,
+    wire,
+};
+use crate::{
+    config::CriticalDeliveryConfig,
+    persistence::{self, Versioned},
+    provider::capabilities::{
+        shared_memory::Metadata,
+        storage::{StorageBackend, StorageError, ValueWithMetadata},
+    },
+};
+
+/// Storage namespace mirroring the unacknowledged critical events, keyed by event ID
+pub const CRITICAL_NAMESPACE: &str = "critical_events";
+
+/// A critical event delivered to one consumer
+pub struct CriticalDelivery {
+    pub event: Event,
+    /// Number of this delivery of the event, 1 for the first
+    pub attempt: u32,
+    /// Acknowledges the event once processed
+    pub ack: AckHandle,
+}
+
+/// Acknowledges a critical event for one consumer. Dropping it without calling
+/// [`AckHandle::ack`] leaves the event to be delivered again.
+pub struct AckHandle {
+    event_id: String,
+    consumer: String,
+    shared: Arc<CriticalShared>,
+}
+
+impl AckHandle {
+    pub fn event_id(&self) -> &str {
+        &self.event_id
+    }
+
+    /// Marks the event as processed by the consumer. Acknowledging an event a
+    /// second time, through the handle of another delivery, has no effect.
+    pub fn ack(self) {
+        self.shared.ack(&self.event_id, &self.consumer);
+    }
+}
+
+impl fmt::Debug for AckHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AckHandle")
+            .field("event_id", &self.event_id)
+            .field("consumer", &self.consumer)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Critical events delivered to a registered consumer
+pub struct CriticalReceiver {
+    receiver: mpsc::UnboundedReceiver<CriticalDelivery>,
+}
+
+impl CriticalReceiver {
+    /// The next delivery. `None` once the consumer is registered again or the event
+    /// bus is dropped.
+    pub async fn recv(&mut self) -> Option<CriticalDelivery> {
+        self.receiver.recv().await
+    }
+}
+
+/// Acknowledgement status of a critical event
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeliveryReport {
+    pub event_id: String,
+    /// Whether every consumer registered for the event acknowledged it
+    pub acknowledged: bool,
+    /// Consumers that had not acknowledged, sorted by name
+    pub unacknowledged: Vec<String>,
+    /// Deliveries of the event so far, the first one included
+    pub attempts: u32,
+    /// Whether a `CriticalDeliveryFailed` event was published for it
+    pub escalated: bool,
+}
+
+/// Resolves to the [`DeliveryReport`] of a critical event once every consumer
+/// acknowledged it or the deadline passed
+pub struct DeliveryReceipt {
+    event_id: String,
+    report: Pin<Box<dyn Future<Output = DeliveryReport> + Send>>,
+}
+
+impl DeliveryReceipt {
+    pub fn event_id(&self) -> &str {
+        &self.event_id
+    }
+}
+
+impl Future for DeliveryReceipt {
+    type Output = DeliveryReport;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.get_mut().report.as_mut().poll(cx)
+    }
+}
+
+/// Persisted form of a critical event. Embeds the [`wire`] encoding of the event, so
+/// a new wire version is a new version of this one too.
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredCriticalEvent {
+    event: Vec<u8>,
+    unacknowledged: Vec<String>,
+    attempts: u32,
+}
+
+impl Versioned for StoredCriticalEvent {
+    const KIND: &'static str = "critical event";
+    const VERSION: u32 = 1;
+}
+
+/// Critical consumers and the events awaiting their acknowledgement
+pub(crate) struct CriticalDeliveries {
+    shared: Arc<CriticalShared>,
+}
+
+struct CriticalShared {
+    config: CriticalDeliveryConfig,
+    store: Option<Arc<dyn StorageBackend>>,
+    // 書き込みと削除の順序を保つ
+    store_lock: tokio::sync::Mutex<()>,
+    // ロックは consumers, pending の順に取る
+    consumers: Mutex<HashMap<String, Consumer>>,
+    pending: Mutex<HashMap<String, PendingEvent>>,
+    event_sender: broadcast::Sender<Event>,
+    lineage: Arc<EventLineageLog>,
+}
+
+struct Consumer {
+    event_types: HashSet<EventType>,
+    sender: mpsc::UnboundedSender<CriticalDelivery>,
+}
+
+struct PendingEvent {
+    event: Event,
+    unacknowledged: HashSet<String>,
+    attempts: u32,
+    escalated: bool,
+    done: Option<oneshot::Sender<DeliveryReport>>,
+}
+
+impl PendingEvent {
+    fn report(&self, event_id: &str) -> DeliveryReport {
+        let mut unacknowledged: Vec<_> = self.unacknowledged.iter().cloned().collect();
+        unacknowledged.sort();
+        DeliveryReport {
+            event_id: event_id.to_string(),
+            acknowledged: unacknowledged.is_empty(),
+            unacknowledged,
+            attempts: self.attempts,
+            escalated: self.escalated,
+        }
+    }
+}
+
+impl CriticalDeliveries {
+    pub(crate) fn new(
+        config: CriticalDeliveryConfig,
+        store: Option<Arc<dyn StorageBackend>>,
+        event_sender: broadcast::Sender<Event>,
+        lineage: Arc<EventLineageLog>,
+    ) -> Self {
+        Self {
+            shared: Arc::new(CriticalShared {
+                config,
+                store,
+                store_lock: tokio::sync::Mutex::new(()),
+                consumers: Mutex::new(HashMap::new()),
+                pending: Mutex::new(HashMap::new()),
+                event_sender,
+                lineage,
+            }),
+        }
+    }
+
+    pub(crate) fn register(&self, name: &str, event_types: &[EventType]) -> CriticalReceiver {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let consumer = Consumer {
+            event_types: event_types.iter().cloned().collect(),
+            sender,
+        };
+        self.shared
+            .consumers
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), consumer);
+        CriticalReceiver { receiver }
+    }
+
+    /// Track an event stamped by the bus until its consumers acknowledge it
+    pub(crate) async fn publish(&self, event: &Event) -> EventResult<DeliveryReceipt> {
+        let consumers = self
+            .shared
+            .consumers
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, consumer)| consumer.event_types.contains(&event.event_type))
+            .map(|(name, _)| name.clone())
+            .collect();
+        self.track(event.clone(), consumers, 0).await
+    }
+
+    pub(crate) async fn redeliver(&self) -> EventResult<Vec<DeliveryReceipt>> {
+        let Some(store) = &self.shared.store else {
+            return Ok(vec![]);
+        };
+        let stored = store
+            .load(CRITICAL_NAMESPACE)
+            .await
+            .map_err(|e| EventError::CriticalStore(e.to_string()))?;
+        let mut receipts = vec![];
+        for (event_id, entry) in stored {
+            if self.shared.pending.lock().unwrap().contains_key(&event_id) {
+                continue;
+            }
+            let decoded = persistence::from_stored::<StoredCriticalEvent>(entry.value)
+                .map_err(|e| e.to_string())
+                .and_then(|stored| {
+                    let event = wire::decode_event(&stored.event).map_err(|e| e.to_string())?;
+                    Ok((stored, event))
+                });
+            // 読めないものはストアに残したまま飛ばす
+            let (stored, event) = match decoded {
+                Ok(decoded) => decoded,
+                Err(e) => {
+                    warn!("Skipping unreadable critical event {}: {}", event_id, e);
+                    continue;
+                }
+            };
+            let consumers = stored.unacknowledged.into_iter().collect();
+            receipts.push(self.track(event, consumers, stored.attempts).await?);
+        }
+        Ok(receipts)
+    }
+
+    pub(crate) fn pending(&self) -> Vec<DeliveryReport> {
+        let mut reports: Vec<_> = self
+            .shared
+            .pending
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(event_id, pending)| pending.report(event_id))
+            .collect();
+        reports.sort_by(|a, b| a.event_id.cmp(&b.event_id));
+        reports
+    }
+
+    async fn track(
+        &self,
+        event: Event,
+        consumers: HashSet<String>,
+        attempts: u32,
+    ) -> EventResult<DeliveryReceipt> {
+        let event_id = event.event_id.clone().unwrap_or_default();
+        if consumers.is_empty() {
+            let report = DeliveryReport {
+                event_id: event_id.clone(),
+                acknowledged: true,
+                unacknowledged: vec![],
+                attempts,
+                escalated: false,
+            };
+            return Ok(DeliveryReceipt {
+                event_id,
+                report: Box::pin(std::future::ready(report)),
+            });
+        }
+
+        let (done, acknowledged) = oneshot::channel();
+        {
+            let mut pending = self.shared.pending.lock().unwrap();
+            if pending.len() >= self.shared.config.max_pending {
+                return Err(EventError::CriticalBufferFull {
+                    capacity: self.shared.config.max_pending,
+                });
+            }
+            pending.insert(
+                event_id.clone(),
+                PendingEvent {
+                    event,
+                    unacknowledged: consumers,
+                    attempts,
+                    escalated: false,
+                    done: Some(done),
+                },
+            );
+        }
+        // 最初の配信より前に書き込む
+        self.shared.next_attempt(&event_id);
+        if let Err(e) = self.shared.persist(&event_id).await {
+            self.shared.pending.lock().unwrap().remove(&event_id);
+            return Err(EventError::CriticalStore(e.to_string()));
+        }
+        self.shared.deliver(&event_id);
+        tokio::spawn(CriticalShared::retry(
+            Arc::downgrade(&self.shared),
+            event_id.clone(),
+        ));
+
+        let shared = self.shared.clone();
+        let deadline = self.shared.config.deadline;
+        let id = event_id.clone();
+        let report = async move {
+            match tokio::time::timeout(deadline, acknowledged).await {
+                Ok(Ok(report)) => report,
+                // 期限切れ。イベントは確認されるまでバッファに残る
+                _ => shared.report(&id),
+            }
+        };
+        Ok(DeliveryReceipt {
+            event_id,
+            report: Box::pin(report),
+        })
+    }
+}
+
+impl CriticalShared {
+    fn next_attempt(&self, event_id: &str) {
+        if let Some(entry) = self.pending.lock().unwrap().get_mut(event_id) {
+            entry.attempts += 1;
+        }
+    }
+
+    /// Deliver the event to the consumers that have not acknowledged it
+    fn deliver(self: &Arc<Self>, event_id: &str) {
+        let consumers = self.consumers.lock().unwrap();
+        let pending = self.pending.lock().unwrap();
+        let Some(entry) = pending.get(event_id) else {
+            return;
+        };
+        for name in &entry.unacknowledged {
+            // 登録のない消費者、受信側を閉じた消費者には届かず、次の再送を待つ
+            let Some(consumer) = consumers.get(name) else {
+                continue;
+            };
+            let delivery = CriticalDelivery {
+                event: entry.event.clone(),
+                attempt: entry.attempts,
+                ack: AckHandle {
+                    event_id: event_id.to_string(),
+                    consumer: name.clone(),
+                    shared: self.clone(),
+                },
+            };
+            let _ = consumer.sender.send(delivery);
+        }
+    }
+
+    /// Retry the event until it is acknowledged, escalated or the bus is dropped
+    async fn retry(deliveries: Weak<Self>, event_id: String) {
+        loop {
+            let Some(retry_interval) = deliveries.upgrade().map(|s| s.config.retry_interval) else {
+                return;
+            };
+            tokio::time::sleep(retry_interval).await;
+            let Some(shared) = deliveries.upgrade() else {
+                return;
+            };
+            let alert = {
+                let mut pending = shared.pending.lock().unwrap();
+                let Some(entry) = pending.get_mut(&event_id) else {
+                    return;
+                };
+                if entry.attempts >= shared.config.max_attempts {
+                    entry.escalated = true;
+                    Some(alert_event(&event_id, entry))
+                } else {
+                    entry.attempts += 1;
+                    None
+                }
+            };
+            if let Some(alert) = alert {
+                warn!("Critical event {} was not acknowledged", event_id);
+                let alert = alert.assign_id(Uuid::new_v4().to_string());
+                shared.lineage.record(&alert);
+                if let Err(e) = shared.event_sender.send(alert) {
+                    warn!("Failed to publish critical delivery failure: {}", e);
+                }
+                return;
+            }
+            if let Err(e) = shared.persist(&event_id).await {
+                warn!("Failed to persist critical event {}: {}", event_id, e);
+            }
+            shared.deliver(&event_id);
+        }
+    }
+
+    fn ack(self: &Arc<Self>, event_id: &str, consumer: &str) {
+        let done = {
+            let mut pending = self.pending.lock().unwrap();
+            let Some(entry) = pending.get_mut(event_id) else {
+                return;
+            };
+            if !entry.unacknowledged.remove(consumer) {
+                return;
+            }
+            if entry.unacknowledged.is_empty() {
+                pending.remove(event_id)
+            } else {
+                None
+            }
+        };
+
+        if let Some(mut entry) = done {
+            if let Some(done) = entry.done.take() {
+                let _ = done.send(entry.report(event_id));
+            }
+        }
+        if self.store.is_none() {
+            return;
+        }
+        // 残りの消費者を書き直すか、全員の確認が済んだものを消す
+        let shared = self.clone();
+        let event_id = event_id.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = shared.persist(&event_id).await {
+                warn!("Failed to persist critical event {}: {}", event_id, e);
+            }
+        });
+    }
+
+    /// Write the buffered event to the store, or remove it once acknowledged
+    async fn persist(&self, event_id: &str) -> Result<(), StorageError> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        let _guard = self.store_lock.lock().await;
+        let stored = self
+            .pending
+            .lock()
+            .unwrap()
+            .get(event_id)
+            .map(|entry| StoredCriticalEvent {
+                event: wire::encode_event(&entry.event),
+                unacknowledged: entry.report(event_id).unacknowledged,
+                attempts: entry.attempts,
+            });
+        match stored {
+            Some(stored) => {
+                let value = ValueWithMetadata {
+                    value: persistence::to_stored(&stored)?,
+                    metadata: Metadata::default(),
+                    expiry: None,
+                };
+                store.save_key(CRITICAL_NAMESPACE, event_id, &value).await
+            }
+            None => store.delete_key(CRITICAL_NAMESPACE, event_id).await,
+        }
+    }
+
+    fn report(&self, event_id: &str) -> DeliveryReport {
+        match self.pending.lock().unwrap().get(event_id) {
+            Some(entry) => entry.report(event_id),
+            // 期限と同時に確認が済んだ
+            None => DeliveryReport {
+                event_id: event_id.to_string(),
+                acknowledged: true,
+                unacknowledged: vec![],
+                attempts: 0,
+                escalated: false,
+            },
+        }
+    }
+}
+
+fn alert_event(event_id: &str, entry: &PendingEvent) -> Event {
+    let report = entry.report(event_id);
+    let consumers = report
+        .unacknowledged
+        .into_iter()
+        .map(Value::String)
+        .collect();
+    Event {
+        event_type: EventType::CriticalDeliveryFailed {
+            event_type: entry.event.event_type.to_string(),
+        },
+        parameters: HashMap::from([
+            ("event_id".to_string(), Value::String(event_id.to_string())),
+            ("consumers".to_string(), Value::List(consumers)),
+            (
+                "attempts".to_string(),
+                Value::Integer(i64::from(report.attempts)),
+            ),
+        ]),
+        ..Default::default()
+    }
+    .caused_by(&entry.event)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        event_bus::EventBus,
+        provider::{config::plugins::InMemoryConfig, plugins::storage::in_memory::InMemoryBackend},
+    };
+
+    fn invoice() -> EventType {
+        EventType::Custom("InvoiceIssued".to_string())
+    }
+
+    fn bus(max_attempts: u32, store: Option<Arc<dyn StorageBackend>>) -> EventBus {
+        let config = CriticalDeliveryConfig {
+            retry_interval: Duration::from_millis(50),
+            max_attempts,
+            deadline: Duration::from_millis(500),
+            ..Default::default()
+        };
+        EventBus::new(32).with_critical_delivery(config, store)
+    }
+
+    fn invoice_event() -> Event {
+        Event {
+            event_type: invoice(),
+            parameters: HashMap::from([("amount".to_string(), Value::Integer(120))]),
+            ..Default::default()
+        }
+    }
+
+    async fn next(receiver: &mut CriticalReceiver) -> Option<CriticalDelivery> {
+        tokio::time::timeout(Duration::from_millis(300), receiver.recv())
+            .await
+            .ok()
+            .flatten()
+    }
+
+    #[tokio::test]
+    async fn test_delayed_ack_is_retried_until_acknowledged() {
+        let bus = bus(5, None);
+        let mut billing = bus.register_critical_consumer("billing", &[invoice()]);
+        let mut audit = bus.register_critical_consumer("audit", &[invoice()]);
+        let (mut event_rx, _) = bus.subscribe();
+
+        let receipt = bus.publish_critical(invoice_event()).await.unwrap();
+        // 通常の購読者にも届く
+        assert_eq!(event_rx.recv().await.unwrap().event_type, invoice());
+
+        let delivery = next(&mut billing).await.unwrap();
+        assert_eq!(delivery.attempt, 1);
+        assert_eq!(delivery.event.parameters["amount"], Value::Integer(120));
+        delivery.ack.ack();
+
+        // audit は 1 回目を確認せず、再送された 2 回目で確認する
+        let first = next(&mut audit).await.unwrap();
+        assert_eq!(first.attempt, 1);
+        drop(first);
+        let second = next(&mut audit).await.unwrap();
+        assert_eq!(second.attempt, 2);
+        assert_eq!(second.ack.event_id(), receipt.event_id());
+        assert_eq!(
+            bus.pending_critical()[0].unacknowledged,
+            vec!["audit".to_string()]
+        );
+        second.ack.ack();
+
+        let report = receipt.await;
+        assert!(report.acknowledged);
+        assert!(report.unacknowledged.is_empty());
+        assert_eq!(report.attempts, 2);
+        assert!(!report.escalated);
+        // 確認済みの billing には再送しない
+        assert!(next(&mut billing).await.is_none());
+        assert!(bus.pending_critical().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unacknowledged_event_is_escalated() {
+        let bus = bus(3, None);
+        let mut billing = bus.register_critical_consumer("billing", &[invoice()]);
+        let mut audit = bus.register_critical_consumer("audit", &[invoice()]);
+        let (mut event_rx, _) = bus.subscribe();
+
+        let receipt = bus.publish_critical(invoice_event()).await.unwrap();
+        let event_id = receipt.event_id().to_string();
+        next(&mut billing).await.unwrap().ack.ack();
+
+        let alert = loop {
+            let event = event_rx.recv().await.unwrap();
+            if matches!(event.event_type, EventType::CriticalDeliveryFailed { .. }) {
+                break event;
+            }
+        };
+        assert_eq!(
+            alert.event_type,
+            EventType::CriticalDeliveryFailed {
+                event_type: "InvoiceIssued".to_string()
+            }
+        );
+        assert_eq!(
+            alert.parameters["consumers"],
+            Value::List(vec![Value::String("audit".to_string())])
+        );
+        assert_eq!(alert.parameters["attempts"], Value::Integer(3));
+        assert_eq!(alert.parent_event_id.as_deref(), Some(event_id.as_str()));
+
+        let report = receipt.await;
+        assert!(!report.acknowledged);
+        assert_eq!(report.unacknowledged, vec!["audit".to_string()]);
+        assert_eq!(report.attempts, 3);
+        assert!(report.escalated);
+
+        // 再送は止まるが、イベントは確認されるまで残る
+        let mut deliveries = vec![];
+        while let Some(delivery) = next(&mut audit).await {
+            deliveries.push(delivery);
+        }
+        assert_eq!(deliveries.len(), 3);
+        assert_eq!(bus.pending_critical().len(), 1);
+        deliveries.pop().unwrap().ack.ack();
+        assert!(bus.pending_critical().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_events_without_consumers_are_acknowledged() {
+        let bus = bus(3, None);
+        let _audit = bus.register_critical_consumer("audit", &[invoice()]);
+        let event = Event {
+            event_type: EventType::Custom("Heartbeat".to_string()),
+            ..Default::default()
+        };
+        let report = bus.publish_critical(event).await.unwrap().await;
+        assert!(report.acknowledged);
+        assert_eq!(report.attempts, 0);
+        assert!(bus.pending_critical().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unacknowledged_events_are_redelivered_from_store() {
+        let store: Arc<dyn StorageBackend> =
+            Arc::new(InMemoryBackend::new(InMemoryConfig::default()));
+
+        let first = bus(3, Some(store.clone()));
+        let mut audit = first.register_critical_consumer("audit", &[invoice()]);
+        let receipt = first.publish_critical(invoice_event()).await.unwrap();
+        let event_id = receipt.event_id().to_string();
+        assert_eq!(next(&mut audit).await.unwrap().attempt, 1);
+        drop((receipt, first));
+
+        let second = bus(3, Some(store.clone()));
+        let mut audit = second.register_critical_consumer("audit", &[invoice()]);
+        let receipts = second.redeliver_critical().await.unwrap();
+        assert_eq!(receipts.len(), 1);
+
+        let delivery = next(&mut audit).await.unwrap();
+        assert_eq!(delivery.event.event_id.as_deref(), Some(event_id.as_str()));
+        // 前回の配信回数から数え続ける
+        assert_eq!(delivery.attempt, 2);
+        delivery.ack.ack();
+        assert!(receipts.into_iter().next().unwrap().await.acknowledged);
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(store.load(CRITICAL_NAMESPACE).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,483 @@
+//! # Handler Dispatch Index
+//!
+//! Without an index every agent receives every event of the [`EventBus`] and looks
+//! up its own handlers, so with many agents each event wakes all of them. A
+//! [`DispatchIndex`] instead maps the key of an event, its type and for requests the
+//! responder, to the agents with a handler for it. One router task takes events
+//! from the bus and forwards each to the agents found by a single lookup.
+//!
+//! An agent enters the index with [`DispatchIndex::subscribe`] when it starts running
+//! and leaves it when the returned [`DispatchSubscription`] is dropped, i.e. when it
+//! stops, is scaled down or is rebuilt. The index therefore always holds the
+//! handlers of the agents that run, including those of an agent restarted after
+//! its AST was reloaded. Scaled instances share their agent's name and each get
+//! the events, as they did from the bus.
+//!
+//! Before forwarding, the router checks that the event carries every required
+//! parameter of the agent's handlers. An event that lacks one is not forwarded.
+//!
+//! Requests to an agent that answers requests but has no handler for the request
+//! type are still forwarded to it, so that its catch-all handler runs or it reports
+//! the missing handler as before. An agent without answer handlers gets no requests.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use tokio::sync::broadcast;
+use tracing::{debug, warn};
+
+use super::event_bus::{Event, EventBus, EventCategory, EventError, EventReceiver};
+use super::event_registry::EventType;
+use crate::ast::{MicroAgentDef, Parameter, TypeInfo};
+use crate::background_tasks::BackgroundTasks;
+
+/// Kind of handler an event is dispatched to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandlerKind {
+    Observe,
+    React,
+    Answer,
+}
+
+impl HandlerKind {
+    /// Handlers an agent runs for an event of `category`
+    fn runs_for(self, category: &EventCategory) -> bool {
+        match category {
+            EventCategory::Request { .. } => self == HandlerKind::Answer,
+            EventCategory::Agent => matches!(self, HandlerKind::Observe | HandlerKind::React),
+            EventCategory::System | EventCategory::Component => self == HandlerKind::Observe,
+            EventCategory::Response => false,
+        }
+    }
+}
+
+/// Key of an event in the index: the event type, or the request type and responder
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DispatchKey {
+    pub event: String,
+    pub responder: Option<String>,
+}
+
+impl DispatchKey {
+    /// `None` for responses, which are handled by the requester's evaluator
+    pub fn of(event: &Event) -> Option<Self> {
+        match &event.event_type {
+            EventType::Request {
+                request_type,
+                responder,
+                ..
+            } => Some(Self {
+                event: request_type.clone(),
+                responder: Some(responder.clone()),
+            }),
+            EventType::ResponseSuccess { .. }
+            | EventType::ResponsePartial { .. }
+            | EventType::ResponseFailure { .. } => None,
+            event_type => Some(Self {
+                event: event_type.to_string(),
+                responder: None,
+            }),
+        }
+    }
+}
+
+/// A handler of an agent, as entered in the index
+#[derive(Debug, Clone, PartialEq)]
+pub struct HandlerRoute {
+    pub key: DispatchKey,
+    pub kind: HandlerKind,
+    /// Parameters the event must carry for the handler to run
+    pub required_parameters: Vec<String>,
+}
+
+impl HandlerRoute {
+    /// The observe, react and answer handlers of `agent_def`, running as `agent_name`
+    pub fn from_agent(agent_name: &str, agent_def: &MicroAgentDef) -> Vec<Self> {
+        let mut routes = Vec::new();
+        let event_handlers = [
+            (
+                HandlerKind::Observe,
+                agent_def.observe.as_ref().map(|d| &d.handlers),
+            ),
+            (
+                HandlerKind::React,
+                agent_def.react.as_ref().map(|d| &d.handlers),
+            ),
+        ];
+        for (kind, handlers) in event_handlers {
+            for handler in handlers.into_iter().flatten() {
+                routes.push(Self {
+                    key: DispatchKey {
+                        event: handler.event_type.to_string(),
+                        responder: None,
+                    },
+                    kind,
+                    required_parameters: required_parameters(&handler.parameters),
+                });
+            }
+        }
+        for handler in agent_def.answer.iter().flat_map(|d| &d.handlers) {
+            routes.push(Self {
+                key: DispatchKey {
+                    event: handler.request_type.to_string(),
+                    responder: Some(agent_name.to_string()),
+                },
+                kind: HandlerKind::Answer,
+                required_parameters: required_parameters(&handler.parameters),
+            });
+        }
+        routes
+    }
+}
+
+/// Parameters that `RuntimeAgentData::bind_parameters` fails without
+fn required_parameters(parameters: &[Parameter]) -> Vec<String> {
+    parameters
+        .iter()
+        .filter(|p| !matches!(p.type_info, TypeInfo::Option(_)) && !p.type_info.is_any())
+        .map(|p| p.name.clone())
+        .collect()
+}
+
+/// Index from event keys to the agents handling them, see the [module docs](self)
+pub struct DispatchIndex {
+    state: RwLock<IndexState>,
+    next_generation: AtomicU64,
+    /// Capacity of each agent's channel
+    capacity: usize,
+}
+
+#[derive(Default)]
+struct IndexState {
+    targets: HashMap<DispatchKey, Vec<DispatchTarget>>,
+    agents: HashMap<String, Vec<Arc<AgentRoute>>>,
+}
+
+impl IndexState {
+    /// The agents `event` is routed to
+    fn recipients(&self, event: &Event) -> Vec<&Arc<AgentRoute>> {
+        let Some(key) = DispatchKey::of(event) else {
+            return Vec::new();
+        };
+        let category = event.category();
+        match self.targets.get(&key) {
+            Some(targets) => targets
+                .iter()
+                .filter(|target| target.accepts(event, &category))
+                .map(|target| &target.route)
+                .collect(),
+            // 応答ハンドラのないリクエストも宛先に届け、catch-all を実行するかハンドラがないことを報告させる
+            None => key
+                .responder
+                .as_ref()
+                .and_then(|responder| self.agents.get(responder))
+                .into_iter()
+                .flatten()
+                .filter(|route| route.answers)
+                .collect(),
+        }
+    }
+}
+
+/// The handlers of one agent for one key
+struct DispatchTarget {
+    route: Arc<AgentRoute>,
+    handlers: Vec<(HandlerKind, Vec<String>)>,
+}
+
+impl DispatchTarget {
+    /// Whether the agent runs a handler for `event` and every such handler gets its
+    /// required parameters
+    fn accepts(&self, event: &Event, category: &EventCategory) -> bool {
+        let mut runs = false;
+        for (kind, required) in &self.handlers {
+            if !kind.runs_for(category) {
+                continue;
+            }
+            if let Some(missing) = required
+                .iter()
+                .find(|name| !event.parameters.contains_key(*name))
+            {
+                debug!(
+                    "Skip agent {} for event {}: missing parameter {}",
+                    self.route.agent, event.event_type, missing
+                );
+                return false;
+            }
+            runs = true;
+        }
+        runs
+    }
+}
+
+struct AgentRoute {
+    agent: String,
+    generation: u64,
+    sender: broadcast::Sender<Event>,
+    keys: Vec<DispatchKey>,
+    /// Whether the agent has an answer handler, and so is sent requests without one
+    answers: bool,
+}
+
+impl DispatchIndex {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            state: RwLock::new(IndexState::default()),
+            next_generation: AtomicU64::new(0),
+            capacity,
+        }
+    }
+
+    /// Create an index and spawn the task that routes the events of `event_bus`.
+    /// The task stops with `tasks`.
+    pub fn start(event_bus: &EventBus, tasks: &BackgroundTasks) -> Arc<Self> {
+        let index = Arc::new(Self::new(event_bus.capacity()));
+        let router = index.clone();
+        let mut event_rx = event_bus.subscribe().0;
+        tasks.spawn(async move {
+            loop {
+                match event_rx.recv().await {
+                    Ok(event) => {
+                        router.route(&event);
+                    }
+                    Err(EventError::Lagged { count }) => {
+                        warn!("Dispatch router skipped {} events", count);
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+        index
+    }
+
+    /// Enter `agent` with its handlers. The agent receives the events routed to it
+    /// until the subscription is dropped.
+    pub fn subscribe(
+        self: &Arc<Self>,
+        agent: &str,
+        routes: Vec<HandlerRoute>,
+    ) -> (EventReceiver, DispatchSubscription) {
+        let mut handlers: HashMap<DispatchKey, Vec<(HandlerKind, Vec<String>)>> = HashMap::new();
+        for route in routes {
+            handlers
+                .entry(route.key)
+                .or_default()
+                .push((route.kind, route.required_parameters));
+        }
+
+        let (sender, receiver) = broadcast::channel(self.capacity);
+        let generation = self.next_generation.fetch_add(1, Ordering::Relaxed);
+        let route = Arc::new(AgentRoute {
+            agent: agent.to_string(),
+            generation,
+            sender,
+            keys: handlers.keys().cloned().collect(),
+            answers: handlers.keys().any(|key| key.responder.is_some()),
+        });
+
+        let mut state = self.state.write().unwrap();
+        for (key, handlers) in handlers {
+            state.targets.entry(key).or_default().push(DispatchTarget {
+                route: route.clone(),
+                handlers,
+            });
+        }
+        state
+            .agents
+            .entry(agent.to_string())
+            .or_default()
+            .push(route);
+        drop(state);
+
+        let subscription = DispatchSubscription {
+            index: self.clone(),
+            agent: agent.to_string(),
+            generation,
+        };
+        (EventReceiver::new(receiver), subscription)
+    }
+
+    /// Forward `event` to the agents handling it. Returns how many it was sent to.
+    pub fn route(&self, event: &Event) -> usize {
+        let state = self.state.read().unwrap();
+        let recipients = state.recipients(event);
+        for route in &recipients {
+            // 停止中のエージェントには受信側がないので、送信の失敗は無視する
+            let _ = route.sender.send(event.clone());
+        }
+        recipients.len()
+    }
+
+    /// Names of the agents `event` is routed to
+    pub fn targets(&self, event: &Event) -> Vec<String> {
+        let state = self.state.read().unwrap();
+        let mut agents: Vec<String> = state
+            .recipients(event)
+            .into_iter()
+            .map(|route| route.agent.clone())
+            .collect();
+        agents.sort();
+        agents
+    }
+
+    /// Number of keys with at least one target
+    pub fn key_count(&self) -> usize {
+        self.state.read().unwrap().targets.len()
+    }
+
+    fn unsubscribe(&self, agent: &str, generation: u64) {
+        let mut state = self.state.write().unwrap();
+        let Some(routes) = state.agents.get_mut(agent) else {
+            return;
+        };
+        let Some(position) = routes.iter().position(|r| r.generation == generation) else {
+            return;
+        };
+        // 同じ名前の他のインスタンスの登録は残す
+        let route = routes.remove(position);
+        if routes.is_empty() {
+            state.agents.remove(agent);
+        }
+        for key in &route.keys {
+            if let Some(targets) = state.targets.get_mut(key) {
+                targets.retain(|target| target.route.generation != generation);
+                if targets.is_empty() {
+                    state.targets.remove(key);
+                }
+            }
+        }
+    }
+}
+
+/// An agent's entry in a [`DispatchIndex`], removed when dropped
+pub struct DispatchSubscription {
+    index: Arc<DispatchIndex>,
+    agent: String,
+    generation: u64,
+}
+
+impl Drop for DispatchSubscription {
+    fn drop(&mut self) {
+        self.index.unsubscribe(&self.agent, self.generation);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::event_bus::Value;
+
+    fn route(event: &str, kind: HandlerKind, required: &[&str]) -> HandlerRoute {
+        HandlerRoute {
+            key: DispatchKey {
+                event: event.to_string(),
+                responder: None,
+            },
+            kind,
+            required_parameters: required.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    fn message(content_type: &str, parameters: &[&str]) -> Event {
+        Event {
+            event_type: EventType::Message {
+                content_type: content_type.to_string(),
+            },
+            parameters: parameters
+                .iter()
+                .map(|name| (name.to_string(), Value::Integer(1)))
+                .collect::<HashMap<_, _>>(),
+            ..Default::default()
+        }
+    }
+
+    fn message_key(content_type: &str) -> String {
+        EventType::Message {
+            content_type: content_type.to_string(),
+        }
+        .to_string()
+    }
+
+    fn request(request_type: &str, responder: &str) -> Event {
+        Event::request_builder()
+            .request_type(request_type)
+            .requester("client")
+            .responder(responder)
+            .request_id("request-1")
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_routes_events_and_requests() {
+        let index = Arc::new(DispatchIndex::new(16));
+        let start = message_key("Start");
+        let (mut a_rx, _a) = index.subscribe(
+            "a",
+            vec![
+                route(&start, HandlerKind::Observe, &[]),
+                route(&start, HandlerKind::React, &[]),
+            ],
+        );
+        let (mut b_rx, _b) = index.subscribe(
+            "b",
+            vec![HandlerRoute {
+                key: DispatchKey {
+                    event: "Get".to_string(),
+                    responder: Some("b".to_string()),
+                },
+                kind: HandlerKind::Answer,
+                required_parameters: vec![],
+            }],
+        );
+
+        // observe と react を持つエージェントにも 1 回だけ届く
+        assert_eq!(index.route(&message("Start", &[])), 1);
+        assert_eq!(index.route(&message("Stop", &[])), 0);
+        assert_eq!(
+            a_rx.recv().await.unwrap().event_type,
+            message("Start", &[]).event_type
+        );
+
+        assert_eq!(index.route(&request("Get", "b")), 1);
+        assert!(b_rx.receiver.try_recv().is_ok());
+        // 応答ハンドラがなくても宛先には届ける
+        assert_eq!(index.route(&request("Put", "b")), 1);
+        assert_eq!(index.route(&request("Get", "a")), 0);
+        assert!(a_rx.receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_skips_events_missing_required_parameters() {
+        let index = Arc::new(DispatchIndex::new(16));
+        let tick = message_key("Tick");
+        let _a = index.subscribe("a", vec![route(&tick, HandlerKind::React, &["count"])]);
+        let _b = index.subscribe("b", vec![route(&tick, HandlerKind::Observe, &[])]);
+
+        assert_eq!(index.targets(&message("Tick", &[])), vec!["b"]);
+        assert_eq!(index.targets(&message("Tick", &["count"])), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_index_follows_rebuilt_and_removed_agents() {
+        let index = Arc::new(DispatchIndex::new(16));
+        let (start, stop) = (message_key("Start"), message_key("Stop"));
+        let (_rx, old) = index.subscribe("a", vec![route(&start, HandlerKind::React, &[])]);
+        let (_rx, other) = index.subscribe("b", vec![route(&start, HandlerKind::React, &[])]);
+
+        // 再構築したエージェントは、古い登録が破棄されると新しいハンドラだけになる
+        let (_rx, new) = index.subscribe("a", vec![route(&stop, HandlerKind::React, &[])]);
+        drop(old);
+        assert_eq!(index.targets(&message("Start", &[])), vec!["b"]);
+        assert_eq!(index.targets(&message("Stop", &[])), vec!["a"]);
+
+        drop(new);
+        drop(other);
+        assert!(index.targets(&message("Start", &[])).is_empty());
+        assert!(index.targets(&message("Stop", &[])).is_empty());
+        assert_eq!(index.key_count(), 0);
+    }
+}
@@ -25,13 +25,28 @@
 //! - Subscribers should process events quickly to avoid lagging behind
 //! - For high-volume events like ticks, consider filtering at the receiver level
 
-use std::{collections::HashMap, time::Duration};
-
-use crate::{RetryDelay, eval::expression, event_registry::EventType};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use crate::{
+    RetryDelay,
+    config::CriticalDeliveryConfig,
+    eval::{
+        context::RequestContext, evaluator::ConstraintViolation, expression,
+        profile::RequestProfile, set::ValueSet,
+    },
+    event_registry::EventType,
+    provider::capabilities::storage::StorageBackend,
+};
 use chrono::{DateTime, Utc};
 use thiserror::Error;
 use tokio::sync::broadcast;
 use tracing::{debug, trace};
+use uuid::Uuid;
+
+use super::{
+    critical::{CriticalDeliveries, CriticalReceiver, DeliveryReceipt, DeliveryReport},
+    lineage::{EventLineageLog, LineageNode, LineageRecord},
+};
 
 /// # Event
 ///
@@ -43,6 +58,9 @@ use tracing::{debug, trace};
 ///
 /// * `event_type`: Specifies the type and category of the event
 /// * `parameters`: Contains the event payload as key-value pairs
+/// * `event_id`, `parent_event_id`, `root_event_id`: Lineage of the event (see
+///   [`lineage`](super::lineage)). Leave them at their defaults; the bus assigns the ID
+///   and the execution context fills in the parent.
 ///
 /// ## Example
 ///
@@ -58,6 +76,7 @@ use tracing::{debug, trace};
 ///         params.insert("field".to_string(), Value::String("email".to_string()));
 ///         params
 ///     },
+///     ..Default::default()
 /// };
 /// ```
 #[derive(Debug, Clone, PartialEq, Default)]
@@ -66,6 +85,12 @@ pub struct Event {
     pub event_type: EventType,
     /// Event payload data as key-value pairs
     pub parameters: HashMap<String, Value>,
+    /// Unique ID, assigned by the event bus on publish when not set
+    pub event_id: Option<String>,
+    /// ID of the event whose handler emitted this event
+    pub parent_event_id: Option<String>,
+    /// ID of the first event of the causal chain; the event's own ID for a root event
+    pub root_event_id: Option<String>,
 }
 
 impl Event {
@@ -73,7 +98,31 @@ impl Event {
         Self {
             event_type: event_type.clone(),
             parameters: parameters.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Marks this event as caused by `parent`. Has no effect if `parent` was never published.
+    /// An event caused by a replayed event is replayed too.
+    pub fn caused_by(mut self, parent: &Event) -> Self {
+        if let Some(parent_id) = &parent.event_id {
+            self.parent_event_id = Some(parent_id.clone());
+            self.root_event_id = parent
+                .root_event_id
+                .clone()
+                .or_else(|| Some(parent_id.clone()));
+        }
+        if parent.is_replay() {
+            self = self.replayed();
         }
+        self
+    }
+
+    /// Sets `event_id` unless already set, and makes the event its own root if it has none.
+    pub fn assign_id(mut self, event_id: String) -> Self {
+        let event_id = self.event_id.get_or_insert(event_id).clone();
+        self.root_event_id.get_or_insert(event_id);
+        self
     }
 }
 
@@ -92,6 +141,9 @@ impl Event {
         match &self.event_type {
             EventType::Tick => EventCategory::System,
             EventType::MetricsSummary => EventCategory::System,
+            EventType::GuardrailTripped { .. } => EventCategory::System,
+            EventType::AgentLog { .. } => EventCategory::System,
+            EventType::CriticalDeliveryFailed { .. } => EventCategory::System,
             EventType::StateUpdated { .. } => EventCategory::Agent,
             EventType::Message { .. } => EventCategory::Agent,
             EventType::Failure { .. } => EventCategory::Agent,
@@ -99,6 +151,7 @@ impl Event {
                 request_type: request_type.clone(),
             },
             EventType::ResponseSuccess { .. } => EventCategory::Response,
+            EventType::ResponsePartial { .. } => EventCategory::Response,
             EventType::ResponseFailure { .. } => EventCategory::Response,
             EventType::AgentCreated => EventCategory::Agent,
             EventType::AgentAdded => EventCategory::Agent,
@@ -127,6 +180,82 @@ impl Event {
         }
     }
 
+    /// Returns the request context attached to a request event, if any.
+    pub fn request_context(&self) -> Option<RequestContext> {
+        self.parameters
+            .get(REQUEST_CONTEXT_KEY)
+            .and_then(RequestContext::from_event_value)
+    }
+
+    /// Whether the event is replayed from the event journal, see [`REPLAY_KEY`]
+    pub fn is_replay(&self) -> bool {
+        self.parameters.get(REPLAY_KEY) == Some(&Value::Boolean(true))
+    }
+
+    /// Marks the event as replayed from the event journal
+    pub fn replayed(mut self) -> Self {
+        self.parameters
+            .insert(REPLAY_KEY.to_string(), Value::Boolean(true));
+        self
+    }
+
+    /// When a request asks for a profile, the time it was dispatched. A request
+    /// that only sets the flag is profiled from now on.
+    pub fn profile_requested_at(&self) -> Option<DateTime<Utc>> {
+        match self.parameters.get(PROFILE_KEY) {
+            Some(Value::Integer(micros)) => DateTime::from_timestamp_micros(*micros),
+            Some(Value::Boolean(true)) => Some(Utc::now()),
+            _ => None,
+        }
+    }
+
+    /// The time by which a request must be answered, see [`DEADLINE_KEY`]
+    pub fn deadline(&self) -> Option<DateTime<Utc>> {
+        match self.parameters.get(DEADLINE_KEY) {
+            Some(Value::Integer(micros)) => DateTime::from_timestamp_micros(*micros),
+            _ => None,
+        }
+    }
+
+    /// Sets the deadline of a request, keeping an earlier one already set
+    pub fn with_deadline(mut self, deadline: DateTime<Utc>) -> Self {
+        if self.deadline().is_none_or(|current| deadline < current) {
+            self.parameters.insert(
+                DEADLINE_KEY.to_string(),
+                Value::Integer(deadline.timestamp_micros()),
+            );
+        }
+        self
+    }
+
+    /// Retries the request chain has left, see [`RETRY_BUDGET_KEY`]
+    pub fn retry_budget(&self) -> Option<u32> {
+        match self.parameters.get(RETRY_BUDGET_KEY) {
+            Some(Value::Integer(retries)) => Some((*retries).clamp(0, u32::MAX as i64) as u32),
+            _ => None,
+        }
+    }
+
+    /// Records now as the dispatch time of a request that asks for a profile
+    pub fn stamp_profile_request(&mut self) {
+        if self.parameters.get(PROFILE_KEY) == Some(&Value::Boolean(true)) {
+            self.parameters.insert(
+                PROFILE_KEY.to_string(),
+                Value::Integer(Utc::now().timestamp_micros()),
+            );
+        }
+    }
+
+    /// The timing breakdown attached to a response
+    pub fn profile(&self) -> Option<RequestProfile> {
+        if !self.event_type.is_response() {
+            return None;
+        }
+        self.parameters
+            .get(PROFILE_KEY)
+            .and_then(RequestProfile::from_event_value)
+    }
+
     pub fn request_builder() -> RequestBuilder {
         RequestBuilder::new()
     }
@@ -137,7 +266,7 @@ impl Event {
 
     pub fn response_value(&self) -> Value {
         match &self.event_type {
-            EventType::ResponseSuccess { .. } => self
+            EventType::ResponseSuccess { .. } | EventType::ResponsePartial { .. } => self
                 .parameters
                 .get("response")
                 .cloned()
@@ -148,8 +277,55 @@ impl Event {
             _ => Value::Null,
         }
     }
+
+    /// The violated parameter constraints of a failure response, when the
+    /// request was rejected by the `where` constraints of its handler
+    pub fn constraint_violations(&self) -> Option<Vec<ConstraintViolation>> {
+        if !matches!(self.event_type, EventType::ResponseFailure { .. }) {
+            return None;
+        }
+        match self.parameters.get(VIOLATIONS_KEY) {
+            Some(Value::List(violations)) => Some(
+                violations
+                    .iter()
+                    .filter_map(ConstraintViolation::from_event_value)
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
 }
 
+/// Reserved parameter key carrying the [`RequestContext`] of a request event.
+pub const REQUEST_CONTEXT_KEY: &str = "request_context";
+
+/// Reserved parameter key marking an event replayed from the event journal, and the
+/// events its handlers emit; see [`crate::event::journal`].
+pub const REPLAY_KEY: &str = "replay";
+
+/// Parameter key carrying the [`ConstraintViolation`]s of a failure response.
+pub const VIOLATIONS_KEY: &str = "violations";
+
+/// Parameter key carrying the error of a failure response as a message for end
+/// users, in the locale of the request. Set for errors caused by the request, such
+/// as rejected parameters; `error` keeps the English message for logs.
+pub const ERROR_MESSAGE_KEY: &str = "error_message";
+
+/// Reserved parameter key asking for the timing breakdown of a request, see
+/// [`crate::eval::profile`]. On a request it is `true`, or the dispatch time in
+/// microseconds since the Unix epoch; on the response it carries the
+/// [`RequestProfile`].
+pub const PROFILE_KEY: &str = "profile";
+
+/// Reserved parameter key carrying the deadline of a request, in microseconds since
+/// the Unix epoch. Requests sent while handling it inherit it, so a chain of agent
+/// requests shares the time of the first one; see [`crate::request_manager`].
+pub const DEADLINE_KEY: &str = "deadline";
+
+/// Reserved parameter key carrying how many retries of `retry(...)` a request chain
+/// has left. The response reports what is left after the responder's retries.
+pub const RETRY_BUDGET_KEY: &str = "retry_budget";
+
 #[derive(Default, Clone)]
 pub struct RequestBuilder {
     request_type: Option<String>,
@@ -195,6 +371,21 @@ impl RequestBuilder {
         self
     }
 
+    pub fn request_context(mut self, request_context: &RequestContext) -> Self {
+        self.parameters.insert(
+            REQUEST_CONTEXT_KEY.to_string(),
+            request_context.to_event_value(),
+        );
+        self
+    }
+
+    /// Asks for the timing breakdown of the request, returned with the response
+    pub fn profile(mut self) -> Self {
+        self.parameters
+            .insert(PROFILE_KEY.to_string(), Value::Boolean(true));
+        self
+    }
+
     pub fn build(self) -> EventResult<Event> {
         Ok(Event {
             event_type: EventType::Request {
@@ -212,6 +403,7 @@ impl RequestBuilder {
                 ))?,
             },
             parameters: self.parameters,
+            ..Default::default()
         })
     }
 }
@@ -314,6 +506,7 @@ impl ResponseBuilder {
                 ))?,
             },
             parameters,
+            ..Default::default()
         })
     }
 
@@ -341,6 +534,7 @@ impl ResponseBuilder {
                 ))?,
             },
             parameters,
+            ..Default::default()
         })
     }
 }
@@ -379,6 +573,7 @@ pub enum Value {
     List(Vec<Value>),
     Duration(Duration),
     Map(HashMap<String, Value>),
+    Set(ValueSet),
     Null,
 }
 
@@ -409,6 +604,7 @@ impl From<expression::Value> for Value {
                     .map(|(k, v)| (k, Value::from(v)))
                     .collect::<HashMap<String, Value>>(),
             ),
+            expression::Value::Set(s) => Value::Set(s),
             expression::Value::Error(s) => Value::String(s),
             expression::Value::Delay(retry) => {
                 let mut map = HashMap::new();
@@ -432,6 +628,7 @@ impl From<expression::Value> for Value {
             }
             expression::Value::Ok(value) => Value::from(*value),
             expression::Value::Err(value) => Value::from(*value),
+            expression::Value::Secret(secret) => Value::String(secret.to_string()),
         }
     }
 }
@@ -446,6 +643,7 @@ impl From<Value> for expression::Value {
             Value::List(l) => expression::Value::List(l.into_iter().map(Into::into).collect()),
             Value::Null => expression::Value::Null,
             Value::Duration(d) => expression::Value::Duration(d),
+            Value::Set(s) => expression::Value::Set(s),
             Value::Map(m) => {
                 expression::Value::Map(m.into_iter().map(|(k, v)| (k, v.into())).collect::<HashMap<
                     String,
@@ -474,6 +672,7 @@ impl From<&Value> for serde_json::Value {
                     .map(|(k, v)| (k.clone(), serde_json::Value::from(v)))
                     .collect(),
             ),
+            Value::Set(s) => s.to_json(),
             Value::Null => serde_json::Value::Null,
         }
     }
@@ -497,6 +696,7 @@ impl From<LastStatus> for Event {
                 );
                 params
             },
+            ..Default::default()
         }
     }
 }
@@ -526,6 +726,10 @@ pub struct EventBus {
     _internal_receiver: broadcast::Receiver<Event>,
     /// Internal receiver to keep the error channel active
     _internal_error_receiver: broadcast::Receiver<ErrorEvent>,
+    /// Causal links of recently published events
+    lineage: Arc<EventLineageLog>,
+    /// Critical events awaiting acknowledgement, see [`super::critical`]
+    critical: CriticalDeliveries,
 }
 
 impl EventBus {
@@ -549,15 +753,40 @@ impl EventBus {
     pub fn new(capacity: usize) -> Self {
         let (event_sender, event_receiver) = broadcast::channel(capacity);
         let (error_sender, error_reciever) = broadcast::channel(capacity);
+        let lineage = Arc::new(EventLineageLog::default());
+        let critical = CriticalDeliveries::new(
+            CriticalDeliveryConfig::default(),
+            None,
+            event_sender.clone(),
+            lineage.clone(),
+        );
         Self {
             event_sender,
             error_sender,
             capacity,
             _internal_receiver: event_receiver,
             _internal_error_receiver: error_reciever,
+            lineage,
+            critical,
         }
     }
 
+    /// Sets the retries of critical events, and the storage mirroring the ones not
+    /// acknowledged yet. Consumers registered before are dropped.
+    pub fn with_critical_delivery(
+        mut self,
+        config: CriticalDeliveryConfig,
+        store: Option<Arc<dyn StorageBackend>>,
+    ) -> Self {
+        self.critical = CriticalDeliveries::new(
+            config,
+            store,
+            self.event_sender.clone(),
+            self.lineage.clone(),
+        );
+        self
+    }
+
     /// Subscribes to both regular and error events.
     ///
     /// Returns a tuple containing an EventReceiver for regular events and an
@@ -622,6 +851,7 @@ impl EventBus {
     /// # }
     /// ```
     pub async fn publish(&self, event: Event) -> EventResult<()> {
+        let event = self.stamp(event);
         debug_event("Publishing", &event);
         self.event_sender
             .send(event)
@@ -631,6 +861,69 @@ impl EventBus {
         Ok(())
     }
 
+    /// Publishes an event that every consumer registered for its type must
+    /// acknowledge, see [`super::critical`].
+    ///
+    /// The event is also broadcast to all subscribers like [`EventBus::publish`].
+    /// The returned receipt resolves once the consumers have acknowledged, or when
+    /// the configured deadline passes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the buffer of unacknowledged events is full, or the
+    /// event could not be written to the store.
+    pub async fn publish_critical(&self, event: Event) -> EventResult<DeliveryReceipt> {
+        let event = self.stamp(event);
+        debug_event("Publishing critical", &event);
+        let receipt = self.critical.publish(&event).await?;
+        self.event_sender
+            .send(event)
+            .map_err(|e| EventError::SendFailed {
+                message: e.to_string(),
+            })?;
+        Ok(receipt)
+    }
+
+    /// Registers `name` as a consumer that must acknowledge the critical events of
+    /// `event_types`. Registering the same name again replaces the previous receiver;
+    /// deliveries not acknowledged yet are retried to the new one.
+    pub fn register_critical_consumer(
+        &self,
+        name: &str,
+        event_types: &[EventType],
+    ) -> CriticalReceiver {
+        self.critical.register(name, event_types)
+    }
+
+    /// Delivers again the critical events a previous run left unacknowledged in the
+    /// store. Call it once the consumers are registered.
+    pub async fn redeliver_critical(&self) -> EventResult<Vec<DeliveryReceipt>> {
+        self.critical.redeliver().await
+    }
+
+    /// The critical events not acknowledged by every consumer yet, by event ID
+    pub fn pending_critical(&self) -> Vec<DeliveryReport> {
+        self.critical.pending()
+    }
+
+    /// Assigns the event ID and records the event in the lineage log
+    fn stamp(&self, event: Event) -> Event {
+        let event = event.assign_id(Uuid::new_v4().to_string());
+        self.lineage.record(&event);
+        event
+    }
+
+    /// Returns the causal tree around a recently published event.
+    /// See [`EventLineageLog::get_lineage`] for how `max_depth` bounds the walk.
+    pub fn get_lineage(&self, event_id: &str, max_depth: usize) -> Option<LineageNode> {
+        self.lineage.get_lineage(event_id, max_depth)
+    }
+
+    /// Returns the `limit` most recently published events, in publish order.
+    pub fn recent_events(&self, limit: usize) -> Vec<LineageRecord> {
+        self.lineage.recent(limit)
+    }
+
     /// Publishes an event synchronously without awaiting.
     ///
     /// This method is useful when you need to publish an event from a synchronous
@@ -644,6 +937,7 @@ impl EventBus {
     ///
     /// * `EventResult<()>` - Success or error result
     pub fn sync_publish(&self, event: Event) -> EventResult<()> {
+        let event = self.stamp(event);
         debug_event("Sync Publishing", &event);
         self.event_sender
             .send(event)
@@ -831,6 +1125,12 @@ pub enum EventError {
 
     #[error("response builder failed: {0}")]
     ResponseBuilderFailed(String),
+
+    #[error("Critical event buffer is full: {capacity} events await acknowledgement")]
+    CriticalBufferFull { capacity: usize },
+
+    #[error("Critical event store failed: {0}")]
+    CriticalStore(String),
 }
 
 pub type EventResult<T> = Result<T, EventError>;
@@ -24,12 +24,28 @@
 //!
 //! Events are validated against this registry before being published to ensure
 //! system integrity and prevent runtime errors from malformed events.
+//!
+//! ## Snapshots
+//!
+//! The custom events of a registry are exported with [`EventRegistry::export_events`]
+//! into an [`EventRegistrySnapshot`], and imported into another registry with
+//! [`EventRegistry::import_events`], e.g. to create a system from the events of
+//! another one. Built-in events are registered by every registry and are not part
+//! of snapshots. A snapshot is [`Versioned`]: store it with
+//! [`persistence::to_stored`](crate::persistence::to_stored) and read it back with
+//! [`persistence::from_stored`](crate::persistence::from_stored), which rejects a
+//! snapshot written in a version this build does not know.
 
 use crate::event_bus::{EventError, EventResult};
+use crate::persistence::Versioned;
 use crate::{TypeInfo, ast, native_feature::types::NativeFeatureType};
 use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
 use std::str::FromStr;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
 
 /// Metadata about an event type including its structure and parameters
 ///
@@ -65,6 +81,29 @@ pub enum EventType {
     Tick,
     /// Periodic metrics collection summary
     MetricsSummary,
+    /// A handler execution exceeded one of its agent's guardrails
+    ///
+    /// The guardrail, its limit and the usage counters are in the parameters.
+    GuardrailTripped {
+        /// Name of the agent whose handler was stopped
+        agent_name: String,
+    },
+    /// A `log` statement of an agent that publishes its logs
+    ///
+    /// The level, handler, message and fields are in the parameters.
+    AgentLog {
+        /// Name of the agent that logged
+        agent_name: String,
+    },
+    /// A critical event was delivered `max_attempts` times without every consumer
+    /// registered for it acknowledging
+    ///
+    /// The event ID, the consumers that did not acknowledge and the number of
+    /// deliveries are in the parameters.
+    CriticalDeliveryFailed {
+        /// Type of the undelivered event
+        event_type: String,
+    },
     /// Notification that an agent's internal state has changed
     StateUpdated {
         /// Name of the agent whose state changed
@@ -106,6 +145,19 @@ pub enum EventType {
         /// Unique identifier matching the original request
         request_id: String,
     },
+    /// Partial response published by an answer handler before its final response
+    ///
+    /// The position in the stream is carried in the `sequence` parameter.
+    ResponsePartial {
+        /// Type of the original request
+        request_type: String,
+        /// Original requesting agent
+        requester: String,
+        /// Agent sending the response
+        responder: String,
+        /// Unique identifier matching the original request
+        request_id: String,
+    },
     /// Failed response to a request
     ResponseFailure {
         /// Type of the original request
@@ -164,6 +216,11 @@ impl EventType {
         )
     }
 
+    /// 最終応答の前に送られる部分応答かどうか
+    pub fn is_partial_response(&self) -> bool {
+        matches!(self, EventType::ResponsePartial { .. })
+    }
+
     pub fn request_for_me(&self, agent_name: &str) -> bool {
         match self {
             EventType::Request { responder, .. } => responder == agent_name,
@@ -174,6 +231,7 @@ impl EventType {
     pub fn response_for_me(&self, agent_name: &str) -> bool {
         match self {
             EventType::ResponseSuccess { requester, .. }
+            | EventType::ResponsePartial { requester, .. }
             | EventType::ResponseFailure { requester, .. } => requester == agent_name,
             _ => false,
         }
@@ -183,6 +241,7 @@ impl EventType {
         match self {
             EventType::Request { request_id, .. } => Some(request_id),
             EventType::ResponseSuccess { request_id, .. } => Some(request_id),
+            EventType::ResponsePartial { request_id, .. } => Some(request_id),
             EventType::ResponseFailure { request_id, .. } => Some(request_id),
             _ => None,
         }
@@ -223,11 +282,15 @@ impl std::fmt::Display for EventType {
                 state_name,
             } => write!(f, "StateUpdated({}.{})", agent_name, state_name),
             EventType::MetricsSummary => write!(f, "MetricsSummary"),
+            EventType::GuardrailTripped { .. } => write!(f, "GuardrailTripped"),
+            EventType::AgentLog { .. } => write!(f, "agent_log"),
+            EventType::CriticalDeliveryFailed { .. } => write!(f, "CriticalDeliveryFailed"),
             EventType::Custom(name) => write!(f, "{}", name),
             EventType::Message { content_type } => write!(f, "{}", content_type),
             EventType::Failure { error_type } => write!(f, "{}", error_type),
             EventType::Request { request_type, .. } => write!(f, "{}", request_type),
             EventType::ResponseSuccess { request_type, .. } => write!(f, "{}", request_type),
+            EventType::ResponsePartial { request_type, .. } => write!(f, "{}", request_type),
             EventType::ResponseFailure { request_type, .. } => write!(f, "{}", request_type),
             EventType::AgentCreated => write!(f, "AgentCreated"),
             EventType::AgentAdded => write!(f, "AgentAdded"),
@@ -287,7 +350,9 @@ pub enum LifecycleEvent {
 }
 
 /// イベントパラメータの型情報
-#[derive(Clone, Debug, PartialEq, strum::EnumString, strum::Display, Default)]
+#[derive(
+    Clone, Debug, PartialEq, strum::EnumString, strum::Display, Default, Serialize, Deserialize,
+)]
 pub enum ParameterType {
     #[default]
     String,
@@ -300,6 +365,7 @@ pub enum ParameterType {
     Custom(String), // カスタム型
     List(Box<ParameterType>),
     Map(Box<ParameterType>, Box<ParameterType>),
+    Set(Box<ParameterType>),
 }
 
 impl From<TypeInfo> for ParameterType {
@@ -312,6 +378,45 @@ impl From<TypeInfo> for ParameterType {
     }
 }
 
+/// A custom event and its parameters, as stored in an [`EventRegistrySnapshot`]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EventDefinition {
+    pub name: String,
+    pub parameters: BTreeMap<String, ParameterType>,
+}
+
+/// The custom events of a registry, sorted by name
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct EventRegistrySnapshot {
+    pub events: Vec<EventDefinition>,
+}
+
+impl Versioned for EventRegistrySnapshot {
+    const KIND: &'static str = "event registry";
+    const VERSION: u32 = 1;
+}
+
+/// What [`EventRegistry::import_events`] does with an event already registered
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportConflict {
+    /// Keep the registered event
+    Skip,
+    /// Replace the registered event with the imported one
+    Overwrite,
+    /// Import nothing and fail
+    #[default]
+    Error,
+}
+
+/// The events an import registered, replaced or left as they were
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EventImport {
+    pub imported: Vec<String>,
+    pub overwritten: Vec<String>,
+    pub skipped: Vec<String>,
+}
+
 /// イベントレジストリ
 #[derive(Default)]
 pub struct EventRegistry {
@@ -369,6 +474,22 @@ impl EventRegistry {
         self.register_event(event_info)
     }
 
+    /// カスタムイベントを登録し、登録済みならパラメータを置き換える（DSLの再読み込み用）
+    pub fn replace_custom_event(
+        &mut self,
+        name: String,
+        parameters: HashMap<String, ParameterType>,
+    ) {
+        let event_type = EventType::Custom(name);
+        self.events.insert(
+            event_type.clone(),
+            EventInfo {
+                event_type,
+                parameters,
+            },
+        );
+    }
+
     /// イベント情報を取得
     pub fn get_event_info(&self, event_type: &EventType) -> Option<EventInfo> {
         self.events.get(event_type).map(|info| info.clone())
@@ -430,6 +551,57 @@ impl EventRegistry {
             })
             .collect()
     }
+
+    /// The custom events and their parameters, see [Snapshots](self#snapshots)
+    pub fn export_events(&self) -> EventRegistrySnapshot {
+        let mut events: Vec<EventDefinition> = self
+            .get_custom_events()
+            .into_iter()
+            .map(|info| EventDefinition {
+                name: info.event_type.to_string(),
+                parameters: info.parameters.into_iter().collect(),
+            })
+            .collect();
+        events.sort_by(|a, b| a.name.cmp(&b.name));
+        EventRegistrySnapshot { events }
+    }
+
+    /// Registers the events of `snapshot`. An event already registered is handled
+    /// as `on_conflict` says; with [`ImportConflict::Error`] nothing is imported
+    /// when any event is.
+    pub fn import_events(
+        &mut self,
+        snapshot: EventRegistrySnapshot,
+        on_conflict: ImportConflict,
+    ) -> EventResult<EventImport> {
+        if on_conflict == ImportConflict::Error {
+            // 途中まで取り込まないよう、先にすべての重複を確かめる
+            if let Some(event) = snapshot
+                .events
+                .iter()
+                .find(|event| self.contains_event(&EventType::Custom(event.name.clone())))
+            {
+                return Err(EventError::AlreadyRegistered {
+                    event_type: event.name.clone(),
+                });
+            }
+        }
+
+        let mut import = EventImport::default();
+        for event in snapshot.events {
+            let registered = self.contains_event(&EventType::Custom(event.name.clone()));
+            match (registered, on_conflict) {
+                (true, ImportConflict::Skip) => {
+                    import.skipped.push(event.name);
+                    continue;
+                }
+                (true, _) => import.overwritten.push(event.name.clone()),
+                (false, _) => import.imported.push(event.name.clone()),
+            }
+            self.replace_custom_event(event.name, event.parameters.into_iter().collect());
+        }
+        Ok(import)
+    }
 }
 
 #[cfg(test)]
@@ -453,6 +625,112 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    fn moved_parameters() -> HashMap<String, ParameterType> {
+        HashMap::from([
+            ("player_id".to_string(), ParameterType::String),
+            (
+                "path".to_string(),
+                ParameterType::List(Box::new(ParameterType::Float)),
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_export_and_import_events() {
+        let mut source = EventRegistry::new();
+        source
+            .register_custom_event("PlayerMoved".to_string(), moved_parameters())
+            .unwrap();
+        source
+            .register_custom_event("GameOver".to_string(), HashMap::new())
+            .unwrap();
+
+        let snapshot = source.export_events();
+        let names: Vec<&str> = snapshot.events.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["GameOver", "PlayerMoved"]);
+
+        // 保存した形式から読み戻して取り込む
+        let stored = crate::persistence::to_stored(&snapshot).unwrap();
+        let restored: EventRegistrySnapshot = crate::persistence::from_stored(stored).unwrap();
+        let mut target = EventRegistry::new();
+        let import = target
+            .import_events(restored, ImportConflict::Error)
+            .unwrap();
+        assert_eq!(import.imported, vec!["GameOver", "PlayerMoved"]);
+        assert_eq!(target.export_events(), snapshot);
+
+        let moved = EventType::Custom("PlayerMoved".to_string());
+        let valid = vec![
+            ("player_id".to_string(), ParameterType::String),
+            (
+                "path".to_string(),
+                ParameterType::List(Box::new(ParameterType::Float)),
+            ),
+        ];
+        assert!(target.validate_parameters(&moved, &valid).is_ok());
+        let invalid = vec![
+            ("player_id".to_string(), ParameterType::Int),
+            (
+                "path".to_string(),
+                ParameterType::List(Box::new(ParameterType::Float)),
+            ),
+        ];
+        assert!(target.validate_parameters(&moved, &invalid).is_err());
+    }
+
+    #[test]
+    fn test_import_conflicts() {
+        let mut source = EventRegistry::new();
+        source
+            .register_custom_event("PlayerMoved".to_string(), moved_parameters())
+            .unwrap();
+        source
+            .register_custom_event("GameOver".to_string(), HashMap::new())
+            .unwrap();
+        let snapshot = source.export_events();
+
+        let mut target = EventRegistry::new();
+        target
+            .register_custom_event("PlayerMoved".to_string(), HashMap::new())
+            .unwrap();
+
+        // 重複があれば何も取り込まない
+        let error = target
+            .import_events(snapshot.clone(), ImportConflict::Error)
+            .unwrap_err();
+        assert!(
+            matches!(error, EventError::AlreadyRegistered { event_type } if event_type == "PlayerMoved")
+        );
+        assert!(!target.contains_event(&EventType::Custom("GameOver".to_string())));
+
+        let import = target
+            .import_events(snapshot.clone(), ImportConflict::Skip)
+            .unwrap();
+        assert_eq!(import.imported, vec!["GameOver"]);
+        assert_eq!(import.skipped, vec!["PlayerMoved"]);
+        let moved = EventType::Custom("PlayerMoved".to_string());
+        assert!(target.get_event_info(&moved).unwrap().parameters.is_empty());
+
+        let import = target
+            .import_events(snapshot.clone(), ImportConflict::Overwrite)
+            .unwrap();
+        assert_eq!(import.overwritten, vec!["GameOver", "PlayerMoved"]);
+        assert_eq!(target.export_events(), snapshot);
+    }
+
+    #[test]
+    fn test_snapshot_of_unknown_version() {
+        let stored = serde_json::json!({
+            "version": 2,
+            "payload": { "events": [] },
+        });
+        let error = crate::persistence::from_stored::<EventRegistrySnapshot>(stored).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unsupported event registry version 2, this build reads versions 1 to 1"
+        );
+    }
+
     #[test]
     fn test_parameter_validation() {
         let mut registry = EventRegistry::new();
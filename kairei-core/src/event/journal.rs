@@ -0,0 +1,320 @@
+//! # Event Journal
+//!
+//! The journal records the custom events sent to a system from outside, with
+//! [`System::send_event`], so that a later run can rebuild the state of its agents by
+//! replaying them. Each event is written in the [`JOURNAL_NAMESPACE`] namespace of a
+//! [`StorageBackend`] before it is published, under a zero-padded sequence number.
+//!
+//! On startup [`System::initialize`] reads the journal, and each initial user agent
+//! runs the observe and react handlers of the journaled events, oldest first, before
+//! it handles new events. Replayed events are marked with [`REPLAY_KEY`], as are the
+//! events their handlers emit, so handlers can tell them from live ones;
+//! `@replay(skip)` handlers do not run for them.
+//!
+//! Events emitted by handlers are not journaled: they are emitted again when their
+//! handlers replay.
+//!
+//! [`System::send_event`]: crate::system::System::send_event
+//! [`System::initialize`]: crate::system::System::initialize
+//! [`REPLAY_KEY`]: super::event_bus::REPLAY_KEY
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use super::{
+    event_bus::{Event, Value},
+    event_registry::EventType,
+};
+use crate::{
+    MicroAgentDef, ReplayPolicy, ast,
+    eval::set::ValueSet,
+    persistence::{self, Versioned},
+    provider::capabilities::{
+        shared_memory::Metadata,
+        storage::{StorageBackend, StorageError, ValueWithMetadata},
+    },
+};
+
+/// Storage namespace of the journaled events, keyed by sequence number
+pub const JOURNAL_NAMESPACE: &str = "event_journal";
+
+/// Persisted form of a journaled event. Embeds [`JournalValue`]s, so a new version
+/// of their representation is a new version of this one too.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct JournalEntry {
+    pub(crate) sequence: u64,
+    pub(crate) event_type: String,
+    pub(crate) parameters: HashMap<String, JournalValue>,
+    pub(crate) event_id: Option<String>,
+    pub(crate) parent_event_id: Option<String>,
+    pub(crate) root_event_id: Option<String>,
+}
+
+impl Versioned for JournalEntry {
+    const KIND: &'static str = "journaled event";
+    const VERSION: u32 = 1;
+}
+
+// event_bus::Value の保存用の写し
+#[derive(Serialize, Deserialize)]
+pub(crate) enum JournalValue {
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Boolean(bool),
+    List(Vec<JournalValue>),
+    Duration(Duration),
+    Map(HashMap<String, JournalValue>),
+    Set(ValueSet),
+    Null,
+}
+
+impl From<&Value> for JournalValue {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Integer(i) => Self::Integer(*i),
+            Value::Float(f) => Self::Float(*f),
+            Value::String(s) => Self::String(s.clone()),
+            Value::Boolean(b) => Self::Boolean(*b),
+            Value::List(l) => Self::List(l.iter().map(Self::from).collect()),
+            Value::Duration(d) => Self::Duration(*d),
+            Value::Map(m) => Self::Map(m.iter().map(|(k, v)| (k.clone(), v.into())).collect()),
+            Value::Set(s) => Self::Set(s.clone()),
+            Value::Null => Self::Null,
+        }
+    }
+}
+
+impl From<JournalValue> for Value {
+    fn from(value: JournalValue) -> Self {
+        match value {
+            JournalValue::Integer(i) => Value::Integer(i),
+            JournalValue::Float(f) => Value::Float(f),
+            JournalValue::String(s) => Value::String(s),
+            JournalValue::Boolean(b) => Value::Boolean(b),
+            JournalValue::List(l) => Value::List(l.into_iter().map(Value::from).collect()),
+            JournalValue::Duration(d) => Value::Duration(d),
+            JournalValue::Map(m) => Value::Map(m.into_iter().map(|(k, v)| (k, v.into())).collect()),
+            JournalValue::Set(s) => Value::Set(s),
+            JournalValue::Null => Value::Null,
+        }
+    }
+}
+
+impl JournalEntry {
+    fn new(sequence: u64, event: &Event) -> Self {
+        Self {
+            sequence,
+            event_type: event.event_type.to_string(),
+            parameters: event
+                .parameters
+                .iter()
+                .map(|(k, v)| (k.clone(), v.into()))
+                .collect(),
+            event_id: event.event_id.clone(),
+            parent_event_id: event.parent_event_id.clone(),
+            root_event_id: event.root_event_id.clone(),
+        }
+    }
+
+    pub(crate) fn into_event(self) -> Event {
+        Event {
+            event_type: EventType::Custom(self.event_type),
+            parameters: self
+                .parameters
+                .into_iter()
+                .map(|(k, v)| (k, v.into()))
+                .collect(),
+            event_id: self.event_id,
+            parent_event_id: self.parent_event_id,
+            root_event_id: self.root_event_id,
+        }
+    }
+}
+
+/// Events sent to the system, in the order they were sent
+pub struct EventJournal {
+    store: Arc<dyn StorageBackend>,
+    // 次に書く連番。最初の読み書きでストアから求める
+    next_sequence: Mutex<Option<u64>>,
+}
+
+impl EventJournal {
+    pub fn new(store: Arc<dyn StorageBackend>) -> Self {
+        Self {
+            store,
+            next_sequence: Mutex::new(None),
+        }
+    }
+
+    /// Whether `event` is journaled: custom events that are not replays
+    pub fn records(event: &Event) -> bool {
+        matches!(event.event_type, EventType::Custom(_)) && !event.is_replay()
+    }
+
+    /// Writes `event` after the events already journaled
+    pub async fn append(&self, event: &Event) -> Result<(), StorageError> {
+        let mut next_sequence = self.next_sequence.lock().await;
+        let sequence = match *next_sequence {
+            Some(sequence) => sequence,
+            None => Self::sequence_after(&self.entries().await?),
+        };
+        let entry = JournalEntry::new(sequence, event);
+        let value = ValueWithMetadata {
+            value: persistence::to_stored(&entry).map_err(StorageError::from)?,
+            metadata: Metadata::default(),
+            expiry: None,
+        };
+        self.store
+            .save_key(JOURNAL_NAMESPACE, &format!("{:020}", sequence), &value)
+            .await?;
+        *next_sequence = Some(sequence + 1);
+        Ok(())
+    }
+
+    /// The journaled events, oldest first. Entries that cannot be read are skipped.
+    pub async fn events(&self) -> Result<Vec<Event>, StorageError> {
+        let mut next_sequence = self.next_sequence.lock().await;
+        let entries = self.entries().await?;
+        next_sequence.get_or_insert(Self::sequence_after(&entries));
+        Ok(entries.into_iter().map(|(_, event)| event).collect())
+    }
+
+    /// The readable entries of the store, ordered by sequence number
+    async fn entries(&self) -> Result<Vec<(u64, Event)>, StorageError> {
+        let stored = self.store.load(JOURNAL_NAMESPACE).await?;
+        let mut entries = vec![];
+        for (key, entry) in stored {
+            // 読めないものはストアに残したまま飛ばす
+            match persistence::from_stored::<JournalEntry>(entry.value) {
+                Ok(entry) => entries.push((entry.sequence, entry.into_event())),
+                Err(e) => warn!("Skipping unreadable journaled event {}: {}", key, e),
+            }
+        }
+        entries.sort_by_key(|(sequence, _)| *sequence);
+        Ok(entries)
+    }
+
+    fn sequence_after(entries: &[(u64, Event)]) -> u64 {
+        entries.last().map_or(0, |(sequence, _)| sequence + 1)
+    }
+}
+
+/// The replay of the event journal planned by [`System::initialize`]
+///
+/// [`System::initialize`]: crate::system::System::initialize
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReplayReport {
+    /// Journaled events replayed to the initial user agents
+    pub events: usize,
+    /// Handler invocations not run again, as their handlers are `@replay(skip)`
+    pub skipped: Vec<SkippedReplay>,
+    /// Handlers run again by the replay without a `@replay` directive, as
+    /// `Agent.EventType`
+    pub unannotated: Vec<String>,
+}
+
+/// A journaled event a `@replay(skip)` handler does not handle again
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkippedReplay {
+    pub agent: String,
+    pub event_type: String,
+    pub event_id: Option<String>,
+}
+
+impl ReplayReport {
+    /// Plans the replay of `events` to the handlers of `agents`
+    pub fn plan(events: &[Event], agents: &[MicroAgentDef]) -> Self {
+        let mut report = Self {
+            events: events.len(),
+            ..Default::default()
+        };
+        for agent in agents {
+            let handlers = agent
+                .observe
+                .iter()
+                .flat_map(|observe| &observe.handlers)
+                .chain(agent.react.iter().flat_map(|react| &react.handlers));
+            for handler in handlers {
+                let ast::EventType::Custom(name) = &handler.event_type else {
+                    continue;
+                };
+                let replayed = events
+                    .iter()
+                    .filter(|event| matches!(&event.event_type, EventType::Custom(n) if n == name));
+                match handler.replay.as_ref().and_then(|replay| replay.policy()) {
+                    Some(ReplayPolicy::Skip) => {
+                        report.skipped.extend(replayed.map(|event| SkippedReplay {
+                            agent: agent.name.clone(),
+                            event_type: name.clone(),
+                            event_id: event.event_id.clone(),
+                        }))
+                    }
+                    Some(ReplayPolicy::Safe) => {}
+                    None => {
+                        let handler = format!("{}.{}", agent.name, name);
+                        if replayed.count() > 0 && !report.unannotated.contains(&handler) {
+                            report.unannotated.push(handler);
+                        }
+                    }
+                }
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::{
+        config::plugins::InMemoryConfig, plugins::storage::in_memory::InMemoryBackend,
+    };
+
+    fn order(id: i64) -> Event {
+        Event {
+            event_type: EventType::Custom("OrderPlaced".to_string()),
+            parameters: HashMap::from([
+                ("id".to_string(), Value::Integer(id)),
+                (
+                    "items".to_string(),
+                    Value::List(vec![Value::String("book".to_string()), Value::Null]),
+                ),
+            ]),
+            event_id: Some(format!("event-{}", id)),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_events_are_read_back_in_order() {
+        let store: Arc<dyn StorageBackend> =
+            Arc::new(InMemoryBackend::new(InMemoryConfig::default()));
+        let journal = EventJournal::new(store.clone());
+        for id in 0..12 {
+            journal.append(&order(id)).await.unwrap();
+        }
+        let events = journal.events().await.unwrap();
+        assert_eq!(events, (0..12).map(order).collect::<Vec<_>>());
+
+        // 次の実行は続きの連番から書く
+        let journal = EventJournal::new(store);
+        journal.append(&order(12)).await.unwrap();
+        let events = journal.events().await.unwrap();
+        assert_eq!(events.len(), 13);
+        assert_eq!(events[12], order(12));
+    }
+
+    #[test]
+    fn test_only_sent_custom_events_are_recorded() {
+        assert!(EventJournal::records(&order(1)));
+        assert!(!EventJournal::records(&order(1).replayed()));
+        assert!(!EventJournal::records(&Event {
+            event_type: EventType::Tick,
+            ..Default::default()
+        }));
+    }
+}
@@ -0,0 +1,253 @@
+//! # Event Lineage
+//!
+//! Every event published on the [`EventBus`](super::event_bus::EventBus) gets an
+//! `event_id`. When a handler emits an event or sends a request, the execution context
+//! stamps it with the ID of the event that triggered the handler (`parent_event_id`)
+//! and the first event of the chain (`root_event_id`).
+//!
+//! The bus keeps a bounded log of these links so that a causal chain such as
+//! "A reacts to X and emits Y, B reacts to Y and requests C" can be reconstructed
+//! afterwards with [`EventLineageLog::get_lineage`].
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::event_bus::{Event, EventCategory};
+
+/// Default number of events kept in the lineage log
+pub const DEFAULT_LINEAGE_CAPACITY: usize = 10_000;
+
+/// Default depth limit for lineage queries, counted both up and down from the event
+pub const DEFAULT_LINEAGE_DEPTH: usize = 32;
+
+/// Lineage of a single published event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LineageRecord {
+    pub event_id: String,
+    pub parent_event_id: Option<String>,
+    pub root_event_id: String,
+    pub event_type: String,
+    /// `request`, `response`, `agent`, `system` or `component`
+    pub category: String,
+    pub published_at: DateTime<Utc>,
+    /// Publish order on the bus
+    pub sequence: u64,
+}
+
+/// A lineage record and the events it caused, ordered by publish order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LineageNode {
+    #[serde(flatten)]
+    pub record: LineageRecord,
+    pub children: Vec<LineageNode>,
+}
+
+impl LineageNode {
+    /// Event IDs of the tree in depth-first order
+    pub fn event_ids(&self) -> Vec<String> {
+        let mut ids = vec![self.record.event_id.clone()];
+        for child in &self.children {
+            ids.extend(child.event_ids());
+        }
+        ids
+    }
+}
+
+/// Bounded log of published events and their causal links.
+/// The oldest records are evicted first once the capacity is reached.
+#[derive(Debug)]
+pub struct EventLineageLog {
+    capacity: usize,
+    sequence: AtomicU64,
+    entries: Mutex<LineageEntries>,
+}
+
+#[derive(Debug, Default)]
+struct LineageEntries {
+    records: HashMap<String, LineageRecord>,
+    children: HashMap<String, Vec<String>>,
+    order: VecDeque<String>,
+}
+
+impl Default for EventLineageLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_LINEAGE_CAPACITY)
+    }
+}
+
+impl EventLineageLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            sequence: AtomicU64::new(0),
+            entries: Mutex::new(LineageEntries::default()),
+        }
+    }
+
+    /// Record a published event. The event must already carry its IDs.
+    pub fn record(&self, event: &Event) {
+        let (Some(event_id), Some(root_event_id)) = (&event.event_id, &event.root_event_id) else {
+            return;
+        };
+        let record = LineageRecord {
+            event_id: event_id.clone(),
+            parent_event_id: event.parent_event_id.clone(),
+            root_event_id: root_event_id.clone(),
+            event_type: event.event_type.to_string(),
+            category: category_name(&event.category()).to_string(),
+            published_at: Utc::now(),
+            sequence: self.sequence.fetch_add(1, Ordering::SeqCst),
+        };
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.records.contains_key(event_id) {
+            return;
+        }
+        while entries.order.len() >= self.capacity {
+            let Some(oldest) = entries.order.pop_front() else {
+                break;
+            };
+            if let Some(evicted) = entries.records.remove(&oldest) {
+                if let Some(parent) = &evicted.parent_event_id {
+                    if let Some(siblings) = entries.children.get_mut(parent) {
+                        siblings.retain(|id| id != &oldest);
+                    }
+                }
+            }
+            entries.children.remove(&oldest);
+        }
+        if let Some(parent) = &record.parent_event_id {
+            entries
+                .children
+                .entry(parent.clone())
+                .or_default()
+                .push(event_id.clone());
+        }
+        entries.order.push_back(event_id.clone());
+        entries.records.insert(event_id.clone(), record);
+    }
+
+    pub fn get(&self, event_id: &str) -> Option<LineageRecord> {
+        self.entries.lock().unwrap().records.get(event_id).cloned()
+    }
+
+    /// The `limit` most recently published events still in the log, in publish order
+    pub fn recent(&self, limit: usize) -> Vec<LineageRecord> {
+        let entries = self.entries.lock().unwrap();
+        let skip = entries.order.len().saturating_sub(limit);
+        entries
+            .order
+            .iter()
+            .skip(skip)
+            .filter_map(|id| entries.records.get(id).cloned())
+            .collect()
+    }
+
+    /// Reconstruct the causal tree around `event_id`.
+    ///
+    /// Walks up the parent links at most `max_depth` steps (stopping at the root or at
+    /// an evicted ancestor), then returns the tree below that ancestor, at most
+    /// `max_depth` levels deep. Returns `None` if the event is not in the log.
+    pub fn get_lineage(&self, event_id: &str, max_depth: usize) -> Option<LineageNode> {
+        let entries = self.entries.lock().unwrap();
+        let mut top = entries.records.get(event_id)?;
+        for _ in 0..max_depth {
+            match top
+                .parent_event_id
+                .as_ref()
+                .and_then(|parent| entries.records.get(parent))
+            {
+                Some(parent) => top = parent,
+                None => break,
+            }
+        }
+        Some(entries.subtree(top, max_depth))
+    }
+}
+
+fn category_name(category: &EventCategory) -> &'static str {
+    match category {
+        EventCategory::Request { .. } => "request",
+        EventCategory::Response => "response",
+        EventCategory::Agent => "agent",
+        EventCategory::System => "system",
+        EventCategory::Component => "component",
+    }
+}
+
+impl LineageEntries {
+    fn subtree(&self, record: &LineageRecord, depth: usize) -> LineageNode {
+        let mut children: Vec<LineageNode> = if depth == 0 {
+            vec![]
+        } else {
+            self.children
+                .get(&record.event_id)
+                .into_iter()
+                .flatten()
+                .filter_map(|id| self.records.get(id))
+                .map(|child| self.subtree(child, depth - 1))
+                .collect()
+        };
+        children.sort_by_key(|child| child.record.sequence);
+        LineageNode {
+            record: record.clone(),
+            children,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_registry::EventType;
+
+    fn event(id: &str, parent: Option<&Event>) -> Event {
+        let event = Event {
+            event_type: EventType::Custom(id.to_string()),
+            ..Default::default()
+        };
+        let event = match parent {
+            Some(parent) => event.caused_by(parent),
+            None => event,
+        };
+        event.assign_id(id.to_string())
+    }
+
+    #[test]
+    fn test_lineage_depth_and_eviction() {
+        let log = EventLineageLog::new(3);
+        let a = event("a", None);
+        let b = event("b", Some(&a));
+        let c = event("c", Some(&b));
+        for e in [&a, &b, &c] {
+            log.record(e);
+        }
+        assert_eq!(c.root_event_id.as_deref(), Some("a"));
+
+        let tree = log.get_lineage("c", 8).unwrap();
+        assert_eq!(tree.event_ids(), vec!["a", "b", "c"]);
+
+        // 深さ制限: 1 段だけ遡り、1 段だけ下る
+        let tree = log.get_lineage("c", 1).unwrap();
+        assert_eq!(tree.event_ids(), vec!["b", "c"]);
+
+        // 容量超過で最古の "a" が消える
+        log.record(&event("d", Some(&c)));
+        assert!(log.get("a").is_none());
+        let tree = log.get_lineage("d", 8).unwrap();
+        assert_eq!(tree.event_ids(), vec!["b", "c", "d"]);
+        assert!(log.get_lineage("a", 8).is_none());
+
+        let recent: Vec<String> = log.recent(2).into_iter().map(|r| r.event_id).collect();
+        assert_eq!(recent, vec!["c", "d"]);
+        assert_eq!(log.recent(10).len(), 3);
+    }
+}
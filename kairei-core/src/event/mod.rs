@@ -10,6 +10,13 @@
 //! - **EventBus**: Central hub for publishing and subscribing to events using a broadcast channel
 //! - **EventRegistry**: Registry of event types with parameter validation
 //! - **RequestManager**: Manages request-response patterns with timeout handling
+//! - **EventJournal**: Record of the events sent to the system, replayed on startup
+//! - **Critical delivery**: Acknowledged, retried delivery of events that must reach
+//!   their consumers, published with `EventBus::publish_critical`
+//! - **DispatchIndex**: Routes each event only to the agents with a handler for it
+//! - **StreamEvent**: Typed events of a streamed response, as sent over the wire
+//! - **Wire / Transport**: Binary encoding of events and framing over TCP, for
+//!   forwarding events between processes
 //!
 //! ## Event Flow
 //!
@@ -60,6 +67,7 @@
 //!         params.insert("user_id".to_string(), Value::String("12345".to_string()));
 //!         params
 //!     },
+//!     ..Default::default()
 //! };
 //! event_bus.publish(event).await?;
 //! # Ok(())
@@ -126,6 +134,14 @@
 //! # }
 //! ```
 
+pub mod coercion;
+pub mod critical;
+pub mod dispatch;
 pub mod event_bus;
 pub mod event_registry;
+pub mod journal;
+pub mod lineage;
 pub mod request_manager;
+pub mod stream_event;
+pub mod transport;
+pub mod wire;
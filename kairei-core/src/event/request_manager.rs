@@ -10,6 +10,15 @@
 //! - **Timeout Handling**: Automatically times out requests that don't receive responses
 //! - **Response Awaiting**: Provides a Future that resolves when a response is received
 //! - **Cancellation**: Supports cancelling pending requests when a component shuts down
+//! - **Streaming**: Delivers partial responses (`yield` in answer handlers) followed
+//!   by the final response through [`RequestManager::request_streaming`]
+//! - **Deadline Propagation**: Every request carries its deadline. A request sent
+//!   while handling another one inherits the caller's deadline through
+//!   [`RequestManager::request_within`], and fails fast once it has passed
+//! - **Retry Budget**: [`RetryBudget`] limits the retries of `retry(...)` across a
+//!   chain of agent requests
+//! - **Request Dedup**: [`DedupWindow`] lets a responder answer a request delivered
+//!   again, e.g. by a client retry, with the response of the original
 //!
 //! ## Implementation Details
 //!
@@ -18,18 +27,38 @@
 //! is made, a oneshot receiver is registered, and the corresponding sender is stored
 //! with the request ID. When a matching response arrives, it's forwarded through
 //! the oneshot channel to awaken the waiting task.
+//!
+//! Streaming requests use an mpsc channel instead, so that every partial response
+//! can be forwarded before the final one closes the stream. Non-streaming requests
+//! ignore partial responses and only resolve with the final response.
 
-use std::{sync::Arc, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    future::Future,
+    pin::Pin,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU32, AtomicU64, Ordering},
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
 
+use chrono::{TimeDelta, Utc};
 use dashmap::DashMap;
+use futures::Stream;
 use thiserror::Error;
-use tokio::sync::oneshot;
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::{Instant, Sleep},
+};
 use tracing::{debug, instrument};
 
 use super::{
-    event_bus::{Event, EventBus, EventError, Value},
+    event_bus::{Event, EventBus, EventError, RETRY_BUDGET_KEY, Value},
     event_registry::EventType,
 };
+use crate::config::RequestDedupConfig;
 
 /// Type alias for request correlation identifiers
 type RequestId = String;
@@ -45,6 +74,14 @@ pub struct PendingRequest {
     request_event_type: EventType,
 }
 
+/// A streaming request awaiting its partial and final responses
+pub struct StreamingRequest {
+    /// Channel forwarding every response event to the [`ResponseStream`]
+    sender: mpsc::UnboundedSender<Event>,
+    /// The original request event type (for cancellation)
+    request_event_type: EventType,
+}
+
 /// # Request Manager
 ///
 /// Coordinates the request-response pattern on top of the event bus.
@@ -57,8 +94,12 @@ pub struct RequestManager {
     event_bus: Arc<EventBus>,
     /// Map of pending requests indexed by request ID
     pending_requests: Arc<DashMap<RequestId, PendingRequest>>,
+    /// Map of streaming requests indexed by request ID
+    streaming_requests: Arc<DashMap<RequestId, StreamingRequest>>,
     /// Default timeout duration for requests that don't specify one
     default_timeout: Duration,
+    /// Requests recently received by the agent, answered again on redelivery
+    dedup: DedupWindow,
 }
 
 impl RequestManager {
@@ -86,10 +127,27 @@ impl RequestManager {
         Self {
             event_bus,
             pending_requests: Arc::new(DashMap::new()),
+            streaming_requests: Arc::new(DashMap::new()),
             default_timeout: timeout,
+            dedup: DedupWindow::new(&RequestDedupConfig::default()),
         }
     }
 
+    /// Remember the requests received for `config.window`, see [`DedupWindow`]
+    pub fn with_dedup_window(mut self, config: &RequestDedupConfig) -> Self {
+        self.dedup = DedupWindow::new(config);
+        self
+    }
+
+    /// Whether the agent should run `request`, or answer it as a request seen before
+    pub fn admit(&self, request: &Event) -> Admission {
+        self.dedup.admit(request)
+    }
+
+    pub fn dedup_stats(&self) -> DedupStats {
+        self.dedup.stats()
+    }
+
     /// Sends a request event and waits for a matching response.
     ///
     /// This method provides a synchronous request-response pattern by:
@@ -112,6 +170,7 @@ impl RequestManager {
     /// * `RequestError::Timeout` - If no response is received within the timeout period
     /// * `RequestError::EventBus` - If publishing the request fails
     /// * `RequestError::ChannelClosed` - If the response channel unexpectedly closes
+    /// * `RequestError::DeadlineExceeded` - If the request's deadline has already passed
     ///
     /// # Example
     ///
@@ -155,6 +214,8 @@ impl RequestManager {
                 "Request ID not found in event".to_string(),
             ))?
             .to_string();
+        // 期限切れなら送らずに失敗させる
+        let (request, timeout) = self.within_deadline(&request_id, request)?;
 
         self.pending_requests.insert(
             request_id.clone(),
@@ -164,13 +225,94 @@ impl RequestManager {
             },
         );
 
-        let timeout = self.timeout(request);
-        self.event_bus.publish(request.clone()).await?;
+        self.event_bus.publish(request).await?;
 
         // Wait for the response
         self.await_response(request_id, timeout, rx).await
     }
 
+    /// Sends a request made while handling `caller`, e.g. by an answer handler.
+    ///
+    /// The request inherits the caller's deadline, so it waits no longer than the
+    /// caller has left. Once that time is up it is not sent at all.
+    ///
+    /// # Errors
+    ///
+    /// As [`Self::request`], and `RequestError::DeadlineExceeded` if the caller's
+    /// deadline has already passed
+    pub async fn request_within(&self, caller: &Event, request: &Event) -> RequestResult<Event> {
+        let request = match caller.deadline() {
+            Some(deadline) => request.clone().with_deadline(deadline),
+            None => request.clone(),
+        };
+        self.request(&request).await
+    }
+
+    /// Sends a request event and returns a stream of its responses.
+    ///
+    /// The stream yields every partial response (`EventType::ResponsePartial`) in the
+    /// order the responder published them, then the final success or failure response,
+    /// and ends. The request timeout applies to the wait for each next event, so a
+    /// long answer keeps streaming as long as the responder makes progress.
+    ///
+    /// # Errors
+    ///
+    /// * `RequestError::InvalidRequest` - If the request is missing required fields
+    /// * `RequestError::EventBus` - If publishing the request fails
+    ///
+    /// The stream itself yields `RequestError::Timeout` when no event arrives in time.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use kairei_core::event_bus::{EventBus, Event};
+    /// # use kairei_core::event::request_manager::RequestManager;
+    /// # use futures::StreamExt;
+    /// # use std::sync::Arc;
+    /// # use std::time::Duration;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let event_bus = Arc::new(EventBus::new(10));
+    /// # let request_manager = Arc::new(RequestManager::new(event_bus.clone(), Duration::from_secs(5)));
+    /// # let request = Event::request_builder()
+    /// #     .request_type("chat")
+    /// #     .requester("client")
+    /// #     .responder("assistant")
+    /// #     .request_id("test-id")
+    /// #     .build()?;
+    /// let mut responses = request_manager.request_streaming(&request).await?;
+    /// while let Some(response) = responses.next().await {
+    ///     let event = response?;
+    ///     println!("{}: {:?}", event.event_type.is_partial_response(), event.response_value());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(skip(self))]
+    pub async fn request_streaming(&self, request: &Event) -> RequestResult<ResponseStream> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let request_id = request
+            .event_type
+            .request_id()
+            .ok_or(RequestError::InvalidRequest(
+                "Request ID not found in event".to_string(),
+            ))?
+            .to_string();
+        let (request, timeout) = self.within_deadline(&request_id, request)?;
+
+        self.streaming_requests.insert(
+            request_id.clone(),
+            StreamingRequest {
+                sender: tx,
+                request_event_type: request.event_type.clone(),
+            },
+        );
+        let stream = ResponseStream::new(request_id, rx, timeout, self.streaming_requests.clone());
+
+        // 失敗時は stream の drop で登録が解除される
+        self.event_bus.publish(request).await?;
+        Ok(stream)
+    }
+
     #[instrument(skip(self, rx))]
     async fn await_response(
         &self,
@@ -207,7 +349,21 @@ impl RequestManager {
     /// EventカテゴリーResponseのみを処理。それ以外はエラーを返す
     #[instrument(skip(self))]
     pub fn handle_event(&self, event: &Event) -> RequestResult<()> {
-        if event.event_type.is_response() {
+        if event.event_type.is_partial_response() {
+            // 部分応答はストリーミングリクエストにのみ転送する
+            let request_id = event
+                .event_type
+                .request_id()
+                .ok_or(RequestError::InvalidRequest(
+                    "Request ID not found in event".to_string(),
+                ))?;
+            if let Some(streaming) = self.streaming_requests.get(request_id) {
+                let _ = streaming.sender.send(event.clone());
+            }
+            Ok(())
+        } else if event.event_type.is_response() {
+            // 自身が受けたリクエストへの応答なら、待っている再送に渡して覚えておく
+            self.dedup.complete(event);
             event
                 .event_type
                 .request_id()
@@ -218,6 +374,9 @@ impl RequestManager {
                             event, pending.1.request_event_type
                         );
                         let _ = pending.1.sender.send(event.clone());
+                    } else if let Some((_, streaming)) = self.streaming_requests.remove(id) {
+                        // 最終応答を送って sender を drop し、ストリームを閉じる
+                        let _ = streaming.sender.send(event.clone());
                     }
                 })
                 .ok_or(RequestError::InvalidRequest(
@@ -240,43 +399,96 @@ impl RequestManager {
         for entry in self.pending_requests.iter() {
             let request_id = entry.key();
             if let Some((_, pending_request)) = self.pending_requests.remove(request_id) {
-                match &pending_request.request_event_type {
-                    EventType::Request {
-                        requester,
-                        responder,
-                        request_type,
-                        ..
-                    } => {
-                        if let Ok(response_failure) = Event::response_builder()
-                            .failure()
-                            .request_id(request_id)
-                            .requester(requester)
-                            .responder(responder)
-                            .request_type(request_type)
-                            .error(format!("request_cancelled: {}", failure_message).as_str())
-                            .build()
-                        {
-                            ret.push(response_failure.clone());
-                            let _ = pending_request.sender.send(response_failure);
-                        } else {
-                            return Err(RequestError::InvalidRequest(
-                                "Failed to create response".to_string(),
-                            ));
-                        }
-                    }
-                    _ => {
-                        return Err(RequestError::InvalidRequest(
-                            "EventType not supported".to_string(),
-                        ));
-                    }
-                }
+                let response_failure = Self::cancellation_response(
+                    request_id,
+                    &pending_request.request_event_type,
+                    failure_message,
+                )?;
+                ret.push(response_failure.clone());
+                let _ = pending_request.sender.send(response_failure);
             }
         }
         self.pending_requests.clear();
+
+        let streaming_ids: Vec<RequestId> = self
+            .streaming_requests
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect();
+        for request_id in streaming_ids {
+            if let Some((_, streaming_request)) = self.streaming_requests.remove(&request_id) {
+                let response_failure = Self::cancellation_response(
+                    &request_id,
+                    &streaming_request.request_event_type,
+                    failure_message,
+                )?;
+                ret.push(response_failure.clone());
+                let _ = streaming_request.sender.send(response_failure);
+            }
+        }
         Ok(ret)
     }
 
-    fn timeout(&self, event: &Event) -> Duration {
+    fn cancellation_response(
+        request_id: &str,
+        request_event_type: &EventType,
+        failure_message: &str,
+    ) -> RequestResult<Event> {
+        match request_event_type {
+            EventType::Request {
+                requester,
+                responder,
+                request_type,
+                ..
+            } => Event::response_builder()
+                .failure()
+                .request_id(request_id)
+                .requester(requester)
+                .responder(responder)
+                .request_type(request_type)
+                .error(format!("request_cancelled: {}", failure_message).as_str())
+                .build()
+                .map_err(|_| RequestError::InvalidRequest("Failed to create response".to_string())),
+            _ => Err(RequestError::InvalidRequest(
+                "EventType not supported".to_string(),
+            )),
+        }
+    }
+
+    /// The request to publish and how long to wait for it. A request without a
+    /// deadline gets one from its timeout; a request with one waits at most until
+    /// then, and fails with `RequestError::DeadlineExceeded` if it has passed.
+    fn within_deadline(
+        &self,
+        request_id: &str,
+        request: &Event,
+    ) -> RequestResult<(Event, Duration)> {
+        let timeout = self.timeout(request);
+        let now = Utc::now();
+        match request.deadline() {
+            Some(deadline) => {
+                let remaining = (deadline - now)
+                    .to_std()
+                    .ok()
+                    .filter(|remaining| !remaining.is_zero())
+                    .ok_or_else(|| RequestError::DeadlineExceeded(request_id.to_string()))?;
+                Ok((request.clone(), timeout.min(remaining)))
+            }
+            None => {
+                let deadline = TimeDelta::from_std(timeout)
+                    .ok()
+                    .and_then(|timeout| now.checked_add_signed(timeout));
+                let request = match deadline {
+                    Some(deadline) => request.clone().with_deadline(deadline),
+                    None => request.clone(),
+                };
+                Ok((request, timeout))
+            }
+        }
+    }
+
+    /// Timeout applied to `event`: its `timeout` parameter, or the default.
+    pub fn timeout(&self, event: &Event) -> Duration {
         match event.parameters.get("timeout") {
             Some(Value::Duration(d)) if *d > Duration::from_secs(1) => *d,
             _ => self.default_timeout,
@@ -284,10 +496,412 @@ impl RequestManager {
     }
 }
 
+/// Stream of the responses to a streaming request
+///
+/// Yields partial responses in order, then the final response, then ends.
+/// Dropping the stream unregisters the request, so late responses are discarded.
+pub struct ResponseStream {
+    request_id: RequestId,
+    receiver: mpsc::UnboundedReceiver<Event>,
+    timeout: Duration,
+    deadline: Pin<Box<Sleep>>,
+    streaming_requests: Arc<DashMap<RequestId, StreamingRequest>>,
+    finished: bool,
+}
+
+impl ResponseStream {
+    fn new(
+        request_id: RequestId,
+        receiver: mpsc::UnboundedReceiver<Event>,
+        timeout: Duration,
+        streaming_requests: Arc<DashMap<RequestId, StreamingRequest>>,
+    ) -> Self {
+        Self {
+            request_id,
+            receiver,
+            timeout,
+            deadline: Box::pin(tokio::time::sleep(timeout)),
+            streaming_requests,
+            finished: false,
+        }
+    }
+
+    pub fn request_id(&self) -> &str {
+        &self.request_id
+    }
+}
+
+impl Stream for ResponseStream {
+    type Item = RequestResult<Event>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.finished {
+            return Poll::Ready(None);
+        }
+
+        match this.receiver.poll_recv(cx) {
+            Poll::Ready(Some(event)) => {
+                if event.event_type.is_partial_response() {
+                    // 次のイベントまでのタイムアウトを延長
+                    let deadline = Instant::now() + this.timeout;
+                    this.deadline.as_mut().reset(deadline);
+                } else {
+                    this.finished = true;
+                }
+                Poll::Ready(Some(Ok(event)))
+            }
+            Poll::Ready(None) => {
+                this.finished = true;
+                Poll::Ready(Some(Err(RequestError::ChannelClosed)))
+            }
+            Poll::Pending => match this.deadline.as_mut().poll(cx) {
+                Poll::Ready(()) => {
+                    this.finished = true;
+                    this.streaming_requests.remove(&this.request_id);
+                    Poll::Ready(Some(Err(RequestError::Timeout(this.request_id.clone()))))
+                }
+                Poll::Pending => Poll::Pending,
+            },
+        }
+    }
+}
+
+impl Drop for ResponseStream {
+    fn drop(&mut self) {
+        self.streaming_requests.remove(&self.request_id);
+    }
+}
+
+/// # Retry Budget
+///
+/// Retries that `retry(...)` may still spend across a chain of agent requests,
+/// shared by the forks of a handler's context.
+///
+/// A request carries what is left in [`RETRY_BUDGET_KEY`], so the handlers it
+/// reaches start from there, and its response brings back what they left, so
+/// the caller does not spend the same retries again.
+#[derive(Debug)]
+pub struct RetryBudget {
+    remaining: AtomicU32,
+}
+
+impl RetryBudget {
+    pub fn new(retries: u32) -> Self {
+        Self {
+            remaining: AtomicU32::new(retries),
+        }
+    }
+
+    /// The budget of a handler run for `event`: what the request chain has left,
+    /// or `retries` for a chain starting here. Unlimited when neither is set.
+    pub fn for_event(event: &Event, retries: Option<u32>) -> Option<Self> {
+        event.retry_budget().or(retries).map(Self::new)
+    }
+
+    /// Takes one retry, `false` once none is left
+    pub fn try_spend(&self) -> bool {
+        self.remaining
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |remaining| {
+                remaining.checked_sub(1)
+            })
+            .is_ok()
+    }
+
+    pub fn remaining(&self) -> u32 {
+        self.remaining.load(Ordering::SeqCst)
+    }
+
+    /// Writes what is left into a request or response
+    pub fn stamp(&self, event: &mut Event) {
+        event.parameters.insert(
+            RETRY_BUDGET_KEY.to_string(),
+            Value::Integer(self.remaining() as i64),
+        );
+    }
+
+    /// Takes over what the responder left of the budget
+    pub fn settle(&self, response: &Event) {
+        if let Some(left) = response.retry_budget() {
+            self.remaining.fetch_min(left, Ordering::SeqCst);
+        }
+    }
+}
+
+/// What to do with a request received by an agent, see [`DedupWindow`]
+#[derive(Debug)]
+pub enum Admission {
+    /// Not seen within the window: run the handler
+    First,
+    /// The original is still running: send its response once it is published
+    InFlight(oneshot::Receiver<Event>),
+    /// The original was answered: send its response again
+    Answered(Box<Event>),
+}
+
+/// Counters of a [`DedupWindow`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DedupStats {
+    /// Duplicates that waited for the original's response
+    pub joined: u64,
+    /// Duplicates answered with a response sent before
+    pub replayed: u64,
+    /// Requests currently remembered
+    pub entries: usize,
+    /// Requests forgotten to make room for newer ones
+    pub evictions: u64,
+}
+
+/// Key of a request: its requester and request ID
+type DedupKey = (String, RequestId);
+
+enum SeenRequest {
+    /// Still running, with the senders of the duplicates waiting for it
+    InFlight(Vec<oneshot::Sender<Event>>),
+    Answered {
+        response: Box<Event>,
+        at: Instant,
+    },
+}
+
+struct DedupEntry {
+    responder: String,
+    seen: SeenRequest,
+    /// Position in [`DedupEntries::recency`]
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct DedupEntries {
+    entries: HashMap<DedupKey, DedupEntry>,
+    // 使った順の番号からキーへ。先頭ほど長く使われていない
+    recency: BTreeMap<u64, DedupKey>,
+    // 応答した順。期限切れは先頭から取り除く
+    answered: VecDeque<(Instant, DedupKey)>,
+    next_use: u64,
+}
+
+impl DedupEntries {
+    fn touch(&mut self, key: &DedupKey) {
+        let Some(entry) = self.entries.get_mut(key) else {
+            return;
+        };
+        self.recency.remove(&entry.last_used);
+        entry.last_used = self.next_use;
+        self.recency.insert(self.next_use, key.clone());
+        self.next_use += 1;
+    }
+
+    fn insert(&mut self, key: DedupKey, responder: String) {
+        self.remove(&key);
+        self.entries.insert(
+            key.clone(),
+            DedupEntry {
+                responder,
+                seen: SeenRequest::InFlight(Vec::new()),
+                last_used: self.next_use,
+            },
+        );
+        self.recency.insert(self.next_use, key);
+        self.next_use += 1;
+    }
+
+    fn remove(&mut self, key: &DedupKey) {
+        // 待っている再送は sender の drop で打ち切られる
+        if let Some(entry) = self.entries.remove(key) {
+            self.recency.remove(&entry.last_used);
+        }
+    }
+
+    /// Forgets the responses kept for longer than `window`. Requests still running
+    /// never expire.
+    fn expire(&mut self, now: Instant, window: Duration) {
+        while let Some((at, _)) = self.answered.front() {
+            if now.duration_since(*at) < window {
+                break;
+            }
+            let (at, key) = self.answered.pop_front().unwrap();
+            // 同じキーで受け直したものは残す
+            let expired = matches!(
+                self.entries.get(&key),
+                Some(DedupEntry { seen: SeenRequest::Answered { at: answered_at, .. }, .. })
+                    if *answered_at == at
+            );
+            if expired {
+                self.remove(&key);
+            }
+        }
+    }
+
+    /// Evicts the least recently used entries until one more fits, answered ones
+    /// before running ones. Returns how many were evicted.
+    fn make_room(&mut self, max_entries: usize) -> u64 {
+        let mut evicted = 0;
+        while !self.entries.is_empty() && self.entries.len() >= max_entries {
+            let victim = self
+                .recency
+                .values()
+                .find(|key| {
+                    matches!(
+                        self.entries.get(*key),
+                        Some(DedupEntry {
+                            seen: SeenRequest::Answered { .. },
+                            ..
+                        })
+                    )
+                })
+                .or_else(|| self.recency.values().next())
+                .cloned();
+            let Some(victim) = victim else {
+                break;
+            };
+            self.remove(&victim);
+            evicted += 1;
+        }
+        evicted
+    }
+}
+
+/// # Request Dedup Window
+///
+/// The requests an agent received recently, keyed by requester and request ID, so
+/// that a request delivered twice, by a client retry or the HTTP layer retrying,
+/// runs its handler once.
+///
+/// A duplicate that arrives while the original runs is answered with the original's
+/// response once it is published, however long that takes; one that arrives after
+/// is answered with the response kept from then. Both get the same response
+/// envelope. A response is forgotten `window` after it was sent, and at most
+/// `max_entries` requests are kept, evicting the least recently used answered one
+/// first. A running request is only evicted when every entry is running. Partial
+/// responses are not kept, so a duplicate of a streaming request only gets the
+/// final response.
+///
+/// This only covers retries within seconds. `@cache` answer handlers keep responses
+/// for longer, keyed by their parameters.
+pub struct DedupWindow {
+    window: Duration,
+    max_entries: usize,
+    seen: Mutex<DedupEntries>,
+    joined: AtomicU64,
+    replayed: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl DedupWindow {
+    pub fn new(config: &RequestDedupConfig) -> Self {
+        Self {
+            window: config.window,
+            max_entries: config.max_entries,
+            seen: Mutex::new(DedupEntries::default()),
+            joined: AtomicU64::new(0),
+            replayed: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    /// Records `request` as received, unless it duplicates one received before
+    pub fn admit(&self, request: &Event) -> Admission {
+        let EventType::Request {
+            requester,
+            responder,
+            request_id,
+            ..
+        } = &request.event_type
+        else {
+            return Admission::First;
+        };
+        if self.window.is_zero() || self.max_entries == 0 {
+            return Admission::First;
+        }
+
+        let key = (requester.clone(), request_id.clone());
+        let mut seen = self.seen.lock().unwrap();
+        seen.expire(Instant::now(), self.window);
+        let duplicate = seen
+            .entries
+            .get_mut(&key)
+            .filter(|entry| entry.responder == *responder);
+        let admission = match duplicate.map(|entry| &mut entry.seen) {
+            Some(SeenRequest::InFlight(waiters)) => {
+                let (tx, rx) = oneshot::channel();
+                waiters.push(tx);
+                self.joined.fetch_add(1, Ordering::Relaxed);
+                Admission::InFlight(rx)
+            }
+            Some(SeenRequest::Answered { response, .. }) => {
+                self.replayed.fetch_add(1, Ordering::Relaxed);
+                Admission::Answered(response.clone())
+            }
+            None => {
+                if !seen.entries.contains_key(&key) && seen.entries.len() >= self.max_entries {
+                    let evicted = seen.make_room(self.max_entries);
+                    self.evictions.fetch_add(evicted, Ordering::Relaxed);
+                }
+                seen.insert(key.clone(), responder.clone());
+                Admission::First
+            }
+        };
+        seen.touch(&key);
+        admission
+    }
+
+    /// Keeps the final `response` to a request admitted as first, and passes it to
+    /// the duplicates waiting for it
+    pub fn complete(&self, response: &Event) {
+        let (EventType::ResponseSuccess {
+            requester,
+            responder,
+            request_id,
+            ..
+        }
+        | EventType::ResponseFailure {
+            requester,
+            responder,
+            request_id,
+            ..
+        }) = &response.event_type
+        else {
+            return;
+        };
+        let key = (requester.clone(), request_id.clone());
+        let mut seen = self.seen.lock().unwrap();
+        let Some(entry) = seen.entries.get_mut(&key) else {
+            return;
+        };
+        // 再送への応答は記録を更新しない
+        if entry.responder != *responder || !matches!(entry.seen, SeenRequest::InFlight(_)) {
+            return;
+        }
+        let at = Instant::now();
+        let answered = SeenRequest::Answered {
+            response: Box::new(response.clone()),
+            at,
+        };
+        if let SeenRequest::InFlight(waiters) = std::mem::replace(&mut entry.seen, answered) {
+            for waiter in waiters {
+                let _ = waiter.send(response.clone());
+            }
+        }
+        seen.answered.push_back((at, key));
+    }
+
+    pub fn stats(&self) -> DedupStats {
+        DedupStats {
+            joined: self.joined.load(Ordering::Relaxed),
+            replayed: self.replayed.load(Ordering::Relaxed),
+            entries: self.seen.lock().unwrap().entries.len(),
+            evictions: self.evictions.load(Ordering::Relaxed),
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum RequestError {
     #[error("Request timed out: {0}")]
     Timeout(RequestId),
+    #[error("Deadline exceeded before the request was sent: {0}")]
+    DeadlineExceeded(RequestId),
     #[error("Response channel closed")]
     ChannelClosed,
     #[error("Event bus error: {0}")]
@@ -303,6 +917,10 @@ type RequestResult<T> = Result<T, RequestError>;
 #[cfg(test)]
 mod tests {
 
+    use std::collections::HashMap;
+
+    use futures::StreamExt;
+
     use super::*;
 
     use crate::event_bus::{self};
@@ -351,6 +969,8 @@ mod tests {
         let (event_bus, manager) = setup().await;
 
         let (request_event, response_event) = create_events("test");
+        // publish 時に振られる ID を固定しておく
+        let response_event = response_event.assign_id("response".to_string());
 
         // ハンドラーをSpawnする
         let manager_ref = manager.clone();
@@ -438,6 +1058,107 @@ mod tests {
         let _ = handler_task.await; // エラーは無視
     }
 
+    fn partial_response(request: &Event, sequence: i64) -> Event {
+        let EventType::Request {
+            request_type,
+            requester,
+            responder,
+            request_id,
+        } = request.event_type.clone()
+        else {
+            panic!("not a request");
+        };
+        let mut parameters = HashMap::new();
+        parameters.insert(
+            "response".to_string(),
+            event_bus::Value::String(format!("chunk{}", sequence)),
+        );
+        parameters.insert("sequence".to_string(), event_bus::Value::Integer(sequence));
+        Event {
+            event_type: EventType::ResponsePartial {
+                request_type,
+                requester,
+                responder,
+                request_id,
+            },
+            parameters,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_streaming() {
+        let (_, manager) = setup().await;
+        let (request_event, response_event) = create_events("stream");
+
+        let mut stream = manager.request_streaming(&request_event).await.unwrap();
+        manager
+            .handle_event(&partial_response(&request_event, 0))
+            .unwrap();
+        manager
+            .handle_event(&partial_response(&request_event, 1))
+            .unwrap();
+        manager.handle_event(&response_event).unwrap();
+
+        let first = stream.next().await.unwrap().unwrap();
+        assert_eq!(first.parameters.get("sequence"), Some(&Value::Integer(0)));
+        let second = stream.next().await.unwrap().unwrap();
+        assert_eq!(second.parameters.get("sequence"), Some(&Value::Integer(1)));
+        assert_eq!(stream.next().await.unwrap().unwrap(), response_event);
+        assert!(stream.next().await.is_none());
+        assert!(manager.streaming_requests.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_request_ignores_partial_responses() {
+        let (event_bus, manager) = setup().await;
+        let (request_event, response_event) = create_events("final");
+        let response_event = response_event.assign_id("response".to_string());
+
+        let manager_ref = manager.clone();
+        let (mut event_rx, _) = event_bus.subscribe();
+        let handler_task = tokio::spawn(async move {
+            while let Ok(event) = event_rx.recv().await {
+                let _ = manager_ref.handle_event(&event);
+            }
+        });
+
+        let request_task = tokio::spawn({
+            let manager = manager.clone();
+            async move { manager.request(&request_event).await }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let (partial_source, _) = create_events("final");
+        event_bus
+            .publish(partial_response(&partial_source, 0))
+            .await
+            .unwrap();
+        event_bus.publish(response_event.clone()).await.unwrap();
+
+        let result = request_task.await.unwrap().unwrap();
+        assert_eq!(result, response_event);
+
+        handler_task.abort();
+        let _ = handler_task.await;
+    }
+
+    #[tokio::test]
+    async fn test_request_streaming_timeout() {
+        let (event_bus, _) = setup().await;
+        let manager = RequestManager::new(event_bus, Duration::from_millis(100));
+        let (request_event, _) = create_events("slow");
+
+        let mut stream = manager.request_streaming(&request_event).await.unwrap();
+
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(RequestError::Timeout(_)))
+        ));
+        assert!(stream.next().await.is_none());
+        assert!(manager.streaming_requests.is_empty());
+    }
+
     #[tokio::test]
     async fn test_cancelled_request() {
         let (_, manager) = setup().await;
@@ -451,4 +1172,224 @@ mod tests {
         // pending_requestsからクリーンアップされていることを確認
         assert!(manager.pending_requests.is_empty());
     }
+
+    /// `name` 宛てのリクエストに `work` だけかけて応答するエージェント。`downstream` があれば
+    /// 処理中のリクエストの中から依頼し、その結果を `results` に送る
+    fn spawn_agent(
+        event_bus: Arc<EventBus>,
+        name: &'static str,
+        work: Duration,
+        downstream: Option<&'static str>,
+        results: mpsc::UnboundedSender<(&'static str, RequestResult<Event>, Duration)>,
+    ) -> tokio::task::JoinHandle<()> {
+        let manager = Arc::new(RequestManager::new(
+            event_bus.clone(),
+            Duration::from_secs(5),
+        ));
+        let (mut event_rx, _) = event_bus.subscribe();
+        tokio::spawn(async move {
+            while let Ok(event) = event_rx.recv().await {
+                let _ = manager.handle_event(&event);
+                let EventType::Request {
+                    responder,
+                    requester,
+                    request_type,
+                    request_id,
+                } = event.event_type.clone()
+                else {
+                    continue;
+                };
+                if responder != name {
+                    continue;
+                }
+                let manager = manager.clone();
+                let event_bus = event_bus.clone();
+                let results = results.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(work).await;
+                    if let Some(downstream) = downstream {
+                        let request = Event::request_builder()
+                            .request_type("plan")
+                            .requester(name)
+                            .responder(downstream)
+                            .request_id(&format!("{}-{}", name, downstream))
+                            .build()
+                            .unwrap();
+                        let started = Instant::now();
+                        let result = manager.request_within(&event, &request).await;
+                        let _ = results.send((name, result, started.elapsed()));
+                    }
+                    let response = Event::response_builder()
+                        .success()
+                        .request_type(&request_type)
+                        .requester(&requester)
+                        .responder(name)
+                        .request_id(&request_id)
+                        .response(Value::String(name.to_string()))
+                        .build()
+                        .unwrap();
+                    let _ = event_bus.publish(response).await;
+                });
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_deadline_propagates_through_agent_chain() {
+        let event_bus = Arc::new(EventBus::new(32));
+        let (results_tx, mut results) = mpsc::unbounded_channel();
+        // A -> B -> C。B の処理中にトップレベルの期限が切れる
+        let agents = [
+            spawn_agent(
+                event_bus.clone(),
+                "A",
+                Duration::ZERO,
+                Some("B"),
+                results_tx.clone(),
+            ),
+            spawn_agent(
+                event_bus.clone(),
+                "B",
+                Duration::from_millis(300),
+                Some("C"),
+                results_tx.clone(),
+            ),
+            spawn_agent(event_bus.clone(), "C", Duration::ZERO, None, results_tx),
+        ];
+        let client = RequestManager::new(event_bus.clone(), Duration::from_secs(5));
+        let (mut event_rx, _) = event_bus.subscribe();
+        let request = Event::request_builder()
+            .request_type("plan")
+            .requester("client")
+            .responder("A")
+            .request_id("top")
+            .build()
+            .unwrap()
+            .with_deadline(Utc::now() + TimeDelta::milliseconds(150));
+
+        let started = Instant::now();
+        let top = client.request(&request).await;
+
+        // トップレベルは既定の 5 秒ではなく、自身の期限で打ち切られる
+        assert!(matches!(top, Err(RequestError::Timeout(_))));
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        // A は自身の待ち時間が期限で切れ、B は期限切れ後に C へ依頼しようとする
+        let (name, upstream, _) = results.recv().await.unwrap();
+        assert_eq!(name, "A");
+        assert!(matches!(upstream, Err(RequestError::Timeout(_))));
+        let (name, deepest, elapsed) = results.recv().await.unwrap();
+        assert_eq!(name, "B");
+        assert!(
+            matches!(deepest, Err(RequestError::DeadlineExceeded(ref id)) if id == "B-C"),
+            "{:?}",
+            deepest
+        );
+        // 期限切れのリクエストは送られず、待たずに失敗する
+        assert!(elapsed < Duration::from_millis(50));
+        while let Ok(Ok(event)) =
+            tokio::time::timeout(Duration::from_millis(10), event_rx.recv()).await
+        {
+            assert_ne!(event.event_type.request_id(), Some("B-C"));
+        }
+
+        for agent in agents {
+            agent.abort();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_without_deadline_gets_one_from_timeout() {
+        let (event_bus, manager) = setup().await;
+        let (mut event_rx, _) = event_bus.subscribe();
+        let (request_event, _) = create_events("stamp");
+
+        let before = Utc::now();
+        let _ = manager.request_streaming(&request_event).await.unwrap();
+
+        let published = event_rx.recv().await.unwrap();
+        let deadline = published.deadline().unwrap();
+        assert!(deadline >= before + TimeDelta::seconds(5));
+        assert!(deadline <= Utc::now() + TimeDelta::seconds(5));
+    }
+
+    #[tokio::test]
+    async fn test_dedup_window_is_bounded() {
+        let window = DedupWindow::new(&RequestDedupConfig {
+            window: Duration::from_millis(100),
+            max_entries: 2,
+        });
+        let (request, response) = create_events("dedup");
+
+        assert!(matches!(window.admit(&request), Admission::First));
+        let Admission::InFlight(waiting) = window.admit(&request) else {
+            panic!("duplicate should wait for the original");
+        };
+        window.complete(&response);
+        assert_eq!(waiting.await.unwrap(), response);
+        assert!(matches!(window.admit(&request), Admission::Answered(ref r) if **r == response));
+
+        // 上限を超えると最も長く使われていないものから忘れる
+        let (a, _) = create_events("a");
+        let (b, _) = create_events("b");
+        assert!(matches!(window.admit(&a), Admission::First));
+        assert!(matches!(window.admit(&b), Admission::First));
+        assert!(matches!(window.admit(&request), Admission::First));
+        assert_eq!(
+            window.stats(),
+            DedupStats {
+                joined: 1,
+                replayed: 1,
+                entries: 2,
+                evictions: 2,
+            }
+        );
+
+        // 期限を過ぎた応答は忘れ、リクエストは再び実行される
+        let (_, b_response) = create_events("b");
+        window.complete(&b_response);
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(matches!(window.admit(&b), Admission::First));
+    }
+
+    #[tokio::test]
+    async fn test_dedup_window_keeps_running_requests() {
+        let window = DedupWindow::new(&RequestDedupConfig {
+            window: Duration::from_millis(50),
+            max_entries: 4,
+        });
+        let (request, response) = create_events("slow");
+
+        assert!(matches!(window.admit(&request), Admission::First));
+        // 期限を過ぎても実行中のリクエストには合流する
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let Admission::InFlight(waiting) = window.admit(&request) else {
+            panic!("duplicate of a running request should wait for it");
+        };
+        window.complete(&response);
+        assert_eq!(waiting.await.unwrap(), response);
+    }
+
+    #[test]
+    fn test_retry_budget_is_shared_along_the_chain() {
+        let (mut request, response) = create_events("budget");
+        let budget = RetryBudget::for_event(&request, Some(3)).unwrap();
+        assert!(budget.try_spend());
+
+        // 依頼先は残り 2 回から始め、1 回使って応答する
+        budget.stamp(&mut request);
+        let downstream = RetryBudget::for_event(&request, Some(3)).unwrap();
+        assert_eq!(downstream.remaining(), 2);
+        assert!(downstream.try_spend());
+        let mut response = response;
+        downstream.stamp(&mut response);
+
+        budget.settle(&response);
+        assert_eq!(budget.remaining(), 1);
+        assert!(budget.try_spend());
+        assert!(!budget.try_spend());
+        assert!(RetryBudget::for_event(&request, None).is_some());
+        let (unlimited, _) = create_events("unlimited");
+        assert!(RetryBudget::for_event(&unlimited, None).is_none());
+    }
 }
@@ -0,0 +1,316 @@
+//! Typed events of a streamed response.
+//!
+//! A streamed request yields the partial responses of its answer handler, then the
+//! final response (see [`RequestManager::request_streaming`]). [`StreamEvent`] is the
+//! form these take over the wire: each `yield`, and each piece of output generated by
+//! a `think` with `stream: true`, becomes a `token`, each `call_tool`
+//! a `tool_call_start` followed by its arguments in `tool_call_args_delta`, and the
+//! stream ends with the `usage` of the execution and `done`, or with `error`.
+//!
+//! Events other than tokens travel in partial responses as the JSON of the event
+//! under [`STREAM_EVENT_KEY`]; the usage travels with the final response under
+//! [`USAGE_KEY`].
+//!
+//! [`RequestManager::request_streaming`]: crate::event::request_manager::RequestManager::request_streaming
+
+use std::collections::HashMap;
+
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::{
+    event_bus::{Event, Value},
+    event_registry::EventType,
+    request_manager::RequestError,
+};
+use crate::eval::budget::ExecutionCounters;
+
+/// Parameter key of a partial response carrying a [`StreamEvent`] as JSON
+pub const STREAM_EVENT_KEY: &str = "stream_event";
+
+/// Parameter key of a final response carrying the [`StreamUsage`] of the execution
+pub const USAGE_KEY: &str = "usage";
+
+/// An event of a streamed response, serialized with its kind in `type`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamEvent {
+    /// A chunk of the answer, published by `yield`
+    Token { content: String },
+    /// The handler started a tool call
+    ToolCallStart { id: String, name: String },
+    /// Arguments of a started tool call, as JSON text
+    ToolCallArgsDelta { id: String, delta: String },
+    /// Usage of the execution, sent before `done` or `error`
+    Usage(StreamUsage),
+    /// The final answer; the stream ends
+    Done { response: serde_json::Value },
+    /// The request failed; the stream ends
+    Error { message: String },
+}
+
+/// Think calls, tokens and sub-requests of the execution answering a request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct StreamUsage {
+    pub think_calls: u32,
+    pub tokens: u64,
+    pub sub_requests: u32,
+}
+
+impl From<ExecutionCounters> for StreamUsage {
+    fn from(counters: ExecutionCounters) -> Self {
+        Self {
+            think_calls: counters.think_calls,
+            tokens: counters.tokens,
+            sub_requests: counters.sub_requests,
+        }
+    }
+}
+
+impl StreamUsage {
+    pub fn to_event_value(&self) -> Value {
+        Value::Map(HashMap::from([
+            (
+                "think_calls".to_string(),
+                Value::Integer(self.think_calls.into()),
+            ),
+            ("tokens".to_string(), Value::Integer(self.tokens as i64)),
+            (
+                "sub_requests".to_string(),
+                Value::Integer(self.sub_requests.into()),
+            ),
+        ]))
+    }
+
+    pub fn from_event_value(value: &Value) -> Option<Self> {
+        let Value::Map(map) = value else {
+            return None;
+        };
+        let count = |key: &str| match map.get(key) {
+            Some(Value::Integer(n)) => Some((*n).max(0) as u64),
+            _ => None,
+        };
+        Some(Self {
+            think_calls: count("think_calls")? as u32,
+            tokens: count("tokens")?,
+            sub_requests: count("sub_requests")? as u32,
+        })
+    }
+}
+
+impl StreamEvent {
+    /// The stream events a response of a streamed request stands for. Other events
+    /// stand for none.
+    pub fn from_response(event: &Event) -> Vec<StreamEvent> {
+        let usage = || {
+            event
+                .parameters
+                .get(USAGE_KEY)
+                .and_then(StreamUsage::from_event_value)
+                .map(StreamEvent::Usage)
+        };
+        match &event.event_type {
+            EventType::ResponsePartial { .. } => {
+                if let Some(Value::String(json)) = event.parameters.get(STREAM_EVENT_KEY) {
+                    return match serde_json::from_str(json) {
+                        Ok(stream_event) => vec![stream_event],
+                        Err(e) => {
+                            tracing::warn!("Invalid stream event {}: {}", json, e);
+                            vec![]
+                        }
+                    };
+                }
+                let content = match event.response_value() {
+                    Value::String(s) => s,
+                    other => serde_json::Value::from(&other).to_string(),
+                };
+                vec![StreamEvent::Token { content }]
+            }
+            EventType::ResponseSuccess { .. } => usage()
+                .into_iter()
+                .chain([StreamEvent::Done {
+                    response: serde_json::Value::from(&event.response_value()),
+                }])
+                .collect(),
+            EventType::ResponseFailure { .. } => {
+                let message = match event.response_value() {
+                    Value::String(message) => message,
+                    other => serde_json::Value::from(&other).to_string(),
+                };
+                usage()
+                    .into_iter()
+                    .chain([StreamEvent::Error { message }])
+                    .collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Name of the kind of event, as in `type`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            StreamEvent::Token { .. } => "token",
+            StreamEvent::ToolCallStart { .. } => "tool_call_start",
+            StreamEvent::ToolCallArgsDelta { .. } => "tool_call_args_delta",
+            StreamEvent::Usage(_) => "usage",
+            StreamEvent::Done { .. } => "done",
+            StreamEvent::Error { .. } => "error",
+        }
+    }
+
+    /// Whether the stream ends with this event
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, StreamEvent::Done { .. } | StreamEvent::Error { .. })
+    }
+}
+
+/// Turns the responses of a streamed request into stream events. A failure to
+/// receive the next response, such as a timeout, becomes an `error` event.
+pub fn stream_events(
+    responses: impl Stream<Item = Result<Event, RequestError>>,
+) -> impl Stream<Item = StreamEvent> {
+    responses.flat_map(|response| {
+        futures::stream::iter(match response {
+            Ok(event) => StreamEvent::from_response(&event),
+            Err(e) => vec![StreamEvent::Error {
+                message: e.to_string(),
+            }],
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(event_type: EventType, parameters: Vec<(&str, Value)>) -> Event {
+        Event {
+            event_type,
+            parameters: parameters
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn partial(parameters: Vec<(&str, Value)>) -> Event {
+        response(
+            EventType::ResponsePartial {
+                request_type: "Ask".to_string(),
+                requester: "test".to_string(),
+                responder: "Agent".to_string(),
+                request_id: "1".to_string(),
+            },
+            parameters,
+        )
+    }
+
+    #[test]
+    fn test_serializes_with_type_tag() {
+        let events = vec![
+            StreamEvent::Token {
+                content: "Hel".to_string(),
+            },
+            StreamEvent::ToolCallStart {
+                id: "call_1".to_string(),
+                name: "search".to_string(),
+            },
+            StreamEvent::Usage(StreamUsage {
+                think_calls: 1,
+                tokens: 42,
+                sub_requests: 0,
+            }),
+            StreamEvent::Done {
+                response: serde_json::json!("Hello"),
+            },
+        ];
+        let json = serde_json::to_value(&events).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {"type": "token", "content": "Hel"},
+                {"type": "tool_call_start", "id": "call_1", "name": "search"},
+                {"type": "usage", "think_calls": 1, "tokens": 42, "sub_requests": 0},
+                {"type": "done", "response": "Hello"},
+            ])
+        );
+        for (event, json) in events.iter().zip(json.as_array().unwrap()) {
+            assert_eq!(json["type"], event.kind());
+            assert_eq!(
+                &serde_json::from_value::<StreamEvent>(json.clone()).unwrap(),
+                event
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_response() {
+        assert_eq!(
+            StreamEvent::from_response(&partial(vec![(
+                "response",
+                Value::String("one".to_string())
+            )])),
+            vec![StreamEvent::Token {
+                content: "one".to_string()
+            }]
+        );
+        let delta = StreamEvent::ToolCallArgsDelta {
+            id: "call_1".to_string(),
+            delta: r#"{"q":"rust"}"#.to_string(),
+        };
+        assert_eq!(
+            StreamEvent::from_response(&partial(vec![(
+                STREAM_EVENT_KEY,
+                Value::String(serde_json::to_string(&delta).unwrap())
+            )])),
+            vec![delta]
+        );
+
+        let usage = StreamUsage {
+            think_calls: 2,
+            tokens: 10,
+            sub_requests: 1,
+        };
+        let success = response(
+            EventType::ResponseSuccess {
+                request_type: "Ask".to_string(),
+                requester: "test".to_string(),
+                responder: "Agent".to_string(),
+                request_id: "1".to_string(),
+            },
+            vec![
+                ("response", Value::Integer(3)),
+                (USAGE_KEY, usage.to_event_value()),
+            ],
+        );
+        let events = StreamEvent::from_response(&success);
+        assert_eq!(
+            events,
+            vec![
+                StreamEvent::Usage(usage),
+                StreamEvent::Done {
+                    response: serde_json::json!(3)
+                }
+            ]
+        );
+        assert!(events.last().unwrap().is_terminal());
+
+        let failure = response(
+            EventType::ResponseFailure {
+                request_type: "Ask".to_string(),
+                requester: "test".to_string(),
+                responder: "Agent".to_string(),
+                request_id: "1".to_string(),
+            },
+            vec![("error", Value::String("boom".to_string()))],
+        );
+        assert_eq!(
+            StreamEvent::from_response(&failure),
+            vec![StreamEvent::Error {
+                message: "boom".to_string()
+            }]
+        );
+    }
+}
@@ -0,0 +1,332 @@
+//! # Event Transport
+//!
+//! Carries [`Frame`]s between two processes, for the
+//! [`RemoteEventBridge`](crate::native_feature::remote_bridge::RemoteEventBridge).
+//! A frame is its length as a big-endian u32, a tag byte and a body; events are
+//! encoded with the [`wire`](super::wire) format.
+//!
+//! | tag | frame       | body                                           |
+//! |-----|-------------|------------------------------------------------|
+//! | 0   | `Hello`     | protocol version byte, optional token          |
+//! | 1   | `Welcome`   | empty                                          |
+//! | 2   | `Rejected`  | reason                                         |
+//! | 3   | `Event`     | sequence number as u64, length-prefixed event  |
+//! | 4   | `Ack`       | sequence number as u64                         |
+//! | 5   | `Heartbeat` | empty                                          |
+//!
+//! [`EventTransport`] dials a peer and [`EventListener`] accepts one; both hand out
+//! an [`EventConnection`]. [`TcpTransport`] and [`TcpEventListener`] implement them
+//! over TCP.
+
+use std::net::SocketAddr;
+
+use async_trait::async_trait;
+use thiserror::Error;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+use super::{
+    event_bus::Event,
+    wire::{Reader, WireError, Writer, decode_event, encode_event},
+};
+
+/// Frames with a longer body are rejected
+pub const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Frame {
+    /// First frame of a connection, sent by the dialing side
+    Hello { version: u8, token: Option<String> },
+    /// Accepts a `Hello`
+    Welcome,
+    /// Refuses a `Hello`; the connection is closed afterwards
+    Rejected { reason: String },
+    /// An event, numbered by the sender so that the receiver can acknowledge it
+    Event { sequence: u64, event: Box<Event> },
+    /// Acknowledges every event up to and including `sequence`
+    Ack { sequence: u64 },
+    /// Keeps the connection alive; the listening side answers with a heartbeat
+    Heartbeat,
+}
+
+#[derive(Debug, Error)]
+pub enum TransportError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Malformed frame: {0}")]
+    Wire(#[from] WireError),
+
+    #[error("Frame of {0} bytes exceeds the limit of {MAX_FRAME_LEN} bytes")]
+    FrameTooLarge(usize),
+
+    #[error("Connection closed in the middle of a frame")]
+    Truncated,
+}
+
+pub type TransportResult<T> = Result<T, TransportError>;
+
+impl Frame {
+    /// The frame with its length prefix
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::default();
+        match self {
+            Frame::Hello { version, token } => {
+                writer.u8(0);
+                writer.u8(*version);
+                writer.optional_string(token.as_deref());
+            }
+            Frame::Welcome => writer.u8(1),
+            Frame::Rejected { reason } => {
+                writer.u8(2);
+                writer.string(reason);
+            }
+            Frame::Event { sequence, event } => {
+                writer.u8(3);
+                writer.u64(*sequence);
+                writer.bytes(&encode_event(event));
+            }
+            Frame::Ack { sequence } => {
+                writer.u8(4);
+                writer.u64(*sequence);
+            }
+            Frame::Heartbeat => writer.u8(5),
+        }
+        let body = writer.into_bytes();
+        let mut frame = Vec::with_capacity(4 + body.len());
+        frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&body);
+        frame
+    }
+
+    /// Decode a frame body, without the length prefix
+    pub fn decode(body: &[u8]) -> TransportResult<Self> {
+        let mut reader = Reader::new(body);
+        let frame = match reader.u8("frame tag")? {
+            0 => Frame::Hello {
+                version: reader.u8("protocol version")?,
+                token: reader.optional_string("token")?,
+            },
+            1 => Frame::Welcome,
+            2 => Frame::Rejected {
+                reason: reader.string("reason")?,
+            },
+            3 => Frame::Event {
+                sequence: reader.u64("sequence")?,
+                event: Box::new(decode_event(reader.bytes("event")?)?),
+            },
+            4 => Frame::Ack {
+                sequence: reader.u64("sequence")?,
+            },
+            5 => Frame::Heartbeat,
+            tag => return Err(WireError::UnknownTag { kind: "frame", tag }.into()),
+        };
+        reader.finish()?;
+        Ok(frame)
+    }
+}
+
+/// A connection to a peer, exchanging frames
+#[async_trait]
+pub trait EventConnection: Send {
+    async fn send(&mut self, frame: &Frame) -> TransportResult<()>;
+
+    /// The next frame, or `None` once the peer closed the connection.
+    /// Cancel safe: a frame partly read when the future is dropped is completed by
+    /// the next call.
+    async fn recv(&mut self) -> TransportResult<Option<Frame>>;
+}
+
+/// Dials a peer
+#[async_trait]
+pub trait EventTransport: Send + Sync {
+    async fn connect(&self) -> TransportResult<Box<dyn EventConnection>>;
+}
+
+/// Accepts peers
+#[async_trait]
+pub trait EventListener: Send + Sync {
+    /// Wait for the next peer. Cancel safe.
+    async fn accept(&self) -> TransportResult<Box<dyn EventConnection>>;
+}
+
+/// Frames over any byte stream
+pub struct FramedConnection<S> {
+    stream: S,
+    buffer: Vec<u8>,
+}
+
+impl<S> FramedConnection<S> {
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Split off the first complete frame in the buffer
+    fn take_frame(&mut self) -> TransportResult<Option<Frame>> {
+        let Some(prefix) = self.buffer.first_chunk::<4>() else {
+            return Ok(None);
+        };
+        let len = u32::from_be_bytes(*prefix) as usize;
+        if len > MAX_FRAME_LEN {
+            return Err(TransportError::FrameTooLarge(len));
+        }
+        if self.buffer.len() < 4 + len {
+            return Ok(None);
+        }
+        let frame = Frame::decode(&self.buffer[4..4 + len]);
+        self.buffer.drain(..4 + len);
+        frame.map(Some)
+    }
+}
+
+#[async_trait]
+impl<S> EventConnection for FramedConnection<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    async fn send(&mut self, frame: &Frame) -> TransportResult<()> {
+        let bytes = frame.encode();
+        if bytes.len() - 4 > MAX_FRAME_LEN {
+            return Err(TransportError::FrameTooLarge(bytes.len() - 4));
+        }
+        self.stream.write_all(&bytes).await?;
+        self.stream.flush().await?;
+        Ok(())
+    }
+
+    async fn recv(&mut self) -> TransportResult<Option<Frame>> {
+        loop {
+            if let Some(frame) = self.take_frame()? {
+                return Ok(Some(frame));
+            }
+            // read_buf は取り消し安全なので、読みかけのフレームはバッファに残る
+            if self.stream.read_buf(&mut self.buffer).await? == 0 {
+                return if self.buffer.is_empty() {
+                    Ok(None)
+                } else {
+                    Err(TransportError::Truncated)
+                };
+            }
+        }
+    }
+}
+
+/// Dials a peer over TCP
+#[derive(Debug, Clone)]
+pub struct TcpTransport {
+    addr: String,
+}
+
+impl TcpTransport {
+    pub fn new(addr: impl Into<String>) -> Self {
+        Self { addr: addr.into() }
+    }
+}
+
+#[async_trait]
+impl EventTransport for TcpTransport {
+    async fn connect(&self) -> TransportResult<Box<dyn EventConnection>> {
+        let stream = TcpStream::connect(&self.addr).await?;
+        stream.set_nodelay(true)?;
+        Ok(Box::new(FramedConnection::new(stream)))
+    }
+}
+
+/// Accepts peers over TCP
+#[derive(Debug)]
+pub struct TcpEventListener {
+    listener: TcpListener,
+}
+
+impl TcpEventListener {
+    pub async fn bind(addr: &str) -> TransportResult<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr).await?,
+        })
+    }
+
+    pub fn local_addr(&self) -> TransportResult<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+}
+
+#[async_trait]
+impl EventListener for TcpEventListener {
+    async fn accept(&self) -> TransportResult<Box<dyn EventConnection>> {
+        let (stream, _) = self.listener.accept().await?;
+        stream.set_nodelay(true)?;
+        Ok(Box::new(FramedConnection::new(stream)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{event_bus::Value, event_registry::EventType};
+
+    #[tokio::test]
+    async fn test_frames_over_tcp() -> TransportResult<()> {
+        let listener = TcpEventListener::bind("127.0.0.1:0").await?;
+        let transport = TcpTransport::new(listener.local_addr()?.to_string());
+        let (client, server) = tokio::join!(transport.connect(), listener.accept());
+        let (mut client, mut server) = (client?, server?);
+
+        let event = Event {
+            event_type: EventType::Custom("Bump".to_string()),
+            parameters: HashMap::from([("by".to_string(), Value::Integer(2))]),
+            event_id: Some("event-1".to_string()),
+            ..Default::default()
+        };
+        let frames = [
+            Frame::Hello {
+                version: 1,
+                token: Some("secret".to_string()),
+            },
+            Frame::Event {
+                sequence: 7,
+                event: Box::new(event),
+            },
+            Frame::Heartbeat,
+        ];
+        for frame in &frames {
+            client.send(frame).await?;
+        }
+        for frame in frames {
+            assert_eq!(server.recv().await?, Some(frame));
+        }
+
+        drop(client);
+        assert_eq!(server.recv().await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_partial_frames_are_buffered() -> TransportResult<()> {
+        let (mut writer, reader) = tokio::io::duplex(64);
+        let mut connection = FramedConnection::new(reader);
+        let bytes = Frame::Ack { sequence: 42 }.encode();
+
+        writer.write_all(&bytes[..3]).await?;
+        // 途中までしか届いていないので、取り消されても読んだ分は失われない
+        let pending =
+            tokio::time::timeout(std::time::Duration::from_millis(20), connection.recv()).await;
+        assert!(pending.is_err());
+
+        writer.write_all(&bytes[3..]).await?;
+        assert_eq!(connection.recv().await?, Some(Frame::Ack { sequence: 42 }));
+
+        writer.write_all(&(u32::MAX).to_be_bytes()).await?;
+        assert!(matches!(
+            connection.recv().await,
+            Err(TransportError::FrameTooLarge(_))
+        ));
+        Ok(())
+    }
+}
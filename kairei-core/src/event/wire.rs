@@ -0,0 +1,708 @@
+//! # Binary Event Encoding
+//!
+//! A compact encoding of [`Event`] and [`Value`] for passing events between processes,
+//! used by the [`transport`](super::transport). An encoded event starts with
+//! [`WIRE_VERSION`]; a decoder rejects any other version instead of guessing.
+//!
+//! Values are tag-length-value records: a tag byte, the length of the payload as an
+//! unsigned LEB128 varint, then the payload.
+//!
+//! | tag | value      | payload                                            |
+//! |-----|------------|----------------------------------------------------|
+//! | 0   | `Null`     | empty                                              |
+//! | 1   | `Boolean`  | 1 byte                                             |
+//! | 2   | `Integer`  | i64, big endian                                    |
+//! | 3   | `Float`    | bits of the f64, big endian                        |
+//! | 4   | `String`   | UTF-8                                              |
+//! | 5   | `Duration` | seconds as u64 and nanoseconds as u32, big endian  |
+//! | 6   | `List`     | the records of the items                           |
+//! | 7   | `Map`      | a string and a record per entry                    |
+//! | 8   | `Set`      | the records of the elements, in ascending order    |
+//!
+//! Strings outside records (map keys, names in event types, IDs) are a varint length
+//! followed by UTF-8. An event is the version byte, its event type (a tag byte and the
+//! variant's strings), the number of parameters and the parameters, then the event ID,
+//! parent ID and root ID, each a presence byte and, if present, a string.
+
+use std::{collections::HashMap, str::FromStr, time::Duration};
+
+use thiserror::Error;
+
+use super::{
+    event_bus::{Event, Value},
+    event_registry::EventType,
+};
+use crate::{
+    eval::set::{SetElement, ValueSet},
+    native_feature::types::NativeFeatureType,
+};
+
+/// Version of the encoding written by this build
+pub const WIRE_VERSION: u8 = 1;
+
+/// Values nested deeper than this are rejected when decoding
+pub const MAX_NESTING: usize = 64;
+
+#[derive(Debug, Clone, Error, PartialEq)]
+pub enum WireError {
+    #[error("Unsupported wire version {0}, this build reads version {WIRE_VERSION}")]
+    UnsupportedVersion(u8),
+
+    #[error("Unexpected end of input while reading {0}")]
+    UnexpectedEnd(&'static str),
+
+    #[error("Unknown {kind} tag {tag}")]
+    UnknownTag { kind: &'static str, tag: u8 },
+
+    #[error("Invalid {kind}: {message}")]
+    Invalid { kind: &'static str, message: String },
+
+    #[error("{0} trailing bytes after the encoded value")]
+    TrailingBytes(usize),
+}
+
+pub type WireResult<T> = Result<T, WireError>;
+
+pub fn encode_event(event: &Event) -> Vec<u8> {
+    let mut writer = Writer::default();
+    writer.u8(WIRE_VERSION);
+    writer.event_type(&event.event_type);
+    writer.varint(event.parameters.len() as u64);
+    for (key, value) in &event.parameters {
+        writer.string(key);
+        writer.value(value);
+    }
+    for id in [
+        &event.event_id,
+        &event.parent_event_id,
+        &event.root_event_id,
+    ] {
+        writer.optional_string(id.as_deref());
+    }
+    writer.into_bytes()
+}
+
+pub fn decode_event(bytes: &[u8]) -> WireResult<Event> {
+    let mut reader = Reader::new(bytes);
+    let version = reader.u8("version")?;
+    if version != WIRE_VERSION {
+        return Err(WireError::UnsupportedVersion(version));
+    }
+    let event_type = reader.event_type()?;
+    let count = reader.varint("parameter count")?;
+    // 件数は信用せず、読めた分だけ確保する
+    let mut parameters = HashMap::new();
+    for _ in 0..count {
+        let key = reader.string("parameter name")?;
+        let value = reader.value(0)?;
+        parameters.insert(key, value);
+    }
+    let event = Event {
+        event_type,
+        parameters,
+        event_id: reader.optional_string("event ID")?,
+        parent_event_id: reader.optional_string("parent event ID")?,
+        root_event_id: reader.optional_string("root event ID")?,
+    };
+    reader.finish()?;
+    Ok(event)
+}
+
+pub fn encode_value(value: &Value) -> Vec<u8> {
+    let mut writer = Writer::default();
+    writer.value(value);
+    writer.into_bytes()
+}
+
+pub fn decode_value(bytes: &[u8]) -> WireResult<Value> {
+    let mut reader = Reader::new(bytes);
+    let value = reader.value(0)?;
+    reader.finish()?;
+    Ok(value)
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub(crate) fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.bytes.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        self.bytes.push(value as u8);
+    }
+
+    /// Length-prefixed bytes
+    pub(crate) fn bytes(&mut self, bytes: &[u8]) {
+        self.varint(bytes.len() as u64);
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub(crate) fn string(&mut self, value: &str) {
+        self.bytes(value.as_bytes());
+    }
+
+    pub(crate) fn optional_string(&mut self, value: Option<&str>) {
+        match value {
+            Some(value) => {
+                self.u8(1);
+                self.string(value);
+            }
+            None => self.u8(0),
+        }
+    }
+
+    fn value(&mut self, value: &Value) {
+        let mut payload = Writer::default();
+        let tag = match value {
+            Value::Null => 0,
+            Value::Boolean(b) => {
+                payload.u8(u8::from(*b));
+                1
+            }
+            Value::Integer(i) => {
+                payload.bytes.extend_from_slice(&i.to_be_bytes());
+                2
+            }
+            Value::Float(f) => {
+                payload.u64(f.to_bits());
+                3
+            }
+            Value::String(s) => {
+                payload.bytes.extend_from_slice(s.as_bytes());
+                4
+            }
+            Value::Duration(d) => {
+                payload.u64(d.as_secs());
+                payload
+                    .bytes
+                    .extend_from_slice(&d.subsec_nanos().to_be_bytes());
+                5
+            }
+            Value::List(items) => {
+                for item in items {
+                    payload.value(item);
+                }
+                6
+            }
+            Value::Map(entries) => {
+                for (key, value) in entries {
+                    payload.string(key);
+                    payload.value(value);
+                }
+                7
+            }
+            Value::Set(elements) => {
+                for element in elements.iter() {
+                    payload.value(&match element.clone() {
+                        SetElement::Boolean(b) => Value::Boolean(b),
+                        SetElement::Integer(i) => Value::Integer(i),
+                        SetElement::String(s) => Value::String(s),
+                    });
+                }
+                8
+            }
+        };
+        self.u8(tag);
+        self.bytes(&payload.bytes);
+    }
+
+    fn event_type(&mut self, event_type: &EventType) {
+        match event_type {
+            EventType::Tick => self.u8(0),
+            EventType::MetricsSummary => self.u8(1),
+            EventType::GuardrailTripped { agent_name } => {
+                self.u8(2);
+                self.string(agent_name);
+            }
+            EventType::StateUpdated {
+                agent_name,
+                state_name,
+            } => {
+                self.u8(3);
+                self.string(agent_name);
+                self.string(state_name);
+            }
+            EventType::Message { content_type } => {
+                self.u8(4);
+                self.string(content_type);
+            }
+            EventType::Failure { error_type } => {
+                self.u8(5);
+                self.string(error_type);
+            }
+            EventType::Request {
+                request_type,
+                requester,
+                responder,
+                request_id,
+            }
+            | EventType::ResponseSuccess {
+                request_type,
+                requester,
+                responder,
+                request_id,
+            }
+            | EventType::ResponsePartial {
+                request_type,
+                requester,
+                responder,
+                request_id,
+            }
+            | EventType::ResponseFailure {
+                request_type,
+                requester,
+                responder,
+                request_id,
+            } => {
+                self.u8(match event_type {
+                    EventType::Request { .. } => 6,
+                    EventType::ResponseSuccess { .. } => 7,
+                    EventType::ResponsePartial { .. } => 8,
+                    _ => 9,
+                });
+                self.string(request_type);
+                self.string(requester);
+                self.string(responder);
+                self.string(request_id);
+            }
+            EventType::AgentCreated => self.u8(10),
+            EventType::AgentAdded => self.u8(11),
+            EventType::AgentRemoved => self.u8(12),
+            EventType::AgentStarting => self.u8(13),
+            EventType::AgentStarted => self.u8(14),
+            EventType::AgentStopping => self.u8(15),
+            EventType::AgentStopped => self.u8(16),
+            EventType::SystemCreated => self.u8(17),
+            EventType::SystemNativeFeaturesRegistered => self.u8(18),
+            EventType::SystemProvidersRegistered => self.u8(19),
+            EventType::SystemWorldRegistered => self.u8(20),
+            EventType::SystemBuiltinAgentsRegistered => self.u8(21),
+            EventType::SystemUserAgentsRegistered => self.u8(22),
+            EventType::SystemStarting => self.u8(23),
+            EventType::SystemStarted => self.u8(24),
+            EventType::SystemStopping => self.u8(25),
+            EventType::SystemStopped => self.u8(26),
+            EventType::FeatureStatusUpdated { feature_type } => {
+                self.u8(27);
+                self.string(&feature_type.to_string());
+            }
+            EventType::FeatureFailure { error } => {
+                self.u8(28);
+                self.string(error);
+            }
+            EventType::ProviderRegistered => self.u8(29),
+            EventType::ProviderStatusUpdated => self.u8(30),
+            EventType::ProviderShutdown => self.u8(31),
+            EventType::ProviderPrimarySet => self.u8(32),
+            EventType::Custom(name) => {
+                self.u8(33);
+                self.string(name);
+            }
+            EventType::AgentLog { agent_name } => {
+                self.u8(34);
+                self.string(agent_name);
+            }
+            EventType::CriticalDeliveryFailed { event_type } => {
+                self.u8(35);
+                self.string(event_type);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Fails unless every byte has been read
+    pub(crate) fn finish(self) -> WireResult<()> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(WireError::TrailingBytes(self.bytes.len()))
+        }
+    }
+
+    fn take(&mut self, len: usize, what: &'static str) -> WireResult<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err(WireError::UnexpectedEnd(what));
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self, what: &'static str) -> WireResult<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N, what)?);
+        Ok(array)
+    }
+
+    pub(crate) fn u8(&mut self, what: &'static str) -> WireResult<u8> {
+        Ok(self.take(1, what)?[0])
+    }
+
+    pub(crate) fn u64(&mut self, what: &'static str) -> WireResult<u64> {
+        Ok(u64::from_be_bytes(self.array(what)?))
+    }
+
+    pub(crate) fn varint(&mut self, what: &'static str) -> WireResult<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8(what)?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(WireError::Invalid {
+            kind: what,
+            message: "varint longer than 64 bits".to_string(),
+        })
+    }
+
+    /// Length-prefixed bytes
+    pub(crate) fn bytes(&mut self, what: &'static str) -> WireResult<&'a [u8]> {
+        let len = self.varint(what)?;
+        let len = usize::try_from(len).map_err(|_| WireError::UnexpectedEnd(what))?;
+        self.take(len, what)
+    }
+
+    pub(crate) fn string(&mut self, what: &'static str) -> WireResult<String> {
+        utf8(self.bytes(what)?, what)
+    }
+
+    pub(crate) fn optional_string(&mut self, what: &'static str) -> WireResult<Option<String>> {
+        match self.u8(what)? {
+            0 => Ok(None),
+            1 => self.string(what).map(Some),
+            tag => Err(WireError::UnknownTag { kind: what, tag }),
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> WireResult<Value> {
+        if depth > MAX_NESTING {
+            return Err(WireError::Invalid {
+                kind: "value",
+                message: format!("nested deeper than {} levels", MAX_NESTING),
+            });
+        }
+        let tag = self.u8("value tag")?;
+        let payload = self.bytes("value")?;
+        let mut inner = Reader::new(payload);
+        let value = match tag {
+            0 => Value::Null,
+            1 => Value::Boolean(inner.u8("boolean")? != 0),
+            2 => Value::Integer(i64::from_be_bytes(inner.array("integer")?)),
+            3 => Value::Float(f64::from_bits(inner.u64("float")?)),
+            4 => return utf8(payload, "string").map(Value::String),
+            5 => {
+                let secs = inner.u64("duration")?;
+                let nanos = u32::from_be_bytes(inner.array("duration")?);
+                if nanos >= 1_000_000_000 {
+                    return Err(WireError::Invalid {
+                        kind: "duration",
+                        message: format!("{} nanoseconds", nanos),
+                    });
+                }
+                Value::Duration(Duration::new(secs, nanos))
+            }
+            6 => {
+                let mut items = Vec::new();
+                while !inner.is_empty() {
+                    items.push(inner.value(depth + 1)?);
+                }
+                return Ok(Value::List(items));
+            }
+            7 => {
+                let mut entries = HashMap::new();
+                while !inner.is_empty() {
+                    let key = inner.string("map key")?;
+                    entries.insert(key, inner.value(depth + 1)?);
+                }
+                return Ok(Value::Map(entries));
+            }
+            8 => {
+                let mut elements = ValueSet::new();
+                while !inner.is_empty() {
+                    let element = match inner.value(depth + 1)? {
+                        Value::Boolean(b) => SetElement::Boolean(b),
+                        Value::Integer(i) => SetElement::Integer(i),
+                        Value::String(s) => SetElement::String(s),
+                        other => {
+                            return Err(WireError::Invalid {
+                                kind: "set element",
+                                message: format!("{:?}", other),
+                            });
+                        }
+                    };
+                    elements.insert(element);
+                }
+                return Ok(Value::Set(elements));
+            }
+            tag => return Err(WireError::UnknownTag { kind: "value", tag }),
+        };
+        inner.finish()?;
+        Ok(value)
+    }
+
+    fn event_type(&mut self) -> WireResult<EventType> {
+        let tag = self.u8("event type")?;
+        Ok(match tag {
+            0 => EventType::Tick,
+            1 => EventType::MetricsSummary,
+            2 => EventType::GuardrailTripped {
+                agent_name: self.string("agent name")?,
+            },
+            3 => EventType::StateUpdated {
+                agent_name: self.string("agent name")?,
+                state_name: self.string("state name")?,
+            },
+            4 => EventType::Message {
+                content_type: self.string("content type")?,
+            },
+            5 => EventType::Failure {
+                error_type: self.string("error type")?,
+            },
+            6..=9 => {
+                let request_type = self.string("request type")?;
+                let requester = self.string("requester")?;
+                let responder = self.string("responder")?;
+                let request_id = self.string("request ID")?;
+                match tag {
+                    6 => EventType::Request {
+                        request_type,
+                        requester,
+                        responder,
+                        request_id,
+                    },
+                    7 => EventType::ResponseSuccess {
+                        request_type,
+                        requester,
+                        responder,
+                        request_id,
+                    },
+                    8 => EventType::ResponsePartial {
+                        request_type,
+                        requester,
+                        responder,
+                        request_id,
+                    },
+                    _ => EventType::ResponseFailure {
+                        request_type,
+                        requester,
+                        responder,
+                        request_id,
+                    },
+                }
+            }
+            10 => EventType::AgentCreated,
+            11 => EventType::AgentAdded,
+            12 => EventType::AgentRemoved,
+            13 => EventType::AgentStarting,
+            14 => EventType::AgentStarted,
+            15 => EventType::AgentStopping,
+            16 => EventType::AgentStopped,
+            17 => EventType::SystemCreated,
+            18 => EventType::SystemNativeFeaturesRegistered,
+            19 => EventType::SystemProvidersRegistered,
+            20 => EventType::SystemWorldRegistered,
+            21 => EventType::SystemBuiltinAgentsRegistered,
+            22 => EventType::SystemUserAgentsRegistered,
+            23 => EventType::SystemStarting,
+            24 => EventType::SystemStarted,
+            25 => EventType::SystemStopping,
+            26 => EventType::SystemStopped,
+            27 => {
+                let name = self.string("feature type")?;
+                EventType::FeatureStatusUpdated {
+                    feature_type: NativeFeatureType::from_str(&name).map_err(|_| {
+                        WireError::Invalid {
+                            kind: "feature type",
+                            message: name.clone(),
+                        }
+                    })?,
+                }
+            }
+            28 => EventType::FeatureFailure {
+                error: self.string("error")?,
+            },
+            29 => EventType::ProviderRegistered,
+            30 => EventType::ProviderStatusUpdated,
+            31 => EventType::ProviderShutdown,
+            32 => EventType::ProviderPrimarySet,
+            33 => EventType::Custom(self.string("event name")?),
+            34 => EventType::AgentLog {
+                agent_name: self.string("agent name")?,
+            },
+            35 => EventType::CriticalDeliveryFailed {
+                event_type: self.string("event type name")?,
+            },
+            tag => {
+                return Err(WireError::UnknownTag {
+                    kind: "event type",
+                    tag,
+                });
+            }
+        })
+    }
+}
+
+fn utf8(bytes: &[u8], what: &'static str) -> WireResult<String> {
+    String::from_utf8(bytes.to_vec()).map_err(|e| WireError::Invalid {
+        kind: what,
+        message: e.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_event() -> Event {
+        Event {
+            event_type: EventType::ResponseSuccess {
+                request_type: "GetForecast".to_string(),
+                requester: "Orchestrator".to_string(),
+                responder: "Weather".to_string(),
+                request_id: "req-1".to_string(),
+            },
+            parameters: HashMap::from([
+                ("count".to_string(), Value::Integer(-3)),
+                ("ratio".to_string(), Value::Float(0.25)),
+                ("ok".to_string(), Value::Boolean(true)),
+                ("city".to_string(), Value::String("東京".to_string())),
+                (
+                    "timeout".to_string(),
+                    Value::Duration(Duration::new(30, 500)),
+                ),
+                (
+                    "response".to_string(),
+                    Value::Map(HashMap::from([(
+                        "days".to_string(),
+                        Value::List(vec![Value::String("sunny".to_string()), Value::Null]),
+                    )])),
+                ),
+                (
+                    "tags".to_string(),
+                    Value::Set(
+                        [
+                            SetElement::String("rain".to_string()),
+                            SetElement::Integer(3),
+                        ]
+                        .into_iter()
+                        .collect(),
+                    ),
+                ),
+            ]),
+            event_id: Some("event-2".to_string()),
+            parent_event_id: Some("event-1".to_string()),
+            root_event_id: None,
+        }
+    }
+
+    #[test]
+    fn test_event_round_trip() {
+        let event = sample_event();
+        assert_eq!(decode_event(&encode_event(&event)), Ok(event));
+
+        for event_type in [
+            EventType::Tick,
+            EventType::Custom("Bump".to_string()),
+            EventType::FeatureStatusUpdated {
+                feature_type: NativeFeatureType::Metrics,
+            },
+            EventType::SystemStopped,
+        ] {
+            let event = Event {
+                event_type,
+                ..Default::default()
+            };
+            assert_eq!(decode_event(&encode_event(&event)), Ok(event));
+        }
+    }
+
+    #[test]
+    fn test_value_records() {
+        // タグ 1 バイト、長さ 1 バイト、i64 の 8 バイト
+        assert_eq!(encode_value(&Value::Integer(1)).len(), 10);
+        assert_eq!(encode_value(&Value::Null), vec![0, 0]);
+        assert_eq!(
+            encode_value(&Value::String("a".repeat(200))).len(),
+            1 + 2 + 200
+        );
+    }
+
+    #[test]
+    fn test_malformed_input_is_rejected() {
+        let mut bytes = encode_event(&sample_event());
+        bytes[0] = 2;
+        assert_eq!(decode_event(&bytes), Err(WireError::UnsupportedVersion(2)));
+
+        let bytes = encode_event(&sample_event());
+        assert!(matches!(
+            decode_event(&bytes[..bytes.len() - 1]),
+            Err(WireError::UnexpectedEnd(_))
+        ));
+
+        let mut bytes = encode_value(&Value::Null);
+        bytes.push(0);
+        assert_eq!(decode_value(&bytes), Err(WireError::TrailingBytes(1)));
+
+        assert_eq!(
+            decode_value(&[9, 0]),
+            Err(WireError::UnknownTag {
+                kind: "value",
+                tag: 9
+            })
+        );
+
+        // 集合の要素になれない値は受け付けない
+        let mut bytes = vec![8];
+        let float = encode_value(&Value::Float(0.5));
+        bytes.push(float.len() as u8);
+        bytes.extend(float);
+        assert!(matches!(
+            decode_value(&bytes),
+            Err(WireError::Invalid {
+                kind: "set element",
+                ..
+            })
+        ));
+
+        let mut nested = Value::Null;
+        for _ in 0..=MAX_NESTING + 1 {
+            nested = Value::List(vec![nested]);
+        }
+        assert!(matches!(
+            decode_value(&encode_value(&nested)),
+            Err(WireError::Invalid { kind: "value", .. })
+        ));
+    }
+}
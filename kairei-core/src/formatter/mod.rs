@@ -2,7 +2,7 @@ pub mod config;
 pub mod error;
 pub mod visitor;
 
-use crate::ast::Root;
+use crate::ast::{ParameterConstraint, Root, Statement};
 use config::FormatterConfig;
 use error::FormatterError;
 use visitor::FormatterVisitor;
@@ -20,4 +20,22 @@ impl Formatter {
         let mut visitor = FormatterVisitor::new(self.config.clone());
         visitor.format_root(ast)
     }
+
+    /// Source text of `statement`, e.g. `count = count + 1`
+    pub fn format_statement(&self, statement: &Statement) -> Result<String, FormatterError> {
+        let mut visitor = FormatterVisitor::new(self.config.clone());
+        visitor.format_statement(statement)?;
+        Ok(visitor.into_output())
+    }
+
+    /// Source text of the `where` constraint of parameter `name`, e.g. `days in 1..30`
+    pub fn format_parameter_constraint(
+        &self,
+        name: &str,
+        constraint: &ParameterConstraint,
+    ) -> Result<String, FormatterError> {
+        let mut visitor = FormatterVisitor::new(self.config.clone());
+        visitor.format_parameter_constraint(name, constraint)?;
+        Ok(visitor.into_output())
+    }
 }
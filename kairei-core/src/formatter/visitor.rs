@@ -19,6 +19,18 @@ impl FormatterVisitor {
     }
 
     pub fn format_root(&mut self, root: &Root) -> Result<String, FormatterError> {
+        // バージョン宣言はファイルの先頭に残す
+        if let Some(version) = &root.dsl_version {
+            self.write(&format!("kairei \"{}\"", version))?;
+            self.newline()?;
+            self.newline()?;
+        }
+
+        for type_def in &root.type_defs {
+            self.format_type_def(type_def)?;
+            self.newline()?;
+        }
+
         // Format world definition if exists
         if let Some(world) = &root.world_def {
             self.format_world(world)?;
@@ -34,6 +46,10 @@ impl FormatterVisitor {
         Ok(self.output.clone())
     }
 
+    pub(crate) fn into_output(self) -> String {
+        self.output
+    }
+
     fn format_world(&mut self, world: &WorldDef) -> Result<(), FormatterError> {
         self.write("world ")?;
         self.write(&world.name)?;
@@ -48,6 +64,8 @@ impl FormatterVisitor {
             self.newline()?;
         }
 
+        self.format_persona(&world.persona)?;
+
         // Format config if present
         if let Some(config) = &world.config {
             self.format_world_config(config)?;
@@ -57,11 +75,49 @@ impl FormatterVisitor {
         // Format events
         self.format_events(&world.events)?;
 
+        for schedule in &world.schedules {
+            self.format_schedule(schedule)?;
+        }
+
+        if let Some(on_init) = &world.on_init {
+            self.write("onInit ")?;
+            self.format_handler_block(on_init)?;
+            self.newline()?;
+        }
+
         self.dedent();
         self.write("}")?;
         Ok(())
     }
 
+    fn format_persona(&mut self, persona: &Option<String>) -> Result<(), FormatterError> {
+        if let Some(persona) = persona {
+            self.write("persona ")?;
+            self.format_string(persona)?;
+            self.newline()?;
+        }
+        Ok(())
+    }
+
+    fn format_schedule(&mut self, schedule: &ScheduleDef) -> Result<(), FormatterError> {
+        self.write(&format!(
+            "schedule \"{}\" emit {}",
+            schedule.cron, schedule.event
+        ))?;
+        if !schedule.parameters.is_empty() {
+            self.write("(")?;
+            for (i, arg) in schedule.parameters.iter().enumerate() {
+                if i > 0 {
+                    self.write(", ")?;
+                }
+                self.format_argument(arg)?;
+            }
+            self.write(")")?;
+        }
+        self.newline()?;
+        Ok(())
+    }
+
     fn format_world_config(&mut self, config: &ConfigDef) -> Result<(), FormatterError> {
         self.write("config {")?;
         self.indent();
@@ -123,9 +179,30 @@ impl FormatterVisitor {
         self.write(&param.name)?;
         self.write(": ")?;
         self.format_type_info(&param.type_info)?;
+        if let Some(constraint) = &param.constraint {
+            self.write(" where ")?;
+            self.format_parameter_constraint(&param.name, constraint)?;
+        }
         Ok(())
     }
 
+    pub(crate) fn format_parameter_constraint(
+        &mut self,
+        name: &str,
+        constraint: &ParameterConstraint,
+    ) -> Result<(), FormatterError> {
+        match constraint {
+            ParameterConstraint::Condition(condition) => self.format_expression(condition),
+            ParameterConstraint::Range { start, end } => {
+                self.write(name)?;
+                self.write(" in ")?;
+                self.format_literal(start)?;
+                self.write("..")?;
+                self.format_literal(end)
+            }
+        }
+    }
+
     fn format_expression(&mut self, expr: &Expression) -> Result<(), FormatterError> {
         match expr {
             Expression::Literal(lit) => self.format_literal(lit)?,
@@ -218,6 +295,7 @@ impl FormatterVisitor {
                     BinaryOperator::Subtract => "-",
                     BinaryOperator::Multiply => "*",
                     BinaryOperator::Divide => "/",
+                    BinaryOperator::Modulo => "%",
                     BinaryOperator::Equal => "==",
                     BinaryOperator::NotEqual => "!=",
                     BinaryOperator::LessThan => "<",
@@ -248,7 +326,11 @@ impl FormatterVisitor {
         match lit {
             Literal::Integer(i) => self.write(&i.to_string())?,
             Literal::Float(f) => self.write(&f.to_string())?,
-            Literal::String(s) => self.write(&format!("\"{}\"", s))?,
+            Literal::String(s) => self.format_string(s)?,
+            Literal::RawString { value, hashes } => {
+                let marks = "#".repeat(*hashes);
+                self.write(&format!("r{marks}\"{value}\"{marks}"))?
+            }
             Literal::Boolean(b) => self.write(if *b { "true" } else { "false" })?,
             Literal::Duration(d) => self.write(&format!("{}s", d.as_secs()))?,
             Literal::List(items) => {
@@ -273,6 +355,16 @@ impl FormatterVisitor {
                 }
                 self.write("}")?;
             }
+            Literal::Set(items) => {
+                self.write("#{")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        self.write(", ")?;
+                    }
+                    self.format_literal(item)?;
+                }
+                self.write("}")?;
+            }
             Literal::Retry(config) => {
                 self.write("retry(")?;
                 self.write(&format!("max_attempts: {}", config.max_attempts))?;
@@ -340,10 +432,18 @@ impl FormatterVisitor {
             self.write(&format!("temperature: {}", temp))?;
             self.newline()?;
         }
+        if let Some(top_p) = attrs.top_p {
+            self.write(&format!("top_p: {}", top_p))?;
+            self.newline()?;
+        }
         if let Some(tokens) = attrs.max_tokens {
             self.write(&format!("max_tokens: {}", tokens))?;
             self.newline()?;
         }
+        if attrs.stream {
+            self.write("stream: true")?;
+            self.newline()?;
+        }
         if let Some(retry) = &attrs.retry {
             self.write("retry: ")?;
             self.format_literal(&Literal::Retry(retry.clone()))?;
@@ -399,6 +499,31 @@ impl FormatterVisitor {
         Ok(())
     }
 
+    fn format_type_def(&mut self, type_def: &TypeDef) -> Result<(), FormatterError> {
+        self.write("type ")?;
+        self.write(&type_def.name)?;
+        if !type_def.type_params.is_empty() {
+            self.write(&format!("<{}>", type_def.type_params.join(", ")))?;
+        }
+        self.write(" {")?;
+        self.indent();
+        self.newline()?;
+        let mut fields: Vec<_> = type_def.fields.iter().collect();
+        fields.sort_by_key(|(name, _)| name.as_str());
+        for (name, field) in fields {
+            self.write(name)?;
+            if let Some(type_info) = &field.type_info {
+                self.write(": ")?;
+                self.format_type_info(type_info)?;
+            }
+            self.write(",")?;
+            self.newline()?;
+        }
+        self.dedent();
+        self.write("}")?;
+        Ok(())
+    }
+
     fn format_type_info(&mut self, type_info: &TypeInfo) -> Result<(), FormatterError> {
         match type_info {
             TypeInfo::Simple(name) => self.write(name)?,
@@ -426,6 +551,11 @@ impl FormatterVisitor {
                 self.format_type_info(value)?;
                 self.write("}")?;
             }
+            TypeInfo::Set(inner) => {
+                self.write("Set{")?;
+                self.format_type_info(inner)?;
+                self.write("}")?;
+            }
             TypeInfo::Custom { name, fields } => {
                 self.write(name)?;
                 if !fields.is_empty() {
@@ -450,6 +580,17 @@ impl FormatterVisitor {
                     self.write("}")?;
                 }
             }
+            TypeInfo::Generic { name, args } => {
+                self.write(name)?;
+                self.write("<")?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        self.write(", ")?;
+                    }
+                    self.format_type_info(arg)?;
+                }
+                self.write(">")?;
+            }
         }
         Ok(())
     }
@@ -489,6 +630,7 @@ impl FormatterVisitor {
             }
             first = false;
 
+            self.format_doc(&var.doc)?;
             self.write(name)?;
             self.write(": ")?;
             self.format_type_info(&var.type_info)?;
@@ -504,6 +646,35 @@ impl FormatterVisitor {
         Ok(())
     }
 
+    fn format_agent_config(&mut self, config: &AgentConfigDef) -> Result<(), FormatterError> {
+        self.write("config {")?;
+        self.indent();
+        self.newline()?;
+
+        let mut first = true;
+        for param in &config.parameters {
+            if !first {
+                self.write(",")?;
+                self.newline()?;
+            }
+            first = false;
+
+            self.format_doc(&param.doc)?;
+            self.write(&param.name)?;
+            self.write(": ")?;
+            self.format_type_info(&param.type_info)?;
+            if let Some(default) = &param.default {
+                self.write(" = ")?;
+                self.format_literal(default)?;
+            }
+        }
+
+        self.newline()?;
+        self.dedent();
+        self.write("}")?;
+        Ok(())
+    }
+
     fn format_observe(&mut self, observe: &ObserveDef) -> Result<(), FormatterError> {
         self.write("observe {")?;
         self.indent();
@@ -550,6 +721,11 @@ impl FormatterVisitor {
     }
 
     fn format_event_handler(&mut self, handler: &EventHandler) -> Result<(), FormatterError> {
+        self.format_doc(&handler.doc)?;
+        if let Some(replay) = &handler.replay {
+            self.write(&format!("@replay({})", replay.arguments.join(", ")))?;
+            self.newline()?;
+        }
         self.write("on ")?;
         match &handler.event_type {
             EventType::Tick => self.write("tick")?,
@@ -572,16 +748,27 @@ impl FormatterVisitor {
         }
         self.write(") ")?;
 
+        if let Some(requires) = &handler.requires {
+            self.write("requires ")?;
+            self.format_expression(requires)?;
+            self.write(" ")?;
+        }
+
         self.format_handler_block(&handler.block)?;
         Ok(())
     }
 
     fn format_request_handler(&mut self, handler: &RequestHandler) -> Result<(), FormatterError> {
+        self.format_doc(&handler.doc)?;
+        if let Some(cache) = &handler.cache {
+            self.format_cache_directive(cache)?;
+        }
         self.write("on request ")?;
         match &handler.request_type {
             RequestType::Query { query_type } => self.write(query_type)?,
             RequestType::Action { action_type } => self.write(action_type)?,
             RequestType::Custom(name) => self.write(name)?,
+            RequestType::Fallback => self.write("_")?,
         }
         self.write("(")?;
 
@@ -594,6 +781,11 @@ impl FormatterVisitor {
         self.write(") -> ")?;
         self.format_type_info(&handler.return_type)?;
 
+        if let Some(requires) = &handler.requires {
+            self.write(" requires ")?;
+            self.format_expression(requires)?;
+        }
+
         if let Some(constraints) = &handler.constraints {
             self.write(" with {")?;
             self.indent();
@@ -611,6 +803,11 @@ impl FormatterVisitor {
                 self.write(&format!("latency: {}", latency))?;
                 self.newline()?;
             }
+            if !constraints.postprocess.is_empty() {
+                self.write("postprocess: ")?;
+                self.format_literal(&Literal::List(constraints.postprocess.clone()))?;
+                self.newline()?;
+            }
 
             self.dedent();
             self.write("} ")?;
@@ -620,6 +817,61 @@ impl FormatterVisitor {
         Ok(())
     }
 
+    fn format_cache_directive(&mut self, cache: &CacheDirective) -> Result<(), FormatterError> {
+        // 秒未満がなければ最も大きい単位で書き戻す
+        let ttl = cache.ttl;
+        let ttl = if ttl.subsec_millis() > 0 {
+            format!("{}ms", ttl.as_millis())
+        } else if ttl.as_secs() > 0 && ttl.as_secs() % 3600 == 0 {
+            format!("{}h", ttl.as_secs() / 3600)
+        } else if ttl.as_secs() > 0 && ttl.as_secs() % 60 == 0 {
+            format!("{}m", ttl.as_secs() / 60)
+        } else {
+            format!("{}s", ttl.as_secs())
+        };
+        self.write(&format!("@cache(ttl: {}", ttl))?;
+        if let Some(key) = &cache.key {
+            self.write(&format!(", key: [{}]", key.join(", ")))?;
+        }
+        self.write(")")?;
+        self.newline()?;
+        Ok(())
+    }
+
+    fn format_contracts(&mut self, contracts: &[RequestContract]) -> Result<(), FormatterError> {
+        self.write("contract {")?;
+        self.indent();
+        self.newline()?;
+
+        for contract in contracts {
+            self.format_doc(&contract.doc)?;
+            match &contract.request_type {
+                RequestType::Query { query_type } => {
+                    self.write(&format!("query.{}", query_type))?
+                }
+                RequestType::Action { action_type } => {
+                    self.write(&format!("action.{}", action_type))?
+                }
+                RequestType::Custom(name) => self.write(&format!("request {}", name))?,
+                RequestType::Fallback => self.write("request _")?,
+            }
+            self.write("(")?;
+            for (i, param) in contract.parameters.iter().enumerate() {
+                if i > 0 {
+                    self.write(", ")?;
+                }
+                self.format_parameter(param)?;
+            }
+            self.write(") -> ")?;
+            self.format_type_info(&contract.return_type)?;
+            self.newline()?;
+        }
+
+        self.dedent();
+        self.write("}")?;
+        Ok(())
+    }
+
     fn format_handler_block(&mut self, block: &HandlerBlock) -> Result<(), FormatterError> {
         self.write("{")?;
         self.indent();
@@ -635,7 +887,7 @@ impl FormatterVisitor {
         Ok(())
     }
 
-    fn format_statement(&mut self, stmt: &Statement) -> Result<(), FormatterError> {
+    pub(crate) fn format_statement(&mut self, stmt: &Statement) -> Result<(), FormatterError> {
         match stmt {
             Statement::Expression(expr) => self.format_expression(expr)?,
             Statement::Assignment { target, value } => {
@@ -648,16 +900,34 @@ impl FormatterVisitor {
                 self.write(" = ")?;
                 self.format_expression(value)?;
             }
+            Statement::Let {
+                name,
+                type_info,
+                value,
+            } => {
+                self.write(&format!("let {}", name))?;
+                if let Some(type_info) = type_info {
+                    self.write(": ")?;
+                    self.format_type_info(type_info)?;
+                }
+                self.write(" = ")?;
+                self.format_expression(value)?;
+            }
             Statement::Return(expr) => {
                 self.write("return ")?;
                 self.format_expression(expr)?;
             }
+            Statement::Yield(expr) => {
+                self.write("yield ")?;
+                self.format_expression(expr)?;
+            }
             Statement::Emit {
                 event_type,
                 parameters,
                 target,
+                immediate,
             } => {
-                self.write("emit ")?;
+                self.write(if *immediate { "emit! " } else { "emit " })?;
                 if let Some(t) = target {
                     self.write(&format!("to {} ", t))?;
                 }
@@ -671,6 +941,40 @@ impl FormatterVisitor {
                 }
                 self.write(")")?;
             }
+            Statement::Log {
+                level,
+                message,
+                fields,
+            } => {
+                self.write(&format!("log.{}(", level))?;
+                self.format_expression(message)?;
+                for (key, value) in fields {
+                    self.write(&format!(", {}: ", key))?;
+                    self.format_expression(value)?;
+                }
+                self.write(")")?;
+            }
+            Statement::Retry {
+                times,
+                backoff,
+                body,
+            } => {
+                self.write("retry(times: ")?;
+                self.format_expression(times)?;
+                if let Some(backoff) = backoff {
+                    self.write(", backoff: ")?;
+                    self.format_expression(backoff)?;
+                }
+                self.write(") {")?;
+                self.indent();
+                self.newline()?;
+                for stmt in body {
+                    self.format_statement(stmt)?;
+                    self.newline()?;
+                }
+                self.dedent();
+                self.write("}")?;
+            }
             Statement::Block(statements) => {
                 self.write("{")?;
                 self.indent();
@@ -739,6 +1043,7 @@ impl FormatterVisitor {
     }
 
     fn format_micro_agent(&mut self, agent: &MicroAgentDef) -> Result<(), FormatterError> {
+        self.format_doc(&agent.doc)?;
         self.write("micro ")?;
         self.write(&agent.name)?;
         self.write(" {")?;
@@ -752,8 +1057,19 @@ impl FormatterVisitor {
             self.newline()?;
         }
 
+        self.format_persona(&agent.persona)?;
+        let mut localized: Vec<_> = agent.localized_personas.iter().collect();
+        localized.sort();
+        for (locale, persona) in localized {
+            self.write(&format!("persona {} ", locale))?;
+            self.format_string(persona)?;
+            self.newline()?;
+        }
+
         // Add newline after policies if there are other components
-        if agent.state.is_some()
+        if !agent.contracts.is_empty()
+            || agent.state.is_some()
+            || agent.config.is_some()
             || agent.lifecycle.is_some()
             || agent.observe.is_some()
             || agent.answer.is_some()
@@ -762,6 +1078,12 @@ impl FormatterVisitor {
             self.newline()?;
         }
 
+        // Format contracts if present
+        if !agent.contracts.is_empty() {
+            self.format_contracts(&agent.contracts)?;
+            self.newline()?;
+        }
+
         // Format lifecycle if present
         if let Some(lifecycle) = &agent.lifecycle {
             self.format_lifecycle(lifecycle)?;
@@ -774,6 +1096,12 @@ impl FormatterVisitor {
             self.newline()?;
         }
 
+        // Format config if present
+        if let Some(config) = &agent.config {
+            self.format_agent_config(config)?;
+            self.newline()?;
+        }
+
         // Format observe if present
         if let Some(observe) = &agent.observe {
             self.format_observe(observe)?;
@@ -797,6 +1125,36 @@ impl FormatterVisitor {
         Ok(())
     }
 
+    // ドキュメントコメントは行ごとに `///` として書き戻す
+    fn format_doc(&mut self, doc: &Option<String>) -> Result<(), FormatterError> {
+        if let Some(doc) = doc {
+            for line in doc.lines() {
+                if line.is_empty() {
+                    self.write("///")?;
+                } else {
+                    self.write(&format!("/// {}", line))?;
+                }
+                self.newline()?;
+            }
+        }
+        Ok(())
+    }
+
+    // 改行や引用符を含む文字列は通常の "..." では表現できないため、
+    // ヒアドキュメント（triple quote）として書き戻す
+    fn format_string(&mut self, s: &str) -> Result<(), FormatterError> {
+        if !s.contains('\n') && !s.contains('"') {
+            return self.write(&format!("\"{}\"", s));
+        }
+        if !s.contains("\"\"\"") && !s.ends_with('"') {
+            return self.write(&format!("\"\"\"{}\"\"\"", s));
+        }
+        Err(FormatterError::Format(format!(
+            "string cannot be written back as a string literal: {}",
+            s
+        )))
+    }
+
     fn write(&mut self, text: &str) -> Result<(), FormatterError> {
         self.output.push_str(text);
         Ok(())
@@ -847,12 +1205,35 @@ mod tests {
             config: None,
             events: Default::default(),
             handlers: Default::default(),
+            persona: Some("You are a careful planner.\nBe brief.".to_string()),
+            schedules: vec![ScheduleDef {
+                cron: "0 2 * * *".to_string(),
+                event: "cleanupRequested".to_string(),
+                parameters: vec![Argument::Named {
+                    name: "scope".to_string(),
+                    value: Expression::Literal(Literal::String("all".to_string())),
+                }],
+            }],
+            on_init: Some(HandlerBlock {
+                statements: vec![Statement::Expression(Expression::FunctionCall {
+                    function: "increment".to_string(),
+                    arguments: vec![
+                        Expression::Literal(Literal::String("seats".to_string())),
+                        Expression::Literal(Literal::Integer(10)),
+                    ],
+                })],
+            }),
         };
 
         visitor.format_world(&world).unwrap();
         let output = visitor.output;
         assert!(output.contains("world TestWorld {"));
         assert!(output.contains("    policy \"Test policy\""));
+        assert!(output.contains("    persona \"\"\"You are a careful planner.\nBe brief.\"\"\""));
+        assert!(
+            output.contains("    schedule \"0 2 * * *\" emit cleanupRequested(scope: \"all\")")
+        );
+        assert!(output.contains("    onInit {"));
         assert!(output.ends_with("}"));
     }
 
@@ -877,6 +1258,7 @@ mod tests {
                             initial_value: Some(Expression::Literal(Literal::String(
                                 "none".to_string(),
                             ))),
+                            doc: None,
                         },
                     );
                     map.insert(
@@ -887,6 +1269,7 @@ mod tests {
                             initial_value: Some(Expression::Literal(Literal::String(
                                 "none".to_string(),
                             ))),
+                            doc: None,
                         },
                     );
                     map
@@ -901,22 +1284,27 @@ mod tests {
                         Parameter {
                             name: "destination".to_string(),
                             type_info: TypeInfo::Simple("String".to_string()),
+                            constraint: None,
                         },
                         Parameter {
                             name: "start".to_string(),
                             type_info: TypeInfo::Simple("String".to_string()),
+                            constraint: None,
                         },
                         Parameter {
                             name: "end".to_string(),
                             type_info: TypeInfo::Simple("String".to_string()),
+                            constraint: None,
                         },
                         Parameter {
                             name: "budget".to_string(),
                             type_info: TypeInfo::Simple("Float".to_string()),
+                            constraint: None,
                         },
                         Parameter {
                             name: "interests".to_string(),
                             type_info: TypeInfo::Simple("String".to_string()),
+                            constraint: None,
                         },
                     ],
                     return_type: TypeInfo::Result {
@@ -924,10 +1312,19 @@ mod tests {
                         err_type: Box::new(TypeInfo::Simple("Error".to_string())),
                     },
                     constraints: None,
+                    requires: None,
                     block: HandlerBlock { statements: vec![] },
+                    doc: None,
+                    cache: None,
                 }],
             }),
             react: None,
+            doc: None,
+            labels: Default::default(),
+            persona: None,
+            localized_personas: Default::default(),
+            contracts: vec![],
+            config: None,
         };
 
         visitor.format_micro_agent(&agent).unwrap();
@@ -982,6 +1379,7 @@ mod tests {
                         name: "counter".to_string(),
                         type_info: TypeInfo::Simple("Int".to_string()),
                         initial_value: Some(Expression::Literal(Literal::Integer(0))),
+                        doc: None,
                     },
                 );
                 map.insert(
@@ -990,6 +1388,7 @@ mod tests {
                         name: "name".to_string(),
                         type_info: TypeInfo::Simple("String".to_string()),
                         initial_value: None,
+                        doc: None,
                     },
                 );
                 map
@@ -1010,12 +1409,15 @@ mod tests {
             handlers: vec![EventHandler {
                 event_type: EventType::Tick,
                 parameters: vec![],
+                requires: None,
                 block: HandlerBlock {
                     statements: vec![Statement::Expression(Expression::FunctionCall {
                         function: "update".to_string(),
                         arguments: vec![],
                     })],
                 },
+                doc: None,
+                replay: None,
             }],
         };
 
@@ -1026,6 +1428,109 @@ mod tests {
         assert!(output.contains("update()"));
     }
 
+    #[test]
+    fn test_format_doc_comments() {
+        let mut visitor = FormatterVisitor::new(create_test_config());
+        let observe = ObserveDef {
+            handlers: vec![EventHandler {
+                event_type: EventType::Tick,
+                parameters: vec![],
+                requires: None,
+                block: HandlerBlock { statements: vec![] },
+                doc: Some("Runs every tick\n\nKeep it cheap".to_string()),
+                replay: None,
+            }],
+        };
+
+        visitor.format_observe(&observe).unwrap();
+        assert!(
+            visitor
+                .output
+                .contains("    /// Runs every tick\n    ///\n    /// Keep it cheap\n    on tick()")
+        );
+    }
+
+    #[test]
+    fn test_format_replay_directive() {
+        let mut visitor = FormatterVisitor::new(create_test_config());
+        let handler = EventHandler {
+            event_type: EventType::Custom("Deposited".to_string()),
+            parameters: vec![],
+            requires: None,
+            block: HandlerBlock { statements: vec![] },
+            doc: None,
+            replay: Some(ReplayDirective {
+                arguments: vec!["skip".to_string()],
+            }),
+        };
+
+        visitor.format_event_handler(&handler).unwrap();
+        assert!(visitor.output.starts_with("@replay(skip)\non Deposited"));
+    }
+
+    #[test]
+    fn test_format_contracts() {
+        let mut visitor = FormatterVisitor::new(create_test_config());
+        let contracts = vec![
+            RequestContract {
+                request_type: RequestType::Custom("GetForecast".to_string()),
+                parameters: vec![Parameter {
+                    name: "city".to_string(),
+                    type_info: TypeInfo::Simple("String".to_string()),
+                    constraint: None,
+                }],
+                return_type: TypeInfo::Simple("String".to_string()),
+                doc: Some("Forecast for a city".to_string()),
+            },
+            RequestContract {
+                request_type: RequestType::Query {
+                    query_type: "GetStatus".to_string(),
+                },
+                parameters: vec![],
+                return_type: TypeInfo::Simple("Boolean".to_string()),
+                doc: None,
+            },
+        ];
+
+        visitor.format_contracts(&contracts).unwrap();
+        let output = visitor.output;
+        assert!(output.starts_with("contract {"));
+        assert!(output.contains(
+            "    /// Forecast for a city\n    request GetForecast(city: String) -> String\n"
+        ));
+        assert!(output.contains("    query.GetStatus() -> Boolean\n"));
+        assert!(output.ends_with("}"));
+    }
+
+    #[test]
+    fn test_format_cache_directive() {
+        let mut visitor = FormatterVisitor::new(create_test_config());
+        let handler = RequestHandler {
+            request_type: RequestType::Custom("Forecast".to_string()),
+            parameters: vec![Parameter {
+                name: "city".to_string(),
+                type_info: TypeInfo::Simple("String".to_string()),
+                constraint: None,
+            }],
+            return_type: TypeInfo::Simple("String".to_string()),
+            requires: None,
+            constraints: None,
+            block: HandlerBlock { statements: vec![] },
+            doc: None,
+            cache: Some(CacheDirective {
+                ttl: Duration::from_secs(600),
+                key: Some(vec!["city".to_string()]),
+            }),
+        };
+
+        visitor.format_request_handler(&handler).unwrap();
+        assert!(
+            visitor
+                .output
+                .starts_with("@cache(ttl: 10m, key: [city])\non request Forecast(city: String)")
+        );
+    }
+
     #[test]
     fn test_format_react() {
         let mut visitor = FormatterVisitor::new(create_test_config());
@@ -1036,12 +1541,15 @@ mod tests {
                     state_name: "status".to_string(),
                 },
                 parameters: vec![],
+                requires: None,
                 block: HandlerBlock {
                     statements: vec![Statement::Expression(Expression::FunctionCall {
                         function: "react".to_string(),
                         arguments: vec![],
                     })],
                 },
+                doc: None,
+                replay: None,
             }],
         };
 
@@ -1066,6 +1574,12 @@ mod tests {
                 observe: None,
                 answer: None,
                 react: None,
+                doc: None,
+                labels: Default::default(),
+                persona: None,
+                localized_personas: Default::default(),
+                contracts: vec![],
+                config: None,
             }],
             vec![],
         );
@@ -1075,6 +1589,17 @@ mod tests {
         assert!(output.ends_with("}\n"));
     }
 
+    #[test]
+    fn test_format_dsl_version() {
+        let config = create_test_config();
+        let mut visitor = FormatterVisitor::new(config);
+        let mut root = Root::new(None, vec![], vec![]);
+        root.dsl_version = Some("0.4".to_string());
+
+        let output = visitor.format_root(&root).unwrap();
+        assert_eq!(output, "kairei \"0.4\"\n\n");
+    }
+
     #[test]
     fn test_indentation() {
         let config = FormatterConfig {
@@ -1094,6 +1619,9 @@ mod tests {
             config: None,
             events: Default::default(),
             handlers: Default::default(),
+            persona: None,
+            schedules: vec![],
+            on_init: None,
         };
 
         visitor.format_world(&world).unwrap();
@@ -1128,6 +1656,7 @@ mod tests {
                 parameters: vec![Parameter {
                     name: "param1".to_string(),
                     type_info: TypeInfo::Simple("String".to_string()),
+                    constraint: None,
                 }],
             }],
         };
@@ -1236,6 +1765,23 @@ mod tests {
         assert_eq!(visitor.output, "\"test\"");
         visitor.output.clear();
 
+        // Test multi-line string is written back as a heredoc
+        visitor
+            .format_literal(&Literal::String("line \"one\"\nline two".to_string()))
+            .unwrap();
+        assert_eq!(visitor.output, "\"\"\"line \"one\"\nline two\"\"\"");
+        visitor.output.clear();
+
+        // Test raw string keeps its delimiters
+        visitor
+            .format_literal(&Literal::RawString {
+                value: "{\"name\": \"${name}\"}".to_string(),
+                hashes: 1,
+            })
+            .unwrap();
+        assert_eq!(visitor.output, "r#\"{\"name\": \"${name}\"}\"#");
+        visitor.output.clear();
+
         // Test list
         visitor
             .format_literal(&Literal::List(vec![
@@ -1252,4 +1798,64 @@ mod tests {
         visitor.format_literal(&Literal::Map(map)).unwrap();
         assert_eq!(visitor.output, "{key: \"value\"}");
     }
+
+    fn parse_expression_source(source: &str) -> Expression {
+        use crate::analyzer::Parser;
+        use crate::preprocessor::Preprocessor;
+
+        let token_spans = crate::tokenizer::token::Tokenizer::new()
+            .tokenize(source)
+            .unwrap();
+        let tokens: Vec<_> = crate::preprocessor::TokenPreprocessor::default()
+            .process(token_spans)
+            .into_iter()
+            .map(|span| span.token)
+            .collect();
+        let (_, expr) = crate::analyzer::parsers::expression::parse_expression()
+            .parse(tokens.as_slice(), 0)
+            .unwrap();
+        expr
+    }
+
+    #[test]
+    fn test_parameter_constraints_round_trip() {
+        use crate::analyzer::Parser;
+        use crate::preprocessor::Preprocessor;
+
+        let source = "city: String where len(city) > 0, days: Int where days in 1..30";
+        let token_spans = crate::tokenizer::token::Tokenizer::new()
+            .tokenize(&format!("({})", source))
+            .unwrap();
+        let tokens: Vec<_> = crate::preprocessor::TokenPreprocessor::default()
+            .process(token_spans)
+            .into_iter()
+            .map(|span| span.token)
+            .collect();
+        let (_, parameters) = crate::analyzer::parsers::handlers::parse_parameters()
+            .parse(tokens.as_slice(), 0)
+            .unwrap();
+
+        let mut visitor = FormatterVisitor::new(create_test_config());
+        for (i, param) in parameters.iter().enumerate() {
+            if i > 0 {
+                visitor.write(", ").unwrap();
+            }
+            visitor.format_parameter(param).unwrap();
+        }
+        assert_eq!(visitor.output, source);
+    }
+
+    #[test]
+    fn test_string_delimiters_round_trip() {
+        let source = "think(r#\"Reply as {\"city\": \"${city}\"}\"#, \"\"\"Plan a trip to ${city}.\r\nAnswer with \"JSON\" only.\"\"\", \"short\")";
+        let expr = parse_expression_source(source);
+
+        let mut visitor = FormatterVisitor::new(create_test_config());
+        visitor.format_expression(&expr).unwrap();
+        assert_eq!(
+            visitor.output,
+            "think(r#\"Reply as {\"city\": \"${city}\"}\"#, \"\"\"Plan a trip to ${city}.\nAnswer with \"JSON\" only.\"\"\", \"short\")"
+        );
+        assert_eq!(parse_expression_source(&visitor.output), expr);
+    }
 }
@@ -1,9 +1,13 @@
+pub mod bindings;
+
 use crate::ast::*;
 use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
 
 use crate::CodeGen;
 
+pub use bindings::{rust_bindings, rust_bindings_from_file};
+
 impl CodeGen for MicroAgentDef {
     fn generate_rust(&self) -> TokenStream {
         let agent_name = format_ident!("{}", &self.name);
@@ -151,6 +155,7 @@ impl CodeGen for RequestHandler {
             RequestType::Query { query_type } => format_ident!("{}", query_type),
             RequestType::Action { action_type } => format_ident!("{}", action_type),
             RequestType::Custom(request_type) => format_ident!("{}", request_type),
+            RequestType::Fallback => format_ident!("fallback"),
         };
 
         let params = self.parameters.iter().map(|p| p.generate_rust());
@@ -210,11 +215,20 @@ impl CodeGen for TypeInfo {
                 let value_type_tokens = value_type.generate_rust();
                 quote! { HashMap<#key_type_tokens, #value_type_tokens> }
             }
+            TypeInfo::Set(element_type) => {
+                let element_type_tokens = element_type.generate_rust();
+                quote! { BTreeSet<#element_type_tokens> }
+            }
             TypeInfo::Custom { name, .. } => {
                 let type_ident = format_ident!("{}", name);
                 // 今は利用しない
                 quote! { #type_ident }
             }
+            TypeInfo::Generic { name, args } => {
+                let type_ident = format_ident!("{}", name);
+                let args = args.iter().map(|arg| arg.generate_rust());
+                quote! { #type_ident<#(#args),*> }
+            }
         }
     }
 }
@@ -240,10 +254,26 @@ impl CodeGen for Statement {
                 // 今は利用しない
                 quote! {}
             }
+            Statement::Let { .. } => {
+                // 今は利用しない
+                quote! {}
+            }
             Statement::Emit { .. } => {
                 // 今は利用しない
                 quote! {}
             }
+            Statement::Yield(_) => {
+                // 部分応答はランタイムのイベントとしてのみ扱う
+                quote! {}
+            }
+            Statement::Log { .. } => {
+                // ログはランタイムでのみ出力する
+                quote! {}
+            }
+            Statement::Retry { .. } => {
+                // 再試行と待機はランタイムでのみ扱う
+                quote! {}
+            }
             Statement::If {
                 condition,
                 then_block,
@@ -341,7 +371,7 @@ impl CodeGen for Literal {
         match self {
             Literal::Integer(i) => quote! { #i },
             Literal::Float(f) => quote! { #f },
-            Literal::String(s) => quote! { #s },
+            Literal::String(s) | Literal::RawString { value: s, .. } => quote! { #s },
             Literal::Boolean(b) => quote! { #b },
             Literal::Duration(d) => {
                 let secs = d.as_secs();
@@ -358,6 +388,10 @@ impl CodeGen for Literal {
                 });
                 quote! { vec![#(#items),*].into_iter().collect() }
             }
+            Literal::Set(s) => {
+                let items = s.iter().map(|item| item.generate_rust());
+                quote! { BTreeSet::from([#(#items),*]) }
+            }
             Literal::Null => quote! { None },
             Literal::Retry(_) => {
                 todo!()
@@ -373,6 +407,7 @@ impl CodeGen for BinaryOperator {
             BinaryOperator::Subtract => quote! { - },
             BinaryOperator::Multiply => quote! { * },
             BinaryOperator::Divide => quote! { / },
+            BinaryOperator::Modulo => quote! { % },
             BinaryOperator::Equal => quote! { == },
             BinaryOperator::NotEqual => quote! { != },
             BinaryOperator::LessThan => quote! { < },
@@ -464,6 +499,7 @@ mod tests {
                     event_type: EventType::Custom("destroy".to_string()),
                     parameters: vec![],
                     target: Some("manager".to_string()),
+                    immediate: false,
                 }],
             }),
         };
@@ -538,6 +574,7 @@ mod tests {
             handlers: vec![EventHandler {
                 event_type: EventType::Tick,
                 parameters: vec![],
+                requires: None,
                 block: HandlerBlock {
                     statements: vec![Statement::Assignment {
                         target: vec![Expression::StateAccess(StateAccessPath(vec![
@@ -554,6 +591,7 @@ mod tests {
                         },
                     }],
                 },
+                replay: None,
             }],
         };
 
@@ -584,11 +622,13 @@ mod tests {
                     err_type: Box::new(TypeInfo::Simple("Error".to_string())),
                 },
                 constraints: None,
+                requires: None,
                 block: HandlerBlock {
                     statements: vec![Statement::Return(Expression::StateAccess(StateAccessPath(
                         vec!["self".to_string(), "counter".to_string()],
                     )))],
                 },
+                cache: None,
             }],
         };
 
@@ -610,6 +650,7 @@ mod tests {
                     content_type: "reset".to_string(),
                 },
                 parameters: vec![],
+                requires: None,
                 block: HandlerBlock {
                     statements: vec![
                         Statement::Assignment {
@@ -628,9 +669,11 @@ mod tests {
                                 Literal::String("counter".to_string()),
                             ))],
                             target: Some("manager".to_string()),
+                            immediate: false,
                         },
                     ],
                 },
+                replay: None,
             }],
         };
 
@@ -654,6 +697,7 @@ mod tests {
         let event_handler = EventHandler {
             event_type: EventType::Tick,
             parameters: vec![],
+            requires: None,
             block: HandlerBlock {
                 statements: vec![Statement::Assignment {
                     target: vec![Expression::StateAccess(StateAccessPath(vec![
@@ -670,6 +714,7 @@ mod tests {
                     },
                 }],
             },
+            replay: None,
         };
 
         let expected = quote! {
@@ -696,11 +741,13 @@ mod tests {
                 err_type: Box::new(TypeInfo::Simple("Error".to_string())),
             },
             constraints: None,
+            requires: None,
             block: HandlerBlock {
                 statements: vec![Statement::Return(Expression::StateAccess(StateAccessPath(
                     vec!["self".to_string(), "counter".to_string()],
                 )))],
             },
+            cache: None,
         };
 
         let expected = quote! {
@@ -721,6 +768,7 @@ mod tests {
         let parameter = Parameter {
             name: "count".to_string(),
             type_info: TypeInfo::Simple("i64".to_string()),
+            constraint: None,
         };
 
         let expected = quote! {
@@ -0,0 +1,491 @@
+//! Typed Rust bindings of the events a world declares.
+//!
+//! Applications embedding KAIREI publish and receive events as [`Event`]s whose
+//! parameters are a map of [`Value`]s. [`rust_bindings`] generates a struct for
+//! every event of the world's `events` block, so that the types the DSL declares
+//! are checked by the Rust compiler:
+//!
+//! ```text
+//! world Shop {
+//!     events {
+//!         OrderPlaced(order_id: String, amount: Float, note: String?)
+//!     }
+//! }
+//! ```
+//!
+//! becomes, besides the conversions `From<OrderPlaced> for Event` and
+//! `TryFrom<&Event> for OrderPlaced`:
+//!
+//! ```ignore
+//! pub const ORDER_PLACED: &str = "OrderPlaced";
+//!
+//! #[derive(Debug, Clone, PartialEq)]
+//! pub struct OrderPlaced {
+//!     pub order_id: String,
+//!     pub amount: f64,
+//!     pub note: Option<String>,
+//! }
+//! ```
+//!
+//! `Int`, `Float`, `String`, `Boolean` and `Duration` map to `i64`, `f64`, `String`,
+//! `bool` and `Duration`; `Option{T}` (or `T?`), `Array{T}` and `Set{T}` to `Option`,
+//! `Vec` and [`ValueSet`], and a map keyed by `String` to a `HashMap`. Other types
+//! stay a [`Value`]. A parameter named after a Rust keyword becomes a raw identifier
+//! (`r#type`), or gets a trailing underscore where Rust has none (`self_`).
+//!
+//! # Build script
+//!
+//! With kairei-core in both `[dependencies]` and `[build-dependencies]`, a build
+//! script generates the bindings whenever the DSL changes:
+//!
+//! ```ignore
+//! // build.rs
+//! fn main() {
+//!     println!("cargo:rerun-if-changed=agents/shop.kairei");
+//!     let bindings = kairei_core::r#gen::rust_bindings_from_file("agents/shop.kairei")
+//!         .expect("Failed to generate event bindings");
+//!     let out_dir = std::env::var("OUT_DIR").unwrap();
+//!     std::fs::write(format!("{}/shop_events.rs", out_dir), bindings).unwrap();
+//! }
+//!
+//! // src/lib.rs
+//! pub mod shop_events {
+//!     include!(concat!(env!("OUT_DIR"), "/shop_events.rs"));
+//! }
+//! ```
+
+use std::{collections::HashMap, path::Path, time::Duration};
+
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::{format_ident, quote};
+use thiserror::Error;
+
+use crate::{
+    ASTError, CustomEventDef, Root, TypeInfo,
+    ast_registry::AstRegistry,
+    eval::set::ValueSet,
+    event_bus::{Event, Value},
+    event_registry::EventType,
+};
+
+/// A received event that does not fit a generated struct
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum EventBindingError {
+    #[error("Expected event {expected}, got {actual}")]
+    WrongEvent { expected: String, actual: String },
+    #[error("Event {event} is missing parameter {parameter}")]
+    MissingParameter { event: String, parameter: String },
+    #[error("Parameter {parameter} of event {event} is not a {expected}")]
+    MismatchedType {
+        event: String,
+        parameter: String,
+        expected: String,
+    },
+}
+
+/// Failure of [`rust_bindings_from_file`]
+#[derive(Error, Debug)]
+pub enum GenerateBindingsError {
+    #[error("Failed to read DSL: {0}")]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Ast(#[from] ASTError),
+}
+
+/// A field type of a generated struct, converted to and from an event parameter
+pub trait EventParameter: Sized {
+    /// Name of the type in errors
+    fn type_name() -> String;
+
+    fn into_value(self) -> Value;
+
+    /// `None` when `value` does not fit the type
+    fn from_value(value: &Value) -> Option<Self>;
+
+    /// The value of a parameter the event does not carry, if the type has one
+    fn missing() -> Option<Self> {
+        None
+    }
+}
+
+macro_rules! event_parameter {
+    ($ty:ty, $name:literal, $variant:ident) => {
+        impl EventParameter for $ty {
+            fn type_name() -> String {
+                $name.to_string()
+            }
+
+            fn into_value(self) -> Value {
+                Value::$variant(self)
+            }
+
+            fn from_value(value: &Value) -> Option<Self> {
+                match value {
+                    Value::$variant(v) => Some(v),
+                    _ => None,
+                }
+                .cloned()
+            }
+        }
+    };
+}
+
+event_parameter!(i64, "Int", Integer);
+event_parameter!(String, "String", String);
+event_parameter!(bool, "Boolean", Boolean);
+event_parameter!(Duration, "Duration", Duration);
+event_parameter!(ValueSet, "Set", Set);
+
+impl EventParameter for f64 {
+    fn type_name() -> String {
+        "Float".to_string()
+    }
+
+    fn into_value(self) -> Value {
+        Value::Float(self)
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Float(f) => Some(*f),
+            Value::Integer(i) => Some(*i as f64),
+            _ => None,
+        }
+    }
+}
+
+impl EventParameter for Value {
+    fn type_name() -> String {
+        "Any".to_string()
+    }
+
+    fn into_value(self) -> Value {
+        self
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+impl<T: EventParameter> EventParameter for Option<T> {
+    fn type_name() -> String {
+        format!("Option<{}>", T::type_name())
+    }
+
+    fn into_value(self) -> Value {
+        self.map_or(Value::Null, T::into_value)
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Null => Some(None),
+            value => T::from_value(value).map(Some),
+        }
+    }
+
+    fn missing() -> Option<Self> {
+        Some(None)
+    }
+}
+
+impl<T: EventParameter> EventParameter for Vec<T> {
+    fn type_name() -> String {
+        format!("Array<{}>", T::type_name())
+    }
+
+    fn into_value(self) -> Value {
+        Value::List(self.into_iter().map(T::into_value).collect())
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::List(values) => values.iter().map(T::from_value).collect(),
+            _ => None,
+        }
+    }
+}
+
+impl<T: EventParameter> EventParameter for HashMap<String, T> {
+    fn type_name() -> String {
+        format!("Map<String, {}>", T::type_name())
+    }
+
+    fn into_value(self) -> Value {
+        Value::Map(self.into_iter().map(|(k, v)| (k, v.into_value())).collect())
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Map(map) => map
+                .iter()
+                .map(|(k, v)| T::from_value(v).map(|v| (k.clone(), v)))
+                .collect(),
+            _ => None,
+        }
+    }
+}
+
+/// The event `name` with `parameters`, built by the generated `From` impls
+pub fn event(name: &str, parameters: Vec<(&str, Value)>) -> Event {
+    Event {
+        event_type: EventType::Custom(name.to_string()),
+        parameters: parameters
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect(),
+        ..Default::default()
+    }
+}
+
+/// Checks that `event` is the event `name`
+pub fn expect_event(event: &Event, name: &str) -> Result<(), EventBindingError> {
+    match &event.event_type {
+        EventType::Custom(actual) if actual == name => Ok(()),
+        other => Err(EventBindingError::WrongEvent {
+            expected: name.to_string(),
+            actual: other.to_string(),
+        }),
+    }
+}
+
+/// The parameter `parameter` of the event `name`, as a field of the generated struct
+pub fn parameter<T: EventParameter>(
+    event: &Event,
+    name: &str,
+    parameter: &str,
+) -> Result<T, EventBindingError> {
+    match event.parameters.get(parameter) {
+        Some(value) => T::from_value(value).ok_or_else(|| EventBindingError::MismatchedType {
+            event: name.to_string(),
+            parameter: parameter.to_string(),
+            expected: T::type_name(),
+        }),
+        None => T::missing().ok_or_else(|| EventBindingError::MissingParameter {
+            event: name.to_string(),
+            parameter: parameter.to_string(),
+        }),
+    }
+}
+
+/// Rust source of the structs for the events `root` declares, see the
+/// [module docs](self)
+pub fn rust_bindings(root: &Root) -> String {
+    let Some(world) = &root.world_def else {
+        return HEADER.to_string();
+    };
+    let items = world
+        .events
+        .events
+        .iter()
+        .map(|event| event_bindings(event).to_string())
+        .collect::<Vec<_>>();
+    format!("{}\n{}\n", HEADER, items.join("\n\n"))
+}
+
+/// [`rust_bindings`] of the DSL file at `path`, for build scripts
+pub fn rust_bindings_from_file(path: impl AsRef<Path>) -> Result<String, GenerateBindingsError> {
+    let dsl = std::fs::read_to_string(path)?;
+    // ビルドスクリプトにはランタイムがないので、解析の間だけ用意する
+    let root = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?
+        .block_on(AstRegistry::default().create_ast_from_dsl(&dsl))?;
+    Ok(rust_bindings(&root))
+}
+
+const HEADER: &str = "// Generated by kairei_core::r#gen::rust_bindings. Do not edit.\n";
+
+fn event_bindings(event: &CustomEventDef) -> TokenStream {
+    let name = &event.name;
+    let struct_name = escape_ident(&upper_camel_case(name));
+    let constant = format_ident!("{}", screaming_snake_case(name));
+    let fields = event
+        .parameters
+        .iter()
+        .map(|p| escape_ident(&p.name))
+        .collect::<Vec<_>>();
+    let parameters = event.parameters.iter().map(|p| &p.name).collect::<Vec<_>>();
+    let types = event.parameters.iter().map(|p| rust_type(&p.type_info));
+    // パラメータのないイベントでは変換元を使わない
+    let source = if fields.is_empty() {
+        format_ident!("_event")
+    } else {
+        format_ident!("event")
+    };
+    let const_doc = format!(" Name of the `{}` event", name);
+    let struct_doc = format!(" Parameters of the `{}` event", name);
+
+    quote! {
+        #[doc = #const_doc]
+        pub const #constant: &str = #name;
+
+        #[doc = #struct_doc]
+        #[derive(Debug, Clone, PartialEq)]
+        #[allow(non_snake_case)]
+        pub struct #struct_name {
+            #(pub #fields: #types,)*
+        }
+
+        impl #struct_name {
+            pub const NAME: &'static str = #constant;
+        }
+
+        impl ::core::convert::From<#struct_name> for ::kairei_core::event_bus::Event {
+            fn from(#source: #struct_name) -> Self {
+                ::kairei_core::r#gen::bindings::event(
+                    #struct_name::NAME,
+                    ::std::vec![
+                        #((#parameters, ::kairei_core::r#gen::bindings::EventParameter::into_value(#source.#fields)),)*
+                    ],
+                )
+            }
+        }
+
+        impl ::core::convert::TryFrom<&::kairei_core::event_bus::Event> for #struct_name {
+            type Error = ::kairei_core::r#gen::bindings::EventBindingError;
+
+            fn try_from(
+                event: &::kairei_core::event_bus::Event,
+            ) -> ::core::result::Result<Self, Self::Error> {
+                ::kairei_core::r#gen::bindings::expect_event(event, Self::NAME)?;
+                ::core::result::Result::Ok(Self {
+                    #(#fields: ::kairei_core::r#gen::bindings::parameter(event, Self::NAME, #parameters)?,)*
+                })
+            }
+        }
+    }
+}
+
+fn rust_type(type_info: &TypeInfo) -> TokenStream {
+    match type_info {
+        TypeInfo::Simple(name) => match name.as_str() {
+            "Int" => quote! { i64 },
+            "Float" => quote! { f64 },
+            "String" => quote! { ::std::string::String },
+            "Boolean" => quote! { bool },
+            "Duration" => quote! { ::std::time::Duration },
+            _ => quote! { ::kairei_core::event_bus::Value },
+        },
+        TypeInfo::Option(inner) => {
+            let inner = rust_type(inner);
+            quote! { ::core::option::Option<#inner> }
+        }
+        TypeInfo::Array(element) => {
+            let element = rust_type(element);
+            quote! { ::std::vec::Vec<#element> }
+        }
+        TypeInfo::Map(key, value) if **key == TypeInfo::Simple("String".to_string()) => {
+            let value = rust_type(value);
+            quote! { ::std::collections::HashMap<::std::string::String, #value> }
+        }
+        TypeInfo::Set(_) => quote! { ::kairei_core::eval::set::ValueSet },
+        _ => quote! { ::kairei_core::event_bus::Value },
+    }
+}
+
+/// `name` as an identifier: a raw one for a keyword, and with a trailing underscore
+/// for the keywords that cannot be raw
+fn escape_ident(name: &str) -> Ident {
+    match name {
+        "self" | "Self" | "super" | "crate" | "_" => format_ident!("{}_", name),
+        // syn が知らない新しいエディションの予約語
+        "gen" => Ident::new_raw(name, Span::call_site()),
+        _ if syn::parse_str::<Ident>(name).is_err() => Ident::new_raw(name, Span::call_site()),
+        _ => format_ident!("{}", name),
+    }
+}
+
+fn upper_camel_case(name: &str) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+fn screaming_snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    let mut previous: Option<char> = None;
+    for c in name.chars() {
+        if c.is_uppercase() && previous.is_some_and(|p| p.is_lowercase() || p.is_ascii_digit()) {
+            snake.push('_');
+        }
+        snake.extend(c.to_uppercase());
+        previous = Some(c);
+    }
+    snake
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identifiers() {
+        assert_eq!(upper_camel_case("cleanupRequested"), "CleanupRequested");
+        assert_eq!(upper_camel_case("order_placed"), "OrderPlaced");
+        assert_eq!(screaming_snake_case("OrderPlaced"), "ORDER_PLACED");
+        assert_eq!(
+            screaming_snake_case("cleanupRequested"),
+            "CLEANUP_REQUESTED"
+        );
+        assert_eq!(screaming_snake_case("tick2Done"), "TICK2_DONE");
+
+        assert_eq!(escape_ident("order_id").to_string(), "order_id");
+        assert_eq!(escape_ident("type").to_string(), "r#type");
+        assert_eq!(escape_ident("async").to_string(), "r#async");
+        assert_eq!(escape_ident("gen").to_string(), "r#gen");
+        assert_eq!(escape_ident("self").to_string(), "self_");
+        assert_eq!(escape_ident("Self").to_string(), "Self_");
+    }
+
+    #[test]
+    fn test_parameter_conversion() {
+        let event = event(
+            "OrderPlaced",
+            vec![
+                ("amount", Value::Integer(3)),
+                ("tags", Value::List(vec![Value::String("gift".to_string())])),
+                ("note", Value::Null),
+            ],
+        );
+        assert_eq!(parameter::<f64>(&event, "OrderPlaced", "amount"), Ok(3.0));
+        assert_eq!(
+            parameter::<Vec<String>>(&event, "OrderPlaced", "tags"),
+            Ok(vec!["gift".to_string()])
+        );
+        assert_eq!(
+            parameter::<Option<String>>(&event, "OrderPlaced", "note"),
+            Ok(None)
+        );
+        assert_eq!(
+            parameter::<Option<String>>(&event, "OrderPlaced", "absent"),
+            Ok(None)
+        );
+        assert_eq!(
+            parameter::<String>(&event, "OrderPlaced", "absent"),
+            Err(EventBindingError::MissingParameter {
+                event: "OrderPlaced".to_string(),
+                parameter: "absent".to_string(),
+            })
+        );
+        assert_eq!(
+            parameter::<Vec<i64>>(&event, "OrderPlaced", "tags"),
+            Err(EventBindingError::MismatchedType {
+                event: "OrderPlaced".to_string(),
+                parameter: "tags".to_string(),
+                expected: "Array<Int>".to_string(),
+            })
+        );
+        assert!(matches!(
+            expect_event(&event, "OrderShipped"),
+            Err(EventBindingError::WrongEvent { .. })
+        ));
+    }
+}
@@ -0,0 +1,450 @@
+//! # Handler Tests
+//!
+//! [`System::test_handler`](crate::system::System::test_handler) fires a synthetic
+//! event at a single `observe` or `react` handler of an agent and reports what the
+//! handler did, so that a developer can try a handler without running the system:
+//!
+//! * the state variables it changed, with their values before and after;
+//! * the events it emitted, including the failure event of a handler that fails,
+//!   but not the `StateUpdated` notifications of its state changes;
+//! * the value of its block.
+//!
+//! As in the runtime, the state writes and the events other than `emit!` are
+//! committed only when the handler succeeds.
+//!
+//! The handler runs in a context of its own with a private event bus, against a copy
+//! of the agent's state: the state of the running agent when there is one, otherwise
+//! the initial values of its `state` block. Nothing is committed, the agent and the
+//! system's event bus never see the run. `think` is answered by a mock provider from
+//! [`HandlerTest::mock_responses`], which has no memory plugin, so the handler reaches
+//! neither an LLM nor a memory store. The run is limited to
+//! [`SystemConfig::debug_eval_timeout`](crate::config::SystemConfig::debug_eval_timeout).
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use dashmap::DashMap;
+use thiserror::Error;
+use tokio::sync::broadcast::error::TryRecvError;
+
+use crate::{
+    EventHandler, MicroAgentDef, Policy,
+    catalog::AgentCatalog,
+    config::{ContextConfig, ProviderConfig},
+    eval::{
+        context::{AgentInfo, ExecutionContext, SESSION_ID_STATE, StateAccessMode},
+        evaluator::{EvalError, Evaluator},
+        expression::Value,
+        statement::{ControlFlow, StatementResult},
+    },
+    event_bus::{self, Event, EventBus},
+    event_registry::EventType,
+    provider::{provider::ProviderType, provider_registry::ProviderInstance},
+    runtime::{RuntimeAgentData, RuntimeError},
+};
+
+pub use crate::eval::recording::{HandlerKind, StateChange};
+
+/// Name of the provider answering `think` in a handler test
+pub const MOCK_PROVIDER_NAME: &str = "handler_test_mock";
+
+/// 1 回の実行で発行されるイベントを取りこぼさない程度の容量
+const EVENT_CAPACITY: usize = 256;
+
+#[derive(Debug, Error)]
+pub enum HandlerTestError {
+    #[error("Agent {agent_name} has no observe or react handler for event {event}")]
+    HandlerNotFound { agent_name: String, event: String },
+
+    #[error("Failed to run the handler: {0}")]
+    Runtime(#[from] RuntimeError),
+
+    #[error("Evaluation failed: {0}")]
+    Eval(#[from] EvalError),
+
+    #[error("Handler did not finish within {0:?}")]
+    Timeout(Duration),
+}
+
+pub type HandlerTestResult<T> = Result<T, HandlerTestError>;
+
+/// The synthetic event and the mocks of a handler test
+#[derive(Debug, Clone, Default)]
+pub struct HandlerTest {
+    /// Parameters of the synthetic event
+    pub parameters: HashMap<String, event_bus::Value>,
+    /// Handler to run. By default the observe handler, or the react handler when
+    /// the agent does not observe the event
+    pub kind: Option<HandlerKind>,
+    /// Answers of the mock provider: a `think` whose prompt contains a key is
+    /// answered with its value
+    pub mock_responses: HashMap<String, String>,
+}
+
+/// The agent a handler test runs against
+#[derive(Debug, Clone)]
+pub struct TestedAgent {
+    pub definition: Arc<MicroAgentDef>,
+    /// State of the running agent. `None` starts from the initial values
+    pub state: Option<HashMap<String, Value>>,
+    pub config_values: HashMap<String, Value>,
+    pub world_policies: Vec<Policy>,
+    pub catalog: Arc<AgentCatalog>,
+}
+
+/// What a handler did in a test
+#[derive(Debug, Clone)]
+pub struct HandlerTestReport {
+    pub kind: HandlerKind,
+    /// Whether the `requires` clause held. The block does not run otherwise
+    pub ran: bool,
+    pub value: Value,
+    /// Changed variables, ordered by name
+    pub state_changes: Vec<StateChange>,
+    /// Events in the order the handler emitted them, without `StateUpdated`
+    pub emitted_events: Vec<Event>,
+}
+
+/// Run the handler of `agent` for `event` with `test`, see the [module docs](self)
+pub async fn run(
+    agent: TestedAgent,
+    event: &str,
+    test: HandlerTest,
+    timeout: Duration,
+) -> HandlerTestResult<HandlerTestReport> {
+    let (kind, handler) = find_handler(&agent.definition, event, test.kind).ok_or_else(|| {
+        HandlerTestError::HandlerNotFound {
+            agent_name: agent.definition.name.clone(),
+            event: event.to_string(),
+        }
+    })?;
+
+    let event_bus = Arc::new(EventBus::new(EVENT_CAPACITY));
+    let primary = Arc::new(mock_provider(test.mock_responses));
+    let providers = DashMap::new();
+    providers.insert(MOCK_PROVIDER_NAME.to_string(), primary.clone());
+    let base = Arc::new(sandbox_context(
+        &agent,
+        event_bus.clone(),
+        primary,
+        providers,
+    ));
+    let evaluator = Evaluator::new();
+
+    let before = match agent.state {
+        Some(state) => state,
+        None => initial_state(&agent.definition, &evaluator, &base).await?,
+    };
+    for (name, value) in &before {
+        base.set_state(name, value.clone())
+            .map_err(EvalError::from)?;
+    }
+
+    let event = Event {
+        event_type: EventType::from(&handler.event_type),
+        parameters: test.parameters,
+        ..Default::default()
+    };
+    // 実行前に購読し、ハンドラが発行したイベントをすべて受け取る
+    let mut receiver = event_bus.subscribe().0;
+    let (ran, value) =
+        tokio::time::timeout(timeout, run_handler(&evaluator, handler, &base, &event))
+            .await
+            .map_err(|_| HandlerTestError::Timeout(timeout))??;
+
+    let mut emitted_events = Vec::new();
+    loop {
+        match receiver.receiver.try_recv() {
+            // 状態の更新通知は state_changes として報告する
+            Ok(Event {
+                event_type: EventType::StateUpdated { .. },
+                ..
+            }) => continue,
+            Ok(event) => emitted_events.push(event),
+            Err(TryRecvError::Lagged(_)) => continue,
+            Err(_) => break,
+        }
+    }
+
+    let mut names = base.list_state_variables();
+    // think が作るセッション ID はハンドラの変更として報告しない
+    names.retain(|name| name != SESSION_ID_STATE);
+    names.sort();
+    let mut state_changes = Vec::new();
+    for name in names {
+        let after = base.get_state(&name).await.map_err(EvalError::from)?;
+        let before = before.get(&name).cloned();
+        if before.as_ref() != Some(&after) {
+            state_changes.push(StateChange {
+                name,
+                before,
+                after,
+            });
+        }
+    }
+
+    Ok(HandlerTestReport {
+        kind,
+        ran,
+        value,
+        state_changes,
+        emitted_events,
+    })
+}
+
+/// The handler of `agent_def` for `event`, of `kind` or as [`HandlerTest::kind`] picks it
+pub(crate) fn find_handler<'a>(
+    agent_def: &'a MicroAgentDef,
+    event: &str,
+    kind: Option<HandlerKind>,
+) -> Option<(HandlerKind, &'a EventHandler)> {
+    let find = |kind: HandlerKind| {
+        let handlers = match kind {
+            HandlerKind::Observe => agent_def.observe.as_ref().map(|def| &def.handlers),
+            HandlerKind::React => agent_def.react.as_ref().map(|def| &def.handlers),
+        };
+        handlers?
+            .iter()
+            .find(|handler| handler.event_type.to_string() == event)
+            .map(|handler| (kind, handler))
+    };
+    match kind {
+        Some(kind) => find(kind),
+        None => find(HandlerKind::Observe).or_else(|| find(HandlerKind::React)),
+    }
+}
+
+/// `agent` のハンドラを実行する専用のコンテキスト。
+/// イベントは `event_bus` に流れ、think は `primary` か `providers` が答える
+pub(crate) fn sandbox_context(
+    agent: &TestedAgent,
+    event_bus: Arc<EventBus>,
+    primary: Arc<ProviderInstance>,
+    providers: DashMap<String, Arc<ProviderInstance>>,
+) -> ExecutionContext {
+    let mut policies = agent.definition.policies.clone();
+    policies.extend(agent.world_policies.iter().cloned());
+    ExecutionContext::new(
+        event_bus,
+        AgentInfo {
+            agent_name: agent.definition.name.clone(),
+            ..Default::default()
+        },
+        StateAccessMode::ReadWrite,
+        ContextConfig::default(),
+        primary,
+        Arc::new(providers),
+        policies,
+    )
+    .with_catalog(agent.catalog.clone())
+    .with_agent_config(agent.config_values.clone())
+}
+
+/// `think` をプロンプトに含まれるキーの値で答えるプロバイダー
+fn mock_provider(responses: HashMap<String, String>) -> ProviderInstance {
+    ProviderInstance {
+        config: ProviderConfig {
+            provider_type: ProviderType::SimpleExpert,
+            name: MOCK_PROVIDER_NAME.to_string(),
+            provider_specific: responses
+                .into_iter()
+                .map(|(pattern, answer)| (pattern, serde_json::Value::String(answer)))
+                .collect(),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+async fn initial_state(
+    agent_def: &MicroAgentDef,
+    evaluator: &Evaluator,
+    context: &Arc<ExecutionContext>,
+) -> HandlerTestResult<HashMap<String, Value>> {
+    let mut state = HashMap::new();
+    for (name, var_def) in agent_def.state.iter().flat_map(|def| &def.variables) {
+        if let Some(initial) = &var_def.initial_value {
+            let value = evaluator.eval_expression(initial, context.clone()).await?;
+            state.insert(name.clone(), value);
+        }
+    }
+    Ok(state)
+}
+
+/// ランタイムの observe / react ハンドラと同じ手順で実行する。
+/// `requires` が成り立たなければ本体を実行せずに `false` を返す
+async fn run_handler(
+    evaluator: &Evaluator,
+    handler: &EventHandler,
+    base: &ExecutionContext,
+    event: &Event,
+) -> HandlerTestResult<(bool, Value)> {
+    let Some(context) = enter_handler::<HandlerTestError>(evaluator, handler, base, event).await?
+    else {
+        return Ok((false, Value::Unit));
+    };
+    let value = match evaluator
+        .eval_handler_block(&handler.block, context.clone())
+        .await?
+    {
+        StatementResult::Value(value) | StatementResult::Control(ControlFlow::Return(value)) => {
+            value
+        }
+        StatementResult::Control(_) => Value::Unit,
+    };
+    context.commit().await.map_err(EvalError::from)?;
+    Ok((true, value))
+}
+
+/// ランタイムと同じく、ハンドラのコンテキストを作って引数を束縛する。
+/// `requires` が成り立たなければ `None` を返す
+pub(crate) async fn enter_handler<E>(
+    evaluator: &Evaluator,
+    handler: &EventHandler,
+    base: &ExecutionContext,
+    event: &Event,
+) -> Result<Option<Arc<ExecutionContext>>, E>
+where
+    E: From<RuntimeError> + From<EvalError>,
+{
+    let context = Arc::new(
+        base.fork(Some(StateAccessMode::ReadWrite))
+            .await
+            .with_new_execution()
+            .with_transaction()
+            .with_event_metadata(event)
+            .with_trigger_event(event),
+    );
+    RuntimeAgentData::bind_parameters(&context, &handler.parameters, event).await?;
+    RuntimeAgentData::check_parameter_constraints(
+        evaluator,
+        &handler.parameters,
+        &context,
+        &handler.event_type.to_string(),
+    )
+    .await?;
+    if !evaluator
+        .eval_precondition(handler.requires.as_ref(), context.clone())
+        .await?
+    {
+        return Ok(None);
+    }
+    Ok(Some(context))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast_registry::AstRegistry;
+
+    async fn forecaster(state: Option<HashMap<String, Value>>) -> TestedAgent {
+        let root = AstRegistry::default()
+            .create_ast_from_dsl(
+                r#"
+                micro Forecaster {
+                    state {
+                        calls: Int = 0;
+                    }
+                    react {
+                        on Forecast(city: String, days: Int) requires days > 0 {
+                            calls = calls + 1
+                            forecast = think("Weather in ${city}")
+                            emit Forecasted(city: city, forecast: forecast)
+                        }
+                    }
+                }
+                "#,
+            )
+            .await
+            .unwrap();
+        TestedAgent {
+            definition: Arc::new(root.micro_agent_defs[0].clone()),
+            state,
+            config_values: HashMap::new(),
+            world_policies: vec![],
+            catalog: Arc::new(AgentCatalog::default()),
+        }
+    }
+
+    fn forecast(days: i64) -> HandlerTest {
+        HandlerTest {
+            parameters: HashMap::from([
+                (
+                    "city".to_string(),
+                    event_bus::Value::String("Tokyo".to_string()),
+                ),
+                ("days".to_string(), event_bus::Value::Integer(days)),
+            ]),
+            mock_responses: HashMap::from([("Weather in Tokyo".to_string(), "Sunny".to_string())]),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reports_effects_of_handler() -> HandlerTestResult<()> {
+        let report = run(
+            forecaster(None).await,
+            "Forecast",
+            forecast(3),
+            Duration::from_secs(1),
+        )
+        .await?;
+
+        assert_eq!(report.kind, HandlerKind::React);
+        assert!(report.ran);
+        // 実行中のエージェントがなければ state ブロックの初期値から始める
+        assert_eq!(
+            report.state_changes,
+            vec![StateChange {
+                name: "calls".to_string(),
+                before: Some(Value::Integer(0)),
+                after: Value::Integer(1),
+            }]
+        );
+        assert_eq!(report.emitted_events.len(), 1);
+        let event = &report.emitted_events[0];
+        assert_eq!(
+            event.event_type,
+            EventType::Custom("Forecasted".to_string())
+        );
+        // think の結果は output を持つ Map
+        let event_bus::Value::Map(forecast) = &event.parameters["forecast"] else {
+            panic!("unexpected forecast: {:?}", event.parameters["forecast"]);
+        };
+        assert_eq!(
+            forecast["output"],
+            event_bus::Value::String("Sunny".to_string())
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_precondition_and_missing_handler() -> HandlerTestResult<()> {
+        let state = HashMap::from([("calls".to_string(), Value::Integer(5))]);
+        let report = run(
+            forecaster(Some(state)).await,
+            "Forecast",
+            forecast(0),
+            Duration::from_secs(1),
+        )
+        .await?;
+        assert!(!report.ran);
+        assert!(report.state_changes.is_empty());
+        assert!(report.emitted_events.is_empty());
+
+        let result = run(
+            forecaster(None).await,
+            "Forecast",
+            HandlerTest {
+                kind: Some(HandlerKind::Observe),
+                ..forecast(3)
+            },
+            Duration::from_secs(1),
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(HandlerTestError::HandlerNotFound { .. })
+        ));
+        Ok(())
+    }
+}
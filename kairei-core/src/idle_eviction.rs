@@ -0,0 +1,201 @@
+//! # Idle Agent Eviction
+//!
+//! With [`IdleEvictionConfig::idle_timeout`] set, the [`System`](crate::system::System)
+//! tracks when each user agent last received a request. An agent idle for longer is
+//! suspended: its state variables are saved in a [`StorageBackend`] namespace, and the
+//! agent is shut down and removed from the registry. The next request addressed to a
+//! suspended agent rebuilds it from its AST, restores the saved state and publishes the
+//! request again once the agent is listening.
+//!
+//! `onInit` and `onDestroy` run on every rehydration and suspension; the saved state is
+//! applied after `onInit`. Events other than requests do not wake a suspended agent and
+//! are not delivered to it.
+//!
+//! [`IdleEvictionConfig::idle_timeout`]: crate::config::IdleEvictionConfig::idle_timeout
+
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use dashmap::{DashMap, DashSet};
+
+use crate::{
+    clock::Clock,
+    eval::expression,
+    persistence,
+    provider::capabilities::{
+        shared_memory::Metadata,
+        storage::{StorageBackend, StorageError, ValueWithMetadata},
+    },
+};
+
+/// Time of the last request of each tracked agent
+#[derive(Debug)]
+pub struct IdleTracker {
+    clock: Arc<dyn Clock>,
+    idle_timeout: Duration,
+    last_request: DashMap<String, Instant>,
+    suspended: DashSet<String>,
+}
+
+impl IdleTracker {
+    pub fn new(idle_timeout: Duration, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            clock,
+            idle_timeout,
+            last_request: DashMap::new(),
+            suspended: DashSet::new(),
+        }
+    }
+
+    pub fn idle_timeout(&self) -> Duration {
+        self.idle_timeout
+    }
+
+    /// Track `agent_name` as active from now on
+    pub fn track(&self, agent_name: &str) {
+        self.last_request
+            .insert(agent_name.to_string(), self.clock.now());
+        self.suspended.remove(agent_name);
+    }
+
+    /// Record a request to `agent_name`; agents not tracked are ignored
+    pub fn touch(&self, agent_name: &str) {
+        if let Some(mut last) = self.last_request.get_mut(agent_name) {
+            *last = self.clock.now();
+        }
+    }
+
+    /// Tracked agents without a request for `idle_timeout`, not suspended yet
+    pub fn idle_agents(&self) -> Vec<String> {
+        let now = self.clock.now();
+        self.last_request
+            .iter()
+            .filter(|entry| now.saturating_duration_since(*entry.value()) >= self.idle_timeout)
+            .map(|entry| entry.key().clone())
+            .filter(|agent_name| !self.suspended.contains(agent_name))
+            .collect()
+    }
+
+    pub fn mark_suspended(&self, agent_name: &str) {
+        self.suspended.insert(agent_name.to_string());
+    }
+
+    pub fn is_suspended(&self, agent_name: &str) -> bool {
+        self.suspended.contains(agent_name)
+    }
+}
+
+/// State of suspended agents, one storage namespace per agent. Clones share the backend.
+#[derive(Clone)]
+pub struct SuspendedStateStore {
+    backend: Arc<dyn StorageBackend>,
+}
+
+impl SuspendedStateStore {
+    pub fn new(backend: Arc<dyn StorageBackend>) -> Self {
+        Self { backend }
+    }
+
+    pub async fn save(
+        &self,
+        agent_name: &str,
+        state: &HashMap<String, expression::Value>,
+    ) -> Result<(), StorageError> {
+        let mut entries = HashMap::with_capacity(state.len());
+        for (name, value) in state {
+            let value = persistence::to_stored(value)?;
+            entries.insert(
+                name.clone(),
+                ValueWithMetadata {
+                    value,
+                    metadata: Metadata::default(),
+                    expiry: None,
+                },
+            );
+        }
+        self.backend
+            .save(&Self::namespace(agent_name), &entries)
+            .await
+    }
+
+    pub async fn load(
+        &self,
+        agent_name: &str,
+    ) -> Result<HashMap<String, expression::Value>, StorageError> {
+        self.backend
+            .load(&Self::namespace(agent_name))
+            .await?
+            .into_iter()
+            .map(|(name, stored)| {
+                persistence::from_stored(stored.value)
+                    .map(|value| (name, value))
+                    .map_err(StorageError::from)
+            })
+            .collect()
+    }
+
+    /// Drop the saved state once the agent is running again
+    pub async fn clear(&self, agent_name: &str) -> Result<(), StorageError> {
+        self.backend
+            .save(&Self::namespace(agent_name), &HashMap::new())
+            .await
+    }
+
+    fn namespace(agent_name: &str) -> String {
+        format!("suspended_agent.{}", agent_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clock::MockClock, provider::config::plugins::InMemoryConfig,
+        provider::plugins::storage::in_memory::InMemoryBackend,
+    };
+
+    #[test]
+    fn test_agents_become_idle_after_timeout() {
+        let clock = MockClock::new();
+        let tracker = IdleTracker::new(Duration::from_secs(60), Arc::new(clock.clone()));
+        tracker.track("Busy");
+        tracker.track("Quiet");
+        // 追跡していないエージェントへのリクエストは無視される
+        tracker.touch("Builtin");
+
+        clock.advance(Duration::from_secs(59));
+        assert!(tracker.idle_agents().is_empty());
+        tracker.touch("Busy");
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(tracker.idle_agents(), vec!["Quiet".to_string()]);
+
+        tracker.mark_suspended("Quiet");
+        assert!(tracker.is_suspended("Quiet"));
+        assert!(tracker.idle_agents().is_empty());
+
+        tracker.track("Quiet");
+        assert!(!tracker.is_suspended("Quiet"));
+    }
+
+    #[tokio::test]
+    async fn test_suspended_state_round_trip() {
+        let store =
+            SuspendedStateStore::new(Arc::new(InMemoryBackend::new(InMemoryConfig::default())));
+        let state = HashMap::from([
+            ("count".to_string(), expression::Value::Integer(3)),
+            (
+                "name".to_string(),
+                expression::Value::String("kairei".to_string()),
+            ),
+        ]);
+        store.save("Counter", &state).await.unwrap();
+        assert_eq!(store.load("Counter").await.unwrap(), state);
+
+        store.clear("Counter").await.unwrap();
+        assert!(store.load("Counter").await.unwrap().is_empty());
+    }
+}
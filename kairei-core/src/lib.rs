@@ -83,22 +83,46 @@
 //! The [`runtime`] and [`event`] modules execute the AST in an event-driven environment,
 //! orchestrating agent interactions through asynchronous events and message passing.
 
+pub mod agent_batch;
+pub mod agent_log;
 pub mod agent_registry;
+pub mod agent_settings;
 pub mod analyzer;
 pub mod ast;
 pub mod ast_registry;
+pub mod background_tasks;
+pub mod catalog;
+pub mod clock;
 pub mod config;
+pub mod config_values;
 pub mod core;
+pub mod cron;
+pub mod debug_eval;
+pub mod debug_session;
+pub mod diagnostics;
+pub mod dsl_version;
+pub mod dsl_watcher;
 pub mod error;
 pub mod eval;
 pub mod event;
 pub mod formatter;
 pub mod r#gen;
+pub mod handler_test;
+pub mod idle_eviction;
+pub mod message_catalog;
 pub mod native_feature;
+pub mod persistence;
+pub mod preflight;
 pub mod preprocessor;
 pub mod provider;
+pub mod reload_plan;
+pub mod request_queue;
+pub mod response_cache;
 pub mod runtime;
+pub mod scheduler;
+pub mod secret_scan;
 pub mod system;
+pub mod test_support;
 pub mod timestamp;
 pub mod tokenizer;
 pub mod type_checker;
@@ -0,0 +1,530 @@
+//! Localized messages for parser and type checker diagnostics.
+//!
+//! Errors of the tokenizer, parser, analysis limits and type checker, and the
+//! errors of requests shown to end users, describe
+//! themselves as a [`Diagnostic`]: a stable code from [`codes`] plus named
+//! arguments. A [`MessageCatalog`] renders a diagnostic in its [`Locale`],
+//! replacing each `{name}` in the template with the argument of that name. A code
+//! the locale has no entry for falls back to English.
+//!
+//! The catalogs are compiled into the binary. Every code must have an English
+//! entry; other locales may lag behind. Codes are explained at length in the
+//! [`diagnostics`](crate::diagnostics) registry.
+//!
+//! The `Display` text of the errors themselves stays English, for logs.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Language of rendered diagnostics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    En,
+    Ja,
+}
+
+impl Locale {
+    /// The locale of a language tag such as `ja` or `ja-JP`, if supported
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let language = tag.trim().split(['-', '_']).next()?.to_ascii_lowercase();
+        match language.as_str() {
+            "en" => Some(Self::En),
+            "ja" => Some(Self::Ja),
+            _ => None,
+        }
+    }
+
+    /// The first supported locale listed in an `Accept-Language` header. Quality
+    /// values are ignored: the header is read in order.
+    pub fn from_accept_language(header: &str) -> Option<Self> {
+        header
+            .split(',')
+            .filter_map(|tag| tag.split(';').next())
+            .find_map(Self::from_tag)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::En => "en",
+            Self::Ja => "ja",
+        }
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+macro_rules! diagnostic_codes {
+    ($($(#[$doc:meta])* $name:ident = $code:literal;)*) => {
+        /// Stable codes of diagnostics
+        pub mod codes {
+            $($(#[$doc])* pub const $name: &str = $code;)*
+        }
+
+        /// Every code in [`codes`]
+        pub const DIAGNOSTIC_CODES: &[&str] = &[$($code),*];
+    };
+}
+
+diagnostic_codes! {
+    /// `message`
+    PARSE_UNEXPECTED_EOF = "parse.unexpected_eof";
+    /// `expected`, `found`
+    PARSE_UNEXPECTED = "parse.unexpected";
+    PARSE_NO_ALTERNATIVE = "parse.no_alternative";
+    /// `message`
+    PARSE_FAILURE = "parse.failure";
+    PARSE_TRAILING_TOKENS = "parse.trailing_tokens";
+    /// `message`, `found`
+    TOKENIZE_INVALID = "tokenize.invalid";
+    /// `name`
+    AST_NOT_FOUND = "ast.not_found";
+    /// `agent`, `handler`, `count`, `max`
+    LIMIT_TOO_MANY_STATEMENTS = "limit.too_many_statements";
+    /// `agent`, `count`, `max`
+    LIMIT_TOO_MANY_HANDLERS = "limit.too_many_handlers";
+    /// `location`, `max`
+    LIMIT_NESTING_TOO_DEEP = "limit.nesting_too_deep";
+    /// `declared`
+    VERSION_INVALID = "version.invalid";
+    /// `required`, `supported`
+    VERSION_TOO_NEW = "version.too_new";
+    /// `declared`, `oldest`, `current`
+    VERSION_TOO_OLD = "version.too_old";
+    /// `expected`, `found`
+    TYPE_MISMATCH = "type.mismatch";
+    /// `name`
+    TYPE_UNDEFINED_TYPE = "type.undefined_type";
+    /// `message`
+    TYPE_INVALID_TYPE_ARGUMENTS = "type.invalid_type_arguments";
+    /// `cycle`
+    TYPE_RECURSIVE_TYPE = "type.recursive_type";
+    /// `message`
+    TYPE_INVALID_STATE_VARIABLE = "type.invalid_state_variable";
+    /// `message`
+    TYPE_INVALID_HANDLER_SIGNATURE = "type.invalid_handler_signature";
+    /// `message`
+    TYPE_INVALID_THINK_BLOCK = "type.invalid_think_block";
+    /// `message`
+    TYPE_INFERENCE = "type.inference";
+    /// `name`
+    TYPE_UNDEFINED_VARIABLE = "type.undefined_variable";
+    /// `name`
+    TYPE_UNDEFINED_FUNCTION = "type.undefined_function";
+    /// `expected`, `found`
+    TYPE_INVALID_RETURN_TYPE = "type.invalid_return_type";
+    /// `function`, `argument`, `expected`, `found`
+    TYPE_INVALID_ARGUMENT_TYPE = "type.invalid_argument_type";
+    /// `operator`, `left_type`, `right_type`
+    TYPE_INVALID_OPERATOR_TYPE = "type.invalid_operator_type";
+    /// `message`
+    TYPE_INVALID_WILL_ACTION = "type.invalid_will_action";
+    /// `message`
+    TYPE_WILL_ACTION_PARAMETER = "type.will_action_parameter";
+    /// `message`
+    TYPE_INVALID_SISTENCE_CONTEXT = "type.invalid_sistence_context";
+    /// `message`
+    TYPE_INVALID_SCHEDULE = "type.invalid_schedule";
+    /// `handler`
+    REQUEST_PRECONDITION_FAILED = "request.precondition_failed";
+    /// `handler`, `violations`
+    REQUEST_INVALID_PARAMETERS = "request.invalid_parameters";
+}
+
+const EN: &[(&str, &str)] = &[
+    (
+        codes::PARSE_UNEXPECTED_EOF,
+        "Unexpected end of input: {message}",
+    ),
+    (
+        codes::PARSE_UNEXPECTED,
+        "Expected {expected}, found {found}",
+    ),
+    (codes::PARSE_NO_ALTERNATIVE, "No syntax matches here"),
+    (codes::PARSE_FAILURE, "Syntax error: {message}"),
+    (codes::PARSE_TRAILING_TOKENS, "Not all tokens were consumed"),
+    (codes::TOKENIZE_INVALID, "Invalid token {found}: {message}"),
+    (codes::AST_NOT_FOUND, "AST not found: {name}"),
+    (
+        codes::LIMIT_TOO_MANY_STATEMENTS,
+        "{handler} of {agent} has {count} statements, more than the limit of {max}",
+    ),
+    (
+        codes::LIMIT_TOO_MANY_HANDLERS,
+        "{agent} has {count} handlers, more than the limit of {max}",
+    ),
+    (
+        codes::LIMIT_NESTING_TOO_DEEP,
+        "{location} is nested deeper than the limit of {max}",
+    ),
+    (
+        codes::VERSION_INVALID,
+        "\"{declared}\" is not a DSL version, declare one as kairei \"MAJOR.MINOR\"",
+    ),
+    (
+        codes::VERSION_TOO_NEW,
+        "This file requires DSL {required}, this build supports up to {supported}",
+    ),
+    (
+        codes::VERSION_TOO_OLD,
+        "This file is written for DSL {declared}, this build reads DSL {oldest} to {current}; migrate it to DSL {current}",
+    ),
+    (
+        codes::TYPE_MISMATCH,
+        "Type mismatch: expected {expected}, found {found}",
+    ),
+    (codes::TYPE_UNDEFINED_TYPE, "Undefined type: {name}"),
+    (
+        codes::TYPE_INVALID_TYPE_ARGUMENTS,
+        "Invalid type arguments: {message}",
+    ),
+    (
+        codes::TYPE_RECURSIVE_TYPE,
+        "Recursive type without indirection: {cycle}",
+    ),
+    (
+        codes::TYPE_INVALID_STATE_VARIABLE,
+        "Invalid state variable: {message}",
+    ),
+    (
+        codes::TYPE_INVALID_HANDLER_SIGNATURE,
+        "Invalid handler signature: {message}",
+    ),
+    (
+        codes::TYPE_INVALID_THINK_BLOCK,
+        "Invalid think block: {message}",
+    ),
+    (codes::TYPE_INFERENCE, "Type inference error: {message}"),
+    (codes::TYPE_UNDEFINED_VARIABLE, "Undefined variable: {name}"),
+    (codes::TYPE_UNDEFINED_FUNCTION, "Undefined function: {name}"),
+    (
+        codes::TYPE_INVALID_RETURN_TYPE,
+        "Invalid return type: expected {expected}, found {found}",
+    ),
+    (
+        codes::TYPE_INVALID_ARGUMENT_TYPE,
+        "Invalid argument type for function {function}: argument {argument} expected {expected}, found {found}",
+    ),
+    (
+        codes::TYPE_INVALID_OPERATOR_TYPE,
+        "Invalid operator type: operator {operator} cannot be applied to {left_type} and {right_type}",
+    ),
+    (
+        codes::TYPE_INVALID_WILL_ACTION,
+        "Invalid will action: {message}",
+    ),
+    (
+        codes::TYPE_WILL_ACTION_PARAMETER,
+        "Will action parameter error: {message}",
+    ),
+    (
+        codes::TYPE_INVALID_SISTENCE_CONTEXT,
+        "Invalid sistence context: {message}",
+    ),
+    (codes::TYPE_INVALID_SCHEDULE, "Invalid schedule: {message}"),
+    (
+        codes::REQUEST_PRECONDITION_FAILED,
+        "{handler} cannot be answered now: its requires clause does not hold",
+    ),
+    (
+        codes::REQUEST_INVALID_PARAMETERS,
+        "Invalid parameters for {handler}: {violations}",
+    ),
+];
+
+const JA: &[(&str, &str)] = &[
+    (
+        codes::PARSE_UNEXPECTED_EOF,
+        "入力が途中で終わっています: {message}",
+    ),
+    (
+        codes::PARSE_UNEXPECTED,
+        "{expected} が必要ですが {found} が見つかりました",
+    ),
+    (
+        codes::PARSE_NO_ALTERNATIVE,
+        "ここに当てはまる構文がありません",
+    ),
+    (codes::PARSE_FAILURE, "構文エラー: {message}"),
+    (
+        codes::PARSE_TRAILING_TOKENS,
+        "解析されずに残ったトークンがあります",
+    ),
+    (codes::TOKENIZE_INVALID, "不正なトークン {found}: {message}"),
+    (codes::AST_NOT_FOUND, "AST が見つかりません: {name}"),
+    (
+        codes::LIMIT_TOO_MANY_STATEMENTS,
+        "{agent} の {handler} の文の数 {count} が上限 {max} を超えています",
+    ),
+    (
+        codes::LIMIT_TOO_MANY_HANDLERS,
+        "{agent} のハンドラの数 {count} が上限 {max} を超えています",
+    ),
+    (
+        codes::LIMIT_NESTING_TOO_DEEP,
+        "{location} の入れ子が上限 {max} より深くなっています",
+    ),
+    (
+        codes::VERSION_INVALID,
+        "\"{declared}\" は DSL のバージョンではありません。kairei \"MAJOR.MINOR\" の形で宣言してください",
+    ),
+    (
+        codes::VERSION_TOO_NEW,
+        "このファイルには DSL {required} が必要ですが、このビルドは {supported} までしか対応していません",
+    ),
+    (
+        codes::VERSION_TOO_OLD,
+        "このファイルは DSL {declared} 向けですが、このビルドが読めるのは DSL {oldest} から {current} までです。DSL {current} に移行してください",
+    ),
+    (
+        codes::TYPE_MISMATCH,
+        "型が一致しません: {expected} が必要ですが {found} です",
+    ),
+    (codes::TYPE_UNDEFINED_TYPE, "未定義の型: {name}"),
+    (
+        codes::TYPE_INVALID_TYPE_ARGUMENTS,
+        "型引数が不正です: {message}",
+    ),
+    (
+        codes::TYPE_RECURSIVE_TYPE,
+        "型が間接参照なしに自身を含んでいます: {cycle}",
+    ),
+    (
+        codes::TYPE_INVALID_STATE_VARIABLE,
+        "state 変数が不正です: {message}",
+    ),
+    (
+        codes::TYPE_INVALID_HANDLER_SIGNATURE,
+        "ハンドラのシグネチャが不正です: {message}",
+    ),
+    (
+        codes::TYPE_INVALID_THINK_BLOCK,
+        "think ブロックが不正です: {message}",
+    ),
+    (codes::TYPE_INFERENCE, "型を推論できません: {message}"),
+    (codes::TYPE_UNDEFINED_VARIABLE, "未定義の変数: {name}"),
+    (codes::TYPE_UNDEFINED_FUNCTION, "未定義の関数: {name}"),
+    (
+        codes::TYPE_INVALID_RETURN_TYPE,
+        "戻り値の型が不正です: {expected} が必要ですが {found} です",
+    ),
+    (
+        codes::TYPE_INVALID_ARGUMENT_TYPE,
+        "関数 {function} の引数 {argument} の型が不正です: {expected} が必要ですが {found} です",
+    ),
+    (
+        codes::TYPE_INVALID_OPERATOR_TYPE,
+        "演算子 {operator} は {left_type} と {right_type} に適用できません",
+    ),
+    (
+        codes::TYPE_INVALID_WILL_ACTION,
+        "will アクションが不正です: {message}",
+    ),
+    (
+        codes::TYPE_WILL_ACTION_PARAMETER,
+        "will アクションの引数が不正です: {message}",
+    ),
+    (
+        codes::TYPE_INVALID_SISTENCE_CONTEXT,
+        "sistence エージェントのコンテキストが不正です: {message}",
+    ),
+    (
+        codes::TYPE_INVALID_SCHEDULE,
+        "schedule が不正です: {message}",
+    ),
+    (
+        codes::REQUEST_PRECONDITION_FAILED,
+        "{handler} の requires 条件を満たしていないため応答できません",
+    ),
+    (
+        codes::REQUEST_INVALID_PARAMETERS,
+        "{handler} のパラメータが不正です: {violations}",
+    ),
+];
+
+/// A diagnostic code with the arguments of its message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub args: Vec<(&'static str, String)>,
+}
+
+impl Diagnostic {
+    pub fn new(code: &'static str) -> Self {
+        Self {
+            code,
+            args: Vec::new(),
+        }
+    }
+
+    pub fn with_arg(mut self, name: &'static str, value: impl ToString) -> Self {
+        self.args.push((name, value.to_string()));
+        self
+    }
+
+    pub fn arg(&self, name: &str) -> Option<&str> {
+        self.args
+            .iter()
+            .find(|(arg, _)| *arg == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Renders diagnostics in one locale, see the [module docs](self)
+#[derive(Debug, Clone, Copy)]
+pub struct MessageCatalog {
+    locale: Locale,
+    entries: &'static [(&'static str, &'static str)],
+}
+
+impl Default for MessageCatalog {
+    fn default() -> Self {
+        Self::new(Locale::default())
+    }
+}
+
+impl MessageCatalog {
+    pub fn new(locale: Locale) -> Self {
+        let entries = match locale {
+            Locale::En => EN,
+            Locale::Ja => JA,
+        };
+        Self { locale, entries }
+    }
+
+    pub fn locale(&self) -> Locale {
+        self.locale
+    }
+
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        match lookup(self.entries, diagnostic.code).or_else(|| lookup(EN, diagnostic.code)) {
+            Some(template) => interpolate(template, &diagnostic.args),
+            // 英語の定義漏れはテストで防ぐが、念のためコードと引数をそのまま出す
+            None => {
+                let args: Vec<String> = diagnostic
+                    .args
+                    .iter()
+                    .map(|(name, value)| format!("{}={}", name, value))
+                    .collect();
+                format!("{} ({})", diagnostic.code, args.join(", "))
+            }
+        }
+    }
+
+    /// Where to read more about `code`, appended to rendered errors for users
+    pub fn explain_hint(&self, code: &str) -> String {
+        match self.locale {
+            Locale::En => format!("run explain {} for details", code),
+            Locale::Ja => format!("詳しくは explain {} を実行してください", code),
+        }
+    }
+}
+
+fn lookup(entries: &[(&str, &'static str)], code: &str) -> Option<&'static str> {
+    entries
+        .iter()
+        .find(|(entry, _)| *entry == code)
+        .map(|(_, template)| *template)
+}
+
+/// Replace each `{name}` with its argument. Unknown placeholders are kept, and
+/// arguments are not interpolated again.
+fn interpolate(template: &str, args: &[(&'static str, String)]) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let value = after.find('}').and_then(|end| {
+            let name = &after[..end];
+            args.iter()
+                .find(|(arg, _)| *arg == name)
+                .map(|(_, value)| (value, end))
+        });
+        match value {
+            Some((value, end)) => {
+                rendered.push_str(value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                rendered.push('{');
+                rest = after;
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_every_code_has_an_english_entry() {
+        let english: HashSet<&str> = EN.iter().map(|(code, _)| *code).collect();
+        for code in DIAGNOSTIC_CODES {
+            assert!(english.contains(code), "no English message for {}", code);
+        }
+        // 翻訳側に存在しないコードがあれば打ち間違い
+        let known: HashSet<&str> = DIAGNOSTIC_CODES.iter().copied().collect();
+        for (code, _) in EN.iter().chain(JA) {
+            assert!(known.contains(code), "unknown code {} in a catalog", code);
+        }
+    }
+
+    #[test]
+    fn test_render_interpolates_arguments() {
+        let diagnostic = Diagnostic::new(codes::TYPE_MISMATCH)
+            .with_arg("expected", "Int")
+            .with_arg("found", "{found}");
+        assert_eq!(
+            MessageCatalog::default().render(&diagnostic),
+            "Type mismatch: expected Int, found {found}"
+        );
+        assert_eq!(
+            MessageCatalog::new(Locale::Ja).render(&diagnostic),
+            "型が一致しません: Int が必要ですが {found} です"
+        );
+        // 引数のない placeholder はそのまま残す
+        assert_eq!(
+            interpolate("{a} and {b", &[("a", "x".to_string())]),
+            "x and {b"
+        );
+    }
+
+    #[test]
+    fn test_untranslated_code_falls_back_to_english() {
+        let partial = MessageCatalog {
+            locale: Locale::Ja,
+            entries: &[(codes::TYPE_UNDEFINED_TYPE, "未定義の型: {name}")],
+        };
+        let diagnostic = Diagnostic::new(codes::TYPE_UNDEFINED_VARIABLE).with_arg("name", "x");
+        assert_eq!(partial.render(&diagnostic), "Undefined variable: x");
+    }
+
+    #[test]
+    fn test_locale_from_accept_language() {
+        assert_eq!(
+            Locale::from_accept_language("ja-JP,ja;q=0.9,en;q=0.8"),
+            Some(Locale::Ja)
+        );
+        assert_eq!(
+            Locale::from_accept_language("fr-FR, en-US;q=0.5"),
+            Some(Locale::En)
+        );
+        assert_eq!(Locale::from_accept_language("fr, *"), None);
+        assert_eq!(
+            serde_json::from_str::<Locale>("\"ja\"").unwrap(),
+            Locale::Ja
+        );
+    }
+}
@@ -0,0 +1,997 @@
+//! # External Broker Bridge
+//!
+//! Connects the event bus to an external message broker, such as NATS, through a
+//! [`BrokerAdapter`].
+//!
+//! Exports publish local events to broker topics. Each [`BrokerExport`] maps an event
+//! type to a topic and an encoding, the binary wire format of the remote event bridge
+//! or JSON. Imports subscribe to topics and publish each message, a JSON object of
+//! parameters, as a custom event. Each [`BrokerImport`] names the event and the
+//! parameters a message must carry; messages that do not match are counted and
+//! dropped.
+//!
+//! Delivery is at least once:
+//!
+//! * an export the broker refuses stays queued and is published again after
+//!   `reconnect_delay`;
+//! * a subscription that fails or ends is renewed after `reconnect_delay`;
+//! * an imported message with an ID in its `id_field` is published once, however often
+//!   the broker delivers it, with that ID as its event ID. It is never exported back.
+//!
+//! Every `metrics_interval`, the bridge publishes its [`BrokerBridgeStats`] as a
+//! `MetricsSummary` event whose `source` parameter is `broker_bridge`.
+//!
+//! [`BrokerExport`]: crate::config::BrokerExport
+
+use std::{
+    collections::{HashMap, VecDeque},
+    pin::Pin,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+};
+
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use thiserror::Error;
+use tokio::sync::{Notify, RwLock, broadcast};
+use tokio_stream::wrappers::{BroadcastStream, errors::BroadcastStreamRecvError};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use super::{
+    remote_bridge::RecentIds,
+    types::{
+        FeatureError, FeatureResult, NativeFeature, NativeFeatureContext, NativeFeatureStatus,
+        NativeFeatureType,
+    },
+};
+use crate::{
+    TypeInfo,
+    config::{BrokerBridgeConfig, BrokerConfig, BrokerEncoding, BrokerImport},
+    event::{
+        coercion::{self, CoercionError},
+        wire,
+    },
+    event_bus::{Event, EventError, EventReceiver, Value},
+    event_registry::{EventType, ParameterType},
+};
+
+/// Value of the `source` parameter of the bridge's `MetricsSummary` events
+pub const METRICS_SOURCE: &str = "broker_bridge";
+
+/// Messages kept per topic for slow subscribers of an [`InProcessBroker`]
+const IN_PROCESS_CAPACITY: usize = 1024;
+
+#[derive(Debug, Error)]
+pub enum BrokerError {
+    #[error("Failed to connect to the broker: {0}")]
+    Connection(String),
+
+    #[error("Failed to publish to {topic}: {message}")]
+    Publish { topic: String, message: String },
+
+    #[error("Failed to subscribe to {topic}: {message}")]
+    Subscribe { topic: String, message: String },
+
+    #[error("Subscription missed {0} messages")]
+    Lagged(u64),
+
+    #[error("Broker not available: {0}")]
+    Unavailable(String),
+}
+
+pub type BrokerResult<T> = Result<T, BrokerError>;
+
+/// Messages of a subscription. It ends when the subscription is lost.
+pub type BrokerStream = Pin<Box<dyn Stream<Item = BrokerResult<Vec<u8>>> + Send>>;
+
+/// A message broker the bridge publishes to and subscribes through
+#[async_trait]
+pub trait BrokerAdapter: Send + Sync {
+    async fn publish(&self, topic: &str, payload: Vec<u8>) -> BrokerResult<()>;
+
+    async fn subscribe(&self, topic: &str) -> BrokerResult<BrokerStream>;
+}
+
+/// The adapter for the configured broker
+pub fn adapter_for(config: &BrokerConfig) -> BrokerResult<Arc<dyn BrokerAdapter>> {
+    match config {
+        BrokerConfig::InProcess => Ok(Arc::new(InProcessBroker::new())),
+        #[cfg(feature = "nats")]
+        BrokerConfig::Nats { url } => Ok(Arc::new(NatsAdapter::new(url.clone()))),
+        #[cfg(not(feature = "nats"))]
+        BrokerConfig::Nats { .. } => Err(BrokerError::Unavailable(
+            "NATS requires the `nats` feature".to_string(),
+        )),
+    }
+}
+
+/// A broker inside the process. A subscriber receives the messages published to its
+/// topic after it subscribed.
+#[derive(Default)]
+pub struct InProcessBroker {
+    topics: Mutex<HashMap<String, broadcast::Sender<Vec<u8>>>>,
+    subscriptions: AtomicUsize,
+    refused_publishes: AtomicUsize,
+}
+
+impl InProcessBroker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn sender(&self, topic: &str) -> broadcast::Sender<Vec<u8>> {
+        self.topics
+            .lock()
+            .unwrap()
+            .entry(topic.to_string())
+            .or_insert_with(|| broadcast::channel(IN_PROCESS_CAPACITY).0)
+            .clone()
+    }
+
+    /// End the subscriptions to `topic`, as a lost connection would
+    pub fn close_subscriptions(&self, topic: &str) {
+        self.topics.lock().unwrap().remove(topic);
+    }
+
+    /// Subscriptions made so far, ended ones included
+    pub fn subscriptions(&self) -> usize {
+        self.subscriptions.load(Ordering::SeqCst)
+    }
+
+    /// Refuse the next `count` publishes
+    pub fn refuse_publishes(&self, count: usize) {
+        self.refused_publishes.store(count, Ordering::SeqCst);
+    }
+}
+
+#[async_trait]
+impl BrokerAdapter for InProcessBroker {
+    async fn publish(&self, topic: &str, payload: Vec<u8>) -> BrokerResult<()> {
+        if self
+            .refused_publishes
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                count.checked_sub(1)
+            })
+            .is_ok()
+        {
+            return Err(BrokerError::Publish {
+                topic: topic.to_string(),
+                message: "refused".to_string(),
+            });
+        }
+        // 購読者のいないトピックへのメッセージは捨てる
+        let _ = self.sender(topic).send(payload);
+        Ok(())
+    }
+
+    async fn subscribe(&self, topic: &str) -> BrokerResult<BrokerStream> {
+        let receiver = self.sender(topic).subscribe();
+        self.subscriptions.fetch_add(1, Ordering::SeqCst);
+        Ok(Box::pin(BroadcastStream::new(receiver).map(|message| {
+            message.map_err(|BroadcastStreamRecvError::Lagged(count)| BrokerError::Lagged(count))
+        })))
+    }
+}
+
+#[cfg(feature = "nats")]
+pub use nats::NatsAdapter;
+
+#[cfg(feature = "nats")]
+mod nats {
+    use tokio::sync::OnceCell;
+
+    use super::*;
+
+    /// A core NATS connection, made on first use. NATS keeps no message for a
+    /// subject nobody subscribes to, so messages published while the bridge
+    /// resubscribes are lost.
+    pub struct NatsAdapter {
+        url: String,
+        client: OnceCell<async_nats::Client>,
+    }
+
+    impl NatsAdapter {
+        pub fn new(url: String) -> Self {
+            Self {
+                url,
+                client: OnceCell::new(),
+            }
+        }
+
+        /// 接続に失敗したら、次に使うときに接続し直す
+        async fn client(&self) -> BrokerResult<&async_nats::Client> {
+            self.client
+                .get_or_try_init(|| async {
+                    async_nats::connect(self.url.as_str())
+                        .await
+                        .map_err(|e| BrokerError::Connection(e.to_string()))
+                })
+                .await
+        }
+    }
+
+    #[async_trait]
+    impl BrokerAdapter for NatsAdapter {
+        async fn publish(&self, topic: &str, payload: Vec<u8>) -> BrokerResult<()> {
+            let client = self.client().await?;
+            let failed = |message: String| BrokerError::Publish {
+                topic: topic.to_string(),
+                message,
+            };
+            client
+                .publish(topic.to_string(), payload.into())
+                .await
+                .map_err(|e| failed(e.to_string()))?;
+            // サーバーに届くまで待ち、届かなければ再送させる
+            client.flush().await.map_err(|e| failed(e.to_string()))
+        }
+
+        async fn subscribe(&self, topic: &str) -> BrokerResult<BrokerStream> {
+            let subscriber = self
+                .client()
+                .await?
+                .subscribe(topic.to_string())
+                .await
+                .map_err(|e| BrokerError::Subscribe {
+                    topic: topic.to_string(),
+                    message: e.to_string(),
+                })?;
+            Ok(Box::pin(
+                subscriber.map(|message| Ok(message.payload.to_vec())),
+            ))
+        }
+    }
+}
+
+/// Counts of a broker bridge since it was created
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BrokerBridgeStats {
+    /// Messages published to the broker
+    pub exported: u64,
+    /// Publishes the broker refused; the message is published again
+    pub export_failures: u64,
+    /// Exports dropped because `max_pending` were already waiting
+    pub dropped: u64,
+    /// Exports waiting for the broker
+    pub pending: usize,
+    /// Local events or broker messages missed because the bridge fell behind
+    pub lagged: u64,
+    /// Messages published on the local bus
+    pub imported: u64,
+    /// Redelivered messages dropped
+    pub duplicates: u64,
+    /// Messages dropped as malformed, not matching their import, or failing to publish
+    pub rejected: u64,
+    /// Subscriptions retried after failing or ending
+    pub resubscribes: u64,
+}
+
+impl BrokerBridgeStats {
+    fn metrics_event(&self) -> Event {
+        let counts = [
+            ("exported", self.exported),
+            ("export_failures", self.export_failures),
+            ("dropped", self.dropped),
+            ("pending", self.pending as u64),
+            ("lagged", self.lagged),
+            ("imported", self.imported),
+            ("duplicates", self.duplicates),
+            ("rejected", self.rejected),
+            ("resubscribes", self.resubscribes),
+        ];
+        let mut parameters: HashMap<String, Value> = counts
+            .into_iter()
+            .map(|(name, count)| (name.to_string(), Value::Float(count as f64)))
+            .collect();
+        parameters.insert(
+            "source".to_string(),
+            Value::String(METRICS_SOURCE.to_string()),
+        );
+        Event {
+            event_type: EventType::MetricsSummary,
+            parameters,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    exported: AtomicU64,
+    export_failures: AtomicU64,
+    dropped: AtomicU64,
+    lagged: AtomicU64,
+    imported: AtomicU64,
+    duplicates: AtomicU64,
+    rejected: AtomicU64,
+    resubscribes: AtomicU64,
+}
+
+fn count(counter: &AtomicU64, by: u64) {
+    counter.fetch_add(by, Ordering::Relaxed);
+}
+
+/// Why an imported message was not published
+#[derive(Debug, Error)]
+enum ImportError {
+    #[error("Message is not JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Message is not a JSON object")]
+    NotAnObject,
+
+    #[error("Field {0} holds neither a string nor a number")]
+    InvalidId(String),
+
+    #[error(transparent)]
+    Parameter(#[from] CoercionError),
+
+    #[error("Failed to publish the event: {0}")]
+    Publish(#[from] EventError),
+}
+
+/// An import with its parameter types parsed
+struct Import {
+    config: BrokerImport,
+    schema: HashMap<String, ParameterType>,
+}
+
+/// Encoded exports waiting to be published, oldest first
+struct ExportQueue {
+    capacity: usize,
+    messages: Mutex<VecDeque<(String, Vec<u8>)>>,
+    /// Notified when a message is queued
+    added: Notify,
+}
+
+impl ExportQueue {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            messages: Mutex::new(VecDeque::new()),
+            added: Notify::new(),
+        }
+    }
+
+    /// Returns `false` if the oldest message was dropped to make room
+    fn push(&self, topic: String, payload: Vec<u8>) -> bool {
+        let kept = {
+            let mut messages = self.messages.lock().unwrap();
+            messages.push_back((topic, payload));
+            let full = messages.len() > self.capacity;
+            if full {
+                messages.pop_front();
+            }
+            !full
+        };
+        self.added.notify_one();
+        kept
+    }
+
+    fn pop(&self) -> Option<(String, Vec<u8>)> {
+        self.messages.lock().unwrap().pop_front()
+    }
+
+    /// Put back a message that failed to publish, unless the queue filled up meanwhile
+    fn retry(&self, topic: String, payload: Vec<u8>) -> bool {
+        let mut messages = self.messages.lock().unwrap();
+        if messages.len() >= self.capacity {
+            return false;
+        }
+        messages.push_front((topic, payload));
+        true
+    }
+
+    fn len(&self) -> usize {
+        self.messages.lock().unwrap().len()
+    }
+}
+
+/// Exports local events to an external broker and imports its messages as events
+#[derive(Clone)]
+pub struct ExternalBrokerBridge {
+    context: Arc<NativeFeatureContext>,
+    config: BrokerBridgeConfig,
+    adapter: Arc<dyn BrokerAdapter>,
+    status: Arc<RwLock<NativeFeatureStatus>>,
+    /// Cancelled by `stop`, replaced on every start
+    stop: Arc<Mutex<CancellationToken>>,
+    queue: Arc<ExportQueue>,
+    /// IDs of the imported messages
+    imported: Arc<Mutex<RecentIds>>,
+    counters: Arc<Counters>,
+}
+
+impl ExternalBrokerBridge {
+    pub fn new(
+        context: Arc<NativeFeatureContext>,
+        config: BrokerBridgeConfig,
+        adapter: Arc<dyn BrokerAdapter>,
+    ) -> Self {
+        let queue = Arc::new(ExportQueue::new(config.max_pending));
+        let imported = Arc::new(Mutex::new(RecentIds::new(config.dedup_capacity)));
+        Self {
+            context,
+            config,
+            adapter,
+            status: Arc::new(RwLock::new(NativeFeatureStatus::Inactive)),
+            stop: Arc::new(Mutex::new(CancellationToken::new())),
+            queue,
+            imported,
+            counters: Arc::new(Counters::default()),
+        }
+    }
+
+    /// A bridge to the broker of `config.broker`
+    pub fn from_config(
+        context: Arc<NativeFeatureContext>,
+        config: BrokerBridgeConfig,
+    ) -> BrokerResult<Self> {
+        let adapter = adapter_for(&config.broker)?;
+        Ok(Self::new(context, config, adapter))
+    }
+
+    pub fn stats(&self) -> BrokerBridgeStats {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        let counters = &self.counters;
+        BrokerBridgeStats {
+            exported: load(&counters.exported),
+            export_failures: load(&counters.export_failures),
+            dropped: load(&counters.dropped),
+            pending: self.queue.len(),
+            lagged: load(&counters.lagged),
+            imported: load(&counters.imported),
+            duplicates: load(&counters.duplicates),
+            rejected: load(&counters.rejected),
+            resubscribes: load(&counters.resubscribes),
+        }
+    }
+
+    async fn set_status(&self, status: NativeFeatureStatus) {
+        if *self.status.read().await == status {
+            return;
+        }
+        *self.status.write().await = status;
+        let _ = self.emit_status().await;
+    }
+
+    /// The imports with their parameter types parsed
+    fn imports(&self) -> FeatureResult<Vec<Arc<Import>>> {
+        if self.config.metrics_interval.is_zero() {
+            return Err(FeatureError::StartError {
+                feature: self.feature_type(),
+                message: "metrics_interval must be positive".to_string(),
+            });
+        }
+        self.config
+            .imports
+            .iter()
+            .map(|import| {
+                let schema = import
+                    .parameters
+                    .iter()
+                    .map(|(name, type_name)| {
+                        coercion::parameter_type(&TypeInfo::Simple(type_name.clone()))
+                            .map(|target| (name.clone(), target))
+                            .ok_or_else(|| FeatureError::StartError {
+                                feature: self.feature_type(),
+                                message: format!(
+                                    "Unsupported type {} of parameter {} imported from {}",
+                                    type_name, name, import.topic
+                                ),
+                            })
+                    })
+                    .collect::<FeatureResult<_>>()?;
+                Ok(Arc::new(Import {
+                    config: import.clone(),
+                    schema,
+                }))
+            })
+            .collect()
+    }
+
+    fn was_imported(&self, event: &Event) -> bool {
+        event
+            .event_id
+            .as_ref()
+            .is_some_and(|id| self.imported.lock().unwrap().contains(id))
+    }
+
+    /// Queue the local events to export, until stopped
+    async fn collect(&self, mut receiver: EventReceiver, stop: CancellationToken) {
+        loop {
+            let received = tokio::select! {
+                _ = stop.cancelled() => return,
+                received = receiver.recv() => received,
+            };
+            let event = match received {
+                Ok(event) => event,
+                Err(EventError::Lagged { count: missed }) => {
+                    warn!("Broker bridge missed {} local events", missed);
+                    count(&self.counters.lagged, missed);
+                    continue;
+                }
+                Err(e) => {
+                    debug!("Broker bridge stopped collecting events: {}", e);
+                    return;
+                }
+            };
+            // 取り込んだイベントは書き出さない
+            if self.was_imported(&event) {
+                continue;
+            }
+            let name = event.event_type.to_string();
+            for export in self.config.exports.iter().filter(|e| e.event_type == name) {
+                if !self
+                    .queue
+                    .push(export.topic.clone(), encode(&event, export.encoding))
+                {
+                    warn!(
+                        "Broker bridge dropped its oldest export, over {} pending",
+                        self.config.max_pending
+                    );
+                    count(&self.counters.dropped, 1);
+                }
+            }
+        }
+    }
+
+    /// Publish the queued exports to the broker, until stopped
+    async fn export(&self, stop: CancellationToken) {
+        loop {
+            let Some((topic, payload)) = self.queue.pop() else {
+                tokio::select! {
+                    _ = stop.cancelled() => return,
+                    _ = self.queue.added.notified() => continue,
+                }
+            };
+            // 失敗したら戻せるよう、複製を送る
+            let Err(e) = self.adapter.publish(&topic, payload.clone()).await else {
+                count(&self.counters.exported, 1);
+                continue;
+            };
+            warn!("Broker bridge failed to export to {}: {}", topic, e);
+            count(&self.counters.export_failures, 1);
+            if !self.queue.retry(topic, payload) {
+                count(&self.counters.dropped, 1);
+            }
+            tokio::select! {
+                _ = stop.cancelled() => return,
+                _ = tokio::time::sleep(self.config.reconnect_delay) => {}
+            }
+        }
+    }
+
+    /// Keep a subscription to the topic of `import` and publish its messages, until
+    /// stopped
+    async fn import(&self, import: Arc<Import>, stop: CancellationToken) {
+        let topic = &import.config.topic;
+        loop {
+            let subscribed = tokio::select! {
+                _ = stop.cancelled() => return,
+                subscribed = self.adapter.subscribe(topic) => subscribed,
+            };
+            match subscribed {
+                Ok(mut messages) => {
+                    info!("Broker bridge subscribed to {}", topic);
+                    loop {
+                        let message = tokio::select! {
+                            _ = stop.cancelled() => return,
+                            message = messages.next() => message,
+                        };
+                        match message {
+                            Some(Ok(payload)) => self.import_message(&import, &payload).await,
+                            Some(Err(BrokerError::Lagged(missed))) => {
+                                warn!("Broker bridge missed {} messages on {}", missed, topic);
+                                count(&self.counters.lagged, missed);
+                            }
+                            Some(Err(e)) => {
+                                warn!("Broker bridge subscription to {} failed: {}", topic, e);
+                                break;
+                            }
+                            None => {
+                                warn!("Broker bridge subscription to {} ended", topic);
+                                break;
+                            }
+                        }
+                    }
+                }
+                Err(e) => warn!("Broker bridge failed to subscribe to {}: {}", topic, e),
+            }
+            count(&self.counters.resubscribes, 1);
+            tokio::select! {
+                _ = stop.cancelled() => return,
+                _ = tokio::time::sleep(self.config.reconnect_delay) => {}
+            }
+        }
+    }
+
+    async fn import_message(&self, import: &Import, payload: &[u8]) {
+        match self.publish_imported(import, payload).await {
+            Ok(true) => count(&self.counters.imported, 1),
+            Ok(false) => {
+                debug!(
+                    "Broker bridge dropped a redelivered message on {}",
+                    import.config.topic
+                );
+                count(&self.counters.duplicates, 1);
+            }
+            Err(e) => {
+                warn!(
+                    "Broker bridge rejected a message on {}: {}",
+                    import.config.topic, e
+                );
+                count(&self.counters.rejected, 1);
+            }
+        }
+    }
+
+    /// Publish a message as a custom event. Returns `false` for a redelivered message.
+    async fn publish_imported(&self, import: &Import, payload: &[u8]) -> Result<bool, ImportError> {
+        let serde_json::Value::Object(mut message) = serde_json::from_slice(payload)? else {
+            return Err(ImportError::NotAnObject);
+        };
+        let event_id = match message.remove(&import.config.id_field) {
+            None | Some(serde_json::Value::Null) => None,
+            Some(serde_json::Value::String(id)) => Some(id),
+            Some(id @ serde_json::Value::Number(_)) => Some(id.to_string()),
+            Some(_) => return Err(ImportError::InvalidId(import.config.id_field.clone())),
+        };
+        let parameters =
+            coercion::coerce_parameters(&serde_json::Value::Object(message), &import.schema)?;
+        // エラーを決定的にするため、欠けたパラメータは名前順で最初のものを報告する
+        if let Some((name, target)) = import
+            .schema
+            .iter()
+            .filter(|(name, _)| matches!(parameters.get(*name), None | Some(Value::Null)))
+            .min_by_key(|(name, _)| *name)
+        {
+            return Err(CoercionError {
+                parameter: name.clone(),
+                expected: target.to_string(),
+                reason: format!("required by the import from {}", import.config.topic),
+            }
+            .into());
+        }
+
+        // 公開より先に記録し、このイベントが書き出されないようにする
+        if event_id
+            .as_ref()
+            .is_some_and(|id| !self.imported.lock().unwrap().insert(id.clone()))
+        {
+            return Ok(false);
+        }
+        let event = Event {
+            event_type: EventType::Custom(import.config.event_name.clone()),
+            parameters,
+            event_id: event_id.clone(),
+            ..Default::default()
+        };
+        if let Err(e) = self.context.event_bus.publish(event).await {
+            // 再配信を受け付けられるよう記録を戻す
+            if let Some(id) = &event_id {
+                self.imported.lock().unwrap().remove(id);
+            }
+            return Err(e.into());
+        }
+        Ok(true)
+    }
+
+    /// Publish the counters every `metrics_interval`, until stopped
+    async fn report(&self, stop: CancellationToken) {
+        let mut interval = tokio::time::interval(self.config.metrics_interval);
+        // 最初の tick はすぐに来るので読み捨てる
+        interval.tick().await;
+        loop {
+            tokio::select! {
+                _ = stop.cancelled() => return,
+                _ = interval.tick() => {}
+            }
+            if let Err(e) = self
+                .context
+                .event_bus
+                .publish(self.stats().metrics_event())
+                .await
+            {
+                debug!("Broker bridge failed to publish its metrics: {}", e);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl NativeFeature for ExternalBrokerBridge {
+    fn feature_type(&self) -> NativeFeatureType {
+        NativeFeatureType::BrokerBridge
+    }
+
+    async fn status(&self) -> NativeFeatureStatus {
+        self.status.read().await.clone()
+    }
+
+    fn publish(&self, event: Event) -> FeatureResult<()> {
+        self.context
+            .event_bus
+            .sync_publish(event)
+            .map_err(FeatureError::from)
+    }
+
+    async fn start(&self) -> FeatureResult<()> {
+        if self.status().await == NativeFeatureStatus::Active {
+            return Ok(());
+        }
+        let imports = self.imports()?;
+        let stop = CancellationToken::new();
+        *self.stop.lock().unwrap() = stop.clone();
+
+        if !self.config.exports.is_empty() {
+            // ブローカーに繋がる前のイベントも取りこぼさないよう、先に購読しておく
+            let (receiver, _) = self.context.event_bus.subscribe();
+            let bridge = self.clone();
+            let collect_stop = stop.clone();
+            self.context
+                .background_tasks
+                .spawn(async move { bridge.collect(receiver, collect_stop).await });
+
+            let bridge = self.clone();
+            let export_stop = stop.clone();
+            self.context
+                .background_tasks
+                .spawn(async move { bridge.export(export_stop).await });
+        }
+
+        for import in imports {
+            let bridge = self.clone();
+            let import_stop = stop.clone();
+            self.context
+                .background_tasks
+                .spawn(async move { bridge.import(import, import_stop).await });
+        }
+
+        let bridge = self.clone();
+        self.context
+            .background_tasks
+            .spawn(async move { bridge.report(stop).await });
+
+        self.set_status(NativeFeatureStatus::Active).await;
+        Ok(())
+    }
+
+    async fn stop(&self) -> FeatureResult<()> {
+        debug!("Broker bridge stopping");
+        self.stop.lock().unwrap().cancel();
+        self.set_status(NativeFeatureStatus::Inactive).await;
+        Ok(())
+    }
+}
+
+fn encode(event: &Event, encoding: BrokerEncoding) -> Vec<u8> {
+    match encoding {
+        BrokerEncoding::Binary => wire::encode_event(event),
+        BrokerEncoding::Json => {
+            let parameters: serde_json::Map<String, serde_json::Value> = event
+                .parameters
+                .iter()
+                .map(|(name, value)| (name.clone(), serde_json::Value::from(value)))
+                .collect();
+            serde_json::json!({
+                "event_type": event.event_type.to_string(),
+                "event_id": event.event_id,
+                "parent_event_id": event.parent_event_id,
+                "root_event_id": event.root_event_id,
+                "parameters": parameters,
+            })
+            .to_string()
+            .into_bytes()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{config::BrokerExport, event_bus::EventBus};
+
+    fn bridge(
+        config: BrokerBridgeConfig,
+    ) -> (ExternalBrokerBridge, Arc<InProcessBroker>, Arc<EventBus>) {
+        let event_bus = Arc::new(EventBus::new(64));
+        let context = Arc::new(NativeFeatureContext::new(event_bus.clone()));
+        let broker = Arc::new(InProcessBroker::new());
+        let config = BrokerBridgeConfig {
+            enabled: true,
+            reconnect_delay: Duration::from_millis(10),
+            ..config
+        };
+        let bridge = ExternalBrokerBridge::new(context, config, broker.clone());
+        (bridge, broker, event_bus)
+    }
+
+    fn orders_import() -> BrokerImport {
+        BrokerImport {
+            topic: "orders".to_string(),
+            event_name: "OrderPlaced".to_string(),
+            parameters: HashMap::from([("amount".to_string(), "Int".to_string())]),
+            id_field: "event_id".to_string(),
+        }
+    }
+
+    async fn eventually(condition: impl Fn() -> bool) {
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while !condition() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("condition not met in time");
+    }
+
+    /// The next custom event on the bus
+    async fn next_custom(events: &mut EventReceiver) -> Event {
+        tokio::time::timeout(Duration::from_secs(2), async {
+            loop {
+                let event = events.recv().await.unwrap();
+                if matches!(event.event_type, EventType::Custom(_)) {
+                    return event;
+                }
+            }
+        })
+        .await
+        .expect("no custom event in time")
+    }
+
+    async fn next_message(messages: &mut BrokerStream) -> Vec<u8> {
+        tokio::time::timeout(Duration::from_secs(2), messages.next())
+            .await
+            .expect("no message in time")
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_exports_events_to_their_topics() {
+        let (bridge, broker, event_bus) = bridge(BrokerBridgeConfig {
+            exports: vec![
+                BrokerExport {
+                    event_type: "Bump".to_string(),
+                    topic: "bumps".to_string(),
+                    encoding: BrokerEncoding::Json,
+                },
+                BrokerExport {
+                    event_type: "Reset".to_string(),
+                    topic: "resets".to_string(),
+                    encoding: BrokerEncoding::Binary,
+                },
+            ],
+            ..Default::default()
+        });
+        let mut bumps = broker.subscribe("bumps").await.unwrap();
+        let mut resets = broker.subscribe("resets").await.unwrap();
+        // 最初の書き出しは拒否され、再送される
+        broker.refuse_publishes(1);
+        bridge.start().await.unwrap();
+
+        for (name, by) in [("Ignored", 0), ("Bump", 2), ("Reset", 0)] {
+            event_bus
+                .publish(Event {
+                    event_type: EventType::Custom(name.to_string()),
+                    parameters: HashMap::from([("by".to_string(), Value::Integer(by))]),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+        }
+
+        let bump: serde_json::Value =
+            serde_json::from_slice(&next_message(&mut bumps).await).unwrap();
+        assert_eq!(bump["event_type"], "Bump");
+        assert_eq!(bump["parameters"], serde_json::json!({"by": 2}));
+        assert!(bump["event_id"].is_string());
+
+        let reset = wire::decode_event(&next_message(&mut resets).await).unwrap();
+        assert_eq!(reset.event_type, EventType::Custom("Reset".to_string()));
+        assert_eq!(reset.parameters["by"], Value::Integer(0));
+
+        let stats = bridge.stats();
+        assert_eq!(stats.exported, 2);
+        assert_eq!(stats.export_failures, 1);
+        assert_eq!(stats.pending, 0);
+        bridge.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_imports_valid_messages_once() {
+        let (bridge, broker, event_bus) = bridge(BrokerBridgeConfig {
+            imports: vec![orders_import()],
+            // 取り込んだイベントは書き出さない
+            exports: vec![BrokerExport {
+                event_type: "OrderPlaced".to_string(),
+                topic: "orders".to_string(),
+                encoding: BrokerEncoding::Json,
+            }],
+            ..Default::default()
+        });
+        let (mut events, _) = event_bus.subscribe();
+        bridge.start().await.unwrap();
+        eventually(|| broker.subscriptions() == 1).await;
+
+        let messages: [&[u8]; 6] = [
+            br#"{"event_id": "m-1", "amount": "5"}"#,
+            br#"{"event_id": "m-1", "amount": "5"}"#,
+            b"not json",
+            br#"{"event_id": "m-2"}"#,
+            br#"{"event_id": "m-3", "amount": "five"}"#,
+            br#"{"event_id": 4, "amount": 7, "note": "gift"}"#,
+        ];
+        for message in messages {
+            broker.publish("orders", message.to_vec()).await.unwrap();
+        }
+
+        let first = next_custom(&mut events).await;
+        assert_eq!(
+            first.event_type,
+            EventType::Custom("OrderPlaced".to_string())
+        );
+        assert_eq!(first.event_id.as_deref(), Some("m-1"));
+        assert_eq!(
+            first.parameters,
+            HashMap::from([("amount".to_string(), Value::Integer(5))])
+        );
+
+        let second = next_custom(&mut events).await;
+        assert_eq!(second.event_id.as_deref(), Some("4"));
+        assert_eq!(second.parameters["amount"], Value::Integer(7));
+        assert_eq!(second.parameters["note"], Value::String("gift".to_string()));
+
+        eventually(|| bridge.stats().rejected == 3).await;
+        let stats = bridge.stats();
+        assert_eq!(stats.imported, 2);
+        assert_eq!(stats.duplicates, 1);
+        assert_eq!(stats.exported, 0);
+        bridge.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resubscribes_after_the_subscription_ends() {
+        let (bridge, broker, event_bus) = bridge(BrokerBridgeConfig {
+            imports: vec![orders_import()],
+            ..Default::default()
+        });
+        let (mut events, _) = event_bus.subscribe();
+        bridge.start().await.unwrap();
+        eventually(|| broker.subscriptions() == 1).await;
+
+        broker.close_subscriptions("orders");
+        eventually(|| broker.subscriptions() == 2).await;
+        broker
+            .publish("orders", br#"{"amount": 3}"#.to_vec())
+            .await
+            .unwrap();
+
+        let event = next_custom(&mut events).await;
+        assert_eq!(event.parameters["amount"], Value::Integer(3));
+        assert_eq!(bridge.stats().resubscribes, 1);
+        bridge.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_parameter_type_fails_start() {
+        let mut import = orders_import();
+        import
+            .parameters
+            .insert("items".to_string(), "Basket".to_string());
+        let (bridge, _, _) = bridge(BrokerBridgeConfig {
+            imports: vec![import],
+            ..Default::default()
+        });
+        assert!(matches!(
+            bridge.start().await,
+            Err(FeatureError::StartError { .. })
+        ));
+    }
+}
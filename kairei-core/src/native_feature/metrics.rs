@@ -88,7 +88,7 @@ impl NativeFeature for MetricsFeature {
         let publish_interval = self.publish_interval;
         let context = self.context.clone();
 
-        tokio::spawn(async move {
+        self.context.background_tasks.spawn(async move {
             let (mut sub, _) = event_bus.subscribe();
             while running.load(Ordering::SeqCst) {
                 if let Ok(event) = sub.recv().await {
@@ -262,6 +262,7 @@ impl MetricsFeature {
                     );
                     params
                 },
+                ..Default::default()
             })
             .await
             .map_err(FeatureError::from)?;
@@ -300,7 +301,7 @@ mod tests {
 
     async fn setup_test_context() -> Arc<NativeFeatureContext> {
         let event_bus = Arc::new(EventBus::new(100));
-        Arc::new(NativeFeatureContext { event_bus })
+        Arc::new(NativeFeatureContext::new(event_bus))
     }
 
     #[tokio::test]
@@ -369,6 +370,7 @@ mod tests {
                     );
                     params
                 },
+                ..Default::default()
             })
             .await
             .unwrap();
@@ -385,6 +387,7 @@ mod tests {
                     request_id: request_id.clone(),
                 },
                 parameters: HashMap::new(),
+                ..Default::default()
             })
             .unwrap();
 
@@ -469,6 +472,7 @@ mod tests {
                 .sync_publish(Event {
                     event_type: EventType::Tick,
                     parameters: HashMap::new(),
+                    ..Default::default()
                 })
                 .unwrap();
         }
@@ -3,12 +3,16 @@
 // Main Components:
 // System Events:
 // - Tick: System heartbeat (Native implementation)
+// - Scheduled events: `schedule` declarations of the world (world_scheduler)
 // System State:
 // - Memory usage
 // - Number of agents
 // - Event queue status
 // - Plugin state management
+pub mod broker_bridge;
 pub mod metrics;
 pub mod native_registry;
+pub mod remote_bridge;
 pub mod ticker;
 pub mod types;
+pub mod world_scheduler;
@@ -3,7 +3,9 @@ use std::{collections::HashMap, sync::Arc};
 use tokio::{sync::RwLock, time::timeout};
 use tracing::{error, info};
 
+use super::broker_bridge::ExternalBrokerBridge;
 use super::metrics::MetricsFeature;
+use super::remote_bridge::RemoteEventBridge;
 use super::types::{
     FeatureError, FeatureResult, NativeFeature, NativeFeatureContext, NativeFeatureType,
 };
@@ -127,6 +129,28 @@ impl NativeFeatureRegistry {
         {
             res.push(NativeFeatureType::Metrics)
         }
+        if self
+            .config
+            .read()
+            .await
+            .remote_bridge
+            .clone()
+            .unwrap_or_default()
+            .enabled
+        {
+            res.push(NativeFeatureType::RemoteBridge)
+        }
+        if self
+            .config
+            .read()
+            .await
+            .broker_bridge
+            .clone()
+            .unwrap_or_default()
+            .enabled
+        {
+            res.push(NativeFeatureType::BrokerBridge)
+        }
         res
     }
 
@@ -147,6 +171,35 @@ impl NativeFeatureRegistry {
                     metrics_config,
                 )))
             }
+            NativeFeatureType::RemoteBridge => {
+                let bridge_config = self
+                    .config
+                    .read()
+                    .await
+                    .clone()
+                    .remote_bridge
+                    .unwrap_or_default();
+                Some(Arc::new(RemoteEventBridge::new(
+                    self.context.clone(),
+                    bridge_config,
+                )))
+            }
+            NativeFeatureType::BrokerBridge => {
+                let bridge_config = self
+                    .config
+                    .read()
+                    .await
+                    .clone()
+                    .broker_bridge
+                    .unwrap_or_default();
+                match ExternalBrokerBridge::from_config(self.context.clone(), bridge_config) {
+                    Ok(bridge) => Some(Arc::new(bridge)),
+                    Err(e) => {
+                        error!("Failed to create the broker bridge: {}", e);
+                        None
+                    }
+                }
+            }
             _ => None,
         }
     }
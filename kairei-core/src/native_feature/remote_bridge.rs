@@ -0,0 +1,648 @@
+//! # Remote Event Bridge
+//!
+//! Forwards selected events between the event buses of two Systems, typically in
+//! separate processes, over an [`EventTransport`].
+//!
+//! With `listen`, the bridge accepts a peer and publishes the events it receives on the
+//! local bus. With `peer`, it dials that peer and forwards the local events whose type
+//! is listed in `event_types`. A bridge may do both; run one on each side to forward
+//! in both directions.
+//!
+//! Delivery is at least once:
+//!
+//! * every forwarded event is numbered and kept until the peer acknowledges it;
+//! * after a reconnect, the unacknowledged events are sent again;
+//! * the receiving side remembers the IDs of recent events and drops redeliveries. It
+//!   never forwards an event it received back to the peer.
+//!
+//! The dialing side sends heartbeats, which the listening side answers. A connection
+//! silent for `heartbeat_timeout` is dropped and redialled after `reconnect_delay`.
+//! With a `token`, the listening side refuses peers that do not present the same token.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use thiserror::Error;
+use tokio::{
+    sync::{Notify, RwLock},
+    time::Instant,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use super::types::{
+    FeatureError, FeatureResult, NativeFeature, NativeFeatureContext, NativeFeatureStatus,
+    NativeFeatureType,
+};
+use crate::{
+    config::RemoteBridgeConfig,
+    event::{
+        transport::{
+            EventConnection, EventListener, EventTransport, Frame, TcpEventListener, TcpTransport,
+            TransportError,
+        },
+        wire::WIRE_VERSION,
+    },
+    event_bus::{Event, EventError, EventReceiver},
+};
+
+/// Why a connection to or from the peer ended
+#[derive(Debug, Error)]
+enum LinkError {
+    #[error(transparent)]
+    Transport(#[from] TransportError),
+
+    #[error("Peer rejected the connection: {0}")]
+    Rejected(String),
+
+    #[error("Unexpected {0} frame")]
+    Unexpected(&'static str),
+
+    #[error("Peer closed the connection")]
+    Closed,
+
+    #[error("Peer silent for {0:?}")]
+    Silent(Duration),
+
+    #[error("Failed to publish a received event: {0}")]
+    Publish(#[from] EventError),
+}
+
+/// Forwards selected events to a peer System, and publishes the events the peer forwards
+#[derive(Clone)]
+pub struct RemoteEventBridge {
+    context: Arc<NativeFeatureContext>,
+    config: RemoteBridgeConfig,
+    status: Arc<RwLock<NativeFeatureStatus>>,
+    /// Cancelled by `stop`, replaced on every start
+    stop: Arc<Mutex<CancellationToken>>,
+    outbox: Arc<Outbox>,
+    /// IDs of the events received from the peer
+    received: Arc<Mutex<RecentIds>>,
+}
+
+impl RemoteEventBridge {
+    pub fn new(context: Arc<NativeFeatureContext>, config: RemoteBridgeConfig) -> Self {
+        let outbox = Arc::new(Outbox::new(config.max_pending));
+        let received = Arc::new(Mutex::new(RecentIds::new(config.dedup_capacity)));
+        Self {
+            context,
+            config,
+            status: Arc::new(RwLock::new(NativeFeatureStatus::Inactive)),
+            stop: Arc::new(Mutex::new(CancellationToken::new())),
+            outbox,
+            received,
+        }
+    }
+
+    async fn set_status(&self, status: NativeFeatureStatus) {
+        if *self.status.read().await == status {
+            return;
+        }
+        *self.status.write().await = status;
+        let _ = self.emit_status().await;
+    }
+
+    fn validate(&self) -> FeatureResult<()> {
+        let message = if self.config.listen.is_none() && self.config.peer.is_none() {
+            "neither listen nor peer is set"
+        } else if self.config.heartbeat_interval.is_zero()
+            || self.config.heartbeat_interval >= self.config.heartbeat_timeout
+        {
+            "heartbeat_interval must be positive and shorter than heartbeat_timeout"
+        } else {
+            return Ok(());
+        };
+        Err(FeatureError::StartError {
+            feature: self.feature_type(),
+            message: message.to_string(),
+        })
+    }
+
+    fn should_forward(&self, event: &Event) -> bool {
+        let name = event.event_type.to_string();
+        self.config.event_types.contains(&name)
+            // 相手から届いたイベントは送り返さない
+            && !event
+                .event_id
+                .as_ref()
+                .is_some_and(|id| self.received.lock().unwrap().contains(id))
+    }
+
+    /// Queue the local events to forward, until stopped
+    async fn collect(&self, mut receiver: EventReceiver, stop: CancellationToken) {
+        loop {
+            let received = tokio::select! {
+                _ = stop.cancelled() => return,
+                received = receiver.recv() => received,
+            };
+            match received {
+                Ok(event) => {
+                    if self.should_forward(&event) {
+                        self.outbox.push(event);
+                    }
+                }
+                Err(EventError::Lagged { count }) => {
+                    warn!("Remote bridge missed {} local events", count)
+                }
+                Err(e) => {
+                    debug!("Remote bridge stopped collecting events: {}", e);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Keep a connection to the peer and send it the queued events, until stopped
+    async fn forward(&self, transport: Arc<dyn EventTransport>, stop: CancellationToken) {
+        loop {
+            let connected = tokio::select! {
+                _ = stop.cancelled() => return,
+                connected = self.dial(transport.as_ref()) => connected,
+            };
+            let result = match connected {
+                Ok(connection) => {
+                    info!("Remote bridge connected to its peer");
+                    self.send_events(connection, &stop).await
+                }
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(()) => return,
+                Err(e) => warn!("Remote bridge connection to its peer failed: {}", e),
+            }
+            tokio::select! {
+                _ = stop.cancelled() => return,
+                _ = tokio::time::sleep(self.config.reconnect_delay) => {}
+            }
+        }
+    }
+
+    async fn dial(
+        &self,
+        transport: &dyn EventTransport,
+    ) -> Result<Box<dyn EventConnection>, LinkError> {
+        let mut connection = transport.connect().await?;
+        connection
+            .send(&Frame::Hello {
+                version: WIRE_VERSION,
+                token: self.config.token.clone(),
+            })
+            .await?;
+        match self.next_frame(connection.as_mut()).await? {
+            Frame::Welcome => Ok(connection),
+            Frame::Rejected { reason } => Err(LinkError::Rejected(reason)),
+            frame => Err(LinkError::Unexpected(frame_name(&frame))),
+        }
+    }
+
+    /// Send the queued events over `connection`. Returns `Ok` only once stopped.
+    async fn send_events(
+        &self,
+        mut connection: Box<dyn EventConnection>,
+        stop: &CancellationToken,
+    ) -> Result<(), LinkError> {
+        let mut heartbeat = tokio::time::interval(self.config.heartbeat_interval);
+        let mut last_seen = Instant::now();
+        // 新しい接続では、確認の取れていないイベントをすべて送り直す
+        let mut sent = 0;
+        loop {
+            for (sequence, event) in self.outbox.after(sent) {
+                let event = Box::new(event);
+                connection.send(&Frame::Event { sequence, event }).await?;
+                sent = sequence;
+            }
+            tokio::select! {
+                _ = stop.cancelled() => return Ok(()),
+                _ = self.outbox.added.notified() => {}
+                frame = connection.recv() => {
+                    match frame?.ok_or(LinkError::Closed)? {
+                        Frame::Ack { sequence } => self.outbox.ack(sequence),
+                        Frame::Heartbeat => {}
+                        frame => return Err(LinkError::Unexpected(frame_name(&frame))),
+                    }
+                    last_seen = Instant::now();
+                }
+                _ = heartbeat.tick() => {
+                    if last_seen.elapsed() > self.config.heartbeat_timeout {
+                        return Err(LinkError::Silent(self.config.heartbeat_timeout));
+                    }
+                    connection.send(&Frame::Heartbeat).await?;
+                }
+            }
+        }
+    }
+
+    /// Accept peers, until stopped
+    async fn accept(&self, listener: Arc<dyn EventListener>, stop: CancellationToken) {
+        loop {
+            let accepted = tokio::select! {
+                _ = stop.cancelled() => return,
+                accepted = listener.accept() => accepted,
+            };
+            match accepted {
+                Ok(connection) => {
+                    let bridge = self.clone();
+                    let stop = stop.clone();
+                    self.context.background_tasks.spawn(async move {
+                        if let Err(e) = bridge.receive_events(connection, &stop).await {
+                            warn!("Remote bridge connection from its peer failed: {}", e);
+                        }
+                    });
+                }
+                Err(e) => {
+                    warn!("Remote bridge failed to accept a peer: {}", e);
+                    tokio::time::sleep(self.config.reconnect_delay).await;
+                }
+            }
+        }
+    }
+
+    /// Check the peer's hello, then publish the events it sends. Returns `Ok` only once
+    /// stopped.
+    async fn receive_events(
+        &self,
+        mut connection: Box<dyn EventConnection>,
+        stop: &CancellationToken,
+    ) -> Result<(), LinkError> {
+        let hello = tokio::select! {
+            _ = stop.cancelled() => return Ok(()),
+            frame = self.next_frame(connection.as_mut()) => frame?,
+        };
+        let refusal = match hello {
+            Frame::Hello { version, .. } if version != WIRE_VERSION => {
+                Some(format!("unsupported protocol version {}", version))
+            }
+            Frame::Hello { token, .. } => (self.config.token.is_some()
+                && token != self.config.token)
+                .then(|| "invalid token".to_string()),
+            frame => return Err(LinkError::Unexpected(frame_name(&frame))),
+        };
+        if let Some(reason) = refusal {
+            connection
+                .send(&Frame::Rejected {
+                    reason: reason.clone(),
+                })
+                .await?;
+            return Err(LinkError::Rejected(reason));
+        }
+        connection.send(&Frame::Welcome).await?;
+        info!("Remote bridge accepted a peer");
+
+        loop {
+            let frame = tokio::select! {
+                _ = stop.cancelled() => return Ok(()),
+                frame = self.next_frame(connection.as_mut()) => frame?,
+            };
+            match frame {
+                Frame::Event { sequence, event } => {
+                    self.publish_received(*event).await?;
+                    connection.send(&Frame::Ack { sequence }).await?;
+                }
+                Frame::Heartbeat => connection.send(&Frame::Heartbeat).await?,
+                frame => return Err(LinkError::Unexpected(frame_name(&frame))),
+            }
+        }
+    }
+
+    /// Publish an event from the peer on the local bus, unless it was received before
+    async fn publish_received(&self, event: Event) -> Result<(), LinkError> {
+        let event_id = event.event_id.clone();
+        // 公開より先に記録し、このイベントが送り返されないようにする
+        if event_id
+            .as_ref()
+            .is_some_and(|id| !self.received.lock().unwrap().insert(id.clone()))
+        {
+            debug!("Remote bridge dropped redelivered event {:?}", event_id);
+            return Ok(());
+        }
+        if let Err(e) = self.context.event_bus.publish(event).await {
+            // 確認を返さないので、再送を受け付けられるよう記録を戻す
+            if let Some(id) = &event_id {
+                self.received.lock().unwrap().remove(id);
+            }
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    /// The next frame, failing when the peer stays silent for `heartbeat_timeout`
+    async fn next_frame(&self, connection: &mut dyn EventConnection) -> Result<Frame, LinkError> {
+        match tokio::time::timeout(self.config.heartbeat_timeout, connection.recv()).await {
+            Ok(frame) => frame?.ok_or(LinkError::Closed),
+            Err(_) => Err(LinkError::Silent(self.config.heartbeat_timeout)),
+        }
+    }
+}
+
+#[async_trait]
+impl NativeFeature for RemoteEventBridge {
+    fn feature_type(&self) -> NativeFeatureType {
+        NativeFeatureType::RemoteBridge
+    }
+
+    async fn status(&self) -> NativeFeatureStatus {
+        self.status.read().await.clone()
+    }
+
+    fn publish(&self, event: Event) -> FeatureResult<()> {
+        self.context
+            .event_bus
+            .sync_publish(event)
+            .map_err(FeatureError::from)
+    }
+
+    async fn start(&self) -> FeatureResult<()> {
+        if self.status().await == NativeFeatureStatus::Active {
+            return Ok(());
+        }
+        self.validate()?;
+        let stop = CancellationToken::new();
+        *self.stop.lock().unwrap() = stop.clone();
+
+        if let Some(addr) = &self.config.listen {
+            let listener =
+                TcpEventListener::bind(addr)
+                    .await
+                    .map_err(|e| FeatureError::StartError {
+                        feature: self.feature_type(),
+                        message: format!("Failed to listen on {}: {}", addr, e),
+                    })?;
+            info!("Remote bridge listening on {}", addr);
+            let bridge = self.clone();
+            let stop = stop.clone();
+            self.context
+                .background_tasks
+                .spawn(async move { bridge.accept(Arc::new(listener), stop).await });
+        }
+
+        if let Some(peer) = &self.config.peer {
+            // 接続を待つ間のイベントも取りこぼさないよう、先に購読しておく
+            let (receiver, _) = self.context.event_bus.subscribe();
+            let bridge = self.clone();
+            let collect_stop = stop.clone();
+            self.context
+                .background_tasks
+                .spawn(async move { bridge.collect(receiver, collect_stop).await });
+
+            let bridge = self.clone();
+            let transport = Arc::new(TcpTransport::new(peer.clone()));
+            self.context
+                .background_tasks
+                .spawn(async move { bridge.forward(transport, stop).await });
+        }
+
+        self.set_status(NativeFeatureStatus::Active).await;
+        Ok(())
+    }
+
+    async fn stop(&self) -> FeatureResult<()> {
+        debug!("Remote bridge stopping");
+        self.stop.lock().unwrap().cancel();
+        self.set_status(NativeFeatureStatus::Inactive).await;
+        Ok(())
+    }
+}
+
+fn frame_name(frame: &Frame) -> &'static str {
+    match frame {
+        Frame::Hello { .. } => "hello",
+        Frame::Welcome => "welcome",
+        Frame::Rejected { .. } => "rejected",
+        Frame::Event { .. } => "event",
+        Frame::Ack { .. } => "ack",
+        Frame::Heartbeat => "heartbeat",
+    }
+}
+
+/// Forwarded events waiting for the peer's acknowledgement, in sequence order
+struct Outbox {
+    queue: Mutex<OutboxQueue>,
+    /// Notified when an event is queued
+    added: Notify,
+}
+
+struct OutboxQueue {
+    capacity: usize,
+    last_sequence: u64,
+    events: VecDeque<(u64, Event)>,
+}
+
+impl Outbox {
+    fn new(capacity: usize) -> Self {
+        Self {
+            queue: Mutex::new(OutboxQueue {
+                capacity,
+                last_sequence: 0,
+                events: VecDeque::new(),
+            }),
+            added: Notify::new(),
+        }
+    }
+
+    fn push(&self, event: Event) {
+        {
+            let mut queue = self.queue.lock().unwrap();
+            queue.last_sequence += 1;
+            let sequence = queue.last_sequence;
+            queue.events.push_back((sequence, event));
+            if queue.events.len() > queue.capacity {
+                queue.events.pop_front();
+                warn!(
+                    "Remote bridge dropped its oldest unacknowledged event, over {} pending",
+                    queue.capacity
+                );
+            }
+        }
+        self.added.notify_one();
+    }
+
+    /// Queued events numbered after `sequence`
+    fn after(&self, sequence: u64) -> Vec<(u64, Event)> {
+        self.queue
+            .lock()
+            .unwrap()
+            .events
+            .iter()
+            .filter(|(queued, _)| *queued > sequence)
+            .cloned()
+            .collect()
+    }
+
+    /// Drop the events up to and including `sequence`
+    fn ack(&self, sequence: u64) {
+        let mut queue = self.queue.lock().unwrap();
+        while queue
+            .events
+            .front()
+            .is_some_and(|(queued, _)| *queued <= sequence)
+        {
+            queue.events.pop_front();
+        }
+    }
+}
+
+/// The most recently inserted IDs, up to a capacity
+pub(super) struct RecentIds {
+    capacity: usize,
+    ids: HashSet<String>,
+    order: VecDeque<String>,
+}
+
+impl RecentIds {
+    pub(super) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ids: HashSet::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    pub(super) fn contains(&self, id: &str) -> bool {
+        self.ids.contains(id)
+    }
+
+    /// Returns `false` if `id` is already present
+    pub(super) fn insert(&mut self, id: String) -> bool {
+        if !self.ids.insert(id.clone()) {
+            return false;
+        }
+        self.order.push_back(id);
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    pub(super) fn remove(&mut self, id: &str) {
+        if self.ids.remove(id) {
+            self.order.retain(|other| other != id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{event_bus::EventBus, event_registry::EventType};
+
+    fn listening_bridge(token: Option<&str>) -> (RemoteEventBridge, Arc<EventBus>) {
+        let event_bus = Arc::new(EventBus::new(16));
+        let context = Arc::new(NativeFeatureContext::new(event_bus.clone()));
+        let bridge = RemoteEventBridge::new(
+            context,
+            RemoteBridgeConfig {
+                enabled: true,
+                listen: Some("127.0.0.1:0".to_string()),
+                event_types: vec!["Bump".to_string()],
+                token: token.map(str::to_string),
+                ..Default::default()
+            },
+        );
+        (bridge, event_bus)
+    }
+
+    /// Serve one connection from a listener bound on an ephemeral port
+    async fn serve_one(bridge: &RemoteEventBridge) -> TcpTransport {
+        let listener = TcpEventListener::bind("127.0.0.1:0").await.unwrap();
+        let transport = TcpTransport::new(listener.local_addr().unwrap().to_string());
+        let bridge = bridge.clone();
+        tokio::spawn(async move {
+            let connection = listener.accept().await.unwrap();
+            let _ = bridge
+                .receive_events(connection, &CancellationToken::new())
+                .await;
+        });
+        transport
+    }
+
+    #[tokio::test]
+    async fn test_wrong_token_is_rejected() {
+        let (bridge, _) = listening_bridge(Some("secret"));
+        let transport = serve_one(&bridge).await;
+
+        let mut connection = transport.connect().await.unwrap();
+        connection
+            .send(&Frame::Hello {
+                version: WIRE_VERSION,
+                token: Some("guess".to_string()),
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            connection.recv().await.unwrap(),
+            Some(Frame::Rejected {
+                reason: "invalid token".to_string()
+            })
+        );
+        assert_eq!(connection.recv().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_redelivered_events_are_published_once() {
+        let (bridge, event_bus) = listening_bridge(Some("secret"));
+        let (mut events, _) = event_bus.subscribe();
+        let transport = serve_one(&bridge).await;
+
+        let mut connection = transport.connect().await.unwrap();
+        connection
+            .send(&Frame::Hello {
+                version: WIRE_VERSION,
+                token: Some("secret".to_string()),
+            })
+            .await
+            .unwrap();
+        assert_eq!(connection.recv().await.unwrap(), Some(Frame::Welcome));
+
+        let event = Event {
+            event_type: EventType::Custom("Bump".to_string()),
+            event_id: Some("event-1".to_string()),
+            ..Default::default()
+        };
+        // 確認が届かなかった送り手は同じイベントを再送する
+        for sequence in [1, 2] {
+            connection
+                .send(&Frame::Event {
+                    sequence,
+                    event: Box::new(event.clone()),
+                })
+                .await
+                .unwrap();
+            assert_eq!(
+                connection.recv().await.unwrap(),
+                Some(Frame::Ack { sequence })
+            );
+        }
+
+        let published = events.recv().await.unwrap();
+        assert_eq!(published.event_id.as_deref(), Some("event-1"));
+        assert!(events.receiver.try_recv().is_err());
+        // 受け取ったイベントは相手に送り返さない
+        assert!(!bridge.should_forward(&published));
+    }
+
+    #[test]
+    fn test_outbox_keeps_unacknowledged_events() {
+        let outbox = Outbox::new(2);
+        for name in ["A", "B", "C"] {
+            outbox.push(Event {
+                event_type: EventType::Custom(name.to_string()),
+                ..Default::default()
+            });
+        }
+        // 上限を超えた最古のイベントは捨てられる
+        let sequences: Vec<_> = outbox.after(0).into_iter().map(|(s, _)| s).collect();
+        assert_eq!(sequences, vec![2, 3]);
+
+        outbox.ack(2);
+        let sequences: Vec<_> = outbox.after(0).into_iter().map(|(s, _)| s).collect();
+        assert_eq!(sequences, vec![3]);
+        assert!(outbox.after(3).is_empty());
+    }
+}
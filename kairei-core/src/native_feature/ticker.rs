@@ -133,10 +133,11 @@ impl NativeFeature for Ticker {
                 );
                 param
             },
+            ..Default::default()
         };
 
         let self_clone = self.clone();
-        tokio::spawn(async move {
+        self.context.background_tasks.spawn(async move {
             let _ = self_clone.tick(interval_timer, event).await;
             self_clone.set_status(NativeFeatureStatus::Inactive).await;
         });
@@ -162,7 +163,7 @@ mod tests {
     // テスト用のセットアップ関数
     async fn setup_test_context() -> Arc<NativeFeatureContext> {
         let event_bus = Arc::new(EventBus::new(100));
-        Arc::new(NativeFeatureContext { event_bus })
+        Arc::new(NativeFeatureContext::new(event_bus))
     }
 
     #[tokio::test]
@@ -1,6 +1,7 @@
 use std::{collections::HashMap, sync::Arc};
 
 use crate::{
+    background_tasks::BackgroundTasks,
     event_bus::{self, Event},
     event_registry::{self, EventType},
 };
@@ -16,6 +17,9 @@ pub enum NativeFeatureType {
     Ticker,
     ResourceMonitor,
     Metrics,
+    RemoteBridge,
+    BrokerBridge,
+    Scheduler,
 }
 
 #[derive(Debug, Clone, strum::Display, PartialEq)]
@@ -92,6 +96,7 @@ pub trait NativeFeature: Send + Sync {
                 );
                 hashmap
             },
+            ..Default::default()
         };
         self.publish(status_event)
     }
@@ -109,6 +114,7 @@ pub trait NativeFeature: Send + Sync {
                 );
                 hashmap
             },
+            ..Default::default()
         };
         self.publish(failure)
     }
@@ -117,11 +123,21 @@ pub trait NativeFeature: Send + Sync {
 #[derive(Clone)]
 pub struct NativeFeatureContext {
     pub event_bus: Arc<EventBus>,
+    /// Features spawn their loops here so that System shutdown stops them
+    pub background_tasks: BackgroundTasks,
 }
 
 impl NativeFeatureContext {
     pub fn new(event_bus: Arc<EventBus>) -> Self {
-        Self { event_bus }
+        Self {
+            event_bus,
+            background_tasks: BackgroundTasks::new(),
+        }
+    }
+
+    pub fn with_background_tasks(mut self, background_tasks: BackgroundTasks) -> Self {
+        self.background_tasks = background_tasks;
+        self
     }
 
     pub fn event_bus(&self) -> Arc<EventBus> {
@@ -0,0 +1,378 @@
+//! # World Schedules
+//!
+//! Fires the `schedule` declarations of the world:
+//!
+//! ```text
+//! schedule "0 2 * * *" emit cleanupRequested(scope: "all")
+//! ```
+//!
+//! Fire times are computed from the wall-clock time of a [`Clock`], so a
+//! [`MockClock`](crate::clock::MockClock) moves schedules along with the rest of
+//! the runtime. The scheduler checks the clock at each fire time and at least every
+//! `poll_interval`.
+//!
+//! A fire time found more than `misfire_threshold` in the past — because the
+//! scheduler was stopped, the host slept or the clock jumped — is missed. Missed
+//! fires are dropped or caught up with a single emission, per [`MissedFirePolicy`].
+//! A schedule never emits more than once per check.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex as StdMutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+use utoipa::ToSchema;
+
+use super::types::{
+    FeatureError, FeatureResult, NativeFeature, NativeFeatureContext, NativeFeatureStatus,
+    NativeFeatureType,
+};
+use crate::{
+    ast::{Argument, Expression, ScheduleDef},
+    clock::{Clock, SystemClock},
+    config::{MissedFirePolicy, ScheduleConfig},
+    cron::CronSchedule,
+    eval::expression::ExpressionEvaluator,
+    event_bus::{self, Event},
+    event_registry::EventType,
+};
+
+/// A schedule of the world, as listed by [`WorldScheduler::schedules`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ScheduleStatus {
+    /// The cron expression
+    pub cron: String,
+    /// Name of the emitted event
+    pub event: String,
+    /// The next time the schedule fires, if it ever does
+    pub next_fire: Option<DateTime<Utc>>,
+    /// When the schedule last emitted its event
+    pub last_fire: Option<DateTime<Utc>>,
+}
+
+struct ScheduleEntry {
+    cron: CronSchedule,
+    event: Event,
+    /// Fire times up to here are handled. Unset until the first check.
+    checked_until: Option<DateTime<Utc>>,
+    last_fire: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone)]
+pub struct WorldScheduler {
+    context: Arc<NativeFeatureContext>,
+    config: ScheduleConfig,
+    clock: Arc<dyn Clock>,
+    status: Arc<RwLock<NativeFeatureStatus>>,
+    running: Arc<AtomicBool>,
+    schedules: Arc<StdMutex<Vec<ScheduleEntry>>>,
+}
+
+impl WorldScheduler {
+    pub fn new(context: Arc<NativeFeatureContext>, config: ScheduleConfig) -> Self {
+        Self {
+            context,
+            config,
+            clock: Arc::new(SystemClock),
+            status: Arc::new(RwLock::new(NativeFeatureStatus::Inactive)),
+            running: Arc::new(AtomicBool::new(false)),
+            schedules: Arc::new(StdMutex::new(Vec::new())),
+        }
+    }
+
+    /// Compute fire times against `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Replace the schedules with those declared by the world
+    pub fn set_schedules(&self, defs: &[ScheduleDef]) -> FeatureResult<()> {
+        let mut entries = Vec::with_capacity(defs.len());
+        for def in defs {
+            entries.push(ScheduleEntry {
+                cron: CronSchedule::parse(&def.cron).map_err(|e| self.init_error(def, e))?,
+                event: scheduled_event(def).map_err(|e| self.init_error(def, e))?,
+                checked_until: None,
+                last_fire: None,
+            });
+        }
+        *self.schedules.lock().unwrap() = entries;
+        Ok(())
+    }
+
+    /// The schedules with their next fire times
+    pub fn schedules(&self) -> Vec<ScheduleStatus> {
+        let now = self.clock.utc_now();
+        self.schedules
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|entry| ScheduleStatus {
+                cron: entry.cron.to_string(),
+                event: entry.event.event_type.to_string(),
+                next_fire: entry.cron.next_after(entry.checked_until.unwrap_or(now)),
+                last_fire: entry.last_fire,
+            })
+            .collect()
+    }
+
+    /// Emit the events of the schedules that fired since the last check and
+    /// return how many were emitted. The first check only records the time.
+    pub async fn fire_due(&self) -> FeatureResult<usize> {
+        let now = self.clock.utc_now();
+        let on_time_since = now
+            - chrono::Duration::from_std(self.config.misfire_threshold)
+                .unwrap_or(chrono::Duration::zero());
+        let mut due = Vec::new();
+        {
+            let mut schedules = self.schedules.lock().unwrap();
+            for entry in schedules.iter_mut() {
+                let Some(since) = entry.checked_until.replace(now) else {
+                    continue;
+                };
+                let Some(first) = entry.cron.next_after(since) else {
+                    continue;
+                };
+                if first > now {
+                    continue;
+                }
+                let on_time = entry
+                    .cron
+                    .next_after(since.max(on_time_since))
+                    .is_some_and(|fire| fire <= now);
+                if first <= on_time_since {
+                    warn!(
+                        "Schedule '{}' of {} missed fires since {}",
+                        entry.cron, entry.event.event_type, first
+                    );
+                }
+                // 遅れた発火は fire_once なら今回の 1 回にまとめる
+                if on_time || self.config.missed_fires == MissedFirePolicy::FireOnce {
+                    entry.last_fire = Some(now);
+                    due.push(entry.event.clone());
+                }
+            }
+        }
+        let emitted = due.len();
+        for event in due {
+            self.context.event_bus.publish(event).await?;
+        }
+        Ok(emitted)
+    }
+
+    async fn set_status(&self, status: NativeFeatureStatus) {
+        if *self.status.read().await == status {
+            return;
+        }
+        *self.status.write().await = status;
+        let _ = self.emit_status().await;
+    }
+
+    /// Wait until the next fire time, or at most `poll_interval`
+    fn next_wait(&self) -> std::time::Duration {
+        let now = self.clock.utc_now();
+        self.schedules
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|entry| entry.cron.next_after(now))
+            .min()
+            .and_then(|next| (next - now).to_std().ok())
+            .map_or(self.config.poll_interval, |wait| {
+                wait.min(self.config.poll_interval)
+            })
+    }
+
+    fn init_error(&self, def: &ScheduleDef, error: impl std::fmt::Display) -> FeatureError {
+        FeatureError::InitError {
+            feature: self.feature_type(),
+            message: format!("schedule '{}' emitting {}: {}", def.cron, def.event, error),
+        }
+    }
+}
+
+/// The event of a schedule, with its literal arguments evaluated
+fn scheduled_event(def: &ScheduleDef) -> Result<Event, String> {
+    let mut parameters = HashMap::new();
+    for argument in &def.parameters {
+        let Argument::Named {
+            name,
+            value: Expression::Literal(literal),
+        } = argument
+        else {
+            return Err("arguments must be named literals".to_string());
+        };
+        let value = ExpressionEvaluator::eval_literal(literal).map_err(|e| e.to_string())?;
+        parameters.insert(name.clone(), event_bus::Value::from(value));
+    }
+    Ok(Event::new(
+        &EventType::Custom(def.event.clone()),
+        &parameters,
+    ))
+}
+
+#[async_trait]
+impl NativeFeature for WorldScheduler {
+    fn feature_type(&self) -> NativeFeatureType {
+        NativeFeatureType::Scheduler
+    }
+
+    async fn status(&self) -> NativeFeatureStatus {
+        self.status.read().await.clone()
+    }
+
+    fn publish(&self, event: Event) -> FeatureResult<()> {
+        self.context
+            .event_bus
+            .sync_publish(event)
+            .map_err(FeatureError::from)
+    }
+
+    async fn start(&self) -> FeatureResult<()> {
+        if self.schedules.lock().unwrap().is_empty() {
+            debug!("No world schedules");
+            return Ok(());
+        }
+        if self.running.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        // 停止前の確認時刻は残し、停止中の発火を取りこぼしとして扱う
+        self.fire_due().await?;
+
+        let self_clone = self.clone();
+        self.context.background_tasks.spawn(async move {
+            while self_clone.running.load(Ordering::SeqCst) {
+                tokio::time::sleep(self_clone.next_wait()).await;
+                if let Err(e) = self_clone.fire_due().await {
+                    let _ = self_clone
+                        .emit_failure(&format!("Failed to emit a scheduled event: {}", e))
+                        .await;
+                }
+            }
+        });
+        self.set_status(NativeFeatureStatus::Active).await;
+        Ok(())
+    }
+
+    async fn stop(&self) -> FeatureResult<()> {
+        self.running.store(false, Ordering::SeqCst);
+        self.set_status(NativeFeatureStatus::Inactive).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{ast::Literal, clock::MockClock, event_bus::EventBus};
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn scheduler(missed_fires: MissedFirePolicy) -> (WorldScheduler, MockClock) {
+        let clock = MockClock::starting_at(utc("2025-03-10T00:00:00Z"));
+        let context = Arc::new(NativeFeatureContext::new(Arc::new(EventBus::new(100))));
+        let config = ScheduleConfig {
+            missed_fires,
+            ..Default::default()
+        };
+        let scheduler = WorldScheduler::new(context, config).with_clock(Arc::new(clock.clone()));
+        scheduler
+            .set_schedules(&[
+                ScheduleDef {
+                    cron: "*/15 * * * *".to_string(),
+                    event: "quarterPassed".to_string(),
+                    parameters: vec![],
+                },
+                ScheduleDef {
+                    cron: "0 2 * * *".to_string(),
+                    event: "cleanupRequested".to_string(),
+                    parameters: vec![Argument::Named {
+                        name: "scope".to_string(),
+                        value: Expression::Literal(Literal::String("all".to_string())),
+                    }],
+                },
+            ])
+            .unwrap();
+        (scheduler, clock)
+    }
+
+    #[tokio::test]
+    async fn test_fires_at_each_fire_time() {
+        let (scheduler, clock) = scheduler(MissedFirePolicy::Skip);
+        let (mut receiver, _) = scheduler.context.event_bus.subscribe();
+        assert_eq!(scheduler.fire_due().await.unwrap(), 0);
+
+        let mut emitted = 0;
+        for _ in 0..8 {
+            clock.advance(Duration::from_secs(15 * 60));
+            emitted += scheduler.fire_due().await.unwrap();
+        }
+        // 15 分ごとに 8 回と、02:00 に 1 回
+        assert_eq!(emitted, 9);
+
+        let mut cleanup = None;
+        for _ in 0..emitted {
+            let event = receiver.recv().await.unwrap();
+            if event.event_type == EventType::Custom("cleanupRequested".to_string()) {
+                cleanup = Some(event);
+            }
+        }
+        assert_eq!(
+            cleanup.unwrap().parameters.get("scope"),
+            Some(&event_bus::Value::String("all".to_string()))
+        );
+
+        let schedules = scheduler.schedules();
+        assert_eq!(schedules[0].next_fire, Some(utc("2025-03-10T02:15:00Z")));
+        assert_eq!(schedules[1].last_fire, Some(utc("2025-03-10T02:00:00Z")));
+        assert_eq!(schedules[1].next_fire, Some(utc("2025-03-11T02:00:00Z")));
+    }
+
+    #[tokio::test]
+    async fn test_missed_fires_are_skipped() {
+        let (scheduler, clock) = scheduler(MissedFirePolicy::Skip);
+        scheduler.fire_due().await.unwrap();
+
+        // 00:15 から 01:00 までの 4 回を取りこぼす
+        clock.advance(Duration::from_secs(65 * 60));
+        assert_eq!(scheduler.fire_due().await.unwrap(), 0);
+
+        clock.advance(Duration::from_secs(10 * 60));
+        assert_eq!(scheduler.fire_due().await.unwrap(), 1);
+
+        // 閾値内の遅れは取りこぼしではない
+        clock.advance(Duration::from_secs(15 * 60 + 30));
+        assert_eq!(scheduler.fire_due().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_missed_fires_are_fired_once() {
+        let (scheduler, clock) = scheduler(MissedFirePolicy::FireOnce);
+        scheduler.fire_due().await.unwrap();
+
+        // 丸一日止まっていても、それぞれ 1 回だけ発火する
+        clock.advance(Duration::from_secs(24 * 60 * 60 + 5 * 60));
+        assert_eq!(scheduler.fire_due().await.unwrap(), 2);
+        assert_eq!(
+            scheduler.schedules()[1].last_fire,
+            Some(utc("2025-03-11T00:05:00Z"))
+        );
+
+        // 取りこぼしがなければ何もしない
+        clock.advance(Duration::from_secs(5 * 60));
+        assert_eq!(scheduler.fire_due().await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,435 @@
+//! # Persistence Envelopes
+//!
+//! Everything the runtime writes to a [`StorageBackend`](crate::provider::capabilities::storage::StorageBackend)
+//! and reads back in a later run — the state of suspended agents, queued requests,
+//! memory items and shared memory — is wrapped in an [`Envelope`] carrying the
+//! version of its representation:
+//!
+//! ```json
+//! { "version": 1, "payload": { "Integer": 42 } }
+//! ```
+//!
+//! Each persisted type implements [`Versioned`]. When its serde representation changes,
+//! bump [`Versioned::VERSION`] and teach [`Versioned::migrate`] to rewrite the previous
+//! version, so files written by older builds keep loading. Reading a version newer than
+//! the build knows fails with [`PersistenceError::UnsupportedVersion`] instead of a serde
+//! error. Unknown fields, e.g. added to the envelope by a newer build, are ignored.
+//!
+//! Values stored before envelopes were introduced are read as version 1.
+//!
+//! The fixtures under `tests/fixtures/persistence` were written once for each version
+//! and must never be regenerated: the tests below read them on every run.
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use thiserror::Error;
+
+use crate::{eval::expression::Value, provider::capabilities::relevant_memory::DetailedMemoryItem};
+
+#[derive(Debug, Clone, Error, PartialEq)]
+pub enum PersistenceError {
+    #[error("Unsupported {kind} version {version}, this build reads versions 1 to {current}")]
+    UnsupportedVersion {
+        kind: &'static str,
+        version: u32,
+        current: u32,
+    },
+
+    #[error("Failed to serialize {kind}: {message}")]
+    Serialization { kind: &'static str, message: String },
+
+    #[error("Failed to deserialize {kind} version {version}: {message}")]
+    Deserialization {
+        kind: &'static str,
+        version: u32,
+        message: String,
+    },
+}
+
+/// A type persisted across runs
+pub trait Versioned: Serialize + DeserializeOwned {
+    /// Name of the type in errors
+    const KIND: &'static str;
+    /// Version of the representation written by this build
+    const VERSION: u32;
+
+    /// Rewrite a payload of version `from` into version `from + 1`.
+    /// Only called for `from < VERSION`.
+    fn migrate(
+        from: u32,
+        _payload: serde_json::Value,
+    ) -> Result<serde_json::Value, PersistenceError> {
+        Err(PersistenceError::UnsupportedVersion {
+            kind: Self::KIND,
+            version: from,
+            current: Self::VERSION,
+        })
+    }
+}
+
+/// A persisted payload and the version of its representation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Envelope {
+    pub version: u32,
+    pub payload: serde_json::Value,
+}
+
+impl Envelope {
+    pub fn seal<T: Versioned>(value: &T) -> Result<Self, PersistenceError> {
+        let payload = serde_json::to_value(value).map_err(|e| PersistenceError::Serialization {
+            kind: T::KIND,
+            message: e.to_string(),
+        })?;
+        Ok(Self {
+            version: T::VERSION,
+            payload,
+        })
+    }
+
+    /// Migrate the payload to the current version of `T` and deserialize it
+    pub fn open<T: Versioned>(self) -> Result<T, PersistenceError> {
+        if self.version == 0 || self.version > T::VERSION {
+            return Err(PersistenceError::UnsupportedVersion {
+                kind: T::KIND,
+                version: self.version,
+                current: T::VERSION,
+            });
+        }
+
+        let mut payload = self.payload;
+        for from in self.version..T::VERSION {
+            payload = T::migrate(from, payload)?;
+        }
+        serde_json::from_value(payload).map_err(|e| PersistenceError::Deserialization {
+            kind: T::KIND,
+            version: self.version,
+            message: e.to_string(),
+        })
+    }
+
+    /// Read a stored JSON value. A value that is not an envelope was stored before
+    /// envelopes existed, and is read as version 1.
+    pub fn from_stored(stored: serde_json::Value) -> Self {
+        match stored {
+            serde_json::Value::Object(mut fields)
+                if fields.get("version").is_some_and(|v| v.is_u64())
+                    && fields.contains_key("payload") =>
+            {
+                let version = fields["version"].as_u64().unwrap_or_default();
+                Self {
+                    // 桁あふれするバージョンは未知のバージョンとして扱う
+                    version: u32::try_from(version).unwrap_or(u32::MAX),
+                    payload: fields.remove("payload").unwrap_or_default(),
+                }
+            }
+            legacy => Self {
+                version: 1,
+                payload: legacy,
+            },
+        }
+    }
+
+    pub fn into_stored(self) -> serde_json::Value {
+        serde_json::json!({
+            "version": self.version,
+            "payload": self.payload,
+        })
+    }
+}
+
+/// Serialize `value` in its envelope
+pub fn to_stored<T: Versioned>(value: &T) -> Result<serde_json::Value, PersistenceError> {
+    Envelope::seal(value).map(Envelope::into_stored)
+}
+
+/// Deserialize a value written by [`to_stored`] in this or an earlier version
+pub fn from_stored<T: Versioned>(stored: serde_json::Value) -> Result<T, PersistenceError> {
+    Envelope::from_stored(stored).open()
+}
+
+impl Versioned for Value {
+    const KIND: &'static str = "value";
+    const VERSION: u32 = 1;
+}
+
+impl Versioned for DetailedMemoryItem {
+    const KIND: &'static str = "memory item";
+    const VERSION: u32 = 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, time::Duration};
+
+    use super::*;
+    use crate::{
+        ast::RetryDelay,
+        event::{event_bus, event_registry::EventType, journal::JournalEntry},
+        provider::{
+            capabilities::{
+                sistence_memory::{ContentType, ItemType, RetentionPolicy},
+                storage::ValueWithMetadata,
+            },
+            plugins::memory::persistent_shared_memory::{
+                SharedMemoryValue, from_stored_entry, to_stored_entry,
+            },
+        },
+        request_queue::StoredRequest,
+    };
+
+    macro_rules! fixture {
+        ($name:literal) => {
+            serde_json::from_str::<serde_json::Value>(include_str!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/persistence/",
+                $name
+            )))
+            .unwrap()
+        };
+    }
+
+    #[test]
+    fn test_value_v1_fixture() {
+        let stored = fixture!("value_v1.json");
+        let value: Value = from_stored(stored.clone()).unwrap();
+
+        let Value::Map(fields) = &value else {
+            panic!("fixture should hold a map");
+        };
+        assert_eq!(fields["count"], Value::Integer(3));
+        assert_eq!(fields["limit"], Value::UInteger(10));
+        assert_eq!(fields["ratio"], Value::Float(0.5));
+        assert_eq!(fields["city"], Value::String("Tokyo".to_string()));
+        assert_eq!(fields["enabled"], Value::Boolean(true));
+        assert_eq!(
+            fields["tags"],
+            Value::List(vec![
+                Value::String("weather".to_string()),
+                Value::Integer(1)
+            ])
+        );
+        assert_eq!(fields["timeout"], Value::Duration(Duration::from_secs(30)));
+        assert_eq!(
+            fields["backoff"],
+            Value::Delay(RetryDelay::Exponential {
+                initial: 100,
+                max: 1000
+            })
+        );
+        assert_eq!(
+            fields["pair"],
+            Value::Tuple(vec![Value::Integer(1), Value::Null])
+        );
+        assert_eq!(fields["unit"], Value::Unit);
+        assert_eq!(fields["error"], Value::Error("Timeout".to_string()));
+        assert_eq!(
+            fields["answer"],
+            Value::Ok(Box::new(Value::String("sunny".to_string())))
+        );
+        assert_eq!(
+            fields["failure"],
+            Value::Err(Box::new(Value::String("boom".to_string())))
+        );
+
+        // 現在のビルドも同じ表現で書く
+        assert_eq!(to_stored(&value).unwrap(), stored);
+    }
+
+    #[test]
+    fn test_queued_request_v1_fixture() {
+        let stored = fixture!("queued_request_v1.json");
+        let request: StoredRequest = from_stored(stored.clone()).unwrap();
+
+        assert_eq!(request.request_type, "GetForecast");
+        assert_eq!(request.requester, "Orchestrator");
+        assert_eq!(request.responder, "WeatherAgent");
+        assert_eq!(request.request_id, "req-1");
+        assert_eq!(
+            request.parameters["city"],
+            Value::String("Tokyo".to_string())
+        );
+        assert_eq!(request.parameters["days"], Value::Integer(3));
+        assert_eq!(request.event_id.as_deref(), Some("event-2"));
+        assert_eq!(request.parent_event_id.as_deref(), Some("event-1"));
+        assert_eq!(request.root_event_id.as_deref(), Some("event-1"));
+
+        assert_eq!(to_stored(&request).unwrap(), stored);
+    }
+
+    #[test]
+    fn test_journal_entry_v1_fixture() {
+        let stored = fixture!("journal_entry_v1.json");
+        let entry: JournalEntry = from_stored(stored.clone()).unwrap();
+
+        assert_eq!(entry.sequence, 7);
+        assert_eq!(to_stored(&entry).unwrap(), stored);
+
+        let event = entry.into_event();
+        assert_eq!(
+            event.event_type,
+            EventType::Custom("OrderPlaced".to_string())
+        );
+        assert_eq!(
+            event.parameters["city"],
+            event_bus::Value::String("Tokyo".to_string())
+        );
+        assert_eq!(
+            event.parameters["items"],
+            event_bus::Value::List(vec![
+                event_bus::Value::Integer(2),
+                event_bus::Value::Float(0.5)
+            ])
+        );
+        assert_eq!(
+            event.parameters["timeout"],
+            event_bus::Value::Duration(Duration::from_secs(30))
+        );
+        assert_eq!(event.parameters["note"], event_bus::Value::Null);
+        assert_eq!(event.event_id.as_deref(), Some("event-2"));
+        assert_eq!(event.parent_event_id.as_deref(), Some("event-1"));
+        assert_eq!(event.root_event_id.as_deref(), Some("event-1"));
+    }
+
+    #[test]
+    fn test_memory_item_v1_fixture() {
+        let stored = fixture!("memory_item_v1.json");
+        let item: DetailedMemoryItem = from_stored(stored.clone()).unwrap();
+
+        assert_eq!(item.id, "memory-1");
+        assert_eq!(item.content, "Tokyo is sunny in spring");
+        assert_eq!(item.content_type, ContentType::Text);
+        assert_eq!(item.item_type, ItemType::Knowledge);
+        assert_eq!(item.topics, vec!["weather".to_string()]);
+        assert_eq!(item.tags["city"], "Tokyo");
+        assert_eq!(item.source.source_id, "WeatherAgent");
+        assert_eq!(item.references[0].ref_id, "memory-0");
+        assert_eq!(item.importance.base_score, 0.5);
+        assert_eq!(item.access_stats.access_count, 2);
+        assert_eq!(item.ttl, Some(Duration::from_secs(3600)));
+        assert_eq!(item.retention_policy, RetentionPolicy::Important);
+        assert!(item.attachments.is_empty());
+
+        assert_eq!(to_stored(&item).unwrap(), stored);
+
+        // 添付は項目と一緒に保存される
+        let mut item = item;
+        item.attachments.insert(
+            "source".to_string(),
+            serde_json::json!({"url": "https://example.com/tokyo", "page": 3}),
+        );
+        let reloaded: DetailedMemoryItem = from_stored(to_stored(&item).unwrap()).unwrap();
+        assert_eq!(reloaded.attachments, item.attachments);
+    }
+
+    #[test]
+    fn test_shared_memory_entry_v1_fixture() {
+        let stored = fixture!("shared_memory_entry_v1.json");
+        let entry: ValueWithMetadata = serde_json::from_value(stored.clone()).unwrap();
+        let entry = from_stored_entry(entry).unwrap();
+
+        assert_eq!(
+            entry.value,
+            serde_json::json!({"name": "Alice", "visits": 3})
+        );
+        assert_eq!(entry.metadata.size, 27);
+        assert_eq!(entry.metadata.tags["owner"], "Reception");
+
+        let restored = to_stored_entry(&entry).unwrap();
+        assert_eq!(serde_json::to_value(restored).unwrap(), stored);
+    }
+
+    #[test]
+    fn test_unknown_shared_memory_version_is_rejected() {
+        let stored = serde_json::json!({
+            "version": 2,
+            "payload": { "name": "Alice" },
+        });
+        assert_eq!(
+            from_stored::<SharedMemoryValue>(stored),
+            Err(PersistenceError::UnsupportedVersion {
+                kind: "shared memory value",
+                version: 2,
+                current: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn test_legacy_value_without_envelope() {
+        let value: Value = from_stored(fixture!("value_unversioned.json")).unwrap();
+        assert_eq!(
+            value,
+            Value::Map(HashMap::from([(
+                "city".to_string(),
+                Value::String("Tokyo".to_string())
+            )]))
+        );
+    }
+
+    #[test]
+    fn test_unknown_version_is_rejected() {
+        let stored = serde_json::json!({
+            "version": 7,
+            "payload": { "Integer": 1 },
+            "written_by": "a newer build",
+        });
+        assert_eq!(
+            from_stored::<Value>(stored),
+            Err(PersistenceError::UnsupportedVersion {
+                kind: "value",
+                version: 7,
+                current: 1,
+            })
+        );
+
+        // 未知のフィールドは無視する
+        let stored = serde_json::json!({
+            "version": 1,
+            "payload": { "Integer": 1 },
+            "written_by": "a newer build",
+        });
+        assert_eq!(from_stored::<Value>(stored), Ok(Value::Integer(1)));
+    }
+
+    /// A note whose representation changed in version 2: `text` became `body`,
+    /// and `tags` was added
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Note {
+        body: String,
+        tags: Vec<String>,
+    }
+
+    impl Versioned for Note {
+        const KIND: &'static str = "note";
+        const VERSION: u32 = 2;
+
+        fn migrate(
+            from: u32,
+            payload: serde_json::Value,
+        ) -> Result<serde_json::Value, PersistenceError> {
+            match from {
+                1 => Ok(serde_json::json!({
+                    "body": payload["text"],
+                    "tags": [],
+                })),
+                _ => Err(PersistenceError::UnsupportedVersion {
+                    kind: Self::KIND,
+                    version: from,
+                    current: Self::VERSION,
+                }),
+            }
+        }
+    }
+
+    #[test]
+    fn test_bumped_version_migrates_v1_fixture() {
+        let note: Note = from_stored(fixture!("note_v1.json")).unwrap();
+        assert_eq!(
+            note,
+            Note {
+                body: "Remember the umbrella".to_string(),
+                tags: vec![],
+            }
+        );
+        assert_eq!(to_stored(&note).unwrap()["version"], 2);
+    }
+}
@@ -0,0 +1,658 @@
+//! # Preflight
+//!
+//! [`System::preflight`] checks a deployment, a system config with its secrets and
+//! DSL sources, with the validators the system runs when it starts, but without
+//! creating the system: no agent is spawned and no event bus is started. A failing
+//! check does not stop the others, so one call reports everything that would keep
+//! the deployment from starting, grouped by [`PreflightComponent`]:
+//!
+//! | component     | checks                                                                  |
+//! |---------------|-------------------------------------------------------------------------|
+//! | `config`      | providers named by the config, agents named by per-agent settings, and `agent_configs` against the `config` blocks of the agents |
+//! | `secrets`     | a secret with an API key for every provider, the handler secrets granted, and secrets leaked into the config |
+//! | `dsl`         | parsing and type checking of the sources, secrets leaked into them, agent labels and event references |
+//! | `providers`   | the validators of each provider config, and the capabilities of each provider; plugins a provider ignores are warned about |
+//! | `plugins`     | the plugin configs of each provider, and `shared_counters`              |
+//! | `schemas`     | the custom events of the world, registered in an empty event registry   |
+//! | `credentials` | provider credentials against their APIs, when `validate_credentials.enabled` is set |
+//!
+//! The sources are joined in order, as the [`DslWatcher`](crate::dsl_watcher::DslWatcher)
+//! joins DSL files, and parsed once; their diagnostics are located in the source
+//! they come from. When they do not parse, the checks that need the agents are
+//! skipped and `schemas` is reported as not checked.
+//!
+//! Components are reported in the order above and their diagnostics sorted, errors
+//! first, so that two runs over the same deployment give equal reports.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    ASTError, Root,
+    ast_registry::AstRegistry,
+    config::{EventValidationMode, PluginConfig, ProviderConfig, SecretConfig, SystemConfig},
+    config_values,
+    event_bus::EventBus,
+    event_registry::EventRegistry,
+    message_catalog::MessageCatalog,
+    provider::{
+        config::plugins::ProviderSpecificConfig, middleware::redaction::PatternRedactor,
+        provider::ProviderType, provider_registry::ProviderRegistry,
+        provider_secret::SecretRegistry, types::CredentialStatus,
+    },
+    secret_scan::SecretFinding,
+    system::{
+        System, register_custom_event, scan_for_secrets, unregistered_event_references,
+        validate_labels,
+    },
+};
+
+/// A DSL file of a deployment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct PreflightSource {
+    /// Name diagnostics are located by, e.g. the path of the file
+    pub name: String,
+    pub dsl: String,
+}
+
+impl PreflightSource {
+    pub fn new(name: impl Into<String>, dsl: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            dsl: dsl.into(),
+        }
+    }
+}
+
+/// The group of checks a diagnostic comes from, see the [module docs](self)
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum PreflightComponent {
+    Config,
+    Secrets,
+    Dsl,
+    Providers,
+    Plugins,
+    Schemas,
+    Credentials,
+}
+
+impl PreflightComponent {
+    /// Every component, in the order of the report
+    pub const ALL: [Self; 7] = [
+        Self::Config,
+        Self::Secrets,
+        Self::Dsl,
+        Self::Providers,
+        Self::Plugins,
+        Self::Schemas,
+        Self::Credentials,
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Keeps the deployment from starting
+    Error,
+    /// Worth a look, but does not keep the deployment from starting
+    Warning,
+}
+
+/// One finding of a preflight
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+pub struct PreflightDiagnostic {
+    pub severity: Severity,
+    /// What the finding is about: a provider, an agent, a config field or a
+    /// `<source>:<line>`
+    pub subject: String,
+    /// Code of the finding, when its validator has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    pub message: String,
+}
+
+/// The findings of one component
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ComponentReport {
+    pub component: PreflightComponent,
+    /// False when the checks of the component were skipped
+    pub checked: bool,
+    /// No error was found
+    pub passed: bool,
+    pub diagnostics: Vec<PreflightDiagnostic>,
+}
+
+/// The result of [`System::preflight`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct PreflightReport {
+    /// No component reported an error
+    pub passed: bool,
+    pub errors: usize,
+    pub warnings: usize,
+    pub components: Vec<ComponentReport>,
+}
+
+impl PreflightReport {
+    pub fn component(&self, component: PreflightComponent) -> &ComponentReport {
+        self.components
+            .iter()
+            .find(|report| report.component == component)
+            .expect("every component is reported")
+    }
+}
+
+/// Diagnostics collected by the checks, keyed by component
+#[derive(Default)]
+struct Findings {
+    diagnostics: BTreeMap<PreflightComponent, Vec<PreflightDiagnostic>>,
+    skipped: HashSet<PreflightComponent>,
+}
+
+impl Findings {
+    fn push(
+        &mut self,
+        component: PreflightComponent,
+        severity: Severity,
+        subject: impl Into<String>,
+        message: impl Into<String>,
+    ) -> &mut PreflightDiagnostic {
+        let diagnostics = self.diagnostics.entry(component).or_default();
+        diagnostics.push(PreflightDiagnostic {
+            severity,
+            subject: subject.into(),
+            code: None,
+            message: message.into(),
+        });
+        diagnostics.last_mut().unwrap()
+    }
+
+    fn error(
+        &mut self,
+        component: PreflightComponent,
+        subject: impl Into<String>,
+        message: impl Into<String>,
+    ) -> &mut PreflightDiagnostic {
+        self.push(component, Severity::Error, subject, message)
+    }
+
+    fn warning(
+        &mut self,
+        component: PreflightComponent,
+        subject: impl Into<String>,
+        message: impl Into<String>,
+    ) -> &mut PreflightDiagnostic {
+        self.push(component, Severity::Warning, subject, message)
+    }
+
+    /// Secret scan findings, errors unless the scan only warns
+    fn secrets(
+        &mut self,
+        component: PreflightComponent,
+        config: &SystemConfig,
+        findings: Vec<SecretFinding>,
+    ) {
+        let severity = if config.secret_scan.warn_only {
+            Severity::Warning
+        } else {
+            Severity::Error
+        };
+        for finding in findings {
+            self.push(
+                component,
+                severity,
+                finding.location.clone(),
+                format!("possible secret: {}", finding),
+            );
+        }
+    }
+
+    fn into_report(mut self) -> PreflightReport {
+        let components: Vec<ComponentReport> = PreflightComponent::ALL
+            .into_iter()
+            .map(|component| {
+                let mut diagnostics = self.diagnostics.remove(&component).unwrap_or_default();
+                diagnostics.sort();
+                diagnostics.dedup();
+                ComponentReport {
+                    component,
+                    checked: !self.skipped.contains(&component),
+                    passed: diagnostics.iter().all(|d| d.severity != Severity::Error),
+                    diagnostics,
+                }
+            })
+            .collect();
+        let count = |severity: Severity| {
+            components
+                .iter()
+                .flat_map(|report| &report.diagnostics)
+                .filter(|d| d.severity == severity)
+                .count()
+        };
+        PreflightReport {
+            passed: components.iter().all(|report| report.passed),
+            errors: count(Severity::Error),
+            warnings: count(Severity::Warning),
+            components,
+        }
+    }
+}
+
+impl System {
+    /// Checks a deployment without starting it, see the [module docs](self).
+    /// Provider credentials are checked against their APIs only when
+    /// `config.validate_credentials.enabled` is set; a provider listed in
+    /// `validate_credentials.required` without valid credentials is an error,
+    /// any other a warning.
+    pub async fn preflight(
+        config: &SystemConfig,
+        secret: &SecretConfig,
+        sources: &[PreflightSource],
+    ) -> PreflightReport {
+        let mut findings = Findings::default();
+        // 名前順に検査し、レポートを安定させる
+        let providers: BTreeMap<&String, &ProviderConfig> =
+            config.provider_configs.providers.iter().collect();
+
+        check_config(config, &providers, &mut findings);
+        check_secrets(config, secret, &providers, &mut findings);
+        let root = check_dsl(config, sources, &mut findings).await;
+        check_providers(&providers, &mut findings).await;
+        check_plugins(config, &providers, &mut findings);
+        match &root {
+            Some(root) => {
+                check_agents(config, root, &mut findings);
+                check_schemas(config, root, &mut findings);
+            }
+            None => {
+                findings.skipped.insert(PreflightComponent::Schemas);
+            }
+        }
+        if config.validate_credentials.enabled {
+            check_credentials(config, secret, &providers, &mut findings).await;
+        } else {
+            findings.skipped.insert(PreflightComponent::Credentials);
+        }
+        findings.into_report()
+    }
+}
+
+/// Providers named outside of `provider_configs`
+fn check_config(
+    config: &SystemConfig,
+    providers: &BTreeMap<&String, &ProviderConfig>,
+    findings: &mut Findings,
+) {
+    if let Some(primary) = &config.provider_configs.primary_provider {
+        if !providers.contains_key(primary) {
+            findings.error(
+                PreflightComponent::Config,
+                "provider_configs.primary_provider",
+                format!("{} is not a configured provider", primary),
+            );
+        }
+    }
+    for name in &config.validate_credentials.required {
+        if !providers.contains_key(name) {
+            findings.error(
+                PreflightComponent::Config,
+                "validate_credentials.required",
+                format!("{} is not a configured provider", name),
+            );
+        }
+    }
+}
+
+/// Agents named by per-agent settings, and the values of their `config` blocks
+fn check_agents(config: &SystemConfig, root: &Root, findings: &mut Findings) {
+    let agents: HashSet<&str> = root
+        .micro_agent_defs
+        .iter()
+        .map(|agent| agent.name.as_str())
+        .collect();
+    let settings = [
+        ("agent_configs", keys(&config.agent_configs)),
+        ("agent_logs", keys(&config.agent_logs)),
+        ("conversations", keys(&config.conversations)),
+        ("eval_limits", keys(&config.eval_limits)),
+        ("guardrails", keys(&config.guardrails)),
+        ("llm_budgets", keys(&config.llm_budgets)),
+        ("request_queue.agents", config.request_queue.agents.clone()),
+        ("secret_grants", keys(&config.secret_grants)),
+    ];
+    for (setting, names) in settings {
+        for name in names {
+            if !agents.contains(name.as_str()) {
+                findings.warning(
+                    PreflightComponent::Config,
+                    setting,
+                    format!("{} is not an agent of the DSL", name),
+                );
+            }
+        }
+    }
+
+    for agent in &root.micro_agent_defs {
+        let supplied = config
+            .agent_configs
+            .get(&agent.name)
+            .cloned()
+            .unwrap_or_default();
+        if let Err(e) = config_values::resolve(&agent.name, agent.config.as_ref(), &supplied.values)
+        {
+            findings.error(
+                PreflightComponent::Config,
+                format!("agent_configs.{}", agent.name),
+                e.to_string(),
+            );
+        }
+    }
+}
+
+fn keys<V>(map: &HashMap<String, V>) -> Vec<String> {
+    map.keys().cloned().collect()
+}
+
+/// Provider secrets, granted handler secrets and secrets leaked into the config
+fn check_secrets(
+    config: &SystemConfig,
+    secret: &SecretConfig,
+    providers: &BTreeMap<&String, &ProviderConfig>,
+    findings: &mut Findings,
+) {
+    let registry = SecretRegistry::new(secret.clone());
+    for (name, provider) in providers {
+        if let Err(e) = registry.get_secret(name) {
+            findings.error(PreflightComponent::Secrets, name.as_str(), e.to_string());
+            continue;
+        }
+        // SimpleExpert は API キーを使わない
+        let api_key = &secret.providers[name.as_str()].api_key;
+        if provider.provider_type != ProviderType::SimpleExpert && api_key.trim().is_empty() {
+            findings.error(
+                PreflightComponent::Secrets,
+                name.as_str(),
+                "the secret has no api_key",
+            );
+        }
+    }
+
+    for (agent, names) in &config.secret_grants {
+        for name in names {
+            if !secret.handler_secrets.contains_key(name) {
+                findings.error(
+                    PreflightComponent::Secrets,
+                    format!("secret_grants.{}", agent),
+                    format!("{} is not a handler secret", name),
+                );
+            }
+        }
+    }
+
+    match scan_for_secrets(config, None) {
+        Ok(leaked) => findings.secrets(PreflightComponent::Secrets, config, leaked),
+        Err(e) => {
+            findings.error(
+                PreflightComponent::Secrets,
+                "secret_scan.patterns",
+                e.to_string(),
+            );
+        }
+    }
+}
+
+/// Parses and type checks the sources; the root when they do
+async fn check_dsl(
+    config: &SystemConfig,
+    sources: &[PreflightSource],
+    findings: &mut Findings,
+) -> Option<Root> {
+    // secret_scan.patterns の誤りは secrets で報告済み
+    for source in sources {
+        if let Ok(leaked) = scan_for_secrets(config, Some((&source.dsl, &source.name))) {
+            findings.secrets(PreflightComponent::Dsl, config, leaked);
+        }
+    }
+
+    let joined = JoinedSources::new(sources);
+    let registry = AstRegistry::default().with_limits(config.analysis_limits.clone());
+    let root = match registry.create_ast_from_dsl(&joined.dsl).await {
+        Ok(root) => root,
+        Err(error) => {
+            let subject = match &error {
+                ASTError::ParseError {
+                    token_span: Some(token_span),
+                    ..
+                } => joined.locate(token_span.span.line),
+                _ => joined.name(),
+            };
+            let diagnostic = error.diagnostic();
+            findings
+                .error(
+                    PreflightComponent::Dsl,
+                    subject,
+                    MessageCatalog::new(config.locale).render(&diagnostic),
+                )
+                .code = Some(diagnostic.code.to_string());
+            return None;
+        }
+    };
+
+    for agent in &root.micro_agent_defs {
+        if let Err(e) = validate_labels(&agent.labels) {
+            findings.error(PreflightComponent::Dsl, agent.name.as_str(), e.to_string());
+        }
+    }
+    Some(root)
+}
+
+/// The sources joined as one DSL, with the line each one starts at
+struct JoinedSources {
+    dsl: String,
+    starts: Vec<(usize, String)>,
+}
+
+impl JoinedSources {
+    fn new(sources: &[PreflightSource]) -> Self {
+        let mut dsl = String::new();
+        let mut starts = Vec::new();
+        let mut line = 1;
+        for source in sources {
+            dsl.push_str(&format!("// From: {}\n", source.name));
+            starts.push((line + 1, source.name.clone()));
+            dsl.push_str(&source.dsl);
+            dsl.push_str("\n\n");
+            line = dsl.lines().count() + 1;
+        }
+        Self { dsl, starts }
+    }
+
+    /// `<source>:<line>` of a line of the joined DSL
+    fn locate(&self, line: usize) -> String {
+        match self.starts.iter().rev().find(|(start, _)| *start <= line) {
+            Some((start, name)) => format!("{}:{}", name, line - start + 1),
+            None => self.name(),
+        }
+    }
+
+    /// The name of the only source, or `dsl`
+    fn name(&self) -> String {
+        match self.starts.as_slice() {
+            [(_, name)] => name.clone(),
+            _ => "dsl".to_string(),
+        }
+    }
+}
+
+/// The validators of each provider config, and the capabilities of each provider
+async fn check_providers(providers: &BTreeMap<&String, &ProviderConfig>, findings: &mut Findings) {
+    for (name, provider) in providers {
+        let collector = ProviderRegistry::config_diagnostics(provider, &provider.provider_type);
+        for (severity, errors) in [
+            (Severity::Error, &collector.errors),
+            (Severity::Warning, &collector.warnings),
+        ] {
+            for error in errors {
+                findings
+                    .push(
+                        PreflightComponent::Providers,
+                        severity,
+                        name.as_str(),
+                        error.to_string(),
+                    )
+                    .code = Some(error.error_code());
+            }
+        }
+        if let Err(e) = ProviderRegistry::negotiate_capabilities(provider).await {
+            findings.error(PreflightComponent::Providers, name.as_str(), e.to_string());
+        }
+        let supported = ProviderRegistry::supported_plugins(provider);
+        for key in provider.plugin_configs.keys() {
+            if !supported.contains(&key.as_str()) {
+                findings.warning(
+                    PreflightComponent::Providers,
+                    name.as_str(),
+                    format!(
+                        "plugin {} is ignored by {} providers",
+                        key, provider.provider_type
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// The plugin configs of each provider, and `shared_counters`
+fn check_plugins(
+    config: &SystemConfig,
+    providers: &BTreeMap<&String, &ProviderConfig>,
+    findings: &mut Findings,
+) {
+    for (name, provider) in providers {
+        for (key, plugin) in &provider.plugin_configs {
+            if let Err(message) = check_plugin(key, plugin) {
+                findings.error(
+                    PreflightComponent::Plugins,
+                    format!("{}.plugin_configs.{}", name, key),
+                    message,
+                );
+            }
+        }
+    }
+    if let Some(shared_counters) = &config.shared_counters {
+        if let Err(e) = shared_counters.validate() {
+            findings.error(
+                PreflightComponent::Plugins,
+                "shared_counters",
+                e.to_string(),
+            );
+        }
+    }
+}
+
+fn check_plugin(key: &str, plugin: &PluginConfig) -> Result<(), String> {
+    // 作成時はキーで設定を読むため、種類の違う設定は無視される
+    let expected = match plugin {
+        PluginConfig::Memory(_) => "memory",
+        PluginConfig::Rag(_) => "rag",
+        PluginConfig::Search(_) => "web_search_serper",
+        PluginConfig::SharedMemory(_) => "shared_memory",
+        PluginConfig::PromptArchive(_) => "prompt_archive",
+        PluginConfig::Catalog(_) => "catalog",
+        PluginConfig::OpenApiTools(_) => "openapi_tools",
+        PluginConfig::SistenceMemory(_) => "sistence_memory",
+        PluginConfig::Unknown(_) => return Err("not a known plugin config".to_string()),
+    };
+    if key != expected {
+        return Err(format!(
+            "holds a {} config, which is only read from plugin_configs.{}",
+            expected, expected
+        ));
+    }
+    match plugin {
+        PluginConfig::SharedMemory(shared_memory) => {
+            shared_memory.validate().map_err(|e| e.to_string())
+        }
+        PluginConfig::PromptArchive(archive) => PatternRedactor::new(&archive.redact_patterns)
+            .map(|_| ())
+            .map_err(|e| e.to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// The custom events of the world, registered as the system registers them
+fn check_schemas(config: &SystemConfig, root: &Root, findings: &mut Findings) {
+    let mut registry = EventRegistry::new();
+    if let Some(world) = &root.world_def {
+        for event_def in &world.events.events {
+            if let Err(e) = register_custom_event(&mut registry, event_def) {
+                findings.error(
+                    PreflightComponent::Schemas,
+                    format!("events.{}", event_def.name),
+                    e.to_string(),
+                );
+            }
+        }
+    }
+
+    // 登録後のレジストリでハンドラのイベントを検証する
+    let severity = match config.event_validation {
+        EventValidationMode::Strict => Severity::Error,
+        EventValidationMode::Lenient => Severity::Warning,
+    };
+    for reference in unregistered_event_references(&registry, &root.micro_agent_defs) {
+        let agent = reference.split(' ').next().unwrap_or_default().to_string();
+        findings.push(
+            PreflightComponent::Dsl,
+            severity,
+            agent,
+            format!("unregistered event type: {}", reference),
+        );
+    }
+}
+
+/// Creates the providers and checks their credentials against their APIs
+async fn check_credentials(
+    config: &SystemConfig,
+    secret: &SecretConfig,
+    providers: &BTreeMap<&String, &ProviderConfig>,
+    findings: &mut Findings,
+) {
+    // 購読者のいないイベントバスで、イベントは捨てられる
+    let event_bus = Arc::new(EventBus::new(config.event_buffer_size));
+    let registry =
+        ProviderRegistry::new(config.provider_configs.clone(), secret.clone(), event_bus).await;
+    for (name, provider) in providers {
+        if let Err(e) = registry
+            .register_provider(name, provider.provider_type.clone())
+            .await
+        {
+            findings.error(
+                PreflightComponent::Credentials,
+                name.as_str(),
+                format!("could not be created: {}", e),
+            );
+        }
+    }
+    let statuses = registry.validate_credentials(&[]).await.unwrap_or_default();
+    for (name, status) in statuses {
+        let required = config.validate_credentials.required.contains(&name);
+        let message = match &status {
+            CredentialStatus::Valid => continue,
+            CredentialStatus::Unknown if !required => continue,
+            CredentialStatus::Unknown => "cannot check its credentials".to_string(),
+            status => format!("credentials are {}", status),
+        };
+        let severity = if required {
+            Severity::Error
+        } else {
+            Severity::Warning
+        };
+        findings.push(PreflightComponent::Credentials, severity, name, message);
+    }
+}
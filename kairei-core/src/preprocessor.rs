@@ -22,7 +22,8 @@
 //!
 //! ### Token Stream Preprocessing
 //!
-//! * **Comment Removal**: Filters out comment tokens
+//! * **Comment Removal**: Filters out comment tokens, except doc comments directly
+//!   preceding a `micro` agent, an `on` handler, a state variable or a request contract
 //! * **Whitespace Normalization**: Removes redundant whitespace tokens
 //! * **Token Simplification**: Converts `TokenSpan` to simple `Token` objects for parsing
 //!
@@ -67,7 +68,11 @@
 //! * **Tokenizer**: Receives the initial token stream from the tokenization process
 //! * **Parser**: Provides the preprocessed token stream to the parsing phase
 
-use crate::tokenizer::token::TokenSpan;
+use crate::tokenizer::{
+    keyword::Keyword,
+    symbol::Delimiter,
+    token::{Token, TokenSpan},
+};
 use regex::Regex;
 
 /// A trait for preprocessing different types of input
@@ -93,13 +98,104 @@ impl TokenPreprocessor {
 
 impl Preprocessor<Vec<TokenSpan>> for TokenPreprocessor {
     fn process(&self, input: Vec<TokenSpan>) -> Vec<TokenSpan> {
-        // Filter out comments and normalize whitespace
-        input
-            .into_iter()
-            .filter(|span| {
-                !span.token.is_comment() && !span.token.is_whitespace() && !span.token.is_newline()
-            })
-            .collect()
+        // Filter out comments and normalize whitespace, keeping doc comments
+        // only where the parser attaches them to the following item
+        let mut output = Vec::with_capacity(input.len());
+        let mut pending_docs = Vec::new();
+        let mut state_block = BlockTracker::new(Keyword::State);
+        let mut contract_block = BlockTracker::new(Keyword::Contract);
+
+        for span in input {
+            if span.token.is_whitespace() || span.token.is_newline() {
+                continue;
+            }
+            if span.token.is_doc_comment() {
+                pending_docs.push(span);
+                continue;
+            }
+            if span.token.is_comment() {
+                continue;
+            }
+
+            let documentable = match &span.token {
+                Token::Keyword(Keyword::Micro) | Token::Keyword(Keyword::On) => true,
+                Token::Identifier(_) => state_block.at_item_start(),
+                Token::Keyword(Keyword::Request | Keyword::Query | Keyword::Action) => {
+                    contract_block.at_top_level()
+                }
+                _ => false,
+            };
+            if documentable {
+                output.append(&mut pending_docs);
+            } else {
+                pending_docs.clear();
+            }
+
+            state_block.advance(&span.token);
+            contract_block.advance(&span.token);
+            output.push(span);
+        }
+        output
+    }
+}
+
+/// Tracks whether the token stream is directly inside a `keyword { ... }` block,
+/// such as the declarations of `state` or the entries of `contract`
+struct BlockTracker {
+    keyword: Keyword,
+    /// `keyword` を読んで `{` を待っている
+    awaiting_brace: bool,
+    /// ブロック内のブレースの深さ
+    depth: usize,
+    /// 直前のトークンが宣言の区切り (`{` または `;`)
+    after_separator: bool,
+}
+
+impl BlockTracker {
+    fn new(keyword: Keyword) -> Self {
+        Self {
+            keyword,
+            awaiting_brace: false,
+            depth: 0,
+            after_separator: false,
+        }
+    }
+
+    fn at_top_level(&self) -> bool {
+        self.depth == 1
+    }
+
+    /// Whether a declaration separated by `;`, such as a state variable, starts here
+    fn at_item_start(&self) -> bool {
+        self.at_top_level() && self.after_separator
+    }
+
+    fn advance(&mut self, token: &Token) {
+        match token {
+            Token::Keyword(keyword) if *keyword == self.keyword => self.awaiting_brace = true,
+            Token::Delimiter(Delimiter::OpenBrace) => {
+                if self.awaiting_brace || self.depth > 0 {
+                    self.depth += 1;
+                }
+                self.awaiting_brace = false;
+            }
+            // 集合リテラルも `}` で閉じるので深さに数える
+            Token::Delimiter(Delimiter::OpenSetBrace) => {
+                if self.depth > 0 {
+                    self.depth += 1;
+                }
+                self.awaiting_brace = false;
+            }
+            Token::Delimiter(Delimiter::CloseBrace) => {
+                self.depth = self.depth.saturating_sub(1);
+                self.awaiting_brace = false;
+            }
+            _ => self.awaiting_brace = false,
+        }
+        self.after_separator = matches!(
+            token,
+            Token::Delimiter(Delimiter::OpenBrace) | Token::Delimiter(Delimiter::Semicolon)
+        );
     }
 }
 
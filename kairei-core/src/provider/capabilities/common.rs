@@ -17,6 +17,12 @@ pub enum CapabilityType {
     PolicyPrompt,
     /// ポリシーベースの制御機能
     Policy,
+    /// リクエストコンテキスト（ユーザー、ロケール）のプロンプト反映
+    RequestContext,
+    /// JSON オブジェクトだけを返すネイティブの出力モード
+    JsonMode,
+    /// 他のエージェントとリクエストの一覧のプロンプト反映
+    Catalog,
 
     // Interaction Capabilities
     /// スレッド/会話の維持機能
@@ -36,6 +42,8 @@ pub enum CapabilityType {
     Rag,
     /// Web検索機能
     Search,
+    /// OpenAPI で定義された外部 API のツール呼び出し
+    Tools,
     /// 外部データソースとの連携
     // ExternalData,
     // Function Capabilities
@@ -24,9 +24,9 @@ use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
 use crate::provider::capabilities::sistence_memory::{
-    ContentType, EnhancedMetadata, ImportanceDistribution, ImportanceEvaluatorType, ItemType,
-    KnowledgeNode, MemoryId, RetentionPolicy, SearchContext, SearchFilters, SistenceMemoryError,
-    Source, StructuredResult, VerificationLevel,
+    ClusterParams, ClusterResult, ContentType, EnhancedMetadata, ImportanceDistribution,
+    ImportanceEvaluatorType, ItemType, KnowledgeNode, MemoryId, RelevanceFactor, RetentionPolicy,
+    SearchContext, SearchFilters, SistenceMemoryError, Source, StructuredResult, VerificationLevel,
 };
 use crate::provider::plugin::ProviderPlugin;
 
@@ -136,6 +136,10 @@ pub struct DetailedMemoryItem {
     pub topics: Vec<String>,
     /// Custom tags
     pub tags: HashMap<String, String>,
+    /// Structured data kept with the item, such as the source document. Not
+    /// indexed for search; read with `get_attachment`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub attachments: HashMap<String, serde_json::Value>,
 
     /// Source information
     pub source: Source,
@@ -380,6 +384,12 @@ pub trait RelevantMemoryCapability: ProviderPlugin + Send + Sync {
         item: DetailedMemoryItem,
     ) -> Result<MemoryId, SistenceMemoryError>;
 
+    /// Store several memory items with a single storage write
+    async fn store_memory_items(
+        &self,
+        items: Vec<DetailedMemoryItem>,
+    ) -> Result<Vec<MemoryId>, SistenceMemoryError>;
+
     /// Retrieve a specific memory item by ID with full metadata
     async fn retrieve_memory_item(
         &self,
@@ -420,6 +430,23 @@ pub trait RelevantMemoryCapability: ProviderPlugin + Send + Sync {
         min_relevance: f32,
     ) -> Result<StructuredResult, SistenceMemoryError>;
 
+    /// The items of `get_context_relevant` with their relevance and its factors
+    async fn score_context_relevance(
+        &self,
+        context: SearchContext,
+        max_items: usize,
+        min_relevance: f32,
+    ) -> Result<Vec<(DetailedMemoryItem, f32, Vec<RelevanceFactor>)>, SistenceMemoryError>;
+
+    /// Find memory items by a tag predicate, see
+    /// [`SistenceMemoryCapability::search_by_tags`](crate::provider::capabilities::sistence_memory::SistenceMemoryCapability::search_by_tags)
+    async fn search_by_tags(
+        &self,
+        include: HashMap<String, String>,
+        exclude: HashMap<String, String>,
+        match_all: bool,
+    ) -> Result<Vec<DetailedMemoryItem>, SistenceMemoryError>;
+
     // === Item Relationship Management ===
 
     /// Build a semantic graph of related items
@@ -477,6 +504,12 @@ pub trait RelevantMemoryCapability: ProviderPlugin + Send + Sync {
         enhancement_options: EnhancementOptions,
     ) -> Result<EnhancedMetadata, SistenceMemoryError>;
 
+    /// Cluster memory items by embedding similarity and extract topic labels
+    async fn cluster_memory_items(
+        &self,
+        params: ClusterParams,
+    ) -> Result<ClusterResult, SistenceMemoryError>;
+
     /// Update importance evaluation for a specific item
     async fn reevaluate_importance(
         &self,
@@ -237,6 +237,44 @@ pub trait SharedMemoryCapability: ProviderPlugin {
     /// # }
     /// ```
     async fn list_keys(&self, pattern: &str) -> Result<Vec<String>, SharedMemoryError>;
+
+    /// Atomically replace the value of a key if it still holds the expected value
+    ///
+    /// # Arguments
+    /// * `key` - The unique identifier for the value
+    /// * `expected` - The value the key must hold, or `None` if it must not exist
+    /// * `value` - The value to store
+    ///
+    /// # Returns
+    /// * `Ok(true)` - If the value was replaced
+    /// * `Ok(false)` - If the key held another value, in which case nothing changed
+    /// * `Err(SharedMemoryError)` - If storage failed
+    ///
+    /// # Notes
+    ///
+    /// - Concurrent callers swapping from the same value cannot both succeed, which
+    ///   makes this the building block for counters and locks shared by agents
+    /// - An expired key counts as absent
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use kairei_core::provider::capabilities::shared_memory::SharedMemoryCapability;
+    /// # use serde_json::json;
+    /// # async fn example(shared_memory: &impl SharedMemoryCapability) -> Result<(), Box<dyn std::error::Error>> {
+    /// // Claim the lock only if nobody holds it
+    /// if shared_memory.compare_and_swap("lock", None, json!("agent-1")).await? {
+    ///     println!("Lock acquired");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn compare_and_swap(
+        &self,
+        key: &str,
+        expected: Option<&Value>,
+        value: Value,
+    ) -> Result<bool, SharedMemoryError>;
 }
 
 /// Metadata associated with stored values in shared memory
@@ -1,9 +1,14 @@
 use std::collections::HashMap;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+use tokio::sync::mpsc;
+use utoipa::ToSchema;
+use uuid::Uuid;
 
 /// Memory item identifier type
 pub type MemoryId = String;
@@ -30,6 +35,9 @@ pub enum SistenceMemoryError {
     #[error("Invalid input: {0}")]
     InvalidInput(String),
 
+    #[error("Invalid filter: {0}")]
+    InvalidFilter(#[from] FilterError),
+
     #[error("LLM processing error: {0}")]
     LlmError(String),
 
@@ -170,8 +178,8 @@ pub struct AccessStats {
     pub access_frequency: f32,
 }
 
-/// Filters for search operations
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Filters for search operations. All given filters must match.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SearchFilters {
     /// Filter by item types
     pub item_types: Option<Vec<ItemType>>,
@@ -187,6 +195,136 @@ pub struct SearchFilters {
     pub min_importance: Option<f32>,
     /// Custom filters as key-value pairs
     pub custom_filters: Option<HashMap<String, String>>,
+    /// Filter tree for conditions the fields above cannot express
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<MemoryFilter>,
+}
+
+impl SearchFilters {
+    /// Checks the filter tree, see [`MemoryFilter::validate`]
+    pub fn validate(&self) -> Result<(), FilterError> {
+        self.filter.as_ref().map_or(Ok(()), MemoryFilter::validate)
+    }
+}
+
+/// A composable condition on memory items.
+///
+/// Serialized in snake_case with the variant as key, e.g. items tagged
+/// `project: atlas` that are not conversations:
+///
+/// ```json
+/// {"and": [
+///     {"tag_equals": {"key": "project", "value": "atlas"}},
+///     {"not": {"item_type_in": ["conversation"]}}
+/// ]}
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[schema(no_recursion)]
+pub enum MemoryFilter {
+    /// Every filter matches; must not be empty
+    And(Vec<MemoryFilter>),
+    /// At least one filter matches; must not be empty
+    Or(Vec<MemoryFilter>),
+    /// The filter does not match
+    Not(Box<MemoryFilter>),
+    /// The creation or update time is within `start..=end`; a missing bound is open
+    TimeRange {
+        #[serde(default)]
+        field: TimeField,
+        #[serde(default)]
+        start: Option<DateTime<Utc>>,
+        #[serde(default)]
+        end: Option<DateTime<Utc>>,
+    },
+    /// The item has the tag `key: value`
+    TagEquals { key: String, value: String },
+    /// The item has a tag `key` with any value
+    TagExists { key: String },
+    /// One of the item's topics is this topic
+    TopicContains(String),
+    /// The item type is one of these: `information`, `decision`, `event`,
+    /// `knowledge`, `thought`, `task`, `conversation` (in any case) or
+    /// `custom:<name>`
+    ItemTypeIn(Vec<String>),
+    /// The base importance score is at least this, between 0.0 and 1.0
+    MinImportance(f32),
+    /// Whether the item is tagged `archived: true`
+    Archived(bool),
+}
+
+/// Timestamp of a memory item read by [`MemoryFilter::TimeRange`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeField {
+    #[default]
+    Created,
+    Updated,
+}
+
+/// Why a [`MemoryFilter`] tree was rejected
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum FilterError {
+    #[error("`{0}` must contain at least one filter")]
+    EmptyGroup(&'static str),
+    #[error(
+        "Unknown item type `{0}`, expected information, decision, event, knowledge, thought, task, conversation or custom:<name>"
+    )]
+    UnknownItemType(String),
+    #[error("Time range starts at {start} after it ends at {end}")]
+    InvalidTimeRange {
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    },
+    #[error("Importance threshold {0} is not between 0.0 and 1.0")]
+    InvalidImportance(f32),
+}
+
+impl MemoryFilter {
+    /// Rejects empty `and`/`or` groups, unknown item types, time ranges that end
+    /// before they start and importance thresholds outside 0.0-1.0
+    pub fn validate(&self) -> Result<(), FilterError> {
+        match self {
+            MemoryFilter::And(filters) | MemoryFilter::Or(filters) => {
+                if filters.is_empty() {
+                    let group = if matches!(self, MemoryFilter::And(_)) {
+                        "and"
+                    } else {
+                        "or"
+                    };
+                    return Err(FilterError::EmptyGroup(group));
+                }
+                filters.iter().try_for_each(MemoryFilter::validate)
+            }
+            MemoryFilter::Not(filter) => filter.validate(),
+            MemoryFilter::TimeRange {
+                start: Some(start),
+                end: Some(end),
+                ..
+            } if start > end => Err(FilterError::InvalidTimeRange {
+                start: *start,
+                end: *end,
+            }),
+            MemoryFilter::ItemTypeIn(names) => names
+                .iter()
+                .try_for_each(|name| filter_item_type(name).map(|_| ())),
+            MemoryFilter::MinImportance(threshold) if !(0.0..=1.0).contains(threshold) => {
+                Err(FilterError::InvalidImportance(*threshold))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The item type named in [`MemoryFilter::ItemTypeIn`]
+pub fn filter_item_type(name: &str) -> Result<ItemType, FilterError> {
+    if let Some(custom) = name.strip_prefix("custom:").filter(|c| !c.is_empty()) {
+        return Ok(ItemType::Custom(custom.to_string()));
+    }
+    match parse_item_type(name) {
+        ItemType::Custom(_) => Err(FilterError::UnknownItemType(name.to_string())),
+        item_type => Ok(item_type),
+    }
 }
 
 /// Sistence profile information
@@ -301,6 +439,81 @@ pub struct KnowledgeConnection {
     pub is_outgoing: bool,
 }
 
+/// One factor of the relevance of a memory item to a context
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RelevanceFactor {
+    /// Factor name, e.g. `topic_match` or `recency`
+    pub name: String,
+    /// Score of the item for this factor (0.0-1.0)
+    pub score: f32,
+    /// Weight of the factor in the relevance
+    pub weight: f32,
+}
+
+impl RelevanceFactor {
+    pub fn new(name: impl Into<String>, score: f32, weight: f32) -> Self {
+        Self {
+            name: name.into(),
+            score,
+            weight,
+        }
+    }
+
+    /// What the factor adds to the relevance
+    pub fn contribution(&self) -> f32 {
+        self.score * self.weight
+    }
+}
+
+/// A memory item ranked for a context, with how its relevance was computed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelevanceExplanation {
+    pub item: MemoryItem,
+    /// Sum of the contributions of the factors
+    pub relevance: f32,
+    pub factors: Vec<RelevanceFactor>,
+}
+
+/// What a [`ContextPin`] does to the items retrieved for a context
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PinMode {
+    /// Always retrieved, ahead of the ranked items
+    #[default]
+    Pin,
+    /// Never retrieved
+    Exclude,
+}
+
+/// An item pinned to, or excluded from, the items retrieved for a context
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContextPin {
+    pub item_id: MemoryId,
+    pub mode: PinMode,
+    /// The pin is dropped after this time
+    pub expires_at: SystemTime,
+}
+
+/// A memory item injected into a prompt
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct InjectedMemory {
+    pub item_id: MemoryId,
+    /// Relevance of the item to the context of the prompt
+    pub relevance: f32,
+    /// Whether the item was pinned
+    pub pinned: bool,
+}
+
+/// The memory items injected into the prompts of one request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InjectedContext {
+    pub request_id: String,
+    /// When the last prompt of the request was built
+    pub recorded_at: SystemTime,
+    /// Items of every prompt of the request, in prompt order
+    pub items: Vec<InjectedMemory>,
+}
+
 /// Temporal context information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TemporalContext {
@@ -361,6 +574,9 @@ pub struct MemoryStats {
     pub avg_importance: f32,
     /// Access statistics
     pub access_stats: HashMap<String, usize>,
+    /// Pins and exclusions that have not expired
+    #[serde(default)]
+    pub context_pins: Vec<ContextPin>,
 }
 
 /// Topic distribution in memory
@@ -400,6 +616,17 @@ pub struct ImportanceDistribution {
     pub recent_changes: Vec<ItemImportanceChange>,
 }
 
+/// Result of recomputing the importance of stored items
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ImportanceRecomputeReport {
+    /// Items whose importance was evaluated
+    pub items_evaluated: usize,
+    /// Items whose score changed and were written back
+    pub items_changed: usize,
+    /// Batches the items were processed in
+    pub batches: usize,
+}
+
 /// Enhanced metadata generated by LLM
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EnhancedMetadata {
@@ -441,6 +668,373 @@ pub struct CleanupStats {
     pub duration: Duration,
 }
 
+/// How many clusters to produce when clustering memory items
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ClusterCount {
+    /// Produce exactly this many clusters (capped at the number of items)
+    Fixed(usize),
+    /// Pick the best number of clusters between 2 and `max` by silhouette score
+    Auto { max: usize },
+}
+
+impl Default for ClusterCount {
+    fn default() -> Self {
+        ClusterCount::Auto { max: 8 }
+    }
+}
+
+/// Parameters for semantic clustering of memory items
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClusterParams {
+    /// Number of clusters to produce
+    pub count: ClusterCount,
+    /// Optional filters restricting which items are clustered
+    pub filters: Option<SearchFilters>,
+    /// Generate a topic label for each cluster with the LLM
+    pub generate_labels: bool,
+    /// Maximum number of item contents sent to the LLM per cluster label
+    pub label_sample_size: Option<usize>,
+}
+
+impl Default for ClusterParams {
+    fn default() -> Self {
+        Self {
+            count: ClusterCount::default(),
+            filters: None,
+            generate_labels: true,
+            label_sample_size: None,
+        }
+    }
+}
+
+/// A group of semantically similar memory items
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryCluster {
+    /// Index of the cluster in the result
+    pub cluster_id: usize,
+    /// Topic label describing the cluster
+    pub label: String,
+    /// Items assigned to this cluster
+    pub item_ids: Vec<MemoryId>,
+    /// Average similarity of members to the cluster centroid (0.0-1.0)
+    pub cohesion: f32,
+}
+
+/// Result of a clustering operation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClusterResult {
+    /// Clusters found, ordered by size (largest first)
+    pub clusters: Vec<MemoryCluster>,
+    /// Cluster index assigned to each item
+    pub assignments: HashMap<MemoryId, usize>,
+    /// Silhouette score of the clustering (-1.0-1.0), if computable
+    pub silhouette: Option<f32>,
+}
+
+/// Field mapping used to turn each JSONL line into a memory item during import.
+/// Field names may contain dots (`meta.topics`) to reach nested objects.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImportMapping {
+    /// Field holding the item content (required on every line)
+    pub content: String,
+    /// Field holding the item ID; a new ID is generated when absent
+    pub id: Option<String>,
+    /// Field holding the item type (`knowledge`, `decision`, ...); unknown values become `Custom`
+    pub item_type: Option<String>,
+    /// Field holding a topic or a list of topics
+    pub topics: Option<String>,
+    /// Field holding an object of tags
+    pub tags: Option<String>,
+    /// Field holding the creation time (RFC 3339 or Unix seconds)
+    pub created_at: Option<String>,
+    /// Field holding the importance score (0.0-1.0)
+    pub importance: Option<String>,
+    /// Source ID recorded on every imported item
+    pub source_id: String,
+    /// Number of items written to storage at once
+    pub batch_size: usize,
+}
+
+impl Default for ImportMapping {
+    fn default() -> Self {
+        Self {
+            content: "content".to_string(),
+            id: Some("id".to_string()),
+            item_type: Some("item_type".to_string()),
+            topics: Some("topics".to_string()),
+            tags: Some("tags".to_string()),
+            created_at: Some("created_at".to_string()),
+            importance: Some("importance".to_string()),
+            source_id: "import".to_string(),
+            batch_size: 100,
+        }
+    }
+}
+
+impl ImportMapping {
+    /// Map one JSONL line to a memory item. The error describes why the line was rejected.
+    pub fn to_memory_item(&self, line: &str) -> Result<MemoryItem, String> {
+        let record: serde_json::Value =
+            serde_json::from_str(line).map_err(|e| format!("invalid JSON: {}", e))?;
+        if !record.is_object() {
+            return Err("line is not a JSON object".to_string());
+        }
+
+        let content = match self.field(&record, Some(&self.content)) {
+            Some(serde_json::Value::String(content)) if !content.trim().is_empty() => {
+                content.clone()
+            }
+            Some(serde_json::Value::String(_)) | None => {
+                return Err(format!("missing content field `{}`", self.content));
+            }
+            Some(_) => return Err(format!("content field `{}` is not a string", self.content)),
+        };
+
+        let id = match self.field(&record, self.id.as_ref()) {
+            Some(serde_json::Value::String(id)) => id.clone(),
+            Some(serde_json::Value::Number(id)) => id.to_string(),
+            Some(_) => return Err("id is not a string or number".to_string()),
+            None => Uuid::new_v4().to_string(),
+        };
+
+        let item_type = match self.field(&record, self.item_type.as_ref()) {
+            Some(serde_json::Value::String(item_type)) => parse_item_type(item_type),
+            Some(_) => return Err("item_type is not a string".to_string()),
+            None => ItemType::Information,
+        };
+
+        let topics = match self.field(&record, self.topics.as_ref()) {
+            Some(serde_json::Value::String(topic)) => vec![topic.clone()],
+            Some(serde_json::Value::Array(topics)) => topics
+                .iter()
+                .map(|topic| topic.as_str().map(str::to_string))
+                .collect::<Option<Vec<_>>>()
+                .ok_or("topics must be strings")?,
+            Some(_) => return Err("topics is not a string or list".to_string()),
+            None => Vec::new(),
+        };
+
+        let tags = match self.field(&record, self.tags.as_ref()) {
+            Some(serde_json::Value::Object(tags)) => tags
+                .iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        serde_json::Value::String(value) => value.clone(),
+                        other => other.to_string(),
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+            Some(_) => return Err("tags is not an object".to_string()),
+            None => HashMap::new(),
+        };
+
+        let now = SystemTime::now();
+        let created_at = match self.field(&record, self.created_at.as_ref()) {
+            Some(serde_json::Value::String(time)) => chrono::DateTime::parse_from_rfc3339(time)
+                .map(SystemTime::from)
+                .map_err(|e| format!("invalid created_at `{}`: {}", time, e))?,
+            Some(serde_json::Value::Number(secs)) => secs
+                .as_u64()
+                .map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+                .ok_or_else(|| format!("invalid created_at `{}`", secs))?,
+            Some(_) => return Err("created_at is not a string or number".to_string()),
+            None => now,
+        };
+
+        let score = match self.field(&record, self.importance.as_ref()) {
+            Some(serde_json::Value::Number(score)) => match score.as_f64() {
+                Some(score) if (0.0..=1.0).contains(&score) => score as f32,
+                _ => return Err(format!("importance `{}` is not between 0 and 1", score)),
+            },
+            Some(_) => return Err("importance is not a number".to_string()),
+            None => 0.5,
+        };
+
+        Ok(MemoryItem {
+            id,
+            created_at,
+            updated_at: now,
+            content,
+            content_type: ContentType::Text,
+            structured_content: Some(record.clone()),
+            item_type,
+            topics,
+            tags,
+            attachments: HashMap::new(),
+            source: Source {
+                source_type: "import".to_string(),
+                source_id: self.source_id.clone(),
+                details: None,
+                reliability: 1.0,
+            },
+            references: Vec::new(),
+            related_items: Vec::new(),
+            importance: ImportanceScore {
+                score,
+                base_score: score,
+                context_score: None,
+                reason: None,
+                evaluated_at: now,
+            },
+            last_accessed: None,
+            access_count: 0,
+            ttl: None,
+            retention_policy: RetentionPolicy::Standard,
+        })
+    }
+
+    /// Look up a (dotted) field, treating `null` as absent
+    fn field<'a>(
+        &self,
+        record: &'a serde_json::Value,
+        path: Option<&String>,
+    ) -> Option<&'a serde_json::Value> {
+        path?
+            .split('.')
+            .try_fold(record, |value, key| value.get(key))
+            .filter(|value| !value.is_null())
+    }
+}
+
+fn parse_item_type(item_type: &str) -> ItemType {
+    match item_type.to_lowercase().as_str() {
+        "information" => ItemType::Information,
+        "decision" => ItemType::Decision,
+        "event" => ItemType::Event,
+        "knowledge" => ItemType::Knowledge,
+        "thought" => ItemType::Thought,
+        "task" => ItemType::Task,
+        "conversation" => ItemType::Conversation,
+        _ => ItemType::Custom(item_type.to_string()),
+    }
+}
+
+/// Progress of a running JSONL import, reported after every stored batch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImportProgress {
+    /// Lines read so far
+    pub lines_read: usize,
+    /// Items stored so far
+    pub imported: usize,
+    /// Lines rejected so far
+    pub failed: usize,
+}
+
+/// A JSONL line that was skipped during import
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImportLineError {
+    /// 1-based line number
+    pub line: usize,
+    /// Why the line was rejected
+    pub error: String,
+}
+
+/// Result of a JSONL import
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportReport {
+    /// Number of lines read, including rejected and blank lines
+    pub lines_read: usize,
+    /// IDs of the stored items, in file order
+    pub item_ids: Vec<MemoryId>,
+    /// Lines that were skipped
+    pub errors: Vec<ImportLineError>,
+    /// Time taken for the import
+    pub duration: Duration,
+}
+
+impl ImportReport {
+    fn progress(&self) -> ImportProgress {
+        ImportProgress {
+            lines_read: self.lines_read,
+            imported: self.item_ids.len(),
+            failed: self.errors.len(),
+        }
+    }
+}
+
+/// An item of a batch operation that failed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchItemError {
+    /// 0-based position of the item in the batch
+    pub index: usize,
+    /// Why the item failed
+    pub reason: String,
+}
+
+/// Outcome of one item of a batch operation: the item's ID, or why it failed
+pub type BatchItemResult = Result<MemoryId, BatchItemError>;
+
+/// The per-item results of a batch operation, collected in any order and returned
+/// in the order of the items
+#[derive(Debug)]
+pub struct BatchResults(Vec<Option<BatchItemResult>>);
+
+impl BatchResults {
+    pub fn new(len: usize) -> Self {
+        Self((0..len).map(|_| None).collect())
+    }
+
+    /// Record the outcome of the item at `index`
+    pub fn record(&mut self, index: usize, result: Result<MemoryId, SistenceMemoryError>) {
+        self.0[index] = Some(result.map_err(|error| BatchItemError {
+            index,
+            reason: error.to_string(),
+        }));
+    }
+
+    /// Record the outcome of a write of the items at `indexes`: their IDs in the same
+    /// order, or the error that failed all of them
+    pub fn record_all(
+        &mut self,
+        indexes: Vec<usize>,
+        result: Result<Vec<MemoryId>, SistenceMemoryError>,
+    ) {
+        match result {
+            Ok(ids) => {
+                let mut ids = ids.into_iter();
+                for index in indexes {
+                    match ids.next() {
+                        Some(id) => self.record(index, Ok(id)),
+                        None => self.fail(index, "storage returned no ID for the item"),
+                    }
+                }
+            }
+            Err(error) => {
+                let reason = error.to_string();
+                for index in indexes {
+                    self.fail(index, &reason);
+                }
+            }
+        }
+    }
+
+    fn fail(&mut self, index: usize, reason: &str) {
+        self.0[index] = Some(Err(BatchItemError {
+            index,
+            reason: reason.to_string(),
+        }));
+    }
+
+    /// The results in the order of the items. An item whose outcome was never
+    /// recorded is reported as failed.
+    pub fn into_results(self) -> Vec<BatchItemResult> {
+        self.0
+            .into_iter()
+            .enumerate()
+            .map(|(index, result)| {
+                result.unwrap_or_else(|| {
+                    Err(BatchItemError {
+                        index,
+                        reason: "no outcome was recorded for the item".to_string(),
+                    })
+                })
+            })
+            .collect()
+    }
+}
+
 /// A memory item with content and metadata (public API)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MemoryItem {
@@ -464,6 +1058,10 @@ pub struct MemoryItem {
     pub topics: Vec<String>,
     /// Custom tags
     pub tags: HashMap<String, String>,
+    /// Structured data kept with the item, such as the source document. Not
+    /// indexed for search; read with `get_attachment`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub attachments: HashMap<String, serde_json::Value>,
 
     /// Source information
     pub source: Source,
@@ -485,6 +1083,32 @@ pub struct MemoryItem {
     pub retention_policy: RetentionPolicy,
 }
 
+/// Largest size of one attachment of a memory item, serialized as JSON
+pub const MAX_ATTACHMENT_BYTES: usize = 64 * 1024;
+
+impl MemoryItem {
+    /// Rejects attachments larger than [`MAX_ATTACHMENT_BYTES`]
+    pub fn check_attachments(&self) -> Result<(), SistenceMemoryError> {
+        self.attachments
+            .iter()
+            .try_for_each(|(key, value)| check_attachment(key, value))
+    }
+}
+
+/// Rejects an attachment larger than [`MAX_ATTACHMENT_BYTES`]
+pub fn check_attachment(key: &str, value: &serde_json::Value) -> Result<(), SistenceMemoryError> {
+    let size = serde_json::to_vec(value)
+        .map_err(|e| SistenceMemoryError::SerializationError(e.to_string()))?
+        .len();
+    if size > MAX_ATTACHMENT_BYTES {
+        return Err(SistenceMemoryError::InvalidInput(format!(
+            "Attachment '{}' is {} bytes, more than the limit of {} bytes",
+            key, size, MAX_ATTACHMENT_BYTES
+        )));
+    }
+    Ok(())
+}
+
 /// Memory item with contextual ranking
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RankedMemoryItem {
@@ -499,9 +1123,10 @@ pub struct RankedMemoryItem {
 }
 
 /// Policy for importance evaluation
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
 pub enum ImportancePolicy {
     /// Standard balanced evaluation
+    #[default]
     Standard,
     /// Focus on factual reliability
     FactualFocus,
@@ -513,6 +1138,18 @@ pub enum ImportancePolicy {
     Custom(String),
 }
 
+impl ImportancePolicy {
+    /// Factors the policy applies to the base and the context score
+    pub fn multipliers(&self) -> (f32, f32) {
+        match self {
+            Self::Standard | Self::Custom(_) => (1.0, 1.0),
+            Self::FactualFocus => (1.2, 0.8),
+            Self::NoveltyFocus => (1.1, 1.1),
+            Self::UtilityFocus => (0.9, 1.3),
+        }
+    }
+}
+
 /// Simple importance metrics for public API
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImportanceScore {
@@ -545,6 +1182,52 @@ pub enum ImportanceEvaluatorType {
     Combined,
 }
 
+/// Items an agent wants to hear about when they change
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum MemoryInterest {
+    /// Every item in the memory namespace
+    Namespace,
+    /// Items with the tag `key`, and the given value if any
+    Tag { key: String, value: Option<String> },
+    /// Items with the topic
+    Topic(String),
+}
+
+impl MemoryInterest {
+    pub fn matches(&self, item: &MemoryItem) -> bool {
+        match self {
+            Self::Namespace => true,
+            Self::Tag { key, value } => item
+                .tags
+                .get(key)
+                .is_some_and(|tag| value.as_ref().is_none_or(|value| tag == value)),
+            Self::Topic(topic) => item.topics.contains(topic),
+        }
+    }
+}
+
+/// Kind of change reported in a `memory_changed` event
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryChangeType {
+    Stored,
+    Updated,
+    Deleted,
+}
+
+impl MemoryChangeType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Stored => "stored",
+            Self::Updated => "updated",
+            Self::Deleted => "deleted",
+        }
+    }
+}
+
+/// Identifier of a change subscription
+pub type MemorySubscriptionId = String;
+
 /// The SistenceMemoryCapability defines the interface for the middle layer
 /// of the 3-layer memory architecture. This layer focuses on metadata enrichment,
 /// context-aware search, and connecting the fast working memory with the
@@ -575,9 +1258,126 @@ pub trait SistenceMemoryCapability: ProviderPlugin + Send + Sync {
         policy: Option<ImportancePolicy>,
     ) -> Result<ImportanceScore, SistenceMemoryError>;
 
+    /// Store several memory items, writing them to storage in one batch
+    async fn store_batch(
+        &self,
+        items: Vec<MemoryItem>,
+    ) -> Result<Vec<MemoryId>, SistenceMemoryError>;
+
+    /// Store several memory items independently: an invalid item is reported in
+    /// its result and does not keep the others from being stored. The valid items
+    /// are written in one batch.
+    async fn store_many(&self, items: Vec<MemoryItem>) -> Vec<BatchItemResult> {
+        let mut results = BatchResults::new(items.len());
+        let mut valid = Vec::with_capacity(items.len());
+        for (index, item) in items.into_iter().enumerate() {
+            match item.check_attachments() {
+                Ok(()) => valid.push((index, item)),
+                Err(error) => results.record(index, Err(error)),
+            }
+        }
+        let (indexes, valid): (Vec<_>, Vec<_>) = valid.into_iter().unzip();
+        if !valid.is_empty() {
+            results.record_all(indexes, self.store_batch(valid).await);
+        }
+        results.into_results()
+    }
+
+    /// Update several existing memory items independently, reporting per item
+    /// those that are invalid or do not exist
+    async fn update_many(&self, items: Vec<MemoryItem>) -> Vec<BatchItemResult> {
+        let mut results = BatchResults::new(items.len());
+        for (index, item) in items.into_iter().enumerate() {
+            let id = item.id.clone();
+            let updated = match self.exists(&id).await {
+                Ok(true) => self.update(item).await,
+                Ok(false) => Err(SistenceMemoryError::NotFound(id.clone())),
+                Err(error) => Err(error),
+            };
+            results.record(index, updated.map(|()| id));
+        }
+        results.into_results()
+    }
+
+    /// Delete several memory items independently, reporting per item those that
+    /// do not exist or fail
+    async fn delete_many(&self, ids: Vec<MemoryId>) -> Vec<BatchItemResult> {
+        let mut results = BatchResults::new(ids.len());
+        for (index, id) in ids.into_iter().enumerate() {
+            let deleted = match self.exists(&id).await {
+                Ok(true) => self.delete(&id).await,
+                Ok(false) => Err(SistenceMemoryError::NotFound(id.clone())),
+                Err(error) => Err(error),
+            };
+            results.record(index, deleted.map(|()| id));
+        }
+        results.into_results()
+    }
+
+    // === Import ===
+
+    /// Import memory items from a JSONL stream, one JSON object per line.
+    ///
+    /// Lines are mapped with `mapping` and stored in batches of `mapping.batch_size`.
+    /// Malformed lines are skipped and listed in the report; storage or read failures
+    /// abort the import. Progress is sent after each stored batch.
+    async fn import_jsonl(
+        &self,
+        reader: &mut (dyn AsyncBufRead + Send + Unpin),
+        mapping: &ImportMapping,
+        progress: Option<mpsc::UnboundedSender<ImportProgress>>,
+    ) -> Result<ImportReport, SistenceMemoryError> {
+        let started = Instant::now();
+        let batch_size = mapping.batch_size.max(1);
+        let mut report = ImportReport::default();
+        let mut batch = Vec::with_capacity(batch_size);
+        let mut lines = reader.lines();
+
+        loop {
+            let line = lines.next_line().await.map_err(|e| {
+                SistenceMemoryError::InvalidInput(format!(
+                    "Failed to read line {}: {}",
+                    report.lines_read + 1,
+                    e
+                ))
+            })?;
+            let end_of_input = line.is_none();
+            if let Some(line) = line {
+                report.lines_read += 1;
+                if !line.trim().is_empty() {
+                    match mapping.to_memory_item(&line) {
+                        Ok(item) => batch.push(item),
+                        Err(error) => report.errors.push(ImportLineError {
+                            line: report.lines_read,
+                            error,
+                        }),
+                    }
+                }
+            }
+
+            if batch.len() >= batch_size || (end_of_input && !batch.is_empty()) {
+                let ids = self.store_batch(std::mem::take(&mut batch)).await?;
+                report.item_ids.extend(ids);
+                if let Some(progress) = &progress {
+                    // 受信側が閉じていても取り込みは続ける
+                    let _ = progress.send(report.progress());
+                }
+            }
+            if end_of_input {
+                break;
+            }
+        }
+
+        report.duration = started.elapsed();
+        Ok(report)
+    }
+
     // === Search Operations ===
 
-    /// Search for memory items
+    /// Search for memory items.
+    ///
+    /// Fails with [`SistenceMemoryError::InvalidFilter`] when the filter tree of
+    /// `filters` is invalid.
     async fn search(
         &self,
         query: &str,
@@ -607,6 +1407,46 @@ pub trait SistenceMemoryCapability: ProviderPlugin + Send + Sync {
         limit: Option<usize>,
     ) -> Result<Vec<MemoryItem>, SistenceMemoryError>;
 
+    /// The items of [`Self::get_relevant_for_context`] in the same order, before
+    /// context pins are applied, each with the factors of its relevance, for
+    /// debugging and tuning retrieval
+    async fn explain_relevance_for_context(
+        &self,
+        context: SearchContext,
+        limit: Option<usize>,
+    ) -> Result<Vec<RelevanceExplanation>, SistenceMemoryError>;
+
+    /// [`Self::get_relevant_for_context`] for a prompt of request `request_id`.
+    /// With `record`, the ids and relevance of the items are kept for
+    /// [`Self::injected_context`].
+    async fn get_relevant_for_request(
+        &self,
+        _request_id: &str,
+        context: SearchContext,
+        limit: Option<usize>,
+        _record: bool,
+    ) -> Result<Vec<MemoryItem>, SistenceMemoryError> {
+        self.get_relevant_for_context(context, limit).await
+    }
+
+    /// The items recorded by [`Self::get_relevant_for_request`] for `request_id`
+    async fn injected_context(
+        &self,
+        request_id: &str,
+    ) -> Result<InjectedContext, SistenceMemoryError> {
+        Err(SistenceMemoryError::NotFound(request_id.to_string()))
+    }
+
+    /// Find items by their tags. With `match_all` an item must carry every tag in
+    /// `include`, otherwise at least one; an empty `include` matches every item.
+    /// Items carrying any tag in `exclude` are left out.
+    async fn search_by_tags(
+        &self,
+        include: HashMap<String, String>,
+        exclude: HashMap<String, String>,
+        match_all: bool,
+    ) -> Result<Vec<MemoryItem>, SistenceMemoryError>;
+
     // === Metadata Management ===
 
     /// Add topics to an item
@@ -623,6 +1463,22 @@ pub trait SistenceMemoryCapability: ProviderPlugin + Send + Sync {
         tags: HashMap<String, String>,
     ) -> Result<(), SistenceMemoryError>;
 
+    /// Set the attachment `key` of an item, replacing its previous value. Fails
+    /// with `InvalidInput` when the value is larger than [`MAX_ATTACHMENT_BYTES`].
+    async fn set_attachment(
+        &self,
+        item_id: &MemoryId,
+        key: &str,
+        value: serde_json::Value,
+    ) -> Result<(), SistenceMemoryError>;
+
+    /// The attachment `key` of an item, or `None` when it has none
+    async fn get_attachment(
+        &self,
+        item_id: &MemoryId,
+        key: &str,
+    ) -> Result<Option<serde_json::Value>, SistenceMemoryError>;
+
     /// Link items together
     async fn link_items(
         &self,
@@ -671,6 +1527,9 @@ pub trait SistenceMemoryCapability: ProviderPlugin + Send + Sync {
         strategy: Option<SearchStrategy>,
     ) -> Result<String, SistenceMemoryError>;
 
+    /// Group items by embedding similarity and label each group with a topic
+    async fn cluster(&self, params: ClusterParams) -> Result<ClusterResult, SistenceMemoryError>;
+
     // === Management Functions ===
 
     /// Clean up expired items
@@ -678,6 +1537,51 @@ pub trait SistenceMemoryCapability: ProviderPlugin + Send + Sync {
 
     /// Get memory statistics
     async fn get_stats(&self) -> Result<MemoryStats, SistenceMemoryError>;
+
+    // === Change Notifications ===
+
+    /// Send `agent_id` a `memory_changed` event whenever an item matching `interest`
+    /// is stored, updated or deleted. The event names the item and the change, not
+    /// its content.
+    async fn subscribe_changes(
+        &self,
+        _agent_id: &str,
+        _interest: MemoryInterest,
+    ) -> Result<MemorySubscriptionId, SistenceMemoryError> {
+        Err(SistenceMemoryError::InternalError(
+            "Change notifications are not supported".to_string(),
+        ))
+    }
+
+    /// Stop a subscription made with [`Self::subscribe_changes`]
+    async fn unsubscribe_changes(
+        &self,
+        subscription_id: &MemorySubscriptionId,
+    ) -> Result<(), SistenceMemoryError> {
+        Err(SistenceMemoryError::NotFound(subscription_id.clone()))
+    }
+
+    // === Context Pins ===
+
+    /// Pin `item_ids` to, or exclude them from, [`Self::get_relevant_for_context`]
+    /// for `ttl`. Pinned items are returned first, as far as the limit allows;
+    /// excluded items are never returned. Pinning an item again replaces its pin.
+    /// Returns the pins that have not expired.
+    async fn pin_for_context(
+        &self,
+        _item_ids: Vec<MemoryId>,
+        _mode: PinMode,
+        _ttl: Duration,
+    ) -> Result<Vec<ContextPin>, SistenceMemoryError> {
+        Err(SistenceMemoryError::InternalError(
+            "Context pins are not supported".to_string(),
+        ))
+    }
+
+    /// The pins that have not expired
+    async fn context_pins(&self) -> Result<Vec<ContextPin>, SistenceMemoryError> {
+        Ok(Vec::new())
+    }
 }
 
 /// Basic metadata for memory items (public API)
@@ -689,6 +1593,10 @@ pub struct BasicMetadata {
     pub topics: Vec<String>,
     /// Custom tags
     pub tags: HashMap<String, String>,
+    /// Structured data kept with the item, such as the source document. Not
+    /// indexed for search; read with `get_attachment`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub attachments: HashMap<String, serde_json::Value>,
     /// Source information
     pub source: Option<String>,
     /// Time-to-live for this item (None = permanent)
@@ -41,6 +41,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use thiserror::Error;
 
+use crate::persistence::PersistenceError;
 use crate::provider::capabilities::shared_memory::Metadata;
 use serde_json::Value;
 use std::time::Instant;
@@ -215,6 +216,9 @@ pub enum StorageError {
 
     #[error("Configuration error: {0}")]
     ConfigurationError(String),
+
+    #[error(transparent)]
+    Persistence(#[from] PersistenceError),
 }
 
 #[cfg(test)]
@@ -10,6 +10,8 @@ use serde_json::Value;
 use std::collections::HashMap;
 use thiserror::Error;
 
+use crate::eval::{expression, secret::SecretValue};
+use crate::event_bus;
 use crate::provider::plugin::ProviderPlugin;
 
 /// Parameters for a will action execution
@@ -20,6 +22,11 @@ pub struct WillActionParams {
 
     /// Positional parameters for the action
     pub positional: Vec<Value>,
+
+    /// Secret parameters, e.g. the token for a request header. Kept apart from
+    /// `named` so they are never serialized.
+    #[serde(skip)]
+    pub secrets: HashMap<String, SecretValue>,
 }
 
 impl WillActionParams {
@@ -28,6 +35,7 @@ impl WillActionParams {
         Self {
             named: HashMap::new(),
             positional: Vec::new(),
+            secrets: HashMap::new(),
         }
     }
 
@@ -42,6 +50,20 @@ impl WillActionParams {
         self.positional.push(value);
         self
     }
+
+    /// Add a secret parameter
+    pub fn with_secret(mut self, name: &str, secret: SecretValue) -> Self {
+        self.secrets.insert(name.to_string(), secret);
+        self
+    }
+
+    /// Add a named parameter evaluated by a handler; secrets go to `secrets`
+    pub fn with_value(self, name: &str, value: expression::Value) -> Self {
+        match value {
+            expression::Value::Secret(secret) => self.with_secret(name, secret),
+            value => self.with_named(name, Value::from(&event_bus::Value::from(value))),
+        }
+    }
 }
 
 impl Default for WillActionParams {
@@ -259,4 +281,60 @@ mod tests {
         assert!(error_result.data.is_none());
         assert!(error_result.error.is_some());
     }
+
+    struct CallApiAction;
+
+    #[async_trait]
+    impl WillAction for CallApiAction {
+        async fn execute(
+            &self,
+            params: WillActionParams,
+            _context: &WillActionContext,
+        ) -> WillActionResult {
+            match params.secrets.get("token") {
+                Some(token) => WillActionResult::success(json!({
+                    "authorization": token.prepend("Bearer ").expose(),
+                })),
+                None => WillActionResult::error(WillActionError::InvalidParameters(
+                    "token is required".to_string(),
+                )),
+            }
+        }
+
+        fn get_signature(&self) -> WillActionSignature {
+            WillActionSignature {
+                name: "call_api".to_string(),
+                description: "Call an API with a bearer token".to_string(),
+                parameters: vec![],
+                return_type: "Object".to_string(),
+                required_permissions: vec![],
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_secret_params_reach_action_but_not_output() {
+        let params = WillActionParams::new()
+            .with_value(
+                "token",
+                expression::Value::Secret(SecretValue::new("s3cr3t")),
+            )
+            .with_value("channel", expression::Value::String("ops".to_string()));
+        assert_eq!(params.named.get("channel").unwrap(), &json!("ops"));
+
+        // シークレットはシリアライズにもデバッグ出力にも現れない
+        assert!(!serde_json::to_string(&params).unwrap().contains("s3cr3t"));
+        assert!(!format!("{:?}", params).contains("s3cr3t"));
+
+        let context = WillActionContext {
+            agent_id: "notifier".to_string(),
+            permissions: vec![],
+            data: HashMap::new(),
+        };
+        let result = CallApiAction.execute(params, &context).await;
+        assert_eq!(
+            result.data.unwrap(),
+            json!({"authorization": "Bearer s3cr3t"})
+        );
+    }
 }
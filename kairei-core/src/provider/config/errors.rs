@@ -721,6 +721,29 @@ impl ProviderConfigError {
         Self::Generic(message.into())
     }
 
+    /// Gets the error context (field location, suggestion, severity) if the
+    /// error carries one. Generic and legacy errors have no context.
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Self::Schema(
+                SchemaError::MissingField { context }
+                | SchemaError::InvalidType { context, .. }
+                | SchemaError::InvalidStructure { context, .. },
+            ) => Some(context),
+            Self::Validation(
+                ValidationError::InvalidValue { context, .. }
+                | ValidationError::ConstraintViolation { context, .. }
+                | ValidationError::DependencyError { context, .. },
+            ) => Some(context),
+            Self::Provider(
+                ProviderError::Initialization { context, .. }
+                | ProviderError::Capability { context, .. }
+                | ProviderError::Configuration { context, .. },
+            ) => Some(context),
+            Self::Generic(_) | Self::Legacy(_) => None,
+        }
+    }
+
     /// Gets the error code for this error
     pub fn error_code(&self) -> String {
         match self {
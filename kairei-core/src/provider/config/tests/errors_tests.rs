@@ -229,6 +229,21 @@ fn test_error_code_generation() {
     assert_eq!(provider_config_error.error_code(), "LEGACY_0001");
 }
 
+#[test]
+fn test_error_context_access() {
+    let validation_error = ValidationError::invalid_value("chunk_size", "Must be positive");
+    let provider_config_error: ProviderConfigError = validation_error.into();
+    let context = provider_config_error.context().unwrap();
+    assert_eq!(context.location.field, Some("chunk_size".to_string()));
+
+    let provider_error = ProviderError::capability("capabilities", "Missing capability");
+    let provider_config_error: ProviderConfigError = provider_error.into();
+    assert!(provider_config_error.context().is_some());
+
+    let generic_error = ProviderConfigError::generic("Generic error message");
+    assert!(generic_error.context().is_none());
+}
+
 #[test]
 fn test_error_display() {
     let schema_error = SchemaError::missing_field("test_field");
@@ -34,6 +34,9 @@ pub fn config_to_map(config: &ProviderConfig) -> HashMap<String, Value> {
         "max_tokens".to_string(),
         Value::from(config.common_config.max_tokens),
     );
+    if let Some(top_p) = config.common_config.top_p {
+        map.insert("top_p".to_string(), Value::from(top_p));
+    }
     map.insert(
         "model".to_string(),
         Value::from(config.common_config.model.clone()),
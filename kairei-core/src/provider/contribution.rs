@@ -0,0 +1,80 @@
+//! # Context Contributions
+//!
+//! What a plugin adds to a request. A `TextSection` is inserted into the prompt
+//! as is; the other kinds keep their structure, so the
+//! [`PromptAssembler`](super::generator::assembler::PromptAssembler) can render
+//! them for the capabilities of the LLM, and providers receive them typed in
+//! [`ProviderRequest::contributions`](super::request::ProviderRequest::contributions).
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use super::provider::Section;
+
+/// A plugin's contribution to the request context
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum ContextContribution {
+    /// Prompt text, inserted unchanged
+    TextSection(Section),
+    /// Records such as memories or search results
+    StructuredRecords {
+        /// Heading placed before the records, e.g. `Previous Context:`
+        title: String,
+        records: Vec<JsonValue>,
+        /// How to render the records for an LLM without JSON mode
+        hint: RecordRendering,
+    },
+    /// Tools the LLM can call through the tool call loop
+    ToolDefinitions(Vec<ToolSpec>),
+    /// An instruction about how to answer
+    SystemDirective(String),
+}
+
+impl ContextContribution {
+    /// The section of a `TextSection`, `None` for the structured kinds
+    pub fn as_section(&self) -> Option<&Section> {
+        match self {
+            ContextContribution::TextSection(section) => Some(section),
+            _ => None,
+        }
+    }
+
+    /// Whether rendering adds nothing to the prompt
+    pub fn is_empty(&self) -> bool {
+        match self {
+            ContextContribution::TextSection(section) => section.content.is_empty(),
+            ContextContribution::StructuredRecords { records, .. } => records.is_empty(),
+            ContextContribution::ToolDefinitions(tools) => tools.is_empty(),
+            ContextContribution::SystemDirective(directive) => directive.is_empty(),
+        }
+    }
+}
+
+impl From<Section> for ContextContribution {
+    fn from(section: Section) -> Self {
+        ContextContribution::TextSection(section)
+    }
+}
+
+/// How `StructuredRecords` are written for an LLM without JSON mode.
+/// LLMs with JSON mode always get a JSON block.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordRendering {
+    /// One `- ` line per record: a string as is, an object as `key: value`
+    /// pairs joined by `, `, anything else as compact JSON
+    #[default]
+    List,
+    /// A JSON block, also for text-only LLMs
+    Json,
+}
+
+/// A tool the LLM can call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolSpec {
+    pub name: String,
+    pub description: Option<String>,
+    /// JSON schema of the arguments
+    pub parameters: JsonValue,
+}
@@ -0,0 +1,49 @@
+use async_trait::async_trait;
+
+use super::types::ProviderResult;
+
+/// Embedding model interface used by memory plugins for semantic operations
+/// such as clustering and similarity search.
+#[async_trait]
+#[mockall::automock]
+pub trait ProviderEmbedding: Send + Sync {
+    /// Embed each text into a dense vector. The returned vectors are in the
+    /// same order as the input texts and share the same dimension.
+    async fn embed(&self, texts: &[String]) -> ProviderResult<Vec<Vec<f32>>>;
+
+    fn name(&self) -> &str;
+}
+
+/// Cosine similarity between two vectors. Returns 0.0 for empty or
+/// zero-length vectors instead of NaN.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let len = a.len().min(b.len());
+    if len == 0 {
+        return 0.0;
+    }
+    let mut dot = 0.0;
+    let mut norm_a = 0.0;
+    let mut norm_b = 0.0;
+    for i in 0..len {
+        dot += a[i] * b[i];
+        norm_a += a[i] * a[i];
+        norm_b += b[i] * b[i];
+    }
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a.sqrt() * norm_b.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[1.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[], &[]), 0.0);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+    }
+}
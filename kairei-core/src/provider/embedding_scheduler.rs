@@ -0,0 +1,639 @@
+//! Batched and cached embeddings of memory items.
+//!
+//! [`EmbeddingScheduler::enqueue`] puts an item that needs an embedding into a
+//! pending set. A background worker sends the pending contents to the
+//! [`ProviderEmbedding`] `batch_size` at a time, or after `flush_interval` when fewer
+//! are waiting, so storing many items costs a few embedding calls instead of one per
+//! item. Items with the same content share one embedding.
+//!
+//! Embeddings are kept in an [`EmbeddingCache`] under the hash of the content and the
+//! name of the embedding model, backed by a [`StorageBackend`]: content stored again,
+//! by any item and after a restart, is not embedded again. Query embeddings of
+//! [`EmbeddingScheduler::embed_query`] go through the same cache.
+//!
+//! A failed call is retried with backoff, during which the worker waits. When every
+//! attempt fails the items are left without an embedding; they are still found by
+//! the keyword search, and are embedded when they are stored again.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{OnceCell, mpsc, oneshot};
+use tracing::warn;
+
+use crate::config::EmbeddingSchedulerConfig;
+use crate::persistence::{self, Versioned};
+use crate::provider::capabilities::shared_memory::Metadata;
+use crate::provider::capabilities::sistence_memory::MemoryId;
+use crate::provider::capabilities::storage::{StorageBackend, StorageError, ValueWithMetadata};
+use crate::provider::embedding::ProviderEmbedding;
+use crate::provider::types::{ProviderError, ProviderResult};
+
+/// Storage namespace of the cached embeddings
+const CACHE_NAMESPACE: &str = "embedding_cache";
+
+/// Counters of an [`EmbeddingScheduler`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct EmbeddingSchedulerStats {
+    /// Items queued and not embedded yet
+    pub queue_depth: u64,
+    /// Calls to the embedding provider, retries included
+    pub batches: u64,
+    /// Contents sent in those calls
+    pub batched_contents: u64,
+    /// Contents in the largest call
+    pub max_batch_size: usize,
+    /// Contents embedded by the provider
+    pub embedded: u64,
+    /// Items left without an embedding because every attempt failed
+    pub failed: u64,
+    /// Lookups answered by the cache
+    pub cache_hits: u64,
+    /// Lookups that needed the provider
+    pub cache_misses: u64,
+}
+
+impl EmbeddingSchedulerStats {
+    /// Share of the lookups answered by the cache, 0 before any lookup
+    pub fn cache_hit_rate(&self) -> f64 {
+        let lookups = self.cache_hits + self.cache_misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.cache_hits as f64 / lookups as f64
+        }
+    }
+
+    /// Contents per call to the embedding provider, 0 before any call
+    pub fn average_batch_size(&self) -> f64 {
+        if self.batches == 0 {
+            0.0
+        } else {
+            self.batched_contents as f64 / self.batches as f64
+        }
+    }
+}
+
+/// Persisted form of a cached embedding
+#[derive(Serialize, Deserialize)]
+struct CachedEmbedding(Vec<f32>);
+
+impl Versioned for CachedEmbedding {
+    const KIND: &'static str = "cached embedding";
+    const VERSION: u32 = 1;
+}
+
+/// Embeddings keyed by the hash of their content and the model, see the
+/// [module docs](self)
+pub struct EmbeddingCache {
+    storage: Arc<dyn StorageBackend>,
+    entries: DashMap<String, Vec<f32>>,
+    // 保存済みのエントリは最初に使うときに読み込む
+    loaded: OnceCell<()>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl EmbeddingCache {
+    pub fn new(storage: Arc<dyn StorageBackend>) -> Self {
+        Self {
+            storage,
+            entries: DashMap::new(),
+            loaded: OnceCell::new(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// The key of `content` embedded by `model`
+    pub fn key(model: &str, content: &str) -> String {
+        let digest = ring::digest::digest(&ring::digest::SHA256, content.as_bytes());
+        let mut key = format!("{}:", model);
+        for byte in digest.as_ref() {
+            let _ = write!(key, "{:02x}", byte);
+        }
+        key
+    }
+
+    async fn load(&self) {
+        self.loaded
+            .get_or_init(|| async {
+                match self.storage.load(CACHE_NAMESPACE).await {
+                    Ok(stored) => {
+                        for (key, stored) in stored {
+                            // 読めないエントリはキャッシュになかったものとして埋め込み直す
+                            match persistence::from_stored::<CachedEmbedding>(stored.value) {
+                                Ok(CachedEmbedding(vector)) => {
+                                    self.entries.insert(key, vector);
+                                }
+                                Err(e) => warn!("Skipping unreadable embedding {}: {}", key, e),
+                            }
+                        }
+                    }
+                    Err(e) => warn!("Embedding cache could not be loaded: {}", e),
+                }
+            })
+            .await;
+    }
+
+    /// The cached embedding of `key`, counted as a hit or a miss
+    pub async fn get(&self, key: &str) -> Option<Vec<f32>> {
+        self.load().await;
+        let vector = self.entries.get(key).map(|entry| entry.clone());
+        let counter = if vector.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        vector
+    }
+
+    /// Cache `vector` under `key`. When it cannot be stored, it is only kept until
+    /// restart.
+    pub async fn insert(&self, key: String, vector: Vec<f32>) {
+        self.load().await;
+        let cached = CachedEmbedding(vector);
+        if let Err(e) = self.store(&key, &cached).await {
+            warn!("Embedding {} could not be stored: {}", key, e);
+        }
+        self.entries.insert(key, cached.0);
+    }
+
+    async fn store(&self, key: &str, cached: &CachedEmbedding) -> Result<(), StorageError> {
+        let value = ValueWithMetadata {
+            value: persistence::to_stored(cached).map_err(StorageError::from)?,
+            metadata: Metadata::default(),
+            expiry: None,
+        };
+        self.storage.save_key(CACHE_NAMESPACE, key, &value).await
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
+enum EmbeddingCommand {
+    Embed { id: MemoryId, content: String },
+    Flush(oneshot::Sender<()>),
+}
+
+#[derive(Debug, Default)]
+struct SchedulerCounters {
+    queue_depth: AtomicU64,
+    batches: AtomicU64,
+    batched_contents: AtomicU64,
+    max_batch_size: AtomicUsize,
+    embedded: AtomicU64,
+    failed: AtomicU64,
+}
+
+/// Embeds memory items in the background, see the [module docs](self)
+pub struct EmbeddingScheduler {
+    sender: mpsc::UnboundedSender<EmbeddingCommand>,
+    embedder: Arc<dyn ProviderEmbedding>,
+    cache: Arc<EmbeddingCache>,
+    embeddings: Arc<DashMap<MemoryId, Vec<f32>>>,
+    counters: Arc<SchedulerCounters>,
+}
+
+impl EmbeddingScheduler {
+    /// Start the worker embedding with `embedder` and caching in `storage`. Must be
+    /// called within a tokio runtime.
+    pub fn new(
+        config: &EmbeddingSchedulerConfig,
+        embedder: Arc<dyn ProviderEmbedding>,
+        storage: Arc<dyn StorageBackend>,
+    ) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let cache = Arc::new(EmbeddingCache::new(storage));
+        let embeddings = Arc::new(DashMap::new());
+        let counters = Arc::new(SchedulerCounters::default());
+        let worker = Worker {
+            embedder: embedder.clone(),
+            cache: cache.clone(),
+            embeddings: embeddings.clone(),
+            counters: counters.clone(),
+            batch_size: config.batch_size.max(1),
+            max_attempts: config.max_attempts.max(1),
+            retry_backoff: config.retry_backoff,
+            pending: Vec::new(),
+        };
+        tokio::spawn(worker.run(receiver, config.flush_interval));
+        Self {
+            sender,
+            embedder,
+            cache,
+            embeddings,
+            counters,
+        }
+    }
+
+    /// Queue item `id` to be embedded with `content`
+    pub fn enqueue(&self, id: MemoryId, content: String) {
+        self.counters.queue_depth.fetch_add(1, Ordering::Relaxed);
+        if self
+            .sender
+            .send(EmbeddingCommand::Embed { id, content })
+            .is_err()
+        {
+            self.counters.queue_depth.fetch_sub(1, Ordering::Relaxed);
+            warn!("Embedding worker has stopped");
+        }
+    }
+
+    /// Embed the queued items now and wait until they are done
+    pub async fn flush(&self) {
+        let (tx, rx) = oneshot::channel();
+        if self.sender.send(EmbeddingCommand::Flush(tx)).is_ok() {
+            let _ = rx.await;
+        }
+    }
+
+    /// The embedding of item `id`, once it is embedded
+    pub fn embedding(&self, id: &str) -> Option<Vec<f32>> {
+        self.embeddings.get(id).map(|entry| entry.clone())
+    }
+
+    /// Forget the embedding of a deleted item. Its content stays cached.
+    pub fn remove(&self, id: &str) {
+        self.embeddings.remove(id);
+    }
+
+    /// The embedding of a search query, from the cache when the query was embedded
+    /// before
+    pub async fn embed_query(&self, query: &str) -> ProviderResult<Vec<f32>> {
+        let mut vectors = self.embed_texts(&[query.to_string()]).await?;
+        Ok(vectors.remove(0))
+    }
+
+    /// Embed `texts` right away through the cache, in one call for those not cached
+    pub async fn embed_texts(&self, texts: &[String]) -> ProviderResult<Vec<Vec<f32>>> {
+        let keys: Vec<String> = texts
+            .iter()
+            .map(|text| EmbeddingCache::key(self.embedder.name(), text))
+            .collect();
+        let mut vectors = Vec::with_capacity(texts.len());
+        // 同じ内容は 1 度だけ送る
+        let mut missing: HashMap<&str, &String> = HashMap::new();
+        let mut missing_keys = Vec::new();
+        for (key, text) in keys.iter().zip(texts) {
+            let vector = self.cache.get(key).await;
+            if vector.is_none() && missing.insert(key.as_str(), text).is_none() {
+                missing_keys.push(key.as_str());
+            }
+            vectors.push(vector);
+        }
+
+        if !missing_keys.is_empty() {
+            let missing_texts: Vec<String> = missing_keys
+                .iter()
+                .map(|key| missing[key].clone())
+                .collect();
+            let embedded = embed(self.embedder.as_ref(), &missing_texts, &self.counters).await?;
+            let embedded: HashMap<&str, Vec<f32>> =
+                missing_keys.into_iter().zip(embedded).collect();
+            for (vector, key) in vectors.iter_mut().zip(&keys) {
+                if vector.is_none() {
+                    *vector = Some(embedded[key.as_str()].clone());
+                }
+            }
+            for (key, vector) in embedded {
+                self.cache.insert(key.to_string(), vector).await;
+            }
+        }
+        Ok(vectors.into_iter().flatten().collect())
+    }
+
+    pub fn stats(&self) -> EmbeddingSchedulerStats {
+        EmbeddingSchedulerStats {
+            queue_depth: self.counters.queue_depth.load(Ordering::Relaxed),
+            batches: self.counters.batches.load(Ordering::Relaxed),
+            batched_contents: self.counters.batched_contents.load(Ordering::Relaxed),
+            max_batch_size: self.counters.max_batch_size.load(Ordering::Relaxed),
+            embedded: self.counters.embedded.load(Ordering::Relaxed),
+            failed: self.counters.failed.load(Ordering::Relaxed),
+            cache_hits: self.cache.hits(),
+            cache_misses: self.cache.misses(),
+        }
+    }
+}
+
+/// A content waiting for its embedding, and the items that have it
+struct PendingContent {
+    key: String,
+    content: String,
+    ids: Vec<MemoryId>,
+}
+
+struct Worker {
+    embedder: Arc<dyn ProviderEmbedding>,
+    cache: Arc<EmbeddingCache>,
+    embeddings: Arc<DashMap<MemoryId, Vec<f32>>>,
+    counters: Arc<SchedulerCounters>,
+    batch_size: usize,
+    max_attempts: u32,
+    retry_backoff: Duration,
+    pending: Vec<PendingContent>,
+}
+
+impl Worker {
+    async fn run(
+        mut self,
+        mut receiver: mpsc::UnboundedReceiver<EmbeddingCommand>,
+        flush_interval: Duration,
+    ) {
+        let mut ticker = tokio::time::interval(flush_interval.max(Duration::from_millis(1)));
+        loop {
+            tokio::select! {
+                command = receiver.recv() => match command {
+                    Some(EmbeddingCommand::Embed { id, content }) => {
+                        self.add(id, content).await;
+                        if self.pending.len() >= self.batch_size {
+                            self.embed_pending().await;
+                        }
+                    }
+                    Some(EmbeddingCommand::Flush(done)) => {
+                        self.embed_pending().await;
+                        let _ = done.send(());
+                    }
+                    None => break,
+                },
+                _ = ticker.tick() => self.embed_pending().await,
+            }
+        }
+    }
+
+    async fn add(&mut self, id: MemoryId, content: String) {
+        let key = EmbeddingCache::key(self.embedder.name(), &content);
+        // 同じ内容が埋め込みを待っていれば、その結果を共有する
+        if let Some(pending) = self.pending.iter_mut().find(|pending| pending.key == key) {
+            pending.ids.push(id);
+            return;
+        }
+        match self.cache.get(&key).await {
+            Some(vector) => {
+                self.embeddings.insert(id, vector);
+                self.counters.queue_depth.fetch_sub(1, Ordering::Relaxed);
+            }
+            None => self.pending.push(PendingContent {
+                key,
+                content,
+                ids: vec![id],
+            }),
+        }
+    }
+
+    async fn embed_pending(&mut self) {
+        while !self.pending.is_empty() {
+            let count = self.pending.len().min(self.batch_size);
+            let batch: Vec<PendingContent> = self.pending.drain(..count).collect();
+            self.embed_batch(batch).await;
+        }
+    }
+
+    async fn embed_batch(&self, batch: Vec<PendingContent>) {
+        let texts: Vec<String> = batch
+            .iter()
+            .map(|pending| pending.content.clone())
+            .collect();
+        let items: u64 = batch.iter().map(|pending| pending.ids.len() as u64).sum();
+        let mut backoff = self.retry_backoff;
+        let mut attempt = 1;
+        let vectors = loop {
+            match embed(self.embedder.as_ref(), &texts, &self.counters).await {
+                Ok(vectors) => break vectors,
+                Err(e) if attempt < self.max_attempts => {
+                    warn!(
+                        "Embedding {} contents failed (attempt {}): {}",
+                        texts.len(),
+                        attempt,
+                        e
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = backoff.saturating_mul(2);
+                    attempt += 1;
+                }
+                Err(e) => {
+                    warn!(
+                        "Embedding {} items failed, they are only found by keyword: {}",
+                        items, e
+                    );
+                    self.counters.failed.fetch_add(items, Ordering::Relaxed);
+                    self.counters
+                        .queue_depth
+                        .fetch_sub(items, Ordering::Relaxed);
+                    return;
+                }
+            }
+        };
+
+        for (pending, vector) in batch.into_iter().zip(vectors) {
+            for id in pending.ids {
+                self.embeddings.insert(id, vector.clone());
+            }
+            self.cache.insert(pending.key, vector).await;
+        }
+        self.counters
+            .queue_depth
+            .fetch_sub(items, Ordering::Relaxed);
+    }
+}
+
+/// One call to the embedding provider, counted in `counters`
+async fn embed(
+    embedder: &dyn ProviderEmbedding,
+    texts: &[String],
+    counters: &SchedulerCounters,
+) -> ProviderResult<Vec<Vec<f32>>> {
+    counters.batches.fetch_add(1, Ordering::Relaxed);
+    counters
+        .batched_contents
+        .fetch_add(texts.len() as u64, Ordering::Relaxed);
+    counters
+        .max_batch_size
+        .fetch_max(texts.len(), Ordering::Relaxed);
+
+    let vectors = embedder.embed(texts).await?;
+    if vectors.len() != texts.len() {
+        return Err(ProviderError::InternalError(format!(
+            "Embedding provider returned {} vectors for {} texts",
+            vectors.len(),
+            texts.len()
+        )));
+    }
+    counters
+        .embedded
+        .fetch_add(texts.len() as u64, Ordering::Relaxed);
+    Ok(vectors)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use std::sync::atomic::AtomicU32;
+
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::provider::config::plugins::InMemoryConfig;
+    use crate::provider::plugins::storage::in_memory::InMemoryBackend;
+
+    /// 呼び出しごとの件数を記録し、最初の `failures` 回は失敗する埋め込み
+    #[derive(Default)]
+    struct CountingEmbedder {
+        calls: Mutex<Vec<usize>>,
+        failures: AtomicU32,
+    }
+
+    impl CountingEmbedder {
+        fn calls(&self) -> Vec<usize> {
+            self.calls.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl ProviderEmbedding for CountingEmbedder {
+        async fn embed(&self, texts: &[String]) -> ProviderResult<Vec<Vec<f32>>> {
+            self.calls.lock().unwrap().push(texts.len());
+            if self
+                .failures
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok()
+            {
+                return Err(ProviderError::Overloaded("busy".to_string()));
+            }
+            Ok(texts
+                .iter()
+                .map(|text| vec![text.len() as f32, 1.0])
+                .collect())
+        }
+
+        fn name(&self) -> &str {
+            "counting"
+        }
+    }
+
+    fn storage() -> Arc<dyn StorageBackend> {
+        Arc::new(InMemoryBackend::new(InMemoryConfig {
+            max_namespaces: 0,
+            max_keys_per_namespace: 0,
+        }))
+    }
+
+    fn config(batch_size: usize) -> EmbeddingSchedulerConfig {
+        EmbeddingSchedulerConfig {
+            batch_size,
+            flush_interval: Duration::from_secs(3600),
+            retry_backoff: Duration::from_millis(1),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_duplicates_share_batched_embeddings() {
+        let embedder = Arc::new(CountingEmbedder::default());
+        let scheduler = EmbeddingScheduler::new(&config(16), embedder.clone(), storage());
+
+        // 80 件の内容に、そのうち 20 件と同じ内容の項目を加える
+        for i in 0..100 {
+            scheduler.enqueue(format!("item-{}", i), format!("note {}", i % 80));
+        }
+        scheduler.flush().await;
+
+        let calls = embedder.calls();
+        assert!(calls.len() <= 5, "calls: {:?}", calls);
+        assert!(calls.iter().all(|&size| size >= 16), "calls: {:?}", calls);
+        assert_eq!(
+            scheduler.embedding("item-95"),
+            scheduler.embedding("item-15")
+        );
+        assert!((0..100).all(|i| scheduler.embedding(&format!("item-{}", i)).is_some()));
+
+        let stats = scheduler.stats();
+        assert_eq!(stats.queue_depth, 0);
+        assert_eq!(stats.embedded, 80);
+        assert_eq!(stats.max_batch_size, 16);
+        assert_eq!(stats.average_batch_size(), 16.0);
+        assert_eq!((stats.cache_hits, stats.cache_misses), (20, 80));
+        assert_eq!(stats.cache_hit_rate(), 0.2);
+    }
+
+    #[tokio::test]
+    async fn test_cache_is_shared_by_queries_and_kept_in_storage() {
+        let embedder = Arc::new(CountingEmbedder::default());
+        let storage = storage();
+        let scheduler = EmbeddingScheduler::new(&config(16), embedder.clone(), storage.clone());
+
+        let first = scheduler.embed_query("trip to Kyoto").await.unwrap();
+        let second = scheduler.embed_query("trip to Kyoto").await.unwrap();
+        assert_eq!(first, second);
+        assert_eq!(embedder.calls(), vec![1]);
+
+        // 同じストレージを使えば、作り直しても同じ内容は埋め込まない
+        let scheduler = EmbeddingScheduler::new(&config(16), embedder.clone(), storage);
+        scheduler.enqueue("note-1".to_string(), "trip to Kyoto".to_string());
+        scheduler.flush().await;
+        assert_eq!(scheduler.embedding("note-1"), Some(first));
+        assert_eq!(embedder.calls(), vec![1]);
+    }
+
+    #[tokio::test]
+    async fn test_unreadable_cached_embedding_is_a_miss() {
+        let embedder = Arc::new(CountingEmbedder::default());
+        let storage = storage();
+        let key = EmbeddingCache::key("counting", "trip to Kyoto");
+        let garbage = ValueWithMetadata {
+            value: serde_json::json!({"version": 1, "payload": "not a vector"}),
+            metadata: Metadata::default(),
+            expiry: None,
+        };
+        storage
+            .save_key(CACHE_NAMESPACE, &key, &garbage)
+            .await
+            .unwrap();
+
+        let scheduler = EmbeddingScheduler::new(&config(16), embedder.clone(), storage.clone());
+        let vector = scheduler.embed_query("trip to Kyoto").await.unwrap();
+        assert_eq!(embedder.calls(), vec![1]);
+        assert_eq!(scheduler.stats().cache_misses, 1);
+
+        // 埋め込み直した値は版付きで書き直される
+        let stored = storage.load(CACHE_NAMESPACE).await.unwrap();
+        assert_eq!(stored[&key].value["version"], 1);
+        let CachedEmbedding(cached) = persistence::from_stored(stored[&key].value.clone()).unwrap();
+        assert_eq!(cached, vector);
+    }
+
+    #[tokio::test]
+    async fn test_failed_batches_are_retried_then_given_up() {
+        let embedder = Arc::new(CountingEmbedder {
+            failures: AtomicU32::new(2),
+            ..Default::default()
+        });
+        let scheduler = EmbeddingScheduler::new(&config(16), embedder.clone(), storage());
+
+        scheduler.enqueue("note-1".to_string(), "Kyoto".to_string());
+        scheduler.flush().await;
+        assert!(scheduler.embedding("note-1").is_some());
+        assert_eq!(embedder.calls(), vec![1, 1, 1]);
+
+        // 3 回とも失敗すると埋め込みのないまま残る
+        embedder.failures.store(3, Ordering::SeqCst);
+        scheduler.enqueue("note-2".to_string(), "Osaka".to_string());
+        scheduler.flush().await;
+        assert!(scheduler.embedding("note-2").is_none());
+        let stats = scheduler.stats();
+        assert_eq!((stats.failed, stats.queue_depth), (1, 0));
+    }
+}
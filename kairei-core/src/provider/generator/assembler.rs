@@ -0,0 +1,278 @@
+use serde_json::Value as JsonValue;
+
+use crate::provider::{
+    capabilities::common::{Capabilities, CapabilityType},
+    contribution::{ContextContribution, RecordRendering, ToolSpec},
+    provider::{Section, SectionMetadata},
+};
+
+/// How the LLM asks for a tool; answered by the plugins' `handle_tool_call`
+pub const TOOL_CALL_INSTRUCTION: &str = "To call a tool, reply with only a JSON object {\"tool\": \"<name>\", \"arguments\": {...}}. \
+     The result is sent back to you before you answer.\n";
+
+/// Renders plugin contributions into prompt sections for the capabilities of
+/// an LLM. A `TextSection` passes through unchanged; records and tools become
+/// JSON blocks when the LLM has JSON mode, and text otherwise.
+pub struct PromptAssembler {
+    capabilities: Capabilities,
+}
+
+impl PromptAssembler {
+    pub fn new(capabilities: Capabilities) -> Self {
+        Self { capabilities }
+    }
+
+    /// Renders a contribution. `priority` is given to the kinds that carry
+    /// none; a `TextSection` keeps its own.
+    pub fn render(&self, contribution: &ContextContribution, priority: i32) -> Section {
+        let (content, source) = match contribution {
+            ContextContribution::TextSection(section) => return section.clone(),
+            ContextContribution::StructuredRecords {
+                title,
+                records,
+                hint,
+            } => (
+                self.render_records(title, records, *hint),
+                "structured_records",
+            ),
+            ContextContribution::ToolDefinitions(tools) => {
+                (self.render_tools(tools), "tool_definitions")
+            }
+            ContextContribution::SystemDirective(directive) => {
+                (directive.clone(), "system_directive")
+            }
+        };
+        Section {
+            content,
+            priority,
+            metadata: SectionMetadata::new(source),
+        }
+    }
+
+    fn json_mode(&self) -> bool {
+        self.capabilities.supports(&CapabilityType::JsonMode)
+    }
+
+    fn render_records(&self, title: &str, records: &[JsonValue], hint: RecordRendering) -> String {
+        if records.is_empty() {
+            return String::new();
+        }
+        if self.json_mode() || hint == RecordRendering::Json {
+            return format!("{}\n{}", title, json_block(&JsonValue::from(records)));
+        }
+        let mut content = format!("{}\n", title);
+        for record in records {
+            content.push_str(&format!("- {}\n", record_line(record)));
+        }
+        content
+    }
+
+    fn render_tools(&self, tools: &[ToolSpec]) -> String {
+        if tools.is_empty() {
+            return String::new();
+        }
+        let mut content = String::from("Tools you can call:\n");
+        if self.json_mode() {
+            let tools = serde_json::to_value(tools).unwrap_or_default();
+            content.push_str(&json_block(&tools));
+        } else {
+            for tool in tools {
+                content.push_str(&format!("- {}", tool.name));
+                if let Some(description) = &tool.description {
+                    content.push_str(&format!(": {}", description));
+                }
+                content.push_str(&format!("\n  arguments: {}\n", tool.parameters));
+            }
+        }
+        content.push_str(TOOL_CALL_INSTRUCTION);
+        content
+    }
+}
+
+fn json_block(value: &JsonValue) -> String {
+    let json = serde_json::to_string_pretty(value).unwrap_or_default();
+    format!("```json\n{}\n```\n", json)
+}
+
+fn record_line(record: &JsonValue) -> String {
+    match record {
+        JsonValue::String(s) => s.clone(),
+        JsonValue::Object(fields) => fields
+            .iter()
+            .map(|(key, value)| match value {
+                JsonValue::String(s) => format!("{}: {}", key, s),
+                other => format!("{}: {}", key, other),
+            })
+            .collect::<Vec<_>>()
+            .join(", "),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::provider::{
+        generator::generator::{Generator, PromptGenerator},
+        types::ProviderResult,
+    };
+
+    fn text_only() -> PromptAssembler {
+        PromptAssembler::new(Capabilities::from(CapabilityType::Generate))
+    }
+
+    fn json_mode() -> PromptAssembler {
+        PromptAssembler::new(Capabilities::from(vec![
+            CapabilityType::Generate,
+            CapabilityType::JsonMode,
+        ]))
+    }
+
+    fn legacy_sections() -> Vec<Section> {
+        vec![
+            Section {
+                content: "You are a travel planner.".to_string(),
+                priority: i32::MIN,
+                metadata: SectionMetadata::new("prompt_preamble"),
+            },
+            Section {
+                content: "Policies:\n- Prefer trains\n".to_string(),
+                priority: 10,
+                ..Default::default()
+            },
+            Section::new(""),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_text_section_is_byte_identical() -> ProviderResult<()> {
+        let generator = PromptGenerator::new(None);
+        let legacy = generator.generate(legacy_sections()).await?;
+
+        for assembler in [text_only(), json_mode()] {
+            let sections = legacy_sections()
+                .into_iter()
+                .map(|section| assembler.render(&section.into(), 0))
+                .collect();
+            assert_eq!(generator.generate(sections).await?, legacy);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_text_section_keeps_priority_and_metadata() {
+        let section = Section {
+            content: "text".to_string(),
+            priority: 5,
+            metadata: SectionMetadata::new("plugin"),
+        };
+        let rendered = text_only().render(&section.into(), 99);
+        assert_eq!(rendered.priority, 5);
+        assert_eq!(rendered.metadata.source, "plugin");
+    }
+
+    fn records() -> ContextContribution {
+        ContextContribution::StructuredRecords {
+            title: "Previous Context:".to_string(),
+            records: vec![
+                json!({"content": "Tom likes trains", "importance": 0.8}),
+                json!("Tom lives in Kyoto"),
+            ],
+            hint: RecordRendering::List,
+        }
+    }
+
+    #[test]
+    fn test_structured_records_text_only() {
+        let section = text_only().render(&records(), 100);
+        assert_eq!(
+            section.content,
+            "Previous Context:\n- content: Tom likes trains, importance: 0.8\n- Tom lives in Kyoto\n"
+        );
+        assert_eq!(section.priority, 100);
+    }
+
+    #[test]
+    fn test_structured_records_json_mode() {
+        let section = json_mode().render(&records(), 100);
+        assert_eq!(
+            section.content,
+            "Previous Context:\n```json\n[\n  {\n    \"content\": \"Tom likes trains\",\n    \"importance\": 0.8\n  },\n  \"Tom lives in Kyoto\"\n]\n```\n"
+        );
+    }
+
+    #[test]
+    fn test_structured_records_json_hint_on_text_only() {
+        let contribution = ContextContribution::StructuredRecords {
+            title: "Results:".to_string(),
+            records: vec![json!({"id": 1})],
+            hint: RecordRendering::Json,
+        };
+        assert_eq!(
+            text_only().render(&contribution, 0).content,
+            "Results:\n```json\n[\n  {\n    \"id\": 1\n  }\n]\n```\n"
+        );
+    }
+
+    fn tools() -> ContextContribution {
+        ContextContribution::ToolDefinitions(vec![ToolSpec {
+            name: "listPets".to_string(),
+            description: Some("List the pets".to_string()),
+            parameters: json!({"type": "object"}),
+        }])
+    }
+
+    #[test]
+    fn test_tool_definitions_text_only() {
+        let section = text_only().render(&tools(), 9);
+        assert_eq!(
+            section.content,
+            format!(
+                "Tools you can call:\n- listPets: List the pets\n  arguments: {{\"type\":\"object\"}}\n{}",
+                TOOL_CALL_INSTRUCTION
+            )
+        );
+    }
+
+    #[test]
+    fn test_tool_definitions_json_mode() {
+        let section = json_mode().render(&tools(), 9);
+        assert!(
+            section
+                .content
+                .starts_with("Tools you can call:\n```json\n")
+        );
+        assert!(section.content.contains("\"name\": \"listPets\""));
+        assert!(section.content.ends_with(TOOL_CALL_INSTRUCTION));
+    }
+
+    #[test]
+    fn test_empty_contributions_render_nothing() {
+        let empty = [
+            ContextContribution::StructuredRecords {
+                title: "Previous Context:".to_string(),
+                records: vec![],
+                hint: RecordRendering::List,
+            },
+            ContextContribution::ToolDefinitions(vec![]),
+        ];
+        for assembler in [text_only(), json_mode()] {
+            for contribution in &empty {
+                assert!(contribution.is_empty());
+                assert!(assembler.render(contribution, 0).content.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn test_system_directive() {
+        let directive = ContextContribution::SystemDirective("Answer in Japanese.".to_string());
+        for assembler in [text_only(), json_mode()] {
+            let section = assembler.render(&directive, 1);
+            assert_eq!(section.content, "Answer in Japanese.");
+            assert_eq!(section.metadata.source, "system_directive");
+        }
+    }
+}
@@ -1,3 +1,4 @@
+pub mod assembler;
 #[allow(clippy::module_inception)]
 pub mod generator;
 pub mod optimizer;
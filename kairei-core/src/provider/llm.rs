@@ -2,6 +2,7 @@ use crate::{config::ProviderConfig, timestamp::Timestamp};
 
 use super::{capabilities::common::Capabilities, provider::ProviderSecret, types::*};
 use async_trait::async_trait;
+use tokio::sync::mpsc;
 
 #[async_trait]
 #[mockall::automock]
@@ -11,6 +12,20 @@ pub trait ProviderLLM: Send + Sync {
         prompt: &str,
         config: &ProviderConfig,
     ) -> ProviderResult<LLMResponse>;
+
+    /// Sends the message, passing the content to `chunks` as it is generated.
+    /// LLMs that cannot stream send the whole content as one chunk.
+    async fn send_message_streaming(
+        &self,
+        prompt: &str,
+        config: &ProviderConfig,
+        chunks: &mpsc::UnboundedSender<String>,
+    ) -> ProviderResult<LLMResponse> {
+        let response = self.send_message(prompt, config).await?;
+        let _ = chunks.send(response.content.clone());
+        Ok(response)
+    }
+
     fn capabilities(&self) -> Capabilities;
 
     fn name(&self) -> &str;
@@ -25,6 +40,11 @@ pub trait ProviderLLM: Send + Sync {
     async fn stop(&self) -> ProviderResult<()> {
         Ok(())
     }
+
+    /// 認証情報をAPIで確認する。確認できないLLMは Unknown
+    async fn validate_credentials(&self) -> ProviderResult<CredentialStatus> {
+        Ok(CredentialStatus::Unknown)
+    }
 }
 
 #[derive(Debug, Default, Clone)]
@@ -49,7 +69,7 @@ mod tests {
 
     use super::*;
     use crate::{
-        config::{CommonConfig, EndpointConfig, ProviderConfig},
+        config::{CommonConfig, ConcurrencyConfig, EndpointConfig, ProviderConfig},
         provider::{llms::simple_expert::SimpleExpertProviderLLM, provider::ProviderType},
     };
     use std::collections::HashMap;
@@ -62,7 +82,11 @@ mod tests {
             common_config: CommonConfig {
                 temperature: 0.7,
                 max_tokens: 1000,
+                top_p: None,
                 model: "gpt-4".to_string(),
+                output_format: Default::default(),
+                response_schema: None,
+                max_repair_attempts: 2,
             },
             provider_specific: {
                 let mut provider_specific = HashMap::new();
@@ -72,6 +96,7 @@ mod tests {
             provider_type: ProviderType::SimpleExpert,
             endpoint: EndpointConfig::default(),
             plugin_configs: HashMap::new(),
+            concurrency: ConcurrencyConfig::default(),
         };
         let provider = SimpleExpertProviderLLM::new("test");
         let response = provider.send_message("Hello", &config).await.unwrap();
@@ -16,8 +16,9 @@ use crate::{
     provider::{
         capabilities::common::{Capabilities, CapabilityType, HasCapabilities},
         llm::{LLMResponse, ProviderLLM, ResponseMetadata},
+        llms::openai_chat::validate_openai_credentials,
         provider::ProviderSecret,
-        types::{ProviderError, ProviderResult},
+        types::{CredentialStatus, ProviderError, ProviderResult},
     },
     timestamp::Timestamp,
 };
@@ -246,6 +247,13 @@ impl ProviderLLM for OpenAIAssistantProviderLLM {
         Ok(())
     }
 
+    async fn validate_credentials(&self) -> ProviderResult<CredentialStatus> {
+        match &self.client {
+            Some(client) => Ok(validate_openai_credentials(client).await),
+            None => Ok(CredentialStatus::Invalid("client not initialized".into())),
+        }
+    }
+
     fn capabilities(&self) -> Capabilities {
         self.capabilities.clone()
     }
@@ -5,22 +5,26 @@ use crate::{
 use async_openai::{
     Client,
     config::OpenAIConfig,
+    error::OpenAIError,
     types::{
         ChatCompletionRequestMessage, ChatCompletionRequestUserMessage,
-        ChatCompletionRequestUserMessageContent, CreateChatCompletionRequest,
+        ChatCompletionRequestUserMessageContent, ChatCompletionStreamOptions,
+        CreateChatCompletionRequest, ResponseFormat,
     },
 };
 use async_trait::async_trait;
+use futures::StreamExt;
 use secrecy::ExposeSecret;
 use std::collections::HashSet;
+use tokio::sync::mpsc;
 use tracing::debug;
 
 use crate::{
-    config::ProviderConfig,
+    config::{EndpointConfig, OutputFormat, ProviderConfig},
     provider::{
         capabilities::common::{Capabilities, CapabilityType, HasCapabilities},
         llm::{LLMResponse, ResponseMetadata},
-        types::{ProviderError, ProviderResult},
+        types::{CredentialStatus, ProviderError, ProviderResult},
     },
 };
 
@@ -35,6 +39,7 @@ impl OpenAIChatProviderLLM {
         let mut capabilities = HashSet::new();
         capabilities.insert(CapabilityType::Generate);
         capabilities.insert(CapabilityType::SystemPrompt);
+        capabilities.insert(CapabilityType::JsonMode);
 
         Self {
             client: None,
@@ -56,20 +61,7 @@ impl OpenAIChatProviderLLM {
 
         debug!("prompt: {}", prompt);
 
-        let messages = vec![ChatCompletionRequestMessage::User(
-            ChatCompletionRequestUserMessage {
-                content: ChatCompletionRequestUserMessageContent::Text(prompt.to_string()),
-                name: None,
-            },
-        )];
-
-        let request = CreateChatCompletionRequest {
-            model: config.common_config.model.clone(),
-            messages,
-            temperature: Some(config.common_config.temperature),
-            max_completion_tokens: Some(config.common_config.max_tokens as u32),
-            ..Default::default()
-        };
+        let request = Self::build_request(prompt, config);
 
         let response = client
             .chat()
@@ -98,6 +90,91 @@ impl OpenAIChatProviderLLM {
             },
         })
     }
+
+    /// Chat completion streamed as server-sent events; each content delta is sent to
+    /// `chunks` as it arrives
+    #[tracing::instrument(skip(self, config, chunks))]
+    async fn chat_completion_stream(
+        &self,
+        prompt: &str,
+        config: &ProviderConfig,
+        chunks: &mpsc::UnboundedSender<String>,
+    ) -> ProviderResult<LLMResponse> {
+        let client = self
+            .client
+            .as_ref()
+            .ok_or_else(|| ProviderError::Authentication("Client not initialized".into()))?;
+
+        debug!("prompt: {}", prompt);
+        let request = CreateChatCompletionRequest {
+            stream: Some(true),
+            // 使用量は最後のチャンクで届く
+            stream_options: Some(ChatCompletionStreamOptions {
+                include_usage: true,
+            }),
+            ..Self::build_request(prompt, config)
+        };
+
+        let mut stream = client
+            .chat()
+            .create_stream(request)
+            .await
+            .map_err(|e| ProviderError::ApiError(e.to_string()))?;
+        let mut content = String::new();
+        let mut token_usage = None;
+        let mut finish_reason = None;
+        while let Some(response) = stream.next().await {
+            let response = response.map_err(|e| ProviderError::ApiError(e.to_string()))?;
+            if let Some(usage) = response.usage {
+                token_usage = Some((
+                    usage.prompt_tokens as usize,
+                    usage.completion_tokens as usize,
+                ));
+            }
+            let Some(choice) = response.choices.first() else {
+                continue;
+            };
+            if let Some(delta) = &choice.delta.content {
+                content.push_str(delta);
+                let _ = chunks.send(delta.clone());
+            }
+            if choice.finish_reason.is_some() {
+                finish_reason = Some(format!("{:?}", choice.finish_reason));
+            }
+        }
+
+        Ok(LLMResponse {
+            content,
+            metadata: ResponseMetadata {
+                model: config.common_config.model.clone(),
+                created_at: Timestamp::now(),
+                token_usage,
+                finish_reason,
+            },
+        })
+    }
+
+    fn build_request(prompt: &str, config: &ProviderConfig) -> CreateChatCompletionRequest {
+        let messages = vec![ChatCompletionRequestMessage::User(
+            ChatCompletionRequestUserMessage {
+                content: ChatCompletionRequestUserMessageContent::Text(prompt.to_string()),
+                name: None,
+            },
+        )];
+
+        CreateChatCompletionRequest {
+            model: config.common_config.model.clone(),
+            messages,
+            temperature: Some(config.common_config.temperature),
+            top_p: config.common_config.top_p,
+            max_completion_tokens: Some(config.common_config.max_tokens as u32),
+            response_format: match config.common_config.output_format {
+                OutputFormat::Json => Some(ResponseFormat::JsonObject),
+                OutputFormat::Text => None,
+            },
+            ..Default::default()
+        }
+    }
 }
 
 #[async_trait]
@@ -110,6 +187,15 @@ impl ProviderLLM for OpenAIChatProviderLLM {
         self.chat_completion(prompt, config).await
     }
 
+    async fn send_message_streaming(
+        &self,
+        prompt: &str,
+        config: &ProviderConfig,
+        chunks: &mpsc::UnboundedSender<String>,
+    ) -> ProviderResult<LLMResponse> {
+        self.chat_completion_stream(prompt, config, chunks).await
+    }
+
     fn capabilities(&self) -> Capabilities {
         self.capabilities.clone()
     }
@@ -120,7 +206,7 @@ impl ProviderLLM for OpenAIChatProviderLLM {
 
     async fn initialize(
         &mut self,
-        _config: &ProviderConfig,
+        config: &ProviderConfig,
         secret: &ProviderSecret,
     ) -> ProviderResult<()> {
         let api_key = secret.api_key.clone();
@@ -129,10 +215,39 @@ impl ProviderLLM for OpenAIChatProviderLLM {
         if let Some(org_id) = secret.additional_auth.get("organization_id") {
             openai_config = openai_config.with_org_id(org_id.expose_secret());
         }
+        if let Some(api_base) = api_base(&config.endpoint) {
+            openai_config = openai_config.with_api_base(api_base);
+        }
 
         self.client = Some(Client::with_config(openai_config));
         Ok(())
     }
+
+    async fn validate_credentials(&self) -> ProviderResult<CredentialStatus> {
+        match &self.client {
+            Some(client) => Ok(validate_openai_credentials(client).await),
+            None => Ok(CredentialStatus::Invalid("client not initialized".into())),
+        }
+    }
+}
+
+/// `endpoint.url` と `endpoint.api_version` から組み立てたAPIのベースURL
+fn api_base(endpoint: &EndpointConfig) -> Option<String> {
+    let url = endpoint.url.as_deref()?.trim_end_matches('/');
+    Some(match &endpoint.api_version {
+        Some(version) => format!("{}/{}", url, version),
+        None => url.to_string(),
+    })
+}
+
+/// Check an OpenAI key by listing the models, which costs no tokens
+pub(crate) async fn validate_openai_credentials(client: &Client<OpenAIConfig>) -> CredentialStatus {
+    match client.models().list().await {
+        Ok(_) => CredentialStatus::Valid,
+        // APIが応答したエラーは認証情報の拒否とみなす
+        Err(OpenAIError::ApiError(error)) => CredentialStatus::Invalid(error.message),
+        Err(error) => CredentialStatus::Unreachable(error.to_string()),
+    }
 }
 
 impl HasCapabilities for OpenAIChatProviderLLM {
@@ -152,4 +267,20 @@ mod tests {
         assert!(provider.supports(&CapabilityType::SystemPrompt));
         assert!(!provider.supports(&CapabilityType::Thread));
     }
+
+    #[test]
+    fn test_json_output_sets_response_format() {
+        let mut config = ProviderConfig::default();
+        let body =
+            serde_json::to_value(OpenAIChatProviderLLM::build_request("hi", &config)).unwrap();
+        assert!(body.get("response_format").is_none());
+
+        config.common_config.output_format = OutputFormat::Json;
+        let body =
+            serde_json::to_value(OpenAIChatProviderLLM::build_request("hi", &config)).unwrap();
+        assert_eq!(
+            body["response_format"],
+            serde_json::json!({"type": "json_object"})
+        );
+    }
 }
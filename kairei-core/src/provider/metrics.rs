@@ -0,0 +1,350 @@
+//! # LLM Call Metrics
+//!
+//! Histograms of the latency and token usage of LLM calls, labeled by provider and
+//! model, rendered in the Prometheus text exposition format.
+//!
+//! Series are registered for the model configured on each provider when it is
+//! registered. A call with any other model, e.g. one overridden with
+//! `think(...) with { model: ... }`, is counted under the model label [`OTHER_MODEL`],
+//! so the number of series stays bounded by the configuration. Only successful calls
+//! are recorded, and token usage only when the LLM reports it.
+//!
+//! [`PrometheusEncoder`] also renders the hit and miss counters of answer handler
+//! response caches next to the LLM histograms.
+
+use std::{collections::BTreeMap, fmt::Write, sync::Mutex, time::Duration};
+
+use crate::response_cache::ResponseCacheStats;
+
+/// Model label of calls with a model that is not configured on their provider
+pub const OTHER_MODEL: &str = "other";
+
+/// Upper bounds (seconds) of the latency buckets
+pub const LATENCY_BUCKETS: [f64; 11] =
+    [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 20.0, 30.0, 60.0];
+
+/// Upper bounds of the buckets of tokens (prompt + completion) per call
+pub const TOKEN_BUCKETS: [f64; 9] = [
+    100.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0, 32000.0,
+];
+
+const LATENCY_METRIC: &str = "kairei_llm_request_duration_seconds";
+const TOKENS_METRIC: &str = "kairei_llm_tokens_per_request";
+const CACHE_HITS_METRIC: &str = "kairei_response_cache_hits_total";
+const CACHE_MISSES_METRIC: &str = "kairei_response_cache_misses_total";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    bounds: &'static [f64],
+    // バケットごとの件数（累積ではない）。最後は +Inf
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len() + 1],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    pub fn observe(&mut self, value: f64) {
+        let bucket = self
+            .bounds
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(self.bounds.len());
+        self.counts[bucket] += 1;
+        self.sum += value;
+        self.count += 1;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    /// Cumulative counts per upper bound, the last one being `+Inf`
+    pub fn buckets(&self) -> Vec<(f64, u64)> {
+        let mut cumulative = 0;
+        self.bounds
+            .iter()
+            .copied()
+            .chain(std::iter::once(f64::INFINITY))
+            .zip(&self.counts)
+            .map(|(bound, count)| {
+                cumulative += count;
+                (bound, cumulative)
+            })
+            .collect()
+    }
+}
+
+/// The histograms of one provider and model
+#[derive(Debug, Clone, PartialEq)]
+pub struct LlmSeries {
+    pub provider: String,
+    pub model: String,
+    pub latency: Histogram,
+    pub tokens: Histogram,
+}
+
+impl LlmSeries {
+    fn new(provider: &str, model: &str) -> Self {
+        Self {
+            provider: provider.to_string(),
+            model: model.to_string(),
+            latency: Histogram::new(&LATENCY_BUCKETS),
+            tokens: Histogram::new(&TOKEN_BUCKETS),
+        }
+    }
+}
+
+/// LLM call histograms of the providers of one registry
+#[derive(Debug, Default)]
+pub struct LlmMetrics {
+    series: Mutex<BTreeMap<(String, String), LlmSeries>>,
+}
+
+impl LlmMetrics {
+    /// Registers the series of `model` on `provider`, and the one its calls with
+    /// unconfigured models fall back to
+    pub fn register(&self, provider: &str, model: &str) {
+        let mut series = self.series.lock().unwrap();
+        for model in [model, OTHER_MODEL] {
+            series
+                .entry((provider.to_string(), model.to_string()))
+                .or_insert_with(|| LlmSeries::new(provider, model));
+        }
+    }
+
+    /// Records a successful call. `tokens` is the prompt + completion usage, if reported.
+    pub fn record(&self, provider: &str, model: &str, latency: Duration, tokens: Option<usize>) {
+        let mut series = self.series.lock().unwrap();
+        let key = (provider.to_string(), model.to_string());
+        let key = if series.contains_key(&key) {
+            key
+        } else {
+            (provider.to_string(), OTHER_MODEL.to_string())
+        };
+        let entry = series
+            .entry(key)
+            .or_insert_with(|| LlmSeries::new(provider, OTHER_MODEL));
+        entry.latency.observe(latency.as_secs_f64());
+        if let Some(tokens) = tokens {
+            entry.tokens.observe(tokens as f64);
+        }
+    }
+
+    /// Every series, ordered by provider and model
+    pub fn series(&self) -> Vec<LlmSeries> {
+        self.series.lock().unwrap().values().cloned().collect()
+    }
+
+    /// The histograms in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut encoder = PrometheusEncoder::default();
+        encoder.add(&[], self);
+        encoder.finish()
+    }
+}
+
+/// Renders the histograms of several [`LlmMetrics`] as one exposition, each with its
+/// own extra labels (e.g. the system they belong to)
+#[derive(Debug, Default)]
+pub struct PrometheusEncoder {
+    latency: String,
+    tokens: String,
+    cache_hits: String,
+    cache_misses: String,
+}
+
+impl PrometheusEncoder {
+    /// Adds every series of `metrics`, labeled with `labels` before provider and model
+    pub fn add(&mut self, labels: &[(&str, &str)], metrics: &LlmMetrics) {
+        for series in metrics.series() {
+            let mut all_labels = labels.to_vec();
+            all_labels.push(("provider", &series.provider));
+            all_labels.push(("model", &series.model));
+            write_histogram(
+                &mut self.latency,
+                LATENCY_METRIC,
+                &all_labels,
+                &series.latency,
+            );
+            write_histogram(&mut self.tokens, TOKENS_METRIC, &all_labels, &series.tokens);
+        }
+    }
+
+    /// Adds the response cache counters of one agent, labeled with `labels`
+    pub fn add_response_cache(&mut self, labels: &[(&str, &str)], stats: &ResponseCacheStats) {
+        let labels = format_labels(labels);
+        let _ = writeln!(
+            self.cache_hits,
+            "{CACHE_HITS_METRIC}{{{labels}}} {}",
+            stats.hits
+        );
+        let _ = writeln!(
+            self.cache_misses,
+            "{CACHE_MISSES_METRIC}{{{labels}}} {}",
+            stats.misses
+        );
+    }
+
+    pub fn finish(self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "# HELP {LATENCY_METRIC} Latency of successful LLM calls by provider and model"
+        );
+        let _ = writeln!(out, "# TYPE {LATENCY_METRIC} histogram");
+        out.push_str(&self.latency);
+        let _ = writeln!(
+            out,
+            "# HELP {TOKENS_METRIC} Tokens (prompt + completion) of LLM calls by provider and model"
+        );
+        let _ = writeln!(out, "# TYPE {TOKENS_METRIC} histogram");
+        out.push_str(&self.tokens);
+        // キャッシュを使うエージェントがなければカウンタ自体を出さない
+        if !self.cache_hits.is_empty() {
+            let _ = writeln!(
+                out,
+                "# HELP {CACHE_HITS_METRIC} Answer handler responses served from the response cache"
+            );
+            let _ = writeln!(out, "# TYPE {CACHE_HITS_METRIC} counter");
+            out.push_str(&self.cache_hits);
+            let _ = writeln!(
+                out,
+                "# HELP {CACHE_MISSES_METRIC} Cacheable answer handler requests not found in the response cache"
+            );
+            let _ = writeln!(out, "# TYPE {CACHE_MISSES_METRIC} counter");
+            out.push_str(&self.cache_misses);
+        }
+        out
+    }
+}
+
+fn format_labels(labels: &[(&str, &str)]) -> String {
+    labels
+        .iter()
+        .map(|(key, value)| format!("{}=\"{}\"", key, escape_label(value)))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn write_histogram(out: &mut String, name: &str, labels: &[(&str, &str)], histogram: &Histogram) {
+    let labels = format_labels(labels);
+    for (bound, count) in histogram.buckets() {
+        let le = if bound.is_infinite() {
+            "+Inf".to_string()
+        } else {
+            bound.to_string()
+        };
+        let _ = writeln!(out, "{name}_bucket{{{labels},le=\"{le}\"}} {count}");
+    }
+    let _ = writeln!(out, "{name}_sum{{{labels}}} {}", histogram.sum());
+    let _ = writeln!(out, "{name}_count{{{labels}}} {}", histogram.count());
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unconfigured_models_share_other_series() {
+        let metrics = LlmMetrics::default();
+        metrics.register("openai", "gpt-4o-mini");
+
+        metrics.record(
+            "openai",
+            "gpt-4o-mini",
+            Duration::from_millis(300),
+            Some(420),
+        );
+        metrics.record("openai", "gpt-4o", Duration::from_millis(700), None);
+        metrics.record("openai", "o1", Duration::from_secs(90), None);
+
+        let series = metrics.series();
+        let models: Vec<&str> = series.iter().map(|s| s.model.as_str()).collect();
+        assert_eq!(models, vec!["gpt-4o-mini", OTHER_MODEL]);
+        assert_eq!(series[0].latency.count(), 1);
+        assert_eq!(series[0].tokens.count(), 1);
+        assert_eq!(series[1].latency.count(), 2);
+        assert_eq!(series[1].tokens.count(), 0);
+        // 60 秒を超えた呼び出しは +Inf のバケットにだけ入る
+        let buckets = series[1].latency.buckets();
+        assert_eq!(buckets[buckets.len() - 2], (60.0, 1));
+        assert_eq!(buckets[buckets.len() - 1], (f64::INFINITY, 2));
+    }
+
+    #[test]
+    fn test_render_prometheus_text() {
+        let metrics = LlmMetrics::default();
+        metrics.register("default", "gpt-4o-mini");
+        metrics.record(
+            "default",
+            "gpt-4o-mini",
+            Duration::from_millis(200),
+            Some(300),
+        );
+
+        let mut encoder = PrometheusEncoder::default();
+        encoder.add(&[("system", "sys-\"1\"")], &metrics);
+        let text = encoder.finish();
+
+        assert!(text.contains("# TYPE kairei_llm_request_duration_seconds histogram"));
+        assert!(text.contains(
+            "kairei_llm_request_duration_seconds_bucket{system=\"sys-\\\"1\\\"\",provider=\"default\",model=\"gpt-4o-mini\",le=\"0.1\"} 0"
+        ));
+        assert!(text.contains(
+            "kairei_llm_request_duration_seconds_bucket{system=\"sys-\\\"1\\\"\",provider=\"default\",model=\"gpt-4o-mini\",le=\"0.25\"} 1"
+        ));
+        assert!(text.contains(
+            "kairei_llm_tokens_per_request_bucket{system=\"sys-\\\"1\\\"\",provider=\"default\",model=\"gpt-4o-mini\",le=\"+Inf\"} 1"
+        ));
+        assert!(text.contains(
+            "kairei_llm_tokens_per_request_sum{system=\"sys-\\\"1\\\"\",provider=\"default\",model=\"gpt-4o-mini\"} 300"
+        ));
+        assert!(text.contains(
+            "kairei_llm_request_duration_seconds_count{system=\"sys-\\\"1\\\"\",provider=\"default\",model=\"other\"} 0"
+        ));
+    }
+    #[test]
+    fn test_render_response_cache_counters() {
+        let text = PrometheusEncoder::default().finish();
+        assert!(!text.contains("kairei_response_cache_hits_total"));
+
+        let mut encoder = PrometheusEncoder::default();
+        let stats = ResponseCacheStats {
+            hits: 3,
+            misses: 2,
+            ..Default::default()
+        };
+        encoder.add_response_cache(&[("system", "sys-1"), ("agent", "Weather")], &stats);
+        let text = encoder.finish();
+
+        assert!(text.contains("# TYPE kairei_response_cache_hits_total counter"));
+        assert!(
+            text.contains("kairei_response_cache_hits_total{system=\"sys-1\",agent=\"Weather\"} 3")
+        );
+        assert!(
+            text.contains(
+                "kairei_response_cache_misses_total{system=\"sys-1\",agent=\"Weather\"} 2"
+            )
+        );
+    }
+}
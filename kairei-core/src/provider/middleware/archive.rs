@@ -0,0 +1,404 @@
+//! Prompt/response archiving middleware.
+//!
+//! Records are handed to a background worker through a bounded buffer and written
+//! in batches to an [`ArchiveSink`]. The provider call never waits on the sink: when
+//! the buffer is full the record is dropped and counted, and sink failures are only
+//! logged.
+
+use std::{
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::AsyncWriteExt,
+    sync::{mpsc, oneshot},
+};
+use tracing::warn;
+
+use super::{
+    MiddlewareStatus, ProviderMiddleware,
+    redaction::{PatternRedactor, Redactor},
+};
+use crate::{
+    config::PromptArchiveConfig,
+    provider::{
+        request::{ProviderContext, ProviderRequest, ProviderResponse},
+        types::{ProviderError, ProviderResult},
+    },
+    timestamp::Timestamp,
+};
+
+/// One archived provider call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveRecord {
+    pub timestamp: Timestamp,
+    pub provider: String,
+    pub agent_name: String,
+    pub session_id: String,
+    pub trace_id: String,
+    pub prompt: String,
+    pub response: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Destination of archived records
+#[async_trait]
+#[mockall::automock]
+pub trait ArchiveSink: Send + Sync {
+    async fn write_batch(&self, records: &[ArchiveRecord]) -> ProviderResult<()>;
+}
+
+/// Sink appending records as JSONL to one file per day in a local directory
+#[derive(Debug, Clone)]
+pub struct LocalDirectorySink {
+    directory: PathBuf,
+}
+
+impl LocalDirectorySink {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    fn file_path(&self) -> PathBuf {
+        self.directory
+            .join(format!("prompts-{}.jsonl", Utc::now().format("%Y%m%d")))
+    }
+}
+
+#[async_trait]
+impl ArchiveSink for LocalDirectorySink {
+    async fn write_batch(&self, records: &[ArchiveRecord]) -> ProviderResult<()> {
+        let mut lines = String::new();
+        for record in records {
+            let line = serde_json::to_string(record)
+                .map_err(|e| ProviderError::InternalError(e.to_string()))?;
+            lines.push_str(&line);
+            lines.push('\n');
+        }
+
+        tokio::fs::create_dir_all(&self.directory)
+            .await
+            .map_err(|e| ProviderError::InternalError(e.to_string()))?;
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.file_path())
+            .await
+            .map_err(|e| ProviderError::InternalError(e.to_string()))?;
+        file.write_all(lines.as_bytes())
+            .await
+            .map_err(|e| ProviderError::InternalError(e.to_string()))?;
+        file.flush()
+            .await
+            .map_err(|e| ProviderError::InternalError(e.to_string()))
+    }
+}
+
+enum ArchiveCommand {
+    Record(ArchiveRecord),
+    Flush(oneshot::Sender<()>),
+}
+
+#[derive(Debug, Default)]
+struct ArchiveCounters {
+    buffered: AtomicU64,
+    flushed: AtomicU64,
+    dropped: AtomicU64,
+}
+
+/// Middleware archiving every prompt and its response
+pub struct PromptArchiveMiddleware {
+    sender: mpsc::Sender<ArchiveCommand>,
+    redactors: Vec<Arc<dyn Redactor>>,
+    counters: Arc<ArchiveCounters>,
+}
+
+impl PromptArchiveMiddleware {
+    /// Start the archive worker writing to `sink`. Must be called within a tokio runtime.
+    pub fn new(config: &PromptArchiveConfig, sink: Arc<dyn ArchiveSink>) -> Self {
+        let (sender, receiver) = mpsc::channel(config.buffer_capacity.max(1));
+        let counters = Arc::new(ArchiveCounters::default());
+        tokio::spawn(run_worker(
+            receiver,
+            sink,
+            counters.clone(),
+            config.batch_size.max(1),
+            config.flush_interval,
+        ));
+        Self {
+            sender,
+            redactors: vec![],
+            counters,
+        }
+    }
+
+    /// Archive to the local directory of the config, with its redaction patterns installed
+    pub fn from_config(config: &PromptArchiveConfig) -> ProviderResult<Self> {
+        let redactor = PatternRedactor::new(&config.redact_patterns)?;
+        let middleware = Self::new(config, Arc::new(LocalDirectorySink::new(&config.directory)));
+        Ok(if redactor.is_empty() {
+            middleware
+        } else {
+            middleware.with_redactor(Arc::new(redactor))
+        })
+    }
+
+    pub fn with_redactor(mut self, redactor: Arc<dyn Redactor>) -> Self {
+        self.redactors.push(redactor);
+        self
+    }
+
+    /// Write all buffered records and wait until the sink has been called.
+    pub async fn flush(&self) {
+        let (tx, rx) = oneshot::channel();
+        if self.sender.send(ArchiveCommand::Flush(tx)).await.is_ok() {
+            let _ = rx.await;
+        }
+    }
+
+    fn redact(&self, text: &str) -> String {
+        self.redactors
+            .iter()
+            .fold(text.to_string(), |acc, redactor| redactor.redact(&acc))
+    }
+
+    fn enqueue(&self, record: ArchiveRecord) {
+        match self.sender.try_send(ArchiveCommand::Record(record)) {
+            Ok(()) => {
+                self.counters.buffered.fetch_add(1, Ordering::SeqCst);
+            }
+            Err(e) => {
+                self.counters.dropped.fetch_add(1, Ordering::SeqCst);
+                warn!("Prompt archive record dropped: {}", e);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ProviderMiddleware for PromptArchiveMiddleware {
+    fn name(&self) -> &str {
+        "prompt_archive"
+    }
+
+    async fn before_execute(
+        &self,
+        _context: &ProviderContext,
+        _request: &ProviderRequest,
+        _prompt: &str,
+    ) -> ProviderResult<()> {
+        // Prompt and response are archived together once the result is known
+        Ok(())
+    }
+
+    async fn after_execute(
+        &self,
+        _context: &ProviderContext,
+        request: &ProviderRequest,
+        prompt: &str,
+        result: &ProviderResult<ProviderResponse>,
+    ) -> ProviderResult<()> {
+        let (response, error) = match result {
+            Ok(response) => (Some(self.redact(&response.output)), None),
+            Err(e) => (None, Some(self.redact(&e.to_string()))),
+        };
+        self.enqueue(ArchiveRecord {
+            timestamp: Timestamp::default(),
+            provider: request.config.name.clone(),
+            agent_name: request.state.agent_name.clone(),
+            session_id: request.state.session_id.clone(),
+            trace_id: request.state.trace_id.clone(),
+            prompt: self.redact(prompt),
+            response,
+            error,
+        });
+        Ok(())
+    }
+
+    fn status(&self) -> MiddlewareStatus {
+        MiddlewareStatus {
+            buffered: self.counters.buffered.load(Ordering::SeqCst),
+            flushed: self.counters.flushed.load(Ordering::SeqCst),
+            dropped: self.counters.dropped.load(Ordering::SeqCst),
+        }
+    }
+}
+
+async fn run_worker(
+    mut receiver: mpsc::Receiver<ArchiveCommand>,
+    sink: Arc<dyn ArchiveSink>,
+    counters: Arc<ArchiveCounters>,
+    batch_size: usize,
+    flush_interval: Duration,
+) {
+    let mut batch = Vec::with_capacity(batch_size);
+    let mut ticker = tokio::time::interval(flush_interval);
+    loop {
+        tokio::select! {
+            command = receiver.recv() => match command {
+                Some(ArchiveCommand::Record(record)) => {
+                    batch.push(record);
+                    if batch.len() >= batch_size {
+                        write_batch(sink.as_ref(), &counters, &mut batch).await;
+                    }
+                }
+                Some(ArchiveCommand::Flush(done)) => {
+                    write_batch(sink.as_ref(), &counters, &mut batch).await;
+                    let _ = done.send(());
+                }
+                None => {
+                    write_batch(sink.as_ref(), &counters, &mut batch).await;
+                    break;
+                }
+            },
+            _ = ticker.tick() => {
+                write_batch(sink.as_ref(), &counters, &mut batch).await;
+            }
+        }
+    }
+}
+
+async fn write_batch(
+    sink: &dyn ArchiveSink,
+    counters: &ArchiveCounters,
+    batch: &mut Vec<ArchiveRecord>,
+) {
+    if batch.is_empty() {
+        return;
+    }
+    let count = batch.len() as u64;
+    let result = sink.write_batch(batch).await;
+    batch.clear();
+    counters.buffered.fetch_sub(count, Ordering::SeqCst);
+    match result {
+        Ok(()) => {
+            counters.flushed.fetch_add(count, Ordering::SeqCst);
+        }
+        Err(e) => {
+            counters.dropped.fetch_add(count, Ordering::SeqCst);
+            warn!("Prompt archive sink failed, {} records lost: {}", count, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::Notify;
+
+    use super::*;
+
+    fn config(batch_size: usize, buffer_capacity: usize) -> PromptArchiveConfig {
+        PromptArchiveConfig {
+            batch_size,
+            buffer_capacity,
+            flush_interval: Duration::from_secs(3600),
+            ..Default::default()
+        }
+    }
+
+    fn ok_result(output: &str) -> ProviderResult<ProviderResponse> {
+        Ok(ProviderResponse {
+            output: output.to_string(),
+            ..Default::default()
+        })
+    }
+
+    async fn archive(middleware: &PromptArchiveMiddleware, prompt: &str) {
+        middleware
+            .after_execute(
+                &ProviderContext::default(),
+                &ProviderRequest::default(),
+                prompt,
+                &ok_result("ok"),
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_records_are_batched() {
+        let mut sink = MockArchiveSink::new();
+        sink.expect_write_batch()
+            .withf(|records| records.len() == 2)
+            .times(1)
+            .returning(|_| Box::pin(async move { Ok(()) }));
+        let middleware = PromptArchiveMiddleware::new(&config(2, 8), Arc::new(sink));
+
+        archive(&middleware, "first").await;
+        archive(&middleware, "second").await;
+        middleware.flush().await;
+
+        assert_eq!(
+            middleware.status(),
+            MiddlewareStatus {
+                buffered: 0,
+                flushed: 2,
+                dropped: 0,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sink_failure_does_not_fail_call() {
+        let mut sink = MockArchiveSink::new();
+        sink.expect_write_batch().returning(|_| {
+            Box::pin(async move { Err(ProviderError::InternalError("disk full".to_string())) })
+        });
+        let middleware = PromptArchiveMiddleware::new(&config(1, 8), Arc::new(sink));
+
+        archive(&middleware, "prompt").await;
+        middleware.flush().await;
+
+        assert_eq!(middleware.status().dropped, 1);
+        assert_eq!(middleware.status().flushed, 0);
+    }
+
+    struct StalledSink {
+        entered: Arc<Notify>,
+        release: Arc<Notify>,
+    }
+
+    #[async_trait]
+    impl ArchiveSink for StalledSink {
+        async fn write_batch(&self, _records: &[ArchiveRecord]) -> ProviderResult<()> {
+            self.entered.notify_one();
+            self.release.notified().await;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_full_buffer_drops_instead_of_blocking() {
+        let entered = Arc::new(Notify::new());
+        let release = Arc::new(Notify::new());
+        let sink = StalledSink {
+            entered: entered.clone(),
+            release: release.clone(),
+        };
+        let middleware = PromptArchiveMiddleware::new(&config(1, 1), Arc::new(sink));
+
+        // The worker takes the first record and stalls in the sink
+        archive(&middleware, "first").await;
+        entered.notified().await;
+        // The second fills the buffer, the third is dropped
+        archive(&middleware, "second").await;
+        archive(&middleware, "third").await;
+
+        let status = middleware.status();
+        assert_eq!(status.dropped, 1);
+        assert_eq!(status.buffered, 2);
+
+        release.notify_one();
+        release.notify_one();
+    }
+}
@@ -0,0 +1,64 @@
+//! # Provider Middleware
+//!
+//! Middlewares wrap each `StandardProvider::execute` call. Unlike plugins, which
+//! contribute prompt sections, middlewares observe the fully generated prompt and
+//! the final result of the call, e.g. to archive them for compliance.
+//!
+//! Middlewares are installed per provider from its `plugin_configs`:
+//!
+//! - `prompt_archive` ([`PromptArchiveConfig`](crate::config::PromptArchiveConfig)):
+//!   [`archive::PromptArchiveMiddleware`] writing JSONL records to a local directory
+
+pub mod archive;
+pub mod redaction;
+
+use async_trait::async_trait;
+use serde::Serialize;
+
+use super::{
+    request::{ProviderContext, ProviderRequest, ProviderResponse},
+    types::ProviderResult,
+};
+
+/// Hooks around a single provider execution.
+///
+/// An error returned from a hook fails the provider call, so implementations that
+/// only observe traffic should swallow their own failures.
+#[async_trait]
+#[mockall::automock]
+pub trait ProviderMiddleware: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Called after the prompt has been generated and before it is sent to the LLM.
+    async fn before_execute(
+        &self,
+        context: &ProviderContext,
+        request: &ProviderRequest,
+        prompt: &str,
+    ) -> ProviderResult<()>;
+
+    /// Called with the result of the call, whether it succeeded or not.
+    async fn after_execute(
+        &self,
+        context: &ProviderContext,
+        request: &ProviderRequest,
+        prompt: &str,
+        result: &ProviderResult<ProviderResponse>,
+    ) -> ProviderResult<()>;
+
+    /// Current counters of the middleware. Middlewares without a buffer report zeros.
+    fn status(&self) -> MiddlewareStatus {
+        MiddlewareStatus::default()
+    }
+}
+
+/// Buffering counters reported by a middleware
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct MiddlewareStatus {
+    /// Records accepted but not yet written
+    pub buffered: u64,
+    /// Records written to the sink
+    pub flushed: u64,
+    /// Records lost because the buffer was full or the sink failed
+    pub dropped: u64,
+}
@@ -0,0 +1,74 @@
+//! Redaction hooks applied to text before it leaves the process.
+
+use regex::Regex;
+
+use crate::provider::types::{ProviderError, ProviderResult};
+
+/// Replacement text for redacted matches
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Masks sensitive content in free text.
+pub trait Redactor: Send + Sync {
+    fn redact(&self, text: &str) -> String;
+}
+
+/// Redactor replacing every match of a set of regex patterns with [`REDACTED`].
+#[derive(Debug, Clone)]
+pub struct PatternRedactor {
+    patterns: Vec<Regex>,
+}
+
+impl PatternRedactor {
+    pub fn new(patterns: &[String]) -> ProviderResult<Self> {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|e| {
+                    ProviderError::Configuration(format!(
+                        "Invalid redaction pattern '{}': {}",
+                        pattern, e
+                    ))
+                })
+            })
+            .collect::<ProviderResult<Vec<_>>>()?;
+        Ok(Self { patterns })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+}
+
+impl Redactor for PatternRedactor {
+    fn redact(&self, text: &str) -> String {
+        self.patterns.iter().fold(text.to_string(), |acc, pattern| {
+            pattern.replace_all(&acc, REDACTED).into_owned()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pattern_redactor() {
+        let redactor = PatternRedactor::new(&[
+            r"[\w.+-]+@[\w-]+\.[\w.]+".to_string(),
+            r"sk-[A-Za-z0-9]+".to_string(),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            redactor.redact("mail alice@example.com with key sk-abc123"),
+            "mail [REDACTED] with key [REDACTED]"
+        );
+        assert_eq!(redactor.redact("nothing here"), "nothing here");
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let result = PatternRedactor::new(&["(unclosed".to_string()]);
+        assert!(matches!(result, Err(ProviderError::Configuration(_))));
+    }
+}
@@ -77,13 +77,19 @@ pub mod provider_secret;
 
 pub mod capabilities;
 pub mod config;
+pub mod contribution;
+pub mod embedding;
+pub mod embedding_scheduler;
 pub mod generator;
 pub mod llm;
 pub mod llms;
+pub mod metrics;
+pub mod middleware;
 pub mod plugin;
 pub mod plugins;
 #[allow(clippy::module_inception)]
 pub mod provider;
 pub mod providers;
 pub mod request;
+pub mod response_schema;
 pub mod types;
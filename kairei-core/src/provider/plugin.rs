@@ -4,8 +4,8 @@ use crate::config::PluginConfig;
 
 use super::{
     capabilities::common::CapabilityType,
+    contribution::ContextContribution,
     llm::LLMResponse,
-    provider::Section,
     request::{ProviderContext, ProviderRequest},
     types::*,
 };
@@ -23,8 +23,9 @@ use serde::Serialize;
 /// Plugins follow a defined lifecycle:
 /// 1. Registration - Plugin is registered with the provider
 /// 2. Capability Declaration - Plugin declares its capabilities and requirements
-/// 3. Section Generation - Plugin contributes to prompt generation
-/// 4. Response Processing - Plugin processes LLM responses
+/// 3. Section Generation - Plugin contributes text or structured context to the prompt
+/// 4. Tool Calls - Plugin answers tool calls requested by the LLM (optional)
+/// 5. Response Processing - Plugin processes LLM responses
 ///
 /// ## Plugin Architecture
 ///
@@ -39,6 +40,7 @@ use serde::Serialize;
 /// ```ignore
 /// # use kairei_core::provider::plugin::ProviderPlugin;
 /// # use kairei_core::provider::plugin::PluginContext;
+/// # use kairei_core::provider::contribution::ContextContribution;
 /// # use kairei_core::provider::provider::Section;
 /// # use kairei_core::provider::capabilities::common::CapabilityType;
 /// # use kairei_core::provider::types::ProviderResult;
@@ -59,10 +61,13 @@ use serde::Serialize;
 /// #         CapabilityType::Memory // This plugin provides memory capability
 /// #     }
 /// #
-/// #     async fn generate_section<'a>(&self, context: &PluginContext<'a>) -> ProviderResult<Section> {
-/// #         // Generate a section for the prompt
+/// #     async fn generate_section<'a>(
+/// #         &self,
+/// #         context: &PluginContext<'a>,
+/// #     ) -> ProviderResult<ContextContribution> {
+/// #         // Contribute a text section to the prompt
 /// #         let section = Section::new("Remember previous context: ...");
-/// #         Ok(section)
+/// #         Ok(section.into())
 /// #     }
 /// #
 /// #     async fn process_response<'a>(
@@ -101,20 +106,26 @@ pub trait ProviderPlugin: Send + Sync {
     /// A `CapabilityType` representing the plugin's primary capability
     fn capability(&self) -> CapabilityType;
 
-    /// Generates a section for the prompt.
+    /// Generates the plugin's contribution to the request context.
     ///
     /// This method is called during prompt generation to allow the plugin
     /// to contribute content to the prompt. For example, a memory plugin
     /// might add relevant context from previous interactions.
     ///
+    /// Plain prompt text is a `TextSection`; records and tool definitions keep
+    /// their structure, so they can be rendered for the capabilities of the LLM.
+    ///
     /// # Parameters
     ///
     /// * `context` - The plugin context containing request information
     ///
     /// # Returns
     ///
-    /// A `ProviderResult` containing either a `Section` or an error
-    async fn generate_section<'a>(&self, context: &PluginContext<'a>) -> ProviderResult<Section>;
+    /// A `ProviderResult` containing either a `ContextContribution` or an error
+    async fn generate_section<'a>(
+        &self,
+        context: &PluginContext<'a>,
+    ) -> ProviderResult<ContextContribution>;
 
     /// Processes the LLM response.
     ///
@@ -135,6 +146,39 @@ pub trait ProviderPlugin: Send + Sync {
         context: &PluginContext<'a>,
         response: &LLMResponse,
     ) -> ProviderResult<()>;
+
+    /// Answers a tool call requested by the LLM.
+    ///
+    /// This method is called with every LLM response before the response is
+    /// processed. A plugin that recognizes the response as a call to one of its
+    /// tools returns the tool's result, which is added to the prompt before the
+    /// LLM is asked again.
+    ///
+    /// # Parameters
+    ///
+    /// * `context` - The plugin context containing request information
+    /// * `response` - The LLM response to inspect
+    ///
+    /// # Returns
+    ///
+    /// The text to send back to the LLM, or `None` when the response is not a
+    /// call to one of this plugin's tools
+    async fn handle_tool_call<'a>(
+        &self,
+        _context: &PluginContext<'a>,
+        _response: &LLMResponse,
+    ) -> ProviderResult<Option<String>> {
+        Ok(None)
+    }
+
+    /// Checks the credentials of the external service the plugin calls.
+    ///
+    /// # Returns
+    ///
+    /// The `CredentialStatus`, `Unknown` for plugins without credentials
+    async fn validate_credentials(&self) -> ProviderResult<CredentialStatus> {
+        Ok(CredentialStatus::Unknown)
+    }
 }
 
 /// # Plugin Context
@@ -0,0 +1,196 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::{
+    catalog::AgentCatalog,
+    config::{CatalogConfig, PluginConfig},
+    provider::{
+        capabilities::common::CapabilityType,
+        contribution::ContextContribution,
+        llm::LLMResponse,
+        plugin::{PluginContext, ProviderPlugin},
+        provider::Section,
+        types::ProviderResult,
+    },
+};
+
+/// 他のエージェントと受け付けるリクエストをプロンプトに載せ、LLM が委譲先を選べるようにするプラグイン。
+/// think の `with` ブロックの `catalog` 設定は、プロバイダーの設定より優先される。
+pub struct CatalogPlugin {
+    catalog: Arc<AgentCatalog>,
+    config: CatalogConfig,
+}
+
+impl CatalogPlugin {
+    pub fn new(catalog: Arc<AgentCatalog>, config: CatalogConfig) -> Self {
+        Self { catalog, config }
+    }
+
+    /// Lines of the agents other than `agent_name`, within the limits of `config`
+    fn render(&self, agent_name: &str, config: &CatalogConfig) -> String {
+        let entries: Vec<_> = self
+            .catalog
+            .entries()
+            .into_iter()
+            .filter(|entry| entry.name != agent_name)
+            .collect();
+        if entries.is_empty() {
+            return String::new();
+        }
+
+        let mut content = String::from("Agents you can send requests to:\n");
+        let mut listed = 0;
+        for entry in entries.iter().take(config.max_agents) {
+            let mut block = String::new();
+            for request in &entry.requests {
+                block.push_str(&format!("- {}: {}", entry.name, request));
+                if let Some(doc) = &request.doc {
+                    block.push_str(&format!(" -- {}", doc.lines().next().unwrap_or_default()));
+                }
+                block.push('\n');
+            }
+            if content.len() + block.len() > config.max_chars {
+                break;
+            }
+            content.push_str(&block);
+            listed += 1;
+        }
+        if listed == 0 {
+            return String::new();
+        }
+        if listed < entries.len() {
+            content.push_str(&format!(
+                "({} more agents not listed)\n",
+                entries.len() - listed
+            ));
+        }
+        content
+    }
+}
+
+#[async_trait]
+impl ProviderPlugin for CatalogPlugin {
+    fn priority(&self) -> i32 {
+        8 // リクエストコンテキストの後、ポリシーの前
+    }
+
+    #[tracing::instrument(skip(self, context))]
+    async fn generate_section<'a>(
+        &self,
+        context: &PluginContext<'a>,
+    ) -> ProviderResult<ContextContribution> {
+        let config = match context.configs.get("catalog") {
+            Some(PluginConfig::Catalog(config)) => config,
+            _ => &self.config,
+        };
+
+        Ok(Section {
+            content: self.render(&context.request.state.agent_name, config),
+            priority: self.priority(),
+            metadata: Default::default(),
+        }
+        .into())
+    }
+
+    fn capability(&self) -> CapabilityType {
+        CapabilityType::Catalog
+    }
+
+    async fn process_response<'a>(
+        &self,
+        _context: &PluginContext<'a>,
+        _response: &LLMResponse,
+    ) -> ProviderResult<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ast::{AnswerDef, HandlerBlock, MicroAgentDef, RequestHandler},
+        provider::plugins::provider_tests::TestContextHolder,
+    };
+
+    fn catalog(agents: &[(&str, &str)]) -> Arc<AgentCatalog> {
+        let catalog = AgentCatalog::default();
+        for (agent, request) in agents {
+            let agent_def = MicroAgentDef {
+                answer: Some(AnswerDef {
+                    handlers: vec![RequestHandler {
+                        request_type: (*request).into(),
+                        parameters: vec![],
+                        return_type: "String".into(),
+                        constraints: None,
+                        requires: None,
+                        block: HandlerBlock { statements: vec![] },
+                        doc: None,
+                        cache: None,
+                    }],
+                }),
+                ..Default::default()
+            };
+            catalog.insert(agent, agent, &agent_def);
+        }
+        Arc::new(catalog)
+    }
+
+    #[tokio::test]
+    async fn test_catalog_section_skips_the_calling_agent() -> ProviderResult<()> {
+        let plugin = CatalogPlugin::new(
+            catalog(&[("Orchestrator", "Route"), ("Weather", "GetForecast")]),
+            CatalogConfig::default(),
+        );
+        let mut context_holder = TestContextHolder::new("test request");
+        context_holder.request.state.agent_name = "Orchestrator".to_string();
+        let context = context_holder.get_plugin_context();
+
+        let contribution = plugin.generate_section(&context).await?;
+        let section = contribution.as_section().unwrap();
+
+        assert!(
+            section
+                .content
+                .contains("- Weather: GetForecast() -> String")
+        );
+        assert!(!section.content.contains("Orchestrator"));
+        assert_eq!(section.priority, 8);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_catalog_section_limits() -> ProviderResult<()> {
+        let agents = catalog(&[("A", "One"), ("B", "Two"), ("C", "Three")]);
+        let context_holder = TestContextHolder::new("test request");
+        let context = context_holder.get_plugin_context();
+
+        let plugin = CatalogPlugin::new(
+            agents.clone(),
+            CatalogConfig {
+                max_agents: 2,
+                ..Default::default()
+            },
+        );
+        let contribution = plugin.generate_section(&context).await?;
+        let section = contribution.as_section().unwrap();
+        assert!(section.content.contains("- B: Two()"));
+        assert!(!section.content.contains("- C:"));
+        assert!(section.content.contains("(1 more agents not listed)"));
+
+        let plugin = CatalogPlugin::new(
+            agents,
+            CatalogConfig {
+                max_chars: 60,
+                ..Default::default()
+            },
+        );
+        let contribution = plugin.generate_section(&context).await?;
+        let section = contribution.as_section().unwrap();
+        assert!(section.content.len() <= 60 + "(2 more agents not listed)\n".len());
+        assert!(section.content.contains("- A: One()"));
+        assert!(!section.content.contains("- B:"));
+        Ok(())
+    }
+}
@@ -5,6 +5,7 @@ use crate::{
     expression,
     provider::{
         capabilities::common::CapabilityType,
+        contribution::ContextContribution,
         llm::LLMResponse,
         plugin::{PluginContext, ProviderPlugin},
         provider::Section,
@@ -26,7 +27,10 @@ impl ProviderPlugin for GeneralPromptPlugin {
     }
 
     #[tracing::instrument(skip(self, context), level = "debug")]
-    async fn generate_section<'a>(&self, context: &PluginContext<'a>) -> ProviderResult<Section> {
+    async fn generate_section<'a>(
+        &self,
+        context: &PluginContext<'a>,
+    ) -> ProviderResult<ContextContribution> {
         // コンテキストからクエリを取得して基本的なセクションを生成
         let query = match context.request.input.query.clone() {
             expression::Value::String(s) => s,
@@ -51,7 +55,8 @@ impl ProviderPlugin for GeneralPromptPlugin {
             content,
             priority: self.priority(),
             ..Default::default()
-        })
+        }
+        .into())
     }
 
     #[tracing::instrument(skip(self, _context, _response), level = "debug")]
@@ -0,0 +1,88 @@
+use async_trait::async_trait;
+
+use crate::{
+    config::OutputFormat,
+    provider::{
+        capabilities::common::CapabilityType,
+        contribution::ContextContribution,
+        llm::LLMResponse,
+        plugin::{PluginContext, ProviderPlugin},
+        provider::Section,
+        types::ProviderResult,
+    },
+};
+
+/// JSON 出力を求められたとき、JSON モードを持たない LLM にはプロンプトで指示するプラグイン。
+/// LLM が `JsonMode` をサポートする場合、このプラグインは使われない。
+pub struct JsonOutputPlugin;
+
+#[async_trait]
+impl ProviderPlugin for JsonOutputPlugin {
+    fn priority(&self) -> i32 {
+        200 // 他のセクションより後、プロンプトの最後に置く
+    }
+
+    #[tracing::instrument(skip(self, context))]
+    async fn generate_section<'a>(
+        &self,
+        context: &PluginContext<'a>,
+    ) -> ProviderResult<ContextContribution> {
+        let content = match context.request.config.common_config.output_format {
+            OutputFormat::Json => "Respond with a single valid JSON object only. \
+                Do not wrap it in a code block or add any text before or after it.\n"
+                .to_string(),
+            OutputFormat::Text => String::new(),
+        };
+
+        Ok(Section {
+            content,
+            priority: self.priority(),
+            metadata: Default::default(),
+        }
+        .into())
+    }
+
+    fn capability(&self) -> CapabilityType {
+        CapabilityType::JsonMode
+    }
+
+    async fn process_response<'a>(
+        &self,
+        _context: &PluginContext<'a>,
+        _response: &LLMResponse,
+    ) -> ProviderResult<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::plugins::provider_tests::TestContextHolder;
+
+    #[tokio::test]
+    async fn test_json_output_section() -> ProviderResult<()> {
+        let mut context_holder = TestContextHolder::new("test request");
+        context_holder.request.config.common_config.output_format = OutputFormat::Json;
+        let context = context_holder.get_plugin_context();
+
+        let contribution = JsonOutputPlugin.generate_section(&context).await?;
+        let section = contribution.as_section().unwrap();
+
+        assert!(section.content.contains("valid JSON object"));
+        assert_eq!(section.priority, 200);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_text_output_adds_nothing() -> ProviderResult<()> {
+        let context_holder = TestContextHolder::new("test request");
+        let context = context_holder.get_plugin_context();
+
+        let contribution = JsonOutputPlugin.generate_section(&context).await?;
+        let section = contribution.as_section().unwrap();
+
+        assert!(section.content.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,185 @@
+//! Change notifications for SistenceMemory
+//!
+//! Agents register interest in a namespace, tag or topic with
+//! [`SistenceMemoryCapability::subscribe_changes`](crate::provider::capabilities::sistence_memory::SistenceMemoryCapability::subscribe_changes).
+//! When a matching item changes, the [`MemoryChangeNotifier`] publishes a
+//! `Custom("memory_changed")` event on the event bus with these parameters:
+//!
+//! | Parameter         | Value                                         |
+//! |-------------------|-----------------------------------------------|
+//! | `to`              | ID of the subscribed agent                    |
+//! | `subscription_id` | ID returned by `subscribe_changes`            |
+//! | `namespace`       | Memory namespace of the item                  |
+//! | `item_id`         | ID of the changed item                        |
+//! | `change_type`     | `"stored"`, `"updated"` or `"deleted"`        |
+//! | `changes`         | Number of changes coalesced into this event   |
+//!
+//! Changes to the same item within the coalescing window are reported once, after
+//! the window closes. The latest change wins, except that an item stored and then
+//! updated is still reported as stored.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use dashmap::DashMap;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::event::event_bus::{Event, EventBus, Value};
+use crate::event::event_registry::EventType;
+use crate::provider::capabilities::sistence_memory::{
+    MemoryChangeType, MemoryId, MemoryInterest, MemoryItem, MemorySubscriptionId,
+};
+
+/// Name of the custom event sent for memory changes
+pub const MEMORY_CHANGED_EVENT: &str = "memory_changed";
+
+#[derive(Debug, Clone)]
+struct Subscription {
+    agent_id: String,
+    interest: MemoryInterest,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PendingChange {
+    change_type: MemoryChangeType,
+    count: i64,
+}
+
+impl PendingChange {
+    fn merge(&mut self, change_type: MemoryChangeType) {
+        // 保存直後の更新は保存として通知する
+        if !(self.change_type == MemoryChangeType::Stored
+            && change_type == MemoryChangeType::Updated)
+        {
+            self.change_type = change_type;
+        }
+        self.count += 1;
+    }
+}
+
+/// Publishes coalesced `memory_changed` events for one memory namespace
+pub struct MemoryChangeNotifier {
+    namespace: String,
+    event_bus: Arc<EventBus>,
+    window: Duration,
+    subscriptions: DashMap<MemorySubscriptionId, Subscription>,
+    /// (subscription, item) ごとの通知待ちの変更
+    pending: Arc<Mutex<HashMap<(MemorySubscriptionId, MemoryId), PendingChange>>>,
+}
+
+impl MemoryChangeNotifier {
+    pub fn new(namespace: impl Into<String>, event_bus: Arc<EventBus>, window: Duration) -> Self {
+        Self {
+            namespace: namespace.into(),
+            event_bus,
+            window,
+            subscriptions: DashMap::new(),
+            pending: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn subscribe(&self, agent_id: &str, interest: MemoryInterest) -> MemorySubscriptionId {
+        let id = Uuid::new_v4().to_string();
+        self.subscriptions.insert(
+            id.clone(),
+            Subscription {
+                agent_id: agent_id.to_string(),
+                interest,
+            },
+        );
+        id
+    }
+
+    /// Returns whether the subscription existed
+    pub fn unsubscribe(&self, subscription_id: &str) -> bool {
+        self.subscriptions.remove(subscription_id).is_some()
+    }
+
+    pub fn has_subscriptions(&self) -> bool {
+        !self.subscriptions.is_empty()
+    }
+
+    /// Record a change to `item`, to be published once the coalescing window closes
+    pub fn notify(&self, change_type: MemoryChangeType, item: &MemoryItem) {
+        let matching: Vec<(MemorySubscriptionId, String)> = self
+            .subscriptions
+            .iter()
+            .filter(|entry| entry.interest.matches(item))
+            .map(|entry| (entry.key().clone(), entry.agent_id.clone()))
+            .collect();
+
+        for (subscription_id, agent_id) in matching {
+            let key = (subscription_id, item.id.clone());
+            {
+                let mut pending = self.pending.lock().unwrap();
+                if let Some(change) = pending.get_mut(&key) {
+                    change.merge(change_type);
+                    continue;
+                }
+                pending.insert(
+                    key.clone(),
+                    PendingChange {
+                        change_type,
+                        count: 1,
+                    },
+                );
+            }
+            self.schedule(key, agent_id);
+        }
+    }
+
+    fn schedule(&self, key: (MemorySubscriptionId, MemoryId), agent_id: String) {
+        let pending = Arc::clone(&self.pending);
+        let event_bus = Arc::clone(&self.event_bus);
+        let namespace = self.namespace.clone();
+        let window = self.window;
+        tokio::spawn(async move {
+            tokio::time::sleep(window).await;
+            let Some(change) = pending.lock().unwrap().remove(&key) else {
+                return;
+            };
+            let (subscription_id, item_id) = key;
+            let parameters = HashMap::from([
+                ("to".to_string(), Value::String(agent_id)),
+                (
+                    "subscription_id".to_string(),
+                    Value::String(subscription_id),
+                ),
+                ("namespace".to_string(), Value::String(namespace)),
+                ("item_id".to_string(), Value::String(item_id)),
+                (
+                    "change_type".to_string(),
+                    Value::String(change.change_type.as_str().to_string()),
+                ),
+                ("changes".to_string(), Value::Integer(change.count)),
+            ]);
+            let event = Event::new(
+                &EventType::Custom(MEMORY_CHANGED_EVENT.to_string()),
+                &parameters,
+            );
+            if let Err(error) = event_bus.publish(event).await {
+                warn!("Failed to publish memory change notification: {}", error);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stored_then_updated_stays_stored() {
+        let mut change = PendingChange {
+            change_type: MemoryChangeType::Stored,
+            count: 1,
+        };
+        change.merge(MemoryChangeType::Updated);
+        assert_eq!(change.change_type, MemoryChangeType::Stored);
+        change.merge(MemoryChangeType::Deleted);
+        assert_eq!(change.change_type, MemoryChangeType::Deleted);
+        assert_eq!(change.count, 3);
+    }
+}
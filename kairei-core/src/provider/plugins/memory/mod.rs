@@ -1,7 +1,10 @@
+pub mod change_notifier;
 pub mod persistent_shared_memory;
+pub mod shared_counters;
 pub mod shared_memory;
 pub mod shared_memory_adapter;
 pub mod single_memory;
+pub mod sistence_context;
 pub mod sistence_memory_adapter;
 pub mod sistence_memory_plugin;
 pub mod stateless_relevant_memory;
@@ -31,6 +31,8 @@
 use async_trait::async_trait;
 use chrono::Utc;
 use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -41,12 +43,14 @@ use tracing::error;
 
 use crate::event::event_registry::EventType;
 use crate::event_bus::{Event, EventBus};
+use crate::persistence::{self, Versioned};
 use crate::provider::capabilities::common::CapabilityType;
 use crate::provider::capabilities::shared_memory::{
     Metadata, SharedMemoryCapability, SharedMemoryError,
 };
 use crate::provider::capabilities::storage::{StorageBackend, StorageError, ValueWithMetadata};
 use crate::provider::config::plugins::PersistentSharedMemoryConfig;
+use crate::provider::contribution::ContextContribution;
 use crate::provider::llm::LLMResponse;
 use crate::provider::plugin::{PluginContext, ProviderPlugin};
 use crate::provider::provider::Section;
@@ -98,6 +102,9 @@ pub struct PersistentSharedMemoryPlugin {
 
     /// Event bus for notifications
     event_bus: Option<Arc<EventBus>>,
+
+    /// Orders the saves after compare-and-swap, so the backend ends with the last value
+    cas_save_lock: tokio::sync::Mutex<()>,
 }
 
 /// Event types for persistent shared memory operations
@@ -171,6 +178,7 @@ impl PersistentSharedMemoryPlugin {
             sync_cancel: None,
             last_sync: Arc::new(RwLock::new(None)),
             event_bus: None,
+            cas_save_lock: tokio::sync::Mutex::new(()),
         };
 
         // Initialize the instance
@@ -277,17 +285,16 @@ impl PersistentSharedMemoryPlugin {
                                 .publish(Event {
                                     event_type: EventType::Custom(PersistentMemoryEventType::SyncStarted.to_string()),
                                     parameters: HashMap::new(),
+                                    ..Default::default()
                                 })
                                 .await;
                         }
 
                         // Perform sync operation
-                        let data: HashMap<String, ValueWithMetadata> = cache
-                            .iter()
-                            .map(|entry| (entry.key().clone(), entry.value().clone()))
-                            .collect();
-
-                        let result = backend.save(&namespace, &data).await;
+                        let result = match to_stored_entries(&cache) {
+                            Ok(data) => backend.save(&namespace, &data).await,
+                            Err(err) => Err(err),
+                        };
 
                         // Update last sync time if successful
                         if result.is_ok() {
@@ -301,6 +308,7 @@ impl PersistentSharedMemoryPlugin {
                                     .publish(Event {
                                         event_type: EventType::Custom(PersistentMemoryEventType::SyncCompleted.to_string()),
                                         parameters: HashMap::new(),
+                                        ..Default::default()
                                     })
                                     .await;
                             }
@@ -314,6 +322,7 @@ impl PersistentSharedMemoryPlugin {
                                     .publish(Event {
                                         event_type: EventType::Custom(PersistentMemoryEventType::SyncFailed.to_string()),
                                         parameters: params,
+                                        ..Default::default()
                                     })
                                     .await;
                             }
@@ -381,6 +390,7 @@ impl PersistentSharedMemoryPlugin {
                         PersistentMemoryEventType::SyncStarted.to_string(),
                     ),
                     parameters: params,
+                    ..Default::default()
                 })
                 .await;
         }
@@ -408,6 +418,7 @@ impl PersistentSharedMemoryPlugin {
                             PersistentMemoryEventType::SyncCompleted.to_string(),
                         ),
                         parameters: params,
+                        ..Default::default()
                     })
                     .await;
             }
@@ -430,6 +441,7 @@ impl PersistentSharedMemoryPlugin {
                             PersistentMemoryEventType::SyncFailed.to_string(),
                         ),
                         parameters: params,
+                        ..Default::default()
                     })
                     .await;
             }
@@ -461,13 +473,18 @@ impl PersistentSharedMemoryPlugin {
                         PersistentMemoryEventType::LoadStarted.to_string(),
                     ),
                     parameters: params,
+                    ..Default::default()
                 })
                 .await;
         }
 
         // Load data from storage backend
         let namespace = &self.config.base.namespace;
-        let result = match self.backend.load(namespace).await {
+        let result = match self.backend.load(namespace).await.and_then(|data| {
+            data.into_iter()
+                .map(|(key, entry)| Ok((key, from_stored_entry(entry)?)))
+                .collect::<Result<Vec<_>, StorageError>>()
+        }) {
             Ok(data) => {
                 // Clear existing cache
                 self.cache.clear();
@@ -508,6 +525,7 @@ impl PersistentSharedMemoryPlugin {
                 .publish(Event {
                     event_type,
                     parameters: params,
+                    ..Default::default()
                 })
                 .await;
         }
@@ -537,23 +555,18 @@ impl PersistentSharedMemoryPlugin {
                         PersistentMemoryEventType::SaveStarted.to_string(),
                     ),
                     parameters: params,
+                    ..Default::default()
                 })
                 .await;
         }
 
-        // Convert cache to HashMap for storage
-        let data: HashMap<String, ValueWithMetadata> = self
-            .cache
-            .iter()
-            .map(|entry| (entry.key().clone(), entry.value().clone()))
-            .collect();
-
         // Save data to storage backend
         let namespace = &self.config.base.namespace;
-        let result = match self.backend.save(namespace, &data).await {
-            Ok(_) => Ok(()),
-            Err(err) => Err(SharedMemoryError::from(err)),
-        };
+        let result = match to_stored_entries(&self.cache) {
+            Ok(data) => self.backend.save(namespace, &data).await,
+            Err(err) => Err(err),
+        }
+        .map_err(SharedMemoryError::from);
 
         // Emit appropriate event based on result
         if let Some(ref event_bus) = self.event_bus {
@@ -581,6 +594,7 @@ impl PersistentSharedMemoryPlugin {
                 .publish(Event {
                     event_type,
                     parameters: params,
+                    ..Default::default()
                 })
                 .await;
         }
@@ -588,6 +602,94 @@ impl PersistentSharedMemoryPlugin {
         result
     }
 
+    /// Save a changed key to the backend if auto-save is configured
+    async fn auto_save_key(
+        &self,
+        key: &str,
+        value_with_metadata: &ValueWithMetadata,
+    ) -> Result<(), SharedMemoryError> {
+        if self.config.persistence.auto_save {
+            let backend = self.backend.clone_backend();
+            let namespace = self.config.base.namespace.clone();
+            let event_bus = self.event_bus.clone();
+
+            // Emit save started event
+            if let Some(ref event_bus) = event_bus {
+                let mut params = HashMap::new();
+                params.insert(
+                    "key".to_string(),
+                    crate::event::event_bus::Value::String(key.to_string()),
+                );
+
+                let _ = event_bus
+                    .publish(Event {
+                        event_type: EventType::Custom(
+                            PersistentMemoryEventType::SaveStarted.to_string(),
+                        ),
+                        parameters: params,
+                        ..Default::default()
+                    })
+                    .await;
+            }
+
+            // Save the specific key
+            let result = match to_stored_entry(value_with_metadata) {
+                Ok(entry) => backend.save_key(&namespace, key, &entry).await,
+                Err(err) => Err(err),
+            };
+            match result {
+                Ok(_) => {
+                    // Emit save completed event
+                    if let Some(ref event_bus) = event_bus {
+                        let mut params = HashMap::new();
+                        params.insert(
+                            "key".to_string(),
+                            crate::event::event_bus::Value::String(key.to_string()),
+                        );
+
+                        let _ = event_bus
+                            .publish(Event {
+                                event_type: EventType::Custom(
+                                    PersistentMemoryEventType::SaveCompleted.to_string(),
+                                ),
+                                parameters: params,
+                                ..Default::default()
+                            })
+                            .await;
+                    }
+                }
+                Err(e) => {
+                    // Emit save failed event
+                    if let Some(ref event_bus) = event_bus {
+                        let mut params = HashMap::new();
+                        params.insert(
+                            "key".to_string(),
+                            crate::event::event_bus::Value::String(key.to_string()),
+                        );
+                        params.insert(
+                            "error".to_string(),
+                            crate::event::event_bus::Value::String(format!("{}", e)),
+                        );
+
+                        let _ = event_bus
+                            .publish(Event {
+                                event_type: EventType::Custom(
+                                    PersistentMemoryEventType::SaveFailed.to_string(),
+                                ),
+                                parameters: params,
+                                ..Default::default()
+                            })
+                            .await;
+                    }
+
+                    return Err(SharedMemoryError::from(e));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     /// Calculate expiry instant based on TTL
     fn calculate_expiry(&self) -> Option<Instant> {
         if self.config.base.ttl.as_millis() > 0 {
@@ -641,9 +743,12 @@ impl ProviderPlugin for PersistentSharedMemoryPlugin {
         CapabilityType::SharedMemory
     }
 
-    async fn generate_section<'a>(&self, _context: &PluginContext<'a>) -> ProviderResult<Section> {
+    async fn generate_section<'a>(
+        &self,
+        _context: &PluginContext<'a>,
+    ) -> ProviderResult<ContextContribution> {
         // Persistent shared memory plugin doesn't generate prompt sections
-        Ok(Section::default())
+        Ok(Section::default().into())
     }
 
     async fn process_response<'a>(
@@ -731,81 +836,7 @@ impl SharedMemoryCapability for PersistentSharedMemoryPlugin {
         self.cache
             .insert(key.to_string(), value_with_metadata.clone());
 
-        // Auto-save if configured
-        if self.config.persistence.auto_save {
-            let backend = self.backend.clone_backend();
-            let namespace = self.config.base.namespace.clone();
-            let event_bus = self.event_bus.clone();
-
-            // Emit save started event
-            if let Some(ref event_bus) = event_bus {
-                let mut params = HashMap::new();
-                params.insert(
-                    "key".to_string(),
-                    crate::event::event_bus::Value::String(key.to_string()),
-                );
-
-                let _ = event_bus
-                    .publish(Event {
-                        event_type: EventType::Custom(
-                            PersistentMemoryEventType::SaveStarted.to_string(),
-                        ),
-                        parameters: params,
-                    })
-                    .await;
-            }
-
-            // Save the specific key
-            match backend
-                .save_key(&namespace, key, &value_with_metadata)
-                .await
-            {
-                Ok(_) => {
-                    // Emit save completed event
-                    if let Some(ref event_bus) = event_bus {
-                        let mut params = HashMap::new();
-                        params.insert(
-                            "key".to_string(),
-                            crate::event::event_bus::Value::String(key.to_string()),
-                        );
-
-                        let _ = event_bus
-                            .publish(Event {
-                                event_type: EventType::Custom(
-                                    PersistentMemoryEventType::SaveCompleted.to_string(),
-                                ),
-                                parameters: params,
-                            })
-                            .await;
-                    }
-                }
-                Err(e) => {
-                    // Emit save failed event
-                    if let Some(ref event_bus) = event_bus {
-                        let mut params = HashMap::new();
-                        params.insert(
-                            "key".to_string(),
-                            crate::event::event_bus::Value::String(key.to_string()),
-                        );
-                        params.insert(
-                            "error".to_string(),
-                            crate::event::event_bus::Value::String(format!("{}", e)),
-                        );
-
-                        let _ = event_bus
-                            .publish(Event {
-                                event_type: EventType::Custom(
-                                    PersistentMemoryEventType::SaveFailed.to_string(),
-                                ),
-                                parameters: params,
-                            })
-                            .await;
-                    }
-
-                    return Err(SharedMemoryError::from(e));
-                }
-            }
-        }
+        self.auto_save_key(key, &value_with_metadata).await?;
 
         Ok(())
     }
@@ -832,6 +863,7 @@ impl SharedMemoryCapability for PersistentSharedMemoryPlugin {
                                 PersistentMemoryEventType::SaveStarted.to_string(),
                             ),
                             parameters: params,
+                            ..Default::default()
                         })
                         .await;
                 }
@@ -853,6 +885,7 @@ impl SharedMemoryCapability for PersistentSharedMemoryPlugin {
                                         PersistentMemoryEventType::SaveCompleted.to_string(),
                                     ),
                                     parameters: params,
+                                    ..Default::default()
                                 })
                                 .await;
                         }
@@ -876,6 +909,7 @@ impl SharedMemoryCapability for PersistentSharedMemoryPlugin {
                                         PersistentMemoryEventType::SaveFailed.to_string(),
                                     ),
                                     parameters: params,
+                                    ..Default::default()
                                 })
                                 .await;
                         }
@@ -992,6 +1026,117 @@ impl SharedMemoryCapability for PersistentSharedMemoryPlugin {
 
         Ok(result)
     }
+
+    async fn compare_and_swap(
+        &self,
+        key: &str,
+        expected: Option<&Value>,
+        value: Value,
+    ) -> Result<bool, SharedMemoryError> {
+        // Validate key
+        self.validate_key(key)?;
+
+        // Only a new key can exceed the capacity. The check locks the whole cache,
+        // so it runs before the entry is locked.
+        if expected.is_none() {
+            self.check_capacity()?;
+        }
+
+        let size = serde_json::to_string(&value)
+            .map_err(|e| SharedMemoryError::InvalidValue(e.to_string()))?
+            .len();
+        let now = Utc::now();
+        let expiry = self.calculate_expiry();
+
+        // The entry stays locked from the comparison to the write
+        match self.cache.entry(key.to_string()) {
+            Entry::Occupied(mut entry) => {
+                let live = entry
+                    .get()
+                    .expiry
+                    .is_none_or(|expiry| Instant::now() < expiry);
+                let current = live.then(|| &entry.get().value);
+                if current != expected {
+                    return Ok(false);
+                }
+                let metadata = Metadata {
+                    created_at: entry.get().metadata.created_at,
+                    last_modified: now,
+                    content_type: "application/json".to_string(),
+                    size,
+                    tags: entry.get().metadata.tags.clone(),
+                };
+                entry.insert(ValueWithMetadata {
+                    value,
+                    metadata,
+                    expiry,
+                });
+            }
+            Entry::Vacant(entry) => {
+                if expected.is_some() {
+                    return Ok(false);
+                }
+                entry.insert(ValueWithMetadata {
+                    value,
+                    metadata: Metadata {
+                        created_at: now,
+                        last_modified: now,
+                        content_type: "application/json".to_string(),
+                        size,
+                        tags: Default::default(),
+                    },
+                    expiry,
+                });
+            }
+        }
+
+        // 並行した保存が古い値で上書きしないよう、順に最新の値を保存する
+        let _guard = self.cas_save_lock.lock().await;
+        let latest = self.cache.get(key).map(|entry| entry.value().clone());
+        if let Some(latest) = latest {
+            self.auto_save_key(key, &latest).await?;
+        }
+
+        Ok(true)
+    }
+}
+
+/// Persisted form of a shared memory value
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub(crate) struct SharedMemoryValue(pub(crate) Value);
+
+impl Versioned for SharedMemoryValue {
+    const KIND: &'static str = "shared memory value";
+    const VERSION: u32 = 1;
+}
+
+/// Wrap the value of an entry in its envelope before it is handed to the backend
+pub(crate) fn to_stored_entry(
+    entry: &ValueWithMetadata,
+) -> Result<ValueWithMetadata, StorageError> {
+    Ok(ValueWithMetadata {
+        value: persistence::to_stored(&SharedMemoryValue(entry.value.clone()))?,
+        metadata: entry.metadata.clone(),
+        expiry: entry.expiry,
+    })
+}
+
+/// Read an entry loaded from the backend, written by this or an earlier version
+pub(crate) fn from_stored_entry(
+    entry: ValueWithMetadata,
+) -> Result<ValueWithMetadata, StorageError> {
+    let SharedMemoryValue(value) = persistence::from_stored(entry.value)?;
+    Ok(ValueWithMetadata { value, ..entry })
+}
+
+fn to_stored_entries(
+    cache: &DashMap<String, ValueWithMetadata>,
+) -> Result<HashMap<String, ValueWithMetadata>, StorageError> {
+    cache
+        .iter()
+        .map(|entry| Ok((entry.key().clone(), to_stored_entry(entry.value())?)))
+        .collect()
 }
 
 /// Dummy storage backend for testing
@@ -1198,7 +1343,10 @@ mod tests {
         assert!(saved.contains_key(&plugin.config.base.namespace));
         let namespace_data = &saved[&plugin.config.base.namespace];
         assert!(namespace_data.contains_key("test_key"));
-        assert_eq!(namespace_data["test_key"].value, json!("test_value"));
+        assert_eq!(
+            namespace_data["test_key"].value,
+            json!({"version": 1, "payload": "test_value"})
+        );
     }
 
     #[tokio::test]
@@ -1259,7 +1407,47 @@ mod tests {
         assert!(namespace_data.contains_key("auto_save_key"));
         assert_eq!(
             namespace_data["auto_save_key"].value,
-            json!("auto_save_value")
+            json!({"version": 1, "payload": "auto_save_value"})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_reads_saved_envelopes() {
+        let mock_backend = Arc::new(MockStorageBackend::new());
+        let mut config = PersistentSharedMemoryConfig::default();
+        config.persistence.sync_interval = Duration::from_secs(3600); // Long interval to avoid auto-sync
+
+        let mut plugin = PersistentSharedMemoryPlugin::new(config.clone()).await;
+        plugin.backend = mock_backend.clone_backend();
+        plugin.set("user", json!({"name": "Alice"})).await.unwrap();
+        plugin.save().await.unwrap();
+
+        let mut restarted = PersistentSharedMemoryPlugin::new(config).await;
+        restarted.backend = mock_backend.clone_backend();
+        restarted.load().await.unwrap();
+        assert_eq!(
+            restarted.get("user").await.unwrap(),
+            json!({"name": "Alice"})
+        );
+
+        // 未知のバージョンがあれば読み込みは失敗し、キャッシュは変わらない
+        mock_backend
+            .saved_data
+            .write()
+            .await
+            .get_mut(&restarted.config.base.namespace)
+            .unwrap()
+            .get_mut("user")
+            .unwrap()
+            .value = json!({"version": 2, "payload": {"name": "Bob"}});
+        let err = restarted.load().await.unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Unsupported shared memory value version 2")
+        );
+        assert_eq!(
+            restarted.get("user").await.unwrap(),
+            json!({"name": "Alice"})
         );
     }
 
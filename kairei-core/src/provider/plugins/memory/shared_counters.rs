@@ -0,0 +1,359 @@
+//! Counters and rate limiters shared by agents.
+//!
+//! Every operation reads the current value from a [`SharedMemoryCapability`] and
+//! writes the new one with [`SharedMemoryCapability::compare_and_swap`], reading
+//! again when another agent changed it in between. Concurrent updates are therefore
+//! never lost. Backed by a
+//! [`PersistentSharedMemoryPlugin`](super::persistent_shared_memory::PersistentSharedMemoryPlugin),
+//! the values are saved to its storage backend and survive restarts.
+//!
+//! The DSL reaches them through the built-ins `increment(key, by)`,
+//! `decrement_if_positive(key)` and `try_acquire(key, rate, per)`.
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use thiserror::Error;
+
+use crate::clock::{Clock, SystemClock};
+use crate::provider::capabilities::shared_memory::{SharedMemoryCapability, SharedMemoryError};
+use crate::provider::config::plugins::SharedMemoryConfig;
+
+use super::shared_memory::InMemorySharedMemoryPlugin;
+
+const COUNTER_PREFIX: &str = "counter:";
+const RATE_LIMITER_PREFIX: &str = "rate_limiter:";
+
+#[derive(Debug, Error)]
+pub enum SharedCounterError {
+    #[error("Shared memory error: {0}")]
+    Memory(#[from] SharedMemoryError),
+    #[error("Shared counter {key} holds {value}, not a counter")]
+    InvalidValue { key: String, value: Value },
+    #[error("Shared counter {key} would overflow")]
+    Overflow { key: String },
+    #[error("Invalid rate limit: {0}")]
+    InvalidRateLimit(String),
+}
+
+pub type SharedCounterResult<T> = Result<T, SharedCounterError>;
+
+/// A token bucket holding up to `rate` tokens that refills at `rate` tokens per `per`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub rate: u32,
+    pub per: Duration,
+}
+
+/// Stored state of a rate limiter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Bucket {
+    tokens: f64,
+    /// Milliseconds since the UNIX epoch
+    updated_at: i64,
+}
+
+/// Counters and rate limiters in a shared memory, see the [module docs](self)
+pub struct SharedCounters {
+    memory: Arc<dyn SharedMemoryCapability>,
+    clock: Arc<dyn Clock>,
+    // Clock の Instant は保存できないので、作成時の時刻を基準に実時刻へ直す
+    epoch: (Instant, DateTime<Utc>),
+}
+
+impl SharedCounters {
+    pub fn new(memory: Arc<dyn SharedMemoryCapability>) -> Self {
+        Self::with_clock(memory, Arc::new(SystemClock))
+    }
+
+    /// Uses `clock` for refilling rate limiters, e.g. a `MockClock` in tests
+    pub fn with_clock(memory: Arc<dyn SharedMemoryCapability>, clock: Arc<dyn Clock>) -> Self {
+        let epoch = (clock.now(), Utc::now());
+        Self {
+            memory,
+            clock,
+            epoch,
+        }
+    }
+
+    /// The value of a counter, 0 if it was never changed
+    pub async fn get(&self, key: &str) -> SharedCounterResult<i64> {
+        let key = format!("{}{}", COUNTER_PREFIX, key);
+        let current = self.read(&key).await?;
+        counter_value(&key, current.as_ref())
+    }
+
+    /// Adds `by` to a counter that starts at 0 and returns the new value
+    pub async fn increment(&self, key: &str, by: i64) -> SharedCounterResult<i64> {
+        let key = format!("{}{}", COUNTER_PREFIX, key);
+        loop {
+            let current = self.read(&key).await?;
+            let value = counter_value(&key, current.as_ref())?
+                .checked_add(by)
+                .ok_or_else(|| SharedCounterError::Overflow { key: key.clone() })?;
+            if self.swap(&key, current.as_ref(), json!(value)).await? {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// Takes 1 from a counter if it is positive. Returns whether it did.
+    pub async fn decrement_if_positive(&self, key: &str) -> SharedCounterResult<bool> {
+        let key = format!("{}{}", COUNTER_PREFIX, key);
+        loop {
+            let current = self.read(&key).await?;
+            let value = counter_value(&key, current.as_ref())?;
+            if value <= 0 {
+                return Ok(false);
+            }
+            if self.swap(&key, current.as_ref(), json!(value - 1)).await? {
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Takes a token from the rate limiter `key`, which starts full. Returns whether
+    /// one was left. A refused call does not change the limiter.
+    pub async fn try_acquire(&self, key: &str, limit: RateLimit) -> SharedCounterResult<bool> {
+        if limit.rate == 0 || limit.per.is_zero() {
+            return Err(SharedCounterError::InvalidRateLimit(format!(
+                "{} per {:?} never allows a call",
+                limit.rate, limit.per
+            )));
+        }
+        let key = format!("{}{}", RATE_LIMITER_PREFIX, key);
+        let capacity = f64::from(limit.rate);
+        loop {
+            let current = self.read(&key).await?;
+            let now = self.now_millis();
+            let tokens = match &current {
+                Some(value) => {
+                    let bucket = serde_json::from_value::<Bucket>(value.clone()).map_err(|_| {
+                        SharedCounterError::InvalidValue {
+                            key: key.clone(),
+                            value: value.clone(),
+                        }
+                    })?;
+                    // 時計が戻っても補充はしない
+                    let elapsed = now.saturating_sub(bucket.updated_at).max(0) as f64;
+                    let refill = elapsed / limit.per.as_millis() as f64 * capacity;
+                    (bucket.tokens + refill).min(capacity)
+                }
+                None => capacity,
+            };
+            if tokens < 1.0 {
+                return Ok(false);
+            }
+            let bucket = Bucket {
+                tokens: tokens - 1.0,
+                updated_at: now,
+            };
+            let value = serde_json::to_value(bucket)
+                .map_err(|e| SharedMemoryError::InvalidValue(e.to_string()))?;
+            if self.swap(&key, current.as_ref(), value).await? {
+                return Ok(true);
+            }
+        }
+    }
+
+    async fn read(&self, key: &str) -> SharedCounterResult<Option<Value>> {
+        match self.memory.get(key).await {
+            Ok(value) => Ok(Some(value)),
+            Err(SharedMemoryError::KeyNotFound(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn swap(
+        &self,
+        key: &str,
+        expected: Option<&Value>,
+        value: Value,
+    ) -> SharedCounterResult<bool> {
+        let swapped = self.memory.compare_and_swap(key, expected, value).await?;
+        if !swapped {
+            // 他のエージェントが先に書いたので、読み直す前に譲る
+            tokio::task::yield_now().await;
+        }
+        Ok(swapped)
+    }
+
+    fn now_millis(&self) -> i64 {
+        let elapsed = self.clock.now().saturating_duration_since(self.epoch.0);
+        (self.epoch.1 + chrono::Duration::from_std(elapsed).unwrap_or(chrono::Duration::zero()))
+            .timestamp_millis()
+    }
+}
+
+fn counter_value(key: &str, value: Option<&Value>) -> SharedCounterResult<i64> {
+    match value {
+        None => Ok(0),
+        Some(value) => value
+            .as_i64()
+            .ok_or_else(|| SharedCounterError::InvalidValue {
+                key: key.to_string(),
+                value: value.clone(),
+            }),
+    }
+}
+
+impl Default for SharedCounters {
+    /// Counters in memory that do not expire
+    fn default() -> Self {
+        Self::new(Arc::new(InMemorySharedMemoryPlugin::new(
+            SharedMemoryConfig {
+                max_keys: 0,
+                ttl: Duration::ZERO,
+                namespace: "shared_counters".to_string(),
+                ..Default::default()
+            },
+        )))
+    }
+}
+
+impl fmt::Debug for SharedCounters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedCounters")
+            .field("clock", &self.clock)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    fn counters(clock: Arc<MockClock>) -> Arc<SharedCounters> {
+        let memory = SharedCounters::default().memory;
+        Arc::new(SharedCounters::with_clock(memory, clock))
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_increments_are_not_lost() {
+        let counters = counters(Arc::new(MockClock::new()));
+
+        // 32 のエージェントが 50 回ずつ同じカウンターを増やす
+        let tasks: Vec<_> = (0..32)
+            .map(|_| {
+                let counters = counters.clone();
+                tokio::spawn(async move {
+                    for _ in 0..50 {
+                        counters.increment("visits", 1).await.unwrap();
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert_eq!(counters.get("visits").await.unwrap(), 32 * 50);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_decrement_if_positive_stops_at_zero() {
+        let counters = counters(Arc::new(MockClock::new()));
+        counters.increment("seats", 10).await.unwrap();
+
+        let tasks: Vec<_> = (0..25)
+            .map(|_| {
+                let counters = counters.clone();
+                tokio::spawn(async move { counters.decrement_if_positive("seats").await.unwrap() })
+            })
+            .collect();
+        let mut taken = 0;
+        for task in tasks {
+            taken += task.await.unwrap() as i64;
+        }
+
+        assert_eq!(taken, 10);
+        assert_eq!(counters.get("seats").await.unwrap(), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_rate_limiter_blocks_past_its_rate() {
+        let clock = Arc::new(MockClock::new());
+        let counters = counters(clock.clone());
+        let limit = RateLimit {
+            rate: 5,
+            per: Duration::from_secs(10),
+        };
+
+        // 20 のエージェントが同時に呼んでも通るのは 5 回だけ
+        let tasks: Vec<_> = (0..20)
+            .map(|_| {
+                let counters = counters.clone();
+                tokio::spawn(async move { counters.try_acquire("api", limit).await.unwrap() })
+            })
+            .collect();
+        let mut allowed = 0;
+        for task in tasks {
+            allowed += task.await.unwrap() as u32;
+        }
+        assert_eq!(allowed, 5);
+
+        // 2 秒で 1 つ補充される
+        clock.advance(Duration::from_secs(2));
+        assert!(counters.try_acquire("api", limit).await.unwrap());
+        assert!(!counters.try_acquire("api", limit).await.unwrap());
+
+        // 別のキーは別の上限
+        assert!(counters.try_acquire("search", limit).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_counters_survive_a_restart() {
+        use crate::provider::config::plugins::{
+            BackendSpecificConfig, BackendType, LocalFileSystemConfig, PersistenceConfig,
+            PersistentSharedMemoryConfig,
+        };
+        use crate::provider::plugins::memory::persistent_shared_memory::PersistentSharedMemoryPlugin;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = PersistentSharedMemoryConfig {
+            base: SharedMemoryConfig {
+                ttl: Duration::ZERO,
+                namespace: "quota".to_string(),
+                ..Default::default()
+            },
+            persistence: PersistenceConfig {
+                backend_type: BackendType::LocalFileSystem,
+                sync_interval: Duration::ZERO,
+                auto_load: true,
+                auto_save: true,
+                backend_config: BackendSpecificConfig::Local(LocalFileSystemConfig {
+                    base_dir: dir.path().to_string_lossy().to_string(),
+                    file_extension: "json".to_string(),
+                }),
+            },
+        };
+
+        let counters = SharedCounters::new(Arc::new(
+            PersistentSharedMemoryPlugin::new(config.clone()).await,
+        ));
+        counters.increment("calls", 3).await.unwrap();
+        counters.increment("calls", 4).await.unwrap();
+        drop(counters);
+
+        let restarted =
+            SharedCounters::new(Arc::new(PersistentSharedMemoryPlugin::new(config).await));
+        assert_eq!(restarted.get("calls").await.unwrap(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_counter_rejects_other_values() {
+        let memory: Arc<dyn SharedMemoryCapability> = SharedCounters::default().memory;
+        memory.set("counter:name", json!("Alice")).await.unwrap();
+        let counters = SharedCounters::new(memory);
+
+        assert!(matches!(
+            counters.increment("name", 1).await,
+            Err(SharedCounterError::InvalidValue { .. })
+        ));
+    }
+}
@@ -34,6 +34,7 @@
 use async_trait::async_trait;
 use chrono::Utc;
 use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
 use glob::Pattern;
 use serde_json::Value;
 use std::sync::Arc;
@@ -44,6 +45,7 @@ use crate::provider::capabilities::shared_memory::{
     Metadata, SharedMemoryCapability, SharedMemoryError,
 };
 use crate::provider::config::plugins::SharedMemoryConfig;
+use crate::provider::contribution::ContextContribution;
 use crate::provider::llm::LLMResponse;
 use crate::provider::plugin::{PluginContext, ProviderPlugin};
 use crate::provider::provider::Section;
@@ -180,9 +182,12 @@ impl ProviderPlugin for InMemorySharedMemoryPlugin {
         CapabilityType::SharedMemory
     }
 
-    async fn generate_section<'a>(&self, _context: &PluginContext<'a>) -> ProviderResult<Section> {
+    async fn generate_section<'a>(
+        &self,
+        _context: &PluginContext<'a>,
+    ) -> ProviderResult<ContextContribution> {
         // Shared memory plugin doesn't generate prompt sections
-        Ok(Section::default())
+        Ok(Section::default().into())
     }
 
     async fn process_response<'a>(
@@ -365,6 +370,72 @@ impl SharedMemoryCapability for InMemorySharedMemoryPlugin {
 
         Ok(result)
     }
+
+    async fn compare_and_swap(
+        &self,
+        key: &str,
+        expected: Option<&Value>,
+        value: Value,
+    ) -> Result<bool, SharedMemoryError> {
+        // Validate key
+        self.validate_key(key)?;
+
+        // Only a new key can exceed the capacity. The check locks the whole map,
+        // so it runs before the entry is locked.
+        if expected.is_none() {
+            self.check_capacity()?;
+        }
+
+        let size = serde_json::to_string(&value)
+            .map_err(|e| SharedMemoryError::InvalidValue(e.to_string()))?
+            .len();
+        let now = Utc::now();
+        let expiry = self.calculate_expiry();
+
+        // The entry stays locked from the comparison to the write
+        match self.data.entry(key.to_string()) {
+            Entry::Occupied(mut entry) => {
+                let live = entry
+                    .get()
+                    .expiry
+                    .is_none_or(|expiry| Instant::now() < expiry);
+                let current = live.then(|| &entry.get().value);
+                if current != expected {
+                    return Ok(false);
+                }
+                let metadata = Metadata {
+                    created_at: entry.get().metadata.created_at,
+                    last_modified: now,
+                    content_type: "application/json".to_string(),
+                    size,
+                    tags: entry.get().metadata.tags.clone(),
+                };
+                entry.insert(ValueWithMetadata {
+                    value,
+                    metadata,
+                    expiry,
+                });
+            }
+            Entry::Vacant(entry) => {
+                if expected.is_some() {
+                    return Ok(false);
+                }
+                entry.insert(ValueWithMetadata {
+                    value,
+                    metadata: Metadata {
+                        created_at: now,
+                        last_modified: now,
+                        content_type: "application/json".to_string(),
+                        size,
+                        tags: Default::default(),
+                    },
+                    expiry,
+                });
+            }
+        }
+
+        Ok(true)
+    }
 }
 
 #[cfg(test)]
@@ -644,4 +715,44 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_compare_and_swap() {
+        let plugin = create_test_plugin();
+
+        // 存在しないキーは None からのみ書ける
+        assert!(
+            !plugin
+                .compare_and_swap("cas", Some(&json!(0)), json!(1))
+                .await
+                .unwrap()
+        );
+        assert!(
+            plugin
+                .compare_and_swap("cas", None, json!(1))
+                .await
+                .unwrap()
+        );
+        assert!(
+            !plugin
+                .compare_and_swap("cas", None, json!(2))
+                .await
+                .unwrap()
+        );
+
+        // 期待値が違えば何も変えない
+        assert!(
+            !plugin
+                .compare_and_swap("cas", Some(&json!(5)), json!(2))
+                .await
+                .unwrap()
+        );
+        assert!(
+            plugin
+                .compare_and_swap("cas", Some(&json!(1)), json!(2))
+                .await
+                .unwrap()
+        );
+        assert_eq!(plugin.get("cas").await.unwrap(), json!(2));
+    }
 }
@@ -11,9 +11,9 @@ use serde_json::Value;
 use crate::provider::{
     capabilities::common::CapabilityType,
     capabilities::shared_memory::{Metadata, SharedMemoryCapability, SharedMemoryError},
+    contribution::ContextContribution,
     llm::LLMResponse,
     plugin::{PluginContext, ProviderPlugin},
-    provider::Section,
     types::ProviderResult,
 };
 
@@ -41,7 +41,10 @@ impl ProviderPlugin for SharedMemoryPluginAdapter {
         CapabilityType::SharedMemory
     }
 
-    async fn generate_section<'a>(&self, context: &PluginContext<'a>) -> ProviderResult<Section> {
+    async fn generate_section<'a>(
+        &self,
+        context: &PluginContext<'a>,
+    ) -> ProviderResult<ContextContribution> {
         // Delegate to the wrapped plugin
         self.plugin.generate_section(context).await
     }
@@ -82,6 +85,15 @@ impl SharedMemoryCapability for SharedMemoryPluginAdapter {
     async fn list_keys(&self, pattern: &str) -> Result<Vec<String>, SharedMemoryError> {
         self.plugin.list_keys(pattern).await
     }
+
+    async fn compare_and_swap(
+        &self,
+        key: &str,
+        expected: Option<&Value>,
+        value: Value,
+    ) -> Result<bool, SharedMemoryError> {
+        self.plugin.compare_and_swap(key, expected, value).await
+    }
 }
 
 #[cfg(test)]
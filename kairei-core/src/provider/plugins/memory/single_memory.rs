@@ -7,9 +7,9 @@ use tokio::sync::RwLock;
 
 use crate::config::MemoryConfig;
 use crate::provider::capabilities::common::CapabilityType;
+use crate::provider::contribution::{ContextContribution, RecordRendering};
 use crate::provider::llm::LLMResponse;
 use crate::provider::plugin::{PluginContext, ProviderPlugin};
-use crate::provider::provider::Section;
 use crate::provider::types::ProviderResult;
 
 /// メモリエントリ
@@ -111,16 +111,17 @@ impl MemoryPlugin {
         Ok(recent_memories)
     }
 
-    /// 記憶のフォーマット
-    fn format_memories(&self, memories: Vec<Memory>) -> String {
-        let mut formatted = String::from("Previous Context:\n");
-        for memory in memories {
-            formatted.push_str(&format!(
-                "- {} (Importance: {:.2})\n",
-                memory.content, memory.importance
-            ));
-        }
-        formatted
+    /// 記憶をレコードに変換（描画は LLM の機能に応じて PromptAssembler が行う）
+    fn memory_records(&self, memories: Vec<Memory>) -> Vec<serde_json::Value> {
+        memories
+            .into_iter()
+            .map(|memory| {
+                json!({
+                    "content": memory.content,
+                    "importance": (memory.importance * 100.0).round() / 100.0,
+                })
+            })
+            .collect()
     }
 
     /// レスポンスの重要度を計算
@@ -187,15 +188,18 @@ impl ProviderPlugin for MemoryPlugin {
     }
 
     #[tracing::instrument(skip(self, context), level = "debug")]
-    async fn generate_section<'a>(&self, context: &PluginContext<'a>) -> ProviderResult<Section> {
+    async fn generate_section<'a>(
+        &self,
+        context: &PluginContext<'a>,
+    ) -> ProviderResult<ContextContribution> {
         // 関連する記憶の取得
         let memories = self.retrieve_relevant_memories(context).await?;
 
-        // セクションの生成
-        Ok(Section {
-            content: self.format_memories(memories),
-            priority: self.priority(),
-            ..Default::default()
+        // 構造化レコードとして渡す
+        Ok(ContextContribution::StructuredRecords {
+            title: "Previous Context:".to_string(),
+            records: self.memory_records(memories),
+            hint: RecordRendering::List,
         })
     }
 
@@ -236,7 +240,10 @@ mod tests {
     use std::time::Duration;
 
     use crate::{
-        provider::{llm::ResponseMetadata, plugins::provider_tests::TestContextHolder},
+        provider::{
+            capabilities::common::Capabilities, generator::assembler::PromptAssembler,
+            llm::ResponseMetadata, plugins::provider_tests::TestContextHolder, provider::Section,
+        },
         timestamp::Timestamp,
     };
 
@@ -252,6 +259,11 @@ mod tests {
         })
     }
 
+    // テキストのみの LLM 向けに描画する
+    fn render_text(contribution: ContextContribution) -> Section {
+        PromptAssembler::new(Capabilities::default()).render(&contribution, 100)
+    }
+
     fn create_test_response(content: &str) -> LLMResponse {
         LLMResponse {
             content: content.to_string(),
@@ -274,7 +286,12 @@ mod tests {
         let response = create_test_response("test response");
         plugin.process_response(&context, &response).await?;
 
-        let section = plugin.generate_section(&context).await?;
+        let contribution = plugin.generate_section(&context).await?;
+        let ContextContribution::StructuredRecords { records, .. } = &contribution else {
+            panic!("expected memory records, got {:?}", contribution);
+        };
+        assert_eq!(records[0]["content"], "test response");
+        let section = render_text(contribution);
         assert!(section.content.contains("test response"));
         Ok(())
     }
@@ -292,7 +309,7 @@ mod tests {
         }
 
         // セクションを生成して内容を確認
-        let section = plugin.generate_section(&context).await?;
+        let section = render_text(plugin.generate_section(&context).await?);
 
         // 最古のメモリ（"response 0"）が削除されているはず
         assert!(!section.content.contains("response 0"));
@@ -339,7 +356,7 @@ mod tests {
         tokio::time::sleep(Duration::from_millis(100)).await;
 
         // セクションを生成して確認
-        let section = plugin.generate_section(&context).await?;
+        let section = render_text(plugin.generate_section(&context).await?);
         assert!(section.content.contains("critical and important message"));
         Ok(())
     }
@@ -396,7 +413,7 @@ mod tests {
 
         // 結果の確認（元のcontext_holderを使用）
         let context = context_holder.get_plugin_context();
-        let section = plugin.generate_section(&context).await.unwrap();
+        let section = render_text(plugin.generate_section(&context).await.unwrap());
         assert!(section.content.contains("concurrent response"));
     }
 }
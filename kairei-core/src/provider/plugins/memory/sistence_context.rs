@@ -0,0 +1,315 @@
+//! Injects the SistenceMemory items relevant to a request into its prompt.
+//!
+//! Each agent reads the SistenceMemory namespace named after it, following the
+//! pins of the namespace (see [`SistenceMemoryCapability::pin_for_context`]). When
+//! the request is profiled, or `record_context` is set, the ids and relevance of
+//! the injected items are kept per request, see
+//! [`SistenceMemoryCapability::injected_context`].
+
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use serde_json::json;
+
+use crate::{
+    config::{PluginConfig, SistenceContextConfig},
+    event_bus::EventBus,
+    provider::{
+        capabilities::{common::CapabilityType, sistence_memory::*},
+        contribution::{ContextContribution, RecordRendering},
+        llm::LLMResponse,
+        plugin::{PluginContext, ProviderPlugin},
+        plugins::memory::sistence_memory_plugin::{SistenceMemoryConfig, SistenceMemoryPlugin},
+        types::{ProviderError, ProviderResult},
+    },
+};
+
+/// SistenceMemory instances by namespace, shared by the provider registry and
+/// the providers configured with the `sistence_memory` plugin.
+/// Instances keep their items in memory for the lifetime of the registry.
+pub struct SistenceMemories {
+    plugins: DashMap<String, Arc<dyn SistenceMemoryCapability>>,
+    event_bus: Arc<EventBus>,
+}
+
+impl SistenceMemories {
+    pub fn new(event_bus: Arc<EventBus>) -> Self {
+        Self {
+            plugins: DashMap::new(),
+            event_bus,
+        }
+    }
+
+    /// Get or create the SistenceMemory instance for a namespace
+    pub async fn get_or_create(
+        &self,
+        namespace: &str,
+    ) -> ProviderResult<Arc<dyn SistenceMemoryCapability>> {
+        if let Some(entry) = self.plugins.get(namespace) {
+            return Ok(entry.value().clone());
+        }
+
+        let config = SistenceMemoryConfig {
+            id: namespace.to_string(),
+            ..Default::default()
+        };
+        let plugin = SistenceMemoryPlugin::new(config, None, None)
+            .await
+            .map_err(|e| ProviderError::Initialization(e.to_string()))?
+            .with_event_bus(self.event_bus.clone());
+        // 同時に作成された場合は先に登録された方を使う
+        Ok(self
+            .plugins
+            .entry(namespace.to_string())
+            .or_insert(Arc::new(plugin))
+            .clone())
+    }
+}
+
+/// エージェントの SistenceMemory から、リクエストに関連する項目をプロンプトに載せるプラグイン。
+/// think の `with` ブロックの `sistence_memory` 設定は、プロバイダーの設定より優先される。
+pub struct SistenceContextPlugin {
+    memories: Arc<SistenceMemories>,
+    config: SistenceContextConfig,
+}
+
+impl SistenceContextPlugin {
+    pub fn new(memories: Arc<SistenceMemories>, config: SistenceContextConfig) -> Self {
+        Self { memories, config }
+    }
+
+    /// The context of the request, searched by its query
+    fn search_context(context: &PluginContext<'_>) -> SearchContext {
+        SearchContext {
+            context_id: context.request.state.trace_id.clone(),
+            current_topics: Vec::new(),
+            recent_items: Vec::new(),
+            query_text: Some(context.request.input.query.to_string()),
+            query_type: QueryType::Semantic,
+            strategy: Some(SearchStrategy::Balanced),
+            participants: Vec::new(),
+            current_activity: None,
+            temporal_context: TemporalContext {
+                current_time: Some(SystemTime::now()),
+                time_focus: Some("present".to_string()),
+                relevant_periods: Vec::new(),
+                historical_context: None,
+            },
+            sistence_profile: None,
+            goals: None,
+            conversation_summary: None,
+            environment_factors: None,
+        }
+    }
+}
+
+#[async_trait]
+impl ProviderPlugin for SistenceContextPlugin {
+    fn priority(&self) -> i32 {
+        100 // メモリは高優先度で実行
+    }
+
+    fn capability(&self) -> CapabilityType {
+        CapabilityType::SistenceMemory
+    }
+
+    #[tracing::instrument(skip(self, context), level = "debug")]
+    async fn generate_section<'a>(
+        &self,
+        context: &PluginContext<'a>,
+    ) -> ProviderResult<ContextContribution> {
+        let config = match context.configs.get("sistence_memory") {
+            Some(PluginConfig::SistenceMemory(config)) => config,
+            _ => &self.config,
+        };
+        let memory = self
+            .memories
+            .get_or_create(&context.request.state.agent_name)
+            .await?;
+
+        let search_context = Self::search_context(context);
+        let limit = Some(config.limit);
+        let items = match &context.request.state.request_id {
+            Some(request_id) => {
+                let record = config.record_context || context.context.profiler.is_some();
+                memory
+                    .get_relevant_for_request(request_id, search_context, limit, record)
+                    .await
+            }
+            None => memory.get_relevant_for_context(search_context, limit).await,
+        }
+        .map_err(|e| ProviderError::InternalError(e.to_string()))?;
+
+        Ok(ContextContribution::StructuredRecords {
+            title: "Relevant Memories:".to_string(),
+            records: items
+                .into_iter()
+                .map(|item| json!({ "content": item.content }))
+                .collect(),
+            hint: RecordRendering::List,
+        })
+    }
+
+    async fn process_response<'a>(
+        &self,
+        _context: &PluginContext<'a>,
+        _response: &LLMResponse,
+    ) -> ProviderResult<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::plugins::provider_tests::TestContextHolder;
+
+    const AGENT: &str = "Planner";
+
+    /// Three items ranked by importance: "top", "middle", "low"
+    async fn memories() -> Arc<SistenceMemories> {
+        let memories = Arc::new(SistenceMemories::new(Arc::new(EventBus::new(10))));
+        let memory = memories.get_or_create(AGENT).await.unwrap();
+        let jsonl = [
+            r#"{"id": "top", "content": "Kyoto trip plan", "importance": 0.9}"#,
+            r#"{"id": "middle", "content": "Tokyo hotel list", "importance": 0.5}"#,
+            r#"{"id": "low", "content": "Old receipt", "importance": 0.1}"#,
+        ]
+        .join("\n");
+        memory
+            .import_jsonl(&mut jsonl.as_bytes(), &ImportMapping::default(), None)
+            .await
+            .unwrap();
+        memories
+    }
+
+    /// Builds the section of request `request_id` and returns its recorded items
+    async fn injected_ids(plugin: &SistenceContextPlugin, request_id: &str) -> Vec<String> {
+        let mut context_holder = TestContextHolder::new("Where should we stay?");
+        context_holder.request.state.agent_name = AGENT.to_string();
+        context_holder.request.state.request_id = Some(request_id.to_string());
+        let contribution = plugin
+            .generate_section(&context_holder.get_plugin_context())
+            .await
+            .unwrap();
+        let ContextContribution::StructuredRecords { records, .. } = contribution else {
+            panic!("memories are not records");
+        };
+
+        let memory = plugin.memories.get_or_create(AGENT).await.unwrap();
+        let injected = memory.injected_context(request_id).await.unwrap();
+        assert_eq!(injected.items.len(), records.len());
+        injected
+            .items
+            .into_iter()
+            .map(|item| item.item_id)
+            .collect()
+    }
+
+    fn recording_plugin(memories: Arc<SistenceMemories>) -> SistenceContextPlugin {
+        SistenceContextPlugin::new(
+            memories,
+            SistenceContextConfig {
+                limit: 2,
+                record_context: true,
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn test_pinned_item_is_injected() {
+        let memories = memories().await;
+        let plugin = recording_plugin(memories.clone());
+        assert_eq!(injected_ids(&plugin, "req-1").await, vec!["top", "middle"]);
+
+        let memory = memories.get_or_create(AGENT).await.unwrap();
+        memory
+            .pin_for_context(
+                vec!["low".to_string()],
+                PinMode::Pin,
+                std::time::Duration::from_secs(60),
+            )
+            .await
+            .unwrap();
+        assert_eq!(injected_ids(&plugin, "req-2").await, vec!["low", "top"]);
+
+        let injected = memory.injected_context("req-2").await.unwrap();
+        assert!(injected.items[0].pinned);
+        assert!(!injected.items[1].pinned);
+        let stats = memory.get_stats().await.unwrap();
+        assert_eq!(stats.context_pins.len(), 1);
+        assert_eq!(stats.context_pins[0].mode, PinMode::Pin);
+    }
+
+    #[tokio::test]
+    async fn test_excluded_item_is_not_injected() {
+        let memories = memories().await;
+        let plugin = recording_plugin(memories.clone());
+        let memory = memories.get_or_create(AGENT).await.unwrap();
+        memory
+            .pin_for_context(
+                vec!["top".to_string()],
+                PinMode::Exclude,
+                std::time::Duration::from_secs(60),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(injected_ids(&plugin, "req-1").await, vec!["middle", "low"]);
+    }
+
+    #[tokio::test]
+    async fn test_pins_expire() {
+        let memories = memories().await;
+        let memory = memories.get_or_create(AGENT).await.unwrap();
+        memory
+            .pin_for_context(
+                vec!["low".to_string()],
+                PinMode::Pin,
+                std::time::Duration::from_millis(20),
+            )
+            .await
+            .unwrap();
+        assert_eq!(memory.context_pins().await.unwrap().len(), 1);
+
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(memory.context_pins().await.unwrap().is_empty());
+        let plugin = recording_plugin(memories);
+        assert_eq!(injected_ids(&plugin, "req-1").await, vec!["top", "middle"]);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_items_cannot_be_pinned() {
+        let memories = memories().await;
+        let memory = memories.get_or_create(AGENT).await.unwrap();
+        let result = memory
+            .pin_for_context(
+                vec!["missing".to_string()],
+                PinMode::Pin,
+                std::time::Duration::from_secs(60),
+            )
+            .await;
+        assert!(matches!(result, Err(SistenceMemoryError::NotFound(id)) if id == "missing"));
+    }
+
+    #[tokio::test]
+    async fn test_context_is_recorded_only_when_asked() {
+        let memories = memories().await;
+        let plugin = SistenceContextPlugin::new(memories.clone(), SistenceContextConfig::default());
+        let mut context_holder = TestContextHolder::new("Where should we stay?");
+        context_holder.request.state.agent_name = AGENT.to_string();
+        context_holder.request.state.request_id = Some("req-1".to_string());
+        plugin
+            .generate_section(&context_holder.get_plugin_context())
+            .await
+            .unwrap();
+
+        let memory = memories.get_or_create(AGENT).await.unwrap();
+        assert!(matches!(
+            memory.injected_context("req-1").await,
+            Err(SistenceMemoryError::NotFound(_))
+        ));
+    }
+}
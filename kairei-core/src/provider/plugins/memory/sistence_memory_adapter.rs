@@ -9,9 +9,9 @@ use uuid::Uuid;
 
 use crate::provider::{
     capabilities::common::CapabilityType,
+    contribution::ContextContribution,
     llm::LLMResponse,
     plugin::{PluginContext, ProviderPlugin},
-    provider::Section,
     types::ProviderResult,
 };
 
@@ -21,9 +21,10 @@ use crate::provider::capabilities::relevant_memory::{
     WorkingMemoryFormat,
 };
 use crate::provider::capabilities::sistence_memory::{
-    CleanupStats, EnhancedMetadata, ImportancePolicy, ImportanceScore, IndexStats, ItemLink,
-    MemoryId, MemoryItem, MemoryStats, Reference, SearchContext, SearchFilters, SearchStrategy,
-    SistenceMemoryCapability, SistenceMemoryError,
+    BatchItemResult, BatchResults, CleanupStats, ClusterParams, ClusterResult, EnhancedMetadata,
+    ImportancePolicy, ImportanceScore, IndexStats, ItemLink, MemoryId, MemoryItem, MemoryStats,
+    Reference, RelevanceExplanation, SearchContext, SearchFilters, SearchStrategy,
+    SistenceMemoryCapability, SistenceMemoryError, check_attachment,
 };
 use crate::provider::plugins::memory::sistence_memory_plugin::SistenceMemoryConfig;
 
@@ -87,6 +88,11 @@ impl SistenceMemoryAdapter {
         }
     }
 
+    /// Number of results returned when a search is not given a limit
+    pub fn default_result_limit(&self) -> usize {
+        self.default_result_limit
+    }
+
     /// Helper method to convert errors to SistenceMemoryError
     ///
     /// This method leverages the From trait implementations for SistenceMemoryError
@@ -332,6 +338,7 @@ impl SistenceMemoryAdapter {
             item_type: detailed.item_type,
             topics: detailed.topics,
             tags: detailed.tags,
+            attachments: detailed.attachments,
             source: detailed.source,
             references: self.convert_references(detailed.references),
             related_items: detailed.related_items,
@@ -364,6 +371,7 @@ impl SistenceMemoryAdapter {
             item_type: simple.item_type,
             topics: simple.topics,
             tags: simple.tags,
+            attachments: simple.attachments,
             source: source_clone.clone(),
             references: references_clone.iter().map(|r| self.convert_to_detailed_reference(r.clone(), now)).collect(),
             related_items: simple.related_items,
@@ -500,7 +508,10 @@ impl ProviderPlugin for SistenceMemoryAdapter {
     }
 
     #[tracing::instrument(skip(self, _context))]
-    async fn generate_section<'a>(&self, _context: &PluginContext<'a>) -> ProviderResult<Section> {
+    async fn generate_section<'a>(
+        &self,
+        _context: &PluginContext<'a>,
+    ) -> ProviderResult<ContextContribution> {
         todo!()
     }
 
@@ -523,6 +534,8 @@ impl SistenceMemoryCapability for SistenceMemoryAdapter {
 
     #[tracing::instrument(level = "debug", skip(self, item), err)]
     async fn store(&self, item: MemoryItem) -> Result<MemoryId, SistenceMemoryError> {
+        item.check_attachments()?;
+
         // Convert to internal format
         let detailed_item = self.simple_to_detailed(item);
 
@@ -530,6 +543,54 @@ impl SistenceMemoryCapability for SistenceMemoryAdapter {
         self.convert_error(self.relevant_memory.store_memory_item(detailed_item).await)
     }
 
+    #[tracing::instrument(level = "debug", skip(self, items), err)]
+    async fn store_batch(
+        &self,
+        items: Vec<MemoryItem>,
+    ) -> Result<Vec<MemoryId>, SistenceMemoryError> {
+        for item in &items {
+            item.check_attachments()?;
+        }
+
+        // Convert to internal format
+        let detailed_items = items
+            .into_iter()
+            .map(|item| self.simple_to_detailed(item))
+            .collect();
+
+        // Delegate to internal implementation using the error conversion helper
+        self.convert_error(
+            self.relevant_memory
+                .store_memory_items(detailed_items)
+                .await,
+        )
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, items), fields(count = items.len()))]
+    async fn update_many(&self, items: Vec<MemoryItem>) -> Vec<BatchItemResult> {
+        let mut results = BatchResults::new(items.len());
+        let mut valid = Vec::with_capacity(items.len());
+        for (index, item) in items.into_iter().enumerate() {
+            let checked = match item.check_attachments() {
+                Ok(()) => self.exists(&item.id).await,
+                Err(error) => Err(error),
+            };
+            match checked {
+                Ok(true) => valid.push((index, self.simple_to_detailed(item))),
+                Ok(false) => results.record(index, Err(SistenceMemoryError::NotFound(item.id))),
+                Err(error) => results.record(index, Err(error)),
+            }
+        }
+
+        // 更新は保存と同じ書き込みなので、有効な項目は 1 回でまとめて書き込む
+        let (indexes, valid): (Vec<_>, Vec<_>) = valid.into_iter().unzip();
+        if !valid.is_empty() {
+            let stored = self.relevant_memory.store_memory_items(valid).await;
+            results.record_all(indexes, self.convert_error(stored));
+        }
+        results.into_results()
+    }
+
     #[tracing::instrument(level = "debug", skip(self), err)]
     async fn retrieve(&self, id: &MemoryId) -> Result<MemoryItem, SistenceMemoryError> {
         // Retrieve detailed item and convert any errors
@@ -542,6 +603,8 @@ impl SistenceMemoryCapability for SistenceMemoryAdapter {
 
     #[tracing::instrument(level = "debug", skip(self, item), err)]
     async fn update(&self, item: MemoryItem) -> Result<(), SistenceMemoryError> {
+        item.check_attachments()?;
+
         // Convert to internal format
         let detailed_item = self.simple_to_detailed(item);
 
@@ -579,14 +642,10 @@ impl SistenceMemoryCapability for SistenceMemoryAdapter {
         let context_score = item.importance.context_score.unwrap_or(0.0);
 
         // Adjust scores based on policy
-        let (new_base, new_context) = match policy {
-            Some(ImportancePolicy::Standard) => (base_score, context_score),
-            Some(ImportancePolicy::FactualFocus) => (base_score * 1.2, context_score * 0.8),
-            Some(ImportancePolicy::NoveltyFocus) => (base_score * 1.1, context_score * 1.1),
-            Some(ImportancePolicy::UtilityFocus) => (base_score * 0.9, context_score * 1.3),
-            Some(ImportancePolicy::Custom(_)) => (base_score, context_score),
-            None => (base_score, context_score),
-        };
+        let (base_factor, context_factor) = policy
+            .as_ref()
+            .map_or((1.0, 1.0), ImportancePolicy::multipliers);
+        let (new_base, new_context) = (base_score * base_factor, context_score * context_factor);
 
         // Ensure scores are in valid range
         let new_base = new_base.clamp(0.0, 1.0);
@@ -701,6 +760,52 @@ impl SistenceMemoryCapability for SistenceMemoryAdapter {
         Ok(result.items)
     }
 
+    #[tracing::instrument(level = "debug", skip(self, context), err)]
+    async fn explain_relevance_for_context(
+        &self,
+        context: SearchContext,
+        limit: Option<usize>,
+    ) -> Result<Vec<RelevanceExplanation>, SistenceMemoryError> {
+        let scored = self.convert_error(
+            self.relevant_memory
+                .score_context_relevance(
+                    context,
+                    limit.unwrap_or(self.default_result_limit),
+                    0.0, // Same threshold as get_relevant_for_context
+                )
+                .await,
+        )?;
+
+        Ok(scored
+            .into_iter()
+            .map(|(item, relevance, factors)| RelevanceExplanation {
+                item: self.detailed_to_simple(item),
+                relevance,
+                factors,
+            })
+            .collect())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn search_by_tags(
+        &self,
+        include: HashMap<String, String>,
+        exclude: HashMap<String, String>,
+        match_all: bool,
+    ) -> Result<Vec<MemoryItem>, SistenceMemoryError> {
+        // Delegate to internal implementation with error conversion
+        let items = self.convert_error(
+            self.relevant_memory
+                .search_by_tags(include, exclude, match_all)
+                .await,
+        )?;
+
+        Ok(items
+            .into_iter()
+            .map(|item| self.detailed_to_simple(item))
+            .collect())
+    }
+
     // === Metadata Management ===
 
     #[tracing::instrument(level = "debug", skip(self), err)]
@@ -743,6 +848,35 @@ impl SistenceMemoryCapability for SistenceMemoryAdapter {
         self.convert_error(self.relevant_memory.update_memory_item(item).await)
     }
 
+    #[tracing::instrument(level = "debug", skip(self, value), err)]
+    async fn set_attachment(
+        &self,
+        item_id: &MemoryId,
+        key: &str,
+        value: Value,
+    ) -> Result<(), SistenceMemoryError> {
+        check_attachment(key, &value)?;
+
+        // Retrieve the item with error conversion
+        let mut item =
+            self.convert_error(self.relevant_memory.retrieve_memory_item(item_id).await)?;
+        item.attachments.insert(key.to_string(), value);
+
+        // Update the item with error conversion
+        self.convert_error(self.relevant_memory.update_memory_item(item).await)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn get_attachment(
+        &self,
+        item_id: &MemoryId,
+        key: &str,
+    ) -> Result<Option<Value>, SistenceMemoryError> {
+        let mut item =
+            self.convert_error(self.relevant_memory.retrieve_memory_item(item_id).await)?;
+        Ok(item.attachments.remove(key))
+    }
+
     #[tracing::instrument(level = "debug", skip(self, target_ids), err)]
     async fn link_items(
         &self,
@@ -894,6 +1028,12 @@ impl SistenceMemoryCapability for SistenceMemoryAdapter {
         )
     }
 
+    #[tracing::instrument(level = "debug", skip(self, params), err)]
+    async fn cluster(&self, params: ClusterParams) -> Result<ClusterResult, SistenceMemoryError> {
+        // Delegate to internal implementation with error conversion
+        self.convert_error(self.relevant_memory.cluster_memory_items(params).await)
+    }
+
     // === Management Functions ===
 
     #[tracing::instrument(level = "debug", skip(self), err)]
@@ -918,6 +1058,7 @@ impl SistenceMemoryCapability for SistenceMemoryAdapter {
             storage_used: 0,
             avg_importance: 0.0,
             access_stats: HashMap::new(),
+            context_pins: Vec::new(),
         })
     }
 }
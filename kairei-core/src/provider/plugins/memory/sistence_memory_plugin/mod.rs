@@ -10,25 +10,33 @@
 //! - StatelessRelevantMemory: Internal implementation of the RelevantMemoryCapability
 //! - SistenceMemoryAdapter: Adapter between the public and internal APIs
 
-use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::{Duration, SystemTime};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant, SystemTime};
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
 use tracing::{info, warn};
 use uuid::Uuid;
 
+use crate::event::event_bus::EventBus;
 use crate::provider::capabilities::common::{Capabilities, CapabilityType, HasCapabilities};
 use crate::provider::capabilities::relevant_memory::RelevantMemoryCapability;
 // Removed unused import: SharedMemoryCapability
+use crate::config::EmbeddingSchedulerConfig;
+use crate::provider::capabilities::shared_memory::Metadata;
 use crate::provider::capabilities::sistence_memory::*;
-use crate::provider::capabilities::storage::StorageBackend;
+use crate::provider::capabilities::storage::{StorageBackend, ValueWithMetadata};
+use crate::provider::contribution::ContextContribution;
+use crate::provider::embedding::ProviderEmbedding;
+use crate::provider::embedding_scheduler::{EmbeddingScheduler, EmbeddingSchedulerStats};
 use crate::provider::llm::{LLMResponse, ProviderLLM};
 use crate::provider::llms::simple_expert::SimpleExpertProviderLLM;
 use crate::provider::plugin::{PluginContext, ProviderPlugin};
+use crate::provider::plugins::memory::change_notifier::MemoryChangeNotifier;
 use crate::provider::plugins::memory::sistence_memory_adapter::SistenceMemoryAdapter;
 use crate::provider::plugins::memory::stateless_relevant_memory::StatelessRelevantMemory;
 use crate::provider::plugins::storage::in_memory::InMemoryBackend;
@@ -65,6 +73,19 @@ mod sistence_memory {
         /// Default importance weights
         pub importance_weights: ImportanceWeights,
 
+        /// Policy applied when importance is recomputed
+        #[serde(default)]
+        pub importance_policy: ImportancePolicy,
+
+        /// How often the importance of every item is recomputed in the background
+        /// (None to recompute only on request)
+        #[serde(default)]
+        pub importance_recompute_interval: Option<Duration>,
+
+        /// Items recomputed per batch
+        #[serde(default = "default_importance_recompute_batch_size")]
+        pub importance_recompute_batch_size: usize,
+
         /// Maximum items in memory (0 for unlimited)
         pub max_items: usize,
 
@@ -74,10 +95,26 @@ mod sistence_memory {
         /// Cleanup interval
         pub cleanup_interval: Duration,
 
+        /// Changes to the same item within this window are sent as one notification
+        #[serde(default = "default_change_coalesce_window")]
+        pub change_coalesce_window: Duration,
+
+        /// Batching and retries of item embeddings, used with an embedding client
+        #[serde(default)]
+        pub embedding: EmbeddingSchedulerConfig,
+
         /// Additional configuration options
         pub options: HashMap<String, String>,
     }
 
+    fn default_change_coalesce_window() -> Duration {
+        Duration::from_millis(200)
+    }
+
+    fn default_importance_recompute_batch_size() -> usize {
+        100
+    }
+
     /// Storage configuration for SistenceMemory
     #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct SistenceStorageConfig {
@@ -154,6 +191,46 @@ mod sistence_memory {
         pub contextual_adjustment: f32,
     }
 
+    impl ImportanceWeights {
+        /// Tag holding the emotional intensity (0.0-1.0) of an item
+        pub const EMOTIONAL_INTENSITY_TAG: &'static str = "emotional_intensity";
+
+        /// Base importance of `item`: the average of its factors, weighted by these
+        /// weights. Every factor is between 0 and 1:
+        ///
+        /// - intrinsic: reliability of the source
+        /// - usage: saturates with the access count
+        /// - network: saturates with the references and related items
+        /// - emotional: the [`EMOTIONAL_INTENSITY_TAG`](Self::EMOTIONAL_INTENSITY_TAG), 0 without it
+        pub fn base_score(&self, item: &MemoryItem) -> f32 {
+            let saturate = |count: usize| 1.0 - 1.0 / (1.0 + count as f32);
+            let emotional = item
+                .tags
+                .get(Self::EMOTIONAL_INTENSITY_TAG)
+                .and_then(|intensity| intensity.parse::<f32>().ok())
+                .unwrap_or(0.0);
+            let factors = [
+                (self.intrinsic_weight, item.source.reliability),
+                (self.usage_weight, saturate(item.access_count as usize)),
+                (
+                    self.network_weight,
+                    saturate(item.references.len() + item.related_items.len()),
+                ),
+                (self.emotional_weight, emotional),
+            ];
+
+            let total_weight: f32 = factors.iter().map(|(weight, _)| weight.max(0.0)).sum();
+            if total_weight <= 0.0 {
+                return 0.0;
+            }
+            let weighted: f32 = factors
+                .iter()
+                .map(|(weight, factor)| weight.max(0.0) * factor.clamp(0.0, 1.0))
+                .sum();
+            weighted / total_weight
+        }
+    }
+
     impl Default for ImportanceWeights {
         fn default() -> Self {
             Self {
@@ -185,14 +262,22 @@ mod sistence_memory {
                     options: HashMap::new(),
                 },
                 importance_weights: ImportanceWeights::default(),
+                importance_policy: ImportancePolicy::default(),
+                importance_recompute_interval: None,
+                importance_recompute_batch_size: default_importance_recompute_batch_size(),
                 max_items: 10000,
                 default_ttl: None,
                 cleanup_interval: Duration::from_secs(3600),
+                change_coalesce_window: default_change_coalesce_window(),
+                embedding: EmbeddingSchedulerConfig::default(),
                 options: HashMap::new(),
             }
         }
     }
 
+    /// Requests whose injected items are kept, see `injected_context`
+    const MAX_INJECTED_CONTEXTS: usize = 100;
+
     /// Status of the plugin
     #[derive(Debug, Clone)]
     struct PluginStatus {
@@ -224,8 +309,7 @@ mod sistence_memory {
         #[allow(dead_code)]
         config: SistenceMemoryConfig,
 
-        /// Storage backend
-        #[allow(dead_code)]
+        /// Storage backend, also holding the context pins
         storage: Arc<dyn StorageBackend>,
 
         /// LLM client for metadata enhancement
@@ -233,7 +317,6 @@ mod sistence_memory {
         llm_client: Arc<dyn ProviderLLM>,
 
         /// The internal implementation
-        #[allow(dead_code)]
         internal: Arc<StatelessRelevantMemory>,
 
         /// The adapter that converts between internal and public APIs
@@ -244,6 +327,15 @@ mod sistence_memory {
 
         /// Plugin status
         status: Arc<RwLock<PluginStatus>>,
+
+        /// Sends change notifications to subscribed agents
+        change_notifier: Option<Arc<MemoryChangeNotifier>>,
+
+        /// Weights and policy used when importance is recomputed
+        importance: RwLock<(ImportanceWeights, ImportancePolicy)>,
+
+        /// Items injected into prompts, by request, oldest first
+        injected: RwLock<VecDeque<InjectedContext>>,
     }
 
     impl SistenceMemoryPlugin {
@@ -339,6 +431,17 @@ mod sistence_memory {
             config: SistenceMemoryConfig,
             storage_provider: Option<Arc<dyn StorageBackend>>,
             llm_client: Option<Arc<dyn ProviderLLM>>,
+        ) -> Result<Self, SistenceMemoryError> {
+            Self::new_with_embedding(config, storage_provider, llm_client, None).await
+        }
+
+        /// Create a new SistenceMemoryPlugin that uses the given embedding client
+        /// for semantic operations such as clustering
+        pub async fn new_with_embedding(
+            config: SistenceMemoryConfig,
+            storage_provider: Option<Arc<dyn StorageBackend>>,
+            llm_client: Option<Arc<dyn ProviderLLM>>,
+            embedding_client: Option<Arc<dyn ProviderEmbedding>>,
         ) -> Result<Self, SistenceMemoryError> {
             // Create or use the provided storage backend
             let storage = if let Some(storage) = storage_provider {
@@ -372,14 +475,25 @@ mod sistence_memory {
             };
 
             // Use the new constructor with weights instead of creating a ProviderConfig directly
-            let internal = Arc::new(StatelessRelevantMemory::new_with_weights(
+            let mut internal = StatelessRelevantMemory::new_with_weights(
                 config.id.clone(),
                 Arc::clone(&storage),
                 Arc::clone(&llm),
                 config.llm.model.clone(),
                 config.llm.max_tokens,
                 config.importance_weights.clone(),
-            ));
+            );
+            if let Some(embedding_client) = embedding_client {
+                let scheduler = EmbeddingScheduler::new(
+                    &config.embedding,
+                    Arc::clone(&embedding_client),
+                    Arc::clone(&storage),
+                );
+                internal = internal
+                    .with_embedding_client(embedding_client)
+                    .with_embedding_scheduler(Arc::new(scheduler));
+            }
+            let internal = Arc::new(internal);
 
             // Create a SistenceContext
             let sistence_context = SistenceContext {
@@ -399,6 +513,11 @@ mod sistence_memory {
             let mut capabilities = Capabilities::default();
             capabilities.push(CapabilityType::SistenceMemory);
 
+            let importance = RwLock::new((
+                config.importance_weights.clone(),
+                config.importance_policy.clone(),
+            ));
+
             // Create the plugin
             let plugin = Self {
                 id: config.id.clone(),
@@ -415,6 +534,9 @@ mod sistence_memory {
                     operation_count: 0,
                     error_count: 0,
                 })),
+                change_notifier: None,
+                importance,
+                injected: RwLock::new(VecDeque::new()),
             };
 
             // Initialize the plugin
@@ -423,6 +545,235 @@ mod sistence_memory {
             Ok(plugin)
         }
 
+        /// Publish change notifications on `event_bus`. Without this, subscribing
+        /// to changes fails.
+        pub fn with_event_bus(mut self, event_bus: Arc<EventBus>) -> Self {
+            self.change_notifier = Some(Arc::new(MemoryChangeNotifier::new(
+                self.id.clone(),
+                event_bus,
+                self.config.change_coalesce_window,
+            )));
+            self
+        }
+
+        /// The notifier, if any agent is subscribed to changes
+        fn listening_notifier(&self) -> Option<&MemoryChangeNotifier> {
+            self.change_notifier
+                .as_deref()
+                .filter(|notifier| notifier.has_subscriptions())
+        }
+
+        /// Notify subscribers that the item with `id` changed
+        async fn notify_change(&self, change_type: MemoryChangeType, id: &MemoryId) {
+            if let Some(notifier) = self.listening_notifier() {
+                match self.adapter.retrieve(id).await {
+                    Ok(item) => notifier.notify(change_type, &item),
+                    Err(e) => warn!("Changed item {} could not be read: {}", id, e),
+                }
+            }
+        }
+
+        /// Storage namespace of the context pins
+        fn pins_namespace(&self) -> String {
+            format!("{}:context_pins", self.id)
+        }
+
+        /// The pins that have not expired. Expired pins are removed from storage.
+        async fn active_pins(&self) -> Result<Vec<ContextPin>, SistenceMemoryError> {
+            let namespace = self.pins_namespace();
+            let now = SystemTime::now();
+            let mut pins = Vec::new();
+            for (key, stored) in self.storage.load(&namespace).await? {
+                match serde_json::from_value::<ContextPin>(stored.value) {
+                    Ok(pin) if pin.expires_at > now => pins.push(pin),
+                    _ => self.storage.delete_key(&namespace, &key).await?,
+                }
+            }
+            pins.sort_by(|a, b| a.item_id.cmp(&b.item_id));
+            Ok(pins)
+        }
+
+        /// The items for `context` with their relevance, after the pins: excluded
+        /// items are left out and pinned items come first, within `limit`
+        async fn relevant_with_pins(
+            &self,
+            context: SearchContext,
+            limit: Option<usize>,
+        ) -> Result<Vec<(MemoryItem, InjectedMemory)>, SistenceMemoryError> {
+            let limit = limit.unwrap_or(self.adapter.default_result_limit());
+            let pins = self.active_pins().await?;
+            // ピンがあれば順位の低い項目も対象になるため、全件を順位付けする
+            let ranked_limit = if pins.is_empty() { limit } else { usize::MAX };
+            let ranked = self
+                .adapter
+                .explain_relevance_for_context(context, Some(ranked_limit))
+                .await?;
+
+            let mut pinned = Vec::new();
+            let mut others = Vec::new();
+            for explanation in ranked {
+                let mode = pins
+                    .iter()
+                    .find(|pin| pin.item_id == explanation.item.id)
+                    .map(|pin| pin.mode);
+                let injected = InjectedMemory {
+                    item_id: explanation.item.id.clone(),
+                    relevance: explanation.relevance,
+                    pinned: mode == Some(PinMode::Pin),
+                };
+                match mode {
+                    Some(PinMode::Pin) => pinned.push((explanation.item, injected)),
+                    Some(PinMode::Exclude) => {}
+                    None => others.push((explanation.item, injected)),
+                }
+            }
+            pinned.extend(others);
+            pinned.truncate(limit);
+            Ok(pinned)
+        }
+
+        /// Add `items` to the items injected for `request_id`
+        async fn record_injected(&self, request_id: &str, items: Vec<InjectedMemory>) {
+            let mut injected = self.injected.write().await;
+            let recorded_at = SystemTime::now();
+            match injected
+                .iter_mut()
+                .find(|context| context.request_id == request_id)
+            {
+                Some(context) => {
+                    context.recorded_at = recorded_at;
+                    context.items.extend(items);
+                }
+                None => {
+                    if injected.len() >= MAX_INJECTED_CONTEXTS {
+                        injected.pop_front();
+                    }
+                    injected.push_back(InjectedContext {
+                        request_id: request_id.to_string(),
+                        recorded_at,
+                        items,
+                    });
+                }
+            }
+        }
+
+        /// Number of filtered searches the tag and topic indexes could not
+        /// narrow down, so every item was checked
+        pub fn full_scan_count(&self) -> usize {
+            self.internal.full_scan_count()
+        }
+
+        /// Counters of the item embeddings, None without an embedding client
+        pub fn embedding_stats(&self) -> Option<EmbeddingSchedulerStats> {
+            self.internal
+                .embedding_scheduler
+                .as_ref()
+                .map(|scheduler| scheduler.stats())
+        }
+
+        /// Recompute importance with `weights` from now on
+        pub async fn set_importance_weights(&self, weights: ImportanceWeights) {
+            self.importance.write().await.0 = weights;
+        }
+
+        /// Recompute importance with `policy` from now on
+        pub async fn set_importance_policy(&self, policy: ImportancePolicy) {
+            self.importance.write().await.1 = policy;
+        }
+
+        /// Recompute the base importance of the items with `ids`, or of every item,
+        /// with the current weights and policy. Unlike `update_importance`, the
+        /// result does not depend on the stored score, so reweighting applies to
+        /// items stored earlier. Context scores are kept.
+        ///
+        /// Items are processed in batches of `importance_recompute_batch_size`, and
+        /// only items whose score changed are written back.
+        pub async fn recompute_importance(
+            &self,
+            ids: Option<Vec<MemoryId>>,
+        ) -> Result<ImportanceRecomputeReport, SistenceMemoryError> {
+            {
+                let mut status = self.status.write().await;
+                status.operation_count += 1;
+            }
+
+            let all_items = ids.is_none();
+            let ids = ids.unwrap_or_else(|| {
+                // 結果を決定的にするため ID 順に処理する
+                let mut ids: Vec<MemoryId> = self
+                    .internal
+                    .memory_index
+                    .iter()
+                    .map(|entry| entry.key().clone())
+                    .collect();
+                ids.sort();
+                ids
+            });
+            let (weights, policy) = self.importance.read().await.clone();
+            let (base_factor, _) = policy.multipliers();
+
+            let mut report = ImportanceRecomputeReport::default();
+            for batch in ids.chunks(self.config.importance_recompute_batch_size.max(1)) {
+                for id in batch {
+                    let mut item = match self.adapter.retrieve(id).await {
+                        Ok(item) => item,
+                        // 全件の再計算中に削除された項目は飛ばす
+                        Err(SistenceMemoryError::NotFound(_)) if all_items => continue,
+                        Err(e) => return Err(e),
+                    };
+                    report.items_evaluated += 1;
+
+                    let base_score = (weights.base_score(&item) * base_factor).clamp(0.0, 1.0);
+                    let context_score = item.importance.context_score.unwrap_or(0.0);
+                    let score = base_score * 0.7 + context_score * 0.3;
+                    if (score - item.importance.score).abs() <= f32::EPSILON {
+                        continue;
+                    }
+
+                    item.importance = ImportanceScore {
+                        score,
+                        base_score,
+                        context_score: Some(context_score),
+                        reason: Some(format!("Recomputed with policy: {:?}", policy)),
+                        evaluated_at: SystemTime::now(),
+                    };
+                    self.adapter.update(item).await?;
+                    self.notify_change(MemoryChangeType::Updated, id).await;
+                    report.items_changed += 1;
+                }
+                report.batches += 1;
+                // バッチの間で他のタスクに譲る
+                tokio::task::yield_now().await;
+            }
+            Ok(report)
+        }
+
+        /// Recompute the importance of every item every
+        /// `importance_recompute_interval`, until the plugin is dropped. Returns
+        /// `None` when no interval is configured.
+        pub fn spawn_importance_recompute(self: &Arc<Self>) -> Option<JoinHandle<()>> {
+            let interval = self.config.importance_recompute_interval?;
+            let plugin: Weak<Self> = Arc::downgrade(self);
+            Some(tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(interval);
+                // 最初の tick は即座に返るので読み捨てる
+                ticker.tick().await;
+                loop {
+                    ticker.tick().await;
+                    let Some(plugin) = plugin.upgrade() else {
+                        break;
+                    };
+                    match plugin.recompute_importance(None).await {
+                        Ok(report) => info!(
+                            "Recomputed importance of {} items, {} changed",
+                            report.items_evaluated, report.items_changed
+                        ),
+                        Err(e) => warn!("Importance recomputation failed: {}", e),
+                    }
+                }
+            }))
+        }
+
         /// Initialize the plugin
         async fn initialize(&self) -> Result<(), SistenceMemoryError> {
             // Create necessary storage structures if needed
@@ -569,9 +920,9 @@ mod sistence_memory {
         async fn generate_section<'a>(
             &self,
             _context: &PluginContext<'a>,
-        ) -> ProviderResult<Section> {
+        ) -> ProviderResult<ContextContribution> {
             // not implemented, this is Just Compatibility
-            Ok(Section::new(""))
+            Ok(Section::new("").into())
         }
 
         async fn process_response<'a>(
@@ -600,9 +951,15 @@ mod sistence_memory {
             status.operation_count += 1;
 
             // Delegate to the adapter
+            let stored = self.listening_notifier().map(|_| item.clone());
             match self.adapter.store(item).await {
                 Ok(id) => {
                     status.item_count += 1;
+                    drop(status);
+                    if let (Some(notifier), Some(mut item)) = (self.listening_notifier(), stored) {
+                        item.id = id.clone();
+                        notifier.notify(MemoryChangeType::Stored, &item);
+                    }
                     Ok(id)
                 }
                 Err(e) => {
@@ -612,6 +969,35 @@ mod sistence_memory {
             }
         }
 
+        async fn store_batch(
+            &self,
+            items: Vec<MemoryItem>,
+        ) -> Result<Vec<MemoryId>, SistenceMemoryError> {
+            // Update plugin stats
+            let mut status = self.status.write().await;
+            status.operation_count += 1;
+
+            // Delegate to the adapter
+            let stored = self.listening_notifier().map(|_| items.clone());
+            match self.adapter.store_batch(items).await {
+                Ok(ids) => {
+                    status.item_count += ids.len();
+                    drop(status);
+                    if let (Some(notifier), Some(items)) = (self.listening_notifier(), stored) {
+                        for (mut item, id) in items.into_iter().zip(&ids) {
+                            item.id = id.clone();
+                            notifier.notify(MemoryChangeType::Stored, &item);
+                        }
+                    }
+                    Ok(ids)
+                }
+                Err(e) => {
+                    status.error_count += 1;
+                    Err(e)
+                }
+            }
+        }
+
         async fn retrieve(&self, id: &MemoryId) -> Result<MemoryItem, SistenceMemoryError> {
             // Update plugin stats
             {
@@ -631,7 +1017,25 @@ mod sistence_memory {
             }
 
             // Delegate to the adapter
-            self.adapter.update(item).await
+            let id = item.id.clone();
+            self.adapter.update(item).await?;
+            self.notify_change(MemoryChangeType::Updated, &id).await;
+            Ok(())
+        }
+
+        async fn update_many(&self, items: Vec<MemoryItem>) -> Vec<BatchItemResult> {
+            // Update plugin stats
+            {
+                let mut status = self.status.write().await;
+                status.operation_count += 1;
+            }
+
+            // Delegate to the adapter, which writes the valid items at once
+            let results = self.adapter.update_many(items).await;
+            for id in results.iter().flatten() {
+                self.notify_change(MemoryChangeType::Updated, id).await;
+            }
+            results
         }
 
         async fn delete(&self, id: &MemoryId) -> Result<(), SistenceMemoryError> {
@@ -641,8 +1045,19 @@ mod sistence_memory {
                 status.operation_count += 1;
             }
 
+            // 削除後は読めないので、通知の照合用に先に読んでおく
+            let deleted = match self.listening_notifier() {
+                Some(_) => self.adapter.retrieve(id).await.ok(),
+                None => None,
+            };
+
             // Delegate to the adapter and update item count if successful
             let result = self.adapter.delete(id).await;
+            if let (Ok(()), Some(notifier), Some(item)) =
+                (&result, self.listening_notifier(), &deleted)
+            {
+                notifier.notify(MemoryChangeType::Deleted, item);
+            }
             if result.is_ok() {
                 let mut status = self.status.write().await;
                 if status.item_count > 0 {
@@ -716,9 +1131,60 @@ mod sistence_memory {
             &self,
             context: SearchContext,
             limit: Option<usize>,
+        ) -> Result<Vec<MemoryItem>, SistenceMemoryError> {
+            let relevant = self.relevant_with_pins(context, limit).await?;
+            Ok(relevant.into_iter().map(|(item, _)| item).collect())
+        }
+
+        async fn get_relevant_for_request(
+            &self,
+            request_id: &str,
+            context: SearchContext,
+            limit: Option<usize>,
+            record: bool,
+        ) -> Result<Vec<MemoryItem>, SistenceMemoryError> {
+            let relevant = self.relevant_with_pins(context, limit).await?;
+            let (items, injected): (Vec<_>, Vec<_>) = relevant.into_iter().unzip();
+            if record {
+                self.record_injected(request_id, injected).await;
+            }
+            Ok(items)
+        }
+
+        async fn injected_context(
+            &self,
+            request_id: &str,
+        ) -> Result<InjectedContext, SistenceMemoryError> {
+            self.injected
+                .read()
+                .await
+                .iter()
+                .find(|context| context.request_id == request_id)
+                .cloned()
+                .ok_or_else(|| SistenceMemoryError::NotFound(request_id.to_string()))
+        }
+
+        async fn explain_relevance_for_context(
+            &self,
+            context: SearchContext,
+            limit: Option<usize>,
+        ) -> Result<Vec<RelevanceExplanation>, SistenceMemoryError> {
+            // Delegate to the adapter
+            self.adapter
+                .explain_relevance_for_context(context, limit)
+                .await
+        }
+
+        async fn search_by_tags(
+            &self,
+            include: HashMap<String, String>,
+            exclude: HashMap<String, String>,
+            match_all: bool,
         ) -> Result<Vec<MemoryItem>, SistenceMemoryError> {
             // Delegate to the adapter
-            self.adapter.get_relevant_for_context(context, limit).await
+            self.adapter
+                .search_by_tags(include, exclude, match_all)
+                .await
         }
 
         // === Metadata Management ===
@@ -729,7 +1195,9 @@ mod sistence_memory {
             topics: Vec<String>,
         ) -> Result<(), SistenceMemoryError> {
             // Delegate to the adapter
-            self.adapter.add_topics(item_id, topics).await
+            self.adapter.add_topics(item_id, topics).await?;
+            self.notify_change(MemoryChangeType::Updated, item_id).await;
+            Ok(())
         }
 
         async fn add_tags(
@@ -738,7 +1206,30 @@ mod sistence_memory {
             tags: HashMap<String, String>,
         ) -> Result<(), SistenceMemoryError> {
             // Delegate to the adapter
-            self.adapter.add_tags(item_id, tags).await
+            self.adapter.add_tags(item_id, tags).await?;
+            self.notify_change(MemoryChangeType::Updated, item_id).await;
+            Ok(())
+        }
+
+        async fn set_attachment(
+            &self,
+            item_id: &MemoryId,
+            key: &str,
+            value: Value,
+        ) -> Result<(), SistenceMemoryError> {
+            // Delegate to the adapter
+            self.adapter.set_attachment(item_id, key, value).await?;
+            self.notify_change(MemoryChangeType::Updated, item_id).await;
+            Ok(())
+        }
+
+        async fn get_attachment(
+            &self,
+            item_id: &MemoryId,
+            key: &str,
+        ) -> Result<Option<Value>, SistenceMemoryError> {
+            // Delegate to the adapter
+            self.adapter.get_attachment(item_id, key).await
         }
 
         async fn link_items(
@@ -811,6 +1302,20 @@ mod sistence_memory {
                 .await
         }
 
+        async fn cluster(
+            &self,
+            params: ClusterParams,
+        ) -> Result<ClusterResult, SistenceMemoryError> {
+            // Update plugin stats
+            {
+                let mut status = self.status.write().await;
+                status.operation_count += 1;
+            }
+
+            // Delegate to the adapter
+            self.adapter.cluster(params).await
+        }
+
         // === Management Functions ===
 
         async fn cleanup_expired(&self) -> Result<CleanupStats, SistenceMemoryError> {
@@ -827,13 +1332,933 @@ mod sistence_memory {
 
         async fn get_stats(&self) -> Result<MemoryStats, SistenceMemoryError> {
             // Delegate to the adapter
-            self.adapter.get_stats().await
+            let mut stats = self.adapter.get_stats().await?;
+            stats.context_pins = self.active_pins().await?;
+            Ok(stats)
+        }
+
+        // === Change Notifications ===
+
+        async fn subscribe_changes(
+            &self,
+            agent_id: &str,
+            interest: MemoryInterest,
+        ) -> Result<MemorySubscriptionId, SistenceMemoryError> {
+            let notifier = self.change_notifier.as_ref().ok_or_else(|| {
+                SistenceMemoryError::InternalError(format!(
+                    "SistenceMemory {} has no event bus for change notifications",
+                    self.id
+                ))
+            })?;
+            Ok(notifier.subscribe(agent_id, interest))
+        }
+
+        async fn unsubscribe_changes(
+            &self,
+            subscription_id: &MemorySubscriptionId,
+        ) -> Result<(), SistenceMemoryError> {
+            match &self.change_notifier {
+                Some(notifier) if notifier.unsubscribe(subscription_id) => Ok(()),
+                _ => Err(SistenceMemoryError::NotFound(subscription_id.clone())),
+            }
+        }
+
+        // === Context Pins ===
+
+        async fn pin_for_context(
+            &self,
+            item_ids: Vec<MemoryId>,
+            mode: PinMode,
+            ttl: Duration,
+        ) -> Result<Vec<ContextPin>, SistenceMemoryError> {
+            if ttl.is_zero() {
+                return Err(SistenceMemoryError::InvalidInput(
+                    "ttl must be positive".to_string(),
+                ));
+            }
+            for id in &item_ids {
+                if !self.adapter.exists(id).await? {
+                    return Err(SistenceMemoryError::NotFound(id.clone()));
+                }
+            }
+
+            let namespace = self.pins_namespace();
+            let expires_at = SystemTime::now() + ttl;
+            for item_id in item_ids {
+                let pin = ContextPin {
+                    item_id,
+                    mode,
+                    expires_at,
+                };
+                let value = serde_json::to_value(&pin)
+                    .map_err(|e| SistenceMemoryError::SerializationError(e.to_string()))?;
+                let stored = ValueWithMetadata {
+                    value,
+                    metadata: Metadata::default(),
+                    expiry: Some(Instant::now() + ttl),
+                };
+                self.storage
+                    .save_key(&namespace, &pin.item_id, &stored)
+                    .await?;
+            }
+            self.active_pins().await
+        }
+
+        async fn context_pins(&self) -> Result<Vec<ContextPin>, SistenceMemoryError> {
+            self.active_pins().await
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::event::event_bus::{Event, EventReceiver};
+    use crate::event::event_registry::EventType;
+    use crate::provider::embedding::MockProviderEmbedding;
+    use crate::provider::llm::{MockProviderLLM, ResponseMetadata};
+    use crate::provider::plugins::memory::change_notifier::MEMORY_CHANGED_EVENT;
+
+    const PET_CONTENTS: [&str; 3] = [
+        "My cat sleeps on the sofa all afternoon",
+        "The dog needs a walk every morning",
+        "Bought new toys for the cat and the dog",
+    ];
+    const MARKET_CONTENTS: [&str; 3] = [
+        "Stock prices fell sharply after the earnings report",
+        "The market rallied on interest rate news",
+        "Rebalanced the portfolio towards index funds",
+    ];
+
+    fn memory_item(content: &str) -> MemoryItem {
+        let now = SystemTime::now();
+        MemoryItem {
+            id: Uuid::new_v4().to_string(),
+            created_at: now,
+            updated_at: now,
+            content: content.to_string(),
+            content_type: ContentType::Text,
+            structured_content: None,
+            item_type: ItemType::Information,
+            topics: Vec::new(),
+            tags: HashMap::new(),
+            attachments: HashMap::new(),
+            source: Source {
+                source_type: "user".to_string(),
+                source_id: "tester".to_string(),
+                details: None,
+                reliability: 1.0,
+            },
+            references: Vec::new(),
+            related_items: Vec::new(),
+            importance: ImportanceScore {
+                score: 0.5,
+                base_score: 0.5,
+                context_score: None,
+                reason: None,
+                evaluated_at: now,
+            },
+            last_accessed: None,
+            access_count: 0,
+            ttl: None,
+            retention_policy: RetentionPolicy::Standard,
+        }
+    }
+
+    /// Embeds pet-related texts and market-related texts on orthogonal axes
+    fn mock_embedding() -> MockProviderEmbedding {
+        let mut embedding = MockProviderEmbedding::new();
+        embedding.expect_embed().returning(|texts| {
+            let vectors = texts
+                .iter()
+                .enumerate()
+                .map(|(i, text)| {
+                    let jitter = (i % 3) as f32 * 0.05;
+                    if PET_CONTENTS.contains(&text.as_str()) {
+                        vec![1.0, jitter, 0.0]
+                    } else {
+                        vec![jitter, 1.0, 0.0]
+                    }
+                })
+                .collect();
+            Box::pin(async move { Ok(vectors) })
+        });
+        embedding
+            .expect_name()
+            .return_const("mock-embedding".to_string());
+        embedding
+    }
+
+    fn mock_llm() -> MockProviderLLM {
+        let mut llm = MockProviderLLM::new();
+        llm.expect_send_message().returning(|prompt, _| {
+            let label = if prompt.contains("cat") {
+                "Pets"
+            } else {
+                "Finance"
+            };
+            let content = format!("\"{}\"", label);
+            Box::pin(async move {
+                Ok(LLMResponse {
+                    content,
+                    metadata: ResponseMetadata::default(),
+                })
+            })
+        });
+        llm
+    }
+
+    async fn plugin_with_items() -> (SistenceMemoryPlugin, Vec<MemoryId>, Vec<MemoryId>) {
+        let plugin = SistenceMemoryPlugin::new_with_embedding(
+            SistenceMemoryConfig::default(),
+            None,
+            Some(Arc::new(mock_llm())),
+            Some(Arc::new(mock_embedding())),
+        )
+        .await
+        .unwrap();
+
+        let mut pet_ids = Vec::new();
+        for content in PET_CONTENTS {
+            pet_ids.push(plugin.store(memory_item(content)).await.unwrap());
+        }
+        let mut market_ids = Vec::new();
+        for content in MARKET_CONTENTS {
+            market_ids.push(plugin.store(memory_item(content)).await.unwrap());
+        }
+        (plugin, pet_ids, market_ids)
+    }
 
-    // Tests would be implemented here
+    #[tokio::test]
+    async fn test_cluster_auto_finds_two_labelled_groups() {
+        let (plugin, pet_ids, market_ids) = plugin_with_items().await;
+
+        let result = plugin.cluster(ClusterParams::default()).await.unwrap();
+
+        assert_eq!(result.clusters.len(), 2);
+        assert_eq!(result.assignments.len(), 6);
+        assert!(result.silhouette.unwrap() > 0.5);
+
+        let pet_cluster = result.assignments[&pet_ids[0]];
+        let market_cluster = result.assignments[&market_ids[0]];
+        assert_ne!(pet_cluster, market_cluster);
+        assert!(
+            pet_ids
+                .iter()
+                .all(|id| result.assignments[id] == pet_cluster)
+        );
+        assert!(
+            market_ids
+                .iter()
+                .all(|id| result.assignments[id] == market_cluster)
+        );
+
+        assert_eq!(result.clusters[pet_cluster].label, "Pets");
+        assert_eq!(result.clusters[market_cluster].label, "Finance");
+        assert!(result.clusters.iter().all(|c| c.cohesion > 0.9));
+    }
+
+    #[tokio::test]
+    async fn test_cluster_fixed_count_without_labels() {
+        let (plugin, pet_ids, _) = plugin_with_items().await;
+
+        let result = plugin
+            .cluster(ClusterParams {
+                count: ClusterCount::Fixed(1),
+                generate_labels: false,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(result.clusters.len(), 1);
+        assert_eq!(result.clusters[0].item_ids.len(), 6);
+        assert!(result.silhouette.is_none());
+        assert!(pet_ids.iter().all(|id| result.assignments[id] == 0));
+        assert!(!result.clusters[0].label.is_empty());
+
+        let error = plugin
+            .cluster(ClusterParams {
+                count: ClusterCount::Fixed(0),
+                ..Default::default()
+            })
+            .await;
+        assert!(matches!(error, Err(SistenceMemoryError::InvalidInput(_))));
+    }
+
+    #[tokio::test]
+    async fn test_batch_reports_invalid_items_by_index() {
+        let plugin = SistenceMemoryPlugin::new(SistenceMemoryConfig::default(), None, None)
+            .await
+            .unwrap();
+        let mut oversized = memory_item("Scanned receipt");
+        oversized.attachments.insert(
+            "scan".to_string(),
+            serde_json::Value::String("x".repeat(MAX_ATTACHMENT_BYTES)),
+        );
+        let items = vec![
+            memory_item("Kyoto trip notes"),
+            oversized.clone(),
+            memory_item("Buy train tickets"),
+        ];
+        let ids: Vec<MemoryId> = items.iter().map(|item| item.id.clone()).collect();
+
+        let results = plugin.store_many(items).await;
+
+        assert_eq!(results[0], Ok(ids[0].clone()));
+        assert_eq!(results[2], Ok(ids[2].clone()));
+        let error = results[1].clone().unwrap_err();
+        assert_eq!(error.index, 1);
+        assert!(error.reason.contains("Attachment 'scan'"));
+        assert!(plugin.exists(&ids[0]).await.unwrap());
+        assert!(plugin.exists(&ids[2]).await.unwrap());
+        assert!(!plugin.exists(&ids[1]).await.unwrap());
+
+        // 更新でも、無効な項目だけが失敗して残りは書き込まれる
+        let mut updated = plugin.retrieve(&ids[2]).await.unwrap();
+        updated.content = "Bought train tickets".to_string();
+        let results = plugin.update_many(vec![oversized, updated]).await;
+        assert_eq!(results[0].clone().unwrap_err().index, 0);
+        assert_eq!(results[1], Ok(ids[2].clone()));
+        assert_eq!(
+            plugin.retrieve(&ids[2]).await.unwrap().content,
+            "Bought train tickets"
+        );
+        let results = plugin.delete_many(vec![ids[1].clone()]).await;
+        assert!(results[0].clone().unwrap_err().reason.contains("not found"));
+
+        // 削除した項目は読み出せず、2 度目の削除は失敗する
+        let results = plugin
+            .delete_many(vec![ids[0].clone(), ids[2].clone()])
+            .await;
+        assert_eq!(results, vec![Ok(ids[0].clone()), Ok(ids[2].clone())]);
+        for id in [&ids[0], &ids[2]] {
+            assert!(matches!(
+                plugin.retrieve(id).await,
+                Err(SistenceMemoryError::NotFound(_))
+            ));
+            assert!(!plugin.exists(id).await.unwrap());
+        }
+        let results = plugin.delete_many(vec![ids[0].clone()]).await;
+        assert!(results[0].clone().unwrap_err().reason.contains("not found"));
+    }
+
+    #[test]
+    fn test_batch_results_without_an_id_fail() {
+        // 書き込みが返した ID が足りない項目と、結果のない項目は失敗として返す
+        let mut results = BatchResults::new(3);
+        results.record_all(vec![0, 2], Ok(vec!["note-1".to_string()]));
+        let results = results.into_results();
+
+        assert_eq!(results[0], Ok("note-1".to_string()));
+        let error = results[1].clone().unwrap_err();
+        assert_eq!(error.index, 1);
+        assert!(error.reason.contains("no outcome"));
+        let error = results[2].clone().unwrap_err();
+        assert_eq!(error.index, 2);
+        assert!(error.reason.contains("no ID"));
+    }
+
+    #[tokio::test]
+    async fn test_import_jsonl_skips_malformed_lines() {
+        let plugin = SistenceMemoryPlugin::new(SistenceMemoryConfig::default(), None, None)
+            .await
+            .unwrap();
+        let jsonl = [
+            r#"{"id": "note-1", "text": "Kyoto trip notes", "labels": ["travel"], "tags": {"lang": "ja"}}"#,
+            r#"{"id": "note-2", "text": "Unfinished"#,
+            "",
+            r#"{"id": "note-3", "text": "Buy train tickets", "kind": "task", "created": "2024-05-01T09:00:00Z"}"#,
+        ]
+        .join("\n");
+        let mapping = ImportMapping {
+            content: "text".to_string(),
+            topics: Some("labels".to_string()),
+            item_type: Some("kind".to_string()),
+            created_at: Some("created".to_string()),
+            batch_size: 2,
+            ..Default::default()
+        };
+        let (progress_tx, mut progress_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let report = plugin
+            .import_jsonl(&mut jsonl.as_bytes(), &mapping, Some(progress_tx))
+            .await
+            .unwrap();
+
+        assert_eq!(report.lines_read, 4);
+        assert_eq!(report.item_ids, vec!["note-1", "note-3"]);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].line, 2);
+        assert!(report.errors[0].error.contains("invalid JSON"));
+
+        // 2 件がまとめて 1 回で書き込まれる
+        assert_eq!(
+            progress_rx.recv().await,
+            Some(ImportProgress {
+                lines_read: 4,
+                imported: 2,
+                failed: 1,
+            })
+        );
+        assert_eq!(progress_rx.recv().await, None);
+
+        let note = plugin.retrieve(&"note-1".to_string()).await.unwrap();
+        assert_eq!(note.content, "Kyoto trip notes");
+        assert_eq!(note.topics, vec!["travel"]);
+        assert_eq!(note.tags.get("lang"), Some(&"ja".to_string()));
+        let task = plugin.retrieve(&"note-3".to_string()).await.unwrap();
+        assert_eq!(task.item_type, ItemType::Task);
+        assert_eq!(
+            task.created_at,
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1_714_554_000)
+        );
+        assert!(!plugin.exists(&"note-2".to_string()).await.unwrap());
+    }
+
+    fn tags(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_search_by_tags_include_and_exclude() {
+        let plugin = SistenceMemoryPlugin::new(SistenceMemoryConfig::default(), None, None)
+            .await
+            .unwrap();
+        let tagged = [
+            ("tokyo-rain", &[("city", "Tokyo"), ("weather", "rain")][..]),
+            ("tokyo-sun", &[("city", "Tokyo"), ("weather", "sun")][..]),
+            ("osaka-rain", &[("city", "Osaka"), ("weather", "rain")][..]),
+            ("untagged", &[][..]),
+        ];
+        for (id, pairs) in tagged {
+            let mut item = memory_item(id);
+            item.id = id.to_string();
+            item.tags = tags(pairs);
+            plugin.store(item).await.unwrap();
+        }
+        let search = |include: &[(&str, &str)], exclude: &[(&str, &str)], match_all: bool| {
+            let plugin = &plugin;
+            let (include, exclude) = (tags(include), tags(exclude));
+            async move {
+                plugin
+                    .search_by_tags(include, exclude, match_all)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|item| item.id)
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        // AND
+        assert_eq!(
+            search(&[("city", "Tokyo"), ("weather", "rain")], &[], true).await,
+            vec!["tokyo-rain"]
+        );
+        // OR
+        assert_eq!(
+            search(&[("city", "Osaka"), ("weather", "sun")], &[], false).await,
+            vec!["osaka-rain", "tokyo-sun"]
+        );
+        // 除外
+        assert_eq!(
+            search(&[("city", "Tokyo")], &[("weather", "sun")], true).await,
+            vec!["tokyo-rain"]
+        );
+        // include が空なら除外だけで絞り込む
+        assert_eq!(
+            search(&[], &[("weather", "rain")], false).await,
+            vec!["tokyo-sun", "untagged"]
+        );
+        assert!(
+            search(&[("city", "Tokyo"), ("weather", "snow")], &[], true)
+                .await
+                .is_empty()
+        );
+
+        // 更新後は古いタグで見つからない
+        let mut item = plugin.retrieve(&"tokyo-sun".to_string()).await.unwrap();
+        item.tags = tags(&[("city", "Nagoya")]);
+        plugin.update(item).await.unwrap();
+        assert_eq!(
+            search(&[("city", "Tokyo")], &[], false).await,
+            vec!["tokyo-rain"]
+        );
+        assert_eq!(
+            search(&[("city", "Nagoya")], &[], false).await,
+            vec!["tokyo-sun"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_attachments() {
+        let plugin = SistenceMemoryPlugin::new(SistenceMemoryConfig::default(), None, None)
+            .await
+            .unwrap();
+        let mut item = memory_item("Tokyo is sunny in spring");
+        item.attachments.insert(
+            "source".to_string(),
+            serde_json::json!({"title": "Spring report", "page": 3}),
+        );
+        let id = plugin.store(item).await.unwrap();
+
+        assert_eq!(
+            plugin.get_attachment(&id, "source").await.unwrap(),
+            Some(serde_json::json!({"title": "Spring report", "page": 3}))
+        );
+        assert_eq!(plugin.get_attachment(&id, "missing").await.unwrap(), None);
+
+        plugin
+            .set_attachment(&id, "source", serde_json::json!({"title": "Summer report"}))
+            .await
+            .unwrap();
+        let item = plugin.retrieve(&id).await.unwrap();
+        assert_eq!(
+            item.attachments["source"],
+            serde_json::json!({"title": "Summer report"})
+        );
+
+        // 上限を超える添付は拒否し、元の値を残す
+        let large = Value::String("x".repeat(MAX_ATTACHMENT_BYTES));
+        assert!(matches!(
+            plugin.set_attachment(&id, "source", large.clone()).await,
+            Err(SistenceMemoryError::InvalidInput(_))
+        ));
+        assert_eq!(
+            plugin.get_attachment(&id, "source").await.unwrap(),
+            Some(serde_json::json!({"title": "Summer report"}))
+        );
+        let mut item = memory_item("Too large");
+        item.attachments.insert("blob".to_string(), large);
+        assert!(matches!(
+            plugin.store(item).await,
+            Err(SistenceMemoryError::InvalidInput(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_change_notifications_are_coalesced() {
+        let event_bus = Arc::new(EventBus::new(16));
+        let (mut events, _) = event_bus.subscribe();
+        let config = SistenceMemoryConfig {
+            id: "notes".to_string(),
+            change_coalesce_window: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let plugin = SistenceMemoryPlugin::new(config, None, None)
+            .await
+            .unwrap()
+            .with_event_bus(event_bus);
+        let subscription = plugin
+            .subscribe_changes("watcher", MemoryInterest::Topic("weather".to_string()))
+            .await
+            .unwrap();
+        async fn next_event(events: &mut EventReceiver) -> Event {
+            tokio::time::timeout(Duration::from_secs(1), events.recv())
+                .await
+                .expect("no notification")
+                .unwrap()
+        }
+        let string = |value: &str| crate::event::event_bus::Value::String(value.to_string());
+
+        // 関係のない項目は通知しない
+        plugin.store(memory_item("Lunch was good")).await.unwrap();
+        let mut item = memory_item("Rain expected tomorrow");
+        item.topics = vec!["weather".to_string()];
+        let id = plugin.store(item.clone()).await.unwrap();
+        item.id = id.clone();
+        item.content = "Heavy rain expected tomorrow".to_string();
+        plugin.update(item.clone()).await.unwrap();
+        plugin
+            .add_tags(&id, tags(&[("city", "Tokyo")]))
+            .await
+            .unwrap();
+
+        let event = next_event(&mut events).await;
+        assert_eq!(
+            event.event_type,
+            EventType::Custom(MEMORY_CHANGED_EVENT.to_string())
+        );
+        assert_eq!(event.parameters["to"], string("watcher"));
+        assert_eq!(event.parameters["subscription_id"], string(&subscription));
+        assert_eq!(event.parameters["namespace"], string("notes"));
+        assert_eq!(event.parameters["item_id"], string(&id));
+        assert_eq!(event.parameters["change_type"], string("stored"));
+        assert_eq!(
+            event.parameters["changes"],
+            crate::event::event_bus::Value::Integer(3)
+        );
+        assert!(!event.parameters.contains_key("content"));
+
+        plugin.delete(&id).await.unwrap();
+        let event = next_event(&mut events).await;
+        assert_eq!(event.parameters["item_id"], string(&id));
+        assert_eq!(event.parameters["change_type"], string("deleted"));
+
+        plugin.unsubscribe_changes(&subscription).await.unwrap();
+        plugin.store(item).await.unwrap();
+        assert!(
+            tokio::time::timeout(Duration::from_millis(200), events.recv())
+                .await
+                .is_err()
+        );
+    }
+
+    fn weights(intrinsic: f32, usage: f32, network: f32) -> ImportanceWeights {
+        ImportanceWeights {
+            intrinsic_weight: intrinsic,
+            usage_weight: usage,
+            network_weight: network,
+            emotional_weight: 0.0,
+            ..Default::default()
+        }
+    }
+
+    async fn base_score(plugin: &SistenceMemoryPlugin, id: &MemoryId) -> f32 {
+        plugin.retrieve(id).await.unwrap().importance.base_score
+    }
+
+    #[tokio::test]
+    async fn test_recompute_importance_applies_new_weights() {
+        let config = SistenceMemoryConfig {
+            importance_weights: weights(1.0, 0.0, 0.0),
+            importance_recompute_batch_size: 2,
+            ..Default::default()
+        };
+        let plugin = SistenceMemoryPlugin::new(config, None, None).await.unwrap();
+
+        // 信頼度 0.6、3 回参照された項目
+        let mut used = memory_item("Used often");
+        used.source.reliability = 0.6;
+        used.access_count = 3;
+        let used = plugin.store(used).await.unwrap();
+        // 信頼度 0.2、関連項目が 1 つの項目
+        let mut linked = memory_item("Linked to the other");
+        linked.source.reliability = 0.2;
+        linked.related_items = vec![used.clone()];
+        let linked = plugin.store(linked).await.unwrap();
+
+        let report = plugin.recompute_importance(None).await.unwrap();
+        assert_eq!(
+            report,
+            ImportanceRecomputeReport {
+                items_evaluated: 2,
+                items_changed: 2,
+                batches: 1,
+            }
+        );
+        assert!((base_score(&plugin, &used).await - 0.6).abs() < 1e-6);
+        assert!((base_score(&plugin, &linked).await - 0.2).abs() < 1e-6);
+
+        // 利用度だけを重視する: 1 - 1 / (1 + 3) と 0
+        plugin.set_importance_weights(weights(0.0, 1.0, 0.0)).await;
+        let report = plugin.recompute_importance(None).await.unwrap();
+        assert_eq!(report.items_changed, 2);
+        let used_importance = plugin.retrieve(&used).await.unwrap().importance;
+        assert!((used_importance.base_score - 0.75).abs() < 1e-6);
+        assert!((used_importance.score - 0.75 * 0.7).abs() < 1e-6);
+        assert_eq!(base_score(&plugin, &linked).await, 0.0);
+
+        // 指定した項目だけを再計算し、変化がなければ書き戻さない
+        plugin.set_importance_weights(weights(1.0, 0.0, 1.0)).await;
+        let report = plugin
+            .recompute_importance(Some(vec![linked.clone()]))
+            .await
+            .unwrap();
+        assert_eq!(report.items_changed, 1);
+        assert!((base_score(&plugin, &linked).await - 0.35).abs() < 1e-6);
+        assert!((base_score(&plugin, &used).await - 0.75).abs() < 1e-6);
+        let report = plugin
+            .recompute_importance(Some(vec![linked]))
+            .await
+            .unwrap();
+        assert_eq!(report.items_evaluated, 1);
+        assert_eq!(report.items_changed, 0);
+
+        // ポリシーは基本スコアに掛かる
+        plugin
+            .set_importance_policy(ImportancePolicy::UtilityFocus)
+            .await;
+        plugin.set_importance_weights(weights(0.0, 1.0, 0.0)).await;
+        plugin.recompute_importance(None).await.unwrap();
+        assert!((base_score(&plugin, &used).await - 0.675).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn test_importance_recompute_runs_in_background() {
+        let config = SistenceMemoryConfig {
+            importance_weights: weights(1.0, 0.0, 0.0),
+            importance_recompute_interval: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let plugin = Arc::new(SistenceMemoryPlugin::new(config, None, None).await.unwrap());
+        let mut item = memory_item("Recomputed later");
+        item.source.reliability = 0.8;
+        let id = plugin.store(item).await.unwrap();
+        let task = plugin.spawn_importance_recompute().unwrap();
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let importance = plugin.retrieve(&id).await.unwrap().importance;
+        assert!((importance.base_score - 0.8).abs() < 1e-6);
+
+        // プラグインが破棄されるとタスクも終わる
+        drop(plugin);
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(task.is_finished());
+    }
+
+    /// 50 items: even IDs belong to project atlas and odd ones to borealis,
+    /// every 5th has a priority tag, every 7th is archived, every 3rd is about
+    /// planning, and item `i` is created `i` days after the epoch of the corpus
+    async fn filter_corpus() -> (SistenceMemoryPlugin, SystemTime) {
+        let plugin = SistenceMemoryPlugin::new(SistenceMemoryConfig::default(), None, None)
+            .await
+            .unwrap();
+        let epoch = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let day = Duration::from_secs(86_400);
+        let item_types = [
+            ItemType::Conversation,
+            ItemType::Decision,
+            ItemType::Task,
+            ItemType::Information,
+        ];
+        let mut items = Vec::new();
+        for i in 0..50u32 {
+            let mut item = memory_item(&format!("Note {}", i));
+            item.id = format!("item-{:02}", i);
+            item.created_at = epoch + day * i;
+            item.updated_at = item.created_at + day;
+            item.item_type = item_types[i as usize % 4].clone();
+            item.topics = vec![if i % 3 == 0 { "planning" } else { "research" }.to_string()];
+            let project = if i % 2 == 0 { "atlas" } else { "borealis" };
+            item.tags = tags(&[("project", project)]);
+            if i % 5 == 0 {
+                item.tags.insert("priority".to_string(), "high".to_string());
+            }
+            if i % 7 == 0 {
+                item.tags.insert("archived".to_string(), "true".to_string());
+            }
+            item.importance.base_score = (i % 10) as f32 / 10.0;
+            items.push(item);
+        }
+        plugin.store_batch(items).await.unwrap();
+        (plugin, epoch)
+    }
+
+    async fn search_ids(plugin: &SistenceMemoryPlugin, filter: MemoryFilter) -> Vec<String> {
+        let filters = SearchFilters {
+            filter: Some(filter),
+            ..Default::default()
+        };
+        let mut ids: Vec<String> = plugin
+            .search("", Some(filters), Some(50))
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|item| item.id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    fn ids(numbers: &[u32]) -> Vec<String> {
+        numbers.iter().map(|i| format!("item-{:02}", i)).collect()
+    }
+
+    fn tag_equals(key: &str, value: &str) -> MemoryFilter {
+        MemoryFilter::TagEquals {
+            key: key.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_with_composite_filters() {
+        let (plugin, epoch) = filter_corpus().await;
+        let day = |n: u64| chrono::DateTime::from(epoch + Duration::from_secs(86_400 * n));
+
+        // atlas の 10 日目から 20 日目、会話を除く
+        let atlas_excluding_conversations = MemoryFilter::And(vec![
+            tag_equals("project", "atlas"),
+            MemoryFilter::TimeRange {
+                field: TimeField::Created,
+                start: Some(day(10)),
+                end: Some(day(20)),
+            },
+            MemoryFilter::Not(Box::new(MemoryFilter::ItemTypeIn(vec![
+                "Conversation".to_string(),
+            ]))),
+        ]);
+        assert_eq!(
+            search_ids(&plugin, atlas_excluding_conversations).await,
+            ids(&[10, 14, 18])
+        );
+
+        // 優先度付きの planning か、重要な borealis
+        let urgent = MemoryFilter::Or(vec![
+            MemoryFilter::And(vec![
+                MemoryFilter::TopicContains("planning".to_string()),
+                MemoryFilter::TagExists {
+                    key: "priority".to_string(),
+                },
+            ]),
+            MemoryFilter::And(vec![
+                tag_equals("project", "borealis"),
+                MemoryFilter::MinImportance(0.85),
+            ]),
+        ]);
+        assert_eq!(
+            search_ids(&plugin, urgent).await,
+            ids(&[0, 9, 15, 19, 29, 30, 39, 45, 49])
+        );
+
+        // 20 日目以降に更新された、アーカイブされていない優先度付きの決定とタスク
+        let open_work = MemoryFilter::And(vec![
+            MemoryFilter::TagExists {
+                key: "priority".to_string(),
+            },
+            MemoryFilter::Not(Box::new(MemoryFilter::Archived(true))),
+            MemoryFilter::ItemTypeIn(vec!["decision".to_string(), "task".to_string()]),
+            MemoryFilter::TimeRange {
+                field: TimeField::Updated,
+                start: Some(day(20)),
+                end: None,
+            },
+        ]);
+        assert_eq!(search_ids(&plugin, open_work).await, ids(&[25, 30, 45]));
+
+        // どれもタグとトピックのインデックスで候補を絞り込める
+        assert_eq!(plugin.full_scan_count(), 0);
+        search_ids(&plugin, MemoryFilter::MinImportance(0.5)).await;
+        assert_eq!(plugin.full_scan_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_search_rejects_invalid_filters() {
+        let (plugin, _) = filter_corpus().await;
+        let search = |filter: MemoryFilter| {
+            let plugin = &plugin;
+            async move {
+                let filters = SearchFilters {
+                    filter: Some(filter),
+                    ..Default::default()
+                };
+                match plugin.search("", Some(filters), None).await {
+                    Err(SistenceMemoryError::InvalidFilter(error)) => error,
+                    other => panic!("expected an invalid filter, got {:?}", other),
+                }
+            }
+        };
+
+        assert_eq!(
+            search(MemoryFilter::Not(Box::new(MemoryFilter::And(vec![])))).await,
+            FilterError::EmptyGroup("and")
+        );
+        assert_eq!(
+            search(MemoryFilter::Or(vec![MemoryFilter::ItemTypeIn(vec![
+                "memo".to_string()
+            ])]))
+            .await,
+            FilterError::UnknownItemType("memo".to_string())
+        );
+        assert_eq!(
+            search(MemoryFilter::MinImportance(1.5)).await,
+            FilterError::InvalidImportance(1.5)
+        );
+        // custom:<name> は独自の種類
+        assert!(
+            MemoryFilter::ItemTypeIn(vec!["custom:memo".to_string()])
+                .validate()
+                .is_ok()
+        );
+    }
+
+    fn search_context(topics: &[&str], activity: Option<&str>) -> SearchContext {
+        SearchContext {
+            context_id: "test".to_string(),
+            current_topics: topics.iter().map(|t| t.to_string()).collect(),
+            recent_items: Vec::new(),
+            query_text: None,
+            query_type: QueryType::Semantic,
+            strategy: None,
+            participants: Vec::new(),
+            current_activity: activity.map(str::to_string),
+            temporal_context: TemporalContext {
+                current_time: None,
+                time_focus: None,
+                relevant_periods: Vec::new(),
+                historical_context: None,
+            },
+            sistence_profile: None,
+            goals: None,
+            conversation_summary: None,
+            environment_factors: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_explain_relevance_for_context() {
+        let plugin = SistenceMemoryPlugin::new(SistenceMemoryConfig::default(), None, None)
+            .await
+            .unwrap();
+        let notes = [
+            ("report", "Quarterly report", "finance", 0.5),
+            ("hotels", "Tokyo hotel list", "travel", 0.2),
+            ("kyoto", "Kyoto trip plan", "travel", 0.9),
+        ];
+        for (id, content, topic, importance) in notes {
+            let mut item = memory_item(content);
+            item.id = id.to_string();
+            item.topics = vec![topic.to_string()];
+            item.importance.base_score = importance;
+            plugin.store(item).await.unwrap();
+        }
+        let context = search_context(&["travel"], Some("trip"));
+
+        let explained = plugin
+            .explain_relevance_for_context(context.clone(), None)
+            .await
+            .unwrap();
+        let ids: Vec<&str> = explained.iter().map(|e| e.item.id.as_str()).collect();
+        assert_eq!(ids, vec!["kyoto", "hotels", "report"]);
+        // get_relevant_for_context と同じ順序
+        let relevant = plugin
+            .get_relevant_for_context(context, None)
+            .await
+            .unwrap();
+        let relevant_ids: Vec<&str> = relevant.iter().map(|item| item.id.as_str()).collect();
+        assert_eq!(relevant_ids, ids);
+
+        let kyoto = &explained[0];
+        let factor = |name: &str| {
+            kyoto
+                .factors
+                .iter()
+                .find(|factor| factor.name == name)
+                .unwrap()
+                .score
+        };
+        assert_eq!(factor("topic_match"), 1.0);
+        assert_eq!(factor("importance"), 0.9);
+        assert_eq!(factor("activity_relevance"), 0.8);
+        assert!(factor("recency") > 0.99);
+        for explanation in &explained {
+            assert_eq!(explanation.factors.len(), 4);
+            let sum: f32 = explanation
+                .factors
+                .iter()
+                .map(RelevanceFactor::contribution)
+                .sum();
+            assert!((explanation.relevance - sum).abs() < 1e-6);
+        }
+        assert!(
+            explained
+                .windows(2)
+                .all(|w| w[0].relevance >= w[1].relevance)
+        );
+    }
 }
@@ -0,0 +1,444 @@
+// Clustering and topic extraction operations for the StatelessRelevantMemory implementation
+
+use std::collections::{BTreeMap, HashMap};
+
+use tracing::{debug, warn};
+
+use crate::provider::capabilities::relevant_memory::DetailedMemoryItem;
+use crate::provider::capabilities::sistence_memory::*;
+use crate::provider::embedding::cosine_similarity;
+
+use super::StatelessRelevantMemory;
+use super::utility_functions::matches_search_filters;
+
+/// Maximum k-means refinement iterations
+const MAX_KMEANS_ITERATIONS: usize = 50;
+
+/// Default number of item contents included in a label prompt
+const DEFAULT_LABEL_SAMPLE_SIZE: usize = 5;
+
+/// Maximum length of a generated topic label
+const MAX_LABEL_LENGTH: usize = 60;
+
+impl StatelessRelevantMemory {
+    /// Cluster memory items by embedding similarity and label each cluster
+    #[tracing::instrument(level = "debug", skip(self, params), err)]
+    pub async fn cluster_memory_items(
+        &self,
+        params: ClusterParams,
+    ) -> Result<ClusterResult, SistenceMemoryError> {
+        if let Some(filters) = &params.filters {
+            filters.validate()?;
+        }
+        let mut items: Vec<DetailedMemoryItem> = self
+            .memory_index
+            .iter()
+            .map(|entry| entry.value().clone())
+            .filter(|item| {
+                params
+                    .filters
+                    .as_ref()
+                    .is_none_or(|filters| matches_search_filters(item, filters))
+            })
+            .collect();
+
+        if items.is_empty() {
+            return Ok(ClusterResult {
+                clusters: Vec::new(),
+                assignments: HashMap::new(),
+                silhouette: None,
+            });
+        }
+
+        // Stable ordering keeps centroid initialisation deterministic
+        items.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.id.cmp(&b.id)));
+
+        let vectors = self.embed_items(&items).await?;
+
+        let (assignments, k) = match params.count {
+            ClusterCount::Fixed(0) => {
+                return Err(SistenceMemoryError::InvalidInput(
+                    "Cluster count must be greater than zero".to_string(),
+                ));
+            }
+            ClusterCount::Fixed(count) => {
+                let k = count.min(items.len());
+                (kmeans(&vectors, k), k)
+            }
+            ClusterCount::Auto { max } => {
+                let max_k = max.min(items.len());
+                if max_k < 2 {
+                    (vec![0; items.len()], 1)
+                } else {
+                    (2..=max_k)
+                        .map(|k| {
+                            let assignments = kmeans(&vectors, k);
+                            let score = silhouette_score(&vectors, &assignments, k).unwrap_or(-1.0);
+                            (assignments, k, score)
+                        })
+                        .max_by(|a, b| a.2.partial_cmp(&b.2).unwrap_or(std::cmp::Ordering::Equal))
+                        .map(|(assignments, k, _)| (assignments, k))
+                        .unwrap_or((vec![0; items.len()], 1))
+                }
+            }
+        };
+        let silhouette = silhouette_score(&vectors, &assignments, k);
+        debug!("Clustered {} items into {} clusters", items.len(), k);
+
+        // Group member indices per cluster, largest cluster first
+        let mut groups: Vec<Vec<usize>> = vec![Vec::new(); k];
+        for (index, cluster) in assignments.iter().enumerate() {
+            groups[*cluster].push(index);
+        }
+        groups.retain(|members| !members.is_empty());
+        groups.sort_by_key(|group| std::cmp::Reverse(group.len()));
+
+        let sample_size = params
+            .label_sample_size
+            .unwrap_or(DEFAULT_LABEL_SAMPLE_SIZE)
+            .max(1);
+        let mut clusters = Vec::with_capacity(groups.len());
+        let mut item_assignments = HashMap::with_capacity(items.len());
+        for (cluster_id, members) in groups.into_iter().enumerate() {
+            let member_vectors: Vec<&Vec<f32>> = members.iter().map(|i| &vectors[*i]).collect();
+            let centroid = mean_vector(&member_vectors);
+            let cohesion = member_vectors
+                .iter()
+                .map(|v| cosine_similarity(v, &centroid))
+                .sum::<f32>()
+                / member_vectors.len() as f32;
+
+            // Members closest to the centroid are the most representative
+            let mut representative = members.clone();
+            representative.sort_by(|a, b| {
+                cosine_similarity(&vectors[*b], &centroid)
+                    .partial_cmp(&cosine_similarity(&vectors[*a], &centroid))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            let member_items: Vec<&DetailedMemoryItem> =
+                representative.iter().map(|i| &items[*i]).collect();
+
+            let label = if params.generate_labels {
+                self.generate_cluster_label(&member_items, sample_size)
+                    .await
+                    .unwrap_or_else(|| fallback_cluster_label(&member_items, cluster_id))
+            } else {
+                fallback_cluster_label(&member_items, cluster_id)
+            };
+
+            let item_ids: Vec<MemoryId> = members.iter().map(|i| items[*i].id.clone()).collect();
+            for id in &item_ids {
+                item_assignments.insert(id.clone(), cluster_id);
+            }
+
+            clusters.push(MemoryCluster {
+                cluster_id,
+                label,
+                item_ids,
+                cohesion: cohesion.clamp(0.0, 1.0),
+            });
+        }
+
+        Ok(ClusterResult {
+            clusters,
+            assignments: item_assignments,
+            silhouette,
+        })
+    }
+
+    /// Embed item contents with the embedding client, or term vectors if none is configured
+    async fn embed_items(
+        &self,
+        items: &[DetailedMemoryItem],
+    ) -> Result<Vec<Vec<f32>>, SistenceMemoryError> {
+        let texts: Vec<String> = items.iter().map(|item| item.content.clone()).collect();
+
+        // スケジューラがあればキャッシュ済みの埋め込みを使う
+        if let Some(scheduler) = &self.embedding_scheduler {
+            return scheduler
+                .embed_texts(&texts)
+                .await
+                .map_err(|e| SistenceMemoryError::LlmError(e.to_string()));
+        }
+        let Some(client) = &self.embedding_client else {
+            return Ok(term_frequency_vectors(&texts));
+        };
+
+        let vectors = client
+            .embed(&texts)
+            .await
+            .map_err(|e| SistenceMemoryError::LlmError(e.to_string()))?;
+        if vectors.len() != texts.len() {
+            return Err(SistenceMemoryError::InternalError(format!(
+                "Embedding client returned {} vectors for {} items",
+                vectors.len(),
+                texts.len()
+            )));
+        }
+        Ok(vectors)
+    }
+
+    /// Ask the LLM for a short topic label describing the given items
+    async fn generate_cluster_label(
+        &self,
+        items: &[&DetailedMemoryItem],
+        sample_size: usize,
+    ) -> Option<String> {
+        let samples = items
+            .iter()
+            .take(sample_size)
+            .map(|item| format!("- {}", item.content.replace('\n', " ")))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let prompt = format!(
+            r#"You are the topic extraction component of the Sistence memory system. The following memory items were grouped together because they are semantically similar.
+
+ITEMS:
+{samples}
+
+Respond with a short topic label (2-5 words) that describes the common theme of these items. Respond with the label only."#
+        );
+
+        match self.llm_client.send_message(&prompt, &self.config).await {
+            Ok(response) => parse_cluster_label(&response.content),
+            Err(e) => {
+                warn!("Failed to generate cluster label: {}", e);
+                None
+            }
+        }
+    }
+}
+
+/// Extract a clean label from an LLM response
+fn parse_cluster_label(content: &str) -> Option<String> {
+    let line = content.lines().map(str::trim).find(|l| !l.is_empty())?;
+    let line = line
+        .strip_prefix("Topic:")
+        .or_else(|| line.strip_prefix("Label:"))
+        .unwrap_or(line)
+        .trim()
+        .trim_matches(|c| c == '"' || c == '\'' || c == '*' || c == '.')
+        .trim();
+    if line.is_empty() {
+        return None;
+    }
+    Some(line.chars().take(MAX_LABEL_LENGTH).collect())
+}
+
+/// Label a cluster from its most common topic or keyword
+fn fallback_cluster_label(items: &[&DetailedMemoryItem], cluster_id: usize) -> String {
+    let mut topic_counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for item in items {
+        for topic in &item.topics {
+            *topic_counts.entry(topic.as_str()).or_default() += 1;
+        }
+    }
+    if let Some((topic, _)) = topic_counts.iter().max_by_key(|(_, count)| **count) {
+        return topic.to_string();
+    }
+
+    let texts: Vec<String> = items.iter().map(|item| item.content.clone()).collect();
+    let mut term_counts: BTreeMap<String, usize> = BTreeMap::new();
+    for term in texts.iter().flat_map(|text| tokenize_terms(text)) {
+        *term_counts.entry(term).or_default() += 1;
+    }
+    term_counts
+        .into_iter()
+        .max_by_key(|(_, count)| *count)
+        .map(|(term, _)| term)
+        .unwrap_or_else(|| format!("Cluster {}", cluster_id + 1))
+}
+
+/// Lowercased alphanumeric terms longer than three characters
+fn tokenize_terms(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|term| term.chars().count() > 3)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Build term-frequency vectors over the shared vocabulary of the texts
+fn term_frequency_vectors(texts: &[String]) -> Vec<Vec<f32>> {
+    let tokenized: Vec<Vec<String>> = texts.iter().map(|text| tokenize_terms(text)).collect();
+    let mut vocabulary: BTreeMap<&str, usize> = BTreeMap::new();
+    for term in tokenized.iter().flatten() {
+        let next = vocabulary.len();
+        vocabulary.entry(term.as_str()).or_insert(next);
+    }
+
+    tokenized
+        .iter()
+        .map(|terms| {
+            let mut vector = vec![0.0; vocabulary.len()];
+            for term in terms {
+                vector[vocabulary[term.as_str()]] += 1.0;
+            }
+            vector
+        })
+        .collect()
+}
+
+/// Element-wise mean of the given vectors
+fn mean_vector(vectors: &[&Vec<f32>]) -> Vec<f32> {
+    let dimension = vectors.iter().map(|v| v.len()).max().unwrap_or(0);
+    let mut mean = vec![0.0; dimension];
+    for vector in vectors {
+        for (i, value) in vector.iter().enumerate() {
+            mean[i] += value;
+        }
+    }
+    let count = vectors.len().max(1) as f32;
+    mean.iter_mut().for_each(|value| *value /= count);
+    mean
+}
+
+fn cosine_distance(a: &[f32], b: &[f32]) -> f32 {
+    1.0 - cosine_similarity(a, b)
+}
+
+/// Spherical k-means with farthest-point initialisation. Returns the cluster
+/// index of each vector.
+fn kmeans(vectors: &[Vec<f32>], k: usize) -> Vec<usize> {
+    if k <= 1 || vectors.len() <= 1 {
+        return vec![0; vectors.len()];
+    }
+
+    // Farthest-point initialisation is deterministic for a given input order
+    let mut centroids: Vec<Vec<f32>> = vec![vectors[0].clone()];
+    while centroids.len() < k {
+        let next = vectors
+            .iter()
+            .map(|v| {
+                centroids
+                    .iter()
+                    .map(|c| cosine_distance(v, c))
+                    .fold(f32::MAX, f32::min)
+            })
+            .enumerate()
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(index, _)| index)
+            .unwrap_or(0);
+        centroids.push(vectors[next].clone());
+    }
+
+    let mut assignments = vec![usize::MAX; vectors.len()];
+    for _ in 0..MAX_KMEANS_ITERATIONS {
+        let mut changed = false;
+        for (i, vector) in vectors.iter().enumerate() {
+            let nearest = centroids
+                .iter()
+                .map(|c| cosine_similarity(vector, c))
+                .enumerate()
+                .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+                .map(|(index, _)| index)
+                .unwrap_or(0);
+            if assignments[i] != nearest {
+                assignments[i] = nearest;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+
+        for (cluster, centroid) in centroids.iter_mut().enumerate() {
+            let members: Vec<&Vec<f32>> = vectors
+                .iter()
+                .zip(&assignments)
+                .filter(|(_, assigned)| **assigned == cluster)
+                .map(|(v, _)| v)
+                .collect();
+            // Empty clusters keep their previous centroid
+            if !members.is_empty() {
+                *centroid = mean_vector(&members);
+            }
+        }
+    }
+
+    assignments
+}
+
+/// Mean silhouette coefficient using cosine distance. None when fewer than
+/// two clusters are populated.
+fn silhouette_score(vectors: &[Vec<f32>], assignments: &[usize], k: usize) -> Option<f32> {
+    let populated = (0..k)
+        .filter(|cluster| assignments.contains(cluster))
+        .count();
+    if populated < 2 {
+        return None;
+    }
+
+    let mut total = 0.0;
+    for (i, vector) in vectors.iter().enumerate() {
+        let own = assignments[i];
+        let mut distances = vec![(0.0f32, 0usize); k];
+        for (j, other) in vectors.iter().enumerate() {
+            if i != j {
+                let entry = &mut distances[assignments[j]];
+                entry.0 += cosine_distance(vector, other);
+                entry.1 += 1;
+            }
+        }
+
+        if distances[own].1 == 0 {
+            // Singleton clusters contribute zero by convention
+            continue;
+        }
+        let a = distances[own].0 / distances[own].1 as f32;
+        let b = distances
+            .iter()
+            .enumerate()
+            .filter(|(cluster, (_, count))| *cluster != own && *count > 0)
+            .map(|(_, (sum, count))| sum / *count as f32)
+            .fold(f32::MAX, f32::min);
+        let denominator = a.max(b);
+        if denominator > 0.0 {
+            total += (b - a) / denominator;
+        }
+    }
+
+    Some(total / vectors.len() as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kmeans_separates_orthogonal_groups() {
+        let vectors = vec![
+            vec![1.0, 0.0],
+            vec![0.0, 1.0],
+            vec![0.9, 0.1],
+            vec![0.1, 0.9],
+        ];
+        let assignments = kmeans(&vectors, 2);
+        assert_eq!(assignments[0], assignments[2]);
+        assert_eq!(assignments[1], assignments[3]);
+        assert_ne!(assignments[0], assignments[1]);
+        assert!(silhouette_score(&vectors, &assignments, 2).unwrap() > 0.5);
+    }
+
+    #[test]
+    fn test_parse_cluster_label() {
+        assert_eq!(
+            parse_cluster_label("\n\"Pet Care\"\n"),
+            Some("Pet Care".to_string())
+        );
+        assert_eq!(
+            parse_cluster_label("Topic: Stock Market"),
+            Some("Stock Market".to_string())
+        );
+        assert_eq!(parse_cluster_label("   "), None);
+    }
+
+    #[test]
+    fn test_term_frequency_vectors_share_vocabulary() {
+        let vectors = term_frequency_vectors(&[
+            "cats love naps".to_string(),
+            "stocks rally today".to_string(),
+        ]);
+        assert_eq!(vectors[0].len(), vectors[1].len());
+        assert_eq!(cosine_similarity(&vectors[0], &vectors[1]), 0.0);
+    }
+}
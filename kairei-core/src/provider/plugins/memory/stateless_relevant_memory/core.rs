@@ -2,6 +2,7 @@
 
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::SystemTime;
 
 use dashmap::DashMap;
@@ -16,6 +17,9 @@ use crate::provider::capabilities::relevant_memory::{
 };
 use crate::provider::capabilities::sistence_memory::*;
 use crate::provider::capabilities::storage::StorageBackend;
+use crate::provider::contribution::ContextContribution;
+use crate::provider::embedding::ProviderEmbedding;
+use crate::provider::embedding_scheduler::EmbeddingScheduler;
 use crate::provider::llm::{LLMResponse, ProviderLLM};
 use crate::provider::plugin::PluginContext;
 use crate::provider::plugin::ProviderPlugin;
@@ -34,6 +38,12 @@ pub struct StatelessRelevantMemory {
     /// LLM client
     pub llm_client: Arc<dyn ProviderLLM>,
 
+    /// Embedding client for semantic operations (falls back to term vectors when absent)
+    pub embedding_client: Option<Arc<dyn ProviderEmbedding>>,
+
+    /// Batches and caches the embeddings of stored items and queries
+    pub embedding_scheduler: Option<Arc<EmbeddingScheduler>>,
+
     /// Memory index - maps memory items by ID
     pub memory_index: Arc<DashMap<String, DetailedMemoryItem>>,
 
@@ -45,6 +55,9 @@ pub struct StatelessRelevantMemory {
 
     /// Provider configuration
     pub config: ProviderConfig,
+
+    /// Number of filtered searches that checked every item
+    full_scans: AtomicUsize,
 }
 
 impl StatelessRelevantMemory {
@@ -59,13 +72,26 @@ impl StatelessRelevantMemory {
             id,
             storage,
             llm_client,
+            embedding_client: None,
+            embedding_scheduler: None,
             memory_index: Arc::new(DashMap::new()),
             topic_index: Arc::new(DashMap::new()),
             tag_index: Arc::new(DashMap::new()),
             config,
+            full_scans: AtomicUsize::new(0),
         }
     }
 
+    /// Number of filtered searches that found no candidates in the tag and topic
+    /// indexes and checked every item
+    pub fn full_scan_count(&self) -> usize {
+        self.full_scans.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn record_full_scan(&self) {
+        self.full_scans.fetch_add(1, Ordering::Relaxed);
+    }
+
     /// Create a new StatelessRelevantMemory with importance weights
     pub fn new_with_weights(
         id: String,
@@ -81,7 +107,11 @@ impl StatelessRelevantMemory {
             common_config: crate::config::CommonConfig {
                 temperature: 0.7,
                 max_tokens,
+                top_p: None,
                 model,
+                output_format: Default::default(),
+                response_schema: None,
+                max_repair_attempts: 2,
             },
             provider_specific: {
                 let mut provider_specific = HashMap::new();
@@ -106,10 +136,31 @@ impl StatelessRelevantMemory {
             provider_type: crate::provider::provider::ProviderType::SimpleExpert,
             endpoint: crate::config::EndpointConfig::default(),
             plugin_configs: HashMap::new(),
+            concurrency: crate::config::ConcurrencyConfig::default(),
         };
 
         Self::new(id, storage, llm_client, provider_config)
     }
+
+    /// Use the given embedding client for semantic operations such as clustering
+    pub fn with_embedding_client(mut self, embedding_client: Arc<dyn ProviderEmbedding>) -> Self {
+        self.embedding_client = Some(embedding_client);
+        self
+    }
+
+    /// Queue stored items to be embedded by the given scheduler, and embed through its
+    /// cache
+    pub fn with_embedding_scheduler(mut self, scheduler: Arc<EmbeddingScheduler>) -> Self {
+        self.embedding_scheduler = Some(scheduler);
+        self
+    }
+
+    /// Queue the item to be embedded when a scheduler is configured
+    fn schedule_embedding(&self, item: &DetailedMemoryItem) {
+        if let Some(scheduler) = &self.embedding_scheduler {
+            scheduler.enqueue(item.id.clone(), item.content.clone());
+        }
+    }
 }
 
 // Core memory operations implementation
@@ -127,10 +178,28 @@ impl RelevantMemoryCapability for StatelessRelevantMemory {
 
         // Update indexes
         self.update_indexes(&item);
+        self.schedule_embedding(&item);
 
         Ok(item.id.clone())
     }
 
+    #[tracing::instrument(level = "debug", skip(self, items), err)]
+    async fn store_memory_items(
+        &self,
+        items: Vec<DetailedMemoryItem>,
+    ) -> Result<Vec<MemoryId>, SistenceMemoryError> {
+        // Store in storage with one write
+        self.store_batch_to_storage(&items).await?;
+
+        // Update indexes
+        for item in &items {
+            self.update_indexes(item);
+            self.schedule_embedding(item);
+        }
+
+        Ok(items.into_iter().map(|item| item.id).collect())
+    }
+
     #[tracing::instrument(level = "debug", skip(self), err)]
     async fn retrieve_memory_item(
         &self,
@@ -160,6 +229,7 @@ impl RelevantMemoryCapability for StatelessRelevantMemory {
 
         // Update memory index
         self.update_indexes(&item);
+        self.schedule_embedding(&item);
 
         Ok(())
     }
@@ -167,14 +237,10 @@ impl RelevantMemoryCapability for StatelessRelevantMemory {
     #[tracing::instrument(level = "debug", skip(self), err)]
     async fn delete_memory_item(&self, id: &MemoryId) -> Result<(), SistenceMemoryError> {
         // Remove from storage
-        /*
-        let item_key = format!("memory_items/{}", id);
-        self.storage.delete(&item_key).await
-            .map_err(|e| SistenceMemoryError::StorageError(e))?;
+        self.delete_from_storage(id).await?;
 
         // Remove from indexes
         self.remove_from_indexes(id);
-        */
 
         Ok(())
     }
@@ -222,6 +288,25 @@ impl RelevantMemoryCapability for StatelessRelevantMemory {
         Ok(result)
     }
 
+    async fn score_context_relevance(
+        &self,
+        context: SearchContext,
+        max_items: usize,
+        min_relevance: f32,
+    ) -> Result<Vec<(DetailedMemoryItem, f32, Vec<RelevanceFactor>)>, SistenceMemoryError> {
+        Ok(self.score_context_relevance(&context, max_items, min_relevance))
+    }
+
+    async fn search_by_tags(
+        &self,
+        include: HashMap<String, String>,
+        exclude: HashMap<String, String>,
+        match_all: bool,
+    ) -> Result<Vec<DetailedMemoryItem>, SistenceMemoryError> {
+        // Delegate to search_operations implementation
+        Ok(self.search_by_tags(&include, &exclude, match_all))
+    }
+
     // === Item Relationship Management ===
 
     async fn build_relationship_graph(
@@ -334,6 +419,14 @@ impl RelevantMemoryCapability for StatelessRelevantMemory {
         Ok(enhanced_metadata)
     }
 
+    async fn cluster_memory_items(
+        &self,
+        params: ClusterParams,
+    ) -> Result<ClusterResult, SistenceMemoryError> {
+        // Delegate to cluster_operations implementation
+        self.cluster_memory_items(params).await
+    }
+
     async fn reevaluate_importance(
         &self,
         item_id: &MemoryId,
@@ -398,10 +491,13 @@ impl ProviderPlugin for StatelessRelevantMemory {
         CapabilityType::Memory
     }
 
-    async fn generate_section<'a>(&self, _context: &PluginContext<'a>) -> ProviderResult<Section> {
+    async fn generate_section<'a>(
+        &self,
+        _context: &PluginContext<'a>,
+    ) -> ProviderResult<ContextContribution> {
         // Generate a section for the prompt
         let section = Section::new("Relevant Memory Context");
-        Ok(section)
+        Ok(section.into())
     }
 
     async fn process_response<'a>(
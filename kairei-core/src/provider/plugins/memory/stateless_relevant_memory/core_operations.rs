@@ -5,11 +5,17 @@ use std::time::SystemTime;
 
 use uuid::Uuid;
 
+use crate::persistence;
 use crate::provider::capabilities::relevant_memory::DetailedMemoryItem;
+use crate::provider::capabilities::shared_memory::Metadata;
 use crate::provider::capabilities::sistence_memory::*;
+use crate::provider::capabilities::storage::{StorageError, ValueWithMetadata};
 
 use super::StatelessRelevantMemory;
 
+/// Storage namespace holding serialized memory items
+const MEMORY_NAMESPACE: &str = "sistence_memory";
+
 impl StatelessRelevantMemory {
     /// Store a memory item in the storage backend
     #[tracing::instrument(level = "debug", skip(self, item), fields(item_id = %item.id), err)]
@@ -17,20 +23,51 @@ impl StatelessRelevantMemory {
         &self,
         item: &DetailedMemoryItem,
     ) -> Result<(), SistenceMemoryError> {
-        /*
         let item_key = format!("memory_items/{}", item.id);
-        let item_data = serde_json::to_vec(item)
-            .map_err(|e| SistenceMemoryError::SerializationError(format!("Failed to serialize memory item: {}", e)))?;
-        let data = ValueWithMetadata {
-            value: item_data,
-            metadata: Metadata::default(),
-            expiry: None,
-        };
+        let data = Self::to_storage_value(item)?;
+
+        self.storage
+            .save_key(MEMORY_NAMESPACE, &item_key, &data)
+            .await
+            .map_err(SistenceMemoryError::StorageError)
+    }
+
+    /// Store several memory items in the storage backend with a single write
+    #[tracing::instrument(level = "debug", skip(self, items), fields(count = items.len()), err)]
+    pub async fn store_batch_to_storage(
+        &self,
+        items: &[DetailedMemoryItem],
+    ) -> Result<(), SistenceMemoryError> {
+        let mut data = self
+            .storage
+            .load(MEMORY_NAMESPACE)
+            .await
+            .map_err(SistenceMemoryError::StorageError)?;
+        for item in items {
+            data.insert(
+                format!("memory_items/{}", item.id),
+                Self::to_storage_value(item)?,
+            );
+        }
 
-        self.storage.save_key("default", &item_key, &data).await
-            .map_err(|e| SistenceMemoryError::StorageError(e))
-             */
-        todo!()
+        self.storage
+            .save(MEMORY_NAMESPACE, &data)
+            .await
+            .map_err(SistenceMemoryError::StorageError)
+    }
+
+    fn to_storage_value(
+        item: &DetailedMemoryItem,
+    ) -> Result<ValueWithMetadata, SistenceMemoryError> {
+        let value = persistence::to_stored(item).map_err(StorageError::from)?;
+        Ok(ValueWithMetadata {
+            metadata: Metadata {
+                size: value.to_string().len(),
+                ..Metadata::default()
+            },
+            value,
+            expiry: None,
+        })
     }
 
     /// Retrieve a memory item from the storage backend
@@ -39,21 +76,35 @@ impl StatelessRelevantMemory {
         &self,
         id: &str,
     ) -> Result<DetailedMemoryItem, SistenceMemoryError> {
-        let _item_key = format!("memory_items/{}", id);
-        /*
-        let item_data = self.storage.load(&item_key).await
-            .map_err(|e| SistenceMemoryError::StorageError(e))?
+        let item_key = format!("memory_items/{}", id);
+        let mut items = self
+            .storage
+            .load(MEMORY_NAMESPACE)
+            .await
+            .map_err(SistenceMemoryError::StorageError)?;
+        let data = items
+            .remove(&item_key)
             .ok_or_else(|| SistenceMemoryError::NotFound(id.to_string()))?;
 
-        serde_json::from_slice(&item_data)
-            .map_err(|e| SistenceMemoryError::SerializationError(format!("Failed to deserialize memory item: {}", e)))
-        */
-        todo!()
+        Ok(persistence::from_stored(data.value).map_err(StorageError::from)?)
+    }
+
+    /// Delete a memory item from the storage backend
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    pub async fn delete_from_storage(&self, id: &str) -> Result<(), SistenceMemoryError> {
+        let item_key = format!("memory_items/{}", id);
+        self.storage
+            .delete_key(MEMORY_NAMESPACE, &item_key)
+            .await
+            .map_err(SistenceMemoryError::StorageError)
     }
 
     /// Update memory indexes with the given item
     #[tracing::instrument(level = "debug", skip(self, item), fields(item_id = %item.id))]
     pub fn update_indexes(&self, item: &DetailedMemoryItem) {
+        // 更新でタグやトピックが変わった場合に古いエントリを残さない
+        self.remove_from_indexes(&item.id);
+
         // Add to memory index
         self.memory_index.insert(item.id.clone(), item.clone());
 
@@ -133,6 +184,7 @@ impl StatelessRelevantMemory {
             item_type: detailed.item_type,
             topics: detailed.topics,
             tags: detailed.tags,
+            attachments: detailed.attachments,
             source: detailed.source,
             references,
             related_items: detailed.related_items,
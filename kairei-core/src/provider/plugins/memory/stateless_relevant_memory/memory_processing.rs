@@ -169,6 +169,7 @@ impl StatelessRelevantMemory {
                 ("key".to_string(), key.to_string()),
                 ("namespace".to_string(), namespace.to_string()),
             ]),
+            attachments: HashMap::new(),
             source: Source {
                 source_type: "working_memory".to_string(),
                 source_id: "system".to_string(),
@@ -27,6 +27,9 @@ mod metadata_operations;
 // Memory processing operations
 mod memory_processing;
 
+// Clustering operations
+mod cluster_operations;
+
 // Re-export the StatelessRelevantMemory struct
 pub use core::StatelessRelevantMemory;
 
@@ -1,6 +1,6 @@
 // Search and relevance operations for the StatelessRelevantMemory implementation
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use serde_json::json;
 use tracing::debug;
@@ -11,7 +11,7 @@ use crate::provider::capabilities::sistence_memory::*;
 use super::StatelessRelevantMemory;
 use super::utility_functions::{
     calculate_reference_similarity, calculate_tag_similarity, calculate_text_similarity,
-    calculate_topic_match,
+    calculate_topic_match, matches_search_filters,
 };
 use crate::provider::capabilities::relevant_memory::RelevantMemoryCapability;
 
@@ -20,6 +20,153 @@ type SearchResultType = Vec<(DetailedMemoryItem, f32, HashMap<String, f32>)>;
 impl StatelessRelevantMemory {
     // === Advanced Search Operations ===
 
+    /// Items matching a tag predicate, ordered by ID.
+    ///
+    /// Candidates come from the tag index: the intersection of the included tags'
+    /// entries with `match_all`, their union otherwise. Only an empty `include`
+    /// scans every item.
+    #[tracing::instrument(level = "debug", skip(self))]
+    pub fn search_by_tags(
+        &self,
+        include: &HashMap<String, String>,
+        exclude: &HashMap<String, String>,
+        match_all: bool,
+    ) -> Vec<DetailedMemoryItem> {
+        let candidates: HashSet<String> = if include.is_empty() {
+            self.memory_index
+                .iter()
+                .map(|entry| entry.key().clone())
+                .collect()
+        } else {
+            let mut postings: Vec<HashSet<String>> = include
+                .iter()
+                .map(|(key, value)| self.tagged_ids(key, value))
+                .collect();
+            if match_all {
+                // 小さい集合から絞り込む
+                postings.sort_by_key(HashSet::len);
+                let mut postings = postings.into_iter();
+                let first = postings.next().unwrap_or_default();
+                postings.fold(first, |ids, posting| {
+                    ids.intersection(&posting).cloned().collect()
+                })
+            } else {
+                postings.into_iter().flatten().collect()
+            }
+        };
+
+        let excluded: HashSet<String> = exclude
+            .iter()
+            .flat_map(|(key, value)| self.tagged_ids(key, value))
+            .collect();
+
+        let has_tag = |item: &DetailedMemoryItem, (key, value): (&String, &String)| {
+            item.tags.get(key) == Some(value)
+        };
+        let mut items: Vec<DetailedMemoryItem> = candidates
+            .difference(&excluded)
+            .filter_map(|id| self.memory_index.get(id).map(|item| item.clone()))
+            // インデックスのキーは "key:value" なので、`:` を含むタグの取り違えを実際のタグで除く
+            .filter(|item| {
+                include.is_empty()
+                    || if match_all {
+                        include.iter().all(|tag| has_tag(item, tag))
+                    } else {
+                        include.iter().any(|tag| has_tag(item, tag))
+                    }
+            })
+            .filter(|item| !exclude.iter().any(|tag| has_tag(item, tag)))
+            .collect();
+        items.sort_by(|a, b| a.id.cmp(&b.id));
+        items
+    }
+
+    /// IDs of the items tagged `key: value` according to the tag index
+    fn tagged_ids(&self, key: &str, value: &str) -> HashSet<String> {
+        self.tag_index
+            .get(&format!("{}:{}", key, value))
+            .map(|ids| ids.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// IDs of the items tagged `key` with any value according to the tag index
+    fn tag_key_ids(&self, key: &str) -> HashSet<String> {
+        let prefix = format!("{}:", key);
+        self.tag_index
+            .iter()
+            .filter(|entry| entry.key().starts_with(&prefix))
+            .flat_map(|entry| entry.value().clone())
+            .collect()
+    }
+
+    /// IDs of the items with the topic according to the topic index
+    fn topic_ids(&self, topic: &str) -> HashSet<String> {
+        self.topic_index
+            .get(topic)
+            .map(|ids| ids.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// IDs of the items that can match `filter` according to the tag and topic
+    /// indexes, `None` when the indexes cannot narrow it down
+    fn filter_candidates(&self, filter: &MemoryFilter) -> Option<HashSet<String>> {
+        match filter {
+            MemoryFilter::TagEquals { key, value } => Some(self.tagged_ids(key, value)),
+            MemoryFilter::TagExists { key } => Some(self.tag_key_ids(key)),
+            MemoryFilter::TopicContains(topic) => Some(self.topic_ids(topic)),
+            MemoryFilter::Archived(true) => Some(self.tagged_ids("archived", "true")),
+            // 絞り込める子の積。どれも絞り込めなければ全件
+            MemoryFilter::And(filters) => filters
+                .iter()
+                .filter_map(|filter| self.filter_candidates(filter))
+                .reduce(|ids, other| ids.intersection(&other).cloned().collect()),
+            // 和は全ての子が絞り込めるときだけ
+            MemoryFilter::Or(filters) => filters
+                .iter()
+                .map(|filter| self.filter_candidates(filter))
+                .collect::<Option<Vec<_>>>()
+                .map(|postings| postings.into_iter().flatten().collect()),
+            _ => None,
+        }
+    }
+
+    /// Items to check against `filters`: the index candidates of the topics and
+    /// the filter tree, or every item when the indexes cannot narrow them down
+    fn filtered_candidates(&self, filters: Option<&SearchFilters>) -> Vec<DetailedMemoryItem> {
+        let Some(filters) = filters else {
+            return self
+                .memory_index
+                .iter()
+                .map(|e| e.value().clone())
+                .collect();
+        };
+        let topics: Option<HashSet<String>> = filters
+            .topics
+            .as_ref()
+            .map(|topics| topics.iter().flat_map(|t| self.topic_ids(t)).collect());
+        let tree = filters
+            .filter
+            .as_ref()
+            .and_then(|filter| self.filter_candidates(filter));
+        let candidates: Option<HashSet<String>> = match (topics, tree) {
+            (Some(topics), Some(tree)) => Some(topics.intersection(&tree).cloned().collect()),
+            (topics, tree) => topics.or(tree),
+        };
+        match candidates {
+            Some(ids) => ids
+                .iter()
+                .filter_map(|id| self.memory_index.get(id).map(|item| item.clone()))
+                .collect(),
+            None => {
+                self.record_full_scan();
+                self.memory_index
+                    .iter()
+                    .map(|e| e.value().clone())
+                    .collect()
+            }
+        }
+    }
+
     #[tracing::instrument(level = "debug", skip(self, filters, _context), err)]
     pub async fn search_with_relevance(
         &self,
@@ -32,44 +179,17 @@ impl StatelessRelevantMemory {
         // Simple implementation - in a real system this would use more sophisticated search
         let mut results = Vec::new();
 
-        // Score and filter items
-        for item_ref in self.memory_index.iter() {
-            let item = item_ref.value().clone();
+        if let Some(filters) = &filters {
+            filters.validate()?;
+        }
+        let items = self.filtered_candidates(filters.as_ref());
 
+        // Score and filter items
+        for item in items {
             // Apply filters if provided
             if let Some(filters) = &filters {
-                // Filter by item type
-                if let Some(item_types) = &filters.item_types {
-                    if !item_types.contains(&item.item_type) {
-                        continue;
-                    }
-                }
-
-                // Filter by topics
-                if let Some(topics) = &filters.topics {
-                    if !topics.iter().any(|t| item.topics.contains(t)) {
-                        continue;
-                    }
-                }
-
-                // Filter by time range
-                if let Some(time_start) = filters.time_start {
-                    if item.created_at < time_start {
-                        continue;
-                    }
-                }
-
-                if let Some(time_end) = filters.time_end {
-                    if item.created_at > time_end {
-                        continue;
-                    }
-                }
-
-                // Filter by importance
-                if let Some(min_importance) = filters.min_importance {
-                    if item.importance.base_score < min_importance {
-                        continue;
-                    }
+                if !matches_search_filters(&item, filters) {
+                    continue;
                 }
             }
 
@@ -158,6 +278,7 @@ impl StatelessRelevantMemory {
             time_end: None,
             min_importance: Some(0.1), // Low threshold for comprehensive results
             custom_filters: None,
+            filter: None,
         };
 
         // Perform the relevance-based search
@@ -220,21 +341,15 @@ impl StatelessRelevantMemory {
         Ok(result)
     }
 
-    #[tracing::instrument(level = "debug", skip(self, context), fields(context_id = %context.context_id, max_items = %max_items), err)]
-    pub async fn get_context_relevant(
+    /// Items scored against `context`, most relevant first, with the factors of
+    /// each score. Items scoring below `min_relevance` are left out.
+    #[tracing::instrument(level = "debug", skip(self, context), fields(context_id = %context.context_id, max_items = %max_items))]
+    pub fn score_context_relevance(
         &self,
-        context: SearchContext,
+        context: &SearchContext,
         max_items: usize,
         min_relevance: f32,
-    ) -> Result<StructuredResult, SistenceMemoryError> {
-        debug!(
-            "Getting context-relevant items for context ID: {}",
-            context.context_id
-        );
-
-        // This is similar to contextual_search but doesn't require a specific query
-        // Instead, it uses the context itself to find relevant items
-
+    ) -> Vec<(DetailedMemoryItem, f32, Vec<RelevanceFactor>)> {
         // Collect all memory items with contextual relevance scores
         let mut relevance_scores = Vec::new();
 
@@ -267,21 +382,17 @@ impl StatelessRelevantMemory {
             };
 
             // Combine factors - adjust weights as needed
-            let relevance = (topic_match * 0.4)
-                + (recency * 0.2)
-                + (importance * 0.3)
-                + (activity_relevance * 0.1);
+            let factors = vec![
+                RelevanceFactor::new("topic_match", topic_match, 0.4),
+                RelevanceFactor::new("recency", recency, 0.2),
+                RelevanceFactor::new("importance", importance, 0.3),
+                RelevanceFactor::new("activity_relevance", activity_relevance, 0.1),
+            ];
+            let relevance: f32 = factors.iter().map(RelevanceFactor::contribution).sum();
 
             // Skip items below minimum relevance
             if relevance >= min_relevance {
-                // Create component map for detailed relevance information
-                let mut components = HashMap::new();
-                components.insert("topic_match".to_string(), topic_match);
-                components.insert("recency".to_string(), recency);
-                components.insert("importance".to_string(), importance);
-                components.insert("activity_relevance".to_string(), activity_relevance);
-
-                relevance_scores.push((item, relevance, components));
+                relevance_scores.push((item, relevance, factors));
             }
         }
 
@@ -289,9 +400,25 @@ impl StatelessRelevantMemory {
         relevance_scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
 
         // Limit results
-        if relevance_scores.len() > max_items {
-            relevance_scores.truncate(max_items);
-        }
+        relevance_scores.truncate(max_items);
+        relevance_scores
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, context), fields(context_id = %context.context_id, max_items = %max_items), err)]
+    pub async fn get_context_relevant(
+        &self,
+        context: SearchContext,
+        max_items: usize,
+        min_relevance: f32,
+    ) -> Result<StructuredResult, SistenceMemoryError> {
+        debug!(
+            "Getting context-relevant items for context ID: {}",
+            context.context_id
+        );
+
+        // This is similar to contextual_search but doesn't require a specific query
+        // Instead, it uses the context itself to find relevant items
+        let relevance_scores = self.score_context_relevance(&context, max_items, min_relevance);
 
         // Convert to MemoryItems
         let memory_items: Vec<MemoryItem> = relevance_scores
@@ -4,6 +4,9 @@ use std::collections::HashMap;
 use std::time::{Duration, SystemTime};
 
 use crate::provider::capabilities::relevant_memory::DetailedMemoryItem;
+use crate::provider::capabilities::sistence_memory::{
+    MemoryFilter, SearchFilters, TimeField, filter_item_type,
+};
 
 /// Calculate similarity between two sets of strings
 pub fn calculate_set_similarity(set1: &[String], set2: &[String]) -> f32 {
@@ -26,6 +29,76 @@ pub fn calculate_set_similarity(set1: &[String], set2: &[String]) -> f32 {
     common_count as f32 / union_size as f32
 }
 
+/// Check whether a memory item passes the given search filters
+pub fn matches_search_filters(item: &DetailedMemoryItem, filters: &SearchFilters) -> bool {
+    // Filter by item type
+    if let Some(item_types) = &filters.item_types {
+        if !item_types.contains(&item.item_type) {
+            return false;
+        }
+    }
+
+    // Filter by topics
+    if let Some(topics) = &filters.topics {
+        if !topics.iter().any(|t| item.topics.contains(t)) {
+            return false;
+        }
+    }
+
+    // Filter by time range
+    if let Some(time_start) = filters.time_start {
+        if item.created_at < time_start {
+            return false;
+        }
+    }
+
+    if let Some(time_end) = filters.time_end {
+        if item.created_at > time_end {
+            return false;
+        }
+    }
+
+    // Filter by importance
+    if let Some(min_importance) = filters.min_importance {
+        if item.importance.base_score < min_importance {
+            return false;
+        }
+    }
+
+    // Filter by the filter tree
+    filters
+        .filter
+        .as_ref()
+        .is_none_or(|filter| matches_filter(item, filter))
+}
+
+/// Check whether a memory item matches a filter tree
+pub fn matches_filter(item: &DetailedMemoryItem, filter: &MemoryFilter) -> bool {
+    match filter {
+        MemoryFilter::And(filters) => filters.iter().all(|f| matches_filter(item, f)),
+        MemoryFilter::Or(filters) => filters.iter().any(|f| matches_filter(item, f)),
+        MemoryFilter::Not(filter) => !matches_filter(item, filter),
+        MemoryFilter::TimeRange { field, start, end } => {
+            let time = match field {
+                TimeField::Created => item.created_at,
+                TimeField::Updated => item.updated_at,
+            };
+            start.is_none_or(|start| time >= SystemTime::from(start))
+                && end.is_none_or(|end| time <= SystemTime::from(end))
+        }
+        MemoryFilter::TagEquals { key, value } => item.tags.get(key) == Some(value),
+        MemoryFilter::TagExists { key } => item.tags.contains_key(key),
+        MemoryFilter::TopicContains(topic) => item.topics.contains(topic),
+        MemoryFilter::ItemTypeIn(names) => names
+            .iter()
+            .any(|name| filter_item_type(name).is_ok_and(|t| t == item.item_type)),
+        MemoryFilter::MinImportance(threshold) => item.importance.base_score >= *threshold,
+        MemoryFilter::Archived(archived) => {
+            item.tags.get("archived").is_some_and(|v| v == "true") == *archived
+        }
+    }
+}
+
 /// Calculate similarity between two text strings
 pub fn calculate_text_similarity(text1: &str, text2: &str) -> f32 {
     if text1.is_empty() && text2.is_empty() {
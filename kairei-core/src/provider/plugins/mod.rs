@@ -1,6 +1,10 @@
+pub mod catalog;
 pub mod general_prompt;
+pub mod json_output;
 pub mod memory;
+pub mod openapi_tools;
 pub mod policy;
+pub mod request_context;
 pub mod storage;
 pub mod web_search_serper;
 pub mod will_action;
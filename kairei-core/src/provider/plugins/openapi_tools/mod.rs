@@ -0,0 +1,712 @@
+//! # OpenAPI Tools
+//!
+//! Calls the operations of external OpenAPI specs as tools. The specs of the
+//! `openapi_tools` plugin config are read when the provider is created, and every
+//! operation becomes a tool named by its `operationId`. A tool's arguments are the
+//! operation's parameters, plus `body` for a JSON request body.
+//!
+//! Tools are called in two ways:
+//! - by the LLM: the plugin lists the tools in the prompt, and a response that is
+//!   only `{"tool": "<name>", "arguments": {...}}` is answered with the tool's
+//!   result before the LLM is asked again
+//! - from the DSL: `call_tool("listPets", { limit: 10 })`, through the system's
+//!   [`ToolRegistry`]
+//!
+//! Both honour `allowed_tools`, the timeout and the retries of the config.
+//! Unsupported spec constructs are logged as warnings, see [`spec`].
+
+pub mod spec;
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use reqwest::{
+    Client, RequestBuilder, StatusCode, Url,
+    header::{AUTHORIZATION, HeaderMap, HeaderName, HeaderValue},
+};
+use secrecy::ExposeSecret;
+use serde::Deserialize;
+use serde_json::{Map, Value as JsonValue};
+use thiserror::Error;
+use tracing::{debug, warn};
+
+use crate::{
+    config::{OpenApiToolsConfig, PluginConfig, ToolAuthConfig},
+    eval::postprocess::strip_fences,
+    provider::{
+        capabilities::common::CapabilityType,
+        contribution::{ContextContribution, ToolSpec},
+        llm::LLMResponse,
+        plugin::{PluginContext, ProviderPlugin},
+        provider::ProviderSecret,
+        types::ProviderResult,
+    },
+};
+
+pub use spec::{ParameterLocation, ParsedSpec, ToolDefinition, ToolParameter, parse_spec};
+
+// エラー応答の本文はこの文字数までエラーに含める
+const MAX_ERROR_BODY_CHARS: usize = 500;
+
+#[derive(Debug, Error, Clone)]
+pub enum ToolError {
+    #[error("Failed to load OpenAPI spec {location}: {message}")]
+    Load { location: String, message: String },
+
+    #[error("Invalid OpenAPI spec {location}: {message}")]
+    InvalidSpec { location: String, message: String },
+
+    #[error("Tool secret not found in additional_auth: {0}")]
+    SecretNotFound(String),
+
+    #[error("Invalid tool auth config: {0}")]
+    InvalidAuth(String),
+
+    #[error("Unknown tool: {0}")]
+    UnknownTool(String),
+
+    #[error("Tool `{0}` is not allowed")]
+    NotAllowed(String),
+
+    #[error("Invalid arguments for tool `{tool}`: {message}")]
+    InvalidArguments { tool: String, message: String },
+
+    #[error("Tool `{tool}` request failed: {message}")]
+    Request { tool: String, message: String },
+
+    #[error("Tool `{tool}` returned {status}: {body}")]
+    Status {
+        tool: String,
+        status: u16,
+        body: String,
+    },
+}
+
+pub type ToolResult<T> = Result<T, ToolError>;
+
+#[derive(Debug)]
+struct Tool {
+    definition: ToolDefinition,
+    base_url: Url,
+    auth: Option<(HeaderName, HeaderValue)>,
+}
+
+/// A response of the LLM asking for a tool call
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ToolCall {
+    tool: String,
+    #[serde(default)]
+    arguments: JsonValue,
+}
+
+/// The operations of the configured OpenAPI specs, as tools
+#[derive(Debug)]
+pub struct OpenApiToolProvider {
+    tools: Vec<Tool>,
+    config: OpenApiToolsConfig,
+    client: Client,
+}
+
+impl OpenApiToolProvider {
+    /// Read every spec of `config`. Credentials are taken from `secret.additional_auth`.
+    pub async fn load(config: &OpenApiToolsConfig, secret: &ProviderSecret) -> ToolResult<Self> {
+        let client = Client::new();
+        let mut tools: Vec<Tool> = vec![];
+        for source in &config.specs {
+            let document = fetch_spec(&client, &source.location, config).await?;
+            let parsed = parse_spec(&source.location, &document, source.base_url.as_deref())?;
+            for warning in &parsed.warnings {
+                warn!("OpenAPI spec {}: {}", source.location, warning);
+            }
+            let auth = source
+                .auth
+                .as_ref()
+                .map(|auth| auth_header(auth, secret))
+                .transpose()?;
+            for definition in parsed.tools {
+                if tools
+                    .iter()
+                    .any(|tool| tool.definition.name == definition.name)
+                {
+                    warn!(
+                        "OpenAPI spec {}: tool `{}` is already defined by another spec, skipped",
+                        source.location, definition.name
+                    );
+                    continue;
+                }
+                let base_url =
+                    Url::parse(&definition.base_url).map_err(|e| ToolError::InvalidSpec {
+                        location: source.location.clone(),
+                        message: format!("invalid server URL `{}`: {}", definition.base_url, e),
+                    })?;
+                tools.push(Tool {
+                    definition,
+                    base_url,
+                    auth: auth.clone(),
+                });
+            }
+        }
+        for name in config.allowed_tools.iter().flatten() {
+            if !tools.iter().any(|tool| &tool.definition.name == name) {
+                warn!("Allowed tool `{}` is not defined by any OpenAPI spec", name);
+            }
+        }
+        Ok(Self {
+            tools,
+            config: config.clone(),
+            client,
+        })
+    }
+
+    /// Every tool of the specs, including those not allowed
+    pub fn tool_names(&self) -> Vec<&str> {
+        self.tools
+            .iter()
+            .map(|tool| tool.definition.name.as_str())
+            .collect()
+    }
+
+    /// The tools that may be called, narrowed further by `narrowed` when set
+    pub fn allowed_tools(&self, narrowed: Option<&[String]>) -> Vec<&ToolDefinition> {
+        self.tools
+            .iter()
+            .map(|tool| &tool.definition)
+            .filter(|definition| self.is_allowed(&definition.name, narrowed))
+            .collect()
+    }
+
+    fn is_allowed(&self, name: &str, narrowed: Option<&[String]>) -> bool {
+        [self.config.allowed_tools.as_deref(), narrowed]
+            .into_iter()
+            .flatten()
+            .all(|allowed| allowed.iter().any(|allowed| allowed == name))
+    }
+
+    /// Call the tool `name` with an object of `arguments`, returning the JSON
+    /// response, or the response text when it is not JSON
+    pub async fn call(&self, name: &str, arguments: &JsonValue) -> ToolResult<JsonValue> {
+        self.call_narrowed(name, arguments, None).await
+    }
+
+    async fn call_narrowed(
+        &self,
+        name: &str,
+        arguments: &JsonValue,
+        narrowed: Option<&[String]>,
+    ) -> ToolResult<JsonValue> {
+        let tool = self
+            .tools
+            .iter()
+            .find(|tool| tool.definition.name == name)
+            .ok_or_else(|| ToolError::UnknownTool(name.to_string()))?;
+        if !self.is_allowed(name, narrowed) {
+            return Err(ToolError::NotAllowed(name.to_string()));
+        }
+        debug!("Calling tool {} with {}", name, arguments);
+
+        let request_error = |e: reqwest::Error| ToolError::Request {
+            tool: name.to_string(),
+            message: e.to_string(),
+        };
+        let mut retries = 0;
+        let response = loop {
+            let result = self.request(tool, arguments)?.send().await;
+            let retryable = match &result {
+                Ok(response) => {
+                    response.status().is_server_error()
+                        || response.status() == StatusCode::TOO_MANY_REQUESTS
+                }
+                Err(e) => e.is_timeout() || e.is_connect(),
+            };
+            if !retryable || retries >= self.config.max_retries {
+                break result.map_err(request_error)?;
+            }
+            retries += 1;
+            warn!(
+                "Tool {} failed, retrying ({}/{})",
+                name, retries, self.config.max_retries
+            );
+            tokio::time::sleep(self.config.retry_delay).await;
+        };
+
+        let status = response.status();
+        let text = response.text().await.map_err(request_error)?;
+        if !status.is_success() {
+            return Err(ToolError::Status {
+                tool: name.to_string(),
+                status: status.as_u16(),
+                body: text.chars().take(MAX_ERROR_BODY_CHARS).collect(),
+            });
+        }
+        if text.trim().is_empty() {
+            return Ok(JsonValue::Null);
+        }
+        Ok(serde_json::from_str(&text).unwrap_or(JsonValue::String(text)))
+    }
+
+    fn request(&self, tool: &Tool, arguments: &JsonValue) -> ToolResult<RequestBuilder> {
+        let definition = &tool.definition;
+        let invalid = |message: String| ToolError::InvalidArguments {
+            tool: definition.name.clone(),
+            message,
+        };
+        let empty = Map::new();
+        let arguments = match arguments {
+            JsonValue::Object(arguments) => arguments,
+            JsonValue::Null => &empty,
+            other => return Err(invalid(format!("expected an object, got {}", other))),
+        };
+        if let Some(unknown) = arguments.keys().find(|key| {
+            !definition.parameters.iter().any(|p| &p.name == *key)
+                && (*key != "body" || definition.body.is_none())
+        }) {
+            return Err(invalid(format!("unknown argument `{}`", unknown)));
+        }
+
+        let mut path_values = vec![];
+        let mut url = tool.base_url.clone();
+        let mut headers = HeaderMap::new();
+        for parameter in &definition.parameters {
+            let value = match arguments.get(&parameter.name) {
+                Some(JsonValue::Null) | None if parameter.required => {
+                    return Err(invalid(format!(
+                        "missing required argument `{}`",
+                        parameter.name
+                    )));
+                }
+                Some(JsonValue::Null) | None => continue,
+                Some(value) => value,
+            };
+            match parameter.location {
+                ParameterLocation::Path => {
+                    path_values.push((format!("{{{}}}", parameter.name), argument_text(value)));
+                }
+                ParameterLocation::Query => {
+                    // 配列は同じ名前のパラメーターを繰り返す (form, explode)
+                    let values: Vec<&JsonValue> = match value {
+                        JsonValue::Array(items) => items.iter().collect(),
+                        value => vec![value],
+                    };
+                    for value in values {
+                        url.query_pairs_mut()
+                            .append_pair(&parameter.name, &argument_text(value));
+                    }
+                }
+                ParameterLocation::Header => {
+                    let name = HeaderName::from_bytes(parameter.name.as_bytes())
+                        .map_err(|e| invalid(e.to_string()))?;
+                    let value = HeaderValue::from_str(&argument_text(value))
+                        .map_err(|e| invalid(e.to_string()))?;
+                    headers.insert(name, value);
+                }
+            }
+        }
+        // `/` を含む値もひとつのセグメントになるよう、分割してから置換する
+        let segments = definition
+            .path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| {
+                path_values
+                    .iter()
+                    .fold(segment.to_string(), |segment, (placeholder, value)| {
+                        segment.replace(placeholder, value)
+                    })
+            });
+        url.path_segments_mut()
+            .map_err(|_| invalid(format!("cannot add a path to {}", tool.base_url)))?
+            .pop_if_empty()
+            .extend(segments);
+        if let Some((name, value)) = &tool.auth {
+            headers.insert(name.clone(), value.clone());
+        }
+
+        let mut request = self
+            .client
+            .request(definition.method.clone(), url)
+            .headers(headers)
+            .timeout(self.config.timeout);
+        match arguments.get("body") {
+            Some(body) if definition.body.is_some() => request = request.json(body),
+            _ if definition.body_required => {
+                return Err(invalid("missing required argument `body`".to_string()));
+            }
+            _ => {}
+        }
+        Ok(request)
+    }
+
+    fn tool_specs(&self, narrowed: Option<&[String]>) -> Vec<ToolSpec> {
+        self.allowed_tools(narrowed)
+            .into_iter()
+            .map(|tool| ToolSpec {
+                name: tool.name.clone(),
+                description: tool.description.clone(),
+                parameters: tool.input_schema(),
+            })
+            .collect()
+    }
+}
+
+fn argument_text(value: &JsonValue) -> String {
+    match value {
+        JsonValue::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+async fn fetch_spec(
+    client: &Client,
+    location: &str,
+    config: &OpenApiToolsConfig,
+) -> ToolResult<JsonValue> {
+    let load_error = |message: String| ToolError::Load {
+        location: location.to_string(),
+        message,
+    };
+    let text = if location.starts_with("http://") || location.starts_with("https://") {
+        let response = client
+            .get(location)
+            .timeout(config.timeout)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| load_error(e.to_string()))?;
+        response
+            .text()
+            .await
+            .map_err(|e| load_error(e.to_string()))?
+    } else {
+        tokio::fs::read_to_string(location)
+            .await
+            .map_err(|e| load_error(e.to_string()))?
+    };
+    serde_json::from_str(&text)
+        .map_err(|e| load_error(format!("only JSON specs are supported: {}", e)))
+}
+
+fn auth_header(
+    auth: &ToolAuthConfig,
+    secret: &ProviderSecret,
+) -> ToolResult<(HeaderName, HeaderValue)> {
+    let lookup = |key: &str| {
+        secret
+            .additional_auth
+            .get(key)
+            .map(|value| value.expose_secret().to_string())
+            .ok_or_else(|| ToolError::SecretNotFound(key.to_string()))
+    };
+    let (name, value) = match auth {
+        ToolAuthConfig::Bearer { secret_key } => {
+            (AUTHORIZATION, format!("Bearer {}", lookup(secret_key)?))
+        }
+        ToolAuthConfig::ApiKey { header, secret_key } => (
+            HeaderName::from_bytes(header.as_bytes())
+                .map_err(|_| ToolError::InvalidAuth(format!("invalid header `{}`", header)))?,
+            lookup(secret_key)?,
+        ),
+    };
+    let mut value = HeaderValue::from_str(&value)
+        .map_err(|_| ToolError::InvalidAuth(format!("invalid value for `{}`", name)))?;
+    value.set_sensitive(true);
+    Ok((name, value))
+}
+
+/// The `allowed_tools` of the think's `with` block
+fn narrowed_tools<'a>(context: &PluginContext<'a>) -> Option<&'a [String]> {
+    match context.configs.get("openapi_tools") {
+        Some(PluginConfig::OpenApiTools(config)) => config.allowed_tools.as_deref(),
+        _ => None,
+    }
+}
+
+#[async_trait]
+impl ProviderPlugin for OpenApiToolProvider {
+    fn priority(&self) -> i32 {
+        9 // カタログの後、ポリシーの前
+    }
+
+    fn capability(&self) -> CapabilityType {
+        CapabilityType::Tools
+    }
+
+    #[tracing::instrument(skip(self, context))]
+    async fn generate_section<'a>(
+        &self,
+        context: &PluginContext<'a>,
+    ) -> ProviderResult<ContextContribution> {
+        Ok(ContextContribution::ToolDefinitions(
+            self.tool_specs(narrowed_tools(context)),
+        ))
+    }
+
+    async fn process_response<'a>(
+        &self,
+        _context: &PluginContext<'a>,
+        _response: &LLMResponse,
+    ) -> ProviderResult<()> {
+        Ok(())
+    }
+
+    async fn handle_tool_call<'a>(
+        &self,
+        context: &PluginContext<'a>,
+        response: &LLMResponse,
+    ) -> ProviderResult<Option<String>> {
+        let Ok(call) = serde_json::from_str::<ToolCall>(strip_fences(&response.content)) else {
+            return Ok(None);
+        };
+        if !self.tool_names().contains(&call.tool.as_str()) {
+            return Ok(None);
+        }
+        // 失敗も LLM に返し、別の呼び出し方や回答を選ばせる
+        let result = match self
+            .call_narrowed(&call.tool, &call.arguments, narrowed_tools(context))
+            .await
+        {
+            Ok(value) => format!("Result of tool `{}`:\n{}", call.tool, value),
+            Err(e) => format!("Tool `{}` failed: {}", call.tool, e),
+        };
+        Ok(Some(result))
+    }
+}
+
+/// The tools of every provider configured with `openapi_tools`, called by
+/// `call_tool(name, args)`. Shared by the agents of a system.
+#[derive(Debug, Default)]
+pub struct ToolRegistry {
+    tools: DashMap<String, Arc<OpenApiToolProvider>>,
+}
+
+impl ToolRegistry {
+    pub fn register(&self, provider: Arc<OpenApiToolProvider>) {
+        for name in provider.tool_names() {
+            // 同じ仕様を複数のプロバイダーが読み込んでいても、最初のものを使う
+            self.tools
+                .entry(name.to_string())
+                .or_insert_with(|| provider.clone());
+        }
+    }
+
+    pub async fn call(&self, name: &str, arguments: &JsonValue) -> ToolResult<JsonValue> {
+        let provider = self
+            .tools
+            .get(name)
+            .map(|entry| entry.value().clone())
+            .ok_or_else(|| ToolError::UnknownTool(name.to_string()))?;
+        provider.call(name, arguments).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use mockito::Matcher;
+    use secrecy::SecretString;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        config::OpenApiSpecConfig,
+        provider::{
+            capabilities::common::Capabilities, generator::assembler::PromptAssembler,
+            plugins::provider_tests::TestContextHolder,
+        },
+    };
+
+    const PETSTORE: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/openapi/petstore.json"
+    );
+
+    fn petstore_config(server: &mockito::Server) -> OpenApiToolsConfig {
+        OpenApiToolsConfig {
+            specs: vec![OpenApiSpecConfig {
+                location: PETSTORE.to_string(),
+                base_url: Some(server.url()),
+                auth: None,
+            }],
+            retry_delay: std::time::Duration::from_millis(1),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_with_query_parameters() -> ToolResult<()> {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/pets")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("limit".to_string(), "2".to_string()),
+                Matcher::UrlEncoded("tag".to_string(), "cat & dog".to_string()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"[{"id": 1, "name": "Tom"}]"#)
+            .create_async()
+            .await;
+        let tools =
+            OpenApiToolProvider::load(&petstore_config(&server), &ProviderSecret::default())
+                .await?;
+
+        let pets = tools
+            .call("listPets", &json!({ "limit": 2, "tag": "cat & dog" }))
+            .await?;
+
+        assert_eq!(pets, json!([{ "id": 1, "name": "Tom" }]));
+        mock.assert_async().await;
+        // cookie パラメーターは捨てられ、オペレーション自体は使える
+        assert_eq!(
+            tools.tool_names(),
+            vec!["listPets", "createPet", "showPetById"]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_post_with_json_body() -> ToolResult<()> {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/pets")
+            .match_header("content-type", "application/json")
+            .match_body(Matcher::Json(json!({ "name": "Rex", "tag": "dog" })))
+            .with_status(201)
+            .with_body(r#"{"id": 7, "name": "Rex"}"#)
+            .create_async()
+            .await;
+        let tools =
+            OpenApiToolProvider::load(&petstore_config(&server), &ProviderSecret::default())
+                .await?;
+
+        let pet = tools
+            .call(
+                "createPet",
+                &json!({ "body": { "name": "Rex", "tag": "dog" } }),
+            )
+            .await?;
+
+        assert_eq!(pet, json!({ "id": 7, "name": "Rex" }));
+        mock.assert_async().await;
+        assert!(matches!(
+            tools.call("createPet", &json!({})).await,
+            Err(ToolError::InvalidArguments { .. })
+        ));
+        assert!(matches!(
+            tools.call("createPet", &json!({ "name": "Rex" })).await,
+            Err(ToolError::InvalidArguments { .. })
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_auth_header_injection() -> ToolResult<()> {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/pets/a%2Fb")
+            .match_header("authorization", "Bearer s3cr3t")
+            .with_status(200)
+            .with_body(r#"{"id": 42}"#)
+            .create_async()
+            .await;
+        let mut config = petstore_config(&server);
+        config.specs[0].auth = Some(ToolAuthConfig::Bearer {
+            secret_key: "petstore_token".to_string(),
+        });
+        let secret = ProviderSecret {
+            additional_auth: HashMap::from([(
+                "petstore_token".to_string(),
+                SecretString::from("s3cr3t".to_string()),
+            )]),
+            ..Default::default()
+        };
+
+        let tools = OpenApiToolProvider::load(&config, &secret).await?;
+        // `/` を含む値もひとつのパスセグメントになる
+        let pet = tools
+            .call("showPetById", &json!({ "petId": "a/b" }))
+            .await?;
+
+        assert_eq!(pet, json!({ "id": 42 }));
+        mock.assert_async().await;
+        assert!(matches!(
+            OpenApiToolProvider::load(&config, &ProviderSecret::default()).await,
+            Err(ToolError::SecretNotFound(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_allowlist_and_retries() -> ToolResult<()> {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/pets")
+            .with_status(503)
+            .with_body("try later")
+            .expect(3)
+            .create_async()
+            .await;
+        let config = OpenApiToolsConfig {
+            allowed_tools: Some(vec!["listPets".to_string()]),
+            ..petstore_config(&server)
+        };
+        let tools = OpenApiToolProvider::load(&config, &ProviderSecret::default()).await?;
+
+        assert!(matches!(
+            tools.call("createPet", &json!({ "body": {} })).await,
+            Err(ToolError::NotAllowed(_))
+        ));
+        // 初回と 2 回の再試行
+        assert!(matches!(
+            tools.call("listPets", &json!({})).await,
+            Err(ToolError::Status { status: 503, .. })
+        ));
+        mock.assert_async().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_llm_tool_call() -> ProviderResult<()> {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/pets")
+            .match_query(Matcher::UrlEncoded("limit".to_string(), "1".to_string()))
+            .with_body(r#"[{"id": 1, "name": "Tom"}]"#)
+            .create_async()
+            .await;
+        let tools =
+            OpenApiToolProvider::load(&petstore_config(&server), &ProviderSecret::default())
+                .await?;
+        let context_holder = TestContextHolder::new("Which pets are there?");
+        let context = context_holder.get_plugin_context();
+
+        let contribution = tools.generate_section(&context).await?;
+        let ContextContribution::ToolDefinitions(specs) = &contribution else {
+            panic!("expected tool definitions, got {:?}", contribution);
+        };
+        assert!(specs.iter().any(|spec| spec.name == "listPets"));
+        let section = PromptAssembler::new(Capabilities::default()).render(&contribution, 9);
+        assert!(section.content.contains("- listPets: List the pets"));
+        assert!(section.content.contains(r#""required":["body"]"#));
+
+        let response = LLMResponse {
+            content: "```json\n{\"tool\": \"listPets\", \"arguments\": {\"limit\": 1}}\n```"
+                .to_string(),
+            ..Default::default()
+        };
+        let result = tools.handle_tool_call(&context, &response).await?;
+        assert_eq!(
+            result.as_deref(),
+            Some("Result of tool `listPets`:\n[{\"id\":1,\"name\":\"Tom\"}]")
+        );
+
+        let answer = LLMResponse {
+            content: "There is Tom".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(tools.handle_tool_call(&context, &answer).await?, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,496 @@
+//! Reads the part of an OpenAPI 3 document needed to call its operations:
+//! the first of `servers`, the operations under `paths`, their path, query and
+//! header `parameters`, and JSON request bodies. Local `$ref`s are resolved.
+//!
+//! Anything else is reported as a warning. An operation is only skipped when it
+//! cannot be called without the unsupported part, e.g. a required cookie parameter
+//! or a required form body.
+
+use reqwest::Method;
+use serde_json::{Map, Value as JsonValue, json};
+
+use super::{ToolError, ToolResult};
+
+// 循環参照するスキーマはこの深さで打ち切る
+const MAX_REF_DEPTH: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParameterLocation {
+    Path,
+    Query,
+    Header,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolParameter {
+    pub name: String,
+    pub location: ParameterLocation,
+    pub required: bool,
+    /// JSON schema with every `$ref` inlined
+    pub schema: JsonValue,
+}
+
+/// An operation, callable as a tool
+#[derive(Debug, Clone)]
+pub struct ToolDefinition {
+    /// The `operationId`, or derived from the method and path
+    pub name: String,
+    pub description: Option<String>,
+    pub method: Method,
+    /// Path template, e.g. `/pets/{petId}`
+    pub path: String,
+    pub base_url: String,
+    pub parameters: Vec<ToolParameter>,
+    /// Schema of the JSON request body, passed as the `body` argument
+    pub body: Option<JsonValue>,
+    pub body_required: bool,
+}
+
+impl ToolDefinition {
+    /// JSON schema of the arguments: a property per parameter, and `body`
+    pub fn input_schema(&self) -> JsonValue {
+        let mut properties = Map::new();
+        let mut required = vec![];
+        for parameter in &self.parameters {
+            properties.insert(parameter.name.clone(), parameter.schema.clone());
+            if parameter.required {
+                required.push(JsonValue::String(parameter.name.clone()));
+            }
+        }
+        if let Some(body) = &self.body {
+            properties.insert("body".to_string(), body.clone());
+            if self.body_required {
+                required.push(JsonValue::String("body".to_string()));
+            }
+        }
+        json!({
+            "type": "object",
+            "properties": properties,
+            "required": required,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ParsedSpec {
+    pub tools: Vec<ToolDefinition>,
+    /// Constructs that were ignored, or operations that were skipped
+    pub warnings: Vec<String>,
+}
+
+/// Read the operations of `document`. `base_url` overrides the spec's `servers`.
+/// Fails only when the document has no `paths` or no server to send requests to.
+pub fn parse_spec(
+    location: &str,
+    document: &JsonValue,
+    base_url: Option<&str>,
+) -> ToolResult<ParsedSpec> {
+    let invalid = |message: &str| ToolError::InvalidSpec {
+        location: location.to_string(),
+        message: message.to_string(),
+    };
+    let mut parser = SpecParser {
+        document,
+        warnings: vec![],
+    };
+
+    match document.get("openapi").and_then(JsonValue::as_str) {
+        Some(version) if version.starts_with("3.") => {}
+        Some(version) => parser.warn(format!(
+            "OpenAPI version {version} is not supported, reading the document as 3.x"
+        )),
+        None => parser.warn("no `openapi` version, reading the document as 3.x".to_string()),
+    }
+
+    let paths = document
+        .get("paths")
+        .and_then(JsonValue::as_object)
+        .ok_or_else(|| invalid("`paths` is missing"))?;
+    let base_url = match base_url {
+        Some(base_url) => base_url.to_string(),
+        None => parser
+            .server_url()
+            .ok_or_else(|| invalid("no absolute URL in `servers` and no `base_url` configured"))?,
+    };
+
+    let mut tools: Vec<ToolDefinition> = vec![];
+    for (path, item) in paths {
+        let Some(item) = parser.resolve(item, path) else {
+            continue;
+        };
+        let shared = item
+            .get("parameters")
+            .and_then(JsonValue::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let Some(operations) = item.as_object() else {
+            parser.warn(format!("{path}: path item is not an object, skipped"));
+            continue;
+        };
+        for (key, operation) in operations {
+            let method = match key.as_str() {
+                "get" => Method::GET,
+                "post" => Method::POST,
+                "put" => Method::PUT,
+                "patch" => Method::PATCH,
+                "delete" => Method::DELETE,
+                "parameters" | "summary" | "description" => continue,
+                key if key.starts_with("x-") => continue,
+                key => {
+                    parser.warn(format!("{path}: `{key}` is not supported, ignored"));
+                    continue;
+                }
+            };
+            let Some(tool) = parser.operation(&base_url, path, method, operation, shared) else {
+                continue;
+            };
+            if tools.iter().any(|existing| existing.name == tool.name) {
+                parser.warn(format!(
+                    "{} {path}: a tool named `{}` already exists, skipped",
+                    tool.method, tool.name
+                ));
+                continue;
+            }
+            tools.push(tool);
+        }
+    }
+
+    Ok(ParsedSpec {
+        tools,
+        warnings: parser.warnings,
+    })
+}
+
+struct SpecParser<'a> {
+    document: &'a JsonValue,
+    warnings: Vec<String>,
+}
+
+impl<'a> SpecParser<'a> {
+    fn warn(&mut self, warning: String) {
+        self.warnings.push(warning);
+    }
+
+    /// The first server's URL, with its variables set to their defaults
+    fn server_url(&mut self) -> Option<String> {
+        let server = self.document.get("servers")?.as_array()?.first()?;
+        let mut url = server.get("url")?.as_str()?.to_string();
+        if let Some(variables) = server.get("variables").and_then(JsonValue::as_object) {
+            for (name, variable) in variables {
+                if let Some(default) = variable.get("default").and_then(JsonValue::as_str) {
+                    url = url.replace(&format!("{{{name}}}"), default);
+                }
+            }
+        }
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            self.warn(format!("server URL `{url}` is not absolute, ignored"));
+            return None;
+        }
+        Some(url)
+    }
+
+    fn lookup(&self, reference: &str) -> Option<&'a JsonValue> {
+        reference
+            .strip_prefix('#')
+            .and_then(|pointer| self.document.pointer(pointer))
+    }
+
+    /// Follow `$ref`s until an object that is not a reference
+    fn resolve(&mut self, value: &'a JsonValue, label: &str) -> Option<&'a JsonValue> {
+        let mut value = value;
+        for _ in 0..MAX_REF_DEPTH {
+            let Some(reference) = value.get("$ref").and_then(JsonValue::as_str) else {
+                return Some(value);
+            };
+            match self.lookup(reference) {
+                Some(target) => value = target,
+                None => {
+                    self.warn(format!("{label}: cannot resolve `{reference}`, ignored"));
+                    return None;
+                }
+            }
+        }
+        self.warn(format!("{label}: too many nested `$ref`s, ignored"));
+        None
+    }
+
+    /// `schema` with its `$ref`s replaced by their targets
+    fn inline_schema(&mut self, schema: &'a JsonValue, depth: usize, label: &str) -> JsonValue {
+        match schema {
+            JsonValue::Object(fields) => {
+                if let Some(reference) = fields.get("$ref").and_then(JsonValue::as_str) {
+                    if depth >= MAX_REF_DEPTH {
+                        self.warn(format!(
+                            "{label}: schema `{reference}` is nested too deeply, accepting any value"
+                        ));
+                        return json!({});
+                    }
+                    return match self.lookup(reference) {
+                        Some(target) => self.inline_schema(target, depth + 1, label),
+                        None => {
+                            self.warn(format!(
+                                "{label}: cannot resolve `{reference}`, accepting any value"
+                            ));
+                            json!({})
+                        }
+                    };
+                }
+                JsonValue::Object(
+                    fields
+                        .iter()
+                        .map(|(key, value)| (key.clone(), self.inline_schema(value, depth, label)))
+                        .collect(),
+                )
+            }
+            JsonValue::Array(items) => JsonValue::Array(
+                items
+                    .iter()
+                    .map(|item| self.inline_schema(item, depth, label))
+                    .collect(),
+            ),
+            other => other.clone(),
+        }
+    }
+
+    fn operation(
+        &mut self,
+        base_url: &str,
+        path: &str,
+        method: Method,
+        operation: &'a JsonValue,
+        shared: &'a [JsonValue],
+    ) -> Option<ToolDefinition> {
+        let label = format!("{method} {path}");
+        let name = operation
+            .get("operationId")
+            .and_then(JsonValue::as_str)
+            .map_or_else(|| derived_name(&method, path), str::to_string);
+
+        let own = operation
+            .get("parameters")
+            .and_then(JsonValue::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let mut parameters: Vec<ToolParameter> = vec![];
+        // オペレーションの定義はパス共通の同名の定義を上書きする
+        for parameter in shared.iter().chain(own) {
+            match self.parameter(parameter, &label) {
+                Ok(Some(parameter)) => {
+                    parameters.retain(|existing| {
+                        existing.name != parameter.name || existing.location != parameter.location
+                    });
+                    parameters.push(parameter);
+                }
+                Ok(None) => {}
+                Err(reason) => {
+                    self.warn(format!("{label}: skipped, {reason}"));
+                    return None;
+                }
+            }
+        }
+        for parameter_name in template_parameters(path) {
+            let declared = parameters
+                .iter()
+                .any(|p| p.location == ParameterLocation::Path && p.name == parameter_name);
+            if !declared {
+                self.warn(format!(
+                    "{label}: path parameter `{parameter_name}` is not declared, accepting it as a string"
+                ));
+                parameters.push(ToolParameter {
+                    name: parameter_name.to_string(),
+                    location: ParameterLocation::Path,
+                    required: true,
+                    schema: json!({ "type": "string" }),
+                });
+            }
+        }
+
+        let mut body = None;
+        let mut body_required = false;
+        if let Some(request_body) = operation
+            .get("requestBody")
+            .and_then(|request_body| self.resolve(request_body, &label))
+        {
+            body_required = request_body
+                .get("required")
+                .and_then(JsonValue::as_bool)
+                .unwrap_or(false);
+            let media = request_body
+                .get("content")
+                .and_then(JsonValue::as_object)
+                .and_then(|content| content.iter().find(|(media, _)| is_json(media)));
+            match media {
+                Some((_, media)) => {
+                    body = Some(match media.get("schema") {
+                        Some(schema) => self.inline_schema(schema, 0, &label),
+                        None => json!({}),
+                    });
+                }
+                None if body_required => {
+                    self.warn(format!(
+                        "{label}: skipped, only JSON request bodies are supported"
+                    ));
+                    return None;
+                }
+                None => self.warn(format!(
+                    "{label}: only JSON request bodies are supported, no body is sent"
+                )),
+            }
+        }
+
+        let description = operation
+            .get("summary")
+            .or_else(|| operation.get("description"))
+            .and_then(JsonValue::as_str)
+            .and_then(|text| text.lines().next())
+            .map(str::to_string);
+
+        Some(ToolDefinition {
+            name,
+            description,
+            method,
+            path: path.to_string(),
+            base_url: base_url.to_string(),
+            parameters,
+            body,
+            body_required,
+        })
+    }
+
+    /// `Ok(None)` drops an unsupported optional parameter, `Err` skips the operation
+    fn parameter(
+        &mut self,
+        parameter: &'a JsonValue,
+        label: &str,
+    ) -> Result<Option<ToolParameter>, String> {
+        let Some(parameter) = self.resolve(parameter, label) else {
+            return Ok(None);
+        };
+        let Some(name) = parameter.get("name").and_then(JsonValue::as_str) else {
+            self.warn(format!("{label}: parameter without a name, ignored"));
+            return Ok(None);
+        };
+        let required = parameter
+            .get("required")
+            .and_then(JsonValue::as_bool)
+            .unwrap_or(false);
+        let location = match parameter.get("in").and_then(JsonValue::as_str) {
+            Some("path") => ParameterLocation::Path,
+            Some("query") => ParameterLocation::Query,
+            Some("header") => ParameterLocation::Header,
+            other => {
+                let message = format!(
+                    "parameter `{name}` in {} is not supported",
+                    other.unwrap_or("an unknown location")
+                );
+                if required {
+                    return Err(message);
+                }
+                self.warn(format!("{label}: {message}, ignored"));
+                return Ok(None);
+            }
+        };
+        let mut schema = match parameter.get("schema") {
+            Some(schema) => self.inline_schema(schema, 0, label),
+            None => {
+                self.warn(format!(
+                    "{label}: parameter `{name}` has no `schema`, accepting any value"
+                ));
+                json!({})
+            }
+        };
+        if let (Some(fields), Some(description)) = (
+            schema.as_object_mut(),
+            parameter.get("description").and_then(JsonValue::as_str),
+        ) {
+            fields
+                .entry("description")
+                .or_insert_with(|| JsonValue::String(description.to_string()));
+        }
+        Ok(Some(ToolParameter {
+            name: name.to_string(),
+            required: required || location == ParameterLocation::Path,
+            location,
+            schema,
+        }))
+    }
+}
+
+fn is_json(media: &str) -> bool {
+    let media = media.split(';').next().unwrap_or_default().trim();
+    media == "application/json" || media.ends_with("+json")
+}
+
+/// Names in the `{braces}` of a path template
+pub(super) fn template_parameters(path: &str) -> impl Iterator<Item = &str> {
+    path.split('{')
+        .skip(1)
+        .filter_map(|part| part.split_once('}').map(|(name, _)| name))
+}
+
+/// `get_pets_petId` for `GET /pets/{petId}`
+fn derived_name(method: &Method, path: &str) -> String {
+    let raw = format!("{}_{}", method.as_str().to_lowercase(), path);
+    let mut name = String::with_capacity(raw.len());
+    for c in raw.chars() {
+        let c = if c.is_ascii_alphanumeric() { c } else { '_' };
+        if c != '_' || !name.ends_with('_') {
+            name.push(c);
+        }
+    }
+    name.trim_end_matches('_').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unsupported_constructs_degrade_to_warnings() -> ToolResult<()> {
+        let document = json!({
+            "openapi": "3.0.3",
+            "servers": [{ "url": "https://{region}.example.com/v1", "variables": { "region": { "default": "eu" } } }],
+            "paths": {
+                "/items/{id}": {
+                    "parameters": [{ "name": "id", "in": "path", "required": true, "schema": { "type": "integer" } }],
+                    "get": {
+                        "parameters": [
+                            { "name": "session", "in": "cookie", "schema": { "type": "string" } },
+                            { "$ref": "#/components/parameters/Missing" }
+                        ]
+                    },
+                    "put": {
+                        "operationId": "replaceItem",
+                        "requestBody": {
+                            "required": true,
+                            "content": { "application/x-www-form-urlencoded": { "schema": { "type": "object" } } }
+                        }
+                    },
+                    "options": {}
+                }
+            }
+        });
+
+        let parsed = parse_spec("inline", &document, None)?;
+
+        assert_eq!(parsed.tools.len(), 1);
+        let tool = &parsed.tools[0];
+        assert_eq!(tool.name, "get_items_id");
+        assert_eq!(tool.base_url, "https://eu.example.com/v1");
+        assert_eq!(tool.parameters.len(), 1);
+        assert_eq!(tool.input_schema()["required"], json!(["id"]));
+
+        let warnings = parsed.warnings.join("\n");
+        assert!(warnings.contains("parameter `session` in cookie is not supported, ignored"));
+        assert!(warnings.contains("cannot resolve `#/components/parameters/Missing`"));
+        assert!(warnings.contains("PUT /items/{id}: skipped, only JSON request bodies"));
+        assert!(warnings.contains("`options` is not supported"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_document_without_paths_is_rejected() {
+        let result = parse_spec("inline", &json!({ "openapi": "3.1.0" }), None);
+        assert!(matches!(result, Err(ToolError::InvalidSpec { .. })));
+    }
+}
@@ -4,6 +4,7 @@ use crate::{
     PolicyScope,
     provider::{
         capabilities::common::CapabilityType,
+        contribution::ContextContribution,
         llm::LLMResponse,
         plugin::{PluginContext, ProviderPlugin},
         provider::Section,
@@ -21,7 +22,10 @@ impl ProviderPlugin for PolicyPlugin {
     }
 
     #[tracing::instrument(skip(self, context))]
-    async fn generate_section<'a>(&self, context: &PluginContext<'a>) -> ProviderResult<Section> {
+    async fn generate_section<'a>(
+        &self,
+        context: &PluginContext<'a>,
+    ) -> ProviderResult<ContextContribution> {
         let mut policy_section = String::new();
         let policies = context.request.state.policies.clone();
 
@@ -64,7 +68,8 @@ impl ProviderPlugin for PolicyPlugin {
             content: policy_section,
             priority: 10,
             metadata: Default::default(),
-        })
+        }
+        .into())
     }
 
     fn capability(&self) -> CapabilityType {
@@ -121,7 +126,8 @@ mod tests {
         let context = context_holder.get_plugin_context();
 
         // セクションの生成
-        let section = plugin.generate_section(&context).await?;
+        let contribution = plugin.generate_section(&context).await?;
+        let section = contribution.as_section().unwrap();
 
         // 各種ポリシーが含まれていることを確認
         assert!(section.content.contains("Global Policies:"));
@@ -143,7 +149,8 @@ mod tests {
         let context_holder = TestContextHolder::new("test request");
         let context = context_holder.get_plugin_context();
 
-        let section = plugin.generate_section(&context).await?;
+        let contribution = plugin.generate_section(&context).await?;
+        let section = contribution.as_section().unwrap();
 
         // ポリシーが空の場合、セクションも空になることを確認
         assert!(section.content.is_empty());
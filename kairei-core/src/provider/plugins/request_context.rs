@@ -0,0 +1,135 @@
+use async_trait::async_trait;
+
+use crate::provider::{
+    capabilities::common::CapabilityType,
+    contribution::ContextContribution,
+    llm::LLMResponse,
+    plugin::{PluginContext, ProviderPlugin},
+    provider::Section,
+    types::ProviderResult,
+};
+
+/// リクエストコンテキスト（ユーザー、ロケール、会話の履歴）をプロンプトに反映するプラグイン
+pub struct RequestContextPlugin;
+
+#[async_trait]
+impl ProviderPlugin for RequestContextPlugin {
+    fn priority(&self) -> i32 {
+        5 // クエリの後、ポリシーの前
+    }
+
+    #[tracing::instrument(skip(self, context))]
+    async fn generate_section<'a>(
+        &self,
+        context: &PluginContext<'a>,
+    ) -> ProviderResult<ContextContribution> {
+        let mut content = String::new();
+
+        if let Some(request_context) = &context.request.state.request_context {
+            if !request_context.is_empty() {
+                content.push_str("Request Context:\n");
+                if let Some(user_id) = &request_context.user_id {
+                    content.push_str(&format!("- User: {}\n", user_id));
+                }
+                if let Some(locale) = &request_context.locale {
+                    content.push_str(&format!("- Locale: {}\n", locale));
+                    content.push_str(&format!(
+                        "Respond in the language and conventions of the \"{}\" locale.\n",
+                        locale
+                    ));
+                }
+                if !request_context.conversation.is_empty() {
+                    content.push_str("Conversation so far (oldest first):\n");
+                    for turn in &request_context.conversation {
+                        content
+                            .push_str(&format!("- User: {}\n  Agent: {}\n", turn.user, turn.agent));
+                    }
+                }
+            }
+        }
+
+        Ok(Section {
+            content,
+            priority: self.priority(),
+            metadata: Default::default(),
+        }
+        .into())
+    }
+
+    fn capability(&self) -> CapabilityType {
+        CapabilityType::RequestContext
+    }
+
+    async fn process_response<'a>(
+        &self,
+        _context: &PluginContext<'a>,
+        _response: &LLMResponse,
+    ) -> ProviderResult<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        context::{ConversationTurn, RequestContext},
+        provider::plugins::provider_tests::TestContextHolder,
+    };
+
+    #[tokio::test]
+    async fn test_request_context_section() -> ProviderResult<()> {
+        let mut context_holder = TestContextHolder::new("test request");
+        context_holder.request.state.request_context = Some(RequestContext::new(
+            Some("user-1".to_string()),
+            Some("ja-JP".to_string()),
+        ));
+        let context = context_holder.get_plugin_context();
+
+        let contribution = RequestContextPlugin.generate_section(&context).await?;
+        let section = contribution.as_section().unwrap();
+
+        assert!(section.content.contains("- User: user-1"));
+        assert!(section.content.contains("- Locale: ja-JP"));
+        assert!(section.content.contains("\"ja-JP\" locale"));
+        assert_eq!(section.priority, 5);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_conversation_section() -> ProviderResult<()> {
+        let mut context_holder = TestContextHolder::new("test request");
+        let turn = |user: &str, agent: &str| ConversationTurn {
+            user: user.to_string(),
+            agent: agent.to_string(),
+        };
+        context_holder.request.state.request_context = Some(
+            RequestContext::default()
+                .with_conversation(vec![turn("Hi", "Hello!"), turn("Weather?", "Sunny")]),
+        );
+        let context = context_holder.get_plugin_context();
+
+        let contribution = RequestContextPlugin.generate_section(&context).await?;
+        let section = contribution.as_section().unwrap();
+
+        assert!(section.content.ends_with(
+            "Conversation so far (oldest first):\n\
+             - User: Hi\n  Agent: Hello!\n\
+             - User: Weather?\n  Agent: Sunny\n"
+        ));
+        assert!(!section.content.contains("- Locale"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_without_request_context() -> ProviderResult<()> {
+        let context_holder = TestContextHolder::new("test request");
+        let context = context_holder.get_plugin_context();
+
+        let contribution = RequestContextPlugin.generate_section(&context).await?;
+        let section = contribution.as_section().unwrap();
+
+        assert!(section.content.is_empty());
+        Ok(())
+    }
+}
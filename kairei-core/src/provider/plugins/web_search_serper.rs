@@ -4,7 +4,7 @@ use async_trait::async_trait;
 use futures::future::join_all;
 use html2text::from_read;
 use reqwest::{
-    Client,
+    Client, StatusCode,
     header::{HeaderMap, HeaderValue},
 };
 use secrecy::ExposeSecret;
@@ -16,10 +16,11 @@ use crate::{
     config::{PluginConfig, SearchConfig},
     provider::{
         capabilities::common::CapabilityType,
+        contribution::ContextContribution,
         llm::LLMResponse,
         plugin::{PluginContext, ProviderPlugin},
         provider::{ProviderSecret, Section},
-        types::{ProviderError, ProviderResult},
+        types::{CredentialStatus, ProviderError, ProviderResult},
     },
 };
 
@@ -50,11 +51,15 @@ struct SearchParameters {
     hl: String,
 }
 
+/// Base URL of the Serper API
+pub const SERPER_ENDPOINT: &str = "https://google.serper.dev";
+
 #[derive(Debug, Clone)]
 pub struct WebSearchPlugin {
     config: SearchConfig,
     api_key: String,
     client: Client,
+    endpoint: String,
 }
 
 impl WebSearchPlugin {
@@ -68,6 +73,7 @@ impl WebSearchPlugin {
             config: config.clone(),
             api_key,
             client: Client::new(),
+            endpoint: SERPER_ENDPOINT.to_string(),
         }
     }
 
@@ -83,27 +89,37 @@ impl WebSearchPlugin {
             config: config.clone(),
             api_key,
             client: Client::new(),
+            endpoint: SERPER_ENDPOINT.to_string(),
         })
     }
 
-    #[tracing::instrument(skip(self))]
-    async fn search(
-        &self,
-        query: &str,
-        config: &SearchConfig,
-    ) -> ProviderResult<Vec<SearchResult>> {
-        debug!("Searching for {}", query);
+    /// Call the Serper API at `endpoint` instead of [`SERPER_ENDPOINT`]
+    pub fn with_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = endpoint.into();
+        self
+    }
+
+    fn headers(&self) -> ProviderResult<HeaderMap> {
         let mut headers = HeaderMap::new();
         headers.insert(
             "X-API-KEY",
             HeaderValue::from_str(&self.api_key)
                 .map_err(|e| ProviderError::InternalError(e.to_string()))?,
         );
+        Ok(headers)
+    }
 
+    #[tracing::instrument(skip(self))]
+    async fn search(
+        &self,
+        query: &str,
+        config: &SearchConfig,
+    ) -> ProviderResult<Vec<SearchResult>> {
+        debug!("Searching for {}", query);
         let response: SerperResponse = self
             .client
-            .post("https://google.serper.dev/search")
-            .headers(headers)
+            .post(format!("{}/search", self.endpoint))
+            .headers(self.headers()?)
             .json(&json!({
                 "q": query,
                 "num": config.max_results,
@@ -235,7 +251,10 @@ impl ProviderPlugin for WebSearchPlugin {
         CapabilityType::Search
     }
 
-    async fn generate_section<'a>(&self, context: &PluginContext<'a>) -> ProviderResult<Section> {
+    async fn generate_section<'a>(
+        &self,
+        context: &PluginContext<'a>,
+    ) -> ProviderResult<ContextContribution> {
         // リクエストから検索クエリを取得
         let query = context.request.input.query.to_string();
 
@@ -257,7 +276,8 @@ impl ProviderPlugin for WebSearchPlugin {
             content,
             priority: self.priority(),
             metadata: Default::default(),
-        })
+        }
+        .into())
     }
 
     async fn process_response<'a>(
@@ -268,6 +288,30 @@ impl ProviderPlugin for WebSearchPlugin {
         // Web検索の場合、レスポンス後の処理は特に必要ない
         Ok(())
     }
+
+    async fn validate_credentials(&self) -> ProviderResult<CredentialStatus> {
+        // Serper には無料の確認用APIがないため、1件だけ検索する
+        let response = match self
+            .client
+            .post(format!("{}/search", self.endpoint))
+            .headers(self.headers()?)
+            .json(&json!({"q": "kairei", "num": 1}))
+            .send()
+            .await
+        {
+            Ok(response) => response,
+            Err(e) => return Ok(CredentialStatus::Unreachable(e.to_string())),
+        };
+        let status = response.status();
+        Ok(if status.is_success() {
+            CredentialStatus::Valid
+        } else if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+            let body = response.text().await.unwrap_or_default();
+            CredentialStatus::Invalid(format!("Serper API answered {}: {}", status, body))
+        } else {
+            CredentialStatus::Unreachable(format!("Serper API answered {}", status))
+        })
+    }
 }
 
 #[cfg(test)]
@@ -291,4 +335,44 @@ mod tests {
         assert!(plugin.api_key == "test_web_search_serper_api_key");
         assert_eq!(plugin.capability(), CapabilityType::Search);
     }
+
+    #[tokio::test]
+    async fn test_validate_credentials() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/search")
+            .match_header("x-api-key", "good_key")
+            .with_status(200)
+            .with_body(r#"{"organic": []}"#)
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/search")
+            .match_header("x-api-key", "bad_key")
+            .with_status(403)
+            .with_body("Unauthorized.")
+            .create_async()
+            .await;
+
+        let plugin = |api_key: &str| {
+            let mut additional_auth = std::collections::HashMap::new();
+            additional_auth.insert(
+                "web_search_serper_api_key".to_string(),
+                secrecy::SecretString::from(api_key),
+            );
+            let secret = ProviderSecret {
+                additional_auth,
+                ..Default::default()
+            };
+            WebSearchPlugin::new(&SearchConfig::default(), &secret).with_endpoint(server.url())
+        };
+
+        assert_eq!(
+            plugin("good_key").validate_credentials().await.unwrap(),
+            CredentialStatus::Valid
+        );
+        let status = plugin("bad_key").validate_credentials().await.unwrap();
+        assert_eq!(status.as_str(), "invalid");
+        assert!(status.to_string().contains("Unauthorized."));
+    }
 }
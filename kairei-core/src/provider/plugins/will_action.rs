@@ -13,6 +13,7 @@ use crate::provider::capabilities::will_action::{
     WillActionResolver, WillActionResult, WillActionSignature,
 };
 use crate::provider::config::plugins::WillActionConfig;
+use crate::provider::contribution::ContextContribution;
 use crate::provider::llm::LLMResponse;
 use crate::provider::plugin::{PluginContext, ProviderPlugin};
 use crate::provider::provider::Section;
@@ -66,9 +67,12 @@ impl ProviderPlugin for DefaultWillActionResolver {
         CapabilityType::Custom("will_action".to_string())
     }
 
-    async fn generate_section<'a>(&self, _context: &PluginContext<'a>) -> ProviderResult<Section> {
+    async fn generate_section<'a>(
+        &self,
+        _context: &PluginContext<'a>,
+    ) -> ProviderResult<ContextContribution> {
         // Will Action resolver doesn't contribute to prompt generation
-        Ok(Section::new(""))
+        Ok(Section::new("").into())
     }
 
     async fn process_response<'a>(
@@ -10,7 +10,7 @@ use super::{
     capabilities::common::Capabilities,
     config::ProviderConfigError,
     request::{ProviderContext, ProviderRequest, ProviderResponse},
-    types::ProviderResult,
+    types::{CredentialStatus, ProviderResult},
 };
 use async_trait::async_trait;
 use secrecy::SecretString;
@@ -190,6 +190,21 @@ pub trait Provider: Send + Sync {
     async fn health_check(&self) -> ProviderResult<()> {
         Ok(())
     }
+
+    /// Checks the provider's credentials against its API.
+    ///
+    /// This method is called during system initialization when credential
+    /// validation is enabled, so that a missing or wrong API key is reported
+    /// at startup rather than on the first request. It should use the cheapest
+    /// call the API offers.
+    ///
+    /// # Returns
+    ///
+    /// The `CredentialStatus`, `Unknown` for providers that cannot check.
+    /// An error means the check itself failed, e.g. the API was unreachable.
+    async fn validate_credentials(&self) -> ProviderResult<CredentialStatus> {
+        Ok(CredentialStatus::Unknown)
+    }
 }
 
 /// # Section
@@ -203,7 +218,7 @@ pub trait Provider: Send + Sync {
 /// * `content` - The text content of the section
 /// * `priority` - The priority of the section (used for ordering)
 /// * `metadata` - Additional information about the section
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Section {
     pub content: String,
     pub priority: i32,
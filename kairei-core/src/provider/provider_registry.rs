@@ -1,30 +1,46 @@
 use dashmap::DashMap;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
 use tokio::sync::RwLock;
 use tracing::{debug, instrument};
 
 use crate::{
+    catalog::AgentCatalog,
     config::{PluginConfig, ProviderConfig, ProviderConfigs, SecretConfig},
     event_bus::{ErrorEvent, Event, EventBus, Value},
     event_registry::EventType,
     provider::{
-        capabilities::shared_memory::SharedMemoryCapability,
+        capabilities::{
+            common::RequiresCapabilities, shared_memory::SharedMemoryCapability,
+            sistence_memory::SistenceMemoryCapability,
+        },
         config::plugins::SharedMemoryConfig,
         llms::{
             openai_assistant::OpenAIAssistantProviderLLM, openai_chat::OpenAIChatProviderLLM,
             simple_expert::SimpleExpertProviderLLM,
         },
+        metrics::LlmMetrics,
+        middleware::archive::PromptArchiveMiddleware,
         plugins::{
+            catalog::CatalogPlugin,
             memory::{
                 shared_memory::InMemorySharedMemoryPlugin,
-                shared_memory_adapter::SharedMemoryPluginAdapter, single_memory::MemoryPlugin,
+                shared_memory_adapter::SharedMemoryPluginAdapter,
+                single_memory::MemoryPlugin,
+                sistence_context::{SistenceContextPlugin, SistenceMemories},
             },
+            openapi_tools::{OpenApiToolProvider, ToolRegistry},
             web_search_serper::WebSearchPlugin,
         },
         provider::{Provider, ProviderSecret, ProviderType},
         provider_secret::SecretRegistry,
-        providers::standard::StandardProvider,
-        types::{ProviderError, ProviderMetrix, ProviderResult},
+        providers::{
+            concurrency::ConcurrencyLimitedProvider, metered::MeteredProvider,
+            standard::StandardProvider,
+        },
+        types::{CredentialStatus, ProviderError, ProviderMetrix, ProviderResult},
     },
     timestamp::Timestamp,
 };
@@ -58,6 +74,14 @@ pub struct ProviderRegistry {
     primary_provider: Arc<RwLock<Option<String>>>,
     event_bus: Arc<EventBus>,
     shared_memory_plugins: Arc<DashMap<String, Arc<dyn SharedMemoryCapability>>>,
+    // sistence_memory プラグインからも読まれる
+    sistence_memories: Arc<SistenceMemories>,
+    // catalog プラグインが読むエージェントのカタログ
+    catalog: Arc<AgentCatalog>,
+    // openapi_tools プラグインのツール。call_tool(name, args) からも呼ばれる
+    tools: Arc<ToolRegistry>,
+    // LLM 呼び出しのレイテンシとトークン数。プロバイダーと設定されたモデルごと
+    llm_metrics: Arc<LlmMetrics>,
 }
 
 impl ProviderRegistry {
@@ -74,11 +98,29 @@ impl ProviderRegistry {
             states: Arc::new(DashMap::new()),
             secret_registry: Arc::new(SecretRegistry::new(secret_config.clone())),
             primary_provider,
+            sistence_memories: Arc::new(SistenceMemories::new(event_bus.clone())),
             event_bus,
             shared_memory_plugins: Arc::new(DashMap::new()),
+            catalog: Arc::new(AgentCatalog::default()),
+            tools: Arc::new(ToolRegistry::default()),
+            llm_metrics: Arc::new(LlmMetrics::default()),
         }
     }
 
+    /// The catalog read by providers configured with the `catalog` plugin.
+    /// Set before providers are registered.
+    pub fn with_catalog(mut self, catalog: Arc<AgentCatalog>) -> Self {
+        self.catalog = catalog;
+        self
+    }
+
+    /// Where providers configured with the `openapi_tools` plugin register their tools.
+    /// Set before providers are registered.
+    pub fn with_tools(mut self, tools: Arc<ToolRegistry>) -> Self {
+        self.tools = tools;
+        self
+    }
+
     #[instrument(level = "debug", skip(self))]
     pub async fn register_providers(&self) -> ProviderResult<()> {
         for (name, config) in self.configs.providers.iter() {
@@ -124,6 +166,7 @@ impl ProviderRegistry {
                     params.insert("provider_name".to_string(), Value::String(name.to_string()));
                     params
                 },
+                ..Default::default()
             })
             .await;
 
@@ -135,13 +178,7 @@ impl ProviderRegistry {
         config: &ProviderConfig,
         provider_type: &ProviderType,
     ) -> ProviderResult<()> {
-        let collector: ErrorCollector = match provider_type {
-            ProviderType::OpenAIAssistant => StandardProvider::validate_config_collecting(config),
-            ProviderType::SimpleExpert => StandardProvider::validate_config_collecting(config),
-            ProviderType::OpenAIChat => StandardProvider::validate_config_collecting(config),
-            ProviderType::Sistence => SistenceProvider::validate_config_collecting(config),
-            ProviderType::Unknown => ErrorCollector::new(),
-        };
+        let collector = Self::config_diagnostics(config, provider_type);
 
         // Handle errors
         if collector.has_errors() {
@@ -159,6 +196,67 @@ impl ProviderRegistry {
         Ok(())
     }
 
+    /// Errors and warnings of the validators of `provider_type` for `config`, as
+    /// checked when the provider is registered
+    pub fn config_diagnostics(
+        config: &ProviderConfig,
+        provider_type: &ProviderType,
+    ) -> ErrorCollector {
+        match provider_type {
+            ProviderType::OpenAIAssistant => StandardProvider::validate_config_collecting(config),
+            ProviderType::SimpleExpert => StandardProvider::validate_config_collecting(config),
+            ProviderType::OpenAIChat => StandardProvider::validate_config_collecting(config),
+            ProviderType::Sistence => SistenceProvider::validate_config_collecting(config),
+            ProviderType::Unknown => ErrorCollector::new(),
+        }
+    }
+
+    /// Checks that a provider configured with `config` would offer the capabilities
+    /// it requires, without creating or initializing it. Only the LLM and the
+    /// plugins every provider gets are counted, as configured plugins only add to
+    /// them. SimpleExpert providers are not initialized, so they are not checked.
+    pub async fn negotiate_capabilities(config: &ProviderConfig) -> ProviderResult<()> {
+        let provider = match llm_provider_type(config) {
+            ProviderType::OpenAIAssistant => StandardProvider::new(
+                OpenAIAssistantProviderLLM::new(ProviderType::OpenAIAssistant),
+                vec![],
+            ),
+            ProviderType::OpenAIChat => {
+                StandardProvider::new(OpenAIChatProviderLLM::new(ProviderType::OpenAIChat), vec![])
+            }
+            ProviderType::SimpleExpert => return Ok(()),
+            provider_type => return Err(ProviderError::UnknownProvider(provider_type.to_string())),
+        };
+        provider
+            .required_capabilities()
+            .unsupported(&provider.capabilities().await)
+    }
+
+    /// Keys of `plugin_configs` a provider configured with `config` installs; the
+    /// others are ignored when it is created
+    pub fn supported_plugins(config: &ProviderConfig) -> &'static [&'static str] {
+        match llm_provider_type(config) {
+            ProviderType::OpenAIAssistant => &[
+                "shared_memory",
+                "sistence_memory",
+                "catalog",
+                "openapi_tools",
+                "prompt_archive",
+            ],
+            ProviderType::OpenAIChat => &[
+                "memory",
+                "shared_memory",
+                "sistence_memory",
+                "web_search_serper",
+                "catalog",
+                "openapi_tools",
+                "prompt_archive",
+            ],
+            ProviderType::SimpleExpert => &["catalog", "prompt_archive"],
+            _ => &[],
+        }
+    }
+
     pub async fn register_provider_with(
         &self,
         name: &str,
@@ -172,8 +270,19 @@ impl ProviderRegistry {
             last_health_check: Timestamp::default(),
             error_count: 0,
             last_error: None,
+            credential_status: None,
         };
 
+        // 計測は同時実行の待ち時間を含めないよう内側でラップする
+        self.llm_metrics.register(name, &config.common_config.model);
+        let provider: Arc<dyn Provider> = Arc::new(MeteredProvider::new(
+            provider,
+            name,
+            self.llm_metrics.clone(),
+        ));
+        // 同時実行数の制限があればラップする
+        let provider = ConcurrencyLimitedProvider::wrap(provider, &config.concurrency)?;
+
         let insance = ProviderInstance {
             provider,
             secret: secret.clone(),
@@ -202,6 +311,7 @@ impl ProviderRegistry {
                         params.insert("provider_name".to_string(), Value::String(name.to_string()));
                         params
                     },
+                    ..Default::default()
                 })
                 .await;
             Ok(())
@@ -276,6 +386,16 @@ impl ProviderRegistry {
             .collect()
     }
 
+    /// Get or create the SistenceMemory instance for a namespace
+    ///
+    /// Instances keep their items in memory for the lifetime of the registry.
+    pub async fn get_or_create_sistence_memory(
+        &self,
+        namespace: &str,
+    ) -> ProviderResult<Arc<dyn SistenceMemoryCapability>> {
+        self.sistence_memories.get_or_create(namespace).await
+    }
+
     /// プロバイダーの取得
     pub async fn get_provider(&self, name: &str) -> ProviderResult<Arc<ProviderInstance>> {
         if !self.providers.contains_key(name) {
@@ -376,6 +496,7 @@ impl ProviderRegistry {
                             );
                             params
                         },
+                        ..Default::default()
                     })
                     .await;
             }
@@ -384,6 +505,102 @@ impl ProviderRegistry {
         health_result
     }
 
+    /// プロバイダーの認証情報の検証
+    ///
+    /// Asks every provider to check its credentials, publishing each result as a
+    /// `ProviderStatusUpdated` event. A provider whose check fails is reported as
+    /// unreachable. Fails when a provider listed in `required` is not valid; an
+    /// `unknown` status does not satisfy `required` either.
+    #[instrument(level = "debug", skip(self))]
+    pub async fn validate_credentials(
+        &self,
+        required: &[String],
+    ) -> ProviderResult<BTreeMap<String, CredentialStatus>> {
+        let mut instances = self
+            .providers
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect::<Vec<_>>();
+        instances.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut statuses = BTreeMap::new();
+        for (name, instance) in instances {
+            let status = instance
+                .provider
+                .validate_credentials()
+                .await
+                .unwrap_or_else(|e| CredentialStatus::Unreachable(e.to_string()));
+            if matches!(
+                status,
+                CredentialStatus::Invalid(_) | CredentialStatus::Unreachable(_)
+            ) {
+                tracing::warn!("Credentials of provider {} are {}", name, status);
+            }
+            if let Some(state) = self.states.get(&name) {
+                state.write().await.credential_status = Some(status.clone());
+            }
+            let _ = self
+                .event_bus
+                .publish(Event {
+                    event_type: EventType::ProviderStatusUpdated,
+                    parameters: {
+                        let mut params = HashMap::new();
+                        params.insert("provider_name".to_string(), Value::String(name.clone()));
+                        params.insert(
+                            "credential_status".to_string(),
+                            Value::String(status.as_str().to_string()),
+                        );
+                        if let CredentialStatus::Invalid(reason)
+                        | CredentialStatus::Unreachable(reason) = &status
+                        {
+                            params.insert("reason".to_string(), Value::String(reason.clone()));
+                        }
+                        params
+                    },
+                    ..Default::default()
+                })
+                .await;
+            statuses.insert(name, status);
+        }
+
+        let failures = required
+            .iter()
+            .filter_map(|name| match statuses.get(name) {
+                Some(status) if status.is_valid() => None,
+                Some(status) => Some(format!("{}: {}", name, status)),
+                None => Some(format!("{}: not registered", name)),
+            })
+            .collect::<Vec<_>>();
+        if !failures.is_empty() {
+            return Err(ProviderError::Authentication(format!(
+                "required provider credentials are not valid: {}",
+                failures.join(", ")
+            )));
+        }
+        Ok(statuses)
+    }
+
+    /// Latency and token usage histograms of the calls to the registered providers
+    pub fn llm_metrics(&self) -> Arc<LlmMetrics> {
+        self.llm_metrics.clone()
+    }
+
+    /// Results of the last credential check, keyed by provider name
+    pub async fn credential_statuses(&self) -> BTreeMap<String, CredentialStatus> {
+        let mut statuses = BTreeMap::new();
+        let states = self
+            .states
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect::<Vec<_>>();
+        for (name, state) in states {
+            if let Some(status) = state.read().await.credential_status.clone() {
+                statuses.insert(name, status);
+            }
+        }
+        statuses
+    }
+
     /// すべてのプロバイダーのシャットダウン
     #[instrument(level = "debug", skip(self))]
     pub async fn shutdown(&self) -> ProviderResult<()> {
@@ -412,6 +629,7 @@ impl ProviderRegistry {
                         params.insert("provider_name".to_string(), Value::String(name.clone()));
                         params
                     },
+                    ..Default::default()
                 })
                 .await;
         }
@@ -433,7 +651,7 @@ impl ProviderRegistry {
     ) -> ProviderResult<Arc<dyn Provider>> {
         match provider_type {
             ProviderType::OpenAIAssistant => self.create_assistant(config, secret).await,
-            ProviderType::SimpleExpert => self.create_simple_expert().await,
+            ProviderType::SimpleExpert => self.create_simple_expert(config).await,
             ProviderType::OpenAIChat => self.create_chat(config, secret).await,
             ProviderType::Sistence => self.create_sistence(config, secret).await,
             _ => Err(ProviderError::UnknownProvider(provider_type.to_string())),
@@ -459,16 +677,26 @@ impl ProviderRegistry {
             let plugin_adapter = Arc::new(SharedMemoryPluginAdapter::new(shared_memory_plugin));
             provider.register_plugin(plugin_adapter)?;
         }
+        self.register_sistence_context_plugin(&mut provider, config)?;
+        self.register_catalog_plugin(&mut provider, config)?;
+        self.register_openapi_tools_plugin(&mut provider, config, secret)
+            .await?;
+        Self::register_middlewares(&mut provider, config)?;
 
         provider.initialize(config, secret).await?;
         Ok(Arc::new(provider))
     }
 
-    pub async fn create_simple_expert(&self) -> ProviderResult<Arc<dyn Provider>> {
+    pub async fn create_simple_expert(
+        &self,
+        config: &ProviderConfig,
+    ) -> ProviderResult<Arc<dyn Provider>> {
         let llm = SimpleExpertProviderLLM::new(ProviderType::SimpleExpert);
-        let provider = StandardProvider::new(llm, vec![]);
+        let mut provider = StandardProvider::new(llm, vec![]);
 
-        // No configuration available for SimpleExpert, so no shared memory plugin
+        // SimpleExpert has no shared memory plugin
+        self.register_catalog_plugin(&mut provider, config)?;
+        Self::register_middlewares(&mut provider, config)?;
 
         Ok(Arc::new(provider))
     }
@@ -519,10 +747,78 @@ impl ProviderRegistry {
         if let Ok(web_search_serper_plugin) = WebSearchPlugin::try_new(&search_config, secret) {
             provider.register_plugin(Arc::new(web_search_serper_plugin))?;
         }
+        self.register_sistence_context_plugin(&mut provider, config)?;
+        self.register_catalog_plugin(&mut provider, config)?;
+        self.register_openapi_tools_plugin(&mut provider, config, secret)
+            .await?;
+        Self::register_middlewares(&mut provider, config)?;
         provider.initialize(config, secret).await?;
         Ok(Arc::new(provider))
     }
 
+    /// Install the catalog plugin if `catalog` is configured in `plugin_configs`
+    fn register_catalog_plugin(
+        &self,
+        provider: &mut StandardProvider,
+        config: &ProviderConfig,
+    ) -> ProviderResult<()> {
+        if let Some(PluginConfig::Catalog(catalog_config)) = config.plugin_configs.get("catalog") {
+            provider.register_plugin(Arc::new(CatalogPlugin::new(
+                self.catalog.clone(),
+                catalog_config.clone(),
+            )))?;
+        }
+        Ok(())
+    }
+
+    /// Install the SistenceMemory plugin if `sistence_memory` is configured in `plugin_configs`
+    fn register_sistence_context_plugin(
+        &self,
+        provider: &mut StandardProvider,
+        config: &ProviderConfig,
+    ) -> ProviderResult<()> {
+        if let Some(PluginConfig::SistenceMemory(context_config)) =
+            config.plugin_configs.get("sistence_memory")
+        {
+            provider.register_plugin(Arc::new(SistenceContextPlugin::new(
+                self.sistence_memories.clone(),
+                context_config.clone(),
+            )))?;
+        }
+        Ok(())
+    }
+
+    /// Load the specs of `openapi_tools` in `plugin_configs`, and install their tools
+    async fn register_openapi_tools_plugin(
+        &self,
+        provider: &mut StandardProvider,
+        config: &ProviderConfig,
+        secret: &ProviderSecret,
+    ) -> ProviderResult<()> {
+        if let Some(PluginConfig::OpenApiTools(tools_config)) =
+            config.plugin_configs.get("openapi_tools")
+        {
+            let plugin = Arc::new(OpenApiToolProvider::load(tools_config, secret).await?);
+            self.tools.register(plugin.clone());
+            provider.register_plugin(plugin)?;
+        }
+        Ok(())
+    }
+
+    /// Install the middlewares configured in `plugin_configs`
+    fn register_middlewares(
+        provider: &mut StandardProvider,
+        config: &ProviderConfig,
+    ) -> ProviderResult<()> {
+        if let Some(PluginConfig::PromptArchive(archive_config)) =
+            config.plugin_configs.get("prompt_archive")
+        {
+            let middleware = PromptArchiveMiddleware::from_config(archive_config)?;
+            provider.register_middleware(Arc::new(middleware));
+        }
+        Ok(())
+    }
+
     /// Create a Sistence provider with LLM integration
     ///
     /// This method creates a SistenceProvider that delegates to an underlying
@@ -547,20 +843,8 @@ impl ProviderRegistry {
         use crate::provider::plugins::will_action::DefaultWillActionResolver;
         use crate::provider::providers::sistence::SistenceProvider;
 
-        // Determine base provider type from config, default to OpenAIChat
-        let base_provider_type = config
-            .provider_specific
-            .get("base_provider_type")
-            .and_then(|v| v.as_str())
-            .map(|s| match s {
-                "OpenAIAssistant" => ProviderType::OpenAIAssistant,
-                "OpenAIChat" => ProviderType::OpenAIChat,
-                _ => ProviderType::OpenAIChat, // Default to OpenAIChat for unknown values
-            })
-            .unwrap_or(ProviderType::OpenAIChat);
-
         // Create the base LLM provider
-        let base_provider = match base_provider_type {
+        let base_provider = match sistence_base_type(config) {
             ProviderType::OpenAIAssistant => self.create_assistant(config, secret).await?,
             ProviderType::OpenAIChat => self.create_chat(config, secret).await?,
             _ => self.create_chat(config, secret).await?, // Fallback to OpenAIChat
@@ -603,6 +887,30 @@ impl ProviderRegistry {
     }
 }
 
+/// The provider a Sistence provider delegates to, from `base_provider_type` in
+/// `provider_specific`; OpenAIChat unless OpenAIAssistant is given
+fn sistence_base_type(config: &ProviderConfig) -> ProviderType {
+    config
+        .provider_specific
+        .get("base_provider_type")
+        .and_then(|v| v.as_str())
+        .map(|s| match s {
+            "OpenAIAssistant" => ProviderType::OpenAIAssistant,
+            "OpenAIChat" => ProviderType::OpenAIChat,
+            _ => ProviderType::OpenAIChat, // Default to OpenAIChat for unknown values
+        })
+        .unwrap_or(ProviderType::OpenAIChat)
+}
+
+/// The provider type whose LLM answers for `config`: the base of a Sistence
+/// provider, the configured type otherwise
+fn llm_provider_type(config: &ProviderConfig) -> ProviderType {
+    match config.provider_type {
+        ProviderType::Sistence => sistence_base_type(config),
+        ref provider_type => provider_type.clone(),
+    }
+}
+
 // Registry access methods for shared memory plugins have been implemented above
 
 // Unit tests for shared memory plugin functionality
@@ -611,10 +919,14 @@ impl ProviderRegistry {
 mod tests {
     use std::collections::HashMap;
 
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
     use async_trait::async_trait;
 
     use crate::{
-        config::{CommonConfig, EndpointConfig, ProviderConfig, ProviderSecretConfig},
+        config::{
+            CommonConfig, ConcurrencyConfig, EndpointConfig, ProviderConfig, ProviderSecretConfig,
+        },
         provider::{
             capabilities::common::Capabilities,
             request::{ProviderContext, ProviderRequest, ProviderResponse},
@@ -662,6 +974,7 @@ mod tests {
         let mut provider_configs = HashMap::new();
         let mut secret_configs = SecretConfig {
             providers: HashMap::new(),
+            handler_secrets: HashMap::new(),
         };
         let primary_name = names[0].clone();
         for name in names.iter() {
@@ -671,11 +984,16 @@ mod tests {
                 common_config: CommonConfig {
                     temperature: 0.7,
                     max_tokens: 1000,
+                    top_p: None,
                     model: "gpt-3.5-turbo".to_string(),
+                    output_format: Default::default(),
+                    response_schema: None,
+                    max_repair_attempts: 2,
                 },
                 provider_specific: HashMap::new(),
                 endpoint: EndpointConfig::default(),
                 plugin_configs: HashMap::new(),
+                concurrency: ConcurrencyConfig::default(),
             };
             provider_configs.insert(name.to_string(), config);
 
@@ -766,6 +1084,91 @@ mod tests {
         assert_eq!(providers.len(), 10);
     }
 
+    struct CountingProvider {
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Provider for CountingProvider {
+        async fn execute(
+            &self,
+            _context: &ProviderContext,
+            _request: &ProviderRequest,
+        ) -> ProviderResult<ProviderResponse> {
+            let current = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(current, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(ProviderResponse::default())
+        }
+        async fn capabilities(&self) -> Capabilities {
+            Capabilities::default()
+        }
+        fn name(&self) -> &str {
+            "counting"
+        }
+        async fn initialize(
+            &mut self,
+            _config: &ProviderConfig,
+            _secret: &ProviderSecret,
+        ) -> ProviderResult<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_provider_concurrency_limit() {
+        let registry = get_registry(&["limited".to_string()]).await;
+        let config = ProviderConfig {
+            concurrency: ConcurrencyConfig {
+                max_concurrent_calls: Some(2),
+                max_queue_wait: None,
+            },
+            ..Default::default()
+        };
+        let counting = Arc::new(CountingProvider {
+            in_flight: AtomicUsize::new(0),
+            max_in_flight: AtomicUsize::new(0),
+        });
+        registry
+            .register_provider_with(
+                "limited",
+                &config,
+                &ProviderSecret::default(),
+                counting.clone(),
+            )
+            .await
+            .unwrap();
+
+        let instance = registry.get_provider("limited").await.unwrap();
+        let handles = (0..10)
+            .map(|_| {
+                let instance = instance.clone();
+                tokio::spawn(async move {
+                    instance
+                        .provider
+                        .execute(&ProviderContext::default(), &ProviderRequest::default())
+                        .await
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            assert!(handle.await.unwrap().is_ok());
+        }
+
+        assert_eq!(counting.max_in_flight.load(Ordering::SeqCst), 2);
+
+        // 各呼び出しは設定されたモデルの系列に記録される
+        let series = registry.llm_metrics().series();
+        let configured = series
+            .iter()
+            .find(|s| s.model == config.common_config.model)
+            .unwrap();
+        assert_eq!(configured.provider, "limited");
+        assert_eq!(configured.latency.count(), 10);
+    }
+
     use crate::provider::config::plugins::SharedMemoryConfig;
     use std::time::Duration;
 
@@ -0,0 +1,239 @@
+//! # Concurrency Limited Provider
+//!
+//! Wraps a registered provider so that at most `max_concurrent_calls` executions are
+//! in flight at once. Further calls queue on a semaphore instead of all reaching the
+//! LLM API together, which protects against provider-side rate limit storms.
+
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use tokio::sync::Semaphore;
+
+use crate::{
+    config::{ConcurrencyConfig, ProviderConfig},
+    provider::{
+        capabilities::common::Capabilities,
+        config::ProviderConfigError,
+        provider::{Provider, ProviderSecret},
+        request::{ProviderContext, ProviderRequest, ProviderResponse},
+        types::{CredentialStatus, ProviderError, ProviderResult},
+    },
+};
+
+pub struct ConcurrencyLimitedProvider {
+    inner: Arc<dyn Provider>,
+    semaphore: Arc<Semaphore>,
+    max_concurrent_calls: usize,
+    max_queue_wait: Option<Duration>,
+}
+
+impl ConcurrencyLimitedProvider {
+    pub fn new(
+        inner: Arc<dyn Provider>,
+        max_concurrent_calls: usize,
+        max_queue_wait: Option<Duration>,
+    ) -> ProviderResult<Self> {
+        if max_concurrent_calls == 0 {
+            return Err(ProviderError::Configuration(
+                "max_concurrent_calls must be greater than 0".to_string(),
+            ));
+        }
+        Ok(Self {
+            inner,
+            semaphore: Arc::new(Semaphore::new(max_concurrent_calls)),
+            max_concurrent_calls,
+            max_queue_wait,
+        })
+    }
+
+    /// Wraps `inner` when the config sets a limit, otherwise returns it unchanged.
+    pub fn wrap(
+        inner: Arc<dyn Provider>,
+        config: &ConcurrencyConfig,
+    ) -> ProviderResult<Arc<dyn Provider>> {
+        match config.max_concurrent_calls {
+            Some(max) => Ok(Arc::new(Self::new(inner, max, config.max_queue_wait)?)),
+            None => Ok(inner),
+        }
+    }
+
+    /// Number of calls currently executing
+    pub fn in_flight(&self) -> usize {
+        self.max_concurrent_calls - self.semaphore.available_permits()
+    }
+}
+
+#[async_trait]
+impl Provider for ConcurrencyLimitedProvider {
+    async fn execute(
+        &self,
+        context: &ProviderContext,
+        request: &ProviderRequest,
+    ) -> ProviderResult<ProviderResponse> {
+        let acquire = self.semaphore.acquire();
+        let permit = match self.max_queue_wait {
+            Some(wait) => tokio::time::timeout(wait, acquire).await.map_err(|_| {
+                ProviderError::Overloaded(format!(
+                    "{}: no free slot within {}ms ({} calls in flight)",
+                    self.inner.name(),
+                    wait.as_millis(),
+                    self.max_concurrent_calls
+                ))
+            })?,
+            None => acquire.await,
+        }
+        .map_err(|e| ProviderError::InternalError(e.to_string()))?;
+
+        let result = self.inner.execute(context, request).await;
+        drop(permit);
+        result
+    }
+
+    async fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities().await
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn initialize(
+        &mut self,
+        config: &ProviderConfig,
+        secret: &ProviderSecret,
+    ) -> ProviderResult<()> {
+        match Arc::get_mut(&mut self.inner) {
+            Some(inner) => inner.initialize(config, secret).await,
+            None => Err(ProviderError::Initialization(format!(
+                "{}: wrapped provider is shared and cannot be initialized",
+                self.inner.name()
+            ))),
+        }
+    }
+
+    fn validate_config(&self, config: &ProviderConfig) -> Result<(), ProviderConfigError> {
+        self.inner.validate_config(config)
+    }
+
+    async fn shutdown(&self) -> ProviderResult<()> {
+        self.inner.shutdown().await
+    }
+
+    async fn health_check(&self) -> ProviderResult<()> {
+        self.inner.health_check().await
+    }
+
+    async fn validate_credentials(&self) -> ProviderResult<CredentialStatus> {
+        self.inner.validate_credentials().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    struct SlowProvider {
+        delay: Duration,
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+    }
+
+    impl SlowProvider {
+        fn new(delay: Duration) -> Self {
+            Self {
+                delay,
+                in_flight: AtomicUsize::new(0),
+                max_in_flight: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Provider for SlowProvider {
+        async fn execute(
+            &self,
+            _context: &ProviderContext,
+            _request: &ProviderRequest,
+        ) -> ProviderResult<ProviderResponse> {
+            let current = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(current, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(ProviderResponse::default())
+        }
+
+        async fn capabilities(&self) -> Capabilities {
+            Capabilities::default()
+        }
+
+        fn name(&self) -> &str {
+            "slow"
+        }
+
+        async fn initialize(
+            &mut self,
+            _config: &ProviderConfig,
+            _secret: &ProviderSecret,
+        ) -> ProviderResult<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_in_flight_calls_never_exceed_limit() {
+        let inner = Arc::new(SlowProvider::new(Duration::from_millis(20)));
+        let provider = Arc::new(ConcurrencyLimitedProvider::new(inner.clone(), 3, None).unwrap());
+
+        let handles = (0..12)
+            .map(|_| {
+                let provider = provider.clone();
+                tokio::spawn(async move {
+                    provider
+                        .execute(&ProviderContext::default(), &ProviderRequest::default())
+                        .await
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            assert!(handle.await.unwrap().is_ok());
+        }
+
+        assert_eq!(inner.max_in_flight.load(Ordering::SeqCst), 3);
+        assert_eq!(provider.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_queue_wait_exceeded_is_overloaded() {
+        let inner = Arc::new(SlowProvider::new(Duration::from_millis(200)));
+        let provider = Arc::new(
+            ConcurrencyLimitedProvider::new(inner, 1, Some(Duration::from_millis(20))).unwrap(),
+        );
+
+        let busy = {
+            let provider = provider.clone();
+            tokio::spawn(async move {
+                provider
+                    .execute(&ProviderContext::default(), &ProviderRequest::default())
+                    .await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let result = provider
+            .execute(&ProviderContext::default(), &ProviderRequest::default())
+            .await;
+        assert!(matches!(result, Err(ProviderError::Overloaded(_))));
+        assert!(busy.await.unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_zero_limit_is_rejected() {
+        let inner = Arc::new(SlowProvider::new(Duration::ZERO));
+        assert!(matches!(
+            ConcurrencyLimitedProvider::new(inner, 0, None),
+            Err(ProviderError::Configuration(_))
+        ));
+    }
+}
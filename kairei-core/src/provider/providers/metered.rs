@@ -0,0 +1,99 @@
+//! # Metered Provider
+//!
+//! Wraps a registered provider so that the latency and token usage of each successful
+//! execution is recorded in the [`LlmMetrics`] of its registry, labeled with the
+//! provider name and the model of the request.
+
+use std::{sync::Arc, time::Instant};
+
+use async_trait::async_trait;
+
+use crate::{
+    config::ProviderConfig,
+    provider::{
+        capabilities::common::Capabilities,
+        config::ProviderConfigError,
+        metrics::LlmMetrics,
+        provider::{Provider, ProviderSecret},
+        request::{ProviderContext, ProviderRequest, ProviderResponse},
+        types::{CredentialStatus, ProviderError, ProviderResult},
+    },
+};
+
+pub struct MeteredProvider {
+    inner: Arc<dyn Provider>,
+    provider_name: String,
+    metrics: Arc<LlmMetrics>,
+}
+
+impl MeteredProvider {
+    pub fn new(inner: Arc<dyn Provider>, provider_name: &str, metrics: Arc<LlmMetrics>) -> Self {
+        Self {
+            inner,
+            provider_name: provider_name.to_string(),
+            metrics,
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for MeteredProvider {
+    async fn execute(
+        &self,
+        context: &ProviderContext,
+        request: &ProviderRequest,
+    ) -> ProviderResult<ProviderResponse> {
+        let started = Instant::now();
+        let result = self.inner.execute(context, request).await;
+        if let Ok(response) = &result {
+            self.metrics.record(
+                &self.provider_name,
+                &request.config.common_config.model,
+                started.elapsed(),
+                response
+                    .metadata
+                    .token_usage
+                    .map(|(prompt, completion)| prompt + completion),
+            );
+        }
+        result
+    }
+
+    async fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities().await
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn initialize(
+        &mut self,
+        config: &ProviderConfig,
+        secret: &ProviderSecret,
+    ) -> ProviderResult<()> {
+        match Arc::get_mut(&mut self.inner) {
+            Some(inner) => inner.initialize(config, secret).await,
+            None => Err(ProviderError::Initialization(format!(
+                "{}: wrapped provider is shared and cannot be initialized",
+                self.inner.name()
+            ))),
+        }
+    }
+
+    fn validate_config(&self, config: &ProviderConfig) -> Result<(), ProviderConfigError> {
+        self.inner.validate_config(config)
+    }
+
+    async fn shutdown(&self) -> ProviderResult<()> {
+        self.inner.shutdown().await
+    }
+
+    async fn health_check(&self) -> ProviderResult<()> {
+        self.inner.health_check().await
+    }
+
+    async fn validate_credentials(&self) -> ProviderResult<CredentialStatus> {
+        self.inner.validate_credentials().await
+    }
+}
@@ -1,2 +1,4 @@
+pub mod concurrency;
+pub mod metered;
 pub mod sistence;
 pub mod standard;
@@ -67,7 +67,7 @@ use crate::provider::config::{ErrorCollector, ProviderConfigValidator, config_to
 use crate::provider::provider::{Provider, ProviderSecret};
 use crate::provider::request::RequestInput;
 use crate::provider::request::{ProviderContext, ProviderRequest, ProviderResponse};
-use crate::provider::types::{ProviderError, ProviderResult};
+use crate::provider::types::{CredentialStatus, ProviderError, ProviderResult};
 use crate::timestamp::Timestamp;
 
 use super::standard::StandardProvider;
@@ -732,6 +732,7 @@ impl SistenceProvider {
             },
             state: request.state.clone(),
             config: request.config.clone(),
+            ..Default::default()
         };
 
         // Execute LLM request
@@ -907,6 +908,11 @@ impl Provider for SistenceProvider {
         // Forward to the underlying provider
         self.llm_provider.shutdown().await
     }
+
+    async fn validate_credentials(&self) -> ProviderResult<CredentialStatus> {
+        // Forward to the underlying provider
+        self.llm_provider.validate_credentials().await
+    }
 }
 
 #[cfg(test)]
@@ -1087,6 +1093,23 @@ mod tests {
             let storage = self.storage.lock().unwrap();
             Ok(storage.keys().cloned().collect())
         }
+
+        async fn compare_and_swap(
+            &self,
+            key: &str,
+            expected: Option<&serde_json::Value>,
+            value: serde_json::Value,
+        ) -> Result<bool, crate::provider::capabilities::shared_memory::SharedMemoryError> {
+            let mut storage = self.storage.lock().unwrap();
+            let current = storage
+                .get(key)
+                .and_then(|data| serde_json::from_slice::<serde_json::Value>(data).ok());
+            if current.as_ref() != expected {
+                return Ok(false);
+            }
+            storage.insert(key.to_string(), value.to_string().into_bytes());
+            Ok(true)
+        }
     }
 
     #[async_trait]
@@ -1104,8 +1127,10 @@ mod tests {
         async fn generate_section<'a>(
             &self,
             _context: &crate::provider::plugin::PluginContext<'a>,
-        ) -> crate::provider::types::ProviderResult<crate::provider::provider::Section> {
-            Ok(crate::provider::provider::Section::default())
+        ) -> crate::provider::types::ProviderResult<
+            crate::provider::contribution::ContextContribution,
+        > {
+            Ok(crate::provider::provider::Section::default().into())
         }
 
         async fn process_response<'a>(
@@ -1143,8 +1168,10 @@ mod tests {
         async fn generate_section<'a>(
             &self,
             _context: &crate::provider::plugin::PluginContext<'a>,
-        ) -> crate::provider::types::ProviderResult<crate::provider::provider::Section> {
-            Ok(crate::provider::provider::Section::default())
+        ) -> crate::provider::types::ProviderResult<
+            crate::provider::contribution::ContextContribution,
+        > {
+            Ok(crate::provider::provider::Section::default().into())
         }
 
         async fn process_response<'a>(
@@ -1302,8 +1329,7 @@ mod tests {
                 query: crate::eval::expression::Value::String("test query".to_string()),
                 parameters: HashMap::new(),
             },
-            state: Default::default(),
-            config: Default::default(),
+            ..Default::default()
         };
 
         let response = sistence_provider.execute(&context, &request).await.unwrap();
@@ -1353,8 +1379,7 @@ mod tests {
                 query: crate::eval::expression::Value::String("notify".to_string()),
                 parameters,
             },
-            state: Default::default(),
-            config: Default::default(),
+            ..Default::default()
         };
 
         let response = sistence_provider.execute(&context, &request).await.unwrap();
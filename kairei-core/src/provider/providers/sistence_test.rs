@@ -16,6 +16,7 @@ use crate::provider::capabilities::will_action::{
 };
 use crate::provider::capabilities::common::{Capabilities, CapabilityType};
 use crate::provider::plugin::{PluginContext, ProviderPlugin};
+use crate::provider::contribution::ContextContribution;
 use crate::provider::provider::{Provider, ProviderError, ProviderResult, ProviderSecret, Section};
 use crate::provider::providers::sistence::{SistenceAgentContext, SistenceProvider};
 use crate::provider::request::{ProviderContext, ProviderRequest, ProviderResponse};
@@ -161,8 +162,8 @@ impl ProviderPlugin for MockSharedMemory {
         CapabilityType::Custom("shared_memory".to_string())
     }
 
-    async fn generate_section<'a>(&self, _context: &PluginContext<'a>) -> ProviderResult<Section> {
-        Ok(Section::new(""))
+    async fn generate_section<'a>(&self, _context: &PluginContext<'a>) -> ProviderResult<ContextContribution> {
+        Ok(Section::new("").into())
     }
 
     async fn process_response<'a>(
@@ -322,8 +323,8 @@ impl ProviderPlugin for MockWillActionResolver {
         CapabilityType::Custom("will_action".to_string())
     }
 
-    async fn generate_section<'a>(&self, _context: &PluginContext<'a>) -> ProviderResult<Section> {
-        Ok(Section::new(""))
+    async fn generate_section<'a>(&self, _context: &PluginContext<'a>) -> ProviderResult<ContextContribution> {
+        Ok(Section::new("").into())
     }
 
     async fn process_response<'a>(
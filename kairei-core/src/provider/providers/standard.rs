@@ -11,21 +11,41 @@ use crate::{
             Capabilities, CapabilityType, RequiredCapabilities, RequiresCapabilities,
         },
         config::{ErrorCollector, ProviderConfigValidator, TypeCheckerValidator, config_to_map},
-        generator::generator::{Generator, PromptGenerator},
+        contribution::ContextContribution,
+        generator::{
+            assembler::PromptAssembler,
+            generator::{Generator, PromptGenerator},
+        },
         llm::{LLMResponse, ProviderLLM},
         llms::simple_expert::SimpleExpertProviderLLM,
+        middleware::{MiddlewareStatus, ProviderMiddleware},
         plugin::{PluginContext, ProviderPlugin},
-        plugins::{general_prompt::GeneralPromptPlugin, policy::PolicyPlugin},
-        provider::{Provider, ProviderSecret, Section},
+        plugins::{
+            general_prompt::GeneralPromptPlugin, json_output::JsonOutputPlugin,
+            policy::PolicyPlugin, request_context::RequestContextPlugin,
+        },
+        provider::{Provider, ProviderSecret, Section, SectionMetadata},
         request::{ProviderContext, ProviderRequest, ProviderResponse},
-        types::{ProviderError, ProviderResult},
+        response_schema::{check_response, repair_prompt},
+        types::{CredentialStatus, ProviderError, ProviderResult},
     },
 };
 
+/// Priority of the prompt preamble (persona) section.
+/// Sections are ordered by ascending priority, so the preamble always comes
+/// first, ahead of every plugin section (general prompt 0, request context 5,
+/// catalog 8, tools 9, policy 10, memory 10/100, JSON output 200).
+pub const PROMPT_PREAMBLE_PRIORITY: i32 = i32::MIN;
+
+/// Tool calls answered for a single request. The request fails when the LLM
+/// asks for more.
+pub const MAX_TOOL_ROUNDS: usize = 5;
+
 pub struct StandardProvider {
     name: String,
     llm: Arc<RwLock<dyn ProviderLLM>>,
     plugins: Vec<Arc<dyn ProviderPlugin>>,
+    middlewares: Vec<Arc<dyn ProviderMiddleware>>,
     generator: Arc<dyn Generator>,
 }
 
@@ -37,7 +57,13 @@ impl Default for StandardProvider {
                 "SimpleExpertProviderLLM",
             ))),
 
-            plugins: vec![Arc::new(GeneralPromptPlugin), Arc::new(PolicyPlugin)],
+            plugins: vec![
+                Arc::new(GeneralPromptPlugin),
+                Arc::new(RequestContextPlugin),
+                Arc::new(PolicyPlugin),
+                Arc::new(JsonOutputPlugin),
+            ],
+            middlewares: vec![],
             generator: Arc::new(PromptGenerator::new(None)),
         }
     }
@@ -70,33 +96,52 @@ impl Provider for StandardProvider {
         context: &ProviderContext,
         request: &ProviderRequest,
     ) -> ProviderResult<ProviderResponse> {
-        // 1. プラグインによるセクション生成
+        let provider_context = context;
+        // 1. プラグインの寄与の生成と描画
         let context = Arc::new(PluginContext {
             context,
             request,
             configs: &request.config.plugin_configs,
         });
         debug!("context: {:?}", request);
-        let sections = self.generate_plugin_sections(&context).await?;
+        let profiler = provider_context.profiler.as_ref();
+        let assembly = profiler.map(|profiler| profiler.enter("assembly"));
+        let contributions = self.generate_plugin_contributions(&context).await?;
+        let sections = self.assemble_sections(&contributions).await;
         debug!("sections: {:?}", sections);
         // 2. プロンプトの生成
         let prompt = self.generator.generate(sections).await?;
         debug!("prompt: {}", prompt);
-        // 3. LLMの実行
-        let llm_response = self
-            .llm
-            .read()
-            .await
-            .send_message(&prompt, &request.config)
-            .await?;
-        debug!("llm_response: {:?}", llm_response);
-
-        // 4. プラグインの後処理
-        self.process_plugins_response(&context, &llm_response)
-            .await?;
-
-        // 5. レスポンスの構築
-        Ok(ProviderResponse::from(llm_response))
+        // 以降は型付きの寄与を持つリクエストで処理する
+        let request = &ProviderRequest {
+            contributions: contributions
+                .into_iter()
+                .map(|(_, contribution)| contribution)
+                .collect(),
+            ..request.clone()
+        };
+        let context = Arc::new(PluginContext {
+            context: provider_context,
+            request,
+            configs: &request.config.plugin_configs,
+        });
+        for middleware in &self.middlewares {
+            middleware
+                .before_execute(provider_context, request, &prompt)
+                .await?;
+        }
+        drop(assembly);
+        // 3. LLMの実行と後処理
+        let result = self.send_and_process(&context, &prompt).await;
+        let _processing = profiler
+            .filter(|_| !self.middlewares.is_empty())
+            .map(|profiler| profiler.enter("processing"));
+        for middleware in &self.middlewares {
+            middleware
+                .after_execute(provider_context, request, &prompt, &result)
+                .await?;
+        }
+        result
     }
     async fn capabilities(&self) -> Capabilities {
         self.llm.read().await.capabilities().or(self
@@ -115,6 +160,15 @@ impl Provider for StandardProvider {
         self.llm.write().await.stop().await?;
         Ok(())
     }
+
+    async fn validate_credentials(&self) -> ProviderResult<CredentialStatus> {
+        // LLM とプラグインの結果のうち最も悪いもの
+        let mut status = self.llm.read().await.validate_credentials().await?;
+        for plugin in &self.plugins {
+            status = status.combine(plugin.validate_credentials().await?);
+        }
+        Ok(status)
+    }
 }
 
 impl RequiresCapabilities for StandardProvider {
@@ -137,6 +191,7 @@ impl StandardProvider {
                 name,
                 llm,
                 plugins: default.plugins,
+                middlewares: default.middlewares,
                 generator: default.generator,
             };
         }
@@ -144,6 +199,7 @@ impl StandardProvider {
             name,
             llm,
             plugins,
+            middlewares: default.middlewares,
             generator: default.generator,
         }
     }
@@ -153,13 +209,119 @@ impl StandardProvider {
         Ok(())
     }
 
+    /// Install a middleware wrapping every `execute` call, in registration order.
+    pub fn register_middleware(&mut self, middleware: Arc<dyn ProviderMiddleware>) {
+        self.middlewares.push(middleware);
+    }
+
+    /// Status of each installed middleware, keyed by middleware name
+    pub fn middleware_status(&self) -> Vec<(String, MiddlewareStatus)> {
+        self.middlewares
+            .iter()
+            .map(|m| (m.name().to_string(), m.status()))
+            .collect()
+    }
+
     #[allow(clippy::needless_lifetimes)]
-    async fn generate_plugin_sections<'a>(
+    async fn send_and_process<'a>(
         &self,
         context: &PluginContext<'a>,
-    ) -> ProviderResult<Vec<Section>> {
-        debug!("generate_plugin_sections");
-        let mut sections = Vec::new();
+        prompt: &str,
+    ) -> ProviderResult<ProviderResponse> {
+        let common_config = &context.request.config.common_config;
+        let mut prompt = prompt.to_string();
+        let mut rounds = 0;
+        let mut repairs = 0;
+        let profiler = context.context.profiler.as_ref();
+        let mut processing = None;
+        // ツール呼び出しやスキーマ修正で問い直す応答は途中で送れないため、その場合は流さない
+        let chunks = context.context.chunks.as_ref().filter(|_| {
+            common_config.response_schema.is_none()
+                && !self
+                    .plugins
+                    .iter()
+                    .any(|plugin| plugin.capability() == CapabilityType::Tools)
+        });
+        let llm_response = loop {
+            drop(processing.take());
+            let network = profiler.map(|profiler| profiler.enter("network"));
+            let llm = self.llm.read().await;
+            let llm_response = match chunks {
+                Some(chunks) => {
+                    llm.send_message_streaming(&prompt, &context.request.config, chunks)
+                        .await?
+                }
+                None => llm.send_message(&prompt, &context.request.config).await?,
+            };
+            drop(llm);
+            drop(network);
+            debug!("llm_response: {:?}", llm_response);
+            processing = profiler.map(|profiler| profiler.enter("processing"));
+
+            // ツール呼び出しなら結果をプロンプトに追記して問い直す
+            if let Some(result) = self.handle_tool_call(context, &llm_response).await? {
+                if rounds == MAX_TOOL_ROUNDS {
+                    return Err(ProviderError::InvalidRequest(format!(
+                        "LLM requested more than {} tool calls",
+                        MAX_TOOL_ROUNDS
+                    )));
+                }
+                rounds += 1;
+                prompt = format!("{}\n\n{}\n\n{}", prompt, llm_response.content, result);
+                continue;
+            }
+
+            // スキーマ違反なら違反箇所を示して修正を求める
+            let violations = common_config
+                .response_schema
+                .as_ref()
+                .and_then(|schema| check_response(schema, &llm_response.content).err());
+            let Some(violations) = violations else {
+                break llm_response;
+            };
+            if repairs == common_config.max_repair_attempts {
+                let listed: Vec<String> = violations.iter().map(ToString::to_string).collect();
+                return Err(ProviderError::InvalidRequest(format!(
+                    "response still violates its schema after {} repair attempts: {}",
+                    repairs,
+                    listed.join("; ")
+                )));
+            }
+            repairs += 1;
+            prompt = format!(
+                "{}\n\n{}\n\n{}",
+                prompt,
+                llm_response.content,
+                repair_prompt(&violations)
+            );
+        };
+
+        // 4. プラグインの後処理
+        self.process_plugins_response(context, &llm_response)
+            .await?;
+        drop(processing);
+
+        // 5. レスポンスの構築
+        Ok(ProviderResponse::from(llm_response))
+    }
+
+    /// プラグインの寄与を、描画に使う優先度と共にプロンプト順で集める
+    #[allow(clippy::needless_lifetimes)]
+    async fn generate_plugin_contributions<'a>(
+        &self,
+        context: &PluginContext<'a>,
+    ) -> ProviderResult<Vec<(i32, ContextContribution)>> {
+        debug!("generate_plugin_contributions");
+        let mut contributions = Vec::new();
+
+        if let Some(preamble) = &context.request.state.prompt_preamble {
+            let section = Section {
+                content: preamble.clone(),
+                priority: PROMPT_PREAMBLE_PRIORITY,
+                metadata: SectionMetadata::new("prompt_preamble"),
+            };
+            contributions.push((PROMPT_PREAMBLE_PRIORITY, section.into()));
+        }
 
         let mut plugins = self.plugins.clone();
         plugins.sort_by_key(|p| p.priority());
@@ -170,11 +332,42 @@ impl StandardProvider {
             if llm_capabilities.supports(&plugin.capability()) {
                 continue;
             }
-            let section = plugin.generate_section(context).await?;
-            sections.push(section);
+            let _span = context
+                .context
+                .profiler
+                .as_ref()
+                .map(|profiler| profiler.enter(format!("plugin {:?}", plugin.capability())));
+            let contribution = plugin.generate_section(context).await?;
+            contributions.push((plugin.priority(), contribution));
         }
 
-        Ok(sections)
+        Ok(contributions)
+    }
+
+    /// 寄与を LLM の機能に応じてセクションに描画する
+    async fn assemble_sections(
+        &self,
+        contributions: &[(i32, ContextContribution)],
+    ) -> Vec<Section> {
+        let assembler = PromptAssembler::new(self.llm.read().await.capabilities());
+        contributions
+            .iter()
+            .map(|(priority, contribution)| assembler.render(contribution, *priority))
+            .collect()
+    }
+
+    #[allow(clippy::needless_lifetimes)]
+    async fn handle_tool_call<'a>(
+        &self,
+        context: &PluginContext<'a>,
+        response: &LLMResponse,
+    ) -> ProviderResult<Option<String>> {
+        for plugin in &self.plugins {
+            if let Some(result) = plugin.handle_tool_call(context, response).await? {
+                return Ok(Some(result));
+            }
+        }
+        Ok(None)
     }
 
     #[allow(clippy::needless_lifetimes)]
@@ -307,8 +500,8 @@ mod tests {
         async fn generate_section<'a>(
             &self,
             _context: &PluginContext<'a>,
-        ) -> ProviderResult<Section> {
-            Ok(Section::default())
+        ) -> ProviderResult<ContextContribution> {
+            Ok(Section::default().into())
         }
 
         async fn process_response<'a>(
@@ -466,4 +659,375 @@ mod tests {
 
         assert_eq!(response.output.len(), 0);
     }
+
+    struct WeatherToolPlugin;
+
+    #[async_trait]
+    impl ProviderPlugin for WeatherToolPlugin {
+        fn priority(&self) -> i32 {
+            9
+        }
+
+        fn capability(&self) -> CapabilityType {
+            CapabilityType::Tools
+        }
+
+        async fn generate_section<'a>(
+            &self,
+            _context: &PluginContext<'a>,
+        ) -> ProviderResult<ContextContribution> {
+            Ok(Section::default().into())
+        }
+
+        async fn process_response<'a>(
+            &self,
+            _context: &PluginContext<'a>,
+            _response: &LLMResponse,
+        ) -> ProviderResult<()> {
+            Ok(())
+        }
+
+        async fn handle_tool_call<'a>(
+            &self,
+            _context: &PluginContext<'a>,
+            response: &LLMResponse,
+        ) -> ProviderResult<Option<String>> {
+            Ok(
+                (response.content == r#"{"tool": "weather"}"#)
+                    .then(|| "weather: sunny".to_string()),
+            )
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute_answers_tool_calls() {
+        use crate::provider::llm::MockProviderLLM;
+
+        let mut llm = MockProviderLLM::new();
+        llm.expect_name().return_const("mock_llm".to_string());
+        llm.expect_capabilities()
+            .returning(|| Capabilities::from(CapabilityType::Generate));
+        llm.expect_send_message().returning(|prompt, _| {
+            // ツールの結果を受け取るまではツールを呼び出す
+            let content = if prompt.ends_with("weather: sunny") {
+                "It is sunny"
+            } else {
+                r#"{"tool": "weather"}"#
+            };
+            Box::pin(async move {
+                Ok(LLMResponse {
+                    content: content.to_string(),
+                    ..Default::default()
+                })
+            })
+        });
+        let mut provider = StandardProvider::new(llm, vec![]);
+        provider
+            .register_plugin(Arc::new(WeatherToolPlugin))
+            .unwrap();
+
+        let context = ProviderContext::default();
+        let response = provider
+            .execute(&context, &create_valid_request())
+            .await
+            .unwrap();
+
+        assert_eq!(response.output, "It is sunny");
+    }
+
+    fn schema_request(max_repair_attempts: usize) -> ProviderRequest {
+        let mut request = create_valid_request();
+        request.config.common_config.response_schema = Some(serde_json::json!({
+            "type": "object",
+            "properties": {"city": {"type": "string"}, "days": {"type": "integer"}},
+            "required": ["city", "days"]
+        }));
+        request.config.common_config.max_repair_attempts = max_repair_attempts;
+        request
+    }
+
+    #[tokio::test]
+    async fn test_execute_repairs_schema_violations() {
+        use crate::provider::llm::MockProviderLLM;
+
+        let mut llm = MockProviderLLM::new();
+        llm.expect_name().return_const("mock_llm".to_string());
+        llm.expect_capabilities()
+            .returning(|| Capabilities::from(CapabilityType::Generate));
+        llm.expect_send_message().times(2).returning(|prompt, _| {
+            // 修正依頼を受け取るまでは days を文字列で返す
+            let content = if prompt.contains("- /days: expected integer, found string") {
+                r#"{"city": "Kyoto", "days": 3}"#
+            } else {
+                r#"{"city": "Kyoto", "days": "three"}"#
+            };
+            Box::pin(async move {
+                Ok(LLMResponse {
+                    content: content.to_string(),
+                    ..Default::default()
+                })
+            })
+        });
+        let provider = StandardProvider::new(llm, vec![]);
+
+        let context = ProviderContext::default();
+        let response = provider
+            .execute(&context, &schema_request(2))
+            .await
+            .unwrap();
+
+        assert_eq!(response.output, r#"{"city": "Kyoto", "days": 3}"#);
+    }
+
+    #[tokio::test]
+    async fn test_execute_fails_after_max_repair_attempts() {
+        use crate::provider::llm::MockProviderLLM;
+
+        let mut llm = MockProviderLLM::new();
+        llm.expect_name().return_const("mock_llm".to_string());
+        llm.expect_capabilities()
+            .returning(|| Capabilities::from(CapabilityType::Generate));
+        llm.expect_send_message().times(2).returning(|_, _| {
+            Box::pin(async move {
+                Ok(LLMResponse {
+                    content: r#"{"city": "Kyoto"}"#.to_string(),
+                    ..Default::default()
+                })
+            })
+        });
+        let provider = StandardProvider::new(llm, vec![]);
+
+        let context = ProviderContext::default();
+        let error = provider
+            .execute(&context, &schema_request(1))
+            .await
+            .unwrap_err();
+
+        assert!(
+            error
+                .to_string()
+                .contains("/: missing required property \"days\"")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_archives_prompts_and_responses() {
+        use crate::{
+            config::PromptArchiveConfig,
+            provider::{llm::MockProviderLLM, middleware::archive::PromptArchiveMiddleware},
+        };
+
+        let mut llm = MockProviderLLM::new();
+        llm.expect_name().return_const("mock_llm".to_string());
+        llm.expect_capabilities()
+            .returning(|| Capabilities::from(CapabilityType::Generate));
+        llm.expect_send_message().returning(|_, _| {
+            Box::pin(async move {
+                Ok(LLMResponse {
+                    content: "Mail sent to bob@example.com".to_string(),
+                    ..Default::default()
+                })
+            })
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = PromptArchiveConfig {
+            directory: dir.path().to_string_lossy().to_string(),
+            redact_patterns: vec![r"[\w.+-]+@[\w-]+\.[\w.]+".to_string()],
+            ..Default::default()
+        };
+        let archive = Arc::new(PromptArchiveMiddleware::from_config(&config).unwrap());
+        let mut provider = StandardProvider::new(llm, vec![]);
+        provider.register_middleware(archive.clone());
+
+        let context = ProviderContext::default();
+        for query in ["Write to alice@example.com", "Summarize the thread"] {
+            let mut request = create_valid_request();
+            request.input.query = expression::Value::String(query.to_string());
+            request.state.agent_name = "Mailer".to_string();
+            provider.execute(&context, &request).await.unwrap();
+        }
+        archive.flush().await;
+
+        let file = std::fs::read_dir(dir.path())
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        let contents = std::fs::read_to_string(file).unwrap();
+        let records: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(records.len(), 2);
+        assert!(!contents.contains("@example.com"));
+        assert!(
+            records[0]["prompt"]
+                .as_str()
+                .unwrap()
+                .contains("[REDACTED]")
+        );
+        assert!(
+            records[1]["prompt"]
+                .as_str()
+                .unwrap()
+                .contains("Summarize the thread")
+        );
+        for record in &records {
+            assert_eq!(record["agent_name"], "Mailer");
+            assert_eq!(record["response"], "Mail sent to [REDACTED]");
+        }
+        assert_eq!(
+            provider.middleware_status(),
+            vec![(
+                "prompt_archive".to_string(),
+                MiddlewareStatus {
+                    buffered: 0,
+                    flushed: 2,
+                    dropped: 0,
+                }
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_prompt_preamble_section_comes_first() {
+        use crate::{
+            ast::{Policy, PolicyId, PolicyScope},
+            config::MemoryConfig,
+            provider::plugins::memory::single_memory::MemoryPlugin,
+        };
+
+        let llm = MockLLM {
+            name: "mock_llm".to_string(),
+            capabilities: Capabilities::from(CapabilityType::Generate),
+        };
+        let provider = StandardProvider::new(
+            llm,
+            vec![
+                Arc::new(MemoryPlugin::new(MemoryConfig::default())),
+                Arc::new(PolicyPlugin),
+            ],
+        );
+
+        let mut request = create_valid_request();
+        request.state.prompt_preamble = Some("You are a careful travel planner.".to_string());
+        request.state.policies = vec![Policy {
+            text: "Prefer trains over flights".to_string(),
+            scope: PolicyScope::World("TestWorld".to_string()),
+            internal_id: PolicyId::new(),
+        }];
+        let context = ProviderContext::default();
+        let plugin_context = PluginContext {
+            context: &context,
+            request: &request,
+            configs: &request.config.plugin_configs,
+        };
+
+        let contributions = provider
+            .generate_plugin_contributions(&plugin_context)
+            .await
+            .unwrap();
+        let sections = provider.assemble_sections(&contributions).await;
+
+        let priorities: Vec<i32> = sections.iter().map(|s| s.priority).collect();
+        assert_eq!(priorities, vec![PROMPT_PREAMBLE_PRIORITY, 10, 100]);
+        assert_eq!(sections[0].content, "You are a careful travel planner.");
+        assert!(sections[1].content.contains("Prefer trains over flights"));
+    }
+
+    struct RecordsPlugin {
+        seen_contributions: std::sync::Mutex<usize>,
+    }
+
+    #[async_trait]
+    impl ProviderPlugin for RecordsPlugin {
+        fn priority(&self) -> i32 {
+            100
+        }
+
+        fn capability(&self) -> CapabilityType {
+            CapabilityType::Memory
+        }
+
+        async fn generate_section<'a>(
+            &self,
+            _context: &PluginContext<'a>,
+        ) -> ProviderResult<ContextContribution> {
+            Ok(ContextContribution::StructuredRecords {
+                title: "Previous Context:".to_string(),
+                records: vec![serde_json::json!({"content": "Tom likes trains"})],
+                hint: Default::default(),
+            })
+        }
+
+        async fn process_response<'a>(
+            &self,
+            context: &PluginContext<'a>,
+            _response: &LLMResponse,
+        ) -> ProviderResult<()> {
+            *self.seen_contributions.lock().unwrap() = context.request.contributions.len();
+            Ok(())
+        }
+    }
+
+    async fn execute_with_records(capabilities: Capabilities) -> (String, usize) {
+        use crate::provider::llm::MockProviderLLM;
+
+        let prompts = Arc::new(std::sync::Mutex::new(vec![]));
+        let mut llm = MockProviderLLM::new();
+        llm.expect_name().return_const("mock_llm".to_string());
+        llm.expect_capabilities()
+            .returning(move || capabilities.clone());
+        let sent = prompts.clone();
+        llm.expect_send_message().returning(move |prompt, _| {
+            sent.lock().unwrap().push(prompt.to_string());
+            Box::pin(async move { Ok(LLMResponse::default()) })
+        });
+        let plugin = Arc::new(RecordsPlugin {
+            seen_contributions: std::sync::Mutex::new(0),
+        });
+        let provider = StandardProvider::new(llm, vec![plugin.clone()]);
+
+        let mut request = create_valid_request();
+        request.state.prompt_preamble = Some("You are a travel planner.".to_string());
+        provider
+            .execute(&ProviderContext::default(), &request)
+            .await
+            .unwrap();
+
+        let prompt = prompts.lock().unwrap()[0].clone();
+        let seen = *plugin.seen_contributions.lock().unwrap();
+        (prompt, seen)
+    }
+
+    #[tokio::test]
+    async fn test_execute_renders_records_for_text_only_llm() {
+        let (prompt, seen) =
+            execute_with_records(Capabilities::from(CapabilityType::Generate)).await;
+
+        assert_eq!(
+            prompt,
+            "You are a travel planner.\n\nPrevious Context:\n- content: Tom likes trains\n\n\n"
+        );
+        // プラグインは型付きの寄与（前置き + レコード）を受け取る
+        assert_eq!(seen, 2);
+    }
+
+    #[tokio::test]
+    async fn test_execute_renders_records_as_json_for_json_mode_llm() {
+        let (prompt, seen) = execute_with_records(Capabilities::from(vec![
+            CapabilityType::Generate,
+            CapabilityType::JsonMode,
+        ]))
+        .await;
+
+        assert_eq!(
+            prompt,
+            "You are a travel planner.\n\nPrevious Context:\n```json\n[\n  {\n    \"content\": \"Tom likes trains\"\n  }\n]\n```\n\n\n"
+        );
+        assert_eq!(seen, 2);
+    }
 }
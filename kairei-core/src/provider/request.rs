@@ -1,12 +1,18 @@
 use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
 
 use crate::{
-    Policy, config::ProviderConfig, context::AgentInfo, expression::Value, timestamp::Timestamp,
+    Policy,
+    config::ProviderConfig,
+    context::{AgentInfo, RequestContext},
+    eval::profile::Profiler,
+    expression::Value,
+    timestamp::Timestamp,
 };
 
-use super::{llm::LLMResponse, provider::ProviderSecret};
+use super::{contribution::ContextContribution, llm::LLMResponse, provider::ProviderSecret};
 
 #[derive(Debug, Default, Clone, Deserialize, Serialize)]
 pub struct ProviderRequest {
@@ -16,6 +22,9 @@ pub struct ProviderRequest {
     pub state: ExecutionState,
     // 3. プロバイダー設定(settings from with block)
     pub config: RuntimeConfig,
+    // 4. プラグインの寄与（プロンプト順）。StandardProvider が組み立て時に設定する
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub contributions: Vec<ContextContribution>,
 }
 
 type RuntimeConfig = ProviderConfig;
@@ -26,6 +35,12 @@ pub struct ProviderContext {
     pub config: ProviderConfig,
     #[serde(skip)]
     pub secret: ProviderSecret,
+    // プロファイルを求めたリクエストの think のみ設定される
+    #[serde(skip)]
+    pub profiler: Option<Profiler>,
+    // stream: true の think のみ設定される。生成された出力の断片を送る
+    #[serde(skip)]
+    pub chunks: Option<mpsc::UnboundedSender<String>>,
 }
 
 // 1. 基本的な入力データ
@@ -53,6 +68,16 @@ pub struct ExecutionState {
 
     // 実行トレース（デバッグ用）
     pub trace_id: String,
+
+    // 処理中のリクエストの ID。リクエストハンドラの think のみ設定される
+    #[serde(default)]
+    pub request_id: Option<String>,
+
+    // エンドユーザーのコンテキスト（ユーザーID、ロケール）
+    pub request_context: Option<RequestContext>,
+
+    // プロンプト前置き（persona）。state を補間済み
+    pub prompt_preamble: Option<String>,
 }
 
 #[derive(Debug, Default)]
@@ -67,6 +92,7 @@ impl From<LLMResponse> for ProviderResponse {
             output: response.content,
             metadata: ResponseMetadata {
                 timestamp: response.metadata.created_at,
+                token_usage: response.metadata.token_usage,
             },
         }
     }
@@ -75,4 +101,6 @@ impl From<LLMResponse> for ProviderResponse {
 #[derive(Debug, Clone, Default)]
 pub struct ResponseMetadata {
     pub timestamp: Timestamp,
+    // (prompt, completion) のトークン数。LLM が報告した場合のみ
+    pub token_usage: Option<(usize, usize)>,
 }
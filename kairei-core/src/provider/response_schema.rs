@@ -0,0 +1,339 @@
+//! # Response Schema Validation
+//!
+//! A provider config can carry a JSON Schema in
+//! [`CommonConfig::response_schema`](crate::config::CommonConfig::response_schema).
+//! After the LLM answers, the response is parsed as JSON (a surrounding markdown
+//! code fence is ignored) and checked against the schema. When it does not conform,
+//! the provider sends a repair prompt listing each violation and asks the model for
+//! a corrected answer, up to
+//! [`CommonConfig::max_repair_attempts`](crate::config::CommonConfig::max_repair_attempts)
+//! times. This is separate from the retry policy, which only repeats failed calls.
+//!
+//! The supported keywords are a subset of JSON Schema:
+//!
+//! | keyword                                | applies to        |
+//! |----------------------------------------|-------------------|
+//! | `type` (a name or a list of names)     | any value         |
+//! | `enum`, `const`                        | any value         |
+//! | `properties`, `required`, `additionalProperties` | objects |
+//! | `items`, `minItems`, `maxItems`        | arrays            |
+//! | `minLength`, `maxLength`, `pattern`    | strings           |
+//! | `minimum`, `maximum`                   | numbers           |
+//!
+//! Other keywords are ignored, so a schema using them validates less than it says
+//! but never rejects a conforming response.
+
+use std::fmt;
+
+use regex::Regex;
+use serde_json::{Map, Value};
+
+use crate::eval::postprocess::strip_fences;
+
+/// One way a response fails its schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+    /// JSON Pointer of the offending value, `""` for the whole response
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "/"
+        } else {
+            &self.path
+        };
+        write!(f, "{}: {}", path, self.message)
+    }
+}
+
+/// Parse `content` as JSON and validate it. A response that is not JSON at all is
+/// reported as a single violation at the root.
+pub fn check_response(schema: &Value, content: &str) -> Result<Value, Vec<SchemaViolation>> {
+    let value: Value = serde_json::from_str(strip_fences(content)).map_err(|e| {
+        vec![SchemaViolation {
+            path: String::new(),
+            message: format!("response is not valid JSON ({})", e),
+        }]
+    })?;
+    let violations = validate(schema, &value);
+    if violations.is_empty() {
+        Ok(value)
+    } else {
+        Err(violations)
+    }
+}
+
+/// Every violation of `schema` by `value`
+pub fn validate(schema: &Value, value: &Value) -> Vec<SchemaViolation> {
+    let mut violations = Vec::new();
+    validate_at(schema, value, "", &mut violations);
+    violations
+}
+
+/// Prompt asking the model to correct `violations` in its previous answer
+pub fn repair_prompt(violations: &[SchemaViolation]) -> String {
+    let listed: Vec<String> = violations.iter().map(|v| format!("- {}", v)).collect();
+    format!(
+        "Your previous response does not match the required JSON schema:\n{}\n\
+         Respond again with the corrected JSON only, keeping every other part of the answer.",
+        listed.join("\n")
+    )
+}
+
+fn validate_at(schema: &Value, value: &Value, path: &str, violations: &mut Vec<SchemaViolation>) {
+    // true / false も JSON Schema として有効
+    let schema = match schema {
+        Value::Bool(true) => return,
+        Value::Bool(false) => {
+            violations.push(violation(path, "no value is allowed here".to_string()));
+            return;
+        }
+        Value::Object(schema) => schema,
+        _ => return,
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let names: Vec<&str> = match expected {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => vec![],
+        };
+        if !names.is_empty() && !names.iter().any(|name| has_type(value, name)) {
+            violations.push(violation(
+                path,
+                format!(
+                    "expected {}, found {}",
+                    names.join(" or "),
+                    type_name(value)
+                ),
+            ));
+            // 型が違えば他のキーワードの検査は意味がない
+            return;
+        }
+    }
+    let allowed = schema.get("enum").filter(|allowed| {
+        allowed
+            .as_array()
+            .is_some_and(|allowed| !allowed.contains(value))
+    });
+    if let Some(allowed) = allowed {
+        violations.push(violation(
+            path,
+            format!("{} is not one of {}", value, allowed),
+        ));
+    }
+    if let Some(expected) = schema.get("const").filter(|expected| *expected != value) {
+        violations.push(violation(path, format!("expected {}", expected)));
+    }
+
+    match value {
+        Value::Object(fields) => validate_object(schema, fields, path, violations),
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    validate_at(item_schema, item, &format!("{}/{}", path, i), violations);
+                }
+            }
+            check_bounds(
+                schema,
+                "minItems",
+                "maxItems",
+                items.len(),
+                "items",
+                path,
+                violations,
+            );
+        }
+        Value::String(s) => {
+            check_bounds(
+                schema,
+                "minLength",
+                "maxLength",
+                s.chars().count(),
+                "characters",
+                path,
+                violations,
+            );
+            // 不正なパターンはスキーマ側の誤りなので無視する
+            let mismatch = schema
+                .get("pattern")
+                .and_then(Value::as_str)
+                .filter(|pattern| Regex::new(pattern).is_ok_and(|regex| !regex.is_match(s)));
+            if let Some(pattern) = mismatch {
+                violations.push(violation(
+                    path,
+                    format!("\"{}\" does not match /{}/", s, pattern),
+                ));
+            }
+        }
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or_default();
+            let minimum = schema.get("minimum").and_then(Value::as_f64);
+            if let Some(minimum) = minimum.filter(|minimum| n < *minimum) {
+                violations.push(violation(path, format!("{} is less than {}", n, minimum)));
+            }
+            let maximum = schema.get("maximum").and_then(Value::as_f64);
+            if let Some(maximum) = maximum.filter(|maximum| n > *maximum) {
+                violations.push(violation(
+                    path,
+                    format!("{} is greater than {}", n, maximum),
+                ));
+            }
+        }
+        _ => {}
+    }
+}
+
+fn validate_object(
+    schema: &Map<String, Value>,
+    fields: &Map<String, Value>,
+    path: &str,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    let properties = schema.get("properties").and_then(Value::as_object);
+    if let Some(Value::Array(required)) = schema.get("required") {
+        for name in required.iter().filter_map(Value::as_str) {
+            if !fields.contains_key(name) {
+                violations.push(violation(
+                    path,
+                    format!("missing required property \"{}\"", name),
+                ));
+            }
+        }
+    }
+    for (name, field) in fields {
+        let field_path = format!("{}/{}", path, escape_pointer(name));
+        match properties.and_then(|properties| properties.get(name)) {
+            Some(field_schema) => validate_at(field_schema, field, &field_path, violations),
+            None => match schema.get("additionalProperties") {
+                Some(Value::Bool(false)) => {
+                    violations.push(violation(path, format!("unexpected property \"{}\"", name)))
+                }
+                Some(additional) => validate_at(additional, field, &field_path, violations),
+                None => {}
+            },
+        }
+    }
+}
+
+fn check_bounds(
+    schema: &Map<String, Value>,
+    min_keyword: &str,
+    max_keyword: &str,
+    len: usize,
+    unit: &str,
+    path: &str,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    let len_u64 = len as u64;
+    let min = schema.get(min_keyword).and_then(Value::as_u64);
+    if let Some(min) = min.filter(|min| len_u64 < *min) {
+        violations.push(violation(
+            path,
+            format!("has {} {}, fewer than {}", len, unit, min),
+        ));
+    }
+    let max = schema.get(max_keyword).and_then(Value::as_u64);
+    if let Some(max) = max.filter(|max| len_u64 > *max) {
+        violations.push(violation(
+            path,
+            format!("has {} {}, more than {}", len, unit, max),
+        ));
+    }
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "integer" => value.as_i64().is_some() || value.as_u64().is_some(),
+        "number" => value.is_number(),
+        other => type_name(value) == other,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// JSON Pointer のエスケープ (RFC 6901)
+fn escape_pointer(name: &str) -> String {
+    name.replace('~', "~0").replace('/', "~1")
+}
+
+fn violation(path: &str, message: String) -> SchemaViolation {
+    SchemaViolation {
+        path: path.to_string(),
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn person_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "string", "minLength": 1},
+                "age": {"type": "integer", "minimum": 0},
+                "tags": {"type": "array", "items": {"enum": ["a", "b"]}, "maxItems": 2}
+            },
+            "required": ["name", "age"],
+            "additionalProperties": false
+        })
+    }
+
+    #[test]
+    fn test_conforming_response_in_a_code_fence() {
+        let content = "```json\n{\"name\": \"Ann\", \"age\": 30, \"tags\": [\"a\"]}\n```";
+        assert_eq!(
+            check_response(&person_schema(), content),
+            Ok(json!({"name": "Ann", "age": 30, "tags": ["a"]}))
+        );
+    }
+
+    #[test]
+    fn test_violations_name_the_path() {
+        let value = json!({"name": "", "age": 1.5, "tags": ["a", "c", "b"], "extra": true});
+        let violations: Vec<String> = validate(&person_schema(), &value)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            violations,
+            vec![
+                "/age: expected integer, found number",
+                "/: unexpected property \"extra\"",
+                "/name: has 0 characters, fewer than 1",
+                "/tags/1: \"c\" is not one of [\"a\",\"b\"]",
+                "/tags: has 3 items, more than 2",
+            ]
+        );
+
+        let violations = validate(&person_schema(), &json!({"name": "Ann"}));
+        assert_eq!(violations[0].message, "missing required property \"age\"");
+    }
+
+    #[test]
+    fn test_invalid_json_is_a_violation() {
+        let violations = check_response(&person_schema(), "I am not sure").unwrap_err();
+        assert_eq!(violations.len(), 1);
+        assert!(
+            violations[0]
+                .message
+                .starts_with("response is not valid JSON")
+        );
+        assert!(repair_prompt(&violations).contains("- /: response is not valid JSON"));
+    }
+}
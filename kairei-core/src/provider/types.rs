@@ -3,12 +3,13 @@ use mockall::automock;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use thiserror::Error;
+use utoipa::ToSchema;
 
 use crate::{config::ProviderConfig, timestamp::Timestamp};
 
 use super::{
     capabilities::common::CapabilityType, capabilities::shared_memory::SharedMemoryError,
-    provider::ProviderSecret,
+    plugins::openapi_tools::ToolError, provider::ProviderSecret,
 };
 
 /// LLMプロバイダーの基本トレイト
@@ -58,6 +59,70 @@ pub struct ProviderMetrix {
     pub last_health_check: Timestamp,
     pub error_count: u32,
     pub last_error: Option<String>,
+    /// Result of the last credential check, `None` until one ran
+    #[serde(default)]
+    pub credential_status: Option<CredentialStatus>,
+}
+
+/// プロバイダーの認証情報の検証結果
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "status", content = "reason", rename_all = "snake_case")]
+pub enum CredentialStatus {
+    /// The API accepted the credentials
+    Valid,
+    /// The API rejected the credentials
+    Invalid(String),
+    /// The API could not be reached to check them
+    Unreachable(String),
+    /// The provider cannot check its credentials
+    #[default]
+    Unknown,
+}
+
+impl CredentialStatus {
+    pub fn is_valid(&self) -> bool {
+        matches!(self, Self::Valid)
+    }
+
+    /// The status of a provider made of parts checked separately, e.g. its LLM and
+    /// plugins: the worst of the two, where `Unknown` only wins over nothing.
+    pub fn combine(self, other: Self) -> Self {
+        if other.rank() > self.rank() {
+            other
+        } else {
+            self
+        }
+    }
+
+    /// Name of the status without its reason
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Valid => "valid",
+            Self::Invalid(_) => "invalid",
+            Self::Unreachable(_) => "unreachable",
+            Self::Unknown => "unknown",
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            Self::Unknown => 0,
+            Self::Valid => 1,
+            Self::Unreachable(_) => 2,
+            Self::Invalid(_) => 3,
+        }
+    }
+}
+
+impl std::fmt::Display for CredentialStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Invalid(reason) | Self::Unreachable(reason) => {
+                write!(f, "{} ({})", self.as_str(), reason)
+            }
+            _ => write!(f, "{}", self.as_str()),
+        }
+    }
 }
 
 /// LLMプロバイダーのエラー
@@ -75,6 +140,9 @@ pub enum ProviderError {
     #[error("Rate limit exceeded: {0}")]
     RateLimit(String),
 
+    #[error("Provider overloaded: {0}")]
+    Overloaded(String),
+
     #[error("Invalid request: {0}")]
     InvalidRequest(String),
 
@@ -112,6 +180,9 @@ pub enum ProviderError {
     // Memory error
     #[error("Memory error: {0}")]
     SharedMemoryError(#[from] SharedMemoryError),
+
+    #[error("Tool error: {0}")]
+    ToolError(#[from] ToolError),
 }
 
 pub type ProviderResult<T> = Result<T, ProviderError>;
@@ -0,0 +1,143 @@
+//! What reloading an agent with a new definition would change.
+//!
+//! `System::reload_agent` keeps the values of the state variables the new
+//! definition still declares with the same type, and starts every other variable
+//! from its initial value. A [`ReloadPlan`], made by `System::preview_reload`
+//! without applying anything, lists which variables are kept and which are reset,
+//! and which handlers are added, removed or changed, so that a reload can be
+//! confirmed first.
+//!
+//! Handlers are named by their block and trigger, e.g. `observe Tick`,
+//! `answer GetCount` or `lifecycle onInit`. A handler is changed when anything
+//! in it differs, including its parameters and doc comment.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::ast::{EventHandler, HandlerBlock, MicroAgentDef, RequestHandler, TypeInfo};
+
+/// Changes of reloading an agent, see the [module docs](self). Names are sorted.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ReloadPlan {
+    pub agent_name: String,
+    /// State variables declared before and after with the same type, which keep
+    /// their values
+    pub preserved_state: Vec<String>,
+    /// State variables whose type changed or that were removed, whose values are lost
+    pub reset_state: Vec<String>,
+    /// State variables new in the definition, which start from their initial value
+    pub added_state: Vec<String>,
+    pub added_handlers: Vec<String>,
+    pub removed_handlers: Vec<String>,
+    pub changed_handlers: Vec<String>,
+}
+
+impl ReloadPlan {
+    /// The plan of replacing `previous` with `next`. Without a previous definition
+    /// the agent is new and everything is added.
+    pub fn new(previous: Option<&MicroAgentDef>, next: &MicroAgentDef) -> Self {
+        let mut plan = Self {
+            agent_name: next.name.clone(),
+            ..Default::default()
+        };
+
+        let before = previous.map(state_types).unwrap_or_default();
+        let after = state_types(next);
+        for (name, type_info) in &before {
+            match after.get(name) {
+                Some(next_type) if next_type == type_info => {
+                    plan.preserved_state.push(name.clone())
+                }
+                _ => plan.reset_state.push(name.clone()),
+            }
+        }
+        plan.added_state = after
+            .keys()
+            .filter(|name| !before.contains_key(*name))
+            .cloned()
+            .collect();
+
+        let before = previous.map(handlers).unwrap_or_default();
+        let after = handlers(next);
+        for (name, handler) in &before {
+            match after.get(name) {
+                Some(next_handler) if next_handler == handler => {}
+                Some(_) => plan.changed_handlers.push(name.clone()),
+                None => plan.removed_handlers.push(name.clone()),
+            }
+        }
+        plan.added_handlers = after
+            .keys()
+            .filter(|name| !before.contains_key(*name))
+            .cloned()
+            .collect();
+        plan
+    }
+
+    /// Whether the reload keeps every state value and every handler as it is
+    pub fn is_unchanged(&self) -> bool {
+        self.reset_state.is_empty()
+            && self.added_state.is_empty()
+            && self.added_handlers.is_empty()
+            && self.removed_handlers.is_empty()
+            && self.changed_handlers.is_empty()
+    }
+}
+
+/// The declared type of `name` in the state of `agent_def`
+pub(crate) fn state_type<'a>(agent_def: &'a MicroAgentDef, name: &str) -> Option<&'a TypeInfo> {
+    agent_def
+        .state
+        .as_ref()
+        .and_then(|state| state.variables.get(name))
+        .map(|variable| &variable.type_info)
+}
+
+fn state_types(agent_def: &MicroAgentDef) -> BTreeMap<String, &TypeInfo> {
+    agent_def
+        .state
+        .iter()
+        .flat_map(|state| &state.variables)
+        .map(|(name, variable)| (name.clone(), &variable.type_info))
+        .collect()
+}
+
+#[derive(PartialEq)]
+enum AgentHandler<'a> {
+    Event(&'a EventHandler),
+    Request(&'a RequestHandler),
+    Lifecycle(&'a HandlerBlock),
+}
+
+fn handlers(agent_def: &MicroAgentDef) -> BTreeMap<String, AgentHandler<'_>> {
+    let mut handlers = BTreeMap::new();
+    for handler in agent_def.observe.iter().flat_map(|def| &def.handlers) {
+        let name = format!("observe {}", handler.event_type);
+        handlers.insert(name, AgentHandler::Event(handler));
+    }
+    for handler in agent_def.react.iter().flat_map(|def| &def.handlers) {
+        let name = format!("react {}", handler.event_type);
+        handlers.insert(name, AgentHandler::Event(handler));
+    }
+    for handler in agent_def.answer.iter().flat_map(|def| &def.handlers) {
+        let name = format!("answer {}", handler.request_type);
+        handlers.insert(name, AgentHandler::Request(handler));
+    }
+    if let Some(lifecycle) = &agent_def.lifecycle {
+        let blocks = [
+            ("onInit", &lifecycle.on_init),
+            ("onDestroy", &lifecycle.on_destroy),
+        ];
+        for (event, block) in blocks {
+            if let Some(block) = block {
+                handlers.insert(
+                    format!("lifecycle {}", event),
+                    AgentHandler::Lifecycle(block),
+                );
+            }
+        }
+    }
+    handlers
+}
@@ -0,0 +1,519 @@
+//! # Request Queue Persistence
+//!
+//! A request waits in the event bus or in the [`RequestScheduler`](crate::scheduler::RequestScheduler)
+//! until its handler runs, and is lost if the process dies in between. For the agents
+//! listed in [`RequestQueueConfig`], a [`RequestQueue`] records each request in a
+//! [`StorageBackend`] namespace when the agent receives it, and removes it once the
+//! handler has finished. The handler only starts after the record is written.
+//!
+//! Writes are group commits: a write stores every request recorded so far, so requests
+//! arriving while a write is in flight share the next one instead of paying for a write
+//! each. With [`QueueDurability::Batched`] a write also waits `batch_window` first to
+//! collect a burst.
+//!
+//! Entries that survive a restart are returned by [`RequestQueue::take_recovered`] in
+//! their original order; the agent runs them before any new request. Entries with the
+//! same request ID are recovered once. A recovered request to a `@cache` handler is
+//! answered from the response cache when its response was kept, see
+//! [`ResponseCacheConfig::memory`](crate::config::ResponseCacheConfig::memory).
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use utoipa::ToSchema;
+
+use crate::{
+    config::{QueueDurability, RequestQueueConfig},
+    eval::expression,
+    event_bus::{Event, Value},
+    event_registry::EventType,
+    persistence::{self, Versioned},
+    provider::capabilities::{
+        shared_memory::Metadata,
+        storage::{StorageBackend, StorageError, ValueWithMetadata},
+    },
+};
+
+/// Queue counters reported with the agent status
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RequestQueueStats {
+    /// Requests recorded and not yet handled
+    pub depth: usize,
+    /// Requests recovered from storage when the queue was opened
+    pub reenqueued: usize,
+}
+
+/// Persisted form of a request event. Embeds [`expression::Value`]s, so a new
+/// version of their representation is a new version of this one too.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct StoredRequest {
+    pub(crate) request_type: String,
+    pub(crate) requester: String,
+    pub(crate) responder: String,
+    pub(crate) request_id: String,
+    pub(crate) parameters: HashMap<String, expression::Value>,
+    pub(crate) event_id: Option<String>,
+    pub(crate) parent_event_id: Option<String>,
+    pub(crate) root_event_id: Option<String>,
+}
+
+impl Versioned for StoredRequest {
+    const KIND: &'static str = "queued request";
+    const VERSION: u32 = 1;
+}
+
+impl StoredRequest {
+    fn from_event(event: &Event) -> Option<Self> {
+        let EventType::Request {
+            request_type,
+            requester,
+            responder,
+            request_id,
+        } = &event.event_type
+        else {
+            return None;
+        };
+        Some(Self {
+            request_type: request_type.clone(),
+            requester: requester.clone(),
+            responder: responder.clone(),
+            request_id: request_id.clone(),
+            parameters: event
+                .parameters
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone().into()))
+                .collect(),
+            event_id: event.event_id.clone(),
+            parent_event_id: event.parent_event_id.clone(),
+            root_event_id: event.root_event_id.clone(),
+        })
+    }
+
+    fn into_event(self) -> Event {
+        Event {
+            event_type: EventType::Request {
+                request_type: self.request_type,
+                requester: self.requester,
+                responder: self.responder,
+                request_id: self.request_id,
+            },
+            parameters: self
+                .parameters
+                .into_iter()
+                .map(|(k, v)| (k, Value::from(v)))
+                .collect(),
+            event_id: self.event_id,
+            parent_event_id: self.parent_event_id,
+            root_event_id: self.root_event_id,
+        }
+    }
+}
+
+/// Pending requests of one agent, mirrored in a storage namespace.
+/// Clones share the same queue.
+#[derive(Clone)]
+pub struct RequestQueue {
+    inner: Arc<QueueInner>,
+}
+
+struct QueueInner {
+    agent_name: String,
+    namespace: String,
+    backend: Arc<dyn StorageBackend>,
+    durability: QueueDurability,
+    batch_window: Duration,
+    state: Mutex<QueueState>,
+    /// Generation covered by the last successful write; held while writing
+    persisted: tokio::sync::Mutex<u64>,
+    reenqueued: usize,
+}
+
+#[derive(Default)]
+struct QueueState {
+    entries: HashMap<String, ValueWithMetadata>,
+    /// Bumped on every change, so a write knows which changes it covers
+    generation: u64,
+    next_sequence: u64,
+    /// Keys and events loaded on open, until taken
+    recovered: Vec<(String, Event)>,
+}
+
+/// Handle of a recorded request, held until its handler has finished
+pub struct QueueTicket {
+    queue: RequestQueue,
+    key: String,
+    generation: u64,
+}
+
+impl RequestQueue {
+    /// Open the queue of `agent_name`, loading the entries left by a previous run
+    pub async fn open(
+        agent_name: &str,
+        backend: Arc<dyn StorageBackend>,
+        config: &RequestQueueConfig,
+    ) -> Result<Self, StorageError> {
+        let namespace = format!("request_queue.{}", agent_name);
+        let mut stored: Vec<_> = backend.load(&namespace).await?.into_iter().collect();
+        let loaded = stored.len();
+        // キーは連番で始まるため、ソートすると投入順になる
+        stored.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut state = QueueState::default();
+        let mut request_ids = HashSet::new();
+        for (key, value) in stored {
+            let request: StoredRequest = match persistence::from_stored(value.value.clone()) {
+                Ok(request) => request,
+                Err(e) => {
+                    warn!(
+                        "Dropping unreadable queued request {} of agent {}: {}",
+                        key, agent_name, e
+                    );
+                    continue;
+                }
+            };
+            if !request_ids.insert(request.request_id.clone()) {
+                continue;
+            }
+            if let Some(sequence) = key.split('-').next().and_then(|s| s.parse::<u64>().ok()) {
+                state.next_sequence = state.next_sequence.max(sequence + 1);
+            }
+            state.recovered.push((key.clone(), request.into_event()));
+            state.entries.insert(key, value);
+        }
+        // 破損・重複したエントリを落とした場合は書き直す
+        let dirty = state.entries.len() != loaded;
+        if dirty {
+            state.generation = 1;
+        }
+
+        let queue = Self {
+            inner: Arc::new(QueueInner {
+                agent_name: agent_name.to_string(),
+                namespace,
+                backend,
+                durability: config.durability,
+                batch_window: config.batch_window,
+                reenqueued: state.recovered.len(),
+                state: Mutex::new(state),
+                persisted: tokio::sync::Mutex::new(0),
+            }),
+        };
+        if dirty {
+            queue.inner.flush(1).await?;
+        }
+        Ok(queue)
+    }
+
+    /// Requests recovered from storage, in their original order.
+    /// Returns them only once.
+    pub fn take_recovered(&self) -> Vec<(QueueTicket, Event)> {
+        let recovered = std::mem::take(&mut self.inner.state.lock().unwrap().recovered);
+        recovered
+            .into_iter()
+            .map(|(key, event)| (self.ticket(key, 0), event))
+            .collect()
+    }
+
+    /// Record a request event. The write happens in the background;
+    /// await [`QueueTicket::durable`] before running the handler.
+    pub fn enqueue(&self, event: &Event) -> Result<QueueTicket, StorageError> {
+        let request = StoredRequest::from_event(event).ok_or_else(|| {
+            StorageError::SerializationError("Only request events can be queued".to_string())
+        })?;
+        let value = persistence::to_stored(&request)?;
+
+        let (key, generation) = {
+            let mut state = self.inner.state.lock().unwrap();
+            let key = format!("{:020}-{}", state.next_sequence, request.request_id);
+            state.next_sequence += 1;
+            state.entries.insert(
+                key.clone(),
+                ValueWithMetadata {
+                    value,
+                    metadata: Metadata::default(),
+                    expiry: None,
+                },
+            );
+            state.generation += 1;
+            (key, state.generation)
+        };
+
+        // 書き込みは単独で完了するため BackgroundTasks には載せない
+        let inner = self.inner.clone();
+        tokio::spawn(async move {
+            if let Err(e) = inner.flush(generation).await {
+                warn!(
+                    "Failed to persist request queue of agent {}: {}",
+                    inner.agent_name, e
+                );
+            }
+        });
+        Ok(self.ticket(key, generation))
+    }
+
+    /// Write every recorded change
+    pub async fn sync(&self) -> Result<(), StorageError> {
+        let generation = self.inner.state.lock().unwrap().generation;
+        self.inner.flush(generation).await
+    }
+
+    pub fn stats(&self) -> RequestQueueStats {
+        RequestQueueStats {
+            depth: self.inner.state.lock().unwrap().entries.len(),
+            reenqueued: self.inner.reenqueued,
+        }
+    }
+
+    fn ticket(&self, key: String, generation: u64) -> QueueTicket {
+        QueueTicket {
+            queue: self.clone(),
+            key,
+            generation,
+        }
+    }
+}
+
+impl QueueTicket {
+    /// Wait until the request is in storage
+    pub async fn durable(&self) -> Result<(), StorageError> {
+        self.queue.inner.flush(self.generation).await
+    }
+
+    /// Remove the request once its handler has finished
+    pub async fn complete(self) {
+        let generation = {
+            let mut state = self.queue.inner.state.lock().unwrap();
+            if state.entries.remove(&self.key).is_none() {
+                return;
+            }
+            state.generation += 1;
+            state.generation
+        };
+        if let Err(e) = self.queue.inner.flush(generation).await {
+            warn!(
+                "Failed to remove request {} from the queue of agent {}: {}",
+                self.key, self.queue.inner.agent_name, e
+            );
+        }
+    }
+}
+
+impl QueueInner {
+    /// Write the queue unless a write covering `generation` already happened
+    async fn flush(&self, generation: u64) -> Result<(), StorageError> {
+        let mut persisted = self.persisted.lock().await;
+        if *persisted >= generation {
+            return Ok(());
+        }
+        if self.durability == QueueDurability::Batched {
+            tokio::time::sleep(self.batch_window).await;
+        }
+        let (entries, current) = {
+            let state = self.state.lock().unwrap();
+            (state.entries.clone(), state.generation)
+        };
+        self.backend.save(&self.namespace, &entries).await?;
+        *persisted = current;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use async_trait::async_trait;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::provider::{
+        config::plugins::LocalFileSystemConfig, plugins::storage::local_fs::LocalFileSystemBackend,
+    };
+
+    /// Local file backend counting whole-namespace writes
+    struct CountingBackend {
+        inner: LocalFileSystemBackend,
+        saves: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl StorageBackend for CountingBackend {
+        fn clone_backend(&self) -> Box<dyn StorageBackend> {
+            Box::new(Self {
+                inner: self.inner.clone(),
+                saves: self.saves.clone(),
+            })
+        }
+
+        async fn load(
+            &self,
+            namespace: &str,
+        ) -> Result<HashMap<String, ValueWithMetadata>, StorageError> {
+            self.inner.load(namespace).await
+        }
+
+        async fn save(
+            &self,
+            namespace: &str,
+            data: &HashMap<String, ValueWithMetadata>,
+        ) -> Result<(), StorageError> {
+            self.saves.fetch_add(1, Ordering::SeqCst);
+            self.inner.save(namespace, data).await
+        }
+
+        async fn save_key(
+            &self,
+            namespace: &str,
+            key: &str,
+            value: &ValueWithMetadata,
+        ) -> Result<(), StorageError> {
+            self.inner.save_key(namespace, key, value).await
+        }
+
+        async fn delete_key(&self, namespace: &str, key: &str) -> Result<(), StorageError> {
+            self.inner.delete_key(namespace, key).await
+        }
+
+        async fn is_available(&self) -> bool {
+            true
+        }
+    }
+
+    fn backend(dir: &TempDir) -> (Arc<dyn StorageBackend>, Arc<AtomicUsize>) {
+        let saves = Arc::new(AtomicUsize::new(0));
+        let backend = CountingBackend {
+            inner: LocalFileSystemBackend::new(LocalFileSystemConfig {
+                base_dir: dir.path().to_string_lossy().to_string(),
+                file_extension: "json".to_string(),
+            }),
+            saves: saves.clone(),
+        };
+        (Arc::new(backend), saves)
+    }
+
+    fn request(request_id: &str) -> Event {
+        Event {
+            event_type: EventType::Request {
+                request_type: "work".to_string(),
+                requester: "tester".to_string(),
+                responder: "worker".to_string(),
+                request_id: request_id.to_string(),
+            },
+            parameters: HashMap::from([("n".to_string(), Value::Integer(1))]),
+            ..Default::default()
+        }
+    }
+
+    fn request_id(event: &Event) -> String {
+        event.event_type.request_id().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_recovers_pending_requests_in_order() {
+        let dir = TempDir::new().unwrap();
+        let config = RequestQueueConfig::default();
+        let (backend, _) = backend(&dir);
+
+        let queue = RequestQueue::open("worker", backend.clone(), &config)
+            .await
+            .unwrap();
+        let mut tickets = vec![];
+        for id in ["r1", "r2", "r3"] {
+            let ticket = queue.enqueue(&request(id)).unwrap();
+            ticket.durable().await.unwrap();
+            tickets.push(ticket);
+        }
+        tickets.remove(1).complete().await;
+        assert_eq!(queue.stats().depth, 2);
+
+        let reopened = RequestQueue::open("worker", backend.clone(), &config)
+            .await
+            .unwrap();
+        let recovered = reopened.take_recovered();
+        let ids: Vec<_> = recovered.iter().map(|(_, e)| request_id(e)).collect();
+        assert_eq!(ids, vec!["r1", "r3"]);
+        assert_eq!(recovered[0].1.parameters.get("n"), Some(&Value::Integer(1)));
+        assert_eq!(
+            reopened.stats(),
+            RequestQueueStats {
+                depth: 2,
+                reenqueued: 2
+            }
+        );
+        assert!(reopened.take_recovered().is_empty());
+
+        // 新しいエントリは復元分の後ろに並ぶ
+        reopened
+            .enqueue(&request("r4"))
+            .unwrap()
+            .durable()
+            .await
+            .unwrap();
+        for (ticket, _) in recovered {
+            ticket.complete().await;
+        }
+        let recovered = RequestQueue::open("worker", backend, &config)
+            .await
+            .unwrap()
+            .take_recovered();
+        let ids: Vec<_> = recovered.iter().map(|(_, e)| request_id(e)).collect();
+        assert_eq!(ids, vec!["r4"]);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_request_ids_are_recovered_once() {
+        let dir = TempDir::new().unwrap();
+        let config = RequestQueueConfig::default();
+        let (backend, _) = backend(&dir);
+
+        let queue = RequestQueue::open("worker", backend.clone(), &config)
+            .await
+            .unwrap();
+        queue.enqueue(&request("same")).unwrap();
+        queue.enqueue(&request("same")).unwrap();
+        queue.sync().await.unwrap();
+
+        let reopened = RequestQueue::open("worker", backend, &config)
+            .await
+            .unwrap();
+        assert_eq!(reopened.take_recovered().len(), 1);
+        assert_eq!(reopened.stats().depth, 1);
+    }
+
+    #[tokio::test]
+    async fn test_batched_durability_shares_writes() {
+        let dir = TempDir::new().unwrap();
+        let config = RequestQueueConfig {
+            durability: QueueDurability::Batched,
+            batch_window: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let (backend, saves) = backend(&dir);
+
+        let queue = RequestQueue::open("worker", backend, &config)
+            .await
+            .unwrap();
+        let tickets: Vec<_> = (0..10)
+            .map(|i| queue.enqueue(&request(&format!("r{}", i))).unwrap())
+            .collect();
+        for ticket in &tickets {
+            ticket.durable().await.unwrap();
+        }
+        assert_eq!(saves.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_only_request_events_are_queued() {
+        let dir = TempDir::new().unwrap();
+        let (backend, _) = backend(&dir);
+        let queue = RequestQueue::open("worker", backend, &RequestQueueConfig::default())
+            .await
+            .unwrap();
+        assert!(queue.enqueue(&Event::default()).is_err());
+    }
+}
@@ -0,0 +1,434 @@
+//! Response cache of the answer handlers declared with `@cache(ttl: ..., key: [...])`.
+//!
+//! A successful response is stored in a [`SharedMemoryCapability`] under the
+//! agent's prefix together with the time it expires, read from a [`Clock`] so
+//! that tests can expire it with a `MockClock`. The key is the handler name and
+//! the values of its key parameters, so requests that only differ in other
+//! parameters share an entry.
+//!
+//! Each agent keeps at most `max_entries` responses and evicts the one used least
+//! recently when a new one does not fit. A request with the parameter
+//! `no_cache: true` neither reads nor stores an entry.
+//!
+//! With a persistent memory the responses outlive a restart. The ones kept by an
+//! earlier run count towards the limit once they are used again.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use utoipa::ToSchema;
+
+use crate::clock::{Clock, SystemClock};
+use crate::config::ResponseCacheConfig;
+use crate::eval::expression;
+use crate::event_bus::{self, Event};
+use crate::provider::capabilities::shared_memory::SharedMemoryCapability;
+use crate::provider::config::plugins::SharedMemoryConfig;
+use crate::provider::plugins::memory::shared_memory::InMemorySharedMemoryPlugin;
+use crate::{CacheDirective, RequestHandler};
+
+const CACHE_PREFIX: &str = "response_cache:";
+
+/// Request parameter that bypasses the cache
+pub const NO_CACHE_PARAMETER: &str = "no_cache";
+
+/// Counters of an agent's response cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ResponseCacheStats {
+    /// Requests answered from the cache
+    pub hits: u64,
+    /// Requests to a cached handler that ran it
+    pub misses: u64,
+    /// Responses currently kept
+    pub entries: usize,
+    /// Responses dropped to make room for newer ones
+    pub evictions: u64,
+}
+
+/// The response cache of one agent, see the [module docs](self)
+pub struct ResponseCache {
+    prefix: String,
+    memory: Arc<dyn SharedMemoryCapability>,
+    clock: Arc<dyn Clock>,
+    max_entries: usize,
+    // 最近使ったキーほど後ろ
+    recency: Mutex<VecDeque<String>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl ResponseCache {
+    pub fn new(
+        agent_name: &str,
+        memory: Arc<dyn SharedMemoryCapability>,
+        clock: Arc<dyn Clock>,
+        config: &ResponseCacheConfig,
+    ) -> Self {
+        Self {
+            prefix: format!("{}{}:", CACHE_PREFIX, agent_name),
+            memory,
+            clock,
+            max_entries: config.max_entries_per_agent,
+            recency: Mutex::new(VecDeque::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    /// A memory for caches, without expiry or limit of its own
+    fn in_memory() -> Arc<dyn SharedMemoryCapability> {
+        Arc::new(InMemorySharedMemoryPlugin::new(SharedMemoryConfig {
+            max_keys: 0,
+            ttl: Duration::ZERO,
+            namespace: "response_cache".to_string(),
+            ..Default::default()
+        }))
+    }
+
+    /// The key of the response of `handler` to `event`, or `None` when the
+    /// handler is not cached or the request bypasses the cache
+    pub fn key(handler: &RequestHandler, event: &Event) -> Option<String> {
+        let directive = handler.cache.as_ref()?;
+        if bypasses_cache(&event.parameters) {
+            return None;
+        }
+        Some(Self::key_of(handler, directive, &event.parameters))
+    }
+
+    fn key_of(
+        handler: &RequestHandler,
+        directive: &CacheDirective,
+        parameters: &HashMap<String, event_bus::Value>,
+    ) -> String {
+        // 名前順に並べたオブジェクトなので、パラメータの順序によらず同じキーになる
+        let values: serde_json::Map<String, serde_json::Value> = directive
+            .key_parameters(handler)
+            .into_iter()
+            .map(|name| {
+                let value = parameters
+                    .get(name)
+                    .map(serde_json::Value::from)
+                    .unwrap_or_default();
+                (name.to_string(), value)
+            })
+            .collect();
+        format!(
+            "{}:{}",
+            handler.request_type,
+            serde_json::Value::Object(values)
+        )
+    }
+
+    /// The cached response for `key`, counting a hit or a miss
+    pub async fn get(&self, key: &str) -> Option<expression::Value> {
+        let response = self.read(key).await;
+        let counter = if response.is_some() {
+            let evicted = self.touch(key);
+            self.evict(evicted).await;
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        response
+    }
+
+    /// Keeps `response` for `ttl`, evicting the least recently used entries
+    /// beyond the limit
+    pub async fn put(&self, key: &str, response: &expression::Value, ttl: Duration) {
+        if self.max_entries == 0 {
+            return;
+        }
+        let expires_at = self.now_millis() + ttl.as_millis() as i64;
+        let entry = match serde_json::to_value(response) {
+            Ok(response) => json!({ "expires_at": expires_at, "response": response }),
+            Err(e) => {
+                tracing::warn!("Response for {} cannot be cached: {}", key, e);
+                return;
+            }
+        };
+        if let Err(e) = self.memory.set(&self.memory_key(key), entry).await {
+            tracing::warn!("Failed to cache the response for {}: {}", key, e);
+            return;
+        }
+        let evicted = self.touch(key);
+        self.evict(evicted).await;
+    }
+
+    /// Drops every response of the agent, e.g. when its definition changes
+    pub async fn clear(&self) {
+        self.recency.lock().unwrap().clear();
+        // 前回の実行で保存された応答も消す
+        let pattern = format!("{}*", glob::Pattern::escape(&self.prefix));
+        let keys = self.memory.list_keys(&pattern).await.unwrap_or_default();
+        for key in keys {
+            let _ = self.memory.delete(&key).await;
+        }
+    }
+
+    pub fn stats(&self) -> ResponseCacheStats {
+        ResponseCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.recency.lock().unwrap().len(),
+            evictions: self.evictions.load(Ordering::Relaxed),
+        }
+    }
+
+    async fn read(&self, key: &str) -> Option<expression::Value> {
+        let entry = self.memory.get(&self.memory_key(key)).await.ok()?;
+        let expires_at = entry
+            .get("expires_at")
+            .and_then(serde_json::Value::as_i64)?;
+        if self.now_millis() >= expires_at {
+            // 期限切れは消して、次の応答で置き換える
+            let _ = self.memory.delete(&self.memory_key(key)).await;
+            self.recency.lock().unwrap().retain(|k| k != key);
+            return None;
+        }
+        serde_json::from_value(entry.get("response")?.clone()).ok()
+    }
+
+    /// Marks `key` as used most recently and returns the keys beyond the limit
+    fn touch(&self, key: &str) -> Vec<String> {
+        let mut recency = self.recency.lock().unwrap();
+        recency.retain(|k| k != key);
+        recency.push_back(key.to_string());
+        let excess = recency.len().saturating_sub(self.max_entries);
+        recency.drain(..excess).collect()
+    }
+
+    async fn evict(&self, keys: Vec<String>) {
+        for key in keys {
+            self.evictions.fetch_add(1, Ordering::Relaxed);
+            let _ = self.memory.delete(&self.memory_key(&key)).await;
+        }
+    }
+
+    fn memory_key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+
+    fn now_millis(&self) -> i64 {
+        self.clock.utc_now().timestamp_millis()
+    }
+}
+
+impl Default for ResponseCache {
+    /// An unnamed cache in its own memory, on the system clock
+    fn default() -> Self {
+        Self::new(
+            "",
+            Self::in_memory(),
+            Arc::new(SystemClock),
+            &ResponseCacheConfig::default(),
+        )
+    }
+}
+
+/// The response caches of a system's agents, keyed by agent name. They share one
+/// memory, each under the prefix of its agent.
+pub struct ResponseCaches {
+    memory: Arc<dyn SharedMemoryCapability>,
+    clock: Arc<dyn Clock>,
+    config: ResponseCacheConfig,
+    caches: DashMap<String, Arc<ResponseCache>>,
+}
+
+impl ResponseCaches {
+    pub fn new(config: ResponseCacheConfig) -> Self {
+        Self {
+            memory: ResponseCache::in_memory(),
+            clock: Arc::new(SystemClock),
+            config,
+            caches: DashMap::new(),
+        }
+    }
+
+    /// Expires responses by `clock`, e.g. a `MockClock` in tests. Call before any
+    /// cache is created.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Keeps the responses in `memory` instead, e.g. a persistent one. Call before
+    /// any cache is created.
+    pub fn with_memory(mut self, memory: Arc<dyn SharedMemoryCapability>) -> Self {
+        self.memory = memory;
+        self
+    }
+
+    pub fn memory(&self) -> Arc<dyn SharedMemoryCapability> {
+        self.memory.clone()
+    }
+
+    pub fn config(&self) -> &ResponseCacheConfig {
+        &self.config
+    }
+
+    /// The cache of `agent_name`, created if it has none
+    pub fn get_or_create(&self, agent_name: &str) -> Arc<ResponseCache> {
+        self.caches
+            .entry(agent_name.to_string())
+            .or_insert_with(|| {
+                Arc::new(ResponseCache::new(
+                    agent_name,
+                    self.memory.clone(),
+                    self.clock.clone(),
+                    &self.config,
+                ))
+            })
+            .clone()
+    }
+
+    /// Drops the responses of `agent_name`, e.g. when the agent is reloaded
+    pub async fn invalidate(&self, agent_name: &str) {
+        let cache = self.caches.get(agent_name).map(|cache| cache.clone());
+        if let Some(cache) = cache {
+            cache.clear().await;
+        }
+    }
+}
+
+impl fmt::Debug for ResponseCaches {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResponseCaches")
+            .field("clock", &self.clock)
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Debug for ResponseCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResponseCache")
+            .field("prefix", &self.prefix)
+            .field("max_entries", &self.max_entries)
+            .field("stats", &self.stats())
+            .finish_non_exhaustive()
+    }
+}
+
+fn bypasses_cache(parameters: &HashMap<String, event_bus::Value>) -> bool {
+    matches!(
+        parameters.get(NO_CACHE_PARAMETER),
+        Some(event_bus::Value::Boolean(true))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::{HandlerBlock, Parameter, TypeInfo};
+
+    fn handler(key: Option<Vec<&str>>) -> RequestHandler {
+        RequestHandler {
+            request_type: "Forecast".into(),
+            parameters: ["city", "days"]
+                .into_iter()
+                .map(|name| Parameter {
+                    name: name.to_string(),
+                    type_info: TypeInfo::Simple("String".to_string()),
+                    constraint: None,
+                })
+                .collect(),
+            return_type: TypeInfo::Simple("String".to_string()),
+            requires: None,
+            constraints: None,
+            block: HandlerBlock { statements: vec![] },
+            doc: None,
+            cache: Some(CacheDirective {
+                ttl: Duration::from_secs(60),
+                key: key.map(|key| key.into_iter().map(String::from).collect()),
+            }),
+        }
+    }
+
+    fn request(parameters: &[(&str, event_bus::Value)]) -> Event {
+        Event {
+            parameters: parameters
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn cache(clock: &MockClock, max_entries: usize) -> ResponseCache {
+        ResponseCache::new(
+            "Weather",
+            ResponseCache::in_memory(),
+            Arc::new(clock.clone()),
+            &ResponseCacheConfig {
+                max_entries_per_agent: max_entries,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_key_uses_key_parameters() {
+        let kyoto = request(&[("city", "Kyoto".into()), ("days", "3".into())]);
+        let kyoto_week = request(&[("city", "Kyoto".into()), ("days", "7".into())]);
+
+        let by_city = handler(Some(vec!["city"]));
+        assert_eq!(
+            ResponseCache::key(&by_city, &kyoto),
+            ResponseCache::key(&by_city, &kyoto_week)
+        );
+        let by_all = handler(None);
+        assert_ne!(
+            ResponseCache::key(&by_all, &kyoto),
+            ResponseCache::key(&by_all, &kyoto_week)
+        );
+
+        let mut no_cache = kyoto.clone();
+        no_cache.parameters.insert(
+            NO_CACHE_PARAMETER.to_string(),
+            event_bus::Value::Boolean(true),
+        );
+        assert_eq!(ResponseCache::key(&by_all, &no_cache), None);
+    }
+
+    #[tokio::test]
+    async fn test_entries_expire_and_evict_least_recently_used() {
+        let clock = MockClock::new();
+        let cache = cache(&clock, 2);
+        let ttl = Duration::from_secs(60);
+        let value = |s: &str| expression::Value::String(s.to_string());
+
+        cache.put("a", &value("A"), ttl).await;
+        cache.put("b", &value("B"), ttl).await;
+        assert_eq!(cache.get("a").await, Some(value("A")));
+        // b が最も古いので追い出される
+        cache.put("c", &value("C"), ttl).await;
+        assert_eq!(cache.get("b").await, None);
+        assert_eq!(cache.get("c").await, Some(value("C")));
+
+        clock.advance(ttl);
+        assert_eq!(cache.get("a").await, None);
+        assert_eq!(
+            cache.stats(),
+            ResponseCacheStats {
+                hits: 2,
+                misses: 2,
+                entries: 1,
+                evictions: 1,
+            }
+        );
+
+        cache.clear().await;
+        assert_eq!(cache.get("c").await, None);
+        assert_eq!(cache.stats().entries, 0);
+    }
+}
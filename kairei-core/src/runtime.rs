@@ -47,19 +47,41 @@
 //!
 //! See the test module for practical examples of Runtime usage.
 
+use crate::agent_log::AgentLogger;
 use crate::agent_registry::AgentError;
-use crate::config::AgentConfig;
-use crate::eval::context::{AgentInfo, AgentType, ExecutionContext, StateAccessMode};
+use crate::agent_settings::RuntimeSettings;
+use crate::catalog::AgentCatalog;
+use crate::config::{AgentConfig, OutputFormat, PreconditionMode};
+use crate::eval::budget::{LlmBudget, LlmBudgetStats};
+use crate::eval::context::{
+    AGENT_LOCALE_PARAMETER, AgentInfo, AgentType, ExecutionContext, StateAccessMode, WorldPreamble,
+};
 use crate::eval::evaluator::Evaluator;
 use crate::eval::expression;
-use crate::evaluator::EvalError;
+use crate::eval::postprocess::AnswerPipeline;
+use crate::eval::profile::Profiler;
+use crate::eval::recording::{self, HandlerKind, HandlerRecordings, Recorder};
+use crate::eval::secret::SecretVault;
+use crate::evaluator::{EvalError, EvalResult};
+use crate::event::dispatch::{DispatchIndex, HandlerRoute};
 use crate::event_bus::{
     self, ErrorEvent, Event, EventBus, EventCategory, EventError, LastStatus, Value,
 };
 use crate::event_registry::{EventType, LifecycleEvent};
+use crate::message_catalog::Locale;
+use crate::provider::capabilities::storage::StorageError;
+use crate::provider::plugins::memory::shared_counters::SharedCounters;
+use crate::provider::plugins::openapi_tools::ToolRegistry;
 use crate::provider::provider_registry::ProviderInstance;
 use crate::provider::types::ProviderError;
-use crate::{EventHandler, HandlerBlock, MicroAgentDef, Policy, RequestHandler};
+use crate::request_manager::Admission;
+use crate::request_queue::{QueueTicket, RequestQueue, RequestQueueStats};
+use crate::response_cache::{ResponseCache, ResponseCacheStats};
+use crate::scheduler::RequestScheduler;
+use crate::{
+    EventHandler, Expression, HandlerBlock, MicroAgentDef, Parameter, Policy, ReplayPolicy,
+    RequestHandler, RequestType, TypeInfo,
+};
 use async_trait::async_trait;
 use chrono::Utc;
 use dashmap::DashMap;
@@ -67,7 +89,7 @@ use futures::future::BoxFuture;
 use futures::{Stream, stream::SelectAll};
 use std::collections::HashMap;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use thiserror::Error;
 use tokio::sync::{RwLock, broadcast};
 use tokio_stream::{StreamExt, wrappers::BroadcastStream};
@@ -116,12 +138,53 @@ pub trait RuntimeAgent: Send + Sync {
     /// Returns the type of this agent (User, World, etc.)
     fn agent_type(&self) -> AgentType;
 
+    /// Returns the metadata labels attached to this agent
+    fn labels(&self) -> HashMap<String, String> {
+        HashMap::new()
+    }
+
     /// Returns the current status of this agent
     async fn status(&self) -> LastStatus;
 
+    /// Returns the counters of the persisted request queue, if this agent has one
+    fn request_queue_stats(&self) -> Option<RequestQueueStats> {
+        None
+    }
+
+    /// Returns the remaining LLM call budget, if this agent has one
+    fn llm_budget_stats(&self) -> Option<LlmBudgetStats> {
+        None
+    }
+
+    /// Returns the counters of the response cache, if this agent has `@cache` handlers
+    fn response_cache_stats(&self) -> Option<ResponseCacheStats> {
+        None
+    }
+
     /// Retrieves a state value by key
     async fn state(&self, key: &str) -> Option<expression::Value>;
 
+    /// Returns every state variable, for saving the agent while it is suspended
+    async fn state_snapshot(&self) -> HashMap<String, expression::Value> {
+        HashMap::new()
+    }
+
+    /// Replaces the values handlers read as `config.<name>`
+    async fn update_config(
+        &self,
+        _values: HashMap<String, expression::Value>,
+    ) -> RuntimeResult<()> {
+        Ok(())
+    }
+
+    /// Evaluates `block` once in the agent's context, like a lifecycle handler
+    async fn run_block(&self, _block: &HandlerBlock) -> RuntimeResult<()> {
+        Err(RuntimeError::EvaluationFailed(format!(
+            "Agent {} cannot run blocks",
+            self.name()
+        )))
+    }
+
     /// Runs the agent's main event processing loop
     ///
     /// Handles:
@@ -183,9 +246,13 @@ pub trait RuntimeAgent: Send + Sync {
 ///     &agent_def,
 ///     &event_bus,
 ///     AgentConfig::default(),
-///     primary_provider,
-///     providers,
+///     AgentResources {
+///         primary: primary_provider,
+///         providers,
+///         ..Default::default()
+///     },
 ///     policies,
+///     world_preamble,
 /// ).await?;
 ///
 /// // Start agent processing
@@ -210,12 +277,28 @@ pub struct RuntimeAgentData {
     pub base_context: Arc<ExecutionContext>,
     /// Event bus for asynchronous communication
     event_bus: Arc<EventBus>,
+    /// Events of the event journal, replayed once on the next start
+    replayed_events: std::sync::Mutex<Vec<Event>>,
     /// Channel for initiating individual agent shutdown
     private_shutdown_start_tx: broadcast::Sender<()>,
     /// Channel for signaling shutdown completion
     private_shutdown_end_tx: broadcast::Sender<()>,
     /// Current agent status
     last_status: RwLock<LastStatus>,
+    /// Shared worker pool for answer handlers; `None` runs them inline
+    scheduler: Option<RequestScheduler>,
+    /// Persisted queue of pending requests; `None` keeps them in memory only
+    request_queue: Option<RequestQueue>,
+    /// State saved when the agent was suspended, applied once on the next start
+    restored_state: std::sync::Mutex<Option<HashMap<String, expression::Value>>>,
+    /// What happens to a handler whose `requires` clause does not hold
+    precondition_mode: PreconditionMode,
+    /// Where observe and react handler runs are recorded, set when recording is enabled
+    recordings: Arc<OnceLock<Arc<HandlerRecordings>>>,
+    /// Index routing only this agent's events to it; `None` receives every event of the bus
+    dispatch: Option<Arc<DispatchIndex>>,
+    /// Responses of the `@cache` answer handlers
+    response_cache: Arc<ResponseCache>,
 }
 
 #[derive(Debug)]
@@ -234,15 +317,66 @@ impl RuntimeAgent for RuntimeAgentData {
     fn agent_type(&self) -> AgentType {
         self.base_context.agent_info().agent_type.clone()
     }
+    fn labels(&self) -> HashMap<String, String> {
+        self.ast.labels.clone()
+    }
     #[tracing::instrument(skip(self), level = "debug")]
     async fn status(&self) -> LastStatus {
         self.last_status.read().await.clone()
     }
 
+    fn request_queue_stats(&self) -> Option<RequestQueueStats> {
+        self.request_queue.as_ref().map(RequestQueue::stats)
+    }
+
+    fn llm_budget_stats(&self) -> Option<LlmBudgetStats> {
+        self.base_context.llm_budget_stats()
+    }
+
+    fn response_cache_stats(&self) -> Option<ResponseCacheStats> {
+        let cached = self
+            .ast
+            .answer
+            .iter()
+            .flat_map(|answer| &answer.handlers)
+            .any(|handler| handler.cache.is_some());
+        cached.then(|| self.response_cache.stats())
+    }
+
     #[tracing::instrument(skip(self), level = "debug")]
     async fn state(&self, key: &str) -> Option<expression::Value> {
         self.base_context.get_state(key).await.ok()
     }
+
+    async fn state_snapshot(&self) -> HashMap<String, expression::Value> {
+        self.base_context.state_snapshot().await
+    }
+
+    async fn update_config(&self, values: HashMap<String, expression::Value>) -> RuntimeResult<()> {
+        self.base_context
+            .update_agent_config(values)
+            .await
+            .map_err(EvalError::from)?;
+        Ok(())
+    }
+
+    async fn run_block(&self, block: &HandlerBlock) -> RuntimeResult<()> {
+        // ライフサイクルハンドラと違い、失敗は呼び出し側に返す
+        let context = Arc::new(
+            self.base_context
+                .fork(Some(StateAccessMode::ReadWrite))
+                .await
+                .with_new_execution()
+                .with_transaction(),
+        );
+        self.evaluator
+            .eval_block(block, context.clone())
+            .await
+            .map_err(|e| RuntimeError::EvaluationFailed(e.to_string()))?;
+        context.commit().await.map_err(EvalError::from)?;
+        Ok(())
+    }
+
     #[tracing::instrument(skip(self, shutdown_rx), level = "debug")]
     async fn run(&self, shutdown_rx: broadcast::Receiver<AgentType>) -> RuntimeResult<()> {
         self.update_last_status(EventType::AgentStarting).await?;
@@ -274,10 +408,48 @@ impl RuntimeAgent for RuntimeAgentData {
         }
 
         let (event_rx, error_rx) = self.event_bus.subscribe();
+        // 振り分け表があれば、ハンドラのあるイベントだけを受け取る。登録は停止時に外れる
+        let (event_rx, _dispatch_subscription) = match &self.dispatch {
+            Some(dispatch) => {
+                drop(event_rx);
+                let routes = HandlerRoute::from_agent(&self.name, &self.ast);
+                let (routed_rx, subscription) = dispatch.subscribe(&self.name, routes);
+                (routed_rx, Some(subscription))
+            }
+            None => (event_rx, None),
+        };
         let private_shutdown_rx = self.private_shutdown_start_tx.subscribe();
 
         self.handle_lifecycle_event(&LifecycleEvent::OnInit).await?;
 
+        // 退避前の状態は初期値と onInit の結果より優先する
+        let restored_state = self.restored_state.lock().unwrap().take();
+        for (name, value) in restored_state.into_iter().flatten() {
+            self.base_context.set_state(&name, value).map_err(|e| {
+                RuntimeError::EvaluationFailed(format!(
+                    "Failed to restore state variable {}: {}",
+                    name, e
+                ))
+            })?;
+        }
+
+        // 記録されたイベントを再生して状態を組み立て直す。失敗しても残りの再生は続ける
+        let replayed_events = std::mem::take(&mut *self.replayed_events.lock().unwrap());
+        for event in replayed_events {
+            if let Err(e) = self.handle_normal_event(&event).await {
+                tracing::error!("Replayed event failed in agent {}: {}", self.name, e);
+            }
+        }
+
+        // 前回の実行で残ったリクエストを、新しいリクエストより先に処理する
+        if let Some(queue) = &self.request_queue {
+            for (ticket, event) in queue.take_recovered() {
+                if let Err(e) = self.handle_request(&event, Some(ticket)).await {
+                    tracing::error!("Recovered request failed in agent {}: {}", self.name, e);
+                }
+            }
+        }
+
         // イベントストリームの変換
         let event_stream = BroadcastStream::new(event_rx.receiver).map(|e| {
             debug!("Event received");
@@ -369,6 +541,7 @@ impl RuntimeAgent for RuntimeAgentData {
                     params.insert("agent_id".to_string(), Value::String(self.name.clone()));
                     params
                 },
+                ..Default::default()
             })
             .await?;
         self.update_last_status(EventType::AgentStopped).await?;
@@ -430,14 +603,51 @@ impl RuntimeAgent for RuntimeAgentData {
     }
 }
 
+/// Runtime objects an agent is built with, handed out by the
+/// [`System`](crate::system::System) next to the agent's [`AgentConfig`]
+#[derive(Clone, Default)]
+pub struct AgentResources {
+    /// Provider of the agent's `think` calls
+    pub primary: Arc<ProviderInstance>,
+
+    /// Providers a `think` call can select by name instead of the primary one
+    pub providers: Arc<DashMap<String, Arc<ProviderInstance>>>,
+
+    /// Secrets granted to the agent, taken from the secret file
+    pub secrets: SecretVault,
+
+    /// Catalog of the system's agents, read by `list_agents()` and `agent_requests(name)`
+    pub catalog: Arc<AgentCatalog>,
+
+    /// Tools of the system's providers, called by `call_tool(name, args)`
+    pub tools: Arc<ToolRegistry>,
+
+    /// Resolved values of the agent's `config { ... }` parameters
+    pub config_values: HashMap<String, expression::Value>,
+
+    /// Writes the agent's `log` statements, shared with its scaled instances
+    pub logger: Arc<AgentLogger>,
+
+    /// Defaults of the agent's think calls tuned while it runs, shared with its
+    /// scaled instances
+    pub runtime_settings: Arc<RuntimeSettings>,
+
+    /// Counters and rate limiters of `increment(...)` and `try_acquire(...)`,
+    /// shared by the system's agents
+    pub shared_counters: Arc<SharedCounters>,
+
+    /// Responses of the agent's `@cache(...)` answer handlers
+    pub response_cache: Arc<ResponseCache>,
+}
+
 impl RuntimeAgentData {
     pub async fn new(
         agent_def: &MicroAgentDef,
         event_bus: &Arc<EventBus>,
         config: AgentConfig,
-        primary: Arc<ProviderInstance>,
-        providers: Arc<DashMap<String, Arc<ProviderInstance>>>,
+        resources: AgentResources,
         world_policies: Vec<Policy>,
+        world_preamble: WorldPreamble,
     ) -> RuntimeResult<Self> {
         let agent_name = agent_def.name.clone();
         let agent_info = AgentInfo {
@@ -448,18 +658,47 @@ impl RuntimeAgentData {
         };
 
         let evaluator = Arc::new(Evaluator::new());
+        let precondition_mode = config.preconditions;
+        let response_cache = resources.response_cache.clone();
         let mut policies = agent_def.policies.clone();
         policies.extend(world_policies.clone());
-
-        let base_context = Arc::new(ExecutionContext::new(
-            event_bus.clone(),
-            agent_info,
-            StateAccessMode::ReadWrite,
-            config.context,
-            primary.clone(),
-            providers.clone(),
-            policies,
-        ));
+        // config ブロックの locale が AgentConfig の既定値より優先される。対応していなければ英語
+        let locale = match resources.config_values.get(AGENT_LOCALE_PARAMETER) {
+            Some(expression::Value::String(tag)) => Locale::from_tag(tag),
+            _ => None,
+        }
+        .or_else(|| config.locale.as_deref().and_then(Locale::from_tag))
+        .unwrap_or_default();
+        let localized_preambles = agent_def
+            .localized_personas
+            .iter()
+            .filter_map(|(tag, persona)| Some((Locale::from_tag(tag)?, persona.clone())))
+            .collect();
+
+        let base_context = Arc::new(
+            ExecutionContext::new(
+                event_bus.clone(),
+                agent_info,
+                StateAccessMode::ReadWrite,
+                config.context,
+                resources.primary,
+                resources.providers,
+                policies,
+            )
+            .with_prompt_preamble(agent_def.persona.clone(), world_preamble)
+            .with_localized_preambles(localized_preambles)
+            .with_locale(locale)
+            .with_llm_budget(config.llm_budget.map(LlmBudget::new))
+            .with_guardrails(config.guardrails)
+            .with_eval_limits(config.eval_limits)
+            .with_secrets(resources.secrets)
+            .with_catalog(resources.catalog)
+            .with_tools(resources.tools)
+            .with_agent_logger(resources.logger)
+            .with_runtime_settings(resources.runtime_settings)
+            .with_shared_counters(resources.shared_counters)
+            .with_agent_config(resources.config_values),
+        );
 
         let last_status = RwLock::new(LastStatus {
             last_event_type: EventType::AgentCreated,
@@ -476,15 +715,66 @@ impl RuntimeAgentData {
             base_context,
             evaluator,
             event_bus: event_bus.clone(),
+            replayed_events: std::sync::Mutex::new(vec![]),
             private_shutdown_start_tx: broadcast::channel(1).0,
             private_shutdown_end_tx: broadcast::channel(1).0,
             last_status,
+            scheduler: None,
+            request_queue: None,
+            restored_state: std::sync::Mutex::new(None),
+            precondition_mode,
+            recordings: Arc::new(OnceLock::new()),
+            dispatch: None,
+            response_cache,
         };
 
         new_self.register_handlers_from_ast(agent_def)?;
         Ok(new_self)
     }
 
+    /// Run answer handlers on a shared scheduler instead of inline
+    pub fn with_scheduler(mut self, scheduler: Option<RequestScheduler>) -> Self {
+        self.scheduler = scheduler;
+        self
+    }
+
+    /// Replay the events of the event journal on start, before new events
+    pub fn with_replayed_events(self, events: Vec<Event>) -> Self {
+        *self.replayed_events.lock().unwrap() = events.into_iter().map(Event::replayed).collect();
+        self
+    }
+
+    /// Receive only the events routed to this agent by `dispatch` while it runs
+    pub fn with_dispatch_index(mut self, dispatch: Option<Arc<DispatchIndex>>) -> Self {
+        self.dispatch = dispatch;
+        self
+    }
+
+    /// Persist pending requests in `request_queue`, and run the ones left by a
+    /// previous run when the agent starts
+    pub fn with_request_queue(mut self, request_queue: Option<RequestQueue>) -> Self {
+        self.request_queue = request_queue;
+        self
+    }
+
+    /// Start with the state saved when the agent was suspended
+    pub fn with_restored_state(
+        self,
+        restored_state: Option<HashMap<String, expression::Value>>,
+    ) -> Self {
+        *self.restored_state.lock().unwrap() = restored_state;
+        self
+    }
+
+    /// Record the runs of the observe and react handlers in `recordings`
+    pub fn with_handler_recordings(self, recordings: Option<Arc<HandlerRecordings>>) -> Self {
+        if let Some(recordings) = recordings {
+            // ハンドラは new で登録済みのため、共有するスロットに設定する
+            let _ = self.recordings.set(recordings);
+        }
+        self
+    }
+
     pub fn register_handlers_from_ast(&mut self, agent_def: &MicroAgentDef) -> RuntimeResult<()> {
         if let Some(observe_def) = &agent_def.observe {
             for handler in observe_def.handlers.iter() {
@@ -492,6 +782,8 @@ impl RuntimeAgentData {
                     self.evaluator.clone(),
                     Arc::new(handler.clone()),
                     self.base_context.clone(),
+                    self.precondition_mode,
+                    self.recordings.clone(),
                 );
                 self.register_observe(&handler.event_type.to_string(), created);
             }
@@ -499,10 +791,15 @@ impl RuntimeAgentData {
         if let Some(answer_def) = &agent_def.answer {
             debug!("Register answer handlers");
             for handler in answer_def.handlers.iter() {
+                let pipeline = AnswerPipeline::from_constraints(handler.constraints.as_ref())
+                    .map_err(EvalError::from)?;
                 let created = Self::create_answer_handler(
                     self.evaluator.clone(),
                     Arc::new(handler.clone()),
                     self.base_context.clone(),
+                    Arc::new(pipeline),
+                    self.precondition_mode,
+                    self.response_cache.clone(),
                 );
                 debug!("Register answer handler: {}", &handler.request_type);
                 self.register_answer(&handler.request_type.to_string(), created);
@@ -514,6 +811,8 @@ impl RuntimeAgentData {
                     self.evaluator.clone(),
                     Arc::new(handler.clone()),
                     self.base_context.clone(),
+                    self.precondition_mode,
+                    self.recordings.clone(),
                 );
                 self.register_react(&handler.event_type.to_string(), created);
             }
@@ -539,46 +838,197 @@ impl RuntimeAgentData {
         Ok(())
     }
 
+    /// Bind event parameters to handler variables.
+    /// A missing optional (`T?`) parameter is bound to null; a missing required one is an error.
+    pub(crate) async fn bind_parameters(
+        context: &ExecutionContext,
+        parameters: &[Parameter],
+        event: &Event,
+    ) -> RuntimeResult<()> {
+        for param in parameters {
+            let value = match event.parameters.get(&param.name) {
+                Some(value) => expression::Value::from(value.clone()),
+                None if matches!(param.type_info, TypeInfo::Option(_)) => expression::Value::Null,
+                // 型指定のないパラメータは従来どおり未束縛のまま
+                None if param.type_info.is_any() => continue,
+                None => {
+                    return Err(RuntimeError::EvaluationFailed(format!(
+                        "Missing required parameter '{}: {}' for event {}",
+                        param.name, param.type_info, event.event_type
+                    )));
+                }
+            };
+            context
+                .set_variable(&param.name, value)
+                .await
+                .map_err(|e| {
+                    RuntimeError::EvaluationFailed(format!(
+                        "Failed to bind parameter {}: {}",
+                        param.name, e
+                    ))
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Binds the request type of `event` to the only parameter of a catch-all handler
+    async fn bind_request_type(
+        context: &ExecutionContext,
+        parameters: &[Parameter],
+        event: &Event,
+    ) -> RuntimeResult<()> {
+        let (Some(param), EventType::Request { request_type, .. }) =
+            (parameters.first(), &event.event_type)
+        else {
+            return Ok(());
+        };
+        context
+            .set_variable(&param.name, expression::Value::String(request_type.clone()))
+            .await
+            .map_err(|e| {
+                RuntimeError::EvaluationFailed(format!(
+                    "Failed to bind parameter {}: {}",
+                    param.name, e
+                ))
+            })
+    }
+
+    /// Checks the `where` constraints of the bound `parameters` and fails with every
+    /// violated one, before the handler block runs
+    pub(crate) async fn check_parameter_constraints(
+        evaluator: &Evaluator,
+        parameters: &[Parameter],
+        context: &Arc<ExecutionContext>,
+        handler: &str,
+    ) -> EvalResult<()> {
+        let violations = evaluator
+            .eval_parameter_constraints(parameters, context.clone())
+            .await?;
+        if violations.is_empty() {
+            return Ok(());
+        }
+        Err(EvalError::ValidationFailed {
+            agent_name: context.agent_name(),
+            handler: handler.to_string(),
+            violations,
+        })
+    }
+
+    /// Whether the handler block runs. Evaluates the `requires` clause of
+    /// `handler` and, when it does not hold, fails or skips according to `mode`.
+    async fn check_precondition(
+        evaluator: &Evaluator,
+        requires: Option<&Expression>,
+        context: &Arc<ExecutionContext>,
+        mode: PreconditionMode,
+        handler: &str,
+    ) -> EvalResult<bool> {
+        if evaluator
+            .eval_precondition(requires, context.clone())
+            .await?
+        {
+            return Ok(true);
+        }
+        match mode {
+            PreconditionMode::Fail => Err(EvalError::PreconditionFailed {
+                agent_name: context.agent_name(),
+                handler: handler.to_string(),
+            }),
+            PreconditionMode::Skip => {
+                debug!("Skip handler {}: requires clause does not hold", handler);
+                Ok(false)
+            }
+        }
+    }
+
     // observe ハンドラの登録
     pub fn register_observe(&mut self, event_type: &str, handler: ObserveHandler) {
         self.observe_handlers
             .insert(event_type.to_string(), handler);
     }
 
+    /// Whether `handler` is marked `@replay(skip)` and `event` is a journal replay
+    fn skips_replay(handler: &EventHandler, event: &Event) -> bool {
+        event.is_replay()
+            && handler.replay.as_ref().and_then(|replay| replay.policy())
+                == Some(ReplayPolicy::Skip)
+    }
+
     pub fn create_observe_handler(
         evaluator: Arc<Evaluator>,
         event_handler: Arc<EventHandler>,
         base_context: Arc<ExecutionContext>,
+        precondition_mode: PreconditionMode,
+        recordings: Arc<OnceLock<Arc<HandlerRecordings>>>,
     ) -> ObserveHandler {
         Box::new(move |event| {
             let evaluator = evaluator.clone();
             let handler = event_handler.clone();
             let base = base_context.clone();
             let event = event.clone();
+            let recordings = recordings.get().cloned();
 
             Box::pin(async move {
-                let context = base.fork(Some(StateAccessMode::ReadWrite)).await;
-                let context_ref = Arc::new(context);
-
-                for param in &handler.parameters {
-                    if let Some(value) = event.parameters.get(&param.name) {
-                        context_ref
-                            .set_variable(&param.name, expression::Value::from(value.clone()))
-                            .await
-                            .unwrap();
-                    }
+                if Self::skips_replay(&handler, &event) {
+                    debug!(
+                        "Skipping replayed {} for {}",
+                        event.event_type, handler.event_type
+                    );
+                    return Ok(());
+                }
+                let context = base
+                    .fork(Some(StateAccessMode::ReadWrite))
+                    .await
+                    .with_new_execution()
+                    .with_transaction()
+                    .with_event_metadata(&event)
+                    .with_trigger_event(&event);
+                let recorder = match &recordings {
+                    Some(_) => Some(Recorder::new(recording::current_state(&context).await)),
+                    None => None,
+                };
+                let context_ref = Arc::new(context.with_recorder(recorder.clone()));
+
+                Self::bind_parameters(&context_ref, &handler.parameters, &event).await?;
+                Self::check_parameter_constraints(
+                    &evaluator,
+                    &handler.parameters,
+                    &context_ref,
+                    &handler.event_type.to_string(),
+                )
+                .await?;
+
+                let runs = Self::check_precondition(
+                    &evaluator,
+                    handler.requires.as_ref(),
+                    &context_ref,
+                    precondition_mode,
+                    &handler.event_type.to_string(),
+                )
+                .await?;
+                if !runs {
+                    return Ok(());
                 }
 
                 evaluator
-                    .eval_handler_block(&handler.block, context_ref)
+                    .eval_handler_block(&handler.block, context_ref.clone())
                     .await
-                    .map(|_| ())
                     .map_err(|e| {
                         RuntimeError::EvaluationFailed(format!(
                             "Failed to evaluate observe handler: {}",
                             e
                         ))
-                    })
+                    })?;
+                // 失敗したときはコンテキストとともに書き込みと保留したイベントを捨てる
+                context_ref.commit().await.map_err(EvalError::from)?;
+                if let (Some(recordings), Some(recorder)) = (&recordings, &recorder) {
+                    recordings.push(recorder.finish(
+                        &context_ref.agent_name(),
+                        HandlerKind::Observe,
+                        &event,
+                    ));
+                }
+                Ok(())
             })
         })
     }
@@ -594,37 +1044,102 @@ impl RuntimeAgentData {
         evaluator: Arc<Evaluator>,
         event_handler: Arc<RequestHandler>,
         base_context: Arc<ExecutionContext>,
+        pipeline: Arc<AnswerPipeline>,
+        precondition_mode: PreconditionMode,
+        response_cache: Arc<ResponseCache>,
     ) -> AnswerHandler {
         Box::new(move |event| {
             let evaluator = evaluator.clone();
             let handler = event_handler.clone();
             let base = base_context.clone();
+            let pipeline = pipeline.clone();
+            let response_cache = response_cache.clone();
             let event = event.clone();
             let event_type = event.event_type.clone();
 
             Box::pin(async move {
-                let context = base.fork(Some(StateAccessMode::ReadOnly)).await;
+                let profiler = event.profile_requested_at().map(Profiler::new);
+                let context = base
+                    .fork(Some(StateAccessMode::ReadOnly))
+                    .await
+                    .with_new_execution()
+                    .with_profiler(profiler)
+                    .with_output_format(if handler.return_type.is_structured() {
+                        OutputFormat::Json
+                    } else {
+                        OutputFormat::Text
+                    })
+                    .with_request_context(event.request_context().unwrap_or_default())
+                    .with_request_metadata(&event)
+                    .with_trigger_event(&event);
                 let context_ref = Arc::new(context);
 
-                for param in &handler.parameters {
-                    if let Some(value) = event.parameters.get(&param.name) {
+                if handler.request_type == RequestType::Fallback {
+                    Self::bind_request_type(&context_ref, &handler.parameters, &event).await?;
+                } else {
+                    Self::bind_parameters(&context_ref, &handler.parameters, &event).await?;
+                }
+
+                let handler_name = handler.request_type.to_string();
+                let precondition = match Self::check_parameter_constraints(
+                    &evaluator,
+                    &handler.parameters,
+                    &context_ref,
+                    &handler_name,
+                )
+                .await
+                {
+                    Ok(()) => {
+                        Self::check_precondition(
+                            &evaluator,
+                            handler.requires.as_ref(),
+                            &context_ref,
+                            precondition_mode,
+                            &handler_name,
+                        )
+                        .await
+                    }
+                    Err(e) => Err(e),
+                };
+                if !matches!(precondition, Ok(true)) {
+                    // 本体を実行せず、エラーまたは値なしで応答する
+                    let response = precondition
+                        .map(|_| expression::Value::Unit)
+                        .map_err(RuntimeError::from);
+                    context_ref
+                        .send_response(event_type, response)
+                        .await
+                        .map_err(|e| EvalError::SendResponseFailed(format!("error: {}", e)))?;
+                    return Ok(());
+                }
+
+                // キャッシュ済みの応答があれば本体を実行しない
+                let cache_key = ResponseCache::key(&handler, &event);
+                if let Some(key) = &cache_key {
+                    if let Some(response) = response_cache.get(key).await {
                         context_ref
-                            .set_variable(&param.name, expression::Value::from(value.clone()))
+                            .send_response(event_type, Ok(response))
                             .await
-                            .unwrap();
+                            .map_err(|e| EvalError::SendResponseFailed(format!("error: {}", e)))?;
+                        return Ok(());
                     }
                 }
 
-                evaluator
-                    .eval_answer_handler_block(&handler.block, context_ref, event_type)
+                let answered = evaluator
+                    .eval_answer_handler_block(&handler.block, context_ref, event_type, &pipeline)
                     .await
-                    .map(|_| ())
                     .map_err(|e| {
                         RuntimeError::EvaluationFailed(format!(
                             "Failed to evaluate answer handler: {}",
                             e
                         ))
-                    })
+                    })?;
+                if let (Some(key), Some(response), Some(cache)) =
+                    (cache_key, answered, &handler.cache)
+                {
+                    response_cache.put(&key, &response, cache.ttl).await;
+                }
+                Ok(())
             })
         })
     }
@@ -638,36 +1153,77 @@ impl RuntimeAgentData {
         evaluator: Arc<Evaluator>,
         event_handler: Arc<EventHandler>,
         base_context: Arc<ExecutionContext>,
+        precondition_mode: PreconditionMode,
+        recordings: Arc<OnceLock<Arc<HandlerRecordings>>>,
     ) -> ReactHandler {
         Box::new(move |event| {
             let evaluator = evaluator.clone();
             let handler = event_handler.clone();
             let base = base_context.clone();
             let event = event.clone();
+            let recordings = recordings.get().cloned();
 
             Box::pin(async move {
-                let context = base.fork(Some(StateAccessMode::ReadWrite)).await;
-                let context_ref = Arc::new(context);
-
-                for param in &handler.parameters {
-                    if let Some(value) = event.parameters.get(&param.name) {
-                        context_ref
-                            .set_variable(&param.name, expression::Value::from(value.clone()))
-                            .await
-                            .unwrap();
-                    }
+                if Self::skips_replay(&handler, &event) {
+                    debug!(
+                        "Skipping replayed {} for {}",
+                        event.event_type, handler.event_type
+                    );
+                    return Ok(());
+                }
+                let context = base
+                    .fork(Some(StateAccessMode::ReadWrite))
+                    .await
+                    .with_new_execution()
+                    .with_transaction()
+                    .with_event_metadata(&event)
+                    .with_trigger_event(&event);
+                let recorder = match &recordings {
+                    Some(_) => Some(Recorder::new(recording::current_state(&context).await)),
+                    None => None,
+                };
+                let context_ref = Arc::new(context.with_recorder(recorder.clone()));
+
+                Self::bind_parameters(&context_ref, &handler.parameters, &event).await?;
+                Self::check_parameter_constraints(
+                    &evaluator,
+                    &handler.parameters,
+                    &context_ref,
+                    &handler.event_type.to_string(),
+                )
+                .await?;
+
+                let runs = Self::check_precondition(
+                    &evaluator,
+                    handler.requires.as_ref(),
+                    &context_ref,
+                    precondition_mode,
+                    &handler.event_type.to_string(),
+                )
+                .await?;
+                if !runs {
+                    return Ok(());
                 }
 
                 evaluator
-                    .eval_handler_block(&handler.block, context_ref)
+                    .eval_handler_block(&handler.block, context_ref.clone())
                     .await
-                    .map(|_| ())
                     .map_err(|e| {
                         RuntimeError::EvaluationFailed(format!(
                             "Failed to evaluate react handler: {}",
                             e
                         ))
-                    })
+                    })?;
+                // 失敗したときはコンテキストとともに書き込みと保留したイベントを捨てる
+                context_ref.commit().await.map_err(EvalError::from)?;
+                if let (Some(recordings), Some(recorder)) = (&recordings, &recorder) {
+                    recordings.push(recorder.finish(
+                        &context_ref.agent_name(),
+                        HandlerKind::React,
+                        &event,
+                    ));
+                }
+                Ok(())
             })
         })
     }
@@ -687,19 +1243,24 @@ impl RuntimeAgentData {
             let base = base_context.clone();
 
             Box::pin(async move {
-                let context = base.fork(Some(StateAccessMode::ReadWrite)).await;
+                let context = base
+                    .fork(Some(StateAccessMode::ReadWrite))
+                    .await
+                    .with_new_execution()
+                    .with_transaction();
                 let context_ref = Arc::new(context);
 
                 evaluator
-                    .eval_handler_block(&handler_block, context_ref)
+                    .eval_handler_block(&handler_block, context_ref.clone())
                     .await
-                    .map(|_| ())
                     .map_err(|e| {
                         RuntimeError::EvaluationFailed(format!(
                             "Failed to evaluate lifecycle handler {}",
                             e
                         ))
-                    })
+                    })?;
+                context_ref.commit().await.map_err(EvalError::from)?;
+                Ok(())
             })
         })
     }
@@ -711,17 +1272,9 @@ impl RuntimeAgentData {
 
         match &event.category() {
             // リクエストイベント
-            EventCategory::Request { request_type, .. } => {
+            EventCategory::Request { .. } => {
                 if event.event_type.request_for_me(&self.name) {
-                    if let Some(handler) = self.answer_handlers.get(request_type) {
-                        debug!("Handler found: {:?}", request_type);
-                        handler(event).await
-                    } else {
-                        Err(RuntimeError::HandlerNotFound {
-                            handler_type: "answer".to_string(),
-                            name: request_type.clone(),
-                        })
-                    }
+                    self.handle_request(event, None).await
                 } else {
                     // not for me
                     Ok(())
@@ -738,6 +1291,100 @@ impl RuntimeAgentData {
         }
     }
 
+    /// Run the answer handler for `event`, inline or on the scheduler.
+    /// `recovered` is the queue entry of a request left by a previous run; new
+    /// requests are recorded in the request queue first, when there is one.
+    ///
+    /// A recovered request skips the dedup window, which starts empty on each run.
+    /// A `@cache` handler still answers it from its response cache without running,
+    /// when the response was kept across the restart.
+    async fn handle_request(
+        &self,
+        event: &Event,
+        recovered: Option<QueueTicket>,
+    ) -> RuntimeResult<()> {
+        let EventType::Request { request_type, .. } = &event.event_type else {
+            return Ok(());
+        };
+        // 一致するハンドラがなければ catch-all に任せる
+        let Some(handler) = self
+            .answer_handlers
+            .get(request_type)
+            .or_else(|| self.answer_handlers.get(&RequestType::Fallback.to_string()))
+        else {
+            if let Some(ticket) = recovered {
+                ticket.complete().await;
+            }
+            return Err(RuntimeError::HandlerNotFound {
+                handler_type: "answer".to_string(),
+                name: request_type.clone(),
+            });
+        };
+        debug!("Handler found: {:?}", request_type);
+
+        // 再送されたリクエストは実行せず、元のリクエストへの応答を送る
+        if recovered.is_none() {
+            match self.base_context.admit_request(event) {
+                Admission::First => {}
+                Admission::Answered(response) => {
+                    debug!("Duplicate request answered again: {:?}", request_type);
+                    self.event_bus.publish(*response).await?;
+                    return Ok(());
+                }
+                Admission::InFlight(original) => {
+                    debug!(
+                        "Duplicate request waits for the original: {:?}",
+                        request_type
+                    );
+                    let event_bus = self.event_bus.clone();
+                    tokio::spawn(async move {
+                        if let Ok(response) = original.await {
+                            let _ = event_bus.publish(response).await;
+                        }
+                    });
+                    return Ok(());
+                }
+            }
+        }
+
+        let ticket = match (recovered, &self.request_queue) {
+            (Some(ticket), _) => Some(ticket),
+            (None, Some(queue)) => Some(queue.enqueue(event)?),
+            (None, None) => None,
+        };
+        let handler_future = handler(event);
+        let future: BoxFuture<'static, RuntimeResult<()>> = match ticket {
+            // 記録が書き込まれてから実行し、終わったら取り除く
+            Some(ticket) => Box::pin(async move {
+                ticket.durable().await?;
+                let result = handler_future.await;
+                ticket.complete().await;
+                result
+            }),
+            None => handler_future,
+        };
+
+        match &self.scheduler {
+            Some(scheduler) => {
+                let agent_name = self.name.clone();
+                scheduler.submit(
+                    &self.name,
+                    Box::pin(async move {
+                        if let Err(e) = future.await {
+                            tracing::error!(
+                                "Scheduled request failed in agent {}: {}",
+                                agent_name,
+                                e
+                            );
+                        }
+                    }),
+                );
+                Ok(())
+            }
+            None => future.await,
+        }
+    }
+
     async fn handle_normal_event(&self, event: &Event) -> RuntimeResult<()> {
         debug!(
             "Normal event received: {:?}, {}, {:?}",
@@ -838,6 +1485,10 @@ pub enum RuntimeError {
     /// Handler lookup failures
     #[error("Handler not found for {handler_type}: {name}")]
     HandlerNotFound { handler_type: String, name: String },
+
+    /// Request queue persistence failures
+    #[error("Request queue error: {0}")]
+    RequestQueue(#[from] StorageError),
 }
 
 #[cfg(test)]
@@ -869,6 +1520,7 @@ mod tests {
                             name: "count".to_string(),
                             type_info: TypeInfo::Simple("i64".to_string()),
                             initial_value: Some(Expression::Literal(Literal::Integer(0))),
+                            doc: None,
                         },
                     );
                     vars
@@ -878,6 +1530,7 @@ mod tests {
                 handlers: vec![EventHandler {
                     event_type: ast::EventType::Tick,
                     parameters: vec![],
+                    requires: None,
                     block: HandlerBlock {
                         statements: vec![Statement::Assignment {
                             target: vec![Expression::StateAccess(StateAccessPath(vec![
@@ -892,6 +1545,8 @@ mod tests {
                             },
                         }],
                     },
+                    doc: None,
+                    replay: None,
                 }],
             }),
             ..Default::default()
@@ -902,9 +1557,9 @@ mod tests {
             counter_def,
             &event_bus,
             AgentConfig::default(),
-            Arc::new(ProviderInstance::default()),
-            Arc::new(DashMap::new()),
+            AgentResources::default(),
             vec![],
+            WorldPreamble::default(),
         )
         .await
         .unwrap();
@@ -990,6 +1645,7 @@ mod tests {
                             name: "self.x".to_string(),
                             type_info: TypeInfo::Simple("i64".to_string()),
                             initial_value: Some(Expression::Literal(Literal::Integer(2))),
+                            doc: None,
                         },
                     );
                     vars
@@ -1002,14 +1658,17 @@ mod tests {
                         Parameter {
                             name: "a".to_string(),
                             type_info: TypeInfo::Simple("i64".to_string()),
+                            constraint: None,
                         },
                         Parameter {
                             name: "b".to_string(),
                             type_info: TypeInfo::Simple("i64".to_string()),
+                            constraint: None,
                         },
                     ],
                     return_type: TypeInfo::Simple("i64".to_string()),
                     constraints: None,
+                    requires: None,
                     block: HandlerBlock {
                         statements: vec![
                             Statement::Assignment {
@@ -1034,6 +1693,8 @@ mod tests {
                             Statement::Return(Expression::Variable("last_result".into())),
                         ],
                     },
+                    doc: None,
+                    cache: None,
                 }],
             }),
             ..Default::default()
@@ -1043,9 +1704,9 @@ mod tests {
             answer_def,
             &event_bus,
             AgentConfig::default(),
-            Arc::new(ProviderInstance::default()),
-            Arc::new(DashMap::new()),
+            AgentResources::default(),
             vec![],
+            WorldPreamble::default(),
         )
         .await
         .unwrap();
@@ -1074,6 +1735,7 @@ mod tests {
                     params.insert("b".to_string(), Value::Integer(5));
                     params
                 },
+                ..Default::default()
             })
             .await
             .unwrap();
@@ -1086,103 +1748,999 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_react_handler() {
+    async fn test_answer_handler_streams_yields() {
+        use crate::request_manager::RequestManager;
+
         let event_bus = Arc::new(EventBus::new(20));
-        let react_def = &MicroAgentDef {
-            name: "reactor".to_string(),
-            state: Some(StateDef {
-                variables: {
-                    let mut vars = HashMap::new();
-                    vars.insert(
-                        "event_count".to_string(),
-                        StateVarDef {
-                            name: "event_count".to_string(),
-                            type_info: TypeInfo::Simple("i64".to_string()),
-                            initial_value: Some(Expression::Literal(Literal::Integer(0))),
-                        },
-                    );
-                    vars.insert(
-                        "last_value".to_string(),
-                        StateVarDef {
-                            name: "last_value".to_string(),
-                            type_info: TypeInfo::Simple("i64".to_string()),
-                            initial_value: Some(Expression::Literal(Literal::Integer(0))),
-                        },
-                    );
-                    vars
-                },
-            }),
-            react: Some(ReactDef {
-                handlers: vec![EventHandler {
-                    event_type: ast::EventType::Message {
-                        content_type: "update".into(),
-                    },
-                    parameters: vec![Parameter {
-                        name: "value".to_string(),
-                        type_info: TypeInfo::Simple("i64".to_string()),
-                    }],
+        let string = |s: &str| Expression::Literal(Literal::String(s.to_string()));
+        let chat_def = &MicroAgentDef {
+            name: "chat".to_string(),
+            answer: Some(AnswerDef {
+                handlers: vec![RequestHandler {
+                    request_type: RequestType::Custom("greet".to_string()),
+                    parameters: vec![],
+                    return_type: TypeInfo::Simple("String".to_string()),
+                    constraints: None,
+                    requires: None,
                     block: HandlerBlock {
                         statements: vec![
-                            Statement::Assignment {
-                                target: vec![Expression::StateAccess(StateAccessPath(vec![
-                                    "event_count".into(),
-                                ]))],
-                                value: Expression::BinaryOp {
-                                    op: BinaryOperator::Add,
-                                    left: Box::new(Expression::StateAccess(StateAccessPath(vec![
-                                        "event_count".into(),
-                                    ]))),
-                                    right: Box::new(Expression::Literal(Literal::Integer(1))),
-                                },
-                            },
-                            Statement::Assignment {
-                                target: vec![Expression::StateAccess(StateAccessPath(vec![
-                                    "last_value".into(),
-                                ]))],
-                                value: Expression::Variable("value".into()),
-                            },
+                            Statement::Yield(string("Hel")),
+                            Statement::Yield(string("lo")),
+                            Statement::Return(string("Hello")),
                         ],
                     },
+                    doc: None,
+                    cache: None,
                 }],
             }),
             ..Default::default()
         };
 
         let agent = RuntimeAgentData::new(
-            react_def,
+            chat_def,
             &event_bus,
             AgentConfig::default(),
-            Arc::new(ProviderInstance::default()),
-            Arc::new(DashMap::new()),
+            AgentResources::default(),
             vec![],
+            WorldPreamble::default(),
         )
         .await
         .unwrap();
-        let context = agent.base_context.clone();
         let shutdown_rx = broadcast::channel(1).1;
-
         tokio::spawn(async move {
             agent.run(shutdown_rx).await.unwrap();
         });
 
+        let request_manager = Arc::new(RequestManager::new(
+            event_bus.clone(),
+            Duration::from_secs(5),
+        ));
+        let (mut event_rx, _) = event_bus.subscribe();
+        let manager_ref = request_manager.clone();
+        tokio::spawn(async move {
+            while let Ok(event) = event_rx.recv().await {
+                let _ = manager_ref.handle_event(&event);
+            }
+        });
         tokio::time::sleep(Duration::from_millis(100)).await;
 
-        // カスタムイベントを送信
-        event_bus
-            .publish(Event {
-                event_type: EventType::Message {
-                    content_type: "update".into(),
-                },
-                parameters: {
-                    let mut params = HashMap::new();
-                    params.insert("value".to_string(), Value::Integer(42));
-                    params
-                },
-            })
-            .await
+        let request_id = Uuid::new_v4().to_string();
+        let request = Event::request_builder()
+            .request_type("greet")
+            .requester("test")
+            .responder("chat")
+            .request_id(&request_id)
+            .build()
             .unwrap();
+        let events: Vec<Event> = request_manager
+            .request_streaming(&request)
+            .await
+            .unwrap()
+            .map(|event| event.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(events.len(), 3);
+        assert!(
+            events
+                .iter()
+                .all(|e| e.event_type.request_id() == Some(request_id.as_str()))
+        );
+        assert!(events[0].event_type.is_partial_response());
+        assert_eq!(events[0].response_value(), Value::String("Hel".to_string()));
+        assert_eq!(
+            events[0].parameters.get("sequence"),
+            Some(&Value::Integer(0))
+        );
+        assert!(events[1].event_type.is_partial_response());
+        assert_eq!(events[1].response_value(), Value::String("lo".to_string()));
+        assert_eq!(
+            events[1].parameters.get("sequence"),
+            Some(&Value::Integer(1))
+        );
+        assert!(matches!(
+            events[2].event_type,
+            EventType::ResponseSuccess { .. }
+        ));
+        assert_eq!(
+            events[2].response_value(),
+            Value::String("Hello".to_string())
+        );
+    }
 
-        tokio::time::sleep(Duration::from_millis(100)).await;
+    #[tokio::test]
+    async fn test_answer_handler_streams_think_output() {
+        use crate::{
+            Argument,
+            ast::{Constraints, ThinkAttributes},
+            config::ProviderConfig,
+            provider::{
+                capabilities::common::Capabilities,
+                llm::{LLMResponse, MockProviderLLM},
+                providers::standard::StandardProvider,
+            },
+            request_manager::RequestManager,
+        };
+
+        let mut llm = MockProviderLLM::new();
+        llm.expect_name().return_const("mock".to_string());
+        llm.expect_capabilities().returning(Capabilities::default);
+        llm.expect_send_message_streaming()
+            .returning(|_, _, chunks| {
+                chunks.send(" Hel".to_string()).unwrap();
+                chunks.send("lo ".to_string()).unwrap();
+                Box::pin(async {
+                    Ok(LLMResponse {
+                        content: " Hello ".to_string(),
+                        ..Default::default()
+                    })
+                })
+            });
+        llm.expect_send_message().returning(|_, _| {
+            Box::pin(async {
+                Ok(LLMResponse {
+                    content: " Hello ".to_string(),
+                    ..Default::default()
+                })
+            })
+        });
+        let primary = Arc::new(ProviderInstance {
+            config: ProviderConfig::default(),
+            provider: Arc::new(StandardProvider::new(llm, vec![])),
+            secret: Default::default(),
+        });
+
+        let event_bus = Arc::new(EventBus::new(20));
+        let chat_def = &MicroAgentDef {
+            name: "chat".to_string(),
+            answer: Some(AnswerDef {
+                handlers: vec![RequestHandler {
+                    request_type: RequestType::Custom("greet".to_string()),
+                    parameters: vec![],
+                    return_type: TypeInfo::Simple("String".to_string()),
+                    // 整形は最終的な応答にだけ適用される
+                    constraints: Some(Constraints {
+                        strictness: None,
+                        stability: None,
+                        latency: None,
+                        postprocess: vec![Literal::String("trim".to_string())],
+                    }),
+                    requires: None,
+                    block: HandlerBlock {
+                        statements: vec![
+                            Statement::Assignment {
+                                target: vec![Expression::Variable("reply".into())],
+                                value: Expression::Think {
+                                    args: vec![Argument::Positional(Expression::Literal(
+                                        Literal::String("Greet".to_string()),
+                                    ))],
+                                    with_block: Some(ThinkAttributes {
+                                        stream: true,
+                                        ..Default::default()
+                                    }),
+                                },
+                            },
+                            Statement::Return(Expression::StateAccess(StateAccessPath(vec![
+                                "reply".into(),
+                                "output".into(),
+                            ]))),
+                        ],
+                    },
+                    doc: None,
+                    cache: None,
+                }],
+            }),
+            ..Default::default()
+        };
+
+        let agent = RuntimeAgentData::new(
+            chat_def,
+            &event_bus,
+            AgentConfig::default(),
+            AgentResources {
+                primary,
+                ..Default::default()
+            },
+            vec![],
+            WorldPreamble::default(),
+        )
+        .await
+        .unwrap();
+        let shutdown_rx = broadcast::channel(1).1;
+        tokio::spawn(async move {
+            agent.run(shutdown_rx).await.unwrap();
+        });
+
+        let request_manager = Arc::new(RequestManager::new(
+            event_bus.clone(),
+            Duration::from_secs(5),
+        ));
+        let (mut event_rx, _) = event_bus.subscribe();
+        let manager_ref = request_manager.clone();
+        tokio::spawn(async move {
+            while let Ok(event) = event_rx.recv().await {
+                let _ = manager_ref.handle_event(&event);
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let request = |request_id: &str| {
+            Event::request_builder()
+                .request_type("greet")
+                .requester("test")
+                .responder("chat")
+                .request_id(request_id)
+                .build()
+                .unwrap()
+        };
+        let events: Vec<Event> = request_manager
+            .request_streaming(&request(&Uuid::new_v4().to_string()))
+            .await
+            .unwrap()
+            .map(|event| event.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(events.len(), 3);
+        assert!(events[0].event_type.is_partial_response());
+        assert_eq!(
+            events[0].response_value(),
+            Value::String(" Hel".to_string())
+        );
+        assert!(events[1].event_type.is_partial_response());
+        assert_eq!(events[1].response_value(), Value::String("lo ".to_string()));
+        assert!(matches!(
+            events[2].event_type,
+            EventType::ResponseSuccess { .. }
+        ));
+        assert_eq!(
+            events[2].response_value(),
+            Value::String("Hello".to_string())
+        );
+
+        // ストリームを受け取らない呼び出し元には完成した応答だけが届く
+        let response = request_manager
+            .request(&request(&Uuid::new_v4().to_string()))
+            .await
+            .unwrap();
+        assert_eq!(
+            response.response_value(),
+            Value::String("Hello".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_answer_handler_with_request_context() {
+        use crate::{
+            Argument,
+            config::ProviderConfig,
+            context::RequestContext,
+            provider::{
+                capabilities::common::Capabilities,
+                llm::{LLMResponse, MockProviderLLM},
+                providers::standard::StandardProvider,
+            },
+        };
+
+        let prompts = Arc::new(Mutex::new(Vec::<String>::new()));
+        let captured = prompts.clone();
+        let mut llm = MockProviderLLM::new();
+        llm.expect_name().return_const("mock".to_string());
+        llm.expect_capabilities().returning(Capabilities::default);
+        llm.expect_send_message().returning(move |prompt, _| {
+            captured.lock().unwrap().push(prompt.to_string());
+            Box::pin(async move {
+                Ok(LLMResponse {
+                    content: "こんにちは".to_string(),
+                    ..Default::default()
+                })
+            })
+        });
+        let primary = Arc::new(ProviderInstance {
+            config: ProviderConfig::default(),
+            provider: Arc::new(StandardProvider::new(llm, vec![])),
+            secret: Default::default(),
+        });
+
+        let event_bus = Arc::new(EventBus::new(20));
+        let greeter_def = &MicroAgentDef {
+            name: "greeter".to_string(),
+            answer: Some(AnswerDef {
+                handlers: vec![RequestHandler {
+                    request_type: RequestType::Custom("greet".to_string()),
+                    parameters: vec![],
+                    return_type: TypeInfo::Simple("String".to_string()),
+                    constraints: None,
+                    requires: None,
+                    block: HandlerBlock {
+                        statements: vec![Statement::Return(Expression::Think {
+                            args: vec![Argument::Positional(Expression::Literal(Literal::String(
+                                "Greet ${request_user_id}".to_string(),
+                            )))],
+                            with_block: None,
+                        })],
+                    },
+                    doc: None,
+                    cache: None,
+                }],
+            }),
+            ..Default::default()
+        };
+
+        let agent = RuntimeAgentData::new(
+            greeter_def,
+            &event_bus,
+            AgentConfig::default(),
+            AgentResources {
+                primary,
+                ..Default::default()
+            },
+            vec![],
+            WorldPreamble::default(),
+        )
+        .await
+        .unwrap();
+        let shutdown_rx = broadcast::channel(1).1;
+        let sender_agent = TestAgent::new("test", &event_bus);
+        tokio::spawn(async move {
+            agent.run(shutdown_rx).await.unwrap();
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let request_id = Uuid::new_v4().to_string();
+        let request = Event::request_builder()
+            .request_type("greet")
+            .requester("test")
+            .responder("greeter")
+            .request_id(&request_id)
+            .request_context(&RequestContext::new(
+                Some("user-1".to_string()),
+                Some("ja-JP".to_string()),
+            ))
+            .build()
+            .unwrap();
+        event_bus.publish(request).await.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let response = sender_agent.get_response(&request_id);
+        assert!(matches!(response, Value::Map(ref map)
+            if map.get("output") == Some(&Value::String("こんにちは".to_string()))));
+
+        let prompts = prompts.lock().unwrap();
+        assert_eq!(prompts.len(), 1);
+        assert!(prompts[0].contains("Greet user-1"));
+        assert!(prompts[0].contains("- Locale: ja-JP"));
+        assert!(prompts[0].contains("\"ja-JP\" locale"));
+    }
+
+    #[tokio::test]
+    async fn test_answer_handler_renders_prompt_in_request_locale() {
+        use crate::{
+            Argument,
+            config::ProviderConfig,
+            context::RequestContext,
+            provider::{
+                capabilities::common::Capabilities,
+                llm::{LLMResponse, MockProviderLLM},
+                providers::standard::StandardProvider,
+            },
+        };
+
+        let prompts = Arc::new(Mutex::new(Vec::<String>::new()));
+        let captured = prompts.clone();
+        let mut llm = MockProviderLLM::new();
+        llm.expect_name().return_const("mock".to_string());
+        llm.expect_capabilities().returning(Capabilities::default);
+        llm.expect_send_message().returning(move |prompt, _| {
+            captured.lock().unwrap().push(prompt.to_string());
+            Box::pin(async move {
+                Ok(LLMResponse {
+                    content: "ok".to_string(),
+                    ..Default::default()
+                })
+            })
+        });
+        let primary = Arc::new(ProviderInstance {
+            config: ProviderConfig::default(),
+            provider: Arc::new(StandardProvider::new(llm, vec![])),
+            secret: Default::default(),
+        });
+
+        let event_bus = Arc::new(EventBus::new(20));
+        let planner_def = &MicroAgentDef {
+            name: "planner".to_string(),
+            persona: Some("You are a travel planner.".to_string()),
+            localized_personas: HashMap::from([(
+                "ja".to_string(),
+                "あなたは旅行プランナーです。".to_string(),
+            )]),
+            answer: Some(AnswerDef {
+                handlers: vec![RequestHandler {
+                    request_type: RequestType::Custom("plan".to_string()),
+                    parameters: vec![Parameter {
+                        name: "departure".to_string(),
+                        type_info: TypeInfo::Simple("Timestamp".to_string()),
+                        constraint: None,
+                    }],
+                    return_type: TypeInfo::Simple("String".to_string()),
+                    constraints: None,
+                    requires: None,
+                    block: HandlerBlock {
+                        statements: vec![Statement::Return(Expression::Think {
+                            args: vec![Argument::Positional(Expression::Literal(Literal::String(
+                                "Plan a trip leaving ${departure}".to_string(),
+                            )))],
+                            with_block: None,
+                        })],
+                    },
+                    doc: None,
+                    cache: None,
+                }],
+            }),
+            ..Default::default()
+        };
+
+        let agent = RuntimeAgentData::new(
+            planner_def,
+            &event_bus,
+            AgentConfig::default(),
+            AgentResources {
+                primary,
+                ..Default::default()
+            },
+            vec![],
+            WorldPreamble::default(),
+        )
+        .await
+        .unwrap();
+        let shutdown_rx = broadcast::channel(1).1;
+        let sender_agent = TestAgent::new("test", &event_bus);
+        tokio::spawn(async move {
+            agent.run(shutdown_rx).await.unwrap();
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        for locale in ["ja-JP", "en"] {
+            let request_id = Uuid::new_v4().to_string();
+            let request = Event::request_builder()
+                .request_type("plan")
+                .requester("test")
+                .responder("planner")
+                .request_id(&request_id)
+                .parameter(
+                    "departure",
+                    &Value::String("2024-05-01T18:30:00+09:00".to_string()),
+                )
+                .request_context(&RequestContext::new(None, Some(locale.to_string())))
+                .build()
+                .unwrap();
+            event_bus.publish(request).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            assert!(matches!(
+                sender_agent.get_response(&request_id),
+                Value::Map(_)
+            ));
+        }
+
+        let prompts = prompts.lock().unwrap();
+        assert_eq!(prompts.len(), 2);
+        assert!(prompts[0].contains("Plan a trip leaving 2024年5月1日 09:30 UTC"));
+        assert!(prompts[0].contains("あなたは旅行プランナーです。"));
+        assert!(!prompts[0].contains("You are a travel planner."));
+        assert!(prompts[1].contains("Plan a trip leaving May 1, 2024 09:30 UTC"));
+        assert!(prompts[1].contains("You are a travel planner."));
+        assert!(!prompts[1].contains("あなたは旅行プランナーです。"));
+    }
+
+    #[tokio::test]
+    async fn test_locale_falls_back_to_agent_default() {
+        use crate::context::RequestContext;
+
+        let locale_handler = RequestHandler {
+            request_type: RequestType::Custom("locale".to_string()),
+            parameters: vec![],
+            return_type: TypeInfo::Simple("String".to_string()),
+            constraints: None,
+            requires: None,
+            block: HandlerBlock {
+                statements: vec![Statement::Return(Expression::StateAccess(StateAccessPath(
+                    vec!["context".into(), "locale".into()],
+                )))],
+            },
+            doc: None,
+            cache: None,
+        };
+        let closed_handler = RequestHandler {
+            request_type: RequestType::Custom("book".to_string()),
+            requires: Some(Expression::Literal(Literal::Boolean(false))),
+            block: HandlerBlock { statements: vec![] },
+            ..locale_handler.clone()
+        };
+        let agent_def = &MicroAgentDef {
+            name: "concierge".to_string(),
+            answer: Some(AnswerDef {
+                handlers: vec![locale_handler, closed_handler],
+            }),
+            ..Default::default()
+        };
+
+        let event_bus = Arc::new(EventBus::new(20));
+        let agent = RuntimeAgentData::new(
+            agent_def,
+            &event_bus,
+            AgentConfig {
+                locale: Some("ja".to_string()),
+                ..Default::default()
+            },
+            AgentResources::default(),
+            vec![],
+            WorldPreamble::default(),
+        )
+        .await
+        .unwrap();
+        let shutdown_rx = broadcast::channel(1).1;
+        let sender_agent = TestAgent::new("test", &event_bus);
+        tokio::spawn(async move {
+            agent.run(shutdown_rx).await.unwrap();
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let send = |request_type: &str, locale: Option<&str>| {
+            let request_id = Uuid::new_v4().to_string();
+            let request = Event::request_builder()
+                .request_type(request_type)
+                .requester("test")
+                .responder("concierge")
+                .request_id(&request_id)
+                .request_context(&RequestContext::new(None, locale.map(str::to_string)))
+                .build()
+                .unwrap();
+            (request_id, request)
+        };
+
+        // 対応していないロケールはエージェントの既定値になる
+        let mut answers = vec![];
+        for locale in [Some("en-US"), Some("fr"), None] {
+            let (request_id, request) = send("locale", locale);
+            event_bus.publish(request).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            answers.push(sender_agent.get_response(&request_id));
+        }
+        assert_eq!(
+            answers,
+            vec![
+                Value::String("en".to_string()),
+                Value::String("ja".to_string()),
+                Value::String("ja".to_string()),
+            ]
+        );
+
+        // 応答のエラーはロケールの言語でも書かれる
+        let (request_id, request) = send("book", Some("fr"));
+        event_bus.publish(request).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let responses = sender_agent.responses.lock().unwrap();
+        let failure = responses
+            .iter()
+            .find(|e| e.event_type.request_id() == Some(request_id.as_str()))
+            .unwrap();
+        assert!(matches!(
+            failure.event_type,
+            EventType::ResponseFailure { .. }
+        ));
+        assert_eq!(
+            failure.parameters.get(event_bus::ERROR_MESSAGE_KEY),
+            Some(&Value::String(
+                "book の requires 条件を満たしていないため応答できません".to_string()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_structured_answer_requests_json_output() {
+        use crate::{
+            Argument,
+            config::ProviderConfig,
+            provider::{
+                capabilities::common::Capabilities,
+                llm::{LLMResponse, MockProviderLLM},
+                providers::standard::StandardProvider,
+            },
+        };
+
+        let calls = Arc::new(Mutex::new(Vec::<(String, OutputFormat)>::new()));
+        let captured = calls.clone();
+        let mut llm = MockProviderLLM::new();
+        llm.expect_name().return_const("mock".to_string());
+        // JSON モードを持たない LLM なので、プロンプトでも指示される
+        llm.expect_capabilities().returning(Capabilities::default);
+        llm.expect_send_message().returning(move |prompt, config| {
+            captured
+                .lock()
+                .unwrap()
+                .push((prompt.to_string(), config.common_config.output_format));
+            Box::pin(async move {
+                Ok(LLMResponse {
+                    content: r#"{"title": "KAIREI"}"#.to_string(),
+                    ..Default::default()
+                })
+            })
+        });
+        let primary = Arc::new(ProviderInstance {
+            config: ProviderConfig::default(),
+            provider: Arc::new(StandardProvider::new(llm, vec![])),
+            secret: Default::default(),
+        });
+
+        let handler = |request_type: &str, return_type: TypeInfo| RequestHandler {
+            request_type: RequestType::Custom(request_type.to_string()),
+            parameters: vec![],
+            return_type,
+            constraints: None,
+            requires: None,
+            block: HandlerBlock {
+                statements: vec![Statement::Return(Expression::Think {
+                    args: vec![Argument::Positional(Expression::Literal(Literal::String(
+                        "Summarize".to_string(),
+                    )))],
+                    with_block: None,
+                })],
+            },
+            doc: None,
+            cache: None,
+        };
+        let event_bus = Arc::new(EventBus::new(20));
+        let summarizer_def = &MicroAgentDef {
+            name: "summarizer".to_string(),
+            answer: Some(AnswerDef {
+                handlers: vec![
+                    handler(
+                        "summary",
+                        TypeInfo::Result {
+                            ok_type: Box::new(TypeInfo::Map(
+                                Box::new(TypeInfo::Simple("String".to_string())),
+                                Box::new(TypeInfo::Simple("String".to_string())),
+                            )),
+                            err_type: Box::new(TypeInfo::Simple("Error".to_string())),
+                        },
+                    ),
+                    handler("text", TypeInfo::Simple("String".to_string())),
+                ],
+            }),
+            ..Default::default()
+        };
+
+        let agent = RuntimeAgentData::new(
+            summarizer_def,
+            &event_bus,
+            AgentConfig::default(),
+            AgentResources {
+                primary,
+                ..Default::default()
+            },
+            vec![],
+            WorldPreamble::default(),
+        )
+        .await
+        .unwrap();
+        let shutdown_rx = broadcast::channel(1).1;
+        let sender_agent = TestAgent::new("test", &event_bus);
+        tokio::spawn(async move {
+            agent.run(shutdown_rx).await.unwrap();
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        for request_type in ["summary", "text"] {
+            let request_id = Uuid::new_v4().to_string();
+            let request = Event::request_builder()
+                .request_type(request_type)
+                .requester("test")
+                .responder("summarizer")
+                .request_id(&request_id)
+                .build()
+                .unwrap();
+            event_bus.publish(request).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            assert!(matches!(
+                sender_agent.get_response(&request_id),
+                Value::Map(_)
+            ));
+        }
+
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].1, OutputFormat::Json);
+        assert!(calls[0].0.contains("valid JSON object"));
+        assert_eq!(calls[1].1, OutputFormat::Text);
+        assert!(!calls[1].0.contains("valid JSON object"));
+    }
+
+    #[tokio::test]
+    async fn test_fallback_answer_handler() {
+        let event_bus = Arc::new(EventBus::new(20));
+        let handler = |request_type: RequestType, parameter: &str, statements| RequestHandler {
+            request_type,
+            parameters: vec![Parameter {
+                name: parameter.to_string(),
+                type_info: TypeInfo::Simple("String".to_string()),
+                constraint: None,
+            }],
+            return_type: TypeInfo::Simple("String".to_string()),
+            requires: None,
+            constraints: None,
+            block: HandlerBlock { statements },
+            doc: None,
+            cache: None,
+        };
+        let agent_def = MicroAgentDef {
+            name: "helper".to_string(),
+            answer: Some(AnswerDef {
+                handlers: vec![
+                    handler(
+                        RequestType::Fallback,
+                        "request_type",
+                        vec![Statement::Return(Expression::BinaryOp {
+                            op: BinaryOperator::Add,
+                            left: Box::new(Expression::Literal(Literal::String(
+                                "unknown request: ".to_string(),
+                            ))),
+                            right: Box::new(Expression::Variable("request_type".into())),
+                        })],
+                    ),
+                    handler(
+                        RequestType::Custom("greet".to_string()),
+                        "name",
+                        vec![Statement::Return(Expression::Variable("name".into()))],
+                    ),
+                ],
+            }),
+            ..Default::default()
+        };
+        let agent = RuntimeAgentData::new(
+            &agent_def,
+            &event_bus,
+            AgentConfig::default(),
+            AgentResources::default(),
+            vec![],
+            WorldPreamble::default(),
+        )
+        .await
+        .unwrap();
+        let shutdown_rx = broadcast::channel(1).1;
+        let sender_agent = TestAgent::new("test", &event_bus);
+        tokio::spawn(async move {
+            agent.run(shutdown_rx).await.unwrap();
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut responses = vec![];
+        for request_type in ["greet", "translate"] {
+            let request_id = Uuid::new_v4().to_string();
+            event_bus
+                .publish(Event {
+                    event_type: EventType::Request {
+                        request_type: request_type.into(),
+                        requester: "test".into(),
+                        responder: "helper".into(),
+                        request_id: request_id.clone(),
+                    },
+                    parameters: HashMap::from([(
+                        "name".to_string(),
+                        Value::String("Alice".to_string()),
+                    )]),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            responses.push(sender_agent.get_response(&request_id));
+        }
+
+        // 一致するハンドラが優先され、catch-all は一致しないときだけ実行される
+        assert_eq!(responses[0], Value::String("Alice".to_string()));
+        assert_eq!(
+            responses[1],
+            Value::String("unknown request: translate".to_string())
+        );
+    }
+
+    /// Answers `n` when `n > 0`, with preconditions handled per `mode`
+    async fn guarded_answer(mode: PreconditionMode, n: i64) -> (Value, bool) {
+        let event_bus = Arc::new(EventBus::new(20));
+        let agent_def = MicroAgentDef {
+            name: "guarded".to_string(),
+            answer: Some(AnswerDef {
+                handlers: vec![RequestHandler {
+                    request_type: RequestType::Custom("echo".to_string()),
+                    parameters: vec![Parameter {
+                        name: "n".to_string(),
+                        type_info: TypeInfo::Simple("Int".to_string()),
+                        constraint: None,
+                    }],
+                    return_type: TypeInfo::Simple("Int".to_string()),
+                    requires: Some(Expression::BinaryOp {
+                        op: BinaryOperator::GreaterThan,
+                        left: Box::new(Expression::Variable("n".into())),
+                        right: Box::new(Expression::Literal(Literal::Integer(0))),
+                    }),
+                    constraints: None,
+                    block: HandlerBlock {
+                        statements: vec![Statement::Return(Expression::Variable("n".into()))],
+                    },
+                    doc: None,
+                    cache: None,
+                }],
+            }),
+            ..Default::default()
+        };
+        let agent = RuntimeAgentData::new(
+            &agent_def,
+            &event_bus,
+            AgentConfig {
+                preconditions: mode,
+                ..Default::default()
+            },
+            AgentResources::default(),
+            vec![],
+            WorldPreamble::default(),
+        )
+        .await
+        .unwrap();
+        let shutdown_rx = broadcast::channel(1).1;
+        let sender_agent = TestAgent::new("test", &event_bus);
+        tokio::spawn(async move {
+            agent.run(shutdown_rx).await.unwrap();
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let request_id = Uuid::new_v4().to_string();
+        event_bus
+            .publish(Event {
+                event_type: EventType::Request {
+                    request_type: "echo".into(),
+                    requester: "test".into(),
+                    responder: "guarded".into(),
+                    request_id: request_id.clone(),
+                },
+                parameters: HashMap::from([("n".to_string(), Value::Integer(n))]),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let failed = sender_agent
+            .responses
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|e| matches!(e.event_type, EventType::ResponseFailure { .. }));
+        (sender_agent.get_response(&request_id), failed)
+    }
+
+    #[tokio::test]
+    async fn test_answer_handler_preconditions() {
+        // 前提条件を満たせば本体が実行される
+        let (response, failed) = guarded_answer(PreconditionMode::Fail, 3).await;
+        assert_eq!(response, Value::Integer(3));
+        assert!(!failed);
+
+        let (response, failed) = guarded_answer(PreconditionMode::Fail, -1).await;
+        assert!(failed);
+        assert!(
+            matches!(response, Value::String(error) if error.contains("did not meet the requires clause of echo"))
+        );
+
+        let (response, failed) = guarded_answer(PreconditionMode::Skip, -1).await;
+        assert_eq!(response, Value::Null);
+        assert!(!failed);
+    }
+
+    #[tokio::test]
+    async fn test_react_handler() {
+        let event_bus = Arc::new(EventBus::new(20));
+        let react_def = &MicroAgentDef {
+            name: "reactor".to_string(),
+            state: Some(StateDef {
+                variables: {
+                    let mut vars = HashMap::new();
+                    vars.insert(
+                        "event_count".to_string(),
+                        StateVarDef {
+                            name: "event_count".to_string(),
+                            type_info: TypeInfo::Simple("i64".to_string()),
+                            initial_value: Some(Expression::Literal(Literal::Integer(0))),
+                            doc: None,
+                        },
+                    );
+                    vars.insert(
+                        "last_value".to_string(),
+                        StateVarDef {
+                            name: "last_value".to_string(),
+                            type_info: TypeInfo::Simple("i64".to_string()),
+                            initial_value: Some(Expression::Literal(Literal::Integer(0))),
+                            doc: None,
+                        },
+                    );
+                    vars
+                },
+            }),
+            react: Some(ReactDef {
+                handlers: vec![EventHandler {
+                    event_type: ast::EventType::Message {
+                        content_type: "update".into(),
+                    },
+                    parameters: vec![Parameter {
+                        name: "value".to_string(),
+                        type_info: TypeInfo::Simple("i64".to_string()),
+                        constraint: None,
+                    }],
+                    requires: None,
+                    block: HandlerBlock {
+                        statements: vec![
+                            Statement::Assignment {
+                                target: vec![Expression::StateAccess(StateAccessPath(vec![
+                                    "event_count".into(),
+                                ]))],
+                                value: Expression::BinaryOp {
+                                    op: BinaryOperator::Add,
+                                    left: Box::new(Expression::StateAccess(StateAccessPath(vec![
+                                        "event_count".into(),
+                                    ]))),
+                                    right: Box::new(Expression::Literal(Literal::Integer(1))),
+                                },
+                            },
+                            Statement::Assignment {
+                                target: vec![Expression::StateAccess(StateAccessPath(vec![
+                                    "last_value".into(),
+                                ]))],
+                                value: Expression::Variable("value".into()),
+                            },
+                        ],
+                    },
+                    doc: None,
+                    replay: None,
+                }],
+            }),
+            ..Default::default()
+        };
+
+        let agent = RuntimeAgentData::new(
+            react_def,
+            &event_bus,
+            AgentConfig::default(),
+            AgentResources::default(),
+            vec![],
+            WorldPreamble::default(),
+        )
+        .await
+        .unwrap();
+        let context = agent.base_context.clone();
+        let shutdown_rx = broadcast::channel(1).1;
+
+        tokio::spawn(async move {
+            agent.run(shutdown_rx).await.unwrap();
+        });
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // カスタムイベントを送信
+        event_bus
+            .publish(Event {
+                event_type: EventType::Message {
+                    content_type: "update".into(),
+                },
+                parameters: {
+                    let mut params = HashMap::new();
+                    params.insert("value".to_string(), Value::Integer(42));
+                    params
+                },
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
 
         let last_value = context.get_state("last_value").await.unwrap();
         assert_eq!(last_value, expression::Value::Integer(42));
@@ -1190,4 +2748,445 @@ mod tests {
         let event_count = context.get_state("event_count").await.unwrap();
         assert_eq!(event_count, expression::Value::Integer(1));
     }
+
+    #[tokio::test]
+    async fn test_optional_event_parameters() {
+        let event_bus = Arc::new(EventBus::new(20));
+        let parameters = vec![
+            Parameter {
+                name: "title".to_string(),
+                type_info: TypeInfo::Simple("String".to_string()),
+                constraint: None,
+            },
+            Parameter {
+                name: "note".to_string(),
+                type_info: TypeInfo::Option(Box::new(TypeInfo::Simple("String".to_string()))),
+                constraint: None,
+            },
+        ];
+        let state_var = |name: &str| StateVarDef {
+            name: name.to_string(),
+            type_info: TypeInfo::Simple("String".to_string()),
+            initial_value: Some(Expression::Literal(Literal::String(String::new()))),
+            doc: None,
+        };
+        let assign = |state: &str, variable: &str| Statement::Assignment {
+            target: vec![Expression::StateAccess(StateAccessPath(vec![state.into()]))],
+            value: Expression::Variable(variable.into()),
+        };
+        let notebook_def = &MicroAgentDef {
+            name: "notebook".to_string(),
+            state: Some(StateDef {
+                variables: HashMap::from([
+                    ("last_title".to_string(), state_var("last_title")),
+                    ("last_note".to_string(), state_var("last_note")),
+                ]),
+            }),
+            observe: Some(ObserveDef {
+                handlers: vec![EventHandler {
+                    event_type: ast::EventType::Custom("NoteAdded".into()),
+                    parameters: parameters.clone(),
+                    requires: None,
+                    block: HandlerBlock {
+                        statements: vec![
+                            assign("last_title", "title"),
+                            assign("last_note", "note"),
+                        ],
+                    },
+                    doc: None,
+                    replay: None,
+                }],
+            }),
+            ..Default::default()
+        };
+
+        let agent = RuntimeAgentData::new(
+            notebook_def,
+            &event_bus,
+            AgentConfig::default(),
+            AgentResources::default(),
+            vec![],
+            WorldPreamble::default(),
+        )
+        .await
+        .unwrap();
+        let context = agent.base_context.clone();
+        let shutdown_rx = broadcast::channel(1).1;
+        tokio::spawn(async move {
+            agent.run(shutdown_rx).await.unwrap();
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let note_added = |params: &[(&str, &str)]| Event {
+            event_type: EventType::Custom("NoteAdded".into()),
+            parameters: params
+                .iter()
+                .map(|(name, value)| (name.to_string(), Value::String(value.to_string())))
+                .collect(),
+            ..Default::default()
+        };
+
+        // 省略可能なパラメータあり
+        event_bus
+            .publish(note_added(&[("title", "todo"), ("note", "buy milk")]))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(
+            context.get_state("last_note").await.unwrap(),
+            expression::Value::String("buy milk".into())
+        );
+
+        // 省略可能なパラメータなし: null が束縛される
+        event_bus
+            .publish(note_added(&[("title", "later")]))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(
+            context.get_state("last_title").await.unwrap(),
+            expression::Value::String("later".into())
+        );
+        assert_eq!(
+            context.get_state("last_note").await.unwrap(),
+            expression::Value::Null
+        );
+
+        // 必須パラメータなし: エラー
+        let result = RuntimeAgentData::bind_parameters(
+            &context,
+            &parameters,
+            &note_added(&[("note", "orphan")]),
+        )
+        .await;
+        assert!(
+            matches!(&result, Err(RuntimeError::EvaluationFailed(message)) if message.contains("title")),
+            "{:?}",
+            result
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_requests_do_not_starve_other_agents() {
+        use crate::background_tasks::BackgroundTasks;
+        use crate::config::{SchedulerConfig, SchedulingPolicy};
+
+        let event_bus = Arc::new(EventBus::new(200));
+        let tasks = BackgroundTasks::new();
+        let scheduler = RequestScheduler::start(
+            &SchedulerConfig {
+                policy: SchedulingPolicy::RoundRobin,
+                workers: 2,
+                ..Default::default()
+            },
+            &tasks,
+        );
+        let (done_tx, mut done_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+
+        for name in ["busy", "quiet"] {
+            let mut agent = RuntimeAgentData::new(
+                &MicroAgentDef {
+                    name: name.to_string(),
+                    ..Default::default()
+                },
+                &event_bus,
+                AgentConfig::default(),
+                AgentResources::default(),
+                vec![],
+                WorldPreamble::default(),
+            )
+            .await
+            .unwrap()
+            .with_scheduler(scheduler.clone());
+            let done_tx = done_tx.clone();
+            agent.register_answer(
+                "work",
+                Box::new(move |_| {
+                    let done_tx = done_tx.clone();
+                    Box::pin(async move {
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        let _ = done_tx.send(name.to_string());
+                        Ok(())
+                    })
+                }),
+            );
+            let shutdown_rx = broadcast::channel(1).1;
+            tokio::spawn(async move {
+                agent.run(shutdown_rx).await.unwrap();
+            });
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let request = |responder: &str| Event {
+            event_type: EventType::Request {
+                request_type: "work".into(),
+                requester: "test".into(),
+                responder: responder.into(),
+                request_id: Uuid::new_v4().to_string(),
+            },
+            parameters: HashMap::new(),
+            ..Default::default()
+        };
+        // 40 x 50ms on 2 workers: ~1s if "quiet" had to wait behind the flood
+        for _ in 0..40 {
+            event_bus.publish(request("busy")).await.unwrap();
+        }
+        let started = std::time::Instant::now();
+        event_bus.publish(request("quiet")).await.unwrap();
+
+        while done_rx.recv().await.unwrap() != "quiet" {}
+        let elapsed = started.elapsed();
+        assert!(elapsed < Duration::from_millis(400), "quiet: {:?}", elapsed);
+        tasks.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_requests_run_the_handler_once() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        use crate::background_tasks::BackgroundTasks;
+        use crate::config::{SchedulerConfig, SchedulingPolicy};
+        use crate::request_manager::RequestManager;
+
+        let event_bus = Arc::new(EventBus::new(100));
+        let tasks = BackgroundTasks::new();
+        let scheduler = RequestScheduler::start(
+            &SchedulerConfig {
+                policy: SchedulingPolicy::RoundRobin,
+                ..Default::default()
+            },
+            &tasks,
+        );
+        let mut agent = RuntimeAgentData::new(
+            &MicroAgentDef {
+                name: "Planner".to_string(),
+                ..Default::default()
+            },
+            &event_bus,
+            AgentConfig::default(),
+            AgentResources::default(),
+            vec![],
+            WorldPreamble::default(),
+        )
+        .await
+        .unwrap()
+        .with_scheduler(scheduler);
+        let runs = Arc::new(AtomicUsize::new(0));
+        let handler_runs = runs.clone();
+        let handler_bus = event_bus.clone();
+        agent.register_answer(
+            "plan",
+            Box::new(move |event| {
+                let runs = handler_runs.clone();
+                let event_bus = handler_bus.clone();
+                let event = event.clone();
+                Box::pin(async move {
+                    let run = runs.fetch_add(1, Ordering::SeqCst) + 1;
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    let EventType::Request {
+                        request_type,
+                        requester,
+                        responder,
+                        request_id,
+                    } = event.event_type
+                    else {
+                        unreachable!();
+                    };
+                    let response = Event::response_builder()
+                        .success()
+                        .request_type(&request_type)
+                        .requester(&requester)
+                        .responder(&responder)
+                        .request_id(&request_id)
+                        .response(Value::Integer(run as i64))
+                        .build()
+                        .unwrap();
+                    event_bus.publish(response).await?;
+                    Ok(())
+                })
+            }),
+        );
+        let shutdown_rx = broadcast::channel(1).1;
+        tokio::spawn(async move {
+            agent.run(shutdown_rx).await.unwrap();
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // クライアントの再送を模して、2 つの要求元から同じリクエストを送る
+        let callers: Vec<Arc<RequestManager>> = (0..2)
+            .map(|_| {
+                let manager = Arc::new(RequestManager::new(
+                    event_bus.clone(),
+                    Duration::from_secs(5),
+                ));
+                let manager_ref = manager.clone();
+                let (mut event_rx, _) = event_bus.subscribe();
+                tokio::spawn(async move {
+                    while let Ok(event) = event_rx.recv().await {
+                        let _ = manager_ref.handle_event(&event);
+                    }
+                });
+                manager
+            })
+            .collect();
+        let (mut events, _) = event_bus.subscribe();
+        let request = Event::request_builder()
+            .request_type("plan")
+            .requester("client")
+            .responder("Planner")
+            .request_id("plan-1")
+            .build()
+            .unwrap();
+
+        let (first, second) =
+            tokio::join!(callers[0].request(&request), callers[1].request(&request));
+        let (first, second) = (first.unwrap(), second.unwrap());
+        assert_eq!(first.parameters.get("response"), Some(&Value::Integer(1)));
+        assert_eq!(first, second);
+
+        // 実行中に届いた再送にも同じ応答が送られる
+        let mut responses = Vec::new();
+        while let Ok(Ok(event)) =
+            tokio::time::timeout(Duration::from_millis(100), events.recv()).await
+        {
+            if event.event_type.is_response_to("plan-1") {
+                responses.push(event);
+            }
+        }
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0], responses[1]);
+
+        // 応答後の再送はその応答で答える
+        let late = callers[0].request(&request).await.unwrap();
+        assert_eq!(late.parameters.get("response"), Some(&Value::Integer(1)));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        tasks.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_persisted_requests_run_once_after_restart() {
+        use crate::background_tasks::BackgroundTasks;
+        use crate::config::{RequestQueueConfig, SchedulerConfig, SchedulingPolicy};
+        use crate::provider::capabilities::storage::StorageBackend;
+        use crate::provider::config::plugins::LocalFileSystemConfig;
+        use crate::provider::plugins::storage::local_fs::LocalFileSystemBackend;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let backend: Arc<dyn StorageBackend> =
+            Arc::new(LocalFileSystemBackend::new(LocalFileSystemConfig {
+                base_dir: dir.path().to_string_lossy().to_string(),
+                file_extension: "json".to_string(),
+            }));
+        let queue_config = RequestQueueConfig::default();
+        let handled = Arc::new(Mutex::new(Vec::<String>::new()));
+        let new_agent = |event_bus: Arc<EventBus>| {
+            let handled = handled.clone();
+            async move {
+                let mut agent = RuntimeAgentData::new(
+                    &MicroAgentDef {
+                        name: "worker".to_string(),
+                        ..Default::default()
+                    },
+                    &event_bus,
+                    AgentConfig::default(),
+                    AgentResources::default(),
+                    vec![],
+                    WorldPreamble::default(),
+                )
+                .await
+                .unwrap();
+                agent.register_answer(
+                    "work",
+                    Box::new(move |event: &Event| {
+                        let handled = handled.clone();
+                        let request_id = event.event_type.request_id().unwrap().to_string();
+                        Box::pin(async move {
+                            handled.lock().unwrap().push(request_id);
+                            Ok(())
+                        })
+                    }),
+                );
+                agent
+            }
+        };
+
+        // 1 回目: 唯一のワーカーを塞ぎ、5 件を処理前のまま止める
+        let event_bus = Arc::new(EventBus::new(200));
+        let tasks = BackgroundTasks::new();
+        let scheduler = RequestScheduler::start(
+            &SchedulerConfig {
+                policy: SchedulingPolicy::Fifo,
+                workers: 1,
+                ..Default::default()
+            },
+            &tasks,
+        )
+        .unwrap();
+        scheduler.submit("gate", Box::pin(std::future::pending::<()>()));
+        let queue = RequestQueue::open("worker", backend.clone(), &queue_config)
+            .await
+            .unwrap();
+        let agent = new_agent(event_bus.clone())
+            .await
+            .with_scheduler(Some(scheduler.clone()))
+            .with_request_queue(Some(queue.clone()));
+        let shutdown_rx = broadcast::channel(1).1;
+        let agent_task = tokio::spawn(async move { agent.run(shutdown_rx).await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let request_ids: Vec<String> = (0..5).map(|_| Uuid::new_v4().to_string()).collect();
+        for request_id in &request_ids {
+            event_bus
+                .publish(Event {
+                    event_type: EventType::Request {
+                        request_type: "work".into(),
+                        requester: "test".into(),
+                        responder: "worker".into(),
+                        request_id: request_id.clone(),
+                    },
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+        }
+        while scheduler.pending() < 5 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        queue.sync().await.unwrap();
+        assert_eq!(queue.stats().depth, 5);
+        assert!(handled.lock().unwrap().is_empty());
+
+        // System の破棄に相当: 全タスクを止めて捨てる
+        agent_task.abort();
+        tasks.shutdown().await;
+        drop((queue, scheduler, event_bus));
+
+        // 2 回目: 残った 5 件が元の順で一度ずつ実行される
+        let event_bus = Arc::new(EventBus::new(200));
+        let queue = RequestQueue::open("worker", backend.clone(), &queue_config)
+            .await
+            .unwrap();
+        assert_eq!(
+            queue.stats(),
+            RequestQueueStats {
+                depth: 5,
+                reenqueued: 5
+            }
+        );
+        let agent = new_agent(event_bus.clone())
+            .await
+            .with_request_queue(Some(queue.clone()));
+        let shutdown_rx = broadcast::channel(1).1;
+        let agent_task = tokio::spawn(async move { agent.run(shutdown_rx).await });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        assert_eq!(*handled.lock().unwrap(), request_ids);
+        assert_eq!(queue.stats().depth, 0);
+        agent_task.abort();
+
+        let reopened = RequestQueue::open("worker", backend, &queue_config)
+            .await
+            .unwrap();
+        assert!(reopened.take_recovered().is_empty());
+    }
 }
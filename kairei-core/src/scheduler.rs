@@ -0,0 +1,251 @@
+//! # Request Scheduler
+//!
+//! By default every agent runs its answer handlers inline, one at a time, on its own
+//! event loop task. With a pooled [`SchedulingPolicy`] the agents instead hand their
+//! requests to a shared [`RequestScheduler`], whose fixed set of workers bounds how
+//! many requests run at once across the whole system.
+//!
+//! The pooled policies differ in how the next request is picked:
+//! - `Fifo`: strictly in arrival order, so a long queue for one agent delays every
+//!   request that arrives after it
+//! - `RoundRobin`: agents with pending requests take turns, one request per turn
+//! - `WeightedFair`: agents take turns, serving up to their configured weight per turn
+//!
+//! Workers are spawned through [`BackgroundTasks`], so they stop with the System.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use futures::future::BoxFuture;
+use tokio::sync::Notify;
+
+use crate::{
+    background_tasks::BackgroundTasks,
+    config::{SchedulerConfig, SchedulingPolicy},
+};
+
+/// A unit of work submitted on behalf of an agent
+pub type Job = BoxFuture<'static, ()>;
+
+/// Shared worker pool for agent requests. Clones share the same pool.
+#[derive(Clone)]
+pub struct RequestScheduler {
+    inner: Arc<SchedulerInner>,
+}
+
+struct SchedulerInner {
+    policy: SchedulingPolicy,
+    weights: HashMap<String, u32>,
+    queues: Mutex<JobQueues>,
+    notify: Notify,
+}
+
+#[derive(Default)]
+struct JobQueues {
+    /// Fifo: all jobs in arrival order
+    fifo: VecDeque<Job>,
+    /// RoundRobin / WeightedFair: pending jobs per agent
+    per_agent: HashMap<String, VecDeque<Job>>,
+    /// Agents with pending jobs, in turn order
+    turns: VecDeque<String>,
+    /// Jobs served in the current turn of the agent at the front of `turns`
+    served_in_turn: u32,
+}
+
+impl RequestScheduler {
+    /// Start a scheduler with `config.workers` workers.
+    /// Returns `None` for [`SchedulingPolicy::Inline`], where no pool is used.
+    pub fn start(config: &SchedulerConfig, tasks: &BackgroundTasks) -> Option<Self> {
+        if config.policy == SchedulingPolicy::Inline {
+            return None;
+        }
+        let scheduler = Self {
+            inner: Arc::new(SchedulerInner {
+                policy: config.policy,
+                weights: config.weights.clone(),
+                queues: Mutex::new(JobQueues::default()),
+                notify: Notify::new(),
+            }),
+        };
+        for _ in 0..config.workers.max(1) {
+            let inner = scheduler.inner.clone();
+            tasks.spawn(async move {
+                loop {
+                    // 取り出しより先に待機を登録し、通知の取りこぼしを防ぐ
+                    let notified = inner.notify.notified();
+                    match inner.next_job() {
+                        Some(job) => job.await,
+                        None => notified.await,
+                    }
+                }
+            });
+        }
+        Some(scheduler)
+    }
+
+    pub fn policy(&self) -> SchedulingPolicy {
+        self.inner.policy
+    }
+
+    /// Queue `job` for `agent_name`
+    pub fn submit(&self, agent_name: &str, job: Job) {
+        {
+            let mut queues = self.inner.queues.lock().unwrap();
+            if self.inner.policy == SchedulingPolicy::Fifo {
+                queues.fifo.push_back(job);
+            } else {
+                let queue = queues.per_agent.entry(agent_name.to_string()).or_default();
+                queue.push_back(job);
+                if queue.len() == 1 {
+                    queues.turns.push_back(agent_name.to_string());
+                }
+            }
+        }
+        self.inner.notify.notify_one();
+    }
+
+    /// Number of jobs waiting for a worker
+    pub fn pending(&self) -> usize {
+        let queues = self.inner.queues.lock().unwrap();
+        queues.fifo.len() + queues.per_agent.values().map(VecDeque::len).sum::<usize>()
+    }
+}
+
+impl SchedulerInner {
+    fn next_job(&self) -> Option<Job> {
+        let mut queues = self.queues.lock().unwrap();
+        if self.policy == SchedulingPolicy::Fifo {
+            return queues.fifo.pop_front();
+        }
+
+        let agent = queues.turns.front()?.clone();
+        let queue = queues.per_agent.get_mut(&agent)?;
+        let job = queue.pop_front();
+        let remaining = queue.len();
+        if remaining == 0 {
+            queues.per_agent.remove(&agent);
+        }
+
+        queues.served_in_turn += 1;
+        let quota = match self.policy {
+            SchedulingPolicy::WeightedFair => self.weights.get(&agent).copied().unwrap_or(1).max(1),
+            _ => 1,
+        };
+        if remaining == 0 || queues.served_in_turn >= quota {
+            queues.turns.pop_front();
+            queues.served_in_turn = 0;
+            if remaining > 0 {
+                queues.turns.push_back(agent);
+            }
+        }
+        job
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    fn config(policy: SchedulingPolicy, workers: usize) -> SchedulerConfig {
+        SchedulerConfig {
+            policy,
+            workers,
+            ..Default::default()
+        }
+    }
+
+    /// Flood agent "busy" with slow jobs, then submit one job for agent "other"
+    /// and return how long it took to complete.
+    async fn latency_behind_flood(policy: SchedulingPolicy) -> Duration {
+        let tasks = BackgroundTasks::new();
+        let scheduler = RequestScheduler::start(&config(policy, 2), &tasks).unwrap();
+        for _ in 0..40 {
+            // sleep の期限は生成時に決まるため、実行時に生成する
+            scheduler.submit(
+                "busy",
+                Box::pin(async { tokio::time::sleep(Duration::from_millis(50)).await }),
+            );
+        }
+
+        let (tx, mut rx) = mpsc::channel(1);
+        let submitted = Instant::now();
+        scheduler.submit(
+            "other",
+            Box::pin(async move {
+                let _ = tx.send(()).await;
+            }),
+        );
+        rx.recv().await.unwrap();
+        let elapsed = submitted.elapsed();
+        tasks.shutdown().await;
+        elapsed
+    }
+
+    #[tokio::test]
+    async fn test_round_robin_does_not_starve_other_agents() {
+        // 40 jobs x 50ms on 2 workers: FIFO waits ~1s behind the flood
+        let fifo = latency_behind_flood(SchedulingPolicy::Fifo).await;
+        assert!(fifo >= Duration::from_millis(500), "fifo: {:?}", fifo);
+
+        let round_robin = latency_behind_flood(SchedulingPolicy::RoundRobin).await;
+        assert!(
+            round_robin < Duration::from_millis(300),
+            "round robin: {:?}",
+            round_robin
+        );
+
+        let weighted = latency_behind_flood(SchedulingPolicy::WeightedFair).await;
+        assert!(
+            weighted < Duration::from_millis(300),
+            "weighted fair: {:?}",
+            weighted
+        );
+    }
+
+    #[tokio::test]
+    async fn test_weighted_fair_serves_by_weight() {
+        let mut config = config(SchedulingPolicy::WeightedFair, 1);
+        config.weights.insert("a".to_string(), 2);
+        let tasks = BackgroundTasks::new();
+
+        // ワーカーを塞いでいる間にキューを積む
+        let (release_tx, release_rx) = tokio::sync::oneshot::channel::<()>();
+        let scheduler = RequestScheduler::start(&config, &tasks).unwrap();
+        scheduler.submit(
+            "gate",
+            Box::pin(async move {
+                let _ = release_rx.await;
+            }),
+        );
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        for agent in ["a", "a", "a", "a", "b", "b"] {
+            let order = order.clone();
+            scheduler.submit(
+                agent,
+                Box::pin(async move {
+                    order.lock().unwrap().push(agent);
+                }),
+            );
+        }
+        release_tx.send(()).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert_eq!(*order.lock().unwrap(), vec!["a", "a", "b", "a", "a", "b"]);
+        assert_eq!(scheduler.pending(), 0);
+        tasks.shutdown().await;
+    }
+
+    #[test]
+    fn test_inline_policy_has_no_pool() {
+        let tasks = BackgroundTasks::new();
+        assert!(RequestScheduler::start(&SchedulerConfig::default(), &tasks).is_none());
+    }
+}
@@ -3,7 +3,7 @@ use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
     sync::Arc,
     time::{Duration, Instant},
 };
@@ -12,30 +12,69 @@ use tokio::{
     sync::{RwLock, broadcast},
     time::sleep,
 };
-use tracing::debug;
+use tracing::{debug, info, warn};
 use utoipa::ToSchema;
 use uuid::Uuid;
 
+use crate::agent_log::{AgentLoggers, CapturedLog};
 use crate::agent_registry::AgentError;
+use crate::agent_settings::{AgentSettings, AgentSettingsPatch, AgentSettingsStore};
+use crate::background_tasks::BackgroundTasks;
+use crate::catalog::{AgentCatalog, AgentEntry};
+use crate::clock::{Clock, SystemClock};
 use crate::config::SecretConfig;
+use crate::config_values::{self, AgentConfigError};
 use crate::context::AGENT_TYPE_CUSTOM_ALL;
-use crate::event_bus::EventError;
-use crate::native_feature::types::FeatureError;
+use crate::debug_eval::{self, DebugEvalError, DebugEvaluation};
+use crate::debug_session::{DebugSession, DebugSessionError, DebugSessions, DebugStep};
+use crate::eval::recording::{HandlerRecording, HandlerRecordings};
+use crate::event::dispatch::DispatchIndex;
+use crate::event::journal::{EventJournal, ReplayReport};
+use crate::event::lineage::{LineageNode, LineageRecord};
+use crate::event_bus::{EventError, EventResult};
+use crate::handler_test::{self, HandlerTest, HandlerTestError, HandlerTestReport, TestedAgent};
+use crate::idle_eviction::{IdleTracker, SuspendedStateStore};
+use crate::message_catalog::MessageCatalog;
+use crate::native_feature::types::{FeatureError, NativeFeature, NativeFeatureType};
+use crate::native_feature::world_scheduler::{ScheduleStatus, WorldScheduler};
+use crate::provider::capabilities::sistence_memory::SistenceMemoryCapability;
+use crate::provider::capabilities::storage::{StorageBackend, StorageError};
+use crate::provider::config::plugins::{InMemoryConfig, LocalFileSystemConfig};
+use crate::provider::metrics::LlmMetrics;
+use crate::provider::plugins::memory::persistent_shared_memory::PersistentSharedMemoryPlugin;
+use crate::provider::plugins::memory::shared_counters::SharedCounters;
+use crate::provider::plugins::openapi_tools::ToolRegistry;
+use crate::provider::plugins::storage::{
+    in_memory::InMemoryBackend, local_fs::LocalFileSystemBackend,
+};
 use crate::provider::provider::ProviderType;
 use crate::provider::provider_registry::{ProviderInstance, ProviderRegistry};
-use crate::provider::types::ProviderError;
-use crate::request_manager::{RequestError, RequestManager};
+use crate::provider::types::{CredentialStatus, ProviderError};
+use crate::reload_plan::{ReloadPlan, state_type};
+use crate::request_manager::{RequestError, RequestManager, ResponseStream};
+use crate::request_queue::{RequestQueue, RequestQueueStats};
+use crate::response_cache::{ResponseCacheStats, ResponseCaches};
 use crate::runtime::RuntimeError;
+use crate::scheduler::RequestScheduler;
+use crate::secret_scan::{SecretFinding, SecretScanner, findings_message};
 use crate::{
     ASTError, CustomEventDef, EventsDef, MicroAgentDef,
     agent_registry::AgentRegistry,
     ast_registry::AstRegistry,
-    config::{AgentConfig, SystemConfig},
-    eval::{context::AgentType, expression},
+    config::{AgentConfig, AgentLogConfig, EventValidationMode, SystemConfig},
+    eval::{
+        budget::LlmBudgetStats,
+        context::{AgentType, WorldPreamble},
+        evaluator::ConstraintViolation,
+        expression,
+        profile::RequestProfile,
+        secret::SecretVault,
+    },
+    event::coercion,
     event_bus::{Event, EventBus, EventReceiver, LastStatus, Value},
     event_registry::{EventInfo, EventRegistry, EventType, ParameterType},
     native_feature::{native_registry::NativeFeatureRegistry, types::NativeFeatureContext},
-    runtime::RuntimeAgentData,
+    runtime::{AgentResources, RuntimeAgentData},
 };
 use crate::{WorldDef, ast};
 
@@ -52,12 +91,46 @@ pub struct System {
     _shutdown_rx: broadcast::Receiver<AgentType>, // シャットダウンシグナルを受信
     // event request/response
     request_manager: Arc<RequestManager>,
+    // send_event で送られたイベントの記録（無効時は None）
+    event_journal: Option<Arc<EventJournal>>,
+    // 初期エージェントの登録時に渡す、前回までに記録されたイベント
+    journal_replay: std::sync::Mutex<Vec<Event>>,
+    // observe / react ハンドラの実行の記録（無効時は None）
+    handler_recordings: Option<Arc<HandlerRecordings>>,
+    // 記録されたハンドラの実行をステップ実行するセッション
+    debug_sessions: DebugSessions,
     filtered_subscriptions: Arc<DashMap<Vec<EventType>, broadcast::Sender<Event>>>, // Vec<EventType>は Sorted　である必要がある
     // metrics
     started_at: DateTime<Utc>,
     uptime_instant: Instant,
     last_status: Arc<RwLock<LastStatus>>,
     config: Arc<RwLock<SystemConfig>>,
+    // System が起動したバックグラウンドタスク（shutdown/Drop で停止する）
+    background_tasks: BackgroundTasks,
+    // 全エージェントで共有する既定のプロンプト前置き
+    world_preamble: WorldPreamble,
+    // answer ハンドラの共有ワーカープール（Inline では None）
+    scheduler: Option<RequestScheduler>,
+    // リクエストのないエージェントの退避（無効時は None）
+    idle_tracker: Option<Arc<IdleTracker>>,
+    // secret_grants で許可されたエージェントが secret("name") で読めるシークレット
+    handler_secrets: Arc<HashMap<String, String>>,
+    // エージェントと受け付けるリクエストの一覧。登録・スケール・AST の再登録で更新する
+    catalog: Arc<AgentCatalog>,
+    // openapi_tools プラグインが登録したツール。call_tool(name, args) で呼ばれる
+    tools: Arc<ToolRegistry>,
+    // エージェントごとの log 文の出力先。スケールしたインスタンスは元の名前のものを使う
+    agent_loggers: Arc<AgentLoggers>,
+    // エージェントごとの実行中に調整できる think の既定値。スケールしたインスタンスは元の名前のものを使う
+    agent_settings: Arc<AgentSettingsStore>,
+    // increment(...) / try_acquire(...) で全エージェントが共有するカウンター
+    shared_counters: Arc<SharedCounters>,
+    // イベントをハンドラのあるエージェントだけに届ける振り分け表。実行中のエージェントが登録する
+    dispatch: Arc<DispatchIndex>,
+    // world の schedule 宣言を発火するネイティブ機能
+    world_scheduler: WorldScheduler,
+    // @cache を宣言した answer ハンドラの応答。エージェントの再読み込みで空にする
+    response_caches: Arc<ResponseCaches>,
 }
 
 impl System {
@@ -65,19 +138,42 @@ impl System {
     pub async fn new(config: &SystemConfig, secret_config: &SecretConfig) -> Self {
         let capacity = config.event_buffer_size;
         let (shutdown_tx, _) = broadcast::channel::<AgentType>(1); // 容量は1で十分
+        let background_tasks = BackgroundTasks::new();
         let event_registry = Arc::new(RwLock::new(EventRegistry::new()));
-        let event_bus = Arc::new(EventBus::new(capacity));
-        let agent_registry = Arc::new(tokio::sync::RwLock::new(AgentRegistry::new(
-            &config.agent_config,
-            &shutdown_tx,
-        )));
-        let ast_registry = Arc::new(RwLock::new(AstRegistry::default()));
-        let native_context = Arc::new(NativeFeatureContext::new(event_bus.clone()));
+        let critical_store = config.critical_delivery.base_dir.clone().map(|base_dir| {
+            Arc::new(LocalFileSystemBackend::new(LocalFileSystemConfig {
+                base_dir,
+                file_extension: "json".to_string(),
+            })) as Arc<dyn StorageBackend>
+        });
+        let event_bus = Arc::new(
+            EventBus::new(capacity)
+                .with_critical_delivery(config.critical_delivery.clone(), critical_store),
+        );
+        let agent_registry = Arc::new(tokio::sync::RwLock::new(
+            AgentRegistry::new(&config.agent_config, &shutdown_tx)
+                .with_background_tasks(background_tasks.clone()),
+        ));
+        let ast_registry = Arc::new(RwLock::new(
+            AstRegistry::default().with_limits(config.analysis_limits.clone()),
+        ));
+        let native_context = Arc::new(
+            NativeFeatureContext::new(event_bus.clone())
+                .with_background_tasks(background_tasks.clone()),
+        );
 
         let feature_registry = Arc::new(RwLock::new(NativeFeatureRegistry::new(
             native_context.clone(),
             config.native_feature_config.clone(),
         )));
+        let world_scheduler = WorldScheduler::new(
+            native_context.clone(),
+            config
+                .native_feature_config
+                .schedules
+                .clone()
+                .unwrap_or_default(),
+        );
         let _shutdown_rx = shutdown_tx.subscribe();
         let request_manager = Arc::new(RequestManager::new(
             event_bus.clone(),
@@ -86,19 +182,23 @@ impl System {
         let request_manager_ref = request_manager.clone();
         let mut event_rx = event_bus.subscribe().0;
         let filtered_subscriptions = Arc::new(DashMap::new());
+        let catalog = Arc::new(AgentCatalog::default());
+        let tools = Arc::new(ToolRegistry::default());
         let provider_registry = Arc::new(RwLock::new(
             ProviderRegistry::new(
                 config.provider_configs.clone(),
                 secret_config.clone(),
                 event_bus.clone(),
             )
-            .await,
+            .await
+            .with_catalog(catalog.clone())
+            .with_tools(tools.clone()),
         ));
 
         // Receive response.
-        tokio::spawn(async move {
+        background_tasks.spawn(async move {
             while let Ok(event) = event_rx.recv().await {
-                if event.event_type.is_response() {
+                if event.event_type.is_response() || event.event_type.is_partial_response() {
                     debug!(
                         "Recv system response: request_id: {:?}",
                         event.event_type.request_id()
@@ -108,6 +208,40 @@ impl System {
             }
         });
 
+        let scheduler = RequestScheduler::start(&config.scheduler, &background_tasks);
+        let event_journal = config.event_journal.enabled.then(|| {
+            let store: Arc<dyn StorageBackend> = match config.event_journal.base_dir.clone() {
+                Some(base_dir) => Arc::new(LocalFileSystemBackend::new(LocalFileSystemConfig {
+                    base_dir,
+                    file_extension: "json".to_string(),
+                })),
+                None => Arc::new(InMemoryBackend::new(InMemoryConfig::default())),
+            };
+            Arc::new(EventJournal::new(store))
+        });
+        let handler_recordings = config
+            .handler_recording
+            .enabled
+            .then(|| Arc::new(HandlerRecordings::new(&config.handler_recording)));
+        let dispatch = DispatchIndex::start(&event_bus, &background_tasks);
+        let shared_counters = Arc::new(match &config.shared_counters {
+            Some(memory_config) => SharedCounters::new(Arc::new(
+                PersistentSharedMemoryPlugin::new(memory_config.clone()).await,
+            )),
+            None => SharedCounters::default(),
+        });
+        let response_caches = ResponseCaches::new(config.response_cache.clone());
+        let response_caches = Arc::new(match &config.response_cache.memory {
+            Some(memory_config) => response_caches.with_memory(Arc::new(
+                PersistentSharedMemoryPlugin::new(memory_config.clone()).await,
+            )),
+            None => response_caches,
+        });
+        let idle_tracker = config
+            .idle_eviction
+            .idle_timeout
+            .map(|idle_timeout| Arc::new(IdleTracker::new(idle_timeout, Arc::new(SystemClock))));
+
         let started_at = Utc::now();
         let uptime_instant = Instant::now();
 
@@ -126,15 +260,49 @@ impl System {
             shutdown_tx,
             _shutdown_rx,
             request_manager,
+            event_journal,
+            journal_replay: std::sync::Mutex::new(vec![]),
+            handler_recordings,
+            debug_sessions: DashMap::new(),
             filtered_subscriptions,
             started_at,
             uptime_instant,
             last_status,
             config: Arc::new(RwLock::new(config.clone())),
+            background_tasks,
+            world_preamble: WorldPreamble::new(config.prompt_preamble.clone()),
+            scheduler,
+            idle_tracker,
+            handler_secrets: Arc::new(secret_config.handler_secrets.clone()),
+            catalog,
+            tools,
+            agent_loggers: Arc::new(AgentLoggers::default()),
+            agent_settings: Arc::new(AgentSettingsStore::default()),
+            shared_counters,
+            dispatch,
+            world_scheduler,
+            response_caches,
         }
     }
 
+    /// Measure agent idle time, fire world schedules and expire cached responses
+    /// with `clock` instead of the system clock. Call before agents are registered.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.world_scheduler = self.world_scheduler.clone().with_clock(clock.clone());
+        self.response_caches = Arc::new(
+            ResponseCaches::new(self.response_caches.config().clone())
+                .with_memory(self.response_caches.memory())
+                .with_clock(clock.clone()),
+        );
+        self.idle_tracker = self
+            .idle_tracker
+            .take()
+            .map(|tracker| Arc::new(IdleTracker::new(tracker.idle_timeout(), clock)));
+        self
+    }
+
     pub async fn parse_dsl(&self, dsl: &str) -> SystemResult<ast::Root> {
+        self.check_leaked_secrets(Some(dsl)).await?;
         self.ast_registry
             .read()
             .await
@@ -143,8 +311,30 @@ impl System {
             .map_err(SystemError::from)
     }
 
+    /// The message of `error` for users. Parser and type checker errors are
+    /// rendered in [`SystemConfig::locale`], followed by a pointer to the
+    /// explanation of their code; other errors keep their own message.
+    pub async fn render_error(&self, error: &SystemError) -> String {
+        match error {
+            SystemError::Ast(error) => {
+                let catalog = MessageCatalog::new(self.config.read().await.locale);
+                let diagnostic = error.diagnostic();
+                format!(
+                    "{} ({})",
+                    catalog.render(&diagnostic),
+                    catalog.explain_hint(diagnostic.code)
+                )
+            }
+            other => other.to_string(),
+        }
+    }
+
+    /// Registers the world and the agents of `root`. With the event journal enabled,
+    /// the events it recorded are replayed to the initial user agents once they start;
+    /// the report tells what is replayed.
     #[tracing::instrument(skip(self, root))]
-    pub async fn initialize(&mut self, root: ast::Root) -> SystemResult<()> {
+    pub async fn initialize(&mut self, root: ast::Root) -> SystemResult<ReplayReport> {
+        self.check_leaked_secrets(None).await?;
         // call all registration methods
         self.register_native_features()
             .await
@@ -155,13 +345,65 @@ impl System {
         self.register_world(&root.world_def)
             .await
             .map_err(|e| SystemError::Initialization(e.to_string()))?;
+        self.run_world_init(&root.world_def).await?;
+        self.validate_event_references(&root.micro_agent_defs)
+            .await?;
         self.register_builtin_agents()
             .await
             .map_err(|e| SystemError::Initialization(e.to_string()))?;
+        let report = self.load_journal_replay(&root.micro_agent_defs).await?;
         self.register_initial_user_agents(root.micro_agent_defs)
             .await
             .map_err(|e| SystemError::Initialization(e.to_string()))?;
-        Ok(())
+        Ok(report)
+    }
+
+    /// Reads the events to replay from the event journal, if it is enabled and
+    /// replayed on startup, and plans their replay to `agents`
+    async fn load_journal_replay(&self, agents: &[MicroAgentDef]) -> SystemResult<ReplayReport> {
+        let Some(journal) = &self.event_journal else {
+            return Ok(ReplayReport::default());
+        };
+        if !self.config.read().await.event_journal.replay_on_startup {
+            return Ok(ReplayReport::default());
+        }
+        let events = journal.events().await.map_err(SystemError::EventJournal)?;
+        let report = ReplayReport::plan(&events, agents);
+        info!("Replaying {} journaled events", report.events);
+        if !report.unannotated.is_empty() {
+            // 副作用のあるハンドラが二度走らないよう、@replay の付け忘れを知らせる
+            warn!(
+                "Handlers replayed without a @replay directive: {}",
+                report.unannotated.join(", ")
+            );
+        }
+        *self.journal_replay.lock().unwrap() = events;
+        Ok(report)
+    }
+
+    /// Checks that the observe and react handlers of `agents` listen to event types
+    /// the event registry knows. Custom events are always accepted, and the
+    /// `StateUpdated` events of state variables declared by `agents` count as
+    /// registered. Unregistered event types fail or are logged per
+    /// [`EventValidationMode`].
+    pub async fn validate_event_references(&self, agents: &[MicroAgentDef]) -> SystemResult<()> {
+        let unregistered =
+            unregistered_event_references(&*self.event_registry.read().await, agents);
+        if unregistered.is_empty() {
+            return Ok(());
+        }
+
+        match self.config.read().await.event_validation {
+            EventValidationMode::Strict => Err(SystemError::UnregisteredEventTypes {
+                references: unregistered,
+            }),
+            EventValidationMode::Lenient => {
+                for reference in &unregistered {
+                    warn!("Unregistered event type: {}", reference);
+                }
+                Ok(())
+            }
+        }
     }
 
     #[tracing::instrument(skip(self))]
@@ -176,6 +418,13 @@ impl System {
 
         let registry = self.feature_registry.write().await;
         registry.register().await?;
+        // world の読み込み前に登録し、停止は他の機能と一緒に行う
+        registry
+            .register_feature(
+                NativeFeatureType::Scheduler,
+                Arc::new(self.world_scheduler.clone()),
+            )
+            .await?;
         self.update_system_status(complete_state).await;
         debug!("register_native_features ended");
         Ok(())
@@ -191,10 +440,31 @@ impl System {
 
         let registry = self.provider_registry.write().await;
         registry.register_providers().await?;
+        let validation = self.config.read().await.validate_credentials.clone();
+        if validation.enabled {
+            registry.validate_credentials(&validation.required).await?;
+        }
         self.update_system_status(complete_state).await;
         Ok(())
     }
 
+    /// Refuse secrets leaked into the system config, or into `dsl` when given.
+    /// See [`crate::secret_scan`].
+    async fn check_leaked_secrets(&self, dsl: Option<&str>) -> SystemResult<()> {
+        let config = self.config.read().await;
+        let findings = scan_for_secrets(&config, dsl.map(|dsl| (dsl, "dsl")))?;
+        if findings.is_empty() {
+            return Ok(());
+        }
+        if config.secret_scan.warn_only {
+            for finding in &findings {
+                warn!("Possible secret at {}", finding);
+            }
+            return Ok(());
+        }
+        Err(SystemError::Initialization(findings_message(&findings)))
+    }
+
     #[tracing::instrument(skip(self, world_def))]
     pub async fn register_world(&self, world_def: &Option<WorldDef>) -> SystemResult<()> {
         debug!("register_world started");
@@ -213,6 +483,11 @@ impl System {
             def
         };
 
+        if world_def.persona.is_some() {
+            self.world_preamble.set(world_def.persona.clone());
+        }
+        self.world_scheduler.set_schedules(&world_def.schedules)?;
+
         let (agent_def, event_defs): (MicroAgentDef, EventsDef) = world_def.into();
         let name = AgentType::World.to_string();
         self.register_agent_ast(&name, &agent_def).await?;
@@ -227,6 +502,22 @@ impl System {
         Ok(())
     }
 
+    /// Runs the `onInit` block of the world once, after the world and its events
+    /// are registered and before any other agent is. Its `increment(...)`
+    /// counters are the ones the agents share.
+    #[tracing::instrument(skip(self, world_def))]
+    async fn run_world_init(&self, world_def: &Option<WorldDef>) -> SystemResult<()> {
+        let Some(on_init) = world_def.as_ref().and_then(|def| def.on_init.as_ref()) else {
+            return Ok(());
+        };
+        self.agent_registry
+            .read()
+            .await
+            .run_block(&AgentType::World.to_string(), on_init)
+            .await
+            .map_err(|e| SystemError::WorldInit(e.to_string()))
+    }
+
     // ビルトインエージェントの登録処理
     #[tracing::instrument(skip(self))]
     pub async fn register_builtin_agents(&self) -> SystemResult<()> {
@@ -270,9 +561,15 @@ impl System {
             complete_state.clone(),
         )?;
 
+        // 記録されたイベントは初期エージェントにだけ再生する
+        let replayed_events = std::mem::take(&mut *self.journal_replay.lock().unwrap());
         for agent_def in micro_agent_defs {
             self.register_agent_ast(&agent_def.name, &agent_def).await?;
-            self.register_agent(&agent_def.name).await?;
+            let runtime = self
+                .agent_factory()
+                .build(&agent_def.name, None, replayed_events.clone())
+                .await?;
+            self.register_runtime(&agent_def.name, runtime).await?;
         }
         self.update_system_status(complete_state).await;
         debug!("register_initial_user_agents ended");
@@ -294,6 +591,7 @@ impl System {
         self.start_world().await?;
         self.start_builtin_agents().await?;
         self.start_users_agents().await?;
+        self.start_idle_eviction().await;
 
         self.update_system_status(EventType::SystemStarted).await;
         Ok(())
@@ -317,6 +615,7 @@ impl System {
     #[tracing::instrument(skip(self))]
     async fn start_world(&self) -> SystemResult<()> {
         self.start_agent(&AgentType::World.to_string()).await?;
+        self.world_scheduler.start().await?;
         Ok(())
     }
 
@@ -350,6 +649,29 @@ impl System {
         Ok(())
     }
 
+    /// Suspend idle user agents in the background, if configured
+    async fn start_idle_eviction(&self) {
+        let Some(tracker) = self.idle_tracker.clone() else {
+            return;
+        };
+        let config = self.config.read().await.idle_eviction.clone();
+        let backend: Arc<dyn StorageBackend> = match config.base_dir {
+            Some(base_dir) => Arc::new(LocalFileSystemBackend::new(LocalFileSystemConfig {
+                base_dir,
+                file_extension: "json".to_string(),
+            })),
+            None => Arc::new(InMemoryBackend::new(InMemoryConfig::default())),
+        };
+        let evictor = IdleEvictor {
+            factory: self.agent_factory(),
+            agent_registry: self.agent_registry.clone(),
+            tracker,
+            store: SuspendedStateStore::new(backend),
+        };
+        self.background_tasks
+            .spawn(evictor.run(config.check_interval));
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn shutdown(&self) -> SystemResult<()> {
         let shutdown_started = Instant::now();
@@ -375,7 +697,7 @@ impl System {
                     .iter()
                     .filter(|e| {
                         e.last_event_type != EventType::AgentStopped
-                            || e.last_event_type != EventType::AgentRemoved
+                            && e.last_event_type != EventType::AgentRemoved
                     })
                     .count();
                 if not_stopped == 0 {
@@ -399,6 +721,10 @@ impl System {
         registry.shutdown().await?;
 
         self.update_system_status(EventType::SystemStopped).await;
+
+        // バックグラウンドタスクの停止、購読者への配信も終了する
+        self.filtered_subscriptions.clear();
+        self.background_tasks.shutdown().await;
         Ok(())
     }
 
@@ -412,21 +738,43 @@ impl System {
             .send(AgentType::World)
             .expect("Failed to send shutdown signal");
         self.agent_registry.write().await.shutdown_all(1).await?;
+        self.filtered_subscriptions.clear();
+        self.background_tasks.shutdown().await;
         Ok(())
     }
 
+    /// Number of background tasks spawned by this System that are still alive.
+    ///
+    /// Intended for tests and diagnostics of task leaks.
+    #[doc(hidden)]
+    pub fn debug_task_count(&self) -> usize {
+        self.background_tasks.len()
+    }
+
+    /// Names of the running agents `event` is dispatched to.
+    ///
+    /// Intended for tests and diagnostics of the dispatch index.
+    #[doc(hidden)]
+    pub fn debug_dispatch_targets(&self, event: &Event) -> Vec<String> {
+        self.dispatch.targets(event)
+    }
+
     /// AST management
     pub async fn register_agent_ast(
         &self,
         agent_name: &str,
         ast: &MicroAgentDef,
     ) -> SystemResult<()> {
+        validate_labels(&ast.labels)?;
         self.ast_registry
             .write()
             .await
             .register_agent_ast(agent_name, ast)
             .await
-            .map_err(SystemError::from)
+            .map_err(SystemError::from)?;
+        // AST の再登録（ホットリロード）にカタログを追従させる
+        self.catalog.update_ast(agent_name, ast);
+        Ok(())
     }
 
     pub async fn get_agent_ast(&self, _agent_name: &str) -> SystemResult<Arc<MicroAgentDef>> {
@@ -443,16 +791,8 @@ impl System {
     }
 
     pub async fn register_event_ast(&self, event_def: CustomEventDef) -> SystemResult<()> {
-        let name = event_def.name.to_string();
-        let parameters: HashMap<String, ParameterType> = event_def
-            .parameters
-            .iter()
-            .map(|p| (p.name.clone(), ParameterType::from(p.type_info.clone())))
-            .collect();
         let mut registry = self.event_registry.write().await;
-        registry
-            .register_custom_event(name, parameters)
-            .map_err(SystemError::from)
+        register_custom_event(&mut registry, &event_def).map_err(SystemError::from)
     }
 
     pub async fn get_event(&self, name: &str) -> SystemResult<EventInfo> {
@@ -468,6 +808,71 @@ impl System {
             .map_err(SystemError::from)
     }
 
+    /// Parameter types of the answer handler for `request_type` on `agent_name`,
+    /// used to coerce parameters sent from outside the runtime
+    pub async fn request_parameter_schema(
+        &self,
+        agent_name: &str,
+        request_type: &str,
+    ) -> Option<HashMap<String, ParameterType>> {
+        let agent_def = self
+            .ast_registry
+            .read()
+            .await
+            .get_agent_ast(agent_name)
+            .await
+            .ok()?;
+        let handler = agent_def
+            .answer
+            .as_ref()?
+            .handlers
+            .iter()
+            .find(|handler| handler.request_type.to_string() == request_type)?;
+        Some(coercion::parameter_schema(&handler.parameters))
+    }
+
+    /// The contract `agent_name` declares for `request_type`. Requests sent from
+    /// outside the runtime are validated against it before they are delivered.
+    pub async fn request_contract(
+        &self,
+        agent_name: &str,
+        request_type: &str,
+    ) -> Option<ast::RequestContract> {
+        let agent_def = self
+            .ast_registry
+            .read()
+            .await
+            .get_agent_ast(agent_name)
+            .await
+            .ok()?;
+        agent_def.contract(request_type).cloned()
+    }
+
+    /// All contracts declared by `agent_name`
+    pub async fn agent_contracts(
+        &self,
+        agent_name: &str,
+    ) -> SystemResult<Vec<ast::RequestContract>> {
+        let agent_def = self
+            .ast_registry
+            .read()
+            .await
+            .get_agent_ast(agent_name)
+            .await?;
+        Ok(agent_def.contracts.clone())
+    }
+
+    /// Parameter types of a registered event, used like [`Self::request_parameter_schema`]
+    pub async fn event_parameter_schema(
+        &self,
+        event_type: &str,
+    ) -> Option<HashMap<String, ParameterType>> {
+        self.get_event(event_type)
+            .await
+            .ok()
+            .map(|info| info.parameters)
+    }
+
     pub async fn scale_up(
         &self,
         name: &str,
@@ -512,6 +917,19 @@ impl System {
         let providers = self.provider_registry.read().await.get_providers().clone();
         let world_def = self.get_agent_ast(&AgentType::World.to_string()).await?;
         let world_polices = world_def.policies.clone();
+        let config = self.config.read().await;
+        let preconditions = config.agent_config.preconditions;
+        let locale = config.agent_config.locale.clone();
+        let eval_limits = config.eval_limits_for(name);
+        let guardrails = config.guardrails_for(name);
+        let supplied = config.agent_configs.get(name).cloned().unwrap_or_default();
+        let logger = self.agent_loggers.get_or_create(
+            name,
+            config.agent_logs.get(name).cloned().unwrap_or_default(),
+        );
+        drop(config);
+        let config_values =
+            config_values::resolve(name, ast_def.config.as_ref(), &supplied.values)?;
 
         // 指定された数だけエージェントを作成
         for i in 0..count {
@@ -522,12 +940,33 @@ impl System {
                 RuntimeAgentData::new(
                     &agent_def,
                     &self.event_bus(),
-                    AgentConfig::default(),
-                    primary.clone(),
-                    providers.clone(),
+                    AgentConfig {
+                        preconditions,
+                        locale: locale.clone(),
+                        guardrails: guardrails.clone(),
+                        eval_limits: eval_limits.clone(),
+                        ..Default::default()
+                    },
+                    AgentResources {
+                        primary: primary.clone(),
+                        providers: providers.clone(),
+                        catalog: self.catalog.clone(),
+                        tools: self.tools.clone(),
+                        config_values: config_values.clone(),
+                        logger: logger.clone(),
+                        runtime_settings: self.agent_settings.get_or_create(name),
+                        shared_counters: self.shared_counters.clone(),
+                        // 同じ定義のインスタンスは応答を共有する
+                        response_cache: self.response_caches.get_or_create(name),
+                        ..Default::default()
+                    },
                     world_polices.clone(),
+                    self.world_preamble.clone(),
                 )
-                .await?,
+                .await?
+                .with_scheduler(self.scheduler.clone())
+                .with_handler_recordings(self.handler_recordings.clone())
+                .with_dispatch_index(Some(self.dispatch.clone())),
             );
 
             let registry = self.agent_registry.write().await;
@@ -537,6 +976,7 @@ impl System {
             registry
                 .run_agent(&agent_name, self.event_bus().clone())
                 .await?;
+            self.catalog.insert(&agent_name, name, &agent_def);
 
             created_agents.push(agent_name);
         }
@@ -572,6 +1012,7 @@ impl System {
         for agent_name in agent_names_to_remove {
             let registry = self.agent_registry.write().await;
             registry.shutdown_agent(agent_name, None).await?;
+            self.catalog.remove(agent_name);
         }
 
         Ok(())
@@ -607,48 +1048,62 @@ impl System {
     /// Agent management
     pub async fn register_agent(&self, agent_name: &str) -> SystemResult<()> {
         debug!("register_agent: {}", agent_name);
-        let ast_registry = self.ast_registry.read().await;
-        let agent_def = ast_registry.get_agent_ast(agent_name).await?;
-        let world_def = ast_registry
-            .get_agent_ast(&AgentType::World.to_string())
-            .await?;
-        drop(ast_registry);
-
-        let providers = self.provider_registry.read().await.get_providers().clone();
-
-        // プライマリプロバイダーの取得
-        let primary = self
-            .provider_registry
-            .read()
-            .await
-            .get_primary_provider()
-            .await
-            .map_err(SystemError::from)?;
+        let runtime = self.agent_factory().build(agent_name, None, vec![]).await?;
+        self.register_runtime(agent_name, runtime).await
+    }
 
-        let runtime = Arc::new(
-            RuntimeAgentData::new(
-                &agent_def,
-                &self.event_bus,
-                AgentConfig::default(),
-                primary,
-                providers,
-                world_def.policies.clone(),
-            )
-            .await?,
-        );
+    async fn register_runtime(
+        &self,
+        agent_name: &str,
+        runtime: Arc<RuntimeAgentData>,
+    ) -> SystemResult<()> {
         let agent_registry = self.agent_registry.write().await;
         agent_registry
             .register_agent(agent_name, runtime, &self.event_bus)
             .await?;
         drop(agent_registry);
+        self.catalog.insert(
+            agent_name,
+            agent_name,
+            &*self.get_agent_ast(agent_name).await?,
+        );
+        if let Some(tracker) = &self.idle_tracker {
+            tracker.track(agent_name);
+        }
         Ok(())
     }
+
+    fn agent_factory(&self) -> AgentFactory {
+        AgentFactory {
+            ast_registry: self.ast_registry.clone(),
+            provider_registry: self.provider_registry.clone(),
+            config: self.config.clone(),
+            event_bus: self.event_bus.clone(),
+            world_preamble: self.world_preamble.clone(),
+            scheduler: self.scheduler.clone(),
+            handler_secrets: self.handler_secrets.clone(),
+            catalog: self.catalog.clone(),
+            tools: self.tools.clone(),
+            agent_loggers: self.agent_loggers.clone(),
+            handler_recordings: self.handler_recordings.clone(),
+            agent_settings: self.agent_settings.clone(),
+            shared_counters: self.shared_counters.clone(),
+            dispatch: self.dispatch.clone(),
+            response_caches: self.response_caches.clone(),
+        }
+    }
+
     pub async fn start_agent(&self, agent_name: &str) -> SystemResult<()> {
         let registry = self.agent_registry.read().await;
         registry
             .run_agent(agent_name, self.event_bus.clone())
             .await
-            .map_err(SystemError::from)
+            .map_err(SystemError::from)?;
+        // 起動した時点からアイドル時間を数える
+        if let Some(tracker) = &self.idle_tracker {
+            tracker.touch(agent_name);
+        }
+        Ok(())
     }
 
     pub async fn list_agents(&self) -> SystemResult<Vec<AgentStatus>> {
@@ -662,6 +1117,46 @@ impl System {
         Ok(agent_statuses)
     }
 
+    /// ラベルで絞り込んだエージェント一覧（すべてのラベルに一致するもの）
+    pub async fn list_agents_by_labels(
+        &self,
+        selector: &HashMap<String, String>,
+    ) -> SystemResult<Vec<AgentStatus>> {
+        let registry = self.agent_registry.read().await;
+        let agent_names = registry
+            .agent_names()
+            .into_iter()
+            .filter(|agent_name| {
+                registry.agent_labels(agent_name).is_some_and(|labels| {
+                    selector
+                        .iter()
+                        .all(|(key, value)| labels.get(key) == Some(value))
+                })
+            })
+            .collect::<Vec<_>>();
+        drop(registry);
+
+        let mut agent_statuses = Vec::with_capacity(agent_names.len());
+        for agent_name in agent_names {
+            agent_statuses.push(self.get_agent_status(&agent_name).await?);
+        }
+        Ok(agent_statuses)
+    }
+
+    pub async fn get_agent_labels(
+        &self,
+        agent_name: &str,
+    ) -> SystemResult<HashMap<String, String>> {
+        self.agent_registry
+            .read()
+            .await
+            .agent_labels(agent_name)
+            .ok_or(AgentError::AgentNotFound {
+                agent_id: agent_name.to_string(),
+            })
+            .map_err(SystemError::from)
+    }
+
     pub async fn stop_agent(&self, agent_name: &str) -> SystemResult<()> {
         let registry = self.agent_registry.read().await;
         registry
@@ -679,8 +1174,148 @@ impl System {
             .map_err(SystemError::from)
     }
 
+    /// Replaces the definition of an agent while the system runs.
+    ///
+    /// A registered agent is rebuilt from `agent_def` and keeps the values of the
+    /// state variables it still declares with the same type; an unknown agent is
+    /// added. The agent runs again once rebuilt if the system has started. When
+    /// `agent_def` is rejected, the running agent is left as it is.
+    pub async fn reload_agent(&self, agent_def: &MicroAgentDef) -> SystemResult<()> {
+        self.validate_event_references(std::slice::from_ref(agent_def))
+            .await?;
+        self.replace_agent(agent_def).await
+    }
+
+    /// What [`Self::reload_agent`] would change if agent `name` were reloaded with
+    /// its definition in `dsl`, without applying anything.
+    ///
+    /// `dsl` is parsed and type checked as for a reload. The plan compares the new
+    /// definition with the registered one; an unknown agent would be added.
+    ///
+    /// # Errors
+    ///
+    /// * `SystemError::Ast` - If `dsl` does not parse or type check
+    /// * `SystemError::Agent` - If `dsl` does not define agent `name`
+    /// * `SystemError::UnregisteredEventTypes` - If its handlers listen to unknown events
+    pub async fn preview_reload(&self, name: &str, dsl: &str) -> SystemResult<ReloadPlan> {
+        let root = self.parse_dsl(dsl).await?;
+        let agent_def = root
+            .micro_agent_defs
+            .into_iter()
+            .find(|agent_def| agent_def.name == name)
+            .ok_or(AgentError::AgentNotFound {
+                agent_id: name.to_string(),
+            })?;
+        self.validate_event_references(std::slice::from_ref(&agent_def))
+            .await?;
+        let previous = self.get_agent_ast(name).await.ok();
+        Ok(ReloadPlan::new(previous.as_deref(), &agent_def))
+    }
+
+    async fn replace_agent(&self, agent_def: &MicroAgentDef) -> SystemResult<()> {
+        let name = agent_def.name.clone();
+        let previous = self.get_agent_ast(&name).await.ok();
+        self.register_agent_ast(&name, agent_def).await?;
+
+        let registry = self.agent_registry.read().await;
+        let restored_state = match (previous, registry.agent_state_snapshot(&name).await) {
+            (Some(previous), Some(state)) => Some(kept_state(&previous, agent_def, state)),
+            _ => None,
+        };
+        drop(registry);
+        // 組み立てに失敗したら動いているエージェントには触れない
+        let runtime = self
+            .agent_factory()
+            .build(&name, restored_state, vec![])
+            .await?;
+
+        let registry = self.agent_registry.read().await;
+        if registry.agent_names().contains(&name) {
+            registry.unregister_agent(&name, &self.event_bus).await?;
+        }
+        drop(registry);
+        // 古い定義で作った応答は返さない
+        self.response_caches.invalidate(&name).await;
+        self.register_runtime(&name, runtime).await?;
+        if self.is_started().await {
+            self.start_agent(&name).await?;
+        }
+        debug!("replace_agent: {}", name);
+        Ok(())
+    }
+
+    /// Stops an agent and removes it from the system
+    pub async fn remove_agent(&self, agent_name: &str) -> SystemResult<()> {
+        self.agent_registry
+            .read()
+            .await
+            .unregister_agent(agent_name, &self.event_bus)
+            .await?;
+        self.catalog.remove(agent_name);
+        Ok(())
+    }
+
+    /// Applies a new definition of the whole world while the system runs.
+    ///
+    /// The persona, schedules, handlers and events of the world are replaced, and
+    /// every agent of `root` is reloaded as by
+    /// [`Self::reload_agent`]. Agents missing from `root` are left running; remove
+    /// them with [`Self::remove_agent`].
+    pub async fn reload_world(&self, root: ast::Root) -> SystemResult<()> {
+        self.validate_event_references(&root.micro_agent_defs)
+            .await?;
+        let world_def = match root.world_def {
+            Some(def) => def,
+            None => self.ast_registry.read().await.create_world_ast(),
+        };
+        self.world_preamble.set(world_def.persona.clone());
+        self.world_scheduler.set_schedules(&world_def.schedules)?;
+
+        let (agent_def, event_defs): (MicroAgentDef, EventsDef) = world_def.into();
+        let mut registry = self.event_registry.write().await;
+        for event_def in event_defs.events {
+            let parameters = event_def
+                .parameters
+                .iter()
+                .map(|p| (p.name.clone(), ParameterType::from(p.type_info.clone())))
+                .collect();
+            registry.replace_custom_event(event_def.name.to_string(), parameters);
+        }
+        drop(registry);
+
+        // エージェントは world の AST を読んで組み立てられるため、world を先に差し替える
+        self.replace_agent(&MicroAgentDef {
+            name: AgentType::World.to_string(),
+            ..agent_def
+        })
+        .await?;
+        for agent_def in &root.micro_agent_defs {
+            self.reload_agent(agent_def).await?;
+        }
+        debug!("reload_world: {} agents", root.micro_agent_defs.len());
+        Ok(())
+    }
+
+    async fn is_started(&self) -> bool {
+        self.last_status.read().await.last_event_type == EventType::SystemStarted
+    }
+
     /// Send/Receive events
+    ///
+    /// With the event journal enabled, a custom event is written to the journal
+    /// before it is published.
     pub async fn send_event(&self, event: Event) -> SystemResult<()> {
+        let mut event = event;
+        if let Some(journal) = &self.event_journal {
+            if EventJournal::records(&event) {
+                // 再生したイベントが元のイベントと同じ ID を持つよう、記録の前に振る
+                event = event.assign_id(Uuid::new_v4().to_string());
+                journal
+                    .append(&event)
+                    .await
+                    .map_err(SystemError::EventJournal)?;
+            }
+        }
         self.event_bus
             .publish(event)
             .await
@@ -752,6 +1387,20 @@ impl System {
     /// * `SystemError::UnsupportedRequest` - If the event is not a request event
     /// * `SystemError::RequestError` - If the request fails or times out
     pub async fn send_request(&self, event: Event) -> SystemResult<Value> {
+        self.send_request_with_profile(event)
+            .await
+            .map(|(value, _)| value)
+    }
+
+    /// Like [`Self::send_request`], and also returns the timing breakdown of the
+    /// request when it asked for one with [`PROFILE_KEY`] (see
+    /// [`crate::eval::profile`]).
+    ///
+    /// [`PROFILE_KEY`]: crate::event_bus::PROFILE_KEY
+    pub async fn send_request_with_profile(
+        &self,
+        mut event: Event,
+    ) -> SystemResult<(Value, Option<RequestProfile>)> {
         let request_id = match event.event_type.clone() {
             EventType::Request { request_id, .. } => request_id,
             _ => {
@@ -761,12 +1410,53 @@ impl System {
             }
         };
         debug!("request_id: {}", request_id);
+        // 処理待ちの時間も計れるよう、送信時刻を記録する
+        event.stamp_profile_request();
         let event = self
             .request_manager
             .request(&event)
             .await
             .map_err(SystemError::from)?;
-        Ok(event.response_value())
+        if let Some(violations) = event.constraint_violations() {
+            let message = match event.response_value() {
+                Value::String(message) => message,
+                other => format!("{:?}", other),
+            };
+            return Err(SystemError::ValidationFailed {
+                message,
+                violations,
+            });
+        }
+        Ok((event.response_value(), event.profile()))
+    }
+
+    /// Send a request and stream its responses: the partial responses published by
+    /// `yield` in the answer handler, followed by the final response.
+    pub async fn send_request_streaming(&self, event: Event) -> SystemResult<ResponseStream> {
+        if !event.event_type.is_request() {
+            return Err(SystemError::UnsupportedRequest {
+                request_type: event.event_type.to_string(),
+            });
+        }
+        self.request_manager
+            .request_streaming(&event)
+            .await
+            .map_err(SystemError::from)
+    }
+
+    /// Causal chain around a recently published event: walks up to `max_depth`
+    /// ancestors, then returns the tree of events they caused (see [`EventLineageLog`]).
+    /// Returns `None` if the event is unknown or was evicted from the lineage log.
+    ///
+    /// [`EventLineageLog`]: crate::event::lineage::EventLineageLog
+    pub fn get_event_lineage(&self, event_id: &str, max_depth: usize) -> Option<LineageNode> {
+        self.event_bus.get_lineage(event_id, max_depth)
+    }
+
+    /// The `limit` most recently published events still in the lineage log, oldest
+    /// first, with their causal links
+    pub fn recent_events(&self, limit: usize) -> Vec<LineageRecord> {
+        self.event_bus.recent_events(limit)
     }
 
     pub async fn get_agent_state(
@@ -784,24 +1474,283 @@ impl System {
             .map_err(SystemError::from)
     }
 
-    /// イベントの購読
-    pub async fn subscribe_events(
+    /// Every state variable of `agent_name` with its current value
+    pub async fn get_agent_state_snapshot(
         &self,
-        event_types: Vec<EventType>,
-    ) -> SystemResult<EventReceiver> {
-        let key = self.get_filtered_subscription_key(&event_types);
-        if let Some(sender) = self.filtered_subscriptions.get(&key) {
-            return Ok(EventReceiver::new(sender.subscribe()));
-        }
-
-        let (tx, rx) = broadcast::channel(100);
-        self.filtered_subscriptions.insert(key.clone(), tx.clone());
-
-        // イベントバスからサブスクライバーを取得
-        let mut subscriber = self.event_bus.subscribe().0;
-
+        agent_name: &str,
+    ) -> SystemResult<HashMap<String, expression::Value>> {
+        let registry = self.agent_registry.read().await;
+        registry
+            .agent_state_snapshot(agent_name)
+            .await
+            .ok_or(AgentError::AgentNotFound {
+                agent_id: agent_name.to_string(),
+            })
+            .map_err(SystemError::from)
+    }
+
+    /// The policies the think calls of `agent_name` follow: its own, then the world's
+    pub async fn effective_policies(&self, agent_name: &str) -> SystemResult<Vec<ast::Policy>> {
+        let agent_def = self.get_agent_ast(agent_name).await?;
+        let world_def = self.get_agent_ast(&AgentType::World.to_string()).await?;
+        let mut policies = agent_def.policies.clone();
+        policies.extend(world_def.policies.iter().cloned());
+        Ok(policies)
+    }
+
+    /// Replace the `config { ... }` values of `agent_name` and its scaled instances
+    /// while they run. Only agents whose `agent_configs` entry sets `hot_reload`
+    /// accept new values; see [`crate::config_values`].
+    pub async fn update_agent_config(
+        &self,
+        agent_name: &str,
+        values: HashMap<String, serde_json::Value>,
+    ) -> SystemResult<()> {
+        let agent_def = self.get_agent_ast(agent_name).await?;
+        let mut config = self.config.write().await;
+        let Some(supplied) = config
+            .agent_configs
+            .get_mut(agent_name)
+            .filter(|supplied| supplied.hot_reload)
+        else {
+            return Err(AgentConfigError::ReloadNotAllowed {
+                agent_name: agent_name.to_string(),
+            }
+            .into());
+        };
+        let resolved = config_values::resolve(agent_name, agent_def.config.as_ref(), &values)?;
+        // 再起動やスケールアップで作られるエージェントも新しい値を使う
+        supplied.values = values;
+        drop(config);
+
+        let instance_prefix = format!("{}-", agent_name);
+        let registry = self.agent_registry.read().await;
+        for name in registry
+            .agent_names()
+            .into_iter()
+            .filter(|name| name == agent_name || name.starts_with(&instance_prefix))
+        {
+            registry
+                .update_agent_config(&name, resolved.clone())
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Change the log level and event publishing of `agent_name` while it runs.
+    /// Its scaled instances share the logger, so they follow the new config too.
+    pub async fn set_agent_log_config(&self, agent_name: &str, log_config: AgentLogConfig) {
+        self.agent_loggers
+            .set_config(agent_name, log_config.clone());
+        // 再起動したエージェントも新しい設定を使う
+        self.config
+            .write()
+            .await
+            .agent_logs
+            .insert(agent_name.to_string(), log_config);
+    }
+
+    /// The log config of `agent_name`, the default unless it was set
+    pub async fn agent_log_config(&self, agent_name: &str) -> AgentLogConfig {
+        self.config
+            .read()
+            .await
+            .agent_logs
+            .get(agent_name)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The recent logs of `agent_name` and its scaled instances, oldest first
+    pub fn agent_logs(&self, agent_name: &str) -> Vec<CapturedLog> {
+        self.agent_loggers.recent_logs(agent_name)
+    }
+
+    /// The runtime settings of `agent_name`, see [`crate::agent_settings`]
+    pub async fn agent_settings(&self, agent_name: &str) -> SystemResult<AgentSettings> {
+        self.get_agent_ast(agent_name).await?;
+        Ok(self.agent_settings.get(agent_name))
+    }
+
+    /// Change the runtime settings of `agent_name` and its scaled instances. The
+    /// settings are checked as a whole; when one is invalid none is changed.
+    pub async fn update_agent_settings(
+        &self,
+        agent_name: &str,
+        patch: AgentSettingsPatch,
+    ) -> SystemResult<AgentSettings> {
+        self.get_agent_ast(agent_name).await?;
+        let providers = self.provider_registry.read().await.get_providers();
+        let updated = self
+            .agent_settings
+            .get_or_create(agent_name)
+            .update(&patch, |settings| {
+                settings
+                    .check(|name| providers.contains_key(name))
+                    .map_err(|(setting, reason)| AgentConfigError::InvalidSetting {
+                        agent_name: agent_name.to_string(),
+                        setting: setting.to_string(),
+                        reason,
+                    })
+            })?;
+        Ok(updated)
+    }
+
+    /// エージェントの状態のスナップショットに対して、式を読み取り専用で評価する。
+    /// 書き込みや think/request を含む式は評価前に拒否する（[`debug_eval`] 参照）
+    pub async fn eval_expression(
+        &self,
+        agent_name: &str,
+        source: &str,
+    ) -> SystemResult<DebugEvaluation> {
+        let expression = debug_eval::parse_expression(&*self.ast_registry.read().await, source)?;
+        let state = self
+            .agent_registry
+            .read()
+            .await
+            .agent_state_snapshot(agent_name)
+            .await
+            .ok_or(AgentError::AgentNotFound {
+                agent_id: agent_name.to_string(),
+            })?;
+        let timeout = self.config.read().await.debug_eval_timeout;
+        Ok(debug_eval::evaluate(
+            agent_name,
+            &expression,
+            state,
+            self.catalog.clone(),
+            timeout,
+        )
+        .await?)
+    }
+
+    /// Fire a synthetic `event` at one observe or react handler of `agent_name` and
+    /// report what it did, without committing anything (see [`handler_test`])
+    pub async fn test_handler(
+        &self,
+        agent_name: &str,
+        event: &str,
+        test: HandlerTest,
+    ) -> SystemResult<HandlerTestReport> {
+        let agent = self.tested_agent(agent_name).await?;
+        let timeout = self.config.read().await.debug_eval_timeout;
+        Ok(handler_test::run(agent, event, test, timeout).await?)
+    }
+
+    /// The recorded observe and react handler runs, oldest first. Empty unless
+    /// `handler_recording` is enabled (see [`crate::eval::recording`])
+    pub fn handler_recordings(&self) -> Vec<Arc<HandlerRecording>> {
+        self.handler_recordings
+            .as_ref()
+            .map(|recordings| recordings.list())
+            .unwrap_or_default()
+    }
+
+    /// Load the recorded handler run `recording_id` into a new debug session paused
+    /// before the handler's first statement, and return the session's ID (see
+    /// [`debug_session`](crate::debug_session))
+    pub async fn debug_start(&self, recording_id: &str) -> SystemResult<String> {
+        let recording = self
+            .handler_recordings
+            .as_ref()
+            .and_then(|recordings| recordings.get(recording_id))
+            .ok_or_else(|| DebugSessionError::RecordingNotFound(recording_id.to_string()))?;
+        let agent = self.tested_agent(&recording.agent_name).await?;
+        let timeout = self.config.read().await.debug_eval_timeout;
+        let session = DebugSession::start(agent, recording, timeout).await?;
+        let session_id = Uuid::new_v4().to_string();
+        self.debug_sessions.insert(
+            session_id.clone(),
+            Arc::new(tokio::sync::Mutex::new(session)),
+        );
+        Ok(session_id)
+    }
+
+    /// Run the next statement of the handler of debug session `session_id`
+    pub async fn debug_step(&self, session_id: &str) -> SystemResult<DebugStep> {
+        let session = self.debug_session(session_id)?;
+        let mut session = session.lock().await;
+        Ok(session.step().await?)
+    }
+
+    /// Evaluate the read-only expression `source` against the paused state and local
+    /// variables of debug session `session_id`
+    pub async fn debug_inspect(
+        &self,
+        session_id: &str,
+        source: &str,
+    ) -> SystemResult<DebugEvaluation> {
+        let expression = debug_eval::parse_expression(&*self.ast_registry.read().await, source)?;
+        let session = self.debug_session(session_id)?;
+        let session = session.lock().await;
+        Ok(session.inspect(&expression).await?)
+    }
+
+    /// End debug session `session_id`
+    pub fn debug_end(&self, session_id: &str) -> SystemResult<()> {
+        self.debug_sessions
+            .remove(session_id)
+            .map(|_| ())
+            .ok_or_else(|| DebugSessionError::SessionNotFound(session_id.to_string()).into())
+    }
+
+    fn debug_session(
+        &self,
+        session_id: &str,
+    ) -> Result<Arc<tokio::sync::Mutex<DebugSession>>, DebugSessionError> {
+        self.debug_sessions
+            .get(session_id)
+            .map(|session| session.clone())
+            .ok_or_else(|| DebugSessionError::SessionNotFound(session_id.to_string()))
+    }
+
+    /// `agent_name` の定義と設定、実行中なら状態をまとめる
+    async fn tested_agent(&self, agent_name: &str) -> SystemResult<TestedAgent> {
+        let definition = self.get_agent_ast(agent_name).await?;
+        let world_def = self.get_agent_ast(&AgentType::World.to_string()).await?;
+        let state = self
+            .agent_registry
+            .read()
+            .await
+            .agent_state_snapshot(agent_name)
+            .await;
+        let supplied = self
+            .config
+            .read()
+            .await
+            .agent_configs
+            .get(agent_name)
+            .cloned()
+            .unwrap_or_default();
+        let config_values =
+            config_values::resolve(agent_name, definition.config.as_ref(), &supplied.values)?;
+        Ok(TestedAgent {
+            definition,
+            state,
+            config_values,
+            world_policies: world_def.policies.clone(),
+            catalog: self.catalog.clone(),
+        })
+    }
+
+    /// イベントの購読
+    pub async fn subscribe_events(
+        &self,
+        event_types: Vec<EventType>,
+    ) -> SystemResult<EventReceiver> {
+        let key = self.get_filtered_subscription_key(&event_types);
+        if let Some(sender) = self.filtered_subscriptions.get(&key) {
+            return Ok(EventReceiver::new(sender.subscribe()));
+        }
+
+        let (tx, rx) = broadcast::channel(100);
+        self.filtered_subscriptions.insert(key.clone(), tx.clone());
+
+        // イベントバスからサブスクライバーを取得
+        let mut subscriber = self.event_bus.subscribe().0;
+
         let event_types = event_types.clone();
-        tokio::spawn(async move {
+        self.background_tasks.spawn(async move {
             while let Ok(event) = subscriber.recv().await {
                 if event_types.contains(&event.event_type) {
                     // エラーは無視（受信側がすべて切断された場合など）
@@ -828,6 +1777,12 @@ impl System {
     pub async fn get_system_status(&self) -> SystemResult<SystemStatus> {
         let registry = self.agent_registry.read().await;
         let event_bus = &self.event_bus;
+        let provider_credentials = self
+            .provider_registry
+            .read()
+            .await
+            .credential_statuses()
+            .await;
 
         Ok(SystemStatus {
             started_at: self.started_at,
@@ -838,6 +1793,7 @@ impl System {
             event_queue_size: event_bus.queue_size(),
             event_subscribers: event_bus.subscribers_size(),
             event_capacity: event_bus.capacity(),
+            provider_credentials,
         })
     }
 
@@ -856,6 +1812,9 @@ impl System {
             name: agent_name.to_string(),
             state: agent_status.last_event_type.to_string(),
             last_lifecycle_updated: agent_status.last_event_time,
+            request_queue: registry.agent_request_queue_stats(agent_name),
+            llm_budget: registry.agent_llm_budget_stats(agent_name),
+            response_cache: registry.agent_response_cache_stats(agent_name),
         })
     }
 
@@ -897,6 +1856,61 @@ impl System {
         Ok(())
     }
 
+    /// Replace the world-level prompt preamble at runtime.
+    /// Agents without their own `persona` use it from their next think onwards.
+    pub async fn set_prompt_preamble(&self, preamble: Option<String>) {
+        self.world_preamble.set(preamble.clone());
+        self.config.write().await.prompt_preamble = preamble;
+    }
+
+    /// The world-level prompt preamble currently in effect
+    pub fn prompt_preamble(&self) -> Option<String> {
+        self.world_preamble.get()
+    }
+
+    /// Agents and the requests they answer, as seen by `list_agents()` and `agent_requests(name)`
+    pub fn catalog(&self) -> Vec<AgentEntry> {
+        self.catalog.entries()
+    }
+
+    /// The `schedule` declarations of the world with their next fire times
+    pub fn schedules(&self) -> Vec<ScheduleStatus> {
+        self.world_scheduler.schedules()
+    }
+
+    /// Latency and token usage histograms of the LLM calls of this system, by provider
+    /// and model
+    pub async fn llm_metrics(&self) -> Arc<LlmMetrics> {
+        self.provider_registry.read().await.llm_metrics()
+    }
+
+    /// Response cache counters of the agents with `@cache` answer handlers, by agent name
+    pub async fn response_cache_stats(&self) -> Vec<(String, ResponseCacheStats)> {
+        let registry = self.agent_registry.read().await;
+        let mut stats: Vec<_> = registry
+            .agent_names()
+            .into_iter()
+            .filter_map(|name| {
+                let agent_stats = registry.agent_response_cache_stats(&name)?;
+                Some((name, agent_stats))
+            })
+            .collect();
+        stats.sort_by(|a, b| a.0.cmp(&b.0));
+        stats
+    }
+
+    /// SistenceMemory for the given namespace, created on first use
+    pub async fn sistence_memory(
+        &self,
+        namespace: &str,
+    ) -> SystemResult<Arc<dyn SistenceMemoryCapability>> {
+        let registry = self.provider_registry.read().await;
+        registry
+            .get_or_create_sistence_memory(namespace)
+            .await
+            .map_err(SystemError::from)
+    }
+
     /// basic accessors
     pub fn event_bus(&self) -> Arc<EventBus> {
         self.event_bus.clone()
@@ -943,6 +1957,270 @@ impl System {
     }
 }
 
+/// Builds user agents from their registered AST, also from background tasks
+#[derive(Clone)]
+struct AgentFactory {
+    ast_registry: Arc<RwLock<AstRegistry>>,
+    provider_registry: Arc<RwLock<ProviderRegistry>>,
+    config: Arc<RwLock<SystemConfig>>,
+    event_bus: Arc<EventBus>,
+    world_preamble: WorldPreamble,
+    scheduler: Option<RequestScheduler>,
+    handler_secrets: Arc<HashMap<String, String>>,
+    catalog: Arc<AgentCatalog>,
+    tools: Arc<ToolRegistry>,
+    agent_loggers: Arc<AgentLoggers>,
+    handler_recordings: Option<Arc<HandlerRecordings>>,
+    agent_settings: Arc<AgentSettingsStore>,
+    shared_counters: Arc<SharedCounters>,
+    dispatch: Arc<DispatchIndex>,
+    response_caches: Arc<ResponseCaches>,
+}
+
+impl AgentFactory {
+    async fn build(
+        &self,
+        agent_name: &str,
+        restored_state: Option<HashMap<String, expression::Value>>,
+        replayed_events: Vec<Event>,
+    ) -> SystemResult<Arc<RuntimeAgentData>> {
+        let ast_registry = self.ast_registry.read().await;
+        let agent_def = ast_registry.get_agent_ast(agent_name).await?;
+        let world_def = ast_registry
+            .get_agent_ast(&AgentType::World.to_string())
+            .await?;
+        drop(ast_registry);
+
+        let providers = self.provider_registry.read().await.get_providers().clone();
+
+        // プライマリプロバイダーの取得
+        let primary = self
+            .provider_registry
+            .read()
+            .await
+            .get_primary_provider()
+            .await
+            .map_err(SystemError::from)?;
+
+        let request_queue = self.open_request_queue(agent_name).await?;
+        let config = self.config.read().await;
+        let supplied = config
+            .agent_configs
+            .get(agent_name)
+            .cloned()
+            .unwrap_or_default();
+        let config_values =
+            config_values::resolve(agent_name, agent_def.config.as_ref(), &supplied.values)?;
+        let agent_config = AgentConfig {
+            llm_budget: config.llm_budgets.get(agent_name).cloned(),
+            guardrails: config.guardrails_for(agent_name),
+            eval_limits: config.eval_limits_for(agent_name),
+            preconditions: config.agent_config.preconditions,
+            locale: config.agent_config.locale.clone(),
+            ..Default::default()
+        };
+        let resources = AgentResources {
+            primary,
+            providers,
+            secrets: SecretVault::granted(
+                &self.handler_secrets,
+                config
+                    .secret_grants
+                    .get(agent_name)
+                    .map(Vec::as_slice)
+                    .unwrap_or_default(),
+            ),
+            catalog: self.catalog.clone(),
+            tools: self.tools.clone(),
+            config_values,
+            logger: self.agent_loggers.get_or_create(
+                agent_name,
+                config
+                    .agent_logs
+                    .get(agent_name)
+                    .cloned()
+                    .unwrap_or_default(),
+            ),
+            runtime_settings: self.agent_settings.get_or_create(agent_name),
+            shared_counters: self.shared_counters.clone(),
+            response_cache: self.response_caches.get_or_create(agent_name),
+        };
+        drop(config);
+
+        Ok(Arc::new(
+            RuntimeAgentData::new(
+                &agent_def,
+                &self.event_bus,
+                agent_config,
+                resources,
+                world_def.policies.clone(),
+                self.world_preamble.clone(),
+            )
+            .await?
+            .with_scheduler(self.scheduler.clone())
+            .with_dispatch_index(Some(self.dispatch.clone()))
+            .with_request_queue(request_queue)
+            .with_restored_state(restored_state)
+            .with_replayed_events(replayed_events)
+            .with_handler_recordings(self.handler_recordings.clone()),
+        ))
+    }
+
+    /// Open the persisted request queue of `agent_name`, if configured for it
+    async fn open_request_queue(&self, agent_name: &str) -> SystemResult<Option<RequestQueue>> {
+        let config = self.config.read().await.request_queue.clone();
+        if !config.persists(agent_name) {
+            return Ok(None);
+        }
+        let backend = LocalFileSystemBackend::new(LocalFileSystemConfig {
+            base_dir: config.base_dir.clone().unwrap_or_default(),
+            file_extension: "json".to_string(),
+        });
+        let queue = RequestQueue::open(agent_name, Arc::new(backend), &config)
+            .await
+            .map_err(RuntimeError::from)?;
+        Ok(Some(queue))
+    }
+}
+
+/// How long a rehydrated agent may take to start listening
+const REHYDRATE_START_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Suspends idle user agents and brings them back on their next request
+/// (see [`crate::idle_eviction`])
+struct IdleEvictor {
+    factory: AgentFactory,
+    agent_registry: Arc<RwLock<AgentRegistry>>,
+    tracker: Arc<IdleTracker>,
+    store: SuspendedStateStore,
+}
+
+impl IdleEvictor {
+    async fn run(self, check_interval: Duration) {
+        let mut events = self.factory.event_bus.subscribe().0;
+        let mut ticks = tokio::time::interval(check_interval);
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Ok(event) => self.handle_event(event).await,
+                    Err(EventError::Lagged { .. }) => continue,
+                    Err(_) => break,
+                },
+                _ = ticks.tick() => {
+                    for agent_name in self.tracker.idle_agents() {
+                        if let Err(e) = self.suspend(&agent_name).await {
+                            warn!("Failed to suspend idle agent {}: {}", agent_name, e);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    async fn handle_event(&self, event: Event) {
+        let EventType::Request { responder, .. } = &event.event_type else {
+            return;
+        };
+        self.tracker.touch(responder);
+        if !self.tracker.is_suspended(responder) {
+            return;
+        }
+        if let Err(e) = self.rehydrate(responder).await {
+            warn!("Failed to rehydrate agent {}: {}", responder, e);
+            return;
+        }
+        // 退避中のエージェントは受け取れなかったため、起動後に配信し直す
+        if let Err(e) = self.factory.event_bus.publish(event.clone()).await {
+            warn!("Failed to redeliver request to agent {}: {}", responder, e);
+        }
+    }
+
+    async fn suspend(&self, agent_name: &str) -> SystemResult<()> {
+        let registry = self.agent_registry.read().await;
+        // 停止中のエージェントは対象外
+        if !registry.is_agent_running(agent_name) {
+            return Ok(());
+        }
+        let state = registry
+            .agent_state_snapshot(agent_name)
+            .await
+            .ok_or_else(|| AgentError::AgentNotFound {
+                agent_id: agent_name.to_string(),
+            })?;
+        self.store
+            .save(agent_name, &state)
+            .await
+            .map_err(|source| SystemError::SuspendedState {
+                agent_name: agent_name.to_string(),
+                source,
+            })?;
+        registry
+            .unregister_agent(agent_name, &self.factory.event_bus)
+            .await?;
+        self.tracker.mark_suspended(agent_name);
+        info!("Agent {} suspended after being idle", agent_name);
+        Ok(())
+    }
+
+    async fn rehydrate(&self, agent_name: &str) -> SystemResult<()> {
+        let state =
+            self.store
+                .load(agent_name)
+                .await
+                .map_err(|source| SystemError::SuspendedState {
+                    agent_name: agent_name.to_string(),
+                    source,
+                })?;
+        let runtime = self.factory.build(agent_name, Some(state), vec![]).await?;
+        let registry = self.agent_registry.write().await;
+        registry
+            .register_agent(agent_name, runtime, &self.factory.event_bus)
+            .await?;
+        registry
+            .run_agent(agent_name, self.factory.event_bus.clone())
+            .await?;
+        drop(registry);
+
+        // 購読前に配信し直すと取りこぼすため、起動完了を待つ
+        let started = Instant::now();
+        while started.elapsed() < REHYDRATE_START_TIMEOUT {
+            let status = self
+                .agent_registry
+                .read()
+                .await
+                .agent_status(agent_name)
+                .await;
+            if status.is_some_and(|s| s.last_event_type == EventType::AgentStarted) {
+                break;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+
+        self.tracker.track(agent_name);
+        if let Err(e) = self.store.clear(agent_name).await {
+            warn!(
+                "Failed to clear the suspended state of agent {}: {}",
+                agent_name, e
+            );
+        }
+        info!("Agent {} rehydrated", agent_name);
+        Ok(())
+    }
+}
+
+impl Drop for System {
+    fn drop(&mut self) {
+        // shutdown せずに破棄された場合もタスクは止める（完了は待てない）
+        if !self.background_tasks.is_cancelled() {
+            warn!(
+                "System dropped without shutdown, cancelling {} background tasks",
+                self.background_tasks.len()
+            );
+            self.background_tasks.cancel();
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ScaleStatus {
     pub base_name: String,
@@ -964,6 +2242,10 @@ pub struct SystemStatus {
     pub event_queue_size: usize,
     pub event_subscribers: usize,
     pub event_capacity: usize,
+    /// Credential check results keyed by provider, empty unless
+    /// `validate_credentials` is enabled
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub provider_credentials: BTreeMap<String, CredentialStatus>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
@@ -971,12 +2253,24 @@ pub struct AgentStatus {
     pub name: String,
     pub state: String,
     pub last_lifecycle_updated: DateTime<Utc>,
+    /// Depth and re-enqueued count of the persisted request queue
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_queue: Option<RequestQueueStats>,
+    /// Remaining LLM call budget in the current window
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub llm_budget: Option<LlmBudgetStats>,
+    /// Hits and misses of the `@cache` answer handlers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_cache: Option<ResponseCacheStats>,
 }
 
 #[derive(Debug, Error)]
 pub enum SystemError {
     #[error("Initialization error: {0}")]
     Initialization(String),
+    /// The `onInit` block of the world failed; the system is not started
+    #[error("World onInit failed: {0}")]
+    WorldInit(String),
     #[error("Parse error: {0}")]
     Runtime(#[from] RuntimeError),
     #[error("Event error: {0}")]
@@ -1004,6 +2298,24 @@ pub enum SystemError {
     #[error("Unsupported request: {request_type}")]
     UnsupportedRequest { request_type: String },
 
+    #[error("Debug session error: {0}")]
+    DebugSession(#[from] DebugSessionError),
+
+    #[error("Event journal error: {0}")]
+    EventJournal(StorageError),
+
+    /// The answer handler rejected the parameters by their `where` constraints
+    #[error("{message}")]
+    ValidationFailed {
+        message: String,
+        violations: Vec<ConstraintViolation>,
+    },
+
+    /// Handlers listen to event types the event registry does not know, rejected
+    /// by [`EventValidationMode::Strict`]
+    #[error("Unregistered event types: {}", references.join(", "))]
+    UnregisteredEventTypes { references: Vec<String> },
+
     #[error("Event Receive response failed: {message}")]
     ReceiveResponseFailed { request_id: String, message: String },
 
@@ -1013,10 +2325,155 @@ pub enum SystemError {
         timeout_secs: u64,
         message: String,
     },
+
+    #[error("Invalid label: {0}")]
+    InvalidLabel(String),
+
+    /// An [`AgentBatch`](crate::agent_batch::AgentBatch) that cannot run as given
+    #[error("Invalid batch: {0}")]
+    InvalidBatch(String),
+
+    #[error("Debug evaluation error: {0}")]
+    DebugEval(#[from] DebugEvalError),
+
+    #[error("Agent config error: {0}")]
+    AgentConfig(#[from] AgentConfigError),
+
+    #[error("Handler test error: {0}")]
+    HandlerTest(#[from] HandlerTestError),
+
+    #[error("Failed to store the state of suspended agent {agent_name}: {source}")]
+    SuspendedState {
+        agent_name: String,
+        source: StorageError,
+    },
 }
 
 pub type SystemResult<T> = Result<T, SystemError>;
 
+/// State of an agent to carry over into its reloaded definition: the variables
+/// `next` still declares with the type `previous` gave them
+fn kept_state(
+    previous: &MicroAgentDef,
+    next: &MicroAgentDef,
+    state: HashMap<String, expression::Value>,
+) -> HashMap<String, expression::Value> {
+    state
+        .into_iter()
+        .filter(|(name, _)| {
+            state_type(next, name)
+                .is_some_and(|type_info| state_type(previous, name) == Some(type_info))
+        })
+        .collect()
+}
+
+/// Registers the custom event a world declares with `event_def`
+pub(crate) fn register_custom_event(
+    registry: &mut EventRegistry,
+    event_def: &CustomEventDef,
+) -> EventResult<()> {
+    let parameters: HashMap<String, ParameterType> = event_def
+        .parameters
+        .iter()
+        .map(|p| (p.name.clone(), ParameterType::from(p.type_info.clone())))
+        .collect();
+    registry.register_custom_event(event_def.name.clone(), parameters)
+}
+
+/// Findings of the secret scan of `config`, or of the DSL `(source, name)` when
+/// given. None when the scan is disabled.
+pub(crate) fn scan_for_secrets(
+    config: &SystemConfig,
+    dsl: Option<(&str, &str)>,
+) -> SystemResult<Vec<SecretFinding>> {
+    let scan = &config.secret_scan;
+    if !scan.enabled {
+        return Ok(Vec::new());
+    }
+    let scanner = SecretScanner::new(&scan.patterns)
+        .map_err(|e| SystemError::Initialization(format!("invalid secret_scan pattern: {}", e)))?;
+    Ok(match dsl {
+        Some((dsl, name)) => scanner.scan_text(dsl, name),
+        None => {
+            let mut value = serde_json::to_value(config)
+                .map_err(|e| SystemError::Initialization(e.to_string()))?;
+            // パターン自体は検査しない
+            if let Some(fields) = value.as_object_mut() {
+                fields.remove("secret_scan");
+            }
+            scanner.scan_json(&value, "config")
+        }
+    })
+}
+
+/// The observe and react handlers of `agents` listening to event types `registry`
+/// does not know, as `<agent> observes <event type>`. See
+/// [`System::validate_event_references`].
+pub fn unregistered_event_references(
+    registry: &EventRegistry,
+    agents: &[MicroAgentDef],
+) -> Vec<String> {
+    let declared_states: HashSet<(&str, &str)> = agents
+        .iter()
+        .flat_map(|agent| {
+            agent.state.iter().flat_map(move |state| {
+                state
+                    .variables
+                    .keys()
+                    .map(move |name| (agent.name.as_str(), name.as_str()))
+            })
+        })
+        .collect();
+
+    let mut unregistered = Vec::new();
+    for agent in agents {
+        let observe = agent.observe.iter().flat_map(|def| &def.handlers);
+        let react = agent.react.iter().flat_map(|def| &def.handlers);
+        let handlers = observe
+            .map(|handler| ("observes", handler))
+            .chain(react.map(|handler| ("reacts to", handler)));
+        for (verb, handler) in handlers {
+            let registered = match &handler.event_type {
+                ast::EventType::Custom(_) => true,
+                ast::EventType::StateUpdated {
+                    agent_name,
+                    state_name,
+                } if declared_states.contains(&(agent_name.as_str(), state_name.as_str())) => true,
+                event_type => registry.contains_event(&EventType::from(event_type)),
+            };
+            if !registered {
+                unregistered.push(format!("{} {} {}", agent.name, verb, handler.event_type));
+            }
+        }
+    }
+    unregistered
+}
+
+/// エージェントのラベルの検証（キーと値は空白のみ不可、キーに `:` は使えない）
+pub fn validate_labels(labels: &HashMap<String, String>) -> SystemResult<()> {
+    for (key, value) in labels {
+        if key.trim().is_empty() {
+            return Err(SystemError::InvalidLabel(format!(
+                "label key must not be empty (value: '{}')",
+                value
+            )));
+        }
+        if key.contains(':') {
+            return Err(SystemError::InvalidLabel(format!(
+                "label key must not contain ':': '{}'",
+                key
+            )));
+        }
+        if value.trim().is_empty() {
+            return Err(SystemError::InvalidLabel(format!(
+                "label value must not be empty (key: '{}')",
+                key
+            )));
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use std::time::Duration;
@@ -1054,6 +2511,7 @@ mod tests {
         let result = system.emergency_shutdown().await;
         sleep(Duration::from_secs(1)).await;
         assert!(result.is_ok());
+        assert_eq!(system.debug_task_count(), 0);
     }
 
     fn create_ping_pong_asts() -> (MicroAgentDef, MicroAgentDef) {
@@ -1068,6 +2526,7 @@ mod tests {
                             name: "received_pong".to_string(),
                             type_info: TypeInfo::Simple("bool".to_string()),
                             initial_value: Some(Expression::Literal(Literal::Boolean(false))),
+                            doc: None,
                         },
                     );
                     vars
@@ -1079,6 +2538,7 @@ mod tests {
                         content_type: "Start".into(),
                     },
                     parameters: vec![],
+                    requires: None,
                     block: HandlerBlock {
                         statements: vec![
                             Statement::Expression(Expression::Request {
@@ -1095,6 +2555,8 @@ mod tests {
                             },
                         ],
                     },
+                    doc: None,
+                    replay: None,
                 }],
             }),
             ..Default::default()
@@ -1108,11 +2570,14 @@ mod tests {
                     parameters: vec![],
                     return_type: "bool".into(),
                     constraints: None,
+                    requires: None,
                     block: HandlerBlock {
                         statements: vec![Statement::Return(Expression::Literal(Literal::Boolean(
                             true,
                         )))],
                     },
+                    doc: None,
+                    cache: None,
                 }],
             }),
             ..Default::default()
@@ -1122,7 +2587,67 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_system() {
+    async fn test_dispatch_index_follows_running_agents() {
+        let system = initialized_system().await;
+        let (ping_ast, pong_ast) = create_ping_pong_asts();
+        system.register_agent_ast("ping", &ping_ast).await.unwrap();
+        system.register_agent_ast("pong", &pong_ast).await.unwrap();
+        let message = |content_type: &str| Event {
+            event_type: EventType::Message {
+                content_type: content_type.into(),
+            },
+            ..Default::default()
+        };
+        let ping_request = Event::request_builder()
+            .request_type("ping")
+            .requester("test")
+            .responder("pong")
+            .request_id("request-1")
+            .build()
+            .unwrap();
+
+        let instances = system.scale_up("ping", 2, HashMap::new()).await.unwrap();
+        system.register_agent("pong").await.unwrap();
+        system.start_agent("pong").await.unwrap();
+        sleep(Duration::from_millis(100)).await;
+        assert_eq!(
+            system.debug_dispatch_targets(&message("Start")),
+            vec!["ping", "ping"]
+        );
+        assert_eq!(system.debug_dispatch_targets(&ping_request), vec!["pong"]);
+
+        // 停止したインスタンスには届かない
+        system.stop_agent(&instances[0]).await.unwrap();
+        sleep(Duration::from_millis(100)).await;
+        assert_eq!(
+            system.debug_dispatch_targets(&message("Start")),
+            vec!["ping"]
+        );
+
+        // 差し替えた AST から作ったインスタンスは新しいハンドラで登録される
+        let mut reloaded = ping_ast.clone();
+        reloaded.react.as_mut().unwrap().handlers[0].event_type = ast::EventType::Message {
+            content_type: "Go".into(),
+        };
+        system.register_agent_ast("ping", &reloaded).await.unwrap();
+        let reloaded_instances = system.scale_up("ping", 1, HashMap::new()).await.unwrap();
+        sleep(Duration::from_millis(100)).await;
+        assert_eq!(
+            system.debug_dispatch_targets(&message("Start")),
+            vec!["ping"]
+        );
+        assert_eq!(system.debug_dispatch_targets(&message("Go")), vec!["ping"]);
+
+        system.stop_agent(&instances[1]).await.unwrap();
+        system.stop_agent(&reloaded_instances[0]).await.unwrap();
+        sleep(Duration::from_millis(100)).await;
+        assert!(system.debug_dispatch_targets(&message("Start")).is_empty());
+        assert!(system.debug_dispatch_targets(&message("Go")).is_empty());
+        assert_eq!(system.debug_dispatch_targets(&ping_request), vec!["pong"]);
+    }
+
+    /// SimpleExpert を既定のプロバイダーにして、空のワールドで初期化した System
+    async fn initialized_system() -> System {
         let default_name = "default";
         let mut system_config = SystemConfig::default();
         let provider_configs = ProviderConfigs {
@@ -1174,6 +2699,12 @@ mod tests {
 
         // システムの初期化
         system.initialize(root_ast).await.unwrap();
+        system
+    }
+
+    #[tokio::test]
+    async fn test_system() {
+        let system = initialized_system().await;
 
         // Ping-Pong AgentのAST作成
         let (ping_ast, pong_ast) = create_ping_pong_asts();
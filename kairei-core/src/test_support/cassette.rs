@@ -0,0 +1,885 @@
+//! # Cassettes
+//!
+//! A cassette is a JSON file of HTTP interactions recorded from a provider's API. A
+//! [`CassetteServer`] listens on a local port; pointing a provider's endpoint at it
+//! runs the provider's full request serialization and response parsing without
+//! network access:
+//!
+//! * in replay mode, the default, it answers each request with the recorded response
+//!   for the same method and path. A request whose body no longer matches the
+//!   recording, or that was never recorded, is listed in the [`ReplayReport`], along
+//!   with the recorded interactions nobody requested. Bodies are compared as JSON,
+//!   so key order and formatting do not matter; headers are not compared.
+//! * with `RUN_API_TESTS` set, it forwards each request to the real API and records
+//!   the interaction, replacing the cassette when [`finished`](CassetteServer::finish).
+//!
+//! Recordings keep no request headers, only the `content-type` of responses, and
+//! replace the secrets given to the server wherever they appear in a body. Streaming
+//! responses are recorded whole and replayed in a single write.
+//!
+//! A cassette carries the [`CASSETTE_VERSION`] it was written with. One of another
+//! version is refused and must be recorded again.
+
+use std::{
+    collections::BTreeMap,
+    fmt, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+use tracing::{debug, warn};
+
+/// Version of the cassette format written by this build
+pub const CASSETTE_VERSION: u32 = 1;
+
+/// Environment variable switching cassette servers to recording
+pub const RUN_API_TESTS: &str = "RUN_API_TESTS";
+
+/// Replaces secrets in recorded bodies
+pub const REDACTED: &str = "<redacted>";
+
+/// Response headers kept in a recording; the others may identify the account
+const RECORDED_RESPONSE_HEADERS: &[&str] = &["content-type"];
+
+/// Request headers not forwarded when recording
+const HOP_HEADERS: &[&str] = &["host", "content-length", "connection", "accept-encoding"];
+
+#[derive(Debug, Error)]
+pub enum CassetteError {
+    #[error("Failed to access cassette {path}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Invalid cassette {path}: {source}")]
+    Invalid {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    #[error(
+        "Cassette {path} has version {found}, this build replays version {CASSETTE_VERSION}; record it again with {RUN_API_TESTS} set"
+    )]
+    UnsupportedVersion { path: PathBuf, found: u32 },
+
+    #[error("Failed to start the cassette server: {0}")]
+    Server(io::Error),
+}
+
+pub type CassetteResult<T> = Result<T, CassetteError>;
+
+/// Whether cassette servers record instead of replaying
+pub fn recording_enabled() -> bool {
+    std::env::var_os(RUN_API_TESTS).is_some()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cassette {
+    pub version: u32,
+    pub interactions: Vec<Interaction>,
+}
+
+impl Default for Cassette {
+    fn default() -> Self {
+        Self {
+            version: CASSETTE_VERSION,
+            interactions: Vec::new(),
+        }
+    }
+}
+
+impl Cassette {
+    pub fn load(path: impl AsRef<Path>) -> CassetteResult<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|source| CassetteError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        // 版だけを先に読み、形式が変わった古いカセットでも版の違いとして報告する
+        #[derive(Deserialize)]
+        struct Versioned {
+            version: u32,
+        }
+        let invalid = |source| CassetteError::Invalid {
+            path: path.to_path_buf(),
+            source,
+        };
+        let Versioned { version } = serde_json::from_str(&text).map_err(invalid)?;
+        if version != CASSETTE_VERSION {
+            return Err(CassetteError::UnsupportedVersion {
+                path: path.to_path_buf(),
+                found: version,
+            });
+        }
+        serde_json::from_str(&text).map_err(invalid)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> CassetteResult<()> {
+        let path = path.as_ref();
+        let io_error = |source| CassetteError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        let mut text =
+            serde_json::to_string_pretty(self).map_err(|source| CassetteError::Invalid {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        text.push('\n');
+        std::fs::write(path, text).map_err(io_error)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// Path and query, e.g. `/v1/chat/completions`
+    pub path: String,
+    #[serde(default)]
+    pub body: Body,
+}
+
+impl fmt::Display for RecordedRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.method, self.path)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub body: Body,
+}
+
+/// A request or response body. JSON bodies are kept as JSON so that recordings stay
+/// readable and compare structurally; other bodies, e.g. server-sent events, as text.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Body {
+    #[default]
+    Empty,
+    Json(serde_json::Value),
+    Text(String),
+}
+
+impl Body {
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        if bytes.is_empty() {
+            return Self::Empty;
+        }
+        match serde_json::from_slice(bytes) {
+            Ok(json) => Self::Json(json),
+            Err(_) => Self::Text(String::from_utf8_lossy(bytes).into_owned()),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Empty => Vec::new(),
+            Self::Json(json) => json.to_string().into_bytes(),
+            Self::Text(text) => text.clone().into_bytes(),
+        }
+    }
+}
+
+/// How a request differs from the recorded request it was matched with
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub request: RecordedRequest,
+    /// Index of the matched interaction; `None` when none was recorded for the
+    /// request's method and path
+    pub interaction: Option<usize>,
+    /// One line per difference, each naming a JSON path such as `$.messages[0].content`
+    pub differences: Vec<String>,
+}
+
+/// What a replay found, see the [module docs](self)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReplayReport {
+    pub cassette: PathBuf,
+    pub mismatches: Vec<Mismatch>,
+    /// Recorded requests that were never made
+    pub unused: Vec<RecordedRequest>,
+}
+
+impl ReplayReport {
+    pub fn is_clean(&self) -> bool {
+        self.mismatches.is_empty() && self.unused.is_empty()
+    }
+}
+
+impl fmt::Display for ReplayReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Cassette {}: {} mismatched requests, {} unused interactions",
+            self.cassette.display(),
+            self.mismatches.len(),
+            self.unused.len()
+        )?;
+        for mismatch in &self.mismatches {
+            match mismatch.interaction {
+                Some(index) => writeln!(
+                    f,
+                    "  {} differs from interaction {}:",
+                    mismatch.request, index
+                )?,
+                None => writeln!(f, "  {} was not recorded", mismatch.request)?,
+            }
+            for difference in &mismatch.differences {
+                writeln!(f, "    {}", difference)?;
+            }
+        }
+        for request in &self.unused {
+            writeln!(f, "  {} was recorded but not requested", request)?;
+        }
+        Ok(())
+    }
+}
+
+/// The differences between two bodies, compared as JSON when both are
+pub fn diff_bodies(expected: &Body, actual: &Body) -> Vec<String> {
+    let mut differences = Vec::new();
+    match (expected, actual) {
+        (Body::Json(expected), Body::Json(actual)) => {
+            diff_json("$", expected, actual, &mut differences)
+        }
+        _ if expected != actual => differences.push(format!(
+            "body: expected {}, got {}",
+            describe(expected),
+            describe(actual)
+        )),
+        _ => {}
+    }
+    differences
+}
+
+fn describe(body: &Body) -> String {
+    match body {
+        Body::Empty => "no body".to_string(),
+        Body::Json(json) => json.to_string(),
+        Body::Text(text) => format!("text {:?}", text),
+    }
+}
+
+fn diff_json(
+    path: &str,
+    expected: &serde_json::Value,
+    actual: &serde_json::Value,
+    differences: &mut Vec<String>,
+) {
+    use serde_json::Value as Json;
+
+    match (expected, actual) {
+        (Json::Object(expected), Json::Object(actual)) => {
+            let mut keys: Vec<&String> = expected.keys().chain(actual.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let path = format!("{}.{}", path, key);
+                match (expected.get(key), actual.get(key)) {
+                    (Some(expected), Some(actual)) => {
+                        diff_json(&path, expected, actual, differences)
+                    }
+                    (Some(expected), None) => {
+                        differences.push(format!("{}: expected {}, missing", path, expected))
+                    }
+                    (None, Some(actual)) => {
+                        differences.push(format!("{}: not recorded, got {}", path, actual))
+                    }
+                    (None, None) => {}
+                }
+            }
+        }
+        (Json::Array(expected_items), Json::Array(actual_items))
+            if expected_items.len() == actual_items.len() =>
+        {
+            for (index, (expected, actual)) in expected_items.iter().zip(actual_items).enumerate() {
+                diff_json(
+                    &format!("{}[{}]", path, index),
+                    expected,
+                    actual,
+                    differences,
+                );
+            }
+        }
+        _ if expected != actual => {
+            differences.push(format!("{}: expected {}, got {}", path, expected, actual))
+        }
+        _ => {}
+    }
+}
+
+enum Mode {
+    Replay,
+    Record {
+        upstream: String,
+        client: reqwest::Client,
+    },
+}
+
+struct ServerState {
+    mode: Mode,
+    cassette: Cassette,
+    /// Interactions already replayed
+    used: Vec<bool>,
+    mismatches: Vec<Mismatch>,
+    secrets: Vec<String>,
+}
+
+impl ServerState {
+    /// The recorded response for `request`, noting any mismatch
+    fn replay(&mut self, request: RecordedRequest) -> RecordedResponse {
+        let found =
+            self.cassette
+                .interactions
+                .iter()
+                .zip(&self.used)
+                .position(|(interaction, used)| {
+                    !used
+                        && interaction.request.method == request.method
+                        && interaction.request.path == request.path
+                });
+        let Some(index) = found else {
+            warn!("Cassette has no interaction for {}", request);
+            self.mismatches.push(Mismatch {
+                request: request.clone(),
+                interaction: None,
+                differences: Vec::new(),
+            });
+            return RecordedResponse {
+                status: StatusCode::NOT_FOUND.as_u16(),
+                headers: BTreeMap::new(),
+                body: Body::Text(format!("No recorded interaction for {}", request)),
+            };
+        };
+        self.used[index] = true;
+        let interaction = &self.cassette.interactions[index];
+        let differences = diff_bodies(&interaction.request.body, &request.body);
+        if !differences.is_empty() {
+            warn!(
+                "{} differs from the recorded request: {:?}",
+                request, differences
+            );
+            self.mismatches.push(Mismatch {
+                request,
+                interaction: Some(index),
+                differences,
+            });
+        }
+        interaction.response.clone()
+    }
+
+    fn scrub(&self, bytes: &[u8]) -> Body {
+        let mut text = String::from_utf8_lossy(bytes).into_owned();
+        for secret in self.secrets.iter().filter(|secret| !secret.is_empty()) {
+            text = text.replace(secret.as_str(), REDACTED);
+        }
+        Body::from_bytes(text.as_bytes())
+    }
+}
+
+/// A request read off the socket
+struct HttpRequest {
+    method: String,
+    target: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+struct HttpResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl From<RecordedResponse> for HttpResponse {
+    fn from(response: RecordedResponse) -> Self {
+        Self {
+            status: response.status,
+            headers: response.headers.into_iter().collect(),
+            body: response.body.to_bytes(),
+        }
+    }
+}
+
+/// Serves a cassette on a local port, see the [module docs](self)
+pub struct CassetteServer {
+    path: PathBuf,
+    url: String,
+    state: Arc<Mutex<ServerState>>,
+    accept: JoinHandle<()>,
+}
+
+impl CassetteServer {
+    /// Record from `upstream` when [`recording_enabled`], replay `path` otherwise.
+    /// `secrets` are redacted from recorded bodies.
+    pub async fn start(
+        path: impl Into<PathBuf>,
+        upstream: &str,
+        secrets: Vec<String>,
+    ) -> CassetteResult<Self> {
+        if recording_enabled() {
+            Self::record(path, upstream, secrets).await
+        } else {
+            Self::replay(path).await
+        }
+    }
+
+    pub async fn replay(path: impl Into<PathBuf>) -> CassetteResult<Self> {
+        let path = path.into();
+        let cassette = Cassette::load(&path)?;
+        Self::listen(path, Mode::Replay, cassette, Vec::new()).await
+    }
+
+    /// Forward every request to `upstream`, e.g. `https://api.openai.com`, and record it
+    pub async fn record(
+        path: impl Into<PathBuf>,
+        upstream: &str,
+        secrets: Vec<String>,
+    ) -> CassetteResult<Self> {
+        let mode = Mode::Record {
+            upstream: upstream.trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+        };
+        Self::listen(path.into(), mode, Cassette::default(), secrets).await
+    }
+
+    async fn listen(
+        path: PathBuf,
+        mode: Mode,
+        cassette: Cassette,
+        secrets: Vec<String>,
+    ) -> CassetteResult<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .map_err(CassetteError::Server)?;
+        let url = format!(
+            "http://{}",
+            listener.local_addr().map_err(CassetteError::Server)?
+        );
+        let state = Arc::new(Mutex::new(ServerState {
+            mode,
+            used: vec![false; cassette.interactions.len()],
+            cassette,
+            mismatches: Vec::new(),
+            secrets,
+        }));
+        let accept_state = state.clone();
+        let accept = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let state = accept_state.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = serve(stream, state).await {
+                        debug!("Cassette connection failed: {}", e);
+                    }
+                });
+            }
+        });
+        Ok(Self {
+            path,
+            url,
+            state,
+            accept,
+        })
+    }
+
+    /// Base URL to use as the provider's endpoint, e.g. `http://127.0.0.1:49152`
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn is_recording(&self) -> bool {
+        matches!(self.state.lock().unwrap().mode, Mode::Record { .. })
+    }
+
+    /// Stop serving. When recording, write the cassette; when replaying, report what
+    /// did not match.
+    pub fn finish(self) -> CassetteResult<ReplayReport> {
+        self.accept.abort();
+        let state = self.state.lock().unwrap();
+        if let Mode::Record { .. } = state.mode {
+            state.cassette.save(&self.path)?;
+            return Ok(ReplayReport {
+                cassette: self.path.clone(),
+                ..Default::default()
+            });
+        }
+        Ok(ReplayReport {
+            cassette: self.path.clone(),
+            mismatches: state.mismatches.clone(),
+            unused: state
+                .cassette
+                .interactions
+                .iter()
+                .zip(&state.used)
+                .filter(|(_, used)| !**used)
+                .map(|(interaction, _)| interaction.request.clone())
+                .collect(),
+        })
+    }
+}
+
+impl Drop for CassetteServer {
+    fn drop(&mut self) {
+        self.accept.abort();
+    }
+}
+
+/// Answer the requests of one connection until the client closes it
+async fn serve(stream: TcpStream, state: Arc<Mutex<ServerState>>) -> io::Result<()> {
+    let mut stream = BufReader::new(stream);
+    while let Some(request) = read_request(&mut stream).await? {
+        let response = respond(&state, request).await;
+        write_response(stream.get_mut(), &response).await?;
+    }
+    Ok(())
+}
+
+async fn respond(state: &Mutex<ServerState>, request: HttpRequest) -> HttpResponse {
+    let recorded = RecordedRequest {
+        method: request.method.clone(),
+        path: request.target.clone(),
+        body: Body::from_bytes(&request.body),
+    };
+    // 記録時はロックを持ったまま上流を待たないよう、先に接続先を取り出す
+    let upstream = match &state.lock().unwrap().mode {
+        Mode::Replay => None,
+        Mode::Record { upstream, client } => Some((upstream.clone(), client.clone())),
+    };
+    let Some((upstream, client)) = upstream else {
+        return state.lock().unwrap().replay(recorded).into();
+    };
+
+    let response = match forward(&client, &upstream, &request).await {
+        Ok(response) => response,
+        Err(e) => {
+            return HttpResponse {
+                status: StatusCode::BAD_GATEWAY.as_u16(),
+                headers: Vec::new(),
+                body: format!("Failed to reach {}: {}", upstream, e).into_bytes(),
+            };
+        }
+    };
+    let mut state = state.lock().unwrap();
+    let interaction = Interaction {
+        request: RecordedRequest {
+            body: state.scrub(&request.body),
+            ..recorded
+        },
+        response: RecordedResponse {
+            status: response.status,
+            headers: response
+                .headers
+                .iter()
+                .filter(|(name, _)| RECORDED_RESPONSE_HEADERS.contains(&name.as_str()))
+                .cloned()
+                .collect(),
+            body: state.scrub(&response.body),
+        },
+    };
+    state.cassette.interactions.push(interaction);
+    response
+}
+
+async fn forward(
+    client: &reqwest::Client,
+    upstream: &str,
+    request: &HttpRequest,
+) -> Result<HttpResponse, reqwest::Error> {
+    let method =
+        reqwest::Method::from_bytes(request.method.as_bytes()).unwrap_or(reqwest::Method::GET);
+    let mut builder = client.request(method, format!("{}{}", upstream, request.target));
+    for (name, value) in &request.headers {
+        if !HOP_HEADERS.contains(&name.as_str()) {
+            builder = builder.header(name, value);
+        }
+    }
+    let response = builder.body(request.body.clone()).send().await?;
+    let status = response.status().as_u16();
+    let headers = response
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| {
+            let value = value.to_str().ok()?;
+            Some((name.as_str().to_string(), value.to_string()))
+        })
+        .collect();
+    let body = response.bytes().await?.to_vec();
+    Ok(HttpResponse {
+        status,
+        headers,
+        body,
+    })
+}
+
+/// The next request on the connection, `None` once the client closed it
+async fn read_request(stream: &mut BufReader<TcpStream>) -> io::Result<Option<HttpRequest>> {
+    let mut line = String::new();
+    if stream.read_line(&mut line).await? == 0 {
+        return Ok(None);
+    }
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("malformed request line {:?}", line),
+        ));
+    };
+    let (method, target) = (method.to_string(), target.to_string());
+
+    let mut headers = Vec::new();
+    loop {
+        let mut header = String::new();
+        stream.read_line(&mut header).await?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+    // HTTP クライアントは JSON の本文に content-length を付ける。chunked には対応しない
+    let length = headers
+        .iter()
+        .find(|(name, _)| name == "content-length")
+        .and_then(|(_, value)| value.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    stream.read_exact(&mut body).await?;
+    Ok(Some(HttpRequest {
+        method,
+        target,
+        headers,
+        body,
+    }))
+}
+
+async fn write_response(stream: &mut TcpStream, response: &HttpResponse) -> io::Result<()> {
+    let reason = StatusCode::from_u16(response.status)
+        .ok()
+        .and_then(|status| status.canonical_reason())
+        .unwrap_or("");
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\ncontent-length: {}\r\n",
+        response.status,
+        reason,
+        response.body.len()
+    );
+    for (name, value) in &response.headers {
+        if !matches!(
+            name.as_str(),
+            "content-length" | "transfer-encoding" | "connection"
+        ) {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&response.body).await?;
+    stream.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn chat_interaction() -> Interaction {
+        Interaction {
+            request: RecordedRequest {
+                method: "POST".to_string(),
+                path: "/v1/chat/completions".to_string(),
+                body: Body::Json(json!({
+                    "model": "gpt-4o-mini",
+                    "messages": [{"role": "user", "content": "hi"}],
+                })),
+            },
+            response: RecordedResponse {
+                status: 200,
+                headers: BTreeMap::from([(
+                    "content-type".to_string(),
+                    "application/json".to_string(),
+                )]),
+                body: Body::Json(json!({"id": "chatcmpl-1"})),
+            },
+        }
+    }
+
+    #[test]
+    fn test_json_bodies_are_compared_structurally() {
+        let expected = Body::Json(json!({
+            "model": "gpt-4o-mini",
+            "messages": [{"role": "user", "content": "hi"}],
+            "temperature": 0.7,
+        }));
+        // キーの順序は比較しない
+        let reordered = Body::from_bytes(
+            br#"{"temperature": 0.7, "messages": [{"content": "hi", "role": "user"}], "model": "gpt-4o-mini"}"#,
+        );
+        assert!(diff_bodies(&expected, &reordered).is_empty());
+
+        let changed = Body::Json(json!({
+            "model": "gpt-4o",
+            "messages": [{"role": "user", "content": "hi"}],
+            "max_tokens": 16,
+        }));
+        assert_eq!(
+            diff_bodies(&expected, &changed),
+            vec![
+                "$.max_tokens: not recorded, got 16".to_string(),
+                r#"$.model: expected "gpt-4o-mini", got "gpt-4o""#.to_string(),
+                "$.temperature: expected 0.7, missing".to_string(),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replay_reports_mismatches_and_unused_interactions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chat.json");
+        let mut models = chat_interaction();
+        models.request = RecordedRequest {
+            method: "GET".to_string(),
+            path: "/v1/models".to_string(),
+            body: Body::Empty,
+        };
+        Cassette {
+            version: CASSETTE_VERSION,
+            interactions: vec![chat_interaction(), models],
+        }
+        .save(&path)
+        .unwrap();
+
+        let server = CassetteServer::replay(&path).await.unwrap();
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("{}/v1/chat/completions", server.url()))
+            .header("authorization", "Bearer sk-anything")
+            .json(&json!({
+                "model": "gpt-4o-mini",
+                "messages": [{"role": "user", "content": "hello"}],
+            }))
+            .send()
+            .await
+            .unwrap();
+        // 本文が違っても記録した応答を返し、違いは報告に残す
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.json::<serde_json::Value>().await.unwrap(),
+            json!({"id": "chatcmpl-1"})
+        );
+        let response = client
+            .delete(format!("{}/v1/files/1", server.url()))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+
+        let report = server.finish().unwrap();
+        assert!(!report.is_clean());
+        assert_eq!(report.mismatches.len(), 2);
+        assert_eq!(report.mismatches[0].interaction, Some(0));
+        assert_eq!(
+            report.mismatches[0].differences,
+            vec![r#"$.messages[0].content: expected "hi", got "hello""#.to_string()]
+        );
+        assert_eq!(report.mismatches[1].interaction, None);
+        assert_eq!(report.unused.len(), 1);
+        assert_eq!(report.unused[0].path, "/v1/models");
+        let text = report.to_string();
+        assert!(text.contains("POST /v1/chat/completions differs from interaction 0"));
+        assert!(text.contains("DELETE /v1/files/1 was not recorded"));
+        assert!(text.contains("GET /v1/models was recorded but not requested"));
+    }
+
+    #[tokio::test]
+    async fn test_record_redacts_secrets_and_replays() {
+        let mut upstream = mockito::Server::new_async().await;
+        upstream
+            .mock("POST", "/search")
+            .match_header("x-api-key", "serper-secret")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_header("x-account", "acct-42")
+            .with_body(r#"{"organic": [], "echo": "serper-secret"}"#)
+            .create_async()
+            .await;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("serper").join("search.json");
+
+        let server =
+            CassetteServer::record(&path, &upstream.url(), vec!["serper-secret".to_string()])
+                .await
+                .unwrap();
+        assert!(server.is_recording());
+        let search = |url: &str| {
+            reqwest::Client::new()
+                .post(format!("{}/search", url))
+                .header("x-api-key", "serper-secret")
+                .json(&json!({"q": "kairei", "num": 1}))
+                .send()
+        };
+        let response = search(server.url()).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert!(server.finish().unwrap().is_clean());
+
+        let cassette = Cassette::load(&path).unwrap();
+        assert_eq!(cassette.interactions.len(), 1);
+        let interaction = &cassette.interactions[0];
+        assert_eq!(
+            interaction.request.body,
+            Body::Json(json!({"q": "kairei", "num": 1}))
+        );
+        assert_eq!(
+            interaction.response.headers,
+            BTreeMap::from([("content-type".to_string(), "application/json".to_string())])
+        );
+        assert_eq!(
+            interaction.response.body,
+            Body::Json(json!({"organic": [], "echo": REDACTED}))
+        );
+
+        let server = CassetteServer::replay(&path).await.unwrap();
+        let response = search(server.url()).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["content-type"], "application/json");
+        assert!(server.finish().unwrap().is_clean());
+    }
+
+    #[test]
+    fn test_cassette_of_another_version_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("old.json");
+        std::fs::write(&path, r#"{"version": 0, "exchanges": []}"#).unwrap();
+        assert!(matches!(
+            Cassette::load(&path),
+            Err(CassetteError::UnsupportedVersion { found: 0, .. })
+        ));
+    }
+}
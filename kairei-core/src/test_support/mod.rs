@@ -0,0 +1,6 @@
+//! Helpers for testing providers against their real APIs offline.
+//!
+//! They are part of the library so that provider implementations outside this crate
+//! can test their HTTP clients the same way, see [`cassette`].
+
+pub mod cassette;
@@ -27,14 +27,18 @@
 //! 2. Documentation line comments (`///`)
 //! 3. Block comments (`/*`)
 //! 4. Line comments (`//`)
+//!
+//! Block comments nest: `/* outer /* inner */ still outer */` is a single comment.
+//! A block comment that is never closed is a hard error reported at its start
+//! position rather than being tokenized as operators.
 
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take_until},
-    character::complete::not_line_ending,
-    combinator::map,
-    error::context,
-    sequence::{delimited, preceded},
+    bytes::complete::tag,
+    character::complete::{char, not_line_ending},
+    combinator::{map, not},
+    error::{VerboseError, VerboseErrorKind, context},
+    sequence::{preceded, terminated},
 };
 
 use super::token::{CommentType, ParserResult, Token};
@@ -80,9 +84,43 @@ pub fn parse_line_comment(input: &str) -> ParserResult<Token> {
     )(input)
 }
 
+/// Context attached to the failure for a block comment without its closing `*/`
+pub const UNTERMINATED_BLOCK_COMMENT: &str = "unterminated block comment";
+
+/// Consumes the body of a block comment whose opening delimiter has already been
+/// consumed, up to and including the matching `*/`.
+///
+/// Nested `/*` ... `*/` pairs are part of the body. Returns the body without the
+/// closing delimiter, or a `Failure` if the input ends before the comment is closed.
+fn block_comment_body(input: &str) -> ParserResult<&str> {
+    let bytes = input.as_bytes();
+    let mut depth = 1usize;
+    let mut i = 0;
+    while i + 1 < bytes.len() {
+        match (bytes[i], bytes[i + 1]) {
+            (b'/', b'*') => {
+                depth += 1;
+                i += 2;
+            }
+            (b'*', b'/') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok((&input[i + 2..], &input[..i]));
+                }
+                i += 2;
+            }
+            _ => i += 1,
+        }
+    }
+    Err(nom::Err::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(UNTERMINATED_BLOCK_COMMENT))],
+    }))
+}
+
 /// Parses a block comment from the input string.
 ///
-/// Block comments start with `/*` and end with `*/`. They can span multiple lines.
+/// Block comments start with `/*` and end with `*/`. They can span multiple lines
+/// and nest, so every `/*` inside the comment needs its own `*/`.
 /// The content of the comment is preserved as-is, including whitespace and newlines.
 ///
 /// # Arguments
@@ -111,13 +149,12 @@ pub fn parse_line_comment(input: &str) -> ParserResult<Token> {
 pub fn parse_block_comment(input: &str) -> ParserResult<Token> {
     context(
         "block comment",
-        map(
-            delimited(tag("/*"), take_until("*/"), tag("*/")),
-            |content: &str| Token::Comment {
+        map(preceded(tag("/*"), block_comment_body), |content: &str| {
+            Token::Comment {
                 content: content.to_string(),
                 comment_type: CommentType::Block,
-            },
-        ),
+            }
+        }),
     )(input)
 }
 
@@ -165,7 +202,8 @@ pub fn parse_line_documentation_comment(input: &str) -> ParserResult<Token> {
 
 /// Parses a block documentation comment from the input string.
 ///
-/// Block documentation comments start with `/**` and end with `*/`. They can span multiple lines.
+/// Block documentation comments start with `/**` and end with `*/`. They can span multiple lines
+/// and nest like block comments. `/**/` is an empty block comment, not a documentation comment.
 /// They are used for generating documentation for the following item.
 /// The content of the comment is preserved as-is, including whitespace and newlines.
 ///
@@ -196,7 +234,7 @@ pub fn parse_block_documentation_comment(input: &str) -> ParserResult<Token> {
     context(
         "block document comment",
         map(
-            delimited(tag("/**"), take_until("*/"), tag("*/")),
+            preceded(terminated(tag("/**"), not(char('/'))), block_comment_body),
             |content: &str| Token::Comment {
                 content: content.to_string(),
                 comment_type: CommentType::DocumentationBlock,
@@ -309,15 +347,55 @@ mod tests {
     }
 
     #[test]
-    fn test_nested_looking_comment() {
-        let input = "/* outer /* not nested */ */";
-        let (_, token) = parse_comment(input).unwrap();
+    fn test_nested_block_comment() {
+        let input = "/* outer /* inner */ still outer */code";
+        let (rest, token) = parse_comment(input).unwrap();
         assert_eq!(
             token,
             Token::Comment {
-                content: " outer /* not nested ".to_string(),
+                content: " outer /* inner */ still outer ".to_string(),
                 comment_type: CommentType::Block,
             }
         );
+        assert_eq!(rest, "code");
+    }
+
+    #[test]
+    fn test_deeply_nested_documentation_comment() {
+        let input = "/** a /* b /* c */ */ */code";
+        let (rest, token) = parse_comment(input).unwrap();
+        assert_eq!(
+            token,
+            Token::Comment {
+                content: " a /* b /* c */ */ ".to_string(),
+                comment_type: CommentType::DocumentationBlock,
+            }
+        );
+        assert_eq!(rest, "code");
+    }
+
+    #[test]
+    fn test_empty_block_comment() {
+        let (rest, token) = parse_comment("/**/code").unwrap();
+        assert_eq!(
+            token,
+            Token::Comment {
+                content: "".to_string(),
+                comment_type: CommentType::Block,
+            }
+        );
+        assert_eq!(rest, "code");
+    }
+
+    #[test]
+    fn test_unterminated_block_comment() {
+        assert!(matches!(
+            parse_comment("/* outer /* inner */ never closed"),
+            Err(nom::Err::Failure(_))
+        ));
+        assert!(matches!(
+            parse_comment("/** doc"),
+            Err(nom::Err::Failure(_))
+        ));
     }
 }
@@ -59,6 +59,10 @@ pub enum Keyword {
     Config,
     /// Defines a policy statement.
     Policy,
+    /// Defines the prompt preamble (persona) of a world or agent.
+    Persona,
+    /// Defines a contract block declaring the requests an agent answers.
+    Contract,
     /// Defines a state block.
     State,
     /// Defines an observe block for event handling.
@@ -83,6 +87,8 @@ pub enum Keyword {
     Else,
     /// Used to return values from handlers.
     Return,
+    /// Used to publish partial responses from answer handlers.
+    Yield,
     /// Used for asynchronous operations.
     Await,
     /// Lifecycle hook for failure handling.
@@ -103,6 +109,8 @@ pub enum Keyword {
     To,
     /// Used in event and request handler definitions.
     On,
+    /// Introduces the precondition of an event or request handler.
+    Requires,
     /// Used for error propagation.
     #[strum(serialize = "reThrow")]
     ReThrow,
@@ -351,6 +359,34 @@ pub fn parse_keyword(input: &str) -> ParserResult<Token> {
                             not(peek(take_while1(|c: char| c.is_alphanumeric() || c == '_'))),
                         ),
                     ),
+                    value(
+                        Keyword::Yield,
+                        terminated(
+                            tag("yield"),
+                            not(peek(take_while1(|c: char| c.is_alphanumeric() || c == '_'))),
+                        ),
+                    ),
+                    value(
+                        Keyword::Persona,
+                        terminated(
+                            tag("persona"),
+                            not(peek(take_while1(|c: char| c.is_alphanumeric() || c == '_'))),
+                        ),
+                    ),
+                    value(
+                        Keyword::Contract,
+                        terminated(
+                            tag("contract"),
+                            not(peek(take_while1(|c: char| c.is_alphanumeric() || c == '_'))),
+                        ),
+                    ),
+                    value(
+                        Keyword::Requires,
+                        terminated(
+                            tag("requires"),
+                            not(peek(take_while1(|c: char| c.is_alphanumeric() || c == '_'))),
+                        ),
+                    ),
                     // TODO: Add more keywords when Keywords enum is updated
                 )),
             )),
@@ -376,6 +412,7 @@ mod tests {
             ("micro Test", Keyword::Micro),
             ("if Test", Keyword::If),
             ("return Test", Keyword::Return),
+            ("yield Test", Keyword::Yield),
             ("await Test", Keyword::Await),
             ("on Test", Keyword::On),
             ("with Test", Keyword::With),
@@ -7,7 +7,8 @@
 //!
 //! The following literal types are supported:
 //!
-//! * **String Literals**: Both single-quoted (`"text"`) and triple-quoted (`"""text"""`)
+//! * **String Literals**: Single-quoted (`"text"`), triple-quoted (`"""text"""`)
+//!   and raw (`r"text"`, `r#"text"#`)
 //! * **Numeric Literals**: Integers (`42`) and floating-point numbers (`3.14`)
 //! * **Boolean Literals**: `true` and `false`
 //! * **Null Literal**: `null`
@@ -31,11 +32,28 @@
 //! """;
 //! ```
 //!
+//! A `${...}` that does not enclose a plain name, such as `${self.counter}` in
+//! an example pasted into a prompt, is kept as text.
+//!
+//! ## Raw Strings
+//!
+//! Raw strings (`r"..."`) keep their content verbatim: quotes, braces and
+//! `${...}` are not interpolated. Wrap the content in matching `#` marks
+//! (`r#"..."#`, `r##"..."##`, ...) when it contains a `"` itself, which makes
+//! them convenient for pasting JSON examples into think prompts:
+//!
+//! ```ignore
+//! let example = r#"{"name": "${not_interpolated}"}"#;
+//! ```
+//!
+//! In every string form, Windows line endings (`\r\n`) are normalized to a
+//! single [`StringPart::NewLine`].
+//!
 //! ## Parsing Strategy
 //!
 //! Literals are parsed using specialized parsers for each type:
 //!
-//! * `parse_string_literal`: Handles both single and triple-quoted strings
+//! * `parse_string_literal`: Handles single, triple-quoted and raw strings
 //! * [`parse_float_literal`]: Parses floating-point numbers
 //! * [`parse_integer_literal`]: Parses integer numbers
 //! * [`parse_boolean_literal`]: Parses boolean values
@@ -47,10 +65,10 @@ use nom::{
         streaming::take_until,
     },
     character::complete::{char, digit1},
-    combinator::{map, map_res, opt, recognize},
-    error::context,
-    multi::many0,
-    sequence::{delimited, pair, tuple},
+    combinator::{all_consuming, map, map_res, not, opt, recognize},
+    error::{VerboseError, VerboseErrorKind, context},
+    multi::{many0, many0_count},
+    sequence::{delimited, pair, terminated, tuple},
 };
 use tracing::debug;
 
@@ -72,15 +90,22 @@ pub enum StringPart {
 
 /// Represents a string literal in the KAIREI DSL.
 ///
-/// KAIREI supports two types of string literals:
+/// KAIREI supports three types of string literals:
 /// - Single-quoted strings with interpolation support
 /// - Triple-quoted strings with preserved formatting and multi-line support
+/// - Raw strings whose content is taken verbatim, without interpolation
 #[derive(Debug, Clone, PartialEq)]
 pub enum StringLiteral {
     /// Single-quoted string with interpolation support
     Single(Vec<StringPart>),
     /// Triple-quoted string with preserved formatting
     Triple(Vec<StringPart>),
+    /// Raw string (`r"..."`, `r#"..."#`). Only contains `Literal` and `NewLine` parts.
+    Raw {
+        /// Number of `#` marks around the quotes
+        hashes: usize,
+        parts: Vec<StringPart>,
+    },
 }
 
 /// Represents a literal value in the KAIREI DSL.
@@ -103,9 +128,9 @@ pub enum Literal {
 
 /// Parses a string literal from the input string.
 ///
-/// This function attempts to parse either a triple-quoted string or a single-quoted string.
-/// Triple-quoted strings are tried first to ensure correct parsing of strings that start with
-/// triple quotes.
+/// This function attempts to parse a raw string, a triple-quoted string or a single-quoted
+/// string. Triple-quoted strings are tried before single-quoted strings to ensure correct
+/// parsing of strings that start with triple quotes.
 ///
 /// # Arguments
 ///
@@ -122,6 +147,8 @@ fn parse_string_literal(input: &str) -> ParserResult<Literal> {
     context(
         "string literal",
         alt((
+            // Raw string literal
+            parse_raw_string,
             // Triple-quoted string literal
             parse_triple_quote_string,
             // Regular string literal
@@ -167,21 +194,107 @@ pub fn parse_triple_quote_string(input: &str) -> ParserResult<Literal> {
     debug!("content: {}", content);
     debug!("remaining: START{}END", remaining);
 
-    let (_, lit) = context(
+    let (_, parts) = context(
         "triple quote string",
-        map(
-            many0(alt((
-                parse_newline,
-                parse_interpolation,
-                map(
-                    take_while1(|c| c != '$' && c != '\n' && c != '\r'),
-                    |content: &str| StringPart::Literal(content.to_string()),
-                ),
-            ))),
-            StringLiteral::Triple,
-        ),
+        all_consuming(many0(alt((
+            parse_newline,
+            parse_interpolation,
+            map(
+                take_while1(|c| c != '$' && c != '\n' && c != '\r'),
+                |content: &str| StringPart::Literal(content.to_string()),
+            ),
+            parse_special_char,
+            // 変数名でない ${...} は本文として残す
+            map(tag("${"), |content: &str| {
+                StringPart::Literal(content.to_string())
+            }),
+        )))),
     )(content)?;
-    Ok((remaining, Literal::String(lit)))
+    Ok((
+        remaining,
+        Literal::String(StringLiteral::Triple(merge_literal_parts(parts))),
+    ))
+}
+
+/// Parses a raw string from the input string.
+///
+/// Raw strings start with `r`, followed by zero or more `#` marks and a `"`. They end at
+/// the first `"` followed by the same number of `#` marks. The content is kept verbatim:
+/// `${...}` placeholders are **not** interpolated and quotes need no escaping.
+/// Line endings are normalized the same way as in triple-quoted strings.
+///
+/// Once the opening delimiter has been recognized, an unterminated string or a closing
+/// delimiter with too many `#` marks is reported as a failure instead of falling back to
+/// other token parsers.
+///
+/// # Arguments
+///
+/// * `input` - The input string to parse
+///
+/// # Returns
+///
+/// * `ParserResult<Literal>` - A result containing either the parsed literal and remaining input,
+///   or an error if parsing fails
+///
+/// # Examples
+///
+/// ```
+/// # use kairei_core::tokenizer::literal::{parse_raw_string, StringLiteral, StringPart, Literal};
+/// let input = "r#\"{\"name\": \"${name}\"}\"#";
+/// let (rest, literal) = parse_raw_string(input).unwrap();
+/// assert_eq!(
+///     literal,
+///     Literal::String(StringLiteral::Raw {
+///         hashes: 1,
+///         parts: vec![StringPart::Literal("{\"name\": \"${name}\"}".to_string())],
+///     })
+/// );
+/// assert_eq!(rest, "");
+/// ```
+#[tracing::instrument(level = "debug", skip(input))]
+pub fn parse_raw_string(input: &str) -> ParserResult<Literal> {
+    let (body, (_, hashes, _)) = context(
+        "raw string",
+        tuple((char('r'), many0_count(char('#')), char('"'))),
+    )(input)?;
+
+    let terminator = format!("\"{}", "#".repeat(hashes));
+    let Some(end) = body.find(&terminator) else {
+        return Err(raw_string_failure(input, "unterminated raw string"));
+    };
+    let content = &body[..end];
+    let remaining = &body[end + terminator.len()..];
+    if remaining.starts_with('#') {
+        return Err(raw_string_failure(
+            remaining,
+            "raw string terminated with too many '#' marks",
+        ));
+    }
+
+    let mut parts = Vec::new();
+    for (i, line) in content.split('\n').enumerate() {
+        if i > 0 {
+            parts.push(StringPart::NewLine);
+        }
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if !line.is_empty() {
+            parts.push(StringPart::Literal(line.to_string()));
+        }
+    }
+
+    Ok((
+        remaining,
+        Literal::String(StringLiteral::Raw { hashes, parts }),
+    ))
+}
+
+fn raw_string_failure<'a>(
+    input: &'a str,
+    message: &'static str,
+) -> nom::Err<VerboseError<&'a str>> {
+    nom::Err::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(message))],
+    })
 }
 
 /// Parses a single-quoted string from the input string.
@@ -212,10 +325,14 @@ pub fn parse_single_quote_string(input: &str) -> ParserResult<Literal> {
         map(
             delimited(
                 char('"'),
-                many0(alt((parse_interpolation, parse_string_literal_part))),
+                many0(alt((
+                    parse_interpolation,
+                    parse_string_literal_part,
+                    parse_special_char,
+                ))),
                 char('"'),
             ),
-            |parts| Literal::String(StringLiteral::Single(parts)),
+            |parts| Literal::String(StringLiteral::Single(merge_literal_parts(parts))),
         ),
     )(input)
 }
@@ -307,6 +424,38 @@ fn parse_string_literal_part(input: &str) -> ParserResult<StringPart> {
     )(input)
 }
 
+/// Parses a `$` that does not start an interpolation, or a lone `\r`, as literal text.
+///
+/// Without this, a price like `$5` or a stray carriage return would stop the string
+/// parser in the middle of the content. A malformed interpolation such as `${}` is
+/// still rejected in single-quoted strings.
+///
+/// Note: This is a private function used internally by the tokenizer.
+fn parse_special_char(input: &str) -> ParserResult<StringPart> {
+    context(
+        "special character",
+        map(
+            alt((
+                terminated(tag("$"), not(char('{'))),
+                terminated(tag("\r"), not(char('\n'))),
+            )),
+            |c: &str| StringPart::Literal(c.to_string()),
+        ),
+    )(input)
+}
+
+/// Joins adjacent `Literal` parts into a single part.
+fn merge_literal_parts(parts: Vec<StringPart>) -> Vec<StringPart> {
+    let mut merged: Vec<StringPart> = Vec::with_capacity(parts.len());
+    for part in parts {
+        match (merged.last_mut(), part) {
+            (Some(StringPart::Literal(prev)), StringPart::Literal(next)) => prev.push_str(&next),
+            (_, part) => merged.push(part),
+        }
+    }
+    merged
+}
+
 /// Parses a floating-point number from the input string.
 ///
 /// Floating-point numbers consist of an optional negative sign,
@@ -409,7 +558,7 @@ pub fn parse_boolean_literal(input: &str) -> ParserResult<Literal> {
 /// Parses any type of literal from the input string.
 ///
 /// This function attempts to match one of the supported literal types:
-/// - String literals (both single and triple-quoted)
+/// - String literals (single, triple-quoted and raw)
 /// - Floating-point numbers
 /// - Integer numbers
 /// - Boolean values
@@ -628,6 +777,159 @@ mod tests {
         }
     }
 
+    // raw string のテスト
+    mod raw_strings {
+        use super::*;
+
+        #[test]
+        fn test_simple_raw_string() {
+            let (rest, result) = parse_raw_string("r\"plain text\"").unwrap();
+            assert_eq!(rest, "");
+            assert_eq!(
+                result,
+                Literal::String(StringLiteral::Raw {
+                    hashes: 0,
+                    parts: vec![StringPart::Literal("plain text".to_string())],
+                })
+            );
+        }
+
+        #[test]
+        fn test_raw_string_with_json() {
+            let input = r###"r#"{"user": {"name": "${name}", "tags": ["a"]}}"# rest"###;
+            let (rest, result) = parse_raw_string(input).unwrap();
+            assert_eq!(rest, " rest");
+            assert_eq!(
+                result,
+                Literal::String(StringLiteral::Raw {
+                    hashes: 1,
+                    parts: vec![StringPart::Literal(
+                        r#"{"user": {"name": "${name}", "tags": ["a"]}}"#.to_string()
+                    )],
+                })
+            );
+        }
+
+        #[test]
+        fn test_raw_string_does_not_interpolate() {
+            let (_, result) = parse_raw_string("r\"${name}\"").unwrap();
+            assert_eq!(
+                result,
+                Literal::String(StringLiteral::Raw {
+                    hashes: 0,
+                    parts: vec![StringPart::Literal("${name}".to_string())],
+                })
+            );
+        }
+
+        #[test]
+        fn test_raw_string_contains_shorter_terminator() {
+            let input = r####"r##"say "#hi"#"##"####;
+            let (rest, result) = parse_raw_string(input).unwrap();
+            assert_eq!(rest, "");
+            assert_eq!(
+                result,
+                Literal::String(StringLiteral::Raw {
+                    hashes: 2,
+                    parts: vec![StringPart::Literal(r##"say "#hi"#"##.to_string())],
+                })
+            );
+        }
+
+        #[test]
+        fn test_raw_string_normalizes_crlf() {
+            let (_, result) = parse_raw_string("r\"line one\r\nline two\n\"").unwrap();
+            assert_eq!(
+                result,
+                Literal::String(StringLiteral::Raw {
+                    hashes: 0,
+                    parts: vec![
+                        StringPart::Literal("line one".to_string()),
+                        StringPart::NewLine,
+                        StringPart::Literal("line two".to_string()),
+                        StringPart::NewLine,
+                    ],
+                })
+            );
+        }
+
+        #[test]
+        fn test_unterminated_raw_string() {
+            let result = parse_raw_string("r#\"never closed\"");
+            assert!(matches!(result, Err(nom::Err::Failure(_))));
+        }
+
+        #[test]
+        fn test_raw_string_with_too_many_hashes() {
+            let result = parse_raw_string("r#\"text\"##");
+            assert!(matches!(result, Err(nom::Err::Failure(_))));
+        }
+
+        #[test]
+        fn test_identifier_starting_with_r_is_not_raw_string() {
+            let result = parse_raw_string("result");
+            assert!(matches!(result, Err(nom::Err::Error(_))));
+        }
+    }
+
+    // ヒアドキュメント（triple quote）の追加ケース
+    mod heredoc_strings {
+        use super::*;
+
+        #[test]
+        fn test_heredoc_normalizes_crlf() {
+            let input = "\"\"\"line one\r\nline two\"\"\"";
+            let (_, result) = parse_triple_quote_string(input).unwrap();
+            assert_eq!(
+                result,
+                Literal::String(StringLiteral::Triple(vec![
+                    StringPart::Literal("line one".to_string()),
+                    StringPart::NewLine,
+                    StringPart::Literal("line two".to_string()),
+                ]))
+            );
+        }
+
+        #[test]
+        fn test_heredoc_with_json_and_interpolation() {
+            let input = "\"\"\"Return {\"city\": \"${city}\", \"price\": \"$5\"}\"\"\"";
+            let (rest, result) = parse_triple_quote_string(input).unwrap();
+            assert_eq!(rest, "");
+            assert_eq!(
+                result,
+                Literal::String(StringLiteral::Triple(vec![
+                    StringPart::Literal("Return {\"city\": \"".to_string()),
+                    StringPart::Interpolation("city".to_string()),
+                    StringPart::Literal("\", \"price\": \"$5\"}".to_string()),
+                ]))
+            );
+        }
+
+        #[test]
+        fn test_heredoc_keeps_malformed_interpolation() {
+            let input = "\"\"\"return Ok(${self.counter}) or ${}\"\"\"";
+            let (rest, result) = parse_triple_quote_string(input).unwrap();
+            assert_eq!(rest, "");
+            assert_eq!(
+                result,
+                Literal::String(StringLiteral::Triple(vec![StringPart::Literal(
+                    "return Ok(${self.counter}) or ${}".to_string()
+                )]))
+            );
+        }
+
+        #[test]
+        fn test_single_quote_with_dollar() {
+            let (_, result) = parse_single_quote_string("\"costs $5\"").unwrap();
+            assert_eq!(
+                result,
+                Literal::String(StringLiteral::Single(vec![StringPart::Literal(
+                    "costs $5".to_string()
+                )]))
+            );
+        }
+    }
+
     #[test]
     fn test_number_literals() {
         // Integer
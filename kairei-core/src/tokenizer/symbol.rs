@@ -27,8 +27,10 @@ use strum_macros::{AsRefStr, Display, EnumString};
 use nom::{
     branch::alt,
     bytes::complete::tag,
-    combinator::{map, value},
-    error::context,
+    character::complete::satisfy,
+    combinator::{map, not, peek, value},
+    error::{ErrorKind, ParseError, VerboseError, context},
+    sequence::terminated,
 };
 
 use super::token::{ParserResult, Token};
@@ -84,6 +86,9 @@ pub enum Operator {
     /// Division operator (`/`)
     #[strum(serialize = "/")]
     Divide,
+    /// Remainder operator (`%`), parsed with the `experimental-operators` feature
+    #[strum(serialize = "%")]
+    Percent,
 
     /// Logical AND operator (`&&`)
     #[strum(serialize = "&&")]
@@ -94,11 +99,18 @@ pub enum Operator {
     /// Logical NOT operator (`!`)
     #[strum(serialize = "!")]
     Not,
+
+    /// Optional type marker (`?`), as in `note: String?`
+    #[strum(serialize = "?")]
+    Question,
 }
 
 /// Constant for the close brace character, used because direct serialization in strum causes errors.
 const CLOSE_BRACE: &str = "}";
 
+/// Names of the handler directives an `@` may introduce
+const DIRECTIVES: [&str; 2] = ["cache", "replay"];
+
 /// Represents delimiters in the KAIREI DSL.
 ///
 /// Delimiters are structural elements that define the boundaries of code blocks,
@@ -108,6 +120,9 @@ pub enum Delimiter {
     /// Opening brace (`{`) for blocks
     #[strum(serialize = "{")]
     OpenBrace,
+    /// Opening of a set literal (`#{`), closed by a brace
+    #[strum(serialize = "#{")]
+    OpenSetBrace,
     /// Closing brace (`}`) for blocks
     #[strum(serialize = "CLOSE_BRACE")]
     CloseBrace,
@@ -135,6 +150,10 @@ pub enum Delimiter {
     /// Equal sign (`=`) for assignment
     #[strum(serialize = "=")]
     Equal,
+    /// At sign (`@`) introducing a handler directive such as `@cache(...)` or
+    /// `@replay(...)`
+    #[strum(serialize = "@")]
+    At,
 }
 
 /// Parses an operator token from the input string.
@@ -187,7 +206,9 @@ pub fn parse_operator(input: &str) -> ParserResult<Token> {
                 value(Operator::Minus, tag("-")),
                 value(Operator::Multiply, tag("*")),
                 value(Operator::Divide, tag("/")),
+                value(Operator::Percent, tag("%")),
                 value(Operator::Not, tag("!")),
+                value(Operator::Question, tag("?")),
             )),
             Token::Operator,
         ),
@@ -225,6 +246,7 @@ pub fn parse_delimiter(input: &str) -> ParserResult<Token> {
         "delimiter",
         map(
             alt((
+                value(Delimiter::OpenSetBrace, tag("#{")),
                 value(Delimiter::OpenBrace, tag("{")),
                 value(Delimiter::CloseBrace, tag(CLOSE_BRACE)),
                 value(Delimiter::OpenParen, tag("(")),
@@ -235,12 +257,34 @@ pub fn parse_delimiter(input: &str) -> ParserResult<Token> {
                 value(Delimiter::Semicolon, tag(";")),
                 value(Delimiter::Colon, tag(":")),
                 value(Delimiter::Equal, tag("=")),
+                value(
+                    Delimiter::At,
+                    terminated(tag("@"), peek(parse_directive_name)),
+                ),
             )),
             Token::Delimiter,
         ),
     )(input)
 }
 
+/// Parses a directive name without consuming it. Any other `@` is not a token, so
+/// that a stray `@name` is reported by the tokenizer.
+fn parse_directive_name(input: &str) -> ParserResult<&str> {
+    for directive in DIRECTIVES {
+        let result: ParserResult<&str> = terminated(
+            tag(directive),
+            not(satisfy(|c: char| c.is_alphanumeric() || c == '_')),
+        )(input);
+        if result.is_ok() {
+            return result;
+        }
+    }
+    Err(nom::Err::Error(VerboseError::from_error_kind(
+        input,
+        ErrorKind::Tag,
+    )))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -256,6 +300,7 @@ mod tests {
             (">=", Token::Operator(Operator::GreaterEqual)),
             (".", Token::Operator(Operator::Dot)),
             (">", Token::Operator(Operator::Greater)),
+            ("?", Token::Operator(Operator::Question)),
         ];
 
         for (input, expected) in test_cases.iter() {
@@ -269,6 +314,7 @@ mod tests {
     fn test_delimiters() {
         let test_cases = [
             ("{", Token::Delimiter(Delimiter::OpenBrace)),
+            ("#{", Token::Delimiter(Delimiter::OpenSetBrace)),
             ("}", Token::Delimiter(Delimiter::CloseBrace)),
             ("(", Token::Delimiter(Delimiter::OpenParen)),
             (")", Token::Delimiter(Delimiter::CloseParen)),
@@ -287,6 +333,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_at_only_before_directives() {
+        for directive in ["cache", "replay"] {
+            let input = format!("@{}(", directive);
+            let (rest, token) = parse_delimiter(&input).unwrap();
+            assert_eq!(token, Token::Delimiter(Delimiter::At));
+            assert_eq!(rest, format!("{}(", directive));
+            assert!(parse_delimiter(&format!("@{}s", directive)).is_err());
+        }
+        assert!(parse_delimiter("@invalid_token").is_err());
+    }
+
     #[test]
     fn test_operator_precedence() {
         // ">="が">"として誤って解釈されないことを確認
@@ -35,15 +35,19 @@ use nom::{
     branch::alt,
     bytes::complete::{take_while, take_while1},
     combinator::recognize,
-    error::{VerboseError, context},
+    error::{VerboseError, VerboseErrorKind, context},
     sequence::pair,
 };
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use utoipa::ToSchema;
+
+use crate::message_catalog::{Diagnostic, codes};
 
 use super::{
-    comment::parse_comment,
+    comment::{UNTERMINATED_BLOCK_COMMENT, parse_comment},
     keyword::{Keyword, parse_keyword},
-    literal::{Literal, parse_literal},
+    literal::{Literal, StringLiteral, StringPart, parse_literal},
     symbol::{Delimiter, Operator, parse_delimiter, parse_operator},
     whitespace::{parse_newline, parse_whitespace},
 };
@@ -122,6 +126,19 @@ impl Token {
         matches!(self, Token::Comment { .. })
     }
 
+    /// Returns true if the token is a documentation comment (`///` or `/** */`).
+    ///
+    /// Documentation comments are attached to the following item by the parser.
+    pub fn is_doc_comment(&self) -> bool {
+        matches!(
+            self,
+            Token::Comment {
+                comment_type: CommentType::DocumentationLine | CommentType::DocumentationBlock,
+                ..
+            }
+        )
+    }
+
     /// Returns true if the token is a newline.
     ///
     /// Used to filter out newline tokens when only semantic tokens are needed.
@@ -254,6 +271,27 @@ impl Tokenizer {
                             found,
                             span,
                         },
+                        // 閉じられていないブロックコメントは開始位置で報告する
+                        nom::Err::Failure(e)
+                            if e.errors.iter().any(|(_, kind)| {
+                                matches!(
+                                    kind,
+                                    VerboseErrorKind::Context(UNTERMINATED_BLOCK_COMMENT)
+                                )
+                            }) =>
+                        {
+                            TokenizerError::ParseError {
+                                message: format!(
+                                    "Unterminated block comment starting at line {}, column {}",
+                                    span.line, span.column
+                                ),
+                                found,
+                                span: Span {
+                                    end: span.start + 2,
+                                    ..span
+                                },
+                            }
+                        }
                         nom::Err::Error(e) | nom::Err::Failure(e) => TokenizerError::ParseError {
                             message: nom::error::convert_error(remaining, e).to_string(),
                             found,
@@ -334,6 +372,100 @@ impl fmt::Display for Span {
     }
 }
 
+/// A token as emitted by [`tokenize_to_json`].
+///
+/// `kind` is one of `keyword`, `identifier`, `operator`, `delimiter`, `string`,
+/// `integer`, `float`, `boolean`, `null`, `whitespace`, `newline` and `comment`.
+/// Whitespace, newlines and comments are kept in the stream with `trivia` set,
+/// so that the source can be reconstructed from it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TokenJson {
+    pub kind: String,
+    /// The keyword, name or symbol, the decoded literal value, or the comment content
+    pub value: String,
+    pub trivia: bool,
+    /// Line number where the token starts (1-based).
+    pub line: usize,
+    /// Column number where the token starts (1-based, in characters).
+    pub column: usize,
+    pub span: ByteSpan,
+}
+
+/// Byte offsets of a token in the input string
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ByteSpan {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl From<&TokenSpan> for TokenJson {
+    fn from(token_span: &TokenSpan) -> Self {
+        let (kind, value) = match &token_span.token {
+            Token::Keyword(kw) => ("keyword", kw.to_string()),
+            Token::Identifier(id) => ("identifier", id.clone()),
+            Token::Operator(op) => ("operator", op.to_string()),
+            Token::Delimiter(d) => ("delimiter", d.to_string()),
+            Token::Literal(Literal::String(string)) => ("string", string_value(string)),
+            Token::Literal(Literal::Integer(i)) => ("integer", i.to_string()),
+            Token::Literal(Literal::Float(f)) => ("float", f.to_string()),
+            Token::Literal(Literal::Boolean(b)) => ("boolean", b.to_string()),
+            Token::Literal(Literal::Null) => ("null", "null".to_string()),
+            Token::Whitespace(ws) => ("whitespace", ws.clone()),
+            Token::Newline => ("newline", "\n".to_string()),
+            Token::Comment { content, .. } => ("comment", content.clone()),
+        };
+        let token = &token_span.token;
+        Self {
+            kind: kind.to_string(),
+            value,
+            trivia: token.is_whitespace() || token.is_newline() || token.is_comment(),
+            line: token_span.span.line,
+            column: token_span.span.column,
+            span: ByteSpan {
+                start: token_span.span.start,
+                end: token_span.span.end,
+            },
+        }
+    }
+}
+
+// 補間は `${name}` の形に戻す
+fn string_value(string: &StringLiteral) -> String {
+    let parts = match string {
+        StringLiteral::Single(parts) | StringLiteral::Triple(parts) => parts,
+        StringLiteral::Raw { parts, .. } => parts,
+    };
+    parts
+        .iter()
+        .map(|part| match part {
+            StringPart::Literal(text) => text.clone(),
+            StringPart::Interpolation(name) => format!("${{{}}}", name),
+            StringPart::NewLine => "\n".to_string(),
+        })
+        .collect()
+}
+
+impl Serialize for TokenSpan {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        TokenJson::from(self).serialize(serializer)
+    }
+}
+
+/// Tokenizes `source` into a JSON array of [`TokenJson`], for tooling and debugging.
+///
+/// # Examples
+///
+/// ```
+/// # use kairei_core::tokenizer::token::tokenize_to_json;
+/// let tokens = tokenize_to_json("count = 1").unwrap();
+/// assert_eq!(tokens[0]["kind"], "identifier");
+/// assert_eq!(tokens[1]["trivia"], true);
+/// ```
+pub fn tokenize_to_json(source: &str) -> TokenizerResult<serde_json::Value> {
+    let tokens = Tokenizer::new().tokenize(source)?;
+    Ok(serde_json::to_value(tokens).expect("tokens are always serializable"))
+}
+
 /// Parses an identifier or keyword from the input string.
 ///
 /// An identifier starts with a letter or underscore, followed by zero or more
@@ -384,6 +516,17 @@ pub enum TokenizerError {
     },
 }
 
+impl TokenizerError {
+    /// The diagnostic of this error, rendered by a [`MessageCatalog`](crate::message_catalog::MessageCatalog)
+    pub fn diagnostic(&self) -> Diagnostic {
+        match self {
+            Self::ParseError { message, found, .. } => Diagnostic::new(codes::TOKENIZE_INVALID)
+                .with_arg("message", message)
+                .with_arg("found", found),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -652,4 +795,71 @@ mod tests {
             .count();
         assert_eq!(answer_count, 1);
     }
+
+    #[test]
+    fn test_nested_block_comment_tokens() {
+        let mut tokenizer = Tokenizer::new();
+        let input = "/* outer /* inner */ */ micro";
+        let tokens = tokenizer.tokenize(input).unwrap();
+
+        assert_eq!(
+            tokens[0].token,
+            Token::Comment {
+                content: " outer /* inner */ ".to_string(),
+                comment_type: CommentType::Block,
+            }
+        );
+        assert_eq!(tokens[2].token, Token::Keyword(Keyword::Micro));
+    }
+
+    #[test]
+    fn test_unterminated_block_comment_position() {
+        let mut tokenizer = Tokenizer::new();
+        let input = "micro Agent {\n  /* outer /* inner */\n}";
+        let result = tokenizer.tokenize(input);
+
+        match result {
+            Err(TokenizerError::ParseError { message, span, .. }) => {
+                assert!(message.contains("Unterminated block comment"));
+                assert_eq!(span.line, 2);
+                assert_eq!(span.column, 3);
+                assert_eq!(span.start, 16);
+            }
+            other => panic!("Expected unterminated comment error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_tokenize_to_json() {
+        let tokens = tokenize_to_json("x = 1 // one\ny").unwrap();
+        assert_eq!(
+            tokens,
+            serde_json::json!([
+                {"kind": "identifier", "value": "x", "trivia": false, "line": 1, "column": 1, "span": {"start": 0, "end": 1}},
+                {"kind": "whitespace", "value": " ", "trivia": true, "line": 1, "column": 2, "span": {"start": 1, "end": 2}},
+                {"kind": "delimiter", "value": "=", "trivia": false, "line": 1, "column": 3, "span": {"start": 2, "end": 3}},
+                {"kind": "whitespace", "value": " ", "trivia": true, "line": 1, "column": 4, "span": {"start": 3, "end": 4}},
+                {"kind": "integer", "value": "1", "trivia": false, "line": 1, "column": 5, "span": {"start": 4, "end": 5}},
+                {"kind": "whitespace", "value": " ", "trivia": true, "line": 1, "column": 6, "span": {"start": 5, "end": 6}},
+                {"kind": "comment", "value": "one", "trivia": true, "line": 1, "column": 7, "span": {"start": 6, "end": 12}},
+                {"kind": "newline", "value": "\n", "trivia": true, "line": 1, "column": 13, "span": {"start": 12, "end": 13}},
+                {"kind": "identifier", "value": "y", "trivia": false, "line": 2, "column": 1, "span": {"start": 13, "end": 14}},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_tokenize_to_json_multibyte_columns() {
+        // column は文字数、span はバイト数で数える
+        let tokens = tokenize_to_json("\"日本語\" + x").unwrap();
+        let token = |i: usize| serde_json::from_value::<TokenJson>(tokens[i].clone()).unwrap();
+
+        assert_eq!(token(0).kind, "string");
+        assert_eq!(token(0).value, "日本語");
+        assert_eq!(token(0).span, ByteSpan { start: 0, end: 11 });
+        assert_eq!(token(2).value, "+");
+        assert_eq!(token(2).column, 7);
+        assert_eq!(token(2).span, ByteSpan { start: 12, end: 13 });
+        assert_eq!(token(4).column, 9);
+    }
 }
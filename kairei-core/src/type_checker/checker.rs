@@ -38,7 +38,7 @@ impl TypeChecker {
 
     /// Register all built-in types
     fn register_builtin_types(&mut self) {
-        let builtin_types = ["Int", "Float", "String", "Boolean", "Duration"];
+        let builtin_types = ["Int", "Float", "String", "Boolean", "Duration", "Timestamp"];
         for type_name in builtin_types.iter() {
             self.context.scope.insert_type(
                 type_name.to_string(),
@@ -1,4 +1,5 @@
 use crate::ast::TypeInfo;
+use crate::message_catalog::{Diagnostic, codes};
 use thiserror::Error;
 
 #[derive(Debug, Clone)]
@@ -36,6 +37,13 @@ pub enum TypeCheckError {
         meta: TypeCheckErrorMeta,
     },
 
+    /// Declared types that contain each other directly, listed along the cycle
+    #[error("Recursive type without indirection: {}", .cycle.join(" -> "))]
+    RecursiveType {
+        cycle: Vec<String>,
+        meta: TypeCheckErrorMeta,
+    },
+
     #[error("Invalid state variable: {message}")]
     InvalidStateVariable {
         message: String,
@@ -109,6 +117,12 @@ pub enum TypeCheckError {
         message: String,
         meta: TypeCheckErrorMeta,
     },
+
+    #[error("Invalid schedule: {message}")]
+    InvalidSchedule {
+        message: String,
+        meta: TypeCheckErrorMeta,
+    },
 }
 
 #[derive(Error, Debug, Clone)]
@@ -162,6 +176,7 @@ impl TypeCheckError {
             Self::InvalidTypeArguments { message, .. } => {
                 Self::InvalidTypeArguments { message, meta }
             }
+            Self::RecursiveType { cycle, .. } => Self::RecursiveType { cycle, meta },
             Self::InvalidThinkBlock { message, .. } => Self::InvalidThinkBlock { message, meta },
             Self::InvalidHandlerSignature { message, .. } => {
                 Self::InvalidHandlerSignature { message, meta }
@@ -179,10 +194,80 @@ impl TypeCheckError {
             Self::InvalidSistenceContextError { message, .. } => {
                 Self::InvalidSistenceContextError { message, meta }
             }
+            Self::InvalidSchedule { message, .. } => Self::InvalidSchedule { message, meta },
             _ => self,
         }
     }
 
+    /// The diagnostic of this error, rendered by a [`MessageCatalog`](crate::message_catalog::MessageCatalog)
+    pub fn diagnostic(&self) -> Diagnostic {
+        match self {
+            Self::TypeMismatch {
+                expected, found, ..
+            } => Diagnostic::new(codes::TYPE_MISMATCH)
+                .with_arg("expected", expected)
+                .with_arg("found", found),
+            Self::UndefinedType { name, .. } => {
+                Diagnostic::new(codes::TYPE_UNDEFINED_TYPE).with_arg("name", name)
+            }
+            Self::InvalidTypeArguments { message, .. } => {
+                Diagnostic::new(codes::TYPE_INVALID_TYPE_ARGUMENTS).with_arg("message", message)
+            }
+            Self::RecursiveType { cycle, .. } => {
+                Diagnostic::new(codes::TYPE_RECURSIVE_TYPE).with_arg("cycle", cycle.join(" -> "))
+            }
+            Self::InvalidStateVariable { message, .. } => {
+                Diagnostic::new(codes::TYPE_INVALID_STATE_VARIABLE).with_arg("message", message)
+            }
+            Self::InvalidHandlerSignature { message, .. } => {
+                Diagnostic::new(codes::TYPE_INVALID_HANDLER_SIGNATURE).with_arg("message", message)
+            }
+            Self::InvalidThinkBlock { message, .. } => {
+                Diagnostic::new(codes::TYPE_INVALID_THINK_BLOCK).with_arg("message", message)
+            }
+            Self::TypeInferenceError { message, .. } => {
+                Diagnostic::new(codes::TYPE_INFERENCE).with_arg("message", message)
+            }
+            Self::UndefinedVariable { name, .. } => {
+                Diagnostic::new(codes::TYPE_UNDEFINED_VARIABLE).with_arg("name", name)
+            }
+            Self::UndefinedFunction { name, .. } => {
+                Diagnostic::new(codes::TYPE_UNDEFINED_FUNCTION).with_arg("name", name)
+            }
+            Self::InvalidReturnType {
+                expected, found, ..
+            } => Diagnostic::new(codes::TYPE_INVALID_RETURN_TYPE)
+                .with_arg("expected", expected)
+                .with_arg("found", found),
+            Self::InvalidArgumentType(data) => Diagnostic::new(codes::TYPE_INVALID_ARGUMENT_TYPE)
+                .with_arg("function", &data.function)
+                .with_arg("argument", &data.argument)
+                .with_arg("expected", &data.expected)
+                .with_arg("found", &data.found),
+            Self::InvalidOperatorType {
+                operator,
+                left_type,
+                right_type,
+                ..
+            } => Diagnostic::new(codes::TYPE_INVALID_OPERATOR_TYPE)
+                .with_arg("operator", operator)
+                .with_arg("left_type", left_type)
+                .with_arg("right_type", right_type),
+            Self::InvalidWillActionError { message, .. } => {
+                Diagnostic::new(codes::TYPE_INVALID_WILL_ACTION).with_arg("message", message)
+            }
+            Self::WillActionParameterError { message, .. } => {
+                Diagnostic::new(codes::TYPE_WILL_ACTION_PARAMETER).with_arg("message", message)
+            }
+            Self::InvalidSistenceContextError { message, .. } => {
+                Diagnostic::new(codes::TYPE_INVALID_SISTENCE_CONTEXT).with_arg("message", message)
+            }
+            Self::InvalidSchedule { message, .. } => {
+                Diagnostic::new(codes::TYPE_INVALID_SCHEDULE).with_arg("message", message)
+            }
+        }
+    }
+
     pub fn type_mismatch(expected: TypeInfo, found: TypeInfo, location: Location) -> Self {
         Self::TypeMismatch {
             expected,
@@ -287,6 +372,16 @@ impl TypeCheckError {
         }
     }
 
+    pub fn recursive_type(cycle: Vec<String>, location: Location) -> Self {
+        Self::RecursiveType {
+            cycle,
+            meta: TypeCheckErrorMeta::default()
+                .with_location(location)
+                .with_help("A value of a type that contains itself directly would never end")
+                .with_suggestion("Wrap one field of the cycle in Option, Array, Set or Map"),
+        }
+    }
+
     pub fn undefined_function(name: String, location: Location) -> Self {
         Self::UndefinedFunction {
             name: name.clone(),
@@ -368,6 +463,18 @@ impl TypeCheckError {
                 .with_suggestion("Check that the sistence agent is properly defined and used within a valid scope"),
         }
     }
+
+    pub fn invalid_schedule(message: String, location: Location) -> Self {
+        Self::InvalidSchedule {
+            message,
+            meta: TypeCheckErrorMeta::default()
+                .with_location(location)
+                .with_help("Invalid schedule declaration in the world")
+                .with_suggestion(
+                    "Use a 5-field cron expression (minute hour day-of-month month day-of-week) and named literal arguments",
+                ),
+        }
+    }
 }
 
 impl TypeCheckErrorMeta {
@@ -530,6 +637,34 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_english_diagnostics_match_display() {
+        use crate::message_catalog::MessageCatalog;
+
+        let location = Location::default();
+        let errors = vec![
+            TypeCheckError::type_mismatch(
+                TypeInfo::Simple("Int".to_string()),
+                TypeInfo::Simple("String".to_string()),
+                location.clone(),
+            ),
+            TypeCheckError::undefined_variable("count".to_string(), location.clone()),
+            TypeCheckError::invalid_argument_type(
+                "len".to_string(),
+                "value".to_string(),
+                TypeInfo::Simple("String".to_string()),
+                TypeInfo::Simple("Int".to_string()),
+                location,
+            ),
+        ];
+        for error in errors {
+            assert_eq!(
+                MessageCatalog::default().render(&error.diagnostic()),
+                error.to_string()
+            );
+        }
+    }
+
     #[test]
     fn test_type_inference_error_with_meta() {
         let location = Location {
@@ -0,0 +1,455 @@
+//! Types declared with `type`, and the instantiation of the generic ones.
+//!
+//! `type Pair<A, B> { first: A, second: B }` declares a type generic over `A` and
+//! `B`. A use such as `Pair<Int, String>` must pass as many type arguments as the
+//! declaration has parameters. The type checker replaces each use in the AST with
+//! the `Custom` type it instantiates: the declared fields with the arguments
+//! substituted for the parameters, named as written (`Pair<Int, String>`). A type
+//! declared without parameters is used by its name alone.
+//!
+//! Declared types may refer to each other, and to themselves, through `Option`,
+//! `Array`, `Set` or `Map`, which can be empty. A type that reaches itself through
+//! fields that always hold a value, as in `type A { b: B }` and `type B { a: A }`,
+//! is an error naming the cycle (`A -> B -> A`), since its values would never end.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    ast::{
+        AnswerDef, FieldInfo, ObserveDef, Parameter, ReactDef, Root, StateDef, TypeDef, TypeInfo,
+    },
+    type_checker::{TypeCheckError, TypeCheckResult, TypeScope},
+};
+
+/// Registers the declared types in the current scope and checks their fields. A
+/// field may refer to a parameter of its type, a built-in type or another declared
+/// type.
+pub fn register_type_defs(type_defs: &[TypeDef], scope: &mut TypeScope) -> TypeCheckResult<()> {
+    // 宣言の順序によらず互いに参照できるよう、先に全て登録する
+    for type_def in type_defs {
+        let mut seen = HashSet::new();
+        if let Some(param) = type_def.type_params.iter().find(|p| !seen.insert(*p)) {
+            return Err(TypeCheckError::invalid_type_arguments(
+                format!(
+                    "Type parameter {} is declared twice in {}",
+                    param, type_def.name
+                ),
+                Default::default(),
+            ));
+        }
+        scope.insert_type_def(type_def.clone());
+    }
+
+    for type_def in type_defs {
+        scope.enter_scope();
+        for param in &type_def.type_params {
+            scope.insert_type_parameter(param.clone());
+        }
+        let result = type_def
+            .fields
+            .values()
+            .filter_map(|field| field.type_info.as_ref())
+            .try_for_each(|type_info| check_field_type(type_info, scope));
+        scope.exit_scope();
+        result?;
+    }
+
+    let defs: HashMap<&str, &TypeDef> = type_defs
+        .iter()
+        .map(|type_def| (type_def.name.as_str(), type_def))
+        .collect();
+    let mut checked = HashSet::new();
+    for type_def in type_defs {
+        find_cycle(&type_def.name, &defs, &mut vec![], &mut checked)?;
+    }
+    Ok(())
+}
+
+/// Follows the types held by `name` depth first. `path` holds the types being
+/// followed, so reaching one of them again closes a cycle.
+fn find_cycle<'a>(
+    name: &'a str,
+    defs: &HashMap<&str, &'a TypeDef>,
+    path: &mut Vec<&'a str>,
+    checked: &mut HashSet<&'a str>,
+) -> TypeCheckResult<()> {
+    if checked.contains(name) {
+        return Ok(());
+    }
+    if let Some(start) = path.iter().position(|visited| *visited == name) {
+        let mut cycle: Vec<String> = path[start..].iter().map(|n| n.to_string()).collect();
+        cycle.push(name.to_string());
+        return Err(TypeCheckError::recursive_type(cycle, Default::default()));
+    }
+
+    let type_def = defs[name];
+    // 報告する循環が決まるよう、フィールド名の順に辿る
+    let mut fields: Vec<_> = type_def.fields.iter().collect();
+    fields.sort_by_key(|(field_name, _)| *field_name);
+    let mut held = vec![];
+    for type_info in fields
+        .iter()
+        .filter_map(|(_, field)| field.type_info.as_ref())
+    {
+        held_types(type_info, defs, &mut held);
+    }
+
+    path.push(type_def.name.as_str());
+    for next in held {
+        find_cycle(next, defs, path, checked)?;
+    }
+    path.pop();
+    checked.insert(type_def.name.as_str());
+    Ok(())
+}
+
+/// Adds to `held` the declared types a value of `type_info` always holds: the type
+/// it names, the arguments its declaration holds and both sides of a `Result`
+fn held_types<'a>(
+    type_info: &TypeInfo,
+    defs: &HashMap<&str, &'a TypeDef>,
+    held: &mut Vec<&'a str>,
+) {
+    match type_info {
+        TypeInfo::Simple(name) => {
+            if let Some(&type_def) = defs.get(name.as_str()) {
+                held.push(type_def.name.as_str());
+            }
+        }
+        TypeInfo::Generic { name, args } => {
+            let Some(&type_def) = defs.get(name.as_str()) else {
+                return;
+            };
+            held.push(type_def.name.as_str());
+            for (param, arg) in type_def.type_params.iter().zip(args) {
+                if holds_parameter(type_def, param, defs, &mut HashSet::new()) {
+                    held_types(arg, defs, held);
+                }
+            }
+        }
+        TypeInfo::Result { ok_type, err_type } => {
+            held_types(ok_type, defs, held);
+            held_types(err_type, defs, held);
+        }
+        TypeInfo::Custom { fields, .. } => {
+            for type_info in fields.values().filter_map(|field| field.type_info.as_ref()) {
+                held_types(type_info, defs, held);
+            }
+        }
+        TypeInfo::Option(_) | TypeInfo::Array(_) | TypeInfo::Set(_) | TypeInfo::Map(_, _) => {}
+    }
+}
+
+/// Whether a value of `type_def` always holds a value of its parameter `param`
+fn holds_parameter(
+    type_def: &TypeDef,
+    param: &str,
+    defs: &HashMap<&str, &TypeDef>,
+    following: &mut HashSet<(String, String)>,
+) -> bool {
+    // 辿っている途中の組は含まないとみなす。循環そのものは find_cycle が報告する
+    let key = (type_def.name.clone(), param.to_string());
+    if !following.insert(key.clone()) {
+        return false;
+    }
+    let holds = type_def
+        .fields
+        .values()
+        .filter_map(|field| field.type_info.as_ref())
+        .any(|type_info| type_holds_parameter(type_info, param, defs, following));
+    following.remove(&key);
+    holds
+}
+
+fn type_holds_parameter(
+    type_info: &TypeInfo,
+    param: &str,
+    defs: &HashMap<&str, &TypeDef>,
+    following: &mut HashSet<(String, String)>,
+) -> bool {
+    match type_info {
+        TypeInfo::Simple(name) => name == param,
+        TypeInfo::Generic { name, args } => defs.get(name.as_str()).is_some_and(|type_def| {
+            type_def.type_params.iter().zip(args).any(|(inner, arg)| {
+                type_holds_parameter(arg, param, defs, following)
+                    && holds_parameter(type_def, inner, defs, following)
+            })
+        }),
+        TypeInfo::Result { ok_type, err_type } => {
+            type_holds_parameter(ok_type, param, defs, following)
+                || type_holds_parameter(err_type, param, defs, following)
+        }
+        TypeInfo::Custom { fields, .. } => fields
+            .values()
+            .filter_map(|field| field.type_info.as_ref())
+            .any(|type_info| type_holds_parameter(type_info, param, defs, following)),
+        TypeInfo::Option(_) | TypeInfo::Array(_) | TypeInfo::Set(_) | TypeInfo::Map(_, _) => false,
+    }
+}
+
+fn check_field_type(type_info: &TypeInfo, scope: &mut TypeScope) -> TypeCheckResult<()> {
+    match type_info {
+        TypeInfo::Simple(name)
+            if !scope.is_type_parameter(name)
+                && !scope.contains_type(name)
+                && scope.get_type_def(name).is_none() =>
+        {
+            Err(TypeCheckError::undefined_type(
+                name.clone(),
+                Default::default(),
+            ))
+        }
+        _ => resolve_type(type_info, scope).map(|_| ()),
+    }
+}
+
+/// Replaces the uses of declared types in the types of the root's state variables,
+/// parameters and return types with the types they instantiate
+pub fn instantiate_root_types(root: &mut Root, scope: &mut TypeScope) -> TypeCheckResult<()> {
+    if let Some(world) = &mut root.world_def {
+        for event in &mut world.events.events {
+            instantiate_parameters(&mut event.parameters, scope)?;
+        }
+        for handler in &mut world.handlers.handlers {
+            instantiate_parameters(&mut handler.parameters, scope)?;
+        }
+    }
+    for agent in &mut root.micro_agent_defs {
+        instantiate_handler_types(
+            agent.state.as_mut(),
+            agent.observe.as_mut(),
+            agent.answer.as_mut(),
+            agent.react.as_mut(),
+            scope,
+        )?;
+        for contract in &mut agent.contracts {
+            instantiate_parameters(&mut contract.parameters, scope)?;
+            instantiate(&mut contract.return_type, scope)?;
+        }
+        if let Some(config) = &mut agent.config {
+            for parameter in &mut config.parameters {
+                instantiate(&mut parameter.type_info, scope)?;
+            }
+        }
+    }
+    for agent in &mut root.sistence_agent_defs {
+        instantiate_handler_types(
+            agent.state.as_mut(),
+            agent.observe.as_mut(),
+            agent.answer.as_mut(),
+            agent.react.as_mut(),
+            scope,
+        )?;
+    }
+    Ok(())
+}
+
+/// The type `type_info` stands for, with the declared types it uses instantiated.
+/// Each instance is registered in the current scope under its name.
+pub fn resolve_type(type_info: &TypeInfo, scope: &mut TypeScope) -> TypeCheckResult<TypeInfo> {
+    match type_info {
+        TypeInfo::Simple(name) if scope.is_type_parameter(name) => Ok(type_info.clone()),
+        TypeInfo::Simple(name) => match scope.get_type_def(name).cloned() {
+            Some(type_def) => instantiate_type_def(&type_def, vec![], scope),
+            None => Ok(type_info.clone()),
+        },
+        TypeInfo::Generic { name, args } => {
+            let Some(type_def) = scope.get_type_def(name).cloned() else {
+                return Err(TypeCheckError::undefined_type(
+                    name.clone(),
+                    Default::default(),
+                ));
+            };
+            let args = args
+                .iter()
+                .map(|arg| resolve_type(arg, scope))
+                .collect::<TypeCheckResult<Vec<_>>>()?;
+            instantiate_type_def(&type_def, args, scope)
+        }
+        TypeInfo::Result { ok_type, err_type } => Ok(TypeInfo::Result {
+            ok_type: Box::new(resolve_type(ok_type, scope)?),
+            err_type: Box::new(resolve_type(err_type, scope)?),
+        }),
+        TypeInfo::Option(inner) => Ok(TypeInfo::Option(Box::new(resolve_type(inner, scope)?))),
+        TypeInfo::Array(inner) => Ok(TypeInfo::Array(Box::new(resolve_type(inner, scope)?))),
+        TypeInfo::Set(inner) => Ok(TypeInfo::Set(Box::new(resolve_type(inner, scope)?))),
+        TypeInfo::Map(key, value) => Ok(TypeInfo::Map(
+            Box::new(resolve_type(key, scope)?),
+            Box::new(resolve_type(value, scope)?),
+        )),
+        TypeInfo::Custom { name, fields } => Ok(TypeInfo::Custom {
+            name: name.clone(),
+            fields: resolve_fields(fields, scope)?,
+        }),
+    }
+}
+
+fn instantiate_type_def(
+    type_def: &TypeDef,
+    args: Vec<TypeInfo>,
+    scope: &mut TypeScope,
+) -> TypeCheckResult<TypeInfo> {
+    if args.len() != type_def.type_params.len() {
+        return Err(TypeCheckError::invalid_type_arguments(
+            format!(
+                "{} takes {} type argument(s), found {}",
+                type_def.name,
+                type_def.type_params.len(),
+                args.len()
+            ),
+            Default::default(),
+        ));
+    }
+    let name = if args.is_empty() {
+        type_def.name.clone()
+    } else {
+        TypeInfo::Generic {
+            name: type_def.name.clone(),
+            args: args.clone(),
+        }
+        .to_string()
+    };
+    // 具体化済みの型は使い回す。自分自身を参照するフィールドは展開中の (フィールドのない) 型を指す
+    if let Some(instance) = scope.get_type(&name) {
+        return Ok(instance);
+    }
+    scope.insert_type(
+        name.clone(),
+        TypeInfo::Custom {
+            name: name.clone(),
+            fields: HashMap::new(),
+        },
+    );
+
+    let substitutions: HashMap<_, _> = type_def.type_params.iter().cloned().zip(args).collect();
+    let fields = type_def
+        .fields
+        .iter()
+        .map(|(field_name, field)| {
+            let field = FieldInfo {
+                type_info: field
+                    .type_info
+                    .as_ref()
+                    .map(|type_info| substitute(type_info, &substitutions)),
+                default_value: field.default_value.clone(),
+            };
+            (field_name.clone(), field)
+        })
+        .collect();
+    let instance = TypeInfo::Custom {
+        name: name.clone(),
+        fields: resolve_fields(&fields, scope)?,
+    };
+    scope.insert_type(name, instance.clone());
+    Ok(instance)
+}
+
+fn resolve_fields(
+    fields: &HashMap<String, FieldInfo>,
+    scope: &mut TypeScope,
+) -> TypeCheckResult<HashMap<String, FieldInfo>> {
+    fields
+        .iter()
+        .map(|(name, field)| {
+            let type_info = match &field.type_info {
+                Some(type_info) => Some(resolve_type(type_info, scope)?),
+                None => None,
+            };
+            Ok((
+                name.clone(),
+                FieldInfo {
+                    type_info,
+                    default_value: field.default_value.clone(),
+                },
+            ))
+        })
+        .collect()
+}
+
+/// `type_info` with the type parameters replaced by their arguments
+fn substitute(type_info: &TypeInfo, substitutions: &HashMap<String, TypeInfo>) -> TypeInfo {
+    let boxed = |inner: &TypeInfo| Box::new(substitute(inner, substitutions));
+    match type_info {
+        TypeInfo::Simple(name) => substitutions
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| type_info.clone()),
+        TypeInfo::Result { ok_type, err_type } => TypeInfo::Result {
+            ok_type: boxed(ok_type),
+            err_type: boxed(err_type),
+        },
+        TypeInfo::Option(inner) => TypeInfo::Option(boxed(inner)),
+        TypeInfo::Array(inner) => TypeInfo::Array(boxed(inner)),
+        TypeInfo::Set(inner) => TypeInfo::Set(boxed(inner)),
+        TypeInfo::Map(key, value) => TypeInfo::Map(boxed(key), boxed(value)),
+        TypeInfo::Custom { name, fields } => TypeInfo::Custom {
+            name: name.clone(),
+            fields: fields
+                .iter()
+                .map(|(field_name, field)| {
+                    let field = FieldInfo {
+                        type_info: field
+                            .type_info
+                            .as_ref()
+                            .map(|type_info| substitute(type_info, substitutions)),
+                        default_value: field.default_value.clone(),
+                    };
+                    (field_name.clone(), field)
+                })
+                .collect(),
+        },
+        TypeInfo::Generic { name, args } => TypeInfo::Generic {
+            name: name.clone(),
+            args: args
+                .iter()
+                .map(|arg| substitute(arg, substitutions))
+                .collect(),
+        },
+    }
+}
+
+fn instantiate(type_info: &mut TypeInfo, scope: &mut TypeScope) -> TypeCheckResult<()> {
+    *type_info = resolve_type(type_info, scope)?;
+    Ok(())
+}
+
+fn instantiate_parameters(
+    parameters: &mut [Parameter],
+    scope: &mut TypeScope,
+) -> TypeCheckResult<()> {
+    parameters
+        .iter_mut()
+        .try_for_each(|parameter| instantiate(&mut parameter.type_info, scope))
+}
+
+fn instantiate_handler_types(
+    state: Option<&mut StateDef>,
+    observe: Option<&mut ObserveDef>,
+    answer: Option<&mut AnswerDef>,
+    react: Option<&mut ReactDef>,
+    scope: &mut TypeScope,
+) -> TypeCheckResult<()> {
+    if let Some(state) = state {
+        for variable in state.variables.values_mut() {
+            instantiate(&mut variable.type_info, scope)?;
+        }
+    }
+    for handler in observe
+        .into_iter()
+        .flat_map(|observe| observe.handlers.iter_mut())
+        .chain(
+            react
+                .into_iter()
+                .flat_map(|react| react.handlers.iter_mut()),
+        )
+    {
+        instantiate_parameters(&mut handler.parameters, scope)?;
+    }
+    if let Some(answer) = answer {
+        for handler in &mut answer.handlers {
+            instantiate_parameters(&mut handler.parameters, scope)?;
+            instantiate(&mut handler.return_type, scope)?;
+        }
+    }
+    Ok(())
+}
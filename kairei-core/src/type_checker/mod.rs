@@ -1,5 +1,6 @@
 pub mod checker;
 mod error;
+pub mod generics;
 mod init;
 mod plugin_config_validator;
 pub mod plugin_interface;
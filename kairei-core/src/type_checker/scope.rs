@@ -1,6 +1,6 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::ast::TypeInfo;
+use crate::ast::{TypeDef, TypeInfo};
 
 /// Manages type scopes for type checking
 #[derive(Clone)]
@@ -17,6 +17,10 @@ pub struct TypeScope {
 #[derive(Clone)]
 pub struct TypeScopeLayer {
     pub types: HashMap<String, TypeInfo>,
+    /// Types declared with `type`, by name
+    pub type_defs: HashMap<String, TypeDef>,
+    /// Parameters of the generic type whose fields are being checked
+    pub type_parameters: HashSet<String>,
 }
 
 impl Default for TypeScopeLayer {
@@ -29,6 +33,8 @@ impl TypeScopeLayer {
     fn new() -> Self {
         Self {
             types: HashMap::new(),
+            type_defs: HashMap::new(),
+            type_parameters: HashSet::new(),
         }
     }
 }
@@ -83,6 +89,35 @@ impl TypeScope {
         }
     }
 
+    /// Get a type declared with `type`, searching from innermost to outermost scope
+    pub fn get_type_def(&self, name: &str) -> Option<&TypeDef> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.type_defs.get(name))
+    }
+
+    /// Insert a type declared with `type` into the current scope
+    pub fn insert_type_def(&mut self, type_def: TypeDef) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.type_defs.insert(type_def.name.clone(), type_def);
+        }
+    }
+
+    /// Check if `name` is a type parameter in any scope
+    pub fn is_type_parameter(&self, name: &str) -> bool {
+        self.scopes
+            .iter()
+            .any(|scope| scope.type_parameters.contains(name))
+    }
+
+    /// Insert a type parameter into the current scope
+    pub fn insert_type_parameter(&mut self, name: String) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.type_parameters.insert(name);
+        }
+    }
+
     /// Clear all scopes and reset to initial state
     pub fn clear(&mut self) {
         self.scopes.clear();
@@ -191,4 +226,24 @@ mod tests {
         assert_eq!(scope.depth(), 1);
         assert!(!scope.contains_type("int"));
     }
+
+    #[test]
+    fn test_type_defs_and_parameters() {
+        let mut scope = TypeScope::new();
+        scope.insert_type_def(TypeDef {
+            name: "Pair".to_string(),
+            type_params: vec!["A".to_string(), "B".to_string()],
+            fields: HashMap::new(),
+        });
+
+        scope.enter_scope();
+        scope.insert_type_parameter("A".to_string());
+        assert!(scope.is_type_parameter("A"));
+        assert!(scope.get_type_def("Pair").is_some());
+
+        // 型パラメータは宣言の本体を検査するスコープの中だけで有効
+        scope.exit_scope();
+        assert!(!scope.is_type_parameter("A"));
+        assert_eq!(scope.get_type_def("Pair").unwrap().type_params.len(), 2);
+    }
 }
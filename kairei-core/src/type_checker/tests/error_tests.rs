@@ -325,3 +325,104 @@ fn test_invalid_think_block_with_meta() {
         panic!("Expected InvalidThinkBlock error");
     }
 }
+
+#[test]
+fn test_invalid_schedule() {
+    use crate::ast::{Argument, Root, ScheduleDef, WorldDef};
+    use crate::type_checker::run_type_checker;
+
+    let check = |cron: &str, parameters: Vec<Argument>| {
+        let mut root = Root {
+            dsl_version: None,
+            type_defs: vec![],
+            world_def: Some(WorldDef {
+                name: "TestWorld".to_string(),
+                policies: vec![],
+                persona: None,
+                config: None,
+                events: Default::default(),
+                handlers: Default::default(),
+                schedules: vec![ScheduleDef {
+                    cron: cron.to_string(),
+                    event: "cleanupRequested".to_string(),
+                    parameters,
+                }],
+                on_init: None,
+            }),
+            micro_agent_defs: vec![],
+            sistence_agent_defs: vec![],
+        };
+        run_type_checker(&mut root)
+    };
+    let scope = |value: Expression| {
+        vec![Argument::Named {
+            name: "scope".to_string(),
+            value,
+        }]
+    };
+
+    assert!(
+        check(
+            "0 2 * * *",
+            scope(Expression::Literal(Literal::String("all".into())))
+        )
+        .is_ok()
+    );
+
+    let error = check("0 25 * * *", vec![]).unwrap_err();
+    assert!(matches!(error, TypeCheckError::InvalidSchedule { .. }));
+    assert_eq!(
+        error.to_string(),
+        "Invalid schedule: '0 25 * * *' emitting cleanupRequested: invalid hour field '25': 25 is out of range 0-23"
+    );
+
+    let error = check("0 2 * * *", scope(Expression::Variable("scope".into()))).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .ends_with("argument 'scope' must be a literal")
+    );
+}
+
+#[test]
+fn test_world_on_init_is_checked() {
+    use crate::ast::{HandlerBlock, Root, Statement, WorldDef};
+    use crate::type_checker::run_type_checker;
+
+    let check = |statement: Statement| {
+        let mut root = Root {
+            dsl_version: None,
+            type_defs: vec![],
+            world_def: Some(WorldDef {
+                name: "TestWorld".to_string(),
+                policies: vec![],
+                persona: None,
+                config: None,
+                events: Default::default(),
+                handlers: Default::default(),
+                schedules: vec![],
+                on_init: Some(HandlerBlock {
+                    statements: vec![statement],
+                }),
+            }),
+            micro_agent_defs: vec![],
+            sistence_agent_defs: vec![],
+        };
+        run_type_checker(&mut root)
+    };
+
+    let seed = Expression::FunctionCall {
+        function: "increment".to_string(),
+        arguments: vec![
+            Expression::Literal(Literal::String("seats".into())),
+            Expression::Literal(Literal::Integer(10)),
+        ],
+    };
+    assert!(check(Statement::Expression(seed)).is_ok());
+
+    let error = check(Statement::Expression(Expression::Variable(
+        "undefined_var".into(),
+    )))
+    .unwrap_err();
+    assert!(matches!(error, TypeCheckError::UndefinedVariable { .. }));
+}
@@ -1,5 +1,7 @@
+use std::collections::HashMap;
+
 use crate::{
-    ast::{BinaryOperator, Expression, Literal, TypeInfo},
+    ast::{Argument, BinaryOperator, Expression, Literal, ThinkAttributes, TypeInfo},
     type_checker::{TypeCheckResult, TypeChecker, TypeContext, visitor::common::TypeVisitor},
 };
 
@@ -44,6 +46,64 @@ fn test_variable_expressions() -> TypeCheckResult<()> {
     Ok(())
 }
 
+#[test]
+fn test_secret_builtin_function() -> TypeCheckResult<()> {
+    let mut checker = TypeChecker::new();
+    let mut ctx = TypeContext::new();
+
+    let expr = Expression::FunctionCall {
+        function: "secret".to_string(),
+        arguments: vec![Expression::Literal(Literal::String("api_key".to_string()))],
+    };
+    checker.visit_expression(&expr, &mut ctx)?;
+
+    let expr = Expression::FunctionCall {
+        function: "secret".to_string(),
+        arguments: vec![Expression::Literal(Literal::Integer(1))],
+    };
+    assert!(checker.visit_expression(&expr, &mut ctx).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_catalog_builtin_functions() -> TypeCheckResult<()> {
+    let mut checker = TypeChecker::new();
+    let mut ctx = TypeContext::new();
+
+    let expr = Expression::FunctionCall {
+        function: "list_agents".to_string(),
+        arguments: vec![],
+    };
+    checker.visit_expression(&expr, &mut ctx)?;
+
+    let expr = Expression::FunctionCall {
+        function: "agent_requests".to_string(),
+        arguments: vec![Expression::Literal(Literal::String("Weather".to_string()))],
+    };
+    checker.visit_expression(&expr, &mut ctx)?;
+
+    let expr = Expression::FunctionCall {
+        function: "agent_requests".to_string(),
+        arguments: vec![],
+    };
+    assert!(checker.visit_expression(&expr, &mut ctx).is_err());
+
+    let expr = Expression::FunctionCall {
+        function: "call_tool".to_string(),
+        arguments: vec![
+            Expression::Literal(Literal::String("listPets".to_string())),
+            Expression::Literal(Literal::Map(HashMap::from([(
+                "limit".to_string(),
+                Literal::Integer(10),
+            )]))),
+        ],
+    };
+    checker.visit_expression(&expr, &mut ctx)?;
+
+    Ok(())
+}
+
 #[test]
 fn test_function_call_expressions() -> TypeCheckResult<()> {
     let mut checker = TypeChecker::new();
@@ -639,3 +699,99 @@ fn test_ok_err_type_mismatch() -> TypeCheckResult<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_think_sampling_ranges() {
+    let mut checker = TypeChecker::new();
+    let mut ctx = TypeContext::new();
+    let think = |attrs: ThinkAttributes| Expression::Think {
+        args: vec![Argument::Positional(Expression::Literal(Literal::String(
+            "Write a poem".to_string(),
+        )))],
+        with_block: Some(attrs),
+    };
+
+    let valid = think(ThinkAttributes {
+        temperature: Some(2.0),
+        top_p: Some(1.0),
+        max_tokens: Some(100),
+        ..Default::default()
+    });
+    assert!(checker.visit_expression(&valid, &mut ctx).is_ok());
+
+    let too_hot = think(ThinkAttributes {
+        temperature: Some(2.5),
+        ..Default::default()
+    });
+    let err = checker.visit_expression(&too_hot, &mut ctx).unwrap_err();
+    assert!(err.to_string().contains("temperature"));
+
+    for attrs in [
+        ThinkAttributes {
+            top_p: Some(0.0),
+            ..Default::default()
+        },
+        ThinkAttributes {
+            top_p: Some(1.5),
+            ..Default::default()
+        },
+        ThinkAttributes {
+            max_tokens: Some(0),
+            ..Default::default()
+        },
+    ] {
+        assert!(checker.visit_expression(&think(attrs), &mut ctx).is_err());
+    }
+}
+
+#[test]
+fn test_set_literals_and_builtins() -> TypeCheckResult<()> {
+    use crate::type_checker::visitor::default::DefaultVisitor;
+
+    let visitor = DefaultVisitor::new();
+    let ctx = TypeContext::new();
+    let string = |s: &str| Expression::Literal(Literal::String(s.to_string()));
+    let tags = Expression::Literal(Literal::Set(vec![
+        Literal::String("a".to_string()),
+        Literal::String("b".to_string()),
+    ]));
+    let set_of_strings = TypeInfo::Set(Box::new(TypeInfo::Simple("String".to_string())));
+    let call = |function: &str, arguments: Vec<Expression>| Expression::FunctionCall {
+        function: function.to_string(),
+        arguments,
+    };
+
+    assert_eq!(visitor.infer_type(&tags, &ctx)?, set_of_strings);
+    assert_eq!(
+        visitor.infer_type(&call("insert", vec![tags.clone(), string("c")]), &ctx)?,
+        set_of_strings
+    );
+    assert_eq!(
+        visitor.infer_type(&call("contains", vec![tags.clone(), string("a")]), &ctx)?,
+        TypeInfo::Simple("Boolean".to_string())
+    );
+    assert_eq!(
+        visitor.infer_type(&call("to_list", vec![tags.clone()]), &ctx)?,
+        TypeInfo::Array(Box::new(TypeInfo::Simple("String".to_string())))
+    );
+    assert_eq!(
+        visitor.infer_type(&call("len", vec![tags.clone()]), &ctx)?,
+        TypeInfo::Simple("Int".to_string())
+    );
+
+    // 要素の型が合わない呼び出しと、ハッシュできない要素は拒否する
+    let wrong_element = call(
+        "insert",
+        vec![tags.clone(), Expression::Literal(Literal::Integer(1))],
+    );
+    assert!(visitor.infer_type(&wrong_element, &ctx).is_err());
+    let floats = Expression::Literal(Literal::Set(vec![Literal::Float(1.5)]));
+    assert!(visitor.infer_type(&floats, &ctx).is_err());
+    let mixed = Expression::Literal(Literal::Set(vec![
+        Literal::Integer(1),
+        Literal::String("1".to_string()),
+    ]));
+    assert!(visitor.infer_type(&mixed, &ctx).is_err());
+
+    Ok(())
+}
@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+
+use crate::{
+    ast::{
+        Expression, FieldInfo, MicroAgentDef, Root, StateAccessPath, StateDef, StateVarDef,
+        TypeDef, TypeInfo,
+    },
+    type_checker::{
+        TypeCheckError, TypeContext, run_type_checker, visitor::default::DefaultVisitor,
+    },
+};
+
+fn simple(name: &str) -> TypeInfo {
+    TypeInfo::Simple(name.to_string())
+}
+
+fn generic(name: &str, args: Vec<TypeInfo>) -> TypeInfo {
+    TypeInfo::Generic {
+        name: name.to_string(),
+        args,
+    }
+}
+
+fn fields(fields: Vec<(&str, TypeInfo)>) -> HashMap<String, FieldInfo> {
+    fields
+        .into_iter()
+        .map(|(name, type_info)| {
+            let field = FieldInfo {
+                type_info: Some(type_info),
+                default_value: None,
+            };
+            (name.to_string(), field)
+        })
+        .collect()
+}
+
+// type Pair<A, B> { first: A, second: B }
+fn pair_def() -> TypeDef {
+    TypeDef {
+        name: "Pair".to_string(),
+        type_params: vec!["A".to_string(), "B".to_string()],
+        fields: fields(vec![("first", simple("A")), ("second", simple("B"))]),
+    }
+}
+
+fn agent_root(type_defs: Vec<TypeDef>, state: Vec<(&str, TypeInfo)>) -> Root {
+    let variables = state
+        .into_iter()
+        .map(|(name, type_info)| {
+            let variable = StateVarDef {
+                name: name.to_string(),
+                type_info,
+                initial_value: None,
+                doc: None,
+            };
+            (name.to_string(), variable)
+        })
+        .collect();
+    let mut root = Root::new(
+        None,
+        vec![MicroAgentDef {
+            name: "Pairs".to_string(),
+            state: Some(StateDef { variables }),
+            ..Default::default()
+        }],
+        vec![],
+    );
+    root.type_defs = type_defs;
+    root
+}
+
+fn state_type(root: &Root, name: &str) -> TypeInfo {
+    root.micro_agent_defs[0].state.as_ref().unwrap().variables[name]
+        .type_info
+        .clone()
+}
+
+#[test]
+fn test_generic_type_is_instantiated_per_use() {
+    let mut root = agent_root(
+        vec![pair_def()],
+        vec![
+            (
+                "score",
+                generic("Pair", vec![simple("String"), simple("Int")]),
+            ),
+            (
+                "flags",
+                generic("Pair", vec![simple("Boolean"), simple("Float")]),
+            ),
+        ],
+    );
+    run_type_checker(&mut root).unwrap();
+
+    let score = state_type(&root, "score");
+    assert_eq!(
+        score,
+        TypeInfo::Custom {
+            name: "Pair<String, Int>".to_string(),
+            fields: fields(vec![("first", simple("String")), ("second", simple("Int"))]),
+        }
+    );
+    assert_eq!(
+        state_type(&root, "flags"),
+        TypeInfo::Custom {
+            name: "Pair<Boolean, Float>".to_string(),
+            fields: fields(vec![
+                ("first", simple("Boolean")),
+                ("second", simple("Float"))
+            ]),
+        }
+    );
+
+    // フィールドの型は型引数で置き換えた型になる
+    let mut ctx = TypeContext::new();
+    ctx.scope.insert_type("score".to_string(), score);
+    let second = Expression::StateAccess(StateAccessPath(vec![
+        "score".to_string(),
+        "second".to_string(),
+    ]));
+    assert_eq!(
+        DefaultVisitor::new().infer_type(&second, &ctx).unwrap(),
+        simple("Int")
+    );
+}
+
+#[test]
+fn test_nested_generic_types() {
+    // type Entry<T> { key: String, value: Pair<T, Array{T}> }
+    let entry = TypeDef {
+        name: "Entry".to_string(),
+        type_params: vec!["T".to_string()],
+        fields: fields(vec![
+            ("key", simple("String")),
+            (
+                "value",
+                generic(
+                    "Pair",
+                    vec![simple("T"), TypeInfo::Array(Box::new(simple("T")))],
+                ),
+            ),
+        ]),
+    };
+    let mut root = agent_root(
+        vec![entry, pair_def()],
+        vec![("entry", generic("Entry", vec![simple("Int")]))],
+    );
+    run_type_checker(&mut root).unwrap();
+
+    let TypeInfo::Custom { fields: entry, .. } = state_type(&root, "entry") else {
+        panic!("Expected Custom type");
+    };
+    assert_eq!(
+        entry["value"].type_info,
+        Some(TypeInfo::Custom {
+            name: "Pair<Int, Array<Int>>".to_string(),
+            fields: fields(vec![
+                ("first", simple("Int")),
+                ("second", TypeInfo::Array(Box::new(simple("Int")))),
+            ]),
+        })
+    );
+}
+
+#[test]
+fn test_generic_type_arity_mismatch() {
+    let mut root = agent_root(
+        vec![pair_def()],
+        vec![("half", generic("Pair", vec![simple("Int")]))],
+    );
+    let error = run_type_checker(&mut root).unwrap_err();
+    assert!(matches!(error, TypeCheckError::InvalidTypeArguments { .. }));
+    assert!(
+        error
+            .to_string()
+            .contains("Pair takes 2 type argument(s), found 1")
+    );
+
+    // 型引数なしで使うこともできない
+    let mut root = agent_root(vec![pair_def()], vec![("bare", simple("Pair"))]);
+    assert!(matches!(
+        run_type_checker(&mut root),
+        Err(TypeCheckError::InvalidTypeArguments { .. })
+    ));
+}
+
+#[test]
+fn test_undefined_types_in_generic_types() {
+    let mut root = agent_root(
+        vec![pair_def()],
+        vec![("missing", generic("Triple", vec![simple("Int")]))],
+    );
+    assert!(matches!(
+        run_type_checker(&mut root),
+        Err(TypeCheckError::UndefinedType { name, .. }) if name == "Triple"
+    ));
+
+    // 宣言していない型パラメータは使えない
+    let mut broken = pair_def();
+    broken.fields.extend(fields(vec![("third", simple("C"))]));
+    let mut root = agent_root(vec![broken], vec![]);
+    assert!(matches!(
+        run_type_checker(&mut root),
+        Err(TypeCheckError::UndefinedType { name, .. }) if name == "C"
+    ));
+}
+
+#[test]
+fn test_recursive_types_through_indirection() {
+    // type Node { value: Int, next: Option{Node} }
+    let node = TypeDef {
+        name: "Node".to_string(),
+        type_params: vec![],
+        fields: fields(vec![
+            ("value", simple("Int")),
+            ("next", TypeInfo::Option(Box::new(simple("Node")))),
+        ]),
+    };
+    // type Team { lead: Member }, type Member { teams: Array{Team} }
+    let team = TypeDef {
+        name: "Team".to_string(),
+        type_params: vec![],
+        fields: fields(vec![("lead", simple("Member"))]),
+    };
+    let member = TypeDef {
+        name: "Member".to_string(),
+        type_params: vec![],
+        fields: fields(vec![("teams", TypeInfo::Array(Box::new(simple("Team"))))]),
+    };
+    let mut root = agent_root(
+        vec![node, team, member],
+        vec![("head", simple("Node")), ("team", simple("Team"))],
+    );
+    run_type_checker(&mut root).unwrap();
+    assert!(matches!(
+        state_type(&root, "head"),
+        TypeInfo::Custom { name, .. } if name == "Node"
+    ));
+}
+
+#[test]
+fn test_direct_type_cycles() {
+    // type A { b: B }, type B { a: A }
+    let a = TypeDef {
+        name: "A".to_string(),
+        type_params: vec![],
+        fields: fields(vec![("b", simple("B"))]),
+    };
+    let b = TypeDef {
+        name: "B".to_string(),
+        type_params: vec![],
+        fields: fields(vec![("a", simple("A"))]),
+    };
+    let mut root = agent_root(vec![a, b], vec![]);
+    let error = run_type_checker(&mut root).unwrap_err();
+    assert!(matches!(
+        &error,
+        TypeCheckError::RecursiveType { cycle, .. } if cycle == &["A", "B", "A"]
+    ));
+    assert_eq!(
+        error.to_string(),
+        "Recursive type without indirection: A -> B -> A"
+    );
+
+    // 型引数として直接含む場合も循環になる: type Loop { inner: Pair<Loop, Int> }
+    let looped = TypeDef {
+        name: "Loop".to_string(),
+        type_params: vec![],
+        fields: fields(vec![(
+            "inner",
+            generic("Pair", vec![simple("Loop"), simple("Int")]),
+        )]),
+    };
+    let mut root = agent_root(vec![looped, pair_def()], vec![]);
+    assert!(matches!(
+        run_type_checker(&mut root),
+        Err(TypeCheckError::RecursiveType { .. })
+    ));
+}
@@ -32,10 +32,12 @@ fn test_handler_parameters_used_in_block() -> TypeCheckResult<()> {
             Parameter {
                 name: "param1".to_string(),
                 type_info: TypeInfo::Simple("String".to_string()),
+                constraint: None,
             },
             Parameter {
                 name: "param2".to_string(),
                 type_info: TypeInfo::Simple("Int".to_string()),
+                constraint: None,
             },
         ],
         block: HandlerBlock {
@@ -48,6 +50,7 @@ fn test_handler_parameters_used_in_block() -> TypeCheckResult<()> {
                 )))),
             ],
         },
+        doc: None,
     };
 
     // This should not throw an undefined variable error
@@ -1,7 +1,14 @@
+use std::time::Duration;
+
 use crate::{
     ErrorHandlerBlock,
-    ast::{Expression, HandlerBlock, HandlerDef, Literal, Parameter, Statement, TypeInfo},
-    type_checker::{TypeCheckResult, TypeChecker, TypeContext, visitor::common::TypeVisitor},
+    ast::{
+        Expression, HandlerBlock, HandlerDef, Literal, Parameter, StateAccessPath, Statement,
+        TypeInfo,
+    },
+    type_checker::{
+        TypeCheckError, TypeCheckResult, TypeChecker, TypeContext, visitor::common::TypeVisitor,
+    },
 };
 
 #[test]
@@ -13,6 +20,7 @@ fn test_empty_handler() -> TypeCheckResult<()> {
         event_name: "test_event".to_string(),
         parameters: vec![],
         block: HandlerBlock { statements: vec![] },
+        doc: None,
     };
 
     checker.visit_handler(&handler, &mut ctx)?;
@@ -50,6 +58,7 @@ fn test_handler_with_statements() -> TypeCheckResult<()> {
                 )))),
             ],
         },
+        doc: None,
     };
 
     checker.visit_handler(&handler, &mut ctx)?;
@@ -89,6 +98,7 @@ fn test_handler_with_error_handling() -> TypeCheckResult<()> {
                 },
             }],
         },
+        doc: None,
     };
 
     checker.visit_handler(&handler, &mut ctx)?;
@@ -129,6 +139,7 @@ fn test_handler_with_conditional() -> TypeCheckResult<()> {
                 else_block: None,
             }],
         },
+        doc: None,
     };
 
     checker.visit_handler(&handler, &mut ctx)?;
@@ -146,15 +157,129 @@ fn test_handler_with_parameters() -> TypeCheckResult<()> {
             Parameter {
                 name: "param1".to_string(),
                 type_info: TypeInfo::Simple("String".to_string()),
+                constraint: None,
             },
             Parameter {
                 name: "param2".to_string(),
                 type_info: TypeInfo::Simple("Int".to_string()),
+                constraint: None,
             },
         ],
         block: HandlerBlock { statements: vec![] },
+        doc: None,
     };
 
     checker.visit_handler(&handler, &mut ctx)?;
     Ok(())
 }
+
+#[test]
+fn test_retry_statement() {
+    let retry = |times: Expression, backoff: Option<Expression>, body: Vec<Statement>| {
+        let mut checker = TypeChecker::new();
+        let mut ctx = TypeContext::new();
+        ctx.scope
+            .insert_type("count".to_string(), TypeInfo::Simple("Int".to_string()));
+        checker.visit_statement(
+            &Statement::Retry {
+                times,
+                backoff,
+                body,
+            },
+            &mut ctx,
+        )
+    };
+    let attempt = || {
+        Statement::Expression(Expression::StateAccess(StateAccessPath(vec![
+            "retry".to_string(),
+            "attempt".to_string(),
+        ])))
+    };
+    let int = |value| Expression::Literal(Literal::Integer(value));
+    let seconds = |value| {
+        Some(Expression::Literal(Literal::Duration(Duration::from_secs(
+            value,
+        ))))
+    };
+
+    assert!(retry(int(3), seconds(2), vec![attempt()]).is_ok());
+
+    // times は定数でなければならない
+    assert!(matches!(
+        retry(
+            Expression::Variable("count".to_string()),
+            None,
+            vec![attempt()]
+        ),
+        Err(TypeCheckError::InvalidTypeArguments { .. })
+    ));
+    assert!(matches!(
+        retry(int(0), None, vec![attempt()]),
+        Err(TypeCheckError::InvalidTypeArguments { .. })
+    ));
+    assert!(matches!(
+        retry(int(3), Some(int(2)), vec![attempt()]),
+        Err(TypeCheckError::TypeMismatch { .. })
+    ));
+
+    // 試行によって値の型が変わってはいけない
+    let branches = Statement::If {
+        condition: Expression::Literal(Literal::Boolean(true)),
+        then_block: vec![attempt()],
+        else_block: Some(vec![Statement::Expression(Expression::Literal(
+            Literal::String("last".to_string()),
+        ))]),
+    };
+    assert!(matches!(
+        retry(int(3), None, vec![branches]),
+        Err(TypeCheckError::TypeMismatch { .. })
+    ));
+}
+
+#[test]
+fn test_let_statement() {
+    let bind = |name: &str, type_info: Option<TypeInfo>, value: Expression| Statement::Let {
+        name: name.to_string(),
+        type_info,
+        value,
+    };
+    let int = |value| Expression::Literal(Literal::Integer(value));
+    let mut checker = TypeChecker::new();
+    let mut ctx = TypeContext::new();
+
+    // 型は値から推論される
+    checker
+        .visit_statement(&bind("count", None, int(1)), &mut ctx)
+        .unwrap();
+    assert_eq!(
+        ctx.scope.get_type("count"),
+        Some(TypeInfo::Simple("Int".to_string()))
+    );
+
+    // 束縛し直すと型も変わる
+    let text = Expression::Literal(Literal::String("one".to_string()));
+    checker
+        .visit_statement(
+            &bind("count", Some(TypeInfo::Simple("String".to_string())), text),
+            &mut ctx,
+        )
+        .unwrap();
+    assert_eq!(
+        ctx.scope.get_type("count"),
+        Some(TypeInfo::Simple("String".to_string()))
+    );
+
+    // 型注釈と値の型は一致しなければならない
+    assert!(matches!(
+        checker.visit_statement(
+            &bind(
+                "total",
+                Some(TypeInfo::Simple("String".to_string())),
+                int(2)
+            ),
+            &mut ctx,
+        ),
+        Err(TypeCheckError::TypeMismatch { .. })
+    ));
+    assert!(!ctx.scope.contains_type("total"));
+}
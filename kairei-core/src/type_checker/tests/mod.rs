@@ -1,6 +1,7 @@
 mod custom_type_tests;
 mod error_tests;
 mod expression_tests;
+mod generic_type_tests;
 mod handler_param_test;
 mod handler_tests;
 mod scope_isolation_tests;
@@ -31,12 +31,14 @@ fn test_scope_isolation_between_handlers() -> TypeCheckResult<()> {
         parameters: vec![Parameter {
             name: "param1".to_string(),
             type_info: TypeInfo::Simple("String".to_string()),
+            constraint: None,
         }],
         block: HandlerBlock {
             statements: vec![Statement::Expression(Expression::Variable(
                 "param1".to_string(),
             ))],
         },
+        doc: None,
     };
 
     // Create second handler with a different parameter
@@ -45,12 +47,14 @@ fn test_scope_isolation_between_handlers() -> TypeCheckResult<()> {
         parameters: vec![Parameter {
             name: "param2".to_string(),
             type_info: TypeInfo::Simple("Int".to_string()),
+            constraint: None,
         }],
         block: HandlerBlock {
             statements: vec![Statement::Expression(Expression::Variable(
                 "param2".to_string(),
             ))],
         },
+        doc: None,
     };
 
     // Check first handler
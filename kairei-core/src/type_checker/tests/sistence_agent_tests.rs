@@ -8,6 +8,8 @@ use std::collections::HashMap;
 #[test]
 fn test_sistence_agent_valid_config() {
     let mut root = Root {
+        dsl_version: None,
+        type_defs: vec![],
         world_def: None,
         micro_agent_defs: vec![],
         sistence_agent_defs: vec![SistenceAgentDef {
@@ -34,6 +36,8 @@ fn test_sistence_agent_valid_config() {
 #[test]
 fn test_sistence_agent_invalid_level() {
     let mut root = Root {
+        dsl_version: None,
+        type_defs: vec![],
         world_def: None,
         micro_agent_defs: vec![],
         sistence_agent_defs: vec![SistenceAgentDef {
@@ -68,6 +72,8 @@ fn test_sistence_agent_invalid_level() {
 #[test]
 fn test_sistence_agent_invalid_initiative_threshold() {
     let mut root = Root {
+        dsl_version: None,
+        type_defs: vec![],
         world_def: None,
         micro_agent_defs: vec![],
         sistence_agent_defs: vec![SistenceAgentDef {
@@ -104,6 +110,8 @@ fn test_sistence_agent_invalid_initiative_threshold() {
 #[test]
 fn test_will_action_expression() {
     let mut root = Root {
+        dsl_version: None,
+        type_defs: vec![],
         world_def: None,
         micro_agent_defs: vec![],
         sistence_agent_defs: vec![SistenceAgentDef {
@@ -121,6 +129,7 @@ fn test_will_action_expression() {
                     },
                     request_type: RequestType::Custom("TestRequest".to_string()),
                     constraints: None,
+                    requires: None,
                     block: HandlerBlock {
                         statements: vec![Statement::Expression(Expression::WillAction {
                             action: "test_action".to_string(),
@@ -130,6 +139,8 @@ fn test_will_action_expression() {
                             target: Some("target_agent".to_string()),
                         })],
                     },
+                    doc: None,
+                    cache: None,
                 }],
             }),
             react: None,
@@ -151,6 +162,8 @@ fn test_will_action_with_invalid_parameters() {
     // This test would be more complex in a real implementation
     // For now, we'll just test that the WillAction is properly type-checked
     let mut root = Root {
+        dsl_version: None,
+        type_defs: vec![],
         world_def: None,
         micro_agent_defs: vec![],
         sistence_agent_defs: vec![SistenceAgentDef {
@@ -164,6 +177,7 @@ fn test_will_action_with_invalid_parameters() {
                     parameters: vec![Parameter {
                         name: "param".to_string(),
                         type_info: TypeInfo::Simple("String".to_string()),
+                        constraint: None,
                     }],
                     return_type: TypeInfo::Result {
                         ok_type: Box::new(TypeInfo::Simple("String".to_string())),
@@ -171,6 +185,7 @@ fn test_will_action_with_invalid_parameters() {
                     },
                     request_type: RequestType::Custom("TestRequest".to_string()),
                     constraints: None,
+                    requires: None,
                     block: HandlerBlock {
                         statements: vec![
                             Statement::Expression(Expression::Variable(
@@ -183,6 +198,8 @@ fn test_will_action_with_invalid_parameters() {
                             }),
                         ],
                     },
+                    doc: None,
+                    cache: None,
                 }],
             }),
             react: None,
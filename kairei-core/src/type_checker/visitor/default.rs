@@ -1,12 +1,20 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::{
     Argument,
     ast::{
-        Expression, FieldInfo, HandlerBlock, HandlerDef, MicroAgentDef, RequestType, Root,
-        SistenceAgentDef, StateDef, Statement, TypeInfo,
+        AgentConfigDef, EventType, Expression, FieldInfo, HandlerBlock, HandlerDef, Literal,
+        MicroAgentDef, OnFailControl, OnFailReturn, Parameter, RequestType, Root, ScheduleDef,
+        SistenceAgentDef, StateDef, Statement, ThinkAttributes, TypeInfo,
+    },
+    context::{
+        AGENT_CONFIG_VARIABLE, EVENT_METADATA_VARIABLE, EXECUTION_CONTEXT_VARIABLE,
+        REQUEST_LOCALE_VARIABLE, REQUEST_METADATA_VARIABLE, REQUEST_USER_ID_VARIABLE,
+    },
+    cron::CronSchedule,
+    type_checker::{
+        TypeCheckError, TypeCheckResult, TypeContext, generics, visitor::common::TypeVisitor,
     },
-    type_checker::{TypeCheckError, TypeCheckResult, TypeContext, visitor::common::TypeVisitor},
 };
 
 use super::{
@@ -18,6 +26,8 @@ use super::{
 pub struct DefaultVisitor {
     expression_checker: DefaultExpressionChecker,
     function_checker: DefaultFunctionChecker,
+    /// Parameters declared by the observe/react handlers of each custom event
+    event_handler_parameters: HashMap<String, Vec<Vec<Parameter>>>,
 }
 
 impl DefaultVisitor {
@@ -25,7 +35,82 @@ impl DefaultVisitor {
         Self {
             expression_checker: DefaultExpressionChecker::new(),
             function_checker: DefaultFunctionChecker::new(),
+            event_handler_parameters: HashMap::new(),
+        }
+    }
+
+    /// Collect the parameters that every handler of a custom event expects
+    fn collect_event_handler_parameters(&mut self, root: &Root) {
+        self.event_handler_parameters.clear();
+        let mut add = |event_name: &str, parameters: &[Parameter]| {
+            self.event_handler_parameters
+                .entry(event_name.to_string())
+                .or_default()
+                .push(parameters.to_vec());
+        };
+
+        if let Some(world_def) = &root.world_def {
+            for handler in &world_def.handlers.handlers {
+                add(&handler.event_name, &handler.parameters);
+            }
+        }
+        let micro_handlers = root.micro_agent_defs.iter().flat_map(|agent| {
+            let observe = agent.observe.iter().flat_map(|def| &def.handlers);
+            let react = agent.react.iter().flat_map(|def| &def.handlers);
+            observe.chain(react)
+        });
+        let sistence_handlers = root.sistence_agent_defs.iter().flat_map(|agent| {
+            let observe = agent.observe.iter().flat_map(|def| &def.handlers);
+            let react = agent.react.iter().flat_map(|def| &def.handlers);
+            observe.chain(react)
+        });
+        for handler in micro_handlers.chain(sistence_handlers) {
+            if let EventType::Custom(event_name) = &handler.event_type {
+                add(event_name, &handler.parameters);
+            }
+        }
+    }
+
+    /// An emitted event must carry every required parameter of its handlers.
+    /// Optional (`T?`) and untyped parameters may be left out; handlers see them as null.
+    fn check_emit_parameters(
+        &self,
+        event_type: &EventType,
+        arguments: &[Argument],
+    ) -> TypeCheckResult<()> {
+        let EventType::Custom(event_name) = event_type else {
+            return Ok(());
+        };
+        // 位置引数は名前と対応付けられないため検査しない
+        if arguments
+            .iter()
+            .any(|argument| matches!(argument, Argument::Positional(_)))
+        {
+            return Ok(());
+        }
+        let Some(signatures) = self.event_handler_parameters.get(event_name) else {
+            return Ok(());
+        };
+
+        for parameters in signatures {
+            for param in parameters {
+                let is_required =
+                    !matches!(param.type_info, TypeInfo::Option(_)) && !param.type_info.is_any();
+                let is_given = arguments.iter().any(
+                    |argument| matches!(argument, Argument::Named { name, .. } if name == &param.name),
+                );
+                if is_required && !is_given {
+                    return Err(TypeCheckError::invalid_handler_signature(
+                        format!(
+                            "Event '{}' is emitted without required parameter '{}: {}'",
+                            event_name, param.name, param.type_info
+                        ),
+                        Default::default(),
+                    ));
+                }
+            }
         }
+        Ok(())
     }
 
     fn check_return_type(
@@ -120,6 +205,180 @@ impl DefaultVisitor {
         Ok(())
     }
 
+    /// `times` and `backoff` of `retry(...)` are fixed before the first attempt, so they
+    /// must be literals or agent config values of the expected type
+    fn check_retry_argument(
+        &self,
+        name: &str,
+        argument: &Expression,
+        expected: &str,
+        ctx: &TypeContext,
+    ) -> TypeCheckResult<()> {
+        let is_constant = match argument {
+            Expression::Literal(_) => true,
+            Expression::StateAccess(path) => {
+                path.0.first().map(String::as_str) == Some(AGENT_CONFIG_VARIABLE)
+            }
+            _ => false,
+        };
+        if !is_constant {
+            return Err(TypeCheckError::invalid_type_arguments(
+                format!(
+                    "retry {} must be a literal or a config value, found {:?}",
+                    name, argument
+                ),
+                Default::default(),
+            ));
+        }
+        let found = self.infer_type(argument, ctx)?;
+        let expected = TypeInfo::Simple(expected.to_string());
+        if found != expected {
+            return Err(TypeCheckError::type_mismatch(
+                expected,
+                found,
+                Default::default(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// The type of the value a block ends with, if it can be told. The branches of a
+    /// trailing `if`/`else` must agree on it.
+    fn block_value_type(
+        &self,
+        statements: &[Statement],
+        ctx: &TypeContext,
+    ) -> TypeCheckResult<Option<TypeInfo>> {
+        match statements.last() {
+            Some(Statement::Expression(expr)) => Ok(self.infer_type(expr, ctx).ok()),
+            Some(Statement::Block(statements)) => self.block_value_type(statements, ctx),
+            Some(Statement::If {
+                then_block,
+                else_block: Some(else_block),
+                ..
+            }) => {
+                let then_type = self.block_value_type(then_block, ctx)?;
+                let else_type = self.block_value_type(else_block, ctx)?;
+                match (then_type, else_type) {
+                    (Some(then_type), Some(else_type)) if then_type != else_type => Err(
+                        TypeCheckError::type_mismatch(then_type, else_type, Default::default()),
+                    ),
+                    (then_type, else_type) => Ok(then_type.or(else_type)),
+                }
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Checks the `requires` clause of a handler, a Boolean expression over the
+    /// handler's parameters (already in scope) and the agent's state
+    fn check_precondition(
+        &self,
+        requires: Option<&Expression>,
+        state: Option<&StateDef>,
+        ctx: &mut TypeContext,
+    ) -> TypeCheckResult<()> {
+        let Some(condition) = requires else {
+            return Ok(());
+        };
+        // 実行時と同じく、同名のパラメータは状態変数より優先する
+        ctx.enter_isolated_scope();
+        for (name, var_def) in state.iter().flat_map(|state| &state.variables) {
+            if ctx.scope.get_type(name).is_none() {
+                ctx.scope
+                    .insert_type(name.clone(), var_def.type_info.clone());
+            }
+        }
+        let result = self.check_condition(condition, ctx);
+        ctx.exit_isolated_scope();
+        result
+    }
+
+    /// Checks the `where` constraints of handler parameters. Each one must be a
+    /// Boolean expression over its own parameter and constants; a missing optional
+    /// parameter is not checked, so the condition sees the type inside `Option<T>`.
+    fn check_parameter_constraints(
+        &self,
+        parameters: &[Parameter],
+        ctx: &mut TypeContext,
+    ) -> TypeCheckResult<()> {
+        for param in parameters {
+            let Some(constraint) = &param.constraint else {
+                continue;
+            };
+            let condition = constraint.condition(&param.name);
+            if let Some(reference) = foreign_reference(&condition, &param.name) {
+                return Err(TypeCheckError::invalid_handler_signature(
+                    format!(
+                        "Constraint of parameter '{}' may only use '{}' and constants, found {}",
+                        param.name, param.name, reference
+                    ),
+                    Default::default(),
+                ));
+            }
+            let type_info = match &param.type_info {
+                TypeInfo::Option(inner) => (**inner).clone(),
+                other => other.clone(),
+            };
+            ctx.enter_isolated_scope();
+            ctx.scope.insert_type(param.name.clone(), type_info);
+            let result = self.check_condition(&condition, ctx);
+            ctx.exit_isolated_scope();
+            result?;
+        }
+        Ok(())
+    }
+
+    /// Checks the parameters of the agent's `config` block and makes them readable
+    /// as `config.<name>` in every handler of the agent
+    fn check_agent_config(
+        &self,
+        config: &AgentConfigDef,
+        ctx: &mut TypeContext,
+    ) -> TypeCheckResult<()> {
+        let mut fields = HashMap::new();
+        for param in &config.parameters {
+            check_set_elements(&param.type_info)?;
+            match &param.type_info {
+                TypeInfo::Simple(type_name) if !ctx.scope.contains_type(type_name) => {
+                    return Err(TypeCheckError::undefined_type(
+                        type_name.clone(),
+                        Default::default(),
+                    ));
+                }
+                _ => {}
+            }
+            if let Some(default) = &param.default {
+                let default_type = self.infer_type(&Expression::Literal(default.clone()), ctx)?;
+                // Option<T> の既定値は T でもよい
+                let accepted = param.type_info == default_type
+                    || matches!(&param.type_info, TypeInfo::Option(inner) if **inner == default_type);
+                if !accepted {
+                    return Err(TypeCheckError::type_mismatch(
+                        param.type_info.clone(),
+                        default_type,
+                        Default::default(),
+                    ));
+                }
+            }
+            fields.insert(
+                param.name.clone(),
+                FieldInfo {
+                    type_info: Some(param.type_info.clone()),
+                    default_value: None,
+                },
+            );
+        }
+        ctx.scope.insert_type(
+            AGENT_CONFIG_VARIABLE.to_string(),
+            TypeInfo::Custom {
+                name: "AgentConfig".to_string(),
+                fields,
+            },
+        );
+        Ok(())
+    }
+
     pub fn infer_type(&self, expr: &Expression, ctx: &TypeContext) -> TypeCheckResult<TypeInfo> {
         match expr {
             Expression::Literal(lit) => self.expression_checker.infer_literal_type(lit, ctx),
@@ -157,6 +416,8 @@ impl DefaultVisitor {
 
                 // Check if there's a custom provider specified in the with_block
                 if let Some(with_attrs) = with_block {
+                    check_sampling(with_attrs)?;
+
                     if let Some(_provider) = &with_attrs.provider {
                         // If a custom provider is specified, check if it's registered
                         // For now, we still return Result<String, Error> as the default
@@ -342,6 +603,10 @@ impl DefaultVisitor {
                                     ));
                                 }
                             }
+                            // Map keys are only known at runtime (e.g. event.parameters.x)
+                            TypeInfo::Map(_, value_type) => {
+                                current_type = *value_type.clone();
+                            }
                             _ => {
                                 return Err(TypeCheckError::type_inference_error(
                                     format!(
@@ -450,6 +715,7 @@ impl DefaultVisitor {
         for field_info in fields {
             // Check field type if specified
             if let Some(field_type) = &field_info.1.type_info {
+                check_set_elements(field_type)?;
                 match field_type {
                     TypeInfo::Simple(type_name) => {
                         if !ctx.scope.contains_type(type_name) {
@@ -491,6 +757,348 @@ impl DefaultVisitor {
     }
 }
 
+/// Reject `Set` types, at any depth, whose elements are not `Boolean`, `Int` or `String`
+fn check_set_elements(type_info: &TypeInfo) -> TypeCheckResult<()> {
+    match type_info {
+        TypeInfo::Set(element) if !element.is_hashable() => {
+            Err(TypeCheckError::invalid_type_arguments(
+                format!(
+                    "Set elements must be Boolean, Int or String, found {}",
+                    element
+                ),
+                Default::default(),
+            ))
+        }
+        TypeInfo::Set(inner) | TypeInfo::Option(inner) | TypeInfo::Array(inner) => {
+            check_set_elements(inner)
+        }
+        TypeInfo::Map(key, value) => {
+            check_set_elements(key)?;
+            check_set_elements(value)
+        }
+        TypeInfo::Result { ok_type, err_type } => {
+            check_set_elements(ok_type)?;
+            check_set_elements(err_type)
+        }
+        TypeInfo::Custom { fields, .. } => fields
+            .values()
+            .filter_map(|field| field.type_info.as_ref())
+            .try_for_each(check_set_elements),
+        _ => Ok(()),
+    }
+}
+
+/// Type of the implicit `request` variable available in answer handlers
+fn request_metadata_type() -> TypeInfo {
+    metadata_type(
+        "RequestMetadata",
+        [
+            ("requester", TypeInfo::Simple("String".to_string())),
+            ("request_id", TypeInfo::Simple("String".to_string())),
+            ("received_at", TypeInfo::Simple("Timestamp".to_string())),
+            ("timeout", TypeInfo::Simple("Duration".to_string())),
+        ],
+    )
+}
+
+/// Type of the implicit `event` variable available in observe and react handlers
+fn event_metadata_type() -> TypeInfo {
+    metadata_type(
+        "EventMetadata",
+        [
+            ("event_type", TypeInfo::Simple("String".to_string())),
+            ("emitted_at", TypeInfo::Simple("Timestamp".to_string())),
+            (
+                "parameters",
+                TypeInfo::Map(
+                    Box::new(TypeInfo::Simple("String".to_string())),
+                    Box::new(TypeInfo::any()),
+                ),
+            ),
+        ],
+    )
+}
+
+/// Type of the implicit `context` variable available in every handler of an agent
+fn execution_context_type() -> TypeInfo {
+    metadata_type(
+        "ExecutionContext",
+        [("locale", TypeInfo::Simple("String".to_string()))],
+    )
+}
+
+fn metadata_type<const N: usize>(name: &str, fields: [(&str, TypeInfo); N]) -> TypeInfo {
+    TypeInfo::Custom {
+        name: name.to_string(),
+        fields: fields
+            .into_iter()
+            .map(|(field, type_info)| {
+                (
+                    field.to_string(),
+                    FieldInfo {
+                        type_info: Some(type_info),
+                        default_value: None,
+                    },
+                )
+            })
+            .collect(),
+    }
+}
+
+/// Puts the agent's state variables in a handler's scope. As at runtime, a
+/// parameter of the same name takes precedence.
+fn insert_state_variables(state: Option<&StateDef>, ctx: &mut TypeContext) {
+    for (name, var_def) in state.iter().flat_map(|state| &state.variables) {
+        if ctx.scope.get_type(name).is_none() {
+            ctx.scope
+                .insert_type(name.clone(), var_def.type_info.clone());
+        }
+    }
+}
+
+/// Rejects handler parameters that would shadow an implicit metadata variable
+fn check_implicit_variable(parameters: &[Parameter], implicit: &str) -> TypeCheckResult<()> {
+    match parameters.iter().find(|param| param.name == implicit) {
+        Some(param) => Err(TypeCheckError::invalid_handler_signature(
+            format!(
+                "Parameter '{}' collides with the implicit '{}' metadata of the handler",
+                param.name, implicit
+            ),
+            Default::default(),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Checks the `@replay` directives of observe and react handlers: each names one
+/// policy, `skip` or `safe`
+fn check_replay_directives(agent: &MicroAgentDef) -> TypeCheckResult<()> {
+    let handlers = agent
+        .observe
+        .iter()
+        .flat_map(|observe| &observe.handlers)
+        .chain(agent.react.iter().flat_map(|react| &react.handlers));
+    for handler in handlers {
+        let Some(replay) = &handler.replay else {
+            continue;
+        };
+        if replay.policy().is_none() {
+            return Err(TypeCheckError::invalid_handler_signature(
+                format!(
+                    "Replay directive of {}.{}: expected one of skip or safe, got ({})",
+                    agent.name,
+                    handler.event_type,
+                    replay.arguments.join(", ")
+                ),
+                Default::default(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The first name in a parameter constraint other than the parameter itself,
+/// or a construct a constraint may not use
+fn foreign_reference(expression: &Expression, parameter: &str) -> Option<String> {
+    match expression {
+        Expression::Literal(_) => None,
+        Expression::Variable(name) if name == parameter => None,
+        Expression::Variable(name) => Some(format!("'{}'", name)),
+        Expression::StateAccess(path) => Some(format!("'{}'", path.0.join("."))),
+        Expression::FunctionCall { arguments, .. } => arguments
+            .iter()
+            .find_map(|argument| foreign_reference(argument, parameter)),
+        Expression::BinaryOp { left, right, .. } => {
+            foreign_reference(left, parameter).or_else(|| foreign_reference(right, parameter))
+        }
+        Expression::Ok(inner) | Expression::Err(inner) => foreign_reference(inner, parameter),
+        Expression::Think { .. } => Some("a think expression".to_string()),
+        Expression::Request { .. } => Some("a request".to_string()),
+        Expression::Await(_) => Some("await".to_string()),
+        Expression::WillAction { .. } => Some("a will action".to_string()),
+    }
+}
+
+/// Schedules need a valid cron expression. Their arguments are evaluated once,
+/// outside any handler, so they must be named literals.
+fn check_schedules(schedules: &[ScheduleDef]) -> TypeCheckResult<()> {
+    for schedule in schedules {
+        let invalid = |message: String| {
+            Err(TypeCheckError::invalid_schedule(
+                format!(
+                    "'{}' emitting {}: {}",
+                    schedule.cron, schedule.event, message
+                ),
+                Default::default(),
+            ))
+        };
+        if let Err(e) = CronSchedule::parse(&schedule.cron) {
+            return invalid(e.to_string());
+        }
+        for argument in &schedule.parameters {
+            match argument {
+                Argument::Named {
+                    value: Expression::Literal(_),
+                    ..
+                } => {}
+                Argument::Named { name, .. } => {
+                    return invalid(format!("argument '{}' must be a literal", name));
+                }
+                Argument::Positional(_) => {
+                    return invalid("arguments must be named".to_string());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks that each declared contract is answered by a handler with the same
+/// parameters, returning the contract's type or a `Result` of it
+fn check_contracts(agent: &MicroAgentDef) -> TypeCheckResult<()> {
+    let handlers = agent
+        .answer
+        .as_ref()
+        .map(|answer| answer.handlers.as_slice())
+        .unwrap_or_default();
+    for contract in &agent.contracts {
+        let request_type = contract.request_type.to_string();
+        let invalid = |message: String| {
+            Err(TypeCheckError::invalid_handler_signature(
+                format!("Contract '{}': {}", request_type, message),
+                Default::default(),
+            ))
+        };
+        let Some(handler) = handlers
+            .iter()
+            .find(|handler| handler.request_type == contract.request_type)
+        else {
+            return invalid("no answer handler for the request".to_string());
+        };
+
+        // `where` 制約はハンドラ側だけで宣言できる
+        let signature_of = |parameters: &[Parameter]| {
+            parameters
+                .iter()
+                .map(|p| (p.name.clone(), p.type_info.clone()))
+                .collect::<Vec<_>>()
+        };
+        if signature_of(&handler.parameters) != signature_of(&contract.parameters) {
+            let signature = |parameters: &[Parameter]| {
+                parameters
+                    .iter()
+                    .map(|p| format!("{}: {}", p.name, p.type_info))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            return invalid(format!(
+                "handler takes ({}), the contract declares ({})",
+                signature(&handler.parameters),
+                signature(&contract.parameters)
+            ));
+        }
+
+        // ハンドラは契約の型をそのまま返すか、Result で包んで返す
+        let returns = handler.return_type == contract.return_type
+            || matches!(&handler.return_type, TypeInfo::Result { ok_type, .. }
+                if **ok_type == contract.return_type);
+        if !returns {
+            return invalid(format!(
+                "handler returns {}, the contract declares {}",
+                handler.return_type, contract.return_type
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Checks the catch-all answer handler (`on request _(request_type: String)`): at most
+/// one per agent, taking only the request type, and not declared in a contract
+fn check_fallback_handler(agent: &MicroAgentDef) -> TypeCheckResult<()> {
+    let invalid = |message: String| {
+        Err(TypeCheckError::invalid_handler_signature(
+            format!("Catch-all handler of {}: {}", agent.name, message),
+            Default::default(),
+        ))
+    };
+    let fallbacks: Vec<_> = agent
+        .answer
+        .iter()
+        .flat_map(|answer| &answer.handlers)
+        .filter(|handler| handler.request_type == RequestType::Fallback)
+        .collect();
+    if fallbacks.len() > 1 {
+        return invalid("an agent has at most one".to_string());
+    }
+    if let Some(handler) = fallbacks.first() {
+        handler.validate_signature().or_else(invalid)?;
+    }
+    if agent
+        .contracts
+        .iter()
+        .any(|contract| contract.request_type == RequestType::Fallback)
+    {
+        return invalid("it answers unknown requests and cannot have a contract".to_string());
+    }
+    Ok(())
+}
+
+/// Checks the `@cache` directives of answer handlers: a response is kept for some
+/// time, and the cache key is made of distinct parameters of the handler
+fn check_cache_directives(agent: &MicroAgentDef) -> TypeCheckResult<()> {
+    for handler in agent.answer.iter().flat_map(|answer| &answer.handlers) {
+        let Some(cache) = &handler.cache else {
+            continue;
+        };
+        let invalid = |message: String| {
+            Err(TypeCheckError::invalid_handler_signature(
+                format!(
+                    "Cache directive of {}.{}: {}",
+                    agent.name, handler.request_type, message
+                ),
+                Default::default(),
+            ))
+        };
+        if cache.ttl.is_zero() {
+            return invalid("ttl must be longer than 0".to_string());
+        }
+        let mut seen = HashSet::new();
+        for name in cache.key.iter().flatten() {
+            if !handler.parameters.iter().any(|param| &param.name == name) {
+                return invalid(format!("key '{}' is not a parameter of the handler", name));
+            }
+            if !seen.insert(name) {
+                return invalid(format!("key '{}' is listed more than once", name));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the sampling overrides of a think block are in the ranges LLMs accept
+fn check_sampling(attrs: &ThinkAttributes) -> TypeCheckResult<()> {
+    let invalid = |message: String| {
+        Err(TypeCheckError::invalid_think_block(
+            message,
+            Default::default(),
+        ))
+    };
+    if let Some(temperature) = attrs.temperature.filter(|t| !(0.0..=2.0).contains(t)) {
+        return invalid(format!(
+            "temperature must be between 0 and 2, got {}",
+            temperature
+        ));
+    }
+    // 0 ではトークンを 1 つも選べない
+    if let Some(top_p) = attrs.top_p.filter(|p| *p <= 0.0 || *p > 1.0) {
+        return invalid(format!("top_p must be in (0, 1], got {}", top_p));
+    }
+    if attrs.max_tokens == Some(0) {
+        return invalid("max_tokens must be a positive integer".to_string());
+    }
+    Ok(())
+}
+
 impl Default for DefaultVisitor {
     fn default() -> Self {
         Self::new()
@@ -547,6 +1155,7 @@ impl TypeVisitor for DefaultVisitor {
             if let Some(init) = &lifecycle.on_init {
                 // Create an isolated scope for the init handler
                 ctx.enter_isolated_scope();
+                insert_state_variables(agent.state.as_ref(), ctx);
                 let result = self.visit_handler_block(init, ctx);
                 ctx.exit_isolated_scope();
                 result?;
@@ -554,6 +1163,7 @@ impl TypeVisitor for DefaultVisitor {
             if let Some(destroy) = &lifecycle.on_destroy {
                 // Create an isolated scope for the destroy handler
                 ctx.enter_isolated_scope();
+                insert_state_variables(agent.state.as_ref(), ctx);
                 let result = self.visit_handler_block(destroy, ctx);
                 ctx.exit_isolated_scope();
                 result?;
@@ -627,7 +1237,17 @@ impl TypeVisitor for DefaultVisitor {
                     },
                 );
 
-                let result = self.visit_handler_block(&handler.block, ctx);
+                insert_state_variables(agent.state.as_ref(), ctx);
+                let result = self
+                    .check_parameter_constraints(&handler.parameters, ctx)
+                    .and_then(|_| {
+                        self.check_precondition(
+                            handler.requires.as_ref(),
+                            agent.state.as_ref(),
+                            ctx,
+                        )
+                    })
+                    .and_then(|_| self.visit_handler_block(&handler.block, ctx));
                 ctx.exit_isolated_scope();
                 result?;
             }
@@ -704,7 +1324,17 @@ impl TypeVisitor for DefaultVisitor {
                     },
                 );
 
-                let result = self.visit_handler_block(&handler.block, ctx);
+                insert_state_variables(agent.state.as_ref(), ctx);
+                let result = self
+                    .check_parameter_constraints(&handler.parameters, ctx)
+                    .and_then(|_| {
+                        self.check_precondition(
+                            handler.requires.as_ref(),
+                            agent.state.as_ref(),
+                            ctx,
+                        )
+                    })
+                    .and_then(|_| self.visit_handler_block(&handler.block, ctx));
                 ctx.exit_isolated_scope();
                 result?;
             }
@@ -782,7 +1412,17 @@ impl TypeVisitor for DefaultVisitor {
                     },
                 );
 
-                let result = self.visit_handler_block(&handler.block, ctx);
+                insert_state_variables(agent.state.as_ref(), ctx);
+                let result = self
+                    .check_parameter_constraints(&handler.parameters, ctx)
+                    .and_then(|_| {
+                        self.check_precondition(
+                            handler.requires.as_ref(),
+                            agent.state.as_ref(),
+                            ctx,
+                        )
+                    })
+                    .and_then(|_| self.visit_handler_block(&handler.block, ctx));
                 ctx.exit_isolated_scope();
                 result?;
             }
@@ -794,8 +1434,14 @@ impl TypeVisitor for DefaultVisitor {
         Ok(())
     }
     fn visit_root(&mut self, root: &mut Root, ctx: &mut TypeContext) -> TypeCheckResult<()> {
+        // 宣言された型を登録し、使う側のジェネリック型を具体的な型に置き換えてから検査する
+        generics::register_type_defs(&root.type_defs, &mut ctx.scope)?;
+        generics::instantiate_root_types(root, &mut ctx.scope)?;
+        self.collect_event_handler_parameters(root);
+
         // Visit world definition if present
         if let Some(world_def) = &mut root.world_def {
+            check_schedules(&world_def.schedules)?;
             for handler in &world_def.handlers.handlers {
                 // 既存の型定義がない場合のみデフォルト値を設定
                 if ctx.scope.get_type("return_type").is_none() {
@@ -826,6 +1472,13 @@ impl TypeVisitor for DefaultVisitor {
                 }
                 self.visit_handler(handler, ctx)?;
             }
+            if let Some(init) = &world_def.on_init {
+                // Create an isolated scope for the world init handler
+                ctx.enter_isolated_scope();
+                let result = self.visit_handler_block(init, ctx);
+                ctx.exit_isolated_scope();
+                result?;
+            }
         }
 
         // Visit all micro agents
@@ -846,6 +1499,11 @@ impl TypeVisitor for DefaultVisitor {
         agent: &mut MicroAgentDef,
         ctx: &mut TypeContext,
     ) -> TypeCheckResult<()> {
+        check_replay_directives(agent)?;
+        check_fallback_handler(agent)?;
+        check_contracts(agent)?;
+        check_cache_directives(agent)?;
+
         // Create an isolated scope for the micro agent
         ctx.enter_isolated_scope();
 
@@ -854,11 +1512,21 @@ impl TypeVisitor for DefaultVisitor {
             self.visit_state(state, ctx)?;
         }
 
+        // Register config parameters for all handlers of the agent
+        if let Some(config) = &agent.config {
+            self.check_agent_config(config, ctx)?;
+        }
+        ctx.scope.insert_type(
+            EXECUTION_CONTEXT_VARIABLE.to_string(),
+            execution_context_type(),
+        );
+
         // Visit lifecycle handlers if present
         if let Some(lifecycle) = &agent.lifecycle {
             if let Some(init) = &lifecycle.on_init {
                 // Create an isolated scope for the init handler
                 ctx.enter_isolated_scope();
+                insert_state_variables(agent.state.as_ref(), ctx);
                 let result = self.visit_handler_block(init, ctx);
                 ctx.exit_isolated_scope();
                 result?;
@@ -866,6 +1534,7 @@ impl TypeVisitor for DefaultVisitor {
             if let Some(destroy) = &lifecycle.on_destroy {
                 // Create an isolated scope for the destroy handler
                 ctx.enter_isolated_scope();
+                insert_state_variables(agent.state.as_ref(), ctx);
                 let result = self.visit_handler_block(destroy, ctx);
                 ctx.exit_isolated_scope();
                 result?;
@@ -884,6 +1553,18 @@ impl TypeVisitor for DefaultVisitor {
                     handler.return_type.clone(),
                 );
 
+                // The implicit request metadata must not be shadowed by a parameter
+                if let Err(e) =
+                    check_implicit_variable(&handler.parameters, REQUEST_METADATA_VARIABLE)
+                {
+                    ctx.exit_isolated_scope();
+                    return Err(e);
+                }
+                ctx.scope.insert_type(
+                    REQUEST_METADATA_VARIABLE.to_string(),
+                    request_metadata_type(),
+                );
+
                 // Check parameter types
                 for param in &handler.parameters {
                     if let Some(existing_type) = ctx.scope.get_type(&param.name) {
@@ -902,7 +1583,25 @@ impl TypeVisitor for DefaultVisitor {
                         .insert_type(param.name.clone(), param.type_info.clone());
                 }
 
-                let result = self.visit_handler_block(&handler.block, ctx);
+                // Register request context values (set by the runtime, may be null)
+                for name in [REQUEST_USER_ID_VARIABLE, REQUEST_LOCALE_VARIABLE] {
+                    ctx.scope.insert_type(
+                        name.to_string(),
+                        TypeInfo::Option(Box::new(TypeInfo::Simple("String".to_string()))),
+                    );
+                }
+
+                insert_state_variables(agent.state.as_ref(), ctx);
+                let result = self
+                    .check_parameter_constraints(&handler.parameters, ctx)
+                    .and_then(|_| {
+                        self.check_precondition(
+                            handler.requires.as_ref(),
+                            agent.state.as_ref(),
+                            ctx,
+                        )
+                    })
+                    .and_then(|_| self.visit_handler_block(&handler.block, ctx));
                 ctx.exit_isolated_scope();
                 result?;
             }
@@ -925,6 +1624,16 @@ impl TypeVisitor for DefaultVisitor {
                     );
                 }
 
+                // The implicit event metadata must not be shadowed by a parameter
+                if let Err(e) =
+                    check_implicit_variable(&handler.parameters, EVENT_METADATA_VARIABLE)
+                {
+                    ctx.exit_isolated_scope();
+                    return Err(e);
+                }
+                ctx.scope
+                    .insert_type(EVENT_METADATA_VARIABLE.to_string(), event_metadata_type());
+
                 // Check parameter types
                 for param in &handler.parameters {
                     if let Some(existing_type) = ctx.scope.get_type(&param.name) {
@@ -941,7 +1650,17 @@ impl TypeVisitor for DefaultVisitor {
                     ctx.scope
                         .insert_type(param.name.clone(), param.type_info.clone());
                 }
-                let result = self.visit_handler_block(&handler.block, ctx);
+                insert_state_variables(agent.state.as_ref(), ctx);
+                let result = self
+                    .check_parameter_constraints(&handler.parameters, ctx)
+                    .and_then(|_| {
+                        self.check_precondition(
+                            handler.requires.as_ref(),
+                            agent.state.as_ref(),
+                            ctx,
+                        )
+                    })
+                    .and_then(|_| self.visit_handler_block(&handler.block, ctx));
                 ctx.exit_isolated_scope();
                 result?;
             }
@@ -964,6 +1683,16 @@ impl TypeVisitor for DefaultVisitor {
                     );
                 }
 
+                // The implicit event metadata must not be shadowed by a parameter
+                if let Err(e) =
+                    check_implicit_variable(&handler.parameters, EVENT_METADATA_VARIABLE)
+                {
+                    ctx.exit_isolated_scope();
+                    return Err(e);
+                }
+                ctx.scope
+                    .insert_type(EVENT_METADATA_VARIABLE.to_string(), event_metadata_type());
+
                 // Check parameter types
                 for param in &handler.parameters {
                     if let Some(existing_type) = ctx.scope.get_type(&param.name) {
@@ -981,7 +1710,17 @@ impl TypeVisitor for DefaultVisitor {
                     ctx.scope
                         .insert_type(param.name.clone(), param.type_info.clone());
                 }
-                let result = self.visit_handler_block(&handler.block, ctx);
+                insert_state_variables(agent.state.as_ref(), ctx);
+                let result = self
+                    .check_parameter_constraints(&handler.parameters, ctx)
+                    .and_then(|_| {
+                        self.check_precondition(
+                            handler.requires.as_ref(),
+                            agent.state.as_ref(),
+                            ctx,
+                        )
+                    })
+                    .and_then(|_| self.visit_handler_block(&handler.block, ctx));
                 ctx.exit_isolated_scope();
                 result?;
             }
@@ -997,6 +1736,7 @@ impl TypeVisitor for DefaultVisitor {
         // Check each state variable's type
         for var_def in &state.variables {
             let var_def = var_def.1;
+            check_set_elements(&var_def.type_info)?;
             match &var_def.type_info {
                 TypeInfo::Simple(type_name) => {
                     if !ctx.scope.contains_type(type_name) {
@@ -1021,7 +1761,12 @@ impl TypeVisitor for DefaultVisitor {
             // If there's an initial value, check its type
             if let Some(init_value) = &var_def.initial_value {
                 let init_type = self.infer_type(init_value, ctx)?;
-                if init_type != var_def.type_info {
+                // 空の集合リテラルはどの集合型の初期値にもなれる
+                let empty_set = matches!(
+                    (&init_type, &var_def.type_info),
+                    (TypeInfo::Set(element), TypeInfo::Set(_)) if element.is_any()
+                );
+                if init_type != var_def.type_info && !empty_set {
                     return Err(TypeCheckError::type_mismatch(
                         var_def.type_info.clone(),
                         init_type,
@@ -1043,6 +1788,10 @@ impl TypeVisitor for DefaultVisitor {
 
         // Register parameters in scope before checking handler block
         for param in &handler.parameters {
+            if let Err(error) = check_set_elements(&param.type_info) {
+                ctx.exit_isolated_scope();
+                return Err(error);
+            }
             ctx.scope
                 .insert_type(param.name.clone(), param.type_info.clone());
         }
@@ -1101,6 +1850,14 @@ impl TypeVisitor for DefaultVisitor {
                             }
                         }
                     }
+                    Expression::StateAccess(path)
+                        if path.0.first().map(String::as_str) == Some(AGENT_CONFIG_VARIABLE) =>
+                    {
+                        return Err(TypeCheckError::invalid_state_variable(
+                            format!("Cannot assign to {}: agent config is read-only", path),
+                            Default::default(),
+                        ));
+                    }
                     _ => {
                         // For other expressions (e.g., StateAccess), get target type and check compatibility
                         let target_type = self.infer_type(&target[0], ctx)?;
@@ -1115,6 +1872,36 @@ impl TypeVisitor for DefaultVisitor {
                 }
                 Ok(())
             }
+            Statement::Let {
+                name,
+                type_info,
+                value,
+            } => {
+                let value_type = self.infer_type(value, ctx)?;
+                let binding_type = match type_info {
+                    Some(type_info) => {
+                        check_set_elements(type_info)?;
+                        let declared = generics::resolve_type(type_info, &mut ctx.scope)?;
+                        // 空の集合リテラルはどの集合型にも束縛できる
+                        let empty_set = matches!(
+                            (&value_type, &declared),
+                            (TypeInfo::Set(element), TypeInfo::Set(_)) if element.is_any()
+                        );
+                        if declared != value_type && !empty_set {
+                            return Err(TypeCheckError::type_mismatch(
+                                declared,
+                                value_type,
+                                Default::default(),
+                            ));
+                        }
+                        declared
+                    }
+                    None => value_type,
+                };
+                // 代入と違い、同じ名前を別の型で束縛し直せる
+                ctx.scope.insert_type(name.clone(), binding_type);
+                Ok(())
+            }
             Statement::Return(expr) => {
                 // Get current function's return type from context
                 // For RequestHandler, use its own return_type
@@ -1133,6 +1920,17 @@ impl TypeVisitor for DefaultVisitor {
                 self.check_return_type(expr, &expected_type, ctx)?;
                 Ok(())
             }
+            Statement::Yield(expr) => {
+                // Partial responses share the declared type of the answer handler
+                let Some(expected_type) = ctx.scope.get_type("handler_return_type") else {
+                    return Err(TypeCheckError::invalid_handler_signature(
+                        "yield is only allowed in answer handlers".to_string(),
+                        Default::default(),
+                    ));
+                };
+                self.check_return_type(expr, &expected_type, ctx)?;
+                Ok(())
+            }
             Statement::Block(statements) => {
                 // Create a checkpoint before entering the block
                 let checkpoint = ctx.create_scope_checkpoint();
@@ -1164,6 +1962,15 @@ impl TypeVisitor for DefaultVisitor {
                     self.visit_statement(stmt, ctx)?;
                 }
 
+                // A trailing `return Ok(..)` / `return Err(..)` is checked like any other return
+                if let Some(OnFailControl::Return(on_fail_return)) = &error_handler_block.control {
+                    let expr = match on_fail_return {
+                        OnFailReturn::Ok(expr) => Expression::Ok(Box::new(expr.clone())),
+                        OnFailReturn::Err(expr) => Expression::Err(Box::new(expr.clone())),
+                    };
+                    self.visit_statement(&Statement::Return(expr), ctx)?;
+                }
+
                 // Restore the checkpoint after exiting the error handler block
                 ctx.restore_scope_checkpoint(checkpoint);
 
@@ -1206,7 +2013,55 @@ impl TypeVisitor for DefaultVisitor {
 
                 Ok(())
             }
-            Statement::Emit { parameters, .. } => {
+            Statement::Retry {
+                times,
+                backoff,
+                body,
+            } => {
+                self.check_retry_argument("times", times, "Int", ctx)?;
+                if let Expression::Literal(Literal::Integer(times @ ..=0)) = times {
+                    return Err(TypeCheckError::invalid_type_arguments(
+                        format!("retry times must be at least 1, found {}", times),
+                        Default::default(),
+                    ));
+                }
+                if let Some(backoff) = backoff {
+                    self.check_retry_argument("backoff", backoff, "Duration", ctx)?;
+                }
+
+                let checkpoint = ctx.create_scope_checkpoint();
+                ctx.scope.enter_scope();
+
+                // Every attempt binds `retry.attempt`, counting from 1
+                ctx.scope.insert_type(
+                    "retry".to_string(),
+                    TypeInfo::Custom {
+                        name: "Retry".to_string(),
+                        fields: HashMap::from([(
+                            "attempt".to_string(),
+                            FieldInfo {
+                                type_info: Some(TypeInfo::Simple("Int".to_string())),
+                                default_value: None,
+                            },
+                        )]),
+                    },
+                );
+                for stmt in body {
+                    self.visit_statement(stmt, ctx)?;
+                }
+                // Whichever attempt succeeds, the statement yields the value of the body
+                self.block_value_type(body, ctx)?;
+
+                ctx.restore_scope_checkpoint(checkpoint);
+
+                Ok(())
+            }
+            Statement::Emit {
+                event_type,
+                parameters,
+                ..
+            } => {
+                self.check_emit_parameters(event_type, parameters)?;
                 for param in parameters {
                     match param {
                         Argument::Named { value, .. } | Argument::Positional(value) => {
@@ -1216,6 +2071,28 @@ impl TypeVisitor for DefaultVisitor {
                 }
                 Ok(())
             }
+            Statement::Log {
+                level,
+                message,
+                fields,
+            } => {
+                let function = format!("log.{}", level);
+                let arguments = std::iter::once(("message", message))
+                    .chain(fields.iter().map(|(key, value)| (key.as_str(), value)));
+                for (argument, value) in arguments {
+                    let value_type = self.infer_type(value, ctx)?;
+                    if let TypeInfo::Result { ok_type, .. } = &value_type {
+                        return Err(TypeCheckError::invalid_argument_type(
+                            function,
+                            argument.to_string(),
+                            ok_type.as_ref().clone(),
+                            value_type,
+                            Default::default(),
+                        ));
+                    }
+                }
+                Ok(())
+            }
         }
     }
 
@@ -121,7 +121,9 @@ impl ExpressionTypeChecker for DefaultExpressionChecker {
         Ok(match lit {
             Literal::Integer(_) => TypeInfo::Simple("Int".to_string()),
             Literal::Float(_) => TypeInfo::Simple("Float".to_string()),
-            Literal::String(_) => TypeInfo::Simple("String".to_string()),
+            Literal::String(_) | Literal::RawString { .. } => {
+                TypeInfo::Simple("String".to_string())
+            }
             Literal::Boolean(_) => TypeInfo::Simple("Boolean".to_string()),
             Literal::Duration(_) => TypeInfo::Simple("Duration".to_string()),
             Literal::List(items) => {
@@ -150,6 +152,36 @@ impl ExpressionTypeChecker for DefaultExpressionChecker {
 
                 TypeInfo::Array(Box::new(first_type))
             }
+            Literal::Set(items) => {
+                // 空の集合は要素を追加するまで型が決まらない
+                let Some((first, rest)) = items.split_first() else {
+                    return Ok(TypeInfo::Set(Box::new(TypeInfo::any())));
+                };
+                let element_type = self.infer_literal_type(first, _ctx)?;
+                if !element_type.is_hashable() {
+                    return Err(TypeCheckError::type_inference_error(
+                        format!(
+                            "Set elements must be Boolean, Int or String, found {}",
+                            element_type
+                        ),
+                        Default::default(),
+                    ));
+                }
+                for item in rest {
+                    let item_type = self.infer_literal_type(item, _ctx)?;
+                    if item_type != element_type {
+                        return Err(TypeCheckError::type_inference_error(
+                            format!(
+                                "Set contains mixed types: found both {} and {}",
+                                element_type, item_type
+                            ),
+                            Default::default(),
+                        ));
+                    }
+                }
+
+                TypeInfo::Set(Box::new(element_type))
+            }
             Literal::Map(entries) => {
                 if entries.is_empty() {
                     return Err(TypeCheckError::type_inference_error(
@@ -232,7 +264,7 @@ impl ExpressionTypeChecker for DefaultExpressionChecker {
                     Ok(TypeInfo::Simple("Int".to_string()))
                 }
             }
-            Subtract | Multiply | Divide => {
+            Subtract | Multiply | Divide | Modulo => {
                 if !self.is_numeric(left) || !self.is_numeric(right) {
                     return Err(TypeCheckError::InvalidOperatorType {
                         operator: op.to_string(),
@@ -108,7 +108,7 @@
 ///    - Function signature caching
 ///    - Optimized type checking for common cases
 use crate::{
-    ast::{Argument, Expression, TypeInfo},
+    ast::{Argument, Expression, Literal, TypeInfo},
     type_checker::{TypeCheckResult, TypeContext, error::TypeCheckError},
 };
 
@@ -401,6 +401,134 @@ impl DefaultFunctionChecker {
         }
     }
 
+    /// Type of a call to a set builtin, whose signature follows the element type
+    /// of its first argument. `None` when `function` is not a set builtin or the
+    /// first argument is not a set.
+    fn check_set_function_call(
+        &self,
+        function: &str,
+        arguments: &[Expression],
+        ctx: &TypeContext,
+    ) -> TypeCheckResult<Option<TypeInfo>> {
+        if !matches!(
+            function,
+            "insert" | "remove" | "contains" | "union" | "intersect" | "to_list"
+        ) {
+            return Ok(None);
+        }
+        let Some(first) = arguments.first() else {
+            return Ok(None);
+        };
+        let set_type = self.infer_expression_type(first, ctx)?;
+        let TypeInfo::Set(element_type) = &set_type else {
+            return Ok(None);
+        };
+
+        let (other_type, return_type) = match function {
+            "to_list" => (None, TypeInfo::Array(element_type.clone())),
+            "contains" => (
+                Some((**element_type).clone()),
+                TypeInfo::Simple("Boolean".to_string()),
+            ),
+            "insert" | "remove" => (Some((**element_type).clone()), set_type.clone()),
+            _ => (Some(set_type.clone()), set_type.clone()),
+        };
+        let param_types: Vec<TypeInfo> = std::iter::once(set_type.clone())
+            .chain(other_type)
+            .collect();
+        self.check_argument_types(function, arguments, &param_types, ctx)?;
+        Ok(Some(return_type))
+    }
+
+    /// Type of a call to `choose_weighted(options, weights)` or
+    /// `choose_seeded(options, weights, seed)`: the element type of `options`.
+    /// Weights must be numbers; when written as literals, there must be one per
+    /// option and none negative. `None` when `function` is neither.
+    fn check_choice_function_call(
+        &self,
+        function: &str,
+        arguments: &[Expression],
+        ctx: &TypeContext,
+    ) -> TypeCheckResult<Option<TypeInfo>> {
+        let arity = match function {
+            "choose_weighted" => 2,
+            "choose_seeded" => 3,
+            _ => return Ok(None),
+        };
+        if arguments.len() != arity {
+            return Err(TypeCheckError::type_inference_error(
+                format!(
+                    "Function {} requires {} arguments, but {} were provided",
+                    function,
+                    arity,
+                    arguments.len()
+                ),
+                Default::default(),
+            ));
+        }
+
+        let options_type = self.infer_expression_type(&arguments[0], ctx)?;
+        let TypeInfo::Array(element_type) = options_type.clone() else {
+            return Err(TypeCheckError::invalid_argument_type(
+                function.to_string(),
+                "arg0".to_string(),
+                TypeInfo::Array(Box::new(TypeInfo::any())),
+                options_type,
+                Default::default(),
+            ));
+        };
+        let weights_type = self.infer_expression_type(&arguments[1], ctx)?;
+        let numeric = matches!(
+            &weights_type,
+            TypeInfo::Array(weight) if matches!(weight.as_ref(), TypeInfo::Simple(name) if name == "Int" || name == "Float")
+        );
+        if !numeric {
+            return Err(TypeCheckError::invalid_argument_type(
+                function.to_string(),
+                "arg1".to_string(),
+                TypeInfo::Array(Box::new(TypeInfo::Simple("Float".to_string()))),
+                weights_type,
+                Default::default(),
+            ));
+        }
+        // シードは表示できる値なら何でもよい
+        if let Some(seed) = arguments.get(2) {
+            self.infer_expression_type(seed, ctx)?;
+        }
+
+        let literal_list = |expression: &Expression| match expression {
+            Expression::Literal(Literal::List(items)) => Some(items.len()),
+            _ => None,
+        };
+        match (literal_list(&arguments[0]), literal_list(&arguments[1])) {
+            (Some(options), Some(weights)) if options != weights => {
+                return Err(TypeCheckError::invalid_type_arguments(
+                    format!(
+                        "{} requires as many weights as options, got {} options and {} weights",
+                        function, options, weights
+                    ),
+                    Default::default(),
+                ));
+            }
+            _ => {}
+        }
+        let negative = match &arguments[1] {
+            Expression::Literal(Literal::List(weights)) => weights.iter().find(|weight| {
+                matches!(weight, Literal::Integer(i) if *i < 0)
+                    || matches!(weight, Literal::Float(f) if *f < 0.0)
+            }),
+            _ => None,
+        };
+        if let Some(weight) = negative {
+            return Err(TypeCheckError::invalid_type_arguments(
+                format!("{} requires non-negative weights, got {}", function, weight),
+                Default::default(),
+            ));
+        }
+
+        Ok(Some(*element_type))
+    }
+
     fn check_argument_types(
         &self,
         function: &str,
@@ -442,6 +570,8 @@ impl DefaultFunctionChecker {
                 ) => **expected_ok == **actual_ok && **expected_err == **actual_err,
                 // If expected is not Result but arg is, check if ok_type matches expected
                 (expected, TypeInfo::Result { ok_type, .. }) => **ok_type == *expected,
+                // Any accepts every argument
+                (TypeInfo::Simple(expected), _) if expected == "Any" => true,
                 // Default case - direct equality check
                 _ => arg_type == *expected_type,
             };
@@ -461,6 +591,63 @@ impl DefaultFunctionChecker {
     }
 }
 
+/// Parameter and return types of built-in functions that are not declared in the DSL
+fn builtin_function_type(function: &str) -> Option<(Vec<TypeInfo>, TypeInfo)> {
+    match function {
+        // シークレットは文字列として扱える（表示はマスクされる）
+        "secret" => Some((
+            vec![TypeInfo::Simple("String".to_string())],
+            TypeInfo::Simple("String".to_string()),
+        )),
+        // 文字列・リスト・マップ・集合の長さ
+        "len" => Some((
+            vec![TypeInfo::Simple("Any".to_string())],
+            TypeInfo::Simple("Int".to_string()),
+        )),
+        "list_agents" => Some((
+            vec![],
+            TypeInfo::Array(Box::new(TypeInfo::Simple("String".to_string()))),
+        )),
+        // { name, parameters: [{ name, type }], return_type } の一覧
+        "agent_requests" => Some((
+            vec![TypeInfo::Simple("String".to_string())],
+            TypeInfo::Array(Box::new(TypeInfo::Map(
+                Box::new(TypeInfo::Simple("String".to_string())),
+                Box::new(TypeInfo::Simple("Any".to_string())),
+            ))),
+        )),
+        // 引数はマップ（引数なしなら null）、戻り値は API の応答次第
+        "call_tool" => Some((
+            vec![
+                TypeInfo::Simple("String".to_string()),
+                TypeInfo::Simple("Any".to_string()),
+            ],
+            TypeInfo::Simple("Any".to_string()),
+        )),
+        // エージェント間で共有するカウンターとレート制限
+        "increment" => Some((
+            vec![
+                TypeInfo::Simple("String".to_string()),
+                TypeInfo::Simple("Int".to_string()),
+            ],
+            TypeInfo::Simple("Int".to_string()),
+        )),
+        "decrement_if_positive" => Some((
+            vec![TypeInfo::Simple("String".to_string())],
+            TypeInfo::Simple("Boolean".to_string()),
+        )),
+        "try_acquire" => Some((
+            vec![
+                TypeInfo::Simple("String".to_string()),
+                TypeInfo::Simple("Int".to_string()),
+                TypeInfo::Simple("Duration".to_string()),
+            ],
+            TypeInfo::Simple("Boolean".to_string()),
+        )),
+        _ => None,
+    }
+}
+
 impl FunctionTypeChecker for DefaultFunctionChecker {
     fn check_function_call(
         &self,
@@ -468,6 +655,17 @@ impl FunctionTypeChecker for DefaultFunctionChecker {
         arguments: &[Expression],
         ctx: &TypeContext,
     ) -> TypeCheckResult<TypeInfo> {
+        if let Some((param_types, return_type)) = builtin_function_type(function) {
+            self.check_argument_types(function, arguments, &param_types, ctx)?;
+            return Ok(return_type);
+        }
+        if let Some(return_type) = self.check_set_function_call(function, arguments, ctx)? {
+            return Ok(return_type);
+        }
+        if let Some(return_type) = self.check_choice_function_call(function, arguments, ctx)? {
+            return Ok(return_type);
+        }
+
         // Get function signature for return type
         let func_type = self.get_function_signature(function, ctx)?;
         let (_, return_type) = self.extract_parameter_types(&func_type)?;
@@ -601,6 +799,75 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_choice_function_call_type_checking() -> TypeCheckResult<()> {
+        let checker = DefaultFunctionChecker::new();
+        let ctx = TypeContext::new();
+        let list = |items: Vec<Literal>| Expression::Literal(Literal::List(items));
+        let options = list(vec![
+            Literal::String("rock".to_string()),
+            Literal::String("paper".to_string()),
+        ]);
+
+        let result = checker.check_function_call(
+            "choose_weighted",
+            &[
+                options.clone(),
+                list(vec![Literal::Integer(1), Literal::Integer(3)]),
+            ],
+            &ctx,
+        )?;
+        assert_eq!(result, TypeInfo::Simple("String".to_string()));
+        let result = checker.check_function_call(
+            "choose_seeded",
+            &[
+                options.clone(),
+                list(vec![Literal::Float(0.5), Literal::Float(0.5)]),
+                Expression::Literal(Literal::Integer(7)),
+            ],
+            &ctx,
+        )?;
+        assert_eq!(result, TypeInfo::Simple("String".to_string()));
+
+        // 選択肢と重みの数が違う
+        let result = checker.check_function_call(
+            "choose_weighted",
+            &[options.clone(), list(vec![Literal::Integer(1)])],
+            &ctx,
+        );
+        assert!(matches!(
+            result,
+            Err(TypeCheckError::InvalidTypeArguments { message, .. }) if message.contains("as many weights as options")
+        ));
+
+        // 負の重み
+        let result = checker.check_function_call(
+            "choose_weighted",
+            &[
+                options.clone(),
+                list(vec![Literal::Integer(1), Literal::Integer(-2)]),
+            ],
+            &ctx,
+        );
+        assert!(matches!(
+            result,
+            Err(TypeCheckError::InvalidTypeArguments { message, .. }) if message.contains("non-negative")
+        ));
+
+        // 数値でない重み
+        let result = checker.check_function_call(
+            "choose_weighted",
+            &[options.clone(), options.clone()],
+            &ctx,
+        );
+        assert!(matches!(
+            result,
+            Err(TypeCheckError::InvalidArgumentType(_))
+        ));
+
+        Ok(())
+    }
+
     #[test]
     fn test_return_type_checking() -> TypeCheckResult<()> {
         let checker = DefaultFunctionChecker::new();
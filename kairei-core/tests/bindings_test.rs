@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+use kairei_core::{
+    ast_registry::AstRegistry,
+    event_bus::{Event, Value},
+    r#gen::{self, bindings::EventBindingError},
+};
+use quote::ToTokens;
+
+// 生成したバインディングをそのままコンパイルする。生成結果と一致することは下のテストで確かめる
+#[allow(dead_code)]
+mod shop_events {
+    include!("fixtures/bindings/shop_events.rs");
+}
+
+use shop_events::{CleanupRequested, Heartbeat, ORDER_PLACED, OrderPlaced};
+
+const SHOP_DSL: &str = include_str!("fixtures/bindings/shop.kairei");
+
+// 書式の違いを無視するため、構文木にしてから出力し直して比べる
+fn tokens(source: &str) -> String {
+    syn::parse_file(source)
+        .unwrap()
+        .into_token_stream()
+        .to_string()
+}
+
+#[tokio::test]
+async fn test_generated_bindings_match_fixture() {
+    let root = AstRegistry::default()
+        .create_ast_from_dsl(SHOP_DSL)
+        .await
+        .unwrap();
+    assert_eq!(
+        tokens(&r#gen::rust_bindings(&root)),
+        tokens(include_str!("fixtures/bindings/shop_events.rs"))
+    );
+}
+
+#[test]
+fn test_rust_bindings_from_file() {
+    let path = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/bindings/shop.kairei"
+    );
+    let generated = r#gen::rust_bindings_from_file(path).unwrap();
+    assert!(generated.starts_with("// Generated by kairei_core::r#gen::rust_bindings"));
+    assert_eq!(
+        tokens(&generated),
+        tokens(include_str!("fixtures/bindings/shop_events.rs"))
+    );
+    assert!(r#gen::rust_bindings_from_file("missing.kairei").is_err());
+}
+
+#[test]
+fn test_event_round_trips_through_struct() {
+    let order = OrderPlaced {
+        order_id: "o-1".to_string(),
+        amount: 12.5,
+        quantity: 2,
+        gift: false,
+        r#type: "express".to_string(),
+        tags: vec!["book".to_string()],
+        note: None,
+    };
+    let event = Event::from(order.clone());
+    assert_eq!(event.event_type.to_string(), ORDER_PLACED);
+    assert_eq!(
+        event.parameters.get("type"),
+        Some(&Value::String("express".to_string()))
+    );
+    assert_eq!(event.parameters.get("note"), Some(&Value::Null));
+    assert_eq!(OrderPlaced::try_from(&event), Ok(order));
+
+    let cleanup = CleanupRequested {
+        scope: "all".to_string(),
+        after: Duration::from_secs(60),
+    };
+    assert_eq!(
+        CleanupRequested::try_from(&Event::from(cleanup.clone())),
+        Ok(cleanup)
+    );
+    assert_eq!(
+        Heartbeat::try_from(&Event::from(Heartbeat {})),
+        Ok(Heartbeat {})
+    );
+}
+
+#[test]
+fn test_mismatched_event_is_reported() {
+    let mut event = Event::from(CleanupRequested {
+        scope: "all".to_string(),
+        after: Duration::from_secs(60),
+    });
+    assert_eq!(
+        OrderPlaced::try_from(&event),
+        Err(EventBindingError::WrongEvent {
+            expected: "OrderPlaced".to_string(),
+            actual: "cleanupRequested".to_string(),
+        })
+    );
+
+    event
+        .parameters
+        .insert("after".to_string(), Value::String("1m".to_string()));
+    assert_eq!(
+        CleanupRequested::try_from(&event),
+        Err(EventBindingError::MismatchedType {
+            event: "cleanupRequested".to_string(),
+            parameter: "after".to_string(),
+            expected: "Duration".to_string(),
+        })
+    );
+
+    event.parameters.remove("scope");
+    assert_eq!(
+        CleanupRequested::try_from(&event).unwrap_err().to_string(),
+        "Event cleanupRequested is missing parameter scope"
+    );
+}
@@ -0,0 +1,127 @@
+// Generated by kairei_core::r#gen::rust_bindings. Do not edit.
+
+#[doc = " Name of the `OrderPlaced` event"]
+pub const ORDER_PLACED: &str = "OrderPlaced";
+
+#[doc = " Parameters of the `OrderPlaced` event"]
+#[derive(Debug, Clone, PartialEq)]
+#[allow(non_snake_case)]
+pub struct OrderPlaced {
+    pub order_id: ::std::string::String,
+    pub amount: f64,
+    pub quantity: i64,
+    pub gift: bool,
+    pub r#type: ::std::string::String,
+    pub tags: ::std::vec::Vec<::std::string::String>,
+    pub note: ::core::option::Option<::std::string::String>,
+}
+
+impl OrderPlaced {
+    pub const NAME: &'static str = ORDER_PLACED;
+}
+
+impl ::core::convert::From<OrderPlaced> for ::kairei_core::event_bus::Event {
+    fn from(event: OrderPlaced) -> Self {
+        ::kairei_core::r#gen::bindings::event(
+            OrderPlaced::NAME,
+            ::std::vec![
+                ("order_id", ::kairei_core::r#gen::bindings::EventParameter::into_value(event.order_id)),
+                ("amount", ::kairei_core::r#gen::bindings::EventParameter::into_value(event.amount)),
+                ("quantity", ::kairei_core::r#gen::bindings::EventParameter::into_value(event.quantity)),
+                ("gift", ::kairei_core::r#gen::bindings::EventParameter::into_value(event.gift)),
+                ("type", ::kairei_core::r#gen::bindings::EventParameter::into_value(event.r#type)),
+                ("tags", ::kairei_core::r#gen::bindings::EventParameter::into_value(event.tags)),
+                ("note", ::kairei_core::r#gen::bindings::EventParameter::into_value(event.note)),
+            ],
+        )
+    }
+}
+
+impl ::core::convert::TryFrom<&::kairei_core::event_bus::Event> for OrderPlaced {
+    type Error = ::kairei_core::r#gen::bindings::EventBindingError;
+
+    fn try_from(
+        event: &::kairei_core::event_bus::Event,
+    ) -> ::core::result::Result<Self, Self::Error> {
+        ::kairei_core::r#gen::bindings::expect_event(event, Self::NAME)?;
+        ::core::result::Result::Ok(Self {
+            order_id: ::kairei_core::r#gen::bindings::parameter(event, Self::NAME, "order_id")?,
+            amount: ::kairei_core::r#gen::bindings::parameter(event, Self::NAME, "amount")?,
+            quantity: ::kairei_core::r#gen::bindings::parameter(event, Self::NAME, "quantity")?,
+            gift: ::kairei_core::r#gen::bindings::parameter(event, Self::NAME, "gift")?,
+            r#type: ::kairei_core::r#gen::bindings::parameter(event, Self::NAME, "type")?,
+            tags: ::kairei_core::r#gen::bindings::parameter(event, Self::NAME, "tags")?,
+            note: ::kairei_core::r#gen::bindings::parameter(event, Self::NAME, "note")?,
+        })
+    }
+}
+
+#[doc = " Name of the `cleanupRequested` event"]
+pub const CLEANUP_REQUESTED: &str = "cleanupRequested";
+
+#[doc = " Parameters of the `cleanupRequested` event"]
+#[derive(Debug, Clone, PartialEq)]
+#[allow(non_snake_case)]
+pub struct CleanupRequested {
+    pub scope: ::std::string::String,
+    pub after: ::std::time::Duration,
+}
+
+impl CleanupRequested {
+    pub const NAME: &'static str = CLEANUP_REQUESTED;
+}
+
+impl ::core::convert::From<CleanupRequested> for ::kairei_core::event_bus::Event {
+    fn from(event: CleanupRequested) -> Self {
+        ::kairei_core::r#gen::bindings::event(
+            CleanupRequested::NAME,
+            ::std::vec![
+                ("scope", ::kairei_core::r#gen::bindings::EventParameter::into_value(event.scope)),
+                ("after", ::kairei_core::r#gen::bindings::EventParameter::into_value(event.after)),
+            ],
+        )
+    }
+}
+
+impl ::core::convert::TryFrom<&::kairei_core::event_bus::Event> for CleanupRequested {
+    type Error = ::kairei_core::r#gen::bindings::EventBindingError;
+
+    fn try_from(
+        event: &::kairei_core::event_bus::Event,
+    ) -> ::core::result::Result<Self, Self::Error> {
+        ::kairei_core::r#gen::bindings::expect_event(event, Self::NAME)?;
+        ::core::result::Result::Ok(Self {
+            scope: ::kairei_core::r#gen::bindings::parameter(event, Self::NAME, "scope")?,
+            after: ::kairei_core::r#gen::bindings::parameter(event, Self::NAME, "after")?,
+        })
+    }
+}
+
+#[doc = " Name of the `Heartbeat` event"]
+pub const HEARTBEAT: &str = "Heartbeat";
+
+#[doc = " Parameters of the `Heartbeat` event"]
+#[derive(Debug, Clone, PartialEq)]
+#[allow(non_snake_case)]
+pub struct Heartbeat {}
+
+impl Heartbeat {
+    pub const NAME: &'static str = HEARTBEAT;
+}
+
+impl ::core::convert::From<Heartbeat> for ::kairei_core::event_bus::Event {
+    fn from(_event: Heartbeat) -> Self {
+        ::kairei_core::r#gen::bindings::event(Heartbeat::NAME, ::std::vec![],)
+    }
+}
+
+impl ::core::convert::TryFrom<&::kairei_core::event_bus::Event> for Heartbeat {
+    type Error = ::kairei_core::r#gen::bindings::EventBindingError;
+
+    fn try_from(
+        event: &::kairei_core::event_bus::Event,
+    ) -> ::core::result::Result<Self, Self::Error> {
+        ::kairei_core::r#gen::bindings::expect_event(event, Self::NAME)?;
+        ::core::result::Result::Ok(Self {})
+    }
+}
@@ -1,6 +1,9 @@
 use std::time::Duration;
 
-use kairei_core::{event_bus::Value, system::System};
+use kairei_core::{
+    event_bus::{PROFILE_KEY, Value},
+    system::System,
+};
 use tokio::time::sleep;
 use tracing::debug;
 use uuid::Uuid;
@@ -213,12 +216,32 @@ async fn test_travel_planner() {
         ("budget", Value::Float(3000.0)),
     ];
     let request_id = Uuid::new_v4();
-    let request = create_request("TravelPlanner", &request_id, "PlanTrip", request_data, None);
+    let mut request = create_request("TravelPlanner", &request_id, "PlanTrip", request_data, None);
+    // 処理時間の内訳も求める
+    request
+        .parameters
+        .insert(PROFILE_KEY.to_string(), Value::Boolean(true));
 
-    let result = system.send_request(request).await.unwrap();
+    let (result, profile) = system.send_request_with_profile(request).await.unwrap();
     println!("Result: {:?}", result);
     assert!(format!("{:?}", result).contains("travel"));
     assert!(format!("{:?}", result).contains("Tokyo"));
+
+    let profile = profile.unwrap();
+    println!("Profile: {:#?}", profile);
+    let sum: f64 = profile.sections.iter().map(|s| s.duration_ms).sum();
+    assert!(
+        (profile.total_ms - sum).abs() <= profile.total_ms * 0.05,
+        "sections sum to {} of {} ms",
+        sum,
+        profile.total_ms
+    );
+    assert!(
+        profile
+            .all_sections()
+            .iter()
+            .any(|section| section.name == "plugin Memory")
+    );
 }
 
 #[tokio::test]
@@ -1,5 +1,6 @@
 use kairei_core::{
-    MicroAgentDef, analyzer::Parser, preprocessor::Preprocessor, tokenizer::token::Token,
+    MicroAgentDef, analyzer::Parser, ast::TypeInfo, ast_registry::AstRegistry,
+    preprocessor::Preprocessor, tokenizer::token::Token,
 };
 use tracing::debug;
 
@@ -103,6 +104,32 @@ fn it_parse_micro_agent_answer() {
     assert!(agent_def.answer.is_some());
 }
 
+#[test]
+fn it_parse_fallback_answer_handler() {
+    let input = r#"
+        micro Helper {
+            answer {
+                on request Greet(name: String) -> String {
+                    return name
+                }
+
+                on request _(request_type: String) -> String {
+                    return "I cannot handle ${request_type} yet"
+                }
+            }
+        }
+    "#;
+    let agent_def = parse_agent(input);
+    let handlers = &agent_def.answer.unwrap().handlers;
+    assert_eq!(
+        handlers[0].request_type,
+        kairei_core::RequestType::Custom("Greet".to_string())
+    );
+    assert_eq!(handlers[1].request_type, kairei_core::RequestType::Fallback);
+    assert_eq!(handlers[1].parameters[0].name, "request_type");
+    assert_eq!(handlers[1].request_type.to_string(), "_");
+}
+
 #[test]
 fn it_parse_micro_agent_react() {
     let input = r#"
@@ -190,3 +217,187 @@ fn it_parse_micro_agent() {
 
     assert_eq!(agent_def.name, "TestAgent");
 }
+
+#[test]
+fn it_parse_think_with_raw_json_prompt() {
+    let input = r###"
+        micro JsonAgent {
+            answer {
+                on request Extract(text: String) -> Result<String, Error> {
+                    return think(r#"Reply as {"name": "${name}", "tags": []}"#, text)
+                }
+            }
+        }
+    "###;
+    let agent_def = parse_agent(input);
+    let handler = &agent_def.answer.as_ref().unwrap().handlers[0];
+    let kairei_core::ast::HandlerBlock { statements } = &handler.block;
+    let kairei_core::ast::Statement::Return(kairei_core::ast::Expression::Think { args, .. }) =
+        &statements[0]
+    else {
+        panic!("expected return think, got {:?}", statements[0]);
+    };
+    assert_eq!(
+        args[0],
+        kairei_core::ast::Argument::Positional(kairei_core::ast::Expression::Literal(
+            kairei_core::ast::Literal::RawString {
+                value: r#"Reply as {"name": "${name}", "tags": []}"#.to_string(),
+                hashes: 1,
+            }
+        ))
+    );
+}
+
+#[test]
+fn it_attaches_doc_comments_to_following_node() {
+    let input = r#"
+        /// Counts requests
+        /** Second paragraph
+         * of the agent docs
+         */
+        micro Counter {
+            /// Ignored: the state keyword is not documentable
+            state {
+                /// Number of requests served
+                count: Int = 0;
+                // plain comment
+                label: String = "counter";
+                /** Last caller */
+                last: String = "none";
+            }
+            observe {
+                /// Resets on every tick
+                on Tick {
+                    count = 0
+                }
+            }
+            answer {
+                /* not a doc comment */
+                on request GetCount() -> Result<Int, Error> {
+                    return Ok(count)
+                }
+                /// Returns the label
+                on request GetLabel() -> Result<String, Error> {
+                    return Ok(label)
+                }
+            }
+        }
+    "#;
+    let agent_def = parse_agent(input);
+
+    assert_eq!(
+        agent_def.doc.as_deref(),
+        Some("Counts requests\nSecond paragraph\nof the agent docs")
+    );
+
+    let state = agent_def.state.unwrap();
+    assert_eq!(
+        state.variables["count"].doc.as_deref(),
+        Some("Number of requests served")
+    );
+    assert_eq!(state.variables["label"].doc, None);
+    assert_eq!(state.variables["last"].doc.as_deref(), Some("Last caller"));
+
+    let observe = agent_def.observe.unwrap();
+    assert_eq!(
+        observe.handlers[0].doc.as_deref(),
+        Some("Resets on every tick")
+    );
+
+    let answer = agent_def.answer.unwrap();
+    assert_eq!(answer.handlers[0].doc, None);
+    assert_eq!(answer.handlers[1].doc.as_deref(), Some("Returns the label"));
+}
+
+#[test]
+fn it_parse_contract_block() {
+    let input = r#"
+        micro Weather {
+            contract {
+                /// Forecast for the next days
+                request GetForecast(city: String, days: Int) -> String
+                query.GetStatus() -> Boolean
+            }
+            answer {
+                on request GetForecast(city: String, days: Int) -> Result<String, Error> {
+                    return Ok(city)
+                }
+            }
+        }
+    "#;
+    let agent_def = parse_agent(input);
+
+    assert_eq!(agent_def.contracts.len(), 2);
+    let forecast = agent_def.contract("GetForecast").unwrap();
+    assert_eq!(forecast.doc.as_deref(), Some("Forecast for the next days"));
+    assert_eq!(
+        forecast
+            .parameters
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["city", "days"]
+    );
+    assert_eq!(forecast.return_type.to_string(), "String");
+    assert!(agent_def.contract("Query.GetStatus").is_some());
+    assert!(agent_def.answer.is_some());
+}
+
+#[tokio::test]
+async fn it_instantiates_generic_types() {
+    let dsl = |score_type: &str| {
+        format!(
+            r#"
+            type Pair<A, B> {{ first: A, second: B }}
+
+            micro Scores {{
+                state {{
+                    best: {};
+                    flags: Pair<Boolean, Float>;
+                }}
+            }}
+        "#,
+            score_type
+        )
+    };
+    let root = AstRegistry::default()
+        .create_ast_from_dsl(&dsl("Pair<String, Int>"))
+        .await
+        .unwrap();
+    assert_eq!(root.type_defs.len(), 1);
+    let state = root.micro_agent_defs[0].state.as_ref().unwrap();
+    let field_types = |name: &str| match &state.variables[name].type_info {
+        TypeInfo::Custom { name, fields } => (
+            name.clone(),
+            fields["first"].type_info.clone().unwrap().to_string(),
+            fields["second"].type_info.clone().unwrap().to_string(),
+        ),
+        other => panic!("Expected Custom type, got {:?}", other),
+    };
+    assert_eq!(
+        field_types("best"),
+        (
+            "Pair<String, Int>".to_string(),
+            "String".to_string(),
+            "Int".to_string()
+        )
+    );
+    assert_eq!(
+        field_types("flags"),
+        (
+            "Pair<Boolean, Float>".to_string(),
+            "Boolean".to_string(),
+            "Float".to_string()
+        )
+    );
+
+    let error = AstRegistry::default()
+        .create_ast_from_dsl(&dsl("Pair<String>"))
+        .await
+        .unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("Pair takes 2 type argument(s), found 1")
+    );
+}
@@ -0,0 +1,188 @@
+//! Contract tests of the providers against recorded API interactions.
+//!
+//! Run with `RUN_API_TESTS` set to record the cassettes again from the real APIs.
+
+use std::{collections::HashMap, time::Duration};
+
+use async_openai::{
+    Client,
+    config::OpenAIConfig,
+    types::{
+        ChatCompletionRequestMessage, ChatCompletionRequestUserMessage,
+        ChatCompletionRequestUserMessageContent, CreateChatCompletionRequest,
+    },
+};
+use futures::StreamExt;
+use kairei_core::{
+    config::{CommonConfig, EndpointConfig, ProviderConfig, SearchConfig},
+    provider::{
+        llm::ProviderLLM,
+        llms::openai_chat::OpenAIChatProviderLLM,
+        plugin::ProviderPlugin,
+        plugins::web_search_serper::{SERPER_ENDPOINT, WebSearchPlugin},
+        provider::ProviderSecret,
+    },
+    test_support::cassette::{CassetteServer, recording_enabled},
+};
+use secrecy::{ExposeSecret, SecretString};
+
+use crate::{TestContextHolder, provider_tests::setup_config};
+
+const CASSETTES: &str = "tests/fixtures/cassettes";
+const OPENAI_API: &str = "https://api.openai.com";
+const OPENAI_PROVIDER: &str = "openai_travel_expert";
+const WEB_SEARCH_PROVIDER: &str = "web_search_test";
+const PROMPT: &str = "Name one sight to visit in Tokyo. Answer in one sentence.";
+
+fn cassette(name: &str) -> String {
+    format!("{}/{}.json", CASSETTES, name)
+}
+
+/// 記録時は本物の認証情報、再生時はダミー
+fn secret(provider: &str) -> ProviderSecret {
+    if !recording_enabled() {
+        return ProviderSecret {
+            api_key: SecretString::from("sk-test"),
+            additional_auth: HashMap::from([(
+                "web_search_serper_api_key".to_string(),
+                SecretString::from("serper-test"),
+            )]),
+        };
+    }
+    let (_, secret_config) = setup_config();
+    ProviderSecret::from(secret_config.providers.get(provider).unwrap().to_owned())
+}
+
+fn secrets(secret: &ProviderSecret) -> Vec<String> {
+    std::iter::once(&secret.api_key)
+        .chain(secret.additional_auth.values())
+        .map(|value| value.expose_secret().to_string())
+        .collect()
+}
+
+fn chat_config(server: &CassetteServer) -> ProviderConfig {
+    ProviderConfig {
+        common_config: CommonConfig {
+            model: "gpt-4o-mini".to_string(),
+            temperature: 0.7,
+            max_tokens: 256,
+            ..Default::default()
+        },
+        endpoint: EndpointConfig {
+            url: Some(format!("{}/v1", server.url())),
+            api_version: None,
+            deployment_id: None,
+        },
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_openai_chat_completion_contract() {
+    let secret = secret(OPENAI_PROVIDER);
+    let server = CassetteServer::start(
+        cassette("openai_chat_completion"),
+        OPENAI_API,
+        secrets(&secret),
+    )
+    .await
+    .unwrap();
+    let config = chat_config(&server);
+    let mut llm = OpenAIChatProviderLLM::new("cassette");
+    llm.initialize(&config, &secret).await.unwrap();
+
+    let response = llm.send_message(PROMPT, &config).await.unwrap();
+
+    assert!(!response.content.is_empty());
+    assert!(response.metadata.token_usage.is_some());
+    let report = server.finish().unwrap();
+    assert!(report.is_clean(), "{}", report);
+}
+
+#[tokio::test]
+async fn test_openai_chat_stream_contract() {
+    let secret = secret(OPENAI_PROVIDER);
+    let server =
+        CassetteServer::start(cassette("openai_chat_stream"), OPENAI_API, secrets(&secret))
+            .await
+            .unwrap();
+    let client = Client::with_config(
+        OpenAIConfig::new()
+            .with_api_key(secret.api_key.expose_secret())
+            .with_api_base(format!("{}/v1", server.url())),
+    );
+    let request = CreateChatCompletionRequest {
+        model: "gpt-4o-mini".to_string(),
+        messages: vec![ChatCompletionRequestMessage::User(
+            ChatCompletionRequestUserMessage {
+                content: ChatCompletionRequestUserMessageContent::Text(PROMPT.to_string()),
+                name: None,
+            },
+        )],
+        temperature: Some(0.7),
+        max_completion_tokens: Some(256),
+        ..Default::default()
+    };
+
+    let mut stream = client.chat().create_stream(request).await.unwrap();
+    let mut content = String::new();
+    let mut finished = false;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.unwrap();
+        for choice in chunk.choices {
+            content.push_str(choice.delta.content.as_deref().unwrap_or_default());
+            finished |= choice.finish_reason.is_some();
+        }
+    }
+
+    assert!(!content.is_empty());
+    assert!(finished);
+    let report = server.finish().unwrap();
+    assert!(report.is_clean(), "{}", report);
+}
+
+#[tokio::test]
+async fn test_openai_rate_limit_contract() {
+    // 実際に上限へ達するのは難しいので、この記録は再生だけに使う
+    let server = CassetteServer::replay(cassette("openai_rate_limit"))
+        .await
+        .unwrap();
+    let config = chat_config(&server);
+    let mut llm = OpenAIChatProviderLLM::new("cassette");
+    llm.initialize(&config, &ProviderSecret::default())
+        .await
+        .unwrap();
+
+    let error = llm.send_message(PROMPT, &config).await.unwrap_err();
+
+    assert!(error.to_string().contains("exceeded your current quota"));
+    let report = server.finish().unwrap();
+    assert!(report.is_clean(), "{}", report);
+}
+
+#[tokio::test]
+async fn test_serper_search_contract() {
+    let secret = secret(WEB_SEARCH_PROVIDER);
+    let server =
+        CassetteServer::start(cassette("serper_search"), SERPER_ENDPOINT, secrets(&secret))
+            .await
+            .unwrap();
+    // 結果はページを取得した分だけ返る。ページ取得は記録しないので、すぐに打ち切って失敗させる
+    let search_config = SearchConfig {
+        max_results: 3,
+        max_fetch_per_result: 1,
+        fetch_timeout: Duration::from_millis(1),
+        ..Default::default()
+    };
+    let plugin = WebSearchPlugin::new(&search_config, &secret).with_endpoint(server.url());
+    let context_holder = TestContextHolder::new("What is Rust programming language?");
+    let context = context_holder.get_plugin_context();
+
+    let contribution = plugin.generate_section(&context).await.unwrap();
+    let section = contribution.as_section().unwrap();
+
+    assert!(section.content.contains("TITLE:"));
+    assert!(section.content.contains("URL: https://"));
+    let report = server.finish().unwrap();
+    assert!(report.is_clean(), "{}", report);
+}
@@ -0,0 +1,152 @@
+use kairei_core::{
+    config::{SecretConfig, SystemConfig},
+    provider::types::CredentialStatus,
+    system::System,
+};
+use serde_json::json;
+
+use crate::{provider_tests::setup_config, should_run_external_api_tests};
+
+const MODELS_RESPONSE: &str = r#"{
+    "object": "list",
+    "data": [{"id": "gpt-4o-mini", "object": "model", "created": 1, "owned_by": "openai"}]
+}"#;
+
+const INVALID_KEY_RESPONSE: &str = r#"{
+    "error": {
+        "message": "Incorrect API key provided",
+        "type": "invalid_request_error",
+        "param": null,
+        "code": "invalid_api_key"
+    }
+}"#;
+
+/// An OpenAIChat provider calling `url`, with credential validation enabled
+fn chat_config(url: &str, required: &[&str]) -> (SystemConfig, SecretConfig) {
+    let config: SystemConfig = serde_json::from_value(json!({
+        "provider_configs": {
+            "primary_provider": "chat",
+            "providers": {
+                "chat": {
+                    "name": "chat",
+                    "provider_type": "OpenAIChat",
+                    "endpoint": {"url": url, "api_version": "v1"},
+                    "plugin_configs": {}
+                }
+            }
+        },
+        "validate_credentials": {"enabled": true, "required": required}
+    }))
+    .unwrap();
+    let secret_config: SecretConfig = serde_json::from_value(json!({
+        "providers": {"chat": {"api_key": "test-key", "additional_auth": {}}}
+    }))
+    .unwrap();
+    (config, secret_config)
+}
+
+#[tokio::test]
+async fn test_valid_credentials_are_reported_in_status() {
+    let mut server = mockito::Server::new_async().await;
+    let models = server
+        .mock("GET", "/v1/models")
+        .match_header("authorization", "Bearer test-key")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(MODELS_RESPONSE)
+        .create_async()
+        .await;
+
+    let (config, secret_config) = chat_config(&server.url(), &["chat"]);
+    let mut system = System::new(&config, &secret_config).await;
+    let root = system.parse_dsl("").await.unwrap();
+    system.initialize(root).await.unwrap();
+
+    models.assert_async().await;
+    let status = system.get_system_status().await.unwrap();
+    assert_eq!(
+        status.provider_credentials.get("chat"),
+        Some(&CredentialStatus::Valid)
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_credentials_warn_unless_required() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("GET", "/v1/models")
+        .with_status(401)
+        .with_header("content-type", "application/json")
+        .with_body(INVALID_KEY_RESPONSE)
+        .create_async()
+        .await;
+
+    // 必須でなければ起動は続く
+    let (config, secret_config) = chat_config(&server.url(), &[]);
+    let mut system = System::new(&config, &secret_config).await;
+    let root = system.parse_dsl("").await.unwrap();
+    system.initialize(root).await.unwrap();
+    let status = system.get_system_status().await.unwrap();
+    assert_eq!(
+        status.provider_credentials.get("chat"),
+        Some(&CredentialStatus::Invalid(
+            "Incorrect API key provided".to_string()
+        ))
+    );
+
+    let (config, secret_config) = chat_config(&server.url(), &["chat"]);
+    let mut system = System::new(&config, &secret_config).await;
+    let root = system.parse_dsl("").await.unwrap();
+    let error = system.initialize(root).await.unwrap_err();
+    assert!(error.to_string().contains("chat: invalid"));
+}
+
+#[tokio::test]
+async fn test_leaked_secrets_refuse_to_start() {
+    let leaked = "sk-abcdefghijklmnopqrstuvwx";
+    let mut config = SystemConfig {
+        prompt_preamble: Some(format!("Use the key {}", leaked)),
+        ..Default::default()
+    };
+    let mut system = System::new(&config, &SecretConfig::default()).await;
+    let root = system.parse_dsl("").await.unwrap();
+    let error = system.initialize(root).await.unwrap_err().to_string();
+    assert!(error.contains("config/prompt_preamble: \"sk-abc...\""));
+    assert!(!error.contains(leaked));
+
+    let dsl = format!(
+        "micro Fetcher {{\n    state {{\n        key: String = \"{}\";\n    }}\n}}",
+        leaked
+    );
+    let error = system.parse_dsl(&dsl).await.unwrap_err().to_string();
+    assert!(error.contains("dsl:3"));
+
+    // warn_only なら起動できる
+    config.secret_scan.warn_only = true;
+    let system = System::new(&config, &SecretConfig::default()).await;
+    assert!(system.parse_dsl(&dsl).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_live_credentials() {
+    if !should_run_external_api_tests() {
+        return;
+    }
+
+    let (mut config, secret_config) = setup_config();
+    config.validate_credentials.enabled = true;
+    config.validate_credentials.required =
+        config.provider_configs.providers.keys().cloned().collect();
+
+    let mut system = System::new(&config, &secret_config).await;
+    let root = system.parse_dsl("").await.unwrap();
+    system.initialize(root).await.unwrap();
+
+    let status = system.get_system_status().await.unwrap();
+    assert!(
+        status
+            .provider_credentials
+            .values()
+            .all(CredentialStatus::is_valid)
+    );
+}
@@ -1,5 +1,7 @@
 use kairei_core::config::{self, SecretConfig, SystemConfig};
 
+pub mod cassette_test;
+pub mod credentials_test;
 pub mod openai_test;
 pub mod provider_test;
 pub mod serper_test;
@@ -137,6 +137,8 @@ async fn setup_standard_provider(provider_name: &str) -> (StandardProvider, Prov
             .unwrap()
             .clone(),
         secret: ProviderSecret::from(secret_config.providers.get(provider_name).unwrap().clone()),
+        profiler: None,
+        chunks: None,
     };
 
     (provider, context)
@@ -98,6 +98,7 @@ async fn test_travel_agent_basic_flow() {
                 );
                 hashmap
             },
+            ..Default::default()
         })
         .await
         .unwrap();
@@ -111,3 +112,106 @@ async fn test_travel_agent_basic_flow() {
         )]))
     );
 }
+
+#[tokio::test]
+async fn test_prompt_preamble_override_and_reload() {
+    // SimpleExpert はプロンプトに含まれるパターンで応答を選ぶので、どの前置きが使われたかが分かる
+    let config: SystemConfig = serde_json::from_str(
+        r#"
+        {
+            "prompt_preamble": "Persona from config",
+            "provider_configs": {
+                "primary_provider": "simple_expert",
+                "providers": {
+                    "simple_expert": {
+                        "name": "simple_expert",
+                        "provider_type": "SimpleExpert",
+                        "provider_specific": {
+                            "Persona from config": "config",
+                            "Persona from world": "world",
+                            "Persona of the auditor": "agent",
+                            "Persona after reload": "reloaded"
+                        },
+                        "plugin_configs": {}
+                    }
+                }
+            }
+        }
+    "#,
+    )
+    .unwrap();
+
+    let dsl = r#"
+        world PreambleWorld {
+            persona "Persona from world"
+        }
+        micro Clerk {
+            answer {
+                on request Ask() -> Result<String, Error> {
+                    return think("Who are you?")
+                }
+            }
+        }
+        micro Auditor {
+            persona "Persona of ${role}"
+            state {
+                role: String = "the auditor";
+            }
+            answer {
+                on request Ask() -> Result<String, Error> {
+                    return think("Who are you?")
+                }
+            }
+        }
+    "#;
+
+    let secret_config: SecretConfig = serde_json::from_str(
+        r#"{ "providers": { "simple_expert": { "api_key": "test_key", "additional_auth": {} } } }"#,
+    )
+    .unwrap();
+
+    let mut system = System::new(&config, &secret_config).await;
+    let root = system.parse_dsl(dsl).await.unwrap();
+    system.initialize(root).await.unwrap();
+    system.start().await.unwrap();
+    sleep(Duration::from_millis(100)).await;
+
+    let ask = |responder: &str| {
+        Event::request_builder()
+            .request_type("Ask")
+            .requester("test")
+            .responder(responder)
+            .request_id(&uuid::Uuid::new_v4().to_string())
+            .build()
+            .unwrap()
+    };
+    let output = |answer: &str| {
+        Value::Map(HashMap::from([(
+            "output".to_string(),
+            Value::String(answer.to_string()),
+        )]))
+    };
+
+    // world の persona が config の既定値を置き換え、エージェントの persona がさらに優先される
+    assert_eq!(
+        system.send_request(ask("Clerk")).await.unwrap(),
+        output("world")
+    );
+    assert_eq!(
+        system.send_request(ask("Auditor")).await.unwrap(),
+        output("agent")
+    );
+
+    // 実行中の更新は以降のリクエストに反映される
+    system
+        .set_prompt_preamble(Some("Persona after reload".to_string()))
+        .await;
+    assert_eq!(
+        system.send_request(ask("Clerk")).await.unwrap(),
+        output("reloaded")
+    );
+    assert_eq!(
+        system.send_request(ask("Auditor")).await.unwrap(),
+        output("agent")
+    );
+}
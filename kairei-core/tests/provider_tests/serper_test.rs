@@ -2,7 +2,7 @@ use kairei_core::{
     config::SearchConfig,
     provider::{
         plugin::ProviderPlugin, plugins::web_search_serper::WebSearchPlugin,
-        provider::ProviderSecret,
+        provider::ProviderSecret, types::CredentialStatus,
     },
 };
 
@@ -31,7 +31,8 @@ async fn test_web_search() {
     let context_holder = TestContextHolder::new("What is Rust programming language?");
     let context = context_holder.get_plugin_context();
 
-    let section = plugin.generate_section(&context).await.unwrap();
+    let contribution = plugin.generate_section(&context).await.unwrap();
+    let section = contribution.as_section().unwrap();
 
     assert!(!section.content.is_empty());
     assert!(section.content.contains("Rust"));
@@ -40,3 +41,28 @@ async fn test_web_search() {
     assert!(section.content.contains("TITLE:"));
     assert!(section.content.contains("URL:"));
 }
+
+#[tokio::test]
+async fn test_web_search_credentials() {
+    if !should_run_external_api_tests() {
+        return;
+    }
+
+    let (_system_config, secret_config) = setup_config();
+
+    let plugin = WebSearchPlugin::new(
+        &SearchConfig::default(),
+        &ProviderSecret::from(
+            secret_config
+                .providers
+                .get(WEB_SEARCH_TEST_PROVIDER)
+                .unwrap()
+                .to_owned(),
+        ),
+    );
+
+    assert_eq!(
+        plugin.validate_credentials().await.unwrap(),
+        CredentialStatus::Valid
+    );
+}
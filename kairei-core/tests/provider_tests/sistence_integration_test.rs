@@ -15,6 +15,7 @@ use kairei_core::provider::capabilities::will_action::{
     WillAction, WillActionContext, WillActionError, WillActionParams, WillActionResolver,
     WillActionResult, WillActionSignature,
 };
+use kairei_core::provider::contribution::ContextContribution;
 use kairei_core::provider::llm::{LLMResponse, ProviderLLM};
 use kairei_core::provider::llms::simple_expert::SimpleExpertProviderLLM;
 use kairei_core::provider::plugin::{PluginContext, ProviderPlugin};
@@ -54,8 +55,11 @@ impl ProviderPlugin for MockSharedMemory {
         CapabilityType::SharedMemory
     }
 
-    async fn generate_section<'a>(&self, _context: &PluginContext<'a>) -> ProviderResult<Section> {
-        Ok(Section::new(""))
+    async fn generate_section<'a>(
+        &self,
+        _context: &PluginContext<'a>,
+    ) -> ProviderResult<ContextContribution> {
+        Ok(Section::new("").into())
     }
 
     async fn process_response<'a>(
@@ -112,6 +116,25 @@ impl SharedMemoryCapability for MockSharedMemory {
             .map(|entry| entry.key().clone())
             .collect())
     }
+
+    async fn compare_and_swap(
+        &self,
+        key: &str,
+        expected: Option<&Value>,
+        value: Value,
+    ) -> Result<bool, SharedMemoryError> {
+        match self.storage.entry(key.to_string()) {
+            dashmap::mapref::entry::Entry::Occupied(mut entry) if Some(entry.get()) == expected => {
+                entry.insert(value);
+                Ok(true)
+            }
+            dashmap::mapref::entry::Entry::Vacant(entry) if expected.is_none() => {
+                entry.insert(value);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
 }
 
 /// Mock Will Action implementation for testing
@@ -204,8 +227,11 @@ impl ProviderPlugin for MockWillActionResolver {
         CapabilityType::Custom("will_action".to_string())
     }
 
-    async fn generate_section<'a>(&self, _context: &PluginContext<'a>) -> ProviderResult<Section> {
-        Ok(Section::new(""))
+    async fn generate_section<'a>(
+        &self,
+        _context: &PluginContext<'a>,
+    ) -> ProviderResult<ContextContribution> {
+        Ok(Section::new("").into())
     }
 
     async fn process_response<'a>(
@@ -294,6 +320,7 @@ impl Provider for SimpleExpertProviderWrapper {
             output: llm_response.content,
             metadata: kairei_core::provider::request::ResponseMetadata {
                 timestamp: kairei_core::timestamp::Timestamp::now(),
+                token_usage: None,
             },
         })
     }
@@ -356,8 +383,7 @@ fn create_test_request(
             query: kairei_core::eval::expression::Value::String(query.to_string()),
             parameters,
         },
-        state: Default::default(),
-        config: Default::default(),
+        ..Default::default()
     }
 }
 
@@ -428,6 +454,8 @@ async fn test_sistence_provider_regular_request() {
     let context_with_config = ProviderContext {
         config,
         secret: ProviderSecret::default(),
+        profiler: None,
+        chunks: None,
     };
 
     let response = provider
@@ -466,6 +494,8 @@ async fn test_sistence_provider_will_action_request() {
     let context = ProviderContext {
         config: create_provider_config(),
         secret: ProviderSecret::default(),
+        profiler: None,
+        chunks: None,
     };
 
     let request = create_will_action_request("notify");
@@ -513,6 +543,8 @@ async fn test_sistence_provider_will_action_llm_fallback() {
     let context = ProviderContext {
         config: create_provider_config(),
         secret: ProviderSecret::default(),
+        profiler: None,
+        chunks: None,
     };
 
     let request = create_will_action_request("suggest");
@@ -558,6 +590,8 @@ async fn test_sistence_provider_agent_context_persistence() {
     let context = ProviderContext {
         config,
         secret: ProviderSecret::default(),
+        profiler: None,
+        chunks: None,
     };
 
     // Create requests with agent information
@@ -622,6 +656,8 @@ async fn test_sistence_provider_error_handling() {
     let context_with_config = ProviderContext {
         config,
         secret: ProviderSecret::default(),
+        profiler: None,
+        chunks: None,
     };
 
     let response = provider
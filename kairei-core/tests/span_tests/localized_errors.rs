@@ -0,0 +1,51 @@
+//! Integration tests for diagnostics rendered in the configured locale
+
+use kairei_core::{
+    config::{SecretConfig, SystemConfig},
+    message_catalog::{Locale, codes},
+    system::{System, SystemError},
+};
+
+async fn parse_error(locale: Locale, dsl: &str) -> (System, SystemError) {
+    let config = SystemConfig {
+        locale,
+        ..Default::default()
+    };
+    let system = System::new(&config, &SecretConfig::default()).await;
+    let error = system.parse_dsl(dsl).await.unwrap_err();
+    (system, error)
+}
+
+#[tokio::test]
+async fn test_parse_error_renders_in_japanese() {
+    let dsl = "micro TestAgent { }\n}";
+
+    let (system, error) = parse_error(Locale::Ja, dsl).await;
+    match &error {
+        SystemError::Ast(ast_error) => {
+            assert_eq!(ast_error.diagnostic().code, codes::PARSE_TRAILING_TOKENS)
+        }
+        other => panic!("Expected a parse error, got {:?}", other),
+    }
+    assert_eq!(
+        system.render_error(&error).await,
+        "解析されずに残ったトークンがあります (詳しくは explain parse.trailing_tokens を実行してください)"
+    );
+
+    let (system, error) = parse_error(Locale::En, dsl).await;
+    assert_eq!(
+        system.render_error(&error).await,
+        "Not all tokens were consumed (run explain parse.trailing_tokens for details)"
+    );
+}
+
+#[tokio::test]
+async fn test_tokenize_error_keeps_its_arguments() {
+    let (system, error) = parse_error(Locale::Ja, "micro TestAgent { @invalid_token }").await;
+    let rendered = system.render_error(&error).await;
+    assert!(
+        rendered.starts_with("不正なトークン @invalid_token"),
+        "{}",
+        rendered
+    );
+}
@@ -100,6 +100,7 @@ async fn test_multi_line_block_parsing() {
             message,
             token_span,
             error,
+            ..
         }) => {
             debug!("Parse error: {}", message);
             debug!("Error: {}", error);
@@ -150,6 +151,7 @@ async fn test_system_multi_line_error_tracking() {
             message,
             token_span,
             error,
+            ..
         })) => {
             debug!("Parse error: {}", message);
             debug!("Error: {}", error);
@@ -213,6 +215,7 @@ async fn test_multi_line_error_visualization() {
             message,
             token_span,
             error,
+            ..
         })) => {
             debug!("Parse error: {}", message);
             debug!("Error: {}", error);
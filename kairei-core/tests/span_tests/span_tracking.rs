@@ -95,6 +95,30 @@ async fn test_tokenization_error_preserves_span_multiline() {
     }
 }
 
+/// Test that an unterminated block comment is reported at its start
+#[tokio::test]
+async fn test_unterminated_block_comment_preserves_span() {
+    let invalid_dsl = "micro TestAgent {\n    /* not closed\n}";
+
+    let result = AstRegistry::default()
+        .create_ast_from_dsl(invalid_dsl)
+        .await;
+
+    match result {
+        Err(ASTError::TokenizeError(TokenizerError::ParseError { message, span, .. })) => {
+            assert!(
+                message.contains("Unterminated block comment"),
+                "{}",
+                message
+            );
+            assert_eq!(span.line, 2);
+            assert_eq!(span.column, 5);
+            assert_eq!(&invalid_dsl[span.start..span.end], "/*");
+        }
+        other => panic!("Expected TokenizeError, got {:?}", other),
+    }
+}
+
 /// Test that parsing errors preserve span information
 #[tokio::test]
 async fn test_parsing_error_preserves_span() {
@@ -112,6 +136,7 @@ async fn test_parsing_error_preserves_span() {
         message,
         token_span,
         error,
+        ..
     }) = result
     {
         // For now, we're just checking that we get a parse error
@@ -186,6 +211,7 @@ async fn test_compilation_pipeline_error_locations() {
                 message,
                 token_span,
                 error,
+                ..
             }) => {
                 assert_eq!(expected_error_type, "ParseError");
 
@@ -72,6 +72,7 @@ async fn test_system_parsing_error_preserves_span() {
             message,
             token_span,
             error,
+            ..
         })) => {
             // Verify that the token span information is correct
             let span = token_span.unwrap().span;
@@ -1,14 +1,40 @@
-use std::{collections::HashMap, time::Duration};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
+use kairei_core::agent_batch::{
+    AgentBatch, AgentSelector, BatchOperation, BatchOutcome, BatchSummary, RunStatus,
+};
+use kairei_core::agent_log::LogLevel;
 use kairei_core::analyzer::Parser;
-use kairei_core::config::{ProviderConfig, ProviderConfigs, ProviderSecretConfig, SecretConfig};
+use kairei_core::clock::MockClock;
+use kairei_core::config::{
+    AgentConfigValues, AgentLogConfig, CatalogConfig, EventJournalConfig, EventValidationMode,
+    HandlerRecordingConfig, IdleEvictionConfig, PluginConfig, ProviderConfig, ProviderConfigs,
+    ProviderSecretConfig, RemoteBridgeConfig, RequestQueueConfig, SchedulerConfig,
+    SchedulingPolicy, SecretConfig,
+};
+use kairei_core::debug_session::DebugSessionError;
+use kairei_core::dsl_watcher::{DslWatcher, ReloadScope, WatchEvent};
+use kairei_core::eval::evaluator::ConstraintViolation;
+use kairei_core::eval::profile::ProfileSection;
+use kairei_core::event::journal::ReplayReport;
+use kairei_core::preflight::{PreflightComponent, PreflightSource, Severity};
 use kairei_core::preprocessor::Preprocessor;
+use kairei_core::provider::capabilities::storage::StorageBackend;
+use kairei_core::provider::config::plugins::{
+    BackendSpecificConfig, BackendType, LocalFileSystemConfig, PersistenceConfig,
+    PersistentSharedMemoryConfig, SharedMemoryConfig,
+};
+use kairei_core::provider::plugins::storage::local_fs::LocalFileSystemBackend;
 use kairei_core::provider::provider::ProviderType;
-use kairei_core::system::SystemResult;
+use kairei_core::reload_plan::ReloadPlan;
+use kairei_core::system::{SystemError, SystemResult};
 use kairei_core::tokenizer::token::Token;
 use kairei_core::type_checker::run_type_checker;
 use kairei_core::{
-    MicroAgentDef, config::SystemConfig, event_bus::Event, event_registry::EventType,
+    MicroAgentDef,
+    config::SystemConfig,
+    event_bus::{Event, EventReceiver, Value},
+    event_registry::EventType,
     system::System,
 };
 use tokio::{self, time::sleep};
@@ -49,6 +75,479 @@ pub fn setup_non_api_config() -> (SystemConfig, SecretConfig) {
     (system_config, secret_config)
 }
 
+const JOURNAL_DSL: &str = r#"
+    micro Ledger {
+        observe {
+            on Deposited(amount: Int) {
+                emit Credited(amount: amount)
+            }
+        }
+    }
+"#;
+
+/// イベントの記録を `dir` に置いたシステムを `dsl` で起動する。`watched` の
+/// イベントは再生の前から受け取る
+async fn journaled_system(
+    dir: &std::path::Path,
+    dsl: &str,
+    watched: &[&str],
+) -> SystemResult<(System, ReplayReport, Vec<EventReceiver>)> {
+    let (mut system_config, secret_config) = setup_non_api_config();
+    system_config.event_journal = EventJournalConfig {
+        enabled: true,
+        base_dir: Some(dir.to_string_lossy().to_string()),
+        ..Default::default()
+    };
+    let mut system = System::new(&system_config, &secret_config).await;
+    let root = system.parse_dsl(dsl).await?;
+    let report = system.initialize(root).await?;
+    let mut receivers = vec![];
+    for name in watched {
+        receivers.push(
+            system
+                .subscribe_events(vec![EventType::Custom(name.to_string())])
+                .await?,
+        );
+    }
+    system.start().await?;
+    Ok((system, report, receivers))
+}
+
+fn deposit(amount: i64) -> Event {
+    Event {
+        event_type: EventType::Custom("Deposited".to_string()),
+        parameters: HashMap::from([("amount".to_string(), Value::Integer(amount))]),
+        ..Default::default()
+    }
+}
+
+/// `events` から次の `count` 件の `amount` を受け取る
+async fn received_amounts(events: &mut EventReceiver, count: usize) -> Vec<i64> {
+    let mut amounts = vec![];
+    for _ in 0..count {
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .expect("event not received")
+            .unwrap();
+        match event.parameters.get("amount") {
+            Some(Value::Integer(amount)) => amounts.push(*amount),
+            other => panic!("unexpected amount: {:?}", other),
+        }
+    }
+    amounts
+}
+
+#[tokio::test]
+async fn test_journaled_events_are_replayed_on_startup() -> SystemResult<()> {
+    let dir = tempfile::tempdir().unwrap();
+    let (system, report, mut receivers) =
+        journaled_system(dir.path(), JOURNAL_DSL, &["Credited"]).await?;
+    assert_eq!(report, ReplayReport::default());
+    for amount in [10, 20, 12] {
+        system.send_event(deposit(amount)).await?;
+    }
+    assert_eq!(
+        received_amounts(&mut receivers[0], 3).await,
+        vec![10, 20, 12]
+    );
+    drop(system);
+
+    // 次の実行は記録したイベントを古い順に処理し直す
+    let (system, report, mut receivers) =
+        journaled_system(dir.path(), JOURNAL_DSL, &["Credited"]).await?;
+    assert_eq!(report.events, 3);
+    assert_eq!(report.unannotated, vec!["Ledger.Deposited".to_string()]);
+    assert_eq!(
+        received_amounts(&mut receivers[0], 3).await,
+        vec![10, 20, 12]
+    );
+
+    // 再生のあとに送ったイベントも記録に続けて書く
+    system.send_event(deposit(8)).await?;
+    assert_eq!(received_amounts(&mut receivers[0], 1).await, vec![8]);
+    drop(system);
+    let (_system, report, mut receivers) =
+        journaled_system(dir.path(), JOURNAL_DSL, &["Credited"]).await?;
+    assert_eq!(report.events, 4);
+    assert_eq!(
+        received_amounts(&mut receivers[0], 4).await,
+        vec![10, 20, 12, 8]
+    );
+    Ok(())
+}
+
+const REPLAY_DIRECTIVE_DSL: &str = r#"
+    micro Ledger {
+        observe {
+            @replay(safe)
+            on Deposited(amount: Int) {
+                emit Credited(amount: amount)
+            }
+        }
+    }
+
+    micro Mailer {
+        observe {
+            @replay(skip)
+            on Deposited(amount: Int) {
+                emit MailSent(amount: amount)
+            }
+        }
+    }
+
+    micro Auditor {
+        react {
+            on Deposited(amount: Int) {
+                emit Audited(amount: amount)
+            }
+        }
+    }
+"#;
+
+#[tokio::test]
+async fn test_replay_directives_choose_replayed_handlers() -> SystemResult<()> {
+    let watched = ["Credited", "MailSent", "Audited"];
+    let dir = tempfile::tempdir().unwrap();
+    let (system, _, mut receivers) =
+        journaled_system(dir.path(), REPLAY_DIRECTIVE_DSL, &watched).await?;
+    for amount in [10, 20] {
+        system.send_event(deposit(amount)).await?;
+    }
+    for events in receivers.iter_mut() {
+        assert_eq!(received_amounts(events, 2).await, vec![10, 20]);
+    }
+    drop(system);
+
+    let (system, report, mut receivers) =
+        journaled_system(dir.path(), REPLAY_DIRECTIVE_DSL, &watched).await?;
+    assert_eq!(report.events, 2);
+    assert_eq!(report.skipped.len(), 2);
+    assert!(
+        report
+            .skipped
+            .iter()
+            .all(|skipped| skipped.agent == "Mailer"
+                && skipped.event_type == "Deposited"
+                && skipped.event_id.is_some())
+    );
+    assert_eq!(report.unannotated, vec!["Auditor.Deposited".to_string()]);
+    assert_eq!(received_amounts(&mut receivers[0], 2).await, vec![10, 20]);
+    assert_eq!(received_amounts(&mut receivers[2], 2).await, vec![10, 20]);
+
+    // Mailer は再生を飛ばし、再生のあとに届いた新しいイベントだけを処理する
+    system.send_event(deposit(5)).await?;
+    assert_eq!(received_amounts(&mut receivers[1], 1).await, vec![5]);
+    Ok(())
+}
+
+const QUEUED_REQUESTS_DSL: &str = r#"
+    micro Gate {
+        answer {
+            on request Hold() -> Result<String, Error> {
+                reply = request Ping to Echo()
+                return Ok("released")
+            }
+        }
+    }
+
+    micro Echo {
+        answer {
+            on request Ping() -> Result<String, Error> {
+                return Ok("pong")
+            }
+        }
+    }
+
+    micro Worker {
+        answer {
+            on request Work(n: Int) -> Result<Int, Error> {
+                return Ok(n)
+            }
+        }
+    }
+"#;
+
+/// リクエストを 1 件ずつ処理し、`Worker` への未処理のリクエストを `dir` に記録する設定
+fn queued_config(dir: &std::path::Path) -> (SystemConfig, SecretConfig) {
+    let (mut system_config, secret_config) = setup_non_api_config();
+    system_config.scheduler = SchedulerConfig {
+        policy: SchedulingPolicy::Fifo,
+        workers: 1,
+        ..Default::default()
+    };
+    system_config.request_queue = RequestQueueConfig {
+        base_dir: Some(dir.join("queue").to_string_lossy().to_string()),
+        agents: vec!["Worker".to_string()],
+        ..Default::default()
+    };
+    (system_config, secret_config)
+}
+
+/// `dsl` でシステムを起動する。記録から戻したリクエストは起動時に実行されるため、
+/// その応答も受け取れるよう起動の前に購読する
+async fn queued_system(
+    (system_config, secret_config): &(SystemConfig, SecretConfig),
+    dsl: &str,
+) -> SystemResult<(System, EventReceiver)> {
+    let mut system = System::new(system_config, secret_config).await;
+    let root = system.parse_dsl(dsl).await?;
+    system.initialize(root).await?;
+    let (events, _) = system.event_bus().subscribe();
+    system.start().await?;
+    Ok((system, events))
+}
+
+fn queued_request(request_type: &str, responder: &str, parameters: &[(&str, Value)]) -> Event {
+    let mut builder = Event::request_builder()
+        .request_type(request_type)
+        .requester("test")
+        .responder(responder)
+        .request_id(&uuid::Uuid::new_v4().to_string());
+    for (name, value) in parameters {
+        builder = builder.parameter(name, value);
+    }
+    builder.build().unwrap()
+}
+
+/// 唯一のワーカーを `Gate` で塞ぎ、`requests` を処理前のまま記録させる
+async fn queue_behind_gate(system: &System, dir: &std::path::Path, requests: &[Event]) {
+    system
+        .send_event(queued_request("Hold", "Gate", &[]))
+        .await
+        .unwrap();
+    for request in requests {
+        system.send_event(request.clone()).await.unwrap();
+    }
+    let backend = LocalFileSystemBackend::new(LocalFileSystemConfig {
+        base_dir: dir.join("queue").to_string_lossy().to_string(),
+        file_extension: "json".to_string(),
+    });
+    let mut request_ids: Vec<&str> = requests
+        .iter()
+        .map(|request| request.event_type.request_id().unwrap())
+        .collect();
+    request_ids.sort();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            // キーは「連番-リクエスト ID」
+            let stored = backend
+                .load("request_queue.Worker")
+                .await
+                .unwrap_or_default();
+            let mut stored_ids: Vec<&str> = stored
+                .keys()
+                .filter_map(|key| key.split_once('-').map(|(_, id)| id))
+                .collect();
+            stored_ids.sort();
+            if stored_ids == request_ids {
+                break;
+            }
+            sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("requests should be written to the queue");
+}
+
+/// `events` から最終応答を、`request_ids` のすべてに届いてから 300ms 後まで集める
+async fn responses_to(
+    events: &mut EventReceiver,
+    request_ids: &[String],
+) -> HashMap<String, Vec<Value>> {
+    let mut responses: HashMap<String, Vec<Value>> = HashMap::new();
+    // Tick などが絶えず届くため、待ち時間は全体の期限で区切る
+    let mut deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    let mut done = false;
+    loop {
+        if !done && request_ids.iter().all(|id| responses.contains_key(id)) {
+            done = true;
+            deadline = tokio::time::Instant::now() + Duration::from_millis(300);
+        }
+        let Ok(event) = tokio::time::timeout_at(deadline, events.recv()).await else {
+            assert!(done, "responses received: {:?}", responses);
+            return responses;
+        };
+        let event = event.unwrap();
+        if !event.event_type.is_response() {
+            continue;
+        }
+        let request_id = event.event_type.request_id().unwrap().to_string();
+        if request_ids.contains(&request_id) {
+            let response = event.parameters.get("response").cloned();
+            responses
+                .entry(request_id)
+                .or_default()
+                .push(response.unwrap_or(Value::Null));
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_queued_requests_run_once_after_restart() -> SystemResult<()> {
+    let dir = tempfile::tempdir().unwrap();
+    let config = queued_config(dir.path());
+    let (system, mut events) = queued_system(&config, QUEUED_REQUESTS_DSL).await?;
+    sleep(Duration::from_millis(100)).await;
+
+    let requests: Vec<Event> = (0..5)
+        .map(|n| queued_request("Work", "Worker", &[("n", Value::Integer(n))]))
+        .collect();
+    let request_ids: Vec<String> = requests
+        .iter()
+        .map(|request| request.event_type.request_id().unwrap().to_string())
+        .collect();
+    queue_behind_gate(&system, dir.path(), &requests).await;
+    let status = system.get_agent_status("Worker").await?;
+    assert_eq!(status.request_queue.unwrap().depth, 5);
+    while let Ok(event) = events.receiver.try_recv() {
+        assert!(
+            !event.event_type.is_response(),
+            "no request should have run: {:?}",
+            event
+        );
+    }
+    drop(system);
+
+    // 同じ設定で作り直すと、残った 5 件が新しいリクエストより先に一度ずつ実行される
+    let (system, mut events) = queued_system(&config, QUEUED_REQUESTS_DSL).await?;
+    let responses = responses_to(&mut events, &request_ids).await;
+    for (n, request_id) in request_ids.iter().enumerate() {
+        assert_eq!(responses[request_id], vec![Value::Integer(n as i64)]);
+    }
+    let status = system.get_agent_status("Worker").await?;
+    let stats = status.request_queue.unwrap();
+    assert_eq!((stats.depth, stats.reenqueued), (0, 5));
+
+    // 3 回目の起動では何も残っていない
+    drop(system);
+    let (system, mut events) = queued_system(&config, QUEUED_REQUESTS_DSL).await?;
+    let stats = system
+        .get_agent_status("Worker")
+        .await?
+        .request_queue
+        .unwrap();
+    assert_eq!(stats.reenqueued, 0);
+    let deadline = tokio::time::Instant::now() + Duration::from_millis(300);
+    while let Ok(event) = tokio::time::timeout_at(deadline, events.recv()).await {
+        let event = event.unwrap();
+        assert!(!event.event_type.is_response(), "ran again: {:?}", event);
+    }
+    Ok(())
+}
+
+const QUEUED_CACHED_DSL: &str = r#"
+    micro Gate {
+        answer {
+            on request Hold() -> Result<String, Error> {
+                reply = request Ping to Echo()
+                return Ok("released")
+            }
+        }
+    }
+
+    micro Echo {
+        answer {
+            on request Ping() -> Result<String, Error> {
+                return Ok("pong")
+            }
+        }
+    }
+
+    micro Worker {
+        answer {
+            @cache(ttl: 10m, key: [city])
+            on request Forecast(city: String, days: Int) -> Result<String, Error> {
+                increment("forecast_runs", 1)
+                return Ok(city)
+            }
+
+            on request Runs() -> Result<Int, Error> {
+                return Ok(increment("forecast_runs", 0))
+            }
+        }
+    }
+"#;
+
+/// `dir` のファイルに保存する共有メモリ
+fn persistent_memory(dir: &std::path::Path, namespace: &str) -> PersistentSharedMemoryConfig {
+    PersistentSharedMemoryConfig {
+        base: SharedMemoryConfig {
+            namespace: namespace.to_string(),
+            ..Default::default()
+        },
+        persistence: PersistenceConfig {
+            backend_type: BackendType::LocalFileSystem,
+            sync_interval: Duration::ZERO,
+            auto_load: true,
+            auto_save: true,
+            backend_config: BackendSpecificConfig::Local(LocalFileSystemConfig {
+                base_dir: dir.join(namespace).to_string_lossy().to_string(),
+                file_extension: "json".to_string(),
+            }),
+        },
+    }
+}
+
+async fn forecast_runs(system: &System) -> Value {
+    let request = queued_request("Runs", "Worker", &[]);
+    system.send_request(request).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_recovered_request_answered_from_kept_response() -> SystemResult<()> {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = queued_config(dir.path());
+    config.0.shared_counters = Some(persistent_memory(dir.path(), "counters"));
+    config.0.response_cache.memory = Some(persistent_memory(dir.path(), "responses"));
+    let forecast = |city: &str, days: i64| {
+        queued_request(
+            "Forecast",
+            "Worker",
+            &[
+                ("city", Value::String(city.to_string())),
+                ("days", Value::Integer(days)),
+            ],
+        )
+    };
+
+    let (system, _events) = queued_system(&config, QUEUED_CACHED_DSL).await?;
+    sleep(Duration::from_millis(100)).await;
+    assert_eq!(
+        system.send_request(forecast("Kyoto", 3)).await?,
+        Value::String("Kyoto".to_string())
+    );
+    assert_eq!(forecast_runs(&system).await, Value::Integer(1));
+
+    // Kyoto の応答は保存済み、Osaka はまだない
+    let requests = vec![forecast("Kyoto", 7), forecast("Osaka", 3)];
+    let request_ids: Vec<String> = requests
+        .iter()
+        .map(|request| request.event_type.request_id().unwrap().to_string())
+        .collect();
+    queue_behind_gate(&system, dir.path(), &requests).await;
+    drop(system);
+
+    // 作り直すと、保存した応答がある Kyoto はハンドラを実行せずに答える
+    let (system, mut events) = queued_system(&config, QUEUED_CACHED_DSL).await?;
+    let responses = responses_to(&mut events, &request_ids).await;
+    assert_eq!(
+        responses[&request_ids[0]],
+        vec![Value::String("Kyoto".to_string())]
+    );
+    assert_eq!(
+        responses[&request_ids[1]],
+        vec![Value::String("Osaka".to_string())]
+    );
+    assert_eq!(forecast_runs(&system).await, Value::Integer(2));
+    let stats = system
+        .get_agent_status("Worker")
+        .await?
+        .response_cache
+        .unwrap();
+    assert_eq!((stats.hits, stats.misses), (1, 1));
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_system_lifecycle() -> SystemResult<()> {
     let (system_config, secret_config) = setup_non_api_config();
@@ -220,3 +719,2320 @@ async fn test_request_response() -> SystemResult<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_per_agent_log_level() -> SystemResult<()> {
+    let (system_config, secret_config) = setup_non_api_config();
+    let mut system = System::new(&system_config, &secret_config).await;
+    let agent = |name: &str| {
+        format!(
+            r#"
+            micro {} {{
+                answer {{
+                    on request Ping() -> Result<String, Error> {{
+                        log.debug("pinged", count: 1)
+                        log.info("answered")
+                        return Ok("pong")
+                    }}
+                }}
+            }}
+        "#,
+            name
+        )
+    };
+    let root = system
+        .parse_dsl(&format!("{}{}", agent("Noisy"), agent("Quiet")))
+        .await?;
+    system.initialize(root).await?;
+    system.start().await?;
+    sleep(Duration::from_millis(100)).await;
+
+    let ping = |responder: &str, request_id: &str| {
+        Event::request_builder()
+            .request_type("Ping")
+            .requester("test")
+            .responder(responder)
+            .request_id(request_id)
+            .build()
+            .unwrap()
+    };
+    let messages = |agent: &str| {
+        system
+            .agent_logs(agent)
+            .into_iter()
+            .map(|log| format!("{}:{}", log.level, log.message))
+            .collect::<Vec<_>>()
+    };
+
+    // 再起動せずに Noisy だけ debug にする
+    system
+        .set_agent_log_config(
+            "Noisy",
+            AgentLogConfig {
+                level: LogLevel::Debug,
+                ..Default::default()
+            },
+        )
+        .await;
+    system.send_request(ping("Noisy", "noisy-1")).await?;
+    system.send_request(ping("Quiet", "quiet-1")).await?;
+    assert_eq!(messages("Noisy"), vec!["debug:pinged", "info:answered"]);
+    assert_eq!(messages("Quiet"), vec!["info:answered"]);
+    let logs = system.agent_logs("Noisy");
+    assert_eq!(logs[0].agent, "Noisy");
+    assert_eq!(logs[0].fields["count"], 1);
+
+    system
+        .set_agent_log_config("Noisy", AgentLogConfig::default())
+        .await;
+    system.send_request(ping("Noisy", "noisy-2")).await?;
+    assert_eq!(
+        messages("Noisy"),
+        vec!["debug:pinged", "info:answered", "info:answered"]
+    );
+    assert_eq!(system.agent_log_config("Noisy").await.level, LogLevel::Info);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_request_streaming() -> SystemResult<()> {
+    use futures::StreamExt;
+
+    let (system_config, secret_config) = setup_non_api_config();
+    let mut system = System::new(&system_config, &secret_config).await;
+    let root = system
+        .parse_dsl(
+            r#"
+            micro Streamer {
+                answer {
+                    on request Count() -> Result<String, Error> {
+                        yield Ok("one")
+                        yield Ok("two")
+                        return Ok("done")
+                    }
+                }
+            }
+        "#,
+        )
+        .await?;
+    system.initialize(root).await?;
+    system.start().await?;
+    sleep(Duration::from_millis(100)).await;
+
+    let request = Event::request_builder()
+        .request_type("Count")
+        .requester("test")
+        .responder("Streamer")
+        .request_id("streaming-request")
+        .build()
+        .unwrap();
+    let events = system
+        .send_request_streaming(request)
+        .await?
+        .collect::<Vec<_>>()
+        .await;
+
+    let outputs = events
+        .into_iter()
+        .map(|event| {
+            let event = event.unwrap();
+            (
+                event.event_type.is_partial_response(),
+                event.response_value(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        outputs,
+        vec![
+            (
+                true,
+                kairei_core::event_bus::Value::String("one".to_string())
+            ),
+            (
+                true,
+                kairei_core::event_bus::Value::String("two".to_string())
+            ),
+            (
+                false,
+                kairei_core::event_bus::Value::String("done".to_string())
+            ),
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_request_stream_events() -> SystemResult<()> {
+    use futures::StreamExt;
+    use kairei_core::event::stream_event::{StreamEvent, StreamUsage, stream_events};
+
+    let (system_config, secret_config) = setup_non_api_config();
+    let mut system = System::new(&system_config, &secret_config).await;
+    let root = system
+        .parse_dsl(
+            r#"
+            micro Streamer {
+                answer {
+                    on request Count() -> Result<String, Error> {
+                        yield Ok("one")
+                        yield Ok("two")
+                        return Ok("done")
+                    }
+                    on request Broken() -> Result<String, Error> {
+                        yield Ok("one")
+                        return Err("broken")
+                    }
+                }
+            }
+        "#,
+        )
+        .await?;
+    system.initialize(root).await?;
+    system.start().await?;
+    sleep(Duration::from_millis(100)).await;
+
+    let request = |request_type: &str| {
+        Event::request_builder()
+            .request_type(request_type)
+            .requester("test")
+            .responder("Streamer")
+            .request_id(&format!("stream-{}", request_type))
+            .build()
+            .unwrap()
+    };
+
+    let events = stream_events(system.send_request_streaming(request("Count")).await?)
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(
+        events,
+        vec![
+            StreamEvent::Token {
+                content: "one".to_string()
+            },
+            StreamEvent::Token {
+                content: "two".to_string()
+            },
+            StreamEvent::Usage(StreamUsage::default()),
+            StreamEvent::Done {
+                response: serde_json::json!("done")
+            },
+        ]
+    );
+
+    // 途中で失敗すると、それまでのトークンのあとに error で終わる
+    let events = stream_events(system.send_request_streaming(request("Broken")).await?)
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(
+        events[0],
+        StreamEvent::Token {
+            content: "one".to_string()
+        }
+    );
+    assert!(
+        matches!(events.last(), Some(StreamEvent::Error { message }) if message.contains("broken")),
+        "{:?}",
+        events
+    );
+    assert!(
+        !events
+            .iter()
+            .any(|event| matches!(event, StreamEvent::Done { .. }))
+    );
+    Ok(())
+}
+
+/// Resident set size of the test process, where the platform exposes it
+fn resident_memory_bytes() -> Option<usize> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: usize = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some(pages * 4096)
+}
+
+async fn create_started_system() -> SystemResult<System> {
+    let (system_config, secret_config) = setup_non_api_config();
+    let mut system = System::new(&system_config, &secret_config).await;
+    let root = system
+        .parse_dsl(
+            r#"
+            micro Echo {
+                answer {
+                    on request Echo(message: String) -> Result<String, Error> {
+                        return Ok(message)
+                    }
+                }
+            }
+        "#,
+        )
+        .await?;
+    system.initialize(root).await?;
+    system.start().await?;
+    let _ = system.subscribe_events(vec![EventType::Tick]).await?;
+    Ok(system)
+}
+
+async fn wait_for_alive_tasks(expected: usize) -> usize {
+    let metrics = tokio::runtime::Handle::current().metrics();
+    for _ in 0..100 {
+        if metrics.num_alive_tasks() <= expected {
+            break;
+        }
+        sleep(Duration::from_millis(20)).await;
+    }
+    metrics.num_alive_tasks()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_dropped_systems_release_background_tasks() -> SystemResult<()> {
+    // 初回生成時の遅延初期化をベースラインから除く
+    drop(create_started_system().await?);
+    let baseline_tasks = wait_for_alive_tasks(0).await;
+    let baseline_memory = resident_memory_bytes();
+
+    for _ in 0..50 {
+        let system = create_started_system().await?;
+        assert!(system.debug_task_count() > 0);
+        drop(system);
+    }
+
+    assert_eq!(wait_for_alive_tasks(baseline_tasks).await, baseline_tasks);
+    if let (Some(baseline), Some(current)) = (baseline_memory, resident_memory_bytes()) {
+        assert!(
+            current < baseline + 64 * 1024 * 1024,
+            "memory grew from {} to {} bytes",
+            baseline,
+            current
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_emergency_shutdown_stops_background_tasks() -> SystemResult<()> {
+    let system = create_started_system().await?;
+    assert!(system.debug_task_count() > 0);
+
+    system.emergency_shutdown().await?;
+    assert_eq!(system.debug_task_count(), 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_list_agents_by_labels() -> SystemResult<()> {
+    let (system_config, secret_config) = setup_non_api_config();
+    let mut system = System::new(&system_config, &secret_config).await;
+    let root = system.parse_dsl("micro Placeholder {}").await?;
+    system.initialize(root).await?;
+
+    let agents = [
+        ("PaymentsProd", "payments", "prod"),
+        ("PaymentsDev", "payments", "dev"),
+        ("SearchProd", "search", "prod"),
+    ];
+    for (name, team, environment) in agents {
+        let ast = MicroAgentDef {
+            name: name.to_string(),
+            labels: HashMap::from([
+                ("team".to_string(), team.to_string()),
+                ("env".to_string(), environment.to_string()),
+            ]),
+            ..Default::default()
+        };
+        system.register_agent_ast(name, &ast).await?;
+        system.register_agent(name).await?;
+    }
+
+    let names = |statuses: Vec<kairei_core::system::AgentStatus>| {
+        let mut names = statuses.into_iter().map(|s| s.name).collect::<Vec<_>>();
+        names.sort();
+        names
+    };
+    let selector = HashMap::from([("team".to_string(), "payments".to_string())]);
+    assert_eq!(
+        names(system.list_agents_by_labels(&selector).await?),
+        vec!["PaymentsDev", "PaymentsProd"]
+    );
+    let selector = HashMap::from([
+        ("team".to_string(), "payments".to_string()),
+        ("env".to_string(), "prod".to_string()),
+    ]);
+    assert_eq!(
+        names(system.list_agents_by_labels(&selector).await?),
+        vec!["PaymentsProd"]
+    );
+    assert_eq!(
+        system.get_agent_labels("SearchProd").await?.get("team"),
+        Some(&"search".to_string())
+    );
+
+    let invalid = MicroAgentDef {
+        name: "Invalid".to_string(),
+        labels: HashMap::from([("team".to_string(), " ".to_string())]),
+        ..Default::default()
+    };
+    assert!(matches!(
+        system.register_agent_ast("Invalid", &invalid).await,
+        Err(kairei_core::system::SystemError::InvalidLabel(_))
+    ));
+    Ok(())
+}
+
+async fn system_with_workers() -> SystemResult<System> {
+    let (system_config, secret_config) = setup_non_api_config();
+    let mut system = System::new(&system_config, &secret_config).await;
+    let root = system.parse_dsl("micro Placeholder {}").await?;
+    system.initialize(root).await?;
+    for name in (0..5).flat_map(|i| [format!("worker-{}", i), format!("reporter-{}", i)]) {
+        let ast = MicroAgentDef {
+            name: name.clone(),
+            ..Default::default()
+        };
+        system.register_agent_ast(&name, &ast).await?;
+        system.register_agent(&name).await?;
+    }
+    Ok(system)
+}
+
+async fn is_running(system: &System, agent: &str) -> bool {
+    system.agent_registry().read().await.is_agent_running(agent)
+}
+
+#[tokio::test]
+async fn test_agent_batch_glob_selector() -> SystemResult<()> {
+    let system = system_with_workers().await?;
+
+    let batch = AgentBatch::new(
+        BatchOperation::Start,
+        AgentSelector::Glob("worker-*".to_string()),
+    );
+    let report = system.run_agent_batch(&batch).await?;
+    assert_eq!(
+        report.summary,
+        BatchSummary {
+            selected: 5,
+            succeeded: 5,
+            ..Default::default()
+        }
+    );
+    let agents: Vec<&str> = report.results.iter().map(|r| r.agent.as_str()).collect();
+    assert_eq!(
+        agents,
+        vec!["worker-0", "worker-1", "worker-2", "worker-3", "worker-4"]
+    );
+    assert!(is_running(&system, "worker-3").await);
+    assert!(!is_running(&system, "reporter-3").await);
+
+    // 実行中のエージェントを起動しても何もしない
+    let batch = AgentBatch::new(
+        BatchOperation::Start,
+        AgentSelector::Status(RunStatus::Running),
+    );
+    let report = system.run_agent_batch(&batch).await?;
+    assert_eq!(report.summary.selected, 5);
+    assert_eq!(report.summary.skipped, 5);
+    assert!(matches!(
+        &report.results[0].outcome,
+        BatchOutcome::Skipped { reason } if reason == "already running"
+    ));
+
+    assert!(matches!(
+        system
+            .run_agent_batch(&AgentBatch::new(
+                BatchOperation::Stop,
+                AgentSelector::Glob("worker-[".to_string()),
+            ))
+            .await,
+        Err(SystemError::InvalidBatch(_))
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_agent_batch_partial_failure() -> SystemResult<()> {
+    let system = system_with_workers().await?;
+    system.start_agent("worker-0").await?;
+    system.start_agent("worker-1").await?;
+
+    let names = ["worker-0", "ghost", "worker-1", "reporter-0"];
+    let batch = AgentBatch::new(
+        BatchOperation::Stop,
+        AgentSelector::Names(names.iter().map(|name| name.to_string()).collect()),
+    );
+    let report = system.run_agent_batch(&batch).await?;
+    assert_eq!(
+        report.summary,
+        BatchSummary {
+            selected: 4,
+            succeeded: 2,
+            skipped: 1,
+            failed: 1,
+            rolled_back: 0,
+        }
+    );
+    let outcomes: Vec<&BatchOutcome> = report.results.iter().map(|r| &r.outcome).collect();
+    assert_eq!(outcomes[0], &BatchOutcome::Succeeded);
+    assert!(matches!(outcomes[1], BatchOutcome::Failed { error } if error.contains("ghost")));
+    assert_eq!(outcomes[2], &BatchOutcome::Succeeded);
+    assert!(matches!(outcomes[3], BatchOutcome::Skipped { .. }));
+    assert!(!is_running(&system, "worker-0").await);
+    assert!(!is_running(&system, "worker-1").await);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_atomic_agent_batch_rolls_back_stop() -> SystemResult<()> {
+    let system = system_with_workers().await?;
+    system.start_agent("worker-0").await?;
+    system.start_agent("worker-1").await?;
+
+    // 1 つずつ操作して、失敗の前に 2 つが停止するようにする
+    let names = ["worker-0", "worker-1", "ghost", "worker-2"];
+    let batch = AgentBatch::new(
+        BatchOperation::Stop,
+        AgentSelector::Names(names.iter().map(|name| name.to_string()).collect()),
+    )
+    .with_parallelism(1)
+    .with_atomic(true);
+    let report = system.run_agent_batch(&batch).await?;
+    let outcomes: Vec<&BatchOutcome> = report.results.iter().map(|r| &r.outcome).collect();
+    assert_eq!(outcomes[0], &BatchOutcome::RolledBack);
+    assert_eq!(outcomes[1], &BatchOutcome::RolledBack);
+    assert!(matches!(outcomes[2], BatchOutcome::Failed { .. }));
+    assert!(matches!(outcomes[3], BatchOutcome::Skipped { .. }));
+    assert_eq!(report.summary.rolled_back, 2);
+    assert!(is_running(&system, "worker-0").await);
+    assert!(is_running(&system, "worker-1").await);
+
+    // 再読み込みは取り消せないので原子的には実行できない
+    let reload = AgentBatch::new(
+        BatchOperation::Reload,
+        AgentSelector::Glob("worker-*".to_string()),
+    )
+    .with_atomic(true);
+    assert!(matches!(
+        system.run_agent_batch(&reload).await,
+        Err(SystemError::InvalidBatch(_))
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_handler_metadata() -> SystemResult<()> {
+    let (system_config, secret_config) = setup_non_api_config();
+    let mut system = System::new(&system_config, &secret_config).await;
+    let root = system
+        .parse_dsl(
+            r#"
+            micro Auditor {
+                observe {
+                    on Ping {
+                        emit Seen(kind: event.event_type, sequence: event.parameters.sequence)
+                    }
+                }
+                answer {
+                    on request WhoAmI() -> Result<String, Error> {
+                        if request.requester == "admin" {
+                            return Ok(request.request_id)
+                        }
+                        return Ok("guest")
+                    }
+                }
+            }
+        "#,
+        )
+        .await?;
+    system.initialize(root).await?;
+    system.start().await?;
+    sleep(Duration::from_millis(100)).await;
+
+    let who_am_i = |requester: &str, request_id: &str| {
+        Event::request_builder()
+            .request_type("WhoAmI")
+            .requester(requester)
+            .responder("Auditor")
+            .request_id(request_id)
+            .build()
+            .unwrap()
+    };
+    assert_eq!(
+        system.send_request(who_am_i("admin", "audit-1")).await?,
+        kairei_core::event_bus::Value::String("audit-1".to_string())
+    );
+    assert_eq!(
+        system.send_request(who_am_i("someone", "audit-2")).await?,
+        kairei_core::event_bus::Value::String("guest".to_string())
+    );
+
+    let mut seen = system
+        .subscribe_events(vec![EventType::Custom("Seen".to_string())])
+        .await?;
+    system
+        .send_event(Event {
+            event_type: EventType::Custom("Ping".to_string()),
+            parameters: HashMap::from([(
+                "sequence".to_string(),
+                kairei_core::event_bus::Value::Integer(7),
+            )]),
+            ..Default::default()
+        })
+        .await?;
+    let seen = tokio::time::timeout(Duration::from_secs(1), seen.recv())
+        .await
+        .expect("observe handler should emit Seen")
+        .unwrap();
+    assert_eq!(
+        seen.parameters.get("kind"),
+        Some(&kairei_core::event_bus::Value::String("Ping".to_string()))
+    );
+    assert_eq!(
+        seen.parameters.get("sequence"),
+        Some(&kairei_core::event_bus::Value::Integer(7))
+    );
+    Ok(())
+}
+
+const CONFIGURED_AGENT_DSL: &str = r#"
+    micro Search {
+        config {
+            region: String,
+            max_results: Int = 10
+        }
+        answer {
+            on request Where() -> Result<String, Error> {
+                return Ok(config.region)
+            }
+            on request Limit() -> Result<Int, Error> {
+                return Ok(config.max_results)
+            }
+        }
+    }
+"#;
+
+fn with_agent_config(values: serde_json::Value) -> (SystemConfig, SecretConfig) {
+    let (mut system_config, secret_config) = setup_non_api_config();
+    system_config.agent_configs.insert(
+        "Search".to_string(),
+        AgentConfigValues {
+            values: serde_json::from_value(values).unwrap(),
+            hot_reload: true,
+        },
+    );
+    (system_config, secret_config)
+}
+
+#[tokio::test]
+async fn test_agent_config_values() -> SystemResult<()> {
+    let (system_config, secret_config) = with_agent_config(serde_json::json!({
+        "region": "eu-west-1"
+    }));
+    let mut system = System::new(&system_config, &secret_config).await;
+    let root = system.parse_dsl(CONFIGURED_AGENT_DSL).await?;
+    system.initialize(root).await?;
+    system.start().await?;
+    sleep(Duration::from_millis(100)).await;
+
+    let ask = |request_type: &str| {
+        Event::request_builder()
+            .request_type(request_type)
+            .requester("test")
+            .responder("Search")
+            .request_id(&uuid::Uuid::new_v4().to_string())
+            .build()
+            .unwrap()
+    };
+    assert_eq!(
+        system.send_request(ask("Where")).await?,
+        kairei_core::event_bus::Value::String("eu-west-1".to_string())
+    );
+    assert_eq!(
+        system.send_request(ask("Limit")).await?,
+        kairei_core::event_bus::Value::Integer(10)
+    );
+
+    // hot_reload の有効なエージェントは実行中に設定を差し替えられる
+    system
+        .update_agent_config(
+            "Search",
+            HashMap::from([("region".to_string(), serde_json::json!("us-east-1"))]),
+        )
+        .await?;
+    assert_eq!(
+        system.send_request(ask("Where")).await?,
+        kairei_core::event_bus::Value::String("us-east-1".to_string())
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_agent_config_validated_at_initialize() -> SystemResult<()> {
+    let (system_config, secret_config) = with_agent_config(serde_json::json!({}));
+    let mut system = System::new(&system_config, &secret_config).await;
+    let root = system.parse_dsl(CONFIGURED_AGENT_DSL).await?;
+    let error = system.initialize(root).await.unwrap_err().to_string();
+    assert!(error.contains("Agent Search: config `region` is required"));
+
+    let (system_config, secret_config) = with_agent_config(serde_json::json!({
+        "region": "eu-west-1",
+        "max_results": "ten"
+    }));
+    let mut system = System::new(&system_config, &secret_config).await;
+    let root = system.parse_dsl(CONFIGURED_AGENT_DSL).await?;
+    let error = system.initialize(root).await.unwrap_err().to_string();
+    assert!(error.contains("Agent Search: config `max_results` expects Int"));
+    Ok(())
+}
+
+const CONSTRAINED_AGENT_DSL: &str = r#"
+    micro TripPlanner {
+        answer {
+            on request plan(city: String where len(city) > 0, days: Int where days in 1..30) -> Result<String, Error> {
+                return Ok(city)
+            }
+        }
+    }
+"#;
+
+#[tokio::test]
+async fn test_parameter_constraints() -> SystemResult<()> {
+    let (system_config, secret_config) = setup_non_api_config();
+    let mut system = System::new(&system_config, &secret_config).await;
+    let root = system.parse_dsl(CONSTRAINED_AGENT_DSL).await?;
+    system.initialize(root).await?;
+    system.start().await?;
+    sleep(Duration::from_millis(100)).await;
+
+    let plan = |city: &str, days: i64| {
+        Event::request_builder()
+            .request_type("plan")
+            .requester("test")
+            .responder("TripPlanner")
+            .request_id(&uuid::Uuid::new_v4().to_string())
+            .parameter(
+                "city",
+                &kairei_core::event_bus::Value::String(city.to_string()),
+            )
+            .parameter("days", &kairei_core::event_bus::Value::Integer(days))
+            .build()
+            .unwrap()
+    };
+    let violations = |result: SystemResult<kairei_core::event_bus::Value>| match result {
+        Err(SystemError::ValidationFailed { violations, .. }) => violations,
+        other => panic!("expected ValidationFailed, got {:?}", other),
+    };
+    let violation = |parameter: &str, constraint: &str| ConstraintViolation {
+        parameter: parameter.to_string(),
+        constraint: constraint.to_string(),
+    };
+
+    // 範囲の両端を含む
+    assert_eq!(
+        system.send_request(plan("Kyoto", 30)).await?,
+        kairei_core::event_bus::Value::String("Kyoto".to_string())
+    );
+    assert_eq!(
+        violations(system.send_request(plan("Kyoto", 31)).await),
+        vec![violation("days", "days in 1..30")]
+    );
+    // 違反はすべて一度に返す
+    let result = system.send_request(plan("", 0)).await;
+    assert!(
+        result
+            .as_ref()
+            .unwrap_err()
+            .to_string()
+            .contains("city violates `len(city) > 0`, days violates `days in 1..30`")
+    );
+    assert_eq!(
+        violations(result),
+        vec![
+            violation("city", "len(city) > 0"),
+            violation("days", "days in 1..30")
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ill_typed_parameter_constraint_rejected() {
+    let (system_config, secret_config) = setup_non_api_config();
+    let system = System::new(&system_config, &secret_config).await;
+    let error = system
+        .parse_dsl(&CONSTRAINED_AGENT_DSL.replace("len(city) > 0", "len(city)"))
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("expected Boolean, found Int"));
+
+    let error = system
+        .parse_dsl(&CONSTRAINED_AGENT_DSL.replace("len(city) > 0", "days > 0"))
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains(
+        "Constraint of parameter 'city' may only use 'city' and constants, found 'days'"
+    ));
+}
+
+const EVENT_REFERENCES_DSL: &str = r#"
+    world Town {
+        events {
+            Alarm(level: Int)
+        }
+    }
+
+    micro Clock {
+        state {
+            hour: Int = 0;
+        }
+        observe {
+            on Tick {
+                hour = hour + 1
+            }
+            on StateUpdated.Clock.hour {
+                emit Alarm(level: 1)
+            }
+        }
+        react {
+            on Alarm(level: Int) {
+                hour = 0
+            }
+        }
+    }
+"#;
+
+async fn initialize_with_event_validation(
+    dsl: &str,
+    mode: EventValidationMode,
+) -> SystemResult<()> {
+    let (mut system_config, secret_config) = setup_non_api_config();
+    system_config.event_validation = mode;
+    let mut system = System::new(&system_config, &secret_config).await;
+    let root = system.parse_dsl(dsl).await?;
+    system.initialize(root).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_registered_event_references_accepted() -> SystemResult<()> {
+    // Tick、宣言済みの状態変数の StateUpdated、world のイベントは登録済み
+    initialize_with_event_validation(EVENT_REFERENCES_DSL, EventValidationMode::Strict).await?;
+    // 未宣言のカスタムイベントも許可する
+    let dsl = EVENT_REFERENCES_DSL.replace("on Alarm(level: Int)", "on Unknown(level: Int)");
+    initialize_with_event_validation(&dsl, EventValidationMode::Strict).await
+}
+
+#[tokio::test]
+async fn test_unregistered_event_reference() -> SystemResult<()> {
+    let dsl = EVENT_REFERENCES_DSL.replace("StateUpdated.Clock.hour", "StateUpdated.Clock.minute");
+    match initialize_with_event_validation(&dsl, EventValidationMode::Strict).await {
+        Err(SystemError::UnregisteredEventTypes { references }) => {
+            assert_eq!(
+                references,
+                vec!["Clock observes StateUpdated.Clock.minute".to_string()]
+            );
+        }
+        other => panic!("expected UnregisteredEventTypes, got {:?}", other),
+    }
+
+    // lenient では警告のみで初期化を続ける
+    initialize_with_event_validation(&dsl, EventValidationMode::Lenient).await
+}
+
+#[tokio::test]
+async fn test_request_profile() -> SystemResult<()> {
+    let (system_config, secret_config) = setup_non_api_config();
+    let mut system = System::new(&system_config, &secret_config).await;
+    let root = system
+        .parse_dsl(
+            r#"
+            micro Advisor {
+                answer {
+                    on request Advise(topic: String) -> Result<String, Error> {
+                        advice = think("Which type of ${topic} fits?")
+                        return advice
+                    }
+                }
+            }
+            "#,
+        )
+        .await?;
+    system.initialize(root).await?;
+    system.start().await?;
+    sleep(Duration::from_millis(100)).await;
+
+    let advise = || {
+        Event::request_builder()
+            .request_type("Advise")
+            .requester("test")
+            .responder("Advisor")
+            .request_id(&uuid::Uuid::new_v4().to_string())
+            .parameter(
+                "topic",
+                &kairei_core::event_bus::Value::String("tea".to_string()),
+            )
+    };
+
+    // 求めなければプロファイルは付かない
+    let (_, profile) = system
+        .send_request_with_profile(advise().build().unwrap())
+        .await?;
+    assert_eq!(profile, None);
+
+    let (value, profile) = system
+        .send_request_with_profile(advise().profile().build().unwrap())
+        .await?;
+    // think の結果は output を持つマップ
+    assert_eq!(
+        value,
+        kairei_core::event_bus::Value::Map(HashMap::from([(
+            "output".to_string(),
+            kairei_core::event_bus::Value::String("simple_expert".to_string())
+        )]))
+    );
+    let profile = profile.expect("profile requested");
+    let names = |sections: &[ProfileSection]| -> Vec<String> {
+        sections
+            .iter()
+            .map(|section| section.name.clone())
+            .collect()
+    };
+    assert_eq!(
+        names(&profile.sections),
+        vec![
+            "dispatch_wait",
+            "statement 1 (assignment)",
+            "statement 2 (return)",
+            "postprocess",
+            "serialization"
+        ]
+    );
+    let sum: f64 = profile.sections.iter().map(|s| s.duration_ms).sum();
+    assert!(sum <= profile.total_ms && profile.total_ms - sum < 50.0);
+
+    // think は組み立て (プラグインごと)、通信、後処理に分かれる
+    let provider = &profile.sections[1].children[0];
+    assert_eq!(provider.name, "provider default");
+    assert_eq!(
+        names(&provider.children),
+        vec!["assembly", "network", "processing"]
+    );
+    assert_eq!(
+        names(&provider.children[0].children),
+        vec![
+            "plugin GeneralPrompt",
+            "plugin RequestContext",
+            "plugin PolicyPrompt",
+            "plugin JsonMode"
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_llm_metrics_by_model() -> SystemResult<()> {
+    let (mut system_config, mut secret_config) = setup_non_api_config();
+    let providers = &mut system_config.provider_configs.providers;
+    let mut small = providers["default"].clone();
+    small.name = "small".to_string();
+    small.common_config.model = "mock-small".to_string();
+    providers.insert("small".to_string(), small);
+    providers.get_mut("default").unwrap().common_config.model = "mock-large".to_string();
+    secret_config
+        .providers
+        .insert("small".to_string(), ProviderSecretConfig::default());
+
+    let mut system = System::new(&system_config, &secret_config).await;
+    let root = system
+        .parse_dsl(
+            r#"
+            micro Advisor {
+                answer {
+                    on request AskLarge() -> Result<String, Error> {
+                        reply = think("Which type is large?")
+                        return reply
+                    }
+                    on request AskSmall() -> Result<String, Error> {
+                        reply = think("Which type is small?") with { provider: "small" }
+                        return reply
+                    }
+                    on request AskUnlisted() -> Result<String, Error> {
+                        reply = think("Which type is unlisted?") with { provider: "small", model: "mock-unlisted" }
+                        return reply
+                    }
+                }
+            }
+            "#,
+        )
+        .await?;
+    system.initialize(root).await?;
+    system.start().await?;
+    sleep(Duration::from_millis(100)).await;
+
+    for (request_type, times) in [("AskLarge", 2), ("AskSmall", 3), ("AskUnlisted", 1)] {
+        for _ in 0..times {
+            let request = Event::request_builder()
+                .request_type(request_type)
+                .requester("test")
+                .responder("Advisor")
+                .request_id(&uuid::Uuid::new_v4().to_string())
+                .build()
+                .unwrap();
+            system.send_request(request).await?;
+        }
+    }
+
+    // 設定されたモデルごとに系列が分かれ、設定にないモデルは other にまとまる
+    let metrics = system.llm_metrics().await;
+    let counts: Vec<(String, String, u64)> = metrics
+        .series()
+        .into_iter()
+        .map(|series| (series.provider, series.model, series.latency.count()))
+        .collect();
+    assert_eq!(
+        counts,
+        vec![
+            ("default".to_string(), "mock-large".to_string(), 2),
+            ("default".to_string(), "other".to_string(), 0),
+            ("small".to_string(), "mock-small".to_string(), 3),
+            ("small".to_string(), "other".to_string(), 1),
+        ]
+    );
+
+    let text = metrics.render();
+    assert!(text.contains(
+        r#"kairei_llm_request_duration_seconds_count{provider="default",model="mock-large"} 2"#
+    ));
+    assert!(text.contains(
+        r#"kairei_llm_request_duration_seconds_count{provider="small",model="mock-small"} 3"#
+    ));
+    assert!(!text.contains("mock-unlisted"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_answer_postprocess_pipeline() -> SystemResult<()> {
+    let (system_config, secret_config) = setup_non_api_config();
+    let mut system = System::new(&system_config, &secret_config).await;
+    let root = system
+        .parse_dsl(
+            r#"
+            micro Extractor {
+                answer {
+                    on request Extract(raw: String) -> Result<String, Error> with {
+                        postprocess: ["strip_fences", { json_field: "reply.text" }, "trim"]
+                    } {
+                        return Ok(raw)
+                    }
+                }
+            }
+        "#,
+        )
+        .await?;
+    system.initialize(root).await?;
+    system.start().await?;
+    sleep(Duration::from_millis(100)).await;
+
+    let extract = |raw: &str, request_id: &str| {
+        Event::request_builder()
+            .request_type("Extract")
+            .requester("test")
+            .responder("Extractor")
+            .request_id(request_id)
+            .parameter(
+                "raw",
+                &kairei_core::event_bus::Value::String(raw.to_string()),
+            )
+            .build()
+            .unwrap()
+    };
+    let fenced = "```json\n{\"reply\": {\"text\": \"  hello  \"}}\n```";
+    assert_eq!(
+        system.send_request(extract(fenced, "extract-1")).await?,
+        kairei_core::event_bus::Value::String("hello".to_string())
+    );
+
+    // 失敗した変換は、どの段で何が起きたかをエラー応答に残す
+    let missing = "```json\n{\"reply\": {}}\n```";
+    let kairei_core::event_bus::Value::String(error) =
+        system.send_request(extract(missing, "extract-2")).await?
+    else {
+        panic!("a failed step should respond with an error message");
+    };
+    assert!(
+        error.contains("step 2 (json_field `reply.text`) failed: field `reply.text` not found"),
+        "{}",
+        error
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_event_lineage_three_hops() -> SystemResult<()> {
+    let (system_config, secret_config) = setup_non_api_config();
+    let mut system = System::new(&system_config, &secret_config).await;
+    let root = system
+        .parse_dsl(
+            r#"
+            micro Starter {
+                react {
+                    on Kickoff {
+                        emit Forwarded()
+                    }
+                }
+            }
+            micro Relay {
+                react {
+                    on Forwarded {
+                        result = request Work to Worker()
+                        emit Finished(result: result)
+                    }
+                }
+            }
+            micro Worker {
+                answer {
+                    on request Work() -> Result<String, Error> {
+                        return Ok("done")
+                    }
+                }
+            }
+        "#,
+        )
+        .await?;
+    system.initialize(root).await?;
+    system.start().await?;
+    sleep(Duration::from_millis(100)).await;
+
+    let mut finished = system
+        .subscribe_events(vec![EventType::Custom("Finished".to_string())])
+        .await?;
+    system
+        .send_event(Event {
+            event_type: EventType::Custom("Kickoff".to_string()),
+            event_id: Some("kickoff-1".to_string()),
+            ..Default::default()
+        })
+        .await?;
+    let finished = tokio::time::timeout(Duration::from_secs(2), finished.recv())
+        .await
+        .expect("Relay should emit Finished")
+        .unwrap();
+    assert_eq!(finished.root_event_id.as_deref(), Some("kickoff-1"));
+
+    // どのイベントからでも同じ木が得られる
+    let finished_id = finished.event_id.clone().unwrap();
+    let tree = system.get_event_lineage(&finished_id, 8).unwrap();
+    assert_eq!(tree, system.get_event_lineage("kickoff-1", 8).unwrap());
+
+    // Kickoff -> Forwarded -> (request Work -> response, Finished)
+    assert_eq!(tree.record.event_id, "kickoff-1");
+    assert_eq!(tree.children.len(), 1);
+    let forwarded = &tree.children[0];
+    assert_eq!(forwarded.record.event_type, "Forwarded");
+    let kinds: Vec<_> = forwarded
+        .children
+        .iter()
+        .map(|child| {
+            (
+                child.record.category.as_str(),
+                child.record.event_type.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(kinds, vec![("request", "Work"), ("agent", "Finished")]);
+    let request = &forwarded.children[0];
+    assert_eq!(request.children.len(), 1);
+    let response = &request.children[0];
+    assert_eq!(response.record.category, "response");
+    assert_eq!(forwarded.children[1].record.event_id, finished_id);
+
+    // 実行順: Kickoff < Forwarded < Request < Response < Finished
+    let sequences = [
+        tree.record.sequence,
+        forwarded.record.sequence,
+        request.record.sequence,
+        response.record.sequence,
+        forwarded.children[1].record.sequence,
+    ];
+    assert!(sequences.windows(2).all(|w| w[0] < w[1]), "{:?}", sequences);
+    for node in [forwarded, request, response] {
+        assert_eq!(node.record.root_event_id, "kickoff-1");
+    }
+
+    // 深さ制限
+    let shallow = system.get_event_lineage("kickoff-1", 1).unwrap();
+    assert!(shallow.children[0].children.is_empty());
+    assert!(system.get_event_lineage("unknown", 8).is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_idle_agent_evicted_and_rehydrated_with_state() -> SystemResult<()> {
+    let (mut system_config, secret_config) = setup_non_api_config();
+    system_config.idle_eviction = IdleEvictionConfig {
+        idle_timeout: Some(Duration::from_secs(60)),
+        check_interval: Duration::from_millis(10),
+        base_dir: None,
+    };
+    let clock = MockClock::new();
+    let mut system = System::new(&system_config, &secret_config)
+        .await
+        .with_clock(Arc::new(clock.clone()));
+    let root = system
+        .parse_dsl(
+            r#"
+            micro Counter {
+                state {
+                    count: Int = 0;
+                }
+                react {
+                    on Bump {
+                        count = count + 1
+                    }
+                }
+                answer {
+                    on request GetCount() -> Result<Int, Error> {
+                        return Ok(count)
+                    }
+                }
+            }
+        "#,
+        )
+        .await?;
+    system.initialize(root).await?;
+    system.start().await?;
+    sleep(Duration::from_millis(100)).await;
+
+    for _ in 0..2 {
+        system
+            .send_event(Event {
+                event_type: EventType::Custom("Bump".to_string()),
+                ..Default::default()
+            })
+            .await?;
+    }
+    sleep(Duration::from_millis(100)).await;
+    assert_eq!(
+        system.get_agent_state("Counter", "count").await?,
+        kairei_core::eval::expression::Value::Integer(2)
+    );
+
+    // タイムアウト前は退避されない
+    clock.advance(Duration::from_secs(59));
+    sleep(Duration::from_millis(100)).await;
+    assert!(system.get_agent_status("Counter").await.is_ok());
+
+    clock.advance(Duration::from_secs(1));
+    sleep(Duration::from_millis(200)).await;
+    assert!(
+        system.get_agent_status("Counter").await.is_err(),
+        "idle agent should be evicted from the registry"
+    );
+
+    // 次のリクエストで状態を保ったまま復元される
+    let get_count = Event::request_builder()
+        .request_type("GetCount")
+        .requester("test")
+        .responder("Counter")
+        .request_id("count-1")
+        .build()
+        .unwrap();
+    assert_eq!(
+        system.send_request(get_count).await?,
+        kairei_core::event_bus::Value::Integer(2)
+    );
+    assert_eq!(
+        system.get_agent_status("Counter").await?.state,
+        EventType::AgentStarted.to_string()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_orchestrator_routes_by_catalog() -> SystemResult<()> {
+    let (mut system_config, secret_config) = setup_non_api_config();
+    let provider = system_config
+        .provider_configs
+        .providers
+        .get_mut("default")
+        .unwrap();
+    // SimpleExpert はプロンプトに含まれるパターンに答える。カタログに載った行があるときだけ委譲先を返す
+    provider.provider_specific = HashMap::from([(
+        "- WeatherAgent: GetForecast(city: String) -> Result<String, Error>".to_string(),
+        serde_json::Value::String("WeatherAgent\n".to_string()),
+    )]);
+    provider.plugin_configs.insert(
+        "catalog".to_string(),
+        PluginConfig::Catalog(CatalogConfig::default()),
+    );
+    let mut system = System::new(&system_config, &secret_config).await;
+    let root = system
+        .parse_dsl(
+            r#"
+            micro WeatherAgent {
+                answer {
+                    on request GetForecast(city: String) -> Result<String, Error> {
+                        return Ok("sunny in " + city)
+                    }
+                }
+            }
+            micro NewsAgent {
+                answer {
+                    on request GetHeadlines(topic: String) -> Result<String, Error> {
+                        return Ok("no news about " + topic)
+                    }
+                }
+            }
+            micro Orchestrator {
+                answer {
+                    // 委譲先の応答の型は実行時まで分からない
+                    on request Forecast(city: String) -> Result<Any, Error> {
+                        delegate = think("Which agent answers GetForecast? Reply with its name only.")
+                        forecast = request GetForecast to delegate(city: city)
+                        return forecast
+                    }
+                }
+            }
+        "#,
+        )
+        .await?;
+    system.initialize(root).await?;
+    system.start().await?;
+    sleep(Duration::from_millis(100)).await;
+
+    let catalog = system.catalog();
+    for agent in ["NewsAgent", "Orchestrator", "WeatherAgent"] {
+        assert!(catalog.iter().any(|entry| entry.name == agent), "{}", agent);
+    }
+    let news = catalog
+        .iter()
+        .find(|entry| entry.name == "NewsAgent")
+        .unwrap();
+    assert_eq!(
+        news.requests[0].to_string(),
+        "GetHeadlines(topic: String) -> Result<String, Error>"
+    );
+
+    let forecast = Event::request_builder()
+        .request_type("Forecast")
+        .requester("test")
+        .responder("Orchestrator")
+        .request_id("forecast-1")
+        .parameter(
+            "city",
+            &kairei_core::event_bus::Value::String("Tokyo".to_string()),
+        )
+        .build()
+        .unwrap();
+    assert_eq!(
+        system.send_request(forecast).await?,
+        kairei_core::event_bus::Value::String("sunny in Tokyo".to_string())
+    );
+
+    // スケールしたインスタンスもカタログに載り、スケールダウンで外れる
+    let instances = system.scale_up("NewsAgent", 1, HashMap::new()).await?;
+    assert!(
+        system
+            .catalog()
+            .iter()
+            .any(|entry| entry.name == instances[0])
+    );
+    system.scale_down("NewsAgent", 2, HashMap::new()).await?;
+    assert!(
+        !system
+            .catalog()
+            .iter()
+            .any(|entry| entry.name.starts_with("NewsAgent"))
+    );
+    Ok(())
+}
+
+/// Relays TCP connections to `target`, and can cut all of them at once
+struct Relay {
+    addr: String,
+    connections: Arc<std::sync::Mutex<Vec<tokio::task::JoinHandle<()>>>>,
+}
+
+impl Relay {
+    async fn start(target: String) -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let connections = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let relayed = connections.clone();
+        tokio::spawn(async move {
+            while let Ok((mut inbound, _)) = listener.accept().await {
+                let target = target.clone();
+                relayed.lock().unwrap().push(tokio::spawn(async move {
+                    if let Ok(mut outbound) = tokio::net::TcpStream::connect(&target).await {
+                        let _ = tokio::io::copy_bidirectional(&mut inbound, &mut outbound).await;
+                    }
+                }));
+            }
+        });
+        Self { addr, connections }
+    }
+
+    fn cut(&self) {
+        for connection in self.connections.lock().unwrap().drain(..) {
+            connection.abort();
+        }
+    }
+}
+
+async fn wait_for_state(
+    system: &System,
+    agent: &str,
+    key: &str,
+    expected: i64,
+) -> SystemResult<kairei_core::eval::expression::Value> {
+    let expected = kairei_core::eval::expression::Value::Integer(expected);
+    for _ in 0..50 {
+        // 状態変数はエージェントが動き出してから用意されるので、それまでは待つ
+        if let Ok(value) = system.get_agent_state(agent, key).await {
+            if value == expected {
+                return Ok(value);
+            }
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    system.get_agent_state(agent, key).await
+}
+
+#[tokio::test]
+async fn test_remote_bridge_forwards_events_across_reconnect() -> SystemResult<()> {
+    let bridge_config = RemoteBridgeConfig {
+        enabled: true,
+        token: Some("shared-secret".to_string()),
+        heartbeat_interval: Duration::from_millis(100),
+        heartbeat_timeout: Duration::from_millis(500),
+        reconnect_delay: Duration::from_millis(100),
+        ..Default::default()
+    };
+    let listen_addr = std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .unwrap()
+        .to_string();
+    let relay = Relay::start(listen_addr.clone()).await;
+
+    // B は A から転送されたイベントを受け取る
+    let (mut config_b, secret_config) = setup_non_api_config();
+    config_b.native_feature_config.remote_bridge = Some(RemoteBridgeConfig {
+        listen: Some(listen_addr),
+        ..bridge_config.clone()
+    });
+    let mut system_b = System::new(&config_b, &secret_config).await;
+    let root = system_b
+        .parse_dsl(
+            r#"
+            micro Watcher {
+                state {
+                    bumps: Int = 0;
+                    ignored: Int = 0;
+                }
+                react {
+                    on Bump {
+                        bumps = bumps + 1
+                    }
+                    on Ignored {
+                        ignored = ignored + 1
+                    }
+                }
+            }
+        "#,
+        )
+        .await?;
+    system_b.initialize(root).await?;
+    system_b.start().await?;
+
+    // A は Bump だけを B に転送する
+    let (mut config_a, secret_config) = setup_non_api_config();
+    config_a.native_feature_config.remote_bridge = Some(RemoteBridgeConfig {
+        peer: Some(relay.addr.clone()),
+        event_types: vec!["Bump".to_string()],
+        ..bridge_config
+    });
+    let mut system_a = System::new(&config_a, &secret_config).await;
+    let root = system_a
+        .parse_dsl(
+            r#"
+            micro Sensor {
+                react {
+                    on Kick {
+                        emit Bump()
+                    }
+                }
+            }
+        "#,
+        )
+        .await?;
+    system_a.initialize(root).await?;
+    system_a.start().await?;
+    sleep(Duration::from_millis(100)).await;
+
+    let send = |name: &str| Event {
+        event_type: EventType::Custom(name.to_string()),
+        ..Default::default()
+    };
+    system_a.send_event(send("Kick")).await?;
+    system_a.send_event(send("Kick")).await?;
+    system_a.send_event(send("Ignored")).await?;
+    assert_eq!(
+        wait_for_state(&system_b, "Watcher", "bumps", 2).await?,
+        kairei_core::eval::expression::Value::Integer(2)
+    );
+
+    // 接続を切っている間に出たイベントも、再接続後に一度だけ届く
+    relay.cut();
+    system_a.send_event(send("Kick")).await?;
+    assert_eq!(
+        wait_for_state(&system_b, "Watcher", "bumps", 3).await?,
+        kairei_core::eval::expression::Value::Integer(3)
+    );
+    sleep(Duration::from_millis(300)).await;
+    assert_eq!(
+        system_b.get_agent_state("Watcher", "bumps").await?,
+        kairei_core::eval::expression::Value::Integer(3)
+    );
+    assert_eq!(
+        system_b.get_agent_state("Watcher", "ignored").await?,
+        kairei_core::eval::expression::Value::Integer(0)
+    );
+
+    system_a.shutdown().await?;
+    system_b.shutdown().await?;
+    Ok(())
+}
+
+const RECORDED_DSL: &str = r#"
+    micro Shopper {
+        state {
+            count: Int = 0;
+        }
+        observe {
+            on ItemAdded(name: String) {
+                count = count + 1
+                reply = think("Which type of tea is ${name}?")
+                emit ItemNoted(name: name)
+            }
+        }
+    }
+"#;
+
+/// ハンドラ実行の記録を有効にしたシステムを起動する
+async fn recording_system() -> SystemResult<System> {
+    let (mut system_config, secret_config) = setup_non_api_config();
+    system_config.handler_recording = HandlerRecordingConfig {
+        enabled: true,
+        ..Default::default()
+    };
+    let mut system = System::new(&system_config, &secret_config).await;
+    let root = system.parse_dsl(RECORDED_DSL).await?;
+    system.initialize(root).await?;
+    system.start().await?;
+    sleep(Duration::from_millis(100)).await;
+    Ok(system)
+}
+
+fn item_added(name: &str) -> Event {
+    Event {
+        event_type: EventType::Custom("ItemAdded".to_string()),
+        parameters: HashMap::from([(
+            "name".to_string(),
+            kairei_core::event_bus::Value::String(name.to_string()),
+        )]),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_handler_runs_are_recorded() -> SystemResult<()> {
+    let system = recording_system().await?;
+    system.send_event(item_added("tea")).await?;
+    wait_for_state(&system, "Shopper", "count", 1).await?;
+
+    let recordings = system.handler_recordings();
+    assert_eq!(recordings.len(), 1);
+    let recording = &recordings[0];
+    assert_eq!(recording.agent_name, "Shopper");
+    assert_eq!(
+        recording.event.event_type,
+        EventType::Custom("ItemAdded".to_string())
+    );
+    assert_eq!(
+        recording.initial_state.get("count"),
+        Some(&kairei_core::eval::expression::Value::Integer(0))
+    );
+    assert_eq!(recording.responses.len(), 1);
+    assert_eq!(recording.responses[0].output, "simple_expert");
+
+    let statements: Vec<&str> = recording
+        .steps
+        .iter()
+        .map(|step| step.statement.as_str())
+        .collect();
+    assert_eq!(statements.len(), 3);
+    assert!(
+        statements[0].starts_with("count = count + 1"),
+        "{:?}",
+        statements
+    );
+    // 状態を変えたのは 1 文目だけで、ローカル変数は含まない
+    assert_eq!(recording.steps[0].state_changes.len(), 1);
+    assert_eq!(
+        recording.steps[0].state_changes[0].after,
+        kairei_core::eval::expression::Value::Integer(1)
+    );
+    assert!(recording.steps[1].state_changes.is_empty());
+    assert_eq!(recording.steps[2].emitted_events.len(), 1);
+    assert_eq!(
+        recording.steps[2].emitted_events[0].event_type,
+        EventType::Custom("ItemNoted".to_string())
+    );
+
+    // 記録しなければ何も残らない
+    let (system_config, secret_config) = setup_non_api_config();
+    let system = System::new(&system_config, &secret_config).await;
+    assert!(system.handler_recordings().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_debug_session_steps_through_recording() -> SystemResult<()> {
+    use kairei_core::eval::expression::Value;
+
+    let system = recording_system().await?;
+    system.send_event(item_added("tea")).await?;
+    wait_for_state(&system, "Shopper", "count", 1).await?;
+    let recording = system.handler_recordings()[0].clone();
+
+    let session_id = system.debug_start(&recording.id).await?;
+    // 1 文目の前では初期状態のまま
+    assert_eq!(
+        system.debug_inspect(&session_id, "count").await?.value,
+        Value::Integer(0)
+    );
+
+    let mut steps = vec![];
+    for _ in 0..3 {
+        steps.push(system.debug_step(&session_id).await?);
+    }
+    for (step, recorded) in steps.iter().zip(&recording.steps) {
+        assert_eq!(step.statement, recorded.statement);
+        assert_eq!(step.state_changes, recorded.state_changes);
+    }
+    assert!(steps[0].variable_changes.is_empty());
+    // think は記録された出力で答える
+    assert_eq!(steps[1].variable_changes.len(), 1);
+    assert_eq!(steps[1].variable_changes[0].name, "reply");
+    assert_eq!(
+        steps[1].variable_changes[0].after,
+        Value::Map(HashMap::from([(
+            "output".to_string(),
+            Value::String("simple_expert".to_string())
+        )]))
+    );
+    assert!(steps[1].pending_events.is_empty());
+    assert_eq!(steps[2].pending_events.len(), 1);
+    assert_eq!(
+        steps[2].pending_events[0].event_type,
+        EventType::Custom("ItemNoted".to_string())
+    );
+    assert!(!steps[1].finished);
+    assert!(steps[2].finished);
+
+    // 止まった状態とローカル変数に対して式を評価する
+    assert_eq!(
+        system.debug_inspect(&session_id, "count").await?.value,
+        Value::Integer(1)
+    );
+    assert_eq!(
+        system.debug_inspect(&session_id, "name").await?.value,
+        Value::String("tea".to_string())
+    );
+    assert!(matches!(
+        system.debug_step(&session_id).await,
+        Err(SystemError::DebugSession(DebugSessionError::Finished))
+    ));
+
+    // エージェント自身の状態は変わらない
+    wait_for_state(&system, "Shopper", "count", 1).await?;
+    system.debug_end(&session_id)?;
+    assert!(matches!(
+        system.debug_step(&session_id).await,
+        Err(SystemError::DebugSession(
+            DebugSessionError::SessionNotFound(_)
+        ))
+    ));
+    assert!(matches!(
+        system.debug_start("missing").await,
+        Err(SystemError::DebugSession(
+            DebugSessionError::RecordingNotFound(_)
+        ))
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_dsl_version_compatibility() -> SystemResult<()> {
+    let (system_config, secret_config) = setup_non_api_config();
+    let system = System::new(&system_config, &secret_config).await;
+    let legacy = r#"
+        micro Counter {
+            state {
+                count: i64 = 0;
+            }
+        }
+    "#;
+    let state_type = |root: &kairei_core::ast::Root| {
+        root.micro_agent_defs[0].state.as_ref().unwrap().variables["count"]
+            .type_info
+            .to_string()
+    };
+
+    // 宣言がなければ現行バージョンとして読む
+    let root = system.parse_dsl("micro Counter {}").await?;
+    assert_eq!(root.dsl_version, None);
+    assert!(system.parse_dsl(legacy).await.is_err());
+
+    let root = system
+        .parse_dsl("kairei \"0.5\"\n\nmicro Counter {}")
+        .await?;
+    assert_eq!(root.dsl_version.as_deref(), Some("0.5"));
+
+    // 0.4 のファイルは型名が書き換えられる
+    let root = system
+        .parse_dsl(&format!("kairei \"0.4\"\n{}", legacy))
+        .await?;
+    assert_eq!(root.dsl_version.as_deref(), Some("0.4"));
+    assert_eq!(state_type(&root), "Int");
+
+    let error = system
+        .parse_dsl("kairei \"0.6\"\n\nmicro Counter {}")
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        SystemError::Ast(kairei_core::ASTError::DslVersion(_))
+    ));
+    assert!(
+        error
+            .to_string()
+            .contains("this file requires DSL 0.6, this build supports up to 0.5")
+    );
+    Ok(())
+}
+
+fn greeter_dsl(greeting: &str) -> String {
+    format!(
+        r#"
+    micro Greeter {{
+        answer {{
+            on request Greet() -> Result<String, Error> {{
+                return Ok("{}")
+            }}
+        }}
+    }}
+"#,
+        greeting
+    )
+}
+
+type ReportedEvents = Arc<std::sync::Mutex<Vec<WatchEvent>>>;
+
+/// システムを `dsl` で起動し、同じ内容のファイルを監視するウォッチャーを返す
+async fn watched_system(
+    dsl: &str,
+) -> SystemResult<(
+    Arc<System>,
+    Arc<DslWatcher>,
+    tempfile::TempDir,
+    ReportedEvents,
+)> {
+    let (system_config, secret_config) = setup_non_api_config();
+    let mut system = System::new(&system_config, &secret_config).await;
+    let root = system.parse_dsl(dsl).await?;
+    system.initialize(root).await?;
+    system.start().await?;
+    let system = Arc::new(system);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("greeter.kairei");
+    std::fs::write(&path, dsl).unwrap();
+    let events = Arc::new(std::sync::Mutex::new(vec![]));
+    let reported = events.clone();
+    let watcher = Arc::new(
+        DslWatcher::new(system.clone(), [path])
+            .with_debounce(Duration::from_millis(100))
+            .with_callback(move |event| reported.lock().unwrap().push(event.clone())),
+    );
+    assert_eq!(watcher.baseline().await, None);
+    Ok((system, watcher, dir, events))
+}
+
+async fn greet(system: &System) -> SystemResult<kairei_core::event_bus::Value> {
+    sleep(Duration::from_millis(100)).await;
+    system
+        .send_request(
+            Event::request_builder()
+                .request_type("Greet")
+                .requester("test")
+                .responder("Greeter")
+                .request_id(&uuid::Uuid::new_v4().to_string())
+                .build()
+                .unwrap(),
+        )
+        .await
+}
+
+fn greeting(text: &str) -> kairei_core::event_bus::Value {
+    kairei_core::event_bus::Value::String(text.to_string())
+}
+
+#[tokio::test]
+async fn test_dsl_watcher_reloads_changed_agent() -> SystemResult<()> {
+    let (system, watcher, dir, events) = watched_system(&greeter_dsl("hello")).await?;
+    assert_eq!(greet(&system).await?, greeting("hello"));
+
+    std::fs::write(dir.path().join("greeter.kairei"), greeter_dsl("hi")).unwrap();
+    let reloaded = WatchEvent::Reloaded(ReloadScope::Agents {
+        reloaded: vec!["Greeter".to_string()],
+        removed: vec![],
+    });
+    assert_eq!(watcher.handle_change().await, Some(reloaded.clone()));
+    assert_eq!(greet(&system).await?, greeting("hi"));
+
+    // 内容が同じなら読み込み直さない
+    assert_eq!(watcher.handle_change().await, None);
+    assert_eq!(*events.lock().unwrap(), vec![reloaded]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_dsl_watcher_keeps_running_system_on_failure() -> SystemResult<()> {
+    let (system, watcher, dir, _) = watched_system(&greeter_dsl("hello")).await?;
+    let path = dir.path().join("greeter.kairei");
+
+    std::fs::write(&path, "micro Greeter { answer {").unwrap();
+    let Some(WatchEvent::Failed { message }) = watcher.handle_change().await else {
+        panic!("a broken file should be reported");
+    };
+    assert!(!message.is_empty());
+    assert_eq!(greet(&system).await?, greeting("hello"));
+
+    // 直したファイルは失敗前の内容との差分として読み込む
+    std::fs::write(&path, greeter_dsl("fixed")).unwrap();
+    assert_eq!(
+        watcher.handle_change().await,
+        Some(WatchEvent::Reloaded(ReloadScope::Agents {
+            reloaded: vec!["Greeter".to_string()],
+            removed: vec![],
+        }))
+    );
+    assert_eq!(greet(&system).await?, greeting("fixed"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_dsl_watcher_collapses_rapid_edits() -> SystemResult<()> {
+    let (system, watcher, dir, events) = watched_system(&greeter_dsl("hello")).await?;
+    let path = dir.path().join("greeter.kairei");
+
+    let mut changes = vec![];
+    for text in ["one", "two", "three"] {
+        std::fs::write(&path, greeter_dsl(text)).unwrap();
+        let watcher = watcher.clone();
+        changes.push(tokio::spawn(async move { watcher.handle_change().await }));
+        sleep(Duration::from_millis(20)).await;
+    }
+    let mut outcomes = vec![];
+    for change in changes {
+        outcomes.push(change.await.unwrap());
+    }
+
+    // 最後の変更だけが読み込み、途中の内容は適用されない
+    assert_eq!(outcomes[0], None);
+    assert_eq!(outcomes[1], None);
+    assert!(matches!(outcomes[2], Some(WatchEvent::Reloaded(_))));
+    assert_eq!(events.lock().unwrap().len(), 1);
+    assert_eq!(greet(&system).await?, greeting("three"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_dsl_watcher_initial_load_and_removed_agents() -> SystemResult<()> {
+    let (system_config, secret_config) = setup_non_api_config();
+    let mut system = System::new(&system_config, &secret_config).await;
+    let root = system.parse_dsl("micro Idle {}").await?;
+    system.initialize(root).await?;
+    system.start().await?;
+    let system = Arc::new(system);
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("greeter.kairei");
+    std::fs::write(&path, greeter_dsl("hello")).unwrap();
+    let watcher = DslWatcher::new(system.clone(), [&path]).with_debounce(Duration::ZERO);
+
+    assert_eq!(
+        watcher.load().await,
+        Some(WatchEvent::Reloaded(ReloadScope::World))
+    );
+    assert_eq!(greet(&system).await?, greeting("hello"));
+
+    std::fs::write(&path, "micro Other {}").unwrap();
+    assert_eq!(
+        watcher.handle_change().await,
+        Some(WatchEvent::Reloaded(ReloadScope::Agents {
+            reloaded: vec!["Other".to_string()],
+            removed: vec!["Greeter".to_string()],
+        }))
+    );
+    let agents = system.list_agent_asts().await?;
+    assert!(agents.contains(&"Other".to_string()));
+    assert!(system.get_agent_status("Greeter").await.is_err());
+    Ok(())
+}
+
+const WORLD_INIT_DSL: &str = r#"
+    world Shop {
+        onInit {
+            increment("open_seats", 40)
+            increment("world_inits", 1)
+        }
+    }
+
+    micro Booking {
+        state {
+            seats_at_init: Int = 0;
+        }
+        lifecycle {
+            onInit {
+                seats_at_init = increment("open_seats", 0)
+            }
+        }
+        answer {
+            on request WorldInits() -> Result<Int, Error> {
+                return Ok(increment("world_inits", 0))
+            }
+        }
+    }
+"#;
+
+#[tokio::test]
+async fn test_world_init_runs_once_before_agents() -> SystemResult<()> {
+    let (system_config, secret_config) = setup_non_api_config();
+    let mut system = System::new(&system_config, &secret_config).await;
+    let root = system.parse_dsl(WORLD_INIT_DSL).await?;
+    system.initialize(root).await?;
+    system.start().await?;
+
+    // エージェントの onInit は world の onInit が用意した値を見る
+    assert_eq!(
+        wait_for_state(&system, "Booking", "seats_at_init", 40).await?,
+        kairei_core::eval::expression::Value::Integer(40)
+    );
+    let world_inits = Event::request_builder()
+        .request_type("WorldInits")
+        .requester("test")
+        .responder("Booking")
+        .request_id(&uuid::Uuid::new_v4().to_string())
+        .build()
+        .unwrap();
+    assert_eq!(
+        system.send_request(world_inits).await?,
+        kairei_core::event_bus::Value::Integer(1)
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_world_init_failure_aborts_initialize() -> SystemResult<()> {
+    let (system_config, secret_config) = setup_non_api_config();
+    let mut system = System::new(&system_config, &secret_config).await;
+    let root = system
+        .parse_dsl(
+            r#"
+            world Shop {
+                onInit {
+                    increment("open_seats", 40 / 0)
+                }
+            }
+        "#,
+        )
+        .await?;
+    let error = system.initialize(root).await.unwrap_err();
+    assert!(matches!(error, SystemError::WorldInit(_)), "{:?}", error);
+    assert!(error.to_string().starts_with("World onInit failed:"));
+    Ok(())
+}
+
+const CACHED_FORECAST_DSL: &str = r#"
+    micro Weather {
+        answer {
+            @cache(ttl: 10m, key: [city])
+            on request Forecast(city: String, days: Int) -> Result<String, Error> {
+                increment("forecast_runs", 1)
+                return Ok(city)
+            }
+
+            on request Runs() -> Result<Int, Error> {
+                return Ok(increment("forecast_runs", 0))
+            }
+        }
+    }
+"#;
+
+#[tokio::test]
+async fn test_cached_answer_handler() -> SystemResult<()> {
+    let (system_config, secret_config) = setup_non_api_config();
+    let clock = MockClock::new();
+    let mut system = System::new(&system_config, &secret_config)
+        .await
+        .with_clock(Arc::new(clock.clone()));
+    let root = system.parse_dsl(CACHED_FORECAST_DSL).await?;
+    let weather = root.micro_agent_defs[0].clone();
+    system.initialize(root).await?;
+    system.start().await?;
+    sleep(Duration::from_millis(100)).await;
+
+    let request = |request_type: &str, parameters: &[(&str, kairei_core::event_bus::Value)]| {
+        let mut builder = Event::request_builder()
+            .request_type(request_type)
+            .requester("test")
+            .responder("Weather")
+            .request_id(&uuid::Uuid::new_v4().to_string());
+        for (name, value) in parameters {
+            builder = builder.parameter(name, value);
+        }
+        builder.build().unwrap()
+    };
+    let string = |s: &str| kairei_core::event_bus::Value::String(s.to_string());
+    let forecast = |city: &str, days: i64| {
+        request(
+            "Forecast",
+            &[
+                ("city", string(city)),
+                ("days", kairei_core::event_bus::Value::Integer(days)),
+            ],
+        )
+    };
+    let runs = || async {
+        match system.send_request(request("Runs", &[])).await {
+            Ok(kairei_core::event_bus::Value::Integer(runs)) => runs,
+            other => panic!("unexpected Runs response: {:?}", other),
+        }
+    };
+
+    // key にない days だけが違うリクエストは同じ応答を使う
+    assert_eq!(
+        system.send_request(forecast("Kyoto", 3)).await?,
+        string("Kyoto")
+    );
+    assert_eq!(
+        system.send_request(forecast("Kyoto", 7)).await?,
+        string("Kyoto")
+    );
+    assert_eq!(runs().await, 1);
+
+    assert_eq!(
+        system.send_request(forecast("Osaka", 3)).await?,
+        string("Osaka")
+    );
+    assert_eq!(runs().await, 2);
+
+    let mut bypass = forecast("Kyoto", 3);
+    bypass.parameters.insert(
+        "no_cache".to_string(),
+        kairei_core::event_bus::Value::Boolean(true),
+    );
+    system.send_request(bypass).await?;
+    assert_eq!(runs().await, 3);
+
+    let stats = system
+        .get_agent_status("Weather")
+        .await?
+        .response_cache
+        .unwrap();
+    assert_eq!((stats.hits, stats.misses, stats.entries), (1, 2, 2));
+
+    // TTL を過ぎると再実行する
+    clock.advance(Duration::from_secs(10 * 60));
+    system.send_request(forecast("Kyoto", 3)).await?;
+    assert_eq!(runs().await, 4);
+
+    // 定義を入れ替えるとキャッシュは捨てられる
+    system.reload_agent(&weather).await?;
+    sleep(Duration::from_millis(100)).await;
+    system.send_request(forecast("Kyoto", 3)).await?;
+    assert_eq!(runs().await, 5);
+    Ok(())
+}
+
+fn counter_dsl(state: &str, step: i64, extra_handlers: &str) -> String {
+    format!(
+        r#"
+    micro Counter {{
+        state {{
+            {}
+        }}
+        react {{
+            on Bump {{
+                count = count + {}
+            }}
+        }}
+        answer {{
+            on request GetCount() -> Result<Int, Error> {{
+                return Ok(count)
+            }}
+            {}
+        }}
+    }}
+"#,
+        state, step, extra_handlers
+    )
+}
+
+#[tokio::test]
+async fn test_preview_reload_categorizes_changes() -> SystemResult<()> {
+    let (system_config, secret_config) = setup_non_api_config();
+    let mut system = System::new(&system_config, &secret_config).await;
+    let root = system
+        .parse_dsl(&counter_dsl(
+            r#"count: Int = 0; label: String = "counter"; note: String = "";"#,
+            1,
+            "",
+        ))
+        .await?;
+    system.initialize(root).await?;
+    system.start().await?;
+    sleep(Duration::from_millis(100)).await;
+
+    // label の型を変え、note を消し、step とハンドラを加える
+    let plan = system
+        .preview_reload(
+            "Counter",
+            &counter_dsl(
+                "count: Int = 0; label: Int = 0; step: Int = 1;",
+                2,
+                r#"on request Reset() -> Result<Int, Error> {
+                    return Ok(0)
+                }"#,
+            ),
+        )
+        .await?;
+    assert_eq!(
+        plan,
+        ReloadPlan {
+            agent_name: "Counter".to_string(),
+            preserved_state: vec!["count".to_string()],
+            reset_state: vec!["label".to_string(), "note".to_string()],
+            added_state: vec!["step".to_string()],
+            added_handlers: vec!["answer Reset".to_string()],
+            removed_handlers: vec![],
+            changed_handlers: vec!["react Bump".to_string()],
+        }
+    );
+
+    // 何も適用されない
+    assert_eq!(
+        system.get_agent_state("Counter", "label").await?,
+        kairei_core::eval::expression::Value::String("counter".to_string())
+    );
+    let unchanged = system
+        .preview_reload(
+            "Counter",
+            &counter_dsl(
+                r#"count: Int = 0; label: String = "counter"; note: String = "";"#,
+                1,
+                "",
+            ),
+        )
+        .await?;
+    assert!(unchanged.is_unchanged());
+    assert!(matches!(
+        system
+            .preview_reload("Missing", &counter_dsl("count: Int = 0;", 1, ""))
+            .await,
+        Err(SystemError::Agent(_))
+    ));
+    Ok(())
+}
+
+const PREFLIGHT_WORLD_DSL: &str = r#"
+    world Town {
+        events {
+            Alarm(level: Int)
+        }
+    }
+"#;
+
+const PREFLIGHT_AGENTS_DSL: &str = r#"
+    micro Search {
+        config {
+            region: String
+        }
+        state {
+            key: String = "none";
+        }
+        react {
+            on Alarm(level: Int) {
+                key = config.region
+            }
+        }
+    }
+"#;
+
+fn preflight_sources(world: &str, agents: &str) -> Vec<PreflightSource> {
+    vec![
+        PreflightSource::new("world.kairei", world),
+        PreflightSource::new("agents.kairei", agents),
+    ]
+}
+
+#[tokio::test]
+async fn test_preflight_of_valid_deployment() {
+    let (mut system_config, secret_config) = setup_non_api_config();
+    system_config.agent_configs.insert(
+        "Search".to_string(),
+        AgentConfigValues {
+            values: HashMap::from([("region".to_string(), serde_json::json!("Tokyo"))]),
+            hot_reload: false,
+        },
+    );
+    let sources = preflight_sources(PREFLIGHT_WORLD_DSL, PREFLIGHT_AGENTS_DSL);
+
+    let report = System::preflight(&system_config, &secret_config, &sources).await;
+    assert!(report.passed, "{:#?}", report);
+    assert_eq!(report.errors, 0);
+    assert_eq!(report.components.len(), PreflightComponent::ALL.len());
+    assert!(report.component(PreflightComponent::Schemas).checked);
+    // 資格情報は validate_credentials.enabled の時だけ検査する
+    assert!(!report.component(PreflightComponent::Credentials).checked);
+
+    // 同じ入力からは同じレポート
+    let again = System::preflight(&system_config, &secret_config, &sources).await;
+    assert_eq!(
+        serde_json::to_string(&report).unwrap(),
+        serde_json::to_string(&again).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_preflight_reports_every_failing_component() {
+    let (mut system_config, mut secret_config) = setup_non_api_config();
+    // config: 存在しないプロバイダー、region のない設定
+    system_config.provider_configs.primary_provider = Some("missing".to_string());
+    // secrets: 登録されていないハンドラのシークレット
+    system_config
+        .secret_grants
+        .insert("Search".to_string(), vec!["db_password".to_string()]);
+    // providers: 種類の分からないプロバイダー
+    system_config.provider_configs.providers.insert(
+        "broken".to_string(),
+        ProviderConfig {
+            name: "broken".to_string(),
+            provider_type: ProviderType::Unknown,
+            ..Default::default()
+        },
+    );
+    secret_config.providers.insert(
+        "broken".to_string(),
+        ProviderSecretConfig {
+            api_key: "test-key".to_string(),
+            ..Default::default()
+        },
+    );
+    // plugins: 使えない文字を含む名前空間
+    system_config
+        .provider_configs
+        .providers
+        .get_mut("default")
+        .unwrap()
+        .plugin_configs
+        .insert(
+            "shared_memory".to_string(),
+            PluginConfig::SharedMemory(SharedMemoryConfig {
+                namespace: "team space".to_string(),
+                ..Default::default()
+            }),
+        );
+    // dsl: ソースに書かれた API キー、schemas: 同じ名前のイベント
+    let agents = PREFLIGHT_AGENTS_DSL.replace("\"none\"", "\"sk-abcdefghijklmnopqrstuvwx\"");
+    let world = PREFLIGHT_WORLD_DSL.replace(
+        "Alarm(level: Int)",
+        "Alarm(level: Int)\n            Alarm(message: String)",
+    );
+    let sources = preflight_sources(&world, &agents);
+
+    let report = System::preflight(&system_config, &secret_config, &sources).await;
+    assert!(!report.passed);
+    let failed: Vec<PreflightComponent> = report
+        .components
+        .iter()
+        .filter(|component| !component.passed)
+        .map(|component| component.component)
+        .collect();
+    assert_eq!(
+        failed,
+        vec![
+            PreflightComponent::Config,
+            PreflightComponent::Secrets,
+            PreflightComponent::Dsl,
+            PreflightComponent::Providers,
+            PreflightComponent::Plugins,
+            PreflightComponent::Schemas,
+        ]
+    );
+
+    let errors = |component: PreflightComponent| -> Vec<(String, String)> {
+        report
+            .component(component)
+            .diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .map(|d| (d.subject.clone(), d.message.clone()))
+            .collect()
+    };
+    let config_errors = errors(PreflightComponent::Config);
+    assert!(config_errors.iter().any(|(subject, message)| {
+        subject == "provider_configs.primary_provider" && message.contains("missing")
+    }));
+    assert!(
+        config_errors
+            .iter()
+            .any(|(subject, message)| subject == "agent_configs.Search"
+                && message.contains("`region` is required"))
+    );
+    assert_eq!(
+        errors(PreflightComponent::Secrets),
+        vec![(
+            "secret_grants.Search".to_string(),
+            "db_password is not a handler secret".to_string()
+        )]
+    );
+    // ソースごとの行で報告する
+    let dsl_errors = errors(PreflightComponent::Dsl);
+    assert_eq!(dsl_errors.len(), 1);
+    assert!(dsl_errors[0].0.starts_with("agents.kairei:"));
+    assert!(!dsl_errors[0].1.contains("sk-abcdefghijklmnopqrstuvwx"));
+    assert_eq!(errors(PreflightComponent::Providers)[0].0, "broken");
+    assert_eq!(
+        errors(PreflightComponent::Plugins)[0].0,
+        "default.plugin_configs.shared_memory"
+    );
+    assert_eq!(
+        errors(PreflightComponent::Schemas)[0].0,
+        "events.Alarm".to_string()
+    );
+}
+
+#[tokio::test]
+async fn test_preflight_of_unparsable_dsl() {
+    let (system_config, secret_config) = setup_non_api_config();
+    let sources = preflight_sources(PREFLIGHT_WORLD_DSL, "micro Search {\n    state {\n");
+
+    let report = System::preflight(&system_config, &secret_config, &sources).await;
+    let dsl = report.component(PreflightComponent::Dsl);
+    assert!(!dsl.passed);
+    assert!(dsl.diagnostics[0].code.is_some());
+    // エージェントが分からないため、イベントの登録は検査しない
+    assert!(!report.component(PreflightComponent::Schemas).checked);
+    assert!(report.component(PreflightComponent::Providers).passed);
+}
@@ -14,6 +14,8 @@ fn test_await_expression_type_checking() {
 
     // Create AST with a single await expression
     let ast = Root {
+        dsl_version: None,
+        type_defs: vec![],
         world_def: None,
         micro_agent_defs: vec![MicroAgentDef {
             name: "TravelAgent".to_string(),
@@ -27,6 +29,7 @@ fn test_await_expression_type_checking() {
                     parameters: vec![],
                     return_type: TypeInfo::Simple("Any".to_string()),
                     constraints: None,
+                    requires: None,
                     block: HandlerBlock {
                         statements: vec![Statement::Return(Expression::Await(vec![
                             Expression::Request {
@@ -39,9 +42,17 @@ fn test_await_expression_type_checking() {
                             },
                         ]))],
                     },
+                    doc: None,
+                    cache: None,
                 }],
             }),
             react: None,
+            doc: None,
+            labels: Default::default(),
+            persona: None,
+            localized_personas: Default::default(),
+            contracts: vec![],
+            config: None,
         }],
         sistence_agent_defs: vec![],
     };
@@ -69,6 +80,8 @@ fn test_multiple_await_expressions() {
 
     // Create AST with multiple await expressions
     let ast = Root {
+        dsl_version: None,
+        type_defs: vec![],
         world_def: None,
         micro_agent_defs: vec![MicroAgentDef {
             name: "TravelAgent".to_string(),
@@ -82,6 +95,7 @@ fn test_multiple_await_expressions() {
                     parameters: vec![],
                     return_type: TypeInfo::Array(Box::new(TypeInfo::Simple("Any".to_string()))),
                     constraints: None,
+                    requires: None,
                     block: HandlerBlock {
                         statements: vec![Statement::Return(Expression::Await(vec![
                             Expression::Request {
@@ -102,9 +116,17 @@ fn test_multiple_await_expressions() {
                             },
                         ]))],
                     },
+                    doc: None,
+                    cache: None,
                 }],
             }),
             react: None,
+            doc: None,
+            labels: Default::default(),
+            persona: None,
+            localized_personas: Default::default(),
+            contracts: vec![],
+            config: None,
         }],
         sistence_agent_defs: vec![],
     };
@@ -120,6 +142,8 @@ fn test_nested_await_expressions() {
 
     // Create AST with nested await expressions
     let ast = Root {
+        dsl_version: None,
+        type_defs: vec![],
         world_def: None,
         micro_agent_defs: vec![MicroAgentDef {
             name: "TravelAgent".to_string(),
@@ -133,6 +157,7 @@ fn test_nested_await_expressions() {
                     parameters: vec![],
                     return_type: TypeInfo::Simple("Any".to_string()),
                     constraints: None,
+                    requires: None,
                     block: HandlerBlock {
                         statements: vec![
                             Statement::Assignment {
@@ -156,9 +181,17 @@ fn test_nested_await_expressions() {
                             }])),
                         ],
                     },
+                    doc: None,
+                    cache: None,
                 }],
             }),
             react: None,
+            doc: None,
+            labels: Default::default(),
+            persona: None,
+            localized_personas: Default::default(),
+            contracts: vec![],
+            config: None,
         }],
         sistence_agent_defs: vec![],
     };
@@ -4,9 +4,12 @@ use kairei_core::{
 };
 
 pub mod await_expression_test;
+pub mod optional_event_parameter_test;
 pub mod plugin_integration;
+pub mod replay_directive_test;
 pub mod request_expression_test;
 pub mod request_handler_type_checking;
+pub mod return_type_test;
 pub mod think_expression_test;
 
 struct TestPlugin;
@@ -0,0 +1,60 @@
+use kairei_core::{ast::ASTError, ast_registry::AstRegistry, type_checker::TypeCheckError};
+
+fn dsl(emit: &str) -> String {
+    format!(
+        r#"
+        micro Notebook {{
+            observe {{
+                on NoteAdded(title: String, note: String?) {{
+                    emit NoteSeen(title: title, note: note)
+                }}
+            }}
+        }}
+
+        micro Writer {{
+            observe {{
+                on Tick {{
+                    {}
+                }}
+            }}
+        }}
+        "#,
+        emit
+    )
+}
+
+#[tokio::test]
+async fn test_optional_event_parameter_present() {
+    let result = AstRegistry::default()
+        .create_ast_from_dsl(&dsl(r#"emit NoteAdded(title: "todo", note: "buy milk")"#))
+        .await;
+    assert!(result.is_ok(), "{:?}", result.err());
+}
+
+#[tokio::test]
+async fn test_optional_event_parameter_absent() {
+    let root = AstRegistry::default()
+        .create_ast_from_dsl(&dsl(r#"emit NoteAdded(title: "todo")"#))
+        .await
+        .unwrap();
+    let parameters = &root.micro_agent_defs[0].observe.as_ref().unwrap().handlers[0].parameters;
+    assert!(matches!(
+        parameters[1].type_info,
+        kairei_core::ast::TypeInfo::Option(_)
+    ));
+}
+
+#[tokio::test]
+async fn test_required_event_parameter_missing() {
+    let result = AstRegistry::default()
+        .create_ast_from_dsl(&dsl(r#"emit NoteAdded(note: "buy milk")"#))
+        .await;
+    match result {
+        Err(ASTError::TypeCheckError(TypeCheckError::InvalidHandlerSignature {
+            message, ..
+        })) => {
+            assert!(message.contains("title"), "{}", message);
+        }
+        other => panic!("expected missing parameter error, got {:?}", other),
+    }
+}
@@ -9,6 +9,8 @@ use super::TestPlugin;
 fn test_plugin_integration() -> TypeCheckResult<()> {
     // Create an AST using plugin features
     let mut root = Root {
+        dsl_version: None,
+        type_defs: vec![],
         micro_agent_defs: vec![MicroAgentDef {
             name: "PluginAgent".to_string(),
             ..Default::default()
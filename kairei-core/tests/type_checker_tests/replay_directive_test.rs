@@ -0,0 +1,45 @@
+use kairei_core::{ast::ASTError, ast_registry::AstRegistry, type_checker::TypeCheckError};
+
+fn dsl(directive: &str) -> String {
+    format!(
+        r#"
+        micro Mailer {{
+            observe {{
+                {}
+                on Ordered(id: String) {{
+                    emit Sent(id: id)
+                }}
+            }}
+        }}
+        "#,
+        directive
+    )
+}
+
+#[tokio::test]
+async fn test_replay_directive_policies() {
+    for directive in ["@replay(skip)", "@replay(safe)", ""] {
+        let result = AstRegistry::default()
+            .create_ast_from_dsl(&dsl(directive))
+            .await;
+        assert!(result.is_ok(), "{}: {:?}", directive, result.err());
+    }
+}
+
+#[tokio::test]
+async fn test_unknown_replay_policy() {
+    for directive in ["@replay(sometimes)", "@replay(skip, safe)", "@replay()"] {
+        let result = AstRegistry::default()
+            .create_ast_from_dsl(&dsl(directive))
+            .await;
+        match result {
+            Err(ASTError::TypeCheckError(TypeCheckError::InvalidHandlerSignature {
+                message,
+                ..
+            })) => {
+                assert!(message.contains("Mailer.Ordered"), "{}", message);
+            }
+            other => panic!("{}: expected replay error, got {:?}", directive, other),
+        }
+    }
+}
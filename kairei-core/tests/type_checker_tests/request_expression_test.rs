@@ -15,6 +15,8 @@ fn test_request_expression_type_checking() {
 
     // Create AST with a Request expression
     let ast = Root {
+        dsl_version: None,
+        type_defs: vec![],
         world_def: None,
         micro_agent_defs: vec![MicroAgentDef {
             name: "TravelAgent".to_string(),
@@ -31,6 +33,7 @@ fn test_request_expression_type_checking() {
                         err_type: Box::new(TypeInfo::Simple("Error".to_string())),
                     },
                     constraints: None,
+                    requires: None,
                     block: HandlerBlock {
                         statements: vec![Statement::Return(Expression::Request {
                             agent: "WeatherAgent".to_string(),
@@ -41,9 +44,17 @@ fn test_request_expression_type_checking() {
                             options: None,
                         })],
                     },
+                    doc: None,
+                    cache: None,
                 }],
             }),
             react: None,
+            doc: None,
+            labels: Default::default(),
+            persona: None,
+            localized_personas: Default::default(),
+            contracts: vec![],
+            config: None,
         }],
         sistence_agent_defs: vec![],
     };
@@ -71,6 +82,8 @@ fn test_request_expression_in_assignment() {
 
     // Create AST with a variable assignment from a Request expression
     let ast = Root {
+        dsl_version: None,
+        type_defs: vec![],
         world_def: None,
         micro_agent_defs: vec![MicroAgentDef {
             name: "TravelAgent".to_string(),
@@ -87,6 +100,7 @@ fn test_request_expression_in_assignment() {
                         err_type: Box::new(TypeInfo::Simple("Error".to_string())),
                     },
                     constraints: None,
+                    requires: None,
                     block: HandlerBlock {
                         statements: vec![
                             Statement::Assignment {
@@ -106,9 +120,17 @@ fn test_request_expression_in_assignment() {
                             Statement::Return(Expression::Variable("weather".to_string())),
                         ],
                     },
+                    doc: None,
+                    cache: None,
                 }],
             }),
             react: None,
+            doc: None,
+            labels: Default::default(),
+            persona: None,
+            localized_personas: Default::default(),
+            contracts: vec![],
+            config: None,
         }],
         sistence_agent_defs: vec![],
     };
@@ -1,7 +1,10 @@
+use std::collections::HashMap;
+
 use kairei_core::{
     ast::{
-        AnswerDef, Expression, HandlerBlock, Literal, MicroAgentDef, Parameter, RequestHandler,
-        RequestType, Root, Statement, TypeInfo,
+        AnswerDef, BinaryOperator, CacheDirective, Expression, HandlerBlock, Literal,
+        MicroAgentDef, Parameter, RequestHandler, RequestType, Root, StateAccessPath, StateDef,
+        StateVarDef, Statement, TypeInfo,
     },
     type_checker::{TypeCheckError, TypeCheckResult, TypeChecker},
 };
@@ -12,6 +15,8 @@ fn test_request_handler_type_checking_ok() -> TypeCheckResult<()> {
 
     // Test successful case
     let mut root = Root {
+        dsl_version: None,
+        type_defs: vec![],
         micro_agent_defs: vec![MicroAgentDef {
             name: "EventAgent".to_string(),
             answer: Some(AnswerDef {
@@ -20,17 +25,21 @@ fn test_request_handler_type_checking_ok() -> TypeCheckResult<()> {
                     parameters: vec![Parameter {
                         name: "input".to_string(),
                         type_info: TypeInfo::Simple("String".to_string()),
+                        constraint: None,
                     }],
                     return_type: TypeInfo::Result {
                         ok_type: Box::new(TypeInfo::Simple("String".to_string())),
                         err_type: Box::new(TypeInfo::Simple("Error".to_string())),
                     },
                     constraints: None,
+                    requires: None,
                     block: HandlerBlock {
                         statements: vec![Statement::Return(Expression::Ok(Box::new(
                             Expression::Literal(Literal::String("Response".to_string())),
                         )))],
                     },
+                    doc: None,
+                    cache: None,
                 }],
             }),
             ..Default::default()
@@ -53,6 +62,8 @@ fn test_request_handler_type_checking_ng() -> TypeCheckResult<()> {
 
     // Test error case - wrong parameter type
     let mut root = Root {
+        dsl_version: None,
+        type_defs: vec![],
         micro_agent_defs: vec![MicroAgentDef {
             name: "EventAgent".to_string(),
             answer: Some(AnswerDef {
@@ -61,17 +72,21 @@ fn test_request_handler_type_checking_ng() -> TypeCheckResult<()> {
                     parameters: vec![Parameter {
                         name: "input".to_string(),
                         type_info: TypeInfo::Simple("Int".to_string()), // Wrong type
+                        constraint: None,
                     }],
                     return_type: TypeInfo::Result {
                         ok_type: Box::new(TypeInfo::Simple("String".to_string())),
                         err_type: Box::new(TypeInfo::Simple("Error".to_string())),
                     },
                     constraints: None,
+                    requires: None,
                     block: HandlerBlock {
                         statements: vec![Statement::Return(Expression::Ok(Box::new(
                             Expression::Literal(Literal::String("Response".to_string())),
                         )))],
                     },
+                    doc: None,
+                    cache: None,
                 }],
             }),
             ..Default::default()
@@ -85,3 +100,493 @@ fn test_request_handler_type_checking_ng() -> TypeCheckResult<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_request_handler_can_read_request_context() -> TypeCheckResult<()> {
+    let mut checker = TypeChecker::new();
+
+    let mut root = Root {
+        dsl_version: None,
+        type_defs: vec![],
+        micro_agent_defs: vec![MicroAgentDef {
+            name: "LocaleAgent".to_string(),
+            answer: Some(AnswerDef {
+                handlers: vec![RequestHandler {
+                    request_type: RequestType::Custom("greet".to_string()),
+                    parameters: vec![],
+                    return_type: TypeInfo::Result {
+                        ok_type: Box::new(TypeInfo::Simple("String".to_string())),
+                        err_type: Box::new(TypeInfo::Simple("Error".to_string())),
+                    },
+                    constraints: None,
+                    requires: None,
+                    block: HandlerBlock {
+                        statements: vec![
+                            Statement::Assignment {
+                                target: vec![Expression::Variable("locale".to_string())],
+                                value: Expression::Variable("request_locale".to_string()),
+                            },
+                            Statement::Assignment {
+                                target: vec![Expression::Variable("user".to_string())],
+                                value: Expression::Variable("request_user_id".to_string()),
+                            },
+                            Statement::Return(Expression::Ok(Box::new(Expression::Literal(
+                                Literal::String("Response".to_string()),
+                            )))),
+                        ],
+                    },
+                    doc: None,
+                    cache: None,
+                }],
+            }),
+            ..Default::default()
+        }],
+        world_def: None,
+        sistence_agent_defs: vec![],
+    };
+
+    checker.check_types(&mut root)?;
+
+    Ok(())
+}
+
+fn request_metadata_root(parameters: Vec<Parameter>, field: &str) -> Root {
+    Root {
+        dsl_version: None,
+        type_defs: vec![],
+        micro_agent_defs: vec![MicroAgentDef {
+            name: "AuditAgent".to_string(),
+            answer: Some(AnswerDef {
+                handlers: vec![RequestHandler {
+                    request_type: RequestType::Custom("audit".to_string()),
+                    parameters,
+                    return_type: TypeInfo::Result {
+                        ok_type: Box::new(TypeInfo::Simple("String".to_string())),
+                        err_type: Box::new(TypeInfo::Simple("Error".to_string())),
+                    },
+                    constraints: None,
+                    requires: None,
+                    block: HandlerBlock {
+                        statements: vec![Statement::Return(Expression::Ok(Box::new(
+                            Expression::StateAccess(StateAccessPath(vec![
+                                "request".to_string(),
+                                field.to_string(),
+                            ])),
+                        )))],
+                    },
+                    doc: None,
+                    cache: None,
+                }],
+            }),
+            ..Default::default()
+        }],
+        world_def: None,
+        sistence_agent_defs: vec![],
+    }
+}
+
+#[test]
+fn test_request_handler_can_read_request_metadata() -> TypeCheckResult<()> {
+    for field in ["requester", "request_id"] {
+        TypeChecker::new().check_types(&mut request_metadata_root(vec![], field))?;
+    }
+
+    // received_at is a Timestamp, not a String
+    let mut root = request_metadata_root(vec![], "received_at");
+    assert!(TypeChecker::new().check_types(&mut root).is_err());
+
+    let mut root = request_metadata_root(vec![], "unknown");
+    assert!(matches!(
+        TypeChecker::new().check_types(&mut root),
+        Err(TypeCheckError::UndefinedVariable { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_request_parameter_collides_with_request_metadata() {
+    let parameters = vec![Parameter {
+        name: "request".to_string(),
+        type_info: TypeInfo::Simple("String".to_string()),
+        constraint: None,
+    }];
+    let mut root = request_metadata_root(parameters, "requester");
+    assert!(matches!(
+        TypeChecker::new().check_types(&mut root),
+        Err(TypeCheckError::InvalidHandlerSignature { .. })
+    ));
+}
+
+fn streaming_root(yielded: Expression) -> Root {
+    Root {
+        dsl_version: None,
+        type_defs: vec![],
+        micro_agent_defs: vec![MicroAgentDef {
+            name: "ChatAgent".to_string(),
+            answer: Some(AnswerDef {
+                handlers: vec![RequestHandler {
+                    request_type: RequestType::Custom("chat".to_string()),
+                    parameters: vec![],
+                    return_type: TypeInfo::Result {
+                        ok_type: Box::new(TypeInfo::Simple("String".to_string())),
+                        err_type: Box::new(TypeInfo::Simple("Error".to_string())),
+                    },
+                    constraints: None,
+                    requires: None,
+                    block: HandlerBlock {
+                        statements: vec![
+                            Statement::Yield(yielded),
+                            Statement::Return(Expression::Ok(Box::new(Expression::Literal(
+                                Literal::String("done".to_string()),
+                            )))),
+                        ],
+                    },
+                    doc: None,
+                    cache: None,
+                }],
+            }),
+            ..Default::default()
+        }],
+        world_def: None,
+        sistence_agent_defs: vec![],
+    }
+}
+
+#[test]
+fn test_yield_shares_handler_return_type() -> TypeCheckResult<()> {
+    let mut checker = TypeChecker::new();
+    let mut root = streaming_root(Expression::Ok(Box::new(Expression::Literal(
+        Literal::String("partial".to_string()),
+    ))));
+    checker.check_types(&mut root)?;
+
+    let mut checker = TypeChecker::new();
+    let mut root = streaming_root(Expression::Ok(Box::new(Expression::Literal(
+        Literal::Integer(42),
+    ))));
+    assert!(checker.check_types(&mut root).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_yield_outside_answer_handler() {
+    use kairei_core::ast::{EventHandler, EventType, ObserveDef};
+
+    let mut checker = TypeChecker::new();
+    let mut root = Root {
+        dsl_version: None,
+        type_defs: vec![],
+        micro_agent_defs: vec![MicroAgentDef {
+            name: "TickAgent".to_string(),
+            observe: Some(ObserveDef {
+                handlers: vec![EventHandler {
+                    event_type: EventType::Tick,
+                    parameters: vec![],
+                    requires: None,
+                    block: HandlerBlock {
+                        statements: vec![Statement::Yield(Expression::Literal(Literal::String(
+                            "tick".to_string(),
+                        )))],
+                    },
+                    doc: None,
+                    replay: None,
+                }],
+            }),
+            ..Default::default()
+        }],
+        world_def: None,
+        sistence_agent_defs: vec![],
+    };
+
+    assert!(matches!(
+        checker.check_types(&mut root),
+        Err(TypeCheckError::InvalidHandlerSignature { .. })
+    ));
+}
+
+fn contract_root(contract_parameters: Vec<Parameter>, contract_return: TypeInfo) -> Root {
+    use kairei_core::ast::RequestContract;
+
+    let city = Parameter {
+        name: "city".to_string(),
+        type_info: TypeInfo::Simple("String".to_string()),
+        constraint: None,
+    };
+    Root {
+        dsl_version: None,
+        type_defs: vec![],
+        micro_agent_defs: vec![MicroAgentDef {
+            name: "Weather".to_string(),
+            contracts: vec![RequestContract {
+                request_type: RequestType::Custom("GetForecast".to_string()),
+                parameters: contract_parameters,
+                return_type: contract_return,
+                doc: None,
+            }],
+            answer: Some(AnswerDef {
+                handlers: vec![RequestHandler {
+                    request_type: RequestType::Custom("GetForecast".to_string()),
+                    parameters: vec![city],
+                    return_type: TypeInfo::Result {
+                        ok_type: Box::new(TypeInfo::Simple("String".to_string())),
+                        err_type: Box::new(TypeInfo::Simple("Error".to_string())),
+                    },
+                    constraints: None,
+                    requires: None,
+                    block: HandlerBlock {
+                        statements: vec![Statement::Return(Expression::Ok(Box::new(
+                            Expression::Variable("city".to_string()),
+                        )))],
+                    },
+                    doc: None,
+                    cache: None,
+                }],
+            }),
+            ..Default::default()
+        }],
+        world_def: None,
+        sistence_agent_defs: vec![],
+    }
+}
+
+#[test]
+fn test_handler_satisfies_contract() -> TypeCheckResult<()> {
+    let city = Parameter {
+        name: "city".to_string(),
+        type_info: TypeInfo::Simple("String".to_string()),
+        constraint: None,
+    };
+    let mut root = contract_root(vec![city], TypeInfo::Simple("String".to_string()));
+    TypeChecker::new().check_types(&mut root)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_handler_violates_contract() {
+    let days = Parameter {
+        name: "days".to_string(),
+        type_info: TypeInfo::Simple("Int".to_string()),
+        constraint: None,
+    };
+    let city = Parameter {
+        name: "city".to_string(),
+        type_info: TypeInfo::Simple("String".to_string()),
+        constraint: None,
+    };
+
+    // パラメータが契約と異なる
+    let mut root = contract_root(
+        vec![city.clone(), days],
+        TypeInfo::Simple("String".to_string()),
+    );
+    assert!(matches!(
+        TypeChecker::new().check_types(&mut root),
+        Err(TypeCheckError::InvalidHandlerSignature { .. })
+    ));
+
+    // 戻り値の型が契約と異なる
+    let mut root = contract_root(vec![city], TypeInfo::Simple("Int".to_string()));
+    assert!(matches!(
+        TypeChecker::new().check_types(&mut root),
+        Err(TypeCheckError::InvalidHandlerSignature { .. })
+    ));
+
+    // 契約に対応するハンドラがない
+    let mut root = contract_root(vec![], TypeInfo::Simple("String".to_string()));
+    root.micro_agent_defs[0].answer = None;
+    assert!(matches!(
+        TypeChecker::new().check_types(&mut root),
+        Err(TypeCheckError::InvalidHandlerSignature { .. })
+    ));
+}
+
+/// `Withdraw(amount: Int)` of an agent with `balance: Int` state, guarded by `requires`
+fn precondition_root(requires: Expression) -> Root {
+    let int = TypeInfo::Simple("Int".to_string());
+    Root {
+        dsl_version: None,
+        type_defs: vec![],
+        micro_agent_defs: vec![MicroAgentDef {
+            name: "Account".to_string(),
+            state: Some(StateDef {
+                variables: HashMap::from([(
+                    "balance".to_string(),
+                    StateVarDef {
+                        name: "balance".to_string(),
+                        type_info: int.clone(),
+                        initial_value: Some(Expression::Literal(Literal::Integer(10))),
+                        doc: None,
+                    },
+                )]),
+            }),
+            answer: Some(AnswerDef {
+                handlers: vec![RequestHandler {
+                    request_type: RequestType::Custom("Withdraw".to_string()),
+                    parameters: vec![Parameter {
+                        name: "amount".to_string(),
+                        type_info: int.clone(),
+                        constraint: None,
+                    }],
+                    return_type: TypeInfo::Result {
+                        ok_type: Box::new(int),
+                        err_type: Box::new(TypeInfo::Simple("Error".to_string())),
+                    },
+                    constraints: None,
+                    requires: Some(requires),
+                    block: HandlerBlock {
+                        statements: vec![Statement::Return(Expression::Ok(Box::new(
+                            Expression::Variable("amount".to_string()),
+                        )))],
+                    },
+                    doc: None,
+                    cache: None,
+                }],
+            }),
+            ..Default::default()
+        }],
+        world_def: None,
+        sistence_agent_defs: vec![],
+    }
+}
+
+#[test]
+fn test_requires_clause_must_be_boolean() -> TypeCheckResult<()> {
+    // 状態とパラメータを参照できる
+    let mut root = precondition_root(Expression::BinaryOp {
+        op: BinaryOperator::GreaterThanEqual,
+        left: Box::new(Expression::Variable("balance".to_string())),
+        right: Box::new(Expression::Variable("amount".to_string())),
+    });
+    TypeChecker::new().check_types(&mut root)?;
+
+    let mut root = precondition_root(Expression::Variable("amount".to_string()));
+    assert!(matches!(
+        TypeChecker::new().check_types(&mut root),
+        Err(TypeCheckError::TypeMismatch { .. })
+    ));
+
+    let mut root = precondition_root(Expression::Variable("unknown".to_string()));
+    assert!(matches!(
+        TypeChecker::new().check_types(&mut root),
+        Err(TypeCheckError::UndefinedVariable { .. })
+    ));
+
+    Ok(())
+}
+
+/// Agent with a catch-all handler `_(parameters) -> String` returning `value`
+fn fallback_root(parameters: Vec<Parameter>, value: Expression) -> Root {
+    Root {
+        dsl_version: None,
+        type_defs: vec![],
+        micro_agent_defs: vec![MicroAgentDef {
+            name: "Helper".to_string(),
+            answer: Some(AnswerDef {
+                handlers: vec![RequestHandler {
+                    request_type: RequestType::Fallback,
+                    parameters,
+                    return_type: TypeInfo::Simple("String".to_string()),
+                    constraints: None,
+                    requires: None,
+                    block: HandlerBlock {
+                        statements: vec![Statement::Return(value)],
+                    },
+                    doc: None,
+                    cache: None,
+                }],
+            }),
+            ..Default::default()
+        }],
+        world_def: None,
+        sistence_agent_defs: vec![],
+    }
+}
+
+#[test]
+fn test_fallback_handler_type_checking() -> TypeCheckResult<()> {
+    let parameter = |type_name: &str| Parameter {
+        name: "request_type".to_string(),
+        type_info: TypeInfo::Simple(type_name.to_string()),
+        constraint: None,
+    };
+    let request_type = Expression::Variable("request_type".to_string());
+
+    let mut root = fallback_root(vec![parameter("String")], request_type.clone());
+    TypeChecker::new().check_types(&mut root)?;
+
+    // 受け取れるのは元のリクエストタイプだけ
+    for parameters in [
+        vec![],
+        vec![parameter("Int")],
+        vec![parameter("String"), parameter("String")],
+    ] {
+        let mut root = fallback_root(parameters, request_type.clone());
+        assert!(matches!(
+            TypeChecker::new().check_types(&mut root),
+            Err(TypeCheckError::InvalidHandlerSignature { .. })
+        ));
+    }
+
+    // 戻り値は宣言した型で検査される
+    let mut root = fallback_root(
+        vec![parameter("String")],
+        Expression::Literal(Literal::Integer(1)),
+    );
+    assert!(matches!(
+        TypeChecker::new().check_types(&mut root),
+        Err(TypeCheckError::TypeMismatch { .. })
+    ));
+
+    // catch-all は一つだけ
+    let mut root = fallback_root(vec![parameter("String")], request_type);
+    let answer = root.micro_agent_defs[0].answer.as_mut().unwrap();
+    answer.handlers.push(answer.handlers[0].clone());
+    assert!(matches!(
+        TypeChecker::new().check_types(&mut root),
+        Err(TypeCheckError::InvalidHandlerSignature { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_cache_directive_type_checking() -> TypeCheckResult<()> {
+    let with_cache = |ttl: u64, key: Option<Vec<&str>>| {
+        let mut root = fallback_root(vec![], Expression::Literal(Literal::String("ok".into())));
+        let handler = &mut root.micro_agent_defs[0].answer.as_mut().unwrap().handlers[0];
+        handler.request_type = RequestType::Custom("Forecast".to_string());
+        handler.parameters = ["city", "days"]
+            .into_iter()
+            .map(|name| Parameter {
+                name: name.to_string(),
+                type_info: TypeInfo::Simple("String".to_string()),
+                constraint: None,
+            })
+            .collect();
+        handler.cache = Some(CacheDirective {
+            ttl: std::time::Duration::from_secs(ttl),
+            key: key.map(|key| key.into_iter().map(String::from).collect()),
+        });
+        root
+    };
+
+    TypeChecker::new().check_types(&mut with_cache(600, None))?;
+    TypeChecker::new().check_types(&mut with_cache(600, Some(vec!["city", "days"])))?;
+
+    // キーはハンドラの重複しないパラメータで、ttl は 0 より長い
+    for mut root in [
+        with_cache(600, Some(vec!["country"])),
+        with_cache(600, Some(vec!["city", "city"])),
+        with_cache(0, None),
+    ] {
+        assert!(matches!(
+            TypeChecker::new().check_types(&mut root),
+            Err(TypeCheckError::InvalidHandlerSignature { .. })
+        ));
+    }
+
+    Ok(())
+}
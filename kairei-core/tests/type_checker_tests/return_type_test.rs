@@ -0,0 +1,143 @@
+use kairei_core::{
+    ast::{
+        AnswerDef, ErrorHandlerBlock, Expression, HandlerBlock, Literal, MicroAgentDef,
+        RequestHandler, RequestType, Root, Statement, TypeInfo,
+    },
+    type_checker::{TypeCheckError, TypeChecker},
+};
+
+fn string_result() -> TypeInfo {
+    TypeInfo::Result {
+        ok_type: Box::new(TypeInfo::Simple("String".to_string())),
+        err_type: Box::new(TypeInfo::Simple("Error".to_string())),
+    }
+}
+
+fn answer_root(statements: Vec<Statement>) -> Root {
+    Root {
+        dsl_version: None,
+        type_defs: vec![],
+        micro_agent_defs: vec![MicroAgentDef {
+            name: "ReturnAgent".to_string(),
+            answer: Some(AnswerDef {
+                handlers: vec![RequestHandler {
+                    request_type: RequestType::Custom("check".to_string()),
+                    parameters: vec![],
+                    return_type: string_result(),
+                    constraints: None,
+                    requires: None,
+                    block: HandlerBlock { statements },
+                    doc: None,
+                    cache: None,
+                }],
+            }),
+            ..Default::default()
+        }],
+        world_def: None,
+        sistence_agent_defs: vec![],
+    }
+}
+
+fn return_ok(literal: Literal) -> Statement {
+    Statement::Return(Expression::Ok(Box::new(Expression::Literal(literal))))
+}
+
+fn ok_string() -> Statement {
+    return_ok(Literal::String("ok".to_string()))
+}
+
+fn ok_integer() -> Statement {
+    return_ok(Literal::Integer(1))
+}
+
+fn if_else(then_block: Vec<Statement>, else_block: Option<Vec<Statement>>) -> Statement {
+    Statement::If {
+        condition: Expression::Literal(Literal::Boolean(true)),
+        then_block,
+        else_block,
+    }
+}
+
+fn check(statements: Vec<Statement>) -> Result<(), TypeCheckError> {
+    TypeChecker::new().check_types(&mut answer_root(statements))
+}
+
+#[test]
+fn test_return_sites_matching_declared_type() {
+    let statements = vec![
+        if_else(
+            vec![if_else(vec![ok_string()], None)],
+            Some(vec![Statement::Block(vec![ok_string()])]),
+        ),
+        ok_string(),
+    ];
+    assert!(check(statements).is_ok());
+}
+
+#[test]
+fn test_mismatched_return_in_then_branch() {
+    let result = check(vec![if_else(vec![ok_integer()], None), ok_string()]);
+    assert!(matches!(result, Err(TypeCheckError::TypeMismatch { .. })));
+}
+
+#[test]
+fn test_mismatched_return_in_else_branch() {
+    let result = check(vec![if_else(vec![ok_string()], Some(vec![ok_integer()]))]);
+    assert!(matches!(result, Err(TypeCheckError::TypeMismatch { .. })));
+}
+
+#[test]
+fn test_mismatched_return_in_nested_branch() {
+    let nested = if_else(
+        vec![Statement::Block(vec![if_else(
+            vec![ok_string()],
+            Some(vec![ok_integer()]),
+        )])],
+        None,
+    );
+    let result = check(vec![nested, ok_string()]);
+    assert!(matches!(result, Err(TypeCheckError::TypeMismatch { .. })));
+}
+
+#[test]
+fn test_mismatched_return_in_error_handler() {
+    let with_error = Statement::WithError {
+        statement: Box::new(Statement::Expression(Expression::Literal(
+            Literal::Integer(1),
+        ))),
+        error_handler_block: ErrorHandlerBlock {
+            error_binding: None,
+            error_handler_statements: vec![ok_integer()],
+            control: None,
+        },
+    };
+    let result = check(vec![with_error, ok_string()]);
+    assert!(matches!(result, Err(TypeCheckError::TypeMismatch { .. })));
+}
+
+#[test]
+fn test_mismatched_on_fail_return_control() {
+    use kairei_core::ast::{OnFailControl, OnFailReturn};
+
+    let with_error = |value: Literal| Statement::WithError {
+        statement: Box::new(Statement::Expression(Expression::Literal(
+            Literal::Integer(1),
+        ))),
+        error_handler_block: ErrorHandlerBlock {
+            error_binding: None,
+            error_handler_statements: vec![],
+            control: Some(OnFailControl::Return(OnFailReturn::Ok(
+                Expression::Literal(value),
+            ))),
+        },
+    };
+    assert!(
+        check(vec![
+            with_error(Literal::String("fallback".to_string())),
+            ok_string()
+        ])
+        .is_ok()
+    );
+    let result = check(vec![with_error(Literal::Integer(0)), ok_string()]);
+    assert!(matches!(result, Err(TypeCheckError::TypeMismatch { .. })));
+}
@@ -14,6 +14,8 @@ fn test_think_expression_type_checking() {
 
     // Create AST that matches the original failing case
     let ast = Root {
+        dsl_version: None,
+        type_defs: vec![],
         world_def: None,
         micro_agent_defs: vec![MicroAgentDef {
             name: "TravelAgent".to_string(),
@@ -30,6 +32,7 @@ fn test_think_expression_type_checking() {
                         err_type: Box::new(TypeInfo::Simple("Error".to_string())),
                     },
                     constraints: None,
+                    requires: None,
                     block: HandlerBlock {
                         statements: vec![Statement::Return(Expression::Think {
                             args: vec![Argument::Positional(Expression::Literal(Literal::String(
@@ -38,9 +41,17 @@ fn test_think_expression_type_checking() {
                             with_block: None,
                         })],
                     },
+                    doc: None,
+                    cache: None,
                 }],
             }),
             react: None,
+            doc: None,
+            labels: Default::default(),
+            persona: None,
+            localized_personas: Default::default(),
+            contracts: vec![],
+            config: None,
         }],
         sistence_agent_defs: vec![],
     };
@@ -68,6 +79,8 @@ fn test_think_expression_in_assignment() {
 
     // Create AST with a variable assignment from a Think expression
     let ast = Root {
+        dsl_version: None,
+        type_defs: vec![],
         world_def: None,
         micro_agent_defs: vec![MicroAgentDef {
             name: "TravelAgent".to_string(),
@@ -84,6 +97,7 @@ fn test_think_expression_in_assignment() {
                         err_type: Box::new(TypeInfo::Simple("Error".to_string())),
                     },
                     constraints: None,
+                    requires: None,
                     block: HandlerBlock {
                         statements: vec![
                             Statement::Assignment {
@@ -98,9 +112,17 @@ fn test_think_expression_in_assignment() {
                             Statement::Return(Expression::Variable("plan".to_string())),
                         ],
                     },
+                    doc: None,
+                    cache: None,
                 }],
             }),
             react: None,
+            doc: None,
+            labels: Default::default(),
+            persona: None,
+            localized_personas: Default::default(),
+            contracts: vec![],
+            config: None,
         }],
         sistence_agent_defs: vec![],
     };
@@ -1,23 +1,23 @@
-use crate::models::user::User;
+use crate::auth::AuthContext;
 use axum::{
     extract::FromRequestParts,
     http::{StatusCode, request::Parts},
 };
 
-/// Extractor for the authenticated user
+/// Extractor for the authenticated caller
 ///
-/// This extractor gets the authenticated user from the request extensions
-/// without consuming the request body.
-pub struct AuthUser(pub User);
+/// This extractor gets the [`AuthContext`] set by the auth middleware from the
+/// request extensions without consuming the request body.
+pub struct AuthUser(pub AuthContext);
 
 impl AuthUser {
-    /// Get a reference to the inner user
-    pub fn user(&self) -> &User {
+    /// Get a reference to the auth context
+    pub fn context(&self) -> &AuthContext {
         &self.0
     }
 
-    /// Unwrap the extractor to get the inner user
-    pub fn into_inner(self) -> User {
+    /// Unwrap the extractor to get the auth context
+    pub fn into_inner(self) -> AuthContext {
         self.0
     }
 }
@@ -29,30 +29,30 @@ where
     type Rejection = StatusCode;
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-        let user = parts
+        let context = parts
             .extensions
-            .get::<User>()
+            .get::<AuthContext>()
             .ok_or(StatusCode::UNAUTHORIZED)?
             .clone();
 
-        Ok(AuthUser(user))
+        Ok(AuthUser(context))
     }
 }
 
 /// Extractor for the authenticated admin user
 ///
-/// This extractor gets the authenticated user from the request extensions
-/// and ensures that the user has admin role.
-pub struct AuthAdmin(pub User);
+/// This extractor gets the [`AuthContext`] from the request extensions
+/// and ensures that the caller has admin role.
+pub struct AuthAdmin(pub AuthContext);
 
 impl AuthAdmin {
-    /// Get a reference to the inner user
-    pub fn user(&self) -> &User {
+    /// Get a reference to the auth context
+    pub fn context(&self) -> &AuthContext {
         &self.0
     }
 
-    /// Unwrap the extractor to get the inner user
-    pub fn into_inner(self) -> User {
+    /// Unwrap the extractor to get the auth context
+    pub fn into_inner(self) -> AuthContext {
         self.0
     }
 }
@@ -64,16 +64,16 @@ where
     type Rejection = StatusCode;
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-        let user = parts
+        let context = parts
             .extensions
-            .get::<User>()
+            .get::<AuthContext>()
             .ok_or(StatusCode::UNAUTHORIZED)?
             .clone();
 
-        if !user.is_admin() {
+        if !context.is_admin() {
             return Err(StatusCode::FORBIDDEN);
         }
 
-        Ok(AuthAdmin(user))
+        Ok(AuthAdmin(context))
     }
 }
@@ -1,5 +1,4 @@
-use crate::auth::AuthStore;
-use crate::models::user::User;
+use crate::auth::{AuthContext, AuthProviderChain, RequestCredentials};
 use axum::{
     extract::{Request, State},
     http::StatusCode,
@@ -7,36 +6,27 @@ use axum::{
     response::Response,
 };
 use std::sync::Arc;
+use tracing::debug;
 
-/// Axum middleware for API key authentication
+/// Axum middleware authenticating requests with the configured provider chain
 pub async fn auth_middleware(
-    State(auth_store): State<Arc<AuthStore>>,
+    State(providers): State<Arc<AuthProviderChain>>,
     mut request: Request,
     next: Next,
 ) -> Result<Response, StatusCode> {
-    println!(
-        "auth_middleware, request.uri().path(): {:?}",
-        request.uri().path()
-    );
     let path = request.uri().path();
     if ignore_auth_path(path) {
-        println!("auth_middleware, ignore_auth_path, path: {:?}", path);
         return Ok(next.run(request).await);
     }
-    // Extract API key from headers
-    let api_key = request
-        .headers()
-        .get("X-API-Key")
-        .and_then(|value| value.to_str().ok())
-        .ok_or(StatusCode::UNAUTHORIZED)?;
 
-    // Look up user by API key
-    let user = auth_store
-        .get_user_by_api_key(api_key)
-        .ok_or(StatusCode::UNAUTHORIZED)?;
+    let credentials = RequestCredentials::from_headers(request.headers());
+    let context = providers.authenticate(&credentials).await.map_err(|e| {
+        debug!("auth_middleware: {} rejected: {}", request.uri().path(), e);
+        StatusCode::UNAUTHORIZED
+    })?;
 
-    // Add user to request extensions
-    request.extensions_mut().insert(user);
+    // Add the auth context to request extensions
+    request.extensions_mut().insert(context);
 
     // Continue with the request
     Ok(next.run(request).await)
@@ -65,14 +55,14 @@ pub fn is_docs_path(path: &str) -> bool {
     path.starts_with("/api/v1/docs")
 }
 
-/// Extension trait for Request to easily extract the authenticated user
+/// Extension trait for Request to easily extract the auth context
 pub trait AuthExt {
-    /// Get the authenticated user from the request
-    fn user(&self) -> Option<&User>;
+    /// Get the auth context of the authenticated caller
+    fn auth_context(&self) -> Option<&AuthContext>;
 }
 
 impl AuthExt for Request {
-    fn user(&self) -> Option<&User> {
-        self.extensions().get::<User>()
+    fn auth_context(&self) -> Option<&AuthContext> {
+        self.extensions().get::<AuthContext>()
     }
 }
@@ -1,8 +1,10 @@
 pub mod extractor;
 pub mod middleware;
+pub mod provider;
 pub mod store;
 
 // Re-export for easier imports
 pub use extractor::*;
 pub use middleware::*;
+pub use provider::*;
 pub use store::*;
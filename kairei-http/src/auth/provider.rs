@@ -0,0 +1,510 @@
+//! Pluggable authentication backends.
+//!
+//! Each [`AuthProvider`] turns the credentials of a request into an [`AuthContext`].
+//! Providers are composed into an ordered [`AuthProviderChain`]: the first provider
+//! that accepts the credentials wins, and the next one is tried otherwise.
+//!
+//! The chain is configured by [`AuthConfig`] in `ServerConfig`. Besides the built-in
+//! providers, embedders can register their own (e.g. LDAP) by name on the
+//! [`crate::server::ServerBuilder`] and refer to them from the configuration.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use axum::http::HeaderMap;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::debug;
+
+use crate::{
+    auth::AuthStore,
+    models::user::{User, UserRole},
+};
+
+/// Header carrying the API key
+pub const API_KEY_HEADER: &str = "X-API-Key";
+
+/// The authenticated caller, as seen by the handlers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuthContext {
+    /// Identifier of the caller (user ID for API keys)
+    pub principal: String,
+    /// Role used for authorization
+    pub role: UserRole,
+    /// Additional permissions granted by the backend
+    #[serde(default)]
+    pub scopes: Vec<String>,
+}
+
+impl AuthContext {
+    pub fn new(principal: impl Into<String>, role: UserRole) -> Self {
+        Self {
+            principal: principal.into(),
+            role,
+            scopes: vec![],
+        }
+    }
+
+    pub fn with_scopes(mut self, scopes: Vec<String>) -> Self {
+        self.scopes = scopes;
+        self
+    }
+
+    /// Check if the caller has admin role
+    pub fn is_admin(&self) -> bool {
+        self.role == UserRole::Admin
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+}
+
+impl From<User> for AuthContext {
+    fn from(user: User) -> Self {
+        Self::new(user.user_id, user.role)
+    }
+}
+
+/// Credentials presented with a request
+#[derive(Debug, Clone, Default)]
+pub struct RequestCredentials {
+    /// Value of the `X-API-Key` header
+    pub api_key: Option<String>,
+    /// Token of an `Authorization: Bearer` header
+    pub bearer_token: Option<String>,
+    /// All request headers, for providers that need other schemes
+    pub headers: HeaderMap,
+}
+
+impl RequestCredentials {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        Self {
+            api_key: header(API_KEY_HEADER),
+            bearer_token: header("Authorization")
+                .and_then(|value| value.strip_prefix("Bearer ").map(str::to_string)),
+            headers: headers.clone(),
+        }
+    }
+
+    pub fn with_api_key(api_key: impl Into<String>) -> Self {
+        Self {
+            api_key: Some(api_key.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Key identifying these credentials in a cache
+    fn cache_key(&self) -> Option<String> {
+        match (&self.api_key, &self.bearer_token) {
+            (Some(key), _) => Some(format!("api_key:{}", key)),
+            (None, Some(token)) => Some(format!("bearer:{}", token)),
+            (None, None) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum AuthError {
+    /// The provider does not handle this kind of credentials; the chain moves on
+    #[error("No credentials for this provider")]
+    MissingCredentials,
+
+    /// The credentials were checked and rejected
+    #[error("Invalid credentials: {0}")]
+    InvalidCredentials(String),
+
+    /// The backend could not be reached or answered with an error
+    #[error("Auth backend error: {0}")]
+    Backend(String),
+
+    /// The configuration refers to a provider that was not registered
+    #[error("Unknown auth provider: {0}")]
+    UnknownProvider(String),
+
+    #[error("Auth configuration error: {0}")]
+    Config(String),
+}
+
+pub type AuthResult<T> = Result<T, AuthError>;
+
+/// An authentication backend
+#[async_trait]
+pub trait AuthProvider: Send + Sync {
+    /// Name used in logs
+    fn name(&self) -> &str;
+
+    /// Authenticate the credentials of a request.
+    /// Returns [`AuthError::MissingCredentials`] when the request carries nothing
+    /// this provider understands.
+    async fn authenticate(&self, credentials: &RequestCredentials) -> AuthResult<AuthContext>;
+}
+
+/// API keys looked up in the [`AuthStore`]
+pub struct ApiKeyAuthProvider {
+    store: AuthStore,
+}
+
+impl ApiKeyAuthProvider {
+    pub fn new(store: AuthStore) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl AuthProvider for ApiKeyAuthProvider {
+    fn name(&self) -> &str {
+        "api_key"
+    }
+
+    async fn authenticate(&self, credentials: &RequestCredentials) -> AuthResult<AuthContext> {
+        let api_key = credentials
+            .api_key
+            .as_ref()
+            .ok_or(AuthError::MissingCredentials)?;
+        self.store
+            .get_user_by_api_key(api_key)
+            .map(AuthContext::from)
+            .ok_or_else(|| AuthError::InvalidCredentials("unknown API key".to_string()))
+    }
+}
+
+/// Entry of a static credentials file
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct StaticCredential {
+    pub api_key: String,
+    pub principal: String,
+    #[serde(default)]
+    pub role: UserRole,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+}
+
+/// API keys read once from a JSON file (an array of [`StaticCredential`])
+pub struct StaticFileAuthProvider {
+    credentials: HashMap<String, AuthContext>,
+}
+
+impl StaticFileAuthProvider {
+    pub fn new(credentials: Vec<StaticCredential>) -> Self {
+        Self {
+            credentials: credentials
+                .into_iter()
+                .map(|c| {
+                    let context = AuthContext::new(c.principal, c.role).with_scopes(c.scopes);
+                    (c.api_key, context)
+                })
+                .collect(),
+        }
+    }
+
+    pub fn from_file(path: &Path) -> AuthResult<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| AuthError::Config(format!("{}: {}", path.display(), e)))?;
+        let credentials = serde_json::from_str(&content)
+            .map_err(|e| AuthError::Config(format!("{}: {}", path.display(), e)))?;
+        Ok(Self::new(credentials))
+    }
+}
+
+#[async_trait]
+impl AuthProvider for StaticFileAuthProvider {
+    fn name(&self) -> &str {
+        "static_file"
+    }
+
+    async fn authenticate(&self, credentials: &RequestCredentials) -> AuthResult<AuthContext> {
+        let api_key = credentials
+            .api_key
+            .as_ref()
+            .ok_or(AuthError::MissingCredentials)?;
+        self.credentials
+            .get(api_key)
+            .cloned()
+            .ok_or_else(|| AuthError::InvalidCredentials("unknown API key".to_string()))
+    }
+}
+
+/// Caches successful lookups of an expensive provider for a fixed TTL.
+/// Rejections are not cached, so a newly added credential works immediately.
+pub struct CachedAuthProvider {
+    inner: Arc<dyn AuthProvider>,
+    ttl: Duration,
+    entries: DashMap<String, (Instant, AuthContext)>,
+}
+
+impl CachedAuthProvider {
+    pub fn new(inner: Arc<dyn AuthProvider>, ttl: Duration) -> Self {
+        Self {
+            inner,
+            ttl,
+            entries: DashMap::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl AuthProvider for CachedAuthProvider {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn authenticate(&self, credentials: &RequestCredentials) -> AuthResult<AuthContext> {
+        let Some(key) = credentials.cache_key() else {
+            return self.inner.authenticate(credentials).await;
+        };
+        if let Some(entry) = self.entries.get(&key) {
+            let (cached_at, context) = entry.value();
+            if cached_at.elapsed() < self.ttl {
+                return Ok(context.clone());
+            }
+        }
+        self.entries.remove(&key);
+
+        let context = self.inner.authenticate(credentials).await?;
+        self.entries.insert(key, (Instant::now(), context.clone()));
+        Ok(context)
+    }
+}
+
+/// Ordered list of providers; the first one to accept the credentials wins
+#[derive(Clone, Default)]
+pub struct AuthProviderChain {
+    providers: Vec<Arc<dyn AuthProvider>>,
+}
+
+impl AuthProviderChain {
+    pub fn new(providers: Vec<Arc<dyn AuthProvider>>) -> Self {
+        Self { providers }
+    }
+
+    /// Chain with only the API key provider backed by `store`
+    pub fn api_key(store: AuthStore) -> Self {
+        Self::new(vec![Arc::new(ApiKeyAuthProvider::new(store))])
+    }
+
+    /// Build the chain described by `config`.
+    /// `custom` holds the providers registered by the embedder, by name.
+    pub fn from_config(
+        config: &AuthConfig,
+        store: &AuthStore,
+        custom: &HashMap<String, Arc<dyn AuthProvider>>,
+    ) -> AuthResult<Self> {
+        let mut providers = Vec::with_capacity(config.providers.len());
+        for entry in &config.providers {
+            let provider: Arc<dyn AuthProvider> = match &entry.kind {
+                AuthProviderKind::ApiKey => Arc::new(ApiKeyAuthProvider::new(store.clone())),
+                AuthProviderKind::StaticFile { path } => {
+                    Arc::new(StaticFileAuthProvider::from_file(path)?)
+                }
+                AuthProviderKind::Custom { name } => custom
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| AuthError::UnknownProvider(name.clone()))?,
+            };
+            let provider = match entry.cache_ttl_secs {
+                Some(ttl) => Arc::new(CachedAuthProvider::new(provider, Duration::from_secs(ttl))),
+                None => provider,
+            };
+            providers.push(provider);
+        }
+        Ok(Self::new(providers))
+    }
+
+    pub fn len(&self) -> usize {
+        self.providers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.providers.is_empty()
+    }
+
+    /// Try each provider in order.
+    /// If none accepts the credentials, the first rejection is returned
+    /// ([`AuthError::MissingCredentials`] if no provider understood them).
+    pub async fn authenticate(&self, credentials: &RequestCredentials) -> AuthResult<AuthContext> {
+        let mut rejection = None;
+        for provider in &self.providers {
+            match provider.authenticate(credentials).await {
+                Ok(context) => return Ok(context),
+                Err(AuthError::MissingCredentials) => {}
+                Err(e) => {
+                    debug!("auth provider {} rejected request: {}", provider.name(), e);
+                    rejection.get_or_insert(e);
+                }
+            }
+        }
+        Err(rejection.unwrap_or(AuthError::MissingCredentials))
+    }
+}
+
+/// Authentication settings in `ServerConfig`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AuthConfig {
+    /// Providers in the order they are tried
+    pub providers: Vec<AuthProviderConfig>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            providers: vec![AuthProviderConfig {
+                kind: AuthProviderKind::ApiKey,
+                cache_ttl_secs: None,
+            }],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AuthProviderConfig {
+    #[serde(flatten)]
+    pub kind: AuthProviderKind,
+    /// Cache successful lookups for this many seconds
+    #[serde(default)]
+    pub cache_ttl_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuthProviderKind {
+    /// API keys from the server's [`AuthStore`]
+    ApiKey,
+    /// API keys from a JSON file
+    StaticFile { path: PathBuf },
+    /// A provider registered by the embedder under `name`
+    Custom { name: String },
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// Accepts API keys starting with `test-` and counts lookups
+    struct HeaderProvider {
+        lookups: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl AuthProvider for HeaderProvider {
+        fn name(&self) -> &str {
+            "header"
+        }
+
+        async fn authenticate(&self, credentials: &RequestCredentials) -> AuthResult<AuthContext> {
+            self.lookups.fetch_add(1, Ordering::SeqCst);
+            match credentials.api_key.as_deref() {
+                Some(key) if key.starts_with("test-") => {
+                    Ok(AuthContext::new(key, UserRole::User).with_scopes(vec!["test".into()]))
+                }
+                Some(_) => Err(AuthError::InvalidCredentials("not a test key".into())),
+                None => Err(AuthError::MissingCredentials),
+            }
+        }
+    }
+
+    fn header_provider() -> Arc<HeaderProvider> {
+        Arc::new(HeaderProvider {
+            lookups: AtomicUsize::new(0),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_chain_falls_through_in_order() {
+        let custom = header_provider();
+        let chain = AuthProviderChain::new(vec![
+            custom.clone(),
+            Arc::new(ApiKeyAuthProvider::new(AuthStore::default())),
+        ]);
+
+        // 1 番目が受け付ける
+        let context = chain
+            .authenticate(&RequestCredentials::with_api_key("test-alice"))
+            .await
+            .unwrap();
+        assert_eq!(context.principal, "test-alice");
+        assert!(context.has_scope("test"));
+
+        // 1 番目が拒否し、2 番目が受け付ける
+        let context = chain
+            .authenticate(&RequestCredentials::with_api_key("admin-key"))
+            .await
+            .unwrap();
+        assert_eq!(context.principal, "admin");
+        assert!(context.is_admin());
+
+        // 全員が拒否: 最初の拒否理由を返す
+        let error = chain
+            .authenticate(&RequestCredentials::with_api_key("nobody"))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error,
+            AuthError::InvalidCredentials("not a test key".to_string())
+        );
+        assert_eq!(
+            chain
+                .authenticate(&RequestCredentials::default())
+                .await
+                .unwrap_err(),
+            AuthError::MissingCredentials
+        );
+        assert_eq!(custom.lookups.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_cache_expires_after_ttl() {
+        let inner = header_provider();
+        let cached = CachedAuthProvider::new(inner.clone(), Duration::from_millis(100));
+        let credentials = RequestCredentials::with_api_key("test-bob");
+
+        cached.authenticate(&credentials).await.unwrap();
+        cached.authenticate(&credentials).await.unwrap();
+        assert_eq!(inner.lookups.load(Ordering::SeqCst), 1);
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        cached.authenticate(&credentials).await.unwrap();
+        assert_eq!(inner.lookups.load(Ordering::SeqCst), 2);
+
+        // 拒否はキャッシュしない
+        let rejected = RequestCredentials::with_api_key("nobody");
+        assert!(cached.authenticate(&rejected).await.is_err());
+        assert!(cached.authenticate(&rejected).await.is_err());
+        assert_eq!(inner.lookups.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn test_chain_from_config() {
+        let config: AuthConfig = serde_json::from_value(serde_json::json!({
+            "providers": [
+                { "type": "custom", "name": "header", "cache_ttl_secs": 60 },
+                { "type": "api_key" }
+            ]
+        }))
+        .unwrap();
+        let mut custom: HashMap<String, Arc<dyn AuthProvider>> = HashMap::new();
+        custom.insert("header".to_string(), header_provider());
+
+        let chain = AuthProviderChain::from_config(&config, &AuthStore::default(), &custom);
+        assert_eq!(chain.unwrap().len(), 2);
+
+        let missing =
+            AuthProviderChain::from_config(&config, &AuthStore::default(), &HashMap::new());
+        assert!(matches!(missing, Err(AuthError::UnknownProvider(name)) if name == "header"));
+    }
+}
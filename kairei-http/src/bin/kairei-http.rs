@@ -1,7 +1,8 @@
 use clap::{Parser, Subcommand};
 use kairei_http::{
     self,
-    server::{Secret, ServerConfig},
+    server::{GptsManifestConfig, Secret, ServerConfig},
+    services::agent_import::AgentImportConfig,
 };
 use std::path::PathBuf;
 use tracing::{debug, info, warn};
@@ -59,6 +60,30 @@ struct Cli {
     #[arg(long, env = "KAIREI_ENABLE_TICKER", default_value = "false")]
     enable_ticker: bool,
 
+    /// Reload the compiler services' system when its DSL files change
+    #[arg(long, env = "KAIREI_WATCH_DSL", default_value = "false")]
+    watch_dsl: bool,
+
+    /// Public base URL announced in the GPTs action manifest
+    #[arg(long, env = "KAIREI_PUBLIC_BASE_URL")]
+    public_base_url: Option<String>,
+
+    /// Require If-Match on destructive operations
+    #[arg(long, env = "KAIREI_REQUIRE_IF_MATCH", default_value = "false")]
+    require_if_match: bool,
+
+    /// Comma-separated hosts agent definitions may be imported from
+    #[arg(long, env = "KAIREI_AGENT_IMPORT_HOSTS")]
+    agent_import_hosts: Option<String>,
+
+    /// Registry resolving `name@version` agent sources
+    #[arg(long, env = "KAIREI_AGENT_REGISTRY_URL")]
+    agent_registry_url: Option<String>,
+
+    /// Directory keeping the ETag versions of systems and agents across restarts
+    #[arg(long, env = "KAIREI_VERSIONS_DIR")]
+    versions_dir: Option<String>,
+
     /// Subcommands
     #[command(subcommand)]
     command: Option<Commands>,
@@ -129,6 +154,22 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 dsl_directory: cli.dsl_dir,
                 enable_dsl_compiler: cli.enable_dsl_compiler,
                 enable_ticker: cli.enable_ticker,
+                watch_dsl: cli.watch_dsl,
+                gpts_manifest: GptsManifestConfig {
+                    public_base_url: cli.public_base_url,
+                    ..Default::default()
+                },
+                require_if_match: cli.require_if_match,
+                agent_import: AgentImportConfig {
+                    allowed_hosts: cli
+                        .agent_import_hosts
+                        .map(|s| s.split(',').map(|s| s.trim().to_string()).collect())
+                        .unwrap_or_default(),
+                    registry_url: cli.agent_registry_url,
+                    ..Default::default()
+                },
+                versions_directory: cli.versions_dir,
+                ..Default::default()
             }
         }
     };
@@ -1,53 +1,101 @@
 use crate::auth::{AuthAdmin, AuthUser};
+use crate::handlers::etag::{ETagHeader, etag_header, precondition, precondition_failed};
+use crate::handlers::events::coerce_payload;
+use crate::handlers::shaping::AGENT_SHAPE;
+use crate::handlers::validation::ValidatedJson;
 use crate::models::{
-    AgentCreationRequest, AgentCreationResponse, AgentStatus, GetAgentResponse, ListAgentsResponse,
+    AgentConfigErrorResponse, AgentContractsResponse, AgentCreationRequest, AgentCreationResponse,
+    AgentLogsResponse, AgentStatus, ApiError, BatchAgentsRequest, DebugEvalErrorResponse,
+    DebugEvalRequest, DebugEvalResponse, GetAgentResponse, LifecycleEvent, LifecycleEventKind,
+    ListAgentsResponse, ParameterErrorResponse, ReloadPreviewErrorResponse, ReloadPreviewRequest,
     ScaleDownAgentRequest, ScaleUpAgentRequest, SendRequestAgentRequest, SendRequestAgentResponse,
-    ValidationResult,
+    TestHandlerErrorResponse, TestHandlerRequest, TestHandlerResponse, UpdateAgentConfigRequest,
+    ValidationErrorResponse, ValidationResult,
 };
 use crate::server::AppState;
+use crate::session::versions::IfMatch;
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::Json,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode, header::ACCEPT_LANGUAGE},
+    response::{
+        IntoResponse, Json, Response,
+        sse::{self, KeepAlive, Sse},
+    },
 };
-use kairei_core::event_bus;
+use futures::{Stream, StreamExt};
+use kairei_core::{
+    ASTError,
+    agent_batch::{AgentBatch, BatchOperation, BatchOutcome, BatchReport},
+    agent_registry::AgentError,
+    agent_settings::{AgentSettings, AgentSettingsPatch},
+    catalog::RequestSignature,
+    config::AgentLogConfig,
+    config_values::AgentConfigError,
+    context::{ConversationTurn, RequestContext},
+    debug_eval::DebugEvalError,
+    event::{
+        coercion,
+        stream_event::{StreamEvent, stream_events},
+    },
+    event_bus,
+    handler_test::{HandlerTest, HandlerTestError},
+    reload_plan::ReloadPlan,
+    system::{System, SystemError, validate_labels},
+};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use tokio::sync::RwLock;
+
+/// Header asking `request_agent` for the timing breakdown of the request
+pub const PROFILE_HEADER: &str = "X-Kairei-Profile";
 
 /// Create a new agent in the system
 ///
 /// Creates a new agent with the provided DSL code and adds it to the system.
+/// With `If-Match`, the agent is only added when the system's version is unchanged.
 /// Requires authentication with admin role.
 #[utoipa::path(
     post,
     path = "/systems/{system_id}/agents",
     request_body = AgentCreationRequest,
     responses(
-        (status = 201, description = "Agent created successfully", body = AgentCreationResponse),
+        (status = 201, description = "Agent created successfully", body = AgentCreationResponse,
+            headers(("ETag" = String, description = "New version of the system"))),
         (status = 400, description = "Invalid request"),
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden"),
         (status = 404, description = "System not found"),
+        (status = 412, description = "The system changed since the given ETag"),
         (status = 500, description = "Internal server error")
     ),
     params(
-        ("system_id" = String, Path, description = "System identifier")
+        ("system_id" = String, Path, description = "System identifier"),
+        ("If-Match" = Option<String>, Header, description = "ETag of the system")
     )
 )]
 #[axum::debug_handler]
 pub async fn create_agent(
     State(state): State<AppState>,
     auth: AuthAdmin,
+    headers: HeaderMap,
     Path(system_id): Path<String>,
     Json(payload): Json<AgentCreationRequest>,
-) -> Result<(StatusCode, Json<AgentCreationResponse>), StatusCode> {
-    if !auth.user().is_admin() {
+) -> Result<(StatusCode, ETagHeader, Json<AgentCreationResponse>), StatusCode> {
+    if !auth.context().is_admin() {
         return Err(StatusCode::FORBIDDEN);
     }
+    let precondition = precondition(&state, &headers, false)?;
 
     let session = state
         .session_manager
         .get_session(&system_id)
         .await
         .ok_or(StatusCode::NOT_FOUND)?;
+    let version = session
+        .versions
+        .update_system(&precondition)
+        .await
+        .map_err(precondition_failed)?;
 
     let system = session.system.write().await;
 
@@ -61,19 +109,27 @@ pub async fn create_agent(
     };
 
     // Find the agent definition in the parsed AST
-    let agent_def = ast
+    let mut agent_def = ast
         .micro_agent_defs
-        .iter()
+        .into_iter()
         .find(|def| def.name == payload.name)
         .ok_or_else(|| {
             tracing::error!("Agent definition not found in DSL");
             StatusCode::BAD_REQUEST
         })?;
+    agent_def.labels.extend(payload.labels);
 
     // Register the agent AST
-    if let Err(e) = system.register_agent_ast(&payload.name, agent_def).await {
-        tracing::error!("Failed to register agent AST: {}", e);
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    match system.register_agent_ast(&payload.name, &agent_def).await {
+        Ok(()) => {}
+        Err(e @ SystemError::InvalidLabel(_)) => {
+            tracing::error!("Invalid agent labels: {}", e);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        Err(e) => {
+            tracing::error!("Failed to register agent AST: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
     }
 
     // Register the agent
@@ -88,6 +144,11 @@ pub async fn create_agent(
             tracing::error!("Failed to start agent: {}", e);
             // We don't return an error here since the agent was created successfully
             // Just log the error and continue
+        } else {
+            state.session_manager.lifecycle.publish(
+                &session.user_id,
+                LifecycleEvent::agent(LifecycleEventKind::AgentStarted, &system_id, &payload.name),
+            );
         }
     }
 
@@ -105,18 +166,26 @@ pub async fn create_agent(
         },
     };
 
-    Ok((StatusCode::CREATED, Json(response)))
+    Ok((StatusCode::CREATED, etag_header(version), Json(response)))
 }
 
 /// Get agent details
 ///
 /// Returns details about a specific agent.
+/// The `ETag` header holds the version of the agent; send it as `If-Match`
+/// when changing the agent to detect a concurrent change.
+/// `?fields=` keeps only the listed top-level fields, and `?embed=` adds the
+/// listed sub-resources under `embedded`: `statistics` (instances and counters),
+/// `policies` (the agent's and the world's) and `state` (every state variable).
+/// An embed that cannot be assembled is reported under `embed_errors`.
 /// Requires authentication.
 #[utoipa::path(
     get,
     path = "/systems/{system_id}/agents/{agent_id}",
     responses(
-        (status = 200, description = "Agent retrieved successfully", body = GetAgentResponse),
+        (status = 200, description = "Agent retrieved successfully", body = GetAgentResponse,
+            headers(("ETag" = String, description = "Version of the agent"))),
+        (status = 400, description = "Unknown field or embed", body = ApiError),
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden"),
         (status = 404, description = "Agent not found"),
@@ -124,7 +193,9 @@ pub async fn create_agent(
     ),
     params(
         ("system_id" = String, Path, description = "System identifier"),
-        ("agent_id" = String, Path, description = "Agent identifier")
+        ("agent_id" = String, Path, description = "Agent identifier"),
+        ("fields" = Option<String>, Query, description = "Comma-separated top-level fields to return: `agent_id`, `status`, `labels`"),
+        ("embed" = Option<String>, Query, description = "Comma-separated sub-resources to embed: `statistics`, `policies`, `state`")
     )
 )]
 #[axum::debug_handler]
@@ -132,39 +203,167 @@ pub async fn get_agent(
     State(state): State<AppState>,
     auth: AuthUser,
     Path((system_id, agent_id)): Path<(String, String)>,
-) -> Result<Json<GetAgentResponse>, StatusCode> {
-    let user = auth.user();
+    Query(query): Query<Vec<(String, String)>>,
+) -> Result<(ETagHeader, Json<serde_json::Value>), Response> {
+    let shape = AGENT_SHAPE.parse(&query)?;
+    let user = auth.context();
     let session = state
         .session_manager
         .get_session(&system_id)
         .await
-        .ok_or(StatusCode::NOT_FOUND)?;
-    if user.user_id != session.user_id {
-        return Err(StatusCode::FORBIDDEN);
+        .ok_or(StatusCode::NOT_FOUND.into_response())?;
+    if user.principal != session.user_id {
+        return Err(StatusCode::FORBIDDEN.into_response());
     }
 
     let system = session.system.read().await;
     let status = system.get_agent_status(&agent_id).await.map_err(|e| {
         tracing::error!("Failed to get agent details: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
     })?;
+    let labels = system.get_agent_labels(&agent_id).await.map_err(|e| {
+        tracing::error!("Failed to get agent labels: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    })?;
+
+    let agent = serde_json::json!(GetAgentResponse {
+        agent_id: agent_id.clone(),
+        status,
+        labels,
+    });
+    let agent = shape
+        .render(agent, |embed| agent_embed(&system, &agent_id, embed))
+        .await;
+    Ok((etag_header(session.versions.agent(&agent_id)), Json(agent)))
+}
+
+/// Assembles the `embed` sub-resource of an agent, see [`AGENT_SHAPE`]
+async fn agent_embed(
+    system: &System,
+    agent_id: &str,
+    embed: String,
+) -> Result<serde_json::Value, String> {
+    match embed.as_str() {
+        "statistics" => {
+            let scale = system
+                .get_scale_status(agent_id)
+                .await
+                .map_err(|e| e.to_string())?;
+            let status = system
+                .get_agent_status(agent_id)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(serde_json::json!({
+                "instances": scale.total_count,
+                "running_instances": scale.running_count,
+                "request_queue": status.request_queue,
+                "llm_budget": status.llm_budget,
+                "response_cache": status.response_cache,
+            }))
+        }
+        "policies" => {
+            let policies = system
+                .effective_policies(agent_id)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(serde_json::json!(policies))
+        }
+        "state" => {
+            let snapshot = system
+                .get_agent_state_snapshot(agent_id)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(state_json(snapshot))
+        }
+        _ => Err(format!("unknown embed {}", embed)),
+    }
+}
+
+/// State variables as a JSON object
+pub(crate) fn state_json(
+    snapshot: HashMap<String, kairei_core::expression::Value>,
+) -> serde_json::Value {
+    snapshot
+        .into_iter()
+        .map(|(name, value)| {
+            let value = serde_json::Value::from(&event_bus::Value::from(value));
+            (name, value)
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+/// Get agent contracts
+///
+/// Lists the contracts the agent declares in its `contract` block: the parameters
+/// and the answer type of each request type it guarantees. Requests to these types
+/// are validated against the contract.
+#[utoipa::path(
+    get,
+    path = "/systems/{system_id}/agents/{agent_id}/contracts",
+    responses(
+        (status = 200, description = "Contracts retrieved successfully", body = AgentContractsResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Agent not found")
+    ),
+    params(
+        ("system_id" = String, Path, description = "System identifier"),
+        ("agent_id" = String, Path, description = "Agent identifier")
+    )
+)]
+#[axum::debug_handler]
+pub async fn get_agent_contracts(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((system_id, agent_id)): Path<(String, String)>,
+) -> Result<Json<AgentContractsResponse>, StatusCode> {
+    let session = state
+        .session_manager
+        .get_session(&system_id)
+        .await
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if auth.context().principal != session.user_id {
+        return Err(StatusCode::FORBIDDEN);
+    }
 
-    Ok(Json(GetAgentResponse { agent_id, status }))
+    let contracts = session
+        .system
+        .read()
+        .await
+        .agent_contracts(&agent_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    Ok(Json(AgentContractsResponse {
+        agent_id,
+        contracts: contracts
+            .iter()
+            .map(RequestSignature::from_contract)
+            .collect(),
+    }))
 }
 
 /// List agents
+///
+/// Agents can be filtered by labels with `?label=key:value`. When the parameter is
+/// repeated, only agents having all of the given labels are returned. `?fields=`
+/// and `?embed=` shape each agent as in [`get_agent`].
 #[utoipa::path(
     get,
     path = "/systems/{system_id}/agents",
     responses(
         (status = 200, description = "Agents listed successfully", body = ListAgentsResponse),
+        (status = 400, description = "Invalid label filter, or unknown field or embed"),
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden"),
         (status = 404, description = "System not found"),
         (status = 500, description = "Internal server error")
     ),
     params(
-        ("system_id" = String, Path, description = "System identifier")
+        ("system_id" = String, Path, description = "System identifier"),
+        ("label" = Option<String>, Query, description = "Label filter as `key:value`, may be repeated"),
+        ("fields" = Option<String>, Query, description = "Comma-separated top-level fields of each agent: `agent_id`, `status`, `labels`"),
+        ("embed" = Option<String>, Query, description = "Comma-separated sub-resources to embed in each agent: `statistics`, `policies`, `state`")
     )
 )]
 #[axum::debug_handler]
@@ -172,148 +371,225 @@ pub async fn list_agents(
     State(state): State<AppState>,
     auth: AuthUser,
     Path(system_id): Path<String>,
-) -> Result<Json<ListAgentsResponse>, StatusCode> {
-    let user = auth.user();
+    Query(query): Query<Vec<(String, String)>>,
+) -> Result<Json<serde_json::Value>, Response> {
+    let selector = parse_label_selector(&query).map_err(IntoResponse::into_response)?;
+    let shape = AGENT_SHAPE.parse(&query)?;
+    let user = auth.context();
     let session = state
         .session_manager
         .get_session(&system_id)
         .await
-        .ok_or(StatusCode::NOT_FOUND)?;
-    if user.user_id != session.user_id {
-        return Err(StatusCode::FORBIDDEN);
+        .ok_or(StatusCode::NOT_FOUND.into_response())?;
+    if user.principal != session.user_id {
+        return Err(StatusCode::FORBIDDEN.into_response());
     }
 
     let system = session.system.read().await;
-    let responses: Vec<GetAgentResponse> = system
-        .list_agents()
-        .await
-        .map_err(|e| {
-            tracing::error!("Failed to list agents: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?
-        .iter()
-        .map(|status| GetAgentResponse {
-            agent_id: status.name.clone(),
-            status: status.clone(),
-        })
-        .collect();
+    let statuses = system.list_agents_by_labels(&selector).await.map_err(|e| {
+        tracing::error!("Failed to list agents: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    })?;
+    let mut agents = Vec::with_capacity(statuses.len());
+    for status in statuses {
+        let labels = system.get_agent_labels(&status.name).await.map_err(|e| {
+            tracing::error!("Failed to get agent labels: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        })?;
+        let agent_id = status.name.clone();
+        let agent = serde_json::json!(GetAgentResponse {
+            agent_id: agent_id.clone(),
+            status,
+            labels,
+        });
+        agents.push(
+            shape
+                .render(agent, |embed| agent_embed(&system, &agent_id, embed))
+                .await,
+        );
+    }
+
+    // ListAgentsResponse と同じ形で、各エージェントを整形したもの
+    Ok(Json(serde_json::json!({ "agents": agents })))
+}
 
-    Ok(Json(ListAgentsResponse { agents: responses }))
+/// Collect `label=key:value` query parameters into a label selector
+fn parse_label_selector(query: &[(String, String)]) -> Result<HashMap<String, String>, StatusCode> {
+    let mut selector = HashMap::new();
+    for (_, filter) in query.iter().filter(|(name, _)| name == "label") {
+        let (key, value) = filter.split_once(':').ok_or_else(|| {
+            tracing::error!("Invalid label filter, expected key:value: {}", filter);
+            StatusCode::BAD_REQUEST
+        })?;
+        selector.insert(key.to_string(), value.to_string());
+    }
+    validate_labels(&selector).map_err(|e| {
+        tracing::error!("Invalid label filter: {}", e);
+        StatusCode::BAD_REQUEST
+    })?;
+    Ok(selector)
 }
 
 /// Start agent
+///
+/// With `If-Match`, the agent is only changed when its version is unchanged.
 #[utoipa::path(
     post,
     path = "/systems/{system_id}/agents/{agent_id}/start",
     responses(
-        (status = 200, description = "Agent started successfully"),
+        (status = 200, description = "Agent started successfully",
+            headers(("ETag" = String, description = "New version of the agent"))),
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden"),
         (status = 404, description = "Agent not found"),
+        (status = 412, description = "The agent changed since the given ETag"),
         (status = 500, description = "Internal server error")
     ),
     params(
         ("system_id" = String, Path, description = "System identifier"),
-        ("agent_id" = String, Path, description = "Agent identifier")
+        ("agent_id" = String, Path, description = "Agent identifier"),
+        ("If-Match" = Option<String>, Header, description = "ETag of the agent")
     )
 )]
 #[axum::debug_handler]
 pub async fn start_agent(
     State(state): State<AppState>,
     auth: AuthAdmin,
+    headers: HeaderMap,
     Path((system_id, agent_id)): Path<(String, String)>,
-) -> Result<(), StatusCode> {
-    if !auth.user().is_admin() {
+) -> Result<ETagHeader, StatusCode> {
+    if !auth.context().is_admin() {
         return Err(StatusCode::FORBIDDEN);
     }
+    let precondition = precondition(&state, &headers, false)?;
 
     let session = state
         .session_manager
         .get_session(&system_id)
         .await
         .ok_or(StatusCode::NOT_FOUND)?;
+    let version = session
+        .versions
+        .update_agent(&agent_id, &precondition)
+        .await
+        .map_err(precondition_failed)?;
     let system = session.system.write().await;
     system.start_agent(&agent_id).await.map_err(|e| {
         tracing::error!("Failed to start agent: {}", e);
         StatusCode::INTERNAL_SERVER_ERROR
     })?;
+    state.session_manager.lifecycle.publish(
+        &session.user_id,
+        LifecycleEvent::agent(LifecycleEventKind::AgentStarted, &system_id, &agent_id),
+    );
 
-    Ok(())
+    Ok(etag_header(version))
 }
 
 /// Shutdown agent
+///
+/// With `If-Match`, the agent is only changed when its version is unchanged.
+/// The server may require `If-Match` for this operation.
 #[utoipa::path(
     post,
     path = "/systems/{system_id}/agents/{agent_id}/stop",
     responses(
-        (status = 200, description = "Agent stopped successfully"),
+        (status = 200, description = "Agent stopped successfully",
+            headers(("ETag" = String, description = "New version of the agent"))),
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden"),
         (status = 404, description = "Agent not found"),
+        (status = 412, description = "The agent changed since the given ETag"),
+        (status = 428, description = "The server requires If-Match"),
         (status = 500, description = "Internal server error")
     ),
     params(
         ("system_id" = String, Path, description = "System identifier"),
-        ("agent_id" = String, Path, description = "Agent identifier")
+        ("agent_id" = String, Path, description = "Agent identifier"),
+        ("If-Match" = Option<String>, Header, description = "ETag of the agent")
     )
 )]
 #[axum::debug_handler]
 pub async fn stop_agent(
     State(state): State<AppState>,
     auth: AuthAdmin,
+    headers: HeaderMap,
     Path((system_id, agent_id)): Path<(String, String)>,
-) -> Result<(), StatusCode> {
-    if !auth.user().is_admin() {
+) -> Result<ETagHeader, StatusCode> {
+    if !auth.context().is_admin() {
         return Err(StatusCode::FORBIDDEN);
     }
+    let precondition = precondition(&state, &headers, true)?;
 
     let session = state
         .session_manager
         .get_session(&system_id)
         .await
         .ok_or(StatusCode::NOT_FOUND)?;
+    let version = session
+        .versions
+        .update_agent(&agent_id, &precondition)
+        .await
+        .map_err(precondition_failed)?;
     let system = session.system.write().await;
     system.stop_agent(&agent_id).await.map_err(|e| {
         tracing::error!("Failed to shutdown agent: {}", e);
         StatusCode::INTERNAL_SERVER_ERROR
     })?;
+    state.session_manager.lifecycle.publish(
+        &session.user_id,
+        LifecycleEvent::agent(LifecycleEventKind::AgentStopped, &system_id, &agent_id),
+    );
 
-    Ok(())
+    Ok(etag_header(version))
 }
 
 /// Scale up agent
+///
+/// With `If-Match`, the agent is only changed when its version is unchanged.
 #[utoipa::path(
     post,
     path = "/systems/{system_id}/agents/{agent_id}/scale-up",
     request_body = ScaleUpAgentRequest,
     responses(
-        (status = 200, description = "Agent scaled up successfully"),
+        (status = 200, description = "Agent scaled up successfully",
+            headers(("ETag" = String, description = "New version of the agent"))),
+        (status = 400, description = "A field of the body is invalid", body = ApiError),
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden"),
         (status = 404, description = "Agent not found"),
+        (status = 412, description = "The agent changed since the given ETag"),
         (status = 500, description = "Internal server error")
     ),
     params(
         ("system_id" = String, Path, description = "System identifier"),
-        ("agent_id" = String, Path, description = "Agent identifier")
+        ("agent_id" = String, Path, description = "Agent identifier"),
+        ("If-Match" = Option<String>, Header, description = "ETag of the agent")
     )
 )]
 #[axum::debug_handler]
 pub async fn scale_up_agent(
     State(state): State<AppState>,
     auth: AuthAdmin,
+    headers: HeaderMap,
     Path((system_id, agent_id)): Path<(String, String)>,
-    Json(payload): Json<ScaleUpAgentRequest>,
-) -> Result<(), StatusCode> {
-    if !auth.user().is_admin() {
+    ValidatedJson(payload): ValidatedJson<ScaleUpAgentRequest>,
+) -> Result<ETagHeader, StatusCode> {
+    if !auth.context().is_admin() {
         return Err(StatusCode::FORBIDDEN);
     }
+    let precondition = precondition(&state, &headers, false)?;
 
     let session = state
         .session_manager
         .get_session(&system_id)
         .await
         .ok_or(StatusCode::NOT_FOUND)?;
+    let version = session
+        .versions
+        .update_agent(&agent_id, &precondition)
+        .await
+        .map_err(precondition_failed)?;
     let system = session.system.write().await;
     let metadata = payload
         .options
@@ -327,43 +603,63 @@ pub async fn scale_up_agent(
             tracing::error!("Failed to scale up agent: {}", e);
             StatusCode::INTERNAL_SERVER_ERROR
         })?;
+    state.session_manager.lifecycle.publish(
+        &session.user_id,
+        LifecycleEvent::agent(LifecycleEventKind::AgentScaledUp, &system_id, &agent_id)
+            .with_instances(payload.instances),
+    );
 
-    Ok(())
+    Ok(etag_header(version))
 }
 
 /// Scale down agent
+///
+/// With `If-Match`, the agent is only changed when its version is unchanged.
+/// The server may require `If-Match` for this operation.
 #[utoipa::path(
     post,
     path = "/systems/{system_id}/agents/{agent_id}/scale-down",
     request_body = ScaleDownAgentRequest,
     responses(
-        (status = 200, description = "Agent scaled down successfully"),
+        (status = 200, description = "Agent scaled down successfully",
+            headers(("ETag" = String, description = "New version of the agent"))),
+        (status = 400, description = "A field of the body is invalid", body = ApiError),
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden"),
         (status = 404, description = "Agent not found"),
+        (status = 412, description = "The agent changed since the given ETag"),
+        (status = 428, description = "The server requires If-Match"),
         (status = 500, description = "Internal server error")
     ),
     params(
         ("system_id" = String, Path, description = "System identifier"),
-        ("agent_id" = String, Path, description = "Agent identifier")
+        ("agent_id" = String, Path, description = "Agent identifier"),
+        ("If-Match" = Option<String>, Header, description = "ETag of the agent")
     )
 )]
 #[axum::debug_handler]
 pub async fn scale_down_agent(
     State(state): State<AppState>,
     auth: AuthAdmin,
+    headers: HeaderMap,
     Path((system_id, agent_id)): Path<(String, String)>,
-    Json(payload): Json<ScaleDownAgentRequest>,
-) -> Result<(), StatusCode> {
-    if !auth.user().is_admin() {
+    ValidatedJson(payload): ValidatedJson<ScaleDownAgentRequest>,
+) -> Result<ETagHeader, StatusCode> {
+    if !auth.context().is_admin() {
         return Err(StatusCode::FORBIDDEN);
     }
+    let precondition = precondition(&state, &headers, true)?;
 
     let session = state
         .session_manager
         .get_session(&system_id)
         .await
         .ok_or(StatusCode::NOT_FOUND)?;
+    let version = session
+        .versions
+        .update_agent(&agent_id, &precondition)
+        .await
+        .map_err(precondition_failed)?;
     let system = session.system.write().await;
     let metadata = payload
         .options
@@ -377,11 +673,120 @@ pub async fn scale_down_agent(
             tracing::error!("Failed to scale down agent: {}", e);
             StatusCode::INTERNAL_SERVER_ERROR
         })?;
+    state.session_manager.lifecycle.publish(
+        &session.user_id,
+        LifecycleEvent::agent(LifecycleEventKind::AgentScaledDown, &system_id, &agent_id)
+            .with_instances(payload.instances),
+    );
+
+    Ok(etag_header(version))
+}
+
+/// Operate on many agents at once
+///
+/// Applies `operation` to every agent `selector` picks, at most `parallelism` at a
+/// time, and reports the outcome for each agent with a summary. A failure does not
+/// stop the other agents unless `atomic` is set: then no operation starts after the
+/// first failure and the applied ones are undone. Only `start` and `stop` can be
+/// undone, so other operations are rejected with 400 in atomic mode.
+/// Requires authentication with admin role.
+#[utoipa::path(
+    post,
+    path = "/systems/{system_id}/agents:batch",
+    request_body = BatchAgentsRequest,
+    responses(
+        (status = 200, description = "Batch applied, with the outcome for each agent", body = BatchReport),
+        (status = 400, description = "A field of the body is invalid (`ApiError`), the glob is invalid, or the operation cannot run atomically"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "System not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("system_id" = String, Path, description = "System identifier")
+    )
+)]
+#[axum::debug_handler]
+pub async fn batch_agents(
+    State(state): State<AppState>,
+    auth: AuthAdmin,
+    Path(system_id): Path<String>,
+    ValidatedJson(payload): ValidatedJson<BatchAgentsRequest>,
+) -> Result<Json<BatchReport>, StatusCode> {
+    if !auth.context().is_admin() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let session = state
+        .session_manager
+        .get_session(&system_id)
+        .await
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let batch = AgentBatch::from(payload);
+    let system = session.system.write().await;
+    let report = match system.run_agent_batch(&batch).await {
+        Ok(report) => report,
+        Err(e @ SystemError::InvalidBatch(_)) => {
+            tracing::error!("Invalid agent batch: {}", e);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        Err(e) => {
+            tracing::error!("Failed to run agent batch: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let kind = match batch.operation {
+        BatchOperation::Start => Some(LifecycleEventKind::AgentStarted),
+        BatchOperation::Stop => Some(LifecycleEventKind::AgentStopped),
+        BatchOperation::ScaleUp => Some(LifecycleEventKind::AgentScaledUp),
+        BatchOperation::ScaleDown => Some(LifecycleEventKind::AgentScaledDown),
+        BatchOperation::Reload => None,
+    };
+    // 取り消された操作はエージェントを変えていない
+    for result in report
+        .results
+        .iter()
+        .filter(|result| result.outcome == BatchOutcome::Succeeded)
+    {
+        let _ = session
+            .versions
+            .update_agent(&result.agent, &IfMatch::Absent)
+            .await;
+        if let Some(kind) = kind {
+            let event = LifecycleEvent::agent(kind, &system_id, &result.agent);
+            let event = match batch.operation {
+                BatchOperation::ScaleUp | BatchOperation::ScaleDown => {
+                    event.with_instances(batch.instances)
+                }
+                _ => event,
+            };
+            state
+                .session_manager
+                .lifecycle
+                .publish(&session.user_id, event);
+        }
+    }
 
-    Ok(())
+    Ok(Json(report))
 }
 
 /// Request agent
+///
+/// The payload holds the request parameters. They are converted to the parameter
+/// types of the agent's answer handler, e.g. `"5s"` or `5000` for a `Duration`.
+/// When the agent declares a contract for the request type, a missing, undeclared
+/// or mistyped parameter is rejected with 400. Parameters that violate the `where`
+/// constraints of the handler are rejected with 422, listing every violation.
+///
+/// An agent listed in the `conversations` of the system config answers in
+/// conversational mode: its think calls see the prior requests and answers of
+/// this session, up to the configured limits.
+///
+/// With the `X-Kairei-Profile: true` header the response also carries the timing
+/// breakdown of the request in `profile`: dispatch wait, each statement, each
+/// provider call split into prompt assembly (per plugin), network and processing,
+/// and serialization.
 #[utoipa::path(
     post,
     path = "/systems/{system_id}/agents/{agent_id}/request",
@@ -390,55 +795,77 @@ pub async fn scale_down_agent(
         (status = 200, description = "Request sent successfully", body = SendRequestAgentResponse),
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden"),
+        (status = 400, description = "A field of the body is invalid (`ApiError`), or the parameters violate the request's contract", body = ParameterErrorResponse),
         (status = 404, description = "Agent not found"),
+        (status = 422, description = "A parameter does not fit the handler's type (`ParameterErrorResponse`), or violates its `where` constraint (`ValidationErrorResponse`)", body = ParameterErrorResponse),
         (status = 500, description = "Internal server error")
     ),
     params(
         ("system_id" = String, Path, description = "System identifier"),
-        ("agent_id" = String, Path, description = "Agent identifier")
+        ("agent_id" = String, Path, description = "Agent identifier"),
+        ("X-Kairei-Profile" = Option<String>, Header, description = "`true` to return the timing breakdown of the request")
     )
 )]
 #[axum::debug_handler]
 pub async fn request_agent(
     State(state): State<AppState>,
     auth: AuthUser,
+    headers: HeaderMap,
     Path((system_id, agent_id)): Path<(String, String)>,
-    Json(payload): Json<SendRequestAgentRequest>,
-) -> Result<Json<SendRequestAgentResponse>, StatusCode> {
-    let user = auth.user();
+    ValidatedJson(payload): ValidatedJson<SendRequestAgentRequest>,
+) -> Result<Json<SendRequestAgentResponse>, Response> {
+    let user = auth.context();
     let session = state
         .session_manager
         .get_session(&system_id)
         .await
-        .ok_or(StatusCode::NOT_FOUND)?;
-    if user.user_id != session.user_id {
-        return Err(StatusCode::FORBIDDEN);
+        .ok_or(StatusCode::NOT_FOUND.into_response())?;
+    if user.principal != session.user_id {
+        return Err(StatusCode::FORBIDDEN.into_response());
     }
+    let conversation = session
+        .system_config
+        .conversations
+        .get(&agent_id)
+        .cloned()
+        .map(|config| (config, session.conversations.clone()));
     let system_clone = session.system.clone();
     drop(session);
 
-    let request_id = uuid::Uuid::new_v4();
-    let request = event_bus::Event::request_builder()
-        .request_type(&payload.request_type)
-        .requester(&user.user_id)
-        .responder(&agent_id)
-        .request_id(&request_id.to_string())
-        .build()
-        .map_err(|e| {
-            tracing::error!("Failed to build request: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+    let history = conversation
+        .as_ref()
+        .map(|(_, conversations)| conversations.history(&agent_id))
+        .unwrap_or_default();
+    let request = agent_request(
+        &system_clone,
+        &user.principal,
+        &headers,
+        &agent_id,
+        &payload,
+        history,
+    )
+    .await?;
     let request_clone = request.clone();
 
     let (tx, rx) = tokio::sync::oneshot::channel();
 
     tokio::spawn(async move {
         let system = system_clone.read().await;
-        match system.send_request(request_clone).await {
-            Ok(result) => {
+        match system.send_request_with_profile(request_clone).await {
+            Ok((result, profile)) => {
                 // 成功時の処理
                 tracing::info!("Request succeeded: {:?}", result);
-                let _ = tx.send(result);
+                let _ = tx.send(Ok((result, profile)));
+            }
+            // 制約違反は違反の一覧とともに返す
+            Err(SystemError::ValidationFailed {
+                message,
+                violations,
+            }) => {
+                let _ = tx.send(Err(ValidationErrorResponse {
+                    error: message,
+                    violations,
+                }));
             }
             Err(e) => {
                 // エラー時の処理
@@ -447,13 +874,676 @@ pub async fn request_agent(
         }
     });
 
-    let value = match rx.await {
-        Ok(result) => serde_json::Value::from(&result),
+    let (value, profile) = match rx.await {
+        Ok(Err(rejected)) => {
+            return Err((StatusCode::UNPROCESSABLE_ENTITY, Json(rejected)).into_response());
+        }
+        Ok(Ok((result, profile))) => {
+            let value = serde_json::Value::from(&result);
+            if let Some((config, conversations)) = &conversation {
+                let turn = conversation_turn(&payload.request_type, &payload.payload, &value);
+                conversations.record(&agent_id, turn, config);
+            }
+            (value, profile)
+        }
         Err(_) => {
             tracing::error!("Failed to receive response from task");
-            serde_json::Value::Null
+            (serde_json::Value::Null, None)
+        }
+    };
+
+    Ok(Json(SendRequestAgentResponse { value, profile }))
+}
+
+/// Request agent and stream the answer
+///
+/// Sends the request as `request_agent` does and streams the answer as server-sent
+/// events. Each event is named after the `type` of the `StreamEvent` it carries as
+/// JSON: `token` for every `yield` of the answer handler, `tool_call_start` and
+/// `tool_call_args_delta` for every tool call, then `usage` and `done` with the
+/// final answer. A request that fails, including one whose parameters violate the
+/// `where` constraints of the handler, ends the stream with `error`.
+#[utoipa::path(
+    post,
+    path = "/systems/{system_id}/agents/{agent_id}/request/stream",
+    request_body = SendRequestAgentRequest,
+    responses(
+        (status = 200, description = "Server-sent events of the answer", body = StreamEvent, content_type = "text/event-stream"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 400, description = "A field of the body is invalid (`ApiError`), or the parameters violate the request's contract", body = ParameterErrorResponse),
+        (status = 404, description = "Agent not found"),
+        (status = 422, description = "A parameter does not fit the handler's type", body = ParameterErrorResponse),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("system_id" = String, Path, description = "System identifier"),
+        ("agent_id" = String, Path, description = "Agent identifier")
+    )
+)]
+pub async fn stream_request_agent(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    headers: HeaderMap,
+    Path((system_id, agent_id)): Path<(String, String)>,
+    ValidatedJson(payload): ValidatedJson<SendRequestAgentRequest>,
+) -> Result<Sse<impl Stream<Item = Result<sse::Event, Infallible>>>, Response> {
+    let user = auth.context();
+    let session = state
+        .session_manager
+        .get_session(&system_id)
+        .await
+        .ok_or(StatusCode::NOT_FOUND.into_response())?;
+    if user.principal != session.user_id {
+        return Err(StatusCode::FORBIDDEN.into_response());
+    }
+    let conversation = session
+        .system_config
+        .conversations
+        .get(&agent_id)
+        .cloned()
+        .map(|config| (config, session.conversations.clone()));
+    let system = session.system.clone();
+    drop(session);
+
+    let history = conversation
+        .as_ref()
+        .map(|(_, conversations)| conversations.history(&agent_id))
+        .unwrap_or_default();
+    let request = agent_request(
+        &system,
+        &user.principal,
+        &headers,
+        &agent_id,
+        &payload,
+        history,
+    )
+    .await?;
+    let responses = system
+        .read()
+        .await
+        .send_request_streaming(request)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to request agent: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        })?;
+
+    let events = stream_events(responses).filter_map(move |stream_event| {
+        // 会話モードでは最終の応答を履歴に残す
+        if let (StreamEvent::Done { response }, Some((config, conversations))) =
+            (&stream_event, &conversation)
+        {
+            let turn = conversation_turn(&payload.request_type, &payload.payload, response);
+            conversations.record(&agent_id, turn, config);
+        }
+        futures::future::ready(stream_sse_event(&stream_event).map(Ok))
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+fn stream_sse_event(stream_event: &StreamEvent) -> Option<sse::Event> {
+    sse::Event::default()
+        .event(stream_event.kind())
+        .json_data(stream_event)
+        .inspect_err(|e| tracing::error!("Failed to encode stream event: {}", e))
+        .ok()
+}
+
+/// The request event for `payload`, its parameters converted to the types of the
+/// agent's answer handler or checked against its contract
+async fn agent_request(
+    system: &RwLock<System>,
+    principal: &str,
+    headers: &HeaderMap,
+    agent_id: &str,
+    payload: &SendRequestAgentRequest,
+    history: Vec<ConversationTurn>,
+) -> Result<event_bus::Event, Response> {
+    let contract = system
+        .read()
+        .await
+        .request_contract(agent_id, &payload.request_type)
+        .await;
+    let parameters = match contract {
+        Some(contract) => coercion::coerce_contract_parameters(&payload.payload, &contract)
+            .map_err(|e| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    Json(ParameterErrorResponse::from(e)),
+                )
+                    .into_response()
+            })?,
+        None => {
+            let schema = system
+                .read()
+                .await
+                .request_parameter_schema(agent_id, &payload.request_type)
+                .await;
+            coerce_payload(&payload.payload, schema.as_ref())?
+        }
+    };
+
+    let request_id = uuid::Uuid::new_v4();
+    let builder = event_bus::Event::request_builder()
+        .request_type(&payload.request_type)
+        .requester(principal)
+        .responder(agent_id)
+        .request_id(&request_id.to_string())
+        .parameters(parameters)
+        .request_context(
+            &RequestContext::new(Some(principal.to_string()), preferred_locale(headers))
+                .with_conversation(history),
+        );
+    let builder = if profile_requested(headers) {
+        builder.profile()
+    } else {
+        builder
+    };
+    builder.build().map_err(|e| {
+        tracing::error!("Failed to build request: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    })
+}
+
+/// A request and its answer as kept in the conversation
+fn conversation_turn(
+    request_type: &str,
+    payload: &serde_json::Value,
+    answer: &serde_json::Value,
+) -> ConversationTurn {
+    let text = |value: &serde_json::Value| match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    ConversationTurn {
+        user: format!("{} {}", request_type, text(payload)),
+        agent: text(answer),
+    }
+}
+
+/// Evaluate a debug expression
+///
+/// Evaluates a single DSL expression against a snapshot of the agent's state and
+/// returns its value and type. The agent is never changed: assignments, `emit`,
+/// `think` and `request` are rejected before evaluation.
+/// Requires authentication with admin role.
+#[utoipa::path(
+    post,
+    path = "/systems/{system_id}/agents/{agent_id}/debug/eval",
+    request_body = DebugEvalRequest,
+    responses(
+        (status = 200, description = "Expression evaluated", body = DebugEvalResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Agent not found"),
+        (status = 408, description = "Evaluation exceeded the time limit", body = DebugEvalErrorResponse),
+        (status = 422, description = "The expression was rejected or failed", body = DebugEvalErrorResponse),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("system_id" = String, Path, description = "System identifier"),
+        ("agent_id" = String, Path, description = "Agent identifier")
+    )
+)]
+#[axum::debug_handler]
+pub async fn debug_eval_agent(
+    State(state): State<AppState>,
+    auth: AuthAdmin,
+    Path((system_id, agent_id)): Path<(String, String)>,
+    Json(payload): Json<DebugEvalRequest>,
+) -> Result<Json<DebugEvalResponse>, Response> {
+    if !auth.context().is_admin() {
+        return Err(StatusCode::FORBIDDEN.into_response());
+    }
+
+    let session = state
+        .session_manager
+        .get_session(&system_id)
+        .await
+        .ok_or(StatusCode::NOT_FOUND.into_response())?;
+    let system = session.system.read().await;
+
+    match system.eval_expression(&agent_id, &payload.expression).await {
+        Ok(evaluation) => Ok(Json(DebugEvalResponse {
+            value: serde_json::Value::from(&event_bus::Value::from(evaluation.value)),
+            type_name: evaluation.type_info.to_string(),
+        })),
+        Err(SystemError::Agent(AgentError::AgentNotFound { .. })) => {
+            Err(StatusCode::NOT_FOUND.into_response())
+        }
+        Err(SystemError::DebugEval(e)) => {
+            let status = match e {
+                DebugEvalError::Timeout(_) => StatusCode::REQUEST_TIMEOUT,
+                _ => StatusCode::UNPROCESSABLE_ENTITY,
+            };
+            Err((
+                status,
+                Json(DebugEvalErrorResponse {
+                    error: e.to_string(),
+                }),
+            )
+                .into_response())
+        }
+        Err(e) => {
+            tracing::error!("Failed to evaluate debug expression: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
         }
+    }
+}
+
+/// Test a handler of an agent
+///
+/// Fires a synthetic event at a single observe or react handler and returns what it
+/// did: the state variables it changed, the events it emitted and the value of its
+/// block. The handler runs in an isolated context against a copy of the agent's
+/// state, with a mock provider answering `think` from `mock_responses` and no
+/// memory, so nothing is committed to the agent or the system.
+/// Requires authentication with admin role.
+#[utoipa::path(
+    post,
+    path = "/systems/{system_id}/agents/{agent_id}/handlers/{event}/test",
+    request_body = TestHandlerRequest,
+    responses(
+        (status = 200, description = "Handler ran", body = TestHandlerResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Agent or handler not found"),
+        (status = 408, description = "The handler exceeded the time limit", body = TestHandlerErrorResponse),
+        (status = 422, description = "Invalid payload, or the handler could not run", body = TestHandlerErrorResponse),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("system_id" = String, Path, description = "System identifier"),
+        ("agent_id" = String, Path, description = "Agent identifier"),
+        ("event" = String, Path, description = "Event type the handler is declared for")
+    )
+)]
+#[axum::debug_handler]
+pub async fn test_agent_handler(
+    State(state): State<AppState>,
+    auth: AuthAdmin,
+    Path((system_id, agent_id, event)): Path<(String, String, String)>,
+    Json(payload): Json<TestHandlerRequest>,
+) -> Result<Json<TestHandlerResponse>, Response> {
+    if !auth.context().is_admin() {
+        return Err(StatusCode::FORBIDDEN.into_response());
+    }
+
+    let session = state
+        .session_manager
+        .get_session(&system_id)
+        .await
+        .ok_or(StatusCode::NOT_FOUND.into_response())?;
+    let system = session.system.read().await;
+
+    let schema = system.event_parameter_schema(&event).await;
+    let test = HandlerTest {
+        parameters: coerce_payload(&payload.payload, schema.as_ref())?,
+        kind: payload.kind,
+        mock_responses: payload.mock_responses,
     };
+    match system.test_handler(&agent_id, &event, test).await {
+        Ok(report) => Ok(Json(TestHandlerResponse::from(report))),
+        Err(SystemError::Ast(ASTError::ASTNotFound(_)))
+        | Err(SystemError::HandlerTest(HandlerTestError::HandlerNotFound { .. })) => {
+            Err(StatusCode::NOT_FOUND.into_response())
+        }
+        Err(SystemError::HandlerTest(e)) => {
+            let status = match e {
+                HandlerTestError::Timeout(_) => StatusCode::REQUEST_TIMEOUT,
+                _ => StatusCode::UNPROCESSABLE_ENTITY,
+            };
+            Err((
+                status,
+                Json(TestHandlerErrorResponse {
+                    error: e.to_string(),
+                }),
+            )
+                .into_response())
+        }
+        Err(e) => {
+            tracing::error!("Failed to test handler: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+/// Update the config of an agent
+///
+/// Replaces the values the agent reads as `config.<name>`, also in its scaled
+/// instances. The values are checked against the agent's `config { ... }` block,
+/// and only agents whose `agent_configs` entry sets `hot_reload` accept them.
+/// Requires authentication with admin role.
+#[utoipa::path(
+    put,
+    path = "/systems/{system_id}/agents/{agent_id}/config",
+    request_body = UpdateAgentConfigRequest,
+    responses(
+        (status = 204, description = "Config updated"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Agent not found"),
+        (status = 409, description = "The agent does not allow config reloads", body = AgentConfigErrorResponse),
+        (status = 422, description = "The values do not match the declared config", body = AgentConfigErrorResponse),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("system_id" = String, Path, description = "System identifier"),
+        ("agent_id" = String, Path, description = "Agent identifier")
+    )
+)]
+#[axum::debug_handler]
+pub async fn update_agent_config(
+    State(state): State<AppState>,
+    auth: AuthAdmin,
+    Path((system_id, agent_id)): Path<(String, String)>,
+    Json(payload): Json<UpdateAgentConfigRequest>,
+) -> Result<StatusCode, Response> {
+    if !auth.context().is_admin() {
+        return Err(StatusCode::FORBIDDEN.into_response());
+    }
+
+    let session = state
+        .session_manager
+        .get_session(&system_id)
+        .await
+        .ok_or(StatusCode::NOT_FOUND.into_response())?;
+    let system = session.system.read().await;
+
+    match system.update_agent_config(&agent_id, payload.values).await {
+        Ok(()) => Ok(StatusCode::NO_CONTENT),
+        Err(SystemError::Ast(ASTError::ASTNotFound(_))) => {
+            Err(StatusCode::NOT_FOUND.into_response())
+        }
+        Err(SystemError::AgentConfig(e)) => {
+            let status = match e {
+                AgentConfigError::ReloadNotAllowed { .. } => StatusCode::CONFLICT,
+                _ => StatusCode::UNPROCESSABLE_ENTITY,
+            };
+            Err((
+                status,
+                Json(AgentConfigErrorResponse {
+                    error: e.to_string(),
+                }),
+            )
+                .into_response())
+        }
+        Err(e) => {
+            tracing::error!("Failed to update agent config: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+/// Get the runtime settings of an agent
+///
+/// The defaults of the agent's think calls tuned with
+/// `PATCH /systems/{system_id}/agents/{agent_id}/config`. Unset settings follow the
+/// provider config and the system config. Requires authentication.
+#[utoipa::path(
+    get,
+    path = "/systems/{system_id}/agents/{agent_id}/config",
+    responses(
+        (status = 200, description = "Settings retrieved successfully", body = AgentSettings),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Agent not found")
+    ),
+    params(
+        ("system_id" = String, Path, description = "System identifier"),
+        ("agent_id" = String, Path, description = "Agent identifier")
+    )
+)]
+#[axum::debug_handler]
+pub async fn get_agent_settings(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((system_id, agent_id)): Path<(String, String)>,
+) -> Result<Json<AgentSettings>, StatusCode> {
+    let session = state
+        .session_manager
+        .get_session(&system_id)
+        .await
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if auth.context().principal != session.user_id {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let system = session.system.read().await;
+    system
+        .agent_settings(&agent_id)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::NOT_FOUND)
+}
+
+/// Update the runtime settings of an agent
+///
+/// Changes the provider, the temperature, the maximum tokens or the LLM budget of
+/// the agent's think calls without redeploying it. A field left out keeps its
+/// setting and `null` unsets it; other fields are rejected. The settings apply from
+/// the next think call, also of the scaled instances, and a `with` block of a think
+/// still overrides them. Requires authentication with admin role.
+#[utoipa::path(
+    patch,
+    path = "/systems/{system_id}/agents/{agent_id}/config",
+    request_body = AgentSettingsPatch,
+    responses(
+        (status = 200, description = "Settings updated", body = AgentSettings),
+        (status = 400, description = "Invalid or unknown settings", body = ApiError),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Agent not found"),
+        (status = 422, description = "The settings name no registered provider", body = AgentConfigErrorResponse),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("system_id" = String, Path, description = "System identifier"),
+        ("agent_id" = String, Path, description = "Agent identifier")
+    )
+)]
+#[axum::debug_handler]
+pub async fn update_agent_settings(
+    State(state): State<AppState>,
+    auth: AuthAdmin,
+    Path((system_id, agent_id)): Path<(String, String)>,
+    ValidatedJson(payload): ValidatedJson<AgentSettingsPatch>,
+) -> Result<Json<AgentSettings>, Response> {
+    if !auth.context().is_admin() {
+        return Err(StatusCode::FORBIDDEN.into_response());
+    }
+
+    let session = state
+        .session_manager
+        .get_session(&system_id)
+        .await
+        .ok_or(StatusCode::NOT_FOUND.into_response())?;
+    let system = session.system.read().await;
+
+    match system.update_agent_settings(&agent_id, payload).await {
+        Ok(settings) => Ok(Json(settings)),
+        Err(SystemError::Ast(ASTError::ASTNotFound(_))) => {
+            Err(StatusCode::NOT_FOUND.into_response())
+        }
+        Err(SystemError::AgentConfig(e)) => Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(AgentConfigErrorResponse {
+                error: e.to_string(),
+            }),
+        )
+            .into_response()),
+        Err(e) => {
+            tracing::error!("Failed to update agent settings: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+/// Preview reloading an agent
+///
+/// Compares the agent's definition in `dsl` with the running one, without
+/// reloading it: which state variables keep their values and which are reset or
+/// added, and which handlers are added, removed or changed. An agent that is not
+/// running yet would be added. Requires authentication with admin role.
+#[utoipa::path(
+    post,
+    path = "/systems/{system_id}/agents/{agent_id}/reload/preview",
+    request_body = ReloadPreviewRequest,
+    responses(
+        (status = 200, description = "Changes of the reload", body = ReloadPlan),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "System not found"),
+        (status = 422, description = "The DSL is invalid or does not define the agent", body = ReloadPreviewErrorResponse),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("system_id" = String, Path, description = "System identifier"),
+        ("agent_id" = String, Path, description = "Agent identifier")
+    )
+)]
+#[axum::debug_handler]
+pub async fn preview_agent_reload(
+    State(state): State<AppState>,
+    auth: AuthAdmin,
+    Path((system_id, agent_id)): Path<(String, String)>,
+    Json(payload): Json<ReloadPreviewRequest>,
+) -> Result<Json<ReloadPlan>, Response> {
+    if !auth.context().is_admin() {
+        return Err(StatusCode::FORBIDDEN.into_response());
+    }
+
+    let session = state
+        .session_manager
+        .get_session(&system_id)
+        .await
+        .ok_or(StatusCode::NOT_FOUND.into_response())?;
+    let system = session.system.read().await;
+
+    match system.preview_reload(&agent_id, &payload.dsl).await {
+        Ok(plan) => Ok(Json(plan)),
+        Err(
+            e @ (SystemError::Ast(_)
+            | SystemError::Agent(_)
+            | SystemError::UnregisteredEventTypes { .. }),
+        ) => Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ReloadPreviewErrorResponse {
+                error: e.to_string(),
+            }),
+        )
+            .into_response()),
+        Err(e) => {
+            tracing::error!("Failed to preview agent reload: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+/// Get the recent logs of an agent
+///
+/// The logs the agent and its scaled instances wrote with `log.<level>(...)` at or
+/// above its log level, as many as its log config keeps. Requires authentication.
+#[utoipa::path(
+    get,
+    path = "/systems/{system_id}/agents/{agent_id}/logs",
+    responses(
+        (status = 200, description = "Logs retrieved successfully", body = AgentLogsResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Agent not found")
+    ),
+    params(
+        ("system_id" = String, Path, description = "System identifier"),
+        ("agent_id" = String, Path, description = "Agent identifier")
+    )
+)]
+#[axum::debug_handler]
+pub async fn get_agent_logs(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((system_id, agent_id)): Path<(String, String)>,
+) -> Result<Json<AgentLogsResponse>, StatusCode> {
+    let session = state
+        .session_manager
+        .get_session(&system_id)
+        .await
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if auth.context().principal != session.user_id {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let system = session.system.read().await;
+    if system.get_agent_status(&agent_id).await.is_err() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let level = system.agent_log_config(&agent_id).await.level;
+    Ok(Json(AgentLogsResponse {
+        logs: system.agent_logs(&agent_id),
+        agent_id,
+        level,
+    }))
+}
+
+/// Update the log config of an agent
+///
+/// Takes effect at once, also for its scaled instances, e.g. to log one noisy
+/// agent at `debug` while the others stay at `info`. The new config is kept when
+/// the agent restarts. Requires authentication with admin role.
+#[utoipa::path(
+    put,
+    path = "/systems/{system_id}/agents/{agent_id}/logs/config",
+    request_body = AgentLogConfig,
+    responses(
+        (status = 204, description = "Log config updated"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Agent not found")
+    ),
+    params(
+        ("system_id" = String, Path, description = "System identifier"),
+        ("agent_id" = String, Path, description = "Agent identifier")
+    )
+)]
+#[axum::debug_handler]
+pub async fn update_agent_log_config(
+    State(state): State<AppState>,
+    auth: AuthAdmin,
+    Path((system_id, agent_id)): Path<(String, String)>,
+    Json(payload): Json<AgentLogConfig>,
+) -> Result<StatusCode, StatusCode> {
+    if !auth.context().is_admin() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let session = state
+        .session_manager
+        .get_session(&system_id)
+        .await
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let system = session.system.read().await;
+    if system.get_agent_status(&agent_id).await.is_err() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    system.set_agent_log_config(&agent_id, payload).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Use the first language tag of the `Accept-Language` header as the locale.
+/// Whether the request asks for its timing breakdown with [`PROFILE_HEADER`]
+fn profile_requested(headers: &HeaderMap) -> bool {
+    headers
+        .get(PROFILE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| matches!(value.trim(), "1" | "true"))
+}
 
-    Ok(Json(SendRequestAgentResponse { value }))
+fn preferred_locale(headers: &HeaderMap) -> Option<String> {
+    let header = headers.get(ACCEPT_LANGUAGE)?.to_str().ok()?;
+    header
+        .split(',')
+        .map(|tag| tag.split(';').next().unwrap_or_default().trim())
+        .find(|tag| !tag.is_empty() && *tag != "*")
+        .map(|tag| tag.to_string())
 }
@@ -0,0 +1,241 @@
+use crate::auth::AuthAdmin;
+use crate::models::{
+    DebugEvalRequest, DebugEvalResponse, DebugSessionErrorResponse, DebugStepResponse,
+    HandlerRecordingSummary, ListRecordingsResponse, StartDebugSessionRequest,
+    StartDebugSessionResponse,
+};
+use crate::server::AppState;
+use crate::session::data::SessionData;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use kairei_core::{
+    debug_eval::DebugEvalError, debug_session::DebugSessionError, event_bus, system::SystemError,
+};
+
+/// List recorded handler runs
+///
+/// Lists the recorded runs of observe and react handlers, oldest first. Empty
+/// unless `handler_recording` is enabled in the system configuration.
+/// Requires authentication with admin role.
+#[utoipa::path(
+    get,
+    path = "/systems/{system_id}/debug/recordings",
+    responses(
+        (status = 200, description = "Recorded handler runs", body = ListRecordingsResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "System not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("system_id" = String, Path, description = "System identifier")
+    )
+)]
+#[axum::debug_handler]
+pub async fn list_handler_recordings(
+    State(state): State<AppState>,
+    auth: AuthAdmin,
+    Path(system_id): Path<String>,
+) -> Result<Json<ListRecordingsResponse>, Response> {
+    let session = admin_session(&state, &auth, &system_id).await?;
+    let system = session.system.read().await;
+    let recordings = system
+        .handler_recordings()
+        .iter()
+        .map(|recording| HandlerRecordingSummary::from(recording.as_ref()))
+        .collect();
+    Ok(Json(ListRecordingsResponse { recordings }))
+}
+
+/// Start a debug session
+///
+/// Loads a recorded handler run and pauses before the first statement of the
+/// handler. `think` is answered with the recorded provider outputs, and nothing
+/// the session does reaches the running agent.
+/// Requires authentication with admin role.
+#[utoipa::path(
+    post,
+    path = "/systems/{system_id}/debug/sessions",
+    request_body = StartDebugSessionRequest,
+    responses(
+        (status = 200, description = "Debug session started", body = StartDebugSessionResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "System or recording not found", body = DebugSessionErrorResponse),
+        (status = 408, description = "The handler exceeded the time limit", body = DebugSessionErrorResponse),
+        (status = 422, description = "The recording cannot be replayed", body = DebugSessionErrorResponse),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("system_id" = String, Path, description = "System identifier")
+    )
+)]
+#[axum::debug_handler]
+pub async fn start_debug_session(
+    State(state): State<AppState>,
+    auth: AuthAdmin,
+    Path(system_id): Path<String>,
+    Json(payload): Json<StartDebugSessionRequest>,
+) -> Result<Json<StartDebugSessionResponse>, Response> {
+    let session = admin_session(&state, &auth, &system_id).await?;
+    let system = session.system.read().await;
+    match system.debug_start(&payload.recording_id).await {
+        Ok(session_id) => Ok(Json(StartDebugSessionResponse { session_id })),
+        Err(e) => Err(debug_error_response(e)),
+    }
+}
+
+/// Step a debug session
+///
+/// Runs the next statement of the handler and returns its source text, the state
+/// and local variables it changed and the events emitted so far.
+/// Requires authentication with admin role.
+#[utoipa::path(
+    post,
+    path = "/systems/{system_id}/debug/sessions/{session_id}/step",
+    responses(
+        (status = 200, description = "Statement ran", body = DebugStepResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "System or debug session not found", body = DebugSessionErrorResponse),
+        (status = 408, description = "The statement exceeded the time limit", body = DebugSessionErrorResponse),
+        (status = 409, description = "The debug session has finished", body = DebugSessionErrorResponse),
+        (status = 422, description = "The statement failed", body = DebugSessionErrorResponse),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("system_id" = String, Path, description = "System identifier"),
+        ("session_id" = String, Path, description = "Debug session identifier")
+    )
+)]
+#[axum::debug_handler]
+pub async fn step_debug_session(
+    State(state): State<AppState>,
+    auth: AuthAdmin,
+    Path((system_id, session_id)): Path<(String, String)>,
+) -> Result<Json<DebugStepResponse>, Response> {
+    let session = admin_session(&state, &auth, &system_id).await?;
+    let system = session.system.read().await;
+    match system.debug_step(&session_id).await {
+        Ok(step) => Ok(Json(DebugStepResponse::from(step))),
+        Err(e) => Err(debug_error_response(e)),
+    }
+}
+
+/// Inspect a debug session
+///
+/// Evaluates a read-only DSL expression against the paused state and local
+/// variables of the handler, as the agent debug evaluation does.
+/// Requires authentication with admin role.
+#[utoipa::path(
+    post,
+    path = "/systems/{system_id}/debug/sessions/{session_id}/inspect",
+    request_body = DebugEvalRequest,
+    responses(
+        (status = 200, description = "Expression evaluated", body = DebugEvalResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "System or debug session not found", body = DebugSessionErrorResponse),
+        (status = 408, description = "Evaluation exceeded the time limit", body = DebugSessionErrorResponse),
+        (status = 422, description = "The expression was rejected or failed", body = DebugSessionErrorResponse),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("system_id" = String, Path, description = "System identifier"),
+        ("session_id" = String, Path, description = "Debug session identifier")
+    )
+)]
+#[axum::debug_handler]
+pub async fn inspect_debug_session(
+    State(state): State<AppState>,
+    auth: AuthAdmin,
+    Path((system_id, session_id)): Path<(String, String)>,
+    Json(payload): Json<DebugEvalRequest>,
+) -> Result<Json<DebugEvalResponse>, Response> {
+    let session = admin_session(&state, &auth, &system_id).await?;
+    let system = session.system.read().await;
+    match system.debug_inspect(&session_id, &payload.expression).await {
+        Ok(evaluation) => Ok(Json(DebugEvalResponse {
+            value: serde_json::Value::from(&event_bus::Value::from(evaluation.value)),
+            type_name: evaluation.type_info.to_string(),
+        })),
+        Err(e) => Err(debug_error_response(e)),
+    }
+}
+
+/// End a debug session
+///
+/// Requires authentication with admin role.
+#[utoipa::path(
+    delete,
+    path = "/systems/{system_id}/debug/sessions/{session_id}",
+    responses(
+        (status = 204, description = "Debug session ended"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "System or debug session not found", body = DebugSessionErrorResponse),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("system_id" = String, Path, description = "System identifier"),
+        ("session_id" = String, Path, description = "Debug session identifier")
+    )
+)]
+#[axum::debug_handler]
+pub async fn end_debug_session(
+    State(state): State<AppState>,
+    auth: AuthAdmin,
+    Path((system_id, session_id)): Path<(String, String)>,
+) -> Result<StatusCode, Response> {
+    let session = admin_session(&state, &auth, &system_id).await?;
+    let system = session.system.read().await;
+    match system.debug_end(&session_id) {
+        Ok(()) => Ok(StatusCode::NO_CONTENT),
+        Err(e) => Err(debug_error_response(e)),
+    }
+}
+
+/// 管理者だけがデバッグセッションを使える
+async fn admin_session(
+    state: &AppState,
+    auth: &AuthAdmin,
+    system_id: &str,
+) -> Result<SessionData, Response> {
+    if !auth.context().is_admin() {
+        return Err(StatusCode::FORBIDDEN.into_response());
+    }
+    state
+        .session_manager
+        .get_session(&system_id.to_string())
+        .await
+        .ok_or(StatusCode::NOT_FOUND.into_response())
+}
+
+fn debug_error_response(error: SystemError) -> Response {
+    let status = match &error {
+        SystemError::DebugSession(
+            DebugSessionError::RecordingNotFound(_) | DebugSessionError::SessionNotFound(_),
+        ) => StatusCode::NOT_FOUND,
+        SystemError::DebugSession(DebugSessionError::Finished) => StatusCode::CONFLICT,
+        SystemError::DebugSession(DebugSessionError::Timeout(_))
+        | SystemError::DebugSession(DebugSessionError::Inspect(DebugEvalError::Timeout(_)))
+        | SystemError::DebugEval(DebugEvalError::Timeout(_)) => StatusCode::REQUEST_TIMEOUT,
+        SystemError::DebugSession(_) | SystemError::DebugEval(_) => {
+            StatusCode::UNPROCESSABLE_ENTITY
+        }
+        _ => {
+            tracing::error!("Debug session request failed: {}", error);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    (
+        status,
+        Json(DebugSessionErrorResponse {
+            error: error.to_string(),
+        }),
+    )
+        .into_response()
+}
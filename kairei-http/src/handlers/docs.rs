@@ -2,9 +2,9 @@
 
 use crate::models::ExportFormat;
 use crate::models::docs::{
-    CategoryDocumentation, DocumentationErrorResponse, DocumentationMapResponse,
-    DocumentationQueryParams, DocumentationResponse, ExportDocumentationRequest,
-    ExportDocumentationResponse, ParserDocumentationResponse,
+    CategoryDocumentation, DiagnosticListResponse, DocumentationErrorResponse,
+    DocumentationMapResponse, DocumentationQueryParams, DocumentationResponse,
+    ExportDocumentationRequest, ExportDocumentationResponse, ParserDocumentationResponse,
 };
 use crate::server::AppState;
 use axum::Json;
@@ -12,6 +12,7 @@ use axum::extract::{Path, Query, State};
 use axum::http::{HeaderMap, StatusCode, header};
 use axum::response::{IntoResponse, Response};
 use kairei_core::analyzer::{DocumentationCollection, ParserCategory};
+use kairei_core::diagnostics::{self, Explanation};
 use std::collections::HashMap;
 // use std::str::FromStr;
 use tracing::{debug, warn};
@@ -347,6 +348,47 @@ pub async fn export_documentation(
     }
 }
 
+/// List all diagnostic codes
+///
+/// Returns the explanation of every code emitted by the tokenizer, parser and type checker.
+#[utoipa::path(
+    get,
+    path = "/docs/diagnostics",
+    responses(
+        (status = 200, description = "Diagnostic codes retrieved successfully", body = DiagnosticListResponse),
+        (status = 401, description = "Unauthorized")
+    )
+)]
+pub async fn list_diagnostics() -> Json<DiagnosticListResponse> {
+    let diagnostics = diagnostics::explanations();
+    Json(DiagnosticListResponse {
+        total: diagnostics.len(),
+        diagnostics,
+    })
+}
+
+/// Explain a diagnostic code
+///
+/// Returns what the diagnostic means, an example that produces it and the fix.
+#[utoipa::path(
+    get,
+    path = "/docs/diagnostics/{code}",
+    responses(
+        (status = 200, description = "Diagnostic code explained", body = Explanation),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Unknown diagnostic code")
+    ),
+    params(
+        ("code" = String, Path, description = "Diagnostic code, e.g. type.mismatch")
+    )
+)]
+pub async fn explain_diagnostic(Path(code): Path<String>) -> Result<Json<Explanation>, StatusCode> {
+    debug!("Explaining diagnostic code: {}", code);
+    diagnostics::explain(&code)
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
 // Helper function to get documentation from the system
 async fn get_documentation_from_system(
     _state: &AppState,
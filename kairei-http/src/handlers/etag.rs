@@ -0,0 +1,31 @@
+use axum::http::{HeaderMap, HeaderName, StatusCode, header::ETAG};
+
+use crate::server::AppState;
+use crate::session::versions::{IfMatch, etag};
+
+/// `ETag` header carrying a resource version
+pub type ETagHeader = [(HeaderName, String); 1];
+
+pub fn etag_header(version: u64) -> ETagHeader {
+    [(ETAG, etag(version))]
+}
+
+/// The `If-Match` precondition of a mutating request. A destructive operation
+/// without one is rejected with 428 when the server requires it.
+pub fn precondition(
+    state: &AppState,
+    headers: &HeaderMap,
+    destructive: bool,
+) -> Result<IfMatch, StatusCode> {
+    let precondition = IfMatch::from_headers(headers);
+    if destructive && state.require_if_match && precondition == IfMatch::Absent {
+        return Err(StatusCode::PRECONDITION_REQUIRED);
+    }
+    Ok(precondition)
+}
+
+/// The resource changed since the client read it
+pub fn precondition_failed(current: u64) -> StatusCode {
+    tracing::info!("If-Match does not match the current version {}", current);
+    StatusCode::PRECONDITION_FAILED
+}
@@ -1,14 +1,31 @@
 use crate::auth::AuthUser;
-use crate::models::events::{AgentRequestResponse, EventRequest, EventResponse};
+use crate::models::events::{
+    AgentRequestResponse, EventLineageNode, EventLineageQuery, EventListQuery, EventRequest,
+    EventResponse, EventStatus, ParameterErrorResponse,
+};
 use crate::server::AppState;
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
-    response::Json,
+    response::{IntoResponse, Json, Response},
+};
+use kairei_core::{
+    event::{coercion, lineage::DEFAULT_LINEAGE_DEPTH},
+    event_bus::{self, Event},
+    event_registry::{EventType, ParameterType},
 };
+use std::collections::HashMap;
 use tracing::debug;
 
+/// Events listed when the request does not set `limit`
+const DEFAULT_EVENT_LIST_LIMIT: usize = 100;
+
 /// List events
+///
+/// Returns the most recently published events of the system, oldest first, with
+/// the events that caused them. Only events still in the lineage log are listed.
+/// They are reported as `delivered`, without counting the agents that received them.
+/// Requires authentication.
 #[utoipa::path(
     get,
     path = "/systems/{system_id}/events",
@@ -17,38 +34,57 @@ use tracing::debug;
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden"),
         (status = 404, description = "System not found"),
-        (status = 500, description = "Internal server error"),
-        (status = 501, description = "Not implemented")
+        (status = 500, description = "Internal server error")
     ),
     params(
-        ("system_id" = String, Path, description = "System identifier")
+        ("system_id" = String, Path, description = "System identifier"),
+        EventListQuery
     )
 )]
 #[axum::debug_handler]
 pub async fn list_events(
-    State(_state): State<AppState>,
-    _auth: AuthUser,
+    State(state): State<AppState>,
+    auth: AuthUser,
     Path(system_id): Path<String>,
+    Query(query): Query<EventListQuery>,
 ) -> Result<Json<Vec<EventResponse>>, StatusCode> {
     debug!("list_events, system_id: {}", system_id);
-    Err(StatusCode::NOT_IMPLEMENTED)
+    let user = auth.context();
+    let session = state
+        .session_manager
+        .get_session(&system_id)
+        .await
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if user.principal != session.user_id {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let system = session.system.read().await;
+    let events = system
+        .recent_events(query.limit.unwrap_or(DEFAULT_EVENT_LIST_LIMIT))
+        .into_iter()
+        .map(EventResponse::from)
+        .collect();
+    Ok(Json(events))
 }
 
 /// Send an event
 ///
-/// Sends an event to one or more agents.
+/// Publishes a custom event with the given ID. When the event type is registered,
+/// its parameters are converted to the registered types first.
 /// Requires authentication.
 #[utoipa::path(
     post,
-    path = "/systems/{system_id}/events/{event_id}",
+    path = "/systems/{system_id}/events/{event_id}/emit",
     request_body = EventRequest,
     responses(
         (status = 200, description = "Event emitted successfully", body = EventResponse),
+        (status = 400, description = "Missing event type"),
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden"),
         (status = 404, description = "System not found"),
-        (status = 500, description = "Internal server error"),
-        (status = 501, description = "Not implemented")
+        (status = 422, description = "A parameter does not fit its registered type", body = ParameterErrorResponse),
+        (status = 500, description = "Internal server error")
     ),
     params(
         ("system_id" = String, Path, description = "System identifier"),
@@ -57,16 +93,70 @@ pub async fn list_events(
 )]
 #[axum::debug_handler]
 pub async fn emit_event(
-    State(_state): State<AppState>,
-    _auth: AuthUser,
+    State(state): State<AppState>,
+    auth: AuthUser,
     Path((system_id, event_id)): Path<(String, String)>,
-    Json(_payload): Json<EventRequest>,
-) -> Result<Json<EventResponse>, StatusCode> {
+    Json(payload): Json<EventRequest>,
+) -> Result<Json<EventResponse>, Response> {
     debug!(
         "emit_event, system_id: {}, event_id: {}",
         system_id, event_id
     );
-    Err(StatusCode::NOT_IMPLEMENTED)
+    let user = auth.context();
+    let session = state
+        .session_manager
+        .get_session(&system_id)
+        .await
+        .ok_or(StatusCode::NOT_FOUND.into_response())?;
+    if user.principal != session.user_id {
+        return Err(StatusCode::FORBIDDEN.into_response());
+    }
+    if payload.event_type.is_empty() {
+        return Err(StatusCode::BAD_REQUEST.into_response());
+    }
+
+    let system = session.system.read().await;
+    let schema = system.event_parameter_schema(&payload.event_type).await;
+    let parameters = coerce_payload(&payload.payload, schema.as_ref())?;
+    let event_type = payload.event_type;
+    system
+        .send_event(Event {
+            event_type: EventType::Custom(event_type.clone()),
+            parameters,
+            event_id: Some(event_id.clone()),
+            ..Default::default()
+        })
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to emit event: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        })?;
+
+    // 外から送ったイベントは連鎖の起点になる
+    Ok(Json(EventResponse {
+        root_event_id: event_id.clone(),
+        event_id,
+        event_type,
+        parent_event_id: None,
+        status: EventStatus::Queued,
+        delivered_to: 0,
+    }))
+}
+
+/// Convert JSON parameters to the types in `schema`. A value that does not fit
+/// is answered with 422 naming the parameter.
+pub(crate) fn coerce_payload(
+    payload: &serde_json::Value,
+    schema: Option<&HashMap<String, ParameterType>>,
+) -> Result<HashMap<String, event_bus::Value>, Response> {
+    let empty = HashMap::new();
+    coercion::coerce_parameters(payload, schema.unwrap_or(&empty)).map_err(|e| {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ParameterErrorResponse::from(e)),
+        )
+            .into_response()
+    })
 }
 
 /// Send a request to an agent
@@ -101,3 +191,48 @@ pub async fn subscribe_event(
     );
     Err(StatusCode::NOT_IMPLEMENTED)
 }
+
+/// Get the causal chain of an event
+///
+/// Returns the tree of events around the given event: its ancestors up to the
+/// chain's root, and every event they caused, each walk bounded by `max_depth`.
+/// Requires authentication.
+#[utoipa::path(
+    get,
+    path = "/systems/{system_id}/events/{event_id}/lineage",
+    responses(
+        (status = 200, description = "Lineage retrieved successfully", body = EventLineageNode),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "System or event not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("system_id" = String, Path, description = "System identifier"),
+        ("event_id" = String, Path, description = "Event identifier"),
+        EventLineageQuery
+    )
+)]
+#[axum::debug_handler]
+pub async fn get_event_lineage(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((system_id, event_id)): Path<(String, String)>,
+    Query(query): Query<EventLineageQuery>,
+) -> Result<Json<EventLineageNode>, StatusCode> {
+    let user = auth.context();
+    let session = state
+        .session_manager
+        .get_session(&system_id)
+        .await
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if user.principal != session.user_id {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let system = session.system.read().await;
+    let lineage = system
+        .get_event_lineage(&event_id, query.max_depth.unwrap_or(DEFAULT_LINEAGE_DEPTH))
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(lineage.into()))
+}
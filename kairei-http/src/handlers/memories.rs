@@ -0,0 +1,348 @@
+use crate::auth::AuthUser;
+use crate::handlers::validation::ValidatedJson;
+use crate::models::{
+    ApiError, ImportMemoriesQuery, ImportMemoriesResponse, InjectedContextResponse,
+    MemoryPinsResponse, MemoryRelevanceRequest, MemoryRelevanceResponse, PinMemoriesRequest,
+    SearchMemoriesRequest, SearchMemoriesResponse,
+};
+use crate::server::AppState;
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+};
+use kairei_core::provider::capabilities::sistence_memory::{
+    ImportMapping, SearchFilters, SistenceMemoryError,
+};
+use std::time::Duration;
+
+/// Import memories from JSONL
+///
+/// Reads the request body as JSONL, one memory item per line, and stores the
+/// items in the given memory namespace. Malformed lines are skipped and reported
+/// in the response. Query parameters override the default field mapping.
+/// Requires authentication.
+#[utoipa::path(
+    post,
+    path = "/systems/{system_id}/memories/{namespace}/import",
+    request_body(content = String, description = "JSONL, one memory item per line", content_type = "application/x-ndjson"),
+    responses(
+        (status = 200, description = "Memories imported", body = ImportMemoriesResponse),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "System not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("system_id" = String, Path, description = "System identifier"),
+        ("namespace" = String, Path, description = "Memory namespace"),
+        ImportMemoriesQuery
+    )
+)]
+#[axum::debug_handler]
+pub async fn import_memories(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((system_id, namespace)): Path<(String, String)>,
+    Query(query): Query<ImportMemoriesQuery>,
+    body: Bytes,
+) -> Result<Json<ImportMemoriesResponse>, StatusCode> {
+    let user = auth.context();
+    let session = state
+        .session_manager
+        .get_session(&system_id)
+        .await
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if user.principal != session.user_id {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let memory = {
+        let system = session.system.read().await;
+        system.sistence_memory(&namespace).await.map_err(|e| {
+            tracing::error!("Failed to get memory namespace {}: {}", namespace, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+    };
+
+    let mapping = ImportMapping::from(query);
+    let mut reader = body.as_ref();
+    let report = memory
+        .import_jsonl(&mut reader, &mapping, None)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to import memories: {}", e);
+            match e {
+                SistenceMemoryError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            }
+        })?;
+
+    Ok(Json(report.into()))
+}
+
+/// Search memories
+///
+/// Searches the given memory namespace for items matching the query and the
+/// filter tree. An invalid filter, e.g. an empty `and` or an unknown item type,
+/// is rejected with 400 and an `ApiError` naming the `filter` field.
+/// Requires authentication.
+#[utoipa::path(
+    post,
+    path = "/systems/{system_id}/memories/{namespace}/search",
+    request_body = SearchMemoriesRequest,
+    responses(
+        (status = 200, description = "Matching memories", body = SearchMemoriesResponse),
+        (status = 400, description = "A field of the body is invalid", body = ApiError),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "System not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("system_id" = String, Path, description = "System identifier"),
+        ("namespace" = String, Path, description = "Memory namespace")
+    )
+)]
+#[axum::debug_handler]
+pub async fn search_memories(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((system_id, namespace)): Path<(String, String)>,
+    ValidatedJson(payload): ValidatedJson<SearchMemoriesRequest>,
+) -> Result<Json<SearchMemoriesResponse>, StatusCode> {
+    let user = auth.context();
+    let session = state
+        .session_manager
+        .get_session(&system_id)
+        .await
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if user.principal != session.user_id {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let memory = {
+        let system = session.system.read().await;
+        system.sistence_memory(&namespace).await.map_err(|e| {
+            tracing::error!("Failed to get memory namespace {}: {}", namespace, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+    };
+
+    let filters = SearchFilters {
+        filter: payload.filter,
+        ..Default::default()
+    };
+    let items = memory
+        .search(&payload.query, Some(filters), payload.limit)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to search memories: {}", e);
+            match e {
+                SistenceMemoryError::InvalidFilter(_) => StatusCode::BAD_REQUEST,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            }
+        })?;
+
+    Ok(Json(SearchMemoriesResponse {
+        items: items.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// Explain memory relevance
+///
+/// Ranks the items of the given memory namespace for a context the way
+/// context retrieval does, and returns each item with its relevance and the
+/// score and weight of every factor of it, for debugging and tuning retrieval.
+/// Requires authentication.
+#[utoipa::path(
+    post,
+    path = "/systems/{system_id}/memories/{namespace}/relevance",
+    request_body = MemoryRelevanceRequest,
+    responses(
+        (status = 200, description = "Ranked memories with their score breakdown", body = MemoryRelevanceResponse),
+        (status = 400, description = "A field of the body is invalid", body = ApiError),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "System not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("system_id" = String, Path, description = "System identifier"),
+        ("namespace" = String, Path, description = "Memory namespace")
+    )
+)]
+#[axum::debug_handler]
+pub async fn explain_memory_relevance(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((system_id, namespace)): Path<(String, String)>,
+    ValidatedJson(payload): ValidatedJson<MemoryRelevanceRequest>,
+) -> Result<Json<MemoryRelevanceResponse>, StatusCode> {
+    let user = auth.context();
+    let session = state
+        .session_manager
+        .get_session(&system_id)
+        .await
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if user.principal != session.user_id {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let memory = {
+        let system = session.system.read().await;
+        system.sistence_memory(&namespace).await.map_err(|e| {
+            tracing::error!("Failed to get memory namespace {}: {}", namespace, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+    };
+
+    let explanations = memory
+        .explain_relevance_for_context(payload.context, payload.limit)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to explain memory relevance: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(MemoryRelevanceResponse::new(explanations)))
+}
+
+/// Pin or exclude memories for an agent
+///
+/// Pins items of the agent's memory, the namespace named after the agent, to
+/// the prompts the `sistence_memory` plugin builds for it, or excludes them,
+/// until the TTL expires. Pinned items are injected ahead of the ranked items as
+/// far as the plugin's limit allows; excluded items are never injected. Returns
+/// every pin of the agent that has not expired. Requires authentication.
+#[utoipa::path(
+    post,
+    path = "/systems/{system_id}/agents/{agent_id}/memory/pins",
+    request_body = PinMemoriesRequest,
+    responses(
+        (status = 200, description = "Pins of the agent", body = MemoryPinsResponse),
+        (status = 400, description = "A field of the body is invalid", body = ApiError),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Agent or memory item not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("system_id" = String, Path, description = "System identifier"),
+        ("agent_id" = String, Path, description = "Agent identifier")
+    )
+)]
+#[axum::debug_handler]
+pub async fn pin_agent_memories(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((system_id, agent_id)): Path<(String, String)>,
+    ValidatedJson(payload): ValidatedJson<PinMemoriesRequest>,
+) -> Result<Json<MemoryPinsResponse>, StatusCode> {
+    let user = auth.context();
+    let session = state
+        .session_manager
+        .get_session(&system_id)
+        .await
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if user.principal != session.user_id {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let memory = {
+        let system = session.system.read().await;
+        if system.get_agent_status(&agent_id).await.is_err() {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        system.sistence_memory(&agent_id).await.map_err(|e| {
+            tracing::error!("Failed to get the memory of agent {}: {}", agent_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+    };
+
+    let pins = memory
+        .pin_for_context(
+            payload.item_ids,
+            payload.mode,
+            Duration::from_secs(payload.ttl_secs),
+        )
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to pin memories: {}", e);
+            match e {
+                SistenceMemoryError::NotFound(_) => StatusCode::NOT_FOUND,
+                SistenceMemoryError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            }
+        })?;
+
+    Ok(Json(MemoryPinsResponse {
+        pins: pins.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// Get the memories injected for a request
+///
+/// Returns the ids and relevance of the memory items the `sistence_memory`
+/// plugin injected into the prompts of a request of the agent, pinned items
+/// marked. Injected items are recorded when the request is profiled or the
+/// plugin's `record_context` is set; the last 100 requests are kept.
+/// Requires authentication.
+#[utoipa::path(
+    get,
+    path = "/systems/{system_id}/agents/{agent_id}/requests/{request_id}/context",
+    responses(
+        (status = 200, description = "Injected memories", body = InjectedContextResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Agent or recorded request not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("system_id" = String, Path, description = "System identifier"),
+        ("agent_id" = String, Path, description = "Agent identifier"),
+        ("request_id" = String, Path, description = "Request identifier")
+    )
+)]
+#[axum::debug_handler]
+pub async fn get_request_context(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((system_id, agent_id, request_id)): Path<(String, String, String)>,
+) -> Result<Json<InjectedContextResponse>, StatusCode> {
+    let user = auth.context();
+    let session = state
+        .session_manager
+        .get_session(&system_id)
+        .await
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if user.principal != session.user_id {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let memory = {
+        let system = session.system.read().await;
+        if system.get_agent_status(&agent_id).await.is_err() {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        system.sistence_memory(&agent_id).await.map_err(|e| {
+            tracing::error!("Failed to get the memory of agent {}: {}", agent_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+    };
+
+    let context = memory
+        .injected_context(&request_id)
+        .await
+        .map_err(|e| match e {
+            SistenceMemoryError::NotFound(_) => StatusCode::NOT_FOUND,
+            e => {
+                tracing::error!("Failed to get the injected memories: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
+
+    Ok(Json(InjectedContextResponse::new(agent_id, context)))
+}
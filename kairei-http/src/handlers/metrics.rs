@@ -0,0 +1,39 @@
+use axum::{
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+};
+use kairei_core::provider::metrics::PrometheusEncoder;
+
+use crate::{auth::AuthAdmin, server::AppState};
+
+/// Content type of the Prometheus text exposition format
+pub const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// LLM call histograms of every system, for Prometheus to scrape
+///
+/// Latency and token usage per call, labeled by system, provider and model. Series
+/// exist only for the models configured on each provider; calls with other models
+/// share the `other` model label. Agents with `@cache` answer handlers also report
+/// their response cache hits and misses, labeled by system and agent. Requires an
+/// admin key.
+#[axum::debug_handler]
+pub async fn get_metrics(State(state): State<AppState>, _auth: AuthAdmin) -> Response {
+    let mut encoder = PrometheusEncoder::default();
+    let mut sessions = state.session_manager.all_sessions().await;
+    sessions.sort_by(|a, b| a.1.system_id.cmp(&b.1.system_id));
+    for (_, session) in sessions {
+        let system = session.system.read().await;
+        let metrics = system.llm_metrics().await;
+        encoder.add(&[("system", &session.system_id)], &metrics);
+        for (agent, stats) in system.response_cache_stats().await {
+            encoder
+                .add_response_cache(&[("system", &session.system_id), ("agent", &agent)], &stats);
+        }
+    }
+    (
+        [(header::CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)],
+        encoder.finish(),
+    )
+        .into_response()
+}
@@ -1,11 +1,24 @@
 pub mod agents;
+pub mod debug;
 pub mod docs;
+pub mod etag;
 pub mod events;
+pub mod memories;
+pub mod metrics;
+pub mod preflight;
+pub mod providers;
+pub mod shaping;
 pub mod system;
 pub mod test_helpers;
+pub mod validation;
 
 // Re-export all handlers for easier imports
 pub use agents::*;
+pub use debug::*;
 pub use docs::*;
 pub use events::*;
+pub use memories::*;
+pub use metrics::*;
+pub use preflight::*;
+pub use providers::*;
 pub use system::*;
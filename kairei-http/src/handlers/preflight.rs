@@ -0,0 +1,89 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use kairei_core::preflight::PreflightReport;
+use kairei_core::system::System;
+
+use crate::auth::AuthAdmin;
+use crate::models::{PreflightJob, PreflightRequest};
+use crate::server::AppState;
+use crate::services::preflight::PreflightJobs;
+
+/// Check a deployment without starting it
+///
+/// Validates the system config, the secrets, the DSL sources, the providers, the
+/// plugins and the event schemas of a deployment and reports every problem found,
+/// per component. Nothing is started. The secrets are those of the server.
+/// Small deployments are checked inline and answered with the report; a deployment
+/// whose sources exceed 64 KiB, or whose provider credentials are checked, is
+/// checked by a job answered with 202, see `GET /preflight/{job_id}`.
+/// Requires authentication with admin role.
+#[utoipa::path(
+    post,
+    path = "/preflight",
+    request_body = PreflightRequest,
+    responses(
+        (status = 200, description = "Deployment checked", body = PreflightReport),
+        (status = 202, description = "Preflight job started", body = PreflightJob),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    )
+)]
+#[axum::debug_handler]
+pub async fn run_preflight(
+    State(state): State<AppState>,
+    auth: AuthAdmin,
+    Json(payload): Json<PreflightRequest>,
+) -> Result<Response, StatusCode> {
+    if !auth.context().is_admin() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let mut config = payload.config;
+    config.validate_credentials.enabled = payload.validate_credentials;
+    let secret = state.session_manager.secret_config.clone();
+
+    if PreflightJobs::runs_as_job(&payload.sources, payload.validate_credentials) {
+        let job = state.preflight_jobs.submit(config, secret, payload.sources);
+        return Ok((StatusCode::ACCEPTED, Json(job)).into_response());
+    }
+    let report = System::preflight(&config, &secret, &payload.sources).await;
+    Ok(Json(report).into_response())
+}
+
+/// Get a preflight job
+///
+/// Returns the job started by `POST /preflight`, with its report once completed.
+/// Completed jobs are kept for an hour.
+/// Requires authentication with admin role.
+#[utoipa::path(
+    get,
+    path = "/preflight/{job_id}",
+    responses(
+        (status = 200, description = "Preflight job", body = PreflightJob),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Job not found")
+    ),
+    params(
+        ("job_id" = String, Path, description = "Job identifier")
+    )
+)]
+#[axum::debug_handler]
+pub async fn get_preflight_job(
+    State(state): State<AppState>,
+    auth: AuthAdmin,
+    Path(job_id): Path<String>,
+) -> Result<Json<PreflightJob>, StatusCode> {
+    if !auth.context().is_admin() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    state
+        .preflight_jobs
+        .get(&job_id)
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
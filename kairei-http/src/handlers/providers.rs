@@ -0,0 +1,139 @@
+use std::time::{Duration, Instant};
+
+use crate::auth::AuthUser;
+use crate::models::{
+    ProviderProbeResult, ProviderValidationIssue, ValidateProviderRequest, ValidateProviderResponse,
+};
+use axum::{extract::State, http::Uri, response::Json};
+use kairei_core::config::ProviderConfig;
+use kairei_core::provider::config::{
+    CollectingValidator, DefaultSuggestionGenerator, EvaluatorValidator, ProviderConfigError,
+    SuggestionGenerator, TypeCheckerValidator, config_to_map,
+};
+use tokio::net::TcpStream;
+
+use crate::server::AppState;
+
+/// Timeout for the endpoint connectivity probe
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Validate a provider configuration
+///
+/// Runs the schema and provider-specific validators over the configuration,
+/// collecting every error and warning instead of stopping at the first one.
+/// When `probe` is set, also checks that the configured endpoint accepts connections.
+#[utoipa::path(
+    post,
+    path = "/providers/validate",
+    request_body = ValidateProviderRequest,
+    responses(
+        (status = 200, description = "Provider configuration validated", body = ValidateProviderResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+#[axum::debug_handler]
+pub async fn validate_provider(
+    State(_state): State<AppState>,
+    _auth: AuthUser,
+    Json(payload): Json<ValidateProviderRequest>,
+) -> Json<ValidateProviderResponse> {
+    let config = payload.config;
+    let config_map = config_to_map(&config);
+
+    // Type checker covers the schema, evaluator covers provider-specific values
+    let mut collector = TypeCheckerValidator.validate_collecting(&config_map);
+    let evaluated = EvaluatorValidator.validate_collecting(&config_map);
+    collector.errors.extend(evaluated.errors);
+    collector.warnings.extend(evaluated.warnings);
+
+    let errors = to_issues(&config, &collector.errors);
+    let warnings = to_issues(&config, &collector.warnings);
+
+    let probe = if payload.probe {
+        Some(probe_endpoint(config.endpoint.url.as_deref()).await)
+    } else {
+        None
+    };
+
+    let valid = errors.is_empty() && probe.as_ref().is_none_or(|p| p.reachable);
+    Json(ValidateProviderResponse {
+        valid,
+        errors,
+        warnings,
+        probe,
+    })
+}
+
+fn to_issues(
+    config: &ProviderConfig,
+    errors: &[ProviderConfigError],
+) -> Vec<ProviderValidationIssue> {
+    let generator = DefaultSuggestionGenerator;
+    errors
+        .iter()
+        .map(|error| ProviderValidationIssue {
+            path: error
+                .context()
+                .and_then(|context| context.location.field.as_deref())
+                .map(|field| field_path(config, field)),
+            message: error.to_string(),
+            error_code: error.error_code(),
+            suggestion: generator.generate_suggestion(error),
+        })
+        .collect()
+}
+
+/// Map a flattened validator field back to its path in `ProviderConfig`
+fn field_path(config: &ProviderConfig, field: &str) -> String {
+    if config.provider_specific.contains_key(field) {
+        format!("provider_specific.{}", field)
+    } else if matches!(field, "temperature" | "max_tokens" | "model") {
+        format!("common_config.{}", field)
+    } else {
+        field.to_string()
+    }
+}
+
+/// Check that the endpoint accepts a TCP connection
+async fn probe_endpoint(url: Option<&str>) -> ProviderProbeResult {
+    let unreachable = |error: String| ProviderProbeResult {
+        endpoint: url.map(String::from),
+        reachable: false,
+        latency_ms: None,
+        error: Some(error),
+    };
+
+    let Some(url) = url else {
+        return unreachable("No endpoint url configured".to_string());
+    };
+    let uri = match url.parse::<Uri>() {
+        Ok(uri) => uri,
+        Err(e) => return unreachable(format!("Invalid endpoint url: {}", e)),
+    };
+    let Some(host) = uri.host() else {
+        return unreachable("Endpoint url has no host".to_string());
+    };
+    let port = uri
+        .port_u16()
+        .unwrap_or(if uri.scheme_str() == Some("http") {
+            80
+        } else {
+            443
+        });
+
+    let started = Instant::now();
+    match tokio::time::timeout(PROBE_TIMEOUT, TcpStream::connect((host, port))).await {
+        Ok(Ok(_)) => ProviderProbeResult {
+            endpoint: Some(url.to_string()),
+            reachable: true,
+            latency_ms: Some(started.elapsed().as_millis() as u64),
+            error: None,
+        },
+        Ok(Err(e)) => unreachable(format!("Connection failed: {}", e)),
+        Err(_) => unreachable(format!(
+            "Connection timed out after {}s",
+            PROBE_TIMEOUT.as_secs()
+        )),
+    }
+}
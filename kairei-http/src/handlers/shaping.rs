@@ -0,0 +1,202 @@
+//! Response shaping with `?fields=` and `?embed=`
+//!
+//! A GET handler whose resource declares a [`ResourceShape`] lets the client pick
+//! the top-level fields of the response and pull in sub-resources assembled on
+//! the server:
+//!
+//! ```text
+//! GET /systems/{system_id}/agents/{agent_id}?fields=agent_id,status&embed=statistics,state
+//! ```
+//!
+//! Embedded sub-resources are added under `embedded`, keyed by name. An embed that
+//! cannot be assembled does not fail the response: its error is reported under
+//! `embed_errors` instead. Unknown fields or embeds are rejected with 400 and the
+//! valid options.
+
+use std::future::Future;
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use serde_json::{Map, Value};
+
+use crate::models::{ApiError, FieldError};
+
+/// The fields and embeds a resource can be shaped with
+#[derive(Debug, Clone, Copy)]
+pub struct ResourceShape {
+    pub fields: &'static [&'static str],
+    pub embeds: &'static [&'static str],
+}
+
+/// `GET /systems/{system_id}`
+pub const SYSTEM_SHAPE: ResourceShape = ResourceShape {
+    fields: &[
+        "started_at",
+        "running",
+        "uptime",
+        "agent_count",
+        "running_agent_count",
+        "event_queue_size",
+        "event_subscribers",
+        "event_capacity",
+        "provider_credentials",
+    ],
+    embeds: &["statistics", "policies", "state"],
+};
+
+/// `GET /systems/{system_id}/agents/{agent_id}`, and each agent of the list
+pub const AGENT_SHAPE: ResourceShape = ResourceShape {
+    fields: &["agent_id", "status", "labels"],
+    embeds: &["statistics", "policies", "state"],
+};
+
+/// The shaping a request asked for
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Shape {
+    /// Top-level fields to keep, all when `None`
+    pub fields: Option<Vec<String>>,
+    pub embeds: Vec<String>,
+}
+
+impl ResourceShape {
+    /// Reads `fields` and `embed` from the query, comma-separated. A repeated
+    /// parameter adds to the list.
+    pub fn parse(&self, query: &[(String, String)]) -> Result<Shape, Response> {
+        let mut shape = Shape::default();
+        let mut errors = Vec::new();
+        for (name, value) in query {
+            let (valid, selected) = match name.as_str() {
+                "fields" => (self.fields, shape.fields.get_or_insert_with(Vec::new)),
+                "embed" => (self.embeds, &mut shape.embeds),
+                _ => continue,
+            };
+            for item in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                if !valid.contains(&item) {
+                    errors.push(FieldError {
+                        field: name.clone(),
+                        reason: format!("{} is not one of: {}", item, valid.join(", ")),
+                    });
+                } else if !selected.iter().any(|s| s == item) {
+                    selected.push(item.to_string());
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(shape)
+        } else {
+            Err((
+                StatusCode::BAD_REQUEST,
+                Json(ApiError::invalid_query(errors)),
+            )
+                .into_response())
+        }
+    }
+}
+
+impl Shape {
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_none() && self.embeds.is_empty()
+    }
+
+    /// `resource` restricted to the selected fields, with each embed assembled by `embed`
+    pub async fn render<F, Fut>(&self, resource: Value, embed: F) -> Value
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<Value, String>>,
+    {
+        let mut embedded = Vec::with_capacity(self.embeds.len());
+        for name in &self.embeds {
+            embedded.push((name.clone(), embed(name.clone()).await));
+        }
+        self.apply(resource, embedded)
+    }
+
+    /// `value` restricted to the selected fields, with the embeds assembled for it
+    pub fn apply(&self, value: Value, embedded: Vec<(String, Result<Value, String>)>) -> Value {
+        let Value::Object(mut object) = value else {
+            return value;
+        };
+        if let Some(fields) = &self.fields {
+            object.retain(|key, _| fields.contains(key));
+        }
+        let mut values = Map::new();
+        let mut errors = Map::new();
+        for (name, result) in embedded {
+            match result {
+                Ok(value) => values.insert(name, value),
+                Err(error) => errors.insert(name, Value::String(error)),
+            };
+        }
+        if !values.is_empty() {
+            object.insert("embedded".to_string(), Value::Object(values));
+        }
+        if !errors.is_empty() {
+            object.insert("embed_errors".to_string(), Value::Object(errors));
+        }
+        Value::Object(object)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn query(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_field_projection() {
+        let shape = AGENT_SHAPE
+            .parse(&query(&[
+                ("fields", "agent_id, labels"),
+                ("label", "team:a"),
+            ]))
+            .unwrap();
+        let agent = json!({ "agent_id": "Echo", "status": { "state": "running" }, "labels": {} });
+        assert_eq!(
+            shape.apply(agent, vec![]),
+            json!({ "agent_id": "Echo", "labels": {} })
+        );
+    }
+
+    #[test]
+    fn test_embeds_are_reported_inline() {
+        let shape = AGENT_SHAPE
+            .parse(&query(&[("embed", "state,policies")]))
+            .unwrap();
+        assert_eq!(shape.embeds, vec!["state", "policies"]);
+        let shaped = shape.apply(
+            json!({ "agent_id": "Echo" }),
+            vec![
+                ("state".to_string(), Ok(json!({ "count": 1 }))),
+                ("policies".to_string(), Err("world not found".to_string())),
+            ],
+        );
+        assert_eq!(
+            shaped,
+            json!({
+                "agent_id": "Echo",
+                "embedded": { "state": { "count": 1 } },
+                "embed_errors": { "policies": "world not found" },
+            })
+        );
+    }
+
+    #[test]
+    fn test_unknown_fields_and_embeds() {
+        assert!(
+            SYSTEM_SHAPE
+                .parse(&query(&[("fields", "running,name"), ("embed", "agents")]))
+                .is_err()
+        );
+        assert!(SYSTEM_SHAPE.parse(&query(&[])).unwrap().is_empty());
+    }
+}
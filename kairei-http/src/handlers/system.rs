@@ -1,40 +1,61 @@
 use std::collections::HashMap;
+use std::convert::Infallible;
 use std::sync::Arc;
 
 use crate::auth::{AuthAdmin, AuthUser};
+use crate::handlers::agents::state_json;
+use crate::handlers::etag::{ETagHeader, etag_header, precondition, precondition_failed};
+use crate::handlers::shaping::SYSTEM_SHAPE;
+use crate::handlers::validation::ValidatedJson;
 use crate::models::{
-    CompileSystemRequest, CompileSystemResponse, CreateSystemRequest, CreateSystemResponse,
-    ListSystemsResponse, StartSystemRequest,
+    AgentImportErrorResponse, ApiError, CompileSystemRequest, CompileSystemResponse,
+    CreateSystemRequest, CreateSystemResponse, LifecycleEvent, LifecycleEventKind,
+    LifecycleStreamGap, ListSchedulesResponse, ListSystemsResponse, StartSystemRequest,
+    SystemCatalogResponse,
 };
 use crate::server::AppState;
+use crate::services::agent_import::AgentImportError;
 use crate::session::data::SessionDataBuilder;
-use axum::extract::Path;
-use axum::http::StatusCode;
+use crate::session::lifecycle::{OwnedLifecycleEvent, ResumedLifecycleEvents};
+use axum::extract::{Path, Query};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
 use axum::{extract::State, response::Json};
+use futures::{Stream, StreamExt};
 use kairei_core::Root;
+use kairei_core::eval::context::AgentType;
 use kairei_core::system::{System, SystemStatus};
 use tokio::sync::RwLock;
+use tokio_stream::wrappers::{BroadcastStream, errors::BroadcastStreamRecvError};
 
 /// Create the system
+///
+/// `agent_sources` name agent definitions hosted elsewhere, which are fetched,
+/// parsed and type-checked now and added to the DSL when the system starts. A
+/// source that cannot be imported is answered with an [`AgentImportErrorResponse`].
 #[utoipa::path(
     post,
     path = "/systems",
     request_body = CreateSystemRequest,
     responses(
         (status = 200, description = "Create system successfully", body = CreateSystemResponse),
+        (status = 400, description = "A field of the body is invalid (`ApiError`), or an agent source is malformed", body = AgentImportErrorResponse),
         (status = 401, description = "Unauthorized"),
-        (status = 403, description = "Forbidden"),
-        (status = 500, description = "Internal server error")
+        (status = 403, description = "Forbidden, or an agent source is on a host that is not allowed", body = AgentImportErrorResponse),
+        (status = 422, description = "An imported definition does not parse or type-check", body = AgentImportErrorResponse),
+        (status = 500, description = "Internal server error"),
+        (status = 502, description = "An agent source could not be fetched", body = AgentImportErrorResponse)
     )
 )]
 #[axum::debug_handler]
 pub async fn create_system(
     State(state): State<AppState>,
     auth: AuthUser,
-    Json(payload): Json<CreateSystemRequest>,
-) -> Result<Json<CreateSystemResponse>, StatusCode> {
-    if !auth.user().is_admin() {
-        return Err(StatusCode::FORBIDDEN);
+    ValidatedJson(payload): ValidatedJson<CreateSystemRequest>,
+) -> Result<Json<CreateSystemResponse>, Response> {
+    if !auth.context().is_admin() {
+        return Err(StatusCode::FORBIDDEN.into_response());
     }
 
     let secret = state.session_manager.secret_config.clone();
@@ -42,19 +63,25 @@ pub async fn create_system(
 
     // impl create system using kairei-core with the session manager
     let system = System::new(&config, &secret).await;
+    let imported_agents = state
+        .agent_importer
+        .import(&system, &payload.agent_sources)
+        .await
+        .map_err(import_error_response)?;
 
     let session_data_builder = SessionDataBuilder::new()
         .system_config(config)
         .secret_config(secret)
-        .system(Arc::new(RwLock::new(system)));
+        .system(Arc::new(RwLock::new(system)))
+        .imported_agents(imported_agents);
 
     let (session_id, system_id) = state
         .session_manager
-        .create_session(&auth.user().user_id, session_data_builder)
+        .create_session(&auth.context().principal, session_data_builder)
         .await
         .map_err(|e| {
             tracing::error!("Failed to create system: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
         })?;
     Ok(Json(CreateSystemResponse {
         system_id,
@@ -62,22 +89,48 @@ pub async fn create_system(
     }))
 }
 
+fn import_error_response(error: AgentImportError) -> Response {
+    tracing::warn!("Failed to import agent definition: {}", error);
+    let status = match &error {
+        AgentImportError::InvalidSource { .. } | AgentImportError::RegistryNotConfigured { .. } => {
+            StatusCode::BAD_REQUEST
+        }
+        AgentImportError::HostNotAllowed { .. } => StatusCode::FORBIDDEN,
+        AgentImportError::Validation { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+        AgentImportError::FetchFailed { .. }
+        | AgentImportError::Status { .. }
+        | AgentImportError::TooLarge { .. } => StatusCode::BAD_GATEWAY,
+    };
+    (status, Json(AgentImportErrorResponse::from(error))).into_response()
+}
+
 /// Get system information
 ///
 /// Returns information about the current state of the system.
+/// The `ETag` header holds the version of the system; send it as `If-Match`
+/// when changing the system to detect a concurrent change.
+/// `?fields=` keeps only the listed top-level fields, and `?embed=` adds the
+/// listed sub-resources under `embedded`: `statistics` (LLM calls and response
+/// caches), `policies` (the world's) and `state` (the world's state variables).
+/// An embed that cannot be assembled, e.g. before the system starts, is reported
+/// under `embed_errors`.
 /// Requires authentication with admin role.
 #[utoipa::path(
     get,
     path = "/systems/{system_id}",
     responses(
-        (status = 200, description = "System retrieved successfully", body = SystemStatus),
+        (status = 200, description = "System retrieved successfully", body = SystemStatus,
+            headers(("ETag" = String, description = "Version of the system"))),
+        (status = 400, description = "Unknown field or embed", body = ApiError),
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden"),
         (status = 404, description = "System not found"),
         (status = 500, description = "Internal server error")
     ),
     params(
-        ("system_id" = String, Path, description = "System identifier")
+        ("system_id" = String, Path, description = "System identifier"),
+        ("fields" = Option<String>, Query, description = "Comma-separated top-level fields to return, e.g. `running,agent_count`"),
+        ("embed" = Option<String>, Query, description = "Comma-separated sub-resources to embed: `statistics`, `policies`, `state`")
     )
 )]
 #[axum::debug_handler]
@@ -85,23 +138,242 @@ pub async fn get_system(
     State(state): State<AppState>,
     auth: AuthAdmin,
     Path(system_id): Path<String>,
-) -> Result<Json<SystemStatus>, StatusCode> {
-    if !auth.user().is_admin() {
-        return Err(StatusCode::FORBIDDEN);
+    Query(query): Query<Vec<(String, String)>>,
+) -> Result<(ETagHeader, Json<serde_json::Value>), Response> {
+    if !auth.context().is_admin() {
+        return Err(StatusCode::FORBIDDEN.into_response());
     }
+    let shape = SYSTEM_SHAPE.parse(&query)?;
 
     // get system from session manager
     if let Some(data) = state.session_manager.get_session(&system_id).await {
         let system = data.system.read().await;
         let status = system.get_system_status().await.map_err(|e| {
             tracing::error!("Failed to get system status: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
         })?;
+        let status = shape
+            .render(serde_json::json!(status), |embed| {
+                system_embed(&system, embed)
+            })
+            .await;
         drop(system);
-        Ok(Json(status))
+        Ok((etag_header(data.versions.system()), Json(status)))
     } else {
-        Err(StatusCode::NOT_FOUND)
+        Err(StatusCode::NOT_FOUND.into_response())
+    }
+}
+
+/// Assembles the `embed` sub-resource of a system, see [`SYSTEM_SHAPE`]
+async fn system_embed(system: &System, embed: String) -> Result<serde_json::Value, String> {
+    let world = AgentType::World.to_string();
+    match embed.as_str() {
+        "statistics" => {
+            let llm_calls: Vec<serde_json::Value> = system
+                .llm_metrics()
+                .await
+                .series()
+                .iter()
+                .map(|series| {
+                    serde_json::json!({
+                        "provider": series.provider,
+                        "model": series.model,
+                        "calls": series.latency.count(),
+                        "tokens": series.tokens.sum(),
+                    })
+                })
+                .collect();
+            let response_cache: serde_json::Map<String, serde_json::Value> = system
+                .response_cache_stats()
+                .await
+                .into_iter()
+                .map(|(agent, stats)| (agent, serde_json::json!(stats)))
+                .collect();
+            Ok(serde_json::json!({
+                "llm_calls": llm_calls,
+                "response_cache": response_cache,
+            }))
+        }
+        "policies" => {
+            let world_def = system
+                .get_agent_ast(&world)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(serde_json::json!(world_def.policies))
+        }
+        "state" => {
+            let snapshot = system
+                .get_agent_state_snapshot(&world)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(state_json(snapshot))
+        }
+        _ => Err(format!("unknown embed {}", embed)),
+    }
+}
+
+/// Get the system catalog
+///
+/// Lists the agents of the system and the requests each one answers, the same
+/// catalog agents read with `list_agents()` and `agent_requests(name)`.
+#[utoipa::path(
+    get,
+    path = "/systems/{system_id}/catalog",
+    responses(
+        (status = 200, description = "Catalog retrieved successfully", body = SystemCatalogResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "System not found")
+    ),
+    params(
+        ("system_id" = String, Path, description = "System identifier")
+    )
+)]
+#[axum::debug_handler]
+pub async fn get_system_catalog(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(system_id): Path<String>,
+) -> Result<Json<SystemCatalogResponse>, StatusCode> {
+    let session = state
+        .session_manager
+        .get_session(&system_id)
+        .await
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if auth.context().principal != session.user_id {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let agents = session.system.read().await.catalog();
+    Ok(Json(SystemCatalogResponse { agents }))
+}
+
+/// List the schedules of the system
+///
+/// The `schedule` declarations of the world, with the next time each one fires
+/// and the last time it emitted its event. Times are UTC.
+#[utoipa::path(
+    get,
+    path = "/systems/{system_id}/schedules",
+    responses(
+        (status = 200, description = "Schedules retrieved successfully", body = ListSchedulesResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "System not found")
+    ),
+    params(
+        ("system_id" = String, Path, description = "System identifier")
+    )
+)]
+#[axum::debug_handler]
+pub async fn list_system_schedules(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(system_id): Path<String>,
+) -> Result<Json<ListSchedulesResponse>, StatusCode> {
+    let session = state
+        .session_manager
+        .get_session(&system_id)
+        .await
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if auth.context().principal != session.user_id {
+        return Err(StatusCode::FORBIDDEN);
     }
+
+    let schedules = session.system.read().await.schedules();
+    Ok(Json(ListSchedulesResponse { schedules }))
+}
+
+/// Stream system lifecycle events
+///
+/// Server-sent events for the systems of the caller and their agents: creation,
+/// start, stop and deletion of systems, and start, stop and scaling of agents.
+/// Each SSE event is named after its kind, carries a [`LifecycleEvent`] and has an
+/// increasing id. Only transitions after the stream opened are sent, unless a
+/// reconnecting client sends the id of the last event it received as
+/// `Last-Event-ID`: the retained events after it are replayed before live events
+/// resume. A `gap` event carrying a [`LifecycleStreamGap`] tells the client that
+/// events were lost, because they were no longer retained or the stream fell behind.
+#[utoipa::path(
+    get,
+    path = "/systems/events/stream",
+    params(
+        ("Last-Event-ID" = Option<u64>, Header, description = "Id of the last event received before reconnecting")
+    ),
+    responses(
+        (status = 200, description = "Lifecycle event stream", body = LifecycleEvent, content_type = "text/event-stream"),
+        (status = 400, description = "Malformed Last-Event-ID"),
+        (status = 401, description = "Unauthorized")
+    )
+)]
+#[axum::debug_handler]
+pub async fn stream_system_events(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    let last_event_id = headers
+        .get("last-event-id")
+        .map(|value| {
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .ok_or(StatusCode::BAD_REQUEST)
+        })
+        .transpose()?;
+    let principal = auth.context().principal.clone();
+    let ResumedLifecycleEvents {
+        missed,
+        gap,
+        receiver,
+    } = state.session_manager.lifecycle.resume(last_event_id);
+
+    // 欠落の通知を先に送り、取りこぼした分を再送してからライブのイベントに移る
+    let mut last_id = missed.last().map(|owned| owned.id).or(last_event_id);
+    let gap = gap.then(|| gap_event(last_event_id));
+    let replayed: Vec<Event> = missed
+        .iter()
+        .filter(|owned| owned.owner == principal)
+        .filter_map(lifecycle_event)
+        .collect();
+    let replay = futures::stream::iter(gap.into_iter().flatten().chain(replayed));
+
+    let live = BroadcastStream::new(receiver).filter_map(move |received| {
+        let sse = match received {
+            Ok(owned) => {
+                last_id = Some(owned.id);
+                if owned.owner == principal {
+                    lifecycle_event(&owned)
+                } else {
+                    None
+                }
+            }
+            Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                tracing::warn!("Lifecycle event stream lagged, {} events dropped", skipped);
+                gap_event(last_id)
+            }
+        };
+        futures::future::ready(sse)
+    });
+    Ok(Sse::new(replay.chain(live).map(Ok)).keep_alive(KeepAlive::default()))
+}
+
+fn lifecycle_event(owned: &OwnedLifecycleEvent) -> Option<Event> {
+    Event::default()
+        .event(owned.event.kind.as_str())
+        .id(owned.id.to_string())
+        .json_data(&owned.event)
+        .inspect_err(|e| tracing::error!("Failed to encode lifecycle event: {}", e))
+        .ok()
+}
+
+fn gap_event(last_event_id: Option<u64>) -> Option<Event> {
+    Event::default()
+        .event("gap")
+        .json_data(LifecycleStreamGap { last_event_id })
+        .inspect_err(|e| tracing::error!("Failed to encode lifecycle stream gap: {}", e))
+        .ok()
 }
 
 /// List systems
@@ -120,7 +392,7 @@ pub async fn list_systems(
     State(state): State<AppState>,
     auth: AuthAdmin,
 ) -> Result<Json<ListSystemsResponse>, StatusCode> {
-    if !auth.user().is_admin() {
+    if !auth.context().is_admin() {
         return Err(StatusCode::FORBIDDEN);
     }
 
@@ -128,7 +400,7 @@ pub async fn list_systems(
     // using kairei-core with the session manager. For now, we'll return mock data.
     let sessions = state
         .session_manager
-        .get_sessions(&auth.user().user_id)
+        .get_sessions(&auth.context().principal)
         .await;
     let mut system_statuses = HashMap::new();
     for session in sessions {
@@ -167,7 +439,7 @@ pub async fn compile_system(
     Path(system_id): Path<String>,
     Json(payload): Json<CompileSystemRequest>,
 ) -> Result<Json<CompileSystemResponse>, StatusCode> {
-    if !auth.user().is_admin() {
+    if !auth.context().is_admin() {
         return Err(StatusCode::FORBIDDEN);
     }
 
@@ -188,46 +460,61 @@ pub async fn compile_system(
 ///
 /// This will compile the DSL if provided, and start the system.
 /// If the DSL is not provided, the system will be started with the existing configuration.
+/// Agents imported when the system was created are added to the DSL.
+/// With `If-Match`, the system is only started when its version is unchanged.
 #[utoipa::path(
     post,
     path = "/systems/{system_id}/start",
     request_body = StartSystemRequest,
     responses(
-        (status = 200, description = "System started successfully"),
+        (status = 200, description = "System started successfully",
+            headers(("ETag" = String, description = "New version of the system"))),
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden"),
         (status = 404, description = "System not found"),
+        (status = 412, description = "The system changed since the given ETag"),
         (status = 500, description = "Internal server error")
     ),
     params(
-        ("system_id" = String, Path, description = "System identifier")
+        ("system_id" = String, Path, description = "System identifier"),
+        ("If-Match" = Option<String>, Header, description = "ETag of the system")
     )
 )]
 #[axum::debug_handler]
 pub async fn start_system(
     State(state): State<AppState>,
     auth: AuthAdmin,
+    headers: HeaderMap,
     Path(system_id): Path<String>,
     Json(payload): Json<StartSystemRequest>,
-) -> Result<(), StatusCode> {
-    if !auth.user().is_admin() {
+) -> Result<ETagHeader, StatusCode> {
+    if !auth.context().is_admin() {
         return Err(StatusCode::FORBIDDEN);
     }
+    let precondition = precondition(&state, &headers, false)?;
 
     if let Some(data) = state.session_manager.get_session(&system_id).await {
+        let version = data
+            .versions
+            .update_system(&precondition)
+            .await
+            .map_err(precondition_failed)?;
         let mut system = data.system.write().await;
-        let root_def = if let Some(dsl) = &payload.dsl {
+        let mut root_def = if let Some(dsl) = &payload.dsl {
             system.parse_dsl(dsl).await.map_err(|e| {
                 tracing::error!("Failed to load DSL: {}", e);
                 StatusCode::INTERNAL_SERVER_ERROR
             })?
         } else {
             Root {
+                dsl_version: None,
+                type_defs: vec![],
                 world_def: None,
                 micro_agent_defs: vec![],
                 sistence_agent_defs: vec![],
             }
         };
+        data.imported_agents.merge_into(&mut root_def);
 
         system.initialize(root_def).await.map_err(|e| {
             tracing::error!("Failed to initialize system: {}", e);
@@ -237,50 +524,75 @@ pub async fn start_system(
             tracing::error!("Failed to start system: {}", e);
             StatusCode::INTERNAL_SERVER_ERROR
         })?;
-        Ok(())
+        state.session_manager.lifecycle.publish(
+            &data.user_id,
+            LifecycleEvent::system(LifecycleEventKind::SystemStarted, &system_id),
+        );
+        Ok(etag_header(version))
     } else {
         Err(StatusCode::NOT_FOUND)
     }
 }
 
 /// Shutdown the system
+///
+/// With `If-Match`, the system is only stopped when its version is unchanged.
+/// The server may require `If-Match` for this operation.
 #[utoipa::path(
     post,
     path = "/systems/{system_id}/stop",
     responses(
-        (status = 200, description = "System stopped successfully"),
+        (status = 200, description = "System stopped successfully",
+            headers(("ETag" = String, description = "New version of the system"))),
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden"),
         (status = 404, description = "System not found"),
+        (status = 412, description = "The system changed since the given ETag"),
+        (status = 428, description = "The server requires If-Match"),
         (status = 500, description = "Internal server error")
     ),
     params(
-        ("system_id" = String, Path, description = "System identifier")
+        ("system_id" = String, Path, description = "System identifier"),
+        ("If-Match" = Option<String>, Header, description = "ETag of the system")
     )
 )]
 #[axum::debug_handler]
 pub async fn stop_system(
     State(state): State<AppState>,
     auth: AuthAdmin,
+    headers: HeaderMap,
     Path(system_id): Path<String>,
-) -> Result<(), StatusCode> {
-    if !auth.user().is_admin() {
+) -> Result<ETagHeader, StatusCode> {
+    if !auth.context().is_admin() {
         return Err(StatusCode::FORBIDDEN);
     }
+    let precondition = precondition(&state, &headers, true)?;
 
     if let Some(data) = state.session_manager.get_session(&system_id).await {
+        let version = data
+            .versions
+            .update_system(&precondition)
+            .await
+            .map_err(precondition_failed)?;
         let system = data.system.write().await;
         system.emergency_shutdown().await.map_err(|e| {
             tracing::error!("Failed to initialize system: {}", e);
             StatusCode::INTERNAL_SERVER_ERROR
         })?;
-        Ok(())
+        state.session_manager.lifecycle.publish(
+            &data.user_id,
+            LifecycleEvent::system(LifecycleEventKind::SystemStopped, &system_id),
+        );
+        Ok(etag_header(version))
     } else {
         Err(StatusCode::NOT_FOUND)
     }
 }
 
 /// Delete the system
+///
+/// With `If-Match`, the system is only deleted when its version is unchanged.
+/// The server may require `If-Match` for this operation.
 #[utoipa::path(
     delete,
     path = "/systems/{system_id}",
@@ -289,29 +601,42 @@ pub async fn stop_system(
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden"),
         (status = 404, description = "System not found"),
+        (status = 412, description = "The system changed since the given ETag"),
+        (status = 428, description = "The server requires If-Match"),
         (status = 500, description = "Internal server error")
     ),
     params(
-        ("system_id" = String, Path, description = "System identifier")
+        ("system_id" = String, Path, description = "System identifier"),
+        ("If-Match" = Option<String>, Header, description = "ETag of the system")
     )
 )]
 #[axum::debug_handler]
 pub async fn delete_system(
     State(state): State<AppState>,
     auth: AuthAdmin,
+    headers: HeaderMap,
     Path(system_id): Path<String>,
 ) -> Result<(), StatusCode> {
-    if !auth.user().is_admin() {
+    if !auth.context().is_admin() {
         return Err(StatusCode::FORBIDDEN);
     }
+    let precondition = precondition(&state, &headers, true)?;
 
-    if state
-        .session_manager
-        .get_session(&system_id)
-        .await
-        .is_none()
-    {
+    let Some(data) = state.session_manager.get_session(&system_id).await else {
         return Err(StatusCode::NOT_FOUND);
+    };
+    data.versions
+        .update_system(&precondition)
+        .await
+        .map_err(precondition_failed)?;
+
+    // 削除前にエージェントとバックグラウンドタスクを停止する
+    if let Err(e) = data.system.read().await.emergency_shutdown().await {
+        tracing::warn!(
+            "Failed to shut down system {} before removal: {}",
+            system_id,
+            e
+        );
     }
 
     state
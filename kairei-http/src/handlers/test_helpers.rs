@@ -106,6 +106,11 @@ pub async fn test_get_agent(
             events_processed: 42,
             requests_handled: 15,
             uptime_seconds: 1800,
+            queue_depth: 0,
+            requests_reenqueued: 0,
+            llm_calls_remaining: None,
+            cache_hits: 0,
+            cache_misses: 0,
         },
     };
 
@@ -120,7 +125,10 @@ pub async fn test_send_event(Json(payload): Json<EventRequest>) -> Json<EventRes
     );
 
     let response = EventResponse {
+        root_event_id: event_id.clone(),
         event_id,
+        event_type: payload.event_type,
+        parent_event_id: None,
         status: EventStatus::Delivered,
         delivered_to: payload.target_agents.len().max(1), // If no targets specified, assume broadcast
     };
@@ -182,6 +190,7 @@ pub async fn test_validate_dsl(Json(payload): Json<ValidationRequest>) -> Json<V
             errors: Vec::new(),
             warnings: Vec::new(),
             suggestions: None,
+            dsl_version: None,
         })
     } else {
         // For invalid DSL code
@@ -223,6 +232,7 @@ pub async fn test_validate_dsl(Json(payload): Json<ValidationRequest>) -> Json<V
             suggestions: Some(ValidationSuggestion {
                 code: payload.code.replace("ERROR", ""),
             }),
+            dsl_version: None,
         })
     }
 }
@@ -0,0 +1,524 @@
+//! Validation of request bodies before they reach a handler.
+//!
+//! A handler taking [`ValidatedJson<T>`] instead of `Json<T>` gets a body that
+//! passed the checks of [`ValidateBody`]. The checks run on the JSON before it is
+//! converted to `T`, so a body with several problems, e.g. an empty name and a
+//! negative count, is rejected with 400 and an [`ApiError`] listing every invalid
+//! field rather than only the first error of the conversion. The limits are set by
+//! [`RequestValidationConfig`].
+
+use axum::extract::{FromRequest, Request};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Json, Response};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use kairei_core::provider::capabilities::sistence_memory::{MemoryFilter, PinMode, SearchContext};
+
+use kairei_core::agent_batch::{AgentSelector, BatchOperation};
+use kairei_core::agent_settings::{AgentSettingsPatch, MAX_TEMPERATURE};
+use kairei_core::config::LlmBudgetConfig;
+
+use crate::models::{
+    ApiError, BatchAgentsRequest, CreateSystemRequest, FieldError, MemoryRelevanceRequest,
+    PinMemoriesRequest, ScaleDownAgentRequest, ScaleUpAgentRequest, SearchMemoriesRequest,
+    SendRequestAgentRequest,
+};
+use crate::server::AppState;
+
+/// Limits of request bodies
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RequestValidationConfig {
+    /// Maximum length of system names and request types, in characters
+    pub max_name_length: usize,
+
+    /// Maximum length of a system description, in characters
+    pub max_description_length: usize,
+
+    /// Maximum number of instances added or removed by one scale request
+    pub max_scale_instances: u64,
+}
+
+impl Default for RequestValidationConfig {
+    fn default() -> Self {
+        Self {
+            max_name_length: 64,
+            max_description_length: 1024,
+            max_scale_instances: 100,
+        }
+    }
+}
+
+/// A request body with checks run before it is converted
+pub trait ValidateBody: DeserializeOwned + Send {
+    fn validate(body: &mut BodyValidator, config: &RequestValidationConfig);
+}
+
+/// Collects the invalid fields of a JSON object. A `null` field counts as absent.
+pub struct BodyValidator<'a> {
+    body: &'a serde_json::Map<String, Value>,
+    errors: Vec<FieldError>,
+}
+
+impl<'a> BodyValidator<'a> {
+    pub fn new(body: &'a serde_json::Map<String, Value>) -> Self {
+        Self {
+            body,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn reject(&mut self, field: impl Into<String>, reason: impl Into<String>) {
+        self.errors.push(FieldError {
+            field: field.into(),
+            reason: reason.into(),
+        });
+    }
+
+    fn field(&self, name: &str) -> Option<&'a Value> {
+        self.body.get(name).filter(|value| !value.is_null())
+    }
+
+    pub fn required_string(&mut self, name: &str) -> Option<&'a str> {
+        let Some(value) = self.field(name) else {
+            self.reject(name, "is required");
+            return None;
+        };
+        self.string(name, value)
+    }
+
+    pub fn optional_string(&mut self, name: &str) -> Option<&'a str> {
+        let value = self.field(name)?;
+        self.string(name, value)
+    }
+
+    fn string(&mut self, name: &str, value: &'a Value) -> Option<&'a str> {
+        let string = value.as_str();
+        if string.is_none() {
+            self.reject(name, "must be a string");
+        }
+        string
+    }
+
+    /// A whole number between `min` and `max`
+    pub fn required_count(&mut self, name: &str, min: u64, max: u64) -> Option<u64> {
+        let Some(value) = self.field(name) else {
+            self.reject(name, "is required");
+            return None;
+        };
+        match value.as_i64() {
+            Some(count) if count < 0 => {
+                self.reject(name, "must not be negative");
+                None
+            }
+            Some(count) if (count as u64) < min || (count as u64) > max => {
+                self.reject(name, format!("must be between {} and {}", min, max));
+                None
+            }
+            Some(count) => Some(count as u64),
+            None => {
+                self.reject(name, "must be a whole number");
+                None
+            }
+        }
+    }
+
+    pub fn optional_object(&mut self, name: &str) {
+        if self.field(name).is_some_and(|value| !value.is_object()) {
+            self.reject(name, "must be an object");
+        }
+    }
+
+    /// Strings, each checked by `check`
+    pub fn optional_strings(&mut self, name: &str, check: impl Fn(&str) -> Option<String>) {
+        let Some(value) = self.field(name) else {
+            return;
+        };
+        let Some(items) = value.as_array() else {
+            self.reject(name, "must be an array of strings");
+            return;
+        };
+        for (index, item) in items.iter().enumerate() {
+            let field = format!("{}[{}]", name, index);
+            match item.as_str() {
+                Some(item) => {
+                    if let Some(reason) = check(item) {
+                        self.reject(field, reason);
+                    }
+                }
+                None => self.reject(field, "must be a string"),
+            }
+        }
+    }
+
+    /// A field that must convert to `T`, e.g. a config
+    pub fn required_as<T: DeserializeOwned>(&mut self, name: &str) {
+        let Some(value) = self.field(name) else {
+            self.reject(name, "is required");
+            return;
+        };
+        if let Err(e) = serde_json::from_value::<T>(value.clone()) {
+            self.reject(name, e.to_string());
+        }
+    }
+
+    /// An optional field that must convert to `T` and pass `check`
+    pub fn optional_as<T: DeserializeOwned>(
+        &mut self,
+        name: &str,
+        check: impl Fn(&T) -> Option<String>,
+    ) {
+        let Some(value) = self.field(name) else {
+            return;
+        };
+        match serde_json::from_value::<T>(value.clone()) {
+            Ok(value) => {
+                if let Some(reason) = check(&value) {
+                    self.reject(name, reason);
+                }
+            }
+            Err(e) => self.reject(name, e.to_string()),
+        }
+    }
+
+    /// Rejects the fields not in `allowed`, even `null` ones
+    pub fn only_fields(&mut self, allowed: &[&str]) {
+        let unknown: Vec<String> = self
+            .body
+            .keys()
+            .filter(|name| !allowed.contains(&name.as_str()))
+            .cloned()
+            .collect();
+        for name in unknown {
+            self.reject(
+                name,
+                format!("is not one of the fields {}", allowed.join(", ")),
+            );
+        }
+    }
+
+    pub fn finish(self) -> Result<(), Vec<FieldError>> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors)
+        }
+    }
+}
+
+/// A JSON body that passed [`ValidateBody`], see the [module docs](self)
+#[derive(Debug)]
+pub struct ValidatedJson<T>(pub T);
+
+impl<T: ValidateBody> FromRequest<AppState> for ValidatedJson<T> {
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &AppState) -> Result<Self, Self::Rejection> {
+        let Json(body) = Json::<Value>::from_request(req, state)
+            .await
+            .map_err(|rejection| {
+                rejected(
+                    rejection.status(),
+                    ApiError::malformed_body(rejection.body_text()),
+                )
+            })?;
+        let Some(fields) = body.as_object() else {
+            return Err(rejected(
+                StatusCode::BAD_REQUEST,
+                ApiError::malformed_body("The body must be a JSON object"),
+            ));
+        };
+
+        let mut validator = BodyValidator::new(fields);
+        T::validate(&mut validator, &state.request_validation);
+        validator.finish().map_err(|fields| {
+            rejected(StatusCode::BAD_REQUEST, ApiError::invalid_fields(fields))
+        })?;
+
+        // 検証を通った本文の変換に失敗するのは、検証していない形の違いだけ
+        serde_json::from_value(body)
+            .map(ValidatedJson)
+            .map_err(|e| {
+                rejected(
+                    StatusCode::BAD_REQUEST,
+                    ApiError::invalid_fields(vec![FieldError {
+                        field: String::new(),
+                        reason: e.to_string(),
+                    }]),
+                )
+            })
+    }
+}
+
+fn rejected(status: StatusCode, error: ApiError) -> Response {
+    tracing::info!("Rejected request body: {}", error.message);
+    (status, Json(error)).into_response()
+}
+
+/// A system name: letters, digits, spaces, `_`, `-` and `.`
+fn check_name(name: &str, max_length: usize) -> Option<String> {
+    if name.trim().is_empty() {
+        return Some("must not be empty".to_string());
+    }
+    if name.chars().count() > max_length {
+        return Some(format!("must be at most {} characters", max_length));
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| !(c.is_alphanumeric() || matches!(c, ' ' | '_' | '-' | '.')))
+    {
+        return Some(format!(
+            "must only contain letters, digits, spaces, `_`, `-` and `.`, found {:?}",
+            c
+        ));
+    }
+    None
+}
+
+/// A DSL identifier such as a request type
+fn check_identifier(name: &str, max_length: usize) -> Option<String> {
+    let mut chars = name.chars();
+    match chars.next() {
+        None => Some("must not be empty".to_string()),
+        Some(_) if name.chars().count() > max_length => {
+            Some(format!("must be at most {} characters", max_length))
+        }
+        Some(first) if first.is_ascii_alphabetic() || first == '_' => chars
+            .find(|c| !(c.is_ascii_alphanumeric() || *c == '_'))
+            .map(|c| {
+                format!(
+                    "must only contain ASCII letters, digits and `_`, found {:?}",
+                    c
+                )
+            }),
+        Some(first) => Some(format!(
+            "must start with an ASCII letter or `_`, found {:?}",
+            first
+        )),
+    }
+}
+
+impl ValidateBody for CreateSystemRequest {
+    fn validate(body: &mut BodyValidator, config: &RequestValidationConfig) {
+        if let Some(name) = body.required_string("name") {
+            if let Some(reason) = check_name(name, config.max_name_length) {
+                body.reject("name", reason);
+            }
+        }
+        let max_description = config.max_description_length;
+        if body
+            .optional_string("description")
+            .is_some_and(|description| description.chars().count() > max_description)
+        {
+            body.reject(
+                "description",
+                format!("must be at most {} characters", max_description),
+            );
+        }
+        body.required_as::<kairei_core::config::SystemConfig>("config");
+        body.optional_strings("agent_sources", |source| {
+            source
+                .trim()
+                .is_empty()
+                .then(|| "must not be empty".to_string())
+        });
+    }
+}
+
+impl ValidateBody for SendRequestAgentRequest {
+    fn validate(body: &mut BodyValidator, config: &RequestValidationConfig) {
+        if let Some(request_type) = body.required_string("request_type") {
+            if let Some(reason) = check_identifier(request_type, config.max_name_length) {
+                body.reject("request_type", reason);
+            }
+        }
+        body.optional_object("payload");
+    }
+}
+
+impl ValidateBody for ScaleUpAgentRequest {
+    fn validate(body: &mut BodyValidator, config: &RequestValidationConfig) {
+        body.required_count("instances", 1, config.max_scale_instances);
+        body.optional_object("options");
+    }
+}
+
+impl ValidateBody for ScaleDownAgentRequest {
+    fn validate(body: &mut BodyValidator, config: &RequestValidationConfig) {
+        body.required_count("instances", 1, config.max_scale_instances);
+        body.optional_object("options");
+    }
+}
+
+impl ValidateBody for BatchAgentsRequest {
+    fn validate(body: &mut BodyValidator, config: &RequestValidationConfig) {
+        body.required_as::<BatchOperation>("operation");
+        body.required_as::<AgentSelector>("selector");
+        let max_instances = config.max_scale_instances;
+        body.optional_as::<u64>("instances", |instances| {
+            (*instances < 1 || *instances > max_instances)
+                .then(|| format!("must be between 1 and {}", max_instances))
+        });
+        body.optional_as::<u64>("parallelism", |parallelism| {
+            (*parallelism == 0).then(|| "must be at least 1".to_string())
+        });
+        body.optional_as::<bool>("atomic", |_| None);
+    }
+}
+
+impl ValidateBody for SearchMemoriesRequest {
+    fn validate(body: &mut BodyValidator, _config: &RequestValidationConfig) {
+        body.optional_string("query");
+        body.optional_as::<MemoryFilter>("filter", |filter| {
+            filter.validate().err().map(|e| e.to_string())
+        });
+    }
+}
+
+impl ValidateBody for MemoryRelevanceRequest {
+    fn validate(body: &mut BodyValidator, _config: &RequestValidationConfig) {
+        body.required_as::<SearchContext>("context");
+    }
+}
+
+impl ValidateBody for PinMemoriesRequest {
+    fn validate(body: &mut BodyValidator, _config: &RequestValidationConfig) {
+        body.required_as::<Vec<String>>("item_ids");
+        body.optional_as::<PinMode>("mode", |_| None);
+        // ピンはデバッグ用のため、最長 1 週間
+        body.required_count("ttl_secs", 1, 7 * 24 * 3600);
+    }
+}
+
+impl ValidateBody for AgentSettingsPatch {
+    fn validate(body: &mut BodyValidator, _config: &RequestValidationConfig) {
+        // 実行中に変えてよいのはこの設定だけ
+        body.only_fields(&["provider", "temperature", "max_tokens", "llm_budget"]);
+        body.optional_string("provider");
+        body.optional_as::<f32>("temperature", |temperature| {
+            (!(0.0..=MAX_TEMPERATURE).contains(temperature))
+                .then(|| format!("must be between 0 and {}", MAX_TEMPERATURE))
+        });
+        body.optional_as::<usize>("max_tokens", |max_tokens| {
+            (*max_tokens == 0).then(|| "must be at least 1".to_string())
+        });
+        body.optional_as::<LlmBudgetConfig>("llm_budget", |budget| {
+            (budget.max_calls == 0 || budget.window.is_zero())
+                .then(|| "max_calls and window must be positive".to_string())
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn validate<T: ValidateBody>(body: Value) -> Vec<FieldError> {
+        let mut validator = BodyValidator::new(body.as_object().unwrap());
+        T::validate(&mut validator, &RequestValidationConfig::default());
+        validator.finish().err().unwrap_or_default()
+    }
+
+    #[test]
+    fn test_names_and_identifiers() {
+        assert_eq!(check_name("Travel planner-2", 64), None);
+        assert!(check_name("  ", 64).unwrap().contains("empty"));
+        assert!(check_name("a/b", 64).unwrap().contains("'/'"));
+        assert!(check_name("abcdef", 5).unwrap().contains("at most 5"));
+
+        assert_eq!(check_identifier("_GetCount2", 64), None);
+        assert!(check_identifier("2Get", 64).unwrap().contains("start"));
+        assert!(check_identifier("Get-Count", 64).unwrap().contains("'-'"));
+    }
+
+    #[test]
+    fn test_send_request_payload_must_be_an_object() {
+        assert!(validate::<SendRequestAgentRequest>(json!({"request_type": "Get"})).is_empty());
+        let errors = validate::<SendRequestAgentRequest>(json!({
+            "request_type": "Get Count",
+            "payload": [1, 2],
+        }));
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, vec!["request_type", "payload"]);
+    }
+
+    #[test]
+    fn test_batch_agents_request() {
+        let valid = json!({
+            "operation": "stop",
+            "selector": {"glob": "worker-*"},
+            "parallelism": 4,
+            "atomic": true,
+        });
+        assert!(validate::<BatchAgentsRequest>(valid).is_empty());
+
+        let errors = validate::<BatchAgentsRequest>(json!({
+            "operation": "restart",
+            "selector": {"glob": "worker-*", "status": "running"},
+            "instances": 0,
+            "parallelism": 0,
+        }));
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            vec!["operation", "selector", "instances", "parallelism"]
+        );
+    }
+
+    #[test]
+    fn test_search_memories_filter_is_validated() {
+        let valid = json!({
+            "filter": {"and": [
+                {"tag_equals": {"key": "project", "value": "atlas"}},
+                {"time_range": {"field": "updated", "start": "2024-05-01T00:00:00Z"}},
+                {"not": {"item_type_in": ["conversation"]}}
+            ]}
+        });
+        assert!(validate::<SearchMemoriesRequest>(valid).is_empty());
+
+        let errors = validate::<SearchMemoriesRequest>(json!({
+            "query": 3,
+            "filter": {"or": [{"item_type_in": ["memo"]}]},
+        }));
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, vec!["query", "filter"]);
+        assert!(errors[1].reason.contains("Unknown item type `memo`"));
+
+        let errors = validate::<SearchMemoriesRequest>(json!({"filter": {"and": []}}));
+        assert!(errors[0].reason.contains("`and` must contain"));
+        let errors = validate::<SearchMemoriesRequest>(json!({"filter": {"xor": []}}));
+        assert!(errors[0].reason.contains("unknown variant"));
+    }
+
+    #[test]
+    fn test_pin_memories_request() {
+        let valid = json!({"item_ids": ["a", "b"], "mode": "exclude", "ttl_secs": 600});
+        assert!(validate::<PinMemoriesRequest>(valid).is_empty());
+
+        let errors = validate::<PinMemoriesRequest>(json!({
+            "item_ids": "a",
+            "mode": "boost",
+            "ttl_secs": 0,
+        }));
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, vec!["item_ids", "mode", "ttl_secs"]);
+    }
+
+    #[test]
+    fn test_agent_settings_patch() {
+        let valid = json!({"temperature": 0.2, "provider": null, "llm_budget": {"max_calls": 5, "window": 60000}});
+        assert!(validate::<AgentSettingsPatch>(valid).is_empty());
+
+        let errors = validate::<AgentSettingsPatch>(json!({
+            "model": "gpt-4o",
+            "temperature": 2.5,
+            "max_tokens": 0,
+        }));
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, vec!["model", "temperature", "max_tokens"]);
+    }
+}
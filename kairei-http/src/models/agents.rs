@@ -1,5 +1,11 @@
 use std::collections::HashMap;
 
+use kairei_core::agent_batch::{AgentBatch, AgentSelector, BatchOperation};
+use kairei_core::eval::profile::RequestProfile;
+use kairei_core::eval::recording;
+use kairei_core::event_bus;
+use kairei_core::expression;
+use kairei_core::handler_test::{HandlerKind, HandlerTestReport};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use utoipa::ToSchema;
@@ -16,6 +22,10 @@ pub struct AgentCreationRequest {
     /// Optional agent creation options
     #[serde(default)]
     pub options: AgentCreationOptions,
+
+    /// Metadata labels used to group and find agents (e.g. `team`, `environment`)
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
 }
 
 /// Agent creation options
@@ -62,6 +72,15 @@ pub struct AgentDetails {
 pub struct GetAgentResponse {
     pub agent_id: String,
     pub status: kairei_core::system::AgentStatus,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+}
+
+/// Contracts of the requests an agent answers
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AgentContractsResponse {
+    pub agent_id: String,
+    pub contracts: Vec<kairei_core::catalog::RequestSignature>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -76,6 +95,7 @@ pub struct ScaleUpAgentRequest {
     pub instances: usize,
 
     /// Optional agent scaling options
+    #[serde(default)]
     pub options: HashMap<String, serde_json::Value>,
 }
 
@@ -86,18 +106,224 @@ pub struct ScaleDownAgentRequest {
     pub instances: usize,
 
     /// Optional agent scaling options
+    #[serde(default)]
     pub options: HashMap<String, serde_json::Value>,
 }
 
+/// Bulk operation on the agents a selector picks
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BatchAgentsRequest {
+    /// `start`, `stop`, `scale_up`, `scale_down` or `reload`
+    pub operation: BatchOperation,
+
+    /// One of `{"names": [...]}`, `{"glob": "worker-*"}`, `{"capability": "GetForecast"}`,
+    /// `{"labels": {"team": "search"}}` or `{"status": "running"}`
+    pub selector: AgentSelector,
+
+    /// Instances added or removed for each agent by `scale_up` and `scale_down`, 1 by default
+    #[serde(default)]
+    pub instances: Option<usize>,
+
+    /// Maximum number of agents operated on at the same time, 8 by default
+    #[serde(default)]
+    pub parallelism: Option<usize>,
+
+    /// Undo the applied operations when one fails; only `start` and `stop` can be undone
+    #[serde(default)]
+    pub atomic: bool,
+}
+
+impl From<BatchAgentsRequest> for AgentBatch {
+    fn from(request: BatchAgentsRequest) -> Self {
+        let batch =
+            AgentBatch::new(request.operation, request.selector).with_atomic(request.atomic);
+        let batch = match request.instances {
+            Some(instances) => batch.with_instances(instances),
+            None => batch,
+        };
+        match request.parallelism {
+            Some(parallelism) => batch.with_parallelism(parallelism),
+            None => batch,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct SendRequestAgentRequest {
     pub request_type: String,
+    /// Request parameters as a JSON object, converted to the handler's parameter types
     pub payload: Value,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
 pub struct SendRequestAgentResponse {
     pub value: Value,
+    /// Timing breakdown of the request, when asked for with the `X-Kairei-Profile` header
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<RequestProfile>,
+}
+
+/// A DSL expression to evaluate against the agent's state
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DebugEvalRequest {
+    /// A single read-only expression, e.g. `profile.city`
+    pub expression: String,
+}
+
+/// The value of a debug expression and its DSL type
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DebugEvalResponse {
+    pub value: Value,
+    #[serde(rename = "type")]
+    pub type_name: String,
+}
+
+/// An expression that was rejected or failed to evaluate
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DebugEvalErrorResponse {
+    /// Error message
+    pub error: String,
+}
+
+/// A synthetic event fired at one handler of the agent
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct TestHandlerRequest {
+    /// Parameters of the event, converted to the event's declared types
+    #[serde(default)]
+    pub payload: Value,
+
+    /// Handler to run. By default the observe handler, or the react handler when
+    /// the agent does not observe the event
+    #[serde(default)]
+    pub kind: Option<HandlerKind>,
+
+    /// Answers of the mock provider: a `think` whose prompt contains a key is
+    /// answered with its value
+    #[serde(default)]
+    pub mock_responses: HashMap<String, String>,
+}
+
+/// A variable changed by a handler
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct StateChange {
+    pub name: String,
+    /// Value before the change, `null` for a new variable
+    pub before: Option<Value>,
+    pub after: Value,
+}
+
+/// An event emitted by a handler
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct EmittedEvent {
+    pub event_type: String,
+    pub parameters: HashMap<String, Value>,
+}
+
+/// What the handler did. Nothing of it was committed to the agent
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TestHandlerResponse {
+    pub kind: HandlerKind,
+    /// Whether the `requires` clause held. The handler block does not run otherwise
+    pub ran: bool,
+    /// Value of the handler block
+    pub value: Value,
+    /// Changed state variables, ordered by name
+    pub state_changes: Vec<StateChange>,
+    /// Emitted events, in order
+    pub emitted_events: Vec<EmittedEvent>,
+}
+
+impl From<HandlerTestReport> for TestHandlerResponse {
+    fn from(report: HandlerTestReport) -> Self {
+        Self {
+            kind: report.kind,
+            ran: report.ran,
+            value: json_value(report.value),
+            state_changes: report
+                .state_changes
+                .into_iter()
+                .map(StateChange::from)
+                .collect(),
+            emitted_events: report
+                .emitted_events
+                .iter()
+                .map(EmittedEvent::from)
+                .collect(),
+        }
+    }
+}
+
+impl From<recording::StateChange> for StateChange {
+    fn from(change: recording::StateChange) -> Self {
+        Self {
+            name: change.name,
+            before: change.before.map(json_value),
+            after: json_value(change.after),
+        }
+    }
+}
+
+impl From<&event_bus::Event> for EmittedEvent {
+    fn from(event: &event_bus::Event) -> Self {
+        Self {
+            event_type: event.event_type.to_string(),
+            parameters: event
+                .parameters
+                .iter()
+                .map(|(name, value)| (name.clone(), Value::from(value)))
+                .collect(),
+        }
+    }
+}
+
+fn json_value(value: expression::Value) -> Value {
+    Value::from(&event_bus::Value::from(value))
+}
+
+/// A handler test that could not run
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TestHandlerErrorResponse {
+    /// Error message
+    pub error: String,
+}
+
+/// New values of the agent's `config { ... }` parameters
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UpdateAgentConfigRequest {
+    /// JSON values keyed by parameter name. Parameters left out fall back to
+    /// their defaults.
+    pub values: HashMap<String, Value>,
+}
+
+/// A config update that was rejected
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AgentConfigErrorResponse {
+    /// Error message, naming the agent and the parameter
+    pub error: String,
+}
+
+/// A new definition of an agent, compared with the running one without reloading it
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReloadPreviewRequest {
+    /// DSL defining the agent, possibly among other agents
+    pub dsl: String,
+}
+
+/// A definition that could not be previewed
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReloadPreviewErrorResponse {
+    /// Error message
+    pub error: String,
+}
+
+/// The recent logs an agent keeps in memory
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AgentLogsResponse {
+    pub agent_id: String,
+    /// Logs below this level are discarded, and not kept
+    pub level: kairei_core::agent_log::LogLevel,
+    /// Logs of the agent and its scaled instances, oldest first
+    pub logs: Vec<kairei_core::agent_log::CapturedLog>,
 }
 
 /// Agent status enum
@@ -141,4 +367,18 @@ pub struct AgentStatistics {
 
     /// Agent uptime in seconds
     pub uptime_seconds: u64,
+
+    /// Requests waiting in the persisted request queue
+    pub queue_depth: usize,
+
+    /// Requests recovered from the persisted request queue on startup
+    pub requests_reenqueued: usize,
+    /// LLM calls left in the current budget window; `None` when unlimited
+    pub llm_calls_remaining: Option<u32>,
+
+    /// Requests answered from the `@cache` response cache
+    pub cache_hits: u64,
+
+    /// Cacheable requests that ran their answer handler
+    pub cache_misses: u64,
 }
@@ -0,0 +1,101 @@
+use chrono::{DateTime, Utc};
+use kairei_core::debug_session::DebugStep;
+use kairei_core::eval::recording::HandlerKind;
+use kairei_core::eval::recording::HandlerRecording;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::agents::{EmittedEvent, StateChange};
+
+/// A recorded run of an observe or react handler
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct HandlerRecordingSummary {
+    pub id: String,
+    pub agent_name: String,
+    pub kind: HandlerKind,
+    /// Type of the event the handler ran for
+    pub event_type: String,
+    /// Source text of the statements that ran, in order
+    pub statements: Vec<String>,
+    pub recorded_at: DateTime<Utc>,
+}
+
+impl From<&HandlerRecording> for HandlerRecordingSummary {
+    fn from(recording: &HandlerRecording) -> Self {
+        Self {
+            id: recording.id.clone(),
+            agent_name: recording.agent_name.clone(),
+            kind: recording.kind,
+            event_type: recording.event.event_type.to_string(),
+            statements: recording
+                .steps
+                .iter()
+                .map(|step| step.statement.clone())
+                .collect(),
+            recorded_at: recording.recorded_at,
+        }
+    }
+}
+
+/// The recorded handler runs of a system, oldest first
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ListRecordingsResponse {
+    pub recordings: Vec<HandlerRecordingSummary>,
+}
+
+/// The recording to step through
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct StartDebugSessionRequest {
+    pub recording_id: String,
+}
+
+/// A debug session paused before the first statement of the handler
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct StartDebugSessionResponse {
+    pub session_id: String,
+}
+
+/// What a statement did in a debug session
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DebugStepResponse {
+    /// Position of the statement in the handler's block, from 0
+    pub index: usize,
+    /// Source text of the statement
+    pub statement: String,
+    /// State variables the statement changed, ordered by name
+    pub state_changes: Vec<StateChange>,
+    /// Local variables the statement bound or changed, ordered by name
+    pub variable_changes: Vec<StateChange>,
+    /// Events emitted by the handler so far, in order
+    pub pending_events: Vec<EmittedEvent>,
+    /// Whether the handler has no statement left to run
+    pub finished: bool,
+}
+
+impl From<DebugStep> for DebugStepResponse {
+    fn from(step: DebugStep) -> Self {
+        Self {
+            index: step.index,
+            statement: step.statement,
+            state_changes: step
+                .state_changes
+                .into_iter()
+                .map(StateChange::from)
+                .collect(),
+            variable_changes: step
+                .variable_changes
+                .into_iter()
+                .map(StateChange::from)
+                .collect(),
+            pending_events: step.pending_events.iter().map(EmittedEvent::from).collect(),
+            finished: step.finished,
+        }
+    }
+}
+
+/// A debug session request that could not be served
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DebugSessionErrorResponse {
+    /// Error message
+    pub error: String,
+}
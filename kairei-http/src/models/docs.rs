@@ -1,6 +1,7 @@
 //! Models for DSL documentation API responses.
 
 use kairei_core::analyzer::ParserDocumentation;
+use kairei_core::diagnostics::Explanation;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use utoipa::{IntoParams, ToSchema};
@@ -74,6 +75,15 @@ pub struct DocumentationQueryParams {
     pub format: Option<String>,
 }
 
+/// Response listing every diagnostic code
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DiagnosticListResponse {
+    /// Number of registered codes
+    pub total: usize,
+    /// Explanations of the codes
+    pub diagnostics: Vec<Explanation>,
+}
+
 /// Error response for documentation endpoints
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct DocumentationErrorResponse {
@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A request body that was rejected before it reached the handler
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ApiError {
+    /// `malformed_body` when the body is not JSON, `invalid_fields` when fields are invalid,
+    /// `invalid_query` when query parameters are invalid
+    pub error: String,
+
+    /// Error message
+    pub message: String,
+
+    /// Every invalid field, empty for a malformed body
+    #[serde(default)]
+    pub fields: Vec<FieldError>,
+}
+
+/// A field of a request body and why it was rejected
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct FieldError {
+    /// Path of the field, e.g. `name` or `agent_sources[1]`; empty for the body itself
+    pub field: String,
+
+    /// Why the field was rejected
+    pub reason: String,
+}
+
+impl ApiError {
+    pub fn malformed_body(message: impl Into<String>) -> Self {
+        Self {
+            error: "malformed_body".to_string(),
+            message: message.into(),
+            fields: Vec::new(),
+        }
+    }
+
+    pub fn invalid_fields(fields: Vec<FieldError>) -> Self {
+        let names: Vec<&str> = fields.iter().map(|f| f.field.as_str()).collect();
+        Self {
+            error: "invalid_fields".to_string(),
+            message: format!("Invalid fields: {}", names.join(", ")),
+            fields,
+        }
+    }
+
+    /// Query parameters that were rejected, each named in `field`
+    pub fn invalid_query(fields: Vec<FieldError>) -> Self {
+        let mut names: Vec<&str> = fields.iter().map(|f| f.field.as_str()).collect();
+        names.dedup();
+        Self {
+            error: "invalid_query".to_string(),
+            message: format!("Invalid query parameters: {}", names.join(", ")),
+            fields,
+        }
+    }
+}
@@ -1,6 +1,13 @@
+use kairei_core::{
+    eval::evaluator::ConstraintViolation,
+    event::{
+        coercion::CoercionError,
+        lineage::{LineageNode, LineageRecord},
+    },
+};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
 
 /// Event submission request model
 #[derive(Debug, Deserialize, Serialize, Default, ToSchema)]
@@ -16,12 +23,55 @@ pub struct EventRequest {
     pub target_agents: Vec<String>,
 }
 
-/// Event submission response model
+/// A parameter that cannot be converted to the type its handler expects
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ParameterErrorResponse {
+    /// Path of the rejected parameter, e.g. `timeout` or `delays[1]`
+    pub parameter: String,
+
+    /// Type the handler expects
+    pub expected: String,
+
+    /// Error message
+    pub error: String,
+}
+
+impl From<CoercionError> for ParameterErrorResponse {
+    fn from(error: CoercionError) -> Self {
+        Self {
+            error: error.to_string(),
+            parameter: error.parameter,
+            expected: error.expected,
+        }
+    }
+}
+
+/// Parameters rejected by the `where` constraints of the answer handler
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ValidationErrorResponse {
+    /// Error message
+    pub error: String,
+
+    /// Every violated constraint, in parameter order
+    pub violations: Vec<ConstraintViolation>,
+}
+
+/// Event submission response model, also listing the events of a system
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct EventResponse {
     /// Unique identifier for the event
     pub event_id: String,
 
+    /// Event type (the request type for requests and responses)
+    pub event_type: String,
+
+    /// Identifier of the event whose handler emitted this event; none for an event
+    /// sent from outside
+    pub parent_event_id: Option<String>,
+
+    /// Identifier of the first event of the chain
+    pub root_event_id: String,
+
     /// Status of the event
     pub status: EventStatus,
 
@@ -29,6 +79,19 @@ pub struct EventResponse {
     pub delivered_to: usize,
 }
 
+impl From<LineageRecord> for EventResponse {
+    fn from(record: LineageRecord) -> Self {
+        Self {
+            event_id: record.event_id,
+            event_type: record.event_type,
+            parent_event_id: record.parent_event_id,
+            root_event_id: record.root_event_id,
+            status: EventStatus::Delivered,
+            delivered_to: 0,
+        }
+    }
+}
+
 /// Event status enum
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
@@ -87,3 +150,64 @@ pub enum RequestStatus {
     /// Request failed
     Failed,
 }
+
+/// Query parameters for listing the events of a system
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EventListQuery {
+    /// Maximum number of events returned, the most recent ones (default 100)
+    pub limit: Option<usize>,
+}
+
+/// Query parameters for an event lineage lookup
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EventLineageQuery {
+    /// Maximum number of steps walked up to ancestors and down to descendants (default 32)
+    pub max_depth: Option<usize>,
+}
+
+/// A node of an event's causal tree
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct EventLineageNode {
+    /// Event identifier
+    pub event_id: String,
+
+    /// Identifier of the event whose handler emitted this event
+    pub parent_event_id: Option<String>,
+
+    /// Identifier of the first event of the chain
+    pub root_event_id: String,
+
+    /// Event type (the request type for requests and responses)
+    pub event_type: String,
+
+    /// Event category (`request`, `response`, `agent`, `system` or `component`)
+    pub category: String,
+
+    /// Publish time (RFC 3339)
+    pub published_at: String,
+
+    /// Publish order on the event bus
+    pub sequence: u64,
+
+    /// Events caused by this event, in publish order
+    #[schema(no_recursion)]
+    pub children: Vec<EventLineageNode>,
+}
+
+impl From<LineageNode> for EventLineageNode {
+    fn from(node: LineageNode) -> Self {
+        let record = node.record;
+        Self {
+            event_id: record.event_id,
+            parent_event_id: record.parent_event_id,
+            root_event_id: record.root_event_id,
+            event_type: record.event_type,
+            category: record.category,
+            published_at: record.published_at.to_rfc3339(),
+            sequence: record.sequence,
+            children: node.children.into_iter().map(Self::from).collect(),
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use kairei_core::provider::capabilities::sistence_memory::{
+    ContextPin, ImportMapping, ImportReport, InjectedContext, InjectedMemory, ItemType,
+    MemoryFilter, MemoryItem, PinMode, RelevanceExplanation, RelevanceFactor, SearchContext,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Query parameters for a JSONL memory import
+///
+/// Each parameter names the JSONL field to read; omitted parameters keep the
+/// default mapping (`content`, `id`, `item_type`, `topics`, `tags`,
+/// `created_at`, `importance`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportMemoriesQuery {
+    /// Field holding the item content
+    pub content: Option<String>,
+    /// Field holding the item ID
+    pub id: Option<String>,
+    /// Field holding the item type
+    pub item_type: Option<String>,
+    /// Field holding a topic or a list of topics
+    pub topics: Option<String>,
+    /// Field holding an object of tags
+    pub tags: Option<String>,
+    /// Field holding the creation time (RFC 3339 or Unix seconds)
+    pub created_at: Option<String>,
+    /// Field holding the importance score (0.0-1.0)
+    pub importance: Option<String>,
+    /// Source ID recorded on every imported item
+    pub source_id: Option<String>,
+    /// Number of items written to storage at once
+    pub batch_size: Option<usize>,
+}
+
+impl From<ImportMemoriesQuery> for ImportMapping {
+    fn from(query: ImportMemoriesQuery) -> Self {
+        let default = ImportMapping::default();
+        Self {
+            content: query.content.unwrap_or(default.content),
+            id: query.id.or(default.id),
+            item_type: query.item_type.or(default.item_type),
+            topics: query.topics.or(default.topics),
+            tags: query.tags.or(default.tags),
+            created_at: query.created_at.or(default.created_at),
+            importance: query.importance.or(default.importance),
+            source_id: query.source_id.unwrap_or(default.source_id),
+            batch_size: query.batch_size.unwrap_or(default.batch_size),
+        }
+    }
+}
+
+/// Response for a JSONL memory import
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ImportMemoriesResponse {
+    /// Number of lines read, including rejected and blank lines
+    pub lines_read: usize,
+
+    /// Number of items stored
+    pub imported: usize,
+
+    /// IDs of the stored items, in file order
+    pub item_ids: Vec<String>,
+
+    /// Lines that were skipped
+    pub errors: Vec<ImportLineErrorResponse>,
+
+    /// Time taken for the import, in milliseconds
+    pub duration_ms: u64,
+}
+
+/// A JSONL line that was skipped during import
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ImportLineErrorResponse {
+    /// 1-based line number
+    pub line: usize,
+
+    /// Why the line was rejected
+    pub error: String,
+}
+
+impl From<ImportReport> for ImportMemoriesResponse {
+    fn from(report: ImportReport) -> Self {
+        Self {
+            lines_read: report.lines_read,
+            imported: report.item_ids.len(),
+            item_ids: report.item_ids,
+            errors: report
+                .errors
+                .into_iter()
+                .map(|e| ImportLineErrorResponse {
+                    line: e.line,
+                    error: e.error,
+                })
+                .collect(),
+            duration_ms: report.duration.as_millis() as u64,
+        }
+    }
+}
+
+/// Request for a memory search
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct SearchMemoriesRequest {
+    /// Search text; an empty query returns every item matching the filter
+    #[serde(default)]
+    pub query: String,
+
+    /// Filter tree, e.g. `{"and": [{"tag_equals": {"key": "project", "value": "atlas"}},
+    /// {"not": {"item_type_in": ["conversation"]}}]}`
+    #[serde(default)]
+    pub filter: Option<MemoryFilter>,
+
+    /// Maximum number of items returned
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// Response for a memory search
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SearchMemoriesResponse {
+    /// Matching items, most relevant first
+    pub items: Vec<MemorySearchHit>,
+}
+
+/// A memory item found by a search
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MemorySearchHit {
+    pub id: String,
+    pub content: String,
+    /// Item type as named in `item_type_in` filters, e.g. `decision` or `custom:memo`
+    pub item_type: String,
+    pub topics: Vec<String>,
+    pub tags: HashMap<String, String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Base importance score (0.0-1.0)
+    pub importance: f32,
+}
+
+impl From<MemoryItem> for MemorySearchHit {
+    fn from(item: MemoryItem) -> Self {
+        Self {
+            id: item.id,
+            content: item.content,
+            item_type: match item.item_type {
+                ItemType::Custom(name) => format!("custom:{}", name),
+                item_type => format!("{:?}", item_type).to_lowercase(),
+            },
+            topics: item.topics,
+            tags: item.tags,
+            created_at: item.created_at.into(),
+            updated_at: item.updated_at.into(),
+            importance: item.importance.base_score,
+        }
+    }
+}
+
+/// Request for the relevance of memories to a context
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MemoryRelevanceRequest {
+    /// The context to rank the memories for, e.g. `current_topics` and
+    /// `current_activity`
+    #[schema(value_type = Object)]
+    pub context: SearchContext,
+
+    /// Maximum number of items returned
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// Memories ranked for a context
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MemoryRelevanceResponse {
+    /// Items in the order retrieval returns them, most relevant first
+    pub items: Vec<RankedMemory>,
+}
+
+/// A memory item with the breakdown of its relevance
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RankedMemory {
+    /// 1-based position in the ranking
+    pub rank: usize,
+    pub item: MemorySearchHit,
+    /// Sum of `score * weight` over the factors
+    pub relevance: f32,
+    pub factors: Vec<RelevanceFactor>,
+}
+
+impl MemoryRelevanceResponse {
+    pub fn new(explanations: Vec<RelevanceExplanation>) -> Self {
+        Self {
+            items: explanations
+                .into_iter()
+                .enumerate()
+                .map(|(index, explanation)| RankedMemory {
+                    rank: index + 1,
+                    item: explanation.item.into(),
+                    relevance: explanation.relevance,
+                    factors: explanation.factors,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Request to pin memories to, or exclude them from, the prompts of an agent
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PinMemoriesRequest {
+    /// IDs of items in the memory of the agent
+    pub item_ids: Vec<String>,
+
+    /// `pin` to inject the items ahead of the ranked items, as far as the limit
+    /// allows, `exclude` to never inject them
+    #[serde(default)]
+    pub mode: PinMode,
+
+    /// How long the pins last, in seconds
+    pub ttl_secs: u64,
+}
+
+/// The pins of an agent's memory that have not expired
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MemoryPinsResponse {
+    pub pins: Vec<MemoryPin>,
+}
+
+/// A memory item pinned to, or excluded from, the prompts of an agent
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MemoryPin {
+    pub item_id: String,
+    pub mode: PinMode,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl From<ContextPin> for MemoryPin {
+    fn from(pin: ContextPin) -> Self {
+        Self {
+            item_id: pin.item_id,
+            mode: pin.mode,
+            expires_at: pin.expires_at.into(),
+        }
+    }
+}
+
+/// The memory items injected into the prompts of one request of an agent
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct InjectedContextResponse {
+    pub agent_id: String,
+    pub request_id: String,
+    /// When the last prompt of the request was built
+    pub recorded_at: DateTime<Utc>,
+    /// Items of every prompt of the request, in prompt order
+    pub items: Vec<InjectedMemory>,
+}
+
+impl InjectedContextResponse {
+    pub fn new(agent_id: String, context: InjectedContext) -> Self {
+        Self {
+            agent_id,
+            request_id: context.request_id,
+            recorded_at: context.recorded_at.into(),
+            items: context.items,
+        }
+    }
+}
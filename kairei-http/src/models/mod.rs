@@ -1,12 +1,22 @@
 pub mod agents;
+pub mod debug;
 pub mod docs;
+pub mod errors;
 pub mod events;
+pub mod memories;
+pub mod preflight;
+pub mod providers;
 pub mod system;
 pub mod user;
 
 // Re-export all models for easier imports
 pub use agents::*;
+pub use debug::*;
 pub use docs::*;
+pub use errors::*;
 pub use events::*;
+pub use memories::*;
+pub use preflight::*;
+pub use providers::*;
 pub use system::*;
 pub use user::*;
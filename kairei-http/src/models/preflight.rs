@@ -0,0 +1,40 @@
+use chrono::{DateTime, Utc};
+use kairei_core::preflight::{PreflightReport, PreflightSource};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Request for checking a deployment without starting it
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PreflightRequest {
+    /// System config of the deployment
+    pub config: kairei_core::config::SystemConfig,
+
+    /// DSL sources of the deployment, checked as one program in this order
+    #[serde(default)]
+    pub sources: Vec<PreflightSource>,
+
+    /// Whether to check the provider credentials against their APIs. The secrets
+    /// are those of the server, never taken from the request.
+    #[serde(default)]
+    pub validate_credentials: bool,
+}
+
+/// Status of a preflight job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PreflightJobStatus {
+    Running,
+    Completed,
+}
+
+/// A preflight run in the background, for large deployments and credential checks
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PreflightJob {
+    pub job_id: String,
+    pub status: PreflightJobStatus,
+    pub submitted_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+
+    /// The report, once completed
+    pub report: Option<PreflightReport>,
+}
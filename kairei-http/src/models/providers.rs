@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Request for validating a provider configuration before it is saved
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ValidateProviderRequest {
+    /// Provider configuration to validate
+    pub config: kairei_core::config::ProviderConfig,
+
+    /// Whether to probe the configured endpoint for connectivity
+    #[serde(default)]
+    pub probe: bool,
+}
+
+/// Response for provider configuration validation
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ValidateProviderResponse {
+    /// Whether the configuration is valid (and reachable, when probed)
+    pub valid: bool,
+
+    /// Validation errors
+    pub errors: Vec<ProviderValidationIssue>,
+
+    /// Validation warnings
+    pub warnings: Vec<ProviderValidationIssue>,
+
+    /// Connectivity probe result, present only when probing was requested
+    pub probe: Option<ProviderProbeResult>,
+}
+
+/// A single validation error or warning
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ProviderValidationIssue {
+    /// Path of the offending field in the provider config (e.g. `provider_specific.chunk_size`)
+    pub path: Option<String>,
+
+    /// Error message
+    pub message: String,
+
+    /// Error code (e.g. `VALIDATION_0001`)
+    pub error_code: String,
+
+    /// Suggestion for fixing the issue
+    pub suggestion: Option<String>,
+}
+
+/// Result of the endpoint connectivity probe
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ProviderProbeResult {
+    /// Endpoint that was probed
+    pub endpoint: Option<String>,
+
+    /// Whether the endpoint accepted a connection
+    pub reachable: bool,
+
+    /// Connection latency in milliseconds
+    pub latency_ms: Option<u64>,
+
+    /// Error message when the endpoint is unreachable
+    pub error: Option<String>,
+}
@@ -1,9 +1,12 @@
 use std::collections::HashMap;
 
+use chrono::{DateTime, Utc};
 use kairei_core::system::SystemError;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
+use crate::services::agent_import::AgentImportError;
+
 /// Requests
 #[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
 pub struct CreateSystemRequest {
@@ -15,6 +18,35 @@ pub struct CreateSystemRequest {
 
     /// System config
     pub config: kairei_core::config::SystemConfig,
+
+    /// Agent definitions to import: URLs of DSL files, or registry coordinates
+    /// `name@version`. Only hosts allowed by the server are fetched.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub agent_sources: Vec<String>,
+}
+
+/// An agent source of `create_system` could not be imported
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AgentImportErrorResponse {
+    /// The source as given in the request
+    pub source: String,
+
+    /// `invalid_source`, `registry_not_configured`, `host_not_allowed`,
+    /// `fetch_failed`, `fetch_status`, `too_large` or `validation`
+    pub kind: String,
+
+    /// Error message
+    pub error: String,
+}
+
+impl From<AgentImportError> for AgentImportErrorResponse {
+    fn from(error: AgentImportError) -> Self {
+        Self {
+            source: error.source_ref().to_string(),
+            kind: error.kind().to_string(),
+            error: error.to_string(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
@@ -73,6 +105,96 @@ pub struct ListSystemsResponse {
     pub system_statuses: HashMap<String, kairei_core::system::SystemStatus>,
 }
 
+/// Agents of a system and the requests they answer
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SystemCatalogResponse {
+    pub agents: Vec<kairei_core::catalog::AgentEntry>,
+}
+
+/// The `schedule` declarations of the world of a system
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ListSchedulesResponse {
+    pub schedules: Vec<kairei_core::native_feature::world_scheduler::ScheduleStatus>,
+}
+
+/// Control-plane lifecycle transitions, streamed by `GET /systems/events/stream`.
+/// Not to be confused with the events of the DSL event bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LifecycleEventKind {
+    SystemCreated,
+    SystemStarted,
+    SystemStopped,
+    SystemDeleted,
+    AgentStarted,
+    AgentStopped,
+    AgentScaledUp,
+    AgentScaledDown,
+}
+
+impl LifecycleEventKind {
+    /// Name of the SSE event
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::SystemCreated => "system_created",
+            Self::SystemStarted => "system_started",
+            Self::SystemStopped => "system_stopped",
+            Self::SystemDeleted => "system_deleted",
+            Self::AgentStarted => "agent_started",
+            Self::AgentStopped => "agent_stopped",
+            Self::AgentScaledUp => "agent_scaled_up",
+            Self::AgentScaledDown => "agent_scaled_down",
+        }
+    }
+}
+
+/// A lifecycle transition of a system or one of its agents
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct LifecycleEvent {
+    pub kind: LifecycleEventKind,
+    pub system_id: String,
+    /// Set for agent events
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+    /// Number of instances added or removed by scaling
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instances: Option<usize>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl LifecycleEvent {
+    pub fn system(kind: LifecycleEventKind, system_id: &str) -> Self {
+        Self {
+            kind,
+            system_id: system_id.to_string(),
+            agent_id: None,
+            instances: None,
+            timestamp: Utc::now(),
+        }
+    }
+
+    pub fn agent(kind: LifecycleEventKind, system_id: &str, agent_id: &str) -> Self {
+        Self {
+            agent_id: Some(agent_id.to_string()),
+            ..Self::system(kind, system_id)
+        }
+    }
+
+    pub fn with_instances(mut self, instances: usize) -> Self {
+        self.instances = Some(instances);
+        self
+    }
+}
+
+/// Sent as a `gap` SSE event when lifecycle events after `last_event_id` were lost:
+/// evicted from the history before a client resumed, or dropped because the stream
+/// fell behind. Without `last_event_id`, the events lost are from before the stream
+/// delivered anything.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct LifecycleStreamGap {
+    pub last_event_id: Option<u64>,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct StartSystemRequest {
     pub dsl: Option<String>,
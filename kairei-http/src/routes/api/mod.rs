@@ -10,4 +10,6 @@ pub fn api_v1_router() -> Router<AppState> {
         .merge(v1::system::routes())
         .merge(v1::compiler::routes())
         .merge(v1::docs::routes())
+        .merge(v1::providers::routes())
+        .merge(v1::preflight::routes())
 }
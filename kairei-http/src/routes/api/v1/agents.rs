@@ -1,12 +1,16 @@
-use crate::handlers::agents::get_agent;
+use crate::handlers::agents::{
+    get_agent, get_agent_contracts, get_agent_logs, get_agent_settings, preview_agent_reload,
+    update_agent_log_config, update_agent_settings,
+};
 use crate::handlers::{
-    create_agent, list_agents, request_agent, scale_down_agent, scale_up_agent, start_agent,
-    stop_agent,
+    create_agent, debug_eval_agent, get_request_context, list_agents, pin_agent_memories,
+    request_agent, scale_down_agent, scale_up_agent, start_agent, stop_agent, stream_request_agent,
+    test_agent_handler, update_agent_config,
 };
 use crate::server::AppState;
 use axum::{
     Router,
-    routing::{get, post},
+    routing::{get, post, put},
 };
 
 /// Create the agents routes with state
@@ -15,9 +19,30 @@ pub fn routes() -> Router<AppState> {
         .route("/", get(list_agents))
         .route("/", post(create_agent))
         .route("/{agent_id}", get(get_agent))
+        .route("/{agent_id}/contracts", get(get_agent_contracts))
         .route("/{agent_id}/start", post(start_agent))
         .route("/{agent_id}/stop", post(stop_agent))
         .route("/{agent_id}/scaleup", post(scale_up_agent))
         .route("/{agent_id}/scaledown", post(scale_down_agent))
         .route("/{agent_id}/request", post(request_agent))
+        .route("/{agent_id}/request/stream", post(stream_request_agent))
+        .route("/{agent_id}/debug/eval", post(debug_eval_agent))
+        .route(
+            "/{agent_id}/handlers/{event}/test",
+            post(test_agent_handler),
+        )
+        .route(
+            "/{agent_id}/config",
+            get(get_agent_settings)
+                .put(update_agent_config)
+                .patch(update_agent_settings),
+        )
+        .route("/{agent_id}/reload/preview", post(preview_agent_reload))
+        .route("/{agent_id}/logs", get(get_agent_logs))
+        .route("/{agent_id}/logs/config", put(update_agent_log_config))
+        .route("/{agent_id}/memory/pins", post(pin_agent_memories))
+        .route(
+            "/{agent_id}/requests/{request_id}/context",
+            get(get_request_context),
+        )
 }
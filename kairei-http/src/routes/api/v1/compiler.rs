@@ -2,12 +2,14 @@ use axum::{Router, routing::post};
 
 use crate::{
     server::AppState,
-    services::compiler::handlers::{suggest_fixes, validate_dsl},
+    services::compiler::handlers::{suggest_fixes, tokenize_dsl, validate_dsl},
 };
 
 /// Create the compiler routes with state
 pub fn routes() -> Router<AppState> {
-    Router::new().nest("/compiler", compiler_router())
+    Router::new()
+        .nest("/compiler", compiler_router())
+        .nest("/dsl", dsl_router())
 }
 
 pub fn compiler_router() -> Router<AppState> {
@@ -15,3 +17,7 @@ pub fn compiler_router() -> Router<AppState> {
         .route("/validate", post(validate_dsl))
         .route("/suggest", post(suggest_fixes))
 }
+
+pub fn dsl_router() -> Router<AppState> {
+    Router::new().route("/tokenize", post(tokenize_dsl))
+}
@@ -0,0 +1,22 @@
+use crate::handlers::{
+    end_debug_session, inspect_debug_session, list_handler_recordings, start_debug_session,
+    step_debug_session,
+};
+use crate::server::AppState;
+use axum::{
+    Router,
+    routing::{delete, get, post},
+};
+
+/// Create the debug routes with state
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/recordings", get(list_handler_recordings))
+        .route("/sessions", post(start_debug_session))
+        .route("/sessions/{session_id}/step", post(step_debug_session))
+        .route(
+            "/sessions/{session_id}/inspect",
+            post(inspect_debug_session),
+        )
+        .route("/sessions/{session_id}", delete(end_debug_session))
+}
@@ -1,8 +1,8 @@
 //! API routes for DSL documentation.
 
 use crate::handlers::{
-    export_documentation, get_all_documentation, get_category_documentation, get_documentation_map,
-    get_parser_documentation,
+    explain_diagnostic, export_documentation, get_all_documentation, get_category_documentation,
+    get_documentation_map, get_parser_documentation, list_diagnostics,
 };
 use crate::server::AppState;
 use axum::{
@@ -23,4 +23,6 @@ fn docs_routes() -> Router<AppState> {
         .route("/dsl/export", post(export_documentation))
         .route("/dsl/{category}", get(get_category_documentation))
         .route("/dsl/{category}/{name}", get(get_parser_documentation))
+        .route("/diagnostics", get(list_diagnostics))
+        .route("/diagnostics/{code}", get(explain_diagnostic))
 }
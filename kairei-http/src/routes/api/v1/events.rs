@@ -1,4 +1,4 @@
-use crate::handlers::events::{emit_event, subscribe_event};
+use crate::handlers::events::{emit_event, get_event_lineage, subscribe_event};
 use crate::handlers::list_events;
 use crate::server::AppState;
 use axum::routing::get;
@@ -10,4 +10,5 @@ pub fn routes() -> Router<AppState> {
         .route("/", get(list_events))
         .route("/{event_id}/emit", post(emit_event))
         .route("/{event_id}/subscribe", post(subscribe_event))
+        .route("/{event_id}/lineage", get(get_event_lineage))
 }
@@ -0,0 +1,11 @@
+use crate::handlers::{explain_memory_relevance, import_memories, search_memories};
+use crate::server::AppState;
+use axum::{Router, routing::post};
+
+/// Create the memories routes with state
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/{namespace}/import", post(import_memories))
+        .route("/{namespace}/search", post(search_memories))
+        .route("/{namespace}/relevance", post(explain_memory_relevance))
+}
@@ -1,5 +1,9 @@
 pub mod agents;
 pub mod compiler;
+pub mod debug;
 pub mod docs;
 pub mod events;
+pub mod memories;
+pub mod preflight;
+pub mod providers;
 pub mod system;
@@ -0,0 +1,20 @@
+use axum::{
+    Router,
+    routing::{get, post},
+};
+
+use crate::{
+    handlers::{get_preflight_job, run_preflight},
+    server::AppState,
+};
+
+/// Create the preflight routes with state
+pub fn routes() -> Router<AppState> {
+    Router::new().nest("/preflight", preflight_routes())
+}
+
+fn preflight_routes() -> Router<AppState> {
+    Router::new()
+        .route("/", post(run_preflight))
+        .route("/{job_id}", get(get_preflight_job))
+}
@@ -0,0 +1,12 @@
+use axum::{Router, routing::post};
+
+use crate::{handlers::validate_provider, server::AppState};
+
+/// Create the provider routes with state
+pub fn routes() -> Router<AppState> {
+    Router::new().nest("/providers", provider_routes())
+}
+
+fn provider_routes() -> Router<AppState> {
+    Router::new().route("/validate", post(validate_provider))
+}
@@ -1,6 +1,6 @@
 use crate::handlers::{
-    compile_system, create_system, delete_system, get_system, list_systems, start_system,
-    stop_system,
+    batch_agents, compile_system, create_system, delete_system, get_system, get_system_catalog,
+    list_system_schedules, list_systems, start_system, stop_system, stream_system_events,
 };
 use crate::server::AppState;
 use axum::routing::delete;
@@ -9,7 +9,7 @@ use axum::{
     routing::{get, post},
 };
 
-use super::{agents, events};
+use super::{agents, debug, events, memories};
 
 /// Create the system routes with state
 pub fn routes() -> Router<AppState> {
@@ -20,11 +20,17 @@ fn system_routes() -> Router<AppState> {
     Router::new()
         .route("/", get(list_systems))
         .route("/", post(create_system))
+        .route("/events/stream", get(stream_system_events))
         .route("/{system_id}", get(get_system))
+        .route("/{system_id}/catalog", get(get_system_catalog))
+        .route("/{system_id}/schedules", get(list_system_schedules))
         .route("/{system_id}/compile", post(compile_system))
         .route("/{system_id}/start", post(start_system))
         .route("/{system_id}/stop", post(stop_system))
         .route("/{system_id}", delete(delete_system))
+        .route("/{system_id}/agents:batch", post(batch_agents))
         .nest("/{system_id}/agents", agents::routes())
         .nest("/{system_id}/events", events::routes())
+        .nest("/{system_id}/memories", memories::routes())
+        .nest("/{system_id}/debug", debug::routes())
 }
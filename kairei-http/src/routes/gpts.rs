@@ -0,0 +1,194 @@
+//! GPTs action manifest derived from the OpenAPI document.
+//!
+//! GPTs actions accept only a small OpenAPI document, so the manifest keeps the
+//! allowlisted operations, compacts their descriptions, and declares the public
+//! server URL and the API key scheme.
+
+use std::collections::BTreeSet;
+
+use thiserror::Error;
+use utoipa::openapi::{
+    OpenApi, Server,
+    path::{Operation, PathItem},
+    security::{ApiKey, ApiKeyValue, SecurityRequirement, SecurityScheme},
+};
+
+use crate::server::ServerConfig;
+
+/// Name of the API key security scheme declared in the manifest
+pub const API_KEY_SCHEME: &str = "api_key";
+
+/// Maximum description length accepted by GPTs actions
+const MAX_DESCRIPTION_CHARS: usize = 300;
+
+const SCHEMA_REF_PREFIX: &str = "#/components/schemas/";
+
+#[derive(Debug, Error)]
+pub enum GptsManifestError {
+    #[error("Manifest has {count} operations, limit is {max}")]
+    TooManyOperations { count: usize, max: usize },
+
+    #[error("Manifest is {size} bytes, limit is {max}")]
+    TooLarge { size: usize, max: usize },
+
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+/// Build the GPTs action manifest from the full OpenAPI document
+///
+/// Returns the serialized manifest, which is checked against the operation
+/// and byte limits in [`crate::server::GptsManifestConfig`].
+pub fn build_gpts_manifest(
+    doc: &OpenApi,
+    config: &ServerConfig,
+) -> Result<String, GptsManifestError> {
+    let settings = &config.gpts_manifest;
+    let mut manifest = doc.clone();
+
+    manifest
+        .paths
+        .paths
+        .retain(|path, _| is_allowed(path, &settings.allowed_paths));
+
+    let mut count = 0;
+    for item in manifest.paths.paths.values_mut() {
+        for operation in operations_mut(item) {
+            count += 1;
+            let compact = operation
+                .operation_id
+                .as_ref()
+                .and_then(|id| settings.description_overrides.get(id))
+                .cloned()
+                .or_else(|| compact_description(operation));
+            operation.description = compact.map(|d| truncate(&d, MAX_DESCRIPTION_CHARS));
+            operation.summary = None;
+            operation.tags = None;
+        }
+    }
+    if count > settings.max_operations {
+        return Err(GptsManifestError::TooManyOperations {
+            count,
+            max: settings.max_operations,
+        });
+    }
+
+    // 参照されているスキーマだけ残す
+    let mut components = manifest.components.take().unwrap_or_default();
+    let mut used = BTreeSet::new();
+    let mut pending = schema_refs(&serde_json::to_value(&manifest.paths)?);
+    while let Some(name) = pending.pop_first() {
+        if !used.insert(name.clone()) {
+            continue;
+        }
+        if let Some(schema) = components.schemas.get(&name) {
+            pending.extend(schema_refs(&serde_json::to_value(schema)?));
+        }
+    }
+    components.schemas.retain(|name, _| used.contains(name));
+    components.responses.clear();
+    components.security_schemes.clear();
+    components.add_security_scheme(
+        API_KEY_SCHEME,
+        SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-Key"))),
+    );
+    manifest.components = Some(components);
+    manifest.security = Some(vec![SecurityRequirement::new(
+        API_KEY_SCHEME,
+        Vec::<String>::new(),
+    )]);
+
+    let base_url = settings
+        .public_base_url
+        .clone()
+        .unwrap_or_else(|| format!("http://{}:{}", config.host, config.port));
+    manifest.servers = Some(vec![Server::new(format!(
+        "{}/api/v1",
+        base_url.trim_end_matches('/')
+    ))]);
+    manifest.tags = None;
+
+    let json = serde_json::to_string(&manifest)?;
+    if json.len() > settings.max_bytes {
+        return Err(GptsManifestError::TooLarge {
+            size: json.len(),
+            max: settings.max_bytes,
+        });
+    }
+    Ok(json)
+}
+
+/// An allowlist entry matches the path itself and every path below it
+fn is_allowed(path: &str, allowed: &[String]) -> bool {
+    allowed.iter().any(|prefix| {
+        let prefix = prefix.trim_end_matches('/');
+        path == prefix
+            || path
+                .strip_prefix(prefix)
+                .is_some_and(|rest| rest.starts_with('/'))
+    })
+}
+
+fn operations_mut(item: &mut PathItem) -> impl Iterator<Item = &mut Operation> {
+    [
+        item.get.as_mut(),
+        item.put.as_mut(),
+        item.post.as_mut(),
+        item.delete.as_mut(),
+        item.options.as_mut(),
+        item.head.as_mut(),
+        item.patch.as_mut(),
+        item.trace.as_mut(),
+    ]
+    .into_iter()
+    .flatten()
+}
+
+/// Summary if present, otherwise the first paragraph of the description
+fn compact_description(operation: &Operation) -> Option<String> {
+    operation.summary.clone().or_else(|| {
+        operation
+            .description
+            .as_ref()
+            .and_then(|d| d.split("\n\n").next())
+            .map(|d| d.split_whitespace().collect::<Vec<_>>().join(" "))
+    })
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max_chars - 3).collect();
+    truncated.push_str("...");
+    truncated
+}
+
+fn schema_refs(value: &serde_json::Value) -> BTreeSet<String> {
+    let mut refs = BTreeSet::new();
+    collect_schema_refs(value, &mut refs);
+    refs
+}
+
+fn collect_schema_refs(value: &serde_json::Value, refs: &mut BTreeSet<String>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map {
+                match (key.as_str(), value) {
+                    ("$ref", serde_json::Value::String(reference)) => {
+                        if let Some(name) = reference.strip_prefix(SCHEMA_REF_PREFIX) {
+                            refs.insert(name.to_string());
+                        }
+                    }
+                    _ => collect_schema_refs(value, refs),
+                }
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                collect_schema_refs(item, refs);
+            }
+        }
+        _ => {}
+    }
+}
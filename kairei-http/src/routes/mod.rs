@@ -1,9 +1,16 @@
 pub mod api;
+pub mod gpts;
 pub mod swagger;
 
+use crate::handlers::get_metrics;
 use crate::server::{AppState, ServerConfig};
 use api::api_v1_router;
-use axum::{Router, http::StatusCode, response::IntoResponse, routing::get};
+use axum::{
+    Router,
+    http::{StatusCode, header},
+    response::IntoResponse,
+    routing::get,
+};
 
 use swagger::ApiDoc;
 use utoipa::{OpenApi, openapi::Server};
@@ -12,6 +19,9 @@ use utoipa_swagger_ui::SwaggerUi;
 /// Create the main API router with state
 pub fn create_api_router(config: &ServerConfig) -> Router<AppState> {
     let mut doc = ApiDoc::openapi();
+    let gpts_manifest = gpts::build_gpts_manifest(&doc, config).map_err(|e| {
+        tracing::warn!("GPTs action manifest is unavailable: {}", e);
+    });
     doc.servers = doc.servers.map(|mut servers| {
         let mut currents: Vec<Server> = config
             .servers
@@ -30,6 +40,18 @@ pub fn create_api_router(config: &ServerConfig) -> Router<AppState> {
     Router::new()
         .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", doc))
         .route("/health", get(health_check))
+        .route("/metrics", get(get_metrics))
+        .route(
+            "/api/v1/docs/gpts-manifest.json",
+            get(move || async move {
+                match gpts_manifest {
+                    Ok(manifest) => {
+                        ([(header::CONTENT_TYPE, "application/json")], manifest).into_response()
+                    }
+                    Err(()) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+                }
+            }),
+        )
         .nest("/api/v1", api_v1_router())
 }
 
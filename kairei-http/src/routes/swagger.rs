@@ -1,5 +1,9 @@
 use crate::handlers::agents;
+use crate::handlers::debug;
 use crate::handlers::events;
+use crate::handlers::memories;
+use crate::handlers::preflight;
+use crate::handlers::providers;
 use crate::handlers::system;
 use crate::models::CompileSystemRequest;
 use crate::models::CompileSystemResponse;
@@ -8,48 +12,119 @@ use crate::services::compiler::handlers as compiler;
 use utoipa::OpenApi;
 
 use crate::models::agents::{
-    AgentStatistics, AgentStatus, GetAgentResponse, ListAgentsResponse, ScaleDownAgentRequest,
-    ScaleUpAgentRequest, SendRequestAgentRequest, SendRequestAgentResponse, ValidationResult,
+    AgentConfigErrorResponse, AgentContractsResponse, AgentLogsResponse, AgentStatistics,
+    AgentStatus, BatchAgentsRequest, DebugEvalErrorResponse, DebugEvalRequest, DebugEvalResponse,
+    EmittedEvent, GetAgentResponse, ListAgentsResponse, ReloadPreviewErrorResponse,
+    ReloadPreviewRequest, ScaleDownAgentRequest, ScaleUpAgentRequest, SendRequestAgentRequest,
+    SendRequestAgentResponse, StateChange, TestHandlerErrorResponse, TestHandlerRequest,
+    TestHandlerResponse, UpdateAgentConfigRequest, ValidationResult,
 };
+use crate::models::debug::{
+    DebugSessionErrorResponse, DebugStepResponse, HandlerRecordingSummary, ListRecordingsResponse,
+    StartDebugSessionRequest, StartDebugSessionResponse,
+};
+use crate::models::errors::{ApiError, FieldError};
 use crate::models::events::{
-    AgentRequestPayload, AgentRequestResponse, EventRequest, EventResponse, EventStatus,
-    RequestStatus,
+    AgentRequestPayload, AgentRequestResponse, EventLineageNode, EventRequest, EventResponse,
+    EventStatus, ParameterErrorResponse, RequestStatus, ValidationErrorResponse,
+};
+use crate::models::memories::{
+    ImportLineErrorResponse, ImportMemoriesResponse, InjectedContextResponse, MemoryPin,
+    MemoryPinsResponse, MemoryRelevanceRequest, MemoryRelevanceResponse, MemorySearchHit,
+    PinMemoriesRequest, RankedMemory, SearchMemoriesRequest, SearchMemoriesResponse,
+};
+use crate::models::preflight::{PreflightJob, PreflightJobStatus, PreflightRequest};
+use crate::models::providers::{
+    ProviderProbeResult, ProviderValidationIssue, ValidateProviderRequest, ValidateProviderResponse,
 };
 use crate::models::{
-    CreateSystemRequest, CreateSystemResponse, ListSystemsResponse, StartSystemRequest, SystemInfo,
-    SystemStatistics, SystemStatus,
+    AgentImportErrorResponse, CreateSystemRequest, CreateSystemResponse, LifecycleEvent,
+    LifecycleEventKind, LifecycleStreamGap, ListSchedulesResponse, ListSystemsResponse,
+    StartSystemRequest, SystemCatalogResponse, SystemInfo, SystemStatistics, SystemStatus,
 };
 use crate::services::compiler::models::{
-    ErrorLocation, SuggestionRequest, SuggestionResponse, ValidationError, ValidationRequest,
-    ValidationResponse, ValidationSuggestion, ValidationWarning,
+    ErrorLocation, SuggestionRequest, SuggestionResponse, TokenizeRequest, TokenizeResponse,
+    ValidationError, ValidationRequest, ValidationResponse, ValidationSuggestion,
+    ValidationWarning,
+};
+use kairei_core::agent_batch::{
+    AgentSelector, BatchOperation, BatchOutcome, BatchReport, BatchResult, BatchSummary, RunStatus,
+};
+use kairei_core::agent_log::{CapturedLog, LogLevel};
+use kairei_core::agent_settings::{AgentSettings, AgentSettingsPatch};
+use kairei_core::catalog::{AgentEntry, ParameterSignature, RequestSignature};
+use kairei_core::config::AgentLogConfig;
+use kairei_core::eval::evaluator::ConstraintViolation;
+use kairei_core::eval::profile::{ProfileSection, RequestProfile};
+use kairei_core::event::stream_event::{StreamEvent, StreamUsage};
+use kairei_core::handler_test::HandlerKind;
+use kairei_core::native_feature::world_scheduler::ScheduleStatus;
+use kairei_core::preflight::{
+    ComponentReport, PreflightComponent, PreflightDiagnostic, PreflightReport, PreflightSource,
+    Severity,
+};
+use kairei_core::provider::capabilities::sistence_memory::{
+    InjectedMemory, MemoryFilter, PinMode, RelevanceFactor, TimeField,
 };
+use kairei_core::reload_plan::ReloadPlan;
+use kairei_core::tokenizer::token::{ByteSpan, TokenJson};
 
 #[derive(OpenApi)]
 #[openapi(
     paths(
         system::create_system,
         system::get_system,
+        system::get_system_catalog,
+        system::list_system_schedules,
         system::list_systems,
         system::compile_system,
         system::start_system,
         system::stop_system,
         system::delete_system,
+        system::stream_system_events,
         agents::get_agent,
+        agents::get_agent_contracts,
         agents::list_agents,
         agents::start_agent,
         agents::stop_agent,
         agents::scale_up_agent,
         agents::scale_down_agent,
+        agents::batch_agents,
         agents::request_agent,
+        agents::stream_request_agent,
+        agents::debug_eval_agent,
+        agents::test_agent_handler,
+        agents::update_agent_config,
+        agents::get_agent_settings,
+        agents::update_agent_settings,
+        agents::preview_agent_reload,
+        agents::get_agent_logs,
+        agents::update_agent_log_config,
+        debug::list_handler_recordings,
+        debug::start_debug_session,
+        debug::step_debug_session,
+        debug::inspect_debug_session,
+        debug::end_debug_session,
         events::list_events,
         events::emit_event,
         events::subscribe_event,
+        events::get_event_lineage,
+        memories::import_memories,
+        memories::search_memories,
+        memories::explain_memory_relevance,
+        memories::pin_agent_memories,
+        memories::get_request_context,
+        providers::validate_provider,
+        preflight::run_preflight,
+        preflight::get_preflight_job,
         compiler::validate_dsl,
-        compiler::suggest_fixes
+        compiler::suggest_fixes,
+        compiler::tokenize_dsl
     ),
     components(schemas(
         CreateSystemRequest,
         CreateSystemResponse,
+        AgentImportErrorResponse,
         ListSystemsResponse,
         CompileSystemRequest,
         CompileSystemResponse,
@@ -57,21 +132,105 @@ use crate::services::compiler::models::{
         SystemInfo,
         SystemStatus,
         SystemStatistics,
+        SystemCatalogResponse,
+        ListSchedulesResponse,
+        ScheduleStatus,
+        LifecycleEvent,
+        LifecycleEventKind,
+        LifecycleStreamGap,
+        AgentEntry,
+        RequestSignature,
+        ParameterSignature,
         GetAgentResponse,
+        AgentContractsResponse,
         ListAgentsResponse,
         ScaleUpAgentRequest,
         ScaleDownAgentRequest,
+        BatchAgentsRequest,
+        BatchOperation,
+        AgentSelector,
+        RunStatus,
+        BatchReport,
+        BatchResult,
+        BatchOutcome,
+        BatchSummary,
         SendRequestAgentRequest,
         SendRequestAgentResponse,
+        StreamEvent,
+        StreamUsage,
+        DebugEvalRequest,
+        DebugEvalResponse,
+        DebugEvalErrorResponse,
+        TestHandlerRequest,
+        TestHandlerResponse,
+        TestHandlerErrorResponse,
+        StateChange,
+        EmittedEvent,
+        HandlerKind,
+        HandlerRecordingSummary,
+        ListRecordingsResponse,
+        StartDebugSessionRequest,
+        StartDebugSessionResponse,
+        DebugStepResponse,
+        DebugSessionErrorResponse,
+        UpdateAgentConfigRequest,
+        AgentConfigErrorResponse,
+        AgentSettings,
+        AgentSettingsPatch,
+        ReloadPreviewRequest,
+        ReloadPlan,
+        ReloadPreviewErrorResponse,
+        AgentLogsResponse,
+        AgentLogConfig,
+        LogLevel,
+        CapturedLog,
         AgentStatus,
         ValidationResult,
         AgentStatistics,
         EventRequest,
         EventResponse,
         EventStatus,
+        ParameterErrorResponse,
+        ValidationErrorResponse,
+        ConstraintViolation,
+        RequestProfile,
+        ProfileSection,
+        ApiError,
+        FieldError,
         AgentRequestPayload,
         AgentRequestResponse,
         RequestStatus,
+        EventLineageNode,
+        ImportMemoriesResponse,
+        ImportLineErrorResponse,
+        SearchMemoriesRequest,
+        SearchMemoriesResponse,
+        MemorySearchHit,
+        MemoryFilter,
+        TimeField,
+        MemoryRelevanceRequest,
+        MemoryRelevanceResponse,
+        RankedMemory,
+        RelevanceFactor,
+        PinMemoriesRequest,
+        PinMode,
+        MemoryPinsResponse,
+        MemoryPin,
+        InjectedContextResponse,
+        InjectedMemory,
+        ValidateProviderRequest,
+        ValidateProviderResponse,
+        ProviderValidationIssue,
+        ProviderProbeResult,
+        PreflightRequest,
+        PreflightSource,
+        PreflightJob,
+        PreflightJobStatus,
+        PreflightReport,
+        ComponentReport,
+        PreflightComponent,
+        PreflightDiagnostic,
+        Severity,
         ValidationRequest,
         ValidationResponse,
         ValidationError,
@@ -79,7 +238,11 @@ use crate::services::compiler::models::{
         ErrorLocation,
         ValidationSuggestion,
         SuggestionRequest,
-        SuggestionResponse
+        SuggestionResponse,
+        TokenizeRequest,
+        TokenizeResponse,
+        TokenJson,
+        ByteSpan
     )),
     tags(
         (name = "compiler", description = "Compiler API")
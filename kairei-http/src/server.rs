@@ -1,15 +1,23 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::trace::TraceLayer;
 use tracing::{debug, info, warn};
 
-use crate::auth::{AuthStore, auth_middleware};
+use crate::auth::{
+    AuthConfig, AuthProvider, AuthProviderChain, AuthResult, AuthStore, auth_middleware,
+};
+use crate::handlers::validation::RequestValidationConfig;
 use crate::routes::create_api_router;
+use crate::services::agent_import::{AgentImportConfig, AgentImporter};
 use crate::services::compiler::{CompilerSystemManager, DslLoader};
+use crate::services::preflight::PreflightJobs;
 use crate::session::manager::{SessionConfig, SessionManager};
 use kairei_core::config::{SystemConfig, TickerConfig};
+use kairei_core::provider::config::plugins::LocalFileSystemConfig;
+use kairei_core::provider::plugins::storage::local_fs::LocalFileSystemBackend;
 
 /// Server configuration
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -34,6 +42,37 @@ pub struct ServerConfig {
 
     /// Enable the ticker for compiler services
     pub enable_ticker: bool,
+
+    /// Reload the compiler services' system when its DSL files change
+    #[serde(default)]
+    pub watch_dsl: bool,
+
+    /// GPTs action manifest settings
+    #[serde(default)]
+    pub gpts_manifest: GptsManifestConfig,
+
+    /// Authentication providers, tried in order
+    #[serde(default)]
+    pub auth: AuthConfig,
+
+    /// Reject destructive operations (stopping or deleting a system, stopping or
+    /// scaling down an agent) sent without `If-Match` with 428
+    #[serde(default)]
+    pub require_if_match: bool,
+
+    /// Importing agent definitions from other hosts when creating a system
+    #[serde(default)]
+    pub agent_import: AgentImportConfig,
+
+    /// Limits of request bodies
+    #[serde(default)]
+    pub request_validation: RequestValidationConfig,
+
+    /// Directory keeping the versions of systems and agents served as ETags, so an
+    /// ETag seen before a restart is not taken for a current one. Kept in memory
+    /// when unset
+    #[serde(default)]
+    pub versions_directory: Option<String>,
 }
 
 impl Default for ServerConfig {
@@ -46,6 +85,54 @@ impl Default for ServerConfig {
             dsl_directory: "dsl".to_string(),
             enable_dsl_compiler: true,
             enable_ticker: false,
+            watch_dsl: false,
+            gpts_manifest: GptsManifestConfig::default(),
+            auth: AuthConfig::default(),
+            require_if_match: false,
+            agent_import: AgentImportConfig::default(),
+            request_validation: RequestValidationConfig::default(),
+            versions_directory: None,
+        }
+    }
+}
+
+/// Settings for the GPTs action manifest served at `/api/v1/docs/gpts-manifest.json`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GptsManifestConfig {
+    /// Public base URL of the server (e.g. `https://kairei.example.com`).
+    /// `/api/v1` is appended; falls back to `http://{host}:{port}` when unset
+    pub public_base_url: Option<String>,
+
+    /// Paths to publish; an entry matches the path itself and every path below it
+    pub allowed_paths: Vec<String>,
+
+    /// Compact operation descriptions keyed by operation ID
+    pub description_overrides: HashMap<String, String>,
+
+    /// Maximum number of operations in the manifest
+    pub max_operations: usize,
+
+    /// Maximum size of the serialized manifest in bytes
+    pub max_bytes: usize,
+}
+
+impl Default for GptsManifestConfig {
+    fn default() -> Self {
+        Self {
+            public_base_url: None,
+            allowed_paths: vec![
+                "/assistant".to_string(),
+                "/compiler/validate".to_string(),
+                "/compiler/format".to_string(),
+            ],
+            description_overrides: HashMap::from([(
+                "validate_dsl".to_string(),
+                "Validate KAIREI DSL code and return errors and warnings with locations."
+                    .to_string(),
+            )]),
+            max_operations: 30,
+            max_bytes: 64 * 1024,
         }
     }
 }
@@ -74,6 +161,14 @@ pub struct AppState {
     pub auth_store: AuthStore,
     /// System instance for DSL validation and execution
     pub compiler_system_manager: Option<Arc<CompilerSystemManager>>,
+    /// See [`ServerConfig::require_if_match`]
+    pub require_if_match: bool,
+    /// Fetches agent definitions named by `create_system`
+    pub agent_importer: Arc<AgentImporter>,
+    /// See [`ServerConfig::request_validation`]
+    pub request_validation: RequestValidationConfig,
+    /// Preflight checks running in the background
+    pub preflight_jobs: Arc<PreflightJobs>,
 }
 
 /// Start the HTTP server
@@ -82,79 +177,153 @@ pub async fn start_server(
     secret: Secret,
     system_secret: Option<kairei_core::config::SecretConfig>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    // Set up CORS
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
-
-    // Create the session manager
-    let session_config = SessionConfig::default();
-    let session_manager = SessionManager::new(session_config, system_secret.clone());
-
-    // Create the auth store
-    let auth_store = AuthStore::default();
-    auth_store.clean_keys();
-    auth_store.add_api_key(secret.admin_service_key, "admin");
-    auth_store.add_api_key(format!("{}_1", secret.user_service_key.clone()), "user1");
-    auth_store.add_api_key(format!("{}_2", secret.user_service_key.clone()), "user2");
-
-    let compiler_system_manager = if config.enable_dsl_compiler {
-        // Initialize the system
-        let mut system_config = SystemConfig::default();
-        system_config.native_feature_config.ticker = Some(TickerConfig {
-            enabled: config.enable_ticker,
-            ..Default::default()
-        });
-        let secret_config = system_secret.unwrap_or_default();
-        let dsl_loader = DslLoader::with_base_dir(config.dsl_directory.clone());
-        debug!("dsl_loader setup: {:?}", dsl_loader);
-        let mut compiler_system_manager =
-            CompilerSystemManager::new(system_config, secret_config, Some(dsl_loader));
-        compiler_system_manager.initialize(true).await?;
-
-        info!("Initialized Kairei system for DSL-based compiler services");
-        Some(Arc::new(compiler_system_manager))
-    } else {
-        warn!("DSL-based compiler services are disabled");
-        None
-    };
-
-    // Create the application state
-    let app_state = AppState {
-        session_manager,
-        auth_store: auth_store.clone(),
-        compiler_system_manager,
-    };
-
-    info!("Initialized session manager and auth store");
-
-    // Create the router with all routes and add the app state
-    let mut app = create_api_router(&config).with_state(app_state.clone());
-
-    // Apply authentication middleware if enabled
-    if config.enable_auth {
-        info!("Authentication enabled");
-        // Apply the auth middleware to all routes
-        let auth_store = Arc::new(app_state.auth_store.clone());
-        app = app.layer(axum::middleware::from_fn_with_state(
-            auth_store,
-            auth_middleware,
-        ));
+    let mut builder = ServerBuilder::new(config).secret(secret);
+    if let Some(system_secret) = system_secret {
+        builder = builder.system_secret(system_secret);
+    }
+    builder.start().await
+}
+
+/// Builder for embedders that customize the server before it starts,
+/// e.g. to register their own auth providers.
+pub struct ServerBuilder {
+    config: ServerConfig,
+    secret: Secret,
+    system_secret: Option<kairei_core::config::SecretConfig>,
+    auth_providers: HashMap<String, Arc<dyn AuthProvider>>,
+}
+
+impl ServerBuilder {
+    pub fn new(config: ServerConfig) -> Self {
+        Self {
+            config,
+            secret: Secret::default(),
+            system_secret: None,
+            auth_providers: HashMap::new(),
+        }
     }
 
-    // Add common middleware
-    let app = app.layer(TraceLayer::new_for_http()).layer(cors);
+    pub fn secret(mut self, secret: Secret) -> Self {
+        self.secret = secret;
+        self
+    }
 
-    // Parse the socket address
-    let addr = format!("{}:{}", config.host, config.port).parse::<SocketAddr>()?;
+    pub fn system_secret(mut self, system_secret: kairei_core::config::SecretConfig) -> Self {
+        self.system_secret = Some(system_secret);
+        self
+    }
 
-    // Start the server
-    info!("Starting server on {}", addr);
+    /// Register a provider under `name`, for `{"type": "custom", "name": ...}`
+    /// entries of [`ServerConfig::auth`]
+    pub fn auth_provider(
+        mut self,
+        name: impl Into<String>,
+        provider: Arc<dyn AuthProvider>,
+    ) -> Self {
+        self.auth_providers.insert(name.into(), provider);
+        self
+    }
+
+    /// Build the provider chain configured in [`ServerConfig::auth`]
+    pub fn auth_chain(&self, auth_store: &AuthStore) -> AuthResult<AuthProviderChain> {
+        AuthProviderChain::from_config(&self.config.auth, auth_store, &self.auth_providers)
+    }
+
+    /// Start the HTTP server
+    pub async fn start(self) -> Result<(), Box<dyn std::error::Error>> {
+        let config = &self.config;
+        let secret = &self.secret;
+        let system_secret = self.system_secret.clone();
+        // Set up CORS
+        let cors = CorsLayer::new()
+            .allow_origin(Any)
+            .allow_methods(Any)
+            .allow_headers(Any);
+
+        // Create the session manager
+        let session_config = SessionConfig::default();
+        let mut session_manager = SessionManager::new(session_config, system_secret.clone());
+        if let Some(base_dir) = config.versions_directory.clone() {
+            session_manager = session_manager.with_versions_store(Arc::new(
+                LocalFileSystemBackend::new(LocalFileSystemConfig {
+                    base_dir,
+                    file_extension: "json".to_string(),
+                }),
+            ));
+        }
 
-    // In axum 0.8.x, we use this pattern to start the server
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+        // Create the auth store
+        let auth_store = AuthStore::default();
+        auth_store.clean_keys();
+        auth_store.add_api_key(secret.admin_service_key.clone(), "admin");
+        auth_store.add_api_key(format!("{}_1", secret.user_service_key.clone()), "user1");
+        auth_store.add_api_key(format!("{}_2", secret.user_service_key.clone()), "user2");
+        let auth_chain = Arc::new(self.auth_chain(&auth_store)?);
 
-    Ok(())
+        let compiler_system_manager = if config.enable_dsl_compiler {
+            // Initialize the system
+            let mut system_config = SystemConfig::default();
+            system_config.native_feature_config.ticker = Some(TickerConfig {
+                enabled: config.enable_ticker,
+                ..Default::default()
+            });
+            let secret_config = system_secret.unwrap_or_default();
+            let dsl_loader = DslLoader::with_base_dir(config.dsl_directory.clone());
+            debug!("dsl_loader setup: {:?}", dsl_loader);
+            let mut compiler_system_manager =
+                CompilerSystemManager::new(system_config, secret_config, Some(dsl_loader))
+                    .with_dsl_watch(config.watch_dsl);
+            compiler_system_manager.initialize(true).await?;
+
+            info!("Initialized Kairei system for DSL-based compiler services");
+            Some(Arc::new(compiler_system_manager))
+        } else {
+            warn!("DSL-based compiler services are disabled");
+            None
+        };
+
+        // Create the application state
+        let app_state = AppState {
+            session_manager,
+            auth_store: auth_store.clone(),
+            compiler_system_manager,
+            require_if_match: config.require_if_match,
+            agent_importer: Arc::new(AgentImporter::new(config.agent_import.clone())),
+            request_validation: config.request_validation.clone(),
+            preflight_jobs: Arc::new(PreflightJobs::default()),
+        };
+
+        info!("Initialized session manager and auth store");
+
+        // Create the router with all routes and add the app state
+        let mut app = create_api_router(config).with_state(app_state.clone());
+
+        // Apply authentication middleware if enabled
+        if config.enable_auth {
+            info!(
+                "Authentication enabled with {} provider(s)",
+                auth_chain.len()
+            );
+            // Apply the auth middleware to all routes
+            app = app.layer(axum::middleware::from_fn_with_state(
+                auth_chain,
+                auth_middleware,
+            ));
+        }
+
+        // Add common middleware
+        let app = app.layer(TraceLayer::new_for_http()).layer(cors);
+
+        // Parse the socket address
+        let addr = format!("{}:{}", config.host, config.port).parse::<SocketAddr>()?;
+
+        // Start the server
+        info!("Starting server on {}", addr);
+
+        // In axum 0.8.x, we use this pattern to start the server
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        axum::serve(listener, app).await?;
+
+        Ok(())
+    }
 }
@@ -0,0 +1,372 @@
+//! Import of agent definitions hosted outside the server.
+//!
+//! `create_system` accepts `agent_sources`, each of which is either
+//!
+//! - the URL of a DSL file (`http` or `https`), or
+//! - a registry coordinate `name@version`, fetched from
+//!   `{registry_url}/{name}/{version}`.
+//!
+//! Definitions are only fetched from the hosts in
+//! [`AgentImportConfig::allowed_hosts`], redirects included, so an empty list
+//! disables imports. A fetched definition is parsed and type-checked by the system
+//! being created; it may declare micro and sistence agents but not a world.
+//! Downloads are cached for [`AgentImportConfig::cache_ttl_secs`].
+
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use kairei_core::{MicroAgentDef, Root, SistenceAgentDef, system::System};
+use reqwest::{StatusCode, Url, redirect};
+use serde::{Deserialize, Serialize};
+
+/// Settings for importing agent definitions
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AgentImportConfig {
+    /// Hosts definitions may be fetched from. `*.example.com` matches the
+    /// subdomains of `example.com`
+    pub allowed_hosts: Vec<String>,
+
+    /// Base URL resolving registry coordinates
+    pub registry_url: Option<String>,
+
+    /// How long a fetched definition is reused
+    pub cache_ttl_secs: u64,
+
+    /// Timeout of a single fetch
+    pub timeout_secs: u64,
+
+    /// Maximum size of a definition in bytes
+    pub max_bytes: usize,
+}
+
+impl Default for AgentImportConfig {
+    fn default() -> Self {
+        Self {
+            allowed_hosts: vec![],
+            registry_url: None,
+            cache_ttl_secs: 300,
+            timeout_secs: 10,
+            max_bytes: 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AgentImportError {
+    #[error("{source_ref}: not a URL or a registry coordinate `name@version`")]
+    InvalidSource { source_ref: String },
+    #[error("{source_ref}: no registry_url is configured for registry coordinates")]
+    RegistryNotConfigured { source_ref: String },
+    #[error("{source_ref}: host `{host}` is not allowed")]
+    HostNotAllowed { source_ref: String, host: String },
+    #[error("{source_ref}: fetch failed: {message}")]
+    FetchFailed { source_ref: String, message: String },
+    #[error("{source_ref}: fetch returned {status}")]
+    Status {
+        source_ref: String,
+        status: StatusCode,
+    },
+    #[error("{source_ref}: definition exceeds {max_bytes} bytes")]
+    TooLarge {
+        source_ref: String,
+        max_bytes: usize,
+    },
+    #[error("{source_ref}: invalid definition: {message}")]
+    Validation { source_ref: String, message: String },
+}
+
+impl AgentImportError {
+    /// The source as given in the request
+    pub fn source_ref(&self) -> &str {
+        match self {
+            Self::InvalidSource { source_ref }
+            | Self::RegistryNotConfigured { source_ref }
+            | Self::HostNotAllowed { source_ref, .. }
+            | Self::FetchFailed { source_ref, .. }
+            | Self::Status { source_ref, .. }
+            | Self::TooLarge { source_ref, .. }
+            | Self::Validation { source_ref, .. } => source_ref,
+        }
+    }
+
+    /// Machine-readable kind, used in error responses
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::InvalidSource { .. } => "invalid_source",
+            Self::RegistryNotConfigured { .. } => "registry_not_configured",
+            Self::HostNotAllowed { .. } => "host_not_allowed",
+            Self::FetchFailed { .. } => "fetch_failed",
+            Self::Status { .. } => "fetch_status",
+            Self::TooLarge { .. } => "too_large",
+            Self::Validation { .. } => "validation",
+        }
+    }
+}
+
+/// Agents imported into a system, added to its DSL when it starts
+#[derive(Debug, Clone, Default)]
+pub struct ImportedAgents {
+    pub micro_agent_defs: Vec<MicroAgentDef>,
+    pub sistence_agent_defs: Vec<SistenceAgentDef>,
+}
+
+impl ImportedAgents {
+    pub fn merge_into(&self, root: &mut Root) {
+        root.micro_agent_defs
+            .extend(self.micro_agent_defs.iter().cloned());
+        root.sistence_agent_defs
+            .extend(self.sistence_agent_defs.iter().cloned());
+    }
+}
+
+struct CachedDefinition {
+    fetched_at: Instant,
+    dsl: String,
+}
+
+/// Fetches and validates agent definitions, see the [module docs](self)
+pub struct AgentImporter {
+    config: AgentImportConfig,
+    client: reqwest::Client,
+    /// 解決済み URL ごとの取得結果
+    cache: DashMap<String, CachedDefinition>,
+}
+
+impl Default for AgentImporter {
+    fn default() -> Self {
+        Self::new(AgentImportConfig::default())
+    }
+}
+
+impl AgentImporter {
+    pub fn new(config: AgentImportConfig) -> Self {
+        // リダイレクト先も許可リストで検査する
+        let allowed_hosts = config.allowed_hosts.clone();
+        let policy = redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= 10 || !is_allowed(&allowed_hosts, attempt.url()) {
+                attempt.stop()
+            } else {
+                attempt.follow()
+            }
+        });
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .redirect(policy)
+            .build()
+            .expect("failed to build the HTTP client for agent imports");
+        Self {
+            config,
+            client,
+            cache: DashMap::new(),
+        }
+    }
+
+    /// The URL `source` is fetched from, if its host is allowed
+    pub fn resolve(&self, source: &str) -> Result<Url, AgentImportError> {
+        let url = if source.contains("://") {
+            Url::parse(source)
+                .ok()
+                .filter(|url| matches!(url.scheme(), "http" | "https"))
+                .ok_or_else(|| AgentImportError::InvalidSource {
+                    source_ref: source.to_string(),
+                })?
+        } else {
+            let (name, version) = source
+                .split_once('@')
+                .filter(|(name, version)| is_coordinate_part(name) && is_coordinate_part(version))
+                .ok_or_else(|| AgentImportError::InvalidSource {
+                    source_ref: source.to_string(),
+                })?;
+            let registry_url = self.config.registry_url.as_ref().ok_or_else(|| {
+                AgentImportError::RegistryNotConfigured {
+                    source_ref: source.to_string(),
+                }
+            })?;
+            Url::parse(&format!(
+                "{}/{}/{}",
+                registry_url.trim_end_matches('/'),
+                name,
+                version
+            ))
+            .map_err(|_| AgentImportError::InvalidSource {
+                source_ref: source.to_string(),
+            })?
+        };
+
+        if !is_allowed(&self.config.allowed_hosts, &url) {
+            return Err(AgentImportError::HostNotAllowed {
+                source_ref: source.to_string(),
+                host: url.host_str().unwrap_or_default().to_string(),
+            });
+        }
+        Ok(url)
+    }
+
+    /// The DSL of `source`, from the cache while it is fresh
+    pub async fn fetch(&self, source: &str) -> Result<String, AgentImportError> {
+        let url = self.resolve(source)?;
+        let ttl = Duration::from_secs(self.config.cache_ttl_secs);
+        let cached = self
+            .cache
+            .get(url.as_str())
+            .filter(|cached| cached.fetched_at.elapsed() < ttl)
+            .map(|cached| cached.dsl.clone());
+        if let Some(dsl) = cached {
+            return Ok(dsl);
+        }
+
+        let fetch_failed = |e: reqwest::Error| AgentImportError::FetchFailed {
+            source_ref: source.to_string(),
+            message: e.to_string(),
+        };
+        let response = self
+            .client
+            .get(url.clone())
+            .send()
+            .await
+            .map_err(fetch_failed)?;
+        if !response.status().is_success() {
+            return Err(AgentImportError::Status {
+                source_ref: source.to_string(),
+                status: response.status(),
+            });
+        }
+        let too_large = AgentImportError::TooLarge {
+            source_ref: source.to_string(),
+            max_bytes: self.config.max_bytes,
+        };
+        if response
+            .content_length()
+            .is_some_and(|len| len > self.config.max_bytes as u64)
+        {
+            return Err(too_large);
+        }
+        let body = response.bytes().await.map_err(fetch_failed)?;
+        if body.len() > self.config.max_bytes {
+            return Err(too_large);
+        }
+        let dsl = String::from_utf8(body.to_vec()).map_err(|_| AgentImportError::Validation {
+            source_ref: source.to_string(),
+            message: "definition is not UTF-8".to_string(),
+        })?;
+
+        self.cache.insert(
+            url.to_string(),
+            CachedDefinition {
+                fetched_at: Instant::now(),
+                dsl: dsl.clone(),
+            },
+        );
+        Ok(dsl)
+    }
+
+    /// Fetch every source and validate it against `system`
+    pub async fn import(
+        &self,
+        system: &System,
+        sources: &[String],
+    ) -> Result<ImportedAgents, AgentImportError> {
+        let mut imported = ImportedAgents::default();
+        for source in sources {
+            let dsl = self.fetch(source).await?;
+            let root = system
+                .parse_dsl(&dsl)
+                .await
+                .map_err(|e| AgentImportError::Validation {
+                    source_ref: source.clone(),
+                    message: e.to_string(),
+                })?;
+            if root.world_def.is_some() {
+                return Err(AgentImportError::Validation {
+                    source_ref: source.clone(),
+                    message: "an imported definition cannot declare a world".to_string(),
+                });
+            }
+            imported.micro_agent_defs.extend(root.micro_agent_defs);
+            imported
+                .sistence_agent_defs
+                .extend(root.sistence_agent_defs);
+        }
+        Ok(imported)
+    }
+}
+
+fn is_allowed(allowed_hosts: &[String], url: &Url) -> bool {
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    allowed_hosts
+        .iter()
+        .any(|allowed| match allowed.strip_prefix("*.") {
+            Some(domain) => host
+                .strip_suffix(domain)
+                .is_some_and(|sub| sub.ends_with('.')),
+            None => host.eq_ignore_ascii_case(allowed),
+        })
+}
+
+fn is_coordinate_part(part: &str) -> bool {
+    !part.is_empty()
+        && part
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        && part != "."
+        && part != ".."
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn importer(allowed_hosts: &[&str], registry_url: Option<&str>) -> AgentImporter {
+        AgentImporter::new(AgentImportConfig {
+            allowed_hosts: allowed_hosts.iter().map(|h| h.to_string()).collect(),
+            registry_url: registry_url.map(str::to_string),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_resolve_checks_the_allowlist() {
+        let importer = importer(&["agents.example.com", "*.kairei.dev"], None);
+        assert!(
+            importer
+                .resolve("https://agents.example.com/a.kairei")
+                .is_ok()
+        );
+        assert!(importer.resolve("https://hub.kairei.dev/a.kairei").is_ok());
+        assert!(matches!(
+            importer.resolve("https://kairei.dev/a.kairei"),
+            Err(AgentImportError::HostNotAllowed { .. })
+        ));
+        assert!(matches!(
+            importer.resolve("https://evilkairei.dev/a.kairei"),
+            Err(AgentImportError::HostNotAllowed { .. })
+        ));
+        assert!(matches!(
+            importer.resolve("file:///etc/passwd"),
+            Err(AgentImportError::InvalidSource { .. })
+        ));
+    }
+
+    #[test]
+    fn test_resolve_registry_coordinates() {
+        let registry = importer(
+            &["registry.kairei.dev"],
+            Some("https://registry.kairei.dev/agents/"),
+        );
+        assert_eq!(
+            registry.resolve("weather@1.2.0").unwrap().as_str(),
+            "https://registry.kairei.dev/agents/weather/1.2.0"
+        );
+        assert!(matches!(
+            registry.resolve("weather@.."),
+            Err(AgentImportError::InvalidSource { .. })
+        ));
+        assert!(matches!(
+            importer(&[], None).resolve("weather@1.2.0"),
+            Err(AgentImportError::RegistryNotConfigured { .. })
+        ));
+    }
+}
@@ -1,14 +1,23 @@
-use axum::{extract::State, http::header::HeaderMap, response::Json};
+use axum::{
+    extract::State,
+    http::header::{ACCEPT_LANGUAGE, HeaderMap},
+    response::Json,
+};
 use chrono::Utc;
-use kairei_core::{ASTError, system::SystemError, tokenizer::token::TokenizerError};
+use kairei_core::{
+    ASTError,
+    message_catalog::{Diagnostic, Locale, MessageCatalog, codes},
+    system::SystemError,
+    tokenizer::token::{TokenJson, Tokenizer, TokenizerError},
+};
 use tracing::{error, info};
 
 use crate::{
     server::AppState,
     services::compiler::models::{
         CloudLog, ErrorLocation, LogErrorMessage, LogKind, LogPayload, SuggestionRequest,
-        SuggestionResponse, ValidationError, ValidationRequest, ValidationResponse,
-        ValidationSuggestion,
+        SuggestionResponse, TokenizeRequest, TokenizeResponse, ValidationError, ValidationRequest,
+        ValidationResponse, ValidationSuggestion,
     },
 };
 
@@ -144,21 +153,39 @@ fn error_type_from_system_error(error: &SystemError) -> String {
         SystemError::Provider(_) => "ProviderError",
         SystemError::Request(_) => "RequestError",
         SystemError::Initialization(_) => "InitializationError",
+        SystemError::EventJournal(_) => "EventJournalError",
+        SystemError::WorldInit(_) => "WorldInitError",
         SystemError::ScalingNotEnoughAgents { .. } => "ScalingError",
         SystemError::ScaleManagerNotFound { .. } => "ScaleManagerError",
         SystemError::InvalidStateTransition { .. } => "StateTransitionError",
         SystemError::UnsupportedRequest { .. } => "UnsupportedRequestError",
+        SystemError::ValidationFailed { .. } => "ValidationFailedError",
+        SystemError::UnregisteredEventTypes { .. } => "UnregisteredEventTypesError",
         SystemError::ReceiveResponseFailed { .. } => "ResponseFailedError",
         SystemError::ReceiveResponseTimeout { .. } => "ResponseTimeoutError",
+        SystemError::InvalidLabel(_) => "InvalidLabelError",
+        SystemError::InvalidBatch(_) => "InvalidBatchError",
+        SystemError::SuspendedState { .. } => "SuspendedStateError",
+        SystemError::DebugEval(_) => "DebugEvalError",
+        SystemError::AgentConfig(_) => "AgentConfigError",
+        SystemError::HandlerTest(_) => "HandlerTestError",
+        SystemError::DebugSession(_) => "DebugSessionError",
     }
     .to_string()
 }
 
 /// Validate DSL code
+///
+/// Parser and type checker messages are rendered in the first supported language
+/// of `Accept-Language` (`en` or `ja`), falling back to the compiler system's
+/// configured locale.
 #[utoipa::path(
     post,
     path = "/compiler/validate",
     request_body = ValidationRequest,
+    params(
+        ("Accept-Language" = Option<String>, Header, description = "Preferred languages of the messages")
+    ),
     responses(
         (status = 200, description = "DSL validated successfully", body = ValidationResponse),
         (status = 500, description = "Internal server error")
@@ -231,10 +258,16 @@ pub async fn validate_dsl(
             }],
             warnings: Vec::new(),
             suggestions: None,
+            dsl_version: None,
         });
     }
 
     let manager = state.compiler_system_manager.clone().unwrap();
+    let locale = headers
+        .get(ACCEPT_LANGUAGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(Locale::from_accept_language)
+        .unwrap_or_else(|| manager.locale());
     let error_logger = ErrorLogger::new();
     if payload.code.is_empty() {
         return Json(ValidationResponse {
@@ -254,23 +287,29 @@ pub async fn validate_dsl(
             }],
             warnings: Vec::new(),
             suggestions: None,
+            dsl_version: None,
         });
     }
 
     // Attempt to parse the DSL using the System
     match manager.validate_dsl(&payload.code).await {
-        Ok(_) => {
+        Ok(dsl_version) => {
             // DSL is valid
             Json(ValidationResponse {
                 valid: true,
                 errors: Vec::new(),
                 warnings: Vec::new(),
                 suggestions: None,
+                dsl_version,
             })
         }
         Err(err) => {
             // DSL has errors
-            let errors = convert_system_error_to_validation_errors(&err, &payload.code);
+            let errors = convert_system_error_to_validation_errors(
+                &err,
+                &payload.code,
+                &MessageCatalog::new(locale),
+            );
 
             // Log system error first
             error_logger
@@ -288,6 +327,10 @@ pub async fn validate_dsl(
                         message: "Parse error".to_string(),
                         token_span: None,
                         error: "Unexpected token".to_string(),
+                        diagnostic: Box::new(
+                            Diagnostic::new(codes::PARSE_FAILURE)
+                                .with_arg("message", "Unexpected token"),
+                        ),
                     }),
                 )
                 .await;
@@ -323,6 +366,7 @@ pub async fn validate_dsl(
                 errors,
                 warnings: Vec::new(),
                 suggestions,
+                dsl_version: None,
             })
         }
     }
@@ -419,10 +463,60 @@ pub async fn suggest_fixes(
     })
 }
 
+/// Tokenize DSL code into its token stream
+#[utoipa::path(
+    post,
+    path = "/dsl/tokenize",
+    request_body = TokenizeRequest,
+    params(
+        ("Accept-Language" = Option<String>, Header, description = "Preferred languages of the messages")
+    ),
+    responses(
+        (status = 200, description = "DSL tokenized, or the tokenizer errors", body = TokenizeResponse)
+    )
+)]
+pub async fn tokenize_dsl(
+    headers: HeaderMap,
+    Json(payload): Json<TokenizeRequest>,
+) -> Json<TokenizeResponse> {
+    let code = &payload.code;
+    match Tokenizer::new().tokenize(code) {
+        Ok(tokens) => Json(TokenizeResponse {
+            tokens: tokens.iter().map(TokenJson::from).collect(),
+            errors: Vec::new(),
+        }),
+        Err(error) => {
+            let locale = headers
+                .get(ACCEPT_LANGUAGE)
+                .and_then(|v| v.to_str().ok())
+                .and_then(Locale::from_accept_language)
+                .unwrap_or_default();
+            let TokenizerError::ParseError { found, span, .. } = &error;
+            Json(TokenizeResponse {
+                tokens: Vec::new(),
+                errors: vec![ValidationError {
+                    message: MessageCatalog::new(locale).render(&error.diagnostic()),
+                    location: ErrorLocation {
+                        line: span.line,
+                        column: span.column,
+                        start_position: Some(span.start),
+                        end_position: Some(span.end),
+                        context: extract_context(code, span.line, span.column),
+                        token_text: code.get(span.start..span.end).map(String::from),
+                    },
+                    error_code: "E1002".to_string(),
+                    suggestion: format!("Unexpected token: {}", found),
+                }],
+            })
+        }
+    }
+}
+
 /// Convert System errors to validation errors
 fn convert_system_error_to_validation_errors(
     system_error: &CompilerError,
     code: &str,
+    catalog: &MessageCatalog,
 ) -> Vec<ValidationError> {
     let mut acc = Vec::new();
     match system_error {
@@ -498,12 +592,9 @@ fn convert_system_error_to_validation_errors(
         }
         CompilerError::ParseError(SystemError::Ast(ast_error)) => {
             // Convert AST errors
+            let message = catalog.render(&ast_error.diagnostic());
             match ast_error {
-                kairei_core::ASTError::ParseError {
-                    message,
-                    token_span,
-                    error,
-                } => {
+                kairei_core::ASTError::ParseError { token_span, .. } => {
                     let (line, column, start_pos, end_pos) = if let Some(span) = token_span {
                         let span = &span.span;
                         (span.line, span.column, Some(span.start), Some(span.end))
@@ -523,7 +614,7 @@ fn convert_system_error_to_validation_errors(
                     };
 
                     acc.push(ValidationError {
-                        message: format!("Parse error in {}: {}", message, error),
+                        message,
                         location: ErrorLocation {
                             line,
                             column,
@@ -537,9 +628,9 @@ fn convert_system_error_to_validation_errors(
                     });
                 }
                 kairei_core::ASTError::TokenizeError(TokenizerError::ParseError {
-                    message,
                     found,
                     span,
+                    ..
                 }) => {
                     // Extract token text from the code using span information
                     let token_text = if span.start < code.len() && span.end <= code.len() {
@@ -549,7 +640,7 @@ fn convert_system_error_to_validation_errors(
                     };
 
                     acc.push(ValidationError {
-                        message: message.clone(),
+                        message,
                         location: ErrorLocation {
                             line: span.line,
                             column: span.column,
@@ -562,9 +653,9 @@ fn convert_system_error_to_validation_errors(
                         suggestion: format!("Unexpected token: {}", found),
                     });
                 }
-                kairei_core::ASTError::TypeCheckError(type_check_error) => {
+                kairei_core::ASTError::TypeCheckError(_) => {
                     acc.push(ValidationError {
-                        message: type_check_error.to_string(),
+                        message,
                         location: ErrorLocation {
                             line: 1,
                             column: 1,
@@ -577,9 +668,9 @@ fn convert_system_error_to_validation_errors(
                         suggestion: "Check type compatibility".to_string(),
                     });
                 }
-                kairei_core::ASTError::ASTNotFound(name) => {
+                kairei_core::ASTError::ASTNotFound(_) => {
                     acc.push(ValidationError {
-                        message: format!("AST not found: {}", name),
+                        message,
                         location: ErrorLocation {
                             line: 1,
                             column: 1,
@@ -592,6 +683,38 @@ fn convert_system_error_to_validation_errors(
                         suggestion: "Check agent name".to_string(),
                     });
                 }
+                kairei_core::ASTError::LimitExceeded(_) => {
+                    acc.push(ValidationError {
+                        message,
+                        location: ErrorLocation {
+                            line: 1,
+                            column: 1,
+                            start_position: None,
+                            end_position: None,
+                            context: extract_context(code, 1, 1),
+                            token_text: None,
+                        },
+                        error_code: "E1005".to_string(),
+                        suggestion:
+                            "Split large handlers and agents, or flatten deeply nested expressions"
+                                .to_string(),
+                    });
+                }
+                kairei_core::ASTError::DslVersion(_) => {
+                    acc.push(ValidationError {
+                        message,
+                        location: ErrorLocation {
+                            line: 1,
+                            column: 1,
+                            start_position: None,
+                            end_position: None,
+                            context: extract_context(code, 1, 1),
+                            token_text: None,
+                        },
+                        error_code: "E1009".to_string(),
+                        suggestion: "Declare a DSL version this build supports".to_string(),
+                    });
+                }
             }
         }
         // Handle other system error types
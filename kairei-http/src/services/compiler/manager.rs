@@ -1,10 +1,12 @@
 use kairei_core::{
     config::{SecretConfig, SystemConfig},
+    dsl_watcher::DslWatcher,
     event_bus::{EventError, RequestBuilder, Value},
+    message_catalog::Locale,
     system::{System, SystemError},
 };
 use std::{collections::HashMap, sync::Arc};
-use tracing::debug;
+use tracing::{debug, info};
 use uuid::Uuid;
 
 use super::{DslLoader, DslSplitter};
@@ -16,6 +18,7 @@ pub struct CompilerSystemManager {
     secret_config: SecretConfig,
     system: Option<Arc<System>>,
     dsl_loader: DslLoader,
+    watch_dsl: bool,
 }
 
 impl CompilerSystemManager {
@@ -30,13 +33,26 @@ impl CompilerSystemManager {
             secret_config,
             system: None,
             dsl_loader: dsl_loader.unwrap_or_default(),
+            watch_dsl: false,
         }
     }
 
+    /// Reload the system when the loaded DSL files change
+    pub fn with_dsl_watch(mut self, watch_dsl: bool) -> Self {
+        self.watch_dsl = watch_dsl;
+        self
+    }
+
+    /// Language of diagnostics when the request does not ask for one
+    pub fn locale(&self) -> Locale {
+        self.config.locale
+    }
+
     pub async fn initialize(&mut self, is_load: bool) -> Result<(), CompilerError> {
         let mut system = System::new(&self.config, &self.secret_config).await;
+        let mut dsl_files = vec![];
         if is_load {
-            let dsl_files = self
+            dsl_files = self
                 .dsl_loader
                 .load_all()
                 .map_err(|e| CompilerError::InitializationError(e.to_string()))?;
@@ -46,16 +62,25 @@ impl CompilerSystemManager {
             tokio::time::sleep(std::time::Duration::from_secs(1)).await;
             system.start().await?;
         }
-        self.system = Some(Arc::new(system));
+        let system = Arc::new(system);
+        if is_load && self.watch_dsl {
+            // 読み込んだファイルを起点に、以降の変更を反映する
+            let watcher =
+                DslWatcher::new(system.clone(), dsl_files.into_iter().map(|file| file.path));
+            Arc::new(watcher).spawn();
+            info!("Watching DSL files of the compiler services");
+        }
+        self.system = Some(system);
         Ok(())
     }
 
-    pub async fn validate_dsl(&self, code: &str) -> Result<String, CompilerError> {
+    /// Parses `code`, returning the DSL version it declares, if any
+    pub async fn validate_dsl(&self, code: &str) -> Result<Option<String>, CompilerError> {
         if let Some(system) = self.system.clone() {
             let parsed = system.parse_dsl(code).await;
             debug!("parsed: {:?}", parsed);
             match parsed {
-                Ok(_) => Ok("Successfully parsed DSL".to_string()),
+                Ok(root) => Ok(root.dsl_version),
                 Err(err) => {
                     let splitted = self.split_dsl_blocks(code);
                     let splitted_one_tier = self.split_dsl_blocks_one_tier(code);
@@ -64,10 +89,9 @@ impl CompilerSystemManager {
                         .await
                         .map(|status| status.agent_count)
                         .map_err(|err| CompilerError::InitializationError(err.to_string()))?;
+                    // 検証用エージェントがなければ構文エラーをそのまま返す
                     if agents == 0 {
-                        return Err(CompilerError::InitializationError(
-                            "Failed to Parse, No agents found".to_string(),
-                        ));
+                        return Err(CompilerError::ParseError(err));
                     }
                     let request = RequestBuilder::new()
                         .request_id(&Uuid::new_v4().to_string())
@@ -1,3 +1,4 @@
+use kairei_core::tokenizer::token::TokenJson;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 use utoipa::ToSchema;
@@ -20,6 +21,9 @@ pub struct ValidationResponse {
     pub warnings: Vec<ValidationWarning>,
     /// Suggestions for fixing errors
     pub suggestions: Option<ValidationSuggestion>,
+    /// DSL version the code declares with `kairei "0.4"`, if it parsed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dsl_version: Option<String>,
 }
 
 /// Detailed validation error
@@ -90,6 +94,22 @@ pub struct SuggestionResponse {
     pub explanation: String,
 }
 
+/// Request for tokenizing DSL code
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TokenizeRequest {
+    /// DSL code to tokenize
+    pub code: String,
+}
+
+/// Response for DSL tokenization
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TokenizeResponse {
+    /// Token stream, including whitespace and comments flagged as trivia
+    pub tokens: Vec<TokenJson>,
+    /// Tokenizer errors; `tokens` is empty when there are any
+    pub errors: Vec<ValidationError>,
+}
+
 // Cloud Logging compatible structures
 
 /// Cloud Logging compatible log structure
@@ -1 +1,3 @@
+pub mod agent_import;
 pub mod compiler;
+pub mod preflight;
@@ -0,0 +1,133 @@
+//! Preflight checks run in the background.
+//!
+//! `POST /preflight` checks small deployments inline. A deployment whose sources
+//! exceed [`INLINE_SOURCE_BYTES`], or whose provider credentials are checked against
+//! their APIs, is checked by a job instead: the request returns the job at once and
+//! `GET /preflight/{job_id}` returns its report when it completes. Completed jobs are
+//! kept for [`JOB_RETENTION`].
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use dashmap::DashMap;
+use kairei_core::config::{SecretConfig, SystemConfig};
+use kairei_core::preflight::PreflightSource;
+use kairei_core::system::System;
+use uuid::Uuid;
+
+use crate::models::{PreflightJob, PreflightJobStatus};
+
+/// Total size of the sources up to which a preflight runs inline, in bytes
+pub const INLINE_SOURCE_BYTES: usize = 64 * 1024;
+
+/// How long a completed job is kept
+pub const JOB_RETENTION: Duration = Duration::from_secs(3600);
+
+struct JobEntry {
+    job: PreflightJob,
+    completed: Option<Instant>,
+}
+
+/// Preflight jobs, see the [module docs](self)
+#[derive(Default)]
+pub struct PreflightJobs {
+    jobs: Arc<DashMap<String, JobEntry>>,
+}
+
+impl PreflightJobs {
+    /// Whether a preflight of `sources` should run as a job
+    pub fn runs_as_job(sources: &[PreflightSource], validate_credentials: bool) -> bool {
+        validate_credentials
+            || sources.iter().map(|source| source.dsl.len()).sum::<usize>() > INLINE_SOURCE_BYTES
+    }
+
+    /// Starts a preflight in the background and returns its job, still running
+    pub fn submit(
+        &self,
+        config: SystemConfig,
+        secret: SecretConfig,
+        sources: Vec<PreflightSource>,
+    ) -> PreflightJob {
+        self.prune();
+        let job = PreflightJob {
+            job_id: Uuid::new_v4().to_string(),
+            status: PreflightJobStatus::Running,
+            submitted_at: Utc::now(),
+            completed_at: None,
+            report: None,
+        };
+        self.jobs.insert(
+            job.job_id.clone(),
+            JobEntry {
+                job: job.clone(),
+                completed: None,
+            },
+        );
+
+        let jobs = self.jobs.clone();
+        let job_id = job.job_id.clone();
+        tokio::spawn(async move {
+            let report = System::preflight(&config, &secret, &sources).await;
+            if let Some(mut entry) = jobs.get_mut(&job_id) {
+                entry.job.status = PreflightJobStatus::Completed;
+                entry.job.completed_at = Some(Utc::now());
+                entry.job.report = Some(report);
+                entry.completed = Some(Instant::now());
+            }
+        });
+        job
+    }
+
+    pub fn get(&self, job_id: &str) -> Option<PreflightJob> {
+        self.jobs.get(job_id).map(|entry| entry.job.clone())
+    }
+
+    /// 保持期間を過ぎた完了済みのジョブを削除する
+    fn prune(&self) {
+        self.jobs.retain(|_, entry| {
+            entry
+                .completed
+                .is_none_or(|completed| completed.elapsed() < JOB_RETENTION)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runs_as_job() {
+        let small = vec![PreflightSource::new("main.kairei", "world Town {}")];
+        assert!(!PreflightJobs::runs_as_job(&small, false));
+        assert!(PreflightJobs::runs_as_job(&small, true));
+
+        let large = vec![
+            PreflightSource::new("a.kairei", "x".repeat(INLINE_SOURCE_BYTES)),
+            PreflightSource::new("b.kairei", "x"),
+        ];
+        assert!(PreflightJobs::runs_as_job(&large, false));
+    }
+
+    #[tokio::test]
+    async fn test_job_completes() {
+        let jobs = PreflightJobs::default();
+        let job = jobs.submit(SystemConfig::default(), SecretConfig::default(), vec![]);
+        assert_eq!(job.status, PreflightJobStatus::Running);
+
+        let mut completed = None;
+        for _ in 0..100 {
+            let current = jobs.get(&job.job_id).unwrap();
+            if current.status == PreflightJobStatus::Completed {
+                completed = Some(current);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let completed = completed.expect("job did not complete");
+        assert!(completed.report.is_some());
+        assert!(completed.completed_at.is_some());
+        assert!(jobs.get("missing").is_none());
+    }
+}
@@ -0,0 +1,94 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use dashmap::DashMap;
+use kairei_core::{config::ConversationConfig, context::ConversationTurn};
+
+/// Exchanges of a session with its conversational agents, keyed by agent name.
+/// Shared by all clones of the session and dropped with it.
+#[derive(Debug, Clone, Default)]
+pub struct Conversations {
+    agents: Arc<DashMap<String, VecDeque<ConversationTurn>>>,
+}
+
+impl Conversations {
+    /// Prior exchanges with `agent`, oldest first
+    pub fn history(&self, agent: &str) -> Vec<ConversationTurn> {
+        self.agents
+            .get(agent)
+            .map(|turns| turns.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Add an exchange with `agent`, dropping the oldest ones past the limits of `config`
+    pub fn record(&self, agent: &str, turn: ConversationTurn, config: &ConversationConfig) {
+        let mut turns = self.agents.entry(agent.to_string()).or_default();
+        turns.push_back(turn);
+        while turns.len() > config.max_turns {
+            turns.pop_front();
+        }
+        if let Some(max_tokens) = config.max_tokens {
+            let mut tokens: usize = turns.iter().map(estimated_tokens).sum();
+            while tokens > max_tokens {
+                let Some(oldest) = turns.pop_front() else {
+                    break;
+                };
+                tokens -= estimated_tokens(&oldest);
+            }
+        }
+    }
+
+    /// Forget every exchange, e.g. when the session ends
+    pub fn clear(&self) {
+        self.agents.clear();
+    }
+}
+
+// トークン数は厳密に数えず、4 文字で 1 トークンと見積もる
+fn estimated_tokens(turn: &ConversationTurn) -> usize {
+    (turn.user.chars().count() + turn.agent.chars().count()).div_ceil(4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn turn(user: &str, agent: &str) -> ConversationTurn {
+        ConversationTurn {
+            user: user.to_string(),
+            agent: agent.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_oldest_turns_are_dropped() {
+        let conversations = Conversations::default();
+        let config = ConversationConfig {
+            max_turns: 2,
+            max_tokens: None,
+        };
+        for (user, agent) in [("a", "1"), ("b", "2"), ("c", "3")] {
+            conversations.record("chat", turn(user, agent), &config);
+        }
+        assert_eq!(
+            conversations.history("chat"),
+            vec![turn("b", "2"), turn("c", "3")]
+        );
+        assert!(conversations.history("other").is_empty());
+
+        // 16 文字 = 4 トークンの上限に、8 文字ずつの 2 件目までしか入らない
+        let config = ConversationConfig {
+            max_turns: 10,
+            max_tokens: Some(4),
+        };
+        conversations.record("budget", turn("1234", "5678"), &config);
+        conversations.record("budget", turn("abcd", "efgh"), &config);
+        conversations.record("budget", turn("ABCD", "EFGH"), &config);
+        assert_eq!(
+            conversations.history("budget"),
+            vec![turn("abcd", "efgh"), turn("ABCD", "EFGH")]
+        );
+
+        conversations.clear();
+        assert!(conversations.history("chat").is_empty());
+    }
+}
@@ -9,6 +9,9 @@ use kairei_core::{
 use secrecy::{ExposeSecret, SecretString};
 use tokio::sync::RwLock;
 
+use super::{conversation::Conversations, versions::ResourceVersions};
+use crate::services::agent_import::ImportedAgents;
+
 pub type SessionId = String;
 pub type UserId = String;
 pub type SystemId = String;
@@ -21,6 +24,7 @@ pub struct SessionSecret {
 #[derive(Debug, Clone)]
 pub struct SessionSecretConfig {
     pub providers: DashMap<String, SessionProviderSecretConfig>,
+    pub handler_secrets: DashMap<String, SecretString>,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -47,7 +51,15 @@ impl From<SecretConfig> for SessionSecretConfig {
                 },
             );
         }
-        Self { providers }
+        let handler_secrets = secret_config
+            .handler_secrets
+            .into_iter()
+            .map(|(name, value)| (name, SecretString::from(value)))
+            .collect();
+        Self {
+            providers,
+            handler_secrets,
+        }
     }
 }
 
@@ -72,7 +84,15 @@ impl From<SessionSecretConfig> for SecretConfig {
                 );
                 map
             });
-        Self { providers }
+        let handler_secrets = secret_config
+            .handler_secrets
+            .iter()
+            .map(|kv| (kv.key().clone(), kv.expose_secret().to_string()))
+            .collect();
+        Self {
+            providers,
+            handler_secrets,
+        }
     }
 }
 
@@ -84,6 +104,12 @@ pub struct SessionData {
     pub system: Arc<RwLock<System>>,
     pub system_config: SystemConfig,
     pub secret_config: SessionSecretConfig,
+    /// Versions of the system and its agents, shared by all clones of the session
+    pub versions: ResourceVersions,
+    /// Agents imported at creation, added to the DSL when the system starts
+    pub imported_agents: ImportedAgents,
+    /// Exchanges with the agents in conversational mode, cleared when the session ends
+    pub conversations: Conversations,
 }
 
 /// Builder for session data
@@ -94,6 +120,7 @@ pub struct SessionDataBuilder {
     system: Option<Arc<RwLock<System>>>,
     system_config: Option<SystemConfig>,
     secret_config: Option<SecretConfig>,
+    imported_agents: ImportedAgents,
 }
 
 impl SessionDataBuilder {
@@ -126,6 +153,11 @@ impl SessionDataBuilder {
         self
     }
 
+    pub fn imported_agents(mut self, imported_agents: ImportedAgents) -> Self {
+        self.imported_agents = imported_agents;
+        self
+    }
+
     pub fn build(self) -> Result<SessionData> {
         Ok(SessionData {
             system_id: self.system_id.context("system_id not set")?,
@@ -135,6 +167,9 @@ impl SessionDataBuilder {
             secret_config: SessionSecretConfig::from(
                 self.secret_config.context("secret_config not set")?,
             ),
+            versions: ResourceVersions::default(),
+            imported_agents: self.imported_agents,
+            conversations: Conversations::default(),
         })
     }
 }
@@ -0,0 +1,172 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::broadcast;
+
+use crate::models::LifecycleEvent;
+
+use super::manager::UserId;
+
+// 購読者が追いつけない場合、古いイベントから捨てられる
+const LIFECYCLE_CHANNEL_CAPACITY: usize = 256;
+
+/// Number of recent events kept for streams that resume after a reconnect
+pub const LIFECYCLE_HISTORY_CAPACITY: usize = 256;
+
+/// A lifecycle event and the owner of its system
+#[derive(Debug, Clone)]
+pub struct OwnedLifecycleEvent {
+    /// Increasing from 1, used as the SSE event id
+    pub id: u64,
+    pub owner: UserId,
+    pub event: LifecycleEvent,
+}
+
+/// A stream picking up after the last event a client received
+#[derive(Debug)]
+pub struct ResumedLifecycleEvents {
+    /// Retained events after the requested id, oldest first
+    pub missed: Vec<OwnedLifecycleEvent>,
+    /// Some events after the requested id are no longer retained, or the id is
+    /// unknown, e.g. from before a restart
+    pub gap: bool,
+    /// Events published after `missed`
+    pub receiver: broadcast::Receiver<OwnedLifecycleEvent>,
+}
+
+#[derive(Debug)]
+struct History {
+    next_id: u64,
+    events: VecDeque<OwnedLifecycleEvent>,
+}
+
+/// Broadcasts lifecycle events of all systems to the open streams
+#[derive(Clone)]
+pub struct LifecycleEvents {
+    sender: broadcast::Sender<OwnedLifecycleEvent>,
+    history: Arc<Mutex<History>>,
+}
+
+impl Default for LifecycleEvents {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(LIFECYCLE_CHANNEL_CAPACITY);
+        Self {
+            sender,
+            history: Arc::new(Mutex::new(History {
+                next_id: 1,
+                events: VecDeque::with_capacity(LIFECYCLE_HISTORY_CAPACITY),
+            })),
+        }
+    }
+}
+
+impl LifecycleEvents {
+    pub fn publish(&self, owner: &UserId, event: LifecycleEvent) {
+        tracing::debug!("Lifecycle event: {:?}", event);
+        // 採番・履歴への追加・送信を同じロックの中で行い、再開時の取りこぼしと重複を防ぐ
+        let mut history = self.history.lock().unwrap();
+        let owned = OwnedLifecycleEvent {
+            id: history.next_id,
+            owner: owner.clone(),
+            event,
+        };
+        history.next_id += 1;
+        if history.events.len() == LIFECYCLE_HISTORY_CAPACITY {
+            history.events.pop_front();
+        }
+        history.events.push_back(owned.clone());
+        // 購読者がいなければ送信エラーになるが、イベントは捨ててよい
+        let _ = self.sender.send(owned);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<OwnedLifecycleEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Subscribe, starting after `last_event_id` when a reconnecting client sent one
+    pub fn resume(&self, last_event_id: Option<u64>) -> ResumedLifecycleEvents {
+        let history = self.history.lock().unwrap();
+        let receiver = self.sender.subscribe();
+        let Some(last_event_id) = last_event_id else {
+            return ResumedLifecycleEvents {
+                missed: vec![],
+                gap: false,
+                receiver,
+            };
+        };
+        let first_retained = history
+            .events
+            .front()
+            .map_or(history.next_id, |owned| owned.id);
+        // 発行前の id は再起動前のものとみなし、残っている全件を送り直す
+        let unknown = last_event_id >= history.next_id;
+        ResumedLifecycleEvents {
+            missed: history
+                .events
+                .iter()
+                .filter(|owned| unknown || owned.id > last_event_id)
+                .cloned()
+                .collect(),
+            gap: unknown || last_event_id.saturating_add(1) < first_retained,
+            receiver,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::LifecycleEventKind;
+
+    fn publish(events: &LifecycleEvents, count: usize) {
+        for i in 0..count {
+            events.publish(
+                &"user".to_string(),
+                LifecycleEvent::system(LifecycleEventKind::SystemStarted, &format!("system-{i}")),
+            );
+        }
+    }
+
+    fn ids(resumed: &ResumedLifecycleEvents) -> Vec<u64> {
+        resumed.missed.iter().map(|owned| owned.id).collect()
+    }
+
+    #[tokio::test]
+    async fn test_resume_replays_missed_events() {
+        let events = LifecycleEvents::default();
+        publish(&events, 3);
+
+        let mut resumed = events.resume(Some(1));
+        assert_eq!(ids(&resumed), vec![2, 3]);
+        assert!(!resumed.gap);
+
+        // 再開後に発行されたイベントは受信側に届く
+        publish(&events, 1);
+        assert_eq!(resumed.receiver.recv().await.unwrap().id, 4);
+
+        let resumed = events.resume(Some(4));
+        assert!(resumed.missed.is_empty());
+        assert!(!resumed.gap);
+        assert!(events.resume(None).missed.is_empty());
+    }
+
+    #[test]
+    fn test_resume_signals_gap() {
+        let events = LifecycleEvents::default();
+        publish(&events, LIFECYCLE_HISTORY_CAPACITY + 2);
+
+        // 1 と 2 は履歴から追い出されている
+        let resumed = events.resume(Some(1));
+        assert!(resumed.gap);
+        assert_eq!(resumed.missed.len(), LIFECYCLE_HISTORY_CAPACITY);
+        assert_eq!(resumed.missed[0].id, 3);
+        assert!(!events.resume(Some(2)).gap);
+
+        // 再起動前の id は知らないので、残っている全件を送る
+        let resumed = events.resume(Some(10_000));
+        assert!(resumed.gap);
+        assert_eq!(resumed.missed.len(), LIFECYCLE_HISTORY_CAPACITY);
+    }
+}
@@ -2,9 +2,15 @@ use std::{collections::HashMap, sync::Arc};
 
 use anyhow::{Context, Result, bail};
 use dashmap::DashMap;
-use kairei_core::config::ProviderSecretConfig;
+use kairei_core::{config::ProviderSecretConfig, provider::capabilities::storage::StorageBackend};
 
-use super::data::{SessionData, SessionDataBuilder};
+use crate::models::{LifecycleEvent, LifecycleEventKind};
+
+use super::{
+    data::{SessionData, SessionDataBuilder},
+    lifecycle::LifecycleEvents,
+    versions::ResourceVersions,
+};
 
 pub type SessionId = String;
 pub type UserId = String;
@@ -19,6 +25,10 @@ pub struct SessionManager {
     users: Arc<DashMap<UserId, Vec<SessionId>>>,
     _config: SessionConfig,
     pub secret_config: kairei_core::config::SecretConfig,
+    /// Lifecycle events of the managed systems
+    pub lifecycle: LifecycleEvents,
+    /// Storage of the versions of the systems, kept in memory when unset
+    versions_store: Option<Arc<dyn StorageBackend>>,
 }
 
 impl SessionManager {
@@ -43,6 +53,13 @@ impl SessionManager {
         }
     }
 
+    /// Keep the versions of the systems and their agents, served as ETags, in
+    /// `storage` so that they survive a restart
+    pub fn with_versions_store(mut self, storage: Arc<dyn StorageBackend>) -> Self {
+        self.versions_store = Some(storage);
+        self
+    }
+
     // session data creation is user matter
     pub async fn create_session(
         &self,
@@ -50,18 +67,27 @@ impl SessionManager {
         builder: SessionDataBuilder,
     ) -> Result<(SessionId, SystemId)> {
         let session_id = uuid::Uuid::new_v4().to_string();
-        let data = builder
+        let mut data = builder
             .user_id(user_id.clone())
             .system_id(session_id.clone())
             .secret_config(self.secret_config.clone())
             .build()
             .with_context(|| "Failed to build session data")?;
+        if let Some(storage) = &self.versions_store {
+            data.versions = ResourceVersions::load(storage.clone(), &data.system_id)
+                .await
+                .with_context(|| "Failed to load the versions of the system")?;
+        }
         let system_id = data.system_id.clone();
         self.sessions.insert(session_id.clone(), data);
         self.users
             .entry(user_id.clone())
             .or_default()
             .push(session_id.clone());
+        self.lifecycle.publish(
+            user_id,
+            LifecycleEvent::system(LifecycleEventKind::SystemCreated, &system_id),
+        );
         Ok((session_id, system_id))
     }
 
@@ -86,12 +112,27 @@ impl SessionManager {
             .unwrap_or_default()
     }
 
+    /// Every session, of all users
+    pub async fn all_sessions(&self) -> Vec<(SessionId, SessionData)> {
+        self.sessions
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect()
+    }
+
     pub async fn remove_session(&self, session_id: &SessionId) -> Result<()> {
         if let Some(data) = self.sessions.remove(session_id) {
+            // 処理中のリクエストが持つクローンからも会話を消す
+            data.1.conversations.clear();
+            data.1.versions.forget().await;
             // remove session from users
             if let Some(mut sessions) = self.users.get_mut(&data.1.user_id) {
                 sessions.retain(|id| id != session_id)
             }
+            self.lifecycle.publish(
+                &data.1.user_id,
+                LifecycleEvent::system(LifecycleEventKind::SystemDeleted, &data.1.system_id),
+            );
             Ok(())
         } else {
             bail!("Session not found".to_string())
@@ -101,7 +142,14 @@ impl SessionManager {
     pub async fn remove_sessions(&self, user_id: &UserId) {
         if let Some(sessions) = self.users.remove(user_id) {
             for session_id in sessions.1 {
-                self.sessions.remove(&session_id);
+                if let Some((_, data)) = self.sessions.remove(&session_id) {
+                    data.conversations.clear();
+                    data.versions.forget().await;
+                    self.lifecycle.publish(
+                        user_id,
+                        LifecycleEvent::system(LifecycleEventKind::SystemDeleted, &data.system_id),
+                    );
+                }
             }
         }
     }
@@ -1,2 +1,5 @@
+pub mod conversation;
 pub mod data;
+pub mod lifecycle;
 pub mod manager;
+pub mod versions;
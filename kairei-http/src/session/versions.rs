@@ -0,0 +1,308 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use axum::http::{HeaderMap, header::IF_MATCH};
+use dashmap::DashMap;
+use kairei_core::{
+    persistence::{self, Versioned},
+    provider::capabilities::{
+        shared_memory::Metadata,
+        storage::{StorageBackend, StorageError, ValueWithMetadata},
+    },
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+/// Storage namespace of the resource versions, keyed by system ID
+pub const VERSIONS_NAMESPACE: &str = "resource_versions";
+
+/// Strong ETag of a resource version, e.g. `"3"`
+pub fn etag(version: u64) -> String {
+    format!("\"{}\"", version)
+}
+
+/// The `If-Match` precondition of a mutating request
+#[derive(Debug, Clone, PartialEq)]
+pub enum IfMatch {
+    /// No `If-Match` header: the request applies to any version
+    Absent,
+    /// `If-Match: *`
+    Any,
+    /// The ETags the client last saw
+    Tags(Vec<String>),
+}
+
+impl IfMatch {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let tags: Vec<String> = headers
+            .get_all(IF_MATCH)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        if tags.is_empty() {
+            Self::Absent
+        } else if tags.iter().any(|tag| tag == "*") {
+            Self::Any
+        } else {
+            Self::Tags(tags)
+        }
+    }
+
+    // If-Match は強い比較なので、W/ 付きの ETag は一致しない
+    pub fn matches(&self, version: u64) -> bool {
+        match self {
+            Self::Absent | Self::Any => true,
+            Self::Tags(tags) => tags.iter().any(|tag| *tag == etag(version)),
+        }
+    }
+}
+
+/// Version counters of a system and its agents, served as ETags.
+/// A change to an agent also changes the version of its system.
+///
+/// Versions [loaded](Self::load) from a store are written back after every change,
+/// so an ETag a client saw before a restart does not match again.
+#[derive(Debug, Clone, Default)]
+pub struct ResourceVersions {
+    system: Arc<AtomicU64>,
+    agents: Arc<DashMap<String, u64>>,
+    store: Option<VersionStore>,
+}
+
+#[derive(Clone)]
+struct VersionStore {
+    storage: Arc<dyn StorageBackend>,
+    system_id: String,
+    // 書き込みを順に行い、古い版で新しい版を上書きしない
+    lock: Arc<Mutex<()>>,
+}
+
+impl VersionStore {
+    async fn write(&self, versions: &StoredVersions) -> Result<(), StorageError> {
+        let value = ValueWithMetadata {
+            value: persistence::to_stored(versions)?,
+            metadata: Metadata::default(),
+            expiry: None,
+        };
+        self.storage
+            .save_key(VERSIONS_NAMESPACE, &self.system_id, &value)
+            .await
+    }
+}
+
+impl fmt::Debug for VersionStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VersionStore")
+            .field("system_id", &self.system_id)
+            .finish()
+    }
+}
+
+/// Persisted form of the versions of a system and its agents
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoredVersions {
+    system: u64,
+    agents: HashMap<String, u64>,
+}
+
+impl Versioned for StoredVersions {
+    const KIND: &'static str = "resource versions";
+    const VERSION: u32 = 1;
+}
+
+impl ResourceVersions {
+    /// The versions of `system_id` kept in `storage`, continuing from those an
+    /// earlier run stored
+    pub async fn load(
+        storage: Arc<dyn StorageBackend>,
+        system_id: &str,
+    ) -> Result<Self, StorageError> {
+        let stored = match storage.load(VERSIONS_NAMESPACE).await?.remove(system_id) {
+            Some(stored) => persistence::from_stored::<StoredVersions>(stored.value)?,
+            None => StoredVersions::default(),
+        };
+        Ok(Self {
+            system: Arc::new(AtomicU64::new(stored.system)),
+            agents: Arc::new(stored.agents.into_iter().collect()),
+            store: Some(VersionStore {
+                storage,
+                system_id: system_id.to_string(),
+                lock: Arc::new(Mutex::new(())),
+            }),
+        })
+    }
+
+    pub fn system(&self) -> u64 {
+        self.system.load(Ordering::SeqCst)
+    }
+
+    pub fn agent(&self, agent_name: &str) -> u64 {
+        self.agents
+            .get(agent_name)
+            .map(|version| *version)
+            .unwrap_or_default()
+    }
+
+    /// Advance the version of the system if `precondition` holds for it.
+    /// Returns the new version, or the current one when the precondition fails.
+    pub async fn update_system(&self, precondition: &IfMatch) -> Result<u64, u64> {
+        let version = self
+            .system
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |version| {
+                precondition.matches(version).then_some(version + 1)
+            })
+            .map(|version| version + 1)?;
+        self.save().await;
+        Ok(version)
+    }
+
+    /// Advance the version of an agent, and of its system, if `precondition`
+    /// holds for the agent. Returns like [`Self::update_system`].
+    pub async fn update_agent(&self, agent_name: &str, precondition: &IfMatch) -> Result<u64, u64> {
+        let version = {
+            // エントリのロックを持ったまま比較と更新を行い、同時の書き込みを片方だけ通す
+            let mut version = self.agents.entry(agent_name.to_string()).or_default();
+            if !precondition.matches(*version) {
+                return Err(*version);
+            }
+            *version += 1;
+            self.system.fetch_add(1, Ordering::SeqCst);
+            *version
+        };
+        self.save().await;
+        Ok(version)
+    }
+
+    /// Remove the stored versions, e.g. when the system is deleted
+    pub async fn forget(&self) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        let _guard = store.lock.lock().await;
+        if let Err(e) = store
+            .storage
+            .delete_key(VERSIONS_NAMESPACE, &store.system_id)
+            .await
+        {
+            tracing::warn!(
+                "Versions of system {} could not be removed: {}",
+                store.system_id,
+                e
+            );
+        }
+    }
+
+    // 保存に失敗しても更新は取り消さず、再起動までメモリ上の版を使う
+    async fn save(&self) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        let _guard = store.lock.lock().await;
+        let stored = StoredVersions {
+            system: self.system(),
+            agents: self
+                .agents
+                .iter()
+                .map(|entry| (entry.key().clone(), *entry.value()))
+                .collect(),
+        };
+        if let Err(e) = store.write(&stored).await {
+            tracing::warn!(
+                "Versions of system {} could not be stored: {}",
+                store.system_id,
+                e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::etag::precondition_failed;
+    use axum::http::{HeaderValue, StatusCode};
+    use kairei_core::provider::{
+        config::plugins::InMemoryConfig, plugins::storage::in_memory::InMemoryBackend,
+    };
+
+    fn if_match(value: &str) -> IfMatch {
+        let mut headers = HeaderMap::new();
+        headers.insert(IF_MATCH, HeaderValue::from_str(value).unwrap());
+        IfMatch::from_headers(&headers)
+    }
+
+    #[test]
+    fn test_if_match_parsing() {
+        assert_eq!(IfMatch::from_headers(&HeaderMap::new()), IfMatch::Absent);
+        assert_eq!(if_match("*"), IfMatch::Any);
+        assert!(if_match("\"1\", \"3\"").matches(3));
+        assert!(!if_match("\"1\"").matches(3));
+        assert!(!if_match("W/\"3\"").matches(3));
+    }
+
+    #[tokio::test]
+    async fn test_second_writer_with_stale_version_fails() {
+        let versions = ResourceVersions::default();
+        let seen = if_match(&etag(versions.system()));
+
+        assert_eq!(versions.update_system(&seen).await, Ok(1));
+        assert_eq!(versions.update_system(&seen).await, Err(1));
+
+        // エージェントの更新はシステムの版も進める
+        let seen = if_match(&etag(versions.agent("Weather")));
+        assert_eq!(versions.update_agent("Weather", &seen).await, Ok(1));
+        assert_eq!(versions.update_agent("Weather", &seen).await, Err(1));
+        assert_eq!(versions.system(), 2);
+        assert_eq!(
+            versions.update_agent("Other", &IfMatch::Absent).await,
+            Ok(1)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stale_version_fails_after_versions_are_recreated() {
+        let storage: Arc<dyn StorageBackend> =
+            Arc::new(InMemoryBackend::new(InMemoryConfig::default()));
+        let versions = ResourceVersions::load(storage.clone(), "system-1")
+            .await
+            .unwrap();
+        let seen_system = if_match(&etag(versions.system()));
+        let seen_agent = if_match(&etag(versions.agent("Weather")));
+        assert_eq!(versions.update_agent("Weather", &seen_agent).await, Ok(1));
+
+        // 再起動で作り直しても、以前に見た ETag は一致しない
+        let versions = ResourceVersions::load(storage.clone(), "system-1")
+            .await
+            .unwrap();
+        assert_eq!((versions.system(), versions.agent("Weather")), (1, 1));
+        for result in [
+            versions.update_system(&seen_system).await,
+            versions.update_agent("Weather", &seen_agent).await,
+        ] {
+            assert_eq!(
+                result.map_err(precondition_failed),
+                Err(StatusCode::PRECONDITION_FAILED)
+            );
+        }
+        let seen = if_match(&etag(versions.agent("Weather")));
+        assert_eq!(versions.update_agent("Weather", &seen).await, Ok(2));
+
+        // 他のシステムの版と、削除したシステムの版は 0 から始まる
+        let other = ResourceVersions::load(storage.clone(), "system-2")
+            .await
+            .unwrap();
+        assert_eq!(other.system(), 0);
+        versions.forget().await;
+        let versions = ResourceVersions::load(storage, "system-1").await.unwrap();
+        assert_eq!(versions.agent("Weather"), 0);
+    }
+}
@@ -0,0 +1,207 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    Router,
+    http::{Request, StatusCode},
+};
+use kairei_core::{
+    config::{ProviderConfig, ProviderConfigs, SystemConfig},
+    provider::provider::ProviderType,
+};
+use kairei_http::{
+    auth::{AuthProviderChain, auth_middleware},
+    handlers::test_helpers::create_test_state,
+    models::{
+        AgentImportErrorResponse, CreateSystemRequest, CreateSystemResponse, ListAgentsResponse,
+        StartSystemRequest,
+    },
+    routes,
+    server::{AppState, ServerConfig},
+    services::agent_import::{AgentImportConfig, AgentImporter},
+};
+use serde_json::json;
+use tower::ServiceExt;
+
+const GREETER_DSL: &str = r#"
+micro Greeter {
+    answer {
+        on request Greet(name: String) -> Result<String, Error> {
+            return Ok("Hello")
+        }
+    }
+}
+"#;
+
+fn create_app(allowed_hosts: &[&str]) -> Router {
+    let app_state = AppState {
+        agent_importer: Arc::new(AgentImporter::new(AgentImportConfig {
+            allowed_hosts: allowed_hosts.iter().map(|h| h.to_string()).collect(),
+            ..Default::default()
+        })),
+        ..create_test_state()
+    };
+    routes::create_api_router(&ServerConfig::default())
+        .with_state(app_state.clone())
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(AuthProviderChain::api_key(app_state.auth_store.clone())),
+            auth_middleware,
+        ))
+}
+
+fn create_system_request(agent_sources: Vec<String>) -> Request<String> {
+    let mut providers = HashMap::new();
+    providers.insert(
+        "default_provider".to_string(),
+        ProviderConfig {
+            provider_type: ProviderType::SimpleExpert,
+            ..Default::default()
+        },
+    );
+    let request_body = CreateSystemRequest {
+        name: "ImportSystem".to_string(),
+        config: SystemConfig {
+            provider_configs: ProviderConfigs {
+                providers,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        agent_sources,
+        ..Default::default()
+    };
+    Request::builder()
+        .uri("/api/v1/systems")
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(json!(request_body).to_string())
+        .unwrap()
+}
+
+async fn body_json<T: serde::de::DeserializeOwned>(response: axum::response::Response) -> T {
+    let body = axum::body::to_bytes(response.into_body(), 10_000)
+        .await
+        .unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+#[tokio::test]
+async fn test_import_agent_from_allowed_host() {
+    let mut server = mockito::Server::new_async().await;
+    let definition = server
+        .mock("GET", "/agents/greeter.kairei")
+        .with_status(200)
+        .with_body(GREETER_DSL)
+        .expect(1)
+        .create_async()
+        .await;
+    let source = format!("{}/agents/greeter.kairei", server.url());
+    let app = create_app(&["127.0.0.1"]);
+
+    let response = app
+        .clone()
+        .oneshot(create_system_request(vec![source.clone()]))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let system_id = body_json::<CreateSystemResponse>(response).await.system_id;
+
+    let request = Request::builder()
+        .uri(format!("/api/v1/systems/{}/start", system_id))
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(json!(StartSystemRequest { dsl: None }).to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let request = Request::builder()
+        .uri(format!("/api/v1/systems/{}/agents", system_id))
+        .method("GET")
+        .header("X-API-Key", "admin-key")
+        .body(String::new())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let agents: ListAgentsResponse = body_json(response).await;
+    assert!(
+        agents
+            .agents
+            .iter()
+            .any(|agent| agent.agent_id == "Greeter")
+    );
+
+    // 2 つ目のシステムはキャッシュから取り込む
+    let response = app
+        .clone()
+        .oneshot(create_system_request(vec![source]))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    definition.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_import_rejects_disallowed_host() {
+    let mut server = mockito::Server::new_async().await;
+    let definition = server
+        .mock("GET", "/agents/greeter.kairei")
+        .with_status(200)
+        .with_body(GREETER_DSL)
+        .expect(0)
+        .create_async()
+        .await;
+    let source = format!("{}/agents/greeter.kairei", server.url());
+    let app = create_app(&["agents.example.com"]);
+
+    let response = app
+        .oneshot(create_system_request(vec![source.clone()]))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let error: AgentImportErrorResponse = body_json(response).await;
+    assert_eq!(error.source, source);
+    assert_eq!(error.kind, "host_not_allowed");
+    definition.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_import_reports_invalid_definitions() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("GET", "/agents/broken.kairei")
+        .with_status(200)
+        .with_body("micro Broken {")
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/agents/missing.kairei")
+        .with_status(404)
+        .create_async()
+        .await;
+    let app = create_app(&["127.0.0.1"]);
+
+    let response = app
+        .clone()
+        .oneshot(create_system_request(vec![format!(
+            "{}/agents/broken.kairei",
+            server.url()
+        )]))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let error: AgentImportErrorResponse = body_json(response).await;
+    assert_eq!(error.kind, "validation");
+
+    let response = app
+        .oneshot(create_system_request(vec![format!(
+            "{}/agents/missing.kairei",
+            server.url()
+        )]))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    let error: AgentImportErrorResponse = body_json(response).await;
+    assert_eq!(error.kind, "fetch_status");
+}
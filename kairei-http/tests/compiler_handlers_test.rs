@@ -1,12 +1,18 @@
 use std::sync::Arc;
 
-use axum::{extract::State, http::header::HeaderMap, response::Json};
+use axum::{
+    extract::State,
+    http::header::{ACCEPT_LANGUAGE, HeaderMap, HeaderValue},
+    response::Json,
+};
 use kairei_http::{
     server::AppState,
     services::compiler::{
         CompilerSystemManager,
-        handlers::{suggest_fixes, validate_dsl},
-        models::{ErrorLocation, SuggestionRequest, ValidationError, ValidationRequest},
+        handlers::{suggest_fixes, tokenize_dsl, validate_dsl},
+        models::{
+            ErrorLocation, SuggestionRequest, TokenizeRequest, ValidationError, ValidationRequest,
+        },
     },
 };
 
@@ -55,6 +61,85 @@ async fn test_validate_dsl_handler_integration() {
     assert!(!error.suggestion.is_empty());
 }
 
+#[tokio::test]
+async fn test_validate_dsl_handler_localizes_messages() {
+    let mut compiler_manager = CompilerSystemManager::default();
+    compiler_manager.initialize(false).await.unwrap();
+    let app_state = AppState {
+        compiler_system_manager: Some(Arc::new(compiler_manager)),
+        ..Default::default()
+    };
+    let code = "micro TestAgent { }\n}";
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        ACCEPT_LANGUAGE,
+        HeaderValue::from_static("ja-JP,ja;q=0.9,en;q=0.8"),
+    );
+    let response = validate_dsl(
+        State(app_state.clone()),
+        headers,
+        Json(ValidationRequest {
+            code: code.to_string(),
+        }),
+    )
+    .await;
+    assert!(!response.0.valid);
+    assert_eq!(
+        response.0.errors[0].message,
+        "解析されずに残ったトークンがあります"
+    );
+
+    // 未対応の言語は英語で返す
+    let mut headers = HeaderMap::new();
+    headers.insert(ACCEPT_LANGUAGE, HeaderValue::from_static("fr-FR"));
+    let response = validate_dsl(
+        State(app_state),
+        headers,
+        Json(ValidationRequest {
+            code: code.to_string(),
+        }),
+    )
+    .await;
+    assert_eq!(response.0.errors[0].message, "Not all tokens were consumed");
+}
+
+#[tokio::test]
+async fn test_validate_dsl_handler_reports_dsl_version() {
+    let mut compiler_manager = CompilerSystemManager::default();
+    compiler_manager.initialize(false).await.unwrap();
+    let app_state = AppState {
+        compiler_system_manager: Some(Arc::new(compiler_manager)),
+        ..Default::default()
+    };
+
+    let response = validate_dsl(
+        State(app_state.clone()),
+        HeaderMap::new(),
+        Json(ValidationRequest {
+            code: "kairei \"0.5\"\n\nmicro TestAgent { }".to_string(),
+        }),
+    )
+    .await;
+    assert!(response.0.valid);
+    assert_eq!(response.0.dsl_version.as_deref(), Some("0.5"));
+
+    let response = validate_dsl(
+        State(app_state),
+        HeaderMap::new(),
+        Json(ValidationRequest {
+            code: "kairei \"0.6\"\n\nmicro TestAgent { }".to_string(),
+        }),
+    )
+    .await;
+    assert!(!response.0.valid);
+    assert_eq!(response.0.errors[0].error_code, "E1009");
+    assert_eq!(
+        response.0.errors[0].message,
+        "This file requires DSL 0.6, this build supports up to 0.5"
+    );
+}
+
 #[tokio::test]
 async fn test_suggest_fixes_handler_integration() {
     // Create a test state
@@ -107,3 +192,43 @@ async fn test_validate_dsl_handler_empty_code() {
     assert!(!response.0.valid);
     assert!(!response.0.errors.is_empty());
 }
+
+#[tokio::test]
+async fn test_tokenize_dsl_handler() {
+    let payload = TokenizeRequest {
+        code: "micro A {} // done".to_string(),
+    };
+    let response = tokenize_dsl(HeaderMap::new(), Json(payload)).await;
+
+    assert!(response.0.errors.is_empty());
+    let kinds: Vec<_> = response.0.tokens.iter().map(|t| t.kind.as_str()).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            "keyword",
+            "whitespace",
+            "identifier",
+            "whitespace",
+            "delimiter",
+            "delimiter",
+            "whitespace",
+            "comment"
+        ]
+    );
+    let comment = response.0.tokens.last().unwrap();
+    assert!(comment.trivia);
+    assert_eq!(comment.column, 12);
+
+    // 閉じられていないブロックコメントはエラーとして返す
+    let mut headers = HeaderMap::new();
+    headers.insert(ACCEPT_LANGUAGE, HeaderValue::from_static("ja"));
+    let payload = TokenizeRequest {
+        code: "micro A {\n  /* open\n}".to_string(),
+    };
+    let response = tokenize_dsl(headers, Json(payload)).await;
+
+    assert!(response.0.tokens.is_empty());
+    assert_eq!(response.0.errors.len(), 1);
+    assert_eq!(response.0.errors[0].location.line, 2);
+    assert_eq!(response.0.errors[0].location.column, 3);
+}
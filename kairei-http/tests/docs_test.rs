@@ -211,3 +211,53 @@ async fn test_export_documentation() {
     // Check the response
     assert_eq!(response.status(), StatusCode::OK);
 }
+
+#[tokio::test]
+async fn test_explain_diagnostic() {
+    let request = Request::builder()
+        .uri("/api/v1/docs/diagnostics/type.mismatch")
+        .method("GET")
+        .body(Body::empty())
+        .unwrap();
+    let response = create_test_app().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = axum::body::to_bytes(response.into_body(), 10000)
+        .await
+        .unwrap();
+    let response: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(response["code"], "type.mismatch");
+    assert_eq!(response["component"], "type_checker");
+    assert!(response["example"].is_string());
+    assert!(response["fix"].is_string());
+
+    // 未登録のコード
+    let request = Request::builder()
+        .uri("/api/v1/docs/diagnostics/type.no_such_code")
+        .method("GET")
+        .body(Body::empty())
+        .unwrap();
+    let response = create_test_app().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_list_diagnostics() {
+    let request = Request::builder()
+        .uri("/api/v1/docs/diagnostics")
+        .method("GET")
+        .body(Body::empty())
+        .unwrap();
+    let response = create_test_app().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = axum::body::to_bytes(response.into_body(), 100_000)
+        .await
+        .unwrap();
+    let response: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        response["total"],
+        kairei_core::message_catalog::DIAGNOSTIC_CODES.len()
+    );
+    assert_eq!(response["diagnostics"][0]["code"], "parse.unexpected_eof");
+}
@@ -0,0 +1,111 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    body::to_bytes,
+    http::{Request, StatusCode},
+};
+use kairei_core::{
+    config::{EndpointConfig, ProviderConfig},
+    provider::provider::ProviderType,
+};
+use kairei_http::{
+    auth::{AuthProviderChain, auth_middleware},
+    handlers::test_helpers::create_test_state,
+    models::{ValidateProviderRequest, ValidateProviderResponse},
+    routes,
+};
+use serde_json::json;
+use tower::ServiceExt;
+
+async fn validate(request_body: ValidateProviderRequest) -> ValidateProviderResponse {
+    let app_state = create_test_state();
+    let config = kairei_http::server::ServerConfig::default();
+    let app = routes::create_api_router(&config)
+        .with_state(app_state.clone())
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(AuthProviderChain::api_key(app_state.auth_store.clone())),
+            auth_middleware,
+        ))
+        .into_service();
+
+    let request = Request::builder()
+        .uri("/api/v1/providers/validate")
+        .method("POST")
+        .header("X-API-Key", "user1-key")
+        .header("Content-Type", "application/json")
+        .body(json!(request_body).to_string())
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+fn rag_config(chunk_size: u64) -> ProviderConfig {
+    let mut provider_specific = HashMap::new();
+    provider_specific.insert("type".to_string(), json!("rag"));
+    provider_specific.insert("chunk_size".to_string(), json!(chunk_size));
+    provider_specific.insert("max_tokens".to_string(), json!(1000));
+    ProviderConfig {
+        provider_type: ProviderType::SimpleExpert,
+        provider_specific,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_validate_provider_valid_config() {
+    let response = validate(ValidateProviderRequest {
+        config: rag_config(512),
+        probe: false,
+    })
+    .await;
+
+    assert!(response.valid);
+    assert!(response.errors.is_empty());
+    assert!(response.probe.is_none());
+}
+
+#[tokio::test]
+async fn test_validate_provider_bad_chunk_size() {
+    let response = validate(ValidateProviderRequest {
+        config: rag_config(0),
+        probe: false,
+    })
+    .await;
+
+    assert!(!response.valid);
+    let error = response
+        .errors
+        .iter()
+        .find(|e| e.path.as_deref() == Some("provider_specific.chunk_size"))
+        .expect("chunk_size error");
+    assert_eq!(error.error_code, "VALIDATION_0001");
+    assert!(error.suggestion.as_ref().unwrap().contains("chunk_size"));
+}
+
+#[tokio::test]
+async fn test_validate_provider_unreachable_endpoint() {
+    // Port 1 on localhost is not expected to accept connections
+    let config = ProviderConfig {
+        endpoint: EndpointConfig {
+            url: Some("http://127.0.0.1:1".to_string()),
+            ..Default::default()
+        },
+        ..rag_config(512)
+    };
+    let response = validate(ValidateProviderRequest {
+        config,
+        probe: true,
+    })
+    .await;
+
+    assert!(!response.valid);
+    assert!(response.errors.is_empty());
+    let probe = response.probe.unwrap();
+    assert!(!probe.reachable);
+    assert_eq!(probe.endpoint.as_deref(), Some("http://127.0.0.1:1"));
+    assert!(probe.error.is_some());
+}
@@ -2,22 +2,37 @@ use std::{collections::HashMap, sync::Arc};
 
 use axum::http::{Request, StatusCode};
 use kairei_core::{
-    config::{ProviderConfig, ProviderConfigs},
-    provider::provider::ProviderType,
+    agent_log::LogLevel,
+    agent_settings::AgentSettings,
+    config::{
+        ConversationConfig, PluginConfig, PromptArchiveConfig, ProviderConfig, ProviderConfigs,
+        ProviderSecretConfig,
+    },
+    preflight::{PreflightComponent, PreflightReport, PreflightSource},
+    provider::{
+        capabilities::sistence_memory::PinMode, middleware::archive::ArchiveRecord,
+        provider::ProviderType,
+    },
+    reload_plan::ReloadPlan,
     system::SystemStatus,
 };
 use kairei_http::{
-    auth::auth_middleware,
+    auth::{AuthProviderChain, auth_middleware},
     handlers::test_helpers::create_test_state,
     models::{
-        CreateSystemRequest, CreateSystemResponse, EventRequest, GetAgentResponse,
-        ListAgentsResponse, ListSystemsResponse, ScaleDownAgentRequest, ScaleUpAgentRequest,
-        SendRequestAgentRequest, StartSystemRequest,
+        AgentContractsResponse, AgentLogsResponse, ApiError, CreateSystemRequest,
+        CreateSystemResponse, DebugEvalRequest, EventRequest, EventResponse, GetAgentResponse,
+        ImportMemoriesResponse, ListAgentsResponse, ListSystemsResponse, MemoryPinsResponse,
+        MemoryRelevanceResponse, PreflightJob, PreflightJobStatus, PreflightRequest,
+        ScaleDownAgentRequest, ScaleUpAgentRequest, SearchMemoriesResponse,
+        SendRequestAgentRequest, StartSystemRequest, TestHandlerRequest,
     },
     routes,
+    services::preflight::INLINE_SOURCE_BYTES,
 };
 use serde_json::json;
 use tower::ServiceExt;
+use utoipa::OpenApi;
 
 fn create_test_system_config() -> kairei_core::config::SystemConfig {
     /*SystemConfig {
@@ -53,7 +68,7 @@ async fn test_system_route() {
     let app = routes::create_api_router(&config)
         .with_state(app_state.clone())
         .layer(axum::middleware::from_fn_with_state(
-            Arc::new(app_state.auth_store.clone()),
+            Arc::new(AuthProviderChain::api_key(app_state.auth_store.clone())),
             auth_middleware,
         ))
         .into_service();
@@ -196,6 +211,93 @@ async fn test_system_route() {
     assert_eq!(response.status(), StatusCode::OK);
 }
 
+#[tokio::test]
+async fn test_system_if_match() {
+    let app_state = kairei_http::server::AppState {
+        require_if_match: true,
+        ..create_test_state()
+    };
+    let config = kairei_http::server::ServerConfig::default();
+    let app = routes::create_api_router(&config)
+        .with_state(app_state.clone())
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(AuthProviderChain::api_key(app_state.auth_store.clone())),
+            auth_middleware,
+        ))
+        .into_service();
+
+    let request_body = CreateSystemRequest {
+        name: "TestSystem".to_string(),
+        config: create_test_system_config(),
+        ..Default::default()
+    };
+    let request = Request::builder()
+        .uri("/api/v1/systems")
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(json!(request_body).to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let system_id = serde_json::from_slice::<CreateSystemResponse>(&body)
+        .unwrap()
+        .system_id;
+
+    // 2 つのクライアントが同じ ETag を読む
+    let request = Request::builder()
+        .uri(format!("/api/v1/systems/{}", system_id))
+        .method("GET")
+        .header("X-API-Key", "admin-key")
+        .body("".to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let seen = response.headers()["etag"].to_str().unwrap().to_string();
+
+    let start = |etag: &str| {
+        Request::builder()
+            .uri(format!("/api/v1/systems/{}/start", system_id))
+            .method("POST")
+            .header("Content-Type", "application/json")
+            .header("X-API-Key", "admin-key")
+            .header("If-Match", etag)
+            .body(json!(StartSystemRequest { dsl: None }).to_string())
+            .unwrap()
+    };
+
+    // 最初の書き込みは通り、新しい ETag を受け取る
+    let response = app.clone().oneshot(start(&seen)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let current = response.headers()["etag"].to_str().unwrap().to_string();
+    assert_ne!(current, seen);
+
+    // 古い ETag を持つ 2 つ目の書き込みは失敗する
+    let response = app.clone().oneshot(start(&seen)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+    // 破壊的な操作には If-Match が必要
+    let stop = |etag: Option<&str>| {
+        let mut builder = Request::builder()
+            .uri(format!("/api/v1/systems/{}/stop", system_id))
+            .method("POST")
+            .header("X-API-Key", "admin-key");
+        if let Some(etag) = etag {
+            builder = builder.header("If-Match", etag);
+        }
+        builder.body("".to_string()).unwrap()
+    };
+    let response = app.clone().oneshot(stop(None)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PRECONDITION_REQUIRED);
+
+    let response = app.clone().oneshot(stop(Some(&current))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_ne!(response.headers()["etag"], current.as_str());
+}
+
 #[tokio::test]
 async fn test_agent_route() {
     // Create the router with a test state
@@ -205,7 +307,7 @@ async fn test_agent_route() {
     let app = routes::create_api_router(&config)
         .with_state(app_state.clone())
         .layer(axum::middleware::from_fn_with_state(
-            Arc::new(app_state.auth_store.clone()),
+            Arc::new(AuthProviderChain::api_key(app_state.auth_store.clone())),
             auth_middleware,
         ))
         .into_service();
@@ -411,6 +513,403 @@ async fn test_agent_route() {
     assert!(response.status().is_success());
 }
 
+#[tokio::test]
+async fn test_agent_label_filter_route() {
+    let app_state: kairei_http::server::AppState = create_test_state();
+    let config = kairei_http::server::ServerConfig::default();
+
+    let app = routes::create_api_router(&config)
+        .with_state(app_state.clone())
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(AuthProviderChain::api_key(app_state.auth_store.clone())),
+            auth_middleware,
+        ))
+        .into_service();
+
+    // setup system
+    let request_body = CreateSystemRequest {
+        name: "LabelSystem".to_string(),
+        config: create_test_system_config(),
+        ..Default::default()
+    };
+    let request = Request::builder()
+        .uri("/api/v1/systems")
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(json!(request_body).to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let resp: CreateSystemResponse = serde_json::from_slice(&body).unwrap();
+    let system_id = resp.system_id.clone();
+
+    let request_body = json!(StartSystemRequest {
+        dsl: Some("micro Placeholder {}".to_string())
+    });
+    let request = Request::builder()
+        .uri(format!("/api/v1/systems/{}/start", system_id))
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(request_body.to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // create labeled agents
+    for (name, team, env) in [
+        ("PaymentsProd", "payments", "prod"),
+        ("PaymentsDev", "payments", "dev"),
+        ("SearchProd", "search", "prod"),
+    ] {
+        let request_body = json!({
+            "name": name,
+            "dsl_code": format!("micro {} {{}}", name),
+            "labels": { "team": team, "env": env }
+        });
+        let request = Request::builder()
+            .uri(format!("/api/v1/systems/{}/agents", system_id))
+            .method("POST")
+            .header("X-API-Key", "admin-key")
+            .header("Content-Type", "application/json")
+            .body(request_body.to_string())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert!(response.status().is_success());
+    }
+
+    // filter by multiple labels (AND)
+    let request = Request::builder()
+        .uri(format!(
+            "/api/v1/systems/{}/agents?label=team:payments&label=env:prod",
+            system_id
+        ))
+        .method("GET")
+        .header("X-API-Key", "admin-key")
+        .body("".to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), 10000)
+        .await
+        .unwrap();
+    let list_agents_response: ListAgentsResponse = serde_json::from_slice(&body).unwrap();
+    assert_eq!(list_agents_response.agents.len(), 1);
+    let agent = &list_agents_response.agents[0];
+    assert_eq!(agent.agent_id, "PaymentsProd");
+    assert_eq!(agent.labels.get("team"), Some(&"payments".to_string()));
+
+    // malformed selector
+    let request = Request::builder()
+        .uri(format!("/api/v1/systems/{}/agents?label=team", system_id))
+        .method("GET")
+        .header("X-API-Key", "admin-key")
+        .body("".to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_response_shaping() {
+    let app_state: kairei_http::server::AppState = create_test_state();
+    let config = kairei_http::server::ServerConfig::default();
+
+    let app = routes::create_api_router(&config)
+        .with_state(app_state.clone())
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(AuthProviderChain::api_key(app_state.auth_store.clone())),
+            auth_middleware,
+        ))
+        .into_service();
+
+    let request = Request::builder()
+        .uri("/api/v1/systems")
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(
+            json!(CreateSystemRequest {
+                name: "ShapedSystem".to_string(),
+                config: create_test_system_config(),
+                ..Default::default()
+            })
+            .to_string(),
+        )
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let system_id = serde_json::from_slice::<CreateSystemResponse>(&body)
+        .unwrap()
+        .system_id;
+
+    let get = |uri: String| {
+        let request = Request::builder()
+            .uri(uri)
+            .method("GET")
+            .header("X-API-Key", "admin-key")
+            .body("".to_string())
+            .unwrap();
+        let app = app.clone();
+        async move {
+            let response = app.oneshot(request).await.unwrap();
+            let status = response.status();
+            let body = axum::body::to_bytes(response.into_body(), 100000)
+                .await
+                .unwrap();
+            (
+                status,
+                serde_json::from_slice::<serde_json::Value>(&body).unwrap(),
+            )
+        }
+    };
+
+    // 起動前はワールドがないため policies だけが失敗する
+    let (status, system) = get(format!(
+        "/api/v1/systems/{}?fields=running,agent_count&embed=statistics,policies",
+        system_id
+    ))
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let keys: Vec<&String> = system.as_object().unwrap().keys().collect();
+    assert_eq!(
+        keys,
+        vec!["agent_count", "embed_errors", "embedded", "running"]
+    );
+    assert!(system["embedded"]["statistics"]["llm_calls"].is_array());
+    assert!(system["embedded"].get("policies").is_none());
+    assert!(system["embed_errors"]["policies"].is_string());
+
+    let (status, error) = get(format!(
+        "/api/v1/systems/{}?fields=name&embed=agents",
+        system_id
+    ))
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let error: ApiError = serde_json::from_value(error).unwrap();
+    assert_eq!(error.error, "invalid_query");
+    let fields: Vec<&str> = error.fields.iter().map(|f| f.field.as_str()).collect();
+    assert_eq!(fields, vec!["fields", "embed"]);
+    assert!(error.fields[0].reason.contains("agent_count"));
+
+    let request_body = json!(StartSystemRequest {
+        dsl: Some(
+            r#"micro Counter {
+            state {
+                count: Int = 2;
+            }
+        }"#
+            .to_string()
+        )
+    });
+    let request = Request::builder()
+        .uri(format!("/api/v1/systems/{}/start", system_id))
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(request_body.to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // 状態の初期値はエージェントのタスクで設定されるので、ランタイムを止めずに待つ
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    let (status, agent) = get(format!(
+        "/api/v1/systems/{}/agents/Counter?fields=agent_id&embed=statistics,policies,state",
+        system_id
+    ))
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(agent["agent_id"], "Counter");
+    assert!(agent.get("status").is_none());
+    assert!(agent.get("embed_errors").is_none());
+    assert_eq!(agent["embedded"]["state"]["count"], 2);
+    assert_eq!(agent["embedded"]["policies"], json!([]));
+    assert_eq!(agent["embedded"]["statistics"]["instances"], 1);
+
+    // 一覧では各エージェントを同じように整形する
+    let (status, list) = get(format!(
+        "/api/v1/systems/{}/agents?fields=agent_id",
+        system_id
+    ))
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let agents = list["agents"].as_array().unwrap();
+    assert!(!agents.is_empty());
+    assert!(
+        agents
+            .iter()
+            .all(|agent| agent.as_object().unwrap().len() == 1 && agent["agent_id"].is_string())
+    );
+}
+
+#[tokio::test]
+async fn test_memories_routes() {
+    let app_state: kairei_http::server::AppState = create_test_state();
+    let config = kairei_http::server::ServerConfig::default();
+
+    let app = routes::create_api_router(&config)
+        .with_state(app_state.clone())
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(AuthProviderChain::api_key(app_state.auth_store.clone())),
+            auth_middleware,
+        ))
+        .into_service();
+
+    // setup system
+    let request_body = CreateSystemRequest {
+        name: "MemorySystem".to_string(),
+        config: create_test_system_config(),
+        ..Default::default()
+    };
+    let request = Request::builder()
+        .uri("/api/v1/systems")
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(json!(request_body).to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let resp: CreateSystemResponse = serde_json::from_slice(&body).unwrap();
+    let system_id = resp.system_id.clone();
+
+    // import JSONL with one malformed line and a custom content field
+    let jsonl = [
+        r#"{"id": "m1", "text": "Rust ownership rules", "topics": ["rust"]}"#,
+        r#"{"id": "m2", "text": "broken"#,
+        r#"{"id": "m3", "text": "Tokio runtime basics", "importance": 0.9}"#,
+    ]
+    .join("\n");
+    let request = Request::builder()
+        .uri(format!(
+            "/api/v1/systems/{}/memories/notes/import?content=text&batch_size=1",
+            system_id
+        ))
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/x-ndjson")
+        .body(jsonl)
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), 10000)
+        .await
+        .unwrap();
+    let resp: ImportMemoriesResponse = serde_json::from_slice(&body).unwrap();
+    assert_eq!(resp.lines_read, 3);
+    assert_eq!(resp.imported, 2);
+    assert_eq!(resp.item_ids, vec!["m1".to_string(), "m3".to_string()]);
+    assert_eq!(resp.errors.len(), 1);
+    assert_eq!(resp.errors[0].line, 2);
+
+    // search with a filter tree
+    let search = |filter: serde_json::Value| {
+        Request::builder()
+            .uri(format!(
+                "/api/v1/systems/{}/memories/notes/search",
+                system_id
+            ))
+            .method("POST")
+            .header("X-API-Key", "admin-key")
+            .header("Content-Type", "application/json")
+            .body(json!({"query": "", "filter": filter}).to_string())
+            .unwrap()
+    };
+    let request = search(json!({"and": [
+        {"min_importance": 0.8},
+        {"not": {"topic_contains": "rust"}}
+    ]}));
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), 10000)
+        .await
+        .unwrap();
+    let resp: SearchMemoriesResponse = serde_json::from_slice(&body).unwrap();
+    let ids: Vec<&str> = resp.items.iter().map(|item| item.id.as_str()).collect();
+    assert_eq!(ids, vec!["m3"]);
+
+    // an empty `and` is rejected
+    let response = app
+        .clone()
+        .oneshot(search(json!({"and": []})))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = axum::body::to_bytes(response.into_body(), 10000)
+        .await
+        .unwrap();
+    let error: ApiError = serde_json::from_slice(&body).unwrap();
+    assert_eq!(error.fields[0].field, "filter");
+
+    // relevance of the memories to a context, with the breakdown of each score
+    let request = Request::builder()
+        .uri(format!(
+            "/api/v1/systems/{}/memories/notes/relevance",
+            system_id
+        ))
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(
+            json!({
+                "context": {
+                    "context_id": "debug",
+                    "current_topics": ["rust"],
+                    "recent_items": [],
+                    "query_type": "Semantic",
+                    "participants": [],
+                    "current_activity": "ownership",
+                    "temporal_context": {"relevant_periods": []}
+                }
+            })
+            .to_string(),
+        )
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), 10000)
+        .await
+        .unwrap();
+    let resp: MemoryRelevanceResponse = serde_json::from_slice(&body).unwrap();
+    let ranked: Vec<(usize, &str)> = resp
+        .items
+        .iter()
+        .map(|ranked| (ranked.rank, ranked.item.id.as_str()))
+        .collect();
+    assert_eq!(ranked, vec![(1, "m1"), (2, "m3")]);
+    assert!(resp.items[0].relevance > resp.items[1].relevance);
+    for ranked in &resp.items {
+        let names: Vec<&str> = ranked.factors.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["topic_match", "recency", "importance", "activity_relevance"]
+        );
+        let sum: f32 = ranked.factors.iter().map(|f| f.score * f.weight).sum();
+        assert!((ranked.relevance - sum).abs() < 1e-6);
+    }
+    assert_eq!(resp.items[0].factors[0].score, 1.0);
+    assert_eq!(resp.items[1].factors[0].score, 0.0);
+
+    // unknown system
+    let request = Request::builder()
+        .uri("/api/v1/systems/unknown/memories/notes/import")
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .body("".to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
 #[tokio::test]
 async fn test_event_route() {
     let app_state: kairei_http::server::AppState = create_test_state();
@@ -420,7 +919,7 @@ async fn test_event_route() {
     let app = routes::create_api_router(&config)
         .with_state(app_state.clone())
         .layer(axum::middleware::from_fn_with_state(
-            Arc::new(app_state.auth_store.clone()),
+            Arc::new(AuthProviderChain::api_key(app_state.auth_store.clone())),
             auth_middleware,
         ))
         .into_service();
@@ -459,7 +958,7 @@ async fn test_event_route() {
         .unwrap();
 
     let response = app.clone().oneshot(request).await.unwrap();
-    assert_eq!(response.status(), StatusCode::NOT_IMPLEMENTED);
+    assert_eq!(response.status(), StatusCode::OK);
 
     let request = Request::builder()
         .uri(format!(
@@ -472,8 +971,9 @@ async fn test_event_route() {
         .body(json!(EventRequest::default()).to_string())
         .unwrap();
 
+    // イベント種別のない送信は受け付けない
     let response = app.clone().oneshot(request).await.unwrap();
-    assert_eq!(response.status(), StatusCode::NOT_IMPLEMENTED);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
 
     let request = Request::builder()
         .uri(format!(
@@ -490,3 +990,2508 @@ async fn test_event_route() {
 
     assert_eq!(response.status(), StatusCode::NOT_IMPLEMENTED);
 }
+
+#[tokio::test]
+async fn test_list_events_route() {
+    let app_state: kairei_http::server::AppState = create_test_state();
+    let config = kairei_http::server::ServerConfig::default();
+
+    let app = routes::create_api_router(&config)
+        .with_state(app_state.clone())
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(AuthProviderChain::api_key(app_state.auth_store.clone())),
+            auth_middleware,
+        ))
+        .into_service();
+
+    let request = Request::builder()
+        .uri("/api/v1/systems")
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(
+            json!(CreateSystemRequest {
+                name: "EventSystem".to_string(),
+                config: create_test_system_config(),
+                ..Default::default()
+            })
+            .to_string(),
+        )
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let system_id = serde_json::from_slice::<CreateSystemResponse>(&body)
+        .unwrap()
+        .system_id;
+
+    let request_body = json!(StartSystemRequest {
+        dsl: Some(
+            r#"micro Ledger {
+            state {
+                total: Int = 10;
+            }
+            observe {
+                on Deposit(amount: Int) {
+                    total = total + amount
+                    emit Deposited(total: total)
+                }
+            }
+        }"#
+            .to_string()
+        )
+    });
+    let request = Request::builder()
+        .uri(format!("/api/v1/systems/{}/start", system_id))
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(request_body.to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    let request = Request::builder()
+        .uri(format!(
+            "/api/v1/systems/{}/events/deposit-1/emit",
+            system_id
+        ))
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(
+            json!(EventRequest {
+                event_type: "Deposit".to_string(),
+                payload: json!({"amount": 5}),
+                ..Default::default()
+            })
+            .to_string(),
+        )
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), 10000)
+        .await
+        .unwrap();
+    let emitted: EventResponse = serde_json::from_slice(&body).unwrap();
+    assert_eq!(emitted.event_type, "Deposit");
+    assert_eq!(emitted.parent_event_id, None);
+    assert_eq!(emitted.root_event_id, "deposit-1");
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    let list = |query: &str, api_key: &str| {
+        Request::builder()
+            .uri(format!("/api/v1/systems/{}/events{}", system_id, query))
+            .method("GET")
+            .header("X-API-Key", api_key)
+            .body(String::new())
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(list("", "admin-key")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), 100_000)
+        .await
+        .unwrap();
+    let events: Vec<EventResponse> = serde_json::from_slice(&body).unwrap();
+    let deposit = events
+        .iter()
+        .position(|event| event.event_id == "deposit-1")
+        .unwrap();
+    assert_eq!(events[deposit].event_type, "Deposit");
+    assert_eq!(events[deposit].parent_event_id, None);
+    // ハンドラーが送ったイベントは、原因のイベントの後に並ぶ
+    let deposited = events
+        .iter()
+        .position(|event| event.event_type == "Deposited")
+        .unwrap();
+    assert!(deposited > deposit);
+    assert_eq!(
+        events[deposited].parent_event_id.as_deref(),
+        Some("deposit-1")
+    );
+    assert_eq!(events[deposited].root_event_id, "deposit-1");
+
+    let response = app
+        .clone()
+        .oneshot(list("?limit=1", "admin-key"))
+        .await
+        .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), 100_000)
+        .await
+        .unwrap();
+    let events: Vec<EventResponse> = serde_json::from_slice(&body).unwrap();
+    assert_eq!(events.len(), 1);
+
+    let response = app.clone().oneshot(list("", "user1-key")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/api/v1/systems/missing/events")
+                .method("GET")
+                .header("X-API-Key", "admin-key")
+                .body(String::new())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_request_parameters_coerced_to_handler_types() {
+    let app_state: kairei_http::server::AppState = create_test_state();
+    let config = kairei_http::server::ServerConfig::default();
+
+    let app = routes::create_api_router(&config)
+        .with_state(app_state.clone())
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(AuthProviderChain::api_key(app_state.auth_store.clone())),
+            auth_middleware,
+        ))
+        .into_service();
+
+    let request = Request::builder()
+        .uri("/api/v1/systems")
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(
+            json!(CreateSystemRequest {
+                name: "TestSystem".to_string(),
+                config: create_test_system_config(),
+                ..Default::default()
+            })
+            .to_string(),
+        )
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let system_id = serde_json::from_slice::<CreateSystemResponse>(&body)
+        .unwrap()
+        .system_id;
+
+    let request_body = json!(StartSystemRequest {
+        dsl: Some(
+            r#"micro Timer {
+            answer {
+                on request Schedule(delay: Duration, times: Int where times in 1..10) -> Result<Int, Error> {
+                    return Ok(times)
+                }
+            }
+        }"#
+            .to_string()
+        )
+    });
+    let request = Request::builder()
+        .uri(format!("/api/v1/systems/{}/start", system_id))
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(request_body.to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let send = |payload: serde_json::Value| {
+        Request::builder()
+            .uri(format!(
+                "/api/v1/systems/{}/agents/Timer/request",
+                system_id
+            ))
+            .method("POST")
+            .header("Content-Type", "application/json")
+            .header("X-API-Key", "admin-key")
+            .body(
+                json!(SendRequestAgentRequest {
+                    request_type: "Schedule".to_string(),
+                    payload,
+                })
+                .to_string(),
+            )
+            .unwrap()
+    };
+
+    // "5s" は Duration に、"3" は Int に変換される
+    let response = app
+        .clone()
+        .oneshot(send(json!({"delay": "5s", "times": "3"})))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body, json!({"value": 3}));
+
+    // ヘッダーで求めると処理時間の内訳も返す
+    let mut request = send(json!({"delay": "5s", "times": "3"}));
+    request
+        .headers_mut()
+        .insert("X-Kairei-Profile", "true".parse().unwrap());
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), 10000)
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["value"], 3);
+    let sections: Vec<&str> = body["profile"]["sections"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|section| section["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        sections,
+        vec![
+            "dispatch_wait",
+            "statement 1 (return)",
+            "postprocess",
+            "serialization"
+        ]
+    );
+
+    // 変換できない値はハンドラに届く前に 422 で拒否される
+    let response = app
+        .clone()
+        .oneshot(send(json!({"delay": "soon", "times": 1})))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["parameter"], "delay");
+    assert_eq!(body["expected"], "Duration");
+
+    let response = app
+        .clone()
+        .oneshot(send(json!({"delay": 100, "times": 1.5})))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    // where 制約に違反すると本体を実行せず、違反の一覧を 422 で返す
+    let response = app
+        .clone()
+        .oneshot(send(json!({"delay": "5s", "times": 0})))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        body["violations"],
+        json!([{"parameter": "times", "constraint": "times in 1..10"}])
+    );
+}
+
+#[tokio::test]
+async fn test_request_validated_against_contract() {
+    let app_state: kairei_http::server::AppState = create_test_state();
+    let config = kairei_http::server::ServerConfig::default();
+
+    let app = routes::create_api_router(&config)
+        .with_state(app_state.clone())
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(AuthProviderChain::api_key(app_state.auth_store.clone())),
+            auth_middleware,
+        ))
+        .into_service();
+
+    let request = Request::builder()
+        .uri("/api/v1/systems")
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(
+            json!(CreateSystemRequest {
+                name: "TestSystem".to_string(),
+                config: create_test_system_config(),
+                ..Default::default()
+            })
+            .to_string(),
+        )
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let system_id = serde_json::from_slice::<CreateSystemResponse>(&body)
+        .unwrap()
+        .system_id;
+
+    let request_body = json!(StartSystemRequest {
+        dsl: Some(
+            r#"micro Weather {
+            contract {
+                /// Forecast for a city
+                request GetForecast(city: String, days: Int) -> String
+            }
+            answer {
+                on request GetForecast(city: String, days: Int) -> Result<String, Error> {
+                    return Ok(city)
+                }
+            }
+        }"#
+            .to_string()
+        )
+    });
+    let request = Request::builder()
+        .uri(format!("/api/v1/systems/{}/start", system_id))
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(request_body.to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    // 契約は一覧で取得できる
+    let request = Request::builder()
+        .uri(format!(
+            "/api/v1/systems/{}/agents/Weather/contracts",
+            system_id
+        ))
+        .method("GET")
+        .header("X-API-Key", "admin-key")
+        .body("".to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let body = serde_json::from_slice::<AgentContractsResponse>(&body).unwrap();
+    assert_eq!(body.contracts.len(), 1);
+    assert_eq!(
+        body.contracts[0].to_string(),
+        "GetForecast(city: String, days: Int) -> String"
+    );
+
+    let send = |payload: serde_json::Value| {
+        Request::builder()
+            .uri(format!(
+                "/api/v1/systems/{}/agents/Weather/request",
+                system_id
+            ))
+            .method("POST")
+            .header("Content-Type", "application/json")
+            .header("X-API-Key", "admin-key")
+            .body(
+                json!(SendRequestAgentRequest {
+                    request_type: "GetForecast".to_string(),
+                    payload,
+                })
+                .to_string(),
+            )
+            .unwrap()
+    };
+
+    let response = app
+        .clone()
+        .oneshot(send(json!({"city": "Tokyo", "days": 3})))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body, json!({"value": "Tokyo"}));
+
+    // 契約に反するリクエストはエージェントに届く前に 400 で拒否される
+    let response = app
+        .clone()
+        .oneshot(send(json!({"city": "Tokyo"})))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["parameter"], "days");
+    assert_eq!(body["expected"], "Int");
+
+    let response = app
+        .clone()
+        .oneshot(send(json!({"city": "Tokyo", "days": 3, "unit": "C"})))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = app
+        .clone()
+        .oneshot(send(json!({"city": "Tokyo", "days": "a few"})))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_gpts_manifest_route() {
+    let app_state: kairei_http::server::AppState = create_test_state();
+    let mut config = kairei_http::server::ServerConfig::default();
+    config.gpts_manifest.public_base_url = Some("https://kairei.example.com/".to_string());
+    config.gpts_manifest.max_bytes = 16 * 1024;
+    let allowed_paths = config.gpts_manifest.allowed_paths.clone();
+
+    let app = routes::create_api_router(&config)
+        .with_state(app_state.clone())
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(AuthProviderChain::api_key(app_state.auth_store.clone())),
+            auth_middleware,
+        ))
+        .into_service();
+
+    // served without an API key
+    let request = Request::builder()
+        .uri("/api/v1/docs/gpts-manifest.json")
+        .method("GET")
+        .body("".to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), 1024 * 1024)
+        .await
+        .unwrap();
+    assert!(body.len() <= config.gpts_manifest.max_bytes);
+
+    let manifest: utoipa::openapi::OpenApi = serde_json::from_slice(&body).unwrap();
+    assert!(!manifest.paths.paths.is_empty());
+    for path in manifest.paths.paths.keys() {
+        assert!(
+            allowed_paths
+                .iter()
+                .any(|allowed| path == allowed || path.starts_with(&format!("{}/", allowed))),
+            "unexpected path in manifest: {}",
+            path
+        );
+    }
+    let validate = manifest.paths.paths["/compiler/validate"]
+        .post
+        .as_ref()
+        .unwrap();
+    assert_eq!(
+        validate.description.as_deref(),
+        config
+            .gpts_manifest
+            .description_overrides
+            .get("validate_dsl")
+            .map(String::as_str)
+    );
+    assert_eq!(
+        manifest.servers.unwrap()[0].url,
+        "https://kairei.example.com/api/v1"
+    );
+    let components = manifest.components.unwrap();
+    assert!(components.security_schemes.contains_key("api_key"));
+    assert!(components.schemas.contains_key("ValidationRequest"));
+    assert!(!components.schemas.contains_key("CreateSystemRequest"));
+
+    // over budget
+    let mut config = kairei_http::server::ServerConfig::default();
+    config.gpts_manifest.max_bytes = 16;
+    let result = kairei_http::routes::gpts::build_gpts_manifest(
+        &kairei_http::routes::swagger::ApiDoc::openapi(),
+        &config,
+    );
+    assert!(matches!(
+        result,
+        Err(kairei_http::routes::gpts::GptsManifestError::TooLarge { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_system_lifecycle_event_stream() {
+    use futures::StreamExt;
+    use kairei_http::models::{LifecycleEvent, LifecycleEventKind};
+
+    let app_state: kairei_http::server::AppState = create_test_state();
+    let config = kairei_http::server::ServerConfig::default();
+    let app = routes::create_api_router(&config)
+        .with_state(app_state.clone())
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(AuthProviderChain::api_key(app_state.auth_store.clone())),
+            auth_middleware,
+        ))
+        .into_service();
+
+    // ストリームを先に開いておく
+    let request = Request::builder()
+        .uri("/api/v1/systems/events/stream")
+        .method("GET")
+        .header("X-API-Key", "admin-key")
+        .body("".to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["content-type"].to_str().unwrap(),
+        "text/event-stream"
+    );
+    let mut stream = response.into_body().into_data_stream();
+
+    // Create, start and stop a system
+    let request = Request::builder()
+        .uri("/api/v1/systems")
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(
+            json!(CreateSystemRequest {
+                name: "TestSystem".to_string(),
+                config: create_test_system_config(),
+                ..Default::default()
+            })
+            .to_string(),
+        )
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let system_id = serde_json::from_slice::<CreateSystemResponse>(&body)
+        .unwrap()
+        .system_id;
+
+    for action in ["start", "stop"] {
+        let request = Request::builder()
+            .uri(format!("/api/v1/systems/{}/{}", system_id, action))
+            .method("POST")
+            .header("X-API-Key", "admin-key")
+            .header("Content-Type", "application/json")
+            .body(json!(StartSystemRequest { dsl: None }).to_string())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    // SSE のフレームを 3 件分読み取る
+    let mut received = String::new();
+    let mut events = vec![];
+    while events.len() < 3 {
+        let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), stream.next())
+            .await
+            .expect("lifecycle event should arrive")
+            .unwrap()
+            .unwrap();
+        received.push_str(std::str::from_utf8(&chunk).unwrap());
+        while let Some(end) = received.find("\n\n") {
+            let frame: String = received.drain(..end + 2).collect();
+            let name = frame
+                .lines()
+                .find_map(|line| line.strip_prefix("event: "))
+                .map(str::to_string);
+            let data = frame.lines().find_map(|line| line.strip_prefix("data: "));
+            if let (Some(name), Some(data)) = (name, data) {
+                let event: LifecycleEvent = serde_json::from_str(data).unwrap();
+                assert_eq!(name, event.kind.as_str());
+                events.push(event);
+            }
+        }
+    }
+
+    assert_eq!(
+        events.iter().map(|event| event.kind).collect::<Vec<_>>(),
+        vec![
+            LifecycleEventKind::SystemCreated,
+            LifecycleEventKind::SystemStarted,
+            LifecycleEventKind::SystemStopped,
+        ]
+    );
+    assert!(events.iter().all(|event| event.system_id == system_id));
+    assert!(events[0].timestamp <= events[2].timestamp);
+}
+
+/// Read SSE frames from `stream` until `count` events arrived, as (id, event, data)
+async fn read_sse_events<S, E>(
+    stream: &mut S,
+    count: usize,
+) -> Vec<(Option<String>, String, serde_json::Value)>
+where
+    S: futures::Stream<Item = Result<axum::body::Bytes, E>> + Unpin,
+    E: std::fmt::Debug,
+{
+    use futures::StreamExt;
+
+    let mut received = String::new();
+    let mut events = vec![];
+    while events.len() < count {
+        let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), stream.next())
+            .await
+            .expect("SSE event should arrive")
+            .unwrap()
+            .unwrap();
+        received.push_str(std::str::from_utf8(&chunk).unwrap());
+        while let Some(end) = received.find("\n\n") {
+            let frame: String = received.drain(..end + 2).collect();
+            let field = |name: &str| {
+                frame
+                    .lines()
+                    .find_map(|line| line.strip_prefix(name))
+                    .map(str::to_string)
+            };
+            if let (Some(name), Some(data)) = (field("event: "), field("data: ")) {
+                events.push((field("id: "), name, serde_json::from_str(&data).unwrap()));
+            }
+        }
+    }
+    events
+}
+
+#[tokio::test]
+async fn test_system_lifecycle_event_stream_resumes() {
+    let app_state: kairei_http::server::AppState = create_test_state();
+    let config = kairei_http::server::ServerConfig::default();
+    let app = routes::create_api_router(&config)
+        .with_state(app_state.clone())
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(AuthProviderChain::api_key(app_state.auth_store.clone())),
+            auth_middleware,
+        ))
+        .into_service();
+    let open_stream = |last_event_id: &str| {
+        Request::builder()
+            .uri("/api/v1/systems/events/stream")
+            .method("GET")
+            .header("X-API-Key", "admin-key")
+            .header("Last-Event-ID", last_event_id)
+            .body("".to_string())
+            .unwrap()
+    };
+    let system_action = |system_id: &str, action: &str| {
+        Request::builder()
+            .uri(format!("/api/v1/systems/{}/{}", system_id, action))
+            .method("POST")
+            .header("X-API-Key", "admin-key")
+            .header("Content-Type", "application/json")
+            .body(json!(StartSystemRequest { dsl: None }).to_string())
+            .unwrap()
+    };
+
+    // ストリームを開いていない間に作成・開始・停止する (id 1, 2, 3)
+    let create_system = || {
+        Request::builder()
+            .uri("/api/v1/systems")
+            .method("POST")
+            .header("X-API-Key", "admin-key")
+            .header("Content-Type", "application/json")
+            .body(
+                json!(CreateSystemRequest {
+                    name: "TestSystem".to_string(),
+                    config: create_test_system_config(),
+                    ..Default::default()
+                })
+                .to_string(),
+            )
+            .unwrap()
+    };
+    let response = app.clone().oneshot(create_system()).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let system_id = serde_json::from_slice::<CreateSystemResponse>(&body)
+        .unwrap()
+        .system_id;
+    for action in ["start", "stop"] {
+        let response = app
+            .clone()
+            .oneshot(system_action(&system_id, action))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    // 最後に受け取ったのが 1 なら、2 と 3 が再送されてからライブのイベントが続く
+    let response = app.clone().oneshot(open_stream("1")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let mut stream = response.into_body().into_data_stream();
+    let replayed = read_sse_events(&mut stream, 2).await;
+    assert_eq!(
+        replayed
+            .iter()
+            .map(|(id, name, _)| (id.as_deref().unwrap(), name.as_str()))
+            .collect::<Vec<_>>(),
+        vec![("2", "system_started"), ("3", "system_stopped")]
+    );
+    assert!(
+        replayed
+            .iter()
+            .all(|(_, _, data)| data["system_id"] == system_id)
+    );
+
+    let response = app.clone().oneshot(create_system()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let live = read_sse_events(&mut stream, 1).await;
+    assert_eq!(live[0].0.as_deref(), Some("4"));
+    assert_eq!(live[0].1, "system_created");
+
+    // 知らない id からの再開は欠落を通知し、残っている全件を送る
+    let response = app.clone().oneshot(open_stream("999")).await.unwrap();
+    let mut stream = response.into_body().into_data_stream();
+    let resumed = read_sse_events(&mut stream, 5).await;
+    assert_eq!(resumed[0].0, None);
+    assert_eq!(resumed[0].1, "gap");
+    assert_eq!(resumed[0].2, json!({ "last_event_id": 999 }));
+    assert_eq!(
+        resumed[1..]
+            .iter()
+            .map(|(id, _, _)| id.as_deref().unwrap())
+            .collect::<Vec<_>>(),
+        vec!["1", "2", "3", "4"]
+    );
+
+    let response = app.clone().oneshot(open_stream("latest")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_debug_eval_route() {
+    let app_state: kairei_http::server::AppState = create_test_state();
+    let config = kairei_http::server::ServerConfig::default();
+
+    let app = routes::create_api_router(&config)
+        .with_state(app_state.clone())
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(AuthProviderChain::api_key(app_state.auth_store.clone())),
+            auth_middleware,
+        ))
+        .into_service();
+
+    let request = Request::builder()
+        .uri("/api/v1/systems")
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(
+            json!(CreateSystemRequest {
+                name: "TestSystem".to_string(),
+                config: create_test_system_config(),
+                ..Default::default()
+            })
+            .to_string(),
+        )
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let system_id = serde_json::from_slice::<CreateSystemResponse>(&body)
+        .unwrap()
+        .system_id;
+
+    let request_body = json!(StartSystemRequest {
+        dsl: Some(
+            r#"micro Counter {
+            state {
+                count: Int = 2;
+            }
+            answer {
+                on request GetCount() -> Result<Int, Error> {
+                    return Ok(count)
+                }
+            }
+        }"#
+            .to_string()
+        )
+    });
+    let request = Request::builder()
+        .uri(format!("/api/v1/systems/{}/start", system_id))
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(request_body.to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // 状態の初期値はエージェントのタスクで設定されるので、ランタイムを止めずに待つ
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    let eval = |expression: &str, api_key: &str| {
+        Request::builder()
+            .uri(format!(
+                "/api/v1/systems/{}/agents/Counter/debug/eval",
+                system_id
+            ))
+            .method("POST")
+            .header("Content-Type", "application/json")
+            .header("X-API-Key", api_key)
+            .body(
+                json!(DebugEvalRequest {
+                    expression: expression.to_string(),
+                })
+                .to_string(),
+            )
+            .unwrap()
+    };
+
+    let response = app
+        .clone()
+        .oneshot(eval("count + 1", "admin-key"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body, json!({"value": 3, "type": "Int"}));
+
+    // 書き込みは評価前に拒否され、状態は変わらない
+    let response = app
+        .clone()
+        .oneshot(eval("count = 0", "admin-key"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(body["error"].as_str().unwrap().contains("read-only"));
+
+    let response = app
+        .clone()
+        .oneshot(eval("request GetCount to Counter()", "admin-key"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        body["error"],
+        "`request` expressions are not allowed in debug evaluation, since they send a request to another agent"
+    );
+
+    let response = app
+        .clone()
+        .oneshot(eval("count", "admin-key"))
+        .await
+        .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["value"], 2);
+
+    // 管理者以外は使えない
+    let response = app
+        .clone()
+        .oneshot(eval("count", "user1-key"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_handler_test_route() {
+    let app_state: kairei_http::server::AppState = create_test_state();
+    let config = kairei_http::server::ServerConfig::default();
+
+    let app = routes::create_api_router(&config)
+        .with_state(app_state.clone())
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(AuthProviderChain::api_key(app_state.auth_store.clone())),
+            auth_middleware,
+        ))
+        .into_service();
+
+    let request = Request::builder()
+        .uri("/api/v1/systems")
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(
+            json!(CreateSystemRequest {
+                name: "TestSystem".to_string(),
+                config: create_test_system_config(),
+                ..Default::default()
+            })
+            .to_string(),
+        )
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let system_id = serde_json::from_slice::<CreateSystemResponse>(&body)
+        .unwrap()
+        .system_id;
+
+    let request_body = json!(StartSystemRequest {
+        dsl: Some(
+            r#"micro Ledger {
+            state {
+                total: Int = 10;
+            }
+            observe {
+                on Deposit(amount: Int) {
+                    total = total + amount
+                    emit Deposited(total: total)
+                }
+            }
+        }"#
+            .to_string()
+        )
+    });
+    let request = Request::builder()
+        .uri(format!("/api/v1/systems/{}/start", system_id))
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(request_body.to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // 状態の初期値はエージェントのタスクで設定されるので、ランタイムを止めずに待つ
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    let fire = |event: &str, api_key: &str| {
+        Request::builder()
+            .uri(format!(
+                "/api/v1/systems/{}/agents/Ledger/handlers/{}/test",
+                system_id, event
+            ))
+            .method("POST")
+            .header("Content-Type", "application/json")
+            .header("X-API-Key", api_key)
+            .body(
+                json!(TestHandlerRequest {
+                    payload: json!({"amount": 5}),
+                    ..Default::default()
+                })
+                .to_string(),
+            )
+            .unwrap()
+    };
+
+    let response = app
+        .clone()
+        .oneshot(fire("Deposit", "admin-key"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), 10000)
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["kind"], "observe");
+    assert_eq!(body["ran"], true);
+    assert_eq!(
+        body["state_changes"],
+        json!([{"name": "total", "before": 10, "after": 15}])
+    );
+    assert_eq!(
+        body["emitted_events"],
+        json!([{"event_type": "Deposited", "parameters": {"total": 15}}])
+    );
+
+    // 実行中のエージェントの状態は変わらない
+    let request = Request::builder()
+        .uri(format!(
+            "/api/v1/systems/{}/agents/Ledger/debug/eval",
+            system_id
+        ))
+        .method("POST")
+        .header("Content-Type", "application/json")
+        .header("X-API-Key", "admin-key")
+        .body(
+            json!(DebugEvalRequest {
+                expression: "total".to_string(),
+            })
+            .to_string(),
+        )
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["value"], 10);
+
+    let response = app
+        .clone()
+        .oneshot(fire("Withdraw", "admin-key"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // 管理者以外は使えない
+    let response = app
+        .clone()
+        .oneshot(fire("Deposit", "user1-key"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_debug_session_routes() {
+    let app_state: kairei_http::server::AppState = create_test_state();
+    let config = kairei_http::server::ServerConfig::default();
+
+    let app = routes::create_api_router(&config)
+        .with_state(app_state.clone())
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(AuthProviderChain::api_key(app_state.auth_store.clone())),
+            auth_middleware,
+        ))
+        .into_service();
+
+    let mut system_config = create_test_system_config();
+    system_config.handler_recording.enabled = true;
+    let request = Request::builder()
+        .uri("/api/v1/systems")
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(
+            json!(CreateSystemRequest {
+                name: "TestSystem".to_string(),
+                config: system_config,
+                ..Default::default()
+            })
+            .to_string(),
+        )
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let system_id = serde_json::from_slice::<CreateSystemResponse>(&body)
+        .unwrap()
+        .system_id;
+
+    let request_body = json!(StartSystemRequest {
+        dsl: Some(
+            r#"micro Ledger {
+            state {
+                total: Int = 10;
+            }
+            observe {
+                on Deposit(amount: Int) {
+                    total = total + amount
+                    doubled = amount * 2
+                    emit Deposited(total: total)
+                }
+            }
+        }"#
+            .to_string()
+        )
+    });
+    let request = Request::builder()
+        .uri(format!("/api/v1/systems/{}/start", system_id))
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(request_body.to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    let request = Request::builder()
+        .uri(format!(
+            "/api/v1/systems/{}/events/deposit-1/emit",
+            system_id
+        ))
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(
+            json!(EventRequest {
+                event_type: "Deposit".to_string(),
+                payload: json!({"amount": 5}),
+                ..Default::default()
+            })
+            .to_string(),
+        )
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    let debug = |method: &str, path: &str, body: serde_json::Value, api_key: &str| {
+        Request::builder()
+            .uri(format!("/api/v1/systems/{}/debug{}", system_id, path))
+            .method(method)
+            .header("Content-Type", "application/json")
+            .header("X-API-Key", api_key)
+            .body(body.to_string())
+            .unwrap()
+    };
+    let read_json = |response: axum::response::Response| async move {
+        let body = axum::body::to_bytes(response.into_body(), 10000)
+            .await
+            .unwrap();
+        serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+    };
+
+    let response = app
+        .clone()
+        .oneshot(debug("GET", "/recordings", json!(null), "admin-key"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = read_json(response).await;
+    let recordings = body["recordings"].as_array().unwrap();
+    assert_eq!(recordings.len(), 1);
+    assert_eq!(recordings[0]["agent_name"], "Ledger");
+    assert_eq!(recordings[0]["event_type"], "Deposit");
+    assert_eq!(recordings[0]["statements"].as_array().unwrap().len(), 3);
+    let recording_id = recordings[0]["id"].as_str().unwrap().to_string();
+
+    let response = app
+        .clone()
+        .oneshot(debug(
+            "POST",
+            "/sessions",
+            json!({"recording_id": recording_id}),
+            "admin-key",
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let session_id = read_json(response).await["session_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let step = format!("/sessions/{}/step", session_id);
+
+    let response = app
+        .clone()
+        .oneshot(debug("POST", &step, json!(null), "admin-key"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = read_json(response).await;
+    assert_eq!(body["index"], 0);
+    assert_eq!(
+        body["state_changes"],
+        json!([{"name": "total", "before": 10, "after": 15}])
+    );
+    assert_eq!(body["finished"], false);
+
+    let response = app
+        .clone()
+        .oneshot(debug("POST", &step, json!(null), "admin-key"))
+        .await
+        .unwrap();
+    let body = read_json(response).await;
+    assert_eq!(
+        body["variable_changes"],
+        json!([{"name": "doubled", "before": null, "after": 10}])
+    );
+
+    let response = app
+        .clone()
+        .oneshot(debug(
+            "POST",
+            &format!("/sessions/{}/inspect", session_id),
+            json!(DebugEvalRequest {
+                expression: "total + doubled".to_string(),
+            }),
+            "admin-key",
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        read_json(response).await,
+        json!({"value": 25, "type": "Int"})
+    );
+
+    let response = app
+        .clone()
+        .oneshot(debug("POST", &step, json!(null), "admin-key"))
+        .await
+        .unwrap();
+    let body = read_json(response).await;
+    assert_eq!(
+        body["pending_events"],
+        json!([{"event_type": "Deposited", "parameters": {"total": 15}}])
+    );
+    assert_eq!(body["finished"], true);
+
+    // 最後の文の後はもう進めない
+    let response = app
+        .clone()
+        .oneshot(debug("POST", &step, json!(null), "admin-key"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    // 管理者以外は使えない
+    let response = app
+        .clone()
+        .oneshot(debug("POST", &step, json!(null), "user1-key"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = app
+        .clone()
+        .oneshot(debug(
+            "DELETE",
+            &format!("/sessions/{}", session_id),
+            json!(null),
+            "admin-key",
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = app
+        .clone()
+        .oneshot(debug("POST", &step, json!(null), "admin-key"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_invalid_bodies_report_every_field() {
+    let app_state: kairei_http::server::AppState = create_test_state();
+    let config = kairei_http::server::ServerConfig::default();
+    let app = routes::create_api_router(&config)
+        .with_state(app_state.clone())
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(AuthProviderChain::api_key(app_state.auth_store.clone())),
+            auth_middleware,
+        ))
+        .into_service();
+    let post = |uri: &str, body: serde_json::Value| {
+        Request::builder()
+            .uri(uri)
+            .method("POST")
+            .header("X-API-Key", "admin-key")
+            .header("Content-Type", "application/json")
+            .body(body.to_string())
+            .unwrap()
+    };
+    let rejected_fields = |body: &[u8]| {
+        let error: ApiError = serde_json::from_slice(body).unwrap();
+        assert_eq!(error.error, "invalid_fields");
+        error
+            .fields
+            .into_iter()
+            .map(|field| (field.field, field.reason))
+            .collect::<Vec<_>>()
+    };
+
+    let response = app
+        .clone()
+        .oneshot(post(
+            "/api/v1/systems",
+            json!({
+                "name": " ",
+                "description": 42,
+                "config": {"max_agents": "many"},
+                "agent_sources": ["https://agents.example.com/a.kairei", ""],
+            }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = axum::body::to_bytes(response.into_body(), 10000)
+        .await
+        .unwrap();
+    let fields = rejected_fields(&body);
+    let names: Vec<&str> = fields.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec!["name", "description", "config", "agent_sources[1]"]
+    );
+    assert_eq!(fields[0].1, "must not be empty");
+    assert_eq!(fields[1].1, "must be a string");
+
+    // 検証はシステムを探す前に行う
+    let response = app
+        .clone()
+        .oneshot(post(
+            "/api/v1/systems/missing/agents/Counter/scaleup",
+            json!({"instances": -2, "options": []}),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = axum::body::to_bytes(response.into_body(), 10000)
+        .await
+        .unwrap();
+    assert_eq!(
+        rejected_fields(&body),
+        vec![
+            ("instances".to_string(), "must not be negative".to_string()),
+            ("options".to_string(), "must be an object".to_string()),
+        ]
+    );
+
+    let response = app
+        .clone()
+        .oneshot(post(
+            "/api/v1/systems/missing/agents/Counter/request",
+            json!({"request_type": "Get Count", "payload": "1"}),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = axum::body::to_bytes(response.into_body(), 10000)
+        .await
+        .unwrap();
+    let fields = rejected_fields(&body);
+    assert_eq!(fields.len(), 2);
+    assert_eq!(fields[0].0, "request_type");
+    assert!(fields[0].1.contains("' '"));
+    assert_eq!(fields[1].0, "payload");
+
+    // JSON でない本文はフィールドを持たない
+    let request = Request::builder()
+        .uri("/api/v1/systems")
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body("{\"name\":".to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = axum::body::to_bytes(response.into_body(), 10000)
+        .await
+        .unwrap();
+    let error: ApiError = serde_json::from_slice(&body).unwrap();
+    assert_eq!(error.error, "malformed_body");
+    assert!(error.fields.is_empty());
+}
+
+#[tokio::test]
+async fn test_agent_logs_route() {
+    let app_state: kairei_http::server::AppState = create_test_state();
+    let config = kairei_http::server::ServerConfig::default();
+
+    let app = routes::create_api_router(&config)
+        .with_state(app_state.clone())
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(AuthProviderChain::api_key(app_state.auth_store.clone())),
+            auth_middleware,
+        ))
+        .into_service();
+
+    let request = Request::builder()
+        .uri("/api/v1/systems")
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(
+            json!(CreateSystemRequest {
+                name: "LoggingSystem".to_string(),
+                config: create_test_system_config(),
+                ..Default::default()
+            })
+            .to_string(),
+        )
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let system_id = serde_json::from_slice::<CreateSystemResponse>(&body)
+        .unwrap()
+        .system_id;
+
+    let agent = |name: &str| {
+        format!(
+            r#"micro {} {{
+            answer {{
+                on request Ping() -> Result<String, Error> {{
+                    log.debug("pinged")
+                    return Ok("pong")
+                }}
+            }}
+        }}
+        "#,
+            name
+        )
+    };
+    let request_body = json!(StartSystemRequest {
+        dsl: Some(format!("{}{}", agent("Noisy"), agent("Quiet")))
+    });
+    let request = Request::builder()
+        .uri(format!("/api/v1/systems/{}/start", system_id))
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(request_body.to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let request = Request::builder()
+        .uri(format!(
+            "/api/v1/systems/{}/agents/Noisy/logs/config",
+            system_id
+        ))
+        .method("PUT")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(json!({ "level": "debug" }).to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    for agent_id in ["Noisy", "Quiet"] {
+        let request = Request::builder()
+            .uri(format!(
+                "/api/v1/systems/{}/agents/{}/request",
+                system_id, agent_id
+            ))
+            .method("POST")
+            .header("Content-Type", "application/json")
+            .header("X-API-Key", "admin-key")
+            .body(
+                json!(SendRequestAgentRequest {
+                    request_type: "Ping".to_string(),
+                    payload: serde_json::Value::Null
+                })
+                .to_string(),
+            )
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let logs = |agent_id: &str| {
+        Request::builder()
+            .uri(format!(
+                "/api/v1/systems/{}/agents/{}/logs",
+                system_id, agent_id
+            ))
+            .method("GET")
+            .header("X-API-Key", "admin-key")
+            .body("".to_string())
+            .unwrap()
+    };
+    let response = app.clone().oneshot(logs("Noisy")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), 10000)
+        .await
+        .unwrap();
+    let noisy: AgentLogsResponse = serde_json::from_slice(&body).unwrap();
+    assert_eq!(noisy.level, LogLevel::Debug);
+    assert_eq!(noisy.logs.len(), 1);
+    assert_eq!(noisy.logs[0].message, "pinged");
+    assert_eq!(noisy.logs[0].agent, "Noisy");
+
+    let response = app.clone().oneshot(logs("Quiet")).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), 10000)
+        .await
+        .unwrap();
+    let quiet: AgentLogsResponse = serde_json::from_slice(&body).unwrap();
+    assert_eq!(quiet.level, LogLevel::Info);
+    assert!(quiet.logs.is_empty());
+
+    let response = app.clone().oneshot(logs("Missing")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_agent_memory_pins_route() {
+    let app_state: kairei_http::server::AppState = create_test_state();
+    let config = kairei_http::server::ServerConfig::default();
+
+    let app = routes::create_api_router(&config)
+        .with_state(app_state.clone())
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(AuthProviderChain::api_key(app_state.auth_store.clone())),
+            auth_middleware,
+        ))
+        .into_service();
+
+    let request = Request::builder()
+        .uri("/api/v1/systems")
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(
+            json!(CreateSystemRequest {
+                name: "PinSystem".to_string(),
+                config: create_test_system_config(),
+                ..Default::default()
+            })
+            .to_string(),
+        )
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let system_id = serde_json::from_slice::<CreateSystemResponse>(&body)
+        .unwrap()
+        .system_id;
+
+    let request_body = json!(StartSystemRequest {
+        dsl: Some("micro Planner {}".to_string())
+    });
+    let request = Request::builder()
+        .uri(format!("/api/v1/systems/{}/start", system_id))
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(request_body.to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // エージェントのメモリはエージェント名の名前空間
+    let request = Request::builder()
+        .uri(format!(
+            "/api/v1/systems/{}/memories/Planner/import",
+            system_id
+        ))
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/x-ndjson")
+        .body(r#"{"id": "m1", "content": "Kyoto trip plan"}"#.to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let pin = |agent_id: &str, body: serde_json::Value| {
+        Request::builder()
+            .uri(format!(
+                "/api/v1/systems/{}/agents/{}/memory/pins",
+                system_id, agent_id
+            ))
+            .method("POST")
+            .header("X-API-Key", "admin-key")
+            .header("Content-Type", "application/json")
+            .body(body.to_string())
+            .unwrap()
+    };
+    let response = app
+        .clone()
+        .oneshot(pin(
+            "Planner",
+            json!({"item_ids": ["m1"], "mode": "exclude", "ttl_secs": 600}),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), 10000)
+        .await
+        .unwrap();
+    let resp: MemoryPinsResponse = serde_json::from_slice(&body).unwrap();
+    assert_eq!(resp.pins.len(), 1);
+    assert_eq!(resp.pins[0].item_id, "m1");
+    assert_eq!(resp.pins[0].mode, PinMode::Exclude);
+
+    let response = app
+        .clone()
+        .oneshot(pin(
+            "Planner",
+            json!({"item_ids": ["missing"], "ttl_secs": 600}),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = app
+        .clone()
+        .oneshot(pin("Planner", json!({"item_ids": ["m1"], "ttl_secs": 0})))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = app
+        .clone()
+        .oneshot(pin("Missing", json!({"item_ids": ["m1"], "ttl_secs": 600})))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // 記録のないリクエスト
+    let request = Request::builder()
+        .uri(format!(
+            "/api/v1/systems/{}/agents/Planner/requests/unknown/context",
+            system_id
+        ))
+        .method("GET")
+        .header("X-API-Key", "admin-key")
+        .body(String::new())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_agent_settings_route() {
+    let mut app_state: kairei_http::server::AppState = create_test_state();
+    app_state.session_manager.secret_config.providers.insert(
+        "tuned_provider".to_string(),
+        ProviderSecretConfig::default(),
+    );
+    let config = kairei_http::server::ServerConfig::default();
+
+    let app = routes::create_api_router(&config)
+        .with_state(app_state.clone())
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(AuthProviderChain::api_key(app_state.auth_store.clone())),
+            auth_middleware,
+        ))
+        .into_service();
+
+    // 2 つのプロバイダーは同じ問いに別の答えを返す
+    let mut system_config = create_test_system_config();
+    for (name, answer) in [
+        ("default_provider", "From the primary provider"),
+        ("tuned_provider", "From the tuned provider"),
+    ] {
+        let mut provider = ProviderConfig {
+            provider_type: ProviderType::SimpleExpert,
+            ..Default::default()
+        };
+        provider
+            .provider_specific
+            .insert("Suggest a hotel".to_string(), json!(answer));
+        system_config
+            .provider_configs
+            .providers
+            .insert(name.to_string(), provider);
+    }
+    let request = Request::builder()
+        .uri("/api/v1/systems")
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(
+            json!(CreateSystemRequest {
+                name: "SettingsSystem".to_string(),
+                config: system_config,
+                ..Default::default()
+            })
+            .to_string(),
+        )
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let system_id = serde_json::from_slice::<CreateSystemResponse>(&body)
+        .unwrap()
+        .system_id;
+
+    let request_body = json!(StartSystemRequest {
+        dsl: Some(
+            r#"micro Concierge {
+            answer {
+                on request Suggest() -> Result<String, Error> {
+                    suggestion = think("Suggest a hotel")
+                    return suggestion
+                }
+            }
+        }"#
+            .to_string()
+        )
+    });
+    let request = Request::builder()
+        .uri(format!("/api/v1/systems/{}/start", system_id))
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(request_body.to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let suggest = || {
+        Request::builder()
+            .uri(format!(
+                "/api/v1/systems/{}/agents/Concierge/request",
+                system_id
+            ))
+            .method("POST")
+            .header("Content-Type", "application/json")
+            .header("X-API-Key", "admin-key")
+            .body(
+                json!(SendRequestAgentRequest {
+                    request_type: "Suggest".to_string(),
+                    payload: json!({}),
+                })
+                .to_string(),
+            )
+            .unwrap()
+    };
+    let patch = |agent_id: &str, key: &str, body: serde_json::Value| {
+        Request::builder()
+            .uri(format!(
+                "/api/v1/systems/{}/agents/{}/config",
+                system_id, agent_id
+            ))
+            .method("PATCH")
+            .header("X-API-Key", key)
+            .header("Content-Type", "application/json")
+            .body(body.to_string())
+            .unwrap()
+    };
+    let get = || {
+        Request::builder()
+            .uri(format!(
+                "/api/v1/systems/{}/agents/Concierge/config",
+                system_id
+            ))
+            .method("GET")
+            .header("X-API-Key", "admin-key")
+            .body(String::new())
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(suggest()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), 10000)
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["value"]["output"], "From the primary provider");
+
+    let response = app
+        .clone()
+        .oneshot(patch(
+            "Concierge",
+            "admin-key",
+            json!({"provider": "tuned_provider", "temperature": 0.3}),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), 10000)
+        .await
+        .unwrap();
+    let settings: AgentSettings = serde_json::from_slice(&body).unwrap();
+    assert_eq!(settings.provider.as_deref(), Some("tuned_provider"));
+    assert_eq!(settings.temperature, Some(0.3));
+
+    // 次のリクエストから新しい設定を使う
+    let response = app.clone().oneshot(suggest()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), 10000)
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["value"]["output"], "From the tuned provider");
+
+    // 範囲外の値、変えられない設定、未登録のプロバイダーは拒否し、設定は変わらない
+    let response = app
+        .clone()
+        .oneshot(patch("Concierge", "admin-key", json!({"temperature": 3.5})))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = axum::body::to_bytes(response.into_body(), 10000)
+        .await
+        .unwrap();
+    let error: ApiError = serde_json::from_slice(&body).unwrap();
+    assert_eq!(error.fields[0].field, "temperature");
+    let response = app
+        .clone()
+        .oneshot(patch("Concierge", "admin-key", json!({"model": "gpt-4o"})))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = app
+        .clone()
+        .oneshot(patch(
+            "Concierge",
+            "admin-key",
+            json!({"provider": "missing_provider"}),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let response = app.clone().oneshot(get()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), 10000)
+        .await
+        .unwrap();
+    let settings: AgentSettings = serde_json::from_slice(&body).unwrap();
+    assert_eq!(settings.provider.as_deref(), Some("tuned_provider"));
+    assert_eq!(settings.temperature, Some(0.3));
+
+    // null で設定を外すとプライマリに戻る
+    let response = app
+        .clone()
+        .oneshot(patch("Concierge", "admin-key", json!({"provider": null})))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = app.clone().oneshot(suggest()).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), 10000)
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["value"]["output"], "From the primary provider");
+
+    let response = app
+        .clone()
+        .oneshot(patch("Concierge", "user1-key", json!({"temperature": 0.5})))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = app
+        .clone()
+        .oneshot(patch("Missing", "admin-key", json!({"temperature": 0.5})))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_conversational_agent_sees_prior_turns() {
+    let app_state: kairei_http::server::AppState = create_test_state();
+    let config = kairei_http::server::ServerConfig::default();
+
+    let app = routes::create_api_router(&config)
+        .with_state(app_state.clone())
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(AuthProviderChain::api_key(app_state.auth_store.clone())),
+            auth_middleware,
+        ))
+        .into_service();
+
+    // プロバイダーに渡ったプロンプトはアーカイブから読む
+    let archive = tempfile::tempdir().unwrap();
+    let mut system_config = create_test_system_config();
+    let provider = system_config
+        .provider_configs
+        .providers
+        .get_mut("default_provider")
+        .unwrap();
+    provider
+        .provider_specific
+        .insert("Reply to the user".to_string(), json!("Noted"));
+    provider.plugin_configs.insert(
+        "prompt_archive".to_string(),
+        PluginConfig::PromptArchive(PromptArchiveConfig {
+            directory: archive.path().to_string_lossy().to_string(),
+            batch_size: 1,
+            ..Default::default()
+        }),
+    );
+    system_config.conversations.insert(
+        "Chatter".to_string(),
+        ConversationConfig {
+            max_turns: 1,
+            max_tokens: None,
+        },
+    );
+    let request = Request::builder()
+        .uri("/api/v1/systems")
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(
+            json!(CreateSystemRequest {
+                name: "ConversationSystem".to_string(),
+                config: system_config,
+                ..Default::default()
+            })
+            .to_string(),
+        )
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let system_id = serde_json::from_slice::<CreateSystemResponse>(&body)
+        .unwrap()
+        .system_id;
+
+    let request_body = json!(StartSystemRequest {
+        dsl: Some(
+            r#"micro Chatter {
+            answer {
+                on request Chat(message: String) -> Result<String, Error> {
+                    reply = think("Reply to the user")
+                    return reply
+                }
+            }
+        }"#
+            .to_string()
+        )
+    });
+    let request = Request::builder()
+        .uri(format!("/api/v1/systems/{}/start", system_id))
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(request_body.to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    for message in ["first", "second", "third"] {
+        let request = Request::builder()
+            .uri(format!(
+                "/api/v1/systems/{}/agents/Chatter/request",
+                system_id
+            ))
+            .method("POST")
+            .header("Content-Type", "application/json")
+            .header("X-API-Key", "admin-key")
+            .body(
+                json!(SendRequestAgentRequest {
+                    request_type: "Chat".to_string(),
+                    payload: json!({ "message": message }),
+                })
+                .to_string(),
+            )
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let archived_prompts = || {
+        let mut files: Vec<_> = std::fs::read_dir(archive.path())
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .collect();
+        files.sort();
+        files
+            .iter()
+            .flat_map(|file| {
+                let lines = std::fs::read_to_string(file).unwrap_or_default();
+                lines
+                    .lines()
+                    .filter_map(|line| serde_json::from_str::<ArchiveRecord>(line).ok())
+                    .map(|record| record.prompt)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>()
+    };
+    // アーカイブは非同期に書かれる
+    let mut prompts = archived_prompts();
+    for _ in 0..50 {
+        if prompts.len() == 3 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        prompts = archived_prompts();
+    }
+    assert_eq!(prompts.len(), 3);
+
+    let first_turn = r#"- User: Chat {"message":"first"}"#;
+    let second_turn = r#"- User: Chat {"message":"second"}"#;
+    assert!(!prompts[0].contains("Conversation so far"));
+    assert!(prompts[1].contains(first_turn), "prompt: {}", prompts[1]);
+    // 上限の 1 往復を超えたので、最初の往復は落ちる
+    assert!(prompts[2].contains(second_turn), "prompt: {}", prompts[2]);
+    assert!(!prompts[2].contains(first_turn), "prompt: {}", prompts[2]);
+}
+
+#[tokio::test]
+async fn test_metrics_route() {
+    let app_state: kairei_http::server::AppState = create_test_state();
+    let config = kairei_http::server::ServerConfig::default();
+
+    let app = routes::create_api_router(&config)
+        .with_state(app_state.clone())
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(AuthProviderChain::api_key(app_state.auth_store.clone())),
+            auth_middleware,
+        ))
+        .into_service();
+
+    let mut system_config = create_test_system_config();
+    let provider = system_config
+        .provider_configs
+        .providers
+        .get_mut("default_provider")
+        .unwrap();
+    provider.common_config.model = "mock-model".to_string();
+    // 計測されるのは成功した呼び出しだけなので、どちらの問いにも答えさせる
+    for prompt in ["configured", "overridden"] {
+        provider
+            .provider_specific
+            .insert(prompt.to_string(), json!("answered"));
+    }
+    let request = Request::builder()
+        .uri("/api/v1/systems")
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(
+            json!(CreateSystemRequest {
+                name: "TestSystem".to_string(),
+                config: system_config,
+                ..Default::default()
+            })
+            .to_string(),
+        )
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let system_id = serde_json::from_slice::<CreateSystemResponse>(&body)
+        .unwrap()
+        .system_id;
+
+    let request_body = json!(StartSystemRequest {
+        dsl: Some(
+            r#"micro Advisor {
+            answer {
+                on request Ask() -> Result<String, Error> {
+                    reply = think("configured")
+                    return reply
+                }
+                on request AskOther() -> Result<String, Error> {
+                    reply = think("overridden") with { model: "mock-other" }
+                    return reply
+                }
+            }
+        }"#
+            .to_string()
+        )
+    });
+    let request = Request::builder()
+        .uri(format!("/api/v1/systems/{}/start", system_id))
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(request_body.to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    for request_type in ["Ask", "Ask", "AskOther"] {
+        let request = Request::builder()
+            .uri(format!(
+                "/api/v1/systems/{}/agents/Advisor/request",
+                system_id
+            ))
+            .method("POST")
+            .header("Content-Type", "application/json")
+            .header("X-API-Key", "admin-key")
+            .body(
+                json!(SendRequestAgentRequest {
+                    request_type: request_type.to_string(),
+                    payload: json!({}),
+                })
+                .to_string(),
+            )
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let metrics = |key: &str| {
+        Request::builder()
+            .uri("/metrics")
+            .method("GET")
+            .header("X-API-Key", key)
+            .body("".to_string())
+            .unwrap()
+    };
+
+    // 全システムの指標を含むため管理者のみ
+    let response = app.clone().oneshot(metrics("user1-key")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = app.clone().oneshot(metrics("admin-key")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(
+        response.headers()["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain; version=0.0.4")
+    );
+    let body = axum::body::to_bytes(response.into_body(), 100_000)
+        .await
+        .unwrap();
+    let text = String::from_utf8(body.to_vec()).unwrap();
+    assert!(text.contains("# TYPE kairei_llm_request_duration_seconds histogram"));
+    assert!(text.contains(&format!(
+        r#"kairei_llm_request_duration_seconds_count{{system="{}",provider="default_provider",model="mock-model"}} 2"#,
+        system_id
+    )));
+    // 設定にないモデルは other にまとまる
+    assert!(text.contains(&format!(
+        r#"kairei_llm_request_duration_seconds_count{{system="{}",provider="default_provider",model="other"}} 1"#,
+        system_id
+    )));
+    assert!(!text.contains("mock-other"));
+}
+
+#[tokio::test]
+async fn test_request_agent_streams_typed_events() {
+    let app_state: kairei_http::server::AppState = create_test_state();
+    let config = kairei_http::server::ServerConfig::default();
+    let app = routes::create_api_router(&config)
+        .with_state(app_state.clone())
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(AuthProviderChain::api_key(app_state.auth_store.clone())),
+            auth_middleware,
+        ))
+        .into_service();
+
+    let request = Request::builder()
+        .uri("/api/v1/systems")
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(
+            json!(CreateSystemRequest {
+                name: "TestSystem".to_string(),
+                config: create_test_system_config(),
+                ..Default::default()
+            })
+            .to_string(),
+        )
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let system_id = serde_json::from_slice::<CreateSystemResponse>(&body)
+        .unwrap()
+        .system_id;
+
+    let request_body = json!(StartSystemRequest {
+        dsl: Some(
+            r#"micro Streamer {
+            answer {
+                on request Count() -> Result<String, Error> {
+                    yield Ok("one")
+                    yield Ok("two")
+                    return Ok("done")
+                }
+                on request Broken() -> Result<String, Error> {
+                    yield Ok("one")
+                    return Err("broken")
+                }
+            }
+        }"#
+            .to_string()
+        )
+    });
+    let request = Request::builder()
+        .uri(format!("/api/v1/systems/{}/start", system_id))
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(request_body.to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // エージェントのタスクが購読を始めるまで、ランタイムを止めずに待つ
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    let stream = |request_type: &str| {
+        Request::builder()
+            .uri(format!(
+                "/api/v1/systems/{}/agents/Streamer/request/stream",
+                system_id
+            ))
+            .method("POST")
+            .header("Content-Type", "application/json")
+            .header("X-API-Key", "admin-key")
+            .body(
+                json!(SendRequestAgentRequest {
+                    request_type: request_type.to_string(),
+                    payload: json!({}),
+                })
+                .to_string(),
+            )
+            .unwrap()
+    };
+
+    // トークンのあとに使用量と最終の応答が続く
+    let response = app.clone().oneshot(stream("Count")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let mut body = response.into_body().into_data_stream();
+    let events = read_sse_events(&mut body, 4).await;
+    assert_eq!(
+        events
+            .iter()
+            .map(|(_, name, data)| (name.as_str(), data.clone()))
+            .collect::<Vec<_>>(),
+        vec![
+            ("token", json!({"type": "token", "content": "one"})),
+            ("token", json!({"type": "token", "content": "two"})),
+            (
+                "usage",
+                json!({"type": "usage", "think_calls": 0, "tokens": 0, "sub_requests": 0})
+            ),
+            ("done", json!({"type": "done", "response": "done"})),
+        ]
+    );
+
+    // 途中で失敗すると error で終わる
+    let response = app.clone().oneshot(stream("Broken")).await.unwrap();
+    let mut body = response.into_body().into_data_stream();
+    let events = read_sse_events(&mut body, 3).await;
+    assert_eq!(events[0].1, "token");
+    assert_eq!(events[1].1, "usage");
+    assert_eq!(events[2].1, "error");
+    assert!(events[2].2["message"].as_str().unwrap().contains("broken"));
+}
+
+#[tokio::test]
+async fn test_preflight_route() {
+    let app_state: kairei_http::server::AppState = create_test_state();
+    let config = kairei_http::server::ServerConfig::default();
+
+    let app = routes::create_api_router(&config)
+        .with_state(app_state.clone())
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(AuthProviderChain::api_key(app_state.auth_store.clone())),
+            auth_middleware,
+        ))
+        .into_service();
+
+    let preflight = |dsl: &str, api_key: &str| {
+        Request::builder()
+            .uri("/api/v1/preflight")
+            .method("POST")
+            .header("Content-Type", "application/json")
+            .header("X-API-Key", api_key)
+            .body(
+                json!(PreflightRequest {
+                    config: create_test_system_config(),
+                    sources: vec![PreflightSource::new("main.kairei", dsl)],
+                    validate_credentials: false,
+                })
+                .to_string(),
+            )
+            .unwrap()
+    };
+    let dsl = r#"micro Counter {
+        state {
+            count: Int = 0;
+        }
+    }"#;
+
+    // 小さな入力はその場で検査する
+    let response = app
+        .clone()
+        .oneshot(preflight(dsl, "admin-key"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let report: PreflightReport = serde_json::from_slice(&body).unwrap();
+    assert_eq!(report.components.len(), PreflightComponent::ALL.len());
+    assert!(report.component(PreflightComponent::Dsl).passed);
+
+    let response = app
+        .clone()
+        .oneshot(preflight("micro Broken {", "admin-key"))
+        .await
+        .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let report: PreflightReport = serde_json::from_slice(&body).unwrap();
+    assert!(!report.passed);
+    let dsl_report = report.component(PreflightComponent::Dsl);
+    assert!(!dsl_report.passed);
+    assert!(dsl_report.diagnostics[0].subject.starts_with("main.kairei"));
+
+    // 大きな入力はジョブで検査する
+    let padding = format!("// {}\n", "x".repeat(INLINE_SOURCE_BYTES));
+    let response = app
+        .clone()
+        .oneshot(preflight(&format!("{}{}", padding, dsl), "admin-key"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let job: PreflightJob = serde_json::from_slice(&body).unwrap();
+
+    let mut completed = None;
+    for _ in 0..100 {
+        let request = Request::builder()
+            .uri(format!("/api/v1/preflight/{}", job.job_id))
+            .header("X-API-Key", "admin-key")
+            .body(String::new())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let current: PreflightJob = serde_json::from_slice(&body).unwrap();
+        if current.status == PreflightJobStatus::Completed {
+            completed = Some(current);
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    let report = completed.expect("job did not complete").report.unwrap();
+    assert!(report.component(PreflightComponent::Dsl).passed);
+
+    let request = Request::builder()
+        .uri("/api/v1/preflight/missing")
+        .header("X-API-Key", "admin-key")
+        .body(String::new())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // 管理者以外は使えない
+    let response = app
+        .clone()
+        .oneshot(preflight(dsl, "user1-key"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_reload_preview_route() {
+    let app_state: kairei_http::server::AppState = create_test_state();
+    let config = kairei_http::server::ServerConfig::default();
+
+    let app = routes::create_api_router(&config)
+        .with_state(app_state.clone())
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(AuthProviderChain::api_key(app_state.auth_store.clone())),
+            auth_middleware,
+        ))
+        .into_service();
+
+    let request = Request::builder()
+        .uri("/api/v1/systems")
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(
+            json!(CreateSystemRequest {
+                name: "ReloadSystem".to_string(),
+                config: create_test_system_config(),
+                ..Default::default()
+            })
+            .to_string(),
+        )
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), 1000)
+        .await
+        .unwrap();
+    let system_id = serde_json::from_slice::<CreateSystemResponse>(&body)
+        .unwrap()
+        .system_id;
+
+    let request_body = json!(StartSystemRequest {
+        dsl: Some(
+            r#"micro Counter {
+            state {
+                count: Int = 0;
+                label: String = "counter";
+            }
+            answer {
+                on request GetCount() -> Result<Int, Error> {
+                    return Ok(count)
+                }
+            }
+        }"#
+            .to_string()
+        )
+    });
+    let request = Request::builder()
+        .uri(format!("/api/v1/systems/{}/start", system_id))
+        .method("POST")
+        .header("X-API-Key", "admin-key")
+        .header("Content-Type", "application/json")
+        .body(request_body.to_string())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let preview = |dsl: &str, api_key: &str| {
+        Request::builder()
+            .uri(format!(
+                "/api/v1/systems/{}/agents/Counter/reload/preview",
+                system_id
+            ))
+            .method("POST")
+            .header("X-API-Key", api_key)
+            .header("Content-Type", "application/json")
+            .body(json!({ "dsl": dsl }).to_string())
+            .unwrap()
+    };
+
+    let response = app
+        .clone()
+        .oneshot(preview(
+            r#"micro Counter {
+            state {
+                count: Int = 0;
+                label: Int = 0;
+            }
+            answer {
+                on request GetCount() -> Result<Int, Error> {
+                    return Ok(count + 1)
+                }
+            }
+        }"#,
+            "admin-key",
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), 10000)
+        .await
+        .unwrap();
+    let plan: ReloadPlan = serde_json::from_slice(&body).unwrap();
+    assert_eq!(plan.preserved_state, vec!["count"]);
+    assert_eq!(plan.reset_state, vec!["label"]);
+    assert_eq!(plan.changed_handlers, vec!["answer GetCount"]);
+
+    // 定義できない DSL、対象のエージェントがない DSL は拒否する
+    for dsl in ["micro Counter {", "micro Other { }"] {
+        let response = app
+            .clone()
+            .oneshot(preview(dsl, "admin-key"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    let response = app
+        .clone()
+        .oneshot(preview("micro Counter { }", "user1-key"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
@@ -1,5 +1,12 @@
-use kairei_http::server::{ServerConfig, start_server};
+use async_trait::async_trait;
+use kairei_http::auth::{
+    AuthConfig, AuthContext, AuthError, AuthProvider, AuthProviderConfig, AuthProviderKind,
+    AuthResult, AuthStore, RequestCredentials,
+};
+use kairei_http::models::user::UserRole;
+use kairei_http::server::{ServerBuilder, ServerConfig, start_server};
 use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::timeout;
 
@@ -140,6 +147,71 @@ async fn test_server_startup() {
     assert!(response.status().is_success(), "Server returned an error");
 }
 
+/// Embedder-defined provider accepting a single bearer token
+struct TokenProvider;
+
+#[async_trait]
+impl AuthProvider for TokenProvider {
+    fn name(&self) -> &str {
+        "token"
+    }
+
+    async fn authenticate(&self, credentials: &RequestCredentials) -> AuthResult<AuthContext> {
+        match credentials.bearer_token.as_deref() {
+            Some("secret-token") => Ok(AuthContext::new("ldap-user", UserRole::User)),
+            Some(_) => Err(AuthError::InvalidCredentials("bad token".to_string())),
+            None => Err(AuthError::MissingCredentials),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_server_builder_custom_auth_provider() {
+    let config = ServerConfig {
+        auth: AuthConfig {
+            providers: vec![
+                AuthProviderConfig {
+                    kind: AuthProviderKind::Custom {
+                        name: "token".to_string(),
+                    },
+                    cache_ttl_secs: Some(60),
+                },
+                AuthProviderConfig {
+                    kind: AuthProviderKind::ApiKey,
+                    cache_ttl_secs: None,
+                },
+            ],
+        },
+        ..Default::default()
+    };
+
+    // Unregistered custom provider is a configuration error
+    let builder = ServerBuilder::new(config.clone());
+    assert!(matches!(
+        builder.auth_chain(&AuthStore::default()),
+        Err(AuthError::UnknownProvider(_))
+    ));
+
+    let builder = ServerBuilder::new(config).auth_provider("token", Arc::new(TokenProvider));
+    let chain = builder.auth_chain(&AuthStore::default()).unwrap();
+    assert_eq!(chain.len(), 2);
+
+    let mut headers = axum::http::HeaderMap::new();
+    headers.insert("Authorization", "Bearer secret-token".parse().unwrap());
+    let context = chain
+        .authenticate(&RequestCredentials::from_headers(&headers))
+        .await
+        .unwrap();
+    assert_eq!(context.principal, "ldap-user");
+
+    // API keys fall through to the built-in provider
+    let context = chain
+        .authenticate(&RequestCredentials::with_api_key("admin-key"))
+        .await
+        .unwrap();
+    assert!(context.is_admin());
+}
+
 // Helper function to find an available port
 fn find_available_port() -> Option<u16> {
     // Try to bind to port 0, which will assign a random available port